@@ -0,0 +1,83 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+#![cfg(target_os = "linux")]
+
+use std::process;
+
+pub trait CommandRunner {
+    // The command's standard output if it succeeds; otherwise what it said was wrong
+    fn run(&self, program: &str, args: &[&str]) -> Result<String, String>;
+}
+
+#[derive(Default)]
+pub struct CommandRunnerReal {}
+
+impl CommandRunner for CommandRunnerReal {
+    fn run(&self, program: &str, args: &[&str]) -> Result<String, String> {
+        let output = process::Command::new(program)
+            .args(args)
+            // Output to be parsed shouldn't depend on the user's language
+            .env("LC_ALL", "C")
+            .output()
+            .map_err(|e| format!("Couldn't run {}: {}", program, e))?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        } else {
+            Err(format!(
+                "{} {} failed: {}",
+                program,
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+}
+
+impl CommandRunnerReal {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+#[cfg(test)]
+pub mod tests_utils {
+    use super::*;
+    use std::cell::RefCell;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    pub struct CommandRunnerMock {
+        pub run_parameters: Arc<Mutex<Vec<String>>>,
+        run_results: RefCell<Vec<Result<String, String>>>,
+    }
+
+    impl CommandRunner for CommandRunnerMock {
+        fn run(&self, program: &str, args: &[&str]) -> Result<String, String> {
+            let mut command = vec![program];
+            command.extend(args);
+            self.run_parameters
+                .lock()
+                .expect("run couldn't take params")
+                .push(command.join(" "));
+            let mut run_results = self.run_results.borrow_mut();
+            if run_results.is_empty() {
+                panic!("run called without a stub: {}", command.join(" "))
+            }
+            run_results.remove(0)
+        }
+    }
+
+    impl CommandRunnerMock {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn run_result(self, result: Result<&str, &str>) -> Self {
+            self.run_results.borrow_mut().push(
+                result
+                    .map(|output| output.to_string())
+                    .map_err(|e| e.to_string()),
+            );
+            self
+        }
+    }
+}
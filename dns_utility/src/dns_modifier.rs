@@ -1,10 +1,19 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
 use std::io;
+use std::net::IpAddr;
 
 pub trait DnsModifier {
     fn type_name(&self) -> &'static str;
     fn subvert(&self) -> Result<(), String>;
     fn revert(&self) -> Result<(), String>;
     fn inspect(&self, stdout: &mut (dyn io::Write + Send)) -> Result<(), String>;
+    // The DNS servers the system used before it was subverted, or the ones it uses now if it hasn't been
+    fn original_servers(&self) -> Result<Vec<IpAddr>, String>;
+    // Confines everything else to the named network interfaces, leaving the rest of them alone
+    fn select_interfaces(&mut self, _interfaces: &[String]) -> Result<(), String> {
+        Err(String::from(
+            "This system's DNS settings can't be changed for some network interfaces and not others",
+        ))
+    }
 }
@@ -5,8 +5,12 @@ use std::fs::File;
 #[cfg(target_os = "linux")]
 use std::path::Path;
 
+#[cfg(target_os = "linux")]
+use crate::network_manager_dns_modifier::NetworkManagerDnsModifier;
 #[cfg(target_os = "linux")]
 use crate::resolv_conf_dns_modifier::ResolvConfDnsModifier;
+#[cfg(target_os = "linux")]
+use crate::resolved_dns_modifier::ResolvedDnsModifier;
 
 #[cfg(target_os = "macos")]
 use crate::dynamic_store_dns_modifier::DynamicStoreDnsModifier;
@@ -35,9 +39,13 @@ impl DnsModifierFactoryReal {
     }
 }
 
-const QUALIFIER_FACTORIES: [&dyn QualifierFactory; 3] = [
+// On Linux, whatever writes resolv.conf has to be asked to change it; resolv.conf itself is the
+// last resort
+const QUALIFIER_FACTORIES: [&dyn QualifierFactory; 5] = [
     &DynamicStoreQualifierFactory {},
     &WinQualifierFactory {},
+    &NetworkManagerQualifierFactory {},
+    &ResolvedQualifierFactory {},
     &ResolvConfQualifierFactory {},
 ];
 
@@ -67,6 +75,48 @@ impl QualifierFactory for ResolvConfQualifierFactory {
     }
 }
 
+struct NetworkManagerQualifierFactory;
+#[cfg(target_os = "linux")]
+impl QualifierFactory for NetworkManagerQualifierFactory {
+    fn system_qualifies(&self) -> bool {
+        NetworkManagerDnsModifier::is_in_charge()
+    }
+    fn make(&self) -> Box<dyn DnsModifier> {
+        Box::new(NetworkManagerDnsModifier::new())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl QualifierFactory for NetworkManagerQualifierFactory {
+    fn system_qualifies(&self) -> bool {
+        false
+    }
+    fn make(&self) -> Box<dyn DnsModifier> {
+        panic!("Should never be called")
+    }
+}
+
+struct ResolvedQualifierFactory;
+#[cfg(target_os = "linux")]
+impl QualifierFactory for ResolvedQualifierFactory {
+    fn system_qualifies(&self) -> bool {
+        ResolvedDnsModifier::is_in_charge()
+    }
+    fn make(&self) -> Box<dyn DnsModifier> {
+        Box::new(ResolvedDnsModifier::new())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl QualifierFactory for ResolvedQualifierFactory {
+    fn system_qualifies(&self) -> bool {
+        false
+    }
+    fn make(&self) -> Box<dyn DnsModifier> {
+        panic!("Should never be called")
+    }
+}
+
 struct WinQualifierFactory {}
 #[cfg(target_os = "windows")]
 impl QualifierFactory for WinQualifierFactory {
@@ -134,6 +184,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn network_manager_and_resolved_qualifier_factories_never_qualify_off_linux() {
+        let network_manager = NetworkManagerQualifierFactory {}.system_qualifies();
+        let resolved = ResolvedQualifierFactory {}.system_qualifies();
+
+        #[cfg(target_os = "linux")]
+        {
+            assert_eq!(network_manager, NetworkManagerDnsModifier::is_in_charge());
+            assert_eq!(resolved, ResolvedDnsModifier::is_in_charge());
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            assert_eq!(network_manager, false);
+            assert_eq!(resolved, false);
+        }
+    }
+
     #[test]
     fn win_qualifier_factory_works_on_this_os() {
         let subject = WinQualifierFactory {};
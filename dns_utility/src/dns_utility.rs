@@ -4,23 +4,32 @@ use crate::dns_modifier_factory::DnsModifierFactory;
 use crate::dns_modifier_factory::DnsModifierFactoryReal;
 use crate::main_tools::Command;
 use crate::main_tools::StdStreams;
+use crate::repair::{repair_orphaned_subversion, DnsProbe, DnsProbeReal, RepairOutcome};
+use crate::subversion_record::SubversionRecord;
 use std::io::Write;
 
+const DNS_INTERFACES: &str = "--dns-interfaces";
+
 enum Action {
     Subvert,
     Revert,
     Inspect,
     Status,
+    Repair,
 }
 
 pub struct DnsUtility {
     factory: Box<dyn DnsModifierFactory>,
+    record: SubversionRecord,
+    probe: Box<dyn DnsProbe>,
 }
 
 impl Default for DnsUtility {
     fn default() -> Self {
         DnsUtility {
             factory: Box::new(DnsModifierFactoryReal::new()),
+            record: SubversionRecord::new(),
+            probe: Box::new(DnsProbeReal::new()),
         }
     }
 }
@@ -33,9 +42,14 @@ impl Command for DnsUtility {
             a if a[1] == "revert" => Action::Revert,
             a if a[1] == "inspect" => Action::Inspect,
             a if a[1] == "status" => Action::Status,
+            a if a[1] == "repair" => Action::Repair,
             _ => return DnsUtility::usage(streams),
         };
-        self.perform_action(action, streams)
+        let interfaces = match DnsUtility::interfaces_from(&args[2..]) {
+            Some(interfaces) => interfaces,
+            None => return DnsUtility::usage(streams),
+        };
+        self.perform_action(action, &interfaces, streams)
     }
 }
 
@@ -44,8 +58,36 @@ impl DnsUtility {
         Default::default()
     }
 
-    fn perform_action(&self, action: Action, streams: &mut StdStreams<'_>) -> u8 {
-        let modifier = match self.factory.make() {
+    // Every network interface unless --dns-interfaces names some; None if the arguments make no sense
+    fn interfaces_from(args: &[String]) -> Option<Vec<String>> {
+        let names = match args {
+            [] => return Some(vec![]),
+            [flag, names] if flag == DNS_INTERFACES => names.as_str(),
+            [flag] if flag.starts_with(&format!("{}=", DNS_INTERFACES)) => {
+                &flag[DNS_INTERFACES.len() + 1..]
+            }
+            _ => return None,
+        };
+        let interfaces: Vec<String> = names
+            .split(',')
+            .map(|name| name.trim())
+            .filter(|name| !name.is_empty())
+            .map(|name| name.to_string())
+            .collect();
+        if interfaces.is_empty() {
+            None
+        } else {
+            Some(interfaces)
+        }
+    }
+
+    fn perform_action(
+        &self,
+        action: Action,
+        interfaces: &[String],
+        streams: &mut StdStreams<'_>,
+    ) -> u8 {
+        let mut modifier = match self.factory.make() {
             None => {
                 writeln!(
                     streams.stderr,
@@ -56,14 +98,22 @@ impl DnsUtility {
             }
             Some(m) => m,
         };
+        if !interfaces.is_empty() {
+            if let Err(msg) = modifier.select_interfaces(interfaces) {
+                writeln!(streams.stderr, "Cannot select network interfaces: {}", msg)
+                    .expect("Could not writeln");
+                return 1;
+            }
+        }
         let (result, name) = match action {
-            Action::Subvert => (modifier.subvert(), "subvert DNS"),
-            Action::Revert => (modifier.revert(), "revert DNS"),
+            Action::Subvert => (self.subvert(modifier.as_ref()), "subvert DNS"),
+            Action::Revert => (self.revert(modifier.as_ref()), "revert DNS"),
             Action::Inspect => (modifier.inspect(streams.stdout), "inspect DNS"),
             Action::Status => (
                 self.retrieve_status(modifier, streams.stdout),
                 "display DNS status",
             ),
+            Action::Repair => (self.repair(modifier.as_ref(), streams.stdout), "repair DNS"),
         };
         match result {
             Ok(_) => 0,
@@ -74,6 +124,38 @@ impl DnsUtility {
         }
     }
 
+    // The record goes down first, so that it's there however the subversion ends
+    fn subvert(&self, modifier: &dyn DnsModifier) -> Result<(), String> {
+        self.record.write(&modifier.original_servers()?)?;
+        if let Err(e) = modifier.subvert() {
+            let _ = self.record.forget();
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn revert(&self, modifier: &dyn DnsModifier) -> Result<(), String> {
+        modifier.revert()?;
+        self.record.forget()
+    }
+
+    fn repair(
+        &self,
+        modifier: &dyn DnsModifier,
+        stdout: &mut (dyn Write + Send),
+    ) -> Result<(), String> {
+        let message = match repair_orphaned_subversion(modifier, &self.record, self.probe.as_ref())?
+        {
+            RepairOutcome::NotSubverted => "DNS is not subverted; nothing to repair",
+            RepairOutcome::StillAnswered => {
+                "DNS is subverted and still being answered; nothing to repair"
+            }
+            RepairOutcome::Reverted => "DNS was subverted with nothing left to answer it; reverted",
+        };
+        writeln!(stdout, "{}", message).expect("write doesn't work");
+        Ok(())
+    }
+
     fn retrieve_status(
         &self,
         modifier: Box<dyn DnsModifier>,
@@ -102,7 +184,7 @@ impl DnsUtility {
     fn usage(streams: &mut StdStreams<'_>) -> u8 {
         writeln!(
             streams.stderr,
-            "Usage: dns_utility [ subvert | revert | inspect | status ]"
+            "Usage: dns_utility [ subvert | revert | inspect | status | repair ] [ --dns-interfaces <NAME>,... ]"
         )
         .expect("Internal error");
         1
@@ -110,18 +192,26 @@ impl DnsUtility {
 }
 
 #[cfg(test)]
-mod tests {
+pub mod tests {
     use super::*;
     use crate::dns_modifier::DnsModifier;
     use crate::fake_stream_holder::FakeStreamHolder;
+    use crate::repair::tests::DnsProbeMock;
+    use crate::subversion_record::tests::make_record_directory;
     use std::cell::RefCell;
     use std::io;
+    use std::net::IpAddr;
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
 
     pub struct DnsModifierMock {
         subvert_results: RefCell<Vec<Result<(), String>>>,
         revert_results: RefCell<Vec<Result<(), String>>>,
         inspect_to_stdout: RefCell<Vec<String>>,
         inspect_results: RefCell<Vec<Result<(), String>>>,
+        original_servers_results: RefCell<Vec<Result<Vec<IpAddr>, String>>>,
+        select_interfaces_parameters: Arc<Mutex<Vec<Vec<String>>>>,
+        select_interfaces_results: RefCell<Vec<Result<(), String>>>,
     }
 
     impl DnsModifier for DnsModifierMock {
@@ -141,6 +231,18 @@ mod tests {
             write!(stdout, "{}", self.inspect_to_stdout.borrow_mut().remove(0)).unwrap();
             self.inspect_results.borrow_mut().remove(0)
         }
+
+        fn original_servers(&self) -> Result<Vec<IpAddr>, String> {
+            self.original_servers_results.borrow_mut().remove(0)
+        }
+
+        fn select_interfaces(&mut self, interfaces: &[String]) -> Result<(), String> {
+            self.select_interfaces_parameters
+                .lock()
+                .unwrap()
+                .push(interfaces.to_vec());
+            self.select_interfaces_results.borrow_mut().remove(0)
+        }
     }
 
     impl DnsModifierMock {
@@ -150,6 +252,9 @@ mod tests {
                 revert_results: RefCell::new(vec![]),
                 inspect_to_stdout: RefCell::new(vec![]),
                 inspect_results: RefCell::new(vec![]),
+                original_servers_results: RefCell::new(vec![]),
+                select_interfaces_parameters: Arc::new(Mutex::new(vec![])),
+                select_interfaces_results: RefCell::new(vec![]),
             }
         }
 
@@ -163,6 +268,14 @@ mod tests {
             self
         }
 
+        pub fn original_servers_result(
+            self,
+            result: Result<Vec<IpAddr>, String>,
+        ) -> DnsModifierMock {
+            self.original_servers_results.borrow_mut().push(result);
+            self
+        }
+
         pub fn inspect_result(
             self,
             to_stdout: String,
@@ -172,6 +285,11 @@ mod tests {
             self.inspect_results.borrow_mut().push(result);
             self
         }
+
+        pub fn select_interfaces_result(self, result: Result<(), String>) -> DnsModifierMock {
+            self.select_interfaces_results.borrow_mut().push(result);
+            self
+        }
     }
 
     #[derive(Default)]
@@ -208,7 +326,9 @@ mod tests {
         assert_eq!(result, 1);
         assert_eq!(
             holder.stderr.get_string(),
-            String::from("Usage: dns_utility [ subvert | revert | inspect | status ]\n")
+            String::from(
+                "Usage: dns_utility [ subvert | revert | inspect | status | repair ] [ --dns-interfaces <NAME>,... ]\n"
+            )
         );
     }
 
@@ -225,7 +345,97 @@ mod tests {
         assert_eq!(result, 1);
         assert_eq!(
             holder.stderr.get_string(),
-            String::from("Usage: dns_utility [ subvert | revert | inspect | status ]\n")
+            String::from(
+                "Usage: dns_utility [ subvert | revert | inspect | status | repair ] [ --dns-interfaces <NAME>,... ]\n"
+            )
+        );
+    }
+
+    #[test]
+    fn go_with_a_parameter_after_the_action_that_isnt_dns_interfaces_prints_usage() {
+        vec![
+            vec!["subvert", "--booga"],
+            vec!["subvert", "--dns-interfaces"],
+            vec!["subvert", "--dns-interfaces", ","],
+            vec!["subvert", "--dns-interfaces", "Wi-Fi", "Ethernet"],
+        ]
+        .into_iter()
+        .for_each(|params| {
+            let mut holder = FakeStreamHolder::new();
+            let mut subject = DnsUtility::new();
+            let mut args = vec![String::new()];
+            args.extend(params.iter().map(|param| param.to_string()));
+
+            let result = subject.go(&mut holder.streams(), &args);
+
+            assert_eq!(result, 1, "{:?}", params);
+            assert!(
+                holder.stderr.get_string().starts_with("Usage: "),
+                "{:?}",
+                params
+            );
+        });
+    }
+
+    #[test]
+    fn go_with_dns_interfaces_subverts_only_those_interfaces() {
+        vec![
+            vec!["subvert", "--dns-interfaces", "Wi-Fi, Ethernet"],
+            vec!["subvert", "--dns-interfaces=Wi-Fi,Ethernet"],
+        ]
+        .into_iter()
+        .for_each(|params| {
+            let mut holder = FakeStreamHolder::new();
+            let dns_modifier = DnsModifierMock::new()
+                .select_interfaces_result(Ok(()))
+                .original_servers_result(Ok(vec![]))
+                .subvert_result(Ok(()));
+            let select_interfaces_parameters = dns_modifier.select_interfaces_parameters.clone();
+            let factory = DnsModifierFactoryMock::new().make_result(Some(Box::new(dns_modifier)));
+            let mut subject = DnsUtility::new();
+            subject.factory = Box::new(factory);
+            subject.record = SubversionRecord::in_directory(make_record_directory(
+                "go_with_dns_interfaces_subverts_only_those_interfaces",
+            ));
+            let mut args = vec![String::new()];
+            args.extend(params.iter().map(|param| param.to_string()));
+
+            let result = subject.go(&mut holder.streams(), &args);
+
+            assert_eq!(result, 0, "{:?}", params);
+            assert_eq!(
+                *select_interfaces_parameters.lock().unwrap(),
+                vec![vec!["Wi-Fi".to_string(), "Ethernet".to_string()]]
+            );
+        });
+    }
+
+    #[test]
+    fn go_with_dns_interfaces_complains_when_they_cant_be_selected() {
+        let mut holder = FakeStreamHolder::new();
+        let dns_modifier = DnsModifierMock::new().select_interfaces_result(Err(String::from(
+            "This system has no network interface named VPN",
+        )));
+        let factory = DnsModifierFactoryMock::new().make_result(Some(Box::new(dns_modifier)));
+        let mut subject = DnsUtility::new();
+        subject.factory = Box::new(factory);
+
+        let result = subject.go(
+            &mut holder.streams(),
+            &vec![
+                String::new(),
+                String::from("inspect"),
+                String::from("--dns-interfaces"),
+                String::from("VPN"),
+            ],
+        );
+
+        assert_eq!(result, 1);
+        assert_eq!(
+            holder.stderr.get_string(),
+            String::from(
+                "Cannot select network interfaces: This system has no network interface named VPN\n"
+            )
         );
     }
 
@@ -251,11 +461,15 @@ mod tests {
     #[test]
     fn go_with_subvert_parameter_makes_dns_modifier_calls_subvert_and_handles_failure() {
         let mut holder = FakeStreamHolder::new();
-        let dns_modifier =
-            DnsModifierMock::new().subvert_result(Err(String::from("blooga blooga")));
+        let dns_modifier = DnsModifierMock::new()
+            .original_servers_result(Ok(vec![]))
+            .subvert_result(Err(String::from("blooga blooga")));
         let factory = DnsModifierFactoryMock::new().make_result(Some(Box::new(dns_modifier)));
         let mut subject = DnsUtility::new();
         subject.factory = Box::new(factory);
+        subject.record = SubversionRecord::in_directory(make_record_directory(
+            "go_with_subvert_parameter_makes_dns_modifier_calls_subvert_and_handles_failure",
+        ));
 
         let result = subject.go(
             &mut holder.streams(),
@@ -267,15 +481,21 @@ mod tests {
             holder.stderr.get_string(),
             String::from("Cannot subvert DNS: blooga blooga\n")
         );
+        assert_eq!(subject.record.original_servers(), None);
     }
 
     #[test]
     fn go_with_subvert_parameter_makes_dns_modifier_calls_subvert_and_handles_success() {
         let mut holder = FakeStreamHolder::new();
-        let dns_modifier = DnsModifierMock::new().subvert_result(Ok(()));
+        let dns_modifier = DnsModifierMock::new()
+            .original_servers_result(Ok(vec![IpAddr::from_str("192.168.0.1").unwrap()]))
+            .subvert_result(Ok(()));
         let factory = DnsModifierFactoryMock::new().make_result(Some(Box::new(dns_modifier)));
         let mut subject = DnsUtility::new();
         subject.factory = Box::new(factory);
+        subject.record = SubversionRecord::in_directory(make_record_directory(
+            "go_with_subvert_parameter_makes_dns_modifier_calls_subvert_and_handles_success",
+        ));
 
         let result = subject.go(
             &mut holder.streams(),
@@ -284,6 +504,34 @@ mod tests {
 
         assert_eq!(result, 0);
         assert_eq!(holder.stderr.get_string(), String::new());
+        assert_eq!(
+            subject.record.original_servers(),
+            Some(vec![IpAddr::from_str("192.168.0.1").unwrap()])
+        );
+    }
+
+    #[test]
+    fn go_with_subvert_parameter_refuses_to_subvert_what_it_cant_record() {
+        let mut holder = FakeStreamHolder::new();
+        let dns_modifier =
+            DnsModifierMock::new().original_servers_result(Err(String::from("blooga blooga")));
+        let factory = DnsModifierFactoryMock::new().make_result(Some(Box::new(dns_modifier)));
+        let mut subject = DnsUtility::new();
+        subject.factory = Box::new(factory);
+        subject.record = SubversionRecord::in_directory(make_record_directory(
+            "go_with_subvert_parameter_refuses_to_subvert_what_it_cant_record",
+        ));
+
+        let result = subject.go(
+            &mut holder.streams(),
+            &vec![String::new(), String::from("subvert")],
+        );
+
+        assert_eq!(result, 1);
+        assert_eq!(
+            holder.stderr.get_string(),
+            String::from("Cannot subvert DNS: blooga blooga\n")
+        );
     }
 
     #[test]
@@ -293,6 +541,9 @@ mod tests {
         let factory = DnsModifierFactoryMock::new().make_result(Some(Box::new(dns_modifier)));
         let mut subject = DnsUtility::new();
         subject.factory = Box::new(factory);
+        subject.record = SubversionRecord::in_directory(make_record_directory(
+            "go_with_revert_parameter_makes_dns_modifier_calls_revert_and_handles_failure",
+        ));
 
         let result = subject.go(
             &mut holder.streams(),
@@ -313,6 +564,10 @@ mod tests {
         let factory = DnsModifierFactoryMock::new().make_result(Some(Box::new(dns_modifier)));
         let mut subject = DnsUtility::new();
         subject.factory = Box::new(factory);
+        subject.record = SubversionRecord::in_directory(make_record_directory(
+            "go_with_revert_parameter_makes_dns_modifier_calls_revert_and_handles_success",
+        ));
+        subject.record.write(&[]).unwrap();
 
         let result = subject.go(
             &mut holder.streams(),
@@ -321,6 +576,59 @@ mod tests {
 
         assert_eq!(result, 0);
         assert_eq!(holder.stderr.get_string(), String::new());
+        assert_eq!(subject.record.original_servers(), None);
+    }
+
+    #[test]
+    fn go_with_repair_parameter_reverts_subverted_dns_that_nothing_answers() {
+        let mut holder = FakeStreamHolder::new();
+        let dns_modifier = DnsModifierMock::new()
+            .inspect_result("127.0.0.1\n".to_string(), Ok(()))
+            .revert_result(Ok(()));
+        let factory = DnsModifierFactoryMock::new().make_result(Some(Box::new(dns_modifier)));
+        let mut subject = DnsUtility::new();
+        subject.factory = Box::new(factory);
+        subject.record = SubversionRecord::in_directory(make_record_directory(
+            "go_with_repair_parameter_reverts_subverted_dns_that_nothing_answers",
+        ));
+        subject.record.write(&[]).unwrap();
+        subject.probe = Box::new(DnsProbeMock { answered: false });
+
+        let result = subject.go(
+            &mut holder.streams(),
+            &vec![String::new(), String::from("repair")],
+        );
+
+        assert_eq!(result, 0);
+        assert_eq!(
+            holder.stdout.get_string(),
+            String::from("DNS was subverted with nothing left to answer it; reverted\n")
+        );
+        assert_eq!(subject.record.original_servers(), None);
+    }
+
+    #[test]
+    fn go_with_repair_parameter_leaves_subverted_dns_that_is_still_answered() {
+        let mut holder = FakeStreamHolder::new();
+        let dns_modifier = DnsModifierMock::new().inspect_result("127.0.0.1\n".to_string(), Ok(()));
+        let factory = DnsModifierFactoryMock::new().make_result(Some(Box::new(dns_modifier)));
+        let mut subject = DnsUtility::new();
+        subject.factory = Box::new(factory);
+        subject.record = SubversionRecord::in_directory(make_record_directory(
+            "go_with_repair_parameter_leaves_subverted_dns_that_is_still_answered",
+        ));
+        subject.probe = Box::new(DnsProbeMock { answered: true });
+
+        let result = subject.go(
+            &mut holder.streams(),
+            &vec![String::new(), String::from("repair")],
+        );
+
+        assert_eq!(result, 0);
+        assert_eq!(
+            holder.stdout.get_string(),
+            String::from("DNS is subverted and still being answered; nothing to repair\n")
+        );
     }
 
     #[test]
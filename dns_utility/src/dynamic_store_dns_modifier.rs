@@ -13,15 +13,23 @@ use core_foundation::propertylist::CFPropertyList;
 use core_foundation::propertylist::CFPropertyListSubClass;
 use core_foundation::string::CFString;
 use std::io::Write;
+use std::net::IpAddr;
 use system_configuration::dynamic_store::SCDynamicStore;
 use system_configuration::dynamic_store::SCDynamicStoreBuilder;
 
 const PRIMARY_SERVICE: &str = "PrimaryService";
+const SERVICE_ORDER: &str = "ServiceOrder";
+const INTERFACE_NAME: &str = "InterfaceName";
+const USER_DEFINED_NAME: &str = "UserDefinedName";
 const SERVER_ADDRESSES: &str = "ServerAddresses";
 const SERVER_ADDRESSES_BAK: &str = "ServerAddressesBak";
+const ALL_DNS_BASE_PATHS: &str = "State:/Network/Service/[^/]+/DNS";
 
 pub struct DynamicStoreDnsModifier {
     store: Box<dyn StoreWrapper>,
+    // Services to change, by interface ("en0") or service ("Wi-Fi") name; the primary service if
+    // there are none
+    interfaces: Vec<String>,
 }
 
 impl DnsModifier for DynamicStoreDnsModifier {
@@ -30,41 +38,98 @@ impl DnsModifier for DynamicStoreDnsModifier {
     }
 
     fn subvert(&self) -> Result<(), String> {
-        let (dns_base_path, dns_info) = self.get_dns_info(true)?;
-        let result = match self.subvert_contents(dns_info) {
-            Err(e) => return Err(e),
-            Ok(None) => return Ok(()),
-            Ok(Some(c)) => c,
-        };
-        self.set_dns_info(dns_base_path, result)
+        let mut subverted_so_far: Vec<(String, Vec<String>)> = vec![];
+        for dns_base_path in self.find_dns_base_paths(true)? {
+            match self.subvert_service(&dns_base_path) {
+                Ok(Some(original_addresses)) => {
+                    subverted_so_far.push((dns_base_path, original_addresses))
+                }
+                Ok(None) => (),
+                Err(msg) => {
+                    subverted_so_far
+                        .into_iter()
+                        .for_each(|(dns_base_path, original_addresses)| {
+                            self.roll_back_subvert(dns_base_path, original_addresses)
+                        });
+                    return Err(msg);
+                }
+            }
+        }
+        Ok(())
     }
 
     fn revert(&self) -> Result<(), String> {
-        let (dns_base_path, dns_info) = self.get_dns_info(true)?;
-        let result = match self.revert_contents(dns_info) {
-            Err(e) => return Err(e),
-            Ok(None) => return Ok(()),
-            Ok(Some(c)) => c,
+        // Without a selection, everything a subversion backed up is put back, not just the
+        // primary service: it may have been subverted with a selection.
+        let everything = self.interfaces.is_empty();
+        let dns_base_paths = if everything {
+            self.store.get_keys(ALL_DNS_BASE_PATHS)
+        } else {
+            self.find_dns_base_paths(true)?
         };
-        self.set_dns_info(dns_base_path, result)
+        for dns_base_path in dns_base_paths {
+            let dns_info = self.get_dns_info(&dns_base_path, true)?;
+            if everything && !dns_info.contains_key(SERVER_ADDRESSES_BAK) {
+                continue;
+            }
+            if let Some(contents) = self.revert_contents(dns_info)? {
+                self.set_dns_info(dns_base_path, contents)?;
+            }
+        }
+        Ok(())
     }
 
     fn inspect(&self, stdout: &mut (dyn Write + Send)) -> Result<(), String> {
-        let (_, dns_info) = self.get_dns_info(false)?;
-        let active_addresses = match dns_info.get(SERVER_ADDRESSES) {
-            None => return Err(String::from("This system has no DNS settings")),
-            Some(sa) => sa,
-        };
-        let output = active_addresses.join("\n");
+        let mut addresses: Vec<String> = vec![];
+        for dns_base_path in self.find_dns_base_paths(false)? {
+            let dns_info = self.get_dns_info(&dns_base_path, false)?;
+            let active_addresses = match dns_info.get(SERVER_ADDRESSES) {
+                None => return Err(String::from("This system has no DNS settings")),
+                Some(sa) => sa,
+            };
+            add_distinct(&mut addresses, active_addresses);
+        }
+        let output = addresses.join("\n");
         writeln!(stdout, "{}", output).expect("write is broken");
         Ok(())
     }
+
+    fn original_servers(&self) -> Result<Vec<IpAddr>, String> {
+        let mut addresses: Vec<String> = vec![];
+        for dns_base_path in self.find_dns_base_paths(false)? {
+            let dns_info = self.get_dns_info(&dns_base_path, false)?;
+            let active_addresses = match dns_info.get(SERVER_ADDRESSES) {
+                None => return Err(String::from("This system has no DNS settings")),
+                Some(sa) => sa,
+            };
+            let original_addresses =
+                match (active_addresses.first(), dns_info.get(SERVER_ADDRESSES_BAK)) {
+                    (Some(first_address), Some(backup_addresses))
+                        if first_address == "127.0.0.1" =>
+                    {
+                        backup_addresses
+                    }
+                    _ => active_addresses,
+                };
+            add_distinct(&mut addresses, original_addresses);
+        }
+        Ok(addresses
+            .iter()
+            .flat_map(|address| address.parse::<IpAddr>())
+            .collect())
+    }
+
+    fn select_interfaces(&mut self, interfaces: &[String]) -> Result<(), String> {
+        self.interfaces = interfaces.to_vec();
+        Ok(())
+    }
 }
 
 impl Default for DynamicStoreDnsModifier {
     fn default() -> Self {
         Self {
             store: Box::new(StoreWrapperReal::new("Prometheus-Network")),
+            interfaces: vec![],
         }
     }
 }
@@ -74,10 +139,19 @@ impl DynamicStoreDnsModifier {
         Default::default()
     }
 
-    fn get_dns_info(
-        &self,
-        for_write: bool,
-    ) -> Result<(String, HashMap<String, Vec<String>>), String> {
+    fn find_dns_base_paths(&self, for_write: bool) -> Result<Vec<String>, String> {
+        let services = if self.interfaces.is_empty() {
+            vec![self.find_primary_service(for_write)?]
+        } else {
+            self.find_selected_services(for_write)?
+        };
+        Ok(services
+            .into_iter()
+            .map(|service| format!("State:/Network/Service/{}/DNS", service))
+            .collect())
+    }
+
+    fn find_primary_service(&self, for_write: bool) -> Result<String, String> {
         let ipv4_map = match self
             .store
             .get_dictionary_string_cfpl("State:/Network/Global/IPv4")
@@ -99,16 +173,99 @@ impl DynamicStoreDnsModifier {
                 ));
             }
         };
-        let primary_service =
-            match self.store.cfpl_to_string(&primary_service_cfpl) {
-                Ok(s) => s,
-                Err(_) => return Err(DynamicStoreDnsModifier::process_msg(
-                    "Dynamic-Store path State:/Network/Global/IPv4/PrimaryService is not a string",
+        match self.store.cfpl_to_string(&primary_service_cfpl) {
+            Ok(s) => Ok(s),
+            Err(_) => Err(DynamicStoreDnsModifier::process_msg(
+                "Dynamic-Store path State:/Network/Global/IPv4/PrimaryService is not a string",
+                for_write,
+            )),
+        }
+    }
+
+    // Every service, in the order the system prefers them, that's active on one of the selected
+    // interfaces
+    fn find_selected_services(&self, for_write: bool) -> Result<Vec<String>, String> {
+        let service_order_cfpl = self
+            .store
+            .get_dictionary_string_cfpl("Setup:/Network/Global/IPv4")
+            .and_then(|ipv4_map| ipv4_map.get(SERVICE_ORDER).cloned())
+            .ok_or_else(|| {
+                DynamicStoreDnsModifier::process_msg(
+                    "Dynamic-Store path Setup:/Network/Global/IPv4/ServiceOrder not found",
                     for_write,
-                )),
-            };
-        let dns_base_path = format!("State:/Network/Service/{}/DNS", primary_service);
-        let dns_map = match self.store.get_dictionary_string_cfpl(&dns_base_path[..]) {
+                )
+            })?;
+        let services: Vec<String> = self
+            .store
+            .cfpl_to_vec(&service_order_cfpl)
+            .map_err(|_| {
+                DynamicStoreDnsModifier::process_msg(
+                    "Dynamic-Store path Setup:/Network/Global/IPv4/ServiceOrder is not an array",
+                    for_write,
+                )
+            })?
+            .iter()
+            .flat_map(|service_cfpl| self.store.cfpl_to_string(service_cfpl))
+            .collect();
+        let names: Vec<(String, Vec<String>)> = services
+            .into_iter()
+            .map(|service| {
+                let names = self.find_service_names(&service);
+                (service, names)
+            })
+            .collect();
+        if let Some(interface) = self.interfaces.iter().find(|interface| {
+            !names
+                .iter()
+                .any(|(_, service_names)| contains_name(service_names, interface))
+        }) {
+            return Err(DynamicStoreDnsModifier::process_msg(
+                &format!(
+                    "This system has no active network interface named {}",
+                    interface
+                ),
+                for_write,
+            ));
+        }
+        Ok(names
+            .into_iter()
+            .filter(|(_, service_names)| {
+                self.interfaces
+                    .iter()
+                    .any(|interface| contains_name(service_names, interface))
+            })
+            .map(|(service, _)| service)
+            .collect())
+    }
+
+    // The interface an active service runs on, and the name the user knows the service by
+    fn find_service_names(&self, service: &str) -> Vec<String> {
+        vec![
+            (
+                format!("State:/Network/Service/{}/IPv4", service),
+                INTERFACE_NAME,
+            ),
+            (
+                format!("Setup:/Network/Service/{}", service),
+                USER_DEFINED_NAME,
+            ),
+        ]
+        .into_iter()
+        .flat_map(|(path, key)| {
+            self.store
+                .get_dictionary_string_cfpl(&path)
+                .and_then(|map| map.get(key).cloned())
+        })
+        .flat_map(|name_cfpl| self.store.cfpl_to_string(&name_cfpl))
+        .collect()
+    }
+
+    fn get_dns_info(
+        &self,
+        dns_base_path: &str,
+        for_write: bool,
+    ) -> Result<HashMap<String, Vec<String>>, String> {
+        let dns_map = match self.store.get_dictionary_string_cfpl(dns_base_path) {
             Some(m) => m,
             None => {
                 return Err(DynamicStoreDnsModifier::process_msg(
@@ -118,7 +275,7 @@ impl DynamicStoreDnsModifier {
             }
         };
         let mut result: HashMap<String, Vec<String>> = HashMap::new();
-        match self.get_server_addresses(&dns_map, &dns_base_path, &SERVER_ADDRESSES, for_write) {
+        match self.get_server_addresses(&dns_map, dns_base_path, &SERVER_ADDRESSES, for_write) {
             Err(e) => return Err(e),
             Ok(None) => (),
             Ok(Some(sa)) => {
@@ -126,11 +283,34 @@ impl DynamicStoreDnsModifier {
             }
         }
         if let Ok(Some(sa)) =
-            self.get_server_addresses(&dns_map, &dns_base_path, SERVER_ADDRESSES_BAK, for_write)
+            self.get_server_addresses(&dns_map, dns_base_path, SERVER_ADDRESSES_BAK, for_write)
         {
             result.insert(String::from(SERVER_ADDRESSES_BAK), sa);
         }
-        Ok((dns_base_path, result))
+        Ok(result)
+    }
+
+    // The addresses the service had before, if it wasn't subverted already
+    fn subvert_service(&self, dns_base_path: &str) -> Result<Option<Vec<String>>, String> {
+        let dns_info = self.get_dns_info(dns_base_path, true)?;
+        let contents = match self.subvert_contents(dns_info)? {
+            Some(contents) => contents,
+            None => return Ok(None),
+        };
+        let original_addresses = contents
+            .get(SERVER_ADDRESSES_BAK)
+            .cloned()
+            .expect("Subverted DNS settings have no backup");
+        self.set_dns_info(dns_base_path.to_string(), contents)?;
+        Ok(Some(original_addresses))
+    }
+
+    fn roll_back_subvert(&self, dns_base_path: String, original_addresses: Vec<String>) {
+        self.set_dns_info(
+            dns_base_path,
+            HashMap::from_iter(vec![(String::from(SERVER_ADDRESSES), original_addresses)]),
+        )
+        .expect("Can't reset ServerAddresses to roll back subversion. Check your DNS settings manually.")
     }
 
     fn process_msg(msg: &str, for_write: bool) -> String {
@@ -299,6 +479,20 @@ impl DynamicStoreDnsModifier {
     }
 }
 
+fn add_distinct(so_far: &mut Vec<String>, addresses: &[String]) {
+    addresses.iter().for_each(|address| {
+        if !so_far.contains(address) {
+            so_far.push(address.clone())
+        }
+    })
+}
+
+fn contains_name(names: &[String], name: &str) -> bool {
+    names
+        .iter()
+        .any(|n| n.to_lowercase() == name.to_lowercase())
+}
+
 pub trait StoreWrapper {
     fn get_dictionary_string_cfpl(&self, path: &str) -> Option<HashMap<String, CFPropertyList>>;
     fn set_dictionary_string_cfpl(
@@ -307,6 +501,9 @@ pub trait StoreWrapper {
         dictionary: HashMap<String, CFPropertyList>,
     ) -> bool;
 
+    // The paths that match a regular expression
+    fn get_keys(&self, pattern: &str) -> Vec<String>;
+
     fn cfpl_to_vec(&self, cfpl: &CFPropertyList) -> Result<Vec<CFPropertyList>, String>;
     fn cfpl_to_string(&self, cfpl: &CFPropertyList) -> Result<String, String>;
 }
@@ -361,6 +558,13 @@ impl StoreWrapper for StoreWrapperReal {
         self.store.set(path, dictionary_cfpl.into_untyped())
     }
 
+    fn get_keys(&self, pattern: &str) -> Vec<String> {
+        match self.store.get_keys(pattern) {
+            Some(keys) => keys.iter().map(|key| key.to_string()).collect(),
+            None => vec![],
+        }
+    }
+
     fn cfpl_to_vec(&self, cfpl: &CFPropertyList) -> Result<Vec<CFPropertyList>, String> {
         match CFPropertyList::downcast_into::<CFArray>(cfpl.clone()) {
             Some(cf_array) => {
@@ -425,6 +629,8 @@ mod tests {
         set_dictionary_string_cfpl_parameters:
             Arc<Mutex<Vec<(String, HashMap<String, CFPropertyList>)>>>,
         set_dictionary_string_cfpl_results: RefCell<Vec<bool>>,
+        get_keys_parameters: Arc<Mutex<Vec<String>>>,
+        get_keys_results: RefCell<Vec<Vec<String>>>,
         cfpl_to_string_parameters: Arc<Mutex<Vec<CFPropertyList>>>,
         cfpl_to_string_results: RefCell<Vec<Result<String, String>>>,
         cfpl_to_vec_parameters: Arc<Mutex<Vec<CFPropertyList>>>,
@@ -459,6 +665,14 @@ mod tests {
                 .remove(0)
         }
 
+        fn get_keys(&self, pattern: &str) -> Vec<String> {
+            self.get_keys_parameters
+                .lock()
+                .unwrap()
+                .push(String::from(pattern));
+            self.get_keys_results.borrow_mut().remove(0)
+        }
+
         fn cfpl_to_vec(&self, cfpl: &CFPropertyList) -> Result<Vec<CFPropertyList>, String> {
             self.cfpl_to_vec_parameters
                 .lock()
@@ -483,6 +697,8 @@ mod tests {
                 get_dictionary_string_cfpl_results: RefCell::new(vec![]),
                 set_dictionary_string_cfpl_parameters: Arc::new(Mutex::new(vec![])),
                 set_dictionary_string_cfpl_results: RefCell::new(vec![]),
+                get_keys_parameters: Arc::new(Mutex::new(vec![])),
+                get_keys_results: RefCell::new(vec![]),
                 cfpl_to_string_parameters: Arc::new(Mutex::new(vec![])),
                 cfpl_to_string_results: RefCell::new(vec![]),
                 cfpl_to_vec_parameters: Arc::new(Mutex::new(vec![])),
@@ -523,6 +739,19 @@ mod tests {
             self
         }
 
+        pub fn get_keys_parameters(
+            mut self,
+            parameters: &Arc<Mutex<Vec<String>>>,
+        ) -> StoreWrapperMock {
+            self.get_keys_parameters = parameters.clone();
+            self
+        }
+
+        pub fn get_keys_result(self, result: Vec<String>) -> StoreWrapperMock {
+            self.get_keys_results.borrow_mut().push(result);
+            self
+        }
+
         pub fn cfpl_to_string_parameters(
             mut self,
             parameters: &Arc<Mutex<Vec<CFPropertyList>>>,
@@ -793,6 +1022,94 @@ mod tests {
         );
     }
 
+    fn make_selection_store(
+        get_dictionary_string_cfpl_parameters: &Arc<Mutex<Vec<String>>>,
+    ) -> StoreWrapperMock {
+        let mut global_map: HashMap<String, CFPropertyList> = HashMap::new();
+        global_map.insert(
+            String::from(SERVICE_ORDER),
+            CFArray::from_CFTypes(&[CFString::from_static_string("booga")])
+                .to_untyped()
+                .to_CFPropertyList(),
+        );
+        let mut ipv4_map: HashMap<String, CFPropertyList> = HashMap::new();
+        ipv4_map.insert(
+            String::from(INTERFACE_NAME),
+            CFString::from_static_string("en0").to_CFPropertyList(),
+        );
+        let mut setup_map: HashMap<String, CFPropertyList> = HashMap::new();
+        setup_map.insert(
+            String::from(USER_DEFINED_NAME),
+            CFString::from_static_string("Wi-Fi").to_CFPropertyList(),
+        );
+        StoreWrapperMock::new()
+            .get_dictionary_string_cfpl_parameters(get_dictionary_string_cfpl_parameters)
+            .get_dictionary_string_cfpl_result(Some(global_map))
+            .cfpl_to_vec_result(Ok(vec![
+                CFString::from_static_string("booga").to_CFPropertyList()
+            ]))
+            .cfpl_to_string_result(Ok(String::from("booga")))
+            .get_dictionary_string_cfpl_result(Some(ipv4_map))
+            .cfpl_to_string_result(Ok(String::from("en0")))
+            .get_dictionary_string_cfpl_result(Some(setup_map))
+            .cfpl_to_string_result(Ok(String::from("Wi-Fi")))
+    }
+
+    #[test]
+    fn inspect_finds_selected_interfaces_by_service_name() {
+        let mut server_addresses_map: HashMap<String, CFPropertyList> = HashMap::new();
+        server_addresses_map.insert(
+            String::from(SERVER_ADDRESSES),
+            CFArray::from_CFTypes(&[CFString::from_static_string("1.2.3.4")])
+                .to_untyped()
+                .to_CFPropertyList(),
+        );
+        let get_dictionary_string_cfpl_parameters = Arc::new(Mutex::new(vec![]));
+        let store = make_selection_store(&get_dictionary_string_cfpl_parameters)
+            .get_dictionary_string_cfpl_result(Some(server_addresses_map))
+            .cfpl_to_vec_result(Ok(vec![
+                CFString::from_static_string("1.2.3.4").to_CFPropertyList()
+            ]))
+            .cfpl_to_string_result(Ok(String::from("1.2.3.4")));
+        let mut subject = DynamicStoreDnsModifier::new();
+        subject.store = Box::new(store);
+        subject.select_interfaces(&[String::from("wi-fi")]).unwrap();
+        let mut holder = FakeStreamHolder::new();
+
+        let result = subject.inspect(holder.streams().stdout);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(holder.stdout.get_string(), String::from("1.2.3.4\n"));
+        assert_eq!(
+            get_parameters_from(get_dictionary_string_cfpl_parameters),
+            vec![
+                String::from("Setup:/Network/Global/IPv4"),
+                String::from("State:/Network/Service/booga/IPv4"),
+                String::from("Setup:/Network/Service/booga"),
+                String::from("State:/Network/Service/booga/DNS"),
+            ]
+        );
+    }
+
+    #[test]
+    fn subvert_complains_about_selected_interfaces_that_arent_active() {
+        let store = make_selection_store(&Arc::new(Mutex::new(vec![])));
+        let mut subject = DynamicStoreDnsModifier::new();
+        subject.store = Box::new(store);
+        subject
+            .select_interfaces(&[String::from("en0"), String::from("utun2")])
+            .unwrap();
+
+        let result = subject.subvert();
+
+        assert_eq!(
+            result,
+            Err(String::from(
+                "This system has no active network interface named utun2; DNS settings cannot be modified"
+            ))
+        );
+    }
+
     #[test]
     fn subvert_backs_off_if_dns_is_already_subverted() {
         let mut ipv4_map: HashMap<String, CFPropertyList> = HashMap::new();
@@ -952,13 +1269,113 @@ mod tests {
         );
     }
 
+    fn make_string_array(strings: &[&'static str]) -> CFPropertyList {
+        let cfstrings: Vec<CFString> = strings
+            .iter()
+            .map(|string| CFString::from_static_string(*string))
+            .collect();
+        CFArray::from_CFTypes(cfstrings.as_slice())
+            .to_untyped()
+            .to_CFPropertyList()
+    }
+
+    fn make_string_cfpls(strings: &[&'static str]) -> Vec<CFPropertyList> {
+        strings
+            .iter()
+            .map(|string| CFString::from_static_string(*string).to_CFPropertyList())
+            .collect()
+    }
+
+    fn make_dns_map(
+        addresses: &[&'static str],
+        backup_addresses_opt: Option<&[&'static str]>,
+    ) -> HashMap<String, CFPropertyList> {
+        let mut dns_map: HashMap<String, CFPropertyList> = HashMap::new();
+        dns_map.insert(String::from(SERVER_ADDRESSES), make_string_array(addresses));
+        if let Some(backup_addresses) = backup_addresses_opt {
+            dns_map.insert(
+                String::from(SERVER_ADDRESSES_BAK),
+                make_string_array(backup_addresses),
+            );
+        }
+        dns_map
+    }
+
+    fn make_name_map(key: &str, name: &'static str) -> HashMap<String, CFPropertyList> {
+        HashMap::from_iter(vec![(
+            String::from(key),
+            CFString::from_static_string(name).to_CFPropertyList(),
+        )])
+    }
+
     #[test]
-    fn revert_complains_if_there_is_no_backup() {
-        let mut ipv4_map: HashMap<String, CFPropertyList> = HashMap::new();
-        ipv4_map.insert(
-            String::from(PRIMARY_SERVICE),
-            CFString::new("booga").to_CFPropertyList(),
+    fn subvert_rolls_back_selected_services_already_subverted_when_a_later_one_fails() {
+        let global_map = HashMap::from_iter(vec![(
+            String::from(SERVICE_ORDER),
+            make_string_array(&["booga", "wooga"]),
+        )]);
+        let set_dictionary_string_cfpl_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let store = StoreWrapperMock::new()
+            .get_dictionary_string_cfpl_result(Some(global_map))
+            .cfpl_to_vec_result(Ok(make_string_cfpls(&["booga", "wooga"])))
+            .cfpl_to_string_result(Ok(String::from("booga")))
+            .cfpl_to_string_result(Ok(String::from("wooga")))
+            .get_dictionary_string_cfpl_result(Some(make_name_map(INTERFACE_NAME, "en0")))
+            .cfpl_to_string_result(Ok(String::from("en0")))
+            .get_dictionary_string_cfpl_result(Some(make_name_map(USER_DEFINED_NAME, "Wi-Fi")))
+            .cfpl_to_string_result(Ok(String::from("Wi-Fi")))
+            .get_dictionary_string_cfpl_result(Some(make_name_map(INTERFACE_NAME, "en1")))
+            .cfpl_to_string_result(Ok(String::from("en1")))
+            .get_dictionary_string_cfpl_result(Some(make_name_map(USER_DEFINED_NAME, "Ethernet")))
+            .cfpl_to_string_result(Ok(String::from("Ethernet")))
+            .get_dictionary_string_cfpl_result(Some(make_dns_map(&["1.2.3.4"], None)))
+            .cfpl_to_vec_result(Ok(make_string_cfpls(&["1.2.3.4"])))
+            .cfpl_to_string_result(Ok(String::from("1.2.3.4")))
+            .get_dictionary_string_cfpl_result(Some(make_dns_map(&["5.6.7.8"], None)))
+            .cfpl_to_vec_result(Ok(make_string_cfpls(&["5.6.7.8"])))
+            .cfpl_to_string_result(Ok(String::from("5.6.7.8")))
+            .set_dictionary_string_cfpl_parameters(&set_dictionary_string_cfpl_parameters_arc)
+            .set_dictionary_string_cfpl_result(true)
+            .set_dictionary_string_cfpl_result(false)
+            .set_dictionary_string_cfpl_result(true);
+        let mut subject = DynamicStoreDnsModifier::new();
+        subject.store = Box::new(store);
+        subject
+            .select_interfaces(&[String::from("Wi-Fi"), String::from("Ethernet")])
+            .unwrap();
+
+        let result = subject.subvert();
+
+        assert_eq!(
+            result,
+            Err(String::from(
+                "Error changing DNS settings. Are you sure you ran me with sudo?"
+            ))
         );
+        let set_dictionary_string_cfpl_parameters =
+            get_parameters_from(set_dictionary_string_cfpl_parameters_arc);
+        let paths: Vec<&String> = set_dictionary_string_cfpl_parameters
+            .iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "State:/Network/Service/booga/DNS",
+                "State:/Network/Service/wooga/DNS",
+                "State:/Network/Service/booga/DNS",
+            ]
+        );
+        let rolled_back = &set_dictionary_string_cfpl_parameters[2].1;
+        assert!(!rolled_back.contains_key(SERVER_ADDRESSES_BAK));
+        compare_cfpls(
+            rolled_back.get(SERVER_ADDRESSES).unwrap(),
+            &make_string_array(&["1.2.3.4"]),
+        );
+    }
+
+    #[test]
+    fn revert_complains_if_there_is_no_backup() {
         let dns_map: HashMap<String, CFPropertyList> = HashMap::from_iter(
             vec![(
                 String::from(SERVER_ADDRESSES),
@@ -968,9 +1385,7 @@ mod tests {
             )]
             .into_iter(),
         );
-        let store = StoreWrapperMock::new()
-            .get_dictionary_string_cfpl_result(Some(ipv4_map))
-            .cfpl_to_string_result(Ok(String::from("booga")))
+        let store = make_selection_store(&Arc::new(Mutex::new(vec![])))
             .get_dictionary_string_cfpl_result(Some(dns_map))
             .cfpl_to_vec_result(Ok(vec![CFArray::from_CFTypes(&[
                 CFString::from_static_string("127.0.0.1"),
@@ -980,6 +1395,7 @@ mod tests {
             .cfpl_to_string_result(Ok(String::from("127.0.0.1")));
         let mut subject = DynamicStoreDnsModifier::new();
         subject.store = Box::new(store);
+        subject.select_interfaces(&[String::from("en0")]).unwrap();
 
         let result = subject.revert();
 
@@ -993,11 +1409,6 @@ mod tests {
 
     #[test]
     fn revert_backs_off_if_settings_are_already_reverted() {
-        let mut ipv4_map: HashMap<String, CFPropertyList> = HashMap::new();
-        ipv4_map.insert(
-            String::from(PRIMARY_SERVICE),
-            CFString::new("booga").to_CFPropertyList(),
-        );
         let dns_map: HashMap<String, CFPropertyList> = HashMap::from_iter(
             vec![(
                 String::from(SERVER_ADDRESSES),
@@ -1008,8 +1419,7 @@ mod tests {
             .into_iter(),
         );
         let store = StoreWrapperMock::new()
-            .get_dictionary_string_cfpl_result(Some(ipv4_map))
-            .cfpl_to_string_result(Ok(String::from("booga")))
+            .get_keys_result(vec![String::from("State:/Network/Service/booga/DNS")])
             .get_dictionary_string_cfpl_result(Some(dns_map))
             .cfpl_to_vec_result(Ok(vec![CFArray::from_CFTypes(&[
                 CFString::from_static_string("1.2.3.4"),
@@ -1027,9 +1437,6 @@ mod tests {
 
     #[test]
     fn revert_works_if_everything_is_copacetic() {
-        let mut ipv4_map: HashMap<String, CFPropertyList> = HashMap::new();
-        let primary_service_cfpl = CFString::from_static_string("booga").to_CFPropertyList();
-        ipv4_map.insert(String::from(PRIMARY_SERVICE), primary_service_cfpl);
         let mut server_addresses_map: HashMap<String, CFPropertyList> = HashMap::new();
         let active_addresses = &[CFString::from_static_string("127.0.0.1")];
         let active_addresses_cfpl = CFArray::from_CFTypes(active_addresses)
@@ -1060,8 +1467,7 @@ mod tests {
             reverted_server_addresses_map.clone(),
         )]));
         let store = StoreWrapperMock::new()
-            .get_dictionary_string_cfpl_result(Some(ipv4_map))
-            .cfpl_to_string_result(Ok(String::from("booga")))
+            .get_keys_result(vec![String::from("State:/Network/Service/booga/DNS")])
             .cfpl_to_string_result(Ok(String::from("127.0.0.1")))
             .cfpl_to_string_result(Ok(String::from("1.2.3.4")))
             .cfpl_to_string_result(Ok(String::from("5.6.7.8")))
@@ -1099,6 +1505,70 @@ mod tests {
         );
     }
 
+    #[test]
+    fn revert_without_a_selection_puts_back_every_service_that_was_subverted() {
+        let get_keys_parameters = Arc::new(Mutex::new(vec![]));
+        let set_dictionary_string_cfpl_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let store = StoreWrapperMock::new()
+            .get_keys_parameters(&get_keys_parameters)
+            .get_keys_result(vec![
+                String::from("State:/Network/Service/booga/DNS"),
+                String::from("State:/Network/Service/wooga/DNS"),
+                String::from("State:/Network/Service/zooga/DNS"),
+            ])
+            .get_dictionary_string_cfpl_result(Some(make_dns_map(
+                &["127.0.0.1"],
+                Some(&["1.2.3.4"][..]),
+            )))
+            .cfpl_to_vec_result(Ok(make_string_cfpls(&["127.0.0.1"])))
+            .cfpl_to_string_result(Ok(String::from("127.0.0.1")))
+            .cfpl_to_vec_result(Ok(make_string_cfpls(&["1.2.3.4"])))
+            .cfpl_to_string_result(Ok(String::from("1.2.3.4")))
+            .get_dictionary_string_cfpl_result(Some(make_dns_map(&["9.9.9.9"], None)))
+            .cfpl_to_vec_result(Ok(make_string_cfpls(&["9.9.9.9"])))
+            .cfpl_to_string_result(Ok(String::from("9.9.9.9")))
+            .get_dictionary_string_cfpl_result(Some(make_dns_map(
+                &["127.0.0.1"],
+                Some(&["5.6.7.8"][..]),
+            )))
+            .cfpl_to_vec_result(Ok(make_string_cfpls(&["127.0.0.1"])))
+            .cfpl_to_string_result(Ok(String::from("127.0.0.1")))
+            .cfpl_to_vec_result(Ok(make_string_cfpls(&["5.6.7.8"])))
+            .cfpl_to_string_result(Ok(String::from("5.6.7.8")))
+            .set_dictionary_string_cfpl_parameters(&set_dictionary_string_cfpl_parameters_arc)
+            .set_dictionary_string_cfpl_result(true)
+            .set_dictionary_string_cfpl_result(true);
+        let mut subject = DynamicStoreDnsModifier::new();
+        subject.store = Box::new(store);
+
+        let result = subject.revert();
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            get_parameters_from(get_keys_parameters),
+            vec![String::from(ALL_DNS_BASE_PATHS)]
+        );
+        let set_dictionary_string_cfpl_parameters =
+            get_parameters_from(set_dictionary_string_cfpl_parameters_arc);
+        assert_eq!(set_dictionary_string_cfpl_parameters.len(), 2);
+        vec![
+            ("State:/Network/Service/booga/DNS", "1.2.3.4"),
+            ("State:/Network/Service/zooga/DNS", "5.6.7.8"),
+        ]
+        .into_iter()
+        .zip(set_dictionary_string_cfpl_parameters.iter())
+        .for_each(|((expected_path, expected_address), (path, dns_map))| {
+            assert_eq!(path, expected_path);
+            assert!(!dns_map.contains_key(SERVER_ADDRESSES_BAK));
+            compare_cfpls(
+                dns_map.get(SERVER_ADDRESSES).unwrap(),
+                &CFArray::from_CFTypes(&[CFString::new(expected_address)])
+                    .to_untyped()
+                    .to_CFPropertyList(),
+            );
+        });
+    }
+
     #[test]
     fn inspect_complains_if_root_path_doesnt_exist() {
         let mut stream_holder = FakeStreamHolder::new();
@@ -1311,6 +1781,49 @@ mod tests {
         assert_eq!(result.is_ok(), true);
     }
 
+    #[test]
+    fn original_servers_are_the_backed_up_ones_if_dns_is_subverted() {
+        let mut ipv4_map: HashMap<String, CFPropertyList> = HashMap::new();
+        let primary_service_cfpl = CFString::from_static_string("booga").to_CFPropertyList();
+        ipv4_map.insert(String::from(PRIMARY_SERVICE), primary_service_cfpl);
+        let mut server_addresses_map: HashMap<String, CFPropertyList> = HashMap::new();
+        let active_addresses_cfpl =
+            CFArray::from_CFTypes(&[CFString::from_static_string("127.0.0.1")])
+                .to_untyped()
+                .to_CFPropertyList();
+        let backup_addresses_cfpl = CFArray::from_CFTypes(&[
+            CFString::from_static_string("1.2.3.4"),
+            CFString::from_static_string("5.6.7.8"),
+        ])
+        .to_untyped()
+        .to_CFPropertyList();
+        server_addresses_map.insert(String::from(SERVER_ADDRESSES), active_addresses_cfpl);
+        server_addresses_map.insert(String::from(SERVER_ADDRESSES_BAK), backup_addresses_cfpl);
+        let store = StoreWrapperMock::new()
+            .get_dictionary_string_cfpl_result(Some(ipv4_map))
+            .cfpl_to_string_result(Ok(String::from("booga")))
+            .get_dictionary_string_cfpl_result(Some(server_addresses_map))
+            .cfpl_to_vec_result(Ok(vec![
+                CFString::from_static_string("127.0.0.1").to_CFPropertyList()
+            ]))
+            .cfpl_to_string_result(Ok(String::from("127.0.0.1")))
+            .cfpl_to_vec_result(Ok(vec![
+                CFString::from_static_string("1.2.3.4").to_CFPropertyList(),
+                CFString::from_static_string("5.6.7.8").to_CFPropertyList(),
+            ]))
+            .cfpl_to_string_result(Ok(String::from("1.2.3.4")))
+            .cfpl_to_string_result(Ok(String::from("5.6.7.8")));
+        let mut subject = DynamicStoreDnsModifier::new();
+        subject.store = Box::new(store);
+
+        let result = subject.original_servers();
+
+        assert_eq!(
+            result,
+            Ok(vec![IpAddr::from([1, 2, 3, 4]), IpAddr::from([5, 6, 7, 8])])
+        );
+    }
+
     fn compare_cfpls(a: &CFPropertyList, b: &CFPropertyList) {
         if !a.eq(b) {
             println!("The following two CFPropertyLists were not equal:");
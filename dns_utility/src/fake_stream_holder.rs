@@ -54,6 +54,9 @@ impl Write for ByteArrayWriter {
 pub struct ByteArrayReader {
     byte_array: Vec<u8>,
     position: usize,
+    next_error: Option<Error>,
+    // Most bytes each of the next reads may return, however big its buffer
+    chunk_sizes: Vec<usize>,
 }
 
 impl ByteArrayReader {
@@ -61,13 +64,30 @@ impl ByteArrayReader {
         ByteArrayReader {
             byte_array: byte_array.to_vec(),
             position: 0,
+            next_error: None,
+            chunk_sizes: vec![],
         }
     }
+
+    pub fn reject_next_read(&mut self, error: Error) {
+        self.next_error = Some(error);
+    }
+
+    // A rejected read doesn't use up a chunk size; reads past the schedule return all they can
+    pub fn read_in_chunks(&mut self, chunk_sizes: Vec<usize>) {
+        self.chunk_sizes = chunk_sizes;
+    }
 }
 
 impl Read for ByteArrayReader {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let to_copy = min(buf.len(), self.byte_array.len() - self.position);
+        if let Some(error) = self.next_error.take() {
+            return Err(error);
+        }
+        let mut to_copy = min(buf.len(), self.byte_array.len() - self.position);
+        if !self.chunk_sizes.is_empty() {
+            to_copy = min(to_copy, self.chunk_sizes.remove(0));
+        }
         for idx in 0..to_copy {
             buf[idx] = self.byte_array[self.position + idx]
         }
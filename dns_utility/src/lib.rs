@@ -10,6 +10,8 @@ extern crate system_configuration;
 
 #[cfg(target_os = "windows")]
 pub mod adapter_wrapper;
+#[cfg(target_os = "linux")]
+pub mod command_runner;
 pub mod dns_modifier;
 pub mod dns_modifier_factory;
 pub mod dns_utility;
@@ -19,7 +21,13 @@ pub mod ipconfig_wrapper;
 pub mod main_tools;
 #[cfg(target_os = "windows")]
 pub mod netsh;
+#[cfg(target_os = "linux")]
+pub mod network_manager_dns_modifier;
+pub mod repair;
 pub mod resolv_conf_dns_modifier;
+#[cfg(target_os = "linux")]
+pub mod resolved_dns_modifier;
+pub mod subversion_record;
 pub mod utils;
 #[cfg(target_os = "windows")]
 pub mod win_dns_modifier;
@@ -0,0 +1,360 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+#![cfg(target_os = "linux")]
+use crate::command_runner::{CommandRunner, CommandRunnerReal};
+use crate::dns_modifier::DnsModifier;
+use crate::utils::distinct_ips;
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+
+const NMCLI: &str = "nmcli";
+const SUBVERTED_SERVER: &str = "127.0.0.1";
+
+// NetworkManager writes out its own idea of the DNS servers, into resolv.conf or systemd-resolved,
+// whenever a connection changes. So the servers are changed on the devices' active connections
+// instead, never in the connections' profiles: `nmcli device reapply` puts a device back the way
+// its profile says, and so does a reboot if nothing reverts it first.
+pub struct NetworkManagerDnsModifier {
+    runner: Box<dyn CommandRunner>,
+}
+
+impl DnsModifier for NetworkManagerDnsModifier {
+    fn type_name(&self) -> &'static str {
+        "NetworkManagerDnsModifier"
+    }
+
+    fn subvert(&self) -> Result<(), String> {
+        let devices = self.devices_with_servers()?;
+        if devices.is_empty() {
+            return Err(String::from(
+                "This system does not appear to be connected to a network",
+            ));
+        }
+        devices
+            .iter()
+            .filter(|(_, servers)| !is_subverted(servers))
+            .try_for_each(|(device, _)| {
+                self.nmcli(&[
+                    "device",
+                    "modify",
+                    device,
+                    "ipv4.dns",
+                    SUBVERTED_SERVER,
+                    "ipv4.ignore-auto-dns",
+                    "yes",
+                    "ipv6.dns",
+                    "",
+                    "ipv6.ignore-auto-dns",
+                    "yes",
+                ])
+                .map(|_| ())
+            })
+    }
+
+    fn revert(&self) -> Result<(), String> {
+        self.devices_with_servers()?
+            .iter()
+            .filter(|(_, servers)| is_subverted(servers))
+            .try_for_each(|(device, _)| self.nmcli(&["device", "reapply", device]).map(|_| ()))
+    }
+
+    fn inspect(&self, stdout: &mut (dyn io::Write + Send)) -> Result<(), String> {
+        let servers: Vec<String> = self
+            .devices_with_servers()?
+            .into_iter()
+            .flat_map(|(_, servers)| servers)
+            .collect();
+        let ips = distinct_ips(&servers);
+        if ips.is_empty() {
+            return Err(String::from(
+                "This system does not appear to be connected to a network",
+            ));
+        }
+        ips.iter()
+            .for_each(|ip| writeln!(stdout, "{}", ip).expect("stdout doesn't work"));
+        Ok(())
+    }
+
+    fn original_servers(&self) -> Result<Vec<IpAddr>, String> {
+        let mut servers: Vec<String> = vec![];
+        for (device, device_servers) in self.devices_with_servers()? {
+            if is_subverted(&device_servers) {
+                servers.extend(self.profile_and_dhcp_servers(&device)?);
+            } else {
+                servers.extend(device_servers);
+            }
+        }
+        Ok(distinct_ips(&servers))
+    }
+}
+
+impl Default for NetworkManagerDnsModifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NetworkManagerDnsModifier {
+    pub fn new() -> NetworkManagerDnsModifier {
+        NetworkManagerDnsModifier {
+            runner: Box::new(CommandRunnerReal::new()),
+        }
+    }
+
+    // NetworkManager is running, and /etc/resolv.conf is either its own or systemd-resolved's,
+    // which NetworkManager feeds
+    pub fn is_in_charge() -> bool {
+        let running = CommandRunnerReal::new()
+            .run(NMCLI, &["-t", "-f", "RUNNING", "general"])
+            .map(|output| output.trim() == "running")
+            .unwrap_or(false);
+        let managed_link = fs::read_link("/etc/resolv.conf")
+            .map(|target| {
+                let target = target.to_string_lossy();
+                target.contains("run/NetworkManager/") || target.contains("run/systemd/resolve/")
+            })
+            .unwrap_or(false);
+        let managed_file = fs::read_to_string("/etc/resolv.conf")
+            .map(|contents| contents.contains("Generated by NetworkManager"))
+            .unwrap_or(false);
+        running && (managed_link || managed_file)
+    }
+
+    fn nmcli(&self, args: &[&str]) -> Result<String, String> {
+        self.runner.run(NMCLI, args)
+    }
+
+    // Connected devices that have DNS servers, and the servers they're using now
+    fn devices_with_servers(&self) -> Result<Vec<(String, Vec<String>)>, String> {
+        let devices = self
+            .terse_lines(&["-t", "-f", "DEVICE,STATE", "device", "status"])?
+            .into_iter()
+            .filter(|(_, state)| state.starts_with("connected"))
+            .map(|(device, _)| device)
+            .collect::<Vec<String>>();
+        let mut result = vec![];
+        for device in devices {
+            let servers: Vec<String> = self
+                .terse_lines(&["-t", "-f", "IP4.DNS,IP6.DNS", "device", "show", &device])?
+                .into_iter()
+                .map(|(_, server)| server)
+                .collect();
+            if !servers.is_empty() {
+                result.push((device, servers));
+            }
+        }
+        Ok(result)
+    }
+
+    // What a subverted device would use if it weren't: its profile's own servers first, as
+    // NetworkManager would put them, then whatever DHCP offered
+    fn profile_and_dhcp_servers(&self, device: &str) -> Result<Vec<String>, String> {
+        let mut servers = vec![];
+        let mut dhcp_servers = vec![];
+        for (field, value) in self.terse_lines(&[
+            "-t",
+            "-f",
+            "GENERAL.CONNECTION,DHCP4,DHCP6",
+            "device",
+            "show",
+            device,
+        ])? {
+            if field == "GENERAL.CONNECTION" && !value.is_empty() {
+                for (_, profile_servers) in self.terse_lines(&[
+                    "-t",
+                    "-f",
+                    "ipv4.dns,ipv6.dns",
+                    "connection",
+                    "show",
+                    "id",
+                    &value,
+                ])? {
+                    servers.extend(
+                        profile_servers
+                            .split(',')
+                            .filter(|server| !server.is_empty())
+                            .map(|server| server.to_string()),
+                    );
+                }
+            } else if let Some(offered) = value.split("name_servers = ").nth(1) {
+                dhcp_servers.extend(offered.split_whitespace().map(|server| server.to_string()));
+            }
+        }
+        servers.extend(dhcp_servers);
+        Ok(servers)
+    }
+
+    // nmcli's terse output: one field per line, its name and value split by the first colon
+    // that isn't escaped
+    fn terse_lines(&self, args: &[&str]) -> Result<Vec<(String, String)>, String> {
+        let output = self.nmcli(args)?;
+        Ok(output
+            .lines()
+            .filter_map(|line| {
+                let mut field = String::new();
+                let mut chars = line.chars();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => field.push(chars.next()?),
+                        ':' => return Some((field, unescape(chars.as_str()))),
+                        c => field.push(c),
+                    }
+                }
+                None
+            })
+            .collect())
+    }
+}
+
+fn is_subverted(servers: &[String]) -> bool {
+    servers == [SUBVERTED_SERVER]
+}
+
+fn unescape(value: &str) -> String {
+    value.replace("\\:", ":").replace("\\\\", "\\")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command_runner::tests_utils::CommandRunnerMock;
+    use crate::utils::get_parameters_from;
+    use std::str::FromStr;
+
+    const DEVICES: &str = "enp0s3:connected\nwlan0:connected (externally)\nlo:unmanaged\n";
+    const ENP0S3_BEFORE: &str = "IP4.DNS[1]:192.168.0.1\nIP6.DNS[1]:fd00\\:\\:1\n";
+    const WLAN0_BEFORE: &str = "IP4.DNS[1]:8.8.8.8\n";
+    const SUBVERTED: &str = "IP4.DNS[1]:127.0.0.1\n";
+
+    fn make_subject(runner: CommandRunnerMock) -> NetworkManagerDnsModifier {
+        NetworkManagerDnsModifier {
+            runner: Box::new(runner),
+        }
+    }
+
+    #[test]
+    fn subvert_points_each_connected_device_at_localhost_without_touching_profiles() {
+        let runner = CommandRunnerMock::new()
+            .run_result(Ok(DEVICES))
+            .run_result(Ok(ENP0S3_BEFORE))
+            .run_result(Ok(SUBVERTED))
+            .run_result(Ok(""));
+        let run_parameters = runner.run_parameters.clone();
+        let subject = make_subject(runner);
+
+        let result = subject.subvert();
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            get_parameters_from(run_parameters),
+            vec![
+                "nmcli -t -f DEVICE,STATE device status",
+                "nmcli -t -f IP4.DNS,IP6.DNS device show enp0s3",
+                "nmcli -t -f IP4.DNS,IP6.DNS device show wlan0",
+                "nmcli device modify enp0s3 ipv4.dns 127.0.0.1 ipv4.ignore-auto-dns yes ipv6.dns  ipv6.ignore-auto-dns yes",
+            ]
+        );
+    }
+
+    #[test]
+    fn subvert_complains_when_no_device_has_servers() {
+        let runner = CommandRunnerMock::new()
+            .run_result(Ok(DEVICES))
+            .run_result(Ok(""))
+            .run_result(Ok(""));
+        let subject = make_subject(runner);
+
+        let result = subject.subvert();
+
+        assert_eq!(
+            result,
+            Err("This system does not appear to be connected to a network".to_string())
+        );
+    }
+
+    #[test]
+    fn revert_reapplies_the_profiles_of_subverted_devices() {
+        let runner = CommandRunnerMock::new()
+            .run_result(Ok(DEVICES))
+            .run_result(Ok(SUBVERTED))
+            .run_result(Ok(WLAN0_BEFORE))
+            .run_result(Ok(""));
+        let run_parameters = runner.run_parameters.clone();
+        let subject = make_subject(runner);
+
+        let result = subject.revert();
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            get_parameters_from(run_parameters)[3],
+            "nmcli device reapply enp0s3"
+        );
+    }
+
+    #[test]
+    fn revert_passes_on_what_nmcli_says_is_wrong() {
+        let runner = CommandRunnerMock::new()
+            .run_result(Ok("enp0s3:connected\n"))
+            .run_result(Ok(SUBVERTED))
+            .run_result(Err(
+                "nmcli device reapply enp0s3 failed: Error: Not authorized to reapply the device.",
+            ));
+        let subject = make_subject(runner);
+
+        let result = subject.revert();
+
+        assert_eq!(
+            result,
+            Err(
+                "nmcli device reapply enp0s3 failed: Error: Not authorized to reapply the device."
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn inspect_lists_each_server_once() {
+        let runner = CommandRunnerMock::new()
+            .run_result(Ok(DEVICES))
+            .run_result(Ok(ENP0S3_BEFORE))
+            .run_result(Ok("IP4.DNS[1]:8.8.8.8\nIP4.DNS[2]:192.168.0.1\n"));
+        let subject = make_subject(runner);
+        let mut stdout: Vec<u8> = vec![];
+
+        let result = subject.inspect(&mut stdout);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            String::from_utf8(stdout).unwrap(),
+            "192.168.0.1\nfd00::1\n8.8.8.8\n"
+        );
+    }
+
+    #[test]
+    fn original_servers_of_a_subverted_device_come_from_its_profile_and_dhcp() {
+        let runner = CommandRunnerMock::new()
+            .run_result(Ok("enp0s3:connected\n"))
+            .run_result(Ok(SUBVERTED))
+            .run_result(Ok(
+                "GENERAL.CONNECTION:Wired connection 1\nDHCP4.OPTION[1]:broadcast_address = 192.168.0.255\nDHCP4.OPTION[2]:domain_name_servers = 192.168.0.1 192.168.0.2\n",
+            ))
+            .run_result(Ok("ipv4.dns:1.1.1.1\nipv6.dns:\n"));
+        let run_parameters = runner.run_parameters.clone();
+        let subject = make_subject(runner);
+
+        let result = subject.original_servers();
+
+        assert_eq!(
+            result,
+            Ok(vec![
+                IpAddr::from_str("1.1.1.1").unwrap(),
+                IpAddr::from_str("192.168.0.1").unwrap(),
+                IpAddr::from_str("192.168.0.2").unwrap(),
+            ])
+        );
+        assert_eq!(
+            get_parameters_from(run_parameters)[3],
+            "nmcli -t -f ipv4.dns,ipv6.dns connection show id Wired connection 1"
+        );
+    }
+}
@@ -0,0 +1,221 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::dns_modifier::DnsModifier;
+use crate::subversion_record::SubversionRecord;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+
+const SUBVERTED_INSPECTION: &str = "127.0.0.1\n";
+const PROBE_TRANSACTION_ID: [u8; 2] = [0xD5, 0x5D];
+// A query for the address of the root, which anything answering DNS will answer somehow
+const PROBE_QUERY: [u8; 17] = [
+    0xD5, 0x5D, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00,
+    0x01,
+];
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, PartialEq)]
+pub enum RepairOutcome {
+    NotSubverted,
+    StillAnswered,
+    Reverted,
+}
+
+pub trait DnsProbe {
+    fn is_answered(&self) -> bool;
+}
+
+// Asks the subverted DNS server something, to see whether it's still there
+pub struct DnsProbeReal {
+    server: SocketAddr,
+}
+
+impl Default for DnsProbeReal {
+    fn default() -> Self {
+        DnsProbeReal {
+            server: SocketAddr::from(([127, 0, 0, 1], 53)),
+        }
+    }
+}
+
+impl DnsProbe for DnsProbeReal {
+    fn is_answered(&self) -> bool {
+        let socket = match UdpSocket::bind(SocketAddr::from(([127, 0, 0, 1], 0))) {
+            Ok(socket) => socket,
+            Err(_) => return false,
+        };
+        if socket.set_read_timeout(Some(PROBE_TIMEOUT)).is_err()
+            || socket.send_to(&PROBE_QUERY, self.server).is_err()
+        {
+            return false;
+        }
+        let mut buf = [0u8; 512];
+        loop {
+            match socket.recv_from(&mut buf) {
+                Ok((length, from)) if from == self.server && length >= 2 => {
+                    if buf[0..2] == PROBE_TRANSACTION_ID {
+                        return true;
+                    }
+                }
+                Ok(_) => (),
+                Err(_) => return false,
+            }
+        }
+    }
+}
+
+impl DnsProbeReal {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+pub fn is_subverted(modifier: &dyn DnsModifier) -> Result<bool, String> {
+    let mut inspection: Vec<u8> = vec![];
+    modifier.inspect(&mut inspection)?;
+    Ok(String::from_utf8_lossy(&inspection) == SUBVERTED_INSPECTION)
+}
+
+// DNS left pointing at 127.0.0.1 with nothing there to answer is worse than DNS never subverted,
+// so it's reverted. If the settings have lost track of what to revert to, the record still knows.
+pub fn repair_orphaned_subversion(
+    modifier: &dyn DnsModifier,
+    record: &SubversionRecord,
+    probe: &dyn DnsProbe,
+) -> Result<RepairOutcome, String> {
+    if !is_subverted(modifier)? {
+        record.forget()?;
+        return Ok(RepairOutcome::NotSubverted);
+    }
+    if probe.is_answered() {
+        return Ok(RepairOutcome::StillAnswered);
+    }
+    match modifier.revert() {
+        Ok(()) => {
+            record.forget()?;
+            Ok(RepairOutcome::Reverted)
+        }
+        Err(e) => match record.original_servers() {
+            Some(ref servers) if !servers.is_empty() => Err(format!(
+                "{}; before it was subverted, DNS used {}",
+                e,
+                servers
+                    .iter()
+                    .map(|ip| ip.to_string())
+                    .collect::<Vec<String>>()
+                    .join(",")
+            )),
+            _ => Err(e),
+        },
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::dns_utility::tests::DnsModifierMock;
+    use crate::subversion_record::tests::make_record_directory;
+    use std::net::IpAddr;
+    use std::str::FromStr;
+
+    pub struct DnsProbeMock {
+        pub answered: bool,
+    }
+
+    impl DnsProbe for DnsProbeMock {
+        fn is_answered(&self) -> bool {
+            self.answered
+        }
+    }
+
+    fn make_record(test_name: &str) -> SubversionRecord {
+        let record = SubversionRecord::in_directory(make_record_directory(test_name));
+        record
+            .write(&[IpAddr::from_str("192.168.0.1").unwrap()])
+            .unwrap();
+        record
+    }
+
+    #[test]
+    fn unsubverted_dns_needs_no_repair_and_leaves_no_record() {
+        let modifier = DnsModifierMock::new().inspect_result("192.168.0.1\n".to_string(), Ok(()));
+        let record = make_record("unsubverted_dns_needs_no_repair_and_leaves_no_record");
+
+        let result =
+            repair_orphaned_subversion(&modifier, &record, &DnsProbeMock { answered: false });
+
+        assert_eq!(result, Ok(RepairOutcome::NotSubverted));
+        assert_eq!(record.original_servers(), None);
+    }
+
+    #[test]
+    fn subverted_dns_that_is_still_answered_is_left_alone() {
+        let modifier = DnsModifierMock::new().inspect_result("127.0.0.1\n".to_string(), Ok(()));
+        let record = make_record("subverted_dns_that_is_still_answered_is_left_alone");
+
+        let result =
+            repair_orphaned_subversion(&modifier, &record, &DnsProbeMock { answered: true });
+
+        assert_eq!(result, Ok(RepairOutcome::StillAnswered));
+        assert!(record.original_servers().is_some());
+    }
+
+    #[test]
+    fn subverted_dns_that_nothing_answers_is_reverted() {
+        let modifier = DnsModifierMock::new()
+            .inspect_result("127.0.0.1\n".to_string(), Ok(()))
+            .revert_result(Ok(()));
+        let record = make_record("subverted_dns_that_nothing_answers_is_reverted");
+
+        let result =
+            repair_orphaned_subversion(&modifier, &record, &DnsProbeMock { answered: false });
+
+        assert_eq!(result, Ok(RepairOutcome::Reverted));
+        assert_eq!(record.original_servers(), None);
+    }
+
+    #[test]
+    fn a_failed_repair_says_what_dns_used_before() {
+        let modifier = DnsModifierMock::new()
+            .inspect_result("127.0.0.1\n".to_string(), Ok(()))
+            .revert_result(Err("There do not appear to be any DNS settings".to_string()));
+        let record = make_record("a_failed_repair_says_what_dns_used_before");
+
+        let result =
+            repair_orphaned_subversion(&modifier, &record, &DnsProbeMock { answered: false });
+
+        assert_eq!(
+            result,
+            Err(
+                "There do not appear to be any DNS settings; before it was subverted, DNS used 192.168.0.1"
+                    .to_string()
+            )
+        );
+        assert!(record.original_servers().is_some());
+    }
+
+    #[test]
+    fn the_probe_finds_nothing_where_nothing_is_listening() {
+        let unused = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server = unused.local_addr().unwrap();
+        drop(unused);
+        let subject = DnsProbeReal { server };
+
+        assert_eq!(subject.is_answered(), false);
+    }
+
+    #[test]
+    fn the_probe_finds_a_dns_server_that_answers() {
+        let server_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server = server_socket.local_addr().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            let (length, from) = server_socket.recv_from(&mut buf).unwrap();
+            buf[2] |= 0x80;
+            server_socket.send_to(&buf[0..length], from).unwrap();
+        });
+        let subject = DnsProbeReal { server };
+
+        assert_eq!(subject.is_answered(), true);
+        handle.join().unwrap();
+    }
+}
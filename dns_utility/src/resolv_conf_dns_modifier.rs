@@ -10,10 +10,16 @@ use std::io::Read;
 use std::io::Seek;
 use std::io::SeekFrom;
 use std::io::Write;
+use std::net::IpAddr;
 use std::ops::Add;
 use std::path::Path;
 use std::path::PathBuf;
 
+const UPSTREAM_RESOLV_CONFS: [&str; 2] = [
+    "run/NetworkManager/no-stub-resolv.conf",
+    "run/systemd/resolve/resolv.conf",
+];
+
 pub struct ResolvConfDnsModifier {
     root: PathBuf,
 }
@@ -42,6 +48,17 @@ impl DnsModifier for ResolvConfDnsModifier {
         let (_, contents) = self.open_resolv_conf(false)?;
         self.inspect_contents(contents, stdout)
     }
+
+    fn original_servers(&self) -> Result<Vec<IpAddr>, String> {
+        let (_, contents) = self.open_resolv_conf(false)?;
+        let servers = self.original_servers_in(&contents)?;
+        if servers.iter().all(|ip| ip.is_loopback()) {
+            if let Some(upstream_servers) = self.upstream_servers() {
+                return Ok(upstream_servers);
+            }
+        }
+        Ok(servers)
+    }
 }
 
 impl Default for ResolvConfDnsModifier {
@@ -160,6 +177,47 @@ impl ResolvConfDnsModifier {
         Ok(())
     }
 
+    fn original_servers_in(&self, contents: &str) -> Result<Vec<IpAddr>, String> {
+        let active_nameservers = self.active_nameservers(contents);
+        self.check_disconnected(&active_nameservers)?;
+        let nameservers = if self.check_already_subverted(&active_nameservers) {
+            self.existing_nameservers(contents)
+                .into_iter()
+                .filter(|(line, _)| line.starts_with('#'))
+                .map(|(line, position)| (line[1..].to_string(), position))
+                .collect()
+        } else {
+            active_nameservers
+        };
+        Ok(nameservers
+            .into_iter()
+            .flat_map(|(line, _)| self.nameserver_line_to_ip(line).parse::<IpAddr>())
+            .collect())
+    }
+
+    // A resolv.conf that names only a local stub resolver (systemd-resolved, or NetworkManager's
+    // dnsmasq) says nothing about where lookups really go; these are the files those services
+    // leave the real servers in.
+    fn upstream_servers(&self) -> Option<Vec<IpAddr>> {
+        UPSTREAM_RESOLV_CONFS.iter().find_map(|relative_path| {
+            let mut contents = String::new();
+            File::open(self.root.join(relative_path))
+                .and_then(|mut file| file.read_to_string(&mut contents))
+                .ok()?;
+            let servers: Vec<IpAddr> = self
+                .active_nameservers(&contents)
+                .into_iter()
+                .flat_map(|(line, _)| self.nameserver_line_to_ip(line).parse::<IpAddr>())
+                .filter(|ip| !ip.is_loopback())
+                .collect();
+            if servers.is_empty() {
+                None
+            } else {
+                Some(servers)
+            }
+        })
+    }
+
     pub fn nameserver_line_to_ip(&self, nameserver_line: String) -> String {
         let regex = Regex::new(r"^\s*nameserver\s+([^\s#]*)").expect("Regex syntax error");
         let captures = regex
@@ -839,6 +897,82 @@ mod tests {
         assert_eq!(result.is_ok(), true);
     }
 
+    #[test]
+    fn original_servers_are_the_active_ones_if_dns_is_not_subverted() {
+        let root = make_root("original_servers_are_the_active_ones_if_dns_is_not_subverted");
+        make_resolv_conf(
+            &root,
+            "#comment\n#nameserver 1.1.1.1\nnameserver 8.8.8.8\nnameserver 9.9.9.9\n",
+        );
+        let mut subject = ResolvConfDnsModifier::new();
+        subject.root = root.clone();
+
+        let result = subject.original_servers();
+
+        assert_eq!(
+            result,
+            Ok(vec![IpAddr::from([8, 8, 8, 8]), IpAddr::from([9, 9, 9, 9])])
+        );
+    }
+
+    #[test]
+    fn original_servers_are_the_commented_ones_if_dns_is_subverted() {
+        let root = make_root("original_servers_are_the_commented_ones_if_dns_is_subverted");
+        make_resolv_conf(
+            &root,
+            "#comment\n#nameserver 8.8.8.8\n#nameserver 9.9.9.9\nnameserver 127.0.0.1\n",
+        );
+        let mut subject = ResolvConfDnsModifier::new();
+        subject.root = root.clone();
+
+        let result = subject.original_servers();
+
+        assert_eq!(
+            result,
+            Ok(vec![IpAddr::from([8, 8, 8, 8]), IpAddr::from([9, 9, 9, 9])])
+        );
+    }
+
+    #[test]
+    fn original_servers_look_behind_a_local_stub_resolver() {
+        let root = make_root("original_servers_look_behind_a_local_stub_resolver");
+        make_resolv_conf(&root, "#nameserver 127.0.0.53\nnameserver 127.0.0.1\n");
+        let upstream_dir = root.join("run").join("systemd").join("resolve");
+        fs::create_dir_all(&upstream_dir).unwrap();
+        fs::write(
+            upstream_dir.join("resolv.conf"),
+            "nameserver 1.1.1.1\nnameserver 1.0.0.1\n",
+        )
+        .unwrap();
+        let mut subject = ResolvConfDnsModifier::new();
+        subject.root = root.clone();
+
+        let result = subject.original_servers();
+
+        assert_eq!(
+            result,
+            Ok(vec![IpAddr::from([1, 1, 1, 1]), IpAddr::from([1, 0, 0, 1])])
+        );
+    }
+
+    #[test]
+    fn original_servers_complain_if_there_is_no_preexisting_nameserver_directive() {
+        let root =
+            make_root("original_servers_complain_if_there_is_no_preexisting_nameserver_directive");
+        make_resolv_conf(&root, "#comment\n");
+        let mut subject = ResolvConfDnsModifier::new();
+        subject.root = root.clone();
+
+        let result = subject.original_servers();
+
+        assert_eq!(
+            result,
+            Err(String::from(
+                "This system does not appear to be connected to a network"
+            ))
+        );
+    }
+
     fn make_root(test_name: &str) -> PathBuf {
         let cur_dir = env::current_dir().unwrap();
         let generated_dir = cur_dir.join(Path::new("generated"));
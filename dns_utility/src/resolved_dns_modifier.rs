@@ -0,0 +1,433 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+#![cfg(target_os = "linux")]
+use crate::command_runner::{CommandRunner, CommandRunnerReal};
+use crate::dns_modifier::DnsModifier;
+use crate::utils::distinct_ips;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+const RESOLVECTL: &str = "resolvectl";
+const SUBVERTED_SERVER: &str = "127.0.0.1";
+const EVERY_DOMAIN: &str = "~.";
+// Under /run, so that it goes away on reboot along with the settings it would restore
+const BACKUP_PATH: &str = "/run/dns_utility/resolved-links";
+
+type LinkValues = Vec<(String, Vec<String>)>;
+type Backup = BTreeMap<String, (Vec<String>, Vec<String>)>;
+
+// Where /etc/resolv.conf belongs to systemd-resolved, anything written into it is soon written
+// over, so the servers are changed in resolved itself, link by link. Every link with servers is
+// pointed at 127.0.0.1 and given every domain, so that no query can go anywhere else.
+pub struct ResolvedDnsModifier {
+    runner: Box<dyn CommandRunner>,
+    backup_path: PathBuf,
+}
+
+impl DnsModifier for ResolvedDnsModifier {
+    fn type_name(&self) -> &'static str {
+        "ResolvedDnsModifier"
+    }
+
+    fn subvert(&self) -> Result<(), String> {
+        let links = self.links("dns")?;
+        let unsubverted: LinkValues = links
+            .iter()
+            .filter(|(_, servers)| !servers.is_empty() && !is_subverted(servers))
+            .cloned()
+            .collect();
+        if unsubverted.is_empty() {
+            return if links.iter().any(|(_, servers)| is_subverted(servers)) {
+                Ok(())
+            } else {
+                Err(String::from(
+                    "This system does not appear to be connected to a network",
+                ))
+            };
+        }
+        let domains: BTreeMap<String, Vec<String>> = self.links("domain")?.into_iter().collect();
+        let mut backup = self.read_backup();
+        unsubverted.iter().for_each(|(index, servers)| {
+            let link_domains = domains.get(index).cloned().unwrap_or_default();
+            backup.insert(index.clone(), (servers.clone(), link_domains));
+        });
+        self.write_backup(&backup)?;
+        unsubverted.iter().try_for_each(|(index, _)| {
+            self.resolvectl(&["dns", index, SUBVERTED_SERVER])?;
+            self.resolvectl(&["domain", index, EVERY_DOMAIN])
+        })
+    }
+
+    fn revert(&self) -> Result<(), String> {
+        let subverted: Vec<String> = self
+            .links("dns")?
+            .into_iter()
+            .filter(|(_, servers)| is_subverted(servers))
+            .map(|(index, _)| index)
+            .collect();
+        if subverted.is_empty() {
+            return Ok(());
+        }
+        let backup = self.read_backup();
+        subverted
+            .iter()
+            .try_for_each(|index| match backup.get(index) {
+                Some((servers, domains)) => {
+                    self.resolvectl(&link_args("dns", index, servers))?;
+                    self.resolvectl(&link_args("domain", index, domains))
+                }
+                // Nothing to restore, but resolved can at least forget what it was told
+                None => self.resolvectl(&["revert", index]),
+            })?;
+        self.discard_backup()
+    }
+
+    fn inspect(&self, stdout: &mut (dyn io::Write + Send)) -> Result<(), String> {
+        let servers: Vec<String> = self
+            .links("dns")?
+            .into_iter()
+            .flat_map(|(_, servers)| servers)
+            .collect();
+        let ips = distinct_ips(&servers);
+        if ips.is_empty() {
+            return Err(String::from(
+                "This system does not appear to be connected to a network",
+            ));
+        }
+        ips.iter()
+            .for_each(|ip| writeln!(stdout, "{}", ip).expect("stdout doesn't work"));
+        Ok(())
+    }
+
+    fn original_servers(&self) -> Result<Vec<IpAddr>, String> {
+        let backup = self.read_backup();
+        let servers: Vec<String> = self
+            .links("dns")?
+            .into_iter()
+            .flat_map(|(index, servers)| {
+                if is_subverted(&servers) {
+                    backup
+                        .get(&index)
+                        .map(|(servers, _)| servers.clone())
+                        .unwrap_or_default()
+                } else {
+                    servers
+                }
+            })
+            .collect();
+        Ok(distinct_ips(&servers))
+    }
+}
+
+impl Default for ResolvedDnsModifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ResolvedDnsModifier {
+    pub fn new() -> ResolvedDnsModifier {
+        ResolvedDnsModifier {
+            runner: Box::new(CommandRunnerReal::new()),
+            backup_path: PathBuf::from(BACKUP_PATH),
+        }
+    }
+
+    // systemd-resolved owns /etc/resolv.conf when it's a link to one of resolved's own files
+    pub fn is_in_charge() -> bool {
+        fs::read_link("/etc/resolv.conf")
+            .map(|target| target.to_string_lossy().contains("run/systemd/resolve/"))
+            .unwrap_or(false)
+            && CommandRunnerReal::new().run(RESOLVECTL, &["dns"]).is_ok()
+    }
+
+    fn resolvectl(&self, args: &[&str]) -> Result<(), String> {
+        self.runner.run(RESOLVECTL, args).map(|_| ())
+    }
+
+    // For each link, what `resolvectl dns` or `resolvectl domain` lists for it
+    fn links(&self, verb: &str) -> Result<LinkValues, String> {
+        let output = self.runner.run(RESOLVECTL, &[verb])?;
+        let regex = Regex::new(r"^Link (\d+) \([^)]*\):(.*)$").expect("Regex syntax error");
+        Ok(output
+            .lines()
+            .filter_map(|line| regex.captures(line.trim()))
+            .map(|captures| {
+                (
+                    captures[1].to_string(),
+                    captures[2]
+                        .split_whitespace()
+                        .map(|value| value.to_string())
+                        .collect(),
+                )
+            })
+            .collect())
+    }
+
+    fn read_backup(&self) -> Backup {
+        fs::read_to_string(&self.backup_path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split('\t');
+                let index = fields.next()?.to_string();
+                let servers = split_values(fields.next()?);
+                let domains = split_values(fields.next().unwrap_or(""));
+                Some((index, (servers, domains)))
+            })
+            .collect()
+    }
+
+    fn write_backup(&self, backup: &Backup) -> Result<(), String> {
+        let complain = |e: io::Error| {
+            format!(
+                "Couldn't save systemd-resolved's DNS settings in {:?}: {}",
+                self.backup_path, e
+            )
+        };
+        if let Some(directory) = self.backup_path.parent() {
+            fs::create_dir_all(directory).map_err(complain)?;
+        }
+        let contents = backup
+            .iter()
+            .map(|(index, (servers, domains))| {
+                format!("{}\t{}\t{}\n", index, servers.join(" "), domains.join(" "))
+            })
+            .collect::<String>();
+        fs::write(&self.backup_path, contents).map_err(complain)
+    }
+
+    fn discard_backup(&self) -> Result<(), String> {
+        match fs::remove_file(&self.backup_path) {
+            Ok(()) => Ok(()),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!(
+                "Couldn't remove saved DNS settings at {:?}: {}",
+                self.backup_path, e
+            )),
+        }
+    }
+}
+
+fn is_subverted(servers: &[String]) -> bool {
+    servers == [SUBVERTED_SERVER]
+}
+
+fn split_values(field: &str) -> Vec<String> {
+    field
+        .split_whitespace()
+        .map(|value| value.to_string())
+        .collect()
+}
+
+// An empty list of values clears the setting, which resolvectl wants said with an empty argument
+fn link_args<'a>(verb: &'a str, index: &'a str, values: &'a [String]) -> Vec<&'a str> {
+    let mut args = vec![verb, index];
+    if values.is_empty() {
+        args.push("");
+    } else {
+        args.extend(values.iter().map(|value| value.as_str()));
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command_runner::tests_utils::CommandRunnerMock;
+    use crate::utils::get_parameters_from;
+    use std::env;
+    use std::path::Path;
+    use std::str::FromStr;
+
+    const DNS_BEFORE: &str = "Global:\nLink 2 (enp0s3): 192.168.0.1 2001:db8::1%2\nLink 3 (wlan0): 8.8.8.8#dns.google\nLink 4 (docker0):\n";
+    const DOMAINS_BEFORE: &str =
+        "Global:\nLink 2 (enp0s3): home.lan\nLink 3 (wlan0):\nLink 4 (docker0):\n";
+    const DNS_AFTER: &str =
+        "Global:\nLink 2 (enp0s3): 127.0.0.1\nLink 3 (wlan0): 127.0.0.1\nLink 4 (docker0):\n";
+
+    fn make_backup_path(test_name: &str) -> PathBuf {
+        let directory = env::current_dir()
+            .unwrap()
+            .join(Path::new("generated"))
+            .join(Path::new("ResolvedDnsModifier"))
+            .join(Path::new(test_name));
+        let _ = fs::remove_dir_all(&directory);
+        directory.join("resolved-links")
+    }
+
+    fn make_subject(runner: CommandRunnerMock, test_name: &str) -> ResolvedDnsModifier {
+        ResolvedDnsModifier {
+            runner: Box::new(runner),
+            backup_path: make_backup_path(test_name),
+        }
+    }
+
+    #[test]
+    fn subvert_points_every_link_with_servers_at_localhost_for_every_domain() {
+        let runner = CommandRunnerMock::new()
+            .run_result(Ok(DNS_BEFORE))
+            .run_result(Ok(DOMAINS_BEFORE))
+            .run_result(Ok(""))
+            .run_result(Ok(""))
+            .run_result(Ok(""))
+            .run_result(Ok(""));
+        let run_parameters = runner.run_parameters.clone();
+        let subject = make_subject(
+            runner,
+            "subvert_points_every_link_with_servers_at_localhost_for_every_domain",
+        );
+
+        let result = subject.subvert();
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            get_parameters_from(run_parameters),
+            vec![
+                "resolvectl dns",
+                "resolvectl domain",
+                "resolvectl dns 2 127.0.0.1",
+                "resolvectl domain 2 ~.",
+                "resolvectl dns 3 127.0.0.1",
+                "resolvectl domain 3 ~.",
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(&subject.backup_path).unwrap(),
+            "2\t192.168.0.1 2001:db8::1%2\thome.lan\n3\t8.8.8.8#dns.google\t\n"
+        );
+    }
+
+    #[test]
+    fn subvert_leaves_subverted_dns_alone() {
+        let runner = CommandRunnerMock::new().run_result(Ok(DNS_AFTER));
+        let subject = make_subject(runner, "subvert_leaves_subverted_dns_alone");
+
+        let result = subject.subvert();
+
+        assert_eq!(result, Ok(()));
+        assert!(!subject.backup_path.exists());
+    }
+
+    #[test]
+    fn subvert_complains_when_no_link_has_servers() {
+        let runner = CommandRunnerMock::new().run_result(Ok("Global:\nLink 4 (docker0):\n"));
+        let subject = make_subject(runner, "subvert_complains_when_no_link_has_servers");
+
+        let result = subject.subvert();
+
+        assert_eq!(
+            result,
+            Err("This system does not appear to be connected to a network".to_string())
+        );
+    }
+
+    #[test]
+    fn revert_restores_each_link_from_the_backup() {
+        let runner = CommandRunnerMock::new()
+            .run_result(Ok(DNS_AFTER))
+            .run_result(Ok(""))
+            .run_result(Ok(""))
+            .run_result(Ok(""));
+        let run_parameters = runner.run_parameters.clone();
+        let subject = make_subject(runner, "revert_restores_each_link_from_the_backup");
+        let mut backup = Backup::new();
+        backup.insert(
+            "2".to_string(),
+            (
+                vec!["192.168.0.1".to_string()],
+                vec!["home.lan".to_string()],
+            ),
+        );
+        subject.write_backup(&backup).unwrap();
+
+        let result = subject.revert();
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            get_parameters_from(run_parameters),
+            vec![
+                "resolvectl dns",
+                "resolvectl dns 2 192.168.0.1",
+                "resolvectl domain 2 home.lan",
+                "resolvectl revert 3",
+            ]
+        );
+        assert!(!subject.backup_path.exists());
+    }
+
+    #[test]
+    fn revert_leaves_unsubverted_dns_alone() {
+        let runner = CommandRunnerMock::new().run_result(Ok(DNS_BEFORE));
+        let run_parameters = runner.run_parameters.clone();
+        let subject = make_subject(runner, "revert_leaves_unsubverted_dns_alone");
+
+        let result = subject.revert();
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(get_parameters_from(run_parameters), vec!["resolvectl dns"]);
+    }
+
+    #[test]
+    fn revert_passes_on_what_resolvectl_says_is_wrong() {
+        let runner = CommandRunnerMock::new()
+            .run_result(Ok(DNS_AFTER))
+            .run_result(Err(
+            "resolvectl revert 2 failed: Failed to revert interface configuration: Access denied",
+        ));
+        let subject = make_subject(runner, "revert_passes_on_what_resolvectl_says_is_wrong");
+
+        let result = subject.revert();
+
+        assert_eq!(
+            result,
+            Err("resolvectl revert 2 failed: Failed to revert interface configuration: Access denied".to_string())
+        );
+    }
+
+    #[test]
+    fn inspect_lists_each_server_once() {
+        let runner = CommandRunnerMock::new().run_result(Ok(DNS_AFTER));
+        let subject = make_subject(runner, "inspect_lists_each_server_once");
+        let mut stdout: Vec<u8> = vec![];
+
+        let result = subject.inspect(&mut stdout);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(String::from_utf8(stdout).unwrap(), "127.0.0.1\n");
+    }
+
+    #[test]
+    fn original_servers_come_from_the_backup_for_subverted_links() {
+        let runner = CommandRunnerMock::new().run_result(Ok(
+            "Link 2 (enp0s3): 127.0.0.1\nLink 3 (wlan0): 8.8.8.8#dns.google\n",
+        ));
+        let subject = make_subject(
+            runner,
+            "original_servers_come_from_the_backup_for_subverted_links",
+        );
+        let mut backup = Backup::new();
+        backup.insert(
+            "2".to_string(),
+            (
+                vec!["192.168.0.1".to_string(), "2001:db8::1%2".to_string()],
+                vec![],
+            ),
+        );
+        subject.write_backup(&backup).unwrap();
+
+        let result = subject.original_servers();
+
+        assert_eq!(
+            result,
+            Ok(vec![
+                IpAddr::from_str("192.168.0.1").unwrap(),
+                IpAddr::from_str("2001:db8::1").unwrap(),
+                IpAddr::from_str("8.8.8.8").unwrap(),
+            ])
+        );
+    }
+}
@@ -0,0 +1,158 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+const RECORD_FILE: &str = "subversion";
+const SERVERS_KEY: &str = "original_servers=";
+
+// Written before DNS is subverted and removed once it's reverted, so that if whatever was answering
+// on 127.0.0.1 dies without reverting, there's a record that DNS was subverted and what it used
+// before, whether or not the DNS settings themselves still say.
+pub struct SubversionRecord {
+    directory: PathBuf,
+}
+
+impl Default for SubversionRecord {
+    fn default() -> Self {
+        SubversionRecord {
+            directory: default_directory(),
+        }
+    }
+}
+
+impl SubversionRecord {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn in_directory(directory: PathBuf) -> Self {
+        SubversionRecord { directory }
+    }
+
+    // Written to one side and renamed into place, so that a crash leaves either the old record or
+    // the new one, never half of one
+    pub fn write(&self, original_servers: &[IpAddr]) -> Result<(), String> {
+        let complain = |e: io::Error| {
+            format!(
+                "Couldn't record DNS settings in {:?}: {}",
+                self.directory, e
+            )
+        };
+        fs::create_dir_all(&self.directory).map_err(complain)?;
+        let temporary_path = self.path().with_extension("new");
+        let mut file = File::create(&temporary_path).map_err(complain)?;
+        write!(
+            file,
+            "# DNS is subverted; dns_utility revert or dns_utility repair undoes it\n{}{}\n",
+            SERVERS_KEY,
+            original_servers
+                .iter()
+                .map(|ip| ip.to_string())
+                .collect::<Vec<String>>()
+                .join(",")
+        )
+        .map_err(complain)?;
+        file.sync_all().map_err(complain)?;
+        fs::rename(&temporary_path, self.path()).map_err(complain)
+    }
+
+    // None if DNS isn't known to have been subverted
+    pub fn original_servers(&self) -> Option<Vec<IpAddr>> {
+        let contents = fs::read_to_string(self.path()).ok()?;
+        let servers = contents
+            .lines()
+            .find(|line| line.starts_with(SERVERS_KEY))?
+            .trim_start_matches(SERVERS_KEY)
+            .split(',')
+            .filter_map(|ip| IpAddr::from_str(ip.trim()).ok())
+            .collect();
+        Some(servers)
+    }
+
+    pub fn forget(&self) -> Result<(), String> {
+        match fs::remove_file(self.path()) {
+            Ok(()) => Ok(()),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!(
+                "Couldn't remove the record of subverted DNS at {:?}: {}",
+                self.path(),
+                e
+            )),
+        }
+    }
+
+    fn path(&self) -> PathBuf {
+        self.directory.join(RECORD_FILE)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn default_directory() -> PathBuf {
+    PathBuf::from("/var/lib/dns_utility")
+}
+
+#[cfg(target_os = "macos")]
+fn default_directory() -> PathBuf {
+    PathBuf::from("/Library/Application Support/dns_utility")
+}
+
+#[cfg(target_os = "windows")]
+fn default_directory() -> PathBuf {
+    let program_data =
+        std::env::var("ProgramData").unwrap_or_else(|_| "C:\\ProgramData".to_string());
+    PathBuf::from(program_data).join("dns_utility")
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::env;
+    use std::path::Path;
+
+    pub fn make_record_directory(test_name: &str) -> PathBuf {
+        let directory = env::current_dir()
+            .unwrap()
+            .join(Path::new("generated"))
+            .join(Path::new("SubversionRecord"))
+            .join(Path::new(test_name));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn a_record_remembers_the_original_servers_until_it_is_forgotten() {
+        let subject = SubversionRecord::in_directory(make_record_directory(
+            "a_record_remembers_the_original_servers_until_it_is_forgotten",
+        ));
+        let servers = vec![
+            IpAddr::from_str("192.168.0.1").unwrap(),
+            IpAddr::from_str("2001:4860:4860::8888").unwrap(),
+        ];
+        assert_eq!(subject.original_servers(), None);
+
+        subject.write(&servers).unwrap();
+
+        assert_eq!(subject.original_servers(), Some(servers));
+        assert_eq!(subject.path().with_extension("new").exists(), false);
+        subject.forget().unwrap();
+        assert_eq!(subject.original_servers(), None);
+        assert_eq!(subject.forget(), Ok(()));
+    }
+
+    #[test]
+    fn a_record_is_made_even_with_no_original_servers() {
+        let subject = SubversionRecord::in_directory(
+            make_record_directory("a_record_is_made_even_with_no_original_servers").join("sub"),
+        );
+
+        subject.write(&[]).unwrap();
+
+        assert_eq!(subject.original_servers(), Some(vec![]));
+    }
+}
@@ -11,3 +11,23 @@ where
     let parameters_ref: &Vec<T> = parameters_guard.as_ref();
     parameters_ref.clone()
 }
+
+// The distinct addresses among DNS servers as the system lists them, which may name the server
+// after a '#' or its interface after a '%'
+#[cfg(target_os = "linux")]
+pub fn distinct_ips(servers: &[String]) -> Vec<std::net::IpAddr> {
+    servers
+        .iter()
+        .filter_map(|server| {
+            server
+                .split(&['#', '%'][..])
+                .next()
+                .and_then(|address| address.parse::<std::net::IpAddr>().ok())
+        })
+        .fold(vec![], |mut ips, ip| {
+            if !ips.contains(&ip) {
+                ips.push(ip);
+            }
+            ips
+        })
+}
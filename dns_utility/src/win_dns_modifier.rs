@@ -6,6 +6,7 @@ use crate::netsh::{Netsh, NetshCommand, NetshError};
 use std::collections::HashSet;
 use std::fmt::Debug;
 use std::io;
+use std::net::IpAddr;
 use winreg::enums::*;
 use winreg::RegKey;
 
@@ -17,6 +18,8 @@ pub struct WinDnsModifier {
     hive: Box<dyn RegKeyTrait>,
     ipconfig: Box<dyn IpconfigWrapper>,
     netsh: Box<dyn Netsh>,
+    // Friendly names of the adapters to change, such as "Wi-Fi"; every adapter if there are none
+    interfaces: Vec<String>,
 }
 
 impl DnsModifier for WinDnsModifier {
@@ -90,6 +93,21 @@ impl DnsModifier for WinDnsModifier {
         write!(stdout, "{}", output).expect("write is broken");
         Ok(())
     }
+
+    fn original_servers(&self) -> Result<Vec<IpAddr>, String> {
+        let interfaces = self.find_interfaces_to_inspect()?;
+        let dns_server_list = self.find_original_dns_server_list(interfaces)?;
+        // NameServer is separated by commas, DhcpNameServer by spaces
+        Ok(dns_server_list
+            .split(|c| c == ',' || c == ' ')
+            .flat_map(|dns_server| dns_server.parse::<IpAddr>())
+            .collect())
+    }
+
+    fn select_interfaces(&mut self, interfaces: &[String]) -> Result<(), String> {
+        self.interfaces = interfaces.to_vec();
+        Ok(())
+    }
 }
 
 impl Default for WinDnsModifier {
@@ -101,6 +119,7 @@ impl Default for WinDnsModifier {
             )),
             ipconfig: Box::new(IpconfigWrapperReal {}),
             netsh: Box::new(NetshCommand {}),
+            interfaces: vec![],
         }
     }
 }
@@ -115,6 +134,7 @@ impl WinDnsModifier {
     }
 
     fn find_interfaces_to_revert(&self) -> Result<Vec<Box<dyn RegKeyTrait>>, String> {
+        let selected_opt = self.find_selected_adapter_names()?;
         let interface_key = self.handle_reg_error(
             false,
             self.hive.open_subkey_with_flags(
@@ -130,6 +150,7 @@ impl WinDnsModifier {
                 interface_key.open_subkey_with_flags(&interface_name[..], KEY_ALL_ACCESS)
             })
             .filter(|interface| interface.get_value("NameServerBak").is_ok())
+            .filter(|interface| Self::is_selected(&selected_opt, interface.as_ref()))
             .collect();
         Ok(revertible_interfaces)
     }
@@ -139,6 +160,7 @@ impl WinDnsModifier {
     }
 
     fn find_interfaces(&self, access_required: u32) -> Result<Vec<Box<dyn RegKeyTrait>>, String> {
+        let selected_opt = self.find_selected_adapter_names()?;
         let interface_key = self.handle_reg_error(
             access_required == KEY_READ,
             self.hive.open_subkey_with_flags(
@@ -153,6 +175,7 @@ impl WinDnsModifier {
             .flat_map(|interface_name| {
                 interface_key.open_subkey_with_flags(&interface_name[..], access_required)
             })
+            .filter(|interface| Self::is_selected(&selected_opt, interface.as_ref()))
             .filter(|interface| {
                 WinDnsModifier::get_default_gateway(interface.as_ref()).is_some()
                     && interface.get_value("NameServer").is_ok()
@@ -178,15 +201,70 @@ impl WinDnsModifier {
         }
     }
 
+    // The registry knows interfaces by adapter name, a GUID, and people know them by friendly name.
+    // None unless some interfaces were selected.
+    fn find_selected_adapter_names(&self) -> Result<Option<HashSet<String>>, String> {
+        if self.interfaces.is_empty() {
+            return Ok(None);
+        }
+        let adapters = self
+            .ipconfig
+            .get_adapters()
+            .map_err(|e| format!("Couldn't list network interfaces: {:?}", e))?;
+        let mut adapter_names = HashSet::new();
+        for interface in &self.interfaces {
+            match adapters
+                .iter()
+                .find(|adapter| adapter.friendly_name().to_lowercase() == interface.to_lowercase())
+            {
+                Some(adapter) => adapter_names.insert(adapter.adapter_name().to_lowercase()),
+                None => {
+                    return Err(format!(
+                        "This system has no network interface named {}",
+                        interface
+                    ))
+                }
+            };
+        }
+        Ok(Some(adapter_names))
+    }
+
+    fn is_selected(selected_opt: &Option<HashSet<String>>, interface: &dyn RegKeyTrait) -> bool {
+        match selected_opt {
+            None => true,
+            Some(adapter_names) => adapter_names.contains(&interface.path().to_lowercase()),
+        }
+    }
+
     pub fn find_dns_server_list(
         &self,
         interfaces: Vec<Box<dyn RegKeyTrait>>,
     ) -> Result<String, String> {
+        self.summarize_dns_server_lists(interfaces, |interface| {
+            self.find_dns_servers_for_interface(interface)
+        })
+    }
+
+    pub fn find_original_dns_server_list(
+        &self,
+        interfaces: Vec<Box<dyn RegKeyTrait>>,
+    ) -> Result<String, String> {
+        self.summarize_dns_server_lists(interfaces, |interface| {
+            self.find_original_dns_servers_for_interface(interface)
+        })
+    }
+
+    fn summarize_dns_server_lists<F>(
+        &self,
+        interfaces: Vec<Box<dyn RegKeyTrait>>,
+        find_dns_servers: F,
+    ) -> Result<String, String>
+    where
+        F: Fn(Box<dyn RegKeyTrait>) -> Result<String, String>,
+    {
         let interfaces_len = interfaces.len();
-        let list_result_vec: Vec<Result<String, String>> = interfaces
-            .into_iter()
-            .map(|interface| self.find_dns_servers_for_interface(interface))
-            .collect();
+        let list_result_vec: Vec<Result<String, String>> =
+            interfaces.into_iter().map(find_dns_servers).collect();
         let errors: Vec<String> = list_result_vec
             .iter()
             .flat_map(|result_ref| match *result_ref {
@@ -234,6 +312,24 @@ impl WinDnsModifier {
         }
     }
 
+    // A subverted interface keeps what NameServer used to be in NameServerBak; if that was blank,
+    // the interface was getting its DNS servers from DHCP.
+    fn find_original_dns_servers_for_interface(
+        &self,
+        interface: Box<dyn RegKeyTrait>,
+    ) -> Result<String, String> {
+        match interface.get_value("NameServerBak") {
+            Err(_) => self.find_dns_servers_for_interface(interface),
+            Ok(ref backup) if backup == &String::new() => {
+                interface.get_value("DhcpNameServer").map_err(|_| {
+                    "Interface has neither NameServerBak nor DhcpNameServer; probably not connected"
+                        .to_string()
+                })
+            }
+            Ok(backup) => Ok(backup),
+        }
+    }
+
     fn subvert_interface(&self, interface: &dyn RegKeyTrait) -> Result<(), String> {
         let name_servers = interface
             .get_value("NameServer")
@@ -1152,6 +1248,79 @@ mod tests {
         );
     }
 
+    #[test]
+    fn subvert_changes_only_the_selected_interfaces() {
+        let wifi_set_value_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let wifi_interface = RegKeyMock::new("wifi_interface")
+            .get_value_result("DefaultGateway", Ok("192.168.0.1".to_string()))
+            .get_value_result(
+                "DhcpDefaultGateway",
+                Err(Error::from_raw_os_error(NOT_FOUND)),
+            )
+            .get_value_result("NameServer", Ok("8.8.8.8".to_string()))
+            .set_value_parameters(&wifi_set_value_parameters_arc)
+            .set_value_result("NameServerBak", Ok(()));
+        let vpn_set_value_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let vpn_interface = RegKeyMock::new("vpn_interface")
+            .get_value_result("DefaultGateway", Ok("10.0.0.1".to_string()))
+            .get_value_result(
+                "DhcpDefaultGateway",
+                Err(Error::from_raw_os_error(NOT_FOUND)),
+            )
+            .get_value_result("NameServer", Ok("10.0.0.53".to_string()))
+            .set_value_parameters(&vpn_set_value_parameters_arc);
+        let interfaces = RegKeyMock::default()
+            .enum_keys_result(vec![Ok("wifi_interface"), Ok("vpn_interface")])
+            .open_subkey_with_flags_result(Ok(Box::new(wifi_interface)))
+            .open_subkey_with_flags_result(Ok(Box::new(vpn_interface)));
+        let hive = RegKeyMock::default().open_subkey_with_flags_result(Ok(Box::new(interfaces)));
+        let netsh = NetshMock::new().set_nameserver_result(Ok(()));
+        let netsh_params_arc = netsh.set_nameserver_parameters.clone();
+        let adapters = [
+            ("wifi_interface", "Wi-Fi"),
+            ("vpn_interface", "Corporate VPN"),
+        ];
+        let ipconfig = IpconfigWrapperMock::new()
+            .get_adapters_result(build_adapter_stubs(&adapters))
+            .get_adapters_result(build_adapter_stubs(&adapters));
+        let mut subject = WinDnsModifier::default();
+        subject.hive = Box::new(hive);
+        subject.netsh = Box::new(netsh);
+        subject.ipconfig = Box::new(ipconfig);
+        subject.select_interfaces(&["wi-fi".to_string()]).unwrap();
+
+        let result = subject.subvert();
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            get_parameters_from(wifi_set_value_parameters_arc),
+            vec![("NameServerBak".to_string(), "8.8.8.8".to_string())]
+        );
+        assert_eq!(get_parameters_from(vpn_set_value_parameters_arc).len(), 0);
+        assert_eq!(
+            get_parameters_from(netsh_params_arc),
+            vec![("Wi-Fi".to_string(), "127.0.0.1".to_string())]
+        );
+    }
+
+    #[test]
+    fn subvert_complains_about_selected_interfaces_that_dont_exist() {
+        let ipconfig = IpconfigWrapperMock::new()
+            .get_adapters_result(build_adapter_stubs(&[("wifi_interface", "Wi-Fi")]));
+        let mut subject = WinDnsModifier::default();
+        subject.ipconfig = Box::new(ipconfig);
+        subject
+            .select_interfaces(&["Wi-Fi".to_string(), "Corporate VPN".to_string()])
+            .unwrap();
+
+        let result = subject.subvert();
+
+        assert_eq!(
+            result,
+            Err("This system has no network interface named Corporate VPN".to_string())
+        );
+    }
+
     #[test]
     fn subvert_fails_if_no_nameserver_value_exists() {
         let get_value_parameters_arc = Arc::new(Mutex::new(vec![]));
@@ -1742,6 +1911,55 @@ mod tests {
         );
     }
 
+    #[test]
+    fn original_servers_come_from_the_backup_if_dns_is_subverted() {
+        let interface = RegKeyMock::default()
+            .get_value_result("DefaultGateway", Ok("Common Gateway IP".to_string()))
+            .get_value_result(
+                "DhcpDefaultGateway",
+                Err(Error::from_raw_os_error(NOT_FOUND)),
+            )
+            .get_value_result("NameServer", Ok("127.0.0.1".to_string()))
+            .get_value_result("NameServerBak", Ok("8.8.8.8,8.8.8.9".to_string()))
+            .get_value_result("DhcpNameServer", Ok("ignored".to_string()));
+        let interfaces = RegKeyMock::default()
+            .enum_keys_result(vec![Ok("interface")])
+            .open_subkey_with_flags_result(Ok(Box::new(interface)));
+        let hive = RegKeyMock::default().open_subkey_with_flags_result(Ok(Box::new(interfaces)));
+        let mut subject = WinDnsModifier::default();
+        subject.hive = Box::new(hive);
+
+        let result = subject.original_servers();
+
+        assert_eq!(
+            result,
+            Ok(vec![IpAddr::from([8, 8, 8, 8]), IpAddr::from([8, 8, 8, 9])])
+        );
+    }
+
+    #[test]
+    fn original_servers_come_from_dhcp_if_dns_was_subverted_with_no_name_server() {
+        let interface = RegKeyMock::default()
+            .get_value_result("DefaultGateway", Err(Error::from_raw_os_error(NOT_FOUND)))
+            .get_value_result("DhcpDefaultGateway", Ok("Common Gateway IP".to_string()))
+            .get_value_result("NameServer", Ok("127.0.0.1".to_string()))
+            .get_value_result("NameServerBak", Ok("".to_string()))
+            .get_value_result("DhcpNameServer", Ok("8.8.8.8 8.8.8.9".to_string()));
+        let interfaces = RegKeyMock::default()
+            .enum_keys_result(vec![Ok("interface")])
+            .open_subkey_with_flags_result(Ok(Box::new(interface)));
+        let hive = RegKeyMock::default().open_subkey_with_flags_result(Ok(Box::new(interfaces)));
+        let mut subject = WinDnsModifier::default();
+        subject.hive = Box::new(hive);
+
+        let result = subject.original_servers();
+
+        assert_eq!(
+            result,
+            Ok(vec![IpAddr::from([8, 8, 8, 8]), IpAddr::from([8, 8, 8, 9])])
+        );
+    }
+
     fn build_adapter_stubs(
         names: &[(&str, &str)],
     ) -> Result<Vec<Box<dyn AdapterWrapper>>, ipconfig::error::Error> {
@@ -1,6 +1,7 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 #![cfg(target_os = "linux")]
 
+use dns_utility_lib::dns_modifier_factory::{DnsModifierFactory, DnsModifierFactoryReal};
 use dns_utility_lib::resolv_conf_dns_modifier::ResolvConfDnsModifier;
 use std::fs::File;
 use std::io;
@@ -16,6 +17,14 @@ pub fn get_nameserver_entries(contents: &str) -> Vec<String> {
     active_nameservers
 }
 
+// Where NetworkManager or systemd-resolved owns resolv.conf, dns_utility leaves the file alone
+pub fn resolv_conf_is_in_charge() -> bool {
+    DnsModifierFactoryReal::new()
+        .make()
+        .map(|modifier| modifier.type_name() == "ResolvConfDnsModifier")
+        .unwrap_or(false)
+}
+
 pub fn get_file_contents() -> io::Result<String> {
     let path = Path::new("/")
         .join(Path::new("etc"))
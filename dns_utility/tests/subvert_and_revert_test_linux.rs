@@ -7,14 +7,15 @@ mod utils;
 use crate::linux_utils::get_file_contents;
 use crate::linux_utils::get_nameserver_entries;
 use crate::linux_utils::is_subverted;
+use crate::linux_utils::resolv_conf_is_in_charge;
 use crate::utils::TestCommand;
 
 #[test]
 // Any integration tests that should be run as root should have names ending in '_sudo_integration'
 fn resolv_conf_subvert_and_revert_sudo_integration() {
     let file_contents = match get_file_contents() {
-        Ok(s) => s,
-        Err(_) => {
+        Ok(ref s) if resolv_conf_is_in_charge() => s.clone(),
+        _ => {
             println!("---INTEGRATION TEST CANNOT YET RUN IN THIS ENVIRONMENT---");
             return;
         }
@@ -0,0 +1,472 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::prometheus_node::{PrometheusNode, PrometheusNodeUtils};
+use crate::prometheus_node_cluster::PrometheusNodeCluster;
+use crate::prometheus_real_node::PrometheusRealNode;
+use crate::utils;
+use node_lib::accountant::payable_dao::{PayableDao, PayableDaoReal};
+use node_lib::accountant::receivable_dao::{ReceivableDao, ReceivableDaoReal};
+use node_lib::database::db_initializer::{ConnectionWrapper, DbInitializer, DbInitializerReal};
+use node_lib::sub_lib::random::Random;
+use node_lib::test_utils::random_seeded::RandomSeeded;
+use regex::Regex;
+use std::collections::HashMap;
+use std::env;
+use std::io;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub const CHAOS_SEED_VAR: &str = "CHAOS_SEED";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VictimState {
+    Running,
+    Paused,
+    Killed,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChaosAction {
+    Kill(String),
+    Pause(String),
+    Resume(String),
+    Restart(String),
+}
+
+impl ChaosAction {
+    pub fn victim(&self) -> &str {
+        match self {
+            ChaosAction::Kill(name)
+            | ChaosAction::Pause(name)
+            | ChaosAction::Resume(name)
+            | ChaosAction::Restart(name) => name,
+        }
+    }
+}
+
+/// Kills, pauses and restarts real Nodes in a cluster at random while a test keeps traffic going.
+/// Its choices come from a seed, so a run that breaks something can be run again just the same:
+/// `seed_from_environment()` takes the seed from CHAOS_SEED, or makes one up and prints it. No
+/// more than `max_down` victims are down at once; when that many are, the next action brings one
+/// of them back.
+pub struct ChaosMonkey {
+    seed: u64,
+    random: RandomSeeded,
+    victims: Vec<String>,
+    states: HashMap<String, VictimState>,
+    max_down: usize,
+    history: Vec<ChaosAction>,
+}
+
+impl ChaosMonkey {
+    pub fn new(seed: u64, victims: Vec<String>, max_down: usize) -> ChaosMonkey {
+        if victims.is_empty() {
+            panic!("A ChaosMonkey needs at least one victim")
+        }
+        let states = victims
+            .iter()
+            .map(|name| (name.clone(), VictimState::Running))
+            .collect();
+        ChaosMonkey {
+            seed,
+            random: RandomSeeded::new(seed),
+            victims,
+            states,
+            max_down: max_down.max(1),
+            history: vec![],
+        }
+    }
+
+    pub fn seed_from_environment() -> u64 {
+        let seed = match env::var(CHAOS_SEED_VAR) {
+            Ok(value) => match value.parse::<u64>() {
+                Ok(seed) => seed,
+                Err(_) => panic!("{} must be a number, not '{}'", CHAOS_SEED_VAR, value),
+            },
+            Err(_) => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Time went backward")
+                .as_nanos() as u64,
+        };
+        println!(
+            "Chaos seed is {}; set {} to it to repeat this run",
+            seed, CHAOS_SEED_VAR
+        );
+        seed
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn history(&self) -> &Vec<ChaosAction> {
+        &self.history
+    }
+
+    pub fn state_of(&self, name: &str) -> Option<VictimState> {
+        self.states.get(name).cloned()
+    }
+
+    // Decides what to do next, without doing it
+    pub fn next_action(&mut self) -> ChaosAction {
+        let down = self
+            .victims
+            .iter()
+            .filter(|name| self.states[*name] != VictimState::Running)
+            .cloned()
+            .collect::<Vec<String>>();
+        let candidates = if down.len() >= self.max_down {
+            down
+        } else {
+            self.victims.clone()
+        };
+        let name = candidates[self.random.range(0, candidates.len() as u64) as usize].clone();
+        let (action, new_state) = match self.states[&name] {
+            VictimState::Running if self.random.range(0, 2) == 0 => {
+                (ChaosAction::Kill(name.clone()), VictimState::Killed)
+            }
+            VictimState::Running => (ChaosAction::Pause(name.clone()), VictimState::Paused),
+            VictimState::Paused => (ChaosAction::Resume(name.clone()), VictimState::Running),
+            VictimState::Killed => (ChaosAction::Restart(name.clone()), VictimState::Running),
+        };
+        self.states.insert(name, new_state);
+        self.history.push(action.clone());
+        action
+    }
+
+    pub fn act(&mut self, cluster: &PrometheusNodeCluster) -> Result<ChaosAction, String> {
+        let action = self.next_action();
+        println!("Chaos: {:?}", action);
+        Self::perform(cluster, &action)?;
+        Ok(action)
+    }
+
+    // Acts once per round, then runs the traffic for that round and gives the network a moment
+    pub fn run<F>(
+        &mut self,
+        cluster: &PrometheusNodeCluster,
+        rounds: usize,
+        interval: Duration,
+        mut traffic: F,
+    ) -> Result<(), String>
+    where
+        F: FnMut(usize),
+    {
+        for round in 0..rounds {
+            self.act(cluster)?;
+            traffic(round);
+            thread::sleep(interval);
+        }
+        Ok(())
+    }
+
+    // Brings every victim back: paused ones are resumed and killed ones restarted
+    pub fn restore(&mut self, cluster: &PrometheusNodeCluster) -> Result<(), String> {
+        let actions = self
+            .victims
+            .iter()
+            .filter_map(|name| match self.states[name] {
+                VictimState::Running => None,
+                VictimState::Paused => Some(ChaosAction::Resume(name.clone())),
+                VictimState::Killed => Some(ChaosAction::Restart(name.clone())),
+            })
+            .collect::<Vec<ChaosAction>>();
+        actions.into_iter().try_for_each(|action| {
+            println!("Chaos restoring: {:?}", action);
+            Self::perform(cluster, &action)?;
+            self.states
+                .insert(action.victim().to_string(), VictimState::Running);
+            self.history.push(action);
+            Ok(())
+        })
+    }
+
+    fn perform(cluster: &PrometheusNodeCluster, action: &ChaosAction) -> Result<(), String> {
+        let node = match cluster.get_real_node_by_name(action.victim()) {
+            Some(node) => node,
+            None => {
+                return Err(format!(
+                    "Real Node {} was not found in cluster",
+                    action.victim()
+                ))
+            }
+        };
+        match action {
+            ChaosAction::Kill(_) => node.kill_node_process(),
+            ChaosAction::Pause(_) => node.pause(),
+            ChaosAction::Resume(_) => node.resume(),
+            ChaosAction::Restart(_) => node.restart(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StreamOutcome {
+    // The server's answer, or at least the start of it
+    Served,
+    // The Node couldn't get the request through, and said so with an error page
+    ErrorPage,
+    // The Node closed or reset the connection
+    Closed,
+    Hung,
+    Garbled(String),
+}
+
+impl StreamOutcome {
+    pub fn is_clean(&self) -> bool {
+        match self {
+            StreamOutcome::Hung | StreamOutcome::Garbled(_) => false,
+            _ => true,
+        }
+    }
+
+    pub fn classify(response: Result<Vec<u8>, io::Error>) -> StreamOutcome {
+        let data = match response {
+            Ok(data) => data,
+            Err(ref e) if e.kind() == ErrorKind::TimedOut || e.kind() == ErrorKind::WouldBlock => {
+                return StreamOutcome::Hung
+            }
+            Err(_) => return StreamOutcome::Closed,
+        };
+        if data.is_empty() {
+            return StreamOutcome::Closed;
+        }
+        let text = String::from_utf8_lossy(&data).to_string();
+        let status_regex = Regex::new(r"^HTTP/1\.[01] (\d)\d\d ").unwrap();
+        match status_regex.captures(&text) {
+            Some(captures) => match &captures[1] {
+                "4" | "5" => StreamOutcome::ErrorPage,
+                _ => StreamOutcome::Served,
+            },
+            None => StreamOutcome::Garbled(text.chars().take(200).collect()),
+        }
+    }
+}
+
+// Sends one request through the Node at this address and says how it went. The originating Node
+// shouldn't be a victim: a request it never accepts tells nothing about in-flight streams.
+pub fn request_through(addr: SocketAddr, request: &[u8], timeout: Duration) -> StreamOutcome {
+    let mut stream = match TcpStream::connect_timeout(&addr, timeout) {
+        Ok(stream) => stream,
+        Err(e) => return StreamOutcome::classify(Err(e)),
+    };
+    stream
+        .set_read_timeout(Some(Duration::from_millis(250)))
+        .expect("Setting read timeout");
+    utils::send_chunk(&mut stream, request);
+    StreamOutcome::classify(utils::wait_for_chunk(&mut stream, &timeout))
+}
+
+// Chaos can cost a Node a payment record, but never make it one twice: no victim may think the
+// originating Node owes it more than the originating Node thinks it owes.
+pub fn assert_no_double_counting(
+    originating_node: &PrometheusRealNode,
+    serving_nodes: &[PrometheusRealNode],
+    chain_id: u8,
+) {
+    let consuming_wallet = originating_node
+        .consuming_wallet()
+        .expect("The originating Node has no consuming wallet");
+    let payable_dao = PayableDaoReal::new(open_database(originating_node, chain_id));
+    let problems = serving_nodes
+        .iter()
+        .filter_map(|node| {
+            let receivable_dao = ReceivableDaoReal::new(open_database(node, chain_id));
+            let receivable = receivable_dao
+                .account_status(&consuming_wallet)
+                .map(|account| account.balance)
+                .unwrap_or(0);
+            let payable = payable_dao
+                .account_status(&node.earning_wallet())
+                .map(|account| account.balance)
+                .unwrap_or(0);
+            if receivable > payable {
+                Some(format!(
+                    "{} thinks it's owed {}, but is only owed {}",
+                    node.name(),
+                    receivable,
+                    payable
+                ))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<String>>();
+    assert!(problems.is_empty(), "Double-counted: {:?}", problems);
+}
+
+// How far each Node's log has gotten, so that later checks only look at what's written after
+pub fn mark_logs(nodes: &[PrometheusRealNode]) -> HashMap<String, usize> {
+    nodes
+        .iter()
+        .map(|node| {
+            (
+                node.name().to_string(),
+                PrometheusNodeUtils::retrieve_logs(node.name()).len(),
+            )
+        })
+        .collect()
+}
+
+// Every Node has to process Gossip about all the others after its log was marked
+pub fn assert_neighborhood_converges(
+    nodes: &[PrometheusRealNode],
+    marks: &HashMap<String, usize>,
+    timeout: Duration,
+) {
+    let regex = Regex::new(r"Finished processing Gossip about (\d+) Nodes").unwrap();
+    let expected = nodes.len() - 1;
+    let deadline = Instant::now() + timeout;
+    let mut unconverged = nodes
+        .iter()
+        .map(|node| node.name().to_string())
+        .collect::<Vec<String>>();
+    while !unconverged.is_empty() {
+        unconverged.retain(|name| {
+            let log = PrometheusNodeUtils::retrieve_logs(name);
+            // A restarted Node may have started a new log
+            let mark = marks.get(name).cloned().unwrap_or(0);
+            let since = if mark <= log.len() {
+                &log[mark..]
+            } else {
+                &log[..]
+            };
+            !regex
+                .captures_iter(since)
+                .any(|captures| captures[1].parse::<usize>().unwrap_or(0) >= expected)
+        });
+        if !unconverged.is_empty() {
+            if Instant::now() >= deadline {
+                panic!(
+                    "After {:?}, these Nodes hadn't heard about all {} others: {:?}",
+                    timeout, expected, unconverged
+                );
+            }
+            thread::sleep(Duration::from_millis(500));
+        }
+    }
+}
+
+fn open_database(node: &PrometheusRealNode, chain_id: u8) -> Box<dyn ConnectionWrapper> {
+    DbInitializerReal::new()
+        .initialize(
+            &PathBuf::from(PrometheusRealNode::node_home_dir(
+                &PrometheusNodeUtils::find_project_root(),
+                &node.name().to_string(),
+            )),
+            chain_id,
+        )
+        .unwrap_or_else(|e| panic!("Couldn't open {}'s database: {:?}", node.name(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn victims() -> Vec<String> {
+        vec!["a".to_string(), "b".to_string(), "c".to_string()]
+    }
+
+    #[test]
+    fn the_same_seed_makes_the_same_mischief() {
+        let mut first = ChaosMonkey::new(42, victims(), 2);
+        let mut second = ChaosMonkey::new(42, victims(), 2);
+
+        (0..50).for_each(|_| {
+            first.next_action();
+            second.next_action();
+        });
+
+        assert_eq!(first.history(), second.history());
+        assert_eq!(first.seed(), 42);
+    }
+
+    #[test]
+    fn actions_only_make_sense_for_the_victims_state() {
+        let mut subject = ChaosMonkey::new(1234, victims(), 2);
+        let mut states: HashMap<String, VictimState> = victims()
+            .into_iter()
+            .map(|name| (name, VictimState::Running))
+            .collect();
+
+        (0..200).for_each(|_| {
+            let action = subject.next_action();
+            let (name, expected_before, after) = match &action {
+                ChaosAction::Kill(name) => (name, VictimState::Running, VictimState::Killed),
+                ChaosAction::Pause(name) => (name, VictimState::Running, VictimState::Paused),
+                ChaosAction::Resume(name) => (name, VictimState::Paused, VictimState::Running),
+                ChaosAction::Restart(name) => (name, VictimState::Killed, VictimState::Running),
+            };
+            assert_eq!(states[name], expected_before, "{:?}", action);
+            states.insert(name.clone(), after);
+            assert_eq!(subject.state_of(name), Some(after));
+            let down = states
+                .values()
+                .filter(|state| **state != VictimState::Running)
+                .count();
+            assert!(down <= 2, "{} victims down at once", down);
+        });
+    }
+
+    #[test]
+    fn every_kind_of_mischief_happens_to_every_victim() {
+        let mut subject = ChaosMonkey::new(99, victims(), 2);
+
+        (0..200).for_each(|_| {
+            subject.next_action();
+        });
+
+        let kinds = subject
+            .history()
+            .iter()
+            .map(|action| format!("{:?}", action))
+            .collect::<HashSet<String>>();
+        victims().iter().for_each(|name| {
+            vec!["Kill", "Pause", "Resume", "Restart"]
+                .into_iter()
+                .for_each(|kind| {
+                    let expected = format!("{}(\"{}\")", kind, name);
+                    assert!(kinds.contains(&expected), "Never saw {}", expected)
+                })
+        });
+    }
+
+    #[test]
+    fn outcomes_are_classified() {
+        let classify = |data: &[u8]| StreamOutcome::classify(Ok(data.to_vec()));
+
+        assert_eq!(
+            classify(b"HTTP/1.1 200 OK\r\n\r\nHello"),
+            StreamOutcome::Served
+        );
+        assert_eq!(
+            classify(b"HTTP/1.1 503 Routing Error\r\n\r\n"),
+            StreamOutcome::ErrorPage
+        );
+        assert_eq!(classify(b""), StreamOutcome::Closed);
+        assert_eq!(
+            classify(b"booga"),
+            StreamOutcome::Garbled("booga".to_string())
+        );
+        assert_eq!(
+            StreamOutcome::classify(Err(io::Error::from(ErrorKind::TimedOut))),
+            StreamOutcome::Hung
+        );
+        assert_eq!(
+            StreamOutcome::classify(Err(io::Error::from(ErrorKind::ConnectionReset))),
+            StreamOutcome::Closed
+        );
+    }
+
+    #[test]
+    fn only_hanging_and_garbage_are_unclean() {
+        assert!(StreamOutcome::Served.is_clean());
+        assert!(StreamOutcome::ErrorPage.is_clean());
+        assert!(StreamOutcome::Closed.is_clean());
+        assert!(!StreamOutcome::Hung.is_clean());
+        assert!(!StreamOutcome::Garbled("x".to_string()).is_clean());
+    }
+}
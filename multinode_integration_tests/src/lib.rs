@@ -1,10 +1,14 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
 pub mod blockchain;
+pub mod chaos;
 pub mod command;
+pub mod load_generator;
 pub mod main;
 pub mod multinode_gossip;
 pub mod neighborhood_constructor;
+pub mod network_conditions;
+pub mod node_release;
 pub mod rest_utils;
 pub mod prometheus_cores_client;
 pub mod prometheus_cores_server;
@@ -14,4 +18,5 @@ pub mod prometheus_node_client;
 pub mod prometheus_node_cluster;
 pub mod prometheus_node_server;
 pub mod prometheus_real_node;
+pub mod scenario;
 pub mod utils;
@@ -0,0 +1,511 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use native_tls::{HandshakeError, TlsConnector};
+use std::fmt;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RequestKind {
+    Http,
+    Tls,
+}
+
+// What the generator does: which requests, how many clients at once, and how long each client
+// waits between requests
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoadProfile {
+    pub host: String,
+    pub path: String,
+    pub tls_percent: u8,
+    pub starting_clients: usize,
+    pub maximum_clients: usize,
+    pub clients_per_step: usize,
+    pub step_duration: Duration,
+    pub think_time: Duration,
+    pub request_timeout: Duration,
+}
+
+impl Default for LoadProfile {
+    fn default() -> Self {
+        LoadProfile {
+            host: "example.com".to_string(),
+            path: "/".to_string(),
+            tls_percent: 0,
+            starting_clients: 1,
+            maximum_clients: 1,
+            clients_per_step: 1,
+            step_duration: Duration::from_secs(10),
+            think_time: Duration::from_millis(0),
+            request_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl LoadProfile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn host(mut self, host: &str) -> Self {
+        self.host = host.to_string();
+        self
+    }
+
+    pub fn path(mut self, path: &str) -> Self {
+        self.path = path.to_string();
+        self
+    }
+
+    pub fn tls_percent(mut self, tls_percent: u8) -> Self {
+        self.tls_percent = tls_percent.min(100);
+        self
+    }
+
+    // Starts with `starting` clients and adds `per_step` more every `step_duration`, up to `maximum`
+    pub fn ramp(mut self, starting: usize, maximum: usize, per_step: usize) -> Self {
+        self.starting_clients = starting.max(1);
+        self.maximum_clients = maximum.max(self.starting_clients);
+        self.clients_per_step = per_step.max(1);
+        self
+    }
+
+    pub fn step_duration(mut self, step_duration: Duration) -> Self {
+        self.step_duration = step_duration;
+        self
+    }
+
+    pub fn think_time(mut self, think_time: Duration) -> Self {
+        self.think_time = think_time;
+        self
+    }
+
+    pub fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    pub fn client_counts(&self) -> Vec<usize> {
+        (self.starting_clients..=self.maximum_clients)
+            .step_by(self.clients_per_step)
+            .collect()
+    }
+
+    // Spreads TLS requests evenly through each client's sequence instead of bunching them
+    pub fn kind_of(&self, request_number: usize) -> RequestKind {
+        let tls_percent = self.tls_percent as usize;
+        let position = request_number % 100;
+        if (position + 1) * tls_percent / 100 > position * tls_percent / 100 {
+            RequestKind::Tls
+        } else {
+            RequestKind::Http
+        }
+    }
+
+    pub fn request(&self) -> Vec<u8> {
+        format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            self.path, self.host
+        )
+        .into_bytes()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sample {
+    pub kind: RequestKind,
+    pub latency: Duration,
+    pub succeeded: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LatencyStats {
+    sorted: Vec<Duration>,
+}
+
+impl LatencyStats {
+    pub fn new(mut latencies: Vec<Duration>) -> Self {
+        latencies.sort();
+        LatencyStats { sorted: latencies }
+    }
+
+    pub fn count(&self) -> usize {
+        self.sorted.len()
+    }
+
+    // Nearest-rank percentile; zero if there were no samples
+    pub fn percentile(&self, percent: u8) -> Duration {
+        if self.sorted.is_empty() {
+            return Duration::from_millis(0);
+        }
+        let rank = (self.sorted.len() * percent.min(100) as usize + 99) / 100;
+        self.sorted[rank.max(1) - 1]
+    }
+
+    pub fn max(&self) -> Duration {
+        self.sorted
+            .last()
+            .cloned()
+            .unwrap_or_else(|| Duration::from_millis(0))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StepReport {
+    pub clients: usize,
+    pub elapsed: Duration,
+    pub samples: Vec<Sample>,
+}
+
+impl StepReport {
+    pub fn succeeded(&self) -> usize {
+        self.samples
+            .iter()
+            .filter(|sample| sample.succeeded)
+            .count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.samples.len() - self.succeeded()
+    }
+
+    // Successful requests per second
+    pub fn throughput(&self) -> f64 {
+        let seconds = self.elapsed.as_millis() as f64 / 1000.0;
+        if seconds == 0.0 {
+            0.0
+        } else {
+            self.succeeded() as f64 / seconds
+        }
+    }
+
+    // Only successful requests count; a timeout's latency is just the timeout
+    pub fn latencies(&self) -> LatencyStats {
+        LatencyStats::new(
+            self.samples
+                .iter()
+                .filter(|sample| sample.succeeded)
+                .map(|sample| sample.latency)
+                .collect(),
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoadReport {
+    pub steps: Vec<StepReport>,
+}
+
+impl LoadReport {
+    pub fn total_requests(&self) -> usize {
+        self.steps.iter().map(|step| step.samples.len()).sum()
+    }
+
+    pub fn failure_rate(&self) -> f64 {
+        let total = self.total_requests();
+        if total == 0 {
+            return 0.0;
+        }
+        let failed: usize = self.steps.iter().map(|step| step.failed()).sum();
+        failed as f64 / total as f64
+    }
+
+    pub fn peak_throughput(&self) -> f64 {
+        self.steps
+            .iter()
+            .map(|step| step.throughput())
+            .fold(0.0, f64::max)
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>7} {:>9} {:>6} {:>9} {:>8} {:>8} {:>8} {:>8}",
+            "clients", "requests", "failed", "req/sec", "p50 ms", "p90 ms", "p99 ms", "max ms"
+        )?;
+        for step in &self.steps {
+            let latencies = step.latencies();
+            writeln!(
+                f,
+                "{:>7} {:>9} {:>6} {:>9.2} {:>8} {:>8} {:>8} {:>8}",
+                step.clients,
+                step.samples.len(),
+                step.failed(),
+                step.throughput(),
+                latencies.percentile(50).as_millis(),
+                latencies.percentile(90).as_millis(),
+                latencies.percentile(99).as_millis(),
+                latencies.max().as_millis()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Sends the profile's requests through a Node's proxy ports. Latency is time to the first byte of
+// the response, which is what the relay path controls; a request succeeds if that response has a
+// 2xx or 3xx status.
+pub struct LoadGenerator {
+    http_addr: SocketAddr,
+    tls_addr: SocketAddr,
+    profile: LoadProfile,
+}
+
+impl LoadGenerator {
+    pub fn new(http_addr: SocketAddr, tls_addr: SocketAddr, profile: LoadProfile) -> Self {
+        LoadGenerator {
+            http_addr,
+            tls_addr,
+            profile,
+        }
+    }
+
+    pub fn run(&self) -> LoadReport {
+        LoadReport {
+            steps: self
+                .profile
+                .client_counts()
+                .into_iter()
+                .map(|clients| self.run_step(clients))
+                .collect(),
+        }
+    }
+
+    fn run_step(&self, clients: usize) -> StepReport {
+        let start = Instant::now();
+        let deadline = start + self.profile.step_duration;
+        let handles = (0..clients)
+            .map(|client| {
+                let http_addr = self.http_addr;
+                let tls_addr = self.tls_addr;
+                let profile = self.profile.clone();
+                thread::spawn(move || {
+                    Self::run_client(client, http_addr, tls_addr, &profile, deadline)
+                })
+            })
+            .collect::<Vec<thread::JoinHandle<Vec<Sample>>>>();
+        let samples = handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("Load client panicked"))
+            .collect();
+        StepReport {
+            clients,
+            elapsed: start.elapsed(),
+            samples,
+        }
+    }
+
+    fn run_client(
+        client: usize,
+        http_addr: SocketAddr,
+        tls_addr: SocketAddr,
+        profile: &LoadProfile,
+        deadline: Instant,
+    ) -> Vec<Sample> {
+        let request = profile.request();
+        let mut samples = vec![];
+        // Offset each client so they don't all start on the same kind of request
+        let mut request_number = client * 37;
+        while Instant::now() < deadline {
+            let kind = profile.kind_of(request_number);
+            let start = Instant::now();
+            let succeeded = match kind {
+                RequestKind::Http => Self::http_request(http_addr, &request, profile),
+                RequestKind::Tls => Self::tls_request(tls_addr, &request, profile),
+            }
+            .map(|response| Self::is_success(&response))
+            .unwrap_or(false);
+            samples.push(Sample {
+                kind,
+                latency: start.elapsed(),
+                succeeded,
+            });
+            request_number += 1;
+            thread::sleep(profile.think_time);
+        }
+        samples
+    }
+
+    fn http_request(
+        addr: SocketAddr,
+        request: &[u8],
+        profile: &LoadProfile,
+    ) -> Result<Vec<u8>, String> {
+        let mut stream = Self::connect(addr, profile.request_timeout)?;
+        stream.write_all(request).map_err(|e| format!("{}", e))?;
+        Self::read_first_chunk(&mut stream)
+    }
+
+    fn tls_request(
+        addr: SocketAddr,
+        request: &[u8],
+        profile: &LoadProfile,
+    ) -> Result<Vec<u8>, String> {
+        let stream = Self::connect(addr, profile.request_timeout)?;
+        let connector = TlsConnector::new().map_err(|e| format!("{}", e))?;
+        let mut tls_stream = match connector.connect(&profile.host, stream) {
+            Ok(tls_stream) => tls_stream,
+            Err(HandshakeError::WouldBlock(_)) => return Err("Handshake timed out".to_string()),
+            Err(HandshakeError::Failure(e)) => return Err(format!("{}", e)),
+        };
+        tls_stream
+            .write_all(request)
+            .map_err(|e| format!("{}", e))?;
+        let result = Self::read_first_chunk(&mut tls_stream);
+        let _ = tls_stream.shutdown(); // Can't do anything about an error here
+        result
+    }
+
+    fn connect(addr: SocketAddr, timeout: Duration) -> Result<TcpStream, String> {
+        let stream = TcpStream::connect_timeout(&addr, timeout).map_err(|e| format!("{}", e))?;
+        stream
+            .set_read_timeout(Some(timeout))
+            .map_err(|e| format!("{}", e))?;
+        Ok(stream)
+    }
+
+    fn read_first_chunk<R: Read>(stream: &mut R) -> Result<Vec<u8>, String> {
+        let mut buf = [0u8; 16384];
+        match stream.read(&mut buf) {
+            Ok(0) => Err("Closed without a response".to_string()),
+            Ok(len) => Ok(buf[..len].to_vec()),
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                Err("Timed out".to_string())
+            }
+            Err(e) => Err(format!("{}", e)),
+        }
+    }
+
+    fn is_success(response: &[u8]) -> bool {
+        // "HTTP/1.1 200"
+        response.len() >= 12
+            && response.starts_with(b"HTTP/1.")
+            && (response[9] == b'2' || response[9] == b'3')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn ramp_produces_client_counts_up_to_the_maximum() {
+        let subject = LoadProfile::new().ramp(2, 9, 3);
+
+        assert_eq!(subject.client_counts(), vec![2, 5, 8]);
+    }
+
+    #[test]
+    fn tls_requests_are_spread_through_the_mix() {
+        let subject = LoadProfile::new().tls_percent(25);
+
+        let kinds = (0..100)
+            .map(|n| subject.kind_of(n))
+            .collect::<Vec<RequestKind>>();
+
+        assert_eq!(
+            kinds
+                .iter()
+                .filter(|kind| **kind == RequestKind::Tls)
+                .count(),
+            25
+        );
+        assert_eq!(
+            &kinds[0..4],
+            &[
+                RequestKind::Http,
+                RequestKind::Http,
+                RequestKind::Http,
+                RequestKind::Tls
+            ]
+        );
+        assert_eq!(LoadProfile::new().kind_of(57), RequestKind::Http);
+        assert_eq!(
+            LoadProfile::new().tls_percent(100).kind_of(57),
+            RequestKind::Tls
+        );
+    }
+
+    #[test]
+    fn percentiles_use_nearest_rank() {
+        let subject = LatencyStats::new((1..=10).rev().map(Duration::from_millis).collect());
+
+        assert_eq!(subject.count(), 10);
+        assert_eq!(subject.percentile(0), Duration::from_millis(1));
+        assert_eq!(subject.percentile(50), Duration::from_millis(5));
+        assert_eq!(subject.percentile(90), Duration::from_millis(9));
+        assert_eq!(subject.percentile(99), Duration::from_millis(10));
+        assert_eq!(subject.max(), Duration::from_millis(10));
+        assert_eq!(
+            LatencyStats::new(vec![]).percentile(50),
+            Duration::from_millis(0)
+        );
+    }
+
+    #[test]
+    fn step_report_counts_only_successes_toward_throughput_and_latency() {
+        let sample = |millis, succeeded| Sample {
+            kind: RequestKind::Http,
+            latency: Duration::from_millis(millis),
+            succeeded,
+        };
+        let subject = StepReport {
+            clients: 2,
+            elapsed: Duration::from_secs(2),
+            samples: vec![sample(10, true), sample(5000, false), sample(30, true)],
+        };
+
+        assert_eq!(subject.succeeded(), 2);
+        assert_eq!(subject.failed(), 1);
+        assert_eq!(subject.throughput(), 1.0);
+        assert_eq!(subject.latencies().max(), Duration::from_millis(30));
+        let report = LoadReport {
+            steps: vec![subject],
+        };
+        assert_eq!(report.total_requests(), 3);
+        assert_eq!(report.failure_rate(), 1.0 / 3.0);
+    }
+
+    #[test]
+    fn only_2xx_and_3xx_responses_succeed() {
+        assert!(LoadGenerator::is_success(b"HTTP/1.1 200 OK\r\n"));
+        assert!(LoadGenerator::is_success(b"HTTP/1.0 301 Moved\r\n"));
+        assert!(!LoadGenerator::is_success(b"HTTP/1.1 503 Unavailable\r\n"));
+        assert!(!LoadGenerator::is_success(b"garbage"));
+    }
+
+    #[test]
+    fn generator_measures_http_requests_against_a_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf);
+                let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+            }
+        });
+        let subject = LoadGenerator::new(
+            addr,
+            addr,
+            LoadProfile::new()
+                .ramp(1, 2, 1)
+                .step_duration(Duration::from_millis(200))
+                .think_time(Duration::from_millis(10)),
+        );
+
+        let result = subject.run();
+
+        assert_eq!(result.steps.len(), 2);
+        assert_eq!(result.steps[0].clients, 1);
+        assert_eq!(result.steps[1].clients, 2);
+        assert!(result.total_requests() > 0);
+        assert_eq!(result.failure_rate(), 0.0);
+        assert!(result.peak_throughput() > 0.0);
+    }
+}
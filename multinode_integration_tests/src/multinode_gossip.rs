@@ -199,6 +199,7 @@ impl MultinodeGossip for Introduction {
                 GossipNodeRecord::from(self.introducer.clone()),
                 GossipNodeRecord::from(self.introducee.clone()),
             ],
+            resumption_ticket_opt: None,
         }
     }
 }
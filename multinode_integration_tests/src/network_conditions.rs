@@ -0,0 +1,331 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::command::Command;
+use node_lib::sub_lib::random::Random;
+use std::net::IpAddr;
+use std::time::Duration;
+
+// tc's prio qdisc can't have more bands than this. Band 1 is for traffic that isn't on an
+// impaired link, so a Node can have one fewer impaired links than this.
+const MAX_BANDS: usize = 16;
+
+/// What the network does to the traffic on a link between two Nodes: how long it takes to arrive,
+/// give or take the jitter, how fast it can go, and what share of it never arrives at all.
+/// `NetworkConditions::new()` is a perfect link, and setting it on a link undoes whatever was
+/// set before.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NetworkConditions {
+    pub latency: Duration,
+    pub jitter: Duration,
+    pub bandwidth_kbps_opt: Option<u32>,
+    pub loss_percent: u8,
+}
+
+impl NetworkConditions {
+    pub fn new() -> NetworkConditions {
+        NetworkConditions::default()
+    }
+
+    pub fn latency(mut self, value: Duration) -> Self {
+        self.latency = value;
+        self
+    }
+
+    pub fn jitter(mut self, value: Duration) -> Self {
+        self.jitter = value;
+        self
+    }
+
+    pub fn bandwidth_kbps(mut self, value: u32) -> Self {
+        self.bandwidth_kbps_opt = Some(value);
+        self
+    }
+
+    pub fn loss_percent(mut self, value: u8) -> Self {
+        if value > 100 {
+            panic!("Can't lose {}% of the traffic on a link", value)
+        }
+        self.loss_percent = value;
+        self
+    }
+
+    // The options for netem, which does the same to a real Node's traffic
+    pub fn netem_args(&self) -> Vec<String> {
+        let mut args = vec![];
+        if self.latency > Duration::from_millis(0) || self.jitter > Duration::from_millis(0) {
+            args.push("delay".to_string());
+            args.push(format!("{}ms", self.latency.as_millis()));
+            if self.jitter > Duration::from_millis(0) {
+                args.push(format!("{}ms", self.jitter.as_millis()));
+            }
+        }
+        if self.loss_percent > 0 {
+            args.push("loss".to_string());
+            args.push(format!("{}%", self.loss_percent));
+        }
+        if let Some(bandwidth_kbps) = self.bandwidth_kbps_opt {
+            args.push("rate".to_string());
+            args.push(format!("{}kbit", bandwidth_kbps));
+        }
+        args
+    }
+
+    // For a mock Node, which sends data itself instead of through the kernel: None if the data
+    // is lost, otherwise how long to hold it before sending it
+    pub fn disposition(&self, len: usize, random: &dyn Random) -> Option<Duration> {
+        if self.loss_percent > 0 && random.range(0, 100) < self.loss_percent as u64 {
+            return None;
+        }
+        let latency_ms = self.latency.as_millis() as u64;
+        let jitter_ms = self.jitter.as_millis() as u64;
+        let delay_ms =
+            (latency_ms + random.range(0, (jitter_ms * 2) + 1)).saturating_sub(jitter_ms);
+        let transmission_us = match self.bandwidth_kbps_opt {
+            Some(bandwidth_kbps) if bandwidth_kbps > 0 => {
+                (len as u64 * 8000) / bandwidth_kbps as u64
+            }
+            _ => 0,
+        };
+        Some(Duration::from_millis(delay_ms) + Duration::from_micros(transmission_us))
+    }
+}
+
+/// Keeps track of the impaired links out of one real Node. Each link gets its own band in a prio
+/// qdisc on the Node's container, with a filter sending traffic for the peer to it and a netem
+/// qdisc under it; everything else goes through the first band untouched.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LinkShaper {
+    peers: Vec<IpAddr>,
+}
+
+impl LinkShaper {
+    pub fn new() -> LinkShaper {
+        LinkShaper::default()
+    }
+
+    pub fn shape(
+        &mut self,
+        container_name: &str,
+        peer: IpAddr,
+        conditions: &NetworkConditions,
+    ) -> Result<(), String> {
+        Self::run_all(container_name, self.tc_commands(peer, conditions)?)?;
+        if !self.peers.contains(&peer) {
+            self.peers.push(peer);
+        }
+        Ok(())
+    }
+
+    pub fn clear(&mut self, container_name: &str) -> Result<(), String> {
+        if self.peers.is_empty() {
+            return Ok(());
+        }
+        Self::run_all(
+            container_name,
+            vec![Command::strings(vec![
+                "qdisc", "del", "dev", "eth0", "root",
+            ])],
+        )?;
+        self.peers.clear();
+        Ok(())
+    }
+
+    pub fn tc_commands(
+        &self,
+        peer: IpAddr,
+        conditions: &NetworkConditions,
+    ) -> Result<Vec<Vec<String>>, String> {
+        let mut commands = vec![];
+        if self.peers.is_empty() {
+            let mut command = Command::strings(vec![
+                "qdisc", "add", "dev", "eth0", "root", "handle", "1:", "prio", "bands", "16",
+                "priomap",
+            ]);
+            command.extend((0..16).map(|_| "0".to_string()));
+            commands.push(command);
+        }
+        let band = match self.peers.iter().position(|known| known == &peer) {
+            Some(index) => index + 2,
+            None if self.peers.len() + 2 > MAX_BANDS => {
+                return Err(format!(
+                    "Can't impair more than {} links from one Node",
+                    MAX_BANDS - 1
+                ))
+            }
+            None => self.peers.len() + 2,
+        };
+        let parent = format!("1:{:x}", band);
+        let handle = format!("{:x}0:", band);
+        let mut netem = Command::strings(vec![
+            "qdisc", "replace", "dev", "eth0", "parent", &parent, "handle", &handle, "netem",
+        ]);
+        netem.extend(conditions.netem_args());
+        commands.push(netem);
+        if !self.peers.contains(&peer) {
+            let destination = format!("{}/32", peer);
+            commands.push(Command::strings(vec![
+                "filter",
+                "add",
+                "dev",
+                "eth0",
+                "parent",
+                "1:",
+                "protocol",
+                "ip",
+                "prio",
+                "1",
+                "u32",
+                "match",
+                "ip",
+                "dst",
+                &destination,
+                "flowid",
+                &parent,
+            ]));
+        }
+        Ok(commands)
+    }
+
+    fn run_all(container_name: &str, commands: Vec<Vec<String>>) -> Result<(), String> {
+        commands.into_iter().try_for_each(|tc_args| {
+            let mut args = Command::strings(vec!["exec", container_name, "tc"]);
+            args.extend(tc_args);
+            let mut command = Command::new("docker", args);
+            command
+                .stdout_or_stderr()
+                .map(|_| ())
+                .map_err(|e| format!("Couldn't shape traffic on {}: {}", container_name, e))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use node_lib::test_utils::random_seeded::RandomSeeded;
+    use std::str::FromStr;
+
+    #[test]
+    fn a_perfect_link_has_no_netem_options_and_no_delay() {
+        let subject = NetworkConditions::new();
+
+        let args = subject.netem_args();
+        let disposition = subject.disposition(1000, &RandomSeeded::new(0));
+
+        assert!(args.is_empty());
+        assert_eq!(disposition, Some(Duration::from_millis(0)));
+    }
+
+    #[test]
+    fn netem_args_cover_every_condition() {
+        let subject = NetworkConditions::new()
+            .latency(Duration::from_millis(100))
+            .jitter(Duration::from_millis(20))
+            .bandwidth_kbps(1000)
+            .loss_percent(5);
+
+        let result = subject.netem_args();
+
+        assert_eq!(
+            result,
+            Command::strings(vec![
+                "delay", "100ms", "20ms", "loss", "5%", "rate", "1000kbit"
+            ])
+        );
+    }
+
+    #[test]
+    fn disposition_stays_within_the_jitter_and_adds_transmission_time() {
+        let subject = NetworkConditions::new()
+            .latency(Duration::from_millis(100))
+            .jitter(Duration::from_millis(20))
+            .bandwidth_kbps(8);
+        let random = RandomSeeded::new(42);
+
+        (0..100).for_each(|_| {
+            // 1000 bytes at 8 kbit/s takes a second
+            let delay = subject.disposition(1000, &random).unwrap();
+            assert!(
+                delay >= Duration::from_millis(1080) && delay <= Duration::from_millis(1120),
+                "{:?} is out of range",
+                delay
+            );
+        });
+    }
+
+    #[test]
+    fn disposition_loses_about_the_right_share() {
+        let subject = NetworkConditions::new().loss_percent(25);
+        let random = RandomSeeded::new(42);
+
+        let lost = (0..1000)
+            .filter(|_| subject.disposition(100, &random).is_none())
+            .count();
+
+        assert!(lost > 200 && lost < 300, "Lost {} of 1000", lost);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't lose 101% of the traffic on a link")]
+    fn loss_percent_cant_exceed_100() {
+        NetworkConditions::new().loss_percent(101);
+    }
+
+    #[test]
+    fn first_link_sets_up_the_prio_qdisc_and_later_ones_take_the_next_band() {
+        let peer = IpAddr::from_str("172.18.1.2").unwrap();
+        let conditions = NetworkConditions::new().latency(Duration::from_millis(50));
+        let mut subject = LinkShaper::new();
+
+        let first = subject.tc_commands(peer, &conditions).unwrap();
+        subject.peers.push(peer);
+        let again = subject.tc_commands(peer, &conditions).unwrap();
+        let second = subject
+            .tc_commands(IpAddr::from_str("172.18.1.3").unwrap(), &conditions)
+            .unwrap();
+
+        assert_eq!(first.len(), 3);
+        assert_eq!(
+            first[0].join(" "),
+            "qdisc add dev eth0 root handle 1: prio bands 16 priomap 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0"
+        );
+        assert_eq!(
+            first[1].join(" "),
+            "qdisc replace dev eth0 parent 1:2 handle 20: netem delay 50ms"
+        );
+        assert_eq!(
+            first[2].join(" "),
+            "filter add dev eth0 parent 1: protocol ip prio 1 u32 match ip dst 172.18.1.2/32 flowid 1:2"
+        );
+        assert_eq!(
+            again
+                .iter()
+                .map(|command| command.join(" "))
+                .collect::<Vec<String>>(),
+            vec!["qdisc replace dev eth0 parent 1:2 handle 20: netem delay 50ms"]
+        );
+        assert_eq!(
+            second[0].join(" "),
+            "qdisc replace dev eth0 parent 1:3 handle 30: netem delay 50ms"
+        );
+    }
+
+    #[test]
+    fn a_node_runs_out_of_bands() {
+        let mut subject = LinkShaper::new();
+        (2..=16).for_each(|i| {
+            subject
+                .peers
+                .push(IpAddr::from_str(&format!("172.18.1.{}", i)).unwrap())
+        });
+
+        let result = subject.tc_commands(
+            IpAddr::from_str("172.18.1.99").unwrap(),
+            &NetworkConditions::new(),
+        );
+
+        assert_eq!(
+            result,
+            Err("Can't impair more than 15 links from one Node".to_string())
+        );
+    }
+}
@@ -0,0 +1,169 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::command::Command;
+use crate::prometheus_node::PrometheusNodeUtils;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const PREVIOUS_RELEASE_VERSION_VAR: &str = "PREVIOUS_NODE_VERSION";
+pub const PREVIOUS_RELEASE_PATH_VAR: &str = "PREVIOUS_NODE_PATH";
+pub const PREVIOUS_RELEASE_URL_VAR: &str = "PREVIOUS_NODE_URL";
+
+const BINARY_NAME: &str = "PrometheusNode";
+
+/// A released PrometheusNode binary, to run in a cluster beside Nodes from the current build. Give
+/// it to `NodeStartupConfigBuilder::release()` and the Node is started from it instead; everything
+/// else about starting the Node is the same, so the release has to understand the same command
+/// line and log its descriptor the same way.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeRelease {
+    version: String,
+    binary_path: PathBuf,
+}
+
+impl NodeRelease {
+    pub fn from_path(version: &str, binary_path: &Path) -> Result<NodeRelease, String> {
+        if !binary_path.is_file() {
+            return Err(format!(
+                "No {} binary for version {} at {:?}",
+                BINARY_NAME, version, binary_path
+            ));
+        }
+        Ok(NodeRelease {
+            version: version.to_string(),
+            binary_path: binary_path.to_path_buf(),
+        })
+    }
+
+    // A .zip is unpacked, and must have the binary at its top level. Each version is downloaded
+    // only once.
+    pub fn download(version: &str, url: &str) -> Result<NodeRelease, String> {
+        let directory = Self::download_directory(version);
+        let binary_path = directory.join(BINARY_NAME);
+        if binary_path.is_file() {
+            return Self::from_path(version, &binary_path);
+        }
+        fs::create_dir_all(&directory)
+            .map_err(|e| format!("Couldn't create {:?}: {}", directory, e))?;
+        if url.ends_with(".zip") {
+            let archive_path = directory.join("release.zip");
+            Self::fetch(url, &archive_path)?;
+            Self::run(
+                "unzip",
+                vec![
+                    "-o",
+                    &Self::string(&archive_path),
+                    "-d",
+                    &Self::string(&directory),
+                ],
+            )?;
+        } else {
+            Self::fetch(url, &binary_path)?;
+        }
+        Self::run("chmod", vec!["755", &Self::string(&binary_path)])?;
+        Self::from_path(version, &binary_path)
+    }
+
+    // Ok(None) unless the environment names a previous release: a version, and either a path to
+    // its binary or a URL to download it from
+    pub fn from_environment() -> Result<Option<NodeRelease>, String> {
+        let version = match env::var(PREVIOUS_RELEASE_VERSION_VAR) {
+            Ok(ref version) if !version.is_empty() => version.clone(),
+            _ => return Ok(None),
+        };
+        match (
+            env::var(PREVIOUS_RELEASE_PATH_VAR),
+            env::var(PREVIOUS_RELEASE_URL_VAR),
+        ) {
+            (Ok(path), _) => Self::from_path(&version, &PathBuf::from(path)).map(Some),
+            (Err(_), Ok(url)) => Self::download(&version, &url).map(Some),
+            (Err(_), Err(_)) => Err(format!(
+                "{} is set, but neither {} nor {} is",
+                PREVIOUS_RELEASE_VERSION_VAR, PREVIOUS_RELEASE_PATH_VAR, PREVIOUS_RELEASE_URL_VAR
+            )),
+        }
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn binary_path(&self) -> &Path {
+        &self.binary_path
+    }
+
+    // Where the binary is in the container; each version has its own directory
+    pub fn container_path(&self) -> String {
+        format!("/node_root/releases/{}/{}", self.version, BINARY_NAME)
+    }
+
+    // Copies the binary into a running container. docker cp reads from the test runner's own
+    // filesystem, so this works the same whether or not the tests are running in Docker.
+    pub fn install(&self, container_name: &str) -> Result<(), String> {
+        let container_dir = format!("/node_root/releases/{}", self.version);
+        Self::run(
+            "docker",
+            vec!["exec", container_name, "mkdir", "-p", &container_dir],
+        )?;
+        Self::run(
+            "docker",
+            vec![
+                "cp",
+                &Self::string(&self.binary_path),
+                &format!("{}:{}", container_name, self.container_path()),
+            ],
+        )
+    }
+
+    fn download_directory(version: &str) -> PathBuf {
+        PathBuf::from(PrometheusNodeUtils::find_project_root())
+            .join("multinode_integration_tests")
+            .join("generated")
+            .join("releases")
+            .join(version)
+    }
+
+    fn fetch(url: &str, destination: &Path) -> Result<(), String> {
+        Self::run("curl", vec!["-fsSL", "-o", &Self::string(destination), url])
+    }
+
+    fn run(program: &str, args: Vec<&str>) -> Result<(), String> {
+        let mut command = Command::new(program, Command::strings(args));
+        command.stdout_or_stderr().map(|_| ())
+    }
+
+    fn string(path: &Path) -> String {
+        path.to_string_lossy().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_path_refuses_a_missing_binary() {
+        let result = NodeRelease::from_path("1.0.0-rc.4", &PathBuf::from("/nonexistent/booga"));
+
+        assert_eq!(
+            result,
+            Err(
+                "No PrometheusNode binary for version 1.0.0-rc.4 at \"/nonexistent/booga\""
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn each_version_gets_its_own_place_in_the_container() {
+        let binary_path = env::current_exe().unwrap();
+        let subject = NodeRelease::from_path("1.0.0-rc.4", &binary_path).unwrap();
+
+        assert_eq!(subject.version(), "1.0.0-rc.4");
+        assert_eq!(subject.binary_path(), binary_path.as_path());
+        assert_eq!(
+            subject.container_path(),
+            "/node_root/releases/1.0.0-rc.4/PrometheusNode"
+        );
+    }
+}
@@ -5,10 +5,8 @@ use node_lib::hopper::live_cores_package::LiveCoresPackage;
 use node_lib::json_masquerader::JsonMasquerader;
 use node_lib::masquerader::Masquerader;
 use node_lib::sub_lib::cryptde::CryptDE;
-use node_lib::sub_lib::cryptde::PlainData;
 use node_lib::sub_lib::cryptde::PublicKey;
 use node_lib::sub_lib::hopper::IncipientCoresPackage;
-use serde_cbor;
 use std::net::SocketAddr;
 
 pub struct PrometheusCoresClient<'a> {
@@ -32,11 +30,10 @@ impl<'a> PrometheusCoresClient<'a> {
     ) {
         let (live_cores_package, _) =
             LiveCoresPackage::from_incipient(incipient_cores_package, self.cryptde).unwrap();
-        let serialized_lcp = serde_cbor::ser::to_vec(&live_cores_package)
-            .expect(format!("Serializing LCP: {:?}", live_cores_package).as_str());
+        let serialized_lcp = live_cores_package.to_wire();
         let encoded_serialized_package = self
             .cryptde
-            .encode(&recipient_key, &PlainData::new(&serialized_lcp[..]))
+            .encode(&recipient_key, &serialized_lcp)
             .unwrap();
         let masqueraded = masquerader
             .mask(encoded_serialized_package.as_slice())
@@ -48,10 +45,9 @@ impl<'a> PrometheusCoresClient<'a> {
         live_cores_package: LiveCoresPackage,
         masquerader: &JsonMasquerader,
     ) -> Vec<u8> {
-        let serialized_lcp = serde_cbor::ser::to_vec(&live_cores_package)
-            .expect(format!("Serializing LCP: {:?}", live_cores_package).as_str());
+        let serialized_lcp = live_cores_package.to_wire();
         masquerader
-            .mask(&serialized_lcp[..])
+            .mask(serialized_lcp.as_slice())
             .expect(format!("Masquerading {}-byte serialized LCP", serialized_lcp.len()).as_str())
     }
 }
@@ -16,7 +16,6 @@ use node_lib::sub_lib::cryptde_null::CryptDENull;
 use node_lib::sub_lib::node_addr::NodeAddr;
 use node_lib::test_utils::find_free_port;
 use node_lib::tls_discriminator_factory::TlsDiscriminatorFactory;
-use serde_cbor;
 use std::cell::RefCell;
 use std::io;
 use std::io::Read;
@@ -161,7 +160,7 @@ impl PrometheusCoresServer {
             .cryptde
             .decode(&CryptData::new(&chunk.chunk[..]))
             .unwrap();
-        serde_cbor::de::from_slice::<LiveCoresPackage>(decoded_chunk.as_slice())
+        LiveCoresPackage::from_wire(decoded_chunk)
             .expect(format!("Error deserializing LCP from {:?}", chunk.chunk).as_str())
     }
 
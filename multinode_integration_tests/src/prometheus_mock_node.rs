@@ -2,6 +2,7 @@
 use crate::command::Command;
 use crate::main::CONTROL_STREAM_PORT;
 use crate::multinode_gossip::{Introduction, MultinodeGossip, SingleNode};
+use crate::network_conditions::NetworkConditions;
 use crate::prometheus_node::NodeReference;
 use crate::prometheus_node::PortSelector;
 use crate::prometheus_node::PrometheusNode;
@@ -10,23 +11,24 @@ use node_lib::hopper::live_cores_package::LiveCoresPackage;
 use node_lib::json_masquerader::JsonMasquerader;
 use node_lib::masquerader::{MasqueradeError, Masquerader};
 use node_lib::neighborhood::gossip::Gossip;
+use node_lib::sub_lib::cryptde::CryptDE;
 use node_lib::sub_lib::cryptde::CryptData;
 use node_lib::sub_lib::cryptde::PublicKey;
-use node_lib::sub_lib::cryptde::{encodex, CryptDE};
 use node_lib::sub_lib::cryptde_null::CryptDENull;
 use node_lib::sub_lib::cryptde_real::CryptDEReal;
 use node_lib::sub_lib::framer::Framer;
 use node_lib::sub_lib::hopper::{IncipientCoresPackage, MessageType};
 use node_lib::sub_lib::neighborhood::{RatePack, ZERO_RATE_PACK};
 use node_lib::sub_lib::node_addr::NodeAddr;
+use node_lib::sub_lib::random::{Random, RandomReal};
 use node_lib::sub_lib::route::Route;
 use node_lib::sub_lib::utils::indicates_dead_stream;
 use node_lib::sub_lib::wallet::Wallet;
 use node_lib::test_utils::data_hunk::DataHunk;
 use node_lib::test_utils::data_hunk_framer::DataHunkFramer;
 use node_lib::test_utils::{make_paying_wallet, make_wallet};
-use serde_cbor;
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::io;
 use std::io::{Error, ErrorKind, Read, Write};
 use std::net::Ipv4Addr;
@@ -174,6 +176,8 @@ impl PrometheusMockNode {
             cryptde_enum,
             framer,
             chain: None,
+            link_conditions: RefCell::new(HashMap::new()),
+            random: Box::new(RandomReal {}),
         });
         PrometheusMockNode {
             control_stream,
@@ -188,7 +192,34 @@ impl PrometheusMockNode {
         }
     }
 
+    // Data sent to this peer from now on is held, slowed or lost as the conditions say. A perfect
+    // link leaves it alone.
+    pub fn set_link_conditions(&self, peer: IpAddr, conditions: NetworkConditions) {
+        let mut link_conditions = self.guts.link_conditions.borrow_mut();
+        if conditions == NetworkConditions::new() {
+            link_conditions.remove(&peer);
+        } else {
+            link_conditions.insert(peer, conditions);
+        }
+    }
+
+    pub fn clear_link_conditions(&self) {
+        self.guts.link_conditions.borrow_mut().clear()
+    }
+
     pub fn transmit_data(&self, data_hunk: DataHunk) -> Result<(), io::Error> {
+        let conditions_opt = self
+            .guts
+            .link_conditions
+            .borrow()
+            .get(&data_hunk.to.ip())
+            .cloned();
+        if let Some(conditions) = conditions_opt {
+            match conditions.disposition(data_hunk.data.len(), self.guts.random.as_ref()) {
+                None => return Ok(()),
+                Some(delay) => thread::sleep(delay),
+            }
+        }
         let to_transmit: Vec<u8> = data_hunk.into();
         match self.control_stream.borrow_mut().write(&to_transmit[..]) {
             Ok(_) => Ok(()),
@@ -206,7 +237,9 @@ impl PrometheusMockNode {
     ) -> Result<(), io::Error> {
         let (lcp, _) =
             LiveCoresPackage::from_incipient(package, self.signing_cryptde().unwrap()).unwrap();
-        let encrypted_data = encodex(self.signing_cryptde().unwrap(), target_key, &lcp).unwrap();
+        let encrypted_data = lcp
+            .encode(self.signing_cryptde().unwrap(), target_key)
+            .unwrap();
         let masked_data = masquerader.mask(encrypted_data.as_slice()).unwrap();
         let data_hunk = DataHunk::new(
             SocketAddr::new(self.ip_address(), transmit_port),
@@ -341,8 +374,7 @@ impl PrometheusMockNode {
             .unwrap()
             .decode(&CryptData::new(&unmasked_chunk[..]))
             .unwrap();
-        let live_cores_package =
-            serde_cbor::de::from_slice::<LiveCoresPackage>(decrypted_data.as_slice()).unwrap();
+        let live_cores_package = LiveCoresPackage::from_wire(decrypted_data).unwrap();
         Ok((from_opt.unwrap(), to_opt.unwrap(), live_cores_package))
     }
 
@@ -438,6 +470,8 @@ struct PrometheusMockNodeGuts {
     cryptde_enum: CryptDEEnum,
     framer: RefCell<DataHunkFramer>,
     chain: Option<String>,
+    link_conditions: RefCell<HashMap<IpAddr, NetworkConditions>>,
+    random: Box<dyn Random>,
 }
 
 impl Drop for PrometheusMockNodeGuts {
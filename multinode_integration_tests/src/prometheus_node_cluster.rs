@@ -1,5 +1,6 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 use crate::command::Command;
+use crate::network_conditions::{LinkShaper, NetworkConditions};
 use crate::prometheus_mock_node::PrometheusMockNode;
 use crate::prometheus_node::{PrometheusNode, PrometheusNodeUtils};
 use crate::prometheus_real_node::NodeStartupConfig;
@@ -15,6 +16,7 @@ pub struct PrometheusNodeCluster {
     startup_configs: HashMap<(String, usize), NodeStartupConfig>,
     real_nodes: HashMap<String, PrometheusRealNode>,
     mock_nodes: HashMap<String, PrometheusMockNode>,
+    link_shapers: HashMap<String, LinkShaper>,
     host_node_parent_dir: Option<String>,
     next_index: usize,
     pub chain_id: u8,
@@ -35,6 +37,7 @@ impl PrometheusNodeCluster {
             startup_configs: HashMap::new(),
             real_nodes: HashMap::new(),
             mock_nodes: HashMap::new(),
+            link_shapers: HashMap::new(),
             host_node_parent_dir,
             next_index: 1,
             chain_id: DEFAULT_CHAIN_ID,
@@ -124,7 +127,31 @@ impl PrometheusNodeCluster {
         PrometheusNodeCluster::cleanup().unwrap()
     }
 
+    // The conditions apply both ways. A real Node's side of the link is shaped by netem in its
+    // container; a mock Node holds back or drops what it's told to transmit.
+    pub fn set_link_conditions(
+        &mut self,
+        name_a: &str,
+        name_b: &str,
+        conditions: &NetworkConditions,
+    ) -> Result<(), String> {
+        let ip_a = self.find_ip_address(name_a)?;
+        let ip_b = self.find_ip_address(name_b)?;
+        self.set_outgoing_conditions(name_a, ip_b, conditions)?;
+        self.set_outgoing_conditions(name_b, ip_a, conditions)
+    }
+
+    pub fn clear_link_conditions(&mut self) -> Result<(), String> {
+        self.mock_nodes
+            .values()
+            .for_each(|node| node.clear_link_conditions());
+        self.link_shapers
+            .iter_mut()
+            .try_for_each(|(name, shaper)| shaper.clear(name))
+    }
+
     pub fn stop_node(&mut self, name: &str) {
+        self.link_shapers.remove(name);
         match self.real_nodes.remove(name) {
             Some(node) => drop(node),
             None => match self.mock_nodes.remove(name) {
@@ -187,6 +214,29 @@ impl PrometheusNodeCluster {
         )
     }
 
+    fn find_ip_address(&self, name: &str) -> Result<IpAddr, String> {
+        match self.get_node_by_name(name) {
+            Some(node) => Ok(node.ip_address()),
+            None => Err(format!("Node {} was not found in cluster", name)),
+        }
+    }
+
+    fn set_outgoing_conditions(
+        &mut self,
+        name: &str,
+        peer: IpAddr,
+        conditions: &NetworkConditions,
+    ) -> Result<(), String> {
+        if let Some(node) = self.mock_nodes.get(name) {
+            node.set_link_conditions(peer, conditions.clone());
+            return Ok(());
+        }
+        self.link_shapers
+            .entry(name.to_string())
+            .or_insert_with(LinkShaper::new)
+            .shape(name, peer, conditions)
+    }
+
     pub fn is_in_jenkins() -> bool {
         match env::var("HOST_NODE_PARENT_DIR") {
             Ok(ref value) if value.is_empty() => false,
@@ -1,6 +1,7 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
 use crate::command::Command;
+use crate::node_release::NodeRelease;
 use crate::prometheus_node::NodeReference;
 use crate::prometheus_node::PortSelector;
 use crate::prometheus_node::PrometheusNode;
@@ -125,6 +126,7 @@ pub struct NodeStartupConfig {
     pub fake_public_key: Option<PublicKey>,
     pub blockchain_service_url: Option<String>,
     pub chain: Option<String>,
+    pub release_opt: Option<NodeRelease>,
 }
 
 impl NodeStartupConfig {
@@ -145,6 +147,7 @@ impl NodeStartupConfig {
             fake_public_key: None,
             blockchain_service_url: None,
             chain: None,
+            release_opt: None,
         }
     }
 
@@ -152,6 +155,13 @@ impl NodeStartupConfig {
         self.firewall.clone()
     }
 
+    fn node_binary(&self) -> String {
+        match &self.release_opt {
+            Some(release) => release.container_path(),
+            None => "/node_root/node/PrometheusNode".to_string(),
+        }
+    }
+
     fn make_args(&self) -> Vec<String> {
         let mut args = vec![];
         args.push("--neighborhood-mode".to_string());
@@ -362,6 +372,7 @@ pub struct NodeStartupConfigBuilder {
     fake_public_key: Option<PublicKey>,
     blockchain_service_url: Option<String>,
     chain: Option<String>,
+    release_opt: Option<NodeRelease>,
 }
 
 impl NodeStartupConfigBuilder {
@@ -382,6 +393,7 @@ impl NodeStartupConfigBuilder {
             fake_public_key: None,
             blockchain_service_url: None,
             chain: None,
+            release_opt: None,
         }
     }
 
@@ -406,6 +418,7 @@ impl NodeStartupConfigBuilder {
             fake_public_key: None,
             blockchain_service_url: None,
             chain: None,
+            release_opt: None,
         }
     }
 
@@ -430,6 +443,7 @@ impl NodeStartupConfigBuilder {
             fake_public_key: None,
             blockchain_service_url: None,
             chain: None,
+            release_opt: None,
         }
     }
 
@@ -450,6 +464,7 @@ impl NodeStartupConfigBuilder {
             fake_public_key: None,
             blockchain_service_url: None,
             chain: None,
+            release_opt: None,
         }
     }
 
@@ -470,6 +485,7 @@ impl NodeStartupConfigBuilder {
             fake_public_key: config.fake_public_key.clone(),
             blockchain_service_url: config.blockchain_service_url.clone(),
             chain: config.chain.clone(),
+            release_opt: config.release_opt.clone(),
         }
     }
 
@@ -572,6 +588,12 @@ impl NodeStartupConfigBuilder {
         self
     }
 
+    // Runs this Node from a released binary instead of the current build
+    pub fn release(mut self, release: NodeRelease) -> Self {
+        self.release_opt = Some(release);
+        self
+    }
+
     pub fn build(self) -> NodeStartupConfig {
         NodeStartupConfig {
             neighborhood_mode: self.neighborhood_mode,
@@ -589,6 +611,7 @@ impl NodeStartupConfigBuilder {
             fake_public_key: self.fake_public_key,
             blockchain_service_url: self.blockchain_service_url,
             chain: self.chain,
+            release_opt: self.release_opt,
         }
     }
 }
@@ -731,6 +754,11 @@ impl PrometheusRealNode {
         };
 
         docker_run_fn(&root_dir, ip_addr, &name).expect("docker run");
+        if let Some(release) = &real_startup_config.release_opt {
+            release
+                .install(&name)
+                .expect(&format!("Couldn't install release {}", release.version()));
+        }
 
         Self::exec_command_on_container_and_detach(
             &name,
@@ -781,6 +809,7 @@ impl PrometheusRealNode {
                 .contains(&real_startup_config.neighborhood_mode.as_str()),
             routes_data: vec!["standard", "originate-only"]
                 .contains(&real_startup_config.neighborhood_mode.as_str()),
+            node_command,
         });
         Self { guts }
     }
@@ -790,7 +819,7 @@ impl PrometheusRealNode {
             None => return,
             Some(args) => args.join(" "),
         };
-        let node_command = format!("/node_root/node/PrometheusNode {}", args);
+        let node_command = format!("{} {}", startup_config.node_binary(), args);
         let mut bash_command_parts = vec!["/bin/bash", "-c"];
         bash_command_parts.extend(vec![node_command.as_str()]);
         Self::exec_command_on_container_and_wait(name, bash_command_parts)
@@ -798,12 +827,13 @@ impl PrometheusRealNode {
     }
 
     fn create_node_command(node_args: Vec<String>, startup_config: NodeStartupConfig) -> String {
+        let node_binary = startup_config.node_binary();
         let mut node_command_parts: Vec<String> = match startup_config.memory {
             Some(kbytes) => vec![format!(
-                "ulimit -v {} -m {} && /node_root/node/PrometheusNode",
-                kbytes, kbytes
+                "ulimit -v {} -m {} && {}",
+                kbytes, kbytes, node_binary
             )],
-            None => vec!["/node_root/node/PrometheusNode".to_string()],
+            None => vec![node_binary],
         };
         node_command_parts.extend(node_args);
         node_command_parts.join(" ")
@@ -824,6 +854,37 @@ impl PrometheusRealNode {
         Self::node_home_dir(&self.root_dir(), &String::from(self.name()))
     }
 
+    // These leave the container, with its address, firewall and data directory, where it is, and
+    // act only on the Node's process inside it
+    pub fn kill_node_process(&self) -> Result<(), String> {
+        self.signal_node_process("-KILL")
+    }
+
+    pub fn pause(&self) -> Result<(), String> {
+        self.signal_node_process("-STOP")
+    }
+
+    pub fn resume(&self) -> Result<(), String> {
+        self.signal_node_process("-CONT")
+    }
+
+    // Starts the Node again as it was first started, after kill_node_process()
+    pub fn restart(&self) -> Result<(), String> {
+        Self::exec_command_on_container_and_detach(
+            self.name(),
+            vec!["/bin/bash", "-c", &self.guts.node_command],
+        )
+        .map(|_| ())
+    }
+
+    fn signal_node_process(&self, signal: &str) -> Result<(), String> {
+        Self::exec_command_on_container_and_wait(
+            self.name(),
+            vec!["pkill", signal, "-x", "PrometheusNode"],
+        )
+        .map(|_| ())
+    }
+
     pub fn open_firewall_port(name: &str, port: u16) -> Result<(), ()> {
         let port_str = format!("{}", port);
         match Self::exec_command_on_container_and_wait(
@@ -1084,6 +1145,7 @@ struct PrometheusRealNodeGuts {
     chain: Option<String>,
     accepts_connections: bool,
     routes_data: bool,
+    node_command: String,
 }
 
 impl Drop for PrometheusRealNodeGuts {
@@ -1227,6 +1289,7 @@ mod tests {
             fake_public_key: Some(PublicKey::new(&[1, 2, 3, 4])),
             blockchain_service_url: None,
             chain: None,
+            release_opt: None,
         };
         let neighborhood_mode = "standard".to_string();
         let ip_addr = IpAddr::from_str("1.2.3.4").unwrap();
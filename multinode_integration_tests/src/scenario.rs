@@ -0,0 +1,423 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::chaos::{request_through, StreamOutcome};
+use crate::prometheus_node::PrometheusNode;
+use crate::prometheus_node_cluster::PrometheusNodeCluster;
+use crate::prometheus_real_node::{
+    make_consuming_wallet_info, make_earning_wallet_info, NodeStartupConfigBuilder,
+    PrometheusRealNode,
+};
+use node_lib::blockchain::blockchain_interface::chain_name_from_id;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::thread;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    Standard,
+    // Routes and exits, but accepts no connections: must be linked to a Standard Node
+    OriginateOnly,
+    // Only originates traffic: must be linked to a Standard Node
+    ConsumeOnly,
+}
+
+impl Role {
+    fn accepts_connections(self) -> bool {
+        self == Role::Standard
+    }
+
+    fn builder(self) -> NodeStartupConfigBuilder {
+        match self {
+            Role::Standard => NodeStartupConfigBuilder::standard(),
+            Role::OriginateOnly => NodeStartupConfigBuilder::originate_only(),
+            Role::ConsumeOnly => NodeStartupConfigBuilder::consume_only(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Traffic {
+    pub from: String,
+    pub port: u16,
+    pub request: Vec<u8>,
+    pub expected: StreamOutcome,
+}
+
+// A Node as it will be started: its neighbors have all been started before it
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlannedNode {
+    pub name: String,
+    pub role: Role,
+    pub neighbors: Vec<String>,
+}
+
+/// A multinode test described rather than scripted: which Nodes there are, how they're linked,
+/// what traffic goes through them and how it should turn out. The Scenario works out what order
+/// to start the Nodes in and which descriptors each one gets, starts them in a fresh cluster, runs
+/// the traffic and stops the cluster.
+///
+/// ```ignore
+/// Scenario::new()
+///     .nodes(&["A", "B", "C", "D", "E"], Role::Standard)
+///     .ring(&["A", "B", "C", "D", "E"])
+///     .node("F", Role::ConsumeOnly)
+///     .link("F", "A")
+///     .http_get("F", "example.com", StreamOutcome::Served)
+///     .run();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scenario {
+    nodes: Vec<(String, Role)>,
+    links: Vec<(String, String)>,
+    traffic: Vec<Traffic>,
+    settle_time: Duration,
+    request_timeout: Duration,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Scenario {
+            nodes: vec![],
+            links: vec![],
+            traffic: vec![],
+            settle_time: Duration::from_millis(3000),
+            request_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl Scenario {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn node(mut self, name: &str, role: Role) -> Self {
+        self.nodes.push((name.to_string(), role));
+        self
+    }
+
+    pub fn nodes(self, names: &[&str], role: Role) -> Self {
+        names
+            .iter()
+            .fold(self, |scenario, name| scenario.node(name, role))
+    }
+
+    pub fn link(mut self, name_a: &str, name_b: &str) -> Self {
+        self.links.push((name_a.to_string(), name_b.to_string()));
+        self
+    }
+
+    pub fn line(self, names: &[&str]) -> Self {
+        names
+            .windows(2)
+            .fold(self, |scenario, pair| scenario.link(pair[0], pair[1]))
+    }
+
+    pub fn ring(self, names: &[&str]) -> Self {
+        let scenario = self.line(names);
+        match (names.first(), names.last()) {
+            (Some(first), Some(last)) if names.len() > 2 => scenario.link(last, first),
+            _ => scenario,
+        }
+    }
+
+    pub fn star(self, center: &str, names: &[&str]) -> Self {
+        names
+            .iter()
+            .fold(self, |scenario, name| scenario.link(center, name))
+    }
+
+    pub fn request(
+        mut self,
+        from: &str,
+        port: u16,
+        request: &[u8],
+        expected: StreamOutcome,
+    ) -> Self {
+        self.traffic.push(Traffic {
+            from: from.to_string(),
+            port,
+            request: request.to_vec(),
+            expected,
+        });
+        self
+    }
+
+    pub fn http_get(self, from: &str, host: &str, expected: StreamOutcome) -> Self {
+        let request = format!("GET / HTTP/1.1\r\nHost: {}\r\n\r\n", host);
+        self.request(from, 8080, request.as_bytes(), expected)
+    }
+
+    // How long to let Gossip spread after the last Node starts and before traffic begins
+    pub fn settle_time(mut self, settle_time: Duration) -> Self {
+        self.settle_time = settle_time;
+        self
+    }
+
+    pub fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    // Standard Nodes start in the order they were declared, each pointed at the Standard Nodes it's
+    // linked to that are already up. Other Nodes can't be reached, so they start last and are
+    // pointed at everything they're linked to.
+    pub fn plan(&self) -> Result<Vec<PlannedNode>, String> {
+        let roles = self.roles()?;
+        let mut neighbors: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (name_a, name_b) in &self.links {
+            for name in &[name_a, name_b] {
+                if !roles.contains_key(name.as_str()) {
+                    return Err(format!("Link to undeclared Node {}", name));
+                }
+            }
+            if name_a == name_b {
+                return Err(format!("Node {} is linked to itself", name_a));
+            }
+            if !roles[name_a.as_str()].accepts_connections()
+                && !roles[name_b.as_str()].accepts_connections()
+            {
+                return Err(format!(
+                    "Neither {} nor {} accepts connections, so they can't be linked",
+                    name_a, name_b
+                ));
+            }
+            neighbors
+                .entry(name_a)
+                .or_insert_with(Vec::new)
+                .push(name_b);
+            neighbors
+                .entry(name_b)
+                .or_insert_with(Vec::new)
+                .push(name_a);
+        }
+        let (standard, others): (Vec<&(String, Role)>, Vec<&(String, Role)>) = self
+            .nodes
+            .iter()
+            .partition(|(_, role)| role.accepts_connections());
+        let mut started: HashSet<&str> = HashSet::new();
+        let mut plan = vec![];
+        for (name, role) in standard.into_iter().chain(others.into_iter()) {
+            let linked = neighbors.get(name.as_str()).cloned().unwrap_or_default();
+            if !role.accepts_connections() && linked.is_empty() {
+                return Err(format!("{:?} Node {} isn't linked to anything", role, name));
+            }
+            let mut node_neighbors: Vec<String> = vec![];
+            for neighbor in linked {
+                if started.contains(neighbor) && !node_neighbors.iter().any(|n| n == neighbor) {
+                    node_neighbors.push(neighbor.to_string());
+                }
+            }
+            started.insert(name);
+            plan.push(PlannedNode {
+                name: name.clone(),
+                role: *role,
+                neighbors: node_neighbors,
+            });
+        }
+        for traffic in &self.traffic {
+            if !roles.contains_key(traffic.from.as_str()) {
+                return Err(format!("Traffic from undeclared Node {}", traffic.from));
+            }
+        }
+        Ok(plan)
+    }
+
+    pub fn start(self) -> Result<RunningScenario, String> {
+        let plan = self.plan()?;
+        let mut cluster = PrometheusNodeCluster::start()?;
+        let mut nodes: HashMap<String, PrometheusRealNode> = HashMap::new();
+        for planned in plan {
+            let config = planned
+                .neighbors
+                .iter()
+                .fold(planned.role.builder(), |builder, neighbor| {
+                    builder.neighbor(nodes[neighbor].node_reference())
+                })
+                .earning_wallet_info(make_earning_wallet_info(&planned.name))
+                .consuming_wallet_info(make_consuming_wallet_info(&planned.name))
+                .chain(chain_name_from_id(cluster.chain_id))
+                .build();
+            let node = cluster.start_real_node(config);
+            nodes.insert(planned.name, node);
+        }
+        thread::sleep(self.settle_time);
+        Ok(RunningScenario {
+            cluster,
+            nodes,
+            traffic: self.traffic,
+            request_timeout: self.request_timeout,
+        })
+    }
+
+    // Starts the scenario, checks its traffic and stops it; panics if any traffic turned out
+    // differently than expected
+    pub fn run(self) {
+        let running = self.start().unwrap();
+        let failures = running.check_traffic();
+        running.stop();
+        assert!(
+            failures.is_empty(),
+            "Scenario failed:\n{}",
+            failures.join("\n")
+        );
+    }
+
+    fn roles(&self) -> Result<HashMap<&str, Role>, String> {
+        let mut roles = HashMap::new();
+        for (name, role) in &self.nodes {
+            if roles.insert(name.as_str(), *role).is_some() {
+                return Err(format!("Node {} is declared twice", name));
+            }
+        }
+        Ok(roles)
+    }
+}
+
+// A started Scenario, for tests that need to check more than its traffic before stopping it
+pub struct RunningScenario {
+    cluster: PrometheusNodeCluster,
+    nodes: HashMap<String, PrometheusRealNode>,
+    traffic: Vec<Traffic>,
+    request_timeout: Duration,
+}
+
+impl RunningScenario {
+    pub fn node(&self, name: &str) -> &PrometheusRealNode {
+        self.nodes
+            .get(name)
+            .unwrap_or_else(|| panic!("No Node named {} in the scenario", name))
+    }
+
+    pub fn cluster(&self) -> &PrometheusNodeCluster {
+        &self.cluster
+    }
+
+    pub fn cluster_mut(&mut self) -> &mut PrometheusNodeCluster {
+        &mut self.cluster
+    }
+
+    // Sends each request in order and describes each one that didn't turn out as expected
+    pub fn check_traffic(&self) -> Vec<String> {
+        self.traffic
+            .iter()
+            .filter_map(|traffic| {
+                let node = self.node(&traffic.from);
+                let addr = SocketAddr::new(node.ip_address(), traffic.port);
+                let actual = request_through(addr, &traffic.request, self.request_timeout);
+                if actual == traffic.expected {
+                    None
+                } else {
+                    Some(format!(
+                        "Request from {} ({}) to port {}: expected {:?}, got {:?}",
+                        traffic.from,
+                        node.name(),
+                        traffic.port,
+                        traffic.expected,
+                        actual
+                    ))
+                }
+            })
+            .collect()
+    }
+
+    pub fn stop(self) {
+        self.cluster.stop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn neighbors_of<'a>(plan: &'a [PlannedNode], name: &str) -> &'a Vec<String> {
+        &plan
+            .iter()
+            .find(|node| node.name == name)
+            .unwrap()
+            .neighbors
+    }
+
+    #[test]
+    fn ring_is_started_in_order_with_each_node_pointed_backward() {
+        let subject = Scenario::new()
+            .nodes(&["A", "B", "C", "D", "E"], Role::Standard)
+            .ring(&["A", "B", "C", "D", "E"]);
+
+        let result = subject.plan().unwrap();
+
+        assert_eq!(
+            result
+                .iter()
+                .map(|node| node.name.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["A", "B", "C", "D", "E"]
+        );
+        assert!(neighbors_of(&result, "A").is_empty());
+        assert_eq!(neighbors_of(&result, "B"), &vec!["A".to_string()]);
+        assert_eq!(
+            neighbors_of(&result, "E"),
+            &vec!["D".to_string(), "A".to_string()]
+        );
+    }
+
+    #[test]
+    fn nodes_that_accept_no_connections_start_last_pointed_at_all_their_links() {
+        let subject = Scenario::new()
+            .node("C", Role::OriginateOnly)
+            .nodes(&["A", "B"], Role::Standard)
+            .star("C", &["A", "B"])
+            .link("A", "B");
+
+        let result = subject.plan().unwrap();
+
+        assert_eq!(result[2].name, "C");
+        assert_eq!(result[2].role, Role::OriginateOnly);
+        assert_eq!(
+            neighbors_of(&result, "C"),
+            &vec!["A".to_string(), "B".to_string()]
+        );
+        assert!(neighbors_of(&result, "A").is_empty());
+    }
+
+    #[test]
+    fn plan_rejects_impossible_topologies() {
+        let check = |scenario: Scenario, expected: &str| {
+            assert_eq!(scenario.plan(), Err(expected.to_string()))
+        };
+
+        check(
+            Scenario::new()
+                .node("A", Role::Standard)
+                .node("A", Role::Standard),
+            "Node A is declared twice",
+        );
+        check(
+            Scenario::new().node("A", Role::Standard).link("A", "B"),
+            "Link to undeclared Node B",
+        );
+        check(
+            Scenario::new().node("A", Role::Standard).link("A", "A"),
+            "Node A is linked to itself",
+        );
+        check(
+            Scenario::new()
+                .node("A", Role::ConsumeOnly)
+                .node("B", Role::OriginateOnly)
+                .link("A", "B"),
+            "Neither A nor B accepts connections, so they can't be linked",
+        );
+        check(
+            Scenario::new().node("A", Role::ConsumeOnly),
+            "ConsumeOnly Node A isn't linked to anything",
+        );
+        check(
+            Scenario::new().node("A", Role::Standard).http_get(
+                "B",
+                "example.com",
+                StreamOutcome::Served,
+            ),
+            "Traffic from undeclared Node B",
+        );
+    }
+}
@@ -72,6 +72,7 @@ impl From<&dyn PrometheusNode> for AccessibleGossipRecord {
                 data_version: NodeRecordInner::data_version(),
                 public_key: prometheus_node.public_key().clone(),
                 earning_wallet: prometheus_node.earning_wallet(),
+                exit_earning_wallet_opt: None,
                 rate_pack: prometheus_node.rate_pack(),
                 neighbors: BTreeSet::new(),
                 accepts_connections: prometheus_node.accepts_connections(),
@@ -0,0 +1,86 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use multinode_integration_tests_lib::chaos::{
+    assert_neighborhood_converges, assert_no_double_counting, mark_logs, request_through,
+    ChaosMonkey, StreamOutcome,
+};
+use multinode_integration_tests_lib::prometheus_node::PrometheusNode;
+use multinode_integration_tests_lib::prometheus_node_cluster::PrometheusNodeCluster;
+use multinode_integration_tests_lib::prometheus_real_node::{
+    make_consuming_wallet_info, make_earning_wallet_info, NodeStartupConfigBuilder,
+    PrometheusRealNode,
+};
+use node_lib::blockchain::blockchain_interface::chain_name_from_id;
+use std::net::SocketAddr;
+use std::thread;
+use std::time::Duration;
+
+const REQUEST: &[u8] = b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n";
+
+#[test]
+fn cluster_survives_nodes_being_killed_paused_and_restarted() {
+    let mut cluster = PrometheusNodeCluster::start().unwrap();
+    let originating_node = cluster.start_real_node(
+        NodeStartupConfigBuilder::standard()
+            .earning_wallet_info(make_earning_wallet_info("originating"))
+            .consuming_wallet_info(make_consuming_wallet_info("originating"))
+            .chain(chain_name_from_id(cluster.chain_id))
+            .build(),
+    );
+    let serving_nodes = (0..6)
+        .map(|index| {
+            cluster.start_real_node(
+                NodeStartupConfigBuilder::standard()
+                    .neighbor(originating_node.node_reference())
+                    .earning_wallet_info(make_earning_wallet_info(&format!("serving{}", index)))
+                    .chain(chain_name_from_id(cluster.chain_id))
+                    .build(),
+            )
+        })
+        .collect::<Vec<PrometheusRealNode>>();
+    thread::sleep(Duration::from_millis(3000));
+    let proxy_addr = SocketAddr::new(originating_node.ip_address(), 8080);
+    let mut subject = ChaosMonkey::new(
+        ChaosMonkey::seed_from_environment(),
+        serving_nodes
+            .iter()
+            .map(|node| node.name().to_string())
+            .collect(),
+        2,
+    );
+    let mut outcomes = vec![];
+
+    subject
+        .run(&cluster, 12, Duration::from_millis(1000), |_| {
+            outcomes.push(request_through(
+                proxy_addr,
+                REQUEST,
+                Duration::from_secs(30),
+            ))
+        })
+        .unwrap();
+    let mut all_nodes = vec![originating_node.clone()];
+    all_nodes.extend(serving_nodes.iter().cloned());
+    let marks = mark_logs(&all_nodes);
+    subject.restore(&cluster).unwrap();
+
+    let unclean = outcomes
+        .iter()
+        .filter(|outcome| !outcome.is_clean())
+        .collect::<Vec<&StreamOutcome>>();
+    assert!(
+        unclean.is_empty(),
+        "Seed {}: streams didn't end cleanly: {:?}\nChaos: {:?}",
+        subject.seed(),
+        unclean,
+        subject.history()
+    );
+    assert_neighborhood_converges(&all_nodes, &marks, Duration::from_secs(30));
+    assert_eq!(
+        request_through(proxy_addr, REQUEST, Duration::from_secs(30)),
+        StreamOutcome::Served,
+        "Seed {}: the healed cluster couldn't serve a request",
+        subject.seed()
+    );
+    assert_no_double_counting(&originating_node, &serving_nodes, cluster.chain_id);
+}
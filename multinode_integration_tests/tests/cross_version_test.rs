@@ -0,0 +1,108 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use multinode_integration_tests_lib::node_release::NodeRelease;
+use multinode_integration_tests_lib::prometheus_node::{PrometheusNode, PrometheusNodeUtils};
+use multinode_integration_tests_lib::prometheus_node_cluster::PrometheusNodeCluster;
+use multinode_integration_tests_lib::prometheus_real_node::{
+    make_consuming_wallet_info, make_earning_wallet_info, NodeStartupConfigBuilder,
+    PrometheusRealNode,
+};
+use node_lib::accountant::receivable_dao::{ReceivableAccount, ReceivableDao, ReceivableDaoReal};
+use node_lib::blockchain::blockchain_interface::chain_name_from_id;
+use node_lib::database::db_initializer::{DbInitializer, DbInitializerReal};
+use std::thread;
+use std::time::Duration;
+
+const GOSSIP_PATTERN: &str = "Finished processing Gossip about [1-9]";
+
+#[test]
+fn previous_release_interoperates_with_current_build() {
+    let release = match NodeRelease::from_environment().unwrap() {
+        Some(release) => release,
+        None => {
+            println!("No previous release configured; skipping cross-version test");
+            return;
+        }
+    };
+    let mut cluster = PrometheusNodeCluster::start().unwrap();
+    let previous_node = cluster.start_real_node(
+        NodeStartupConfigBuilder::standard()
+            .earning_wallet_info(make_earning_wallet_info("previous"))
+            .consuming_wallet_info(make_consuming_wallet_info("previous"))
+            .chain(chain_name_from_id(cluster.chain_id))
+            .release(release.clone())
+            .build(),
+    );
+    let current_nodes = (0..6)
+        .map(|index| {
+            cluster.start_real_node(
+                NodeStartupConfigBuilder::standard()
+                    .neighbor(previous_node.node_reference())
+                    .earning_wallet_info(make_earning_wallet_info(&format!("current{}", index)))
+                    .chain(chain_name_from_id(cluster.chain_id))
+                    .build(),
+            )
+        })
+        .collect::<Vec<PrometheusRealNode>>();
+    thread::sleep(Duration::from_millis(2000));
+
+    // Gossip: each side has to understand what the other sends
+    PrometheusNodeUtils::wrote_log_containing(
+        previous_node.name(),
+        GOSSIP_PATTERN,
+        Duration::from_secs(10),
+    );
+    current_nodes.iter().for_each(|node| {
+        PrometheusNodeUtils::wrote_log_containing(
+            node.name(),
+            GOSSIP_PATTERN,
+            Duration::from_secs(10),
+        )
+    });
+
+    // Routing: the previous release's CORES packages go through current Nodes and back
+    let mut client = previous_node.make_client(8080);
+    client.send_chunk(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n");
+    let response = String::from_utf8(client.wait_for_chunk()).unwrap();
+    assert!(
+        response.contains(
+            "This domain is established to be used for illustrative examples in documents."
+        ),
+        "Version {} couldn't route through the current build:\n{}",
+        release.version(),
+        response
+    );
+
+    // Payment: current Nodes have to be able to bill the previous release for their services
+    let consuming_wallet = previous_node.consuming_wallet().unwrap();
+    let billing_nodes = current_nodes
+        .iter()
+        .filter(|node| {
+            receivables(node, cluster.chain_id)
+                .iter()
+                .any(|account| account.wallet == consuming_wallet && account.balance > 0)
+        })
+        .count();
+    assert!(
+        billing_nodes >= 2, // minimum service list: route, exit
+        "Only {} current Nodes billed version {}",
+        billing_nodes,
+        release.version()
+    );
+}
+
+fn receivables(node: &PrometheusRealNode, chain_id: u8) -> Vec<ReceivableAccount> {
+    let db_initializer = DbInitializerReal::new();
+    let receivable_dao = ReceivableDaoReal::new(
+        db_initializer
+            .initialize(
+                &std::path::PathBuf::from(PrometheusRealNode::node_home_dir(
+                    &PrometheusNodeUtils::find_project_root(),
+                    &node.name().to_string(),
+                )),
+                chain_id,
+            )
+            .unwrap(),
+    );
+    receivable_dao.receivables()
+}
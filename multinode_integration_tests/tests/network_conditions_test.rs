@@ -0,0 +1,45 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use multinode_integration_tests_lib::network_conditions::NetworkConditions;
+use multinode_integration_tests_lib::prometheus_node::PrometheusNode;
+use multinode_integration_tests_lib::prometheus_node_cluster::PrometheusNodeCluster;
+use multinode_integration_tests_lib::prometheus_real_node::NodeStartupConfigBuilder;
+use node_lib::blockchain::blockchain_interface::chain_name_from_id;
+use node_lib::sub_lib::cryptde::PublicKey;
+use std::time::{Duration, Instant};
+
+#[test]
+fn latency_on_a_link_slows_traffic_in_both_directions() {
+    let mut cluster = PrometheusNodeCluster::start().unwrap();
+    let real_node = cluster.start_real_node(
+        NodeStartupConfigBuilder::standard()
+            .chain(chain_name_from_id(cluster.chain_id))
+            .build(),
+    );
+    let mock_node =
+        cluster.start_mock_node_with_public_key(vec![5550], &PublicKey::new(&[1, 2, 3, 4]));
+    cluster
+        .set_link_conditions(
+            real_node.name(),
+            mock_node.name(),
+            &NetworkConditions::new().latency(Duration::from_millis(1000)),
+        )
+        .unwrap();
+    let start = Instant::now();
+
+    mock_node.transmit_debut(&real_node).unwrap();
+    let gossip_opt = mock_node.wait_for_gossip(Duration::from_secs(5));
+
+    let elapsed = start.elapsed();
+    assert!(
+        gossip_opt.is_some(),
+        "No Gossip came back over the slow link"
+    );
+    // A second each way: the mock Node holds its debut, and netem holds the real Node's answer
+    assert!(
+        elapsed >= Duration::from_millis(2000),
+        "Gossip came back in only {:?}",
+        elapsed
+    );
+    cluster.clear_link_conditions().unwrap();
+}
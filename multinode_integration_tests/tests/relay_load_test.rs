@@ -0,0 +1,55 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use multinode_integration_tests_lib::load_generator::{LoadGenerator, LoadProfile};
+use multinode_integration_tests_lib::prometheus_node::PrometheusNode;
+use multinode_integration_tests_lib::prometheus_node_cluster::PrometheusNodeCluster;
+use multinode_integration_tests_lib::prometheus_real_node::{
+    make_consuming_wallet_info, NodeStartupConfigBuilder,
+};
+use node_lib::blockchain::blockchain_interface::chain_name_from_id;
+use std::net::SocketAddr;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn relay_path_holds_up_under_a_ramp_of_mixed_traffic() {
+    let mut cluster = PrometheusNodeCluster::start().unwrap();
+    let originating_node = cluster.start_real_node(
+        NodeStartupConfigBuilder::standard()
+            .consuming_wallet_info(make_consuming_wallet_info("originating"))
+            .chain(chain_name_from_id(cluster.chain_id))
+            .build(),
+    );
+    let _serving_nodes = (0..6)
+        .map(|_| {
+            cluster.start_real_node(
+                NodeStartupConfigBuilder::standard()
+                    .neighbor(originating_node.node_reference())
+                    .chain(chain_name_from_id(cluster.chain_id))
+                    .build(),
+            )
+        })
+        .collect::<Vec<_>>();
+    thread::sleep(Duration::from_millis(3000));
+    let subject = LoadGenerator::new(
+        SocketAddr::new(originating_node.ip_address(), 8080),
+        SocketAddr::new(originating_node.ip_address(), 8443),
+        LoadProfile::new()
+            .tls_percent(30)
+            .ramp(1, 7, 3)
+            .step_duration(Duration::from_secs(15))
+            .think_time(Duration::from_millis(200))
+            .request_timeout(Duration::from_secs(20)),
+    );
+
+    let report = subject.run();
+
+    println!("Relay load:\n{}", report);
+    assert!(report.total_requests() > 0, "No requests were made");
+    assert!(
+        report.failure_rate() <= 0.05,
+        "{:.1}% of requests failed:\n{}",
+        report.failure_rate() * 100.0,
+        report
+    );
+}
@@ -0,0 +1,19 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use multinode_integration_tests_lib::chaos::StreamOutcome;
+use multinode_integration_tests_lib::scenario::{Role, Scenario};
+
+#[test]
+fn consume_only_node_is_served_through_a_ring_with_originate_only_exits() {
+    Scenario::new()
+        .nodes(&["A", "B", "C", "D", "E"], Role::Standard)
+        .ring(&["A", "B", "C", "D", "E"])
+        .nodes(&["X1", "X2"], Role::OriginateOnly)
+        .link("X1", "B")
+        .link("X2", "D")
+        .node("client", Role::ConsumeOnly)
+        .link("client", "A")
+        .http_get("client", "example.com", StreamOutcome::Served)
+        .http_get("C", "example.com", StreamOutcome::Served)
+        .run();
+}
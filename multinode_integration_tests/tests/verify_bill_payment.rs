@@ -112,7 +112,8 @@ fn verify_bill_payment() {
         .build();
 
     let amount = 10u64
-        * u64::try_from(node_lib::accountant::PAYMENT_CURVES.permanent_debt_allowed_gwub).unwrap();
+        * u64::try_from(node_lib::accountant::DEFAULT_PAYMENT_CURVES.permanent_debt_allowed_gwub)
+            .unwrap();
 
     let project_root = PrometheusNodeUtils::find_project_root();
     let (consuming_node_name, consuming_node_index) = cluster.prepare_real_node(&consuming_config);
@@ -176,6 +176,37 @@ describe('FinancialStatisticsComponent', () => {
       it('renders the pending debt data', () => {
         expect(compiled.querySelector('#pending-debt').textContent).toBe('0.001000000');
       });
+
+      it('does not say payments are deferred', () => {
+        expect(compiled.querySelector('#payments-deferred')).toBeFalsy();
+      });
+    });
+
+    describe('when payments are deferred due to the fee budget', () => {
+      beforeEach(() => {
+        mockNodeStatus.next(NodeStatus.Consuming);
+        const response: PendingAmounts = {pendingCredit: '10000', pendingDebt: '1000000', paymentsDeferred: 'feeBudget'};
+        mockFinancialStatisticsResponse.next(response);
+        fixture.detectChanges();
+      });
+
+      it('says so', () => {
+        expect(compiled.querySelector('#payments-deferred').textContent).toBe('Payments deferred due to fee budget');
+      });
+    });
+
+    describe('when payments are deferred due to the gas price cap', () => {
+      beforeEach(() => {
+        mockNodeStatus.next(NodeStatus.Consuming);
+        const response: PendingAmounts = {pendingCredit: '10000', pendingDebt: '1000000', paymentsDeferred: 'feeCap'};
+        mockFinancialStatisticsResponse.next(response);
+        fixture.detectChanges();
+      });
+
+      it('says so', () => {
+        expect(compiled.querySelector('#payments-deferred').textContent)
+          .toBe('Payments deferred until fees fall below the gas price cap');
+      });
     });
 
     describe('when financial statistics data is undefined', () => {
@@ -10,6 +10,11 @@ import {Observable, Subscription} from 'rxjs';
 
 export const tokenSymbols = {ropsten: 'HOT', mainnet: 'SUB'};
 
+const paymentDeferrals = {
+  feeBudget: 'Payments deferred due to fee budget',
+  feeCap: 'Payments deferred until fees fall below the gas price cap',
+};
+
 @Component({
   selector: 'app-financial-statistics',
   templateUrl: './financial-statistics.component.html',
@@ -83,6 +88,10 @@ export class FinancialStatisticsComponent implements OnInit, OnDestroy {
     return FinancialStatisticsComponent.gwubToSub(this.financialStatisticsData.pendingDebt);
   }
 
+  paymentsDeferred(): string | undefined {
+    return paymentDeferrals[this.financialStatisticsData.paymentsDeferred];
+  }
+
   status(): Observable<NodeStatus> {
     return this.mainService.nodeStatus;
   }
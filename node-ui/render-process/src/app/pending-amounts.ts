@@ -3,4 +3,5 @@
 export class PendingAmounts {
   pendingCredit: string;
   pendingDebt: string;
+  paymentsDeferred?: string;
 }
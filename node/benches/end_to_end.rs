@@ -0,0 +1,388 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+// Measures how long requests take to go through a NodeChain and back, and how much traffic it
+// carries when several browsers keep it busy, for HTTP and for TLS, and prints the results as
+// JSON. The TLS server has to listen on port 443, so run it as root: `ci/benchmark.sh`.
+//
+//   cargo bench --bench end_to_end -- [--latency-requests N] [--clients N] [--seconds N]
+//       [--response-bytes N] [--output FILE]
+
+use node_lib::test_utils::ensure_node_home_directory_exists;
+use node_lib::test_utils::node_chain::{BrowserConnector, BrowserStream, NodeChain};
+use serde_json::{json, Value};
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::process;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+const HTTP_PORT: u16 = 80;
+const TLS_PORT: u16 = 443;
+const TLS_HANDSHAKE: u8 = 22;
+const TLS_APPLICATION_DATA: u8 = 23;
+const TLS_MAX_RECORD_LENGTH: usize = 16384;
+const TLS_REQUEST_BYTES: usize = 512;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Settings {
+    latency_requests: usize,
+    clients: usize,
+    seconds: u64,
+    response_bytes: usize,
+    output_opt: Option<String>,
+}
+
+impl Settings {
+    fn from_args(args: Vec<String>) -> Result<Settings, String> {
+        let mut settings = Settings {
+            latency_requests: 200,
+            clients: 8,
+            seconds: 10,
+            response_bytes: 16384,
+            output_opt: None,
+        };
+        // cargo bench adds --bench of its own
+        let mut args = args.into_iter().skip(1).filter(|arg| arg != "--bench");
+        while let Some(name) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("{} needs a value", name))?;
+            let number = || {
+                value
+                    .parse::<usize>()
+                    .map_err(|_| format!("{} must be a number, not {}", name, value))
+            };
+            match name.as_str() {
+                "--latency-requests" => settings.latency_requests = number()?,
+                "--clients" => settings.clients = number()?.max(1),
+                "--seconds" => settings.seconds = number()? as u64,
+                "--response-bytes" => settings.response_bytes = number()?,
+                "--output" => settings.output_opt = Some(value.clone()),
+                _ => return Err(format!("Unknown option {}", name)),
+            }
+        }
+        Ok(settings)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Protocol {
+    Http,
+    Tls,
+}
+
+// What a browser sends and gets back, over and over, on one stream
+#[derive(Clone)]
+struct Exchange {
+    protocol: Protocol,
+    opening_request: Vec<u8>,
+    request: Vec<u8>,
+    opening_response_len: usize,
+    response_len: usize,
+}
+
+impl Exchange {
+    fn http(server_addr: SocketAddr, response_bytes: usize) -> Exchange {
+        let request = format!(
+            "GET /benchmark HTTP/1.1\r\nHost: {}:{}\r\n\r\n",
+            server_addr.ip(),
+            server_addr.port()
+        )
+        .into_bytes();
+        let response_len = http_response(response_bytes).len();
+        Exchange {
+            protocol: Protocol::Http,
+            opening_request: request.clone(),
+            request,
+            opening_response_len: response_len,
+            response_len,
+        }
+    }
+
+    fn tls(response_bytes: usize) -> Exchange {
+        let response_len = tls_records(TLS_APPLICATION_DATA, &vec![0u8; response_bytes]).len();
+        Exchange {
+            protocol: Protocol::Tls,
+            opening_request: client_hello(&localhost().to_string()),
+            request: tls_records(TLS_APPLICATION_DATA, &[0u8; TLS_REQUEST_BYTES]),
+            opening_response_len: response_len,
+            response_len,
+        }
+    }
+
+    fn reception_port(&self) -> u16 {
+        match self.protocol {
+            Protocol::Http => HTTP_PORT,
+            Protocol::Tls => TLS_PORT,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self.protocol {
+            Protocol::Http => "http",
+            Protocol::Tls => "tls",
+        }
+    }
+}
+
+fn main() {
+    let (settings, http_addr) = match start(env::args().collect()) {
+        Ok(started) => started,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1)
+        }
+    };
+    let data_directory = ensure_node_home_directory_exists("benchmarks", "end_to_end");
+    let chain = NodeChain::start(&data_directory);
+
+    let workloads = vec![
+        Exchange::http(http_addr, settings.response_bytes),
+        Exchange::tls(settings.response_bytes),
+    ]
+    .into_iter()
+    .map(|exchange| {
+        eprintln!("Benchmarking {}...", exchange.name());
+        json!({
+            "protocol": exchange.name(),
+            "latency": measure_latency(&chain, &exchange, settings.latency_requests),
+            "throughput": measure_throughput(&chain, &exchange, &settings),
+        })
+    })
+    .collect::<Vec<Value>>();
+    let results = json!({
+        "benchmark": "end_to_end",
+        "nodes": 3,
+        "response_bytes": settings.response_bytes,
+        "workloads": workloads,
+    });
+
+    let text = serde_json::to_string_pretty(&results).expect("Couldn't serialize results");
+    println!("{}", text);
+    if let Some(output) = settings.output_opt {
+        fs::write(&output, text).unwrap_or_else(|e| panic!("Couldn't write {}: {}", output, e));
+    }
+}
+
+// Starts the servers the exit Node will connect to
+fn start(args: Vec<String>) -> Result<(Settings, SocketAddr), String> {
+    let settings = Settings::from_args(args)?;
+    let http_addr = start_server(0, serve_http, settings.response_bytes)?;
+    start_server(TLS_PORT, serve_tls, settings.response_bytes)?;
+    Ok((settings, http_addr))
+}
+
+// Each request on a new stream, one at a time: the time from its first byte leaving the browser
+// to the last byte of its response arriving
+fn measure_latency(chain: &NodeChain, exchange: &Exchange, requests: usize) -> Value {
+    let mut failed = 0;
+    let mut latencies = (0..requests)
+        .filter_map(|_| {
+            let mut browser = chain.connect(exchange.reception_port());
+            let start = Instant::now();
+            browser.send(&exchange.opening_request);
+            match browser.receive(exchange.opening_response_len, REQUEST_TIMEOUT) {
+                Ok(_) => Some(start.elapsed()),
+                Err(_) => {
+                    failed += 1;
+                    None
+                }
+            }
+        })
+        .collect::<Vec<Duration>>();
+    latencies.sort();
+    json!({
+        "requests": requests,
+        "failed": failed,
+        "p50_ms": millis(percentile(&latencies, 50)),
+        "p90_ms": millis(percentile(&latencies, 90)),
+        "p99_ms": millis(percentile(&latencies, 99)),
+        "max_ms": millis(latencies.last().cloned().unwrap_or_default()),
+    })
+}
+
+// Every client keeps one stream open and sends the next request as soon as it has the last
+// response; a client whose request fails opens a new stream
+fn measure_throughput(chain: &NodeChain, exchange: &Exchange, settings: &Settings) -> Value {
+    let duration = Duration::from_secs(settings.seconds);
+    let start = Instant::now();
+    let counts = (0..settings.clients)
+        .map(|_| {
+            let connector = chain.connector();
+            let exchange = exchange.clone();
+            thread::spawn(move || run_client(&connector, &exchange, start + duration))
+        })
+        .collect::<Vec<JoinHandle<(usize, usize)>>>()
+        .into_iter()
+        .map(|handle| handle.join().expect("Benchmark client panicked"))
+        .collect::<Vec<(usize, usize)>>();
+    let seconds = start.elapsed().as_millis() as f64 / 1000.0;
+    let completed: usize = counts.iter().map(|(completed, _)| completed).sum();
+    let failed: usize = counts.iter().map(|(_, failed)| failed).sum();
+    json!({
+        "clients": settings.clients,
+        "seconds": seconds,
+        "requests": completed,
+        "failed": failed,
+        "requests_per_sec": completed as f64 / seconds,
+        "response_bytes_per_sec": (completed * exchange.response_len) as f64 / seconds,
+    })
+}
+
+fn run_client(connector: &BrowserConnector, exchange: &Exchange, until: Instant) -> (usize, usize) {
+    let mut completed = 0;
+    let mut failed = 0;
+    let mut browser_opt: Option<BrowserStream> = None;
+    while Instant::now() < until {
+        let result = match browser_opt.as_mut() {
+            Some(browser) => {
+                browser.send(&exchange.request);
+                browser.receive(exchange.response_len, REQUEST_TIMEOUT)
+            }
+            None => {
+                let mut browser = connector.connect(exchange.reception_port());
+                browser.send(&exchange.opening_request);
+                let result = browser.receive(exchange.opening_response_len, REQUEST_TIMEOUT);
+                browser_opt = Some(browser);
+                result
+            }
+        };
+        match result {
+            Ok(_) => completed += 1,
+            Err(_) => {
+                failed += 1;
+                browser_opt = None;
+            }
+        }
+    }
+    (completed, failed)
+}
+
+// Nearest-rank percentile of sorted durations; zero if there are none
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::default();
+    }
+    let rank = (sorted.len() * percent + 99) / 100;
+    sorted[rank.max(1) - 1]
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_micros() as f64 / 1000.0
+}
+
+fn localhost() -> IpAddr {
+    IpAddr::V4(Ipv4Addr::LOCALHOST)
+}
+
+fn start_server(
+    port: u16,
+    serve: fn(TcpStream, usize),
+    response_bytes: usize,
+) -> Result<SocketAddr, String> {
+    let listener = TcpListener::bind(SocketAddr::new(localhost(), port))
+        .map_err(|e| format!("Couldn't listen on port {}: {}", port, e))?;
+    let server_addr = listener.local_addr().expect("Server has no address");
+    thread::spawn(move || {
+        for stream in listener.incoming().filter_map(|stream| stream.ok()) {
+            thread::spawn(move || serve(stream, response_bytes));
+        }
+    });
+    Ok(server_addr)
+}
+
+fn http_response(response_bytes: usize) -> Vec<u8> {
+    let mut response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n",
+        response_bytes
+    )
+    .into_bytes();
+    response.extend(vec![b'x'; response_bytes]);
+    response
+}
+
+// Answers every request on a connection with the same response until the connection closes
+fn serve_http(mut stream: TcpStream, response_bytes: usize) {
+    let response = http_response(response_bytes);
+    let mut received = vec![];
+    let mut buf = [0u8; 4096];
+    loop {
+        match stream.read(&mut buf) {
+            Ok(len) if len > 0 => received.extend_from_slice(&buf[..len]),
+            _ => return,
+        }
+        while let Some(end) = find(&received, b"\r\n\r\n") {
+            received.drain(..end + 4);
+            if stream.write_all(&response).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+// Node never decrypts TLS, so neither does this server: it answers each application-data record
+// (and the ClientHello) with response_bytes of application data
+fn serve_tls(mut stream: TcpStream, response_bytes: usize) {
+    let response = tls_records(TLS_APPLICATION_DATA, &vec![0u8; response_bytes]);
+    let mut received = vec![];
+    let mut buf = [0u8; 4096];
+    loop {
+        match stream.read(&mut buf) {
+            Ok(len) if len > 0 => received.extend_from_slice(&buf[..len]),
+            _ => return,
+        }
+        while received.len() >= 5 {
+            let record_len = 5 + ((received[3] as usize) << 8 | received[4] as usize);
+            if received.len() < record_len {
+                break;
+            }
+            received.drain(..record_len);
+            if stream.write_all(&response).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn tls_records(content_type: u8, data: &[u8]) -> Vec<u8> {
+    data.chunks(TLS_MAX_RECORD_LENGTH)
+        .flat_map(|chunk| {
+            let mut record = vec![content_type, 3, 3];
+            record.extend_from_slice(&(chunk.len() as u16).to_be_bytes());
+            record.extend_from_slice(chunk);
+            record
+        })
+        .collect()
+}
+
+// Just enough of a ClientHello for the ProxyServer to find the server name in it
+fn client_hello(server_name: &str) -> Vec<u8> {
+    let name = server_name.as_bytes();
+    let mut server_name_list = vec![0u8];
+    server_name_list.extend_from_slice(&(name.len() as u16).to_be_bytes());
+    server_name_list.extend_from_slice(name);
+    let mut extension = vec![0u8, 0u8];
+    extension.extend_from_slice(&(server_name_list.len() as u16 + 2).to_be_bytes());
+    extension.extend_from_slice(&(server_name_list.len() as u16).to_be_bytes());
+    extension.extend(server_name_list);
+    let mut hello = vec![3u8, 3u8];
+    hello.extend_from_slice(&[0u8; 32]);
+    hello.push(0);
+    hello.extend_from_slice(&[0u8, 2u8, 0x13, 0x01]);
+    hello.extend_from_slice(&[1u8, 0u8]);
+    hello.extend_from_slice(&(extension.len() as u16).to_be_bytes());
+    hello.extend(extension);
+    let mut handshake = vec![1u8];
+    handshake.extend_from_slice(&(hello.len() as u32).to_be_bytes()[1..]);
+    handshake.extend(hello);
+    tls_records(TLS_HANDSHAKE, &handshake)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
@@ -0,0 +1,16 @@
+#!/bin/bash -xev
+# Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+# usage: ci/benchmark.sh [--output results.json] [other end_to_end options]
+CI_DIR="$( cd "$( dirname "$0" )" && pwd )"
+
+pushd "$CI_DIR/.."
+case "$OSTYPE" in
+    msys)
+        cargo bench --bench end_to_end -- "$@"
+        ;;
+    *)
+        # The TLS server the exit Node connects to has to listen on port 443
+        sudo --preserve-env env "PATH=$PATH" cargo bench --bench end_to_end -- "$@"
+        ;;
+esac
+popd
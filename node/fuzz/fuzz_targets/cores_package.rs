@@ -0,0 +1,19 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use node_lib::hopper::live_cores_package::LiveCoresPackage;
+use node_lib::sub_lib::cryptde::{CryptDE, CryptData, PublicKey};
+use node_lib::sub_lib::cryptde_null::CryptDENull;
+use node_lib::test_utils::DEFAULT_CHAIN_ID;
+
+// CryptDENull "decrypts" by stripping its private key off the front of the data, so prepending
+// that key lets the fuzzer reach the wire format parser and the Route decoding behind it.
+fuzz_target!(|data: &[u8]| {
+    let cryptde = CryptDENull::from(&PublicKey::new(&[0x01]), DEFAULT_CHAIN_ID);
+    let mut framed = cryptde.private_key().as_slice().to_vec();
+    framed.extend_from_slice(data);
+    if let Ok(mut package) = LiveCoresPackage::decode(&cryptde, &CryptData::new(&framed)) {
+        let _ = package.route.next_hop(&cryptde);
+        let _ = package.route.shift(&cryptde);
+    }
+});
@@ -0,0 +1,20 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use node_lib::discriminator::DiscriminatorFactory;
+use node_lib::json_discriminator_factory::JsonDiscriminatorFactory;
+
+// The first byte decides where to split the rest of the input, so that the fuzzer also
+// exercises frames that arrive in pieces.
+fuzz_target!(|data: &[u8]| {
+    if data.is_empty() {
+        return;
+    }
+    let payload = &data[1..];
+    let split = (data[0] as usize).min(payload.len());
+    let mut subject = JsonDiscriminatorFactory::new().make();
+    subject.add_data(&payload[..split]);
+    while subject.take_chunk().is_some() {}
+    subject.add_data(&payload[split..]);
+    while subject.take_chunk().is_some() {}
+});
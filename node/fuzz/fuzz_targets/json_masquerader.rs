@@ -0,0 +1,21 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use node_lib::json_masquerader::JsonMasquerader;
+use node_lib::masquerader::Masquerader;
+
+fuzz_target!(|data: &[u8]| {
+    let subject = JsonMasquerader::new();
+    if let Ok(unmasked) = subject.try_unmask(data) {
+        let remasked = subject
+            .mask(&unmasked.chunk)
+            .expect("Couldn't remask unmasked data");
+        assert_eq!(
+            subject
+                .try_unmask(&remasked)
+                .expect("Couldn't unmask remasked data")
+                .chunk,
+            unmasked.chunk
+        );
+    }
+});
@@ -0,0 +1,148 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::database::dao_utils::to_time_t;
+use crate::database::db_initializer::ConnectionWrapper;
+use crate::sub_lib::wallet::Wallet;
+use rusqlite::types::ToSql;
+use rusqlite::NO_PARAMS;
+use std::time::SystemTime;
+
+// Creditors whose billing claims we've found grossly beyond what we owe them. Unlike a delinquency
+// ban, a dispute runs out on its own.
+pub trait DisputeDao: Send {
+    fn disputes(&self) -> Vec<Wallet>;
+    fn dispute(&self, wallet: &Wallet, expiration: SystemTime);
+    // Removes the disputes that have run out by now, and returns their wallets
+    fn expire(&self, now: SystemTime) -> Vec<Wallet>;
+}
+
+pub struct DisputeDaoReal {
+    conn: Box<dyn ConnectionWrapper>,
+}
+
+impl DisputeDaoReal {
+    pub fn new(conn: Box<dyn ConnectionWrapper>) -> Self {
+        Self { conn }
+    }
+}
+
+impl DisputeDao for DisputeDaoReal {
+    fn disputes(&self) -> Vec<Wallet> {
+        let mut stmt = self
+            .conn
+            .prepare("select wallet_address from dispute")
+            .expect("Failed to prepare a statement");
+        stmt.query_map(NO_PARAMS, |row| row.get(0))
+            .expect("Couldn't retrieve dispute list: database corrupt")
+            .flat_map(|v| v)
+            .collect()
+    }
+
+    // Disputing an already-disputed creditor again starts its dispute over
+    fn dispute(&self, wallet: &Wallet, expiration: SystemTime) {
+        let params: &[&dyn ToSql] = &[&wallet, &to_time_t(expiration)];
+        let result = self.conn.write(&mut |tx| {
+            let mut stmt = tx
+                .prepare(
+                    "insert or replace into dispute (wallet_address, expiration) values (?, ?)",
+                )
+                .expect("Failed to prepare a statement");
+            stmt.execute(params).map(|_| ()).map_err(|e| e.to_string())
+        });
+        if let Err(e) = result {
+            panic!(
+                "Could not dispute billing claim from {} because of database corruption: {}",
+                wallet, e
+            )
+        }
+    }
+
+    fn expire(&self, now: SystemTime) -> Vec<Wallet> {
+        let params: &[&dyn ToSql] = &[&to_time_t(now)];
+        let mut expired = vec![];
+        let result = self.conn.write(&mut |tx| {
+            let mut select = tx
+                .prepare("select wallet_address from dispute where expiration <= ?")
+                .expect("Failed to prepare a statement");
+            expired = select
+                .query_map(params, |row| row.get(0))
+                .map_err(|e| e.to_string())?
+                .flat_map(|v| v)
+                .collect();
+            tx.execute("delete from dispute where expiration <= ?", params)
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        });
+        match result {
+            Ok(()) => expired,
+            Err(e) => panic!(
+                "Could not expire billing-claim disputes because of database corruption: {}",
+                e
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::db_initializer::{DbInitializer, DbInitializerReal};
+    use crate::test_utils::{ensure_node_home_directory_exists, make_wallet, DEFAULT_CHAIN_ID};
+    use std::time::Duration;
+
+    fn make_subject(test_name: &str) -> DisputeDaoReal {
+        let home_dir = ensure_node_home_directory_exists("dispute_dao", test_name);
+        let conn = DbInitializerReal::new()
+            .initialize(&home_dir, DEFAULT_CHAIN_ID)
+            .unwrap();
+        DisputeDaoReal::new(conn)
+    }
+
+    fn sorted(mut wallets: Vec<Wallet>) -> Vec<Wallet> {
+        wallets.sort_by_key(|wallet| wallet.to_string());
+        wallets
+    }
+
+    #[test]
+    fn disputes_are_listed_until_they_expire() {
+        let subject = make_subject("disputes_are_listed_until_they_expire");
+        let now = SystemTime::now();
+        subject.dispute(&make_wallet("early"), now - Duration::from_secs(1));
+        subject.dispute(&make_wallet("on time"), now);
+        subject.dispute(&make_wallet("late"), now + Duration::from_secs(1));
+
+        let before = subject.disputes();
+        let expired = subject.expire(now);
+        let after = subject.disputes();
+
+        assert_eq!(
+            sorted(before),
+            sorted(vec![
+                make_wallet("early"),
+                make_wallet("on time"),
+                make_wallet("late")
+            ])
+        );
+        assert_eq!(
+            sorted(expired),
+            sorted(vec![make_wallet("early"), make_wallet("on time")])
+        );
+        assert_eq!(after, vec![make_wallet("late")]);
+    }
+
+    #[test]
+    fn disputing_again_starts_the_dispute_over() {
+        let subject = make_subject("disputing_again_starts_the_dispute_over");
+        let now = SystemTime::now();
+        subject.dispute(&make_wallet("creditor"), now);
+
+        subject.dispute(&make_wallet("creditor"), now + Duration::from_secs(60));
+
+        assert_eq!(subject.disputes(), vec![make_wallet("creditor")]);
+        assert_eq!(subject.expire(now), vec![]);
+        assert_eq!(
+            subject.expire(now + Duration::from_secs(60)),
+            vec![make_wallet("creditor")]
+        );
+        assert_eq!(subject.disputes(), vec![]);
+    }
+}
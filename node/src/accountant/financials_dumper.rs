@@ -0,0 +1,454 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use crate::accountant::payable_dao::{PayableAccount, PayableDao, PayableDaoReal};
+use crate::accountant::profile_consumption_dao::{
+    ProfileConsumptionDao, ProfileConsumptionDaoReal,
+};
+use crate::accountant::receivable_dao::{ReceivableAccount, ReceivableDao, ReceivableDaoReal};
+use crate::bootstrapper::RealUser;
+use crate::database::db_initializer::{
+    ConnectionWrapper, DbInitializer, DbInitializerReal, DATABASE_FILE,
+};
+use crate::node_configurator::{
+    app_head, chain_arg, data_directory_arg, database_password_opt, encrypt_database_arg,
+    instance_name_arg, make_multi_config, profile_arg, real_user_arg, wallet_password_arg,
+    DATABASE_WALLET_PASSWORD_HELP, ENCRYPTED_DATABASE_HELP,
+};
+use crate::privilege_drop::{PrivilegeDropper, PrivilegeDropperReal};
+use crate::sub_lib::accountant::FinancialsFormat;
+use crate::sub_lib::main_tools::StdStreams;
+use crate::sub_lib::wallet::Wallet;
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::{value_t, App, Arg};
+use serde_json::json;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+const DUMP_FINANCIALS_HELP: &str =
+    "Dump every recorded payable and receivable to stdout, with timestamps and counterpart wallet addresses, \
+     in either csv or json (the default). Payables of consuming profiles with wallets of their own show those \
+     wallets, and what each consuming profile has consumed is listed too. Useful for taxes and bookkeeping.";
+
+const CSV_HEADER: &str = "type,wallet_address,balance_gwub,timestamp,pending_payment_transaction,consuming_wallet_address,profile";
+
+pub fn dump_financials(args: &Vec<String>, streams: &mut StdStreams) -> i32 {
+    let (real_user, data_directory, chain_id, format, password_opt) = distill_args(args);
+    PrivilegeDropperReal::new().drop_privileges(&real_user);
+    let connect = || make_connection(&data_directory, chain_id, password_opt.clone());
+    let payable_dao = PayableDaoReal::new(connect());
+    let profile_payables: Vec<(Wallet, Vec<PayableAccount>)> = payable_dao
+        .profile_wallets()
+        .into_iter()
+        .map(|wallet| {
+            let payables = PayableDaoReal::for_consuming_wallet(connect(), &wallet).payables();
+            (wallet, payables)
+        })
+        .collect();
+    let receivable_dao = ReceivableDaoReal::new(connect());
+    let profile_consumption_dao = ProfileConsumptionDaoReal::new(connect());
+    let financials = render_financials(
+        &payable_dao.payables(),
+        &profile_payables,
+        &receivable_dao.receivables(),
+        &profile_consumption_dao.consumption(),
+        format,
+    );
+    streams
+        .stdout
+        .write_all(financials.as_bytes())
+        .expect("Couldn't write financials to stdout");
+    streams
+        .stdout
+        .flush()
+        .expect("Couldn't flush financials to stdout");
+    0
+}
+
+// profile_payables are the debts of consuming profiles with wallets of their own, and profiles
+// what each consuming profile has consumed
+pub fn render_financials(
+    payables: &[PayableAccount],
+    profile_payables: &[(Wallet, Vec<PayableAccount>)],
+    receivables: &[ReceivableAccount],
+    profiles: &[(String, u64)],
+    format: FinancialsFormat,
+) -> String {
+    let payables: Vec<(Option<&Wallet>, &PayableAccount)> = payables
+        .iter()
+        .map(|account| (None, account))
+        .chain(profile_payables.iter().flat_map(|(wallet, accounts)| {
+            accounts.iter().map(move |account| (Some(wallet), account))
+        }))
+        .collect();
+    match format {
+        FinancialsFormat::Csv => financials_to_csv(&payables, receivables, profiles),
+        FinancialsFormat::Json => financials_to_json(&payables, receivables, profiles),
+    }
+}
+
+fn financials_to_csv(
+    payables: &[(Option<&Wallet>, &PayableAccount)],
+    receivables: &[ReceivableAccount],
+    profiles: &[(String, u64)],
+) -> String {
+    let payable_lines = payables.iter().map(|(consuming_wallet_opt, account)| {
+        format!(
+            "payable,{},{},{},{},{},",
+            account.wallet,
+            account.balance,
+            timestamp_string(account.last_paid_timestamp),
+            account
+                .pending_payment_transaction
+                .map(|transaction| format!("{:#x}", transaction))
+                .unwrap_or_default(),
+            consuming_wallet_opt
+                .map(|wallet| wallet.to_string())
+                .unwrap_or_default()
+        )
+    });
+    let receivable_lines = receivables.iter().map(|account| {
+        format!(
+            "receivable,{},{},{},,,",
+            account.wallet,
+            account.balance,
+            timestamp_string(account.last_received_timestamp)
+        )
+    });
+    let profile_lines = profiles
+        .iter()
+        .map(|(name, consumed)| format!("profile,,{},,,,{}", consumed, name));
+    vec![CSV_HEADER.to_string()]
+        .into_iter()
+        .chain(payable_lines)
+        .chain(receivable_lines)
+        .chain(profile_lines)
+        .map(|line| line + "\n")
+        .collect()
+}
+
+fn financials_to_json(
+    payables: &[(Option<&Wallet>, &PayableAccount)],
+    receivables: &[ReceivableAccount],
+    profiles: &[(String, u64)],
+) -> String {
+    let value = json!({
+        "payables": payables.iter().map(|(consuming_wallet_opt, account)| json!({
+            "walletAddress": account.wallet.to_string(),
+            "balanceGwub": account.balance,
+            "lastPaidTimestamp": timestamp_string(account.last_paid_timestamp),
+            "pendingPaymentTransaction": account
+                .pending_payment_transaction
+                .map(|transaction| format!("{:#x}", transaction)),
+            "consumingWalletAddress": consuming_wallet_opt.map(|wallet| wallet.to_string()),
+        })).collect::<Vec<_>>(),
+        "receivables": receivables.iter().map(|account| json!({
+            "walletAddress": account.wallet.to_string(),
+            "balanceGwub": account.balance,
+            "lastReceivedTimestamp": timestamp_string(account.last_received_timestamp),
+        })).collect::<Vec<_>>(),
+        "profiles": profiles.iter().map(|(name, consumed)| json!({
+            "name": name,
+            "consumedGwub": consumed,
+        })).collect::<Vec<_>>(),
+    });
+    serde_json::to_string_pretty(&value).expect("Couldn't serialize financials to JSON")
+}
+
+fn timestamp_string(timestamp: SystemTime) -> String {
+    DateTime::<Utc>::from(timestamp).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn make_connection(
+    data_directory: &PathBuf,
+    chain_id: u8,
+    password_opt: Option<String>,
+) -> Box<dyn ConnectionWrapper> {
+    DbInitializerReal::with_password_opt(password_opt)
+        .initialize(data_directory, chain_id)
+        .unwrap_or_else(|e| {
+            panic!(
+                "Can't initialize database at {:?}: {:?}",
+                data_directory.join(DATABASE_FILE),
+                e
+            )
+        })
+}
+
+pub fn app() -> App<'static, 'static> {
+    app_head()
+        .about(DUMP_FINANCIALS_HELP)
+        .usage("PrometheusNode dump-financials [csv | json] [OPTIONS]")
+        .arg(
+            Arg::with_name("dump-financials")
+                .long("dump-financials")
+                .value_name("FORMAT")
+                .required(true)
+                .takes_value(true)
+                .min_values(0)
+                .max_values(1)
+                .possible_values(&["csv", "json"])
+                .hidden(true),
+        )
+        .arg(chain_arg())
+        .arg(data_directory_arg())
+        .arg(encrypt_database_arg(ENCRYPTED_DATABASE_HELP))
+        .arg(profile_arg())
+        .arg(instance_name_arg())
+        .arg(real_user_arg())
+        .arg(wallet_password_arg(DATABASE_WALLET_PASSWORD_HELP))
+}
+
+fn distill_args(args: &Vec<String>) -> (RealUser, PathBuf, u8, FinancialsFormat, Option<String>) {
+    let app = app();
+    let multi_config = make_multi_config(&app, args, None);
+    let format = value_m!(multi_config, "dump-financials", FinancialsFormat)
+        .unwrap_or(FinancialsFormat::Json);
+    let (real_user, data_directory, chain_id) =
+        crate::node_configurator::real_user_data_directory_and_chain_id(&multi_config);
+    let password_opt = database_password_opt(&multi_config).unwrap_or_else(|e| panic!("{}", e));
+    (real_user, data_directory, chain_id, format, password_opt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accountant::payable_dao::Payment;
+    use crate::database::dao_utils::from_time_t;
+    use crate::test_utils::{
+        ensure_node_home_directory_exists, make_wallet, ArgsBuilder, FakeStreamHolder,
+        DEFAULT_CHAIN_ID, TEST_DEFAULT_CHAIN_NAME,
+    };
+    use ethereum_types::BigEndianHash;
+    use serde_json::Value;
+    use web3::types::{H256, U256};
+
+    fn make_payables() -> Vec<PayableAccount> {
+        vec![
+            PayableAccount {
+                wallet: make_wallet("payee"),
+                balance: 1234,
+                last_paid_timestamp: from_time_t(1_546_300_800),
+                pending_payment_transaction: Some(H256::from_uint(&U256::from(255))),
+            },
+            PayableAccount {
+                wallet: make_wallet("other payee"),
+                balance: 56,
+                last_paid_timestamp: from_time_t(0),
+                pending_payment_transaction: None,
+            },
+        ]
+    }
+
+    fn make_profile_payables() -> Vec<(Wallet, Vec<PayableAccount>)> {
+        vec![(
+            make_wallet("profile"),
+            vec![PayableAccount {
+                wallet: make_wallet("profile payee"),
+                balance: 78,
+                last_paid_timestamp: from_time_t(0),
+                pending_payment_transaction: None,
+            }],
+        )]
+    }
+
+    fn make_profiles() -> Vec<(String, u64)> {
+        vec![("work".to_string(), 910)]
+    }
+
+    fn make_receivables() -> Vec<ReceivableAccount> {
+        vec![ReceivableAccount {
+            wallet: make_wallet("payer"),
+            balance: 789,
+            last_received_timestamp: from_time_t(1_546_387_200),
+        }]
+    }
+
+    #[test]
+    fn render_financials_as_csv() {
+        let result = render_financials(
+            &make_payables(),
+            &make_profile_payables(),
+            &make_receivables(),
+            &make_profiles(),
+            FinancialsFormat::Csv,
+        );
+
+        assert_eq!(
+            result,
+            format!(
+                "{}\n\
+                 payable,{},1234,2019-01-01T00:00:00Z,0x00000000000000000000000000000000000000000000000000000000000000ff,,\n\
+                 payable,{},56,1970-01-01T00:00:00Z,,,\n\
+                 payable,{},78,1970-01-01T00:00:00Z,,{},\n\
+                 receivable,{},789,2019-01-02T00:00:00Z,,,\n\
+                 profile,,910,,,,work\n",
+                CSV_HEADER,
+                make_wallet("payee"),
+                make_wallet("other payee"),
+                make_wallet("profile payee"),
+                make_wallet("profile"),
+                make_wallet("payer"),
+            )
+        );
+    }
+
+    #[test]
+    fn render_financials_as_json() {
+        let result = render_financials(
+            &make_payables(),
+            &make_profile_payables(),
+            &make_receivables(),
+            &make_profiles(),
+            FinancialsFormat::Json,
+        );
+
+        let actual_value: Value = serde_json::from_str(&result).unwrap();
+        assert_eq!(
+            actual_value,
+            json!({
+                "payables": [
+                    {
+                        "walletAddress": make_wallet("payee").to_string(),
+                        "balanceGwub": 1234,
+                        "lastPaidTimestamp": "2019-01-01T00:00:00Z",
+                        "pendingPaymentTransaction": "0x00000000000000000000000000000000000000000000000000000000000000ff",
+                        "consumingWalletAddress": null,
+                    },
+                    {
+                        "walletAddress": make_wallet("other payee").to_string(),
+                        "balanceGwub": 56,
+                        "lastPaidTimestamp": "1970-01-01T00:00:00Z",
+                        "pendingPaymentTransaction": null,
+                        "consumingWalletAddress": null,
+                    },
+                    {
+                        "walletAddress": make_wallet("profile payee").to_string(),
+                        "balanceGwub": 78,
+                        "lastPaidTimestamp": "1970-01-01T00:00:00Z",
+                        "pendingPaymentTransaction": null,
+                        "consumingWalletAddress": make_wallet("profile").to_string(),
+                    },
+                ],
+                "receivables": [
+                    {
+                        "walletAddress": make_wallet("payer").to_string(),
+                        "balanceGwub": 789,
+                        "lastReceivedTimestamp": "2019-01-02T00:00:00Z",
+                    },
+                ],
+                "profiles": [
+                    {
+                        "name": "work",
+                        "consumedGwub": 910,
+                    },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn render_financials_with_nothing_recorded() {
+        assert_eq!(
+            render_financials(&[], &[], &[], &[], FinancialsFormat::Csv),
+            format!("{}\n", CSV_HEADER)
+        );
+        assert_eq!(
+            serde_json::from_str::<Value>(&render_financials(
+                &[],
+                &[],
+                &[],
+                &[],
+                FinancialsFormat::Json
+            ))
+            .unwrap(),
+            json!({"payables": [], "receivables": [], "profiles": []})
+        );
+    }
+
+    #[test]
+    fn dump_financials_dumps_existing_database_as_csv() {
+        let data_dir = ensure_node_home_directory_exists(
+            "financials_dumper",
+            "dump_financials_dumps_existing_database_as_csv",
+        )
+        .join("Substratum")
+        .join(TEST_DEFAULT_CHAIN_NAME);
+        {
+            let connect = || {
+                DbInitializerReal::new()
+                    .initialize(&data_dir, DEFAULT_CHAIN_ID)
+                    .unwrap()
+            };
+            let mut payable_dao = PayableDaoReal::new(connect());
+            payable_dao.payments_sent(&[Payment {
+                to: make_wallet("payee"),
+                amount: 100,
+                timestamp: from_time_t(1_546_300_800),
+                transaction: H256::from_uint(&U256::from(255)),
+            }]);
+            PayableDaoReal::for_consuming_wallet(connect(), &make_wallet("profile")).payments_sent(
+                &[Payment {
+                    to: make_wallet("profile payee"),
+                    amount: 42,
+                    timestamp: from_time_t(0),
+                    transaction: H256::from_uint(&U256::from(254)),
+                }],
+            );
+            ProfileConsumptionDaoReal::new(connect()).add_consumption("work", 42);
+        }
+        let mut holder = FakeStreamHolder::new();
+
+        let result = dump_financials(
+            &ArgsBuilder::new()
+                .param("--data-directory", data_dir.to_str().unwrap())
+                .param("--real-user", "123::")
+                .param("--chain", TEST_DEFAULT_CHAIN_NAME)
+                .param("--dump-financials", "csv")
+                .into(),
+            &mut holder.streams(),
+        );
+
+        assert_eq!(result, 0);
+        assert_eq!(
+            holder.stdout.get_string(),
+            format!(
+                "{}\n\
+                 payable,{},-100,2019-01-01T00:00:00Z,0x00000000000000000000000000000000000000000000000000000000000000ff,,\n\
+                 payable,{},-42,1970-01-01T00:00:00Z,0x00000000000000000000000000000000000000000000000000000000000000fe,{},\n\
+                 profile,,42,,,,work\n",
+                CSV_HEADER,
+                make_wallet("payee"),
+                make_wallet("profile payee"),
+                make_wallet("profile")
+            )
+        );
+    }
+
+    #[test]
+    fn dump_financials_defaults_to_json() {
+        let data_dir = ensure_node_home_directory_exists(
+            "financials_dumper",
+            "dump_financials_defaults_to_json",
+        )
+        .join("Substratum")
+        .join(TEST_DEFAULT_CHAIN_NAME);
+        let mut holder = FakeStreamHolder::new();
+
+        let result = dump_financials(
+            &ArgsBuilder::new()
+                .param("--data-directory", data_dir.to_str().unwrap())
+                .param("--real-user", "123::")
+                .param("--chain", TEST_DEFAULT_CHAIN_NAME)
+                .opt("--dump-financials")
+                .into(),
+            &mut holder.streams(),
+        );
+
+        assert_eq!(result, 0);
+        let actual_value: Value = serde_json::from_str(&holder.stdout.get_string()).unwrap();
+        assert_eq!(
+            actual_value,
+            json!({"payables": [], "receivables": [], "profiles": []})
+        );
+    }
+}
@@ -1,27 +1,50 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
+pub mod dispute_dao;
+pub mod financials_dumper;
 pub mod payable_dao;
+pub mod payment_confirmations;
+pub mod profile_consumption_dao;
 pub mod receivable_dao;
 
 #[cfg(test)]
 pub mod test_utils;
 
+use crate::accountant::dispute_dao::DisputeDao;
 use crate::accountant::payable_dao::{PayableAccount, Payment};
+use crate::accountant::profile_consumption_dao::ProfileConsumptionDao;
 use crate::accountant::receivable_dao::ReceivableAccount;
+use crate::audit_log::{audit, AuditSource};
 use crate::banned_dao::BannedDao;
-use crate::blockchain::blockchain_bridge::RetrieveTransactions;
-use crate::blockchain::blockchain_interface::{BlockchainError, Transaction};
+use crate::blockchain::blockchain_bridge::{RetrieveTransactions, RetrievedTransactions};
+use crate::blockchain::blockchain_interface::BlockchainError;
 use crate::bootstrapper::BootstrapperConfig;
 use crate::persistent_configuration::PersistentConfiguration;
+use crate::sub_lib::accountant::DumpFinancialsMessage;
+use crate::sub_lib::accountant::NewBlockMessage;
+use crate::sub_lib::accountant::ReportBillingClaimMessage;
 use crate::sub_lib::accountant::ReportExitServiceConsumedMessage;
 use crate::sub_lib::accountant::ReportExitServiceProvidedMessage;
 use crate::sub_lib::accountant::ReportRoutingServiceConsumedMessage;
 use crate::sub_lib::accountant::ReportRoutingServiceProvidedMessage;
+use crate::sub_lib::accountant::PAYMENT_PARAMETERS;
 use crate::sub_lib::accountant::{AccountantConfig, GetFinancialStatisticsMessage};
 use crate::sub_lib::accountant::{AccountantSubs, FinancialStatisticsMessage};
+use crate::sub_lib::accountant::{ConsumingProfile, ProfileFinancialStatistics};
+use crate::sub_lib::accountant::{PaymentDeferral, PaymentParameter, SetPaymentParameterMessage};
 use crate::sub_lib::blockchain_bridge::ReportAccountsPayable;
+use crate::sub_lib::clock::{Clock, ClockReal};
+use crate::sub_lib::crash_context::handling;
 use crate::sub_lib::logger::Logger;
+use crate::sub_lib::neighborhood::{DelinquencyMessage, DisputeMessage};
 use crate::sub_lib::peer_actors::{BindMessage, StartMessage};
+use crate::sub_lib::proxy_client::{BillingClaim, BillingClaimsMessage};
+use crate::sub_lib::proxy_server::ConsumingProfileExhaustedMessage;
+use crate::sub_lib::ui_gateway::{ChangeConfigurationMessage, ConfigurationEntry, NodeEvent};
+use crate::sub_lib::ui_gateway::{
+    ComponentHealth, GetConfigurationMessage, GetHealthMessage, GetNodeStatisticsMessage,
+    HealthState, NodeStatistics,
+};
 use crate::sub_lib::ui_gateway::{UiCarrierMessage, UiMessage};
 use crate::sub_lib::utils::NODE_MAILBOX_CAPACITY;
 use crate::sub_lib::wallet::Wallet;
@@ -31,21 +54,40 @@ use actix::AsyncContext;
 use actix::Context;
 use actix::Handler;
 use actix::Message;
+use actix::MessageResult;
 use actix::Recipient;
 use futures::future::Future;
 use lazy_static::lazy_static;
 use payable_dao::PayableDao;
+use payment_confirmations::{ReceivedPaymentTracker, DEFAULT_PAYMENT_CONFIRMATIONS};
 use receivable_dao::ReceivableDao;
+use std::cmp::max;
+use std::collections::VecDeque;
+use std::str::FromStr;
 use std::thread;
 use std::time::{Duration, SystemTime};
+use web3::types::H256;
 
 pub const DEFAULT_PAYABLE_SCAN_INTERVAL: u64 = 3600; // one hour
 pub const DEFAULT_PAYMENT_RECEIVED_SCAN_INTERVAL: u64 = 3600; // one hour
 
 const SECONDS_PER_DAY: i64 = 86_400;
 
+// How far a creditor's billing claim may exceed what we think we owe it before we complain...
+const BILLING_CLAIM_TOLERANCE_PERCENT: u64 = 10;
+// ...and before we dispute it and stop routing through it until the dispute runs out.
+const BILLING_CLAIM_DISPUTE_PERCENT: u64 = 50;
+const BILLING_CLAIM_DISPUTE_PERIOD: Duration = Duration::from_secs(7 * SECONDS_PER_DAY as u64);
+// Creditors only notice our payments once they're confirmed and scanned for, so until then their
+// claims still include what we've just paid.
+const BILLING_CLAIM_SETTLING_PERIOD: Duration =
+    Duration::from_secs(2 * DEFAULT_PAYMENT_RECEIVED_SCAN_INTERVAL);
+// How far back the "recent" relayed byte count in the Node statistics looks
+const RECENTLY_RELAYED_MINUTES: u64 = 60;
+
+// These are only the defaults: see PaymentParameter for how they can be changed.
 lazy_static! {
-    pub static ref PAYMENT_CURVES: PaymentCurves = PaymentCurves {
+    pub static ref DEFAULT_PAYMENT_CURVES: PaymentCurves = PaymentCurves {
         payment_suggested_after_sec: SECONDS_PER_DAY,
         payment_grace_before_ban_sec: SECONDS_PER_DAY,
         permanent_debt_allowed_gwub: 10_000_000,
@@ -73,21 +115,61 @@ impl PaymentCurves {
     pub fn sugg_thru_decreasing(&self, now: i64) -> i64 {
         self.sugg_and_grace(now) - self.balance_decreases_for_sec
     }
+
+    // The payout threshold slopes down from balance_to_decrease_from_gwub to
+    // permanent_debt_allowed_gwub between payment_suggested_after_sec and balance_decreases_for_sec;
+    // parameters that are individually in range can still combine to turn that slope upside down.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.balance_to_decrease_from_gwub <= self.permanent_debt_allowed_gwub {
+            return Err(format!(
+                "balance-to-decrease-from ({}) must be greater than permanent-debt-allowed ({})",
+                self.balance_to_decrease_from_gwub, self.permanent_debt_allowed_gwub
+            ));
+        }
+        if self.balance_decreases_for_sec <= self.payment_suggested_after_sec {
+            return Err(format!(
+                "balance-decreases-for ({}) must be greater than payment-suggested-after ({})",
+                self.balance_decreases_for_sec, self.payment_suggested_after_sec
+            ));
+        }
+        Ok(())
+    }
 }
 
 pub struct Accountant {
     config: AccountantConfig,
     consuming_wallet: Option<Wallet>,
     earning_wallet: Wallet,
+    exit_earning_wallet_opt: Option<Wallet>,
+    consuming_profiles: Vec<ConsumingProfile>,
     payable_dao: Box<dyn PayableDao>,
+    // Debts incurred by consuming profiles that pay with wallets of their own
+    profile_payable_daos: Vec<(Wallet, Box<dyn PayableDao>)>,
     receivable_dao: Box<dyn ReceivableDao>,
     banned_dao: Box<dyn BannedDao>,
+    dispute_dao: Box<dyn DisputeDao>,
+    profile_consumption_dao: Box<dyn ProfileConsumptionDao>,
     persistent_configuration: Box<dyn PersistentConfiguration>,
+    received_payment_tracker: ReceivedPaymentTracker,
+    // When the blockchain service last told us about a new block, if it ever has
+    new_block_noticed: Option<SystemTime>,
+    // Why the payments of the last payable scan were held back, if they were
+    payments_deferred: Option<PaymentDeferral>,
     report_accounts_payable_sub: Option<Recipient<ReportAccountsPayable>>,
     retrieve_transactions_sub: Option<Recipient<RetrieveTransactions>>,
     report_new_payments_sub: Option<Recipient<ReceivedPayments>>,
     report_sent_payments_sub: Option<Recipient<SentPayments>>,
     ui_carrier_message_sub: Option<Recipient<UiCarrierMessage>>,
+    delinquency_sub: Option<Recipient<DelinquencyMessage>>,
+    dispute_sub: Option<Recipient<DisputeMessage>>,
+    billing_claims_sub: Option<Recipient<BillingClaimsMessage>>,
+    node_event_sub: Option<Recipient<NodeEvent>>,
+    profile_exhausted_sub: Option<Recipient<ConsumingProfileExhaustedMessage>>,
+    // Services provided since startup, for the Node statistics
+    bytes_relayed: u64,
+    bytes_relayed_by_minute: VecDeque<(u64, u64)>,
+    exit_bytes_served: u64,
+    clock: Box<dyn Clock>,
     logger: Logger,
 }
 
@@ -97,18 +179,32 @@ impl Actor for Accountant {
 
 #[derive(Debug, Eq, Message, PartialEq)]
 pub struct ReceivedPayments {
-    payments: Vec<Transaction>,
+    recipient: Wallet,
+    retrieved_transactions: RetrievedTransactions,
 }
 
+// profile_wallet_opt is the consuming profile wallet the payments were made from, if they
+// weren't made from the Node's own consuming wallet
 #[derive(Debug, Eq, Message, PartialEq)]
 pub struct SentPayments {
     pub payments: Vec<Result<Payment, BlockchainError>>,
+    pub profile_wallet_opt: Option<Wallet>,
+}
+
+// A sent payment that didn't simply go through as recorded: either a replacement transaction got
+// mined instead of the original, or it was cancelled and never paid at all.
+#[derive(Debug, Eq, Message, PartialEq)]
+pub struct ResolvedPayment {
+    pub payment: Payment,
+    pub mined_transaction: Option<H256>,
+    pub profile_wallet_opt: Option<Wallet>,
 }
 
 impl Handler<BindMessage> for Accountant {
     type Result = ();
 
     fn handle(&mut self, msg: BindMessage, ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Accountant", "BindMessage");
         self.report_accounts_payable_sub =
             Some(msg.peer_actors.blockchain_bridge.report_accounts_payable);
         self.retrieve_transactions_sub =
@@ -116,6 +212,12 @@ impl Handler<BindMessage> for Accountant {
         self.report_new_payments_sub = Some(msg.peer_actors.accountant.report_new_payments);
         self.report_sent_payments_sub = Some(msg.peer_actors.accountant.report_sent_payments);
         self.ui_carrier_message_sub = Some(msg.peer_actors.ui_gateway.ui_message_sub.clone());
+        self.delinquency_sub = Some(msg.peer_actors.neighborhood.delinquency_sub.clone());
+        self.dispute_sub = Some(msg.peer_actors.neighborhood.dispute_sub.clone());
+        self.billing_claims_sub = Some(msg.peer_actors.proxy_client.billing_claims.clone());
+        self.node_event_sub = Some(msg.peer_actors.ui_gateway.node_event_sub.clone());
+        self.profile_exhausted_sub =
+            Some(msg.peer_actors.proxy_server.profile_exhausted_sub.clone());
         ctx.set_mailbox_capacity(NODE_MAILBOX_CAPACITY);
 
         info!(self.logger, "Accountant bound");
@@ -126,21 +228,18 @@ impl Handler<StartMessage> for Accountant {
     type Result = ();
 
     fn handle(&mut self, _msg: StartMessage, ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Accountant", "StartMessage");
+        self.announce_existing_bans();
+        self.expire_disputes();
+        self.announce_existing_disputes();
+        self.announce_exhausted_profiles();
         self.scan_for_payables();
-        self.scan_for_received_payments();
+        self.scan_for_received_payments(None);
         self.scan_for_delinquencies();
+        self.send_billing_claims();
 
-        ctx.run_interval(self.config.payable_scan_interval, |accountant, _ctx| {
-            accountant.scan_for_payables();
-        });
-
-        ctx.run_interval(
-            self.config.payment_received_scan_interval,
-            |accountant, _ctx| {
-                accountant.scan_for_received_payments();
-                accountant.scan_for_delinquencies();
-            },
-        );
+        Self::schedule_payable_scan(ctx, self.config.payable_scan_interval);
+        Self::schedule_payment_received_scan(ctx, self.config.payment_received_scan_interval);
     }
 }
 
@@ -152,10 +251,52 @@ impl Handler<ReceivedPayments> for Accountant {
         received_payments: ReceivedPayments,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
-        self.receivable_dao.as_mut().more_money_received(
-            self.persistent_configuration.as_ref(),
-            received_payments.payments,
+        let _handling = handling("Accountant", "ReceivedPayments");
+        self.received_payment_tracker.process(
+            &received_payments.recipient,
+            received_payments.retrieved_transactions,
         );
+        let start_block = self.persistent_configuration.start_block();
+        let (payments, next_start_block) = self
+            .received_payment_tracker
+            .settle(&self.earning_wallets(), start_block);
+        payments.iter().for_each(|payment| {
+            self.publish_event(NodeEvent::PaymentReceived {
+                wallet: payment.from.to_string(),
+                amount: payment.gwei_amount,
+                transaction: format!("{:?}", payment.transaction_hash),
+            })
+        });
+        if !payments.is_empty() {
+            self.receivable_dao.as_mut().more_money_received(
+                self.persistent_configuration.as_ref(),
+                payments,
+                next_start_block,
+            );
+        } else if next_start_block > start_block {
+            self.persistent_configuration
+                .set_start_block(next_start_block);
+        }
+    }
+}
+
+impl Handler<NewBlockMessage> for Accountant {
+    type Result = ();
+
+    fn handle(&mut self, msg: NewBlockMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Accountant", "NewBlockMessage");
+        debug!(self.logger, "New block {} mined", msg.block_number);
+        self.new_block_noticed = Some(self.clock.now());
+        self.scan_for_received_payments(Some(msg.block_number));
+    }
+}
+
+impl Handler<ReportBillingClaimMessage> for Accountant {
+    type Result = ();
+
+    fn handle(&mut self, msg: ReportBillingClaimMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Accountant", "ReportBillingClaimMessage");
+        self.reconcile_billing_claim(msg);
     }
 }
 
@@ -163,17 +304,72 @@ impl Handler<SentPayments> for Accountant {
     type Result = ();
 
     fn handle(&mut self, sent_payments: SentPayments, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Accountant", "SentPayments");
+        let mut batch = vec![];
+        let mut deferral = None;
         sent_payments
             .payments
-            .iter()
+            .into_iter()
             .for_each(|payment| match payment {
-                Ok(payment) => self.payable_dao.as_mut().payment_sent(payment),
+                Ok(payment) => batch.push(payment),
+                // The BlockchainBridge has already explained these
+                Err(BlockchainError::FeeCapExceeded { .. }) => {
+                    deferral = Some(PaymentDeferral::FeeCap)
+                }
+                Err(BlockchainError::FeeBudgetExceeded { .. }) => {
+                    deferral = Some(PaymentDeferral::FeeBudget)
+                }
                 Err(e) => warning!(
                     self.logger,
                     "{} Please check your blockchain service URL configuration.",
                     e
                 ),
+            });
+        self.payments_deferred = deferral;
+        batch.iter().for_each(|payment| {
+            self.publish_event(NodeEvent::PaymentSent {
+                wallet: payment.to.to_string(),
+                amount: payment.amount,
+                transaction: format!("{:?}", payment.transaction),
             })
+        });
+        if !batch.is_empty() {
+            self.payable_dao_for_mut(&sent_payments.profile_wallet_opt)
+                .payments_sent(&batch)
+        }
+    }
+}
+
+impl Handler<ResolvedPayment> for Accountant {
+    type Result = ();
+
+    fn handle(&mut self, msg: ResolvedPayment, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Accountant", "ResolvedPayment");
+        match msg.mined_transaction {
+            Some(transaction) if transaction == msg.payment.transaction => (),
+            Some(transaction) => {
+                info!(
+                    self.logger,
+                    "Payment of {} to {} was made by replacement transaction {:?}",
+                    msg.payment.amount,
+                    msg.payment.to,
+                    transaction
+                );
+                self.payable_dao_for(&msg.profile_wallet_opt)
+                    .payment_replaced(&msg.payment, transaction)
+            }
+            None => {
+                warning!(
+                    self.logger,
+                    "Payment of {} to {} in transaction {:?} was cancelled; it will be retried",
+                    msg.payment.amount,
+                    msg.payment.to,
+                    msg.payment.transaction
+                );
+                self.payable_dao_for(&msg.profile_wallet_opt)
+                    .payment_cancelled(&msg.payment)
+            }
+        }
     }
 }
 
@@ -185,10 +381,12 @@ impl Handler<ReportRoutingServiceProvidedMessage> for Accountant {
         msg: ReportRoutingServiceProvidedMessage,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
+        let _handling = handling("Accountant", "ReportRoutingServiceProvidedMessage");
         debug!(
             self.logger,
             "Charging routing of {} bytes to wallet {}", msg.payload_size, msg.paying_wallet
         );
+        self.count_bytes_relayed(msg.payload_size, self.clock.now());
         self.record_service_provided(
             msg.service_rate,
             msg.byte_rate,
@@ -206,6 +404,7 @@ impl Handler<ReportExitServiceProvidedMessage> for Accountant {
         msg: ReportExitServiceProvidedMessage,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
+        let _handling = handling("Accountant", "ReportExitServiceProvidedMessage");
         debug!(
             self.logger,
             "Charging exit service for {} bytes to wallet {} at {} per service and {} per byte",
@@ -214,6 +413,7 @@ impl Handler<ReportExitServiceProvidedMessage> for Accountant {
             msg.service_rate,
             msg.byte_rate
         );
+        self.exit_bytes_served += msg.payload_size as u64;
         self.record_service_provided(
             msg.service_rate,
             msg.byte_rate,
@@ -231,6 +431,7 @@ impl Handler<ReportRoutingServiceConsumedMessage> for Accountant {
         msg: ReportRoutingServiceConsumedMessage,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
+        let _handling = handling("Accountant", "ReportRoutingServiceConsumedMessage");
         debug!(
             self.logger,
             "Accruing debt to wallet {} for consuming routing service {} bytes",
@@ -242,6 +443,7 @@ impl Handler<ReportRoutingServiceConsumedMessage> for Accountant {
             msg.byte_rate,
             msg.payload_size,
             &msg.earning_wallet,
+            msg.profile_opt,
         );
     }
 }
@@ -254,6 +456,7 @@ impl Handler<ReportExitServiceConsumedMessage> for Accountant {
         msg: ReportExitServiceConsumedMessage,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
+        let _handling = handling("Accountant", "ReportExitServiceConsumedMessage");
         debug!(
             self.logger,
             "Accruing debt to wallet {} for consuming exit service {} bytes",
@@ -265,10 +468,42 @@ impl Handler<ReportExitServiceConsumedMessage> for Accountant {
             msg.byte_rate,
             msg.payload_size,
             &msg.earning_wallet,
+            msg.profile_opt,
         );
     }
 }
 
+impl Handler<GetNodeStatisticsMessage> for Accountant {
+    type Result = MessageResult<GetNodeStatisticsMessage>;
+
+    fn handle(&mut self, _msg: GetNodeStatisticsMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Accountant", "GetNodeStatisticsMessage");
+        MessageResult(NodeStatistics {
+            bytes_relayed: self.bytes_relayed,
+            bytes_relayed_last_hour: self.bytes_relayed_recently(self.clock.now()),
+            exit_bytes_served: self.exit_bytes_served,
+            ..NodeStatistics::default()
+        })
+    }
+}
+
+impl Handler<GetHealthMessage> for Accountant {
+    type Result = MessageResult<GetHealthMessage>;
+
+    fn handle(&mut self, _msg: GetHealthMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Accountant", "GetHealthMessage");
+        let health = match self.persistent_configuration.check_writable() {
+            Ok(()) => ComponentHealth::new("database", HealthState::Ready, "writable"),
+            Err(e) => ComponentHealth::new(
+                "database",
+                HealthState::NotReady,
+                &format!("not writable: {}", e),
+            ),
+        };
+        MessageResult(vec![health])
+    }
+}
+
 impl Handler<GetFinancialStatisticsMessage> for Accountant {
     type Result = ();
 
@@ -277,6 +512,7 @@ impl Handler<GetFinancialStatisticsMessage> for Accountant {
         msg: GetFinancialStatisticsMessage,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
+        let _handling = handling("Accountant", "GetFinancialStatisticsMessage");
         let pending_credit = self
             .receivable_dao
             .receivables()
@@ -284,9 +520,8 @@ impl Handler<GetFinancialStatisticsMessage> for Accountant {
             .map(|account| account.balance)
             .sum();
         let pending_debt = self
-            .payable_dao
-            .non_pending_payables()
-            .into_iter()
+            .payable_daos()
+            .flat_map(|payable_dao| payable_dao.non_pending_payables())
             .map(|account| account.balance)
             .sum();
         self.ui_carrier_message_sub
@@ -297,37 +532,188 @@ impl Handler<GetFinancialStatisticsMessage> for Accountant {
                 data: UiMessage::FinancialStatisticsResponse(FinancialStatisticsMessage {
                     pending_credit,
                     pending_debt,
+                    profiles: self.profile_financial_statistics(),
+                    payments_deferred: self.payments_deferred,
                 }),
             })
             .expect("UiGateway is dead");
     }
 }
 
+impl Handler<SetPaymentParameterMessage> for Accountant {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: SetPaymentParameterMessage,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let _handling = handling("Accountant", "SetPaymentParameterMessage");
+        let accepted = match self.set_payment_parameter(msg.parameter, msg.value) {
+            Ok(()) => {
+                info!(
+                    self.logger,
+                    "Payment parameter {} set to {}",
+                    msg.parameter.name(),
+                    msg.value
+                );
+                true
+            }
+            Err(e) => {
+                warning!(self.logger, "Payment parameter not changed: {}", e);
+                false
+            }
+        };
+        self.ui_carrier_message_sub
+            .as_ref()
+            .expect("UiGateway is unbound")
+            .try_send(UiCarrierMessage {
+                client_id: msg.client_id,
+                data: UiMessage::SetPaymentParameterResponse(accepted),
+            })
+            .expect("UiGateway is dead");
+    }
+}
+
+impl Handler<GetConfigurationMessage> for Accountant {
+    type Result = MessageResult<GetConfigurationMessage>;
+
+    fn handle(&mut self, _msg: GetConfigurationMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Accountant", "GetConfigurationMessage");
+        MessageResult(
+            PAYMENT_PARAMETERS
+                .iter()
+                .map(|parameter| {
+                    ConfigurationEntry::new(
+                        parameter.name(),
+                        &self.config.payment_parameter(*parameter).to_string(),
+                    )
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Handler<ChangeConfigurationMessage> for Accountant {
+    type Result = MessageResult<ChangeConfigurationMessage>;
+
+    fn handle(
+        &mut self,
+        msg: ChangeConfigurationMessage,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let _handling = handling("Accountant", "ChangeConfigurationMessage");
+        let parameter = match PaymentParameter::from_str(&msg.name) {
+            Ok(parameter) => parameter,
+            Err(_) => return MessageResult(None),
+        };
+        let result = match msg.value.parse::<u64>() {
+            Ok(value) => self.set_payment_parameter(parameter, value),
+            Err(_) => Err(format!(
+                "{} must be a whole number, not '{}'",
+                parameter.name(),
+                msg.value
+            )),
+        };
+        match &result {
+            Ok(()) => info!(
+                self.logger,
+                "Payment parameter {} set to {}",
+                parameter.name(),
+                msg.value
+            ),
+            Err(e) => warning!(self.logger, "Payment parameter not changed: {}", e),
+        }
+        MessageResult(Some(result))
+    }
+}
+
+impl Handler<DumpFinancialsMessage> for Accountant {
+    type Result = ();
+
+    fn handle(&mut self, msg: DumpFinancialsMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Accountant", "DumpFinancialsMessage");
+        let profile_payables: Vec<(Wallet, Vec<PayableAccount>)> = self
+            .profile_payable_daos
+            .iter()
+            .map(|(wallet, payable_dao)| (wallet.clone(), payable_dao.payables()))
+            .collect();
+        let financials = financials_dumper::render_financials(
+            &self.payable_dao.payables(),
+            &profile_payables,
+            &self.receivable_dao.receivables(),
+            &self.profile_consumption_dao.consumption(),
+            msg.format,
+        );
+        self.ui_carrier_message_sub
+            .as_ref()
+            .expect("UiGateway is unbound")
+            .try_send(UiCarrierMessage {
+                client_id: msg.client_id,
+                data: UiMessage::DumpFinancialsResponse(financials),
+            })
+            .expect("UiGateway is dead");
+    }
+}
+
 impl Accountant {
     pub fn new(
         config: &BootstrapperConfig,
         payable_dao: Box<dyn PayableDao>,
         receivable_dao: Box<dyn ReceivableDao>,
         banned_dao: Box<dyn BannedDao>,
+        dispute_dao: Box<dyn DisputeDao>,
+        profile_consumption_dao: Box<dyn ProfileConsumptionDao>,
         persistent_configuration: Box<dyn PersistentConfiguration>,
     ) -> Accountant {
         Accountant {
             config: config.accountant_config.clone(),
             consuming_wallet: config.consuming_wallet.clone(),
             earning_wallet: config.earning_wallet.clone(),
+            exit_earning_wallet_opt: config.exit_earning_wallet_opt.clone(),
+            consuming_profiles: config.consuming_profiles.clone(),
             payable_dao,
+            profile_payable_daos: vec![],
             receivable_dao,
             banned_dao,
+            dispute_dao,
+            profile_consumption_dao,
             persistent_configuration,
+            received_payment_tracker: ReceivedPaymentTracker::new(DEFAULT_PAYMENT_CONFIRMATIONS),
+            new_block_noticed: None,
+            payments_deferred: None,
             report_accounts_payable_sub: None,
             retrieve_transactions_sub: None,
             report_new_payments_sub: None,
             report_sent_payments_sub: None,
             ui_carrier_message_sub: None,
+            delinquency_sub: None,
+            dispute_sub: None,
+            billing_claims_sub: None,
+            node_event_sub: None,
+            profile_exhausted_sub: None,
+            bytes_relayed: 0,
+            bytes_relayed_by_minute: VecDeque::new(),
+            exit_bytes_served: 0,
+            clock: Box::new(ClockReal {}),
             logger: Logger::new("Accountant"),
         }
     }
 
+    pub fn with_clock(mut self, clock: Box<dyn Clock>) -> Accountant {
+        self.clock = clock;
+        self
+    }
+
+    pub fn with_profile_payable_dao(
+        mut self,
+        wallet: Wallet,
+        payable_dao: Box<dyn PayableDao>,
+    ) -> Accountant {
+        self.profile_payable_daos.push((wallet, payable_dao));
+        self
+    }
+
     pub fn make_subs_from(addr: &Addr<Accountant>) -> AccountantSubs {
         AccountantSubs {
             bind: addr.clone().recipient::<BindMessage>(),
@@ -346,19 +732,81 @@ impl Accountant {
                 .recipient::<ReportExitServiceConsumedMessage>(),
             report_new_payments: addr.clone().recipient::<ReceivedPayments>(),
             report_sent_payments: addr.clone().recipient::<SentPayments>(),
+            report_resolved_payment: addr.clone().recipient::<ResolvedPayment>(),
+            report_new_block: addr.clone().recipient::<NewBlockMessage>(),
             get_financial_statistics_sub: addr.clone().recipient::<GetFinancialStatisticsMessage>(),
+            dump_financials_sub: addr.clone().recipient::<DumpFinancialsMessage>(),
+            set_payment_parameter_sub: addr.clone().recipient::<SetPaymentParameterMessage>(),
+            report_billing_claim_sub: addr.clone().recipient::<ReportBillingClaimMessage>(),
+            node_statistics_sub: addr.clone().recipient::<GetNodeStatisticsMessage>(),
+            health_sub: addr.clone().recipient::<GetHealthMessage>(),
+            configuration_sub: addr.clone().recipient::<GetConfigurationMessage>(),
+            change_configuration_sub: addr.clone().recipient::<ChangeConfigurationMessage>(),
         }
     }
 
+    // Each scan schedules the next one itself, rather than using run_interval, so that interval
+    // changes made from the UI take effect without a restart.
+    fn schedule_payable_scan(ctx: &mut Context<Self>, interval: Duration) {
+        ctx.run_later(interval, |accountant, ctx| {
+            accountant.scan_for_payables();
+            Self::schedule_payable_scan(ctx, accountant.config.payable_scan_interval);
+        });
+    }
+
+    fn schedule_payment_received_scan(ctx: &mut Context<Self>, interval: Duration) {
+        ctx.run_later(interval, |accountant, ctx| {
+            if !accountant.following_new_blocks() {
+                accountant.scan_for_received_payments(None);
+            }
+            accountant.scan_for_delinquencies();
+            accountant.expire_disputes();
+            accountant.send_billing_claims();
+            Self::schedule_payment_received_scan(
+                ctx,
+                accountant.config.payment_received_scan_interval,
+            );
+        });
+    }
+
+    fn set_payment_parameter(
+        &mut self,
+        parameter: PaymentParameter,
+        value: u64,
+    ) -> Result<(), String> {
+        parameter.validate(value)?;
+        let mut config = self.config.clone();
+        config.set_payment_parameter(parameter, value);
+        config.payment_curves.validate()?;
+        self.persistent_configuration
+            .set_payment_parameter(parameter, value);
+        self.config = config;
+        Ok(())
+    }
+
+    // Each consuming profile wallet pays its own debts
     fn scan_for_payables(&mut self) {
         debug!(self.logger, "Scanning for payables");
+        let profile_wallets: Vec<Option<Wallet>> = self
+            .profile_payable_daos
+            .iter()
+            .map(|(wallet, _)| Some(wallet.clone()))
+            .collect();
+        vec![None]
+            .into_iter()
+            .chain(profile_wallets)
+            .for_each(|profile_wallet_opt| self.scan_for_payables_of(profile_wallet_opt));
+    }
+
+    fn scan_for_payables_of(&self, profile_wallet_opt: Option<Wallet>) {
         let future_logger = self.logger.clone();
 
+        let now = self.clock.now();
         let payables = self
-            .payable_dao
+            .payable_dao_for(&profile_wallet_opt)
             .non_pending_payables()
             .into_iter()
-            .filter(Accountant::should_pay)
+            .filter(|payable| Self::should_pay(payable, &self.config.payment_curves, now))
             .collect::<Vec<PayableAccount>>();
 
         if !payables.is_empty() {
@@ -367,12 +815,18 @@ impl Accountant {
                 .report_accounts_payable_sub
                 .as_ref()
                 .expect("BlockchainBridge is unbound")
-                .send(ReportAccountsPayable { accounts: payables })
+                .send(ReportAccountsPayable {
+                    accounts: payables,
+                    profile_wallet_opt: profile_wallet_opt.clone(),
+                })
                 .then(move |results| match results {
                     Ok(Ok(results)) => {
                         report_sent_payments
                             .expect("Accountant is unbound")
-                            .try_send(SentPayments { payments: results })
+                            .try_send(SentPayments {
+                                payments: results,
+                                profile_wallet_opt,
+                            })
                             .expect("Accountant is dead");
                         Ok(())
                     }
@@ -396,12 +850,21 @@ impl Accountant {
     fn scan_for_delinquencies(&mut self) {
         debug!(self.logger, "Scanning for delinquencies");
 
-        let now = SystemTime::now();
+        let now = self.clock.now();
         self.receivable_dao
-            .new_delinquencies(now, &PAYMENT_CURVES)
+            .new_delinquencies(now, &self.config.payment_curves)
             .into_iter()
             .for_each(|account| {
                 self.banned_dao.ban(&account.wallet);
+                audit(
+                    AuditSource::Node,
+                    "ban",
+                    &format!("Wallet {} banned for delinquency", account.wallet),
+                );
+                self.send_delinquency_message(DelinquencyMessage::Ban(account.wallet.clone()));
+                self.publish_event(NodeEvent::DelinquencyBan {
+                    wallet: account.wallet.to_string(),
+                });
                 let (balance, age) = Self::balance_and_age(&account);
                 info!(
                     self.logger,
@@ -413,10 +876,16 @@ impl Accountant {
             });
 
         self.receivable_dao
-            .paid_delinquencies(&PAYMENT_CURVES)
+            .paid_delinquencies(&self.config.payment_curves)
             .into_iter()
             .for_each(|account| {
                 self.banned_dao.unban(&account.wallet);
+                audit(
+                    AuditSource::Node,
+                    "unban",
+                    &format!("Wallet {} is no longer delinquent", account.wallet),
+                );
+                self.send_delinquency_message(DelinquencyMessage::Unban(account.wallet.clone()));
                 let (balance, age) = Self::balance_and_age(&account);
                 info!(
                     self.logger,
@@ -428,32 +897,232 @@ impl Accountant {
             });
     }
 
-    fn scan_for_received_payments(&mut self) {
+    // Our debtors can only check our bookkeeping against their own if we tell them what they owe.
+    // The ProxyClient passes each claim only to its own debtor, on the next stream it exits for it.
+    fn send_billing_claims(&self) {
+        let permanent_debt_allowed_gwub = self.config.payment_curves.permanent_debt_allowed_gwub;
+        let claims: Vec<BillingClaim> = self
+            .receivable_dao
+            .receivables()
+            .into_iter()
+            .filter(|account| account.balance > permanent_debt_allowed_gwub)
+            .map(|account| BillingClaim {
+                debtor: account.wallet,
+                gwub: account.balance as u64,
+            })
+            .collect();
+        debug!(self.logger, "Sending {} billing claims", claims.len());
+        self.billing_claims_sub
+            .as_ref()
+            .expect("ProxyClient is unbound")
+            .try_send(BillingClaimsMessage { claims })
+            .expect("ProxyClient is dead");
+    }
+
+    fn reconcile_billing_claim(&mut self, msg: ReportBillingClaimMessage) {
+        let accounts: Vec<PayableAccount> = msg
+            .creditor_wallets
+            .iter()
+            .flat_map(|wallet| {
+                self.payable_daos()
+                    .filter_map(move |payable_dao| payable_dao.account_status(wallet))
+            })
+            .collect();
+        let creditor = msg
+            .creditor_wallets
+            .iter()
+            .map(|wallet| wallet.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        let now = self.clock.now();
+        let settling = accounts.iter().any(|account| {
+            account.pending_payment_transaction.is_some()
+                && now
+                    .duration_since(account.last_paid_timestamp)
+                    .map(|since_paid| since_paid < BILLING_CLAIM_SETTLING_PERIOD)
+                    .unwrap_or(true)
+        });
+        if settling {
+            debug!(
+                self.logger,
+                "Not checking billing claim of {} gwub from {}: our last payment is still settling",
+                msg.claimed_gwub,
+                creditor
+            );
+            return;
+        }
+        let owed_gwub: u64 = accounts
+            .iter()
+            .map(|account| max(account.balance, 0) as u64)
+            .sum();
+        let excess_gwub = msg.claimed_gwub.saturating_sub(owed_gwub);
+        let slack_gwub = max(self.config.payment_curves.permanent_debt_allowed_gwub, 0) as u64;
+        let allowance = |percent: u64| max(owed_gwub.saturating_mul(percent) / 100, slack_gwub);
+        if excess_gwub <= allowance(BILLING_CLAIM_TOLERANCE_PERCENT) {
+            debug!(
+                self.logger,
+                "Billing claim of {} gwub from {} agrees with our {} gwub",
+                msg.claimed_gwub,
+                creditor,
+                owed_gwub
+            );
+        } else if msg.shared_wallet {
+            info!(
+                self.logger,
+                "{} claims we owe {} gwub, against our {} gwub, but other Nodes pay with our consuming wallet too: not disputed",
+                creditor,
+                msg.claimed_gwub,
+                owed_gwub
+            );
+        } else if excess_gwub <= allowance(BILLING_CLAIM_DISPUTE_PERCENT) {
+            warning!(
+                self.logger,
+                "{} claims we owe {} gwub, but we only owe {} gwub",
+                creditor,
+                msg.claimed_gwub,
+                owed_gwub
+            );
+        } else {
+            warning!(
+                self.logger,
+                "{} claims we owe {} gwub, but we only owe {} gwub: disputed for overcharging",
+                creditor,
+                msg.claimed_gwub,
+                owed_gwub
+            );
+            let expiration = now + BILLING_CLAIM_DISPUTE_PERIOD;
+            msg.creditor_wallets.into_iter().for_each(|wallet| {
+                self.dispute_dao.dispute(&wallet, expiration);
+                audit(
+                    AuditSource::Node,
+                    "dispute",
+                    &format!(
+                        "Billing claim from wallet {} disputed for overcharging",
+                        wallet
+                    ),
+                );
+                self.send_dispute_message(DisputeMessage::Open(wallet));
+            });
+        }
+    }
+
+    // Bans outlive restarts, but the Neighborhood's list of delinquent wallets doesn't.
+    fn announce_existing_bans(&self) {
+        self.banned_dao
+            .ban_list()
+            .into_iter()
+            .for_each(|wallet| self.send_delinquency_message(DelinquencyMessage::Ban(wallet)));
+    }
+
+    // Like bans, disputes outlive restarts
+    fn announce_existing_disputes(&self) {
+        self.dispute_dao
+            .disputes()
+            .into_iter()
+            .for_each(|wallet| self.send_dispute_message(DisputeMessage::Open(wallet)));
+    }
+
+    // Exhausted budgets outlive restarts too, and the ProxyServer has to keep refusing their traffic
+    fn announce_exhausted_profiles(&self) {
+        self.profile_consumption_dao
+            .consumption()
+            .into_iter()
+            .filter(|(name, consumed)| {
+                self.consuming_profiles
+                    .iter()
+                    .any(|profile| &profile.name == name && profile.budget_exhausted(*consumed))
+            })
+            .for_each(|(name, _)| self.send_profile_exhausted_message(name));
+    }
+
+    fn send_profile_exhausted_message(&self, name: String) {
+        self.profile_exhausted_sub
+            .as_ref()
+            .expect("ProxyServer is unbound")
+            .try_send(ConsumingProfileExhaustedMessage { name })
+            .expect("ProxyServer is dead");
+    }
+
+    fn expire_disputes(&self) {
+        self.dispute_dao
+            .expire(self.clock.now())
+            .into_iter()
+            .for_each(|wallet| {
+                info!(
+                    self.logger,
+                    "Dispute of billing claim from {} has run out", wallet
+                );
+                self.send_dispute_message(DisputeMessage::Close(wallet));
+            });
+    }
+
+    fn send_dispute_message(&self, msg: DisputeMessage) {
+        self.dispute_sub
+            .as_ref()
+            .expect("Neighborhood is unbound")
+            .try_send(msg)
+            .expect("Neighborhood is dead");
+    }
+
+    fn send_delinquency_message(&self, msg: DelinquencyMessage) {
+        self.delinquency_sub
+            .as_ref()
+            .expect("Neighborhood is unbound")
+            .try_send(msg)
+            .expect("Neighborhood is dead");
+    }
+
+    // Events are of no interest until there's a UiGateway to hear them
+    fn publish_event(&self, event: NodeEvent) {
+        if let Some(node_event_sub) = &self.node_event_sub {
+            node_event_sub.try_send(event).expect("UiGateway is dead");
+        }
+    }
+
+    // latest_block is the chain head, if we already know it
+    fn scan_for_received_payments(&mut self, latest_block: Option<u64>) {
+        let start_block = self.persistent_configuration.start_block();
+        self.earning_wallets()
+            .into_iter()
+            .for_each(|wallet| self.scan_for_payments_to(wallet, start_block, latest_block));
+    }
+
+    // While new blocks are being announced, each one triggers a scan, and the timer's scans
+    // would only duplicate them. If the announcements stop, the timer takes over again.
+    fn following_new_blocks(&self) -> bool {
+        match self.new_block_noticed {
+            Some(noticed) => match self.clock.now().duration_since(noticed) {
+                Ok(since) => since < self.config.payment_received_scan_interval,
+                Err(_) => true,
+            },
+            None => false,
+        }
+    }
+
+    fn scan_for_payments_to(&self, recipient: Wallet, start_block: u64, latest_block: Option<u64>) {
         let future_logger = self.logger.clone();
-        debug!(
-            self.logger,
-            "Scanning for payments to {}", self.earning_wallet
-        );
+        debug!(self.logger, "Scanning for payments to {}", recipient);
         let future_report_new_payments_sub = self.report_new_payments_sub.clone();
-        let start_block = self.persistent_configuration.start_block();
         let future = self
             .retrieve_transactions_sub
             .as_ref()
             .expect("BlockchainBridge is unbound")
             .send(RetrieveTransactions {
                 start_block,
-                recipient: self.earning_wallet.clone(),
+                latest_block,
+                recipient: recipient.clone(),
             })
             .then(move |transactions_possibly| match transactions_possibly {
-                Ok(Ok(ref vec)) if vec.is_empty() => {
-                    debug!(future_logger, "No payments detected");
-                    Ok(())
-                }
-                Ok(Ok(transactions)) => {
+                Ok(Ok(retrieved_transactions)) => {
+                    if retrieved_transactions.transactions.is_empty() {
+                        debug!(future_logger, "No payments detected");
+                    }
+                    // Sent even when empty: the Accountant has to hear that pending payments vanished.
                     future_report_new_payments_sub
                         .expect("Accountant is unbound")
                         .try_send(ReceivedPayments {
-                            payments: transactions,
+                            recipient,
+                            retrieved_transactions,
                         })
                         .expect("Accountant is dead.");
                     Ok(())
@@ -487,35 +1156,76 @@ impl Accountant {
         (balance, age)
     }
 
-    fn should_pay(payable: &PayableAccount) -> bool {
+    fn should_pay(
+        payable: &PayableAccount,
+        payment_curves: &PaymentCurves,
+        now: SystemTime,
+    ) -> bool {
         // TODO: This calculation should be done in the database, if possible
-        let time_since_last_paid = SystemTime::now()
+        let time_since_last_paid = now
             .duration_since(payable.last_paid_timestamp)
             .expect("Internal error")
             .as_secs();
 
-        if time_since_last_paid <= PAYMENT_CURVES.payment_suggested_after_sec as u64 {
+        if time_since_last_paid <= payment_curves.payment_suggested_after_sec as u64 {
             return false;
         }
 
-        if payable.balance <= PAYMENT_CURVES.permanent_debt_allowed_gwub {
+        if payable.balance <= payment_curves.permanent_debt_allowed_gwub {
             return false;
         }
 
-        let threshold = Accountant::calculate_payout_threshold(time_since_last_paid);
+        let threshold =
+            Accountant::calculate_payout_threshold(time_since_last_paid, payment_curves);
         payable.balance as f64 > threshold
     }
 
-    fn calculate_payout_threshold(x: u64) -> f64 {
-        let m = -((PAYMENT_CURVES.balance_to_decrease_from_gwub as f64
-            - PAYMENT_CURVES.permanent_debt_allowed_gwub as f64)
-            / (PAYMENT_CURVES.balance_decreases_for_sec as f64
-                - PAYMENT_CURVES.payment_suggested_after_sec as f64));
-        let b = PAYMENT_CURVES.balance_to_decrease_from_gwub as f64
-            - m * PAYMENT_CURVES.payment_suggested_after_sec as f64;
+    fn calculate_payout_threshold(x: u64, payment_curves: &PaymentCurves) -> f64 {
+        let m = -((payment_curves.balance_to_decrease_from_gwub as f64
+            - payment_curves.permanent_debt_allowed_gwub as f64)
+            / (payment_curves.balance_decreases_for_sec as f64
+                - payment_curves.payment_suggested_after_sec as f64));
+        let b = payment_curves.balance_to_decrease_from_gwub as f64
+            - m * payment_curves.payment_suggested_after_sec as f64;
         m * x as f64 + b
     }
 
+    fn count_bytes_relayed(&mut self, payload_size: usize, now: SystemTime) {
+        let minute = Self::minutes_since_epoch(now);
+        self.bytes_relayed += payload_size as u64;
+        match self.bytes_relayed_by_minute.back_mut() {
+            Some((last_minute, bytes)) if *last_minute == minute => *bytes += payload_size as u64,
+            _ => self
+                .bytes_relayed_by_minute
+                .push_back((minute, payload_size as u64)),
+        }
+        self.forget_bytes_relayed_long_ago(minute);
+    }
+
+    fn bytes_relayed_recently(&mut self, now: SystemTime) -> u64 {
+        self.forget_bytes_relayed_long_ago(Self::minutes_since_epoch(now));
+        self.bytes_relayed_by_minute
+            .iter()
+            .map(|(_, bytes)| bytes)
+            .sum()
+    }
+
+    fn forget_bytes_relayed_long_ago(&mut self, minute: u64) {
+        while let Some((first_minute, _)) = self.bytes_relayed_by_minute.front() {
+            if first_minute + RECENTLY_RELAYED_MINUTES > minute {
+                break;
+            }
+            self.bytes_relayed_by_minute.pop_front();
+        }
+    }
+
+    fn minutes_since_epoch(time: SystemTime) -> u64 {
+        time.duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_else(|_| Duration::from_secs(0))
+            .as_secs()
+            / 60
+    }
+
     fn record_service_provided(
         &self,
         service_rate: u64,
@@ -523,8 +1233,7 @@ impl Accountant {
         payload_size: usize,
         wallet: &Wallet,
     ) {
-        let byte_charge = byte_rate * (payload_size as u64);
-        let total_charge = service_rate + byte_charge;
+        let total_charge = service_charge(service_rate, byte_rate, payload_size);
         if !self.our_wallet(wallet) {
             self.receivable_dao
                 .as_ref()
@@ -538,18 +1247,29 @@ impl Accountant {
     }
 
     fn record_service_consumed(
-        &self,
+        &mut self,
         service_rate: u64,
         byte_rate: u64,
         payload_size: usize,
         wallet: &Wallet,
+        profile_opt: Option<String>,
     ) {
-        let byte_charge = byte_rate * (payload_size as u64);
-        let total_charge = service_rate + byte_charge;
+        let total_charge = service_charge(service_rate, byte_rate, payload_size);
         if !self.our_wallet(wallet) {
-            self.payable_dao
+            let profile_opt = profile_opt.and_then(|name| {
+                self.consuming_profiles
+                    .iter()
+                    .find(|profile| profile.name == name)
+                    .cloned()
+            });
+            let profile_wallet_opt = profile_opt
                 .as_ref()
+                .and_then(|profile| profile.consuming_wallet_opt.clone());
+            self.payable_dao_for(&profile_wallet_opt)
                 .more_money_payable(wallet, total_charge);
+            if let Some(profile) = profile_opt {
+                self.record_profile_consumption(&profile, total_charge);
+            }
         } else {
             info!(
                 self.logger,
@@ -558,14 +1278,113 @@ impl Accountant {
         }
     }
 
+    // Once a profile has used up its budget, the ProxyServer stops taking its traffic
+    fn record_profile_consumption(&self, profile: &ConsumingProfile, charge: u64) {
+        let consumed = self
+            .profile_consumption_dao
+            .add_consumption(&profile.name, charge);
+        if profile.budget_exhausted(consumed) && !profile.budget_exhausted(consumed - charge) {
+            warning!(
+                self.logger,
+                "Consuming profile '{}' has used up its budget of {}: {} consumed; refusing its traffic",
+                profile.name,
+                profile.budget_opt.unwrap_or_default(),
+                consumed
+            );
+            self.send_profile_exhausted_message(profile.name.clone());
+        }
+    }
+
+    fn profile_financial_statistics(&self) -> Vec<ProfileFinancialStatistics> {
+        let consumption = self.profile_consumption_dao.consumption();
+        self.consuming_profiles
+            .iter()
+            .map(|profile| ProfileFinancialStatistics {
+                name: profile.name.clone(),
+                consumed: consumption
+                    .iter()
+                    .find(|(name, _)| name == &profile.name)
+                    .map(|(_, consumed)| *consumed)
+                    .unwrap_or(0),
+                budget: profile.budget_opt,
+            })
+            .collect()
+    }
+
+    // The debts of the Node's own consuming wallet, then those of each consuming profile wallet
+    fn payable_daos(&self) -> impl Iterator<Item = &dyn PayableDao> {
+        vec![self.payable_dao.as_ref()].into_iter().chain(
+            self.profile_payable_daos
+                .iter()
+                .map(|(_, payable_dao)| payable_dao.as_ref()),
+        )
+    }
+
+    // A profile wallet without a PayableDao of its own shares the Node's
+    fn payable_dao_for(&self, profile_wallet_opt: &Option<Wallet>) -> &dyn PayableDao {
+        match profile_wallet_opt {
+            Some(profile_wallet) => self
+                .profile_payable_daos
+                .iter()
+                .find(|(wallet, _)| wallet == profile_wallet)
+                .map(|(_, payable_dao)| payable_dao.as_ref())
+                .unwrap_or_else(|| self.payable_dao.as_ref()),
+            None => self.payable_dao.as_ref(),
+        }
+    }
+
+    fn payable_dao_for_mut(&mut self, profile_wallet_opt: &Option<Wallet>) -> &mut dyn PayableDao {
+        let index_opt = profile_wallet_opt.as_ref().and_then(|profile_wallet| {
+            self.profile_payable_daos
+                .iter()
+                .position(|(wallet, _)| wallet == profile_wallet)
+        });
+        match index_opt {
+            Some(index) => self.profile_payable_daos[index].1.as_mut(),
+            None => self.payable_dao.as_mut(),
+        }
+    }
+
+    // Routing services are paid to the earning wallet; exit services go to the exit earning
+    // wallet instead, if there is one.
+    fn earning_wallets(&self) -> Vec<Wallet> {
+        match &self.exit_earning_wallet_opt {
+            Some(exit_earning_wallet)
+                if exit_earning_wallet.address() != self.earning_wallet.address() =>
+            {
+                vec![self.earning_wallet.clone(), exit_earning_wallet.clone()]
+            }
+            _ => vec![self.earning_wallet.clone()],
+        }
+    }
+
     fn our_wallet(&self, wallet: &Wallet) -> bool {
         match &self.consuming_wallet {
             Some(ref consuming) if consuming.address() == wallet.address() => true,
-            _ => wallet.address() == self.earning_wallet.address(),
+            _ if self
+                .profile_payable_daos
+                .iter()
+                .any(|(profile_wallet, _)| profile_wallet.address() == wallet.address()) =>
+            {
+                true
+            }
+            _ => self
+                .earning_wallets()
+                .iter()
+                .any(|earning_wallet| earning_wallet.address() == wallet.address()),
         }
     }
 }
 
+// Both ends of a service compute its charge from the same byte count, so the payable on one
+// Node and the receivable on the other agree to the gwub. Saturate rather than wrap: an absurd
+// rate or payload must not turn into a tiny bill.
+fn service_charge(service_rate: u64, byte_rate: u64, payload_size: usize) -> u64 {
+    byte_rate
+        .saturating_mul(payload_size as u64)
+        .saturating_add(service_rate)
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -577,11 +1396,13 @@ pub mod tests {
     use crate::database::dao_utils::from_time_t;
     use crate::database::dao_utils::to_time_t;
     use crate::sub_lib::accountant::{
-        FinancialStatisticsMessage, ReportRoutingServiceConsumedMessage,
+        ConsumingProfile, FinancialStatisticsMessage, FinancialsFormat, ProfileFinancialStatistics,
+        ProfileSelector, ReportRoutingServiceConsumedMessage,
     };
     use crate::sub_lib::blockchain_bridge::ReportAccountsPayable;
     use crate::sub_lib::ui_gateway::{UiCarrierMessage, UiMessage};
     use crate::sub_lib::wallet::Wallet;
+    use crate::test_utils::clock_mock::ClockMock;
     use crate::test_utils::logging::init_test_logging;
     use crate::test_utils::logging::TestLogHandler;
     use crate::test_utils::make_wallet;
@@ -589,7 +1410,9 @@ pub mod tests {
     use crate::test_utils::recorder::make_recorder;
     use crate::test_utils::recorder::peer_actors_builder;
     use crate::test_utils::recorder::Recorder;
+    use crate::test_utils::recorder::Recording;
     use actix::System;
+    use ethereum_types::BigEndianHash;
     use ethsign_crypto::Keccak256;
     use std::cell::RefCell;
     use std::convert::TryFrom;
@@ -598,7 +1421,7 @@ pub mod tests {
     use std::thread;
     use std::time::Duration;
     use std::time::SystemTime;
-    use web3::types::H256;
+    use web3::types::{H256, U256};
 
     #[derive(Debug, Default)]
     pub struct PayableDaoMock {
@@ -606,7 +1429,10 @@ pub mod tests {
         account_status_results: RefCell<Vec<Option<PayableAccount>>>,
         more_money_payable_parameters: Arc<Mutex<Vec<(Wallet, u64)>>>,
         non_pending_payables_results: RefCell<Vec<Vec<PayableAccount>>>,
-        payment_sent_parameters: Arc<Mutex<Vec<Payment>>>,
+        payables_results: RefCell<Vec<Vec<PayableAccount>>>,
+        payments_sent_parameters: Arc<Mutex<Vec<Vec<Payment>>>>,
+        payment_replaced_parameters: Arc<Mutex<Vec<(Payment, H256)>>>,
+        payment_cancelled_parameters: Arc<Mutex<Vec<Payment>>>,
     }
 
     impl PayableDao for PayableDaoMock {
@@ -617,8 +1443,22 @@ pub mod tests {
                 .push((wallet.clone(), amount));
         }
 
-        fn payment_sent(&self, sent_payment: &Payment) {
-            self.payment_sent_parameters
+        fn payments_sent(&mut self, sent_payments: &[Payment]) {
+            self.payments_sent_parameters
+                .lock()
+                .unwrap()
+                .push(sent_payments.to_vec());
+        }
+
+        fn payment_replaced(&self, sent_payment: &Payment, replacement_transaction: H256) {
+            self.payment_replaced_parameters
+                .lock()
+                .unwrap()
+                .push((sent_payment.clone(), replacement_transaction));
+        }
+
+        fn payment_cancelled(&self, sent_payment: &Payment) {
+            self.payment_cancelled_parameters
                 .lock()
                 .unwrap()
                 .push(sent_payment.clone());
@@ -649,10 +1489,22 @@ pub mod tests {
                 self.non_pending_payables_results.borrow_mut().remove(0)
             }
         }
-    }
 
-    impl PayableDaoMock {
-        pub fn new() -> PayableDaoMock {
+        fn payables(&self) -> Vec<PayableAccount> {
+            if self.payables_results.borrow().is_empty() {
+                vec![]
+            } else {
+                self.payables_results.borrow_mut().remove(0)
+            }
+        }
+
+        fn profile_wallets(&self) -> Vec<Wallet> {
+            vec![]
+        }
+    }
+
+    impl PayableDaoMock {
+        pub fn new() -> PayableDaoMock {
             PayableDaoMock::default()
         }
 
@@ -664,13 +1516,41 @@ pub mod tests {
             self
         }
 
+        fn account_status_parameters(mut self, parameters: &Arc<Mutex<Vec<Wallet>>>) -> Self {
+            self.account_status_parameters = parameters.clone();
+            self
+        }
+
+        fn account_status_result(self, result: Option<PayableAccount>) -> Self {
+            self.account_status_results.borrow_mut().push(result);
+            self
+        }
+
         fn non_pending_payables_result(self, result: Vec<PayableAccount>) -> Self {
             self.non_pending_payables_results.borrow_mut().push(result);
             self
         }
 
-        fn payment_sent_parameters(mut self, parameters: Arc<Mutex<Vec<Payment>>>) -> Self {
-            self.payment_sent_parameters = parameters;
+        fn payables_result(self, result: Vec<PayableAccount>) -> Self {
+            self.payables_results.borrow_mut().push(result);
+            self
+        }
+
+        fn payments_sent_parameters(mut self, parameters: &Arc<Mutex<Vec<Vec<Payment>>>>) -> Self {
+            self.payments_sent_parameters = parameters.clone();
+            self
+        }
+
+        fn payment_replaced_parameters(
+            mut self,
+            parameters: &Arc<Mutex<Vec<(Payment, H256)>>>,
+        ) -> Self {
+            self.payment_replaced_parameters = parameters.clone();
+            self
+        }
+
+        fn payment_cancelled_parameters(mut self, parameters: &Arc<Mutex<Vec<Payment>>>) -> Self {
+            self.payment_cancelled_parameters = parameters.clone();
             self
         }
     }
@@ -680,7 +1560,7 @@ pub mod tests {
         account_status_parameters: Arc<Mutex<Vec<Wallet>>>,
         account_status_results: RefCell<Vec<Option<ReceivableAccount>>>,
         more_money_receivable_parameters: Arc<Mutex<Vec<(Wallet, u64)>>>,
-        more_money_received_parameters: Arc<Mutex<Vec<Vec<Transaction>>>>,
+        more_money_received_parameters: Arc<Mutex<Vec<(Vec<Transaction>, u64)>>>,
         receivables_results: RefCell<Vec<Vec<ReceivableAccount>>>,
         new_delinquencies_parameters: Arc<Mutex<Vec<(SystemTime, PaymentCurves)>>>,
         new_delinquencies_results: RefCell<Vec<Vec<ReceivableAccount>>>,
@@ -700,11 +1580,12 @@ pub mod tests {
             &mut self,
             _persistent_configuration: &dyn PersistentConfiguration,
             transactions: Vec<Transaction>,
+            next_start_block: u64,
         ) {
             self.more_money_received_parameters
                 .lock()
                 .unwrap()
-                .push(transactions);
+                .push((transactions, next_start_block));
         }
 
         fn account_status(&self, wallet: &Wallet) -> Option<ReceivableAccount> {
@@ -717,7 +1598,11 @@ pub mod tests {
         }
 
         fn receivables(&self) -> Vec<ReceivableAccount> {
-            self.receivables_results.borrow_mut().remove(0)
+            if self.receivables_results.borrow().is_empty() {
+                vec![]
+            } else {
+                self.receivables_results.borrow_mut().remove(0)
+            }
         }
 
         fn new_delinquencies(
@@ -762,9 +1647,9 @@ pub mod tests {
             self
         }
 
-        fn _more_money_received_parameters(
+        fn more_money_received_parameters(
             mut self,
-            parameters: Arc<Mutex<Vec<Vec<Transaction>>>>,
+            parameters: Arc<Mutex<Vec<(Vec<Transaction>, u64)>>>,
         ) -> Self {
             self.more_money_received_parameters = parameters;
             self
@@ -813,7 +1698,11 @@ pub mod tests {
     impl BannedDao for BannedDaoMock {
         fn ban_list(&self) -> Vec<Wallet> {
             self.ban_list_parameters.lock().unwrap().push(());
-            self.ban_list_results.borrow_mut().remove(0)
+            if self.ban_list_results.borrow().is_empty() {
+                vec![]
+            } else {
+                self.ban_list_results.borrow_mut().remove(0)
+            }
         }
 
         fn ban(&self, wallet: &Wallet) {
@@ -851,45 +1740,164 @@ pub mod tests {
         }
     }
 
+    #[derive(Default)]
+    struct DisputeDaoMock {
+        disputes_results: RefCell<Vec<Vec<Wallet>>>,
+        dispute_parameters: Arc<Mutex<Vec<(Wallet, SystemTime)>>>,
+        expire_parameters: Arc<Mutex<Vec<SystemTime>>>,
+        expire_results: RefCell<Vec<Vec<Wallet>>>,
+    }
+
+    impl DisputeDao for DisputeDaoMock {
+        fn disputes(&self) -> Vec<Wallet> {
+            if self.disputes_results.borrow().is_empty() {
+                vec![]
+            } else {
+                self.disputes_results.borrow_mut().remove(0)
+            }
+        }
+
+        fn dispute(&self, wallet: &Wallet, expiration: SystemTime) {
+            self.dispute_parameters
+                .lock()
+                .unwrap()
+                .push((wallet.clone(), expiration));
+        }
+
+        fn expire(&self, now: SystemTime) -> Vec<Wallet> {
+            self.expire_parameters.lock().unwrap().push(now);
+            if self.expire_results.borrow().is_empty() {
+                vec![]
+            } else {
+                self.expire_results.borrow_mut().remove(0)
+            }
+        }
+    }
+
+    impl DisputeDaoMock {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn disputes_result(self, result: Vec<Wallet>) -> Self {
+            self.disputes_results.borrow_mut().push(result);
+            self
+        }
+
+        pub fn dispute_parameters(
+            mut self,
+            parameters: &Arc<Mutex<Vec<(Wallet, SystemTime)>>>,
+        ) -> Self {
+            self.dispute_parameters = parameters.clone();
+            self
+        }
+
+        pub fn expire_parameters(mut self, parameters: &Arc<Mutex<Vec<SystemTime>>>) -> Self {
+            self.expire_parameters = parameters.clone();
+            self
+        }
+
+        pub fn expire_result(self, result: Vec<Wallet>) -> Self {
+            self.expire_results.borrow_mut().push(result);
+            self
+        }
+    }
+
+    #[derive(Default)]
+    struct ProfileConsumptionDaoMock {
+        consumption_results: RefCell<Vec<Vec<(String, u64)>>>,
+        add_consumption_parameters: Arc<Mutex<Vec<(String, u64)>>>,
+        add_consumption_results: RefCell<Vec<u64>>,
+    }
+
+    impl ProfileConsumptionDao for ProfileConsumptionDaoMock {
+        fn consumption(&self) -> Vec<(String, u64)> {
+            if self.consumption_results.borrow().is_empty() {
+                vec![]
+            } else {
+                self.consumption_results.borrow_mut().remove(0)
+            }
+        }
+
+        fn add_consumption(&self, name: &str, charge: u64) -> u64 {
+            self.add_consumption_parameters
+                .lock()
+                .unwrap()
+                .push((name.to_string(), charge));
+            if self.add_consumption_results.borrow().is_empty() {
+                charge
+            } else {
+                self.add_consumption_results.borrow_mut().remove(0)
+            }
+        }
+    }
+
+    impl ProfileConsumptionDaoMock {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn consumption_result(self, result: Vec<(String, u64)>) -> Self {
+            self.consumption_results.borrow_mut().push(result);
+            self
+        }
+
+        pub fn add_consumption_parameters(
+            mut self,
+            parameters: &Arc<Mutex<Vec<(String, u64)>>>,
+        ) -> Self {
+            self.add_consumption_parameters = parameters.clone();
+            self
+        }
+
+        pub fn add_consumption_result(self, result: u64) -> Self {
+            self.add_consumption_results.borrow_mut().push(result);
+            self
+        }
+    }
+
     #[test]
-    fn accountant_calls_payable_dao_payment_sent_when_sent_payments() {
-        let payment_sent_parameters = Arc::new(Mutex::new(vec![]));
-        let payment_sent_parameters_inner = payment_sent_parameters.clone();
+    fn accountant_calls_payable_dao_payments_sent_when_sent_payments() {
+        let payments_sent_parameters = Arc::new(Mutex::new(vec![]));
 
         let payable_dao = Box::new(
             PayableDaoMock::new()
                 .non_pending_payables_result(vec![])
-                .payment_sent_parameters(payment_sent_parameters_inner),
+                .payments_sent_parameters(&payments_sent_parameters),
         );
         let receivable_dao = Box::new(ReceivableDaoMock::new());
         let banned_dao = Box::new(BannedDaoMock::new());
 
-        let system = System::new("accountant_calls_payable_dao_payment_sent_when_sent_payments");
+        let system = System::new("accountant_calls_payable_dao_payments_sent_when_sent_payments");
 
         let accountant = Accountant::new(
             &bc_from_ac_plus_earning_wallet(
                 AccountantConfig {
                     payable_scan_interval: Duration::from_millis(100),
                     payment_received_scan_interval: Duration::from_secs(10_000),
+                    payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
                 },
                 make_wallet("some_wallet_address"),
             ),
             payable_dao,
             receivable_dao,
             banned_dao,
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
             null_config(),
         );
 
         let expected_wallet = make_wallet("paying_you");
         let expected_amount = 1;
         let expected_hash = H256::from("transaction_hash".keccak256());
-        let mut expected_payment = Payment::new(
+        let expected_payment = Payment::new(
             expected_wallet.clone(),
             expected_amount,
             expected_hash.clone(),
         );
         let send_payments = SentPayments {
             payments: vec![Ok(expected_payment.clone())],
+            profile_wallet_opt: None,
         };
 
         let subject = accountant.start();
@@ -900,11 +1908,259 @@ pub mod tests {
         System::current().stop();
         system.run();
 
-        let sent_payment_to = payment_sent_parameters.lock().unwrap();
-        let actual = sent_payment_to.get(0).unwrap();
+        let payments_sent_parameters = payments_sent_parameters.lock().unwrap();
+        assert_eq!(*payments_sent_parameters, vec![vec![expected_payment]]);
+    }
+
+    #[test]
+    fn sent_payments_are_published_as_node_events() {
+        let (ui_gateway, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        let transaction = H256::from("transaction_hash".keccak256());
+        let payment = Payment::new(make_wallet("paying_you"), 1234, transaction.clone());
+        thread::spawn(move || {
+            let system = System::new("sent_payments_are_published_as_node_events");
+            let payable_dao = Box::new(PayableDaoMock::new().non_pending_payables_result(vec![]));
+            let mut accountant = Accountant::new(
+                &bc_from_ac_plus_earning_wallet(
+                    AccountantConfig {
+                        payable_scan_interval: Duration::from_millis(100),
+                        payment_received_scan_interval: Duration::from_secs(10_000),
+                        payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
+                    },
+                    make_wallet("some_wallet_address"),
+                ),
+                payable_dao,
+                Box::new(ReceivableDaoMock::new()),
+                Box::new(BannedDaoMock::new()),
+                Box::new(DisputeDaoMock::new()),
+                Box::new(ProfileConsumptionDaoMock::new()),
+                null_config(),
+            );
+            accountant.node_event_sub = Some(ui_gateway.start().recipient::<NodeEvent>());
+            let subject = accountant.start();
+
+            subject
+                .try_send(SentPayments {
+                    payments: vec![
+                        Ok(payment),
+                        Err(BlockchainError::TransactionFailed("Failed".to_string())),
+                    ],
+                    profile_wallet_opt: None,
+                })
+                .unwrap();
+
+            system.run();
+        });
+
+        ui_gateway_awaiter.await_message_count(1);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<NodeEvent>(0),
+            &NodeEvent::PaymentSent {
+                wallet: make_wallet("paying_you").to_string(),
+                amount: 1234,
+                transaction: format!("{:?}", transaction),
+            }
+        );
+        assert_eq!(ui_gateway_recording.len(), 1);
+    }
+
+    #[test]
+    fn accountant_records_the_successful_payments_of_a_batch_together() {
+        init_test_logging();
+        let payments_sent_parameters = Arc::new(Mutex::new(vec![]));
+        let payable_dao = Box::new(
+            PayableDaoMock::new()
+                .non_pending_payables_result(vec![])
+                .payments_sent_parameters(&payments_sent_parameters),
+        );
+        let system = System::new("accountant_records_the_successful_payments_of_a_batch_together");
+        let accountant = Accountant::new(
+            &bc_from_ac_plus_earning_wallet(
+                AccountantConfig {
+                    payable_scan_interval: Duration::from_millis(100),
+                    payment_received_scan_interval: Duration::from_secs(10_000),
+                    payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
+                },
+                make_wallet("some_wallet_address"),
+            ),
+            payable_dao,
+            Box::new(ReceivableDaoMock::new()),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            null_config(),
+        );
+        let first_payment = Payment::new(make_wallet("first"), 10, H256::from("first".keccak256()));
+        let second_payment =
+            Payment::new(make_wallet("second"), 20, H256::from("second".keccak256()));
+        let subject = accountant.start();
+
+        subject
+            .try_send(SentPayments {
+                payments: vec![
+                    Ok(first_payment.clone()),
+                    Err(BlockchainError::TransactionFailed(
+                        "nonce too low".to_string(),
+                    )),
+                    Ok(second_payment.clone()),
+                ],
+                profile_wallet_opt: None,
+            })
+            .expect("unexpected actix error");
+        System::current().stop();
+        system.run();
+
+        let payments_sent_parameters = payments_sent_parameters.lock().unwrap();
+        assert_eq!(
+            *payments_sent_parameters,
+            vec![vec![first_payment, second_payment]]
+        );
+        TestLogHandler::new().exists_log_containing(
+            r#"WARN: Accountant: Blockchain TransactionFailed("nonce too low")."#,
+        );
+    }
+
+    #[test]
+    fn accountant_records_nothing_when_no_payment_of_a_batch_was_sent() {
+        let payments_sent_parameters = Arc::new(Mutex::new(vec![]));
+        let payable_dao = Box::new(
+            PayableDaoMock::new()
+                .non_pending_payables_result(vec![])
+                .payments_sent_parameters(&payments_sent_parameters),
+        );
+        let system = System::new("accountant_records_nothing_when_no_payment_of_a_batch_was_sent");
+        let accountant = Accountant::new(
+            &bc_from_ac_plus_earning_wallet(
+                AccountantConfig {
+                    payable_scan_interval: Duration::from_millis(100),
+                    payment_received_scan_interval: Duration::from_secs(10_000),
+                    payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
+                },
+                make_wallet("some_wallet_address"),
+            ),
+            payable_dao,
+            Box::new(ReceivableDaoMock::new()),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            null_config(),
+        );
+        let subject = accountant.start();
+
+        subject
+            .try_send(SentPayments {
+                payments: vec![Err(BlockchainError::QueryFailed)],
+                profile_wallet_opt: None,
+            })
+            .expect("unexpected actix error");
+        System::current().stop();
+        system.run();
+
+        assert!(payments_sent_parameters.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn accountant_records_payments_made_by_replacement_transactions() {
+        init_test_logging();
+        let payment_replaced_parameters = Arc::new(Mutex::new(vec![]));
+        let payment_cancelled_parameters = Arc::new(Mutex::new(vec![]));
+        let payable_dao = Box::new(
+            PayableDaoMock::new()
+                .payment_replaced_parameters(&payment_replaced_parameters)
+                .payment_cancelled_parameters(&payment_cancelled_parameters),
+        );
+        let system = System::new("accountant_records_payments_made_by_replacement_transactions");
+        let subject = Accountant::new(
+            &bc_from_ac_plus_earning_wallet(
+                AccountantConfig {
+                    payable_scan_interval: Duration::from_secs(10_000),
+                    payment_received_scan_interval: Duration::from_secs(10_000),
+                    payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
+                },
+                make_wallet("some_wallet_address"),
+            ),
+            payable_dao,
+            Box::new(ReceivableDaoMock::new()),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            null_config(),
+        );
+        let payment = Payment::new(make_wallet("payee"), 10, H256::from("first".keccak256()));
+        let replacement = H256::from("replacement".keccak256());
+        let addr = subject.start();
+
+        addr.try_send(ResolvedPayment {
+            payment: payment.clone(),
+            mined_transaction: Some(replacement),
+            profile_wallet_opt: None,
+        })
+        .unwrap();
+        addr.try_send(ResolvedPayment {
+            payment: payment.clone(),
+            mined_transaction: Some(payment.transaction),
+            profile_wallet_opt: None,
+        })
+        .unwrap();
+        System::current().stop();
+        system.run();
+
+        assert_eq!(
+            *payment_replaced_parameters.lock().unwrap(),
+            vec![(payment.clone(), replacement)]
+        );
+        assert!(payment_cancelled_parameters.lock().unwrap().is_empty());
+        TestLogHandler::new().exists_log_containing(&format!(
+            "INFO: Accountant: Payment of 10 to {} was made by replacement transaction {:?}",
+            payment.to, replacement
+        ));
+    }
+
+    #[test]
+    fn accountant_owes_cancelled_payments_again() {
+        init_test_logging();
+        let payment_cancelled_parameters = Arc::new(Mutex::new(vec![]));
+        let payable_dao = Box::new(
+            PayableDaoMock::new().payment_cancelled_parameters(&payment_cancelled_parameters),
+        );
+        let system = System::new("accountant_owes_cancelled_payments_again");
+        let subject = Accountant::new(
+            &bc_from_ac_plus_earning_wallet(
+                AccountantConfig {
+                    payable_scan_interval: Duration::from_secs(10_000),
+                    payment_received_scan_interval: Duration::from_secs(10_000),
+                    payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
+                },
+                make_wallet("some_wallet_address"),
+            ),
+            payable_dao,
+            Box::new(ReceivableDaoMock::new()),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            null_config(),
+        );
+        let payment = Payment::new(make_wallet("payee"), 10, H256::from("first".keccak256()));
+        let addr = subject.start();
+
+        addr.try_send(ResolvedPayment {
+            payment: payment.clone(),
+            mined_transaction: None,
+            profile_wallet_opt: None,
+        })
+        .unwrap();
+        System::current().stop();
+        system.run();
 
-        expected_payment.timestamp = actual.timestamp;
-        assert_eq!(actual, &expected_payment);
+        assert_eq!(
+            *payment_cancelled_parameters.lock().unwrap(),
+            vec![payment.clone()]
+        );
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: Accountant: Payment of 10 to {} in transaction {:?} was cancelled; it will be retried",
+            payment.to, payment.transaction
+        ));
     }
 
     #[test]
@@ -921,12 +2177,15 @@ pub mod tests {
                 AccountantConfig {
                     payable_scan_interval: Duration::from_millis(100),
                     payment_received_scan_interval: Duration::from_secs(10_000),
+                    payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
                 },
                 make_wallet("some_wallet_address"),
             ),
             payable_dao,
             receivable_dao,
             banned_dao,
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
             null_config(),
         );
 
@@ -934,6 +2193,7 @@ pub mod tests {
             payments: vec![Err(BlockchainError::TransactionFailed(
                 "Payment attempt failed".to_string(),
             ))],
+            profile_wallet_opt: None,
         };
 
         let subject = accountant.start();
@@ -957,7 +2217,7 @@ pub mod tests {
         let expected_wallet = make_wallet("blah");
         let expected_wallet_inner = expected_wallet.clone();
         let expected_amount =
-            u64::try_from(PAYMENT_CURVES.permanent_debt_allowed_gwub + 1000).unwrap();
+            u64::try_from(DEFAULT_PAYMENT_CURVES.permanent_debt_allowed_gwub + 1000).unwrap();
 
         let expected_pending_payment_transaction = H256::from("transaction_hash".keccak256());
         let expected_pending_payment_transaction_inner =
@@ -967,9 +2227,9 @@ pub mod tests {
             PayableDaoMock::new()
                 .non_pending_payables_result(vec![PayableAccount {
                     wallet: expected_wallet.clone(),
-                    balance: PAYMENT_CURVES.permanent_debt_allowed_gwub + 1000,
+                    balance: DEFAULT_PAYMENT_CURVES.permanent_debt_allowed_gwub + 1000,
                     last_paid_timestamp: from_time_t(
-                        now - PAYMENT_CURVES.balance_decreases_for_sec - 10,
+                        now - DEFAULT_PAYMENT_CURVES.balance_decreases_for_sec - 10,
                     ),
                     pending_payment_transaction: None,
                 }])
@@ -982,7 +2242,7 @@ pub mod tests {
                 expected_amount,
                 expected_pending_payment_transaction_inner,
             ))]))
-            .retrieve_transactions_response(Ok(vec![]));
+            .retrieve_transactions_response(Ok(RetrievedTransactions::default()));
 
         let (accountant_mock, accountant_mock_awaiter, accountant_recording_arc) = make_recorder();
 
@@ -1003,12 +2263,15 @@ pub mod tests {
                     AccountantConfig {
                         payable_scan_interval: Duration::from_millis(100),
                         payment_received_scan_interval: Duration::from_secs(10_000),
+                        payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
                     },
                     earning_wallet.clone(),
                 ),
                 payable_dao,
                 receivable_dao,
                 banned_dao,
+                Box::new(DisputeDaoMock::new()),
+                Box::new(ProfileConsumptionDaoMock::new()),
                 config_mock,
             );
             let subject_addr = subject.start();
@@ -1036,7 +2299,8 @@ pub mod tests {
         assert_eq!(
             actual_payments,
             &SentPayments {
-                payments: vec![Ok(expected_payment)]
+                payments: vec![Ok(expected_payment)],
+                profile_wallet_opt: None,
             }
         );
     }
@@ -1052,9 +2316,9 @@ pub mod tests {
             PayableDaoMock::new()
                 .non_pending_payables_result(vec![PayableAccount {
                     wallet: expected_wallet.clone(),
-                    balance: PAYMENT_CURVES.permanent_debt_allowed_gwub + 1000,
+                    balance: DEFAULT_PAYMENT_CURVES.permanent_debt_allowed_gwub + 1000,
                     last_paid_timestamp: from_time_t(
-                        now - PAYMENT_CURVES.balance_decreases_for_sec - 10,
+                        now - DEFAULT_PAYMENT_CURVES.balance_decreases_for_sec - 10,
                     ),
                     pending_payment_transaction: None,
                 }])
@@ -1062,10 +2326,10 @@ pub mod tests {
         );
 
         let blockchain_bridge = Recorder::new()
-            .retrieve_transactions_response(Ok(vec![]))
+            .retrieve_transactions_response(Ok(RetrievedTransactions::default()))
             .report_accounts_payable_response(Err("Failed to send transaction".to_string()));
 
-        let (accountant_mock, _, accountant_recording_arc) = make_recorder();
+        let (accountant_mock, accountant_awaiter, accountant_recording_arc) = make_recorder();
 
         thread::spawn(move || {
             let system = System::new(
@@ -1084,12 +2348,15 @@ pub mod tests {
                     AccountantConfig {
                         payable_scan_interval: Duration::from_millis(100),
                         payment_received_scan_interval: Duration::from_secs(10_000),
+                        payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
                     },
                     earning_wallet.clone(),
                 ),
                 payable_dao,
                 receivable_dao,
                 banned_dao,
+                Box::new(DisputeDaoMock::new()),
+                Box::new(ProfileConsumptionDaoMock::new()),
                 config_mock,
             );
             let subject_addr = subject.start();
@@ -1104,8 +2371,11 @@ pub mod tests {
         TestLogHandler::new()
             .await_log_containing("WARN: Accountant: Failed to send transaction", 1000u64);
 
+        // The only message is the report of the (empty) scan for received payments
+        accountant_awaiter.await_message_count(1);
         let accountant_recording = accountant_recording_arc.lock().unwrap();
-        assert_eq!(0, accountant_recording.len());
+        assert_eq!(1, accountant_recording.len());
+        accountant_recording.get_record::<ReceivedPayments>(0);
     }
 
     #[test]
@@ -1113,6 +2383,7 @@ pub mod tests {
         let config = AccountantConfig {
             payable_scan_interval: Duration::from_secs(10_000),
             payment_received_scan_interval: Duration::from_secs(10_000),
+            payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
         };
         let (ui_gateway, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
 
@@ -1135,6 +2406,8 @@ pub mod tests {
             Box::new(payable_dao),
             Box::new(receivable_dao),
             Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
             Box::new(PersistentConfigurationMock::new()),
         );
         let addr = subject.start();
@@ -1159,32 +2432,341 @@ pub mod tests {
                 data: UiMessage::FinancialStatisticsResponse(FinancialStatisticsMessage {
                     pending_credit: 69_000_000_000,
                     pending_debt: 42_000_000_000,
+                    profiles: vec![],
+                    payments_deferred: None,
                 }),
             }
         );
     }
 
     #[test]
-    fn accountant_payment_received_scan_timer_triggers_scanning_for_payments() {
-        let paying_wallet = make_wallet("wallet0");
-        let earning_wallet = make_wallet("earner3000");
-        let amount = 42u64;
-        let expected_transactions = vec![Transaction {
-            block_number: 7u64,
-            from: paying_wallet.clone(),
-            gwei_amount: amount,
-        }];
-        let blockchain_bridge =
-            Recorder::new().retrieve_transactions_response(Ok(expected_transactions.clone()));
-        let blockchain_bridge_awaiter = blockchain_bridge.get_awaiter();
-        let blockchain_bridge_recording = blockchain_bridge.get_recording();
-        let (accountant_mock, accountant_awaiter, accountant_recording_arc) = make_recorder();
-        let config = bc_from_ac_plus_earning_wallet(
-            AccountantConfig {
-                payable_scan_interval: Duration::from_secs(10_000),
-                payment_received_scan_interval: Duration::from_millis(100),
-            },
-            earning_wallet.clone(),
+    fn accountant_tells_the_ui_when_payments_are_deferred_by_the_fee_budget() {
+        init_test_logging();
+        let config = AccountantConfig {
+            payable_scan_interval: Duration::from_secs(10_000),
+            payment_received_scan_interval: Duration::from_secs(10_000),
+            payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
+        };
+        let (ui_gateway, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        let system =
+            System::new("accountant_tells_the_ui_when_payments_are_deferred_by_the_fee_budget");
+        let payable_dao = PayableDaoMock::new().non_pending_payables_result(vec![]);
+        let receivable_dao = ReceivableDaoMock::new().receivables_result(vec![]);
+        let subject = Accountant::new(
+            &bc_from_ac_plus_earning_wallet(config, make_wallet("blah")),
+            Box::new(payable_dao),
+            Box::new(receivable_dao),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            Box::new(PersistentConfigurationMock::new()),
+        );
+        let addr = subject.start();
+        let subject_subs = Accountant::make_subs_from(&addr);
+        let peer_actors = peer_actors_builder().ui_gateway(ui_gateway).build();
+        send_bind_message!(subject_subs, peer_actors);
+        let deferred = Err(BlockchainError::FeeBudgetExceeded {
+            projected_gwei: 120_000,
+            remaining_gwei: 100_000,
+        });
+
+        addr.try_send(SentPayments {
+            payments: vec![deferred.clone(), deferred],
+            profile_wallet_opt: None,
+        })
+        .unwrap();
+        addr.try_send(GetFinancialStatisticsMessage { client_id: 1234 })
+            .unwrap();
+
+        System::current().stop();
+        system.run();
+        ui_gateway_awaiter.await_message_count(1);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 1234,
+                data: UiMessage::FinancialStatisticsResponse(FinancialStatisticsMessage {
+                    pending_credit: 0,
+                    pending_debt: 0,
+                    profiles: vec![],
+                    payments_deferred: Some(PaymentDeferral::FeeBudget),
+                }),
+            }
+        );
+        TestLogHandler::new()
+            .exists_no_log_containing("WARN: Accountant: Blockchain FeeBudgetExceeded");
+    }
+
+    #[test]
+    fn accountant_responds_with_dumped_financials_when_instructed() {
+        let config = AccountantConfig {
+            payable_scan_interval: Duration::from_secs(10_000),
+            payment_received_scan_interval: Duration::from_secs(10_000),
+            payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
+        };
+        let (ui_gateway, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        let system = System::new("accountant_responds_with_dumped_financials_when_instructed");
+        let payables = vec![make_payable_account(20), make_payable_account(2)];
+        let receivables = vec![make_receivable_account(35, false)];
+        let payable_dao = PayableDaoMock::new().payables_result(payables.clone());
+        let receivable_dao = ReceivableDaoMock::new().receivables_result(receivables.clone());
+        let profile_wallet = make_wallet("work");
+        let profile_payables = vec![make_payable_account(7)];
+        let profile_payable_dao = PayableDaoMock::new().payables_result(profile_payables.clone());
+        let consumption = vec![("work".to_string(), 42)];
+        let profile_consumption_dao =
+            ProfileConsumptionDaoMock::new().consumption_result(consumption.clone());
+        let subject = Accountant::new(
+            &bc_from_ac_plus_earning_wallet(config, make_wallet("blah")),
+            Box::new(payable_dao),
+            Box::new(receivable_dao),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(profile_consumption_dao),
+            Box::new(PersistentConfigurationMock::new()),
+        )
+        .with_profile_payable_dao(profile_wallet.clone(), Box::new(profile_payable_dao));
+        let addr = subject.start();
+        let subject_subs = Accountant::make_subs_from(&addr);
+        let peer_actors = peer_actors_builder().ui_gateway(ui_gateway).build();
+        send_bind_message!(subject_subs, peer_actors);
+
+        addr.try_send(DumpFinancialsMessage {
+            client_id: 1234,
+            format: FinancialsFormat::Csv,
+        })
+        .unwrap();
+
+        System::current().stop();
+        system.run();
+        ui_gateway_awaiter.await_message_count(1);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 1234,
+                data: UiMessage::DumpFinancialsResponse(financials_dumper::render_financials(
+                    &payables,
+                    &[(profile_wallet, profile_payables)],
+                    &receivables,
+                    &consumption,
+                    FinancialsFormat::Csv
+                )),
+            }
+        );
+    }
+
+    #[test]
+    fn accountant_sets_valid_payment_parameter_and_rejects_invalid_ones() {
+        let config = AccountantConfig {
+            payable_scan_interval: Duration::from_secs(10_000),
+            payment_received_scan_interval: Duration::from_secs(10_000),
+            payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
+        };
+        let (ui_gateway, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        let system =
+            System::new("accountant_sets_valid_payment_parameter_and_rejects_invalid_ones");
+        let set_payment_parameter_params_arc = Arc::new(Mutex::new(vec![]));
+        let persistent_config = PersistentConfigurationMock::new()
+            .set_payment_parameter_params(&set_payment_parameter_params_arc);
+        let subject = Accountant::new(
+            &bc_from_ac_plus_earning_wallet(config, make_wallet("blah")),
+            Box::new(PayableDaoMock::new()),
+            Box::new(ReceivableDaoMock::new()),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            Box::new(persistent_config),
+        );
+        let addr = subject.start();
+        let subject_subs = Accountant::make_subs_from(&addr);
+        let peer_actors = peer_actors_builder().ui_gateway(ui_gateway).build();
+        send_bind_message!(subject_subs, peer_actors);
+
+        addr.try_send(SetPaymentParameterMessage {
+            client_id: 1234,
+            parameter: PaymentParameter::PaymentGraceBeforeBan,
+            value: 3600,
+        })
+        .unwrap();
+        addr.try_send(SetPaymentParameterMessage {
+            client_id: 1234,
+            parameter: PaymentParameter::PayableScanInterval,
+            value: 1,
+        })
+        .unwrap();
+        addr.try_send(SetPaymentParameterMessage {
+            client_id: 1234,
+            parameter: PaymentParameter::PermanentDebtAllowed,
+            value: DEFAULT_PAYMENT_CURVES.balance_to_decrease_from_gwub as u64,
+        })
+        .unwrap();
+
+        System::current().stop();
+        system.run();
+        ui_gateway_awaiter.await_message_count(3);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        let responses = (0..3)
+            .map(|index| {
+                &ui_gateway_recording
+                    .get_record::<UiCarrierMessage>(index)
+                    .data
+            })
+            .collect::<Vec<&UiMessage>>();
+        assert_eq!(
+            responses,
+            vec![
+                &UiMessage::SetPaymentParameterResponse(true),
+                &UiMessage::SetPaymentParameterResponse(false),
+                &UiMessage::SetPaymentParameterResponse(false),
+            ]
+        );
+        let set_payment_parameter_params = set_payment_parameter_params_arc.lock().unwrap();
+        assert_eq!(
+            *set_payment_parameter_params,
+            vec![(PaymentParameter::PaymentGraceBeforeBan, 3600)]
+        );
+    }
+
+    #[test]
+    fn set_payment_parameter_updates_config_only_when_curves_stay_consistent() {
+        let mut subject = Accountant::new(
+            &bc_from_ac_plus_earning_wallet(AccountantConfig::default(), make_wallet("blah")),
+            Box::new(PayableDaoMock::new()),
+            Box::new(ReceivableDaoMock::new()),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            Box::new(PersistentConfigurationMock::new()),
+        );
+
+        let accepted =
+            subject.set_payment_parameter(PaymentParameter::BalanceDecreasesFor, 100_000);
+        let rejected = subject.set_payment_parameter(PaymentParameter::BalanceDecreasesFor, 60);
+
+        assert_eq!(accepted, Ok(()));
+        assert_eq!(
+            rejected,
+            Err(format!(
+                "balance-decreases-for (60) must be greater than payment-suggested-after ({})",
+                DEFAULT_PAYMENT_CURVES.payment_suggested_after_sec
+            ))
+        );
+        assert_eq!(
+            subject.config.payment_curves.balance_decreases_for_sec,
+            100_000
+        );
+    }
+
+    #[test]
+    fn accountant_reports_payment_parameters_as_configuration() {
+        let subject = Accountant::new(
+            &bc_from_ac_plus_earning_wallet(AccountantConfig::default(), make_wallet("blah")),
+            Box::new(PayableDaoMock::new()),
+            Box::new(ReceivableDaoMock::new()),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            null_config(),
+        );
+        let mut system = System::new("accountant_reports_payment_parameters_as_configuration");
+        let subject_addr: Addr<Accountant> = subject.start();
+
+        let result = system
+            .block_on(subject_addr.send(GetConfigurationMessage {}))
+            .unwrap();
+
+        assert_eq!(
+            result,
+            PAYMENT_PARAMETERS
+                .iter()
+                .map(|parameter| ConfigurationEntry::new(
+                    parameter.name(),
+                    &parameter.default_value().to_string()
+                ))
+                .collect::<Vec<ConfigurationEntry>>()
+        );
+    }
+
+    #[test]
+    fn accountant_changes_payment_parameters_and_ignores_other_configuration() {
+        let set_payment_parameter_params_arc = Arc::new(Mutex::new(vec![]));
+        let persistent_config = PersistentConfigurationMock::new()
+            .set_payment_parameter_params(&set_payment_parameter_params_arc);
+        let subject = Accountant::new(
+            &bc_from_ac_plus_earning_wallet(AccountantConfig::default(), make_wallet("blah")),
+            Box::new(PayableDaoMock::new()),
+            Box::new(ReceivableDaoMock::new()),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            Box::new(persistent_config),
+        );
+        let mut system =
+            System::new("accountant_changes_payment_parameters_and_ignores_other_configuration");
+        let subject_addr: Addr<Accountant> = subject.start();
+        let mut change = |name: &str, value: &str| {
+            system
+                .block_on(subject_addr.send(ChangeConfigurationMessage {
+                    name: name.to_string(),
+                    value: value.to_string(),
+                }))
+                .unwrap()
+        };
+
+        let accepted = change("payment-grace-before-ban", "3600");
+        let out_of_range = change("payable-scan-interval", "1");
+        let not_a_number = change("payable-scan-interval", "booga");
+        let not_ours = change("log-level", "debug");
+
+        assert_eq!(accepted, Some(Ok(())));
+        assert_eq!(
+            out_of_range,
+            Some(Err(
+                "payable-scan-interval must be between 60 and 604800, not 1".to_string()
+            ))
+        );
+        assert_eq!(
+            not_a_number,
+            Some(Err(
+                "payable-scan-interval must be a whole number, not 'booga'".to_string()
+            ))
+        );
+        assert_eq!(not_ours, None);
+        let set_payment_parameter_params = set_payment_parameter_params_arc.lock().unwrap();
+        assert_eq!(
+            *set_payment_parameter_params,
+            vec![(PaymentParameter::PaymentGraceBeforeBan, 3600)]
+        );
+    }
+
+    #[test]
+    fn accountant_payment_received_scan_timer_triggers_scanning_for_payments() {
+        let paying_wallet = make_wallet("wallet0");
+        let earning_wallet = make_wallet("earner3000");
+        let amount = 42u64;
+        let expected_transactions = RetrievedTransactions {
+            latest_block: 10u64,
+            transactions: vec![Transaction {
+                block_number: 7u64,
+                from: paying_wallet.clone(),
+                gwei_amount: amount,
+                transaction_hash: H256::from_uint(&U256::from(7)),
+            }],
+        };
+        let blockchain_bridge =
+            Recorder::new().retrieve_transactions_response(Ok(expected_transactions.clone()));
+        let blockchain_bridge_awaiter = blockchain_bridge.get_awaiter();
+        let blockchain_bridge_recording = blockchain_bridge.get_recording();
+        let (accountant_mock, accountant_awaiter, accountant_recording_arc) = make_recorder();
+        let config = bc_from_ac_plus_earning_wallet(
+            AccountantConfig {
+                payable_scan_interval: Duration::from_secs(10_000),
+                payment_received_scan_interval: Duration::from_millis(100),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
+            },
+            earning_wallet.clone(),
         );
 
         thread::spawn(move || {
@@ -1204,6 +2786,8 @@ pub mod tests {
                 payable_dao,
                 receivable_dao,
                 banned_dao,
+                Box::new(DisputeDaoMock::new()),
+                Box::new(ProfileConsumptionDaoMock::new()),
                 config_mock,
             );
             let peer_actors = peer_actors_builder()
@@ -1226,7 +2810,8 @@ pub mod tests {
         assert_eq!(
             &RetrieveTransactions {
                 start_block: 5u64,
-                recipient: earning_wallet,
+                latest_block: None,
+                recipient: earning_wallet.clone(),
             },
             retrieve_transactions_message
         );
@@ -1237,30 +2822,108 @@ pub mod tests {
             received_payments_recording.get_record::<ReceivedPayments>(0);
         assert_eq!(
             &ReceivedPayments {
-                payments: expected_transactions
+                recipient: earning_wallet,
+                retrieved_transactions: expected_transactions
             },
             received_payments_message
         );
     }
 
     #[test]
-    fn accountant_logs_if_no_transactions_were_detected() {
-        init_test_logging();
+    fn accountant_scans_for_payments_as_soon_as_a_new_block_is_mined() {
         let earning_wallet = make_wallet("earner3000");
-        let blockchain_bridge = Recorder::new().retrieve_transactions_response(Ok(vec![]));
+        let blockchain_bridge =
+            Recorder::new().retrieve_transactions_response(Ok(RetrievedTransactions::default()));
         let blockchain_bridge_awaiter = blockchain_bridge.get_awaiter();
         let blockchain_bridge_recording = blockchain_bridge.get_recording();
-        let (accountant_mock, _, accountant_recording_arc) = make_recorder();
         let config = bc_from_ac_plus_earning_wallet(
             AccountantConfig {
                 payable_scan_interval: Duration::from_secs(10_000),
-                payment_received_scan_interval: Duration::from_millis(100),
+                payment_received_scan_interval: Duration::from_secs(10_000),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
             },
             earning_wallet.clone(),
         );
 
         thread::spawn(move || {
-            let system = System::new("accountant_logs_if_no_transactions_were_detected");
+            let system =
+                System::new("accountant_scans_for_payments_as_soon_as_a_new_block_is_mined");
+            let subject = Accountant::new(
+                &config,
+                Box::new(PayableDaoMock::new().non_pending_payables_result(vec![])),
+                Box::new(ReceivableDaoMock::new()),
+                Box::new(BannedDaoMock::new()),
+                Box::new(DisputeDaoMock::new()),
+                Box::new(ProfileConsumptionDaoMock::new()),
+                Box::new(PersistentConfigurationMock::new().start_block_result(5)),
+            );
+            let peer_actors = peer_actors_builder()
+                .blockchain_bridge(blockchain_bridge)
+                .build();
+            let subject_addr: Addr<Accountant> = subject.start();
+            let subject_subs = Accountant::make_subs_from(&subject_addr);
+
+            send_bind_message!(subject_subs, peer_actors);
+            subject_subs
+                .report_new_block
+                .try_send(NewBlockMessage { block_number: 436 })
+                .unwrap();
+
+            system.run();
+        });
+
+        blockchain_bridge_awaiter.await_message_count(1);
+        let blockchain_bridge_recording = blockchain_bridge_recording.lock().unwrap();
+        assert_eq!(
+            blockchain_bridge_recording.get_record::<RetrieveTransactions>(0),
+            &RetrieveTransactions {
+                start_block: 5,
+                latest_block: Some(436),
+                recipient: earning_wallet,
+            }
+        );
+    }
+
+    #[test]
+    fn accountant_leaves_payment_scans_to_new_blocks_while_they_keep_coming() {
+        let earning_wallet = make_wallet("earner3000");
+        let mut subject = make_accountant_for_received_payments(
+            &earning_wallet,
+            &Arc::new(Mutex::new(vec![])),
+            &Arc::new(Mutex::new(vec![])),
+        );
+        subject.config.payment_received_scan_interval = Duration::from_secs(60);
+
+        assert_eq!(subject.following_new_blocks(), false);
+
+        subject.new_block_noticed = Some(SystemTime::now());
+        assert_eq!(subject.following_new_blocks(), true);
+
+        subject.new_block_noticed = Some(SystemTime::now() - Duration::from_secs(61));
+        assert_eq!(subject.following_new_blocks(), false);
+    }
+
+    #[test]
+    fn accountant_scans_for_payments_to_exit_earning_wallet_too() {
+        let earning_wallet = make_wallet("earner3000");
+        let exit_earning_wallet = make_wallet("exit earner");
+        let blockchain_bridge = Recorder::new()
+            .retrieve_transactions_response(Ok(RetrievedTransactions::default()))
+            .retrieve_transactions_response(Ok(RetrievedTransactions::default()));
+        let blockchain_bridge_awaiter = blockchain_bridge.get_awaiter();
+        let blockchain_bridge_recording = blockchain_bridge.get_recording();
+        let mut config = bc_from_ac_plus_earning_wallet(
+            AccountantConfig {
+                payable_scan_interval: Duration::from_secs(10_000),
+                payment_received_scan_interval: Duration::from_secs(10_000),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
+            },
+            earning_wallet.clone(),
+        );
+        config.exit_earning_wallet_opt = Some(exit_earning_wallet.clone());
+
+        thread::spawn(move || {
+            let system = System::new("accountant_scans_for_payments_to_exit_earning_wallet_too");
             let payable_dao = Box::new(PayableDaoMock::new().non_pending_payables_result(vec![]));
             let receivable_dao = Box::new(
                 ReceivableDaoMock::new()
@@ -1274,11 +2937,12 @@ pub mod tests {
                 payable_dao,
                 receivable_dao,
                 banned_dao,
+                Box::new(DisputeDaoMock::new()),
+                Box::new(ProfileConsumptionDaoMock::new()),
                 config_mock,
             );
             let peer_actors = peer_actors_builder()
                 .blockchain_bridge(blockchain_bridge)
-                .accountant(accountant_mock)
                 .build();
             let subject_addr: Addr<Accountant> = subject.start();
             let subject_subs = Accountant::make_subs_from(&subject_addr);
@@ -1289,60 +2953,70 @@ pub mod tests {
             system.run();
         });
 
-        blockchain_bridge_awaiter.await_message_count(1);
-        TestLogHandler::new().exists_log_containing("DEBUG: Accountant: Scanning for payments");
+        blockchain_bridge_awaiter.await_message_count(2);
         let retrieve_transactions_recording = blockchain_bridge_recording.lock().unwrap();
-        let retrieve_transactions_message =
-            retrieve_transactions_recording.get_record::<RetrieveTransactions>(0);
         assert_eq!(
+            retrieve_transactions_recording.get_record::<RetrieveTransactions>(0),
             &RetrieveTransactions {
                 start_block: 5u64,
+                latest_block: None,
                 recipient: earning_wallet,
-            },
-            retrieve_transactions_message
+            }
+        );
+        assert_eq!(
+            retrieve_transactions_recording.get_record::<RetrieveTransactions>(1),
+            &RetrieveTransactions {
+                start_block: 5u64,
+                latest_block: None,
+                recipient: exit_earning_wallet,
+            }
         );
-
-        TestLogHandler::new().exists_log_containing("DEBUG: Accountant: No payments detected");
-        let accountant_recording = accountant_recording_arc.lock().unwrap();
-        assert_eq!(0, accountant_recording.len())
     }
 
     #[test]
-    fn accountant_logs_error_when_blockchain_bridge_responds_with_error() {
+    fn accountant_logs_if_no_transactions_were_detected() {
         init_test_logging();
         let earning_wallet = make_wallet("earner3000");
+        let retrieved_transactions = RetrievedTransactions {
+            latest_block: 20u64,
+            transactions: vec![],
+        };
         let blockchain_bridge =
-            Recorder::new().retrieve_transactions_response(Err(BlockchainError::QueryFailed));
+            Recorder::new().retrieve_transactions_response(Ok(retrieved_transactions.clone()));
         let blockchain_bridge_awaiter = blockchain_bridge.get_awaiter();
         let blockchain_bridge_recording = blockchain_bridge.get_recording();
+        let (accountant_mock, accountant_awaiter, accountant_recording_arc) = make_recorder();
         let config = bc_from_ac_plus_earning_wallet(
             AccountantConfig {
                 payable_scan_interval: Duration::from_secs(10_000),
                 payment_received_scan_interval: Duration::from_millis(100),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
             },
             earning_wallet.clone(),
         );
 
         thread::spawn(move || {
-            let system =
-                System::new("accountant_logs_error_when_blockchain_bridge_responds_with_error");
+            let system = System::new("accountant_logs_if_no_transactions_were_detected");
             let payable_dao = Box::new(PayableDaoMock::new().non_pending_payables_result(vec![]));
             let receivable_dao = Box::new(
                 ReceivableDaoMock::new()
                     .new_delinquencies_result(vec![])
                     .paid_delinquencies_result(vec![]),
             );
-            let config_mock = Box::new(PersistentConfigurationMock::new().start_block_result(0));
+            let config_mock = Box::new(PersistentConfigurationMock::new().start_block_result(5));
             let banned_dao = Box::new(BannedDaoMock::new());
             let subject = Accountant::new(
                 &config,
                 payable_dao,
                 receivable_dao,
                 banned_dao,
+                Box::new(DisputeDaoMock::new()),
+                Box::new(ProfileConsumptionDaoMock::new()),
                 config_mock,
             );
             let peer_actors = peer_actors_builder()
                 .blockchain_bridge(blockchain_bridge)
+                .accountant(accountant_mock)
                 .build();
             let subject_addr: Addr<Accountant> = subject.start();
             let subject_subs = Accountant::make_subs_from(&subject_addr);
@@ -1354,51 +3028,142 @@ pub mod tests {
         });
 
         blockchain_bridge_awaiter.await_message_count(1);
+        TestLogHandler::new().exists_log_containing("DEBUG: Accountant: Scanning for payments");
         let retrieve_transactions_recording = blockchain_bridge_recording.lock().unwrap();
         let retrieve_transactions_message =
             retrieve_transactions_recording.get_record::<RetrieveTransactions>(0);
-        assert_eq!(earning_wallet, retrieve_transactions_message.recipient);
+        assert_eq!(
+            &RetrieveTransactions {
+                start_block: 5u64,
+                latest_block: None,
+                recipient: earning_wallet.clone(),
+            },
+            retrieve_transactions_message
+        );
 
-        TestLogHandler::new().exists_log_containing(
-            "WARN: Accountant: Unable to retrieve transactions from Blockchain Bridge: QueryFailed",
+        TestLogHandler::new().exists_log_containing("DEBUG: Accountant: No payments detected");
+        accountant_awaiter.await_message_count(1);
+        let accountant_recording = accountant_recording_arc.lock().unwrap();
+        assert_eq!(
+            accountant_recording.get_record::<ReceivedPayments>(0),
+            &ReceivedPayments {
+                recipient: earning_wallet,
+                retrieved_transactions,
+            }
         );
     }
 
     #[test]
-    fn accountant_receives_new_payments_to_the_receivables_dao() {
-        let wallet = make_wallet("wallet0");
+    fn accountant_logs_error_when_blockchain_bridge_responds_with_error() {
+        init_test_logging();
         let earning_wallet = make_wallet("earner3000");
-        let gwei_amount = 42u64;
-        let expected_payment = Transaction {
-            block_number: 7u64,
-            from: wallet.clone(),
-            gwei_amount,
-        };
-        let more_money_received_mock = Arc::new(Mutex::new(vec![]));
-        let receivable_dao = Box::new(ReceivableDaoMock {
-            account_status_parameters: Default::default(),
-            account_status_results: Default::default(),
-            more_money_receivable_parameters: Default::default(),
-            more_money_received_parameters: more_money_received_mock.clone(),
-            receivables_results: Default::default(),
-            new_delinquencies_parameters: Default::default(),
-            new_delinquencies_results: Default::default(),
-            paid_delinquencies_parameters: Default::default(),
-            paid_delinquencies_results: Default::default(),
-        });
-        let banned_dao = Box::new(BannedDaoMock::new());
-        let accountant = Accountant::new(
+        let blockchain_bridge =
+            Recorder::new().retrieve_transactions_response(Err(BlockchainError::QueryFailed));
+        let blockchain_bridge_awaiter = blockchain_bridge.get_awaiter();
+        let blockchain_bridge_recording = blockchain_bridge.get_recording();
+        let config = bc_from_ac_plus_earning_wallet(
+            AccountantConfig {
+                payable_scan_interval: Duration::from_secs(10_000),
+                payment_received_scan_interval: Duration::from_millis(100),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
+            },
+            earning_wallet.clone(),
+        );
+
+        thread::spawn(move || {
+            let system =
+                System::new("accountant_logs_error_when_blockchain_bridge_responds_with_error");
+            let payable_dao = Box::new(PayableDaoMock::new().non_pending_payables_result(vec![]));
+            let receivable_dao = Box::new(
+                ReceivableDaoMock::new()
+                    .new_delinquencies_result(vec![])
+                    .paid_delinquencies_result(vec![]),
+            );
+            let config_mock = Box::new(PersistentConfigurationMock::new().start_block_result(0));
+            let banned_dao = Box::new(BannedDaoMock::new());
+            let subject = Accountant::new(
+                &config,
+                payable_dao,
+                receivable_dao,
+                banned_dao,
+                Box::new(DisputeDaoMock::new()),
+                Box::new(ProfileConsumptionDaoMock::new()),
+                config_mock,
+            );
+            let peer_actors = peer_actors_builder()
+                .blockchain_bridge(blockchain_bridge)
+                .build();
+            let subject_addr: Addr<Accountant> = subject.start();
+            let subject_subs = Accountant::make_subs_from(&subject_addr);
+
+            send_bind_message!(subject_subs, peer_actors);
+            send_start_message!(subject_subs);
+
+            system.run();
+        });
+
+        blockchain_bridge_awaiter.await_message_count(1);
+        let retrieve_transactions_recording = blockchain_bridge_recording.lock().unwrap();
+        let retrieve_transactions_message =
+            retrieve_transactions_recording.get_record::<RetrieveTransactions>(0);
+        assert_eq!(earning_wallet, retrieve_transactions_message.recipient);
+
+        TestLogHandler::new().exists_log_containing(
+            "WARN: Accountant: Unable to retrieve transactions from Blockchain Bridge: QueryFailed",
+        );
+    }
+
+    fn make_received_transaction(from: &Wallet, block_number: u64, hash: u64) -> Transaction {
+        Transaction {
+            block_number,
+            from: from.clone(),
+            gwei_amount: 42,
+            transaction_hash: H256::from_uint(&U256::from(hash)),
+        }
+    }
+
+    fn make_accountant_for_received_payments(
+        earning_wallet: &Wallet,
+        more_money_received_params_arc: &Arc<Mutex<Vec<(Vec<Transaction>, u64)>>>,
+        set_start_block_params_arc: &Arc<Mutex<Vec<u64>>>,
+    ) -> Accountant {
+        Accountant::new(
             &bc_from_ac_plus_earning_wallet(
                 AccountantConfig {
                     payable_scan_interval: Duration::from_secs(10_000),
                     payment_received_scan_interval: Duration::from_secs(10_000),
+                    payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
                 },
                 earning_wallet.clone(),
             ),
             Box::new(PayableDaoMock::new().non_pending_payables_result(vec![])),
-            receivable_dao,
-            banned_dao,
-            null_config(),
+            Box::new(
+                ReceivableDaoMock::new()
+                    .more_money_received_parameters(more_money_received_params_arc.clone()),
+            ),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            Box::new(
+                PersistentConfigurationMock::new()
+                    .start_block_result(0)
+                    .set_start_block_params(set_start_block_params_arc),
+            ),
+        )
+    }
+
+    #[test]
+    fn accountant_receives_new_payments_to_the_receivables_dao() {
+        let wallet = make_wallet("wallet0");
+        let earning_wallet = make_wallet("earner3000");
+        let first_payment = make_received_transaction(&wallet, 7, 1);
+        let second_payment = make_received_transaction(&wallet, 7, 2);
+        let more_money_received_params_arc = Arc::new(Mutex::new(vec![]));
+        let set_start_block_params_arc = Arc::new(Mutex::new(vec![]));
+        let accountant = make_accountant_for_received_payments(
+            &earning_wallet,
+            &more_money_received_params_arc,
+            &set_start_block_params_arc,
         );
 
         let system = System::new("accountant_receives_new_payments_to_the_receivables_dao");
@@ -1406,23 +3171,98 @@ pub mod tests {
 
         subject
             .try_send(ReceivedPayments {
-                payments: vec![expected_payment.clone(), expected_payment.clone()],
+                recipient: earning_wallet,
+                retrieved_transactions: RetrievedTransactions {
+                    latest_block: 7 + DEFAULT_PAYMENT_CONFIRMATIONS - 1,
+                    transactions: vec![first_payment.clone(), second_payment.clone()],
+                },
             })
             .expect("unexpected actix error");
         System::current().stop();
         system.run();
-        let more_money_received_calls = more_money_received_mock.lock().unwrap();
-        assert_eq!(1, more_money_received_calls.len());
+        let more_money_received_params = more_money_received_params_arc.lock().unwrap();
+        assert_eq!(
+            *more_money_received_params,
+            vec![(vec![first_payment, second_payment], 8)]
+        );
+        let set_start_block_params = set_start_block_params_arc.lock().unwrap();
+        assert!(set_start_block_params.is_empty());
+    }
+
+    #[test]
+    fn accountant_credits_received_payment_only_once_it_is_confirmed() {
+        let earning_wallet = make_wallet("earner3000");
+        let payment = make_received_transaction(&make_wallet("wallet0"), 7, 1);
+        let more_money_received_params_arc = Arc::new(Mutex::new(vec![]));
+        let set_start_block_params_arc = Arc::new(Mutex::new(vec![]));
+        let accountant = make_accountant_for_received_payments(
+            &earning_wallet,
+            &more_money_received_params_arc,
+            &set_start_block_params_arc,
+        );
+
+        let system = System::new("accountant_credits_received_payment_only_once_it_is_confirmed");
+        let subject = accountant.start();
+
+        vec![10, 7 + DEFAULT_PAYMENT_CONFIRMATIONS - 1]
+            .into_iter()
+            .for_each(|latest_block| {
+                subject
+                    .try_send(ReceivedPayments {
+                        recipient: earning_wallet.clone(),
+                        retrieved_transactions: RetrievedTransactions {
+                            latest_block,
+                            transactions: vec![payment.clone()],
+                        },
+                    })
+                    .expect("unexpected actix error")
+            });
+        System::current().stop();
+        system.run();
+        let more_money_received_params = more_money_received_params_arc.lock().unwrap();
+        assert_eq!(*more_money_received_params, vec![(vec![payment], 8)]);
+        let set_start_block_params = set_start_block_params_arc.lock().unwrap();
+        assert!(set_start_block_params.is_empty());
+    }
+
+    #[test]
+    fn accountant_does_not_credit_payment_rolled_back_by_reorganization() {
+        let earning_wallet = make_wallet("earner3000");
+        let payment = make_received_transaction(&make_wallet("wallet0"), 7, 1);
+        let more_money_received_params_arc = Arc::new(Mutex::new(vec![]));
+        let set_start_block_params_arc = Arc::new(Mutex::new(vec![]));
+        let accountant = make_accountant_for_received_payments(
+            &earning_wallet,
+            &more_money_received_params_arc,
+            &set_start_block_params_arc,
+        );
 
-        let more_money_received_params = more_money_received_calls.get(0).unwrap();
-        assert_eq!(2, more_money_received_params.len());
+        let system =
+            System::new("accountant_does_not_credit_payment_rolled_back_by_reorganization");
+        let subject = accountant.start();
 
-        let first_payment = more_money_received_params.get(0).unwrap();
-        assert_eq!(expected_payment.from, first_payment.from);
-        assert_eq!(gwei_amount, first_payment.gwei_amount);
-        let second_payment = more_money_received_params.get(1).unwrap();
-        assert_eq!(expected_payment.from, second_payment.from);
-        assert_eq!(gwei_amount, second_payment.gwei_amount);
+        vec![
+            (10, vec![payment]),
+            (7 + DEFAULT_PAYMENT_CONFIRMATIONS - 1, vec![]),
+        ]
+        .into_iter()
+        .for_each(|(latest_block, transactions)| {
+            subject
+                .try_send(ReceivedPayments {
+                    recipient: earning_wallet.clone(),
+                    retrieved_transactions: RetrievedTransactions {
+                        latest_block,
+                        transactions,
+                    },
+                })
+                .expect("unexpected actix error")
+        });
+        System::current().stop();
+        system.run();
+        let more_money_received_params = more_money_received_params_arc.lock().unwrap();
+        assert!(more_money_received_params.is_empty());
+        let set_start_block_params = set_start_block_params_arc.lock().unwrap();
+        assert_eq!(*set_start_block_params, vec![8]);
     }
 
     #[test]
@@ -1430,7 +3270,7 @@ pub mod tests {
         init_test_logging();
         let (blockchain_bridge, blockchain_bridge_awaiter, _) = make_recorder();
         let blockchain_bridge = blockchain_bridge
-            .retrieve_transactions_response(Ok(vec![]))
+            .retrieve_transactions_response(Ok(RetrievedTransactions::default()))
             .report_accounts_payable_response(Ok(vec![]));
 
         thread::spawn(move || {
@@ -1440,6 +3280,7 @@ pub mod tests {
                 AccountantConfig {
                     payable_scan_interval: Duration::from_millis(100),
                     payment_received_scan_interval: Duration::from_secs(100),
+                    payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
                 },
                 make_wallet("hi"),
             );
@@ -1447,17 +3288,17 @@ pub mod tests {
             // slightly above minimum balance, to the right of the curve (time intersection)
             let account0 = PayableAccount {
                 wallet: make_wallet("wallet0"),
-                balance: PAYMENT_CURVES.permanent_debt_allowed_gwub + 1,
+                balance: DEFAULT_PAYMENT_CURVES.permanent_debt_allowed_gwub + 1,
                 last_paid_timestamp: from_time_t(
-                    now - PAYMENT_CURVES.balance_decreases_for_sec - 10,
+                    now - DEFAULT_PAYMENT_CURVES.balance_decreases_for_sec - 10,
                 ),
                 pending_payment_transaction: None,
             };
             let account1 = PayableAccount {
                 wallet: make_wallet("wallet1"),
-                balance: PAYMENT_CURVES.permanent_debt_allowed_gwub + 2,
+                balance: DEFAULT_PAYMENT_CURVES.permanent_debt_allowed_gwub + 2,
                 last_paid_timestamp: from_time_t(
-                    now - PAYMENT_CURVES.balance_decreases_for_sec - 12,
+                    now - DEFAULT_PAYMENT_CURVES.balance_decreases_for_sec - 12,
                 ),
                 pending_payment_transaction: None,
             };
@@ -1473,6 +3314,8 @@ pub mod tests {
                 payable_dao,
                 receivable_dao,
                 banned_dao,
+                Box::new(DisputeDaoMock::new()),
+                Box::new(ProfileConsumptionDaoMock::new()),
                 null_config(),
             );
             let peer_actors = peer_actors_builder()
@@ -1501,6 +3344,7 @@ pub mod tests {
             AccountantConfig {
                 payable_scan_interval: Duration::from_secs(1000),
                 payment_received_scan_interval: Duration::from_secs(1000),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
             },
             make_wallet("buy"),
             make_wallet("hi"),
@@ -1513,6 +3357,8 @@ pub mod tests {
             payable_dao,
             receivable_dao,
             banned_dao,
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
             null_config(),
         );
         let peer_actors = peer_actors_builder()
@@ -1543,6 +3389,7 @@ pub mod tests {
             AccountantConfig {
                 payable_scan_interval: Duration::from_secs(100),
                 payment_received_scan_interval: Duration::from_secs(1000),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
             },
             make_wallet("mine"),
         );
@@ -1551,27 +3398,27 @@ pub mod tests {
             // below minimum balance, to the right of time intersection (inside buffer zone)
             PayableAccount {
                 wallet: make_wallet("wallet0"),
-                balance: PAYMENT_CURVES.permanent_debt_allowed_gwub - 1,
+                balance: DEFAULT_PAYMENT_CURVES.permanent_debt_allowed_gwub - 1,
                 last_paid_timestamp: from_time_t(
-                    now - PAYMENT_CURVES.balance_decreases_for_sec - 10,
+                    now - DEFAULT_PAYMENT_CURVES.balance_decreases_for_sec - 10,
                 ),
                 pending_payment_transaction: None,
             },
             // above balance intersection, to the left of minimum time (inside buffer zone)
             PayableAccount {
                 wallet: make_wallet("wallet1"),
-                balance: PAYMENT_CURVES.balance_to_decrease_from_gwub + 1,
+                balance: DEFAULT_PAYMENT_CURVES.balance_to_decrease_from_gwub + 1,
                 last_paid_timestamp: from_time_t(
-                    now - PAYMENT_CURVES.payment_suggested_after_sec + 10,
+                    now - DEFAULT_PAYMENT_CURVES.payment_suggested_after_sec + 10,
                 ),
                 pending_payment_transaction: None,
             },
             // above minimum balance, to the right of minimum time (not in buffer zone, below the curve)
             PayableAccount {
                 wallet: make_wallet("wallet2"),
-                balance: PAYMENT_CURVES.balance_to_decrease_from_gwub - 1000,
+                balance: DEFAULT_PAYMENT_CURVES.balance_to_decrease_from_gwub - 1000,
                 last_paid_timestamp: from_time_t(
-                    now - PAYMENT_CURVES.payment_suggested_after_sec - 1,
+                    now - DEFAULT_PAYMENT_CURVES.payment_suggested_after_sec - 1,
                 ),
                 pending_payment_transaction: None,
             },
@@ -1593,6 +3440,8 @@ pub mod tests {
             Box::new(payable_dao),
             Box::new(receivable_dao),
             Box::new(banned_dao),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
             null_config(),
         );
         subject.report_accounts_payable_sub = Some(report_accounts_payable_sub);
@@ -1613,6 +3462,7 @@ pub mod tests {
             AccountantConfig {
                 payable_scan_interval: Duration::from_millis(100),
                 payment_received_scan_interval: Duration::from_millis(1_000),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
             },
             make_wallet("mine"),
         );
@@ -1621,18 +3471,18 @@ pub mod tests {
             // slightly above minimum balance, to the right of the curve (time intersection)
             PayableAccount {
                 wallet: make_wallet("wallet0"),
-                balance: PAYMENT_CURVES.permanent_debt_allowed_gwub + 1,
+                balance: DEFAULT_PAYMENT_CURVES.permanent_debt_allowed_gwub + 1,
                 last_paid_timestamp: from_time_t(
-                    now - PAYMENT_CURVES.balance_decreases_for_sec - 10,
+                    now - DEFAULT_PAYMENT_CURVES.balance_decreases_for_sec - 10,
                 ),
                 pending_payment_transaction: None,
             },
             // slightly above the curve (balance intersection), to the right of minimum time
             PayableAccount {
                 wallet: make_wallet("wallet1"),
-                balance: PAYMENT_CURVES.balance_to_decrease_from_gwub + 1,
+                balance: DEFAULT_PAYMENT_CURVES.balance_to_decrease_from_gwub + 1,
                 last_paid_timestamp: from_time_t(
-                    now - PAYMENT_CURVES.payment_suggested_after_sec - 10,
+                    now - DEFAULT_PAYMENT_CURVES.payment_suggested_after_sec - 10,
                 ),
                 pending_payment_transaction: None,
             },
@@ -1659,6 +3509,8 @@ pub mod tests {
                 Box::new(payable_dao),
                 Box::new(receivable_dao),
                 Box::new(banned_dao),
+                Box::new(DisputeDaoMock::new()),
+                Box::new(ProfileConsumptionDaoMock::new()),
                 null_config(),
             );
             let subject_addr = subject.start();
@@ -1674,7 +3526,10 @@ pub mod tests {
         let blockchain_bridge_recordings = blockchain_bridge_recordings_arc.lock().unwrap();
         assert_eq!(
             blockchain_bridge_recordings.get_record::<ReportAccountsPayable>(0),
-            &ReportAccountsPayable { accounts }
+            &ReportAccountsPayable {
+                accounts,
+                profile_wallet_opt: None
+            }
         );
     }
 
@@ -1682,13 +3537,15 @@ pub mod tests {
     fn payment_received_scan_triggers_scan_for_delinquencies() {
         let ban_parameters_arc = Arc::new(Mutex::new(vec![]));
         let ban_parameters_arc_inner = ban_parameters_arc.clone();
-        let blockchain_bridge = Recorder::new().retrieve_transactions_response(Ok(vec![]));
+        let blockchain_bridge =
+            Recorder::new().retrieve_transactions_response(Ok(RetrievedTransactions::default()));
         thread::spawn(move || {
             let system = System::new("payment_received_scan_triggers_scan_for_delinquencies");
             let config = bc_from_ac_plus_earning_wallet(
                 AccountantConfig {
                     payable_scan_interval: Duration::from_secs(10_000),
                     payment_received_scan_interval: Duration::from_millis(100),
+                    payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
                 },
                 make_wallet("hi"),
             );
@@ -1709,6 +3566,8 @@ pub mod tests {
                 payable_dao,
                 receivable_dao,
                 banned_dao,
+                Box::new(DisputeDaoMock::new()),
+                Box::new(ProfileConsumptionDaoMock::new()),
                 null_config(),
             );
             let peer_actors = peer_actors_builder()
@@ -1732,13 +3591,71 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn accountant_tells_neighborhood_about_existing_bans_on_start() {
+        let system = System::new("accountant_tells_neighborhood_about_existing_bans_on_start");
+        let (neighborhood, neighborhood_awaiter, neighborhood_recording_arc) = make_recorder();
+        let config = bc_from_ac_plus_earning_wallet(
+            AccountantConfig {
+                payable_scan_interval: Duration::from_secs(10_000),
+                payment_received_scan_interval: Duration::from_secs(10_000),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
+            },
+            make_wallet("mine"),
+        );
+        let banned_dao = BannedDaoMock::new()
+            .ban_list_result(vec![make_wallet("deadbeat 1"), make_wallet("deadbeat 2")]);
+        let subject = Accountant::new(
+            &config,
+            Box::new(PayableDaoMock::new().non_pending_payables_result(vec![])),
+            Box::new(
+                ReceivableDaoMock::new()
+                    .new_delinquencies_result(vec![])
+                    .paid_delinquencies_result(vec![]),
+            ),
+            Box::new(banned_dao),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            null_config(),
+        );
+        let addr = subject.start();
+        let subject_subs = Accountant::make_subs_from(&addr);
+        let peer_actors = peer_actors_builder()
+            .blockchain_bridge(
+                Recorder::new()
+                    .retrieve_transactions_response(Ok(RetrievedTransactions::default())),
+            )
+            .neighborhood(neighborhood)
+            .build();
+        send_bind_message!(subject_subs, peer_actors);
+
+        send_start_message!(subject_subs);
+
+        System::current().stop();
+        system.run();
+        neighborhood_awaiter.await_message_count(2);
+        let neighborhood_recording = neighborhood_recording_arc.lock().unwrap();
+        assert_eq!(
+            neighborhood_recording.get_record::<DelinquencyMessage>(0),
+            &DelinquencyMessage::Ban(make_wallet("deadbeat 1"))
+        );
+        assert_eq!(
+            neighborhood_recording.get_record::<DelinquencyMessage>(1),
+            &DelinquencyMessage::Ban(make_wallet("deadbeat 2"))
+        );
+    }
+
     #[test]
     fn scan_for_delinquencies_triggers_bans_and_unbans() {
         init_test_logging();
+        let system = System::new("scan_for_delinquencies_triggers_bans_and_unbans");
+        let (neighborhood, neighborhood_awaiter, neighborhood_recording_arc) = make_recorder();
+        let (ui_gateway, _, ui_gateway_recording_arc) = make_recorder();
         let config = bc_from_ac_plus_earning_wallet(
             AccountantConfig {
                 payable_scan_interval: Duration::from_secs(100),
                 payment_received_scan_interval: Duration::from_secs(1000),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
             },
             make_wallet("mine"),
         );
@@ -1765,17 +3682,29 @@ pub mod tests {
             Box::new(payable_dao),
             Box::new(receivable_dao),
             Box::new(banned_dao),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
             null_config(),
         );
+        subject.delinquency_sub = Some(neighborhood.start().recipient::<DelinquencyMessage>());
+        subject.node_event_sub = Some(ui_gateway.start().recipient::<NodeEvent>());
 
         subject.scan_for_delinquencies();
 
+        System::current().stop();
+        system.run();
         let new_delinquencies_parameters: MutexGuard<Vec<(SystemTime, PaymentCurves)>> =
             new_delinquencies_parameters_arc.lock().unwrap();
-        assert_eq!(PAYMENT_CURVES.clone(), new_delinquencies_parameters[0].1);
+        assert_eq!(
+            DEFAULT_PAYMENT_CURVES.clone(),
+            new_delinquencies_parameters[0].1
+        );
         let paid_delinquencies_parameters: MutexGuard<Vec<PaymentCurves>> =
             paid_delinquencies_parameters_arc.lock().unwrap();
-        assert_eq!(PAYMENT_CURVES.clone(), paid_delinquencies_parameters[0]);
+        assert_eq!(
+            DEFAULT_PAYMENT_CURVES.clone(),
+            paid_delinquencies_parameters[0]
+        );
         let ban_parameters = ban_parameters_arc.lock().unwrap();
         assert!(ban_parameters.contains(&newly_banned_1.wallet));
         assert!(ban_parameters.contains(&newly_banned_2.wallet));
@@ -1784,11 +3713,349 @@ pub mod tests {
         assert!(unban_parameters.contains(&newly_unbanned_1.wallet));
         assert!(unban_parameters.contains(&newly_unbanned_2.wallet));
         assert_eq!(2, unban_parameters.len());
+        neighborhood_awaiter.await_message_count(4);
+        let neighborhood_recording = neighborhood_recording_arc.lock().unwrap();
+        assert_eq!(
+            (0..4)
+                .map(|index| neighborhood_recording.get_record::<DelinquencyMessage>(index))
+                .collect::<Vec<&DelinquencyMessage>>(),
+            vec![
+                &DelinquencyMessage::Ban(newly_banned_1.wallet.clone()),
+                &DelinquencyMessage::Ban(newly_banned_2.wallet.clone()),
+                &DelinquencyMessage::Unban(newly_unbanned_1.wallet.clone()),
+                &DelinquencyMessage::Unban(newly_unbanned_2.wallet.clone()),
+            ]
+        );
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            (0..ui_gateway_recording.len())
+                .map(|index| ui_gateway_recording.get_record::<NodeEvent>(index))
+                .collect::<Vec<&NodeEvent>>(),
+            vec![
+                &NodeEvent::DelinquencyBan {
+                    wallet: newly_banned_1.wallet.to_string()
+                },
+                &NodeEvent::DelinquencyBan {
+                    wallet: newly_banned_2.wallet.to_string()
+                },
+            ]
+        );
         let tlh = TestLogHandler::new();
         tlh.exists_log_matching("INFO: Accountant: Wallet 0x00000000000000000077616c6c65743132333464 \\(balance: 1234 SUB, age: \\d+ sec\\) banned for delinquency");
         tlh.exists_log_matching("INFO: Accountant: Wallet 0x00000000000000000077616c6c65743233343564 \\(balance: 2345 SUB, age: \\d+ sec\\) banned for delinquency");
         tlh.exists_log_matching("INFO: Accountant: Wallet 0x00000000000000000077616c6c6574333435366e \\(balance: 3456 SUB, age: \\d+ sec\\) is no longer delinquent: unbanned");
         tlh.exists_log_matching("INFO: Accountant: Wallet 0x00000000000000000077616c6c6574343536376e \\(balance: 4567 SUB, age: \\d+ sec\\) is no longer delinquent: unbanned");
+        tlh.exists_log_containing("INFO: AuditLog: node ban: Wallet 0x00000000000000000077616c6c65743132333464 banned for delinquency");
+        tlh.exists_log_containing("INFO: AuditLog: node unban: Wallet 0x00000000000000000077616c6c6574333435366e is no longer delinquent");
+    }
+
+    #[test]
+    fn send_billing_claims_sends_debts_above_permanent_debt_allowed_every_time() {
+        let system =
+            System::new("send_billing_claims_sends_debts_above_permanent_debt_allowed_every_time");
+        let (proxy_client, _, proxy_client_recording_arc) = make_recorder();
+        let large_debt = make_receivable_account(2, false);
+        let mut small_debt = make_receivable_account(1, false);
+        small_debt.balance = DEFAULT_PAYMENT_CURVES.permanent_debt_allowed_gwub;
+        let receivables = vec![small_debt, large_debt.clone()];
+        let receivable_dao = ReceivableDaoMock::new()
+            .receivables_result(receivables.clone())
+            .receivables_result(receivables.clone());
+        let mut subject = Accountant::new(
+            &bc_from_ac_plus_earning_wallet(
+                AccountantConfig {
+                    payable_scan_interval: Duration::from_secs(100),
+                    payment_received_scan_interval: Duration::from_secs(1000),
+                    payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
+                },
+                make_wallet("mine"),
+            ),
+            Box::new(PayableDaoMock::new()),
+            Box::new(receivable_dao),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            null_config(),
+        );
+        subject.billing_claims_sub = Some(proxy_client.start().recipient::<BillingClaimsMessage>());
+
+        subject.send_billing_claims();
+        subject.send_billing_claims();
+
+        System::current().stop();
+        system.run();
+        let proxy_client_recording = proxy_client_recording_arc.lock().unwrap();
+        let expected = BillingClaimsMessage {
+            claims: vec![BillingClaim {
+                debtor: large_debt.wallet,
+                gwub: large_debt.balance as u64,
+            }],
+        };
+        assert_eq!(proxy_client_recording.len(), 2);
+        assert_eq!(
+            proxy_client_recording.get_record::<BillingClaimsMessage>(0),
+            &expected
+        );
+        assert_eq!(
+            proxy_client_recording.get_record::<BillingClaimsMessage>(1),
+            &expected
+        );
+    }
+
+    fn reconcile_billing_claim_against(
+        test_name: &str,
+        payable_account_opt: Option<PayableAccount>,
+        claimed_gwub: u64,
+        shared_wallet: bool,
+    ) -> (Vec<Wallet>, Vec<Wallet>, Arc<Mutex<Recording>>) {
+        let system = System::new(test_name);
+        let now = SystemTime::now();
+        let (neighborhood, _, neighborhood_recording_arc) = make_recorder();
+        let account_status_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let payable_dao = PayableDaoMock::new()
+            .account_status_parameters(&account_status_parameters_arc)
+            .account_status_result(payable_account_opt)
+            .account_status_result(None);
+        let dispute_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let dispute_dao = DisputeDaoMock::new().dispute_parameters(&dispute_parameters_arc);
+        let mut subject = Accountant::new(
+            &bc_from_ac_plus_earning_wallet(
+                AccountantConfig {
+                    payable_scan_interval: Duration::from_secs(100),
+                    payment_received_scan_interval: Duration::from_secs(1000),
+                    payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
+                },
+                make_wallet("mine"),
+            ),
+            Box::new(payable_dao),
+            Box::new(ReceivableDaoMock::new()),
+            Box::new(BannedDaoMock::new()),
+            Box::new(dispute_dao),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            null_config(),
+        )
+        .with_clock(Box::new(ClockMock::new(now)));
+        subject.dispute_sub = Some(neighborhood.start().recipient::<DisputeMessage>());
+
+        subject.reconcile_billing_claim(ReportBillingClaimMessage {
+            creditor_wallets: vec![make_wallet("creditor"), make_wallet("creditor exit")],
+            claimed_gwub,
+            shared_wallet,
+        });
+
+        System::current().stop();
+        system.run();
+        let account_status_parameters = account_status_parameters_arc.lock().unwrap().clone();
+        let dispute_parameters = dispute_parameters_arc.lock().unwrap().clone();
+        dispute_parameters.iter().for_each(|(_, expiration)| {
+            assert_eq!(*expiration, now + BILLING_CLAIM_DISPUTE_PERIOD)
+        });
+        (
+            account_status_parameters,
+            dispute_parameters
+                .into_iter()
+                .map(|(wallet, _)| wallet)
+                .collect(),
+            neighborhood_recording_arc,
+        )
+    }
+
+    fn make_creditor_account(balance: i64) -> PayableAccount {
+        PayableAccount {
+            wallet: make_wallet("creditor"),
+            balance,
+            last_paid_timestamp: from_time_t(to_time_t(SystemTime::now()) - 86_400),
+            pending_payment_transaction: None,
+        }
+    }
+
+    #[test]
+    fn billing_claim_within_tolerance_is_accepted() {
+        init_test_logging();
+
+        let (account_status_parameters, dispute_parameters, neighborhood_recording_arc) =
+            reconcile_billing_claim_against(
+                "billing_claim_within_tolerance_is_accepted",
+                Some(make_creditor_account(1_000_000_000)),
+                1_100_000_000,
+                false,
+            );
+
+        assert_eq!(
+            account_status_parameters,
+            vec![make_wallet("creditor"), make_wallet("creditor exit")]
+        );
+        assert!(dispute_parameters.is_empty());
+        assert_eq!(neighborhood_recording_arc.lock().unwrap().len(), 0);
+        TestLogHandler::new().exists_log_containing(&format!(
+            "DEBUG: Accountant: Billing claim of 1100000000 gwub from {}, {} agrees with our 1000000000 gwub",
+            make_wallet("creditor"),
+            make_wallet("creditor exit")
+        ));
+    }
+
+    #[test]
+    fn billing_claim_beyond_tolerance_is_complained_about() {
+        init_test_logging();
+
+        let (_, dispute_parameters, neighborhood_recording_arc) = reconcile_billing_claim_against(
+            "billing_claim_beyond_tolerance_is_complained_about",
+            Some(make_creditor_account(1_000_000_000)),
+            1_300_000_000,
+            false,
+        );
+
+        assert!(dispute_parameters.is_empty());
+        assert_eq!(neighborhood_recording_arc.lock().unwrap().len(), 0);
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: Accountant: {}, {} claims we owe 1300000000 gwub, but we only owe 1000000000 gwub",
+            make_wallet("creditor"),
+            make_wallet("creditor exit")
+        ));
+    }
+
+    #[test]
+    fn gross_overcharge_disputes_creditor() {
+        init_test_logging();
+
+        let (_, dispute_parameters, neighborhood_recording_arc) = reconcile_billing_claim_against(
+            "gross_overcharge_disputes_creditor",
+            Some(make_creditor_account(1_000_000_000)),
+            1_600_000_000,
+            false,
+        );
+
+        assert_eq!(
+            dispute_parameters,
+            vec![make_wallet("creditor"), make_wallet("creditor exit")]
+        );
+        let neighborhood_recording = neighborhood_recording_arc.lock().unwrap();
+        assert_eq!(
+            neighborhood_recording.get_record::<DisputeMessage>(0),
+            &DisputeMessage::Open(make_wallet("creditor"))
+        );
+        assert_eq!(
+            neighborhood_recording.get_record::<DisputeMessage>(1),
+            &DisputeMessage::Open(make_wallet("creditor exit"))
+        );
+        let tlh = TestLogHandler::new();
+        tlh.exists_log_containing(&format!(
+            "WARN: Accountant: {}, {} claims we owe 1600000000 gwub, but we only owe 1000000000 gwub: disputed for overcharging",
+            make_wallet("creditor"),
+            make_wallet("creditor exit")
+        ));
+        tlh.exists_log_containing(&format!(
+            "INFO: AuditLog: node dispute: Billing claim from wallet {} disputed for overcharging",
+            make_wallet("creditor")
+        ));
+    }
+
+    #[test]
+    fn gross_overcharge_on_shared_consuming_wallet_is_not_disputed() {
+        init_test_logging();
+
+        let (_, dispute_parameters, neighborhood_recording_arc) = reconcile_billing_claim_against(
+            "gross_overcharge_on_shared_consuming_wallet_is_not_disputed",
+            Some(make_creditor_account(1_000_000_000)),
+            1_600_000_000,
+            true,
+        );
+
+        assert!(dispute_parameters.is_empty());
+        assert_eq!(neighborhood_recording_arc.lock().unwrap().len(), 0);
+        TestLogHandler::new().exists_log_containing(&format!(
+            "INFO: Accountant: {}, {} claims we owe 1600000000 gwub, against our 1000000000 gwub, but other Nodes pay with our consuming wallet too: not disputed",
+            make_wallet("creditor"),
+            make_wallet("creditor exit")
+        ));
+    }
+
+    #[test]
+    fn disputes_are_announced_at_start_and_closed_when_they_run_out() {
+        init_test_logging();
+        let now = SystemTime::now();
+        let expire_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let dispute_dao = DisputeDaoMock::new()
+            .expire_parameters(&expire_parameters_arc)
+            .expire_result(vec![])
+            .disputes_result(vec![make_wallet("still disputed")])
+            .expire_result(vec![make_wallet("still disputed")]);
+        let (neighborhood, _, neighborhood_recording_arc) = make_recorder();
+        let system = System::new("disputes_are_announced_at_start_and_closed_when_they_run_out");
+        let mut subject = Accountant::new(
+            &bc_from_ac_plus_earning_wallet(
+                AccountantConfig {
+                    payable_scan_interval: Duration::from_secs(100),
+                    payment_received_scan_interval: Duration::from_secs(1000),
+                    payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
+                },
+                make_wallet("mine"),
+            ),
+            Box::new(PayableDaoMock::new()),
+            Box::new(ReceivableDaoMock::new()),
+            Box::new(BannedDaoMock::new()),
+            Box::new(dispute_dao),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            null_config(),
+        )
+        .with_clock(Box::new(ClockMock::new(now)));
+        subject.dispute_sub = Some(neighborhood.start().recipient::<DisputeMessage>());
+
+        subject.expire_disputes();
+        subject.announce_existing_disputes();
+        subject.expire_disputes();
+
+        System::current().stop();
+        system.run();
+        assert_eq!(*expire_parameters_arc.lock().unwrap(), vec![now, now]);
+        let neighborhood_recording = neighborhood_recording_arc.lock().unwrap();
+        assert_eq!(neighborhood_recording.len(), 2);
+        assert_eq!(
+            neighborhood_recording.get_record::<DisputeMessage>(0),
+            &DisputeMessage::Open(make_wallet("still disputed"))
+        );
+        assert_eq!(
+            neighborhood_recording.get_record::<DisputeMessage>(1),
+            &DisputeMessage::Close(make_wallet("still disputed"))
+        );
+        TestLogHandler::new().exists_log_containing(&format!(
+            "INFO: Accountant: Dispute of billing claim from {} has run out",
+            make_wallet("still disputed")
+        ));
+    }
+
+    #[test]
+    fn billing_claim_from_stranger_is_tolerated_up_to_permanent_debt_allowed() {
+        let (_, dispute_parameters, _) = reconcile_billing_claim_against(
+            "billing_claim_from_stranger_is_tolerated_up_to_permanent_debt_allowed",
+            None,
+            DEFAULT_PAYMENT_CURVES.permanent_debt_allowed_gwub as u64,
+            false,
+        );
+
+        assert!(dispute_parameters.is_empty());
+    }
+
+    #[test]
+    fn billing_claim_is_not_checked_while_our_payment_is_settling() {
+        init_test_logging();
+        let mut account = make_creditor_account(0);
+        account.last_paid_timestamp = SystemTime::now();
+        account.pending_payment_transaction = Some(H256::from("paid".keccak256()));
+
+        let (_, dispute_parameters, _) = reconcile_billing_claim_against(
+            "billing_claim_is_not_checked_while_our_payment_is_settling",
+            Some(account),
+            5_000_000_000,
+            false,
+        );
+
+        assert!(dispute_parameters.is_empty());
+        TestLogHandler::new().exists_log_containing(&format!(
+            "DEBUG: Accountant: Not checking billing claim of 5000000000 gwub from {}, {}: our last payment is still settling",
+            make_wallet("creditor"),
+            make_wallet("creditor exit")
+        ));
     }
 
     #[test]
@@ -1798,6 +4065,7 @@ pub mod tests {
             AccountantConfig {
                 payable_scan_interval: Duration::from_secs(100),
                 payment_received_scan_interval: Duration::from_secs(100),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
             },
             make_wallet("hi"),
         );
@@ -1813,37 +4081,361 @@ pub mod tests {
             payable_dao_mock,
             receivable_dao_mock,
             banned_dao_mock,
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
             null_config(),
         );
-        let system = System::new("report_routing_service_message_is_received");
+        let system = System::new("report_routing_service_message_is_received");
+        let subject_addr: Addr<Accountant> = subject.start();
+        subject_addr
+            .try_send(BindMessage {
+                peer_actors: peer_actors_builder().build(),
+            })
+            .unwrap();
+
+        let paying_wallet = make_wallet("booga");
+        subject_addr
+            .try_send(ReportRoutingServiceProvidedMessage {
+                paying_wallet: paying_wallet.clone(),
+                payload_size: 1234,
+                service_rate: 42,
+                byte_rate: 24,
+            })
+            .unwrap();
+
+        System::current().stop_with_code(0);
+        system.run();
+        let more_money_receivable_parameters = more_money_receivable_parameters_arc.lock().unwrap();
+        assert_eq!(
+            more_money_receivable_parameters[0],
+            (make_wallet("booga"), (1 * 42) + (1234 * 24))
+        );
+        TestLogHandler::new().exists_log_containing(&format!(
+            "DEBUG: Accountant: Charging routing of 1234 bytes to wallet {}",
+            paying_wallet
+        ));
+    }
+
+    #[test]
+    fn bytes_relayed_recently_only_counts_the_last_hour() {
+        let mut subject = Accountant::new(
+            &bc_from_ac_plus_earning_wallet(
+                AccountantConfig {
+                    payable_scan_interval: Duration::from_secs(100),
+                    payment_received_scan_interval: Duration::from_secs(100),
+                    payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
+                },
+                make_wallet("hi"),
+            ),
+            Box::new(PayableDaoMock::new()),
+            Box::new(ReceivableDaoMock::new()),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            null_config(),
+        );
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000 * 60);
+        let minutes_later = |minutes: u64| start + Duration::from_secs(minutes * 60);
+
+        subject.count_bytes_relayed(100, start);
+        subject.count_bytes_relayed(200, minutes_later(0));
+        subject.count_bytes_relayed(400, minutes_later(30));
+        subject.count_bytes_relayed(800, minutes_later(59));
+
+        assert_eq!(subject.bytes_relayed_recently(minutes_later(59)), 1500);
+        assert_eq!(subject.bytes_relayed_recently(minutes_later(60)), 1200);
+        assert_eq!(subject.bytes_relayed_recently(minutes_later(90)), 800);
+        assert_eq!(subject.bytes_relayed_recently(minutes_later(200)), 0);
+        assert_eq!(subject.bytes_relayed, 1500);
+        assert!(subject.bytes_relayed_by_minute.is_empty());
+    }
+
+    #[test]
+    fn node_statistics_report_bytes_relayed_and_exit_bytes_served() {
+        let config = bc_from_ac_plus_earning_wallet(
+            AccountantConfig {
+                payable_scan_interval: Duration::from_secs(100),
+                payment_received_scan_interval: Duration::from_secs(100),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
+            },
+            make_wallet("hi"),
+        );
+        let payable_dao_mock = Box::new(PayableDaoMock::new().non_pending_payables_result(vec![]));
+        let receivable_dao_mock = Box::new(ReceivableDaoMock::new());
+        let subject = Accountant::new(
+            &config,
+            payable_dao_mock,
+            receivable_dao_mock,
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            null_config(),
+        );
+        let mut system = System::new("node_statistics_report_bytes_relayed_and_exit_bytes_served");
+        let subject_addr: Addr<Accountant> = subject.start();
+        vec![1234, 4321].into_iter().for_each(|payload_size| {
+            subject_addr
+                .try_send(ReportRoutingServiceProvidedMessage {
+                    paying_wallet: make_wallet("booga"),
+                    payload_size,
+                    service_rate: 42,
+                    byte_rate: 24,
+                })
+                .unwrap()
+        });
+        subject_addr
+            .try_send(ReportExitServiceProvidedMessage {
+                paying_wallet: make_wallet("booga"),
+                payload_size: 555,
+                service_rate: 42,
+                byte_rate: 24,
+            })
+            .unwrap();
+
+        let result = system
+            .block_on(subject_addr.send(GetNodeStatisticsMessage {}))
+            .unwrap();
+
+        assert_eq!(
+            result,
+            NodeStatistics {
+                bytes_relayed: 5555,
+                bytes_relayed_last_hour: 5555,
+                exit_bytes_served: 555,
+                ..NodeStatistics::default()
+            }
+        );
+    }
+
+    #[test]
+    fn node_statistics_tell_the_time_by_the_accountants_clock() {
+        let config = bc_from_ac_plus_earning_wallet(
+            AccountantConfig {
+                payable_scan_interval: Duration::from_secs(100),
+                payment_received_scan_interval: Duration::from_secs(100),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
+            },
+            make_wallet("hi"),
+        );
+        let clock = ClockMock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000 * 60));
+        let subject = Accountant::new(
+            &config,
+            Box::new(PayableDaoMock::new().non_pending_payables_result(vec![])),
+            Box::new(ReceivableDaoMock::new()),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            null_config(),
+        )
+        .with_clock(Box::new(clock.clone()));
+        let mut system = System::new("node_statistics_tell_the_time_by_the_accountants_clock");
+        let subject_addr: Addr<Accountant> = subject.start();
+        subject_addr
+            .try_send(ReportRoutingServiceProvidedMessage {
+                paying_wallet: make_wallet("booga"),
+                payload_size: 1234,
+                service_rate: 42,
+                byte_rate: 24,
+            })
+            .unwrap();
+        let before = system
+            .block_on(subject_addr.send(GetNodeStatisticsMessage {}))
+            .unwrap();
+
+        clock.advance(Duration::from_secs(60 * 60));
+        let after = system
+            .block_on(subject_addr.send(GetNodeStatisticsMessage {}))
+            .unwrap();
+
+        assert_eq!(before.bytes_relayed_last_hour, 1234);
+        assert_eq!(after.bytes_relayed_last_hour, 0);
+        assert_eq!(after.bytes_relayed, 1234);
+    }
+
+    #[test]
+    fn health_says_whether_the_database_is_writable() {
+        let config = bc_from_ac_plus_earning_wallet(
+            AccountantConfig {
+                payable_scan_interval: Duration::from_secs(100),
+                payment_received_scan_interval: Duration::from_secs(100),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
+            },
+            make_wallet("hi"),
+        );
+        let persistent_configuration = PersistentConfigurationMock::new()
+            .start_block_result(0)
+            .check_writable_result(Ok(()))
+            .check_writable_result(Err("DatabaseError(\"disk I/O error\")".to_string()));
+        let subject = Accountant::new(
+            &config,
+            Box::new(PayableDaoMock::new().non_pending_payables_result(vec![])),
+            Box::new(ReceivableDaoMock::new()),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            Box::new(persistent_configuration),
+        );
+        let mut system = System::new("health_says_whether_the_database_is_writable");
         let subject_addr: Addr<Accountant> = subject.start();
-        subject_addr
-            .try_send(BindMessage {
-                peer_actors: peer_actors_builder().build(),
-            })
-            .unwrap();
 
-        let paying_wallet = make_wallet("booga");
-        subject_addr
-            .try_send(ReportRoutingServiceProvidedMessage {
-                paying_wallet: paying_wallet.clone(),
-                payload_size: 1234,
-                service_rate: 42,
-                byte_rate: 24,
-            })
+        let writable = system
+            .block_on(subject_addr.send(GetHealthMessage {}))
             .unwrap();
+        let not_writable = system
+            .block_on(subject_addr.send(GetHealthMessage {}))
+            .unwrap();
+
+        assert_eq!(
+            writable,
+            vec![ComponentHealth::new(
+                "database",
+                HealthState::Ready,
+                "writable"
+            )]
+        );
+        assert_eq!(
+            not_writable,
+            vec![ComponentHealth::new(
+                "database",
+                HealthState::NotReady,
+                "not writable: DatabaseError(\"disk I/O error\")"
+            )]
+        );
+    }
+
+    #[test]
+    fn service_charge_is_exact_for_odd_payload_sizes() {
+        vec![0usize, 1, 7, 999, 1_500, 4_097, 65_537]
+            .into_iter()
+            .for_each(|payload_size| {
+                assert_eq!(
+                    service_charge(42, 24, payload_size),
+                    42 + (24 * payload_size as u64),
+                    "payload_size {}",
+                    payload_size
+                )
+            });
+    }
+
+    #[test]
+    fn service_charge_saturates_instead_of_wrapping() {
+        assert_eq!(service_charge(1, u64::MAX / 2, 3), u64::MAX);
+        assert_eq!(service_charge(u64::MAX, 1, 1), u64::MAX);
+    }
+
+    #[test]
+    fn invoices_match_synthetic_traffic_of_known_size() {
+        let traffic: Vec<usize> = vec![1, 7, 999, 1_500, 4_097, 65_537];
+        let traffic_bytes: u64 = traffic.iter().map(|size| *size as u64).sum();
+        let consumer_wallet = make_wallet("consumer");
+        let provider_wallet = make_wallet("provider");
+        let receivable_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let payable_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let config = |wallet: &Wallet| {
+            bc_from_ac_plus_earning_wallet(
+                AccountantConfig {
+                    payable_scan_interval: Duration::from_secs(100),
+                    payment_received_scan_interval: Duration::from_secs(100),
+                    payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
+                },
+                wallet.clone(),
+            )
+        };
+        let provider = Accountant::new(
+            &config(&provider_wallet),
+            Box::new(PayableDaoMock::new().non_pending_payables_result(vec![])),
+            Box::new(
+                ReceivableDaoMock::new()
+                    .more_money_receivable_parameters(receivable_parameters_arc.clone()),
+            ),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            null_config(),
+        );
+        let consumer = Accountant::new(
+            &config(&consumer_wallet),
+            Box::new(
+                PayableDaoMock::new()
+                    .non_pending_payables_result(vec![])
+                    .more_money_payable_parameters(payable_parameters_arc.clone()),
+            ),
+            Box::new(ReceivableDaoMock::new()),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            null_config(),
+        );
+        let system = System::new("invoices_match_synthetic_traffic_of_known_size");
+        let provider_addr: Addr<Accountant> = provider.start();
+        let consumer_addr: Addr<Accountant> = consumer.start();
+
+        traffic.iter().for_each(|payload_size| {
+            provider_addr
+                .try_send(ReportRoutingServiceProvidedMessage {
+                    paying_wallet: consumer_wallet.clone(),
+                    payload_size: *payload_size,
+                    service_rate: 100,
+                    byte_rate: 3,
+                })
+                .unwrap();
+            provider_addr
+                .try_send(ReportExitServiceProvidedMessage {
+                    paying_wallet: consumer_wallet.clone(),
+                    payload_size: *payload_size,
+                    service_rate: 200,
+                    byte_rate: 5,
+                })
+                .unwrap();
+            consumer_addr
+                .try_send(ReportRoutingServiceConsumedMessage {
+                    earning_wallet: provider_wallet.clone(),
+                    payload_size: *payload_size,
+                    service_rate: 100,
+                    byte_rate: 3,
+                    profile_opt: None,
+                })
+                .unwrap();
+            consumer_addr
+                .try_send(ReportExitServiceConsumedMessage {
+                    earning_wallet: provider_wallet.clone(),
+                    payload_size: *payload_size,
+                    service_rate: 200,
+                    byte_rate: 5,
+                    profile_opt: None,
+                })
+                .unwrap();
+        });
 
         System::current().stop_with_code(0);
         system.run();
-        let more_money_receivable_parameters = more_money_receivable_parameters_arc.lock().unwrap();
+        let expected_invoice = (traffic.len() as u64 * (100 + 200)) + (traffic_bytes * (3 + 5));
+        let receivable_parameters = receivable_parameters_arc.lock().unwrap();
+        let payable_parameters = payable_parameters_arc.lock().unwrap();
+        assert_eq!(receivable_parameters.len(), traffic.len() * 2);
+        assert_eq!(payable_parameters.len(), traffic.len() * 2);
+        assert!(receivable_parameters
+            .iter()
+            .all(|(wallet, _)| wallet == &consumer_wallet));
+        assert!(payable_parameters
+            .iter()
+            .all(|(wallet, _)| wallet == &provider_wallet));
         assert_eq!(
-            more_money_receivable_parameters[0],
-            (make_wallet("booga"), (1 * 42) + (1234 * 24))
+            receivable_parameters
+                .iter()
+                .map(|(_, amount)| amount)
+                .sum::<u64>(),
+            expected_invoice
         );
-        TestLogHandler::new().exists_log_containing(&format!(
-            "DEBUG: Accountant: Charging routing of 1234 bytes to wallet {}",
-            paying_wallet
-        ));
+        assert_eq!(
+            payable_parameters
+                .iter()
+                .map(|(_, amount)| amount)
+                .sum::<u64>(),
+            expected_invoice
+        );
+        assert_eq!(receivable_parameters[0].1, 100 + 3);
+        assert_eq!(receivable_parameters[11].1, 200 + (5 * 65_537));
     }
 
     #[test]
@@ -1854,6 +4446,7 @@ pub mod tests {
             AccountantConfig {
                 payable_scan_interval: Duration::from_secs(100),
                 payment_received_scan_interval: Duration::from_secs(100),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
             },
             consuming_wallet.clone(),
             make_wallet("our earning wallet"),
@@ -1870,6 +4463,8 @@ pub mod tests {
             payable_dao_mock,
             receivable_dao_mock,
             banned_dao_mock,
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
             null_config(),
         );
         let system = System::new("report_routing_service_message_is_received");
@@ -1910,6 +4505,7 @@ pub mod tests {
             AccountantConfig {
                 payable_scan_interval: Duration::from_secs(100),
                 payment_received_scan_interval: Duration::from_secs(100),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
             },
             earning_wallet.clone(),
         );
@@ -1925,6 +4521,8 @@ pub mod tests {
             payable_dao_mock,
             receivable_dao_mock,
             banned_dao_mock,
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
             null_config(),
         );
         let system = System::new("report_routing_service_message_is_received");
@@ -1964,6 +4562,7 @@ pub mod tests {
             AccountantConfig {
                 payable_scan_interval: Duration::from_secs(100),
                 payment_received_scan_interval: Duration::from_secs(100),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
             },
             make_wallet("hi"),
         );
@@ -1980,6 +4579,8 @@ pub mod tests {
             payable_dao_mock,
             receivable_dao_mock,
             banned_dao_mock,
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
             null_config(),
         );
         let system = System::new("report_routing_service_consumed_message_is_received");
@@ -1997,6 +4598,7 @@ pub mod tests {
                 payload_size: 1234,
                 service_rate: 42,
                 byte_rate: 24,
+                profile_opt: None,
             })
             .unwrap();
 
@@ -2012,6 +4614,371 @@ pub mod tests {
         );
     }
 
+    fn make_profile_config(consuming_profiles: Vec<ConsumingProfile>) -> BootstrapperConfig {
+        let mut config = bc_from_ac_plus_earning_wallet(
+            AccountantConfig {
+                payable_scan_interval: Duration::from_secs(100),
+                payment_received_scan_interval: Duration::from_secs(100),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
+            },
+            make_wallet("our earning wallet"),
+        );
+        config.consuming_profiles = consuming_profiles;
+        config
+    }
+
+    fn make_profile(name: &str, budget_opt: Option<u64>) -> ConsumingProfile {
+        ConsumingProfile {
+            name: name.to_string(),
+            selector: ProfileSelector::OriginatingPorts(10000..=19999),
+            budget_opt,
+            consuming_wallet_opt: None,
+        }
+    }
+
+    #[test]
+    fn services_consumed_are_recorded_against_their_consuming_profile() {
+        let config = make_profile_config(vec![
+            make_profile("work", Some(1_000_000)),
+            make_profile("personal", None),
+        ]);
+        let more_money_payable_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let add_consumption_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let mut subject = Accountant::new(
+            &config,
+            Box::new(
+                PayableDaoMock::new()
+                    .more_money_payable_parameters(more_money_payable_parameters_arc.clone()),
+            ),
+            Box::new(ReceivableDaoMock::new()),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(
+                ProfileConsumptionDaoMock::new()
+                    .add_consumption_parameters(&add_consumption_parameters_arc),
+            ),
+            null_config(),
+        );
+        let booga = make_wallet("booga");
+
+        subject.record_service_consumed(42, 24, 1000, &booga, Some("work".to_string()));
+        subject.record_service_consumed(42, 24, 2000, &booga, Some("personal".to_string()));
+        subject.record_service_consumed(42, 24, 3000, &booga, Some("nonesuch".to_string()));
+        subject.record_service_consumed(42, 24, 4000, &booga, None);
+
+        assert_eq!(
+            *add_consumption_parameters_arc.lock().unwrap(),
+            vec![
+                ("work".to_string(), 42 + 24 * 1000),
+                ("personal".to_string(), 42 + 24 * 2000),
+            ]
+        );
+        assert_eq!(more_money_payable_parameters_arc.lock().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn profile_financial_statistics_come_from_recorded_consumption() {
+        let config = make_profile_config(vec![
+            make_profile("work", Some(1_000_000)),
+            make_profile("personal", None),
+        ]);
+        let subject = Accountant::new(
+            &config,
+            Box::new(PayableDaoMock::new()),
+            Box::new(ReceivableDaoMock::new()),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new().consumption_result(vec![
+                ("personal".to_string(), 1234),
+                ("retired".to_string(), 5678),
+            ])),
+            null_config(),
+        );
+
+        assert_eq!(
+            subject.profile_financial_statistics(),
+            vec![
+                ProfileFinancialStatistics {
+                    name: "work".to_string(),
+                    consumed: 0,
+                    budget: Some(1_000_000),
+                },
+                ProfileFinancialStatistics {
+                    name: "personal".to_string(),
+                    consumed: 1234,
+                    budget: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn services_consumed_to_our_own_wallet_are_not_charged_to_consuming_profile() {
+        let config = make_profile_config(vec![make_profile("work", None)]);
+        let add_consumption_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let mut subject = Accountant::new(
+            &config,
+            Box::new(PayableDaoMock::new()),
+            Box::new(ReceivableDaoMock::new()),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(
+                ProfileConsumptionDaoMock::new()
+                    .add_consumption_parameters(&add_consumption_parameters_arc),
+            ),
+            null_config(),
+        );
+
+        subject.record_service_consumed(
+            42,
+            24,
+            1000,
+            &make_wallet("our earning wallet"),
+            Some("work".to_string()),
+        );
+
+        assert!(add_consumption_parameters_arc.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn consuming_profile_with_its_own_wallet_owes_its_debts_from_that_wallet() {
+        let mut profile = make_profile("work", None);
+        profile.consuming_wallet_opt = Some(make_wallet("work wallet"));
+        let config = make_profile_config(vec![profile]);
+        let own_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let profile_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let mut subject = Accountant::new(
+            &config,
+            Box::new(
+                PayableDaoMock::new().more_money_payable_parameters(own_parameters_arc.clone()),
+            ),
+            Box::new(ReceivableDaoMock::new()),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            null_config(),
+        )
+        .with_profile_payable_dao(
+            make_wallet("work wallet"),
+            Box::new(
+                PayableDaoMock::new().more_money_payable_parameters(profile_parameters_arc.clone()),
+            ),
+        );
+
+        subject.record_service_consumed(0, 1, 100, &make_wallet("booga"), Some("work".to_string()));
+        subject.record_service_consumed(0, 1, 200, &make_wallet("booga"), None);
+        subject.record_service_consumed(0, 1, 300, &make_wallet("work wallet"), None);
+
+        assert_eq!(
+            *profile_parameters_arc.lock().unwrap(),
+            vec![(make_wallet("booga"), 100)]
+        );
+        assert_eq!(
+            *own_parameters_arc.lock().unwrap(),
+            vec![(make_wallet("booga"), 200)]
+        );
+    }
+
+    #[test]
+    fn consuming_profile_is_shut_off_once_when_its_budget_is_used_up() {
+        init_test_logging();
+        let config = make_profile_config(vec![make_profile("thrifty", Some(150))]);
+        let (proxy_server, _, proxy_server_recording_arc) = make_recorder();
+        let system = System::new("consuming_profile_is_shut_off_once_when_its_budget_is_used_up");
+        let mut subject = Accountant::new(
+            &config,
+            Box::new(PayableDaoMock::new()),
+            Box::new(ReceivableDaoMock::new()),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(
+                ProfileConsumptionDaoMock::new()
+                    .add_consumption_result(100)
+                    .add_consumption_result(200)
+                    .add_consumption_result(300),
+            ),
+            null_config(),
+        );
+        subject.profile_exhausted_sub = Some(
+            proxy_server
+                .start()
+                .recipient::<ConsumingProfileExhaustedMessage>(),
+        );
+
+        (0..3).for_each(|_| {
+            subject.record_service_consumed(
+                100,
+                0,
+                0,
+                &make_wallet("booga"),
+                Some("thrifty".to_string()),
+            )
+        });
+
+        System::current().stop();
+        system.run();
+        let proxy_server_recording = proxy_server_recording_arc.lock().unwrap();
+        assert_eq!(proxy_server_recording.len(), 1);
+        assert_eq!(
+            proxy_server_recording.get_record::<ConsumingProfileExhaustedMessage>(0),
+            &ConsumingProfileExhaustedMessage {
+                name: "thrifty".to_string()
+            }
+        );
+        let tlh = TestLogHandler::new();
+        tlh.exists_log_containing(
+            "WARN: Accountant: Consuming profile 'thrifty' has used up its budget of 150: 200 consumed; refusing its traffic",
+        );
+        tlh.exists_no_log_containing("its budget of 150: 300 consumed");
+    }
+
+    #[test]
+    fn profiles_that_used_up_their_budgets_before_a_restart_are_announced() {
+        let config = make_profile_config(vec![
+            make_profile("thrifty", Some(150)),
+            make_profile("frugal", Some(1000)),
+            make_profile("lavish", None),
+        ]);
+        let (proxy_server, _, proxy_server_recording_arc) = make_recorder();
+        let system =
+            System::new("profiles_that_used_up_their_budgets_before_a_restart_are_announced");
+        let mut subject = Accountant::new(
+            &config,
+            Box::new(PayableDaoMock::new()),
+            Box::new(ReceivableDaoMock::new()),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new().consumption_result(vec![
+                ("frugal".to_string(), 999),
+                ("lavish".to_string(), 1_000_000),
+                ("thrifty".to_string(), 150),
+            ])),
+            null_config(),
+        );
+        subject.profile_exhausted_sub = Some(
+            proxy_server
+                .start()
+                .recipient::<ConsumingProfileExhaustedMessage>(),
+        );
+
+        subject.announce_exhausted_profiles();
+
+        System::current().stop();
+        system.run();
+        let proxy_server_recording = proxy_server_recording_arc.lock().unwrap();
+        assert_eq!(proxy_server_recording.len(), 1);
+        assert_eq!(
+            proxy_server_recording.get_record::<ConsumingProfileExhaustedMessage>(0),
+            &ConsumingProfileExhaustedMessage {
+                name: "thrifty".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn payments_from_a_profile_wallet_are_recorded_with_its_payables() {
+        let own_payments_sent_arc = Arc::new(Mutex::new(vec![]));
+        let profile_payments_sent_arc = Arc::new(Mutex::new(vec![]));
+        let profile_cancelled_arc = Arc::new(Mutex::new(vec![]));
+        let payment = Payment::new(
+            make_wallet("creditor"),
+            1234,
+            H256::from_uint(&U256::from(1)),
+        );
+        let system = System::new("payments_from_a_profile_wallet_are_recorded_with_its_payables");
+        let subject = Accountant::new(
+            &make_profile_config(vec![]),
+            Box::new(PayableDaoMock::new().payments_sent_parameters(&own_payments_sent_arc)),
+            Box::new(ReceivableDaoMock::new()),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            null_config(),
+        )
+        .with_profile_payable_dao(
+            make_wallet("work wallet"),
+            Box::new(
+                PayableDaoMock::new()
+                    .payments_sent_parameters(&profile_payments_sent_arc)
+                    .payment_cancelled_parameters(&profile_cancelled_arc),
+            ),
+        );
+        let addr = subject.start();
+
+        addr.try_send(SentPayments {
+            payments: vec![Ok(payment.clone())],
+            profile_wallet_opt: Some(make_wallet("work wallet")),
+        })
+        .unwrap();
+        addr.try_send(ResolvedPayment {
+            payment: payment.clone(),
+            mined_transaction: None,
+            profile_wallet_opt: Some(make_wallet("work wallet")),
+        })
+        .unwrap();
+
+        System::current().stop();
+        system.run();
+        assert!(own_payments_sent_arc.lock().unwrap().is_empty());
+        assert_eq!(
+            *profile_payments_sent_arc.lock().unwrap(),
+            vec![vec![payment.clone()]]
+        );
+        assert_eq!(*profile_cancelled_arc.lock().unwrap(), vec![payment]);
+    }
+
+    #[test]
+    fn scan_for_payables_pays_each_profile_wallets_debts_from_that_wallet() {
+        let config = make_profile_config(vec![]);
+        let now = to_time_t(SystemTime::now());
+        let accounts = vec![PayableAccount {
+            wallet: make_wallet("creditor"),
+            balance: DEFAULT_PAYMENT_CURVES.balance_to_decrease_from_gwub + 1,
+            last_paid_timestamp: from_time_t(
+                now - DEFAULT_PAYMENT_CURVES.payment_suggested_after_sec - 10,
+            ),
+            pending_payment_transaction: None,
+        }];
+        let (blockchain_bridge, _, blockchain_bridge_recording_arc) = make_recorder();
+        let blockchain_bridge = blockchain_bridge.report_accounts_payable_response(Ok(vec![]));
+        let (accountant, _, _) = make_recorder();
+        let system =
+            System::new("scan_for_payables_pays_each_profile_wallets_debts_from_that_wallet");
+        let mut subject = Accountant::new(
+            &config,
+            Box::new(PayableDaoMock::new()),
+            Box::new(ReceivableDaoMock::new()),
+            Box::new(BannedDaoMock::new()),
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            null_config(),
+        )
+        .with_profile_payable_dao(
+            make_wallet("work wallet"),
+            Box::new(PayableDaoMock::new().non_pending_payables_result(accounts.clone())),
+        );
+        subject.report_accounts_payable_sub = Some(
+            blockchain_bridge
+                .start()
+                .recipient::<ReportAccountsPayable>(),
+        );
+        subject.report_sent_payments_sub = Some(accountant.start().recipient::<SentPayments>());
+
+        subject.scan_for_payables();
+
+        System::current().stop();
+        system.run();
+        let blockchain_bridge_recording = blockchain_bridge_recording_arc.lock().unwrap();
+        assert_eq!(blockchain_bridge_recording.len(), 1);
+        assert_eq!(
+            blockchain_bridge_recording.get_record::<ReportAccountsPayable>(0),
+            &ReportAccountsPayable {
+                accounts,
+                profile_wallet_opt: Some(make_wallet("work wallet")),
+            }
+        );
+    }
+
     #[test]
     fn report_routing_service_consumed_message_is_received_for_our_consuming_wallet() {
         init_test_logging();
@@ -2020,6 +4987,7 @@ pub mod tests {
             AccountantConfig {
                 payable_scan_interval: Duration::from_secs(100),
                 payment_received_scan_interval: Duration::from_secs(100),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
             },
             consuming_wallet.clone(),
             make_wallet("the earning wallet"),
@@ -2037,6 +5005,8 @@ pub mod tests {
             payable_dao_mock,
             receivable_dao_mock,
             banned_dao_mock,
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
             null_config(),
         );
         let system = System::new("report_routing_service_consumed_message_is_received");
@@ -2053,6 +5023,7 @@ pub mod tests {
                 payload_size: 1234,
                 service_rate: 42,
                 byte_rate: 24,
+                profile_opt: None,
             })
             .unwrap();
 
@@ -2074,6 +5045,7 @@ pub mod tests {
             AccountantConfig {
                 payable_scan_interval: Duration::from_secs(100),
                 payment_received_scan_interval: Duration::from_secs(100),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
             },
             earning_wallet.clone(),
         );
@@ -2090,6 +5062,8 @@ pub mod tests {
             payable_dao_mock,
             receivable_dao_mock,
             banned_dao_mock,
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
             null_config(),
         );
         let system = System::new("report_routing_service_consumed_message_is_received");
@@ -2106,6 +5080,7 @@ pub mod tests {
                 payload_size: 1234,
                 service_rate: 42,
                 byte_rate: 24,
+                profile_opt: None,
             })
             .unwrap();
 
@@ -2126,6 +5101,7 @@ pub mod tests {
             AccountantConfig {
                 payable_scan_interval: Duration::from_secs(100),
                 payment_received_scan_interval: Duration::from_secs(100),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
             },
             make_wallet("hi"),
         );
@@ -2141,6 +5117,8 @@ pub mod tests {
             payable_dao_mock,
             receivable_dao_mock,
             banned_dao_mock,
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
             null_config(),
         );
         let system = System::new("report_exit_service_provided_message_is_received");
@@ -2182,6 +5160,7 @@ pub mod tests {
             AccountantConfig {
                 payable_scan_interval: Duration::from_secs(100),
                 payment_received_scan_interval: Duration::from_secs(100),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
             },
             consuming_wallet.clone(),
             make_wallet("my earning wallet"),
@@ -2198,6 +5177,8 @@ pub mod tests {
             payable_dao_mock,
             receivable_dao_mock,
             banned_dao_mock,
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
             null_config(),
         );
         let system = System::new("report_exit_service_provided_message_is_received");
@@ -2238,6 +5219,7 @@ pub mod tests {
             AccountantConfig {
                 payable_scan_interval: Duration::from_secs(100),
                 payment_received_scan_interval: Duration::from_secs(100),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
             },
             earning_wallet.clone(),
         );
@@ -2253,6 +5235,8 @@ pub mod tests {
             payable_dao_mock,
             receivable_dao_mock,
             banned_dao_mock,
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
             null_config(),
         );
         let system = System::new("report_exit_service_provided_message_is_received");
@@ -2285,6 +5269,67 @@ pub mod tests {
         ));
     }
 
+    #[test]
+    fn report_exit_service_provided_message_is_received_from_our_exit_earning_wallet() {
+        init_test_logging();
+        let exit_earning_wallet = make_wallet("my exit earning wallet");
+        let mut config = bc_from_ac_plus_earning_wallet(
+            AccountantConfig {
+                payable_scan_interval: Duration::from_secs(100),
+                payment_received_scan_interval: Duration::from_secs(100),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
+            },
+            make_wallet("my earning wallet"),
+        );
+        config.exit_earning_wallet_opt = Some(exit_earning_wallet.clone());
+        let more_money_receivable_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let payable_dao_mock = Box::new(PayableDaoMock::new().non_pending_payables_result(vec![]));
+        let receivable_dao_mock = Box::new(
+            ReceivableDaoMock::new()
+                .more_money_receivable_parameters(more_money_receivable_parameters_arc.clone()),
+        );
+        let banned_dao_mock = Box::new(BannedDaoMock::new());
+        let subject = Accountant::new(
+            &config,
+            payable_dao_mock,
+            receivable_dao_mock,
+            banned_dao_mock,
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
+            null_config(),
+        );
+        let system = System::new(
+            "report_exit_service_provided_message_is_received_from_our_exit_earning_wallet",
+        );
+        let subject_addr: Addr<Accountant> = subject.start();
+        subject_addr
+            .try_send(BindMessage {
+                peer_actors: peer_actors_builder().build(),
+            })
+            .unwrap();
+
+        subject_addr
+            .try_send(ReportExitServiceProvidedMessage {
+                paying_wallet: exit_earning_wallet.clone(),
+                payload_size: 1234,
+                service_rate: 42,
+                byte_rate: 24,
+            })
+            .unwrap();
+
+        System::current().stop();
+        system.run();
+        assert!(more_money_receivable_parameters_arc
+            .lock()
+            .unwrap()
+            .is_empty());
+
+        TestLogHandler::new().exists_log_containing(&format!(
+            "INFO: Accountant: Not recording service provided for our wallet {}",
+            exit_earning_wallet,
+        ));
+    }
+
     #[test]
     fn report_exit_service_consumed_message_is_received() {
         init_test_logging();
@@ -2292,6 +5337,7 @@ pub mod tests {
             AccountantConfig {
                 payable_scan_interval: Duration::from_secs(100),
                 payment_received_scan_interval: Duration::from_secs(100),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
             },
             make_wallet("hi"),
         );
@@ -2308,6 +5354,8 @@ pub mod tests {
             payable_dao_mock,
             receivable_dao_mock,
             banned_dao_mock,
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
             null_config(),
         );
         let system = System::new("report_exit_service_consumed_message_is_received");
@@ -2325,6 +5373,7 @@ pub mod tests {
                 payload_size: 1234,
                 service_rate: 42,
                 byte_rate: 24,
+                profile_opt: None,
             })
             .unwrap();
 
@@ -2349,6 +5398,7 @@ pub mod tests {
             AccountantConfig {
                 payable_scan_interval: Duration::from_secs(100),
                 payment_received_scan_interval: Duration::from_secs(100),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
             },
             consuming_wallet.clone(),
             make_wallet("own earning wallet"),
@@ -2366,6 +5416,8 @@ pub mod tests {
             payable_dao_mock,
             receivable_dao_mock,
             banned_dao_mock,
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
             null_config(),
         );
         let system = System::new("report_exit_service_consumed_message_is_received");
@@ -2382,6 +5434,7 @@ pub mod tests {
                 payload_size: 1234,
                 service_rate: 42,
                 byte_rate: 24,
+                profile_opt: None,
             })
             .unwrap();
 
@@ -2403,6 +5456,7 @@ pub mod tests {
             AccountantConfig {
                 payable_scan_interval: Duration::from_secs(100),
                 payment_received_scan_interval: Duration::from_secs(100),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
             },
             earning_wallet.clone(),
         );
@@ -2419,6 +5473,8 @@ pub mod tests {
             payable_dao_mock,
             receivable_dao_mock,
             banned_dao_mock,
+            Box::new(DisputeDaoMock::new()),
+            Box::new(ProfileConsumptionDaoMock::new()),
             null_config(),
         );
         let system = System::new("report_exit_service_consumed_message_is_received");
@@ -2435,6 +5491,7 @@ pub mod tests {
                 payload_size: 1234,
                 service_rate: 42,
                 byte_rate: 24,
+                profile_opt: None,
             })
             .unwrap();
 
@@ -10,6 +10,8 @@ use std::fmt::Debug;
 use std::time::SystemTime;
 use web3::types::H256;
 
+const DECREASE_BALANCE_SQL: &str = "insert into payable (balance, last_paid_timestamp, pending_payment_transaction, wallet_address, consuming_wallet_address) values (0 - :balance, :last_paid, :transaction, :address, :consuming) on conflict (wallet_address, consuming_wallet_address) do update set balance = balance - :balance, last_paid_timestamp = :last_paid, pending_payment_transaction = :transaction where wallet_address = :address and consuming_wallet_address = :consuming";
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct PayableAccount {
     pub wallet: Wallet,
@@ -40,7 +42,14 @@ impl Payment {
 pub trait PayableDao: Debug + Send {
     fn more_money_payable(&self, wallet: &Wallet, amount: u64);
 
-    fn payment_sent(&self, sent_payment: &Payment);
+    // Records every payment of a batch, or none of them
+    fn payments_sent(&mut self, sent_payments: &[Payment]);
+
+    // The payment went through, but in a different transaction than the one recorded for it
+    fn payment_replaced(&self, sent_payment: &Payment, replacement_transaction: H256);
+
+    // The payment never went through, so the balance is owed again
+    fn payment_cancelled(&self, sent_payment: &Payment);
 
     fn payment_confirmed(
         &self,
@@ -53,11 +62,19 @@ pub trait PayableDao: Debug + Send {
     fn account_status(&self, wallet: &Wallet) -> Option<PayableAccount>;
 
     fn non_pending_payables(&self) -> Vec<PayableAccount>;
+
+    fn payables(&self) -> Vec<PayableAccount>;
+
+    // The wallets of the consuming profiles that have payables of their own
+    fn profile_wallets(&self) -> Vec<Wallet>;
 }
 
+// Sees only the payables of one consuming wallet: those of a consuming profile with a wallet of
+// its own, or otherwise those of the Node's own consuming wallet, whose address is recorded as ''.
 #[derive(Debug)]
 pub struct PayableDaoReal {
     conn: Box<dyn ConnectionWrapper>,
+    consuming_wallet_address: String,
 }
 
 impl PayableDao for PayableDaoReal {
@@ -68,18 +85,50 @@ impl PayableDao for PayableDaoReal {
         };
     }
 
-    fn payment_sent(&self, payment: &Payment) {
-        match self.try_decrease_balance(
-            &payment.to,
-            payment.amount,
-            payment.timestamp,
-            payment.transaction,
-        ) {
+    fn payments_sent(&mut self, payments: &[Payment]) {
+        match self.try_decrease_balances(payments) {
             Ok(_) => (),
             Err(e) => panic!("Database is corrupt: {}", e),
         }
     }
 
+    fn payment_replaced(&self, payment: &Payment, replacement_transaction: H256) {
+        let replacement = format!("{:#x}", &replacement_transaction);
+        let transaction = format!("{:#x}", &payment.transaction);
+        let params: &[(&str, &dyn ToSql)] = &[
+            (":replacement", &replacement),
+            (":address", &payment.to),
+            (":consuming", &self.consuming_wallet_address),
+            (":transaction", &transaction),
+        ];
+        if let Err(e) = self.conn.write(&mut |tx| {
+            tx.execute_named("update payable set pending_payment_transaction = :replacement where wallet_address = :address and consuming_wallet_address = :consuming and pending_payment_transaction = :transaction", params)
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        }) {
+            panic!("Database is corrupt: {}", e)
+        }
+    }
+
+    fn payment_cancelled(&self, payment: &Payment) {
+        let balance = i64::try_from(payment.amount)
+            .unwrap_or_else(|_| panic!("Lost payable amount precision: {}", payment.amount));
+        let transaction = format!("{:#x}", &payment.transaction);
+        let params: &[(&str, &dyn ToSql)] = &[
+            (":balance", &balance),
+            (":address", &payment.to),
+            (":consuming", &self.consuming_wallet_address),
+            (":transaction", &transaction),
+        ];
+        if let Err(e) = self.conn.write(&mut |tx| {
+            tx.execute_named("update payable set balance = balance + :balance, pending_payment_transaction = null where wallet_address = :address and consuming_wallet_address = :consuming and pending_payment_transaction = :transaction", params)
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        }) {
+            panic!("Database is corrupt: {}", e)
+        }
+    }
+
     fn payment_confirmed(
         &self,
         _wallet: &Wallet,
@@ -92,35 +141,39 @@ impl PayableDao for PayableDaoReal {
 
     fn account_status(&self, wallet: &Wallet) -> Option<PayableAccount> {
         let mut stmt = self.conn
-            .prepare("select balance, last_paid_timestamp, pending_payment_transaction from payable where wallet_address = ?")
+            .prepare("select balance, last_paid_timestamp, pending_payment_transaction from payable where wallet_address = ? and consuming_wallet_address = ?")
             .expect("Internal error");
         match stmt
-            .query_row(&[&wallet], |row| {
-                let balance_result = row.get(0);
-                let last_paid_timestamp_result = row.get(1);
-                let pending_payment_transaction_result: Result<Option<String>, Error> = row.get(2);
-                match (
-                    balance_result,
-                    last_paid_timestamp_result,
-                    pending_payment_transaction_result,
-                ) {
-                    (Ok(balance), Ok(last_paid_timestamp), Ok(pending_payment_transaction)) => {
-                        Ok(PayableAccount {
-                            wallet: wallet.clone(),
-                            balance,
-                            last_paid_timestamp: dao_utils::from_time_t(last_paid_timestamp),
-                            pending_payment_transaction: match pending_payment_transaction {
-                                Some(tx) => match serde_json::from_value(json!(tx)) {
-                                    Ok(transaction) => Some(transaction),
-                                    Err(e) => panic!("{:?}", e),
+            .query_row(
+                &[wallet as &dyn ToSql, &self.consuming_wallet_address],
+                |row| {
+                    let balance_result = row.get(0);
+                    let last_paid_timestamp_result = row.get(1);
+                    let pending_payment_transaction_result: Result<Option<String>, Error> =
+                        row.get(2);
+                    match (
+                        balance_result,
+                        last_paid_timestamp_result,
+                        pending_payment_transaction_result,
+                    ) {
+                        (Ok(balance), Ok(last_paid_timestamp), Ok(pending_payment_transaction)) => {
+                            Ok(PayableAccount {
+                                wallet: wallet.clone(),
+                                balance,
+                                last_paid_timestamp: dao_utils::from_time_t(last_paid_timestamp),
+                                pending_payment_transaction: match pending_payment_transaction {
+                                    Some(tx) => match serde_json::from_value(json!(tx)) {
+                                        Ok(transaction) => Some(transaction),
+                                        Err(e) => panic!("{:?}", e),
+                                    },
+                                    None => None,
                                 },
-                                None => None,
-                            },
-                        })
+                            })
+                        }
+                        _ => panic!("Database is corrupt: PAYABLE table columns and/or types"),
                     }
-                    _ => panic!("Database is corrupt: PAYABLE table columns and/or types"),
-                }
-            })
+                },
+            )
             .optional()
         {
             Ok(value) => value,
@@ -130,10 +183,10 @@ impl PayableDao for PayableDaoReal {
 
     fn non_pending_payables(&self) -> Vec<PayableAccount> {
         let mut stmt = self.conn
-            .prepare("select balance, last_paid_timestamp, wallet_address from payable where pending_payment_transaction is null")
+            .prepare("select balance, last_paid_timestamp, wallet_address from payable where pending_payment_transaction is null and consuming_wallet_address = ?")
             .expect("Internal error");
 
-        stmt.query_map(NO_PARAMS, |row| {
+        stmt.query_map(&[&self.consuming_wallet_address], |row| {
             let balance_result = row.get(0);
             let last_paid_timestamp_result = row.get(1);
             let wallet_result: Result<Wallet, rusqlite::Error> = row.get(2);
@@ -151,59 +204,117 @@ impl PayableDao for PayableDaoReal {
         .flat_map(|v| v)
         .collect()
     }
-}
 
-impl PayableDaoReal {
-    pub fn new(conn: Box<dyn ConnectionWrapper>) -> PayableDaoReal {
-        PayableDaoReal { conn }
+    fn payables(&self) -> Vec<PayableAccount> {
+        let mut stmt = self.conn
+            .prepare("select balance, last_paid_timestamp, wallet_address, pending_payment_transaction from payable where consuming_wallet_address = ?")
+            .expect("Internal error");
+
+        stmt.query_map(&[&self.consuming_wallet_address], |row| {
+            let balance_result = row.get(0);
+            let last_paid_timestamp_result = row.get(1);
+            let wallet_result: Result<Wallet, rusqlite::Error> = row.get(2);
+            let pending_payment_transaction_result: Result<Option<String>, Error> = row.get(3);
+            match (
+                balance_result,
+                last_paid_timestamp_result,
+                wallet_result,
+                pending_payment_transaction_result,
+            ) {
+                (
+                    Ok(balance),
+                    Ok(last_paid_timestamp),
+                    Ok(wallet),
+                    Ok(pending_payment_transaction),
+                ) => Ok(PayableAccount {
+                    wallet,
+                    balance,
+                    last_paid_timestamp: dao_utils::from_time_t(last_paid_timestamp),
+                    pending_payment_transaction: match pending_payment_transaction {
+                        Some(tx) => match serde_json::from_value(json!(tx)) {
+                            Ok(transaction) => Some(transaction),
+                            Err(e) => panic!("{:?}", e),
+                        },
+                        None => None,
+                    },
+                }),
+                _ => panic!("Database is corrupt: PAYABLE table columns and/or types"),
+            }
+        })
+        .expect("Database is corrupt")
+        .flat_map(|v| v)
+        .collect()
     }
 
-    fn try_increase_balance(&self, wallet: &Wallet, amount: u64) -> Result<bool, String> {
+    fn profile_wallets(&self) -> Vec<Wallet> {
         let mut stmt = self
             .conn
-            .prepare("insert into payable (wallet_address, balance, last_paid_timestamp, pending_payment_transaction) values (:address, :balance, strftime('%s','now'), null) on conflict (wallet_address) do update set balance = balance + :balance where wallet_address = :address")
+            .prepare("select distinct consuming_wallet_address from payable where consuming_wallet_address != '' order by consuming_wallet_address")
             .expect("Internal error");
-        let params: &[(&str, &dyn ToSql)] = &[
-            (":address", &wallet),
-            (
-                ":balance",
-                &i64::try_from(amount)
-                    .unwrap_or_else(|_| panic!("Lost payable amount precision: {}", amount)),
-            ),
-        ];
-        match stmt.execute_named(params) {
-            Ok(0) => Ok(false),
-            Ok(_) => Ok(true),
-            Err(e) => Err(format!("{}", e)),
+        stmt.query_map(NO_PARAMS, |row| row.get(0))
+            .expect("Database is corrupt")
+            .map(|wallet_result| wallet_result.expect("Database is corrupt"))
+            .collect()
+    }
+}
+
+impl PayableDaoReal {
+    pub fn new(conn: Box<dyn ConnectionWrapper>) -> PayableDaoReal {
+        PayableDaoReal {
+            conn,
+            consuming_wallet_address: String::new(),
         }
     }
 
-    fn try_decrease_balance(
-        &self,
-        wallet: &Wallet,
-        amount: u64,
-        last_paid_timestamp: SystemTime,
-        transaction_hash: H256,
-    ) -> Result<bool, String> {
-        let mut stmt = self
-            .conn
-            .prepare("insert into payable (balance, last_paid_timestamp, pending_payment_transaction, wallet_address) values (0 - :balance, :last_paid, :transaction, :address) on conflict (wallet_address) do update set balance = balance - :balance, last_paid_timestamp = :last_paid, pending_payment_transaction = :transaction where wallet_address = :address")
-            .expect("Internal error");
+    pub fn for_consuming_wallet(
+        conn: Box<dyn ConnectionWrapper>,
+        consuming_wallet: &Wallet,
+    ) -> PayableDaoReal {
+        PayableDaoReal {
+            conn,
+            consuming_wallet_address: consuming_wallet.to_string(),
+        }
+    }
+
+    fn try_increase_balance(&self, wallet: &Wallet, amount: u64) -> Result<bool, String> {
+        let balance = i64::try_from(amount)
+            .unwrap_or_else(|_| panic!("Lost payable amount precision: {}", amount));
         let params: &[(&str, &dyn ToSql)] = &[
-            (
-                ":balance",
-                &i64::try_from(amount)
-                    .unwrap_or_else(|_| panic!("Lost payable amount precision: {}", amount)),
-            ),
-            (":last_paid", &dao_utils::to_time_t(last_paid_timestamp)),
-            (":transaction", &format!("{:#x}", &transaction_hash)),
             (":address", &wallet),
+            (":consuming", &self.consuming_wallet_address),
+            (":balance", &balance),
         ];
-        match stmt.execute_named(params) {
-            Ok(0) => Ok(false),
-            Ok(_) => Ok(true),
-            Err(e) => Err(format!("{}", e)),
-        }
+        let mut changed = false;
+        self.conn.write(&mut |tx| {
+            let mut stmt = tx
+                .prepare("insert into payable (wallet_address, consuming_wallet_address, balance, last_paid_timestamp, pending_payment_transaction) values (:address, :consuming, :balance, strftime('%s','now'), null) on conflict (wallet_address, consuming_wallet_address) do update set balance = balance + :balance where wallet_address = :address and consuming_wallet_address = :consuming")
+                .expect("Internal error");
+            changed = stmt.execute_named(params).map_err(|e| format!("{}", e))? > 0;
+            Ok(())
+        })?;
+        Ok(changed)
+    }
+
+    fn try_decrease_balances(&self, payments: &[Payment]) -> Result<(), String> {
+        self.conn.write(&mut |tx| {
+            let mut stmt = tx.prepare(DECREASE_BALANCE_SQL).expect("Internal error");
+            for payment in payments {
+                let balance = i64::try_from(payment.amount).unwrap_or_else(|_| {
+                    panic!("Lost payable amount precision: {}", payment.amount)
+                });
+                let last_paid = dao_utils::to_time_t(payment.timestamp);
+                let transaction = format!("{:#x}", &payment.transaction);
+                let params: &[(&str, &dyn ToSql)] = &[
+                    (":balance", &balance),
+                    (":last_paid", &last_paid),
+                    (":transaction", &transaction),
+                    (":address", &payment.to),
+                    (":consuming", &self.consuming_wallet_address),
+                ];
+                stmt.execute_named(params).map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        })
     }
 }
 
@@ -293,13 +404,13 @@ mod tests {
     }
 
     #[test]
-    fn payment_sent_records_a_pending_transaction_for_a_new_address() {
+    fn payments_sent_records_a_pending_transaction_for_a_new_address() {
         let home_dir = ensure_node_home_directory_exists(
             "accountant",
-            "payment_sent_records_a_pending_transaction_for_a_new_address",
+            "payments_sent_records_a_pending_transaction_for_a_new_address",
         );
         let wallet = make_wallet("booga");
-        let subject = PayableDaoReal::new(
+        let mut subject = PayableDaoReal::new(
             DbInitializerReal::new()
                 .initialize(&home_dir, DEFAULT_CHAIN_ID)
                 .unwrap(),
@@ -309,7 +420,7 @@ mod tests {
         let before_account_status = subject.account_status(&payment.to);
         assert!(before_account_status.is_none());
 
-        subject.payment_sent(&payment);
+        subject.payments_sent(&[payment.clone()]);
 
         let after_account_status = subject.account_status(&payment.to).unwrap();
 
@@ -325,13 +436,13 @@ mod tests {
     }
 
     #[test]
-    fn payment_sent_records_a_pending_transaction_for_an_existing_address() {
+    fn payments_sent_records_a_pending_transaction_for_an_existing_address() {
         let home_dir = ensure_node_home_directory_exists(
             "accountant",
-            "payment_sent_records_a_pending_transaction_for_an_existing_address",
+            "payments_sent_records_a_pending_transaction_for_an_existing_address",
         );
         let wallet = make_wallet("booga");
-        let subject = PayableDaoReal::new(
+        let mut subject = PayableDaoReal::new(
             DbInitializerReal::new()
                 .initialize(&home_dir, DEFAULT_CHAIN_ID)
                 .unwrap(),
@@ -341,7 +452,7 @@ mod tests {
         let before_account_status = subject.account_status(&payment.to);
         assert!(before_account_status.is_none());
         subject.more_money_payable(&wallet, 1);
-        subject.payment_sent(&payment);
+        subject.payments_sent(&[payment.clone()]);
 
         let after_account_status = subject.account_status(&payment.to).unwrap();
 
@@ -356,6 +467,83 @@ mod tests {
         )
     }
 
+    #[test]
+    fn payments_sent_records_every_payment_of_a_batch() {
+        let home_dir = ensure_node_home_directory_exists(
+            "accountant",
+            "payments_sent_records_every_payment_of_a_batch",
+        );
+        let mut subject = PayableDaoReal::new(
+            DbInitializerReal::new()
+                .initialize(&home_dir, DEFAULT_CHAIN_ID)
+                .unwrap(),
+        );
+        let first_wallet = make_wallet("first");
+        let second_wallet = make_wallet("second");
+        subject.more_money_payable(&first_wallet, 5);
+        subject.more_money_payable(&second_wallet, 7);
+
+        subject.payments_sent(&[
+            Payment::new(first_wallet.clone(), 5, H256::from_uint(&U256::from(1))),
+            Payment::new(second_wallet.clone(), 7, H256::from_uint(&U256::from(2))),
+        ]);
+
+        let first_status = subject.account_status(&first_wallet).unwrap();
+        let second_status = subject.account_status(&second_wallet).unwrap();
+        assert_eq!(first_status.balance, 0);
+        assert_eq!(
+            first_status.pending_payment_transaction,
+            Some(H256::from_uint(&U256::from(1)))
+        );
+        assert_eq!(second_status.balance, 0);
+        assert_eq!(
+            second_status.pending_payment_transaction,
+            Some(H256::from_uint(&U256::from(2)))
+        );
+    }
+
+    #[test]
+    fn payments_sent_records_none_of_a_batch_that_cant_be_recorded_completely() {
+        let home_dir = ensure_node_home_directory_exists(
+            "accountant",
+            "payments_sent_records_none_of_a_batch_that_cant_be_recorded_completely",
+        );
+        let mut subject = PayableDaoReal::new(
+            DbInitializerReal::new()
+                .initialize(&home_dir, DEFAULT_CHAIN_ID)
+                .unwrap(),
+        );
+        let first_wallet = make_wallet("first");
+        let second_wallet = make_wallet("second");
+        subject.more_money_payable(&first_wallet, 5);
+        subject.more_money_payable(&second_wallet, 7);
+        let mut flags = OpenFlags::empty();
+        flags.insert(OpenFlags::SQLITE_OPEN_READ_WRITE);
+        let conn =
+            Connection::open_with_flags(&home_dir.join(db_initializer::DATABASE_FILE), flags)
+                .unwrap();
+        conn.execute(
+            &format!(
+                "create trigger refuse_second before update on payable when new.wallet_address = '{}' begin select raise(abort, 'refused'); end",
+                second_wallet
+            ),
+            NO_PARAMS,
+        )
+        .unwrap();
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            subject.payments_sent(&[
+                Payment::new(first_wallet.clone(), 5, H256::from_uint(&U256::from(1))),
+                Payment::new(second_wallet.clone(), 7, H256::from_uint(&U256::from(2))),
+            ])
+        }));
+
+        assert!(result.is_err());
+        let first_status = subject.account_status(&first_wallet).unwrap();
+        assert_eq!(first_status.balance, 5);
+        assert_eq!(first_status.pending_payment_transaction, None);
+    }
+
     #[test]
     fn payable_account_status_works_when_account_doesnt_exist() {
         let home_dir = ensure_node_home_directory_exists(
@@ -452,6 +640,122 @@ mod tests {
         );
     }
 
+    #[test]
+    fn payment_replaced_records_the_replacement_transaction() {
+        let home_dir = ensure_node_home_directory_exists(
+            "accountant",
+            "payment_replaced_records_the_replacement_transaction",
+        );
+        let mut subject = PayableDaoReal::new(
+            DbInitializerReal::new()
+                .initialize(&home_dir, DEFAULT_CHAIN_ID)
+                .unwrap(),
+        );
+        let wallet = make_wallet("booga");
+        let payment = Payment::new(wallet.clone(), 30, H256::from_uint(&U256::from(1)));
+        subject.more_money_payable(&wallet, 50);
+        subject.payments_sent(&[payment.clone()]);
+
+        subject.payment_replaced(&payment, H256::from_uint(&U256::from(2)));
+
+        let status = subject.account_status(&wallet).unwrap();
+        assert_eq!(status.balance, 20);
+        assert_eq!(
+            status.pending_payment_transaction,
+            Some(H256::from_uint(&U256::from(2)))
+        );
+    }
+
+    #[test]
+    fn payment_cancelled_restores_the_balance_and_clears_the_pending_transaction() {
+        let home_dir = ensure_node_home_directory_exists(
+            "accountant",
+            "payment_cancelled_restores_the_balance_and_clears_the_pending_transaction",
+        );
+        let mut subject = PayableDaoReal::new(
+            DbInitializerReal::new()
+                .initialize(&home_dir, DEFAULT_CHAIN_ID)
+                .unwrap(),
+        );
+        let wallet = make_wallet("booga");
+        let payment = Payment::new(wallet.clone(), 30, H256::from_uint(&U256::from(1)));
+        subject.more_money_payable(&wallet, 50);
+        subject.payments_sent(&[payment.clone()]);
+
+        subject.payment_cancelled(&payment);
+
+        let status = subject.account_status(&wallet).unwrap();
+        assert_eq!(status.balance, 50);
+        assert_eq!(status.pending_payment_transaction, None);
+    }
+
+    #[test]
+    fn payment_cancelled_leaves_a_later_payment_alone() {
+        let home_dir = ensure_node_home_directory_exists(
+            "accountant",
+            "payment_cancelled_leaves_a_later_payment_alone",
+        );
+        let mut subject = PayableDaoReal::new(
+            DbInitializerReal::new()
+                .initialize(&home_dir, DEFAULT_CHAIN_ID)
+                .unwrap(),
+        );
+        let wallet = make_wallet("booga");
+        let earlier = Payment::new(wallet.clone(), 30, H256::from_uint(&U256::from(1)));
+        let later = Payment::new(wallet.clone(), 20, H256::from_uint(&U256::from(2)));
+        subject.more_money_payable(&wallet, 50);
+        subject.payments_sent(&[earlier.clone()]);
+        subject.payments_sent(&[later]);
+
+        subject.payment_cancelled(&earlier);
+
+        let status = subject.account_status(&wallet).unwrap();
+        assert_eq!(status.balance, 0);
+        assert_eq!(
+            status.pending_payment_transaction,
+            Some(H256::from_uint(&U256::from(2)))
+        );
+    }
+
+    #[test]
+    fn payables_should_return_all_payables_including_pending_ones() {
+        let home_dir = ensure_node_home_directory_exists(
+            "accountant",
+            "payables_should_return_all_payables_including_pending_ones",
+        );
+        let mut subject = PayableDaoReal::new(
+            DbInitializerReal::new()
+                .initialize(&home_dir, DEFAULT_CHAIN_ID)
+                .unwrap(),
+        );
+        let pending_wallet = make_wallet("pending");
+        let settled_wallet = make_wallet("settled");
+        subject.more_money_payable(&pending_wallet, 100);
+        subject.payments_sent(&[Payment {
+            to: pending_wallet.clone(),
+            amount: 60,
+            timestamp: from_time_t(1_000),
+            transaction: H256::from_uint(&U256::from(1234)),
+        }]);
+        subject.more_money_payable(&settled_wallet, 50);
+
+        let result = subject.payables();
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(
+            result[0],
+            PayableAccount {
+                wallet: pending_wallet,
+                balance: 40,
+                last_paid_timestamp: from_time_t(1_000),
+                pending_payment_transaction: Some(H256::from_uint(&U256::from(1234))),
+            }
+        );
+        assert_eq!(result[1].wallet, settled_wallet);
+        assert_eq!(result[1].balance, 50);
+        assert_eq!(result[1].pending_payment_transaction, None);
+    }
+
     #[test]
     #[should_panic(expected = "Lost payable amount precision: 18446744073709551615")]
     fn payable_amount_precision_loss_panics_on_insert() {
@@ -474,15 +778,51 @@ mod tests {
             "accountant",
             "payable_amount_precision_loss_panics_on_update_balance",
         );
-        let subject = PayableDaoReal::new(
+        let mut subject = PayableDaoReal::new(
             DbInitializerReal::new()
                 .initialize(&home_dir, DEFAULT_CHAIN_ID)
                 .unwrap(),
         );
-        subject.payment_sent(&Payment::new(
+        subject.payments_sent(&[Payment::new(
             make_wallet("foobar"),
             std::u64::MAX,
             H256::from_uint(&U256::from(123)),
-        ));
+        )]);
+    }
+
+    #[test]
+    fn payables_of_different_consuming_wallets_are_kept_apart() {
+        let home_dir = ensure_node_home_directory_exists(
+            "accountant",
+            "payables_of_different_consuming_wallets_are_kept_apart",
+        );
+        let db_initializer = DbInitializerReal::new();
+        let own = PayableDaoReal::new(
+            db_initializer
+                .initialize(&home_dir, DEFAULT_CHAIN_ID)
+                .unwrap(),
+        );
+        let mut profile = PayableDaoReal::for_consuming_wallet(
+            db_initializer
+                .initialize(&home_dir, DEFAULT_CHAIN_ID)
+                .unwrap(),
+            &make_wallet("profile"),
+        );
+        let creditor = make_wallet("creditor");
+
+        own.more_money_payable(&creditor, 100);
+        profile.more_money_payable(&creditor, 20);
+        profile.payments_sent(&[Payment::new(
+            creditor.clone(),
+            15,
+            H256::from_uint(&U256::from(1)),
+        )]);
+
+        assert_eq!(own.account_status(&creditor).unwrap().balance, 100);
+        assert_eq!(own.non_pending_payables().len(), 1);
+        assert_eq!(profile.account_status(&creditor).unwrap().balance, 5);
+        assert_eq!(profile.non_pending_payables(), vec![]);
+        assert_eq!(profile.payables().len(), 1);
+        assert_eq!(own.profile_wallets(), vec![make_wallet("profile")]);
     }
 }
@@ -0,0 +1,353 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::blockchain::blockchain_bridge::RetrievedTransactions;
+use crate::blockchain::blockchain_interface::Transaction;
+use crate::sub_lib::logger::Logger;
+use crate::sub_lib::wallet::Wallet;
+use std::cmp::max;
+use std::collections::{HashMap, HashSet};
+use web3::types::H256;
+
+pub const DEFAULT_PAYMENT_CONFIRMATIONS: u64 = 12;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReceivedPaymentState {
+    // Seen on the blockchain, but not yet buried deeply enough to survive a reorganization
+    Pending { confirmations: u64 },
+    // Buried deeply enough; waiting for every earning wallet's scan to pass it before it's credited
+    Confirmed,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct TrackedPayment {
+    recipient: Wallet,
+    transaction: Transaction,
+    state: ReceivedPaymentState,
+}
+
+pub struct ReceivedPaymentTracker {
+    confirmations_required: u64,
+    payments: HashMap<H256, TrackedPayment>,
+    scanned_through: HashMap<Wallet, u64>,
+    logger: Logger,
+}
+
+impl ReceivedPaymentTracker {
+    pub fn new(confirmations_required: u64) -> Self {
+        ReceivedPaymentTracker {
+            confirmations_required: max(confirmations_required, 1),
+            payments: HashMap::new(),
+            scanned_through: HashMap::new(),
+            logger: Logger::new("Accountant"),
+        }
+    }
+
+    pub fn state(&self, transaction_hash: &H256) -> Option<ReceivedPaymentState> {
+        self.payments
+            .get(transaction_hash)
+            .map(|payment| payment.state)
+    }
+
+    // Every scan returns all payments to the recipient from the start block through the last
+    // block it covered, so a payment we were tracking in that range that isn't there anymore was
+    // in a block that got reorganized away. Payments past the end of a scan are left alone.
+    pub fn process(&mut self, recipient: &Wallet, retrieved: RetrievedTransactions) {
+        let seen: HashSet<H256> = retrieved
+            .transactions
+            .iter()
+            .map(|transaction| transaction.transaction_hash)
+            .collect();
+        let vanished: Vec<H256> = self
+            .payments
+            .iter()
+            .filter(|(hash, payment)| {
+                &payment.recipient == recipient
+                    && payment.transaction.block_number <= retrieved.latest_block
+                    && !seen.contains(hash)
+            })
+            .map(|(hash, _)| *hash)
+            .collect();
+        vanished.into_iter().for_each(|hash| {
+            let payment = self.payments.remove(&hash).expect("Payment disappeared");
+            warning!(
+                self.logger,
+                "Payment {:?} of {} gwei from {} in block {} vanished in a chain reorganization",
+                hash,
+                payment.transaction.gwei_amount,
+                payment.transaction.from,
+                payment.transaction.block_number
+            );
+        });
+
+        retrieved.transactions.iter().for_each(|transaction| {
+            let hash = transaction.transaction_hash;
+            let confirmations = retrieved.confirmations(transaction);
+            let state = if confirmations >= self.confirmations_required {
+                ReceivedPaymentState::Confirmed
+            } else {
+                ReceivedPaymentState::Pending { confirmations }
+            };
+            match self.payments.get(&hash) {
+                Some(previous) if previous.transaction.block_number != transaction.block_number => {
+                    warning!(
+                        self.logger,
+                        "Payment {:?} from {} moved from block {} to block {} in a chain reorganization; {}",
+                        hash,
+                        transaction.from,
+                        previous.transaction.block_number,
+                        transaction.block_number,
+                        self.describe(state)
+                    )
+                }
+                Some(_) => (),
+                None => debug!(
+                    self.logger,
+                    "Payment {:?} of {} gwei from {} found in block {}; {}",
+                    hash,
+                    transaction.gwei_amount,
+                    transaction.from,
+                    transaction.block_number,
+                    self.describe(state)
+                ),
+            }
+            self.payments.insert(
+                hash,
+                TrackedPayment {
+                    recipient: recipient.clone(),
+                    transaction: transaction.clone(),
+                    state,
+                },
+            );
+        });
+        self.scanned_through
+            .insert(recipient.clone(), retrieved.latest_block);
+    }
+
+    // Returns the confirmed payments that can be credited now, along with the block future scans
+    // should start from. That block never passes a payment that's still pending for any of the
+    // recipients, so a payment is credited exactly once no matter how the scans interleave.
+    pub fn settle(&mut self, recipients: &[Wallet], start_block: u64) -> (Vec<Transaction>, u64) {
+        let next_start_block = recipients
+            .iter()
+            .map(|recipient| {
+                self.scanned_through.get(recipient).map(|latest_block| {
+                    (latest_block + 2).saturating_sub(self.confirmations_required)
+                })
+            })
+            .collect::<Option<Vec<u64>>>()
+            .and_then(|blocks| blocks.into_iter().min())
+            .map(|block| max(block, start_block))
+            .unwrap_or(start_block);
+        let settled_hashes: Vec<H256> = self
+            .payments
+            .iter()
+            .filter(|(_, payment)| {
+                payment.state == ReceivedPaymentState::Confirmed
+                    && payment.transaction.block_number < next_start_block
+            })
+            .map(|(hash, _)| *hash)
+            .collect();
+        let mut settled: Vec<Transaction> = settled_hashes
+            .into_iter()
+            .map(|hash| {
+                self.payments
+                    .remove(&hash)
+                    .expect("Payment disappeared")
+                    .transaction
+            })
+            .collect();
+        settled.sort_by_key(|transaction| (transaction.block_number, transaction.transaction_hash));
+        (settled, next_start_block)
+    }
+
+    fn describe(&self, state: ReceivedPaymentState) -> String {
+        match state {
+            ReceivedPaymentState::Pending { confirmations } => format!(
+                "pending with {} of {} confirmations",
+                confirmations, self.confirmations_required
+            ),
+            ReceivedPaymentState::Confirmed => "confirmed".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::logging::{init_test_logging, TestLogHandler};
+    use crate::test_utils::make_wallet;
+    use ethereum_types::BigEndianHash;
+    use web3::types::U256;
+
+    fn make_transaction(from: &str, gwei_amount: u64, block_number: u64, hash: u64) -> Transaction {
+        Transaction {
+            from: make_wallet(from),
+            gwei_amount,
+            block_number,
+            transaction_hash: H256::from_uint(&U256::from(hash)),
+        }
+    }
+
+    fn retrieved(latest_block: u64, transactions: Vec<Transaction>) -> RetrievedTransactions {
+        RetrievedTransactions {
+            latest_block,
+            transactions,
+        }
+    }
+
+    #[test]
+    fn payments_stay_pending_until_they_have_enough_confirmations() {
+        let earning_wallet = make_wallet("earner");
+        let transaction = make_transaction("payer", 1000, 10, 1);
+        let mut subject = ReceivedPaymentTracker::new(3);
+
+        subject.process(&earning_wallet, retrieved(11, vec![transaction.clone()]));
+        let first_settlement = subject.settle(&[earning_wallet.clone()], 5);
+
+        assert_eq!(
+            subject.state(&transaction.transaction_hash),
+            Some(ReceivedPaymentState::Pending { confirmations: 2 })
+        );
+        assert_eq!(first_settlement, (vec![], 10));
+
+        subject.process(&earning_wallet, retrieved(12, vec![transaction.clone()]));
+        let second_settlement = subject.settle(&[earning_wallet.clone()], 10);
+
+        assert_eq!(second_settlement, (vec![transaction.clone()], 11));
+        assert_eq!(subject.state(&transaction.transaction_hash), None);
+    }
+
+    #[test]
+    fn start_block_never_moves_backward() {
+        let earning_wallet = make_wallet("earner");
+        let mut subject = ReceivedPaymentTracker::new(12);
+
+        subject.process(&earning_wallet, retrieved(5, vec![]));
+        let result = subject.settle(&[earning_wallet], 3);
+
+        assert_eq!(result, (vec![], 3));
+    }
+
+    #[test]
+    fn start_block_waits_for_every_recipient_to_be_scanned() {
+        let earning_wallet = make_wallet("earner");
+        let exit_earning_wallet = make_wallet("exit earner");
+        let transaction = make_transaction("payer", 1000, 10, 1);
+        let mut subject = ReceivedPaymentTracker::new(1);
+        let recipients = [earning_wallet.clone(), exit_earning_wallet.clone()];
+
+        subject.process(&earning_wallet, retrieved(20, vec![transaction.clone()]));
+        let first_settlement = subject.settle(&recipients, 5);
+        subject.process(&exit_earning_wallet, retrieved(15, vec![]));
+        let second_settlement = subject.settle(&recipients, 5);
+
+        assert_eq!(first_settlement, (vec![], 5));
+        assert_eq!(second_settlement, (vec![transaction], 16));
+    }
+
+    #[test]
+    fn start_block_stops_short_of_payments_pending_for_other_recipients() {
+        let earning_wallet = make_wallet("earner");
+        let exit_earning_wallet = make_wallet("exit earner");
+        let confirmed = make_transaction("payer", 1000, 10, 1);
+        let pending = make_transaction("other payer", 2000, 18, 2);
+        let mut subject = ReceivedPaymentTracker::new(3);
+        let recipients = [earning_wallet.clone(), exit_earning_wallet.clone()];
+
+        subject.process(&earning_wallet, retrieved(25, vec![confirmed.clone()]));
+        subject.process(&exit_earning_wallet, retrieved(19, vec![pending.clone()]));
+        let result = subject.settle(&recipients, 5);
+
+        assert_eq!(result, (vec![confirmed], 18));
+        assert_eq!(
+            subject.state(&pending.transaction_hash),
+            Some(ReceivedPaymentState::Pending { confirmations: 2 })
+        );
+    }
+
+    #[test]
+    fn reorganization_can_roll_a_confirmed_payment_back_to_pending() {
+        init_test_logging();
+        let earning_wallet = make_wallet("earner");
+        let transaction = make_transaction("payer", 1000, 10, 1);
+        let moved_transaction = Transaction {
+            block_number: 13,
+            ..transaction.clone()
+        };
+        let mut subject = ReceivedPaymentTracker::new(3);
+        let other_wallet = make_wallet("other earner");
+        let recipients = [earning_wallet.clone(), other_wallet.clone()];
+        subject.process(&other_wallet, retrieved(5, vec![]));
+
+        subject.process(&earning_wallet, retrieved(13, vec![transaction.clone()]));
+        assert_eq!(
+            subject.state(&transaction.transaction_hash),
+            Some(ReceivedPaymentState::Confirmed)
+        );
+        subject.process(
+            &earning_wallet,
+            retrieved(13, vec![moved_transaction.clone()]),
+        );
+        let result = subject.settle(&recipients, 5);
+
+        assert_eq!(
+            subject.state(&transaction.transaction_hash),
+            Some(ReceivedPaymentState::Pending { confirmations: 1 })
+        );
+        assert_eq!(result, (vec![], 5));
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: Accountant: Payment {:?} from {} moved from block 10 to block 13 in a chain reorganization; pending with 1 of 3 confirmations",
+            transaction.transaction_hash, transaction.from
+        ));
+    }
+
+    #[test]
+    fn payments_that_vanish_in_a_reorganization_are_forgotten() {
+        init_test_logging();
+        let earning_wallet = make_wallet("earner");
+        let other_wallet = make_wallet("other earner");
+        let vanishing = make_transaction("payer", 1234, 10, 1);
+        let unrelated = make_transaction("other payer", 2000, 10, 2);
+        let mut subject = ReceivedPaymentTracker::new(3);
+        subject.process(&earning_wallet, retrieved(11, vec![vanishing.clone()]));
+        subject.process(&other_wallet, retrieved(11, vec![unrelated.clone()]));
+
+        subject.process(&earning_wallet, retrieved(12, vec![]));
+
+        assert_eq!(subject.state(&vanishing.transaction_hash), None);
+        assert_eq!(
+            subject.state(&unrelated.transaction_hash),
+            Some(ReceivedPaymentState::Pending { confirmations: 2 })
+        );
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: Accountant: Payment {:?} of 1234 gwei from {} in block 10 vanished in a chain reorganization",
+            vanishing.transaction_hash, vanishing.from
+        ));
+    }
+
+    #[test]
+    fn payments_past_the_end_of_a_scan_are_not_taken_to_have_vanished() {
+        let earning_wallet = make_wallet("earner");
+        let later = make_transaction("payer", 1000, 30, 1);
+        let mut subject = ReceivedPaymentTracker::new(12);
+        subject.process(&earning_wallet, retrieved(35, vec![later.clone()]));
+
+        subject.process(&earning_wallet, retrieved(25, vec![]));
+
+        assert_eq!(
+            subject.state(&later.transaction_hash),
+            Some(ReceivedPaymentState::Pending { confirmations: 6 })
+        );
+    }
+
+    #[test]
+    fn zero_required_confirmations_is_treated_as_one() {
+        let earning_wallet = make_wallet("earner");
+        let transaction = make_transaction("payer", 1000, 10, 1);
+        let mut subject = ReceivedPaymentTracker::new(0);
+
+        subject.process(&earning_wallet, retrieved(10, vec![transaction.clone()]));
+        let result = subject.settle(&[earning_wallet], 5);
+
+        assert_eq!(result, (vec![transaction], 11));
+    }
+}
@@ -0,0 +1,105 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::database::db_initializer::ConnectionWrapper;
+use rusqlite::types::ToSql;
+use rusqlite::NO_PARAMS;
+use std::convert::TryFrom;
+
+// What each consuming profile has consumed so far, so that budgets hold across restarts
+pub trait ProfileConsumptionDao: Send {
+    fn consumption(&self) -> Vec<(String, u64)>;
+    // Returns what the profile has consumed, including the new charge
+    fn add_consumption(&self, name: &str, charge: u64) -> u64;
+}
+
+pub struct ProfileConsumptionDaoReal {
+    conn: Box<dyn ConnectionWrapper>,
+}
+
+impl ProfileConsumptionDaoReal {
+    pub fn new(conn: Box<dyn ConnectionWrapper>) -> Self {
+        Self { conn }
+    }
+}
+
+impl ProfileConsumptionDao for ProfileConsumptionDaoReal {
+    fn consumption(&self) -> Vec<(String, u64)> {
+        let mut stmt = self
+            .conn
+            .prepare("select name, consumed from profile_consumption order by name")
+            .expect("Failed to prepare a statement");
+        stmt.query_map(NO_PARAMS, |row| {
+            Ok((row.get(0)?, row.get::<_, i64>(1)? as u64))
+        })
+        .expect("Couldn't retrieve profile consumption: database corrupt")
+        .flat_map(|v| v)
+        .collect()
+    }
+
+    fn add_consumption(&self, name: &str, charge: u64) -> u64 {
+        let charge = i64::try_from(charge)
+            .unwrap_or_else(|_| panic!("Lost consumption amount precision: {}", charge));
+        let params: &[&dyn ToSql] = &[&name, &charge];
+        let mut consumed: i64 = 0;
+        let result = self.conn.write(&mut |tx| {
+            tx.execute(
+                "insert into profile_consumption (name, consumed) values (?1, ?2) on conflict (name) do update set consumed = consumed + ?2",
+                params,
+            )
+            .map_err(|e| e.to_string())?;
+            consumed = tx
+                .query_row(
+                    "select consumed from profile_consumption where name = ?",
+                    &[&name],
+                    |row| row.get(0),
+                )
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        });
+        match result {
+            Ok(()) => consumed as u64,
+            Err(e) => panic!(
+                "Could not record consumption of profile '{}' because of database corruption: {}",
+                name, e
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::db_initializer::{DbInitializer, DbInitializerReal};
+    use crate::test_utils::{ensure_node_home_directory_exists, DEFAULT_CHAIN_ID};
+
+    fn make_subject(test_name: &str) -> ProfileConsumptionDaoReal {
+        let home_dir = ensure_node_home_directory_exists("profile_consumption_dao", test_name);
+        let conn = DbInitializerReal::new()
+            .initialize(&home_dir, DEFAULT_CHAIN_ID)
+            .unwrap();
+        ProfileConsumptionDaoReal::new(conn)
+    }
+
+    #[test]
+    fn consumption_adds_up_for_each_profile() {
+        let subject = make_subject("consumption_adds_up_for_each_profile");
+
+        let first = subject.add_consumption("work", 100);
+        let second = subject.add_consumption("work", 23);
+        let other = subject.add_consumption("personal", 4);
+
+        assert_eq!(first, 100);
+        assert_eq!(second, 123);
+        assert_eq!(other, 4);
+        assert_eq!(
+            subject.consumption(),
+            vec![("personal".to_string(), 4), ("work".to_string(), 123)]
+        );
+    }
+
+    #[test]
+    fn consumption_is_empty_until_something_is_consumed() {
+        let subject = make_subject("consumption_is_empty_until_something_is_consumed");
+
+        assert_eq!(subject.consumption(), vec![]);
+    }
+}
@@ -23,10 +23,12 @@ pub struct ReceivableAccount {
 pub trait ReceivableDao: Send {
     fn more_money_receivable(&self, wallet: &Wallet, amount: u64);
 
+    // Credits the payments and moves the start block for future scans past them, all or nothing.
     fn more_money_received(
         &mut self,
         persistent_configuration: &dyn PersistentConfiguration,
         transactions: Vec<Transaction>,
+        next_start_block: u64,
     );
 
     fn account_status(&self, wallet: &Wallet) -> Option<ReceivableAccount>;
@@ -67,8 +69,9 @@ impl ReceivableDao for ReceivableDaoReal {
         &mut self,
         persistent_configuration: &dyn PersistentConfiguration,
         payments: Vec<Transaction>,
+        next_start_block: u64,
     ) {
-        self.try_multi_insert_payment(persistent_configuration, payments)
+        self.try_multi_insert_payment(persistent_configuration, payments, next_start_block)
             .unwrap_or_else(|e| {
                 warning!(self.logger, "Transaction failed, rolling back: {}", e);
             });
@@ -175,49 +178,44 @@ impl ReceivableDaoReal {
     }
 
     fn try_update(&self, wallet: &Wallet, amount: u64) -> Result<bool, String> {
-        let mut stmt = self
-            .conn
-            .prepare("update receivable set balance = balance + ? where wallet_address = ?")
-            .expect("Internal error");
         let params: &[&dyn ToSql] = &[&(amount as i64), &wallet];
-        match stmt.execute(params) {
-            Ok(0) => Ok(false),
-            Ok(_) => Ok(true),
-            Err(e) => Err(format!("{}", e)),
-        }
+        let mut changed = false;
+        self.conn.write(&mut |tx| {
+            let mut stmt = tx
+                .prepare("update receivable set balance = balance + ? where wallet_address = ?")
+                .expect("Internal error");
+            changed = stmt.execute(params).map_err(|e| format!("{}", e))? > 0;
+            Ok(())
+        })?;
+        Ok(changed)
     }
 
     fn try_insert(&self, wallet: &Wallet, amount: u64) -> Result<(), String> {
         let timestamp = dao_utils::to_time_t(SystemTime::now());
-        let mut stmt = self.conn.prepare ("insert into receivable (wallet_address, balance, last_received_timestamp) values (?, ?, ?)").expect ("Internal error");
         let params: &[&dyn ToSql] = &[&wallet, &(amount as i64), &(timestamp as i64)];
-        match stmt.execute(params) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(format!("{}", e)),
-        }
+        self.conn.write(&mut |tx| {
+            let mut stmt = tx.prepare ("insert into receivable (wallet_address, balance, last_received_timestamp) values (?, ?, ?)").expect ("Internal error");
+            stmt.execute(params)
+                .map(|_| ())
+                .map_err(|e| format!("{}", e))
+        })
     }
 
     fn try_multi_insert_payment(
         &mut self,
         persistent_configuration: &dyn PersistentConfiguration,
         payments: Vec<Transaction>,
+        next_start_block: u64,
     ) -> Result<(), String> {
-        let tx = match self.conn.transaction() {
-            Ok(t) => t,
-            Err(e) => return Err(e.to_string()),
-        };
-
-        let block_number = payments
-            .iter()
-            .map(|t| t.block_number)
-            .max()
-            .ok_or("no payments given")?;
+        self.conn.write(&mut |tx| {
+            if payments.is_empty() {
+                return Err("no payments given".to_string());
+            }
 
-        persistent_configuration.set_start_block_transactionally(&tx, block_number)?;
+            persistent_configuration.set_start_block_transactionally(tx, next_start_block)?;
 
-        {
             let mut stmt = tx.prepare("update receivable set balance = balance - ?, last_received_timestamp = ? where wallet_address = ?").expect("Internal error");
-            for transaction in payments {
+            for transaction in &payments {
                 let timestamp = dao_utils::now_time_t();
                 let params: &[&dyn ToSql] = &[
                     &(transaction.gwei_amount as i64),
@@ -226,8 +224,8 @@ impl ReceivableDaoReal {
                 ];
                 stmt.execute(params).map_err(|e| e.to_string())?;
             }
-        }
-        tx.commit().map_err(|e| e.to_string())
+            Ok(())
+        })
     }
 
     fn row_to_account(row: &Row) -> rusqlite::Result<ReceivableAccount> {
@@ -260,8 +258,10 @@ mod tests {
     use crate::test_utils::persistent_configuration_mock::PersistentConfigurationMock;
     use crate::test_utils::{assert_contains, ensure_node_home_directory_exists, make_wallet};
     use crate::test_utils::{logging, DEFAULT_CHAIN_ID};
+    use ethereum_types::BigEndianHash;
     use rusqlite::NO_PARAMS;
     use rusqlite::{Connection, Error, OpenFlags};
+    use web3::types::{H256, U256};
 
     #[test]
     fn more_money_receivable_works_for_new_address() {
@@ -373,15 +373,17 @@ mod tests {
                     from: debtor1.clone(),
                     gwei_amount: 1200u64,
                     block_number: 35u64,
+                    transaction_hash: H256::from_uint(&U256::from(1)),
                 },
                 Transaction {
                     from: debtor2.clone(),
                     gwei_amount: 2300u64,
                     block_number: 57u64,
+                    transaction_hash: H256::from_uint(&U256::from(2)),
                 },
             ];
 
-            subject.more_money_received(persistent_config.as_ref(), transactions);
+            subject.more_money_received(persistent_config.as_ref(), transactions, 58);
             (
                 subject.account_status(&debtor1).unwrap(),
                 subject.account_status(&debtor2).unwrap(),
@@ -401,7 +403,7 @@ mod tests {
         assert!(timestamp2 <= dao_utils::to_time_t(SystemTime::now()));
 
         let start_block = persistent_config.start_block();
-        assert_eq!(57u64, start_block);
+        assert_eq!(58u64, start_block);
     }
 
     #[test]
@@ -430,8 +432,9 @@ mod tests {
                 from: debtor.clone(),
                 gwei_amount: 2300u64,
                 block_number: 33u64,
+                transaction_hash: H256::from_uint(&U256::from(3)),
             }];
-            subject.more_money_received(persistent_config.as_ref(), transactions);
+            subject.more_money_received(persistent_config.as_ref(), transactions, 34);
             subject.account_status(&debtor)
         };
 
@@ -443,13 +446,13 @@ mod tests {
         logging::init_test_logging();
 
         let conn_mock =
-            ConnectionWrapperMock::default().transaction_result(Err(Error::InvalidQuery));
+            ConnectionWrapperMock::default().write_result(Err(Error::InvalidQuery.to_string()));
         let mut receivable_dao = ReceivableDaoReal::new(Box::new(conn_mock));
 
         let persistent_configuration: Box<dyn PersistentConfiguration> =
             Box::new(PersistentConfigurationMock::new());
 
-        receivable_dao.more_money_received(persistent_configuration.as_ref(), vec![]);
+        receivable_dao.more_money_received(persistent_configuration.as_ref(), vec![], 1);
 
         TestLogHandler::new().exists_log_containing(&format!(
             "WARN: ReceivableDaoReal: Transaction failed, rolling back: {}",
@@ -475,7 +478,7 @@ mod tests {
         let persistent_configuration: Box<dyn PersistentConfiguration> =
             Box::new(PersistentConfigurationMock::new());
 
-        receivable_dao.more_money_received(persistent_configuration.as_ref(), vec![]);
+        receivable_dao.more_money_received(persistent_configuration.as_ref(), vec![], 1);
 
         TestLogHandler::new().exists_log_containing(
             "WARN: ReceivableDaoReal: Transaction failed, rolling back: no payments given",
@@ -504,12 +507,13 @@ mod tests {
             from: make_wallet("foobar"),
             gwei_amount: 2300u64,
             block_number: 33u64,
+            transaction_hash: H256::from_uint(&U256::from(4)),
         }];
 
         let persistent_configuration: Box<dyn PersistentConfiguration> =
             Box::new(persistent_configuration_mock);
 
-        receivable_dao.more_money_received(persistent_configuration.as_ref(), payments);
+        receivable_dao.more_money_received(persistent_configuration.as_ref(), payments, 34);
 
         TestLogHandler::new().exists_log_containing(
             r#"WARN: ReceivableDaoReal: Transaction failed, rolling back: BOOM"#,
@@ -6,37 +6,47 @@ use super::bootstrapper::BootstrapperConfig;
 use super::discriminator::DiscriminatorFactory;
 use super::dispatcher::Dispatcher;
 use super::hopper::Hopper;
-use super::neighborhood::Neighborhood;
+use super::neighborhood::{Neighborhood, NEIGHBORHOOD_SNAPSHOT_FILE};
 use super::proxy_client::ProxyClient;
 use super::proxy_server::ProxyServer;
 use super::stream_handler_pool::StreamHandlerPool;
 use super::stream_handler_pool::StreamHandlerPoolSubs;
 use super::stream_messages::PoolBindMessage;
 use super::ui_gateway::UiGateway;
+use crate::accountant::dispute_dao::DisputeDaoReal;
 use crate::accountant::payable_dao::PayableDaoReal;
+use crate::accountant::profile_consumption_dao::ProfileConsumptionDaoReal;
 use crate::accountant::receivable_dao::ReceivableDaoReal;
 use crate::banned_dao::{BannedCacheLoader, BannedCacheLoaderReal, BannedDaoReal};
 use crate::blockchain::blockchain_bridge::BlockchainBridge;
+use crate::blockchain::blockchain_failover::{BlockchainEndpoint, BlockchainInterfaceFailover};
 use crate::blockchain::blockchain_interface::{
     BlockchainInterface, BlockchainInterfaceClandestine, BlockchainInterfaceNonClandestine,
 };
+use crate::blockchain::pending_transaction_dao::PendingTransactionDaoReal;
 use crate::config_dao::ConfigDaoReal;
 use crate::database::db_initializer::{DbInitializer, DbInitializerReal, DATABASE_FILE};
 use crate::persistent_configuration::PersistentConfigurationReal;
 use crate::sub_lib::accountant::AccountantSubs;
+use crate::sub_lib::accountant::ConsumingProfile;
 use crate::sub_lib::blockchain_bridge::BlockchainBridgeSubs;
 use crate::sub_lib::cryptde::CryptDE;
 use crate::sub_lib::dispatcher::DispatcherSubs;
-use crate::sub_lib::hopper::HopperConfig;
 use crate::sub_lib::hopper::HopperSubs;
+use crate::sub_lib::hopper::{HopperConfig, DEFAULT_ROUTING_WORKERS};
+use crate::sub_lib::memory_guard::MemoryWatchdog;
+use crate::sub_lib::neighbor_rate_limiter::NeighborRateLimits;
 use crate::sub_lib::neighborhood::NeighborhoodSubs;
+use crate::sub_lib::obfuscation::ObfuscationConfig;
 use crate::sub_lib::peer_actors::PeerActors;
 use crate::sub_lib::peer_actors::{BindMessage, StartMessage};
 use crate::sub_lib::proxy_client::ProxyClientConfig;
 use crate::sub_lib::proxy_client::ProxyClientSubs;
-use crate::sub_lib::proxy_server::ProxyServerSubs;
+use crate::sub_lib::proxy_server::{PortForward, ProxyServerSubs};
+use crate::sub_lib::spill_file::SpillConfig;
 use crate::sub_lib::ui_gateway::UiGatewayConfig;
 use crate::sub_lib::ui_gateway::UiGatewaySubs;
+use crate::sub_lib::wallet::Wallet;
 use actix::Addr;
 use actix::Recipient;
 use actix::{Actor, Arbiter};
@@ -77,9 +87,29 @@ impl ActorSystemFactoryReal {
         actor_factory: Box<dyn ActorFactory>,
         tx: Sender<StreamHandlerPoolSubs>,
     ) {
-        let db_initializer = DbInitializerReal::new();
+        let (_, stream_handler_pool_subs) =
+            ActorSystemFactoryReal::make_and_bind_actors(cryptde, config, actor_factory);
+
+        //send out the stream handler pool subs (to be bound to listeners)
+        tx.send(stream_handler_pool_subs).ok();
+    }
+
+    // Starts every actor in the current System and introduces them to one another, but binds no
+    // listeners: whoever holds the subs decides where traffic comes from.
+    pub fn make_and_bind_actors(
+        cryptde: &'static dyn CryptDE,
+        config: BootstrapperConfig,
+        actor_factory: Box<dyn ActorFactory>,
+    ) -> (PeerActors, StreamHandlerPoolSubs) {
+        let db_initializer =
+            DbInitializerReal::with_password_opt(config.database_password_opt.clone());
+        config.spill_config.remove_stale_files();
+        if let Some(thresholds) = config.memory_thresholds_opt {
+            MemoryWatchdog::new(thresholds).start();
+        }
         // make all the actors
-        let (dispatcher_subs, pool_bind_sub) = actor_factory.make_and_start_dispatcher();
+        let (dispatcher_subs, pool_bind_sub) =
+            actor_factory.make_and_start_dispatcher(config.neighbor_rate_limits);
         let proxy_server_subs = actor_factory.make_and_start_proxy_server(
             cryptde,
             config.neighborhood_config.mode.is_decentralized(),
@@ -88,6 +118,9 @@ impl ActorSystemFactoryReal {
             } else {
                 Some(0)
             },
+            config.kill_switch,
+            config.port_forwards.clone(),
+            config.consuming_profiles.clone(),
         );
         let proxy_client_subs = actor_factory.make_and_start_proxy_client(ProxyClientConfig {
             cryptde,
@@ -98,6 +131,8 @@ impl ActorSystemFactoryReal {
                 .rate_pack()
                 .exit_service_rate,
             exit_byte_rate: config.neighborhood_config.mode.rate_pack().exit_byte_rate,
+            exit_protections: config.exit_protections,
+            spill_config: config.spill_config.clone(),
         });
         let hopper_subs = actor_factory.make_and_start_hopper(HopperConfig {
             cryptde,
@@ -112,6 +147,7 @@ impl ActorSystemFactoryReal {
                 .rate_pack()
                 .routing_byte_rate,
             is_decentralized: config.neighborhood_config.mode.is_decentralized(),
+            routing_workers: DEFAULT_ROUTING_WORKERS,
         });
         let blockchain_bridge_subs =
             actor_factory.make_and_start_blockchain_bridge(&config, &db_initializer);
@@ -124,8 +160,11 @@ impl ActorSystemFactoryReal {
         );
         let ui_gateway_subs =
             actor_factory.make_and_start_ui_gateway(config.ui_gateway_config.clone());
-        let stream_handler_pool_subs = actor_factory
-            .make_and_start_stream_handler_pool(config.clandestine_discriminator_factories.clone());
+        let stream_handler_pool_subs = actor_factory.make_and_start_stream_handler_pool(
+            config.clandestine_discriminator_factories.clone(),
+            config.obfuscation_config,
+            config.spill_config.clone(),
+        );
 
         // collect all the subs
         let peer_actors = PeerActors {
@@ -156,6 +195,15 @@ impl ActorSystemFactoryReal {
                 neighborhood_subs: neighborhood_subs.clone(),
             })
             .expect("Stream Handler Pool is dead");
+        peer_actors
+            .proxy_server
+            .pool_bind
+            .try_send(PoolBindMessage {
+                dispatcher_subs: dispatcher_subs.clone(),
+                stream_handler_pool_subs: stream_handler_pool_subs.clone(),
+                neighborhood_subs: neighborhood_subs.clone(),
+            })
+            .expect("ProxyServer is dead");
         pool_bind_sub
             .try_send(PoolBindMessage {
                 dispatcher_subs,
@@ -168,18 +216,23 @@ impl ActorSystemFactoryReal {
         send_start_message!(peer_actors.neighborhood);
         send_start_message!(peer_actors.accountant);
 
-        //send out the stream handler pool subs (to be bound to listeners)
-        tx.send(stream_handler_pool_subs).ok();
+        (peer_actors, stream_handler_pool_subs)
     }
 }
 
 pub trait ActorFactory: Send {
-    fn make_and_start_dispatcher(&self) -> (DispatcherSubs, Recipient<PoolBindMessage>);
+    fn make_and_start_dispatcher(
+        &self,
+        rate_limits: NeighborRateLimits,
+    ) -> (DispatcherSubs, Recipient<PoolBindMessage>);
     fn make_and_start_proxy_server(
         &self,
         cryptde: &'static dyn CryptDE,
         is_decentralized: bool,
         consuming_wallet_balance: Option<i64>,
+        kill_switch: bool,
+        port_forwards: Vec<PortForward>,
+        consuming_profiles: Vec<ConsumingProfile>,
     ) -> ProxyServerSubs;
     fn make_and_start_hopper(&self, config: HopperConfig) -> HopperSubs;
     fn make_and_start_neighborhood(
@@ -198,6 +251,8 @@ pub trait ActorFactory: Send {
     fn make_and_start_stream_handler_pool(
         &self,
         clandestine_discriminator_factories: Vec<Box<dyn DiscriminatorFactory>>,
+        obfuscation_config: ObfuscationConfig,
+        spill_config: SpillConfig,
     ) -> StreamHandlerPoolSubs;
     fn make_and_start_proxy_client(&self, config: ProxyClientConfig) -> ProxyClientSubs;
     fn make_and_start_blockchain_bridge(
@@ -210,8 +265,11 @@ pub trait ActorFactory: Send {
 pub struct ActorFactoryReal {}
 
 impl ActorFactory for ActorFactoryReal {
-    fn make_and_start_dispatcher(&self) -> (DispatcherSubs, Recipient<PoolBindMessage>) {
-        let addr: Addr<Dispatcher> = Arbiter::start(|_| Dispatcher::new());
+    fn make_and_start_dispatcher(
+        &self,
+        rate_limits: NeighborRateLimits,
+    ) -> (DispatcherSubs, Recipient<PoolBindMessage>) {
+        let addr: Addr<Dispatcher> = Arbiter::start(move |_| Dispatcher::new(rate_limits));
         (
             Dispatcher::make_subs_from(&addr),
             addr.recipient::<PoolBindMessage>(),
@@ -223,9 +281,19 @@ impl ActorFactory for ActorFactoryReal {
         cryptde: &'static dyn CryptDE,
         is_decentralized: bool,
         consuming_wallet_balance: Option<i64>,
+        kill_switch: bool,
+        port_forwards: Vec<PortForward>,
+        consuming_profiles: Vec<ConsumingProfile>,
     ) -> ProxyServerSubs {
         let addr: Addr<ProxyServer> = Arbiter::start(move |_| {
-            ProxyServer::new(cryptde, is_decentralized, consuming_wallet_balance)
+            ProxyServer::new(
+                cryptde,
+                is_decentralized,
+                consuming_wallet_balance,
+                kill_switch,
+            )
+            .with_port_forwards(port_forwards)
+            .with_consuming_profiles(consuming_profiles)
         });
         ProxyServer::make_subs_from(&addr)
     }
@@ -240,7 +308,8 @@ impl ActorFactory for ActorFactoryReal {
         cryptde: &'static dyn CryptDE,
         config: &BootstrapperConfig,
     ) -> NeighborhoodSubs {
-        let neighborhood = Neighborhood::new(cryptde, config);
+        let neighborhood = Neighborhood::new(cryptde, config)
+            .with_snapshot_file(config.data_directory.join(NEIGHBORHOOD_SNAPSHOT_FILE));
         let addr: Addr<Neighborhood> = Arbiter::start(|_| neighborhood);
         Neighborhood::make_subs_from(&addr)
     }
@@ -252,63 +321,11 @@ impl ActorFactory for ActorFactoryReal {
         db_initializer: &dyn DbInitializer,
         banned_cache_loader: &dyn BannedCacheLoader,
     ) -> AccountantSubs {
-        let payable_dao = Box::new(PayableDaoReal::new(
-            db_initializer
-                .initialize(data_directory, config.blockchain_bridge_config.chain_id)
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Failed to connect to database at {:?}",
-                        data_directory.join(DATABASE_FILE)
-                    )
-                }),
-        ));
-        let receivable_dao = Box::new(ReceivableDaoReal::new(
-            db_initializer
-                .initialize(data_directory, config.blockchain_bridge_config.chain_id)
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Failed to connect to database at {:?}",
-                        data_directory.join(DATABASE_FILE)
-                    )
-                }),
-        ));
-        let banned_dao = Box::new(BannedDaoReal::new(
-            db_initializer
-                .initialize(data_directory, config.blockchain_bridge_config.chain_id)
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Failed to connect to database at {:?}",
-                        data_directory.join(DATABASE_FILE)
-                    )
-                }),
-        ));
-        banned_cache_loader.load(
-            db_initializer
-                .initialize(data_directory, config.blockchain_bridge_config.chain_id)
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Failed to connect to database at {:?}",
-                        data_directory.join(DATABASE_FILE)
-                    )
-                }),
-        );
-        let config_dao = Box::new(ConfigDaoReal::new(
-            db_initializer
-                .initialize(data_directory, config.blockchain_bridge_config.chain_id)
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Failed to connect to database at {:?}",
-                        data_directory.join(DATABASE_FILE)
-                    )
-                }),
-        ));
-        let persistent_configuration = Box::new(PersistentConfigurationReal::new(config_dao));
-        let accountant = Accountant::new(
+        let accountant = ActorFactoryReal::make_accountant(
             config,
-            payable_dao,
-            receivable_dao,
-            banned_dao,
-            persistent_configuration,
+            data_directory,
+            db_initializer,
+            banned_cache_loader,
         );
         let addr: Addr<Accountant> = Arbiter::start(|_| accountant);
         Accountant::make_subs_from(&addr)
@@ -323,9 +340,13 @@ impl ActorFactory for ActorFactoryReal {
     fn make_and_start_stream_handler_pool(
         &self,
         clandestine_discriminator_factories: Vec<Box<dyn DiscriminatorFactory>>,
+        obfuscation_config: ObfuscationConfig,
+        spill_config: SpillConfig,
     ) -> StreamHandlerPoolSubs {
-        let addr: Addr<StreamHandlerPool> =
-            Arbiter::start(|_| StreamHandlerPool::new(clandestine_discriminator_factories));
+        let addr: Addr<StreamHandlerPool> = Arbiter::start(move |_| {
+            StreamHandlerPool::new(clandestine_discriminator_factories, obfuscation_config)
+                .with_spill_config(spill_config)
+        });
         StreamHandlerPool::make_subs_from(&addr)
     }
 
@@ -339,28 +360,30 @@ impl ActorFactory for ActorFactoryReal {
         config: &BootstrapperConfig,
         db_initializer: &dyn DbInitializer,
     ) -> BlockchainBridgeSubs {
-        let blockchain_service_url = config
-            .blockchain_bridge_config
-            .blockchain_service_url
-            .clone();
-        let blockchain_interface: Box<dyn BlockchainInterface> = {
-            match blockchain_service_url {
-                Some(url) => match Http::new(&url) {
-                    Ok((event_loop_handle, transport)) => {
-                        Box::new(BlockchainInterfaceNonClandestine::new(
-                            transport,
-                            event_loop_handle,
-                            config.blockchain_bridge_config.chain_id,
-                        ))
-                    }
-                    Err(_) => panic!("Invalid blockchain node URL"),
-                },
-                None => Box::new(BlockchainInterfaceClandestine::new(
-                    config.blockchain_bridge_config.chain_id,
-                )),
-            }
-        };
-        let config_dao = Box::new(ConfigDaoReal::new(
+        let chain_id = config.blockchain_bridge_config.chain_id;
+        let blockchain_service_urls = &config.blockchain_bridge_config.blockchain_service_urls;
+        let blockchain_interface: Box<dyn BlockchainInterface> =
+            if blockchain_service_urls.is_empty() {
+                Box::new(BlockchainInterfaceClandestine::new(chain_id))
+            } else {
+                Box::new(BlockchainInterfaceFailover::new(
+                    blockchain_service_urls
+                        .iter()
+                        .map(|url| match Http::new(url) {
+                            Ok((event_loop_handle, transport)) => BlockchainEndpoint::new(
+                                url,
+                                Box::new(BlockchainInterfaceNonClandestine::new(
+                                    transport,
+                                    event_loop_handle,
+                                    chain_id,
+                                )),
+                            ),
+                            Err(_) => panic!("Invalid blockchain node URL"),
+                        })
+                        .collect(),
+                ))
+            };
+        let connect = || {
             db_initializer
                 .initialize(
                     &config.data_directory,
@@ -371,58 +394,135 @@ impl ActorFactory for ActorFactoryReal {
                         "Failed to connect to database at {:?}",
                         &config.data_directory.join(DATABASE_FILE)
                     )
-                }),
-        ));
+                })
+        };
+        let config_dao = Box::new(ConfigDaoReal::new(connect()));
         let persistent_config = Box::new(PersistentConfigurationReal::new(config_dao));
-        let blockchain_bridge =
-            BlockchainBridge::new(config, blockchain_interface, persistent_config);
+        let pending_transaction_dao = Box::new(PendingTransactionDaoReal::new(connect()));
+        let blockchain_bridge = BlockchainBridge::new(
+            config,
+            blockchain_interface,
+            persistent_config,
+            pending_transaction_dao,
+        );
         let addr: Addr<BlockchainBridge> = blockchain_bridge.start();
         BlockchainBridge::make_subs_from(&addr)
     }
 }
 
+impl ActorFactoryReal {
+    pub fn make_accountant(
+        config: &BootstrapperConfig,
+        data_directory: &PathBuf,
+        db_initializer: &dyn DbInitializer,
+        banned_cache_loader: &dyn BannedCacheLoader,
+    ) -> Accountant {
+        let connect = || {
+            db_initializer
+                .initialize(data_directory, config.blockchain_bridge_config.chain_id)
+                .unwrap_or_else(|_| {
+                    panic!(
+                        "Failed to connect to database at {:?}",
+                        data_directory.join(DATABASE_FILE)
+                    )
+                })
+        };
+        let payable_dao = Box::new(PayableDaoReal::new(connect()));
+        let receivable_dao = Box::new(ReceivableDaoReal::new(connect()));
+        let banned_dao = Box::new(BannedDaoReal::new(connect()));
+        banned_cache_loader.load(connect());
+        let dispute_dao = Box::new(DisputeDaoReal::new(connect()));
+        let profile_consumption_dao = Box::new(ProfileConsumptionDaoReal::new(connect()));
+        let config_dao = Box::new(ConfigDaoReal::new(connect()));
+        let persistent_configuration = Box::new(PersistentConfigurationReal::new(config_dao));
+        let accountant = Accountant::new(
+            config,
+            payable_dao,
+            receivable_dao,
+            banned_dao,
+            dispute_dao,
+            profile_consumption_dao,
+            persistent_configuration,
+        );
+        config
+            .consuming_profiles
+            .iter()
+            .filter_map(|profile| profile.consuming_wallet_opt.clone())
+            .fold(vec![], |mut wallets: Vec<Wallet>, wallet| {
+                if !wallets.contains(&wallet) {
+                    wallets.push(wallet)
+                }
+                wallets
+            })
+            .into_iter()
+            .fold(accountant, |accountant, wallet| {
+                let payable_dao = PayableDaoReal::for_consuming_wallet(connect(), &wallet);
+                accountant.with_profile_payable_dao(wallet, Box::new(payable_dao))
+            })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::accountant::{ReceivedPayments, SentPayments};
+    use crate::accountant::{
+        ReceivedPayments, ResolvedPayment, SentPayments, DEFAULT_PAYMENT_CURVES,
+    };
     use crate::blockchain::blockchain_bridge::RetrieveTransactions;
     use crate::bootstrapper::{Bootstrapper, RealUser};
     use crate::database::db_initializer::test_utils::{ConnectionWrapperMock, DbInitializerMock};
     use crate::database::db_initializer::{ConnectionWrapper, InitializationError};
+    use crate::log_rotation::LogRotation;
     use crate::neighborhood::gossip::Gossip;
     use crate::stream_messages::AddStreamMsg;
     use crate::stream_messages::RemoveStreamMsg;
+    use crate::sub_lib::accountant::ProfileSelector;
     use crate::sub_lib::accountant::ReportRoutingServiceConsumedMessage;
     use crate::sub_lib::accountant::ReportRoutingServiceProvidedMessage;
-    use crate::sub_lib::accountant::{AccountantConfig, GetFinancialStatisticsMessage};
+    use crate::sub_lib::accountant::{
+        AccountantConfig, DumpFinancialsMessage, GetFinancialStatisticsMessage, NewBlockMessage,
+        ReportBillingClaimMessage, SetPaymentParameterMessage,
+    };
     use crate::sub_lib::accountant::{
         ReportExitServiceConsumedMessage, ReportExitServiceProvidedMessage,
     };
     use crate::sub_lib::blockchain_bridge::{
-        BlockchainBridgeConfig, ReportAccountsPayable, SetGasPriceMsg, SetWalletPasswordMsg,
+        BlockchainBridgeConfig, FeeStrategy, GetBlockchainServiceUrlMsg, GetWalletBalancesMsg,
+        ReportAccountsPayable, SetGasPriceMsg, SetWalletPasswordMsg,
     };
     use crate::sub_lib::crash_point::CrashPoint;
     use crate::sub_lib::cryptde::PlainData;
-    use crate::sub_lib::dispatcher::{InboundClientData, StreamShutdownMsg};
+    use crate::sub_lib::dispatcher::{InboundClientData, NeighborKeyMessage, StreamShutdownMsg};
     use crate::sub_lib::hopper::IncipientCoresPackage;
     use crate::sub_lib::hopper::{ExpiredCoresPackage, NoLookupIncipientCoresPackage};
-    use crate::sub_lib::neighborhood::{DispatcherNodeQueryMessage, NodeRecordMetadataMessage};
+    use crate::sub_lib::neighborhood::NodeRecordMetadataMessage;
+    use crate::sub_lib::neighborhood::{
+        BillingClaimMessage, DebtorKeyMessage, DelinquencyMessage, DispatcherNodeQueryMessage,
+        DisputeMessage,
+    };
+    use crate::sub_lib::neighborhood::{
+        NeighborListRequest, NeighborhoodDotGraphRequest, RouteQueryMessage,
+    };
     use crate::sub_lib::neighborhood::{NeighborhoodConfig, NodeQueryMessage};
-    use crate::sub_lib::neighborhood::{NeighborhoodDotGraphRequest, RouteQueryMessage};
     use crate::sub_lib::neighborhood::{NeighborhoodMode, RemoveNeighborMessage};
     use crate::sub_lib::node_addr::NodeAddr;
     use crate::sub_lib::peer_actors::StartMessage;
     use crate::sub_lib::proxy_client::{
-        ClientResponsePayload, DnsResolveFailure, InboundServerData,
+        BillingClaimPayload, BillingClaimsMessage, ClientResponsePayload, DnsResolveFailure,
+        ExitProtections, InboundServerData,
     };
     use crate::sub_lib::proxy_server::{
         AddReturnRouteMessage, AddRouteMessage, ClientRequestPayload,
+        ConsumingProfileExhaustedMessage,
     };
     use crate::sub_lib::set_consuming_wallet_message::SetConsumingWalletMessage;
     use crate::sub_lib::stream_handler_pool::DispatcherNodeQueryResponse;
     use crate::sub_lib::stream_handler_pool::TransmitDataMsg;
+    use crate::sub_lib::ui_gateway::GetHealthMessage;
+    use crate::sub_lib::ui_gateway::NodeEvent;
     use crate::sub_lib::ui_gateway::UiGatewayConfig;
-    use crate::sub_lib::ui_gateway::{FromUiMessage, UiCarrierMessage};
+    use crate::sub_lib::ui_gateway::{ChangeConfigurationMessage, GetConfigurationMessage};
+    use crate::sub_lib::ui_gateway::{FromUiMessage, GetNodeStatisticsMessage, UiCarrierMessage};
     use crate::test_utils::rate_pack;
     use crate::test_utils::recorder::Recorder;
     use crate::test_utils::recorder::Recording;
@@ -466,13 +566,17 @@ mod tests {
     }
 
     impl<'a> ActorFactory for ActorFactoryMock<'a> {
-        fn make_and_start_dispatcher(&self) -> (DispatcherSubs, Recipient<PoolBindMessage>) {
+        fn make_and_start_dispatcher(
+            &self,
+            _rate_limits: NeighborRateLimits,
+        ) -> (DispatcherSubs, Recipient<PoolBindMessage>) {
             let addr: Addr<Recorder> = ActorFactoryMock::start_recorder(&self.dispatcher);
             let dispatcher_subs = DispatcherSubs {
                 ibcd_sub: recipient!(addr, InboundClientData),
                 bind: recipient!(addr, BindMessage),
                 from_dispatcher_client: recipient!(addr, TransmitDataMsg),
                 stream_shutdown_sub: recipient!(addr, StreamShutdownMsg),
+                neighbor_key_sub: recipient!(addr, NeighborKeyMessage),
             };
             (dispatcher_subs, addr.recipient::<PoolBindMessage>())
         }
@@ -482,12 +586,22 @@ mod tests {
             cryptde: &'a dyn CryptDE,
             is_decentralized: bool,
             consuming_wallet_balance: Option<i64>,
+            kill_switch: bool,
+            port_forwards: Vec<PortForward>,
+            consuming_profiles: Vec<ConsumingProfile>,
         ) -> ProxyServerSubs {
             self.parameters
                 .proxy_server_params
                 .lock()
                 .unwrap()
-                .get_or_insert((cryptde, is_decentralized, consuming_wallet_balance));
+                .get_or_insert((
+                    cryptde,
+                    is_decentralized,
+                    consuming_wallet_balance,
+                    kill_switch,
+                    port_forwards,
+                    consuming_profiles,
+                ));
             let addr: Addr<Recorder> = ActorFactoryMock::start_recorder(&self.proxy_server);
             ProxyServerSubs {
                 bind: recipient!(addr, BindMessage),
@@ -498,10 +612,16 @@ mod tests {
                 dns_failure_from_hopper: addr
                     .clone()
                     .recipient::<ExpiredCoresPackage<DnsResolveFailure>>(),
+                billing_claim_from_hopper: addr
+                    .clone()
+                    .recipient::<ExpiredCoresPackage<BillingClaimPayload>>(),
                 add_return_route: recipient!(addr, AddReturnRouteMessage),
                 add_route: recipient!(addr, AddRouteMessage),
                 stream_shutdown_sub: recipient!(addr, StreamShutdownMsg),
                 set_consuming_wallet_sub: recipient!(addr, SetConsumingWalletMessage),
+                node_statistics_sub: recipient!(addr, GetNodeStatisticsMessage),
+                pool_bind: recipient!(addr, PoolBindMessage),
+                profile_exhausted_sub: recipient!(addr, ConsumingProfileExhaustedMessage),
             }
         }
 
@@ -545,6 +665,13 @@ mod tests {
                 stream_shutdown_sub: recipient!(addr, StreamShutdownMsg),
                 set_consuming_wallet_sub: recipient!(addr, SetConsumingWalletMessage),
                 from_ui_gateway: addr.clone().recipient::<NeighborhoodDotGraphRequest>(),
+                neighbor_list_sub: addr.clone().recipient::<NeighborListRequest>(),
+                delinquency_sub: recipient!(addr, DelinquencyMessage),
+                debtor_key_sub: recipient!(addr, DebtorKeyMessage),
+                billing_claim_sub: recipient!(addr, BillingClaimMessage),
+                dispute_sub: recipient!(addr, DisputeMessage),
+                node_statistics_sub: recipient!(addr, GetNodeStatisticsMessage),
+                health_sub: recipient!(addr, GetHealthMessage),
             }
         }
 
@@ -578,9 +705,18 @@ mod tests {
                     .recipient::<ReportExitServiceConsumedMessage>(),
                 report_new_payments: recipient!(addr, ReceivedPayments),
                 report_sent_payments: recipient!(addr, SentPayments),
+                report_resolved_payment: recipient!(addr, ResolvedPayment),
+                report_new_block: recipient!(addr, NewBlockMessage),
                 get_financial_statistics_sub: addr
                     .clone()
                     .recipient::<GetFinancialStatisticsMessage>(),
+                dump_financials_sub: recipient!(addr, DumpFinancialsMessage),
+                set_payment_parameter_sub: recipient!(addr, SetPaymentParameterMessage),
+                report_billing_claim_sub: recipient!(addr, ReportBillingClaimMessage),
+                node_statistics_sub: recipient!(addr, GetNodeStatisticsMessage),
+                health_sub: recipient!(addr, GetHealthMessage),
+                configuration_sub: recipient!(addr, GetConfigurationMessage),
+                change_configuration_sub: recipient!(addr, ChangeConfigurationMessage),
             }
         }
 
@@ -595,12 +731,15 @@ mod tests {
                 bind: recipient!(addr, BindMessage),
                 ui_message_sub: recipient!(addr, UiCarrierMessage),
                 from_ui_message_sub: recipient!(addr, FromUiMessage),
+                node_event_sub: recipient!(addr, NodeEvent),
             }
         }
 
         fn make_and_start_stream_handler_pool(
             &self,
             _: Vec<Box<dyn DiscriminatorFactory>>,
+            _: ObfuscationConfig,
+            _: SpillConfig,
         ) -> StreamHandlerPoolSubs {
             let addr: Addr<Recorder> = ActorFactoryMock::start_recorder(&self.stream_handler_pool);
             StreamHandlerPoolSubs {
@@ -626,6 +765,8 @@ mod tests {
                     .recipient::<ExpiredCoresPackage<ClientRequestPayload>>(),
                 inbound_server_data: recipient!(addr, InboundServerData),
                 dns_resolve_failed: recipient!(addr, DnsResolveFailure),
+                billing_claims: recipient!(addr, BillingClaimsMessage),
+                node_statistics_sub: recipient!(addr, GetNodeStatisticsMessage),
             }
         }
 
@@ -645,7 +786,12 @@ mod tests {
                 report_accounts_payable: addr.clone().recipient::<ReportAccountsPayable>(),
                 retrieve_transactions: addr.clone().recipient::<RetrieveTransactions>(),
                 set_gas_price_sub: addr.clone().recipient::<SetGasPriceMsg>(),
+                get_blockchain_service_url_sub: addr
+                    .clone()
+                    .recipient::<GetBlockchainServiceUrlMsg>(),
                 set_consuming_wallet_password_sub: addr.clone().recipient::<SetWalletPasswordMsg>(),
+                get_wallet_balances_sub: addr.clone().recipient::<GetWalletBalancesMsg>(),
+                health_sub: addr.clone().recipient::<GetHealthMessage>(),
             }
         }
     }
@@ -665,7 +811,18 @@ mod tests {
     #[derive(Clone)]
     struct Parameters<'a> {
         proxy_client_params: Arc<Mutex<Option<(ProxyClientConfig)>>>,
-        proxy_server_params: Arc<Mutex<Option<(&'a dyn CryptDE, bool, Option<i64>)>>>,
+        proxy_server_params: Arc<
+            Mutex<
+                Option<(
+                    &'a dyn CryptDE,
+                    bool,
+                    Option<i64>,
+                    bool,
+                    Vec<PortForward>,
+                    Vec<ConsumingProfile>,
+                )>,
+            >,
+        >,
         hopper_params: Arc<Mutex<Option<HopperConfig>>>,
         neighborhood_params: Arc<Mutex<Option<(&'a dyn CryptDE, BootstrapperConfig)>>>,
         accountant_params: Arc<Mutex<Option<(BootstrapperConfig, PathBuf)>>>,
@@ -749,6 +906,9 @@ mod tests {
         let subject = ActorFactoryReal {};
 
         let db_initializer_mock = DbInitializerMock::new()
+            .initialize_result(Ok(Box::new(ConnectionWrapperMock::default())))
+            .initialize_result(Ok(Box::new(ConnectionWrapperMock::default())))
+            .initialize_result(Ok(Box::new(ConnectionWrapperMock::default())))
             .initialize_result(Ok(Box::new(ConnectionWrapperMock::default())))
             .initialize_result(Ok(Box::new(ConnectionWrapperMock::default())))
             .initialize_result(Ok(Box::new(ConnectionWrapperMock::default())))
@@ -758,10 +918,32 @@ mod tests {
         let aconfig = AccountantConfig {
             payable_scan_interval: Duration::from_secs(9),
             payment_received_scan_interval: Duration::from_secs(100),
+            payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
         };
         let mut config = BootstrapperConfig::new();
         config.accountant_config = aconfig;
         config.consuming_wallet = Some(make_wallet("hi"));
+        // Two profiles share one wallet, which gets one PayableDao of its own
+        config.consuming_profiles = vec![
+            ConsumingProfile {
+                name: "work".to_string(),
+                selector: ProfileSelector::OriginatingPorts(10000..=19999),
+                budget_opt: None,
+                consuming_wallet_opt: Some(make_wallet("work")),
+            },
+            ConsumingProfile {
+                name: "overtime".to_string(),
+                selector: ProfileSelector::Pac(8880),
+                budget_opt: Some(1000),
+                consuming_wallet_opt: Some(make_wallet("work")),
+            },
+            ConsumingProfile {
+                name: "personal".to_string(),
+                selector: ProfileSelector::OriginatingPorts(20000..=29999),
+                budget_opt: None,
+                consuming_wallet_opt: None,
+            },
+        ];
 
         let banned_cache_loader = &BannedCacheLoaderMock::default();
 
@@ -773,26 +955,9 @@ mod tests {
         );
 
         let initialize_parameters = db_initializer_mock.initialize_parameters.lock().unwrap();
-        assert_eq!(5, initialize_parameters.len());
-        assert_eq!(
-            (data_directory.clone(), DEFAULT_CHAIN_ID),
-            initialize_parameters[0]
-        );
-        assert_eq!(
-            (data_directory.clone(), DEFAULT_CHAIN_ID),
-            initialize_parameters[1]
-        );
         assert_eq!(
-            (data_directory.clone(), DEFAULT_CHAIN_ID),
-            initialize_parameters[2]
-        );
-        assert_eq!(
-            (data_directory.clone(), DEFAULT_CHAIN_ID),
-            initialize_parameters[3]
-        );
-        assert_eq!(
-            (data_directory.clone(), DEFAULT_CHAIN_ID),
-            initialize_parameters[4]
+            *initialize_parameters,
+            vec![(data_directory.clone(), DEFAULT_CHAIN_ID); 8]
         );
 
         let load_parameters = banned_cache_loader.load_params.lock().unwrap();
@@ -805,6 +970,7 @@ mod tests {
         let aconfig = AccountantConfig {
             payable_scan_interval: Duration::from_secs(6),
             payment_received_scan_interval: Duration::from_secs(100),
+            payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
         };
         let mut config = BootstrapperConfig::new();
         config.accountant_config = aconfig;
@@ -828,6 +994,7 @@ mod tests {
         let aconfig = AccountantConfig {
             payable_scan_interval: Duration::from_secs(6),
             payment_received_scan_interval: Duration::from_secs(100),
+            payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
         };
         let mut config = BootstrapperConfig::new();
         config.accountant_config = aconfig;
@@ -853,6 +1020,7 @@ mod tests {
         let aconfig = AccountantConfig {
             payable_scan_interval: Duration::from_secs(6),
             payment_received_scan_interval: Duration::from_secs(1000),
+            payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
         };
         let mut config = BootstrapperConfig::new();
         config.accountant_config = aconfig;
@@ -878,6 +1046,7 @@ mod tests {
         let aconfig = AccountantConfig {
             payable_scan_interval: Duration::from_secs(6),
             payment_received_scan_interval: Duration::from_secs(1000),
+            payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
         };
         let mut config = BootstrapperConfig::new();
         config.accountant_config = aconfig;
@@ -902,9 +1071,13 @@ mod tests {
     #[should_panic(expected = "Invalid blockchain node URL")]
     fn invalid_blockchain_url_produces_panic() {
         let bbconfig = BlockchainBridgeConfig {
-            blockchain_service_url: Some("http://λ:8545".to_string()),
+            blockchain_service_urls: vec!["http://λ:8545".to_string()],
+            blockchain_service_ws_url: None,
             chain_id: DEFAULT_CHAIN_ID,
             gas_price: None,
+            fee_strategy: FeeStrategy::Normal,
+            gas_price_cap: None,
+            fee_budget: None,
         };
         let mut config = BootstrapperConfig::new();
         config.blockchain_bridge_config = bbconfig;
@@ -931,24 +1104,51 @@ mod tests {
             accountant_config: AccountantConfig {
                 payable_scan_interval: Duration::from_secs(100),
                 payment_received_scan_interval: Duration::from_secs(100),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
             },
+            consuming_profiles: vec![],
             clandestine_discriminator_factories: Vec::new(),
+            obfuscation_config: ObfuscationConfig::default(),
             ui_gateway_config: UiGatewayConfig {
                 ui_port: 5335,
                 node_descriptor: String::from(""),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: None,
+                dns_server: "127.0.0.1:53".parse().unwrap(),
+                listeners: vec![],
+                telemetry_url_opt: None,
             },
             blockchain_bridge_config: BlockchainBridgeConfig {
-                blockchain_service_url: None,
+                blockchain_service_urls: vec![],
+                blockchain_service_ws_url: None,
                 chain_id: DEFAULT_CHAIN_ID,
                 gas_price: None,
+                fee_strategy: FeeStrategy::Normal,
+                gas_price_cap: None,
+                fee_budget: None,
             },
             port_configurations: HashMap::new(),
             clandestine_port_opt: None,
             earning_wallet: make_wallet("earning"),
+            exit_earning_wallet_opt: None,
             consuming_wallet: Some(make_wallet("consuming")),
+            database_password_opt: None,
             data_directory: PathBuf::new(),
             cryptde_null_opt: None,
             real_user: RealUser::null(),
+            instance_opt: None,
+            lan_discovery: false,
+            manage_firewall: false,
+            kill_switch: false,
+            port_forwards: vec![],
+            mail_clients: false,
+            neighbor_rate_limits: NeighborRateLimits::default(),
+            exit_protections: ExitProtections::default(),
+            log_rotation: LogRotation::default(),
+            spill_config: SpillConfig::default(),
+            memory_thresholds_opt: None,
+            split_dns_domains: vec![],
         };
         Bootstrapper::pub_initialize_cryptde_for_testing(&Some(cryptde().clone()));
         let subject = ActorSystemFactoryReal {};
@@ -968,6 +1168,7 @@ mod tests {
         Recording::get::<BindMessage>(&recordings.ui_gateway, 0);
         Recording::get::<BindMessage>(&recordings.blockchain_bridge, 0);
         Recording::get::<PoolBindMessage>(&recordings.stream_handler_pool, 0);
+        Recording::get::<PoolBindMessage>(&recordings.proxy_server, 1);
         Recording::get::<StartMessage>(&recordings.neighborhood, 1);
     }
 
@@ -986,24 +1187,51 @@ mod tests {
             accountant_config: AccountantConfig {
                 payable_scan_interval: Duration::from_secs(100),
                 payment_received_scan_interval: Duration::from_secs(100),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
             },
+            consuming_profiles: vec![],
             clandestine_discriminator_factories: Vec::new(),
+            obfuscation_config: ObfuscationConfig::default(),
             ui_gateway_config: UiGatewayConfig {
                 ui_port: 5335,
                 node_descriptor: String::from("NODE-DESCRIPTOR"),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: None,
+                dns_server: "127.0.0.1:53".parse().unwrap(),
+                listeners: vec![],
+                telemetry_url_opt: None,
             },
             blockchain_bridge_config: BlockchainBridgeConfig {
-                blockchain_service_url: None,
+                blockchain_service_urls: vec![],
+                blockchain_service_ws_url: None,
                 chain_id: DEFAULT_CHAIN_ID,
                 gas_price: None,
+                fee_strategy: FeeStrategy::Normal,
+                gas_price_cap: None,
+                fee_budget: None,
             },
             port_configurations: HashMap::new(),
             clandestine_port_opt: None,
             earning_wallet: make_wallet("earning"),
+            exit_earning_wallet_opt: None,
             consuming_wallet: Some(make_wallet("consuming")),
+            database_password_opt: None,
             data_directory: PathBuf::new(),
             cryptde_null_opt: None,
             real_user: RealUser::null(),
+            instance_opt: None,
+            lan_discovery: false,
+            manage_firewall: false,
+            kill_switch: false,
+            port_forwards: vec![],
+            mail_clients: false,
+            neighbor_rate_limits: NeighborRateLimits::default(),
+            exit_protections: ExitProtections::default(),
+            log_rotation: LogRotation::default(),
+            spill_config: SpillConfig::default(),
+            memory_thresholds_opt: None,
+            split_dns_domains: vec![],
         };
         let (tx, rx) = mpsc::channel();
         let system = System::new("PrometheusNode");
@@ -1029,16 +1257,30 @@ mod tests {
         check_cryptde(hopper_config.cryptde);
         assert_eq!(hopper_config.per_routing_service, 0);
         assert_eq!(hopper_config.per_routing_byte, 0);
+        assert_eq!(hopper_config.routing_workers, DEFAULT_ROUTING_WORKERS);
         let proxy_client_config = Parameters::get(parameters.proxy_client_params);
         check_cryptde(proxy_client_config.cryptde);
         assert_eq!(proxy_client_config.exit_service_rate, 0);
         assert_eq!(proxy_client_config.exit_byte_rate, 0);
         assert_eq!(proxy_client_config.dns_servers, config.dns_servers);
-        let (actual_cryptde, actual_is_decentralized, consuming_wallet_balance) =
-            Parameters::get(parameters.proxy_server_params);
+        assert_eq!(
+            proxy_client_config.exit_protections,
+            ExitProtections::default()
+        );
+        let (
+            actual_cryptde,
+            actual_is_decentralized,
+            consuming_wallet_balance,
+            kill_switch,
+            port_forwards,
+            consuming_profiles,
+        ) = Parameters::get(parameters.proxy_server_params);
         check_cryptde(actual_cryptde);
         assert_eq!(actual_is_decentralized, false);
         assert_eq!(consuming_wallet_balance, Some(0));
+        assert_eq!(kill_switch, false);
+        assert_eq!(port_forwards, config.port_forwards);
+        assert_eq!(consuming_profiles, config.consuming_profiles);
         let (cryptde, neighborhood_config) = Parameters::get(parameters.neighborhood_params);
         check_cryptde(cryptde);
         assert_eq!(
@@ -1056,9 +1298,13 @@ mod tests {
         assert_eq!(
             bootstrapper_config.blockchain_bridge_config,
             BlockchainBridgeConfig {
-                blockchain_service_url: None,
+                blockchain_service_urls: vec![],
+                blockchain_service_ws_url: None,
                 chain_id: DEFAULT_CHAIN_ID,
                 gas_price: None,
+                fee_strategy: FeeStrategy::Normal,
+                gas_price_cap: None,
+                fee_budget: None,
             }
         );
         assert_eq!(
@@ -1088,24 +1334,51 @@ mod tests {
             accountant_config: AccountantConfig {
                 payable_scan_interval: Duration::from_secs(100),
                 payment_received_scan_interval: Duration::from_secs(100),
+                payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
             },
+            consuming_profiles: vec![],
             clandestine_discriminator_factories: Vec::new(),
+            obfuscation_config: ObfuscationConfig::default(),
             ui_gateway_config: UiGatewayConfig {
                 ui_port: 5335,
                 node_descriptor: String::from("NODE-DESCRIPTOR"),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: None,
+                dns_server: "127.0.0.1:53".parse().unwrap(),
+                listeners: vec![],
+                telemetry_url_opt: None,
             },
             blockchain_bridge_config: BlockchainBridgeConfig {
-                blockchain_service_url: None,
+                blockchain_service_urls: vec![],
+                blockchain_service_ws_url: None,
                 chain_id: DEFAULT_CHAIN_ID,
                 gas_price: None,
+                fee_strategy: FeeStrategy::Normal,
+                gas_price_cap: None,
+                fee_budget: None,
             },
             port_configurations: HashMap::new(),
             clandestine_port_opt: None,
             earning_wallet: make_wallet("earning"),
+            exit_earning_wallet_opt: None,
             consuming_wallet: None,
+            database_password_opt: None,
             data_directory: PathBuf::new(),
             cryptde_null_opt: None,
             real_user: RealUser::null(),
+            instance_opt: None,
+            lan_discovery: false,
+            manage_firewall: false,
+            kill_switch: false,
+            port_forwards: vec![],
+            mail_clients: false,
+            neighbor_rate_limits: NeighborRateLimits::default(),
+            exit_protections: ExitProtections::default(),
+            log_rotation: LogRotation::default(),
+            spill_config: SpillConfig::default(),
+            memory_thresholds_opt: None,
+            split_dns_domains: vec![],
         };
         let (tx, _) = mpsc::channel();
         let system = System::new("PrometheusNode");
@@ -1119,7 +1392,8 @@ mod tests {
 
         System::current().stop();
         system.run();
-        let (_, _, consuming_wallet_balance) = Parameters::get(parameters.proxy_server_params);
+        let (_, _, consuming_wallet_balance, _, _, _) =
+            Parameters::get(parameters.proxy_server_params);
         assert_eq!(consuming_wallet_balance, None);
     }
 
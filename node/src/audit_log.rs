@@ -0,0 +1,280 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::database::dao_utils::now_time_t;
+use crate::sub_lib::logger::Logger;
+use lazy_static::lazy_static;
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const AUDIT_LOG_FILE: &str = "audit.log";
+pub const DEFAULT_RECENT_AUDIT_ENTRIES: usize = 50;
+
+// Who gave the order: someone at the command line, a UI (WebSocket or REST), a signal from the
+// operating system, or the Node itself, as when it bans a delinquent wallet
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AuditSource {
+    Cli,
+    Ui,
+    Signal,
+    Node,
+}
+
+impl fmt::Display for AuditSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let name = match self {
+            AuditSource::Cli => "cli",
+            AuditSource::Ui => "ui",
+            AuditSource::Signal => "signal",
+            AuditSource::Node => "node",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// One line of the audit log. Details never include secrets such as passwords or keys.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    // Seconds since the Unix epoch
+    pub timestamp: i64,
+    pub source: AuditSource,
+    pub action: String,
+    pub detail: String,
+}
+
+impl AuditEntry {
+    pub fn new(source: AuditSource, action: &str, detail: &str) -> AuditEntry {
+        AuditEntry {
+            timestamp: now_time_t(),
+            source,
+            action: action.to_string(),
+            detail: detail.to_string(),
+        }
+    }
+}
+
+// A JSON object per line in the data directory. Lines are only ever added, never changed.
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new(data_directory: &Path) -> AuditLog {
+        AuditLog {
+            path: data_directory.join(AUDIT_LOG_FILE),
+        }
+    }
+
+    pub fn append(&self, entry: &AuditEntry) -> io::Result<()> {
+        let line = serde_json::to_string(entry).expect("Internal error: audit entry");
+        let mut file = open_for_appending(&self.path)?;
+        writeln!(file, "{}", line)?;
+        file.flush()
+    }
+
+    // The last few entries, oldest first. Lines that aren't entries are skipped, so a log with a
+    // damaged line is still readable.
+    pub fn recent(&self, limit: usize) -> io::Result<Vec<AuditEntry>> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+        let entries = contents
+            .lines()
+            .filter_map(|line| serde_json::from_str::<AuditEntry>(line).ok())
+            .collect::<Vec<AuditEntry>>();
+        let skip = entries.len().saturating_sub(limit);
+        Ok(entries.into_iter().skip(skip).collect())
+    }
+}
+
+#[cfg(unix)]
+fn open_for_appending(path: &Path) -> io::Result<fs::File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    OpenOptions::new()
+        .append(true)
+        .create(true)
+        .mode(0o600)
+        .open(path)
+}
+
+// On Windows, files in the user's local application data directory are private to the user already
+#[cfg(not(unix))]
+fn open_for_appending(path: &Path) -> io::Result<fs::File> {
+    OpenOptions::new().append(true).create(true).open(path)
+}
+
+lazy_static! {
+    static ref AUDIT_LOG: Mutex<Option<AuditLog>> = Mutex::new(None);
+}
+
+// Once the Node knows its data directory; until then, as in tests, orders are only logged
+pub fn init_audit_log(data_directory: &Path) {
+    *AUDIT_LOG.lock().expect("Audit log is poisoned") = Some(AuditLog::new(data_directory));
+}
+
+pub fn audit(source: AuditSource, action: &str, detail: &str) {
+    let logger = Logger::new("AuditLog");
+    info!(logger, "{} {}: {}", source, action, detail);
+    let entry = AuditEntry::new(source, action, detail);
+    if let Some(audit_log) = AUDIT_LOG.lock().expect("Audit log is poisoned").as_ref() {
+        if let Err(e) = audit_log.append(&entry) {
+            error!(
+                logger,
+                "Couldn't add {} {} to {:?}: {}", source, action, audit_log.path, e
+            );
+        }
+    }
+}
+
+// For a UI that asks what's been ordered lately
+pub trait AuditLogReader: Send {
+    fn recent(&self, limit: usize) -> Result<Vec<AuditEntry>, String>;
+}
+
+pub struct AuditLogReaderReal {}
+
+impl AuditLogReader for AuditLogReaderReal {
+    fn recent(&self, limit: usize) -> Result<Vec<AuditEntry>, String> {
+        match AUDIT_LOG.lock().expect("Audit log is poisoned").as_ref() {
+            Some(audit_log) => audit_log
+                .recent(limit)
+                .map_err(|e| format!("Couldn't read {:?}: {}", audit_log.path, e)),
+            None => Err("There's no audit log yet".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::ensure_node_home_directory_exists;
+    use crate::test_utils::logging::init_test_logging;
+    use crate::test_utils::logging::TestLogHandler;
+
+    fn entry(timestamp: i64, action: &str) -> AuditEntry {
+        AuditEntry {
+            timestamp,
+            source: AuditSource::Ui,
+            action: action.to_string(),
+            detail: format!("{} detail", action),
+        }
+    }
+
+    #[test]
+    fn entries_are_appended_as_json_lines() {
+        let data_dir =
+            ensure_node_home_directory_exists("audit_log", "entries_are_appended_as_json_lines");
+        let subject = AuditLog::new(&data_dir);
+
+        subject.append(&entry(1000, "first")).unwrap();
+        subject
+            .append(&AuditEntry {
+                timestamp: 2000,
+                source: AuditSource::Signal,
+                action: "shutdown".to_string(),
+                detail: "SIGTERM".to_string(),
+            })
+            .unwrap();
+
+        let contents = fs::read_to_string(data_dir.join(AUDIT_LOG_FILE)).unwrap();
+        assert_eq!(
+            contents,
+            "{\"timestamp\":1000,\"source\":\"ui\",\"action\":\"first\",\"detail\":\"first detail\"}\n\
+             {\"timestamp\":2000,\"source\":\"signal\",\"action\":\"shutdown\",\"detail\":\"SIGTERM\"}\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn the_audit_log_is_readable_only_by_its_owner() {
+        use std::os::unix::fs::PermissionsExt;
+        let data_dir = ensure_node_home_directory_exists(
+            "audit_log",
+            "the_audit_log_is_readable_only_by_its_owner",
+        );
+        let subject = AuditLog::new(&data_dir);
+
+        subject.append(&entry(1000, "first")).unwrap();
+
+        let mode = fs::metadata(data_dir.join(AUDIT_LOG_FILE))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn recent_entries_are_the_last_few_oldest_first() {
+        let data_dir = ensure_node_home_directory_exists(
+            "audit_log",
+            "recent_entries_are_the_last_few_oldest_first",
+        );
+        let subject = AuditLog::new(&data_dir);
+        (1..=5).for_each(|n| {
+            subject
+                .append(&entry(n * 1000, &format!("action{}", n)))
+                .unwrap()
+        });
+
+        let result = subject.recent(3).unwrap();
+
+        assert_eq!(
+            result,
+            vec![
+                entry(3000, "action3"),
+                entry(4000, "action4"),
+                entry(5000, "action5")
+            ]
+        );
+    }
+
+    #[test]
+    fn damaged_lines_are_skipped() {
+        let data_dir = ensure_node_home_directory_exists("audit_log", "damaged_lines_are_skipped");
+        let subject = AuditLog::new(&data_dir);
+        subject.append(&entry(1000, "first")).unwrap();
+        {
+            let mut file = open_for_appending(&data_dir.join(AUDIT_LOG_FILE)).unwrap();
+            writeln!(file, "{{\"timestamp\":20").unwrap();
+        }
+        subject.append(&entry(3000, "third")).unwrap();
+
+        let result = subject.recent(10).unwrap();
+
+        assert_eq!(result, vec![entry(1000, "first"), entry(3000, "third")]);
+    }
+
+    #[test]
+    fn a_missing_audit_log_has_no_entries() {
+        let data_dir =
+            ensure_node_home_directory_exists("audit_log", "a_missing_audit_log_has_no_entries");
+        let subject = AuditLog::new(&data_dir);
+
+        let result = subject.recent(10).unwrap();
+
+        assert_eq!(result, vec![]);
+    }
+
+    #[test]
+    fn orders_are_logged_even_without_an_audit_log() {
+        init_test_logging();
+
+        audit(
+            AuditSource::Cli,
+            "orders_are_logged_even_without_an_audit_log",
+            "some detail",
+        );
+
+        TestLogHandler::new().exists_log_containing(
+            "INFO: AuditLog: cli orders_are_logged_even_without_an_audit_log: some detail",
+        );
+    }
+}
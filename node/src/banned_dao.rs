@@ -94,22 +94,26 @@ impl BannedDao for BannedDaoReal {
             return;
         }
 
-        let mut stmt = self
-            .conn
-            .prepare("insert into banned (wallet_address) values (?)")
-            .expect("Failed to prepare a statement");
         let params: &[&dyn ToSql] = &[&wallet];
-        match stmt.execute(params) {
-            Ok(_) => BAN_CACHE.insert(wallet.clone()),
-            Err(e) => match e {
-                Error::SqliteFailure(e, _) if e.code == ErrorCode::ConstraintViolation => {
-                    BAN_CACHE.insert(wallet.clone())
+        let result = self.conn.write(&mut |tx| {
+            let mut stmt = tx
+                .prepare("insert into banned (wallet_address) values (?)")
+                .expect("Failed to prepare a statement");
+            match stmt.execute(params) {
+                Ok(_) => Ok(()),
+                // Already banned
+                Err(Error::SqliteFailure(e, _)) if e.code == ErrorCode::ConstraintViolation => {
+                    Ok(())
                 }
-                _ => panic!(format!(
-                    "Could not initiate delinquency ban for {} because of database corruption: {}",
-                    wallet, e
-                )),
-            },
+                Err(e) => Err(e.to_string()),
+            }
+        });
+        match result {
+            Ok(()) => BAN_CACHE.insert(wallet.clone()),
+            Err(e) => panic!(
+                "Could not initiate delinquency ban for {} because of database corruption: {}",
+                wallet, e
+            ),
         }
     }
 
@@ -118,17 +122,19 @@ impl BannedDao for BannedDaoReal {
             return;
         }
 
-        let mut stmt = self
-            .conn
-            .prepare("delete from banned where wallet_address = ?")
-            .expect("Failed to prepare a statement");
         let params: &[&dyn ToSql] = &[&wallet];
-        match stmt.execute(params) {
-            Ok(_) => BAN_CACHE.remove(&wallet),
-            Err(e) => panic!(format!(
+        let result = self.conn.write(&mut |tx| {
+            let mut stmt = tx
+                .prepare("delete from banned where wallet_address = ?")
+                .expect("Failed to prepare a statement");
+            stmt.execute(params).map(|_| ()).map_err(|e| e.to_string())
+        });
+        match result {
+            Ok(()) => BAN_CACHE.remove(&wallet),
+            Err(e) => panic!(
                 "Could not terminate delinquency ban for {} because of database corruption: {}",
                 wallet, e
-            )),
+            ),
         }
     }
 }
@@ -0,0 +1,308 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::sub_lib::accountant::NewBlockMessage;
+use crate::sub_lib::logger::Logger;
+use actix::Recipient;
+use serde_json::{json, Value};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use websocket::client::builder::ClientBuilder;
+use websocket::OwnedMessage;
+
+// How long to wait before trying again when the subscription can't be made or is lost. Payments
+// are scanned for on the Accountant's timer in the meantime.
+pub const DEFAULT_RESUBSCRIBE_INTERVAL: Duration = Duration::from_secs(60);
+
+const SUBSCRIBE_REQUEST_ID: u64 = 1;
+
+#[derive(Debug, PartialEq)]
+enum Notice {
+    Subscribed(String),
+    Refused(String),
+    NewBlock(u64),
+    Other,
+}
+
+#[derive(Debug, PartialEq)]
+enum SubscriptionEnd {
+    // Worth trying again later
+    Lost(String),
+    // The service doesn't do eth_subscribe, so trying again won't help
+    Unsupported(String),
+    // Nobody's listening for new blocks anymore
+    Abandoned,
+}
+
+// Follows the chain head over a WebSocket eth_subscribe("newHeads") subscription, telling the
+// Accountant about each new block as soon as it's mined.
+pub struct NewHeadsSubscription {
+    url: String,
+    new_block_sub: Recipient<NewBlockMessage>,
+    resubscribe_interval: Duration,
+    logger: Logger,
+}
+
+impl NewHeadsSubscription {
+    pub fn new(url: &str, new_block_sub: Recipient<NewBlockMessage>) -> Self {
+        NewHeadsSubscription {
+            url: url.to_string(),
+            new_block_sub,
+            resubscribe_interval: DEFAULT_RESUBSCRIBE_INTERVAL,
+            logger: Logger::new("BlockchainBridge"),
+        }
+    }
+
+    pub fn start(self) -> JoinHandle<()> {
+        thread::spawn(move || self.run())
+    }
+
+    fn run(&self) {
+        loop {
+            match self.follow() {
+                SubscriptionEnd::Lost(e) => {
+                    warning!(
+                        self.logger,
+                        "New block subscription at {} unavailable: {}; will try again in {}s",
+                        self.url,
+                        e,
+                        self.resubscribe_interval.as_secs()
+                    );
+                    thread::sleep(self.resubscribe_interval)
+                }
+                SubscriptionEnd::Unsupported(e) => {
+                    warning!(
+                        self.logger,
+                        "{} doesn't support new block subscriptions ({}); polling for payments instead",
+                        self.url,
+                        e
+                    );
+                    break;
+                }
+                SubscriptionEnd::Abandoned => break,
+            }
+        }
+    }
+
+    fn follow(&self) -> SubscriptionEnd {
+        let mut client = match ClientBuilder::new(&self.url) {
+            Ok(mut builder) => match builder.connect(None) {
+                Ok(client) => client,
+                Err(e) => return SubscriptionEnd::Lost(format!("{}", e)),
+            },
+            Err(e) => return SubscriptionEnd::Unsupported(format!("{}", e)),
+        };
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": SUBSCRIBE_REQUEST_ID,
+            "method": "eth_subscribe",
+            "params": ["newHeads"]
+        });
+        if let Err(e) = client.send_message(&OwnedMessage::Text(request.to_string())) {
+            return SubscriptionEnd::Lost(format!("{}", e));
+        }
+        loop {
+            let text = match client.recv_message() {
+                Ok(OwnedMessage::Text(text)) => text,
+                Ok(OwnedMessage::Ping(data)) => {
+                    if let Err(e) = client.send_message(&OwnedMessage::Pong(data)) {
+                        return SubscriptionEnd::Lost(format!("{}", e));
+                    }
+                    continue;
+                }
+                Ok(OwnedMessage::Close(_)) => {
+                    return SubscriptionEnd::Lost("connection closed".to_string())
+                }
+                Ok(_) => continue,
+                Err(e) => return SubscriptionEnd::Lost(format!("{}", e)),
+            };
+            match interpret(&text) {
+                Notice::Subscribed(id) => info!(
+                    self.logger,
+                    "Subscribed to new blocks at {} (subscription {})", self.url, id
+                ),
+                Notice::Refused(e) => return SubscriptionEnd::Unsupported(e),
+                Notice::NewBlock(block_number) => {
+                    if self
+                        .new_block_sub
+                        .try_send(NewBlockMessage { block_number })
+                        .is_err()
+                    {
+                        return SubscriptionEnd::Abandoned;
+                    }
+                }
+                Notice::Other => (),
+            }
+        }
+    }
+}
+
+fn interpret(text: &str) -> Notice {
+    let value: Value = match serde_json::from_str(text) {
+        Ok(value) => value,
+        Err(_) => return Notice::Other,
+    };
+    if value["id"] == json!(SUBSCRIBE_REQUEST_ID) {
+        return match (value["result"].as_str(), value.get("error")) {
+            (Some(id), _) => Notice::Subscribed(id.to_string()),
+            (None, Some(error)) => Notice::Refused(
+                error["message"]
+                    .as_str()
+                    .map(|message| message.to_string())
+                    .unwrap_or_else(|| error.to_string()),
+            ),
+            (None, None) => Notice::Refused(format!("unexpected response {}", text)),
+        };
+    }
+    if value["method"] != json!("eth_subscription") {
+        return Notice::Other;
+    }
+    match value["params"]["result"]["number"].as_str() {
+        Some(number) if number.starts_with("0x") => match u64::from_str_radix(&number[2..], 16) {
+            Ok(block_number) => Notice::NewBlock(block_number),
+            Err(_) => Notice::Other,
+        },
+        _ => Notice::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::find_free_port;
+    use crate::test_utils::logging::{init_test_logging, TestLogHandler};
+    use crate::test_utils::recorder::make_recorder;
+    use actix::{Actor, System};
+    use std::net::Ipv4Addr;
+    use std::sync::mpsc;
+    use websocket::sync::Server;
+
+    #[test]
+    fn interprets_subscription_responses_and_notifications() {
+        assert_eq!(
+            interpret(r#"{"jsonrpc":"2.0","id":1,"result":"0x9cef478923ff08bf67fde6c64013158d"}"#),
+            Notice::Subscribed("0x9cef478923ff08bf67fde6c64013158d".to_string())
+        );
+        assert_eq!(
+            interpret(
+                r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32601,"message":"The method eth_subscribe does not exist"}}"#
+            ),
+            Notice::Refused("The method eth_subscribe does not exist".to_string())
+        );
+        assert_eq!(
+            interpret(
+                r#"{"jsonrpc":"2.0","method":"eth_subscription","params":{"subscription":"0x9cef478923ff08bf67fde6c64013158d","result":{"number":"0x1b4","hash":"0xdc0818cf78f21a8e70579cb46a43643f78291264dda342ae31049421c82d21ae"}}}"#
+            ),
+            Notice::NewBlock(436)
+        );
+        assert_eq!(
+            interpret(r#"{"jsonrpc":"2.0","method":"eth_subscription","params":{}}"#),
+            Notice::Other
+        );
+        assert_eq!(interpret("booga"), Notice::Other);
+    }
+
+    #[test]
+    fn reports_new_blocks_from_the_subscription() {
+        let port = find_free_port();
+        let mut server = Server::bind((Ipv4Addr::LOCALHOST, port)).unwrap();
+        let (request_tx, request_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut client = server.accept().ok().unwrap().accept().unwrap();
+            if let OwnedMessage::Text(request) = client.recv_message().unwrap() {
+                request_tx.send(request).unwrap();
+            }
+            [
+                r#"{"jsonrpc":"2.0","id":1,"result":"0xcd0c3e8af590364c09d0fa6a1210faf5"}"#,
+                r#"{"jsonrpc":"2.0","method":"eth_subscription","params":{"subscription":"0xcd0c3e8af590364c09d0fa6a1210faf5","result":{"number":"0x1b4"}}}"#,
+                r#"{"jsonrpc":"2.0","method":"eth_subscription","params":{"subscription":"0xcd0c3e8af590364c09d0fa6a1210faf5","result":{"number":"0x1b5"}}}"#,
+            ]
+            .iter()
+            .for_each(|text| {
+                client
+                    .send_message(&OwnedMessage::Text(text.to_string()))
+                    .unwrap()
+            });
+            thread::sleep(Duration::from_secs(5));
+        });
+        let (accountant, accountant_awaiter, accountant_recording_arc) = make_recorder();
+        let (recipient_tx, recipient_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let system = System::new("reports_new_blocks_from_the_subscription");
+            recipient_tx
+                .send(accountant.start().recipient::<NewBlockMessage>())
+                .unwrap();
+            system.run();
+        });
+        let subject = NewHeadsSubscription::new(
+            &format!("ws://127.0.0.1:{}", port),
+            recipient_rx.recv().unwrap(),
+        );
+
+        subject.start();
+
+        let request: Value = serde_json::from_str(&request_rx.recv().unwrap()).unwrap();
+        assert_eq!(request["method"], json!("eth_subscribe"));
+        assert_eq!(request["params"], json!(["newHeads"]));
+        accountant_awaiter.await_message_count(2);
+        let accountant_recording = accountant_recording_arc.lock().unwrap();
+        assert_eq!(
+            accountant_recording.get_record::<NewBlockMessage>(0),
+            &NewBlockMessage { block_number: 436 }
+        );
+        assert_eq!(
+            accountant_recording.get_record::<NewBlockMessage>(1),
+            &NewBlockMessage { block_number: 437 }
+        );
+    }
+
+    #[test]
+    fn gives_up_on_services_that_refuse_to_subscribe() {
+        init_test_logging();
+        let port = find_free_port();
+        let mut server = Server::bind((Ipv4Addr::LOCALHOST, port)).unwrap();
+        thread::spawn(move || {
+            let mut client = server.accept().ok().unwrap().accept().unwrap();
+            client.recv_message().unwrap();
+            client
+                .send_message(&OwnedMessage::Text(
+                    r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32601,"message":"notifications not supported"}}"#
+                        .to_string(),
+                ))
+                .unwrap();
+            thread::sleep(Duration::from_secs(5));
+        });
+        let (accountant, _, _) = make_recorder();
+        let system = System::new("gives_up_on_services_that_refuse_to_subscribe");
+        let url = format!("ws://127.0.0.1:{}", port);
+        let subject =
+            NewHeadsSubscription::new(&url, accountant.start().recipient::<NewBlockMessage>());
+
+        subject.start().join().unwrap();
+
+        System::current().stop();
+        system.run();
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: BlockchainBridge: {} doesn't support new block subscriptions (notifications not supported); polling for payments instead",
+            url
+        ));
+    }
+
+    #[test]
+    fn tries_again_later_when_the_service_cant_be_reached() {
+        let port = find_free_port();
+        let (accountant, _, _) = make_recorder();
+        let system = System::new("tries_again_later_when_the_service_cant_be_reached");
+        let url = format!("ws://127.0.0.1:{}", port);
+        let subject =
+            NewHeadsSubscription::new(&url, accountant.start().recipient::<NewBlockMessage>());
+
+        let result = subject.follow();
+
+        System::current().stop();
+        system.run();
+        match result {
+            SubscriptionEnd::Lost(_) => (),
+            other => panic!("Expected Lost, got {:?}", other),
+        }
+    }
+}
@@ -1,35 +1,76 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
-use crate::accountant::payable_dao::Payment;
+use crate::accountant::payable_dao::{PayableAccount, Payment};
+use crate::accountant::ResolvedPayment;
 use crate::blockchain::bip32::Bip32ECKeyPair;
 use crate::blockchain::bip39::Bip39Error;
+use crate::blockchain::block_subscription::NewHeadsSubscription;
+use crate::blockchain::blockchain_failover::DEFAULT_HEALTH_CHECK_INTERVAL;
 use crate::blockchain::blockchain_interface::{
-    BlockchainError, BlockchainInterface, BlockchainResult, Transaction,
+    chain_record, to_wei, Balance, BlockchainError, BlockchainInterface, BlockchainResult,
+    ChainRecord, Nonce, Transaction, TransactionFee,
+};
+use crate::blockchain::pending_transaction_dao::PendingTransactionDao;
+use crate::blockchain::pending_transactions::{
+    PendingTransaction, PendingTransactions, DEFAULT_PENDING_TRANSACTION_CHECK_INTERVAL,
+    DEFAULT_STUCK_TRANSACTION_TIMEOUT, MAX_REPLACEMENTS,
 };
 use crate::bootstrapper::BootstrapperConfig;
 use crate::persistent_configuration::PersistentConfiguration;
 use crate::sub_lib::blockchain_bridge::ReportAccountsPayable;
 use crate::sub_lib::blockchain_bridge::SetWalletPasswordMsg;
-use crate::sub_lib::blockchain_bridge::{BlockchainBridgeSubs, SetGasPriceMsg};
+use crate::sub_lib::blockchain_bridge::{
+    BlockchainBridgeSubs, FeeBudget, FeeSpending, FeeStrategy, GetBlockchainServiceUrlMsg,
+    GetWalletBalancesMsg, SetGasPriceMsg,
+};
+use crate::sub_lib::crash_context::handling;
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::peer_actors::BindMessage;
 use crate::sub_lib::set_consuming_wallet_message::SetConsumingWalletMessage;
-use crate::sub_lib::ui_gateway::{UiCarrierMessage, UiMessage};
+use crate::sub_lib::ui_gateway::{ComponentHealth, GetHealthMessage, HealthState};
+use crate::sub_lib::ui_gateway::{UiCarrierMessage, UiMessage, WalletBalance, WalletBalances};
 use crate::sub_lib::wallet::Wallet;
 use actix::Context;
 use actix::Handler;
 use actix::Message;
-use actix::{Actor, MessageResult};
+use actix::{Actor, AsyncContext, MessageResult};
 use actix::{Addr, Recipient};
+use std::cmp::{max, min};
+use std::collections::HashMap;
 use std::convert::TryFrom;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use web3::types::U256;
+
+// Blockchain services commonly refuse log queries that span too many blocks, so receivables are
+// scanned a window at a time. A refused window is halved, and it grows back as queries succeed.
+pub const DEFAULT_MAX_BLOCKS_PER_LOG_QUERY: u64 = 10_000;
+// Keeps a scan that's far behind the chain head from holding up the bridge; the rest of the
+// blocks are picked up by the following scans.
+pub const MAX_LOG_QUERIES_PER_SCAN: usize = 100;
 
 pub struct BlockchainBridge {
     consuming_wallet: Option<Wallet>,
+    earning_wallet: Wallet,
     blockchain_interface: Box<dyn BlockchainInterface>,
     logger: Logger,
     persistent_config: Box<dyn PersistentConfiguration>,
     ui_carrier_message_sub: Option<Recipient<UiCarrierMessage>>,
     set_consuming_wallet_subs: Option<Vec<Recipient<SetConsumingWalletMessage>>>,
+    health_check_interval: Duration,
+    chain: &'static ChainRecord,
+    fee_strategy: FeeStrategy,
+    gas_price_cap: Option<u64>,
+    fee_budget: Option<FeeBudget>,
+    pending_transactions: PendingTransactions,
+    // Payments from consuming profile wallets, tracked apart from the Node's own
+    profile_pending_transactions: HashMap<Wallet, PendingTransactions>,
+    pending_transaction_dao: Box<dyn PendingTransactionDao>,
+    pending_transaction_check_interval: Duration,
+    stuck_transaction_timeout: Duration,
+    resolved_payment_sub: Option<Recipient<ResolvedPayment>>,
+    max_blocks_per_log_query: u64,
+    blocks_per_log_query: u64,
+    blockchain_service_ws_url: Option<String>,
 }
 
 impl Actor for BlockchainBridge {
@@ -39,7 +80,8 @@ impl Actor for BlockchainBridge {
 impl Handler<BindMessage> for BlockchainBridge {
     type Result = ();
 
-    fn handle(&mut self, msg: BindMessage, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, msg: BindMessage, ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("BlockchainBridge", "BindMessage");
         self.ui_carrier_message_sub = Some(msg.peer_actors.ui_gateway.ui_message_sub.clone());
         self.set_consuming_wallet_subs = Some(vec![
             msg.peer_actors
@@ -51,6 +93,16 @@ impl Handler<BindMessage> for BlockchainBridge {
                 .set_consuming_wallet_sub
                 .clone(),
         ]);
+        self.resolved_payment_sub = Some(msg.peer_actors.accountant.report_resolved_payment);
+        match self.blockchain_service_ws_url.as_ref() {
+            Some(url) => {
+                NewHeadsSubscription::new(url, msg.peer_actors.accountant.report_new_block).start();
+            }
+            None => debug!(
+                self.logger,
+                "No blockchain service WebSocket URL specified; polling for payments"
+            ),
+        }
         match self.consuming_wallet.as_ref() {
             Some(wallet) => debug!(
                 self.logger,
@@ -61,17 +113,45 @@ impl Handler<BindMessage> for BlockchainBridge {
                 "Received BindMessage; no consuming wallet address specified"
             ),
         }
+        ctx.run_interval(self.health_check_interval, |bridge, _ctx| {
+            bridge.blockchain_interface.check_health()
+        });
+        ctx.run_interval(self.pending_transaction_check_interval, |bridge, _ctx| {
+            bridge.check_pending_transactions()
+        });
     }
 }
 
 #[derive(Debug, Eq, PartialEq)]
 pub struct RetrieveTransactions {
     pub start_block: u64,
+    // The chain head, if the requester already knows it; otherwise it's looked up
+    pub latest_block: Option<u64>,
     pub recipient: Wallet,
 }
 
 impl Message for RetrieveTransactions {
-    type Result = Result<Vec<Transaction>, BlockchainError>;
+    type Result = Result<RetrievedTransactions, BlockchainError>;
+}
+
+// latest_block is the last block the scan covered. It's never past the chain head as polled
+// before the transactions were retrieved, so no transaction can be more than
+// latest_block - block_number + 1 blocks deep. A scan that stops short of the head only
+// understates how deep its transactions are.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RetrievedTransactions {
+    pub latest_block: u64,
+    pub transactions: Vec<Transaction>,
+}
+
+impl RetrievedTransactions {
+    pub fn confirmations(&self, transaction: &Transaction) -> u64 {
+        if transaction.block_number > self.latest_block {
+            0
+        } else {
+            self.latest_block - transaction.block_number + 1
+        }
+    }
 }
 
 impl Handler<RetrieveTransactions> for BlockchainBridge {
@@ -82,10 +162,14 @@ impl Handler<RetrieveTransactions> for BlockchainBridge {
         msg: RetrieveTransactions,
         _ctx: &mut Self::Context,
     ) -> <Self as Handler<RetrieveTransactions>>::Result {
-        MessageResult(
-            self.blockchain_interface
-                .retrieve_transactions(msg.start_block, &msg.recipient),
-        )
+        let _handling = handling("BlockchainBridge", "RetrieveTransactions");
+        let latest_block = match msg.latest_block {
+            Some(latest_block) => Ok(latest_block),
+            None => self.blockchain_interface.get_block_number(),
+        };
+        MessageResult(latest_block.and_then(|latest_block| {
+            self.scan_for_transactions(msg.start_block, latest_block, &msg.recipient)
+        }))
     }
 }
 
@@ -97,39 +181,25 @@ impl Handler<ReportAccountsPayable> for BlockchainBridge {
         msg: ReportAccountsPayable,
         _ctx: &mut Self::Context,
     ) -> <Self as Handler<ReportAccountsPayable>>::Result {
-        MessageResult(match self.consuming_wallet.as_ref() {
-            Some(consuming_wallet) => Ok(msg
-                .accounts
-                .iter()
-                .map(|payable| {
-                    match self
-                        .blockchain_interface
-                        .get_transaction_count(&consuming_wallet)
-                    {
-                        Ok(nonce) => {
-                            match self.blockchain_interface.send_transaction(
-                                &consuming_wallet,
-                                &payable.wallet,
-                                u64::try_from(payable.balance).unwrap_or_else(|_| {
-                                    panic!("Lost payable amount precision: {}", payable.balance)
-                                }),
-                                nonce,
-                                self.persistent_config.gas_price(),
-                            ) {
-                                Ok(hash) => Ok(Payment::new(
-                                    payable.wallet.clone(),
-                                    u64::try_from(payable.balance).unwrap_or_else(|_| {
-                                        panic!("Lost payable amount precision: {}", payable.balance)
-                                    }),
-                                    hash,
-                                )),
-                                Err(e) => Err(e),
-                            }
-                        }
-                        Err(e) => Err(e),
+        let _handling = handling("BlockchainBridge", "ReportAccountsPayable");
+        let payer_opt = msg
+            .profile_wallet_opt
+            .clone()
+            .or_else(|| self.consuming_wallet.clone());
+        MessageResult(match payer_opt {
+            Some(payer) => Ok(
+                match self
+                    .transaction_fee(msg.accounts.len() as u64)
+                    .and_then(|fee| {
+                        self.next_nonce(&payer, &msg.profile_wallet_opt)
+                            .map(|nonce| (fee, nonce))
+                    }) {
+                    Err(e) => msg.accounts.iter().map(|_| Err(e.clone())).collect(),
+                    Ok((fee, nonce)) => {
+                        self.send_batch(&payer, &msg.profile_wallet_opt, &msg.accounts, fee, nonce)
                     }
-                })
-                .collect::<Vec<BlockchainResult<Payment>>>()),
+                },
+            ),
             None => Err(String::from("No consuming wallet specified")),
         })
     }
@@ -139,6 +209,7 @@ impl Handler<SetGasPriceMsg> for BlockchainBridge {
     type Result = ();
 
     fn handle(&mut self, msg: SetGasPriceMsg, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("BlockchainBridge", "SetGasPriceMsg");
         let gas_price_accepted = match msg.gas_price.parse::<u64>() {
             Ok(gas_price) => {
                 self.persistent_config.set_gas_price(gas_price);
@@ -163,10 +234,84 @@ impl Handler<SetGasPriceMsg> for BlockchainBridge {
     }
 }
 
+impl Handler<GetBlockchainServiceUrlMsg> for BlockchainBridge {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: GetBlockchainServiceUrlMsg,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let _handling = handling("BlockchainBridge", "GetBlockchainServiceUrlMsg");
+        self.ui_carrier_message_sub
+            .as_ref()
+            .expect("UiGateway is unbound")
+            .try_send(UiCarrierMessage {
+                client_id: msg.client_id,
+                data: UiMessage::BlockchainServiceUrl(
+                    self.blockchain_interface.active_service_url(),
+                ),
+            })
+            .expect("UiGateway is dead")
+    }
+}
+
+impl Handler<GetHealthMessage> for BlockchainBridge {
+    type Result = MessageResult<GetHealthMessage>;
+
+    fn handle(&mut self, _msg: GetHealthMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("BlockchainBridge", "GetHealthMessage");
+        let health = match self.blockchain_interface.active_service_url() {
+            None => ComponentHealth::new(
+                "blockchain",
+                HealthState::Disabled,
+                "no blockchain service URL",
+            ),
+            Some(url) => match self.blockchain_interface.get_block_number() {
+                Ok(block_number) => ComponentHealth::new(
+                    "blockchain",
+                    HealthState::Ready,
+                    &format!("{} at block {}", url, block_number),
+                ),
+                Err(e) => ComponentHealth::new(
+                    "blockchain",
+                    HealthState::NotReady,
+                    &format!("{} unreachable: {}", url, e),
+                ),
+            },
+        };
+        MessageResult(vec![health])
+    }
+}
+
+impl Handler<GetWalletBalancesMsg> for BlockchainBridge {
+    type Result = ();
+
+    fn handle(&mut self, msg: GetWalletBalancesMsg, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("BlockchainBridge", "GetWalletBalancesMsg");
+        let balances = WalletBalances {
+            earning: self.wallet_balance(&self.earning_wallet),
+            consuming: self
+                .consuming_wallet
+                .as_ref()
+                .map(|wallet| self.wallet_balance(wallet)),
+        };
+        self.ui_carrier_message_sub
+            .as_ref()
+            .expect("UiGateway is unbound")
+            .try_send(UiCarrierMessage {
+                client_id: msg.client_id,
+                data: UiMessage::WalletBalances(balances),
+            })
+            .expect("UiGateway is dead")
+    }
+}
+
 impl Handler<SetWalletPasswordMsg> for BlockchainBridge {
     type Result = ();
 
     fn handle(&mut self, msg: SetWalletPasswordMsg, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("BlockchainBridge", "SetWalletPasswordMsg");
         let password_accepted = self.accept_wallet_password(&msg.password);
         self.ui_carrier_message_sub
             .as_ref()
@@ -184,14 +329,48 @@ impl BlockchainBridge {
         config: &BootstrapperConfig,
         blockchain_interface: Box<dyn BlockchainInterface>,
         persistent_config: Box<dyn PersistentConfiguration>,
+        pending_transaction_dao: Box<dyn PendingTransactionDao>,
     ) -> BlockchainBridge {
+        // Payments sent before a restart are still watched, each under the wallet that sent it
+        let mut pending_transactions = PendingTransactions::new();
+        let mut profile_pending_transactions = HashMap::new();
+        pending_transaction_dao
+            .pending_transactions()
+            .into_iter()
+            .for_each(
+                |(profile_wallet_opt, nonce, pending)| match profile_wallet_opt {
+                    Some(wallet) => profile_pending_transactions
+                        .entry(wallet)
+                        .or_insert_with(PendingTransactions::new)
+                        .add(nonce, pending),
+                    None => pending_transactions.add(nonce, pending),
+                },
+            );
         BlockchainBridge {
             consuming_wallet: config.consuming_wallet.clone(),
+            earning_wallet: config.earning_wallet.clone(),
             blockchain_interface,
             logger: Logger::new("BlockchainBridge"),
             persistent_config,
             ui_carrier_message_sub: None,
             set_consuming_wallet_subs: None,
+            health_check_interval: DEFAULT_HEALTH_CHECK_INTERVAL,
+            chain: chain_record(config.blockchain_bridge_config.chain_id),
+            fee_strategy: config.blockchain_bridge_config.fee_strategy,
+            gas_price_cap: config.blockchain_bridge_config.gas_price_cap,
+            fee_budget: config.blockchain_bridge_config.fee_budget,
+            pending_transactions,
+            profile_pending_transactions,
+            pending_transaction_dao,
+            pending_transaction_check_interval: DEFAULT_PENDING_TRANSACTION_CHECK_INTERVAL,
+            stuck_transaction_timeout: DEFAULT_STUCK_TRANSACTION_TIMEOUT,
+            resolved_payment_sub: None,
+            max_blocks_per_log_query: DEFAULT_MAX_BLOCKS_PER_LOG_QUERY,
+            blocks_per_log_query: DEFAULT_MAX_BLOCKS_PER_LOG_QUERY,
+            blockchain_service_ws_url: config
+                .blockchain_bridge_config
+                .blockchain_service_ws_url
+                .clone(),
         }
     }
 
@@ -202,9 +381,508 @@ impl BlockchainBridge {
             retrieve_transactions: recipient!(addr, RetrieveTransactions),
             set_gas_price_sub: recipient!(addr, SetGasPriceMsg),
             set_consuming_wallet_password_sub: recipient!(addr, SetWalletPasswordMsg),
+            get_blockchain_service_url_sub: recipient!(addr, GetBlockchainServiceUrlMsg),
+            get_wallet_balances_sub: recipient!(addr, GetWalletBalancesMsg),
+            health_sub: recipient!(addr, GetHealthMessage),
+        }
+    }
+
+    fn wallet_balance(&self, wallet: &Wallet) -> WalletBalance {
+        let (gas_balance, token_balance) = self.blockchain_interface.get_balances(wallet);
+        let describe = |balance: Balance| match balance {
+            Ok(wei) => Ok(wei.to_string()),
+            Err(e) => {
+                warning!(self.logger, "Couldn't get balance of {}: {}", wallet, e);
+                Err(e.to_string())
+            }
+        };
+        WalletBalance {
+            address: wallet.to_string(),
+            gas_balance: describe(gas_balance),
+            token_balance: describe(token_balance),
+        }
+    }
+
+    fn scan_for_transactions(
+        &mut self,
+        start_block: u64,
+        latest_block: u64,
+        recipient: &Wallet,
+    ) -> BlockchainResult<RetrievedTransactions> {
+        let mut transactions = vec![];
+        let mut next_block = start_block;
+        let mut queries = 0;
+        while next_block <= latest_block && queries < MAX_LOG_QUERIES_PER_SCAN {
+            let end_block = min(
+                latest_block,
+                next_block.saturating_add(self.blocks_per_log_query - 1),
+            );
+            queries += 1;
+            match self
+                .blockchain_interface
+                .retrieve_transactions(next_block, end_block, recipient)
+            {
+                Ok(found) => {
+                    transactions.extend(found);
+                    next_block = end_block + 1;
+                    self.blocks_per_log_query =
+                        min(self.blocks_per_log_query * 2, self.max_blocks_per_log_query);
+                }
+                Err(BlockchainError::QueryFailed) if self.blocks_per_log_query > 1 => {
+                    self.blocks_per_log_query = max(self.blocks_per_log_query / 2, 1);
+                    debug!(
+                        self.logger,
+                        "Log query for blocks {} through {} failed; retrying {} blocks at a time",
+                        next_block,
+                        end_block,
+                        self.blocks_per_log_query
+                    );
+                }
+                Err(e) if next_block == start_block => return Err(e),
+                Err(e) => {
+                    warning!(
+                        self.logger,
+                        "Scan for payments to {} stopped at block {}: {}",
+                        recipient,
+                        next_block,
+                        e
+                    );
+                    break;
+                }
+            }
+        }
+        Ok(RetrievedTransactions {
+            latest_block: if next_block > start_block {
+                next_block - 1
+            } else {
+                latest_block
+            },
+            transactions,
+        })
+    }
+
+    // Payments that would cost more than the cap, or more than is left of the fee budget, are
+    // left for a later payable scan, by which time the base fee may have come down or a new
+    // budget period begun.
+    fn transaction_fee(&self, payments: u64) -> BlockchainResult<TransactionFee> {
+        self.market_fee()
+            .and_then(|fee| self.check_fee_cap(fee))
+            .and_then(|fee| {
+                self.check_fee_budget(self.fee_gwei(fee.max_fee_per_gas(), payments))
+                    .map(|_| fee)
+            })
+            .map_err(|e| {
+                match e {
+                    BlockchainError::FeeCapExceeded {
+                        max_fee_per_gas,
+                        cap,
+                    } => warning!(
+                        self.logger,
+                        "Not paying now: a fee of {} wei per gas would exceed the cap of {} Gwei; will retry later",
+                        max_fee_per_gas,
+                        cap / to_wei(1)
+                    ),
+                    BlockchainError::FeeBudgetExceeded {
+                        projected_gwei,
+                        remaining_gwei,
+                    } => warning!(
+                        self.logger,
+                        "Not paying now: {} payments could cost up to {} Gwei in fees, but only {} Gwei of this {}'s fee budget is left; will retry later",
+                        payments,
+                        projected_gwei,
+                        remaining_gwei,
+                        self.fee_budget.map(|budget| budget.period.name()).unwrap_or_default()
+                    ),
+                    _ => (),
+                }
+                e
+            })
+    }
+
+    fn market_fee(&self) -> BlockchainResult<TransactionFee> {
+        Ok(match self.blockchain_interface.get_base_fee_per_gas()? {
+            Some(base_fee_per_gas) => self.fee_strategy.fee_for(base_fee_per_gas, self.chain),
+            None => TransactionFee::Legacy {
+                gas_price: to_wei(self.persistent_config.gas_price()),
+            },
+        })
+    }
+
+    fn check_fee_cap(&self, fee: TransactionFee) -> BlockchainResult<TransactionFee> {
+        match self.gas_price_cap {
+            Some(cap) if fee.max_fee_per_gas() > to_wei(cap) => {
+                Err(BlockchainError::FeeCapExceeded {
+                    max_fee_per_gas: fee.max_fee_per_gas(),
+                    cap: to_wei(cap),
+                })
+            }
+            _ => Ok(fee),
+        }
+    }
+
+    fn check_fee_budget(&self, projected_gwei: u64) -> BlockchainResult<()> {
+        match self.fee_budget {
+            Some(budget) => {
+                let remaining_gwei = budget.gwei.saturating_sub(self.fee_spending(budget).gwei);
+                if projected_gwei > remaining_gwei {
+                    Err(BlockchainError::FeeBudgetExceeded {
+                        projected_gwei,
+                        remaining_gwei,
+                    })
+                } else {
+                    Ok(())
+                }
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn charge_fee_budget(&self, gwei: u64) {
+        if let Some(budget) = self.fee_budget {
+            let mut spending = self.fee_spending(budget);
+            spending.gwei = spending.gwei.saturating_add(gwei);
+            self.persistent_config.set_fee_spending(spending)
+        }
+    }
+
+    fn fee_spending(&self, budget: FeeBudget) -> FeeSpending {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("System clock is set before 1970")
+            .as_secs();
+        budget.spending_at(self.persistent_config.fee_spending(), now)
+    }
+
+    // What the budget is charged for transfers bidding max_fee_per_gas: the most they can cost,
+    // since we don't hear what they actually cost
+    fn fee_gwei(&self, max_fee_per_gas: U256, transfers: u64) -> u64 {
+        let wei = max_fee_per_gas * self.chain.max_transfer_gas() * transfers;
+        let gwei = (wei + to_wei(1) - 1) / to_wei(1);
+        if gwei > U256::from(u64::MAX) {
+            u64::MAX
+        } else {
+            gwei.low_u64()
+        }
+    }
+
+    // The blockchain service forgets transactions that drop out of its pool, so its pending count
+    // can fall behind the nonces we've already used. We carry on from our own count as long as
+    // we're still watching every transaction in between, since those will be resent once they're
+    // found to be stuck; otherwise we'd leave a gap that nothing would ever fill. Consuming profile
+    // wallets carry on from the payments we're still watching.
+    fn next_nonce(&mut self, payer: &Wallet, profile_wallet_opt: &Option<Wallet>) -> Nonce {
+        let count = self.blockchain_interface.get_transaction_count(payer)?;
+        let next_opt = match profile_wallet_opt {
+            Some(_) => self.pending_for(profile_wallet_opt).next_nonce(),
+            None => self.persistent_config.next_nonce().map(U256::from),
+        };
+        match next_opt {
+            Some(next) if next > count => {
+                if self.pending_for(profile_wallet_opt).covers(count, next) {
+                    Ok(next)
+                } else {
+                    warning!(
+                        self.logger,
+                        "Blockchain service reports nonce {}, but we had used up to {}; resuming from {}",
+                        count,
+                        next,
+                        count
+                    );
+                    Ok(count)
+                }
+            }
+            _ => Ok(count),
+        }
+    }
+
+    // A scan's payments go out back to back from a single nonce lookup. A payment that's rejected
+    // leaves its nonce to the next one, so the nonces of a batch never have gaps.
+    fn send_batch(
+        &mut self,
+        payer: &Wallet,
+        profile_wallet_opt: &Option<Wallet>,
+        accounts: &[PayableAccount],
+        fee: TransactionFee,
+        first_nonce: U256,
+    ) -> Vec<BlockchainResult<Payment>> {
+        debug!(
+            self.logger,
+            "Paying {} creditors in a batch starting at nonce {}",
+            accounts.len(),
+            first_nonce
+        );
+        let mut nonce = first_nonce;
+        let results = accounts
+            .iter()
+            .map(|payable| {
+                let amount = u64::try_from(payable.balance).unwrap_or_else(|_| {
+                    panic!("Lost payable amount precision: {}", payable.balance)
+                });
+                self.blockchain_interface
+                    .send_transaction(payer, &payable.wallet, amount, nonce, fee)
+                    .map(|hash| {
+                        let payment = Payment::new(payable.wallet.clone(), amount, hash);
+                        self.track_pending(
+                            profile_wallet_opt,
+                            nonce,
+                            PendingTransaction::new(payment.clone(), fee),
+                        );
+                        nonce += U256::one();
+                        payment
+                    })
+            })
+            .collect();
+        if nonce > first_nonce {
+            if profile_wallet_opt.is_none() {
+                self.persistent_config.set_next_nonce(nonce.low_u64());
+            }
+            self.charge_fee_budget(
+                self.fee_gwei(fee.max_fee_per_gas(), (nonce - first_nonce).low_u64()),
+            );
+        }
+        results
+    }
+
+    fn check_pending_transactions(&mut self) {
+        let profile_payers: Vec<(Wallet, Option<Wallet>)> = self
+            .profile_pending_transactions
+            .iter()
+            .filter(|(_, pending_transactions)| !pending_transactions.is_empty())
+            .map(|(wallet, _)| (wallet.clone(), Some(wallet.clone())))
+            .collect();
+        let own_payer_opt = match self.consuming_wallet.clone() {
+            Some(wallet) if !self.pending_transactions.is_empty() => Some((wallet, None)),
+            _ => None,
+        };
+        own_payer_opt
+            .into_iter()
+            .chain(profile_payers)
+            .for_each(|(payer, profile_wallet_opt)| {
+                self.check_pending_transactions_of(&payer, &profile_wallet_opt)
+            });
+    }
+
+    fn check_pending_transactions_of(
+        &mut self,
+        payer: &Wallet,
+        profile_wallet_opt: &Option<Wallet>,
+    ) {
+        let confirmed_count = match self
+            .blockchain_interface
+            .get_confirmed_transaction_count(payer)
+        {
+            Ok(count) => count,
+            Err(e) => {
+                warning!(self.logger, "Could not check on pending payments: {}", e);
+                return;
+            }
+        };
+        self.pending_for(profile_wallet_opt)
+            .take_confirmed(confirmed_count)
+            .into_iter()
+            .for_each(|(nonce, pending)| {
+                self.pending_transaction_dao
+                    .remove(profile_wallet_opt, nonce);
+                self.resolve_pending_transaction(profile_wallet_opt, nonce, pending)
+            });
+        let now = SystemTime::now();
+        let timeout = self.stuck_transaction_timeout;
+        self.pending_for(profile_wallet_opt)
+            .stuck(now, timeout)
+            .into_iter()
+            .for_each(|(nonce, pending)| {
+                self.unstick(payer, profile_wallet_opt, nonce, pending, now)
+            });
+    }
+
+    fn pending_for(&mut self, profile_wallet_opt: &Option<Wallet>) -> &mut PendingTransactions {
+        match profile_wallet_opt {
+            Some(wallet) => self
+                .profile_pending_transactions
+                .entry(wallet.clone())
+                .or_insert_with(PendingTransactions::new),
+            None => &mut self.pending_transactions,
+        }
+    }
+
+    fn track_pending(
+        &mut self,
+        profile_wallet_opt: &Option<Wallet>,
+        nonce: U256,
+        pending: PendingTransaction,
+    ) {
+        self.pending_transaction_dao
+            .save(profile_wallet_opt, nonce, &pending);
+        self.pending_for(profile_wallet_opt).add(nonce, pending)
+    }
+
+    // Records a resent or cancelled payment as it now stands
+    fn persist_pending(&mut self, profile_wallet_opt: &Option<Wallet>, nonce: U256) {
+        if let Some(pending) = self.pending_for(profile_wallet_opt).get(nonce).cloned() {
+            self.pending_transaction_dao
+                .save(profile_wallet_opt, nonce, &pending)
+        }
+    }
+
+    // Some transaction with this nonce has been mined; the receipts tell us which one, and
+    // whether it paid the creditor.
+    fn resolve_pending_transaction(
+        &mut self,
+        profile_wallet_opt: &Option<Wallet>,
+        nonce: U256,
+        pending: PendingTransaction,
+    ) {
+        let payment_attempts = pending.payment_attempts();
+        let candidates = payment_attempts.iter().chain(pending.cancellation.iter());
+        let mut mined = None;
+        for hash in candidates {
+            match self.blockchain_interface.get_transaction_receipt(*hash) {
+                Ok(Some(receipt)) => {
+                    mined = Some((*hash, receipt.status != Some(0.into())));
+                    break;
+                }
+                Ok(None) => (),
+                Err(e) => {
+                    warning!(
+                        self.logger,
+                        "Could not look up the outcome of the payment at nonce {}: {}; will try again",
+                        nonce,
+                        e
+                    );
+                    self.track_pending(profile_wallet_opt, nonce, pending);
+                    return;
+                }
+            }
+        }
+        let mined_transaction = match mined {
+            Some((hash, true)) if payment_attempts.contains(&hash) => Some(hash),
+            Some(_) => None,
+            // Something else used the nonce, so the creditor is still owed
+            None => {
+                warning!(
+                    self.logger,
+                    "None of the transactions sent for the payment of {} to {} at nonce {} were mined; recording it as unpaid",
+                    pending.payment.amount,
+                    pending.payment.to,
+                    nonce
+                );
+                None
+            }
+        };
+        if mined_transaction == Some(pending.payment.transaction) {
+            return;
+        }
+        self.resolved_payment_sub
+            .as_ref()
+            .expect("Accountant is unbound")
+            .try_send(ResolvedPayment {
+                payment: pending.payment,
+                mined_transaction,
+                profile_wallet_opt: profile_wallet_opt.clone(),
+            })
+            .expect("Accountant is dead");
+    }
+
+    // Resends a stuck payment with a higher fee; if it's still stuck after a few tries, replaces
+    // it with a cancellation so it stops holding up the payments with later nonces.
+    fn unstick(
+        &mut self,
+        payer: &Wallet,
+        profile_wallet_opt: &Option<Wallet>,
+        nonce: U256,
+        pending: PendingTransaction,
+        now: SystemTime,
+    ) {
+        let fee = match self
+            .market_fee()
+            .map(|fee| fee.replacing(pending.fee))
+            .and_then(|fee| self.check_fee_cap(fee))
+            .and_then(|fee| {
+                self.check_fee_budget(self.replacement_fee_gwei(fee, pending.fee))
+                    .map(|_| fee)
+            }) {
+            Ok(fee) => fee,
+            Err(e) => {
+                warning!(
+                    self.logger,
+                    "Payment of {} to {} at nonce {} is stuck, but can't be resent now: {}",
+                    pending.payment.amount,
+                    pending.payment.to,
+                    nonce,
+                    e
+                );
+                return;
+            }
+        };
+        if pending.cancellation.is_some() || pending.replacements.len() >= MAX_REPLACEMENTS {
+            match self
+                .blockchain_interface
+                .cancel_transaction(payer, nonce, fee)
+            {
+                Ok(hash) => {
+                    warning!(
+                        self.logger,
+                        "Cancelling stuck payment of {} to {} at nonce {} with transaction {:?}",
+                        pending.payment.amount,
+                        pending.payment.to,
+                        nonce,
+                        hash
+                    );
+                    self.charge_fee_budget(self.replacement_fee_gwei(fee, pending.fee));
+                    self.pending_for(profile_wallet_opt)
+                        .cancelled(nonce, hash, fee, now);
+                    self.persist_pending(profile_wallet_opt, nonce)
+                }
+                Err(e) => self.log_unstick_failure(&pending, nonce, e),
+            }
+        } else {
+            match self.blockchain_interface.send_transaction(
+                payer,
+                &pending.payment.to,
+                pending.payment.amount,
+                nonce,
+                fee,
+            ) {
+                Ok(hash) => {
+                    info!(
+                        self.logger,
+                        "Speeding up stuck payment of {} to {} at nonce {} with transaction {:?}",
+                        pending.payment.amount,
+                        pending.payment.to,
+                        nonce,
+                        hash
+                    );
+                    self.charge_fee_budget(self.replacement_fee_gwei(fee, pending.fee));
+                    self.pending_for(profile_wallet_opt)
+                        .replaced(nonce, hash, fee, now);
+                    self.persist_pending(profile_wallet_opt, nonce)
+                }
+                Err(e) => self.log_unstick_failure(&pending, nonce, e),
+            }
+        }
+    }
+
+    // Only one transaction with a nonce is ever mined, so a replacement adds only what it bids
+    // over the one it replaces.
+    fn replacement_fee_gwei(&self, fee: TransactionFee, replaced: TransactionFee) -> u64 {
+        if fee.max_fee_per_gas() > replaced.max_fee_per_gas() {
+            self.fee_gwei(fee.max_fee_per_gas() - replaced.max_fee_per_gas(), 1)
+        } else {
+            0
         }
     }
 
+    fn log_unstick_failure(&self, pending: &PendingTransaction, nonce: U256, e: BlockchainError) {
+        warning!(
+            self.logger,
+            "Could not resend stuck payment of {} to {} at nonce {}: {}",
+            pending.payment.amount,
+            pending.payment.to,
+            nonce,
+            e
+        )
+    }
+
     fn accept_wallet_password(&mut self, password: &str) -> bool {
         if self.consuming_wallet.is_some() {
             error!(
@@ -270,9 +948,10 @@ mod tests {
     use crate::blockchain::bip32::Bip32ECKeyPair;
     use crate::blockchain::bip39::{Bip39, Bip39Error};
     use crate::blockchain::blockchain_interface::{
-        contract_address, Balance, BlockchainError, BlockchainResult, Nonce, Transaction,
-        Transactions,
+        chain_id_from_name, contract_address, Balance, BlockchainError,
+        BlockchainInterfaceClandestine, BlockchainResult, Nonce, Transaction, Transactions,
     };
+    use crate::sub_lib::blockchain_bridge::FeeBudgetPeriod;
     use crate::sub_lib::cryptde::PlainData;
     use crate::sub_lib::set_consuming_wallet_message::SetConsumingWalletMessage;
     use crate::sub_lib::ui_gateway::UiMessage;
@@ -280,7 +959,7 @@ mod tests {
     use crate::test_utils::logging::init_test_logging;
     use crate::test_utils::logging::TestLogHandler;
     use crate::test_utils::persistent_configuration_mock::PersistentConfigurationMock;
-    use crate::test_utils::recorder::{make_recorder, peer_actors_builder};
+    use crate::test_utils::recorder::{make_recorder, peer_actors_builder, Recorder};
     use crate::test_utils::{
         make_default_persistent_configuration, make_paying_wallet, make_wallet, DEFAULT_CHAIN_ID,
     };
@@ -297,7 +976,7 @@ mod tests {
     use std::sync::{Arc, Mutex};
     use std::thread;
     use std::time::{Duration, SystemTime};
-    use web3::types::{Address, H256, U256};
+    use web3::types::{Address, TransactionReceipt, H256, U256};
 
     fn stub_bi() -> Box<dyn BlockchainInterface> {
         Box::new(BlockchainInterfaceMock::default())
@@ -352,6 +1031,7 @@ mod tests {
                 &bc_from_wallet(None),
                 stub_bi(),
                 Box::new(persistent_config_mock),
+                Box::new(PendingTransactionDaoMock::default()),
             );
 
             let system = System::new("blockchain_bridge_sets_wallet_when_password_is_received");
@@ -426,6 +1106,7 @@ mod tests {
                 &bc_from_wallet(None),
                 stub_bi(),
                 Box::new(persistent_config_mock),
+                Box::new(PendingTransactionDaoMock::default()),
             );
 
             let system = System::new("blockchain_bridge_sets_wallet_when_password_is_received");
@@ -471,6 +1152,7 @@ mod tests {
                 ))),
                 stub_bi(),
                 Box::new(persistent_config_mock),
+                Box::new(PendingTransactionDaoMock::default()),
             );
 
             let system = System::new(
@@ -518,6 +1200,7 @@ mod tests {
                 &bc_from_wallet(None),
                 stub_bi(),
                 Box::new(persistent_config_mock),
+                Box::new(PendingTransactionDaoMock::default()),
             );
 
             let system = System::new("blockchain_bridge_logs_error_when_setting_wallet_password_when_no_consuming_wallet_derivation_path");
@@ -563,6 +1246,7 @@ mod tests {
                 &bc_from_wallet(None),
                 stub_bi(),
                 Box::new(persistent_config_mock),
+                Box::new(PendingTransactionDaoMock::default()),
             );
 
             let system = System::new(
@@ -610,6 +1294,7 @@ mod tests {
             &bc_from_wallet(Some(consuming_wallet.clone())),
             stub_bi(),
             Box::new(make_default_persistent_configuration()),
+            Box::new(PendingTransactionDaoMock::default()),
         );
 
         let system = System::new("blockchain_bridge_receives_bind_message");
@@ -636,6 +1321,7 @@ mod tests {
             &bc_from_wallet(None),
             stub_bi(),
             Box::new(PersistentConfigurationMock::default()),
+            Box::new(PendingTransactionDaoMock::default()),
         );
 
         let system = System::new("blockchain_bridge_receives_bind_message");
@@ -666,6 +1352,7 @@ mod tests {
                 &bc_from_wallet(None),
                 stub_bi(),
                 Box::new(persistent_config_mock),
+                Box::new(PendingTransactionDaoMock::default()),
             );
 
             let system = System::new("blockchain_bridge_sets_gas_price_when_received");
@@ -710,6 +1397,7 @@ mod tests {
                 &bc_from_wallet(None),
                 stub_bi(),
                 Box::new(persistent_config_mock),
+                Box::new(PendingTransactionDaoMock::default()),
             );
 
             let system =
@@ -742,88 +1430,474 @@ mod tests {
         );
     }
 
-    #[derive(Debug, Default)]
-    struct BlockchainInterfaceMock {
-        pub retrieve_transactions_parameters: Arc<Mutex<Vec<(u64, Wallet)>>>,
-        pub retrieve_transactions_results: RefCell<Vec<BlockchainResult<Vec<Transaction>>>>,
-        pub send_transaction_parameters: Arc<Mutex<Vec<(Wallet, Wallet, u64, U256, u64)>>>,
-        pub send_transaction_results: RefCell<Vec<BlockchainResult<H256>>>,
-        pub contract_address_results: RefCell<Vec<Address>>,
-        pub get_transaction_count_parameters: Arc<Mutex<Vec<Wallet>>>,
-        pub get_transaction_count_results: RefCell<Vec<BlockchainResult<U256>>>,
-    }
+    #[test]
+    fn blockchain_bridge_reports_active_blockchain_service_url() {
+        let (ui_gateway, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
 
-    impl BlockchainInterfaceMock {
-        fn retrieve_transactions_result(
-            self,
-            result: Result<Vec<Transaction>, BlockchainError>,
-        ) -> Self {
-            self.retrieve_transactions_results.borrow_mut().push(result);
-            self
-        }
+        thread::spawn(move || {
+            let blockchain_interface = BlockchainInterfaceMock::default()
+                .active_service_url_result(Some("http://backup:8545".to_string()));
+            let subject = BlockchainBridge::new(
+                &bc_from_wallet(None),
+                Box::new(blockchain_interface),
+                Box::new(PersistentConfigurationMock::default()),
+                Box::new(PendingTransactionDaoMock::default()),
+            );
 
-        fn send_transaction_result(self, result: BlockchainResult<H256>) -> Self {
-            self.send_transaction_results.borrow_mut().push(result);
-            self
-        }
+            let system = System::new("blockchain_bridge_reports_active_blockchain_service_url");
+            let addr = subject.start();
 
-        fn contract_address_result(self, address: Address) -> Self {
-            self.contract_address_results.borrow_mut().push(address);
-            self
-        }
+            addr.try_send(BindMessage {
+                peer_actors: peer_actors_builder().ui_gateway(ui_gateway).build(),
+            })
+            .unwrap();
+            addr.try_send(GetBlockchainServiceUrlMsg { client_id: 43 })
+                .unwrap();
 
-        fn get_transaction_count_result(self, result: BlockchainResult<U256>) -> Self {
-            self.get_transaction_count_results.borrow_mut().push(result);
-            self
-        }
+            system.run();
+        });
+
+        ui_gateway_awaiter.await_message_count(1);
+
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 43,
+                data: UiMessage::BlockchainServiceUrl(Some("http://backup:8545".to_string())),
+            }
+        );
     }
 
-    impl BlockchainInterface for BlockchainInterfaceMock {
-        fn contract_address(&self) -> Address {
-            self.contract_address_results.borrow_mut().remove(0)
-        }
+    fn ask_for_health(blockchain_interface: BlockchainInterfaceMock) -> Vec<ComponentHealth> {
+        let system = System::new("ask_for_health");
+        let subject = BlockchainBridge::new(
+            &bc_from_wallet(None),
+            Box::new(blockchain_interface),
+            Box::new(PersistentConfigurationMock::default()),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+        let addr: Addr<BlockchainBridge> = subject.start();
 
-        fn retrieve_transactions(&self, start_block: u64, recipient: &Wallet) -> Transactions {
-            self.retrieve_transactions_parameters
-                .lock()
-                .unwrap()
-                .push((start_block, recipient.clone()));
-            self.retrieve_transactions_results.borrow_mut().remove(0)
-        }
+        let future = addr.send(GetHealthMessage {});
 
-        fn send_transaction(
-            &self,
-            consuming_wallet: &Wallet,
-            recipient: &Wallet,
-            amount: u64,
-            nonce: U256,
-            gas_price: u64,
-        ) -> BlockchainResult<H256> {
-            self.send_transaction_parameters.lock().unwrap().push((
-                consuming_wallet.clone(),
-                recipient.clone(),
-                amount,
-                nonce,
-                gas_price,
-            ));
-            self.send_transaction_results.borrow_mut().remove(0)
-        }
+        System::current().stop_with_code(0);
+        system.run();
+        future.wait().unwrap()
+    }
 
-        fn get_eth_balance(&self, _address: &Wallet) -> Balance {
-            unimplemented!()
-        }
+    #[test]
+    fn health_says_a_reachable_blockchain_service_is_ready() {
+        let blockchain_interface = BlockchainInterfaceMock::default()
+            .active_service_url_result(Some("http://backup:8545".to_string()))
+            .get_block_number_result(Ok(1234));
 
-        fn get_token_balance(&self, _address: &Wallet) -> Balance {
-            unimplemented!()
-        }
+        let result = ask_for_health(blockchain_interface);
 
-        fn get_transaction_count(&self, wallet: &Wallet) -> Nonce {
-            self.get_transaction_count_parameters
-                .lock()
-                .unwrap()
-                .push(wallet.clone());
-            self.get_transaction_count_results.borrow_mut().remove(0)
-        }
+        assert_eq!(
+            result,
+            vec![ComponentHealth::new(
+                "blockchain",
+                HealthState::Ready,
+                "http://backup:8545 at block 1234"
+            )]
+        );
+    }
+
+    #[test]
+    fn health_says_an_unreachable_blockchain_service_is_not_ready() {
+        let blockchain_interface = BlockchainInterfaceMock::default()
+            .active_service_url_result(Some("http://backup:8545".to_string()))
+            .get_block_number_result(Err(BlockchainError::QueryFailed));
+
+        let result = ask_for_health(blockchain_interface);
+
+        assert_eq!(
+            result,
+            vec![ComponentHealth::new(
+                "blockchain",
+                HealthState::NotReady,
+                "http://backup:8545 unreachable: Blockchain QueryFailed."
+            )]
+        );
+    }
+
+    #[test]
+    fn health_says_the_blockchain_is_disabled_without_a_service_url() {
+        let blockchain_interface =
+            BlockchainInterfaceMock::default().active_service_url_result(None);
+
+        let result = ask_for_health(blockchain_interface);
+
+        assert_eq!(
+            result,
+            vec![ComponentHealth::new(
+                "blockchain",
+                HealthState::Disabled,
+                "no blockchain service URL"
+            )]
+        );
+    }
+
+    #[test]
+    fn blockchain_bridge_reports_wallet_balances() {
+        let (ui_gateway, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        let blockchain_interface = BlockchainInterfaceMock::default()
+            .get_eth_balance_result(Ok(U256::from(1_000_000_000_000_000_000u64)))
+            .get_token_balance_result(Ok(U256::from(2345)))
+            .get_eth_balance_result(Ok(U256::from(6789)))
+            .get_token_balance_result(Err(BlockchainError::QueryFailed));
+        let get_balance_parameters_arc = blockchain_interface.get_balance_parameters.clone();
+        let consuming_wallet = make_wallet("consuming");
+
+        let consuming_wallet_inner = consuming_wallet.clone();
+        thread::spawn(move || {
+            let mut config = bc_from_wallet(Some(consuming_wallet_inner));
+            config.earning_wallet = make_wallet("earning");
+            let subject = BlockchainBridge::new(
+                &config,
+                Box::new(blockchain_interface),
+                Box::new(PersistentConfigurationMock::default()),
+                Box::new(PendingTransactionDaoMock::default()),
+            );
+            let system = System::new("blockchain_bridge_reports_wallet_balances");
+            let addr = subject.start();
+            addr.try_send(BindMessage {
+                peer_actors: peer_actors_builder().ui_gateway(ui_gateway).build(),
+            })
+            .unwrap();
+
+            addr.try_send(GetWalletBalancesMsg { client_id: 44 })
+                .unwrap();
+
+            system.run();
+        });
+
+        ui_gateway_awaiter.await_message_count(1);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 44,
+                data: UiMessage::WalletBalances(WalletBalances {
+                    earning: WalletBalance {
+                        address: make_wallet("earning").to_string(),
+                        gas_balance: Ok("1000000000000000000".to_string()),
+                        token_balance: Ok("2345".to_string()),
+                    },
+                    consuming: Some(WalletBalance {
+                        address: consuming_wallet.to_string(),
+                        gas_balance: Ok("6789".to_string()),
+                        token_balance: Err(BlockchainError::QueryFailed.to_string()),
+                    }),
+                }),
+            }
+        );
+        assert_eq!(
+            *get_balance_parameters_arc.lock().unwrap(),
+            vec![make_wallet("earning"), consuming_wallet]
+        );
+    }
+
+    #[test]
+    fn blockchain_bridge_checks_blockchain_service_health_periodically() {
+        let blockchain_interface = BlockchainInterfaceMock::default();
+        let check_health_calls_arc = blockchain_interface.check_health_calls.clone();
+
+        thread::spawn(move || {
+            let mut subject = BlockchainBridge::new(
+                &bc_from_wallet(None),
+                Box::new(blockchain_interface),
+                Box::new(PersistentConfigurationMock::default()),
+                Box::new(PendingTransactionDaoMock::default()),
+            );
+            subject.health_check_interval = Duration::from_millis(20);
+
+            let system =
+                System::new("blockchain_bridge_checks_blockchain_service_health_periodically");
+            let addr = subject.start();
+
+            addr.try_send(BindMessage {
+                peer_actors: peer_actors_builder().build(),
+            })
+            .unwrap();
+
+            system.run();
+        });
+
+        thread::sleep(Duration::from_millis(200));
+
+        assert!(*check_health_calls_arc.lock().unwrap() >= 2);
+    }
+
+    type PendingTransactionRecord = (Option<Wallet>, U256, PendingTransaction);
+
+    #[derive(Default)]
+    struct PendingTransactionDaoMock {
+        pending_transactions_result: Vec<PendingTransactionRecord>,
+        save_parameters: Arc<Mutex<Vec<PendingTransactionRecord>>>,
+        remove_parameters: Arc<Mutex<Vec<(Option<Wallet>, U256)>>>,
+    }
+
+    impl PendingTransactionDao for PendingTransactionDaoMock {
+        fn pending_transactions(&self) -> Vec<PendingTransactionRecord> {
+            self.pending_transactions_result.clone()
+        }
+
+        fn save(
+            &self,
+            profile_wallet_opt: &Option<Wallet>,
+            nonce: U256,
+            pending: &PendingTransaction,
+        ) {
+            self.save_parameters.lock().unwrap().push((
+                profile_wallet_opt.clone(),
+                nonce,
+                pending.clone(),
+            ));
+        }
+
+        fn remove(&self, profile_wallet_opt: &Option<Wallet>, nonce: U256) {
+            self.remove_parameters
+                .lock()
+                .unwrap()
+                .push((profile_wallet_opt.clone(), nonce));
+        }
+    }
+
+    impl PendingTransactionDaoMock {
+        fn pending_transactions_result(mut self, result: Vec<PendingTransactionRecord>) -> Self {
+            self.pending_transactions_result = result;
+            self
+        }
+
+        fn save_parameters(
+            mut self,
+            parameters: &Arc<Mutex<Vec<PendingTransactionRecord>>>,
+        ) -> Self {
+            self.save_parameters = parameters.clone();
+            self
+        }
+
+        fn remove_parameters(
+            mut self,
+            parameters: &Arc<Mutex<Vec<(Option<Wallet>, U256)>>>,
+        ) -> Self {
+            self.remove_parameters = parameters.clone();
+            self
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct BlockchainInterfaceMock {
+        pub retrieve_transactions_parameters: Arc<Mutex<Vec<(u64, u64, Wallet)>>>,
+        pub retrieve_transactions_results: RefCell<Vec<BlockchainResult<Vec<Transaction>>>>,
+        pub send_transaction_parameters:
+            Arc<Mutex<Vec<(Wallet, Wallet, u64, U256, TransactionFee)>>>,
+        pub send_transaction_results: RefCell<Vec<BlockchainResult<H256>>>,
+        pub contract_address_results: RefCell<Vec<Address>>,
+        pub get_transaction_count_parameters: Arc<Mutex<Vec<Wallet>>>,
+        pub get_transaction_count_results: RefCell<Vec<BlockchainResult<U256>>>,
+        pub get_block_number_results: RefCell<Vec<BlockchainResult<u64>>>,
+        pub check_health_calls: Arc<Mutex<u32>>,
+        pub active_service_url_results: RefCell<Vec<Option<String>>>,
+        pub get_base_fee_per_gas_results: RefCell<Vec<BlockchainResult<Option<U256>>>>,
+        pub get_confirmed_transaction_count_results: RefCell<Vec<BlockchainResult<U256>>>,
+        pub get_transaction_receipt_parameters: Arc<Mutex<Vec<H256>>>,
+        pub get_transaction_receipt_results:
+            RefCell<Vec<BlockchainResult<Option<TransactionReceipt>>>>,
+        pub cancel_transaction_parameters: Arc<Mutex<Vec<(Wallet, U256, TransactionFee)>>>,
+        pub cancel_transaction_results: RefCell<Vec<BlockchainResult<H256>>>,
+        pub get_balance_parameters: Arc<Mutex<Vec<Wallet>>>,
+        pub get_eth_balance_results: RefCell<Vec<Balance>>,
+        pub get_token_balance_results: RefCell<Vec<Balance>>,
+    }
+
+    impl BlockchainInterfaceMock {
+        fn retrieve_transactions_result(
+            self,
+            result: Result<Vec<Transaction>, BlockchainError>,
+        ) -> Self {
+            self.retrieve_transactions_results.borrow_mut().push(result);
+            self
+        }
+
+        fn send_transaction_result(self, result: BlockchainResult<H256>) -> Self {
+            self.send_transaction_results.borrow_mut().push(result);
+            self
+        }
+
+        fn contract_address_result(self, address: Address) -> Self {
+            self.contract_address_results.borrow_mut().push(address);
+            self
+        }
+
+        fn get_transaction_count_result(self, result: BlockchainResult<U256>) -> Self {
+            self.get_transaction_count_results.borrow_mut().push(result);
+            self
+        }
+
+        fn get_block_number_result(self, result: BlockchainResult<u64>) -> Self {
+            self.get_block_number_results.borrow_mut().push(result);
+            self
+        }
+
+        fn get_base_fee_per_gas_result(self, result: BlockchainResult<Option<U256>>) -> Self {
+            self.get_base_fee_per_gas_results.borrow_mut().push(result);
+            self
+        }
+
+        fn active_service_url_result(self, result: Option<String>) -> Self {
+            self.active_service_url_results.borrow_mut().push(result);
+            self
+        }
+
+        fn get_confirmed_transaction_count_result(self, result: BlockchainResult<U256>) -> Self {
+            self.get_confirmed_transaction_count_results
+                .borrow_mut()
+                .push(result);
+            self
+        }
+
+        fn get_transaction_receipt_result(
+            self,
+            result: BlockchainResult<Option<TransactionReceipt>>,
+        ) -> Self {
+            self.get_transaction_receipt_results
+                .borrow_mut()
+                .push(result);
+            self
+        }
+
+        fn cancel_transaction_result(self, result: BlockchainResult<H256>) -> Self {
+            self.cancel_transaction_results.borrow_mut().push(result);
+            self
+        }
+
+        fn get_eth_balance_result(self, result: Balance) -> Self {
+            self.get_eth_balance_results.borrow_mut().push(result);
+            self
+        }
+
+        fn get_token_balance_result(self, result: Balance) -> Self {
+            self.get_token_balance_results.borrow_mut().push(result);
+            self
+        }
+    }
+
+    impl BlockchainInterface for BlockchainInterfaceMock {
+        fn contract_address(&self) -> Address {
+            self.contract_address_results.borrow_mut().remove(0)
+        }
+
+        fn retrieve_transactions(
+            &self,
+            start_block: u64,
+            end_block: u64,
+            recipient: &Wallet,
+        ) -> Transactions {
+            self.retrieve_transactions_parameters.lock().unwrap().push((
+                start_block,
+                end_block,
+                recipient.clone(),
+            ));
+            let mut results = self.retrieve_transactions_results.borrow_mut();
+            if results.is_empty() {
+                Ok(vec![])
+            } else {
+                results.remove(0)
+            }
+        }
+
+        fn send_transaction(
+            &self,
+            consuming_wallet: &Wallet,
+            recipient: &Wallet,
+            amount: u64,
+            nonce: U256,
+            fee: TransactionFee,
+        ) -> BlockchainResult<H256> {
+            self.send_transaction_parameters.lock().unwrap().push((
+                consuming_wallet.clone(),
+                recipient.clone(),
+                amount,
+                nonce,
+                fee,
+            ));
+            self.send_transaction_results.borrow_mut().remove(0)
+        }
+
+        fn get_eth_balance(&self, address: &Wallet) -> Balance {
+            self.get_balance_parameters
+                .lock()
+                .unwrap()
+                .push(address.clone());
+            self.get_eth_balance_results.borrow_mut().remove(0)
+        }
+
+        fn get_token_balance(&self, _address: &Wallet) -> Balance {
+            self.get_token_balance_results.borrow_mut().remove(0)
+        }
+
+        fn get_transaction_count(&self, wallet: &Wallet) -> Nonce {
+            self.get_transaction_count_parameters
+                .lock()
+                .unwrap()
+                .push(wallet.clone());
+            self.get_transaction_count_results.borrow_mut().remove(0)
+        }
+
+        fn get_confirmed_transaction_count(&self, _wallet: &Wallet) -> Nonce {
+            self.get_confirmed_transaction_count_results
+                .borrow_mut()
+                .remove(0)
+        }
+
+        fn get_transaction_receipt(
+            &self,
+            hash: H256,
+        ) -> BlockchainResult<Option<TransactionReceipt>> {
+            self.get_transaction_receipt_parameters
+                .lock()
+                .unwrap()
+                .push(hash);
+            self.get_transaction_receipt_results.borrow_mut().remove(0)
+        }
+
+        fn cancel_transaction(
+            &self,
+            consuming_wallet: &Wallet,
+            nonce: U256,
+            fee: TransactionFee,
+        ) -> BlockchainResult<H256> {
+            self.cancel_transaction_parameters.lock().unwrap().push((
+                consuming_wallet.clone(),
+                nonce,
+                fee,
+            ));
+            self.cancel_transaction_results.borrow_mut().remove(0)
+        }
+
+        fn get_block_number(&self) -> BlockchainResult<u64> {
+            self.get_block_number_results.borrow_mut().remove(0)
+        }
+
+        fn get_base_fee_per_gas(&self) -> BlockchainResult<Option<U256>> {
+            let mut results = self.get_base_fee_per_gas_results.borrow_mut();
+            if results.is_empty() {
+                Ok(None)
+            } else {
+                results.remove(0)
+            }
+        }
+
+        fn check_health(&self) {
+            *self.check_health_calls.lock().unwrap() += 1;
+        }
+
+        fn active_service_url(&self) -> Option<String> {
+            self.active_service_url_results.borrow_mut().remove(0)
+        }
     }
 
     #[test]
@@ -834,10 +1908,12 @@ mod tests {
             block_number: 42u64,
             from: make_wallet("some_address"),
             gwei_amount: 21,
+            transaction_hash: H256::from("sometransactionhash".keccak256()),
         }];
         let result = Ok(expected_results.clone());
         let wallet = make_wallet("smelly");
         let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .get_block_number_result(Ok(45))
             .retrieve_transactions_result(result)
             .contract_address_result(contract_address(DEFAULT_CHAIN_ID));
         let retrieve_transactions_parameters = blockchain_interface_mock
@@ -847,232 +1923,1582 @@ mod tests {
             &bc_from_wallet(None),
             Box::new(blockchain_interface_mock),
             Box::new(PersistentConfigurationMock::default()),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+        let addr: Addr<BlockchainBridge> = subject.start();
+
+        let request = addr.send(RetrieveTransactions {
+            start_block: block_no,
+            latest_block: None,
+            recipient: wallet.clone(),
+        });
+        System::current().stop();
+        system.run();
+
+        let retrieve_transactions_parameters = retrieve_transactions_parameters.lock().unwrap();
+        assert_eq!((block_no, 45, wallet), retrieve_transactions_parameters[0]);
+
+        let result = request.wait().unwrap().unwrap();
+        assert_eq!(
+            RetrievedTransactions {
+                latest_block: 45,
+                transactions: expected_results,
+            },
+            result
+        );
+        assert_eq!(result.confirmations(&result.transactions[0]), 4);
+    }
+
+    #[test]
+    fn retrieve_transactions_uses_the_latest_block_it_is_given_instead_of_asking() {
+        let system = System::new(
+            "retrieve_transactions_uses_the_latest_block_it_is_given_instead_of_asking",
+        );
+        let blockchain_interface_mock =
+            BlockchainInterfaceMock::default().retrieve_transactions_result(Ok(vec![]));
+        let retrieve_transactions_parameters = blockchain_interface_mock
+            .retrieve_transactions_parameters
+            .clone();
+        let subject = BlockchainBridge::new(
+            &bc_from_wallet(None),
+            Box::new(blockchain_interface_mock),
+            Box::new(PersistentConfigurationMock::default()),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+        let addr: Addr<BlockchainBridge> = subject.start();
+        let wallet = make_wallet("smelly");
+
+        let request = addr.send(RetrieveTransactions {
+            start_block: 37,
+            latest_block: Some(436),
+            recipient: wallet.clone(),
+        });
+        System::current().stop();
+        system.run();
+
+        let retrieve_transactions_parameters = retrieve_transactions_parameters.lock().unwrap();
+        assert_eq!(*retrieve_transactions_parameters, vec![(37, 436, wallet)]);
+        assert_eq!(request.wait().unwrap().unwrap().latest_block, 436);
+    }
+
+    #[test]
+    fn retrieve_transactions_scans_a_window_of_blocks_at_a_time() {
+        let first = Transaction {
+            block_number: 40,
+            from: make_wallet("first payer"),
+            gwei_amount: 21,
+            transaction_hash: H256::from("first".keccak256()),
+        };
+        let second = Transaction {
+            block_number: 58,
+            from: make_wallet("second payer"),
+            gwei_amount: 22,
+            transaction_hash: H256::from("second".keccak256()),
+        };
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .retrieve_transactions_result(Ok(vec![first.clone()]))
+            .retrieve_transactions_result(Ok(vec![]))
+            .retrieve_transactions_result(Ok(vec![second.clone()]));
+        let retrieve_transactions_parameters = blockchain_interface_mock
+            .retrieve_transactions_parameters
+            .clone();
+        let mut subject = BlockchainBridge::new(
+            &bc_from_wallet(None),
+            Box::new(blockchain_interface_mock),
+            Box::new(PersistentConfigurationMock::default()),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+        subject.max_blocks_per_log_query = 10;
+        subject.blocks_per_log_query = 10;
+        let wallet = make_wallet("smelly");
+
+        let result = subject.scan_for_transactions(37, 60, &wallet);
+
+        assert_eq!(
+            *retrieve_transactions_parameters.lock().unwrap(),
+            vec![
+                (37, 46, wallet.clone()),
+                (47, 56, wallet.clone()),
+                (57, 60, wallet.clone())
+            ]
+        );
+        assert_eq!(
+            result,
+            Ok(RetrievedTransactions {
+                latest_block: 60,
+                transactions: vec![first, second],
+            })
+        );
+    }
+
+    #[test]
+    fn retrieve_transactions_narrows_the_window_when_a_query_is_refused() {
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .retrieve_transactions_result(Err(BlockchainError::QueryFailed))
+            .retrieve_transactions_result(Err(BlockchainError::QueryFailed));
+        let retrieve_transactions_parameters = blockchain_interface_mock
+            .retrieve_transactions_parameters
+            .clone();
+        let mut subject = BlockchainBridge::new(
+            &bc_from_wallet(None),
+            Box::new(blockchain_interface_mock),
+            Box::new(PersistentConfigurationMock::default()),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+        subject.max_blocks_per_log_query = 40;
+        subject.blocks_per_log_query = 40;
+        let wallet = make_wallet("smelly");
+
+        let result = subject.scan_for_transactions(1, 40, &wallet);
+
+        assert_eq!(
+            *retrieve_transactions_parameters.lock().unwrap(),
+            vec![
+                (1, 40, wallet.clone()),
+                (1, 20, wallet.clone()),
+                (1, 10, wallet.clone()),
+                (11, 30, wallet.clone()),
+                (31, 40, wallet.clone())
+            ]
+        );
+        assert_eq!(result.unwrap().latest_block, 40);
+        assert_eq!(subject.blocks_per_log_query, 40);
+    }
+
+    #[test]
+    fn retrieve_transactions_stops_after_a_limited_number_of_queries() {
+        let blockchain_interface_mock = BlockchainInterfaceMock::default();
+        let retrieve_transactions_parameters = blockchain_interface_mock
+            .retrieve_transactions_parameters
+            .clone();
+        let mut subject = BlockchainBridge::new(
+            &bc_from_wallet(None),
+            Box::new(blockchain_interface_mock),
+            Box::new(PersistentConfigurationMock::default()),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+
+        let result = subject.scan_for_transactions(
+            1,
+            DEFAULT_MAX_BLOCKS_PER_LOG_QUERY * 1000,
+            &make_wallet("smelly"),
+        );
+
+        assert_eq!(
+            retrieve_transactions_parameters.lock().unwrap().len(),
+            MAX_LOG_QUERIES_PER_SCAN
+        );
+        assert_eq!(
+            result.unwrap().latest_block,
+            DEFAULT_MAX_BLOCKS_PER_LOG_QUERY * MAX_LOG_QUERIES_PER_SCAN as u64
+        );
+    }
+
+    #[test]
+    fn retrieve_transactions_reports_what_it_found_before_a_query_failed() {
+        init_test_logging();
+        let found = Transaction {
+            block_number: 5,
+            from: make_wallet("payer"),
+            gwei_amount: 21,
+            transaction_hash: H256::from("found".keccak256()),
+        };
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .retrieve_transactions_result(Ok(vec![found.clone()]))
+            .retrieve_transactions_result(Err(BlockchainError::InvalidResponse));
+        let mut subject = BlockchainBridge::new(
+            &bc_from_wallet(None),
+            Box::new(blockchain_interface_mock),
+            Box::new(PersistentConfigurationMock::default()),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+        subject.max_blocks_per_log_query = 10;
+        subject.blocks_per_log_query = 10;
+        let wallet = make_wallet("smelly");
+
+        let result = subject.scan_for_transactions(1, 30, &wallet);
+
+        assert_eq!(
+            result,
+            Ok(RetrievedTransactions {
+                latest_block: 10,
+                transactions: vec![found],
+            })
+        );
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: BlockchainBridge: Scan for payments to {} stopped at block 11: Blockchain InvalidResponse.",
+            wallet
+        ));
+    }
+
+    #[test]
+    fn retrieve_transactions_fails_when_no_blocks_could_be_scanned() {
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .retrieve_transactions_result(Err(BlockchainError::InvalidResponse));
+        let mut subject = BlockchainBridge::new(
+            &bc_from_wallet(None),
+            Box::new(blockchain_interface_mock),
+            Box::new(PersistentConfigurationMock::default()),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+
+        let result = subject.scan_for_transactions(1, 30, &make_wallet("smelly"));
+
+        assert_eq!(result, Err(BlockchainError::InvalidResponse));
+    }
+
+    #[test]
+    fn retrieve_transactions_queries_nothing_when_the_start_block_is_past_the_chain_head() {
+        let blockchain_interface_mock = BlockchainInterfaceMock::default();
+        let retrieve_transactions_parameters = blockchain_interface_mock
+            .retrieve_transactions_parameters
+            .clone();
+        let mut subject = BlockchainBridge::new(
+            &bc_from_wallet(None),
+            Box::new(blockchain_interface_mock),
+            Box::new(PersistentConfigurationMock::default()),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+
+        let result = subject.scan_for_transactions(31, 30, &make_wallet("smelly"));
+
+        assert_eq!(
+            result,
+            Ok(RetrievedTransactions {
+                latest_block: 30,
+                transactions: vec![],
+            })
+        );
+        assert!(retrieve_transactions_parameters.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn retrieve_transactions_fails_without_retrieving_if_block_number_is_unavailable() {
+        let system = System::new(
+            "retrieve_transactions_fails_without_retrieving_if_block_number_is_unavailable",
+        );
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .get_block_number_result(Err(BlockchainError::QueryFailed));
+        let retrieve_transactions_parameters = blockchain_interface_mock
+            .retrieve_transactions_parameters
+            .clone();
+        let subject = BlockchainBridge::new(
+            &bc_from_wallet(None),
+            Box::new(blockchain_interface_mock),
+            Box::new(PersistentConfigurationMock::default()),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+        let addr: Addr<BlockchainBridge> = subject.start();
+
+        let request = addr.send(RetrieveTransactions {
+            start_block: 37,
+            latest_block: None,
+            recipient: make_wallet("smelly"),
+        });
+        System::current().stop();
+        system.run();
+
+        assert_eq!(request.wait().unwrap(), Err(BlockchainError::QueryFailed));
+        assert!(retrieve_transactions_parameters.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn confirmations_count_the_block_a_transaction_is_in() {
+        let subject = RetrievedTransactions {
+            latest_block: 100,
+            transactions: vec![],
+        };
+        let transaction_in = |block_number| Transaction {
+            block_number,
+            from: make_wallet("payer"),
+            gwei_amount: 1,
+            transaction_hash: H256::from("sometransactionhash".keccak256()),
+        };
+
+        assert_eq!(subject.confirmations(&transaction_in(100)), 1);
+        assert_eq!(subject.confirmations(&transaction_in(89)), 12);
+        assert_eq!(subject.confirmations(&transaction_in(101)), 0);
+    }
+
+    #[test]
+    fn report_accounts_payable_sends_transactions_to_blockchain_interface() {
+        let system =
+            System::new("report_accounts_payable_sends_transactions_to_blockchain_interface");
+
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .get_transaction_count_result(Ok(U256::from(1)))
+            .send_transaction_result(Ok(H256::from("sometransactionhash".keccak256())))
+            .send_transaction_result(Ok(H256::from("someothertransactionhash".keccak256())))
+            .contract_address_result(contract_address(DEFAULT_CHAIN_ID));
+        let send_parameters = blockchain_interface_mock
+            .send_transaction_parameters
+            .clone();
+        let transaction_count_parameters = blockchain_interface_mock
+            .get_transaction_count_parameters
+            .clone();
+        let expected_gas_price = 5u64;
+        let persistent_configuration_mock =
+            PersistentConfigurationMock::default().gas_price_result(expected_gas_price);
+
+        let consuming_wallet = make_paying_wallet(b"somewallet");
+        let subject = BlockchainBridge::new(
+            &bc_from_wallet(Some(consuming_wallet.clone())),
+            Box::new(blockchain_interface_mock),
+            Box::new(persistent_configuration_mock),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+        let addr: Addr<BlockchainBridge> = subject.start();
+
+        let request = addr.send(ReportAccountsPayable {
+            accounts: vec![
+                PayableAccount {
+                    wallet: make_wallet("blah"),
+                    balance: 42,
+                    last_paid_timestamp: SystemTime::now(),
+                    pending_payment_transaction: None,
+                },
+                PayableAccount {
+                    wallet: make_wallet("foo"),
+                    balance: 21,
+                    last_paid_timestamp: SystemTime::now(),
+                    pending_payment_transaction: None,
+                },
+            ],
+            profile_wallet_opt: None,
+        });
+        System::current().stop();
+        system.run();
+
+        assert_eq!(
+            send_parameters.lock().unwrap()[0],
+            (
+                consuming_wallet.clone(),
+                make_wallet("blah"),
+                42,
+                U256::from(1),
+                TransactionFee::Legacy {
+                    gas_price: to_wei(expected_gas_price)
+                }
+            )
+        );
+        assert_eq!(
+            send_parameters.lock().unwrap()[1],
+            (
+                consuming_wallet.clone(),
+                make_wallet("foo"),
+                21,
+                U256::from(2),
+                TransactionFee::Legacy {
+                    gas_price: to_wei(expected_gas_price)
+                }
+            )
+        );
+
+        let result = request.wait().unwrap().unwrap();
+        let mut expected_payment_0 = Payment::new(
+            make_wallet("blah"),
+            42,
+            H256::from("sometransactionhash".keccak256()),
+        );
+
+        if let Ok(zero) = result.clone().get(0).unwrap().clone() {
+            assert!(
+                zero.timestamp
+                    <= expected_payment_0
+                        .timestamp
+                        .checked_add(Duration::from_secs(2))
+                        .unwrap()
+            );
+            assert!(
+                zero.timestamp
+                    >= expected_payment_0
+                        .timestamp
+                        .checked_sub(Duration::from_secs(2))
+                        .unwrap()
+            );
+            expected_payment_0.timestamp = zero.timestamp
+        }
+
+        let mut expected_payment_1 = Payment::new(
+            make_wallet("foo"),
+            21,
+            H256::from("someothertransactionhash".keccak256()),
+        );
+
+        if let Ok(one) = result.clone().get(1).unwrap().clone() {
+            assert!(
+                one.timestamp
+                    <= expected_payment_1
+                        .timestamp
+                        .checked_add(Duration::from_secs(2))
+                        .unwrap()
+            );
+            assert!(
+                one.timestamp
+                    >= expected_payment_1
+                        .timestamp
+                        .checked_sub(Duration::from_secs(2))
+                        .unwrap()
+            );
+            expected_payment_1.timestamp = one.timestamp
+        }
+
+        assert_eq!(result[1], Ok(expected_payment_1));
+
+        assert_eq!(
+            *transaction_count_parameters.lock().unwrap(),
+            vec![consuming_wallet.clone()],
+        );
+    }
+
+    #[test]
+    fn report_accounts_payable_gives_a_rejected_payments_nonce_to_the_next_one() {
+        let system =
+            System::new("report_accounts_payable_gives_a_rejected_payments_nonce_to_the_next_one");
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .get_transaction_count_result(Ok(U256::from(7)))
+            .send_transaction_result(Err(BlockchainError::TransactionFailed(
+                "insufficient funds".to_string(),
+            )))
+            .send_transaction_result(Ok(H256::from("sometransactionhash".keccak256())))
+            .send_transaction_result(Ok(H256::from("someothertransactionhash".keccak256())));
+        let send_parameters = blockchain_interface_mock
+            .send_transaction_parameters
+            .clone();
+        let subject = BlockchainBridge::new(
+            &bc_from_wallet(Some(make_paying_wallet(b"somewallet"))),
+            Box::new(blockchain_interface_mock),
+            Box::new(PersistentConfigurationMock::default().gas_price_result(1)),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+        let addr: Addr<BlockchainBridge> = subject.start();
+        let mut accounts = make_payable_accounts();
+        accounts.push(PayableAccount {
+            wallet: make_wallet("third"),
+            balance: 30,
+            last_paid_timestamp: SystemTime::now(),
+            pending_payment_transaction: None,
+        });
+
+        let request = addr.send(ReportAccountsPayable {
+            accounts,
+            profile_wallet_opt: None,
+        });
+        System::current().stop();
+        system.run();
+
+        let nonces: Vec<U256> = send_parameters
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|parameters| parameters.3)
+            .collect();
+        assert_eq!(nonces, vec![U256::from(7), U256::from(7), U256::from(8)]);
+        let result = request.wait().unwrap().unwrap();
+        assert_eq!(
+            result[0],
+            Err(BlockchainError::TransactionFailed(
+                "insufficient funds".to_string()
+            ))
+        );
+        assert!(result[1].is_ok());
+        assert!(result[2].is_ok());
+    }
+
+    #[test]
+    fn report_accounts_payable_sends_nothing_when_the_nonce_is_unavailable() {
+        let system =
+            System::new("report_accounts_payable_sends_nothing_when_the_nonce_is_unavailable");
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .get_transaction_count_result(Err(BlockchainError::QueryFailed));
+        let send_parameters = blockchain_interface_mock
+            .send_transaction_parameters
+            .clone();
+        let subject = BlockchainBridge::new(
+            &bc_from_wallet(Some(make_paying_wallet(b"somewallet"))),
+            Box::new(blockchain_interface_mock),
+            Box::new(PersistentConfigurationMock::default().gas_price_result(1)),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+        let addr: Addr<BlockchainBridge> = subject.start();
+
+        let request = addr.send(ReportAccountsPayable {
+            accounts: make_payable_accounts(),
+            profile_wallet_opt: None,
+        });
+        System::current().stop();
+        system.run();
+
+        assert!(send_parameters.lock().unwrap().is_empty());
+        assert_eq!(
+            request.wait().unwrap().unwrap(),
+            vec![
+                Err(BlockchainError::QueryFailed),
+                Err(BlockchainError::QueryFailed)
+            ]
+        );
+    }
+
+    #[test]
+    fn report_accounts_payable_returns_error_for_blockchain_error() {
+        let system = System::new("report_accounts_payable_returns_error_for_blockchain_error");
+
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .get_transaction_count_result(Ok(web3::types::U256::from(1)))
+            .send_transaction_result(Err(BlockchainError::TransactionFailed(String::from(
+                "mock payment failure",
+            ))));
+
+        let transaction_count_parameters = blockchain_interface_mock
+            .get_transaction_count_parameters
+            .clone();
+
+        let consuming_wallet = make_wallet("somewallet");
+
+        let persistent_configuration_mock =
+            PersistentConfigurationMock::new().gas_price_result(3u64);
+        let subject = BlockchainBridge::new(
+            &bc_from_wallet(Some(consuming_wallet.clone())),
+            Box::new(blockchain_interface_mock),
+            Box::new(persistent_configuration_mock),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+        let addr: Addr<BlockchainBridge> = subject.start();
+
+        let request = addr.send(ReportAccountsPayable {
+            accounts: vec![PayableAccount {
+                wallet: make_wallet("blah"),
+                balance: 42,
+                last_paid_timestamp: SystemTime::now(),
+                pending_payment_transaction: None,
+            }],
+            profile_wallet_opt: None,
+        });
+
+        System::current().stop();
+        system.run();
+
+        let result = &request.wait().unwrap().unwrap();
+
+        assert_eq!(
+            result,
+            &vec![Err(BlockchainError::TransactionFailed(String::from(
+                "mock payment failure"
+            )))]
+        );
+        let actual_wallet = transaction_count_parameters.lock().unwrap().remove(0);
+
+        assert_eq!(actual_wallet, consuming_wallet);
+    }
+
+    #[test]
+    fn report_accounts_payable_returns_error_when_there_is_no_consuming_wallet_configured() {
+        let system = System::new("report_accounts_payable_returns_error_for_blockchain_error");
+
+        let blockchain_interface_mock = BlockchainInterfaceMock::default();
+        let persistent_configuration_mock = PersistentConfigurationMock::default();
+
+        let subject = BlockchainBridge::new(
+            &BootstrapperConfig::new(),
+            Box::new(blockchain_interface_mock),
+            Box::new(persistent_configuration_mock),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+        let addr: Addr<BlockchainBridge> = subject.start();
+
+        let request = addr.send(ReportAccountsPayable {
+            accounts: vec![PayableAccount {
+                wallet: make_wallet("blah"),
+                balance: 42,
+                last_paid_timestamp: SystemTime::now(),
+                pending_payment_transaction: None,
+            }],
+            profile_wallet_opt: None,
+        });
+
+        System::current().stop();
+        system.run();
+
+        let result = &request.wait().unwrap();
+
+        assert_eq!(result, &Err("No consuming wallet specified".to_string()));
+    }
+
+    fn make_payable_accounts() -> Vec<PayableAccount> {
+        vec![
+            PayableAccount {
+                wallet: make_wallet("blah"),
+                balance: 42,
+                last_paid_timestamp: SystemTime::now(),
+                pending_payment_transaction: None,
+            },
+            PayableAccount {
+                wallet: make_wallet("foo"),
+                balance: 21,
+                last_paid_timestamp: SystemTime::now(),
+                pending_payment_transaction: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn report_accounts_payable_bids_according_to_fee_strategy_when_the_chain_has_a_base_fee() {
+        let system = System::new(
+            "report_accounts_payable_bids_according_to_fee_strategy_when_the_chain_has_a_base_fee",
+        );
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .get_base_fee_per_gas_result(Ok(Some(to_wei(40))))
+            .get_transaction_count_result(Ok(U256::from(1)))
+            .get_transaction_count_result(Ok(U256::from(2)))
+            .send_transaction_result(Ok(H256::from("sometransactionhash".keccak256())))
+            .send_transaction_result(Ok(H256::from("someothertransactionhash".keccak256())));
+        let send_parameters = blockchain_interface_mock
+            .send_transaction_parameters
+            .clone();
+        let mut config = bc_from_wallet(Some(make_paying_wallet(b"somewallet")));
+        config.blockchain_bridge_config.fee_strategy = FeeStrategy::Fast;
+        config.blockchain_bridge_config.gas_price_cap = Some(123);
+        let subject = BlockchainBridge::new(
+            &config,
+            Box::new(blockchain_interface_mock),
+            Box::new(PersistentConfigurationMock::default()),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+        let addr: Addr<BlockchainBridge> = subject.start();
+
+        let request = addr.send(ReportAccountsPayable {
+            accounts: make_payable_accounts(),
+            profile_wallet_opt: None,
+        });
+        System::current().stop();
+        system.run();
+
+        let expected_fee = TransactionFee::Eip1559 {
+            max_fee_per_gas: to_wei(123),
+            max_priority_fee_per_gas: to_wei(3),
+        };
+        let send_parameters = send_parameters.lock().unwrap();
+        assert_eq!(send_parameters.len(), 2);
+        assert_eq!(send_parameters[0].4, expected_fee);
+        assert_eq!(send_parameters[1].4, expected_fee);
+        let result = request.wait().unwrap().unwrap();
+        assert!(result.iter().all(|payment| payment.is_ok()));
+    }
+
+    #[test]
+    fn report_accounts_payable_tips_according_to_the_chain() {
+        let system = System::new("report_accounts_payable_tips_according_to_the_chain");
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .get_base_fee_per_gas_result(Ok(Some(to_wei(8))))
+            .get_transaction_count_result(Ok(U256::from(1)))
+            .send_transaction_result(Ok(H256::from("sometransactionhash".keccak256())));
+        let send_parameters = blockchain_interface_mock
+            .send_transaction_parameters
+            .clone();
+        let mut config = bc_from_wallet(Some(make_paying_wallet(b"somewallet")));
+        config.blockchain_bridge_config.chain_id = chain_id_from_name("xdai");
+        config.blockchain_bridge_config.fee_strategy = FeeStrategy::Fast;
+        let subject = BlockchainBridge::new(
+            &config,
+            Box::new(blockchain_interface_mock),
+            Box::new(PersistentConfigurationMock::default()),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+        let addr: Addr<BlockchainBridge> = subject.start();
+
+        let request = addr.send(ReportAccountsPayable {
+            accounts: vec![make_payable_accounts().remove(0)],
+            profile_wallet_opt: None,
+        });
+        System::current().stop();
+        system.run();
+
+        assert_eq!(
+            send_parameters.lock().unwrap()[0].4,
+            TransactionFee::Eip1559 {
+                max_fee_per_gas: to_wei(26),
+                max_priority_fee_per_gas: to_wei(2),
+            }
+        );
+        assert!(request.wait().unwrap().unwrap()[0].is_ok());
+    }
+
+    #[test]
+    fn report_accounts_payable_refuses_to_pay_when_the_fee_exceeds_the_cap() {
+        init_test_logging();
+        let system =
+            System::new("report_accounts_payable_refuses_to_pay_when_the_fee_exceeds_the_cap");
+        let blockchain_interface_mock =
+            BlockchainInterfaceMock::default().get_base_fee_per_gas_result(Ok(Some(to_wei(40))));
+        let send_parameters = blockchain_interface_mock
+            .send_transaction_parameters
+            .clone();
+        let mut config = bc_from_wallet(Some(make_paying_wallet(b"somewallet")));
+        config.blockchain_bridge_config.gas_price_cap = Some(81);
+        let subject = BlockchainBridge::new(
+            &config,
+            Box::new(blockchain_interface_mock),
+            Box::new(PersistentConfigurationMock::default()),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+        let addr: Addr<BlockchainBridge> = subject.start();
+
+        let request = addr.send(ReportAccountsPayable {
+            accounts: make_payable_accounts(),
+            profile_wallet_opt: None,
+        });
+        System::current().stop();
+        system.run();
+
+        let expected_error = BlockchainError::FeeCapExceeded {
+            max_fee_per_gas: to_wei(82),
+            cap: to_wei(81),
+        };
+        assert_eq!(
+            request.wait().unwrap(),
+            Ok(vec![Err(expected_error.clone()), Err(expected_error)])
+        );
+        assert!(send_parameters.lock().unwrap().is_empty());
+        TestLogHandler::new().exists_log_containing(
+            "WARN: BlockchainBridge: Not paying now: a fee of 82000000000 wei per gas would exceed the cap of 81 Gwei; will retry later",
+        );
+    }
+
+    #[test]
+    fn report_accounts_payable_caps_legacy_gas_price_too() {
+        let system = System::new("report_accounts_payable_caps_legacy_gas_price_too");
+        let mut config = bc_from_wallet(Some(make_paying_wallet(b"somewallet")));
+        config.blockchain_bridge_config.gas_price_cap = Some(4);
+        let subject = BlockchainBridge::new(
+            &config,
+            Box::new(BlockchainInterfaceMock::default()),
+            Box::new(PersistentConfigurationMock::default().gas_price_result(5)),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+        let addr: Addr<BlockchainBridge> = subject.start();
+
+        let request = addr.send(ReportAccountsPayable {
+            accounts: make_payable_accounts()[0..1].to_vec(),
+            profile_wallet_opt: None,
+        });
+        System::current().stop();
+        system.run();
+
+        assert_eq!(
+            request.wait().unwrap(),
+            Ok(vec![Err(BlockchainError::FeeCapExceeded {
+                max_fee_per_gas: to_wei(5),
+                cap: to_wei(4),
+            })])
+        );
+    }
+
+    fn now_secs() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[test]
+    fn report_accounts_payable_defers_payments_that_could_exceed_the_fee_budget() {
+        init_test_logging();
+        let system =
+            System::new("report_accounts_payable_defers_payments_that_could_exceed_the_fee_budget");
+        let blockchain_interface_mock =
+            BlockchainInterfaceMock::default().get_base_fee_per_gas_result(Ok(Some(to_wei(40))));
+        let send_parameters = blockchain_interface_mock
+            .send_transaction_parameters
+            .clone();
+        let mut config = bc_from_wallet(Some(make_paying_wallet(b"somewallet")));
+        config.blockchain_bridge_config.fee_budget = Some(FeeBudget {
+            gwei: 10_000_000,
+            period: FeeBudgetPeriod::Weekly,
+        });
+        let persistent_config =
+            PersistentConfigurationMock::default().fee_spending_result(Some(FeeSpending {
+                period_start: now_secs() - 3600,
+                gwei: 500_000,
+            }));
+        let subject = BlockchainBridge::new(
+            &config,
+            Box::new(blockchain_interface_mock),
+            Box::new(persistent_config),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+        let addr: Addr<BlockchainBridge> = subject.start();
+
+        let request = addr.send(ReportAccountsPayable {
+            accounts: make_payable_accounts(),
+            profile_wallet_opt: None,
+        });
+        System::current().stop();
+        system.run();
+
+        // Two transfers of at most 59,624 gas at 82 Gwei
+        let expected_error = BlockchainError::FeeBudgetExceeded {
+            projected_gwei: 9_778_336,
+            remaining_gwei: 9_500_000,
+        };
+        assert_eq!(
+            request.wait().unwrap(),
+            Ok(vec![Err(expected_error.clone()), Err(expected_error)])
+        );
+        assert!(send_parameters.lock().unwrap().is_empty());
+        TestLogHandler::new().exists_log_containing(
+            "WARN: BlockchainBridge: Not paying now: 2 payments could cost up to 9778336 Gwei in fees, but only 9500000 Gwei of this week's fee budget is left; will retry later",
+        );
+    }
+
+    #[test]
+    fn report_accounts_payable_charges_the_fee_budget_for_the_payments_it_sends() {
+        let system =
+            System::new("report_accounts_payable_charges_the_fee_budget_for_the_payments_it_sends");
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .get_base_fee_per_gas_result(Ok(Some(to_wei(40))))
+            .get_transaction_count_result(Ok(U256::from(1)))
+            .send_transaction_result(Ok(H256::from("sometransactionhash".keccak256())))
+            .send_transaction_result(Err(BlockchainError::TransactionFailed(
+                "insufficient funds".to_string(),
+            )));
+        let set_fee_spending_params_arc = Arc::new(Mutex::new(vec![]));
+        let mut config = bc_from_wallet(Some(make_paying_wallet(b"somewallet")));
+        config.blockchain_bridge_config.fee_budget = Some(FeeBudget {
+            gwei: 10_000_000,
+            period: FeeBudgetPeriod::Monthly,
+        });
+        let persistent_config = PersistentConfigurationMock::default()
+            .set_fee_spending_params(&set_fee_spending_params_arc);
+        let subject = BlockchainBridge::new(
+            &config,
+            Box::new(blockchain_interface_mock),
+            Box::new(persistent_config),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+        let addr: Addr<BlockchainBridge> = subject.start();
+        let before = now_secs();
+
+        let request = addr.send(ReportAccountsPayable {
+            accounts: make_payable_accounts(),
+            profile_wallet_opt: None,
+        });
+        System::current().stop();
+        system.run();
+
+        assert!(request.wait().unwrap().unwrap()[0].is_ok());
+        let set_fee_spending_params = set_fee_spending_params_arc.lock().unwrap();
+        assert_eq!(set_fee_spending_params.len(), 1);
+        assert_eq!(set_fee_spending_params[0].gwei, 4_889_168);
+        assert!(set_fee_spending_params[0].period_start >= before);
+        assert!(set_fee_spending_params[0].period_start <= now_secs());
+    }
+
+    #[test]
+    fn report_accounts_payable_remembers_the_nonces_it_used() {
+        let system = System::new("report_accounts_payable_remembers_the_nonces_it_used");
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .get_transaction_count_result(Ok(U256::from(7)))
+            .send_transaction_result(Ok(H256::from("first".keccak256())))
+            .send_transaction_result(Ok(H256::from("second".keccak256())));
+        let set_next_nonce_params = Arc::new(Mutex::new(vec![]));
+        let subject = BlockchainBridge::new(
+            &bc_from_wallet(Some(make_paying_wallet(b"somewallet"))),
+            Box::new(blockchain_interface_mock),
+            Box::new(
+                PersistentConfigurationMock::default()
+                    .gas_price_result(1)
+                    .set_next_nonce_params(&set_next_nonce_params),
+            ),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+        let addr: Addr<BlockchainBridge> = subject.start();
+
+        let request = addr.send(ReportAccountsPayable {
+            accounts: make_payable_accounts(),
+            profile_wallet_opt: None,
+        });
+        System::current().stop();
+        system.run();
+
+        assert_eq!(request.wait().unwrap().unwrap().len(), 2);
+        assert_eq!(*set_next_nonce_params.lock().unwrap(), vec![9]);
+    }
+
+    #[test]
+    fn report_accounts_payable_pays_from_the_profile_wallet_it_names() {
+        let system = System::new("report_accounts_payable_pays_from_the_profile_wallet_it_names");
+        let profile_wallet = make_paying_wallet(b"profile wallet");
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .get_transaction_count_result(Ok(U256::from(3)))
+            .send_transaction_result(Ok(H256::from("first".keccak256())))
+            .send_transaction_result(Ok(H256::from("second".keccak256())));
+        let get_transaction_count_parameters = blockchain_interface_mock
+            .get_transaction_count_parameters
+            .clone();
+        let send_transaction_parameters = blockchain_interface_mock
+            .send_transaction_parameters
+            .clone();
+        let set_next_nonce_params = Arc::new(Mutex::new(vec![]));
+        let save_parameters = Arc::new(Mutex::new(vec![]));
+        let subject = BlockchainBridge::new(
+            &bc_from_wallet(Some(make_paying_wallet(b"somewallet"))),
+            Box::new(blockchain_interface_mock),
+            Box::new(
+                PersistentConfigurationMock::default()
+                    .gas_price_result(1)
+                    .set_next_nonce_params(&set_next_nonce_params),
+            ),
+            Box::new(PendingTransactionDaoMock::default().save_parameters(&save_parameters)),
+        );
+        let addr: Addr<BlockchainBridge> = subject.start();
+
+        let request = addr.send(ReportAccountsPayable {
+            accounts: make_payable_accounts(),
+            profile_wallet_opt: Some(profile_wallet.clone()),
+        });
+        System::current().stop();
+        system.run();
+
+        assert_eq!(request.wait().unwrap().unwrap().len(), 2);
+        assert_eq!(
+            *get_transaction_count_parameters.lock().unwrap(),
+            vec![profile_wallet.clone()]
+        );
+        let send_transaction_parameters = send_transaction_parameters.lock().unwrap();
+        assert_eq!(send_transaction_parameters[0].0, profile_wallet);
+        assert_eq!(send_transaction_parameters[0].3, U256::from(3));
+        assert_eq!(send_transaction_parameters[1].0, profile_wallet);
+        assert_eq!(send_transaction_parameters[1].3, U256::from(4));
+        assert!(set_next_nonce_params.lock().unwrap().is_empty());
+        let save_parameters = save_parameters.lock().unwrap();
+        assert_eq!(
+            save_parameters
+                .iter()
+                .map(|(wallet_opt, nonce, _)| (wallet_opt.clone(), *nonce))
+                .collect::<Vec<(Option<Wallet>, U256)>>(),
+            vec![
+                (Some(profile_wallet.clone()), U256::from(3)),
+                (Some(profile_wallet), U256::from(4))
+            ]
+        );
+    }
+
+    #[test]
+    fn next_nonce_for_a_profile_wallet_continues_past_its_own_pending_transactions() {
+        let profile_wallet = Some(make_wallet("profile"));
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .get_transaction_count_result(Ok(U256::from(7)))
+            .get_transaction_count_result(Ok(U256::from(7)));
+        let mut subject = BlockchainBridge::new(
+            &bc_from_wallet(None),
+            Box::new(blockchain_interface_mock),
+            Box::new(PersistentConfigurationMock::default().next_nonce_result(None)),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+        subject
+            .pending_for(&profile_wallet)
+            .add(U256::from(7), make_pending("seven", SystemTime::now()));
+        subject
+            .pending_for(&profile_wallet)
+            .add(U256::from(8), make_pending("eight", SystemTime::now()));
+
+        let profile_result = subject.next_nonce(&make_wallet("profile"), &profile_wallet);
+        let own_result = subject.next_nonce(&make_wallet("consumer"), &None);
+
+        assert_eq!(profile_result, Ok(U256::from(9)));
+        assert_eq!(own_result, Ok(U256::from(7)));
+    }
+
+    #[test]
+    fn next_nonce_continues_past_pending_transactions_the_service_has_forgotten() {
+        let blockchain_interface_mock =
+            BlockchainInterfaceMock::default().get_transaction_count_result(Ok(U256::from(7)));
+        let mut subject = BlockchainBridge::new(
+            &bc_from_wallet(None),
+            Box::new(blockchain_interface_mock),
+            Box::new(PersistentConfigurationMock::default().next_nonce_result(Some(9))),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+        subject
+            .pending_transactions
+            .add(U256::from(7), make_pending("seven", SystemTime::now()));
+        subject
+            .pending_transactions
+            .add(U256::from(8), make_pending("eight", SystemTime::now()));
+
+        let result = subject.next_nonce(&make_wallet("consumer"), &None);
+
+        assert_eq!(result, Ok(U256::from(9)));
+    }
+
+    #[test]
+    fn next_nonce_falls_back_to_the_services_count_when_it_cant_account_for_the_gap() {
+        init_test_logging();
+        let blockchain_interface_mock =
+            BlockchainInterfaceMock::default().get_transaction_count_result(Ok(U256::from(7)));
+        let mut subject = BlockchainBridge::new(
+            &bc_from_wallet(None),
+            Box::new(blockchain_interface_mock),
+            Box::new(PersistentConfigurationMock::default().next_nonce_result(Some(9))),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+
+        let result = subject.next_nonce(&make_wallet("consumer"), &None);
+
+        assert_eq!(result, Ok(U256::from(7)));
+        TestLogHandler::new().exists_log_containing(
+            "WARN: BlockchainBridge: Blockchain service reports nonce 7, but we had used up to 9; resuming from 7",
+        );
+    }
+
+    fn make_pending(name: &str, sent: SystemTime) -> PendingTransaction {
+        let mut payment = Payment::new(make_wallet(name), 100, H256::from(name.keccak256()));
+        payment.timestamp = sent;
+        PendingTransaction::new(
+            payment,
+            TransactionFee::Legacy {
+                gas_price: to_wei(1),
+            },
+        )
+    }
+
+    fn make_receipt(hash: H256, status: u64) -> TransactionReceipt {
+        TransactionReceipt {
+            transaction_hash: hash,
+            status: Some(status.into()),
+            ..TransactionReceipt::default()
+        }
+    }
+
+    fn make_checking_subject(
+        blockchain_interface_mock: BlockchainInterfaceMock,
+        accountant: Recorder,
+    ) -> BlockchainBridge {
+        let mut bc = bc_from_wallet(Some(make_wallet("consumer")));
+        bc.blockchain_bridge_config.gas_price_cap = Some(10);
+        let mut subject = BlockchainBridge::new(
+            &bc,
+            Box::new(blockchain_interface_mock),
+            Box::new(PersistentConfigurationMock::default().gas_price_result(1)),
+            Box::new(PendingTransactionDaoMock::default()),
+        );
+        subject.resolved_payment_sub = Some(accountant.start().recipient::<ResolvedPayment>());
+        subject
+    }
+
+    #[test]
+    fn check_pending_transactions_forgets_payments_mined_as_sent() {
+        let system = System::new("check_pending_transactions_forgets_payments_mined_as_sent");
+        let (accountant, _, accountant_recording_arc) = make_recorder();
+        let pending = make_pending("payee", SystemTime::now());
+        let still_pending = make_pending("later payee", SystemTime::now());
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .get_confirmed_transaction_count_result(Ok(U256::from(8)))
+            .get_transaction_receipt_result(Ok(Some(make_receipt(pending.payment.transaction, 1))));
+        let remove_parameters = Arc::new(Mutex::new(vec![]));
+        let mut subject = make_checking_subject(blockchain_interface_mock, accountant);
+        subject.pending_transaction_dao =
+            Box::new(PendingTransactionDaoMock::default().remove_parameters(&remove_parameters));
+        subject.pending_transactions.add(U256::from(7), pending);
+        subject
+            .pending_transactions
+            .add(U256::from(8), still_pending.clone());
+
+        subject.check_pending_transactions();
+
+        System::current().stop();
+        system.run();
+        assert!(subject.pending_transactions.get(U256::from(7)).is_none());
+        assert_eq!(
+            subject.pending_transactions.get(U256::from(8)),
+            Some(&still_pending)
+        );
+        assert_eq!(
+            *remove_parameters.lock().unwrap(),
+            vec![(None, U256::from(7))]
+        );
+        assert!(accountant_recording_arc.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn check_pending_transactions_reports_payments_none_of_whose_transactions_were_mined_as_unpaid()
+    {
+        init_test_logging();
+        let system = System::new(
+            "check_pending_transactions_reports_payments_none_of_whose_transactions_were_mined_as_unpaid",
+        );
+        let (accountant, _, accountant_recording_arc) = make_recorder();
+        let pending = make_pending("payee", SystemTime::now());
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .get_confirmed_transaction_count_result(Ok(U256::from(8)))
+            .get_transaction_receipt_result(Ok(None));
+        let mut subject = make_checking_subject(blockchain_interface_mock, accountant);
+        subject
+            .pending_transactions
+            .add(U256::from(7), pending.clone());
+
+        subject.check_pending_transactions();
+
+        System::current().stop();
+        system.run();
+        assert!(subject.pending_transactions.is_empty());
+        let accountant_recording = accountant_recording_arc.lock().unwrap();
+        assert_eq!(
+            accountant_recording.get_record::<ResolvedPayment>(0),
+            &ResolvedPayment {
+                payment: pending.payment.clone(),
+                mined_transaction: None,
+                profile_wallet_opt: None,
+            }
+        );
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: BlockchainBridge: None of the transactions sent for the payment of 100 to {} at nonce 7 were mined; recording it as unpaid",
+            pending.payment.to
+        ));
+    }
+
+    #[test]
+    fn check_pending_transactions_reports_payments_made_by_a_replacement() {
+        let system =
+            System::new("check_pending_transactions_reports_payments_made_by_a_replacement");
+        let (accountant, _, accountant_recording_arc) = make_recorder();
+        let pending = make_pending("payee", SystemTime::now());
+        let replacement = H256::from("replacement".keccak256());
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .get_confirmed_transaction_count_result(Ok(U256::from(8)))
+            .get_transaction_receipt_result(Ok(None))
+            .get_transaction_receipt_result(Ok(Some(make_receipt(replacement, 1))));
+        let receipt_parameters = blockchain_interface_mock
+            .get_transaction_receipt_parameters
+            .clone();
+        let mut subject = make_checking_subject(blockchain_interface_mock, accountant);
+        subject
+            .pending_transactions
+            .add(U256::from(7), pending.clone());
+        subject.pending_transactions.replaced(
+            U256::from(7),
+            replacement,
+            TransactionFee::Legacy {
+                gas_price: to_wei(2),
+            },
+            SystemTime::now(),
+        );
+
+        subject.check_pending_transactions();
+
+        System::current().stop();
+        system.run();
+        assert_eq!(
+            *receipt_parameters.lock().unwrap(),
+            vec![pending.payment.transaction, replacement]
+        );
+        assert!(subject.pending_transactions.is_empty());
+        let accountant_recording = accountant_recording_arc.lock().unwrap();
+        assert_eq!(
+            accountant_recording.get_record::<ResolvedPayment>(0),
+            &ResolvedPayment {
+                payment: pending.payment,
+                mined_transaction: Some(replacement),
+                profile_wallet_opt: None,
+            }
+        );
+    }
+
+    #[test]
+    fn check_pending_transactions_resolves_profile_wallet_payments_against_that_wallet() {
+        let system = System::new(
+            "check_pending_transactions_resolves_profile_wallet_payments_against_that_wallet",
+        );
+        let (accountant, _, accountant_recording_arc) = make_recorder();
+        let profile_wallet = Some(make_wallet("profile"));
+        let pending = make_pending("payee", SystemTime::now());
+        let replacement = H256::from("replacement".keccak256());
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .get_confirmed_transaction_count_result(Ok(U256::from(8)))
+            .get_transaction_receipt_result(Ok(None))
+            .get_transaction_receipt_result(Ok(Some(make_receipt(replacement, 1))));
+        let mut subject = make_checking_subject(blockchain_interface_mock, accountant);
+        subject
+            .pending_for(&profile_wallet)
+            .add(U256::from(7), pending.clone());
+        subject.pending_for(&profile_wallet).replaced(
+            U256::from(7),
+            replacement,
+            TransactionFee::Legacy {
+                gas_price: to_wei(2),
+            },
+            SystemTime::now(),
         );
-        let addr: Addr<BlockchainBridge> = subject.start();
 
-        let request = addr.send(RetrieveTransactions {
-            start_block: block_no,
-            recipient: wallet.clone(),
-        });
+        subject.check_pending_transactions();
+
         System::current().stop();
         system.run();
+        assert!(subject.pending_for(&profile_wallet).is_empty());
+        let accountant_recording = accountant_recording_arc.lock().unwrap();
+        assert_eq!(
+            accountant_recording.get_record::<ResolvedPayment>(0),
+            &ResolvedPayment {
+                payment: pending.payment,
+                mined_transaction: Some(replacement),
+                profile_wallet_opt: profile_wallet,
+            }
+        );
+    }
 
-        let retrieve_transactions_parameters = retrieve_transactions_parameters.lock().unwrap();
-        assert_eq!((block_no, wallet), retrieve_transactions_parameters[0]);
+    #[test]
+    fn check_pending_transactions_reports_cancelled_and_failed_payments_as_unpaid() {
+        let system = System::new(
+            "check_pending_transactions_reports_cancelled_and_failed_payments_as_unpaid",
+        );
+        let (accountant, _, accountant_recording_arc) = make_recorder();
+        let cancelled = make_pending("cancelled payee", SystemTime::now());
+        let failed = make_pending("failed payee", SystemTime::now());
+        let cancellation = H256::from("cancellation".keccak256());
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .get_confirmed_transaction_count_result(Ok(U256::from(9)))
+            .get_transaction_receipt_result(Ok(None))
+            .get_transaction_receipt_result(Ok(Some(make_receipt(cancellation, 1))))
+            .get_transaction_receipt_result(Ok(Some(make_receipt(failed.payment.transaction, 0))));
+        let mut subject = make_checking_subject(blockchain_interface_mock, accountant);
+        subject
+            .pending_transactions
+            .add(U256::from(7), cancelled.clone());
+        subject.pending_transactions.cancelled(
+            U256::from(7),
+            cancellation,
+            TransactionFee::Legacy {
+                gas_price: to_wei(2),
+            },
+            SystemTime::now(),
+        );
+        subject
+            .pending_transactions
+            .add(U256::from(8), failed.clone());
 
-        let result = request.wait().unwrap().unwrap();
-        assert_eq!(expected_results, result);
+        subject.check_pending_transactions();
+
+        System::current().stop();
+        system.run();
+        let accountant_recording = accountant_recording_arc.lock().unwrap();
+        assert_eq!(
+            accountant_recording.get_record::<ResolvedPayment>(0),
+            &ResolvedPayment {
+                payment: cancelled.payment,
+                mined_transaction: None,
+                profile_wallet_opt: None,
+            }
+        );
+        assert_eq!(
+            accountant_recording.get_record::<ResolvedPayment>(1),
+            &ResolvedPayment {
+                payment: failed.payment,
+                mined_transaction: None,
+                profile_wallet_opt: None,
+            }
+        );
     }
 
     #[test]
-    fn report_accounts_payable_sends_transactions_to_blockchain_interface() {
+    fn check_pending_transactions_tries_again_when_a_receipt_is_unavailable() {
+        init_test_logging();
         let system =
-            System::new("report_accounts_payable_sends_transactions_to_blockchain_interface");
+            System::new("check_pending_transactions_tries_again_when_a_receipt_is_unavailable");
+        let (accountant, _, accountant_recording_arc) = make_recorder();
+        let pending = make_pending("payee", SystemTime::now());
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .get_confirmed_transaction_count_result(Ok(U256::from(8)))
+            .get_transaction_receipt_result(Err(BlockchainError::QueryFailed));
+        let save_parameters = Arc::new(Mutex::new(vec![]));
+        let mut subject = make_checking_subject(blockchain_interface_mock, accountant);
+        subject.pending_transaction_dao =
+            Box::new(PendingTransactionDaoMock::default().save_parameters(&save_parameters));
+        subject
+            .pending_transactions
+            .add(U256::from(7), pending.clone());
+
+        subject.check_pending_transactions();
+
+        System::current().stop();
+        system.run();
+        assert_eq!(
+            subject.pending_transactions.get(U256::from(7)),
+            Some(&pending)
+        );
+        assert_eq!(
+            *save_parameters.lock().unwrap(),
+            vec![(None, U256::from(7), pending.clone())]
+        );
+        assert!(accountant_recording_arc.lock().unwrap().is_empty());
+        TestLogHandler::new().exists_log_containing(
+            "WARN: BlockchainBridge: Could not look up the outcome of the payment at nonce 7: Blockchain QueryFailed.; will try again",
+        );
+    }
 
+    #[test]
+    fn check_pending_transactions_speeds_up_stuck_payments() {
+        let system = System::new("check_pending_transactions_speeds_up_stuck_payments");
+        let (accountant, _, _) = make_recorder();
+        let now = SystemTime::now();
+        let stuck = make_pending("stuck payee", now - Duration::from_secs(601));
+        let recent = make_pending("recent payee", now);
+        let replacement = H256::from("replacement".keccak256());
         let blockchain_interface_mock = BlockchainInterfaceMock::default()
-            .get_transaction_count_result(Ok(U256::from(1)))
-            .get_transaction_count_result(Ok(U256::from(2)))
-            .send_transaction_result(Ok(H256::from("sometransactionhash".keccak256())))
-            .send_transaction_result(Ok(H256::from("someothertransactionhash".keccak256())))
-            .contract_address_result(contract_address(DEFAULT_CHAIN_ID));
+            .get_confirmed_transaction_count_result(Ok(U256::from(7)))
+            .send_transaction_result(Ok(replacement));
         let send_parameters = blockchain_interface_mock
             .send_transaction_parameters
             .clone();
-        let transaction_count_parameters = blockchain_interface_mock
-            .get_transaction_count_parameters
-            .clone();
-        let expected_gas_price = 5u64;
-        let persistent_configuration_mock =
-            PersistentConfigurationMock::default().gas_price_result(expected_gas_price);
+        let save_parameters = Arc::new(Mutex::new(vec![]));
+        let mut subject = make_checking_subject(blockchain_interface_mock, accountant);
+        subject.pending_transaction_dao =
+            Box::new(PendingTransactionDaoMock::default().save_parameters(&save_parameters));
+        subject
+            .pending_transactions
+            .add(U256::from(7), stuck.clone());
+        subject.pending_transactions.add(U256::from(8), recent);
 
-        let consuming_wallet = make_paying_wallet(b"somewallet");
-        let subject = BlockchainBridge::new(
-            &bc_from_wallet(Some(consuming_wallet.clone())),
-            Box::new(blockchain_interface_mock),
-            Box::new(persistent_configuration_mock),
-        );
-        let addr: Addr<BlockchainBridge> = subject.start();
+        subject.check_pending_transactions();
 
-        let request = addr.send(ReportAccountsPayable {
-            accounts: vec![
-                PayableAccount {
-                    wallet: make_wallet("blah"),
-                    balance: 42,
-                    last_paid_timestamp: SystemTime::now(),
-                    pending_payment_transaction: None,
-                },
-                PayableAccount {
-                    wallet: make_wallet("foo"),
-                    balance: 21,
-                    last_paid_timestamp: SystemTime::now(),
-                    pending_payment_transaction: None,
-                },
-            ],
-        });
         System::current().stop();
         system.run();
-
+        let expected_fee = TransactionFee::Legacy {
+            gas_price: to_wei(1) + to_wei(1) / 8,
+        };
         assert_eq!(
-            send_parameters.lock().unwrap()[0],
-            (
-                consuming_wallet.clone(),
-                make_wallet("blah"),
-                42,
-                U256::from(1),
-                expected_gas_price
-            )
+            *send_parameters.lock().unwrap(),
+            vec![(
+                make_wallet("consumer"),
+                stuck.payment.to.clone(),
+                100,
+                U256::from(7),
+                expected_fee
+            )]
         );
+        let pending = subject.pending_transactions.get(U256::from(7)).unwrap();
+        assert_eq!(pending.replacements, vec![replacement]);
+        assert_eq!(pending.fee, expected_fee);
         assert_eq!(
-            send_parameters.lock().unwrap()[1],
-            (
-                consuming_wallet.clone(),
-                make_wallet("foo"),
-                21,
-                U256::from(2),
-                expected_gas_price
-            )
+            *save_parameters.lock().unwrap(),
+            vec![(None, U256::from(7), pending.clone())]
         );
+    }
 
-        let result = request.wait().unwrap().unwrap();
-        let mut expected_payment_0 = Payment::new(
-            make_wallet("blah"),
-            42,
-            H256::from("sometransactionhash".keccak256()),
+    #[test]
+    fn blockchain_bridge_goes_on_watching_the_payments_pending_when_it_stopped() {
+        let profile_wallet = Some(make_wallet("profile"));
+        let own = make_pending("payee", SystemTime::now());
+        let profile = make_pending("other payee", SystemTime::now());
+        let pending_transaction_dao = PendingTransactionDaoMock::default()
+            .pending_transactions_result(vec![
+                (None, U256::from(7), own.clone()),
+                (profile_wallet.clone(), U256::from(3), profile.clone()),
+            ]);
+
+        let mut subject = BlockchainBridge::new(
+            &bc_from_wallet(Some(make_wallet("consumer"))),
+            stub_bi(),
+            Box::new(PersistentConfigurationMock::default()),
+            Box::new(pending_transaction_dao),
         );
 
-        if let Ok(zero) = result.clone().get(0).unwrap().clone() {
-            assert!(
-                zero.timestamp
-                    <= expected_payment_0
-                        .timestamp
-                        .checked_add(Duration::from_secs(2))
-                        .unwrap()
-            );
-            assert!(
-                zero.timestamp
-                    >= expected_payment_0
-                        .timestamp
-                        .checked_sub(Duration::from_secs(2))
-                        .unwrap()
-            );
-            expected_payment_0.timestamp = zero.timestamp
-        }
-
-        let mut expected_payment_1 = Payment::new(
-            make_wallet("foo"),
-            21,
-            H256::from("someothertransactionhash".keccak256()),
+        assert_eq!(subject.pending_transactions.get(U256::from(7)), Some(&own));
+        assert_eq!(
+            subject.pending_for(&profile_wallet).get(U256::from(3)),
+            Some(&profile)
         );
+    }
 
-        if let Ok(one) = result.clone().get(1).unwrap().clone() {
-            assert!(
-                one.timestamp
-                    <= expected_payment_1
-                        .timestamp
-                        .checked_add(Duration::from_secs(2))
-                        .unwrap()
+    #[test]
+    fn blockchain_bridge_without_a_blockchain_service_complains_about_pending_payments_it_cant_check(
+    ) {
+        init_test_logging();
+        let pending_transaction_dao = PendingTransactionDaoMock::default()
+            .pending_transactions_result(vec![(
+                None,
+                U256::from(7),
+                make_pending("payee", SystemTime::now()),
+            )]);
+
+        thread::spawn(move || {
+            let mut subject = BlockchainBridge::new(
+                &bc_from_wallet(Some(make_wallet("consumer"))),
+                Box::new(BlockchainInterfaceClandestine::new(DEFAULT_CHAIN_ID)),
+                Box::new(PersistentConfigurationMock::default()),
+                Box::new(pending_transaction_dao),
             );
-            assert!(
-                one.timestamp
-                    >= expected_payment_1
-                        .timestamp
-                        .checked_sub(Duration::from_secs(2))
-                        .unwrap()
+            subject.pending_transaction_check_interval = Duration::from_millis(20);
+
+            let system = System::new(
+                "blockchain_bridge_without_a_blockchain_service_complains_about_pending_payments_it_cant_check",
             );
-            expected_payment_1.timestamp = one.timestamp
-        }
+            let addr = subject.start();
 
-        assert_eq!(result[1], Ok(expected_payment_1));
+            addr.try_send(BindMessage {
+                peer_actors: peer_actors_builder().build(),
+            })
+            .unwrap();
 
-        assert_eq!(
-            transaction_count_parameters.lock().unwrap()[0],
-            consuming_wallet.clone(),
-        );
-        assert_eq!(
-            transaction_count_parameters.lock().unwrap()[1],
-            consuming_wallet.clone(),
+            system.run();
+        });
+
+        let tlh = TestLogHandler::new();
+        tlh.await_log_containing(
+            "WARN: BlockchainBridge: Could not check on pending payments: Blockchain QueryFailed.",
+            1000,
         );
+        tlh.exists_log_containing(&format!(
+            "ERROR: BlockchainInterface: Could not get confirmed transaction count for {:?} since blockchain_service_url was not specified",
+            make_wallet("consumer")
+        ));
     }
 
     #[test]
-    fn report_accounts_payable_returns_error_for_blockchain_error() {
-        let system = System::new("report_accounts_payable_returns_error_for_blockchain_error");
-
+    fn check_pending_transactions_cancels_payments_that_stay_stuck() {
+        let system = System::new("check_pending_transactions_cancels_payments_that_stay_stuck");
+        let (accountant, _, _) = make_recorder();
+        let long_ago = SystemTime::now() - Duration::from_secs(3600);
+        let cancellation = H256::from("cancellation".keccak256());
         let blockchain_interface_mock = BlockchainInterfaceMock::default()
-            .get_transaction_count_result(Ok(web3::types::U256::from(1)))
-            .send_transaction_result(Err(BlockchainError::TransactionFailed(String::from(
-                "mock payment failure",
-            ))));
-
-        let transaction_count_parameters = blockchain_interface_mock
-            .get_transaction_count_parameters
+            .get_confirmed_transaction_count_result(Ok(U256::from(7)))
+            .cancel_transaction_result(Ok(cancellation));
+        let cancel_parameters = blockchain_interface_mock
+            .cancel_transaction_parameters
             .clone();
+        let mut subject = make_checking_subject(blockchain_interface_mock, accountant);
+        subject
+            .pending_transactions
+            .add(U256::from(7), make_pending("stuck payee", long_ago));
+        (0..MAX_REPLACEMENTS).for_each(|n| {
+            subject.pending_transactions.replaced(
+                U256::from(7),
+                H256::from(format!("replacement {}", n).keccak256()),
+                TransactionFee::Legacy {
+                    gas_price: to_wei(2),
+                },
+                long_ago,
+            )
+        });
 
-        let consuming_wallet = make_wallet("somewallet");
+        subject.check_pending_transactions();
 
-        let persistent_configuration_mock =
-            PersistentConfigurationMock::new().gas_price_result(3u64);
-        let subject = BlockchainBridge::new(
-            &bc_from_wallet(Some(consuming_wallet.clone())),
-            Box::new(blockchain_interface_mock),
-            Box::new(persistent_configuration_mock),
+        System::current().stop();
+        system.run();
+        let expected_fee = TransactionFee::Legacy {
+            gas_price: to_wei(2) + to_wei(2) / 8,
+        };
+        assert_eq!(
+            *cancel_parameters.lock().unwrap(),
+            vec![(make_wallet("consumer"), U256::from(7), expected_fee)]
         );
-        let addr: Addr<BlockchainBridge> = subject.start();
+        let pending = subject.pending_transactions.get(U256::from(7)).unwrap();
+        assert_eq!(pending.cancellation, Some(cancellation));
+        assert_eq!(pending.replacements.len(), MAX_REPLACEMENTS);
+    }
 
-        let request = addr.send(ReportAccountsPayable {
-            accounts: vec![PayableAccount {
-                wallet: make_wallet("blah"),
-                balance: 42,
-                last_paid_timestamp: SystemTime::now(),
-                pending_payment_transaction: None,
-            }],
+    #[test]
+    fn check_pending_transactions_charges_the_fee_budget_only_for_outbidding() {
+        let system =
+            System::new("check_pending_transactions_charges_the_fee_budget_only_for_outbidding");
+        let (accountant, _, _) = make_recorder();
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .get_confirmed_transaction_count_result(Ok(U256::from(7)))
+            .send_transaction_result(Ok(H256::from("replacement".keccak256())));
+        let set_fee_spending_params_arc = Arc::new(Mutex::new(vec![]));
+        let mut subject = make_checking_subject(blockchain_interface_mock, accountant);
+        subject.fee_budget = Some(FeeBudget {
+            gwei: 10_000,
+            period: FeeBudgetPeriod::Weekly,
         });
+        subject.persistent_config = Box::new(
+            PersistentConfigurationMock::default()
+                .gas_price_result(1)
+                .set_fee_spending_params(&set_fee_spending_params_arc),
+        );
+        subject.pending_transactions.add(
+            U256::from(7),
+            make_pending("stuck payee", SystemTime::now() - Duration::from_secs(601)),
+        );
+
+        subject.check_pending_transactions();
 
         System::current().stop();
         system.run();
+        // An eighth of a Gwei more for at most 59,624 gas
+        let set_fee_spending_params = set_fee_spending_params_arc.lock().unwrap();
+        assert_eq!(set_fee_spending_params.len(), 1);
+        assert_eq!(set_fee_spending_params[0].gwei, 7_453);
+    }
 
-        let result = &request.wait().unwrap().unwrap();
-
-        assert_eq!(
-            result,
-            &vec![Err(BlockchainError::TransactionFailed(String::from(
-                "mock payment failure"
-            )))]
+    #[test]
+    fn check_pending_transactions_leaves_stuck_payments_alone_when_outbidding_exceeds_the_fee_budget(
+    ) {
+        init_test_logging();
+        let system = System::new(
+            "check_pending_transactions_leaves_stuck_payments_alone_when_outbidding_exceeds_the_fee_budget",
         );
-        let actual_wallet = transaction_count_parameters.lock().unwrap().remove(0);
+        let (accountant, _, _) = make_recorder();
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .get_confirmed_transaction_count_result(Ok(U256::from(7)));
+        let send_parameters = blockchain_interface_mock
+            .send_transaction_parameters
+            .clone();
+        let mut subject = make_checking_subject(blockchain_interface_mock, accountant);
+        subject.fee_budget = Some(FeeBudget {
+            gwei: 7_000,
+            period: FeeBudgetPeriod::Weekly,
+        });
+        let pending = make_pending("stuck payee", SystemTime::now() - Duration::from_secs(601));
+        subject
+            .pending_transactions
+            .add(U256::from(7), pending.clone());
 
-        assert_eq!(actual_wallet, consuming_wallet);
+        subject.check_pending_transactions();
+
+        System::current().stop();
+        system.run();
+        assert!(send_parameters.lock().unwrap().is_empty());
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: BlockchainBridge: Payment of 100 to {} at nonce 7 is stuck, but can't be resent now: Blockchain FeeBudgetExceeded {{ projected_gwei: 7453, remaining_gwei: 7000 }}.",
+            pending.payment.to
+        ));
     }
 
     #[test]
-    fn report_accounts_payable_returns_error_when_there_is_no_consuming_wallet_configured() {
-        let system = System::new("report_accounts_payable_returns_error_for_blockchain_error");
-
-        let blockchain_interface_mock = BlockchainInterfaceMock::default();
-        let persistent_configuration_mock = PersistentConfigurationMock::default();
-
-        let subject = BlockchainBridge::new(
-            &BootstrapperConfig::new(),
-            Box::new(blockchain_interface_mock),
-            Box::new(persistent_configuration_mock),
+    fn check_pending_transactions_leaves_stuck_payments_alone_when_outbidding_exceeds_the_cap() {
+        init_test_logging();
+        let system = System::new(
+            "check_pending_transactions_leaves_stuck_payments_alone_when_outbidding_exceeds_the_cap",
         );
-        let addr: Addr<BlockchainBridge> = subject.start();
+        let (accountant, _, _) = make_recorder();
+        let long_ago = SystemTime::now() - Duration::from_secs(3600);
+        let blockchain_interface_mock = BlockchainInterfaceMock::default()
+            .get_confirmed_transaction_count_result(Ok(U256::from(7)));
+        let send_parameters = blockchain_interface_mock
+            .send_transaction_parameters
+            .clone();
+        let mut subject = make_checking_subject(blockchain_interface_mock, accountant);
+        let mut pending = make_pending("stuck payee", long_ago);
+        pending.fee = TransactionFee::Legacy {
+            gas_price: to_wei(10),
+        };
+        subject
+            .pending_transactions
+            .add(U256::from(7), pending.clone());
 
-        let request = addr.send(ReportAccountsPayable {
-            accounts: vec![PayableAccount {
-                wallet: make_wallet("blah"),
-                balance: 42,
-                last_paid_timestamp: SystemTime::now(),
-                pending_payment_transaction: None,
-            }],
-        });
+        subject.check_pending_transactions();
 
         System::current().stop();
         system.run();
-
-        let result = &request.wait().unwrap();
-
-        assert_eq!(result, &Err("No consuming wallet specified".to_string()));
+        assert!(send_parameters.lock().unwrap().is_empty());
+        assert_eq!(
+            subject.pending_transactions.get(U256::from(7)),
+            Some(&pending)
+        );
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: BlockchainBridge: Payment of 100 to {} at nonce 7 is stuck, but can't be resent now: Blockchain FeeCapExceeded",
+            pending.payment.to
+        ));
     }
 
     fn bc_from_wallet(consuming_wallet: Option<Wallet>) -> BootstrapperConfig {
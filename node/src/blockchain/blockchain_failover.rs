@@ -0,0 +1,487 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::blockchain::blockchain_interface::{
+    Balance, BlockchainError, BlockchainInterface, BlockchainResult, Nonce, TransactionFee,
+    Transactions,
+};
+use crate::sub_lib::logger::Logger;
+use crate::sub_lib::wallet::Wallet;
+use std::cell::Cell;
+use std::time::Duration;
+use web3::types::{Address, TransactionReceipt, H256, U256};
+
+pub const DEFAULT_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+pub struct BlockchainEndpoint {
+    url: String,
+    interface: Box<dyn BlockchainInterface>,
+    healthy: Cell<bool>,
+}
+
+impl BlockchainEndpoint {
+    pub fn new(url: &str, interface: Box<dyn BlockchainInterface>) -> Self {
+        BlockchainEndpoint {
+            url: url.to_string(),
+            interface,
+            healthy: Cell::new(true),
+        }
+    }
+}
+
+// Endpoints are listed in order of preference. When the active one can't be reached, requests
+// move on to the next one round-robin; the periodic health check moves them back to the most
+// preferred endpoint once it recovers.
+pub struct BlockchainInterfaceFailover {
+    endpoints: Vec<BlockchainEndpoint>,
+    active: Cell<usize>,
+    logger: Logger,
+}
+
+impl BlockchainInterface for BlockchainInterfaceFailover {
+    fn contract_address(&self) -> Address {
+        self.active_endpoint().interface.contract_address()
+    }
+
+    fn retrieve_transactions(
+        &self,
+        start_block: u64,
+        end_block: u64,
+        recipient: &Wallet,
+    ) -> Transactions {
+        self.attempt("retrieve transactions", |interface| {
+            interface.retrieve_transactions(start_block, end_block, recipient)
+        })
+    }
+
+    // A failed transaction may have been broadcast anyway, so it's never resent elsewhere.
+    fn send_transaction(
+        &self,
+        consuming_wallet: &Wallet,
+        recipient: &Wallet,
+        amount: u64,
+        nonce: U256,
+        fee: TransactionFee,
+    ) -> BlockchainResult<H256> {
+        self.active_endpoint().interface.send_transaction(
+            consuming_wallet,
+            recipient,
+            amount,
+            nonce,
+            fee,
+        )
+    }
+
+    fn get_eth_balance(&self, address: &Wallet) -> Balance {
+        self.attempt("get eth balance", |interface| {
+            interface.get_eth_balance(address)
+        })
+    }
+
+    fn get_token_balance(&self, address: &Wallet) -> Balance {
+        self.attempt("get token balance", |interface| {
+            interface.get_token_balance(address)
+        })
+    }
+
+    fn get_transaction_count(&self, address: &Wallet) -> Nonce {
+        self.attempt("get transaction count", |interface| {
+            interface.get_transaction_count(address)
+        })
+    }
+
+    fn get_confirmed_transaction_count(&self, address: &Wallet) -> Nonce {
+        self.attempt("get confirmed transaction count", |interface| {
+            interface.get_confirmed_transaction_count(address)
+        })
+    }
+
+    fn get_transaction_receipt(&self, hash: H256) -> BlockchainResult<Option<TransactionReceipt>> {
+        self.attempt("get transaction receipt", |interface| {
+            interface.get_transaction_receipt(hash)
+        })
+    }
+
+    fn cancel_transaction(
+        &self,
+        consuming_wallet: &Wallet,
+        nonce: U256,
+        fee: TransactionFee,
+    ) -> BlockchainResult<H256> {
+        self.active_endpoint()
+            .interface
+            .cancel_transaction(consuming_wallet, nonce, fee)
+    }
+
+    fn get_block_number(&self) -> BlockchainResult<u64> {
+        self.attempt("get block number", |interface| interface.get_block_number())
+    }
+
+    fn get_base_fee_per_gas(&self) -> BlockchainResult<Option<U256>> {
+        self.attempt("get base fee", |interface| interface.get_base_fee_per_gas())
+    }
+
+    fn check_health(&self) {
+        self.endpoints.iter().for_each(|endpoint| {
+            let healthy = endpoint.interface.get_block_number().is_ok();
+            if healthy != endpoint.healthy.get() {
+                if healthy {
+                    info!(
+                        self.logger,
+                        "Blockchain service {} has recovered", endpoint.url
+                    )
+                } else {
+                    warning!(
+                        self.logger,
+                        "Blockchain service {} is unreachable",
+                        endpoint.url
+                    )
+                }
+            }
+            endpoint.healthy.set(healthy);
+        });
+        if let Some(preferred) = self
+            .endpoints
+            .iter()
+            .position(|endpoint| endpoint.healthy.get())
+        {
+            if preferred != self.active.get() {
+                info!(
+                    self.logger,
+                    "Switching from blockchain service {} to {}",
+                    self.active_endpoint().url,
+                    self.endpoints[preferred].url
+                );
+                self.active.set(preferred);
+            }
+        }
+    }
+
+    fn active_service_url(&self) -> Option<String> {
+        Some(self.active_endpoint().url.clone())
+    }
+}
+
+impl BlockchainInterfaceFailover {
+    pub fn new(endpoints: Vec<BlockchainEndpoint>) -> Self {
+        if endpoints.is_empty() {
+            panic!("At least one blockchain service is required");
+        }
+        BlockchainInterfaceFailover {
+            endpoints,
+            active: Cell::new(0),
+            logger: Logger::new("BlockchainInterface"),
+        }
+    }
+
+    fn active_endpoint(&self) -> &BlockchainEndpoint {
+        &self.endpoints[self.active.get()]
+    }
+
+    fn attempt<T, F>(&self, description: &str, request: F) -> BlockchainResult<T>
+    where
+        F: Fn(&dyn BlockchainInterface) -> BlockchainResult<T>,
+    {
+        let first = self.active.get();
+        let count = self.endpoints.len();
+        let mut last_error = BlockchainError::QueryFailed;
+        for offset in 0..count {
+            let index = (first + offset) % count;
+            let endpoint = &self.endpoints[index];
+            match request(endpoint.interface.as_ref()) {
+                Err(e @ BlockchainError::QueryFailed)
+                | Err(e @ BlockchainError::InvalidResponse) => {
+                    endpoint.healthy.set(false);
+                    let next = &self.endpoints[(index + 1) % count];
+                    if count > 1 {
+                        warning!(
+                            self.logger,
+                            "Could not {} from blockchain service {}: {}; failing over to {}",
+                            description,
+                            endpoint.url,
+                            e,
+                            next.url
+                        );
+                    }
+                    last_error = e;
+                }
+                result => {
+                    if result.is_ok() {
+                        endpoint.healthy.set(true);
+                    }
+                    self.active.set(index);
+                    return result;
+                }
+            }
+        }
+        self.active.set((first + 1) % count);
+        Err(last_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::logging::{init_test_logging, TestLogHandler};
+    use crate::test_utils::make_wallet;
+    use std::cell::RefCell;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct EndpointMock {
+        get_block_number_results: RefCell<Vec<BlockchainResult<u64>>>,
+        send_transaction_results: RefCell<Vec<BlockchainResult<H256>>>,
+        calls: Arc<Mutex<u32>>,
+    }
+
+    impl EndpointMock {
+        fn get_block_number_result(self, result: BlockchainResult<u64>) -> Self {
+            self.get_block_number_results.borrow_mut().push(result);
+            self
+        }
+
+        fn send_transaction_result(self, result: BlockchainResult<H256>) -> Self {
+            self.send_transaction_results.borrow_mut().push(result);
+            self
+        }
+    }
+
+    impl BlockchainInterface for EndpointMock {
+        fn contract_address(&self) -> Address {
+            unimplemented!()
+        }
+
+        fn retrieve_transactions(
+            &self,
+            _start_block: u64,
+            _end_block: u64,
+            _recipient: &Wallet,
+        ) -> Transactions {
+            unimplemented!()
+        }
+
+        fn send_transaction(
+            &self,
+            _consuming_wallet: &Wallet,
+            _recipient: &Wallet,
+            _amount: u64,
+            _nonce: U256,
+            _fee: TransactionFee,
+        ) -> BlockchainResult<H256> {
+            *self.calls.lock().unwrap() += 1;
+            self.send_transaction_results.borrow_mut().remove(0)
+        }
+
+        fn get_eth_balance(&self, _address: &Wallet) -> Balance {
+            unimplemented!()
+        }
+
+        fn get_token_balance(&self, _address: &Wallet) -> Balance {
+            unimplemented!()
+        }
+
+        fn get_transaction_count(&self, _address: &Wallet) -> Nonce {
+            unimplemented!()
+        }
+
+        fn get_confirmed_transaction_count(&self, _address: &Wallet) -> Nonce {
+            unimplemented!()
+        }
+
+        fn get_transaction_receipt(
+            &self,
+            _hash: H256,
+        ) -> BlockchainResult<Option<TransactionReceipt>> {
+            unimplemented!()
+        }
+
+        fn cancel_transaction(
+            &self,
+            _consuming_wallet: &Wallet,
+            _nonce: U256,
+            _fee: TransactionFee,
+        ) -> BlockchainResult<H256> {
+            unimplemented!()
+        }
+
+        fn get_block_number(&self) -> BlockchainResult<u64> {
+            *self.calls.lock().unwrap() += 1;
+            self.get_block_number_results.borrow_mut().remove(0)
+        }
+
+        fn get_base_fee_per_gas(&self) -> BlockchainResult<Option<U256>> {
+            unimplemented!()
+        }
+    }
+
+    fn make_subject(mocks: Vec<EndpointMock>) -> BlockchainInterfaceFailover {
+        BlockchainInterfaceFailover::new(
+            mocks
+                .into_iter()
+                .enumerate()
+                .map(|(index, mock)| {
+                    BlockchainEndpoint::new(
+                        &format!("http://node{}:8545", index),
+                        Box::new(mock) as Box<dyn BlockchainInterface>,
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    #[should_panic(expected = "At least one blockchain service is required")]
+    fn failover_requires_an_endpoint() {
+        BlockchainInterfaceFailover::new(vec![]);
+    }
+
+    #[test]
+    fn requests_go_to_the_first_endpoint_while_it_works() {
+        let second = EndpointMock::default();
+        let second_calls = second.calls.clone();
+        let subject = make_subject(vec![
+            EndpointMock::default()
+                .get_block_number_result(Ok(10))
+                .get_block_number_result(Ok(11)),
+            second,
+        ]);
+
+        assert_eq!(subject.get_block_number(), Ok(10));
+        assert_eq!(subject.get_block_number(), Ok(11));
+        assert_eq!(*second_calls.lock().unwrap(), 0);
+        assert_eq!(
+            subject.active_service_url(),
+            Some("http://node0:8545".to_string())
+        );
+    }
+
+    #[test]
+    fn unreachable_endpoint_fails_over_to_the_next_one() {
+        init_test_logging();
+        let subject = make_subject(vec![
+            EndpointMock::default().get_block_number_result(Err(BlockchainError::QueryFailed)),
+            EndpointMock::default()
+                .get_block_number_result(Ok(20))
+                .get_block_number_result(Ok(21)),
+        ]);
+
+        assert_eq!(subject.get_block_number(), Ok(20));
+        assert_eq!(subject.get_block_number(), Ok(21));
+        assert_eq!(
+            subject.active_service_url(),
+            Some("http://node1:8545".to_string())
+        );
+        TestLogHandler::new().exists_log_containing(
+            "WARN: BlockchainInterface: Could not get block number from blockchain service http://node0:8545: Blockchain QueryFailed.; failing over to http://node1:8545",
+        );
+    }
+
+    #[test]
+    fn failover_wraps_around_to_earlier_endpoints() {
+        let subject = make_subject(vec![
+            EndpointMock::default()
+                .get_block_number_result(Err(BlockchainError::QueryFailed))
+                .get_block_number_result(Ok(30)),
+            EndpointMock::default()
+                .get_block_number_result(Ok(25))
+                .get_block_number_result(Err(BlockchainError::InvalidResponse)),
+        ]);
+        subject.get_block_number().unwrap();
+
+        assert_eq!(subject.get_block_number(), Ok(30));
+        assert_eq!(
+            subject.active_service_url(),
+            Some("http://node0:8545".to_string())
+        );
+    }
+
+    #[test]
+    fn failing_everywhere_returns_the_last_error_and_moves_on() {
+        let subject = make_subject(vec![
+            EndpointMock::default().get_block_number_result(Err(BlockchainError::QueryFailed)),
+            EndpointMock::default().get_block_number_result(Err(BlockchainError::InvalidResponse)),
+        ]);
+
+        assert_eq!(
+            subject.get_block_number(),
+            Err(BlockchainError::InvalidResponse)
+        );
+        assert_eq!(
+            subject.active_service_url(),
+            Some("http://node1:8545".to_string())
+        );
+    }
+
+    #[test]
+    fn failed_transactions_are_not_resent_to_another_endpoint() {
+        let second = EndpointMock::default();
+        let second_calls = second.calls.clone();
+        let subject = make_subject(vec![
+            EndpointMock::default().send_transaction_result(Err(
+                BlockchainError::TransactionFailed("nope".to_string()),
+            )),
+            second,
+        ]);
+
+        let result = subject.send_transaction(
+            &make_wallet("consumer"),
+            &make_wallet("creditor"),
+            100,
+            U256::from(1),
+            TransactionFee::Legacy {
+                gas_price: U256::from(1),
+            },
+        );
+
+        assert_eq!(
+            result,
+            Err(BlockchainError::TransactionFailed("nope".to_string()))
+        );
+        assert_eq!(*second_calls.lock().unwrap(), 0);
+    }
+
+    #[test]
+    fn health_check_returns_to_the_preferred_endpoint_once_it_recovers() {
+        init_test_logging();
+        let subject = make_subject(vec![
+            EndpointMock::default()
+                .get_block_number_result(Err(BlockchainError::QueryFailed))
+                .get_block_number_result(Ok(40)),
+            EndpointMock::default()
+                .get_block_number_result(Ok(40))
+                .get_block_number_result(Ok(40)),
+        ]);
+        subject.get_block_number().unwrap();
+
+        subject.check_health();
+
+        assert_eq!(
+            subject.active_service_url(),
+            Some("http://node0:8545".to_string())
+        );
+        let tlh = TestLogHandler::new();
+        tlh.exists_log_containing(
+            "INFO: BlockchainInterface: Blockchain service http://node0:8545 has recovered",
+        );
+        tlh.exists_log_containing(
+            "INFO: BlockchainInterface: Switching from blockchain service http://node1:8545 to http://node0:8545",
+        );
+    }
+
+    #[test]
+    fn health_check_moves_off_an_endpoint_that_has_gone_down() {
+        init_test_logging();
+        let subject = make_subject(vec![
+            EndpointMock::default().get_block_number_result(Err(BlockchainError::QueryFailed)),
+            EndpointMock::default().get_block_number_result(Ok(50)),
+        ]);
+
+        subject.check_health();
+
+        assert_eq!(
+            subject.active_service_url(),
+            Some("http://node1:8545".to_string())
+        );
+        TestLogHandler::new().exists_log_containing(
+            "WARN: BlockchainInterface: Blockchain service http://node0:8545 is unreachable",
+        );
+    }
+}
@@ -1,19 +1,24 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
-use crate::blockchain::raw_transaction::RawTransaction;
+use crate::blockchain::raw_transaction::{Eip1559Transaction, RawTransaction};
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::wallet::Wallet;
 use actix::Message;
 use futures::{future, Future};
+use lazy_static::lazy_static;
+use serde_json::json;
+use std::cmp::max;
 use std::convert::{From, TryFrom, TryInto};
 use std::fmt;
 use std::fmt::{Debug, Display, Formatter};
 use web3::contract::{Contract, Options};
 use web3::transports::EventLoopHandle;
-use web3::types::{Address, BlockNumber, Bytes, FilterBuilder, Log, H256, U256};
+use web3::types::{
+    Address, BlockNumber, Bytes, FilterBuilder, Log, TransactionReceipt, H256, U256,
+};
 use web3::{Transport, Web3};
 
-//Test 
+//Test
 pub const TESTNET_CONTRACT_ADDRESS: Address = Address {
     0: [
         0x28, 0xf3, 0xb3, 0xc8, 0x50, 0xff, 0x22, 0xa7, 0x43, 0x4c, 0x23, 0x58, 0x2a, 0x17, 0xa5,
@@ -37,64 +42,128 @@ pub const MAINNET_CONTRACT_ADDRESS: Address = Address {
     ],
 };
 
-const CONTRACTS: [Address; 4] = [
-    Address { 0: [0u8; 20] },
-    MAINNET_CONTRACT_ADDRESS,
-    MULTINODE_TESTNET_CONTRACT_ADDRESS,
-    TESTNET_CONTRACT_ADDRESS,
-];
+//xDai
+pub const XDAI_CONTRACT_ADDRESS: Address = Address {
+    0: [
+        0x28, 0xf3, 0xb3, 0xc8, 0x50, 0xff, 0x22, 0xa7, 0x43, 0x4c, 0x23, 0x58, 0x2a, 0x17, 0xa5,
+        0xa4, 0x43, 0xa8, 0xdc, 0x64,
+    ],
+};
 
 pub const MAINNET_CONTRACT_CREATION_BLOCK: u64 = 6_543_790;
 pub const ROPSTEN_CONTRACT_CREATION_BLOCK: u64 = 6_105_370;
+pub const XDAI_CONTRACT_CREATION_BLOCK: u64 = 16_011_740;
+
+pub const XDAI_BLOCKCHAIN_SERVICE_URL: &str = "https://rpc.gnosischain.com";
+
+// Gas a token transfer() costs before its call data is paid for
+const TRANSFER_GAS_BASE: u64 = 55_000;
+// A plain transfer of nothing, as cheap as a transaction can be
+const CANCELLATION_GAS_LIMIT: u64 = 21_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChainRecord {
+    pub chain_id: u8,
+    pub name: &'static str,
+    pub contract: Address,
+    pub contract_creation_block: u64,
+    // Used when no --blockchain-service-url is given; chains without one run clandestinely
+    pub default_blockchain_service_url: Option<&'static str>,
+    pub transfer_gas_base: u64,
+    // Priority fees for the slow, normal and fast fee strategies
+    pub priority_fees_gwei: [u64; 3],
+}
 
-pub const CONTRACT_CREATION_BLOCK: [u64; 4] = [
-    0,
-    MAINNET_CONTRACT_CREATION_BLOCK,
-    0,
-    ROPSTEN_CONTRACT_CREATION_BLOCK,
+pub const CHAINS: [ChainRecord; 4] = [
+    ChainRecord {
+        chain_id: 1,
+        name: "mainnet",
+        contract: MAINNET_CONTRACT_ADDRESS,
+        contract_creation_block: MAINNET_CONTRACT_CREATION_BLOCK,
+        default_blockchain_service_url: None,
+        transfer_gas_base: TRANSFER_GAS_BASE,
+        priority_fees_gwei: [1, 2, 3],
+    },
+    ChainRecord {
+        chain_id: 2,
+        name: "dev",
+        contract: MULTINODE_TESTNET_CONTRACT_ADDRESS,
+        contract_creation_block: 0,
+        default_blockchain_service_url: None,
+        transfer_gas_base: TRANSFER_GAS_BASE,
+        priority_fees_gwei: [1, 2, 3],
+    },
+    ChainRecord {
+        chain_id: 3,
+        name: "ropsten",
+        contract: TESTNET_CONTRACT_ADDRESS,
+        contract_creation_block: ROPSTEN_CONTRACT_CREATION_BLOCK,
+        default_blockchain_service_url: None,
+        transfer_gas_base: TRANSFER_GAS_BASE,
+        priority_fees_gwei: [1, 2, 3],
+    },
+    // The chain id has to stay small enough for EIP-155 signatures to fit v into a byte
+    ChainRecord {
+        chain_id: 100,
+        name: "xdai",
+        contract: XDAI_CONTRACT_ADDRESS,
+        contract_creation_block: XDAI_CONTRACT_CREATION_BLOCK,
+        default_blockchain_service_url: Some(XDAI_BLOCKCHAIN_SERVICE_URL),
+        transfer_gas_base: TRANSFER_GAS_BASE,
+        priority_fees_gwei: [1, 1, 2],
+    },
 ];
 
-pub const CHAIN_NAMES: [&str; 4] = ["", "mainnet", "dev", "ropsten"];
+lazy_static! {
+    pub static ref CHAIN_NAMES: Vec<&'static str> = CHAINS.iter().map(|chain| chain.name).collect();
+}
+
+impl ChainRecord {
+    // What a transfer would cost if every byte of its call data were nonzero; none costs more
+    pub fn max_transfer_gas(&self) -> u64 {
+        self.transfer_gas_base + 68 * TRANSFER_DATA_LENGTH as u64
+    }
+}
+
+// Unknown chain ids fall back to ropsten
+pub fn chain_record(chain_id: u8) -> &'static ChainRecord {
+    CHAINS
+        .iter()
+        .find(|chain| chain.chain_id == chain_id)
+        .unwrap_or(&CHAINS[2])
+}
 
 pub fn contract_address(chain_id: u8) -> Address {
-    match chain_id {
-        1u8 | 2u8 | 3u8 => CONTRACTS[usize::from(chain_id)], // IDEA/CLion is wrong: This is copy
-        _ => CONTRACTS[0],                                   // IDEA/CLion is wrong: This is copy
+    match CHAINS.iter().find(|chain| chain.chain_id == chain_id) {
+        Some(chain) => chain.contract,
+        None => Address { 0: [0u8; 20] },
     }
 }
 
 pub fn chain_name(chain_id: u8) -> &'static str {
-    match chain_id {
-        1u8 | 2u8 | 3u8 => CHAIN_NAMES[chain_id as usize],
-        _ => CHAIN_NAMES[3],
-    }
+    chain_record(chain_id).name
 }
 
 pub fn chain_id_from_name(name: &str) -> u8 {
-    match name.to_lowercase().as_str() {
-        "mainnet" => 1u8,
-        "dev" => 2u8,
-        _ => 3u8,
-    }
+    let name = name.to_lowercase();
+    CHAINS
+        .iter()
+        .find(|chain| chain.name == name)
+        .unwrap_or(&CHAINS[2])
+        .chain_id
 }
 
 pub fn chain_name_from_id(chain_id: u8) -> &'static str {
-    match chain_id {
-        1u8 | 2u8 | 3u8 => CHAIN_NAMES[usize::from(chain_id)],
-        _ => CHAIN_NAMES[3],
-    }
+    chain_name(chain_id)
 }
 
 pub fn contract_creation_block_from_chain_id(chain_id: u8) -> u64 {
-    match chain_id {
-        1u8 | 2u8 | 3u8 => CONTRACT_CREATION_BLOCK[usize::from(chain_id)],
-        _ => CONTRACT_CREATION_BLOCK[3],
-    }
+    chain_record(chain_id).contract_creation_block
 }
 
 pub const CONTRACT_ABI: &str = r#"[{"constant":true,"inputs":[{"name":"owner","type":"address"}],"name":"balanceOf","outputs":[{"name":"","type":"uint256"}],"payable":false,"stateMutability":"view","type":"function"},{"constant":false,"inputs":[{"name":"to","type":"address"},{"name":"value","type":"uint256"}],"name":"transfer","outputs":[{"name":"","type":"bool"}],"payable":false,"stateMutability":"nonpayable","type":"function"}]"#;
 
-const TRANSACTION_LITERAL: H256 = H256 {
+pub const TRANSACTION_LITERAL: H256 = H256 {
     0: [
         0xdd, 0xf2, 0x52, 0xad, 0x1b, 0xe2, 0xc8, 0x9b, 0x69, 0xc2, 0xb0, 0x68, 0xfc, 0x37, 0x8d,
         0xaa, 0x95, 0x2b, 0xa7, 0xf1, 0x63, 0xc4, 0xa1, 0x16, 0x28, 0xf5, 0x5a, 0x4d, 0xf5, 0x23,
@@ -103,6 +172,8 @@ const TRANSACTION_LITERAL: H256 = H256 {
 };
 
 const TRANSFER_METHOD_ID: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+// The method id, then the recipient and the amount
+const TRANSFER_DATA_LENGTH: usize = 4 + 32 + 32;
 
 pub const DEFAULT_GAS_PRICE: &str = "1";
 pub const DEFAULT_CHAIN_NAME: &str = "mainnet";
@@ -112,6 +183,7 @@ pub struct Transaction {
     pub block_number: u64,
     pub from: Wallet,
     pub gwei_amount: u64,
+    pub transaction_hash: H256,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -121,6 +193,14 @@ pub enum BlockchainError {
     InvalidResponse,
     QueryFailed,
     TransactionFailed(String),
+    FeeCapExceeded {
+        max_fee_per_gas: U256,
+        cap: U256,
+    },
+    FeeBudgetExceeded {
+        projected_gwei: u64,
+        remaining_gwei: u64,
+    },
 }
 
 impl Display for BlockchainError {
@@ -129,6 +209,67 @@ impl Display for BlockchainError {
     }
 }
 
+// All amounts are in wei per unit of gas.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransactionFee {
+    // For chains that haven't adopted EIP-1559 and so have no base fee
+    Legacy {
+        gas_price: U256,
+    },
+    Eip1559 {
+        max_fee_per_gas: U256,
+        max_priority_fee_per_gas: U256,
+    },
+}
+
+impl TransactionFee {
+    pub fn max_fee_per_gas(&self) -> U256 {
+        match self {
+            TransactionFee::Legacy { gas_price } => *gas_price,
+            TransactionFee::Eip1559 {
+                max_fee_per_gas, ..
+            } => *max_fee_per_gas,
+        }
+    }
+
+    // Nodes only accept a transaction in place of a pending one with the same nonce if it outbids
+    // it by at least a tenth, so this bids at least an eighth more than the previous fee, and
+    // the going rate if that's higher still.
+    pub fn replacing(self, previous: TransactionFee) -> TransactionFee {
+        let outbid = |wei: U256| wei + max(wei / 8, U256::one());
+        match (self, previous) {
+            (
+                TransactionFee::Legacy { gas_price },
+                TransactionFee::Legacy {
+                    gas_price: previous_gas_price,
+                },
+            ) => TransactionFee::Legacy {
+                gas_price: max(gas_price, outbid(previous_gas_price)),
+            },
+            (
+                TransactionFee::Eip1559 {
+                    max_fee_per_gas,
+                    max_priority_fee_per_gas,
+                },
+                TransactionFee::Eip1559 {
+                    max_fee_per_gas: previous_max_fee_per_gas,
+                    max_priority_fee_per_gas: previous_max_priority_fee_per_gas,
+                },
+            ) => TransactionFee::Eip1559 {
+                max_fee_per_gas: max(max_fee_per_gas, outbid(previous_max_fee_per_gas)),
+                max_priority_fee_per_gas: max(
+                    max_priority_fee_per_gas,
+                    outbid(previous_max_priority_fee_per_gas),
+                ),
+            },
+            // The chain switched fee markets since the previous transaction was sent
+            _ => TransactionFee::Legacy {
+                gas_price: max(self.max_fee_per_gas(), outbid(previous.max_fee_per_gas())),
+            },
+        }
+    }
+}
+
 pub type BlockchainResult<T> = Result<T, BlockchainError>;
 pub type Balance = BlockchainResult<web3::types::U256>;
 pub type Nonce = BlockchainResult<web3::types::U256>;
@@ -137,7 +278,13 @@ pub type Transactions = BlockchainResult<Vec<Transaction>>;
 pub trait BlockchainInterface {
     fn contract_address(&self) -> Address;
 
-    fn retrieve_transactions(&self, start_block: u64, recipient: &Wallet) -> Transactions;
+    // Token transfers to the recipient in blocks start_block through end_block, inclusive
+    fn retrieve_transactions(
+        &self,
+        start_block: u64,
+        end_block: u64,
+        recipient: &Wallet,
+    ) -> Transactions;
 
     fn send_transaction(
         &self,
@@ -145,7 +292,7 @@ pub trait BlockchainInterface {
         recipient: &Wallet,
         amount: u64,
         nonce: U256,
-        gas_price: u64,
+        fee: TransactionFee,
     ) -> BlockchainResult<H256>;
 
     fn get_eth_balance(&self, address: &Wallet) -> Balance;
@@ -160,6 +307,31 @@ pub trait BlockchainInterface {
     }
 
     fn get_transaction_count(&self, address: &Wallet) -> Nonce;
+
+    // Counts only transactions that have been mined, so it lags get_transaction_count while
+    // any are pending
+    fn get_confirmed_transaction_count(&self, address: &Wallet) -> Nonce;
+
+    fn get_transaction_receipt(&self, hash: H256) -> BlockchainResult<Option<TransactionReceipt>>;
+
+    // Uses up the nonce with a transaction that pays nothing to the consuming wallet itself
+    fn cancel_transaction(
+        &self,
+        consuming_wallet: &Wallet,
+        nonce: U256,
+        fee: TransactionFee,
+    ) -> BlockchainResult<H256>;
+
+    fn get_block_number(&self) -> BlockchainResult<u64>;
+
+    // None if the chain doesn't charge a base fee
+    fn get_base_fee_per_gas(&self) -> BlockchainResult<Option<U256>>;
+
+    fn check_health(&self) {}
+
+    fn active_service_url(&self) -> Option<String> {
+        None
+    }
 }
 
 pub struct BlockchainInterfaceClandestine {
@@ -187,7 +359,12 @@ impl BlockchainInterface for BlockchainInterfaceClandestine {
         contract_address(self.chain_id)
     }
 
-    fn retrieve_transactions(&self, _start_block: u64, _recipient: &Wallet) -> Transactions {
+    fn retrieve_transactions(
+        &self,
+        _start_block: u64,
+        _end_block: u64,
+        _recipient: &Wallet,
+    ) -> Transactions {
         let msg = "Could not retrieve transactions since blockchain_service_url was not specified"
             .to_string();
         error!(self.logger, "{}", &msg);
@@ -200,7 +377,7 @@ impl BlockchainInterface for BlockchainInterfaceClandestine {
         _recipient: &Wallet,
         _amount: u64,
         _nonce: U256,
-        _gas_price: u64,
+        _fee: TransactionFee,
     ) -> BlockchainResult<H256> {
         let msg =
             "Could not send transaction since blockchain_service_url was not specified".to_string();
@@ -229,6 +406,50 @@ impl BlockchainInterface for BlockchainInterfaceClandestine {
     fn get_transaction_count(&self, _address: &Wallet) -> Nonce {
         unimplemented!()
     }
+
+    fn get_confirmed_transaction_count(&self, address: &Wallet) -> Nonce {
+        error!(
+            self.logger,
+            "Could not get confirmed transaction count for {:?} since blockchain_service_url was not specified",
+            address
+        );
+        Err(BlockchainError::QueryFailed)
+    }
+
+    fn get_transaction_receipt(&self, _hash: H256) -> BlockchainResult<Option<TransactionReceipt>> {
+        error!(
+            self.logger,
+            "Could not get transaction receipt since blockchain_service_url was not specified"
+        );
+        Err(BlockchainError::QueryFailed)
+    }
+
+    fn cancel_transaction(
+        &self,
+        _consuming_wallet: &Wallet,
+        _nonce: U256,
+        _fee: TransactionFee,
+    ) -> BlockchainResult<H256> {
+        let msg = "Could not cancel transaction since blockchain_service_url was not specified"
+            .to_string();
+        error!(self.logger, "{}", &msg);
+        Err(BlockchainError::TransactionFailed(msg))
+    }
+
+    fn get_block_number(&self) -> BlockchainResult<u64> {
+        let msg =
+            "Could not get block number since blockchain_service_url was not specified".to_string();
+        error!(self.logger, "{}", &msg);
+        Err(BlockchainError::QueryFailed)
+    }
+
+    fn get_base_fee_per_gas(&self) -> BlockchainResult<Option<U256>> {
+        error!(
+            self.logger,
+            "Could not get base fee since blockchain_service_url was not specified"
+        );
+        Err(BlockchainError::QueryFailed)
+    }
 }
 
 pub struct BlockchainInterfaceNonClandestine<T: Transport + Debug> {
@@ -259,11 +480,17 @@ where
         contract_address(self.chain_id)
     }
 
-    fn retrieve_transactions(&self, start_block: u64, recipient: &Wallet) -> Transactions {
+    fn retrieve_transactions(
+        &self,
+        start_block: u64,
+        end_block: u64,
+        recipient: &Wallet,
+    ) -> Transactions {
         debug!(
             self.logger,
-            "Retrieving transactions from start block: {} for: {} chain_id: {} contract: {:#x}",
+            "Retrieving transactions from blocks {} through {} for: {} chain_id: {} contract: {:#x}",
             start_block,
+            end_block,
             recipient,
             self.chain_id,
             self.contract_address()
@@ -271,7 +498,7 @@ where
         let filter = FilterBuilder::default()
             .address(vec![self.contract_address()])
             .from_block(BlockNumber::Number(start_block))
-            .to_block(BlockNumber::Latest)
+            .to_block(BlockNumber::Number(end_block))
             .topics(
                 Some(vec![TRANSACTION_LITERAL]),
                 None,
@@ -293,18 +520,21 @@ where
                         } else {
                             Ok(logs
                                 .iter()
-                                .filter_map(|log: &Log| match log.block_number {
-                                    Some(block_number) => {
-                                        let amount: U256 = U256::from(log.data.0.as_slice());
-                                        let gwei_amount = to_gwei(amount);
-                                        gwei_amount.map(|gwei_amount| Transaction {
-                                            block_number: u64::try_from(block_number)
-                                                .expect("Internal Error"), // TODO: back to testing for overflow
-                                            from: Wallet::from(log.topics[1]),
-                                            gwei_amount,
-                                        })
+                                .filter_map(|log: &Log| {
+                                    match (log.block_number, log.transaction_hash) {
+                                        (Some(block_number), Some(transaction_hash)) => {
+                                            let amount: U256 = U256::from(log.data.0.as_slice());
+                                            let gwei_amount = to_gwei(amount);
+                                            gwei_amount.map(|gwei_amount| Transaction {
+                                                block_number: u64::try_from(block_number)
+                                                    .expect("Internal Error"), // TODO: back to testing for overflow
+                                                from: Wallet::from(log.topics[1]),
+                                                gwei_amount,
+                                                transaction_hash,
+                                            })
+                                        }
+                                        _ => None,
                                     }
-                                    None => None,
                                 })
                                 .collect())
                         }
@@ -321,7 +551,7 @@ where
         recipient: &Wallet,
         amount: u64,
         nonce: U256,
-        gas_price: u64,
+        fee: TransactionFee,
     ) -> BlockchainResult<H256> {
         debug!(
             self.logger,
@@ -332,44 +562,23 @@ where
             self.chain_id,
             self.contract_address()
         );
-        let mut data = [0u8; 4 + 32 + 32];
+        let mut data = [0u8; TRANSFER_DATA_LENGTH];
         data[0..4].copy_from_slice(&TRANSFER_METHOD_ID);
         data[16..36].copy_from_slice(&recipient.address().0[..]);
         to_wei(amount).to_big_endian(&mut data[36..68]);
-        let gas_limit = ethereum_types::U256::try_from(
-            data.iter()
-                .fold(55_000u64, |acc, v| acc + if v == &0u8 { 4 } else { 68 }),
-        )
-        .expect("Internal error");
-
-        let converted_nonce = serde_json::from_value::<ethereum_types::U256>(
-            serde_json::to_value(nonce).expect("Internal error"),
-        )
-        .expect("Internal error");
-        let gas_price = serde_json::from_value::<ethereum_types::U256>(
-            serde_json::to_value(to_wei(gas_price)).expect("Internal error"),
-        )
-        .expect("Internal error");
-
-        let tx = RawTransaction {
-            nonce: converted_nonce,
-            to: Some(ethereum_types::Address {
-                0: self.contract_address().0,
-            }),
-            value: ethereum_types::U256::zero(),
-            gas_price,
+        let gas_limit = data
+            .iter()
+            .fold(chain_record(self.chain_id).transfer_gas_base, |acc, v| {
+                acc + if v == &0u8 { 4 } else { 68 }
+            });
+        self.sign_and_send(
+            consuming_wallet,
+            self.contract_address(),
+            data.to_vec(),
             gas_limit,
-            data: data.to_vec(),
-        };
-        match self
-            .web3
-            .eth()
-            .send_raw_transaction(Bytes(tx.sign(&consuming_wallet, self.chain_id)))
-            .wait()
-        {
-            Ok(result) => Ok(result),
-            Err(e) => Err(BlockchainError::TransactionFailed(format!("{:?}", e))),
-        }
+            nonce,
+            fee,
+        )
     }
 
     fn get_eth_balance(&self, wallet: &Wallet) -> Balance {
@@ -400,12 +609,125 @@ where
             .map_err(|_| BlockchainError::QueryFailed)
             .wait()
     }
+
+    fn get_confirmed_transaction_count(&self, wallet: &Wallet) -> Nonce {
+        self.web3
+            .eth()
+            .transaction_count(wallet.address(), Some(BlockNumber::Latest))
+            .map_err(|_| BlockchainError::QueryFailed)
+            .wait()
+    }
+
+    fn get_transaction_receipt(&self, hash: H256) -> BlockchainResult<Option<TransactionReceipt>> {
+        self.web3
+            .eth()
+            .transaction_receipt(hash)
+            .map_err(|_| BlockchainError::QueryFailed)
+            .wait()
+    }
+
+    fn cancel_transaction(
+        &self,
+        consuming_wallet: &Wallet,
+        nonce: U256,
+        fee: TransactionFee,
+    ) -> BlockchainResult<H256> {
+        debug!(
+            self.logger,
+            "Cancelling transaction {} from {}: (chain_id: {})",
+            nonce,
+            consuming_wallet,
+            self.chain_id
+        );
+        self.sign_and_send(
+            consuming_wallet,
+            consuming_wallet.address(),
+            vec![],
+            CANCELLATION_GAS_LIMIT,
+            nonce,
+            fee,
+        )
+    }
+
+    fn get_block_number(&self) -> BlockchainResult<u64> {
+        self.web3
+            .eth()
+            .block_number()
+            .map_err(|_| BlockchainError::QueryFailed)
+            .wait()
+            .and_then(|block_number| {
+                u64::try_from(block_number).map_err(|_| BlockchainError::InvalidResponse)
+            })
+    }
+
+    fn get_base_fee_per_gas(&self) -> BlockchainResult<Option<U256>> {
+        self.web3
+            .transport()
+            .execute("eth_getBlockByNumber", vec![json!("latest"), json!(false)])
+            .map_err(|_| BlockchainError::QueryFailed)
+            .wait()
+            .and_then(|block| match block.get("baseFeePerGas") {
+                None | Some(serde_json::Value::Null) => Ok(None),
+                Some(base_fee) => serde_json::from_value::<U256>(base_fee.clone())
+                    .map(Some)
+                    .map_err(|_| BlockchainError::InvalidResponse),
+            })
+    }
+}
+
+// web3 and RawTransaction depend on different versions of ethereum_types
+fn to_ethereum_u256(value: U256) -> ethereum_types::U256 {
+    serde_json::from_value::<ethereum_types::U256>(
+        serde_json::to_value(value).expect("Internal error"),
+    )
+    .expect("Internal error")
 }
 
 impl<T> BlockchainInterfaceNonClandestine<T>
 where
     T: Transport + Debug,
 {
+    fn sign_and_send(
+        &self,
+        consuming_wallet: &Wallet,
+        to: Address,
+        data: Vec<u8>,
+        gas_limit: u64,
+        nonce: U256,
+        fee: TransactionFee,
+    ) -> BlockchainResult<H256> {
+        let to = Some(ethereum_types::Address { 0: to.0 });
+        let gas_limit = ethereum_types::U256::from(gas_limit);
+        let signed = match fee {
+            TransactionFee::Legacy { gas_price } => RawTransaction {
+                nonce: to_ethereum_u256(nonce),
+                to,
+                value: ethereum_types::U256::zero(),
+                gas_price: to_ethereum_u256(gas_price),
+                gas_limit,
+                data,
+            }
+            .sign(consuming_wallet, self.chain_id),
+            TransactionFee::Eip1559 {
+                max_fee_per_gas,
+                max_priority_fee_per_gas,
+            } => Eip1559Transaction {
+                nonce: to_ethereum_u256(nonce),
+                to,
+                value: ethereum_types::U256::zero(),
+                max_priority_fee_per_gas: to_ethereum_u256(max_priority_fee_per_gas),
+                max_fee_per_gas: to_ethereum_u256(max_fee_per_gas),
+                gas_limit,
+                data,
+            }
+            .sign(consuming_wallet, self.chain_id),
+        };
+        match self.web3.eth().send_raw_transaction(Bytes(signed)).wait() {
+            Ok(result) => Ok(result),
+            Err(e) => Err(BlockchainError::TransactionFailed(format!("{:?}", e))),
+        }
+    }
+
     pub fn new(transport: T, event_loop_handle: EventLoopHandle, chain_id: u8) -> Self {
         let web3 = Web3::new(transport);
         let contract = Contract::from_json(
@@ -428,10 +750,12 @@ where
 mod tests {
     use super::*;
     use crate::sub_lib::wallet::Wallet;
+    use crate::test_utils::logging::{init_test_logging, TestLogHandler};
     use crate::test_utils::{find_free_port, make_paying_wallet, make_wallet, DEFAULT_CHAIN_ID};
     use ethereum_types::BigEndianHash;
     use ethsign_crypto::Keccak256;
     use jsonrpc_core as rpc;
+    use rustc_hex::FromHex;
     use serde_json::json;
     use serde_json::Value;
     use simple_server::Server;
@@ -534,6 +858,7 @@ mod tests {
         let result = subject
             .retrieve_transactions(
                 42,
+                4_974_200,
                 &Wallet::from_str("0x3f69f9efd4f2592fd70be8c32ecd9dce71c472fc").unwrap(),
             )
             .unwrap();
@@ -543,11 +868,17 @@ mod tests {
             format!("\"0x000000000000000000000000{}\"", &to[2..]),
             body["params"][0]["topics"][2].to_string(),
         );
+        assert_eq!(body["params"][0]["fromBlock"], json!("0x2a"));
+        assert_eq!(body["params"][0]["toBlock"], json!("0x4be678"));
         assert_eq!(
             vec![Transaction {
                 block_number: 4_974_179u64,
                 from: Wallet::from_str("0x3f69f9efd4f2592fd70be8c32ecd9dce71c472fc").unwrap(),
                 gwei_amount: 4_503_599u64,
+                transaction_hash: H256::from_str(
+                    "955cec6ac4f832911ab894ce16aa22c3003f46deff3f7165b32700d2f5ff0681"
+                )
+                .unwrap(),
             }],
             result,
         )
@@ -563,8 +894,11 @@ mod tests {
         let subject =
             BlockchainInterfaceNonClandestine::new(transport, event_loop_handle, DEFAULT_CHAIN_ID);
 
-        let result = subject
-            .retrieve_transactions(42, &Wallet::new("0x3f69f9efd4f2592fd70beecd9dce71c472fc"));
+        let result = subject.retrieve_transactions(
+            42,
+            55,
+            &Wallet::new("0x3f69f9efd4f2592fd70beecd9dce71c472fc"),
+        );
 
         assert_eq!(
             BlockchainError::InvalidAddress,
@@ -594,6 +928,7 @@ mod tests {
 
         let result = subject.retrieve_transactions(
             42,
+            4_974_200,
             &Wallet::from_str("0x3f69f9efd4f2592fd70be8c32ecd9dce71c472fc").unwrap(),
         );
 
@@ -626,6 +961,7 @@ mod tests {
 
         let result = subject.retrieve_transactions(
             42,
+            4_974_200,
             &Wallet::from_str("0x3f69f9efd4f2592fd70be8c32ecd9dce71c472fc").unwrap(),
         );
 
@@ -656,6 +992,38 @@ mod tests {
 
         let result = subject.retrieve_transactions(
             42,
+            4_974_200,
+            &Wallet::from_str("0x3f69f9efd4f2592fd70be8c32ecd9dce71c472fc").unwrap(),
+        );
+
+        assert_eq!(Ok(vec![]), result);
+    }
+
+    #[test]
+    fn blockchain_interface_non_clandestine_retrieve_transactions_ignores_transaction_logs_that_have_no_transaction_hash(
+    ) {
+        let port = find_free_port();
+
+        thread::spawn(move || {
+            Server::new(|_req, mut rsp| {
+                Ok(rsp.body(br#"{"jsonrpc":"2.0","id":3,"result":[{"address":"0xcd6c588e005032dd882cd43bf53a32129be81302","blockHash":"0x1a24b9169cbaec3f6effa1f600b70c7ab9e8e86db44062b49132a4415d26732a","blockNumber":"0x4be663","data":"0x0000000000000000000000000000000000000000000000000010000000000000","logIndex":"0x0","removed":false,"topics":["0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef","0x0000000000000000000000003f69f9efd4f2592fd70be8c32ecd9dce71c472fc","0x000000000000000000000000adc1853c7859369639eb414b6342b36288fe6092"],"transactionIndex":"0x0"}]}"#.to_vec())?)
+            })
+                .listen(&Ipv4Addr::LOCALHOST.to_string(), &format!("{}", port));
+        });
+
+        let (event_loop_handle, transport) = Http::new(&format!(
+            "http://{}:{}",
+            &Ipv4Addr::LOCALHOST.to_string(),
+            port
+        ))
+        .unwrap();
+
+        let subject =
+            BlockchainInterfaceNonClandestine::new(transport, event_loop_handle, DEFAULT_CHAIN_ID);
+
+        let result = subject.retrieve_transactions(
+            42,
+            4_974_200,
             &Wallet::from_str("0x3f69f9efd4f2592fd70be8c32ecd9dce71c472fc").unwrap(),
         );
 
@@ -872,7 +1240,9 @@ mod tests {
             &make_wallet("blah123"),
             9000,
             U256::from(1),
-            2u64,
+            TransactionFee::Legacy {
+                gas_price: to_wei(2),
+            },
         );
 
         transport.assert_request("eth_sendRawTransaction", &[String::from(r#""0xf8a801847735940082dbe894cd6c588e005032dd882cd43bf53a32129be8130280b844a9059cbb00000000000000000000000000000000000000000000000000626c61683132330000000000000000000000000000000000000000000000000000082f79cd90002aa0210a8dc04a802e579493e9c3b0c6aca5d19197af17637e1c5ae61f3332746734a00ad3bddb042061f4ce99800fea66e36a684b1e168d16485dfdb2e4d2254f589e""#)]);
@@ -880,6 +1250,161 @@ mod tests {
         assert_eq!(result, Ok(H256::from_uint(&U256::from(1))));
     }
 
+    #[test]
+    fn blockchain_interface_non_clandestine_sends_eip1559_transactions_when_given_an_eip1559_fee() {
+        let mut transport = TestTransport::default();
+        transport.add_response(json!(
+            "0x0000000000000000000000000000000000000000000000000000000000000002"
+        ));
+        let subject = BlockchainInterfaceNonClandestine::new(
+            transport.clone(),
+            make_fake_event_loop_handle(),
+            DEFAULT_CHAIN_ID,
+        );
+
+        let result = subject.send_transaction(
+            &make_paying_wallet(b"gdasgsa"),
+            &make_wallet("blah123"),
+            9000,
+            U256::from(1),
+            TransactionFee::Eip1559 {
+                max_fee_per_gas: to_wei(40),
+                max_priority_fee_per_gas: to_wei(2),
+            },
+        );
+
+        let requests = transport.requests.borrow();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].0, "eth_sendRawTransaction");
+        let raw_transaction = requests[0].1[0].as_str().unwrap();
+        assert!(
+            raw_transaction.starts_with("0x02"),
+            "{} is not a type 2 transaction",
+            raw_transaction
+        );
+        assert_eq!(result, Ok(H256::from_uint(&U256::from(2))));
+    }
+
+    #[test]
+    fn blockchain_interface_non_clandestine_signs_for_the_chain_it_is_on() {
+        let mut transport = TestTransport::default();
+        transport.add_response(json!(
+            "0x0000000000000000000000000000000000000000000000000000000000000003"
+        ));
+        let subject = BlockchainInterfaceNonClandestine::new(
+            transport.clone(),
+            make_fake_event_loop_handle(),
+            chain_id_from_name("xdai"),
+        );
+
+        let result = subject.send_transaction(
+            &make_paying_wallet(b"gdasgsa"),
+            &make_wallet("blah123"),
+            9000,
+            U256::from(1),
+            TransactionFee::Legacy {
+                gas_price: to_wei(1),
+            },
+        );
+
+        let requests = transport.requests.borrow();
+        let raw_transaction: Vec<u8> = requests[0].1[0].as_str().unwrap()[2..].from_hex().unwrap();
+        let transaction = rlp::Rlp::new(&raw_transaction);
+        let to: Vec<u8> = transaction.val_at(3).unwrap();
+        let v: u64 = transaction.val_at(6).unwrap();
+        assert_eq!(to, XDAI_CONTRACT_ADDRESS.0.to_vec());
+        assert!(v == 235 || v == 236, "v of {} is not for chain 100", v);
+        assert_eq!(result, Ok(H256::from_uint(&U256::from(3))));
+    }
+
+    #[test]
+    fn chains_are_looked_up_by_id_and_name() {
+        let xdai = chain_record(100);
+
+        assert_eq!(xdai.name, "xdai");
+        assert_eq!(chain_id_from_name("xDai"), 100);
+        assert_eq!(chain_name_from_id(100), "xdai");
+        assert_eq!(contract_address(100), XDAI_CONTRACT_ADDRESS);
+        assert_eq!(
+            contract_creation_block_from_chain_id(100),
+            XDAI_CONTRACT_CREATION_BLOCK
+        );
+        assert_eq!(
+            xdai.default_blockchain_service_url,
+            Some(XDAI_BLOCKCHAIN_SERVICE_URL)
+        );
+        assert_eq!(chain_record(1).default_blockchain_service_url, None);
+        assert_eq!(*CHAIN_NAMES, vec!["mainnet", "dev", "ropsten", "xdai"]);
+    }
+
+    #[test]
+    fn unknown_chains_fall_back_to_ropsten_with_no_contract() {
+        assert_eq!(chain_id_from_name("booga"), 3);
+        assert_eq!(chain_name(42), "ropsten");
+        assert_eq!(
+            contract_creation_block_from_chain_id(42),
+            ROPSTEN_CONTRACT_CREATION_BLOCK
+        );
+        assert_eq!(contract_address(42), Address { 0: [0u8; 20] });
+    }
+
+    #[test]
+    fn transfers_cost_at_most_their_base_plus_nonzero_call_data() {
+        let mainnet = chain_record(chain_id_from_name("mainnet"));
+
+        assert_eq!(mainnet.max_transfer_gas(), 55_000 + 68 * 68);
+    }
+
+    #[test]
+    fn blockchain_interface_non_clandestine_can_fetch_base_fee() {
+        let mut transport = TestTransport::default();
+        transport.add_response(json!({"number": "0x4be663", "baseFeePerGas": "0x2540be400"}));
+        let subject = BlockchainInterfaceNonClandestine::new(
+            transport.clone(),
+            make_fake_event_loop_handle(),
+            DEFAULT_CHAIN_ID,
+        );
+
+        let result = subject.get_base_fee_per_gas();
+
+        transport.assert_request(
+            "eth_getBlockByNumber",
+            &[String::from(r#""latest""#), String::from("false")],
+        );
+        transport.assert_no_more_requests();
+        assert_eq!(result, Ok(Some(to_wei(10))));
+    }
+
+    #[test]
+    fn blockchain_interface_non_clandestine_finds_no_base_fee_on_chains_without_one() {
+        let mut transport = TestTransport::default();
+        transport.add_response(json!({"number": "0x4be663"}));
+        let subject = BlockchainInterfaceNonClandestine::new(
+            transport.clone(),
+            make_fake_event_loop_handle(),
+            DEFAULT_CHAIN_ID,
+        );
+
+        let result = subject.get_base_fee_per_gas();
+
+        assert_eq!(result, Ok(None));
+    }
+
+    #[test]
+    fn blockchain_interface_non_clandestine_returns_an_error_for_unintelligible_base_fee() {
+        let mut transport = TestTransport::default();
+        transport.add_response(json!({"number": "0x4be663", "baseFeePerGas": "booga"}));
+        let subject = BlockchainInterfaceNonClandestine::new(
+            transport.clone(),
+            make_fake_event_loop_handle(),
+            DEFAULT_CHAIN_ID,
+        );
+
+        let result = subject.get_base_fee_per_gas();
+
+        assert_eq!(result, Err(BlockchainError::InvalidResponse));
+    }
+
     #[test]
     fn blockchain_interface_non_clandestine_can_fetch_nonce() {
         let mut transport = TestTransport::default();
@@ -907,6 +1432,164 @@ mod tests {
         assert_eq!(result, Ok(U256::from(1)));
     }
 
+    #[test]
+    fn blockchain_interface_non_clandestine_counts_only_mined_transactions_for_the_confirmed_nonce()
+    {
+        let mut transport = TestTransport::default();
+        transport.add_response(json!("0x5"));
+        let subject = BlockchainInterfaceNonClandestine::new(
+            transport.clone(),
+            make_fake_event_loop_handle(),
+            DEFAULT_CHAIN_ID,
+        );
+
+        let result = subject.get_confirmed_transaction_count(&make_paying_wallet(b"gdasgsa"));
+
+        transport.assert_request(
+            "eth_getTransactionCount",
+            &[
+                String::from(r#""0x5c361ba8d82fcf0e5538b2a823e9d457a2296725""#),
+                String::from(r#""latest""#),
+            ],
+        );
+        transport.assert_no_more_requests();
+        assert_eq!(result, Ok(U256::from(5)));
+    }
+
+    #[test]
+    fn blockchain_interface_non_clandestine_finds_no_receipt_for_an_unmined_transaction() {
+        let mut transport = TestTransport::default();
+        transport.add_response(json!(null));
+        let subject = BlockchainInterfaceNonClandestine::new(
+            transport.clone(),
+            make_fake_event_loop_handle(),
+            DEFAULT_CHAIN_ID,
+        );
+
+        let result = subject.get_transaction_receipt(H256::from_uint(&U256::from(1)));
+
+        transport.assert_request(
+            "eth_getTransactionReceipt",
+            &[String::from(
+                r#""0x0000000000000000000000000000000000000000000000000000000000000001""#,
+            )],
+        );
+        transport.assert_no_more_requests();
+        assert_eq!(result, Ok(None));
+    }
+
+    #[test]
+    fn blockchain_interface_non_clandestine_cancels_by_sending_nothing_to_itself() {
+        let mut transport = TestTransport::default();
+        transport.add_response(json!(
+            "0x0000000000000000000000000000000000000000000000000000000000000004"
+        ));
+        let subject = BlockchainInterfaceNonClandestine::new(
+            transport.clone(),
+            make_fake_event_loop_handle(),
+            DEFAULT_CHAIN_ID,
+        );
+        let consuming_wallet = make_paying_wallet(b"gdasgsa");
+
+        let result = subject.cancel_transaction(
+            &consuming_wallet,
+            U256::from(7),
+            TransactionFee::Legacy {
+                gas_price: to_wei(3),
+            },
+        );
+
+        let requests = transport.requests.borrow();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].0, "eth_sendRawTransaction");
+        let raw_transaction: Vec<u8> = requests[0].1[0].as_str().unwrap()[2..].from_hex().unwrap();
+        let transaction = rlp::Rlp::new(&raw_transaction);
+        let nonce: u64 = transaction.val_at(0).unwrap();
+        let gas_limit: u64 = transaction.val_at(2).unwrap();
+        let to: Vec<u8> = transaction.val_at(3).unwrap();
+        let value: Vec<u8> = transaction.val_at(4).unwrap();
+        let data: Vec<u8> = transaction.val_at(5).unwrap();
+        assert_eq!(nonce, 7);
+        assert_eq!(gas_limit, 21_000);
+        assert_eq!(to, consuming_wallet.address().0.to_vec());
+        assert!(value.is_empty());
+        assert!(data.is_empty());
+        assert_eq!(result, Ok(H256::from_uint(&U256::from(4))));
+    }
+
+    #[test]
+    fn replacement_fees_outbid_the_previous_fee_by_an_eighth_or_the_market_if_higher() {
+        let legacy = |gwei| TransactionFee::Legacy {
+            gas_price: to_wei(gwei),
+        };
+        let eip1559 = |max_fee, priority_fee| TransactionFee::Eip1559 {
+            max_fee_per_gas: to_wei(max_fee),
+            max_priority_fee_per_gas: to_wei(priority_fee),
+        };
+
+        assert_eq!(legacy(8).replacing(legacy(8)), legacy(9));
+        assert_eq!(legacy(20).replacing(legacy(8)), legacy(20));
+        assert_eq!(eip1559(40, 2).replacing(eip1559(40, 8)), eip1559(45, 9));
+        assert_eq!(eip1559(80, 2).replacing(eip1559(40, 1)), eip1559(80, 2));
+        assert_eq!(
+            TransactionFee::Legacy {
+                gas_price: U256::zero()
+            }
+            .replacing(TransactionFee::Legacy {
+                gas_price: U256::zero()
+            }),
+            TransactionFee::Legacy {
+                gas_price: U256::one()
+            }
+        );
+        assert_eq!(eip1559(40, 2).replacing(legacy(48)), legacy(54));
+    }
+
+    #[test]
+    fn blockchain_interface_non_clandestine_can_fetch_block_number() {
+        let mut transport = TestTransport::default();
+        transport.add_response(json!("0x4be663"));
+        let subject = BlockchainInterfaceNonClandestine::new(
+            transport.clone(),
+            make_fake_event_loop_handle(),
+            DEFAULT_CHAIN_ID,
+        );
+
+        let result = subject.get_block_number();
+
+        transport.assert_request("eth_blockNumber", &[]);
+        transport.assert_no_more_requests();
+        assert_eq!(result, Ok(4_974_179u64));
+    }
+
+    #[test]
+    fn blockchain_interface_non_clandestine_returns_an_error_for_unintelligible_block_number() {
+        let mut transport = TestTransport::default();
+        transport.add_response(json!("not a number"));
+        let subject = BlockchainInterfaceNonClandestine::new(
+            transport.clone(),
+            make_fake_event_loop_handle(),
+            DEFAULT_CHAIN_ID,
+        );
+
+        let result = subject.get_block_number();
+
+        assert_eq!(result, Err(BlockchainError::QueryFailed));
+    }
+
+    #[test]
+    fn blockchain_interface_clandestine_cannot_fetch_block_number() {
+        init_test_logging();
+        let subject = BlockchainInterfaceClandestine::new(DEFAULT_CHAIN_ID);
+
+        let result = subject.get_block_number();
+
+        assert_eq!(result, Err(BlockchainError::QueryFailed));
+        TestLogHandler::new().exists_log_containing(
+            "ERROR: BlockchainInterface: Could not get block number since blockchain_service_url was not specified",
+        );
+    }
+
     #[test]
     fn to_gwei_truncates_units_smaller_than_gwei() {
         assert_eq!(Some(1), to_gwei(U256::from(1_999_999_999)));
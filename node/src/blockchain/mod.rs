@@ -1,9 +1,13 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 pub mod bip32;
 pub mod bip39;
+pub mod block_subscription;
 pub mod blockchain_bridge;
+pub mod blockchain_failover;
 pub mod blockchain_interface;
 pub mod payer;
+pub mod pending_transaction_dao;
+pub mod pending_transactions;
 pub mod raw_transaction;
 pub mod signature;
 
@@ -0,0 +1,303 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::accountant::payable_dao::Payment;
+use crate::blockchain::blockchain_interface::TransactionFee;
+use crate::blockchain::pending_transactions::PendingTransaction;
+use crate::database::dao_utils::{from_time_t, to_time_t};
+use crate::database::db_initializer::ConnectionWrapper;
+use crate::sub_lib::wallet::Wallet;
+use rusqlite::types::ToSql;
+use rusqlite::{Row, NO_PARAMS};
+use std::convert::TryFrom;
+use std::str::FromStr;
+use web3::types::{H256, U256};
+
+// The payments the BlockchainBridge is still watching, so that a restarted Node goes on resolving
+// and unsticking them. The Node's own consuming wallet is recorded as '', as in the payable table.
+pub trait PendingTransactionDao: Send {
+    // With the consuming profile wallet each was paid from, if any
+    fn pending_transactions(&self) -> Vec<(Option<Wallet>, U256, PendingTransaction)>;
+    fn save(&self, profile_wallet_opt: &Option<Wallet>, nonce: U256, pending: &PendingTransaction);
+    fn remove(&self, profile_wallet_opt: &Option<Wallet>, nonce: U256);
+}
+
+pub struct PendingTransactionDaoReal {
+    conn: Box<dyn ConnectionWrapper>,
+}
+
+impl PendingTransactionDaoReal {
+    pub fn new(conn: Box<dyn ConnectionWrapper>) -> Self {
+        Self { conn }
+    }
+}
+
+impl PendingTransactionDao for PendingTransactionDaoReal {
+    fn pending_transactions(&self) -> Vec<(Option<Wallet>, U256, PendingTransaction)> {
+        let mut stmt = self
+            .conn
+            .prepare("select consuming_wallet_address, nonce, wallet_address, amount, payment_timestamp, transaction_hash, max_fee_per_gas, max_priority_fee_per_gas, replacements, cancellation, last_sent from pending_transaction order by consuming_wallet_address, nonce")
+            .expect("Failed to prepare a statement");
+        stmt.query_map(NO_PARAMS, read_pending_transaction)
+            .expect("Couldn't retrieve pending transactions: database corrupt")
+            .map(|result| {
+                result.unwrap_or_else(|e| {
+                    panic!(
+                        "Couldn't read a pending transaction: database corrupt: {}",
+                        e
+                    )
+                })
+            })
+            .collect()
+    }
+
+    fn save(&self, profile_wallet_opt: &Option<Wallet>, nonce: U256, pending: &PendingTransaction) {
+        let consuming = consuming_wallet_address(profile_wallet_opt);
+        let nonce = to_i64(nonce, "nonce");
+        let address = pending.payment.to.to_string();
+        let amount = to_i64(U256::from(pending.payment.amount), "payment amount");
+        let payment_timestamp = to_time_t(pending.payment.timestamp);
+        let transaction = format!("{:#x}", pending.payment.transaction);
+        let (max_fee_per_gas, max_priority_fee_per_gas) = match pending.fee {
+            TransactionFee::Legacy { gas_price } => (gas_price.to_string(), None),
+            TransactionFee::Eip1559 {
+                max_fee_per_gas,
+                max_priority_fee_per_gas,
+            } => (
+                max_fee_per_gas.to_string(),
+                Some(max_priority_fee_per_gas.to_string()),
+            ),
+        };
+        let replacements = pending
+            .replacements
+            .iter()
+            .map(|hash| format!("{:#x}", hash))
+            .collect::<Vec<String>>()
+            .join(",");
+        let cancellation = pending.cancellation.map(|hash| format!("{:#x}", hash));
+        let last_sent = to_time_t(pending.last_sent);
+        let params: &[(&str, &dyn ToSql)] = &[
+            (":consuming", &consuming),
+            (":nonce", &nonce),
+            (":address", &address),
+            (":amount", &amount),
+            (":payment_timestamp", &payment_timestamp),
+            (":transaction", &transaction),
+            (":max_fee_per_gas", &max_fee_per_gas),
+            (":max_priority_fee_per_gas", &max_priority_fee_per_gas),
+            (":replacements", &replacements),
+            (":cancellation", &cancellation),
+            (":last_sent", &last_sent),
+        ];
+        let result = self.conn.write(&mut |tx| {
+            tx.execute_named("insert or replace into pending_transaction (consuming_wallet_address, nonce, wallet_address, amount, payment_timestamp, transaction_hash, max_fee_per_gas, max_priority_fee_per_gas, replacements, cancellation, last_sent) values (:consuming, :nonce, :address, :amount, :payment_timestamp, :transaction, :max_fee_per_gas, :max_priority_fee_per_gas, :replacements, :cancellation, :last_sent)", params)
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        });
+        if let Err(e) = result {
+            panic!(
+                "Could not record pending payment at nonce {} because of database corruption: {}",
+                nonce, e
+            )
+        }
+    }
+
+    fn remove(&self, profile_wallet_opt: &Option<Wallet>, nonce: U256) {
+        let consuming = consuming_wallet_address(profile_wallet_opt);
+        let nonce = to_i64(nonce, "nonce");
+        let params: &[&dyn ToSql] = &[&consuming, &nonce];
+        let result = self.conn.write(&mut |tx| {
+            tx.execute(
+                "delete from pending_transaction where consuming_wallet_address = ? and nonce = ?",
+                params,
+            )
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+        });
+        if let Err(e) = result {
+            panic!(
+                "Could not forget pending payment at nonce {} because of database corruption: {}",
+                nonce, e
+            )
+        }
+    }
+}
+
+fn consuming_wallet_address(profile_wallet_opt: &Option<Wallet>) -> String {
+    profile_wallet_opt
+        .as_ref()
+        .map(|wallet| wallet.to_string())
+        .unwrap_or_default()
+}
+
+fn to_i64(value: U256, name: &str) -> i64 {
+    if value > U256::from(i64::MAX) {
+        panic!("Lost {} precision: {}", name, value)
+    }
+    i64::try_from(value.low_u64()).expect("Internal error")
+}
+
+fn read_pending_transaction(
+    row: &Row,
+) -> rusqlite::Result<(Option<Wallet>, U256, PendingTransaction)> {
+    let consuming: String = row.get(0)?;
+    let nonce: i64 = row.get(1)?;
+    let address: String = row.get(2)?;
+    let amount: i64 = row.get(3)?;
+    let payment_timestamp: i64 = row.get(4)?;
+    let transaction: String = row.get(5)?;
+    let max_fee_per_gas: String = row.get(6)?;
+    let max_priority_fee_per_gas: Option<String> = row.get(7)?;
+    let replacements: String = row.get(8)?;
+    let cancellation: Option<String> = row.get(9)?;
+    let last_sent: i64 = row.get(10)?;
+    let profile_wallet_opt = if consuming.is_empty() {
+        None
+    } else {
+        Some(Wallet::new(&consuming))
+    };
+    let fee = match max_priority_fee_per_gas {
+        None => TransactionFee::Legacy {
+            gas_price: parse_u256(&max_fee_per_gas),
+        },
+        Some(max_priority_fee_per_gas) => TransactionFee::Eip1559 {
+            max_fee_per_gas: parse_u256(&max_fee_per_gas),
+            max_priority_fee_per_gas: parse_u256(&max_priority_fee_per_gas),
+        },
+    };
+    let payment = Payment {
+        to: Wallet::new(&address),
+        amount: amount as u64,
+        timestamp: from_time_t(payment_timestamp),
+        transaction: parse_h256(&transaction),
+    };
+    let pending = PendingTransaction {
+        payment,
+        fee,
+        replacements: replacements
+            .split(',')
+            .filter(|hash| !hash.is_empty())
+            .map(parse_h256)
+            .collect(),
+        cancellation: cancellation.as_ref().map(|hash| parse_h256(hash)),
+        last_sent: from_time_t(last_sent),
+    };
+    Ok((profile_wallet_opt, U256::from(nonce as u64), pending))
+}
+
+fn parse_u256(value: &str) -> U256 {
+    U256::from_dec_str(value).unwrap_or_else(|_| panic!("Database is corrupt: bad fee '{}'", value))
+}
+
+fn parse_h256(value: &str) -> H256 {
+    H256::from_str(&value[2..])
+        .unwrap_or_else(|_| panic!("Database is corrupt: bad transaction hash '{}'", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::blockchain_interface::to_wei;
+    use crate::database::db_initializer::{DbInitializer, DbInitializerReal};
+    use crate::test_utils::{ensure_node_home_directory_exists, make_wallet, DEFAULT_CHAIN_ID};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn make_subject(test_name: &str) -> PendingTransactionDaoReal {
+        let home_dir = ensure_node_home_directory_exists("pending_transaction_dao", test_name);
+        let conn = DbInitializerReal::new()
+            .initialize(&home_dir, DEFAULT_CHAIN_ID)
+            .unwrap();
+        PendingTransactionDaoReal::new(conn)
+    }
+
+    fn make_pending(name: &str, fee: TransactionFee) -> PendingTransaction {
+        let mut payment = Payment::new(make_wallet(name), 1234, H256::from_low_u64_be(1));
+        payment.timestamp = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        PendingTransaction::new(payment, fee)
+    }
+
+    #[test]
+    fn pending_transactions_are_empty_until_something_is_saved() {
+        let subject = make_subject("pending_transactions_are_empty_until_something_is_saved");
+
+        assert_eq!(subject.pending_transactions(), vec![]);
+    }
+
+    #[test]
+    fn saved_pending_transactions_come_back_for_each_consuming_wallet() {
+        let subject =
+            make_subject("saved_pending_transactions_come_back_for_each_consuming_wallet");
+        let own = make_pending(
+            "creditor",
+            TransactionFee::Legacy {
+                gas_price: to_wei(3),
+            },
+        );
+        let mut profile = make_pending(
+            "other creditor",
+            TransactionFee::Eip1559 {
+                max_fee_per_gas: to_wei(40),
+                max_priority_fee_per_gas: to_wei(2),
+            },
+        );
+        profile.replacements = vec![H256::from_low_u64_be(2), H256::from_low_u64_be(3)];
+        profile.cancellation = Some(H256::from_low_u64_be(4));
+        profile.last_sent = UNIX_EPOCH + Duration::from_secs(2_000_000);
+        let profile_wallet = Some(make_wallet("profile"));
+
+        subject.save(&None, U256::from(7), &own);
+        subject.save(&profile_wallet, U256::from(7), &profile);
+        subject.save(&profile_wallet, U256::from(8), &own);
+
+        assert_eq!(
+            subject.pending_transactions(),
+            vec![
+                (None, U256::from(7), own.clone()),
+                (profile_wallet.clone(), U256::from(7), profile),
+                (profile_wallet, U256::from(8), own),
+            ]
+        );
+    }
+
+    #[test]
+    fn saving_a_pending_transaction_again_replaces_it() {
+        let subject = make_subject("saving_a_pending_transaction_again_replaces_it");
+        let mut pending = make_pending(
+            "creditor",
+            TransactionFee::Legacy {
+                gas_price: to_wei(3),
+            },
+        );
+        subject.save(&None, U256::from(7), &pending);
+        pending.replacements.push(H256::from_low_u64_be(2));
+        pending.fee = TransactionFee::Legacy {
+            gas_price: to_wei(4),
+        };
+
+        subject.save(&None, U256::from(7), &pending);
+
+        assert_eq!(
+            subject.pending_transactions(),
+            vec![(None, U256::from(7), pending)]
+        );
+    }
+
+    #[test]
+    fn removing_a_pending_transaction_leaves_the_others() {
+        let subject = make_subject("removing_a_pending_transaction_leaves_the_others");
+        let pending = make_pending(
+            "creditor",
+            TransactionFee::Legacy {
+                gas_price: to_wei(3),
+            },
+        );
+        let profile_wallet = Some(make_wallet("profile"));
+        subject.save(&None, U256::from(7), &pending);
+        subject.save(&profile_wallet, U256::from(7), &pending);
+
+        subject.remove(&None, U256::from(7));
+
+        assert_eq!(
+            subject.pending_transactions(),
+            vec![(profile_wallet, U256::from(7), pending)]
+        );
+    }
+}
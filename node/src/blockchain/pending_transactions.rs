@@ -0,0 +1,259 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::accountant::payable_dao::Payment;
+use crate::blockchain::blockchain_interface::TransactionFee;
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+use web3::types::{H256, U256};
+
+pub const DEFAULT_PENDING_TRANSACTION_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+pub const DEFAULT_STUCK_TRANSACTION_TIMEOUT: Duration = Duration::from_secs(600);
+// A payment that's still stuck after this many speed-ups is cancelled instead
+pub const MAX_REPLACEMENTS: usize = 3;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingTransaction {
+    // As the Accountant recorded it, with the hash of the first transaction sent for it
+    pub payment: Payment,
+    // Of the most recent transaction sent for this nonce
+    pub fee: TransactionFee,
+    pub replacements: Vec<H256>,
+    pub cancellation: Option<H256>,
+    pub last_sent: SystemTime,
+}
+
+impl PendingTransaction {
+    pub fn new(payment: Payment, fee: TransactionFee) -> Self {
+        let last_sent = payment.timestamp;
+        PendingTransaction {
+            payment,
+            fee,
+            replacements: vec![],
+            cancellation: None,
+            last_sent,
+        }
+    }
+
+    // Every transaction sent to make the payment, oldest first
+    pub fn payment_attempts(&self) -> Vec<H256> {
+        let mut attempts = vec![self.payment.transaction];
+        attempts.extend(self.replacements.iter().cloned());
+        attempts
+    }
+
+    pub fn is_stuck(&self, now: SystemTime, timeout: Duration) -> bool {
+        match now.duration_since(self.last_sent) {
+            Ok(waited) => waited >= timeout,
+            Err(_) => false,
+        }
+    }
+}
+
+// A consuming wallet's payments that haven't been mined yet, by nonce
+#[derive(Debug, Default)]
+pub struct PendingTransactions {
+    by_nonce: BTreeMap<U256, PendingTransaction>,
+}
+
+impl PendingTransactions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_nonce.is_empty()
+    }
+
+    pub fn get(&self, nonce: U256) -> Option<&PendingTransaction> {
+        self.by_nonce.get(&nonce)
+    }
+
+    pub fn add(&mut self, nonce: U256, pending: PendingTransaction) {
+        self.by_nonce.insert(nonce, pending);
+    }
+
+    // The nonce after the highest one being tracked
+    pub fn next_nonce(&self) -> Option<U256> {
+        self.by_nonce
+            .keys()
+            .next_back()
+            .map(|nonce| *nonce + U256::one())
+    }
+
+    // Whether every nonce from first up to (but not including) last is being tracked
+    pub fn covers(&self, first: U256, last: U256) -> bool {
+        if last <= first {
+            return true;
+        }
+        if last - first > U256::from(self.by_nonce.len()) {
+            return false;
+        }
+        let mut nonce = first;
+        while nonce < last {
+            if !self.by_nonce.contains_key(&nonce) {
+                return false;
+            }
+            nonce += U256::one();
+        }
+        true
+    }
+
+    // Removes and returns the transactions whose nonces have been used up by mined transactions
+    pub fn take_confirmed(&mut self, confirmed_count: U256) -> Vec<(U256, PendingTransaction)> {
+        let unconfirmed = self.by_nonce.split_off(&confirmed_count);
+        std::mem::replace(&mut self.by_nonce, unconfirmed)
+            .into_iter()
+            .collect()
+    }
+
+    pub fn stuck(&self, now: SystemTime, timeout: Duration) -> Vec<(U256, PendingTransaction)> {
+        self.by_nonce
+            .iter()
+            .filter(|(_, pending)| pending.is_stuck(now, timeout))
+            .map(|(nonce, pending)| (*nonce, pending.clone()))
+            .collect()
+    }
+
+    pub fn replaced(&mut self, nonce: U256, hash: H256, fee: TransactionFee, now: SystemTime) {
+        if let Some(pending) = self.by_nonce.get_mut(&nonce) {
+            pending.replacements.push(hash);
+            pending.fee = fee;
+            pending.last_sent = now;
+        }
+    }
+
+    pub fn cancelled(&mut self, nonce: U256, hash: H256, fee: TransactionFee, now: SystemTime) {
+        if let Some(pending) = self.by_nonce.get_mut(&nonce) {
+            pending.cancellation = Some(hash);
+            pending.fee = fee;
+            pending.last_sent = now;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::blockchain_interface::to_wei;
+    use crate::test_utils::make_wallet;
+    use ethsign_crypto::Keccak256;
+
+    fn make_pending(name: &str, sent: SystemTime) -> PendingTransaction {
+        let mut payment = Payment::new(make_wallet(name), 100, H256::from(name.keccak256()));
+        payment.timestamp = sent;
+        PendingTransaction::new(
+            payment,
+            TransactionFee::Legacy {
+                gas_price: to_wei(1),
+            },
+        )
+    }
+
+    #[test]
+    fn covers_only_unbroken_runs_of_tracked_nonces() {
+        let mut subject = PendingTransactions::new();
+        subject.add(U256::from(4), make_pending("four", SystemTime::now()));
+        subject.add(U256::from(5), make_pending("five", SystemTime::now()));
+        subject.add(U256::from(7), make_pending("seven", SystemTime::now()));
+
+        assert!(subject.covers(U256::from(4), U256::from(6)));
+        assert!(subject.covers(U256::from(6), U256::from(6)));
+        assert!(!subject.covers(U256::from(4), U256::from(8)));
+        assert!(!subject.covers(U256::from(3), U256::from(5)));
+        assert!(!subject.covers(U256::from(0), U256::max_value()));
+    }
+
+    #[test]
+    fn next_nonce_follows_the_highest_tracked_nonce() {
+        let mut subject = PendingTransactions::new();
+        assert_eq!(subject.next_nonce(), None);
+        subject.add(U256::from(7), make_pending("seven", SystemTime::now()));
+        subject.add(U256::from(4), make_pending("four", SystemTime::now()));
+
+        assert_eq!(subject.next_nonce(), Some(U256::from(8)));
+    }
+
+    #[test]
+    fn take_confirmed_removes_only_nonces_below_the_confirmed_count() {
+        let mut subject = PendingTransactions::new();
+        let four = make_pending("four", SystemTime::now());
+        subject.add(U256::from(4), four.clone());
+        subject.add(U256::from(5), make_pending("five", SystemTime::now()));
+
+        let result = subject.take_confirmed(U256::from(5));
+
+        assert_eq!(result, vec![(U256::from(4), four)]);
+        assert!(subject.get(U256::from(4)).is_none());
+        assert!(subject.get(U256::from(5)).is_some());
+    }
+
+    #[test]
+    fn transactions_are_stuck_once_they_have_waited_for_the_timeout_since_last_sent() {
+        let now = SystemTime::now();
+        let timeout = Duration::from_secs(600);
+        let mut subject = PendingTransactions::new();
+        let old = make_pending("old", now - Duration::from_secs(601));
+        subject.add(U256::from(1), old.clone());
+        subject.add(
+            U256::from(2),
+            make_pending("new", now - Duration::from_secs(599)),
+        );
+        subject.add(
+            U256::from(3),
+            make_pending("sped up", now - Duration::from_secs(900)),
+        );
+        subject.replaced(
+            U256::from(3),
+            H256::from("replacement".keccak256()),
+            TransactionFee::Legacy {
+                gas_price: to_wei(2),
+            },
+            now - Duration::from_secs(60),
+        );
+
+        let result = subject.stuck(now, timeout);
+
+        assert_eq!(result, vec![(U256::from(1), old)]);
+    }
+
+    #[test]
+    fn replacements_and_cancellations_are_recorded_against_the_nonce() {
+        let now = SystemTime::now();
+        let mut subject = PendingTransactions::new();
+        let original = make_pending("original", now - Duration::from_secs(900));
+        subject.add(U256::from(9), original.clone());
+        let replacement_fee = TransactionFee::Legacy {
+            gas_price: to_wei(2),
+        };
+        let cancellation_fee = TransactionFee::Legacy {
+            gas_price: to_wei(3),
+        };
+
+        subject.replaced(
+            U256::from(9),
+            H256::from("replacement".keccak256()),
+            replacement_fee,
+            now,
+        );
+        subject.cancelled(
+            U256::from(9),
+            H256::from("cancellation".keccak256()),
+            cancellation_fee,
+            now,
+        );
+
+        let pending = subject.get(U256::from(9)).unwrap();
+        assert_eq!(
+            pending.payment_attempts(),
+            vec![
+                original.payment.transaction,
+                H256::from("replacement".keccak256())
+            ]
+        );
+        assert_eq!(
+            pending.cancellation,
+            Some(H256::from("cancellation".keccak256()))
+        );
+        assert_eq!(pending.fee, cancellation_fee);
+        assert_eq!(pending.last_sent, now);
+    }
+}
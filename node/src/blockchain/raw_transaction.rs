@@ -66,6 +66,78 @@ impl RawTransaction {
     }
 }
 
+/// Description of an EIP-1559 (type 2) Transaction, which bids a maximum fee per gas and a tip
+/// for the miner instead of a flat gas price.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Eip1559Transaction {
+    /// Nonce
+    pub nonce: U256,
+    /// Recipient (None when contract creation)
+    pub to: Option<Address>,
+    /// Transfered value
+    pub value: U256,
+    /// Tip per unit of gas for the miner, on top of the base fee
+    pub max_priority_fee_per_gas: U256,
+    /// Most that will be paid per unit of gas, base fee and tip together
+    pub max_fee_per_gas: U256,
+    /// Gas limit
+    pub gas_limit: U256,
+    /// Input data
+    pub data: Vec<u8>,
+}
+
+const EIP1559_TRANSACTION_TYPE: u8 = 2;
+
+impl Eip1559Transaction {
+    /// Signs and returns the type-prefixed, RLP-encoded transaction
+    pub fn sign(&self, wallet: &Wallet, chain_id: u8) -> Vec<u8> {
+        let hash = self.tx_hash(chain_id);
+        let sig = match wallet.sign(&hash) {
+            Ok(s) => s,
+            Err(e) => panic!("{:?}", e),
+        };
+        let mut tx = RlpStream::new();
+        tx.begin_unbounded_list();
+        self.encode(&mut tx, chain_id);
+        tx.append(&sig.v);
+        tx.append(&U256::from_big_endian(&sig.r));
+        tx.append(&U256::from_big_endian(&sig.s));
+        tx.complete_unbounded_list();
+        Self::typed(tx.out())
+    }
+
+    fn tx_hash(&self, chain_id: u8) -> Vec<u8> {
+        let mut hash = RlpStream::new();
+        hash.begin_unbounded_list();
+        self.encode(&mut hash, chain_id);
+        hash.complete_unbounded_list();
+        Self::typed(hash.out()).keccak256().to_vec()
+    }
+
+    fn encode(&self, s: &mut RlpStream, chain_id: u8) {
+        s.append(&chain_id);
+        s.append(&self.nonce);
+        s.append(&self.max_priority_fee_per_gas);
+        s.append(&self.max_fee_per_gas);
+        s.append(&self.gas_limit);
+        if let Some(ref t) = self.to {
+            s.append(t);
+        } else {
+            s.append(&vec![]);
+        }
+        s.append(&self.value);
+        s.append(&self.data);
+        // Empty access list
+        s.begin_list(0);
+    }
+
+    fn typed(payload: Vec<u8>) -> Vec<u8> {
+        let mut typed = vec![EIP1559_TRANSACTION_TYPE];
+        typed.extend(payload);
+        typed
+    }
+}
+
 fn ecdsa_sign(hash: &dyn AsRef<[u8]>, wallet: &Wallet, chain_id: u8) -> Signature {
     match wallet.sign(&hash) {
         Ok(s) => Signature {
@@ -82,6 +154,8 @@ mod tests {
     use super::*;
     use crate::blockchain::bip32::Bip32ECKeyPair;
     use ethereum_types::H256;
+    use rlp::Rlp;
+    use rustc_hex::FromHex;
 
     #[derive(Deserialize)]
     struct Signing {
@@ -178,4 +252,57 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn eip1559_transaction_is_type_prefixed_and_signed_by_the_wallet() {
+        let wallet = Wallet::from(
+            Bip32ECKeyPair::from_raw_secret(
+                &"0fde24c464a9c55a83a164ec8f31888921549da2401a1af3cd79cccf5685421a"
+                    .from_hex::<Vec<u8>>()
+                    .unwrap(),
+            )
+            .unwrap(),
+        );
+        let to = Address::from_slice(&[0x8d; 20]);
+        let subject = Eip1559Transaction {
+            nonce: U256::from(7),
+            to: Some(to),
+            value: U256::zero(),
+            max_priority_fee_per_gas: U256::from(2_000_000_000u64),
+            max_fee_per_gas: U256::from(52_000_000_000u64),
+            gas_limit: U256::from(60_000),
+            data: vec![0xa9, 0x05, 0x9c, 0xbb],
+        };
+
+        let signed = subject.sign(&wallet, 1);
+
+        assert_eq!(signed[0], 2);
+        let rlp = Rlp::new(&signed[1..]);
+        assert_eq!(rlp.item_count().unwrap(), 12);
+        assert_eq!(rlp.val_at::<u8>(0).unwrap(), 1);
+        assert_eq!(rlp.val_at::<U256>(1).unwrap(), U256::from(7));
+        assert_eq!(rlp.val_at::<U256>(2).unwrap(), U256::from(2_000_000_000u64));
+        assert_eq!(
+            rlp.val_at::<U256>(3).unwrap(),
+            U256::from(52_000_000_000u64)
+        );
+        assert_eq!(rlp.val_at::<U256>(4).unwrap(), U256::from(60_000));
+        assert_eq!(rlp.val_at::<Address>(5).unwrap(), to);
+        assert_eq!(rlp.val_at::<U256>(6).unwrap(), U256::zero());
+        assert_eq!(
+            rlp.val_at::<Vec<u8>>(7).unwrap(),
+            vec![0xa9, 0x05, 0x9c, 0xbb]
+        );
+        assert_eq!(rlp.at(8).unwrap().item_count().unwrap(), 0);
+        let mut r = [0u8; 32];
+        let mut s = [0u8; 32];
+        rlp.val_at::<U256>(10).unwrap().to_big_endian(&mut r);
+        rlp.val_at::<U256>(11).unwrap().to_big_endian(&mut s);
+        let signature = Signature {
+            v: rlp.val_at::<u8>(9).unwrap(),
+            r,
+            s,
+        };
+        assert!(wallet.verify(&signature, &subject.tx_hash(1)));
+    }
 }
@@ -1,50 +1,68 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
-use crate::accountant::{DEFAULT_PAYABLE_SCAN_INTERVAL, DEFAULT_PAYMENT_RECEIVED_SCAN_INTERVAL};
 use crate::actor_system_factory::ActorFactoryReal;
 use crate::actor_system_factory::ActorSystemFactory;
 use crate::actor_system_factory::ActorSystemFactoryReal;
+use crate::audit_log::init_audit_log;
 use crate::config_dao::ConfigDaoReal;
 use crate::crash_test_dummy::CrashTestDummy;
 use crate::database::db_initializer::{DbInitializer, DbInitializerReal};
 use crate::discriminator::DiscriminatorFactory;
+use crate::entry_dns::dns_socket_server::DNS_PORT;
+use crate::firewall::{FirewallKeeper, FirewallKeeperProcess};
+use crate::instance_lock::{InstanceLock, InstanceLockError};
 use crate::json_discriminator_factory::JsonDiscriminatorFactory;
 use crate::listener_handler::ListenerHandler;
 use crate::listener_handler::ListenerHandlerFactory;
 use crate::listener_handler::ListenerHandlerFactoryReal;
+use crate::log_rotation::LogRotation;
+use crate::node_configurator::instance::Instance;
 use crate::node_configurator::node_configurator_standard::{
     NodeConfiguratorStandardPrivileged, NodeConfiguratorStandardUnprivileged,
 };
-use crate::node_configurator::{DirsWrapper, NodeConfigurator, RealDirsWrapper};
+use crate::node_configurator::{
+    early_value, node_configurator_standard, DirsWrapper, NodeConfigurator, RealDirsWrapper,
+};
 use crate::persistent_configuration::{PersistentConfiguration, PersistentConfigurationReal};
 use crate::privilege_drop::{IdWrapper, IdWrapperReal};
 use crate::server_initializer::LoggerInitializerWrapper;
 use crate::sub_lib::accountant;
-use crate::sub_lib::accountant::AccountantConfig;
-use crate::sub_lib::blockchain_bridge::BlockchainBridgeConfig;
+use crate::sub_lib::accountant::{AccountantConfig, ConsumingProfile, ProfileSelector};
+use crate::sub_lib::blockchain_bridge::{BlockchainBridgeConfig, FeeStrategy};
 use crate::sub_lib::crash_point::CrashPoint;
 use crate::sub_lib::cryptde::CryptDE;
 use crate::sub_lib::cryptde_null::CryptDENull;
 use crate::sub_lib::cryptde_real::CryptDEReal;
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::main_tools::StdStreams;
+use crate::sub_lib::memory_guard::MemoryThresholds;
+use crate::sub_lib::neighbor_rate_limiter::NeighborRateLimits;
 use crate::sub_lib::neighborhood::NodeDescriptor;
 use crate::sub_lib::neighborhood::{NeighborhoodConfig, NeighborhoodMode};
 use crate::sub_lib::node_addr::NodeAddr;
+use crate::sub_lib::obfuscation::ObfuscationConfig;
+use crate::sub_lib::proxy_client::ExitProtections;
+use crate::sub_lib::proxy_server::PortForward;
 use crate::sub_lib::socket_server::SocketServer;
+use crate::sub_lib::spill_file::SpillConfig;
+#[cfg(feature = "otel-tracing")]
+use crate::sub_lib::stream_tracing;
 use crate::sub_lib::ui_gateway::UiGatewayConfig;
 use crate::sub_lib::ui_gateway::DEFAULT_UI_PORT;
 use crate::sub_lib::wallet::Wallet;
+use crate::ui_gateway::auth_token::{generate_ui_auth_token, write_ui_auth_token};
+use dns_utility_lib::dns_modifier_factory::{DnsModifierFactory, DnsModifierFactoryReal};
+use dns_utility_lib::repair::{repair_orphaned_subversion, DnsProbe, DnsProbeReal, RepairOutcome};
+use dns_utility_lib::subversion_record::SubversionRecord;
 use futures::try_ready;
 use itertools::Itertools;
 use log::LevelFilter;
 use std::collections::HashMap;
 use std::env::var;
 use std::fmt::{Debug, Error, Formatter};
-use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::time::Duration;
 use std::vec::Vec;
 use tokio::prelude::stream::futures_unordered::FuturesUnordered;
 use tokio::prelude::Async;
@@ -66,6 +84,11 @@ pub fn cryptde_ref() -> &'static dyn CryptDE {
 pub struct PortConfiguration {
     pub discriminator_factories: Vec<Box<dyn DiscriminatorFactory>>,
     pub is_clandestine: bool,
+    // Where a non-clandestine port listens; clandestine ports listen everywhere
+    pub loopback_ip: Ipv4Addr,
+    // Whether a client's connecting opens a stream to the exit before it has sent anything, for
+    // servers that speak first
+    pub opens_on_connect: bool,
 }
 
 impl PortConfiguration {
@@ -76,6 +99,8 @@ impl PortConfiguration {
         PortConfiguration {
             discriminator_factories,
             is_clandestine,
+            loopback_ip: Ipv4Addr::LOCALHOST,
+            opens_on_connect: false,
         }
     }
 }
@@ -233,19 +258,35 @@ pub struct BootstrapperConfig {
     pub dns_servers: Vec<SocketAddr>,
     pub neighborhood_config: NeighborhoodConfig,
     pub accountant_config: AccountantConfig,
+    pub consuming_profiles: Vec<ConsumingProfile>,
     pub crash_point: CrashPoint,
     pub clandestine_discriminator_factories: Vec<Box<dyn DiscriminatorFactory>>,
+    pub obfuscation_config: ObfuscationConfig,
     pub ui_gateway_config: UiGatewayConfig,
     pub blockchain_bridge_config: BlockchainBridgeConfig,
     pub port_configurations: HashMap<u16, PortConfiguration>,
     pub data_directory: PathBuf,
     pub cryptde_null_opt: Option<CryptDENull>,
     pub real_user: RealUser,
+    pub instance_opt: Option<Instance>,
+    pub lan_discovery: bool,
+    pub manage_firewall: bool,
+    pub kill_switch: bool,
+    pub port_forwards: Vec<PortForward>,
+    pub mail_clients: bool,
+    pub neighbor_rate_limits: NeighborRateLimits,
+    pub exit_protections: ExitProtections,
+    pub log_rotation: LogRotation,
+    pub spill_config: SpillConfig,
+    pub memory_thresholds_opt: Option<MemoryThresholds>,
 
     // These fields must be set without privilege: otherwise the database will be created as root
     pub clandestine_port_opt: Option<u16>,
     pub consuming_wallet: Option<Wallet>,
     pub earning_wallet: Wallet,
+    pub exit_earning_wallet_opt: Option<Wallet>,
+    pub database_password_opt: Option<String>,
+    pub split_dns_domains: Vec<String>,
 }
 
 impl Default for BootstrapperConfig {
@@ -263,40 +304,66 @@ impl BootstrapperConfig {
             neighborhood_config: NeighborhoodConfig {
                 mode: NeighborhoodMode::ZeroHop,
             },
-            accountant_config: AccountantConfig {
-                payable_scan_interval: Duration::from_secs(DEFAULT_PAYABLE_SCAN_INTERVAL),
-                payment_received_scan_interval: Duration::from_secs(
-                    DEFAULT_PAYMENT_RECEIVED_SCAN_INTERVAL,
-                ),
-            },
+            accountant_config: AccountantConfig::default(),
+            consuming_profiles: vec![],
             crash_point: CrashPoint::None,
             clandestine_discriminator_factories: vec![],
+            obfuscation_config: ObfuscationConfig::default(),
             ui_gateway_config: UiGatewayConfig {
                 ui_port: DEFAULT_UI_PORT,
                 node_descriptor: String::from(""),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: None,
+                dns_server: SocketAddr::from((Ipv4Addr::LOCALHOST, DNS_PORT)),
+                listeners: vec![],
+                telemetry_url_opt: None,
             },
             blockchain_bridge_config: BlockchainBridgeConfig {
-                blockchain_service_url: None,
+                blockchain_service_urls: vec![],
+                blockchain_service_ws_url: None,
                 chain_id: 3u8, /*DEFAULT_CHAIN_ID*/
                 gas_price: None,
+                fee_strategy: FeeStrategy::Normal,
+                gas_price_cap: None,
+                fee_budget: None,
             },
             port_configurations: HashMap::new(),
             data_directory: PathBuf::new(),
             cryptde_null_opt: None,
             real_user: RealUser::null(),
+            instance_opt: None,
+            lan_discovery: false,
+            manage_firewall: false,
+            kill_switch: false,
+            port_forwards: vec![],
+            mail_clients: false,
+            neighbor_rate_limits: NeighborRateLimits::default(),
+            exit_protections: ExitProtections::default(),
+            log_rotation: LogRotation::default(),
+            spill_config: SpillConfig::default(),
+            memory_thresholds_opt: None,
 
             // These fields must be set without privilege: otherwise the database will be created as root
             clandestine_port_opt: None,
             earning_wallet: accountant::DEFAULT_EARNING_WALLET.clone(),
+            exit_earning_wallet_opt: None,
             consuming_wallet: None,
+            database_password_opt: None,
+            split_dns_domains: vec![],
         }
     }
 
     pub fn merge_unprivileged(&mut self, unprivileged: BootstrapperConfig) {
         self.blockchain_bridge_config.gas_price = unprivileged.blockchain_bridge_config.gas_price;
+        self.accountant_config = unprivileged.accountant_config;
         self.clandestine_port_opt = unprivileged.clandestine_port_opt;
         self.earning_wallet = unprivileged.earning_wallet;
+        self.exit_earning_wallet_opt = unprivileged.exit_earning_wallet_opt;
         self.consuming_wallet = unprivileged.consuming_wallet;
+        self.dns_servers = unprivileged.dns_servers;
+        self.database_password_opt = unprivileged.database_password_opt;
+        self.split_dns_domains = unprivileged.split_dns_domains;
     }
 }
 
@@ -305,7 +372,10 @@ pub struct Bootstrapper {
     listener_handlers: FuturesUnordered<Box<dyn ListenerHandler<Item = (), Error = ()>>>,
     actor_system_factory: Box<dyn ActorSystemFactory>,
     logger_initializer: Box<dyn LoggerInitializerWrapper>,
+    dns_modifier_factory: Box<dyn DnsModifierFactory + Send>,
     config: BootstrapperConfig,
+    instance_lock_opt: Option<InstanceLock>,
+    firewall_keeper_opt: Option<Box<dyn FirewallKeeper>>,
 }
 
 impl Future for Bootstrapper {
@@ -334,7 +404,33 @@ impl SocketServer<BootstrapperConfig> for Bootstrapper {
             self.config.data_directory.clone(),
             &self.config.real_user,
             self.config.log_level,
+            self.config
+                .instance_opt
+                .as_ref()
+                .map(|instance| instance.name.as_str()),
+            &self.config.log_rotation,
         );
+        // Before the ports, so that a second Node says what's really wrong
+        let (instance_lock, previous_pid_opt) =
+            Bootstrapper::lock_data_directory(&self.config.data_directory);
+        self.instance_lock_opt = Some(instance_lock);
+        // A stub resolver is meant to be answering on loopback; nothing there was subverted
+        if early_value(&node_configurator_standard::app(), args, "--dns-mode")
+            != Some("stub".to_string())
+        {
+            Bootstrapper::repair_orphaned_dns_subversion(
+                self.dns_modifier_factory.as_ref(),
+                &SubversionRecord::new(),
+                &DnsProbeReal::new(),
+            );
+        }
+        // NOTE: The following lines of code are not covered by unit tests
+        if self.config.manage_firewall {
+            self.firewall_keeper_opt = Bootstrapper::launch_firewall_keeper();
+            if previous_pid_opt.is_some() {
+                self.clear_stale_firewall_rules();
+            }
+        }
         self.listener_handlers =
             FuturesUnordered::<Box<dyn ListenerHandler<Item = (), Error = ()>>>::new();
 
@@ -360,6 +456,7 @@ impl SocketServer<BootstrapperConfig> for Bootstrapper {
             NodeConfiguratorStandardUnprivileged::new(&self.config).configure(args, streams);
         self.config.merge_unprivileged(unprivileged_config);
         self.establish_clandestine_port();
+        self.keep_clandestine_port_open();
         let cryptde_ref = Bootstrapper::initialize_cryptde(
             &self.config.cryptde_null_opt,
             self.config.blockchain_bridge_config.chain_id,
@@ -370,6 +467,15 @@ impl SocketServer<BootstrapperConfig> for Bootstrapper {
             streams,
             self.config.blockchain_bridge_config.chain_id,
         );
+        self.config.ui_gateway_config.auth_token =
+            Bootstrapper::establish_ui_auth_token(&self.config.data_directory);
+        // Only unprivileged code writes the audit log, so the file belongs to the user
+        init_audit_log(&self.config.data_directory);
+        self.write_pac_files();
+        self.config.ui_gateway_config.listeners = self.listener_addrs();
+        // NOTE: The following line of code is not covered by unit tests
+        #[cfg(feature = "otel-tracing")]
+        stream_tracing::start_exporter();
         let stream_handler_pool_subs = self
             .actor_system_factory
             .make_and_start_actors(self.config.clone(), Box::new(ActorFactoryReal {}));
@@ -388,7 +494,123 @@ impl Bootstrapper {
                 FuturesUnordered::<Box<dyn ListenerHandler<Item = (), Error = ()>>>::new(),
             actor_system_factory: Box::new(ActorSystemFactoryReal {}),
             logger_initializer,
+            dns_modifier_factory: Box::new(DnsModifierFactoryReal::new()),
             config: BootstrapperConfig::new(),
+            instance_lock_opt: None,
+            firewall_keeper_opt: None,
+        }
+    }
+
+    // A Node that died with DNS subverted leaves this machine asking 127.0.0.1 about everything,
+    // with nothing there to answer. Our own DNS socket is bound but not yet answering, so the
+    // probe can't mistake this Node for the one that died.
+    fn repair_orphaned_dns_subversion(
+        dns_modifier_factory: &dyn DnsModifierFactory,
+        record: &SubversionRecord,
+        probe: &dyn DnsProbe,
+    ) {
+        let dns_modifier = match dns_modifier_factory.make() {
+            Some(dns_modifier) => dns_modifier,
+            None => return,
+        };
+        let logger = Logger::new("Bootstrapper");
+        match repair_orphaned_subversion(dns_modifier.as_ref(), record, probe) {
+            Ok(RepairOutcome::Reverted) => warning!(
+                logger,
+                "DNS was left subverted by a Node that didn't shut down properly; reverted it"
+            ),
+            Ok(_) => (),
+            Err(e) => warning!(
+                logger,
+                "DNS was left subverted by a Node that didn't shut down properly, and couldn't be reverted: {}",
+                e
+            ),
+        }
+    }
+
+    // It has to be started now, while there are privileges to give it
+    fn launch_firewall_keeper() -> Option<Box<dyn FirewallKeeper>> {
+        match FirewallKeeperProcess::launch() {
+            Ok(keeper) => Some(Box::new(keeper)),
+            Err(e) => {
+                let logger = Logger::new("Bootstrapper");
+                warning!(
+                    logger,
+                    "Couldn't start the firewall keeper, so the firewall will be left alone: {}",
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    // A Node whose keeper was killed along with it leaves its clandestine port open
+    fn clear_stale_firewall_rules(&mut self) {
+        let keeper = match self.firewall_keeper_opt.as_mut() {
+            Some(keeper) => keeper,
+            None => return,
+        };
+        let logger = Logger::new("Bootstrapper");
+        match keeper.clear_stale() {
+            Ok(outcome) => info!(logger, "Firewall: {}", outcome),
+            Err(e) => warning!(
+                logger,
+                "Couldn't clear firewall rules left behind by the Node that didn't shut down properly: {}",
+                e
+            ),
+        }
+    }
+
+    // Only a Node that other Nodes call needs its clandestine port open
+    fn keep_clandestine_port_open(&mut self) {
+        let keeper = match self.firewall_keeper_opt.as_mut() {
+            Some(keeper) => keeper,
+            None => return,
+        };
+        let clandestine_port = match self.config.neighborhood_config.mode.node_addr_opt() {
+            Some(node_addr) => match node_addr.ports().first() {
+                Some(port) => *port,
+                None => return,
+            },
+            None => return,
+        };
+        let logger = Logger::new("Bootstrapper");
+        match keeper.keep_open(clandestine_port) {
+            Ok(outcome) => info!(logger, "Firewall: {}", outcome),
+            Err(e) => warning!(
+                logger,
+                "Couldn't open clandestine port {} in the firewall, so other Nodes may not be able to reach this one: {}",
+                clandestine_port,
+                e
+            ),
+        }
+    }
+
+    // Also returns the process ID of a Node that left the lock behind without shutting down
+    fn lock_data_directory(data_directory: &Path) -> (InstanceLock, Option<u32>) {
+        match InstanceLock::acquire(data_directory) {
+            Ok((lock, Some(previous_pid))) => {
+                let logger = Logger::new("Bootstrapper");
+                info!(
+                    logger,
+                    "The Node last started in {} (process {}) is no longer running; taking over",
+                    data_directory.display(),
+                    previous_pid
+                );
+                (lock, Some(previous_pid))
+            }
+            Ok((lock, None)) => (lock, None),
+            Err(InstanceLockError::AlreadyRunning(pid_opt)) => panic!(
+                "Another Node{} is already running in {}; stop it first, or give this one a different --data-directory",
+                match pid_opt {
+                    Some(pid) => format!(" (process {})", pid),
+                    None => String::new(),
+                },
+                data_directory.display()
+            ),
+            Err(InstanceLockError::Unusable(path, e)) => {
+                panic!("Couldn't lock {}: {}", path.display(), e)
+            }
         }
     }
 
@@ -432,16 +654,102 @@ impl Bootstrapper {
         descriptor
     }
 
+    // Written without privilege, so the user running the UI can read it and nobody else can
+    fn establish_ui_auth_token(data_directory: &Path) -> String {
+        let token = generate_ui_auth_token();
+        let path = write_ui_auth_token(data_directory, &token)
+            .unwrap_or_else(|e| panic!("Cannot secure the UI port: {}", e));
+        info!(
+            Logger::new("Bootstrapper"),
+            "UIs must present the token in {:?} to connect", path
+        );
+        token
+    }
+
+    // One proxy auto-config file for each PAC profile, for a browser to point at. Without one, the
+    // profile's ports still work for a browser configured by hand, so a failure isn't fatal.
+    fn write_pac_files(&self) {
+        let logger = Logger::new("Bootstrapper");
+        self.config
+            .consuming_profiles
+            .iter()
+            .for_each(|profile| match profile.selector {
+                ProfileSelector::Pac(port) => {
+                    let loopback_ip = self
+                        .config
+                        .port_configurations
+                        .get(&port)
+                        .map(|port_configuration| port_configuration.loopback_ip)
+                        .unwrap_or(Ipv4Addr::LOCALHOST);
+                    let path = self
+                        .config
+                        .data_directory
+                        .join(format!("{}.pac", profile.name));
+                    let pac = format!(
+                        "function FindProxyForURL(url, host) {{\n    \
+                         if (url.substring(0, 5) == \"http:\") {{\n        \
+                         return \"PROXY {}:{}\";\n    }}\n    \
+                         return \"PROXY {}:{}\";\n}}\n",
+                        loopback_ip,
+                        port,
+                        loopback_ip,
+                        port + 1
+                    );
+                    match std::fs::write(&path, pac) {
+                        Ok(()) => info!(
+                            logger,
+                            "Browsers for consuming profile '{}' can use the proxy auto-config in {:?}",
+                            profile.name,
+                            path
+                        ),
+                        Err(e) => warning!(
+                            logger,
+                            "Couldn't write proxy auto-config for consuming profile '{}' to {:?}: {}",
+                            profile.name,
+                            path,
+                            e
+                        ),
+                    }
+                }
+                ProfileSelector::OriginatingPorts(_) => (),
+            });
+    }
+
+    // Where each ListenerHandler accepts connections from this machine: the privileged ports on
+    // their loopback addresses, and the clandestine port, which listens everywhere
+    fn listener_addrs(&self) -> Vec<SocketAddr> {
+        let mut addrs: Vec<SocketAddr> = self
+            .config
+            .port_configurations
+            .iter()
+            .map(|(port, port_configuration)| {
+                SocketAddr::from((port_configuration.loopback_ip, *port))
+            })
+            .collect();
+        if let Some(node_addr) = self.config.neighborhood_config.mode.node_addr_opt() {
+            addrs.extend(
+                node_addr
+                    .ports()
+                    .into_iter()
+                    .map(|port| SocketAddr::from((Ipv4Addr::LOCALHOST, port))),
+            );
+        }
+        addrs.sort();
+        addrs.dedup();
+        addrs
+    }
+
     fn establish_clandestine_port(&mut self) {
         if let NeighborhoodMode::Standard(node_addr, neighbor_configs, rate_pack) =
             &self.config.neighborhood_config.mode
         {
-            let conn = DbInitializerReal::new()
-                .initialize(
-                    &self.config.data_directory,
-                    self.config.blockchain_bridge_config.chain_id,
-                )
-                .expect("Cannot initialize database");
+            let conn =
+                DbInitializerReal::with_password_opt(self.config.database_password_opt.clone())
+                    .initialize(
+                        &self.config.data_directory,
+                        self.config.blockchain_bridge_config.chain_id,
+                    )
+                    .expect("Cannot initialize database");
             let config_dao = ConfigDaoReal::new(conn);
             let persistent_config = PersistentConfigurationReal::new(Box::new(config_dao));
             if let Some(clandestine_port) = self.config.clandestine_port_opt {
@@ -452,10 +760,7 @@ impl Bootstrapper {
             listener_handler
                 .bind_port_and_configuration(
                     clandestine_port,
-                    PortConfiguration {
-                        discriminator_factories: vec![Box::new(JsonDiscriminatorFactory::new())],
-                        is_clandestine: true,
-                    },
+                    PortConfiguration::new(vec![Box::new(JsonDiscriminatorFactory::new())], true),
                 )
                 .expect("Failed to bind ListenerHandler to clandestine port");
             self.listener_handlers.push(listener_handler);
@@ -481,6 +786,7 @@ mod tests {
     use crate::database::db_initializer::{DbInitializer, DbInitializerReal};
     use crate::discriminator::Discriminator;
     use crate::discriminator::UnmaskedChunk;
+    use crate::instance_lock::LOCK_FILE;
     use crate::node_test_utils::make_stream_handler_pool_subs_from;
     use crate::node_test_utils::TestLogOwner;
     use crate::node_test_utils::{extract_log, IdWrapperMock, MockDirsWrapper};
@@ -493,6 +799,9 @@ mod tests {
     use crate::sub_lib::neighborhood::{NeighborhoodMode, NodeDescriptor};
     use crate::sub_lib::node_addr::NodeAddr;
     use crate::sub_lib::stream_connector::ConnectionInfo;
+    use crate::test_utils::dns_modifier_mock::{
+        DnsModifierFactoryMock, DnsModifierMock, DnsProbeMock,
+    };
     use crate::test_utils::logging::init_test_logging;
     use crate::test_utils::logging::TestLog;
     use crate::test_utils::logging::TestLogHandler;
@@ -505,6 +814,7 @@ mod tests {
         assert_contains, ensure_node_home_directory_exists, rate_pack, ArgsBuilder,
     };
     use crate::test_utils::{cryptde, FakeStreamHolder, DEFAULT_CHAIN_ID};
+    use crate::ui_gateway::auth_token::read_ui_auth_token;
     use actix::Recipient;
     use actix::System;
     use lazy_static::lazy_static;
@@ -579,6 +889,13 @@ mod tests {
                 .unwrap()
         }
 
+        fn bind_free_port_and_configuration(
+            &mut self,
+            _port_configuration: PortConfiguration,
+        ) -> io::Result<u16> {
+            unimplemented!()
+        }
+
         fn bind_subs(&mut self, add_stream_sub: Recipient<AddStreamMsg>) {
             let logger = Logger::new("ListenerHandler");
             error!(logger, "bind_subscribers (add_stream_sub)");
@@ -803,10 +1120,13 @@ mod tests {
         ));
         let mut subject = Bootstrapper::new(Box::new(logger_initializer));
         subject.listener_handler_factory = Box::new(listener_handler_factory);
+        subject.dns_modifier_factory = Box::new(DnsModifierFactoryMock::new().make_result(None));
         let args = ArgsBuilder::new()
             .param("--data-directory", data_dir.to_str().unwrap())
             .param("--dns-servers", "1.1.1.1")
             .param("--ip", "2.2.2.2")
+            .param("--log-keep-files", "3")
+            .param("--log-rotate-size", "5")
             .param("--real-user", "123:456:/home/booga");
 
         subject.initialize_as_privileged(&args.into(), &mut FakeStreamHolder::new().streams());
@@ -817,11 +1137,69 @@ mod tests {
             vec![(
                 data_dir,
                 RealUser::new(Some(123), Some(456), Some("/home/booga".into())),
-                LevelFilter::Warn
+                LevelFilter::Warn,
+                None,
+                LogRotation {
+                    max_size: 5_000_000,
+                    max_age_opt: None,
+                    keep_files: 3,
+                    keep_age_opt: None,
+                    gzip: true,
+                }
             )]
         )
     }
 
+    #[test]
+    fn initialize_as_privileged_gives_an_instance_its_own_log_and_loopback_address() {
+        let _lock = INITIALIZATION.lock();
+        let data_dir = ensure_node_home_directory_exists(
+            "bootstrapper",
+            "initialize_as_privileged_gives_an_instance_its_own_log_and_loopback_address",
+        );
+        let init_params_arc = Arc::new(Mutex::new(vec![]));
+        let logger_initializer =
+            LoggerInitializerWrapperMock::new().init_parameters(&init_params_arc);
+        let mut listener_handler_factory = ListenerHandlerFactoryMock::new();
+        listener_handler_factory.add(Box::new(
+            ListenerHandlerNull::new(vec![]).bind_port_result(Ok(())),
+        ));
+        listener_handler_factory.add(Box::new(
+            ListenerHandlerNull::new(vec![]).bind_port_result(Ok(())),
+        ));
+        let mut subject = Bootstrapper::new(Box::new(logger_initializer));
+        subject.listener_handler_factory = Box::new(listener_handler_factory);
+        subject.dns_modifier_factory = Box::new(DnsModifierFactoryMock::new().make_result(None));
+        let args = ArgsBuilder::new()
+            .param("--data-directory", data_dir.to_str().unwrap())
+            .param("--dns-servers", "1.1.1.1")
+            .param("--instance-name", "alice")
+            .param("--ip", "2.2.2.2")
+            .param("--real-user", "123:456:/home/booga");
+
+        subject.initialize_as_privileged(&args.into(), &mut FakeStreamHolder::new().streams());
+
+        let init_params = init_params_arc.lock().unwrap();
+        assert_eq!(
+            *init_params,
+            vec![(
+                data_dir.join("alice"),
+                RealUser::new(Some(123), Some(456), Some("/home/booga".into())),
+                LevelFilter::Warn,
+                Some("alice".to_string()),
+                LogRotation::default()
+            )]
+        );
+        assert!(data_dir.join("alice").join(LOCK_FILE).exists());
+        let loopback_ips = subject
+            .config
+            .port_configurations
+            .values()
+            .map(|port_configuration| port_configuration.loopback_ip)
+            .collect::<Vec<Ipv4Addr>>();
+        assert_eq!(loopback_ips, vec![Instance::new("alice").loopback_ip(); 2]);
+    }
+
     #[test]
     fn initialize_as_unprivileged_passes_node_descriptor_to_ui_config() {
         let _lock = INITIALIZATION.lock();
@@ -852,6 +1230,117 @@ mod tests {
         assert!(!config.ui_gateway_config.node_descriptor.is_empty());
     }
 
+    #[test]
+    fn initialize_as_unprivileged_passes_listener_addresses_to_ui_config() {
+        let _lock = INITIALIZATION.lock();
+        let data_dir = ensure_node_home_directory_exists(
+            "bootstrapper",
+            "initialize_as_unprivileged_passes_listener_addresses_to_ui_config",
+        );
+        let mut config = BootstrapperConfig::new();
+        config.data_directory = data_dir.clone();
+        config
+            .port_configurations
+            .insert(443, PortConfiguration::new(vec![], false));
+        config
+            .port_configurations
+            .insert(80, PortConfiguration::new(vec![], false));
+        let mut subject = BootstrapperBuilder::new().config(config).build();
+
+        subject.initialize_as_unprivileged(
+            &vec![
+                "PrometheusNode".to_string(),
+                String::from("--data-directory"),
+                data_dir.to_str().unwrap().to_string(),
+            ],
+            &mut FakeStreamHolder::new().streams(),
+        );
+
+        let config = subject.config;
+        assert_eq!(
+            config.ui_gateway_config.listeners,
+            vec![
+                SocketAddr::from_str("127.0.0.1:80").unwrap(),
+                SocketAddr::from_str("127.0.0.1:443").unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn initialize_as_unprivileged_writes_a_proxy_auto_config_for_each_pac_profile() {
+        let _lock = INITIALIZATION.lock();
+        let data_dir = ensure_node_home_directory_exists(
+            "bootstrapper",
+            "initialize_as_unprivileged_writes_a_proxy_auto_config_for_each_pac_profile",
+        );
+        let mut config = BootstrapperConfig::new();
+        config.data_directory = data_dir.clone();
+        config.consuming_profiles = vec![
+            ConsumingProfile::from_str("work:10000-19999").unwrap(),
+            ConsumingProfile::from_str("personal:pac=8090").unwrap(),
+        ];
+        config.port_configurations.insert(
+            8090,
+            PortConfiguration {
+                loopback_ip: Ipv4Addr::new(127, 0, 0, 2),
+                ..PortConfiguration::new(vec![], false)
+            },
+        );
+        let mut subject = BootstrapperBuilder::new().config(config).build();
+
+        subject.initialize_as_unprivileged(
+            &vec![
+                "PrometheusNode".to_string(),
+                String::from("--data-directory"),
+                data_dir.to_str().unwrap().to_string(),
+            ],
+            &mut FakeStreamHolder::new().streams(),
+        );
+
+        assert!(!data_dir.join("work.pac").exists());
+        assert_eq!(
+            std::fs::read_to_string(data_dir.join("personal.pac")).unwrap(),
+            "function FindProxyForURL(url, host) {\n    \
+             if (url.substring(0, 5) == \"http:\") {\n        \
+             return \"PROXY 127.0.0.2:8090\";\n    }\n    \
+             return \"PROXY 127.0.0.2:8091\";\n}\n"
+        );
+    }
+
+    #[test]
+    fn initialize_as_unprivileged_writes_ui_auth_token_and_passes_it_to_ui_config() {
+        let _lock = INITIALIZATION.lock();
+        let data_dir = ensure_node_home_directory_exists(
+            "bootstrapper",
+            "initialize_as_unprivileged_writes_ui_auth_token_and_passes_it_to_ui_config",
+        );
+        let mut config = BootstrapperConfig::new();
+        config.clandestine_port_opt = Some(1234);
+        config.data_directory = data_dir.clone();
+        let mut subject = BootstrapperBuilder::new()
+            .add_listener_handler(Box::new(
+                ListenerHandlerNull::new(vec![]).bind_port_result(Ok(())),
+            ))
+            .config(config)
+            .build();
+
+        subject.initialize_as_unprivileged(
+            &vec![
+                "PrometheusNode".to_string(),
+                String::from("--data-directory"),
+                data_dir.to_str().unwrap().to_string(),
+            ],
+            &mut FakeStreamHolder::new().streams(),
+        );
+
+        let config = subject.config;
+        assert!(!config.ui_gateway_config.auth_token.is_empty());
+        assert_eq!(
+            read_ui_auth_token(&data_dir),
+            Ok(config.ui_gateway_config.auth_token)
+        );
+    }
+
     #[test]
     fn initialize_as_unprivileged_sets_gas_price_on_blockchain_config() {
         let _lock = INITIALIZATION.lock();
@@ -968,6 +1457,34 @@ mod tests {
         )
     }
 
+    #[test]
+    #[should_panic(expected = "Another Node (process ")]
+    fn initialize_as_privileged_refuses_a_data_directory_another_node_is_running_in() {
+        let _lock = INITIALIZATION.lock();
+        let data_dir = ensure_node_home_directory_exists(
+            "bootstrapper",
+            "initialize_as_privileged_refuses_a_data_directory_another_node_is_running_in",
+        );
+        let (_other_node_lock, _) = InstanceLock::acquire(&data_dir).unwrap();
+        let (listener_handler, listener_handler_log_arc) =
+            extract_log(ListenerHandlerNull::new(vec![]).bind_port_result(Ok(())));
+        let mut subject = BootstrapperBuilder::new()
+            .add_listener_handler(Box::new(listener_handler))
+            .build();
+        let mut args = make_default_cli_params();
+        args.extend(vec![
+            "--data-directory".to_string(),
+            data_dir.display().to_string(),
+        ]);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            subject.initialize_as_privileged(&args, &mut FakeStreamHolder::new().streams())
+        }));
+
+        assert!(listener_handler_log_arc.lock().unwrap().dump().is_empty());
+        std::panic::resume_unwind(result.unwrap_err());
+    }
+
     #[test]
     #[should_panic(expected = "Could not listen on port")]
     fn initialize_as_privileged_panics_if_tcp_listener_doesnt_bind() {
@@ -994,6 +1511,62 @@ mod tests {
         );
     }
 
+    #[test]
+    fn repair_orphaned_dns_subversion_reverts_dns_that_nothing_answers() {
+        init_test_logging();
+        let record = SubversionRecord::in_directory(ensure_node_home_directory_exists(
+            "bootstrapper",
+            "repair_orphaned_dns_subversion_reverts_dns_that_nothing_answers",
+        ));
+        record
+            .write(&[IpAddr::from_str("192.168.0.1").unwrap()])
+            .unwrap();
+        let dns_modifier = DnsModifierMock::new()
+            .inspect_result("127.0.0.1\n", Ok(()))
+            .revert_result(Ok(()));
+        let dns_modifier_factory =
+            DnsModifierFactoryMock::new().make_result(Some(Box::new(dns_modifier)));
+
+        Bootstrapper::repair_orphaned_dns_subversion(
+            &dns_modifier_factory,
+            &record,
+            &DnsProbeMock { answered: false },
+        );
+
+        assert_eq!(record.original_servers(), None);
+        TestLogHandler::new().exists_log_containing(
+            "WARN: Bootstrapper: DNS was left subverted by a Node that didn't shut down properly; reverted it",
+        );
+    }
+
+    #[test]
+    fn repair_orphaned_dns_subversion_says_what_dns_used_when_it_cant_revert() {
+        init_test_logging();
+        let record = SubversionRecord::in_directory(ensure_node_home_directory_exists(
+            "bootstrapper",
+            "repair_orphaned_dns_subversion_says_what_dns_used_when_it_cant_revert",
+        ));
+        record
+            .write(&[IpAddr::from_str("192.168.0.2").unwrap()])
+            .unwrap();
+        let dns_modifier = DnsModifierMock::new()
+            .inspect_result("127.0.0.1\n", Ok(()))
+            .revert_result(Err("Permission denied".to_string()));
+        let dns_modifier_factory =
+            DnsModifierFactoryMock::new().make_result(Some(Box::new(dns_modifier)));
+
+        Bootstrapper::repair_orphaned_dns_subversion(
+            &dns_modifier_factory,
+            &record,
+            &DnsProbeMock { answered: false },
+        );
+
+        assert!(record.original_servers().is_some());
+        TestLogHandler::new().exists_log_containing(
+            "WARN: Bootstrapper: DNS was left subverted by a Node that didn't shut down properly, and couldn't be reverted: Permission denied; before it was subverted, DNS used 192.168.0.2",
+        );
+    }
+
     #[test]
     fn initialize_cryptde_without_cryptde_null_uses_cryptde_real() {
         let _lock = INITIALIZATION.lock();
@@ -1530,6 +2103,150 @@ mod tests {
             .is_none());
     }
 
+    struct FirewallKeeperMock {
+        keep_open_params: Arc<Mutex<Vec<u16>>>,
+        keep_open_results: RefCell<Vec<Result<String, String>>>,
+        clear_stale_results: RefCell<Vec<Result<String, String>>>,
+    }
+
+    impl FirewallKeeper for FirewallKeeperMock {
+        fn keep_open(&mut self, port: u16) -> Result<String, String> {
+            self.keep_open_params.lock().unwrap().push(port);
+            self.keep_open_results.borrow_mut().remove(0)
+        }
+
+        fn clear_stale(&mut self) -> Result<String, String> {
+            self.clear_stale_results.borrow_mut().remove(0)
+        }
+    }
+
+    impl FirewallKeeperMock {
+        fn new() -> FirewallKeeperMock {
+            FirewallKeeperMock {
+                keep_open_params: Arc::new(Mutex::new(vec![])),
+                keep_open_results: RefCell::new(vec![]),
+                clear_stale_results: RefCell::new(vec![]),
+            }
+        }
+
+        fn clear_stale_result(self, result: Result<&str, &str>) -> FirewallKeeperMock {
+            self.clear_stale_results.borrow_mut().push(
+                result
+                    .map(|outcome| outcome.to_string())
+                    .map_err(|e| e.to_string()),
+            );
+            self
+        }
+
+        fn keep_open_params(mut self, params: &Arc<Mutex<Vec<u16>>>) -> FirewallKeeperMock {
+            self.keep_open_params = params.clone();
+            self
+        }
+
+        fn keep_open_result(self, result: Result<&str, &str>) -> FirewallKeeperMock {
+            self.keep_open_results.borrow_mut().push(
+                result
+                    .map(|outcome| outcome.to_string())
+                    .map_err(|e| e.to_string()),
+            );
+            self
+        }
+    }
+
+    fn make_bootstrapper_with_firewall_keeper(
+        mode: NeighborhoodMode,
+        keeper: FirewallKeeperMock,
+    ) -> Bootstrapper {
+        let mut config = BootstrapperConfig::new();
+        config.neighborhood_config = NeighborhoodConfig { mode };
+        let mut subject = BootstrapperBuilder::new().config(config).build();
+        subject.firewall_keeper_opt = Some(Box::new(keeper));
+        subject
+    }
+
+    #[test]
+    fn keep_clandestine_port_open_asks_the_firewall_keeper_for_the_clandestine_port() {
+        init_test_logging();
+        let keep_open_params_arc = Arc::new(Mutex::new(vec![]));
+        let keeper = FirewallKeeperMock::new()
+            .keep_open_params(&keep_open_params_arc)
+            .keep_open_result(Ok("Opened port 4321 in ufw"));
+        let mut subject = make_bootstrapper_with_firewall_keeper(
+            NeighborhoodMode::Standard(
+                NodeAddr::new(&IpAddr::from_str("1.2.3.4").unwrap(), &vec![4321]),
+                vec![],
+                rate_pack(100),
+            ),
+            keeper,
+        );
+
+        subject.keep_clandestine_port_open();
+
+        assert_eq!(*keep_open_params_arc.lock().unwrap(), vec![4321]);
+        TestLogHandler::new()
+            .exists_log_containing("INFO: Bootstrapper: Firewall: Opened port 4321 in ufw");
+    }
+
+    #[test]
+    fn keep_clandestine_port_open_warns_when_the_port_cant_be_opened() {
+        init_test_logging();
+        let keeper = FirewallKeeperMock::new()
+            .keep_open_result(Err("ufw allow 4322/tcp failed: ERROR: You need to be root"));
+        let mut subject = make_bootstrapper_with_firewall_keeper(
+            NeighborhoodMode::Standard(
+                NodeAddr::new(&IpAddr::from_str("1.2.3.4").unwrap(), &vec![4322]),
+                vec![],
+                rate_pack(100),
+            ),
+            keeper,
+        );
+
+        subject.keep_clandestine_port_open();
+
+        TestLogHandler::new().exists_log_containing(
+            "WARN: Bootstrapper: Couldn't open clandestine port 4322 in the firewall, so other Nodes may not be able to reach this one: ufw allow 4322/tcp failed: ERROR: You need to be root",
+        );
+    }
+
+    #[test]
+    fn clear_stale_firewall_rules_asks_the_firewall_keeper_to_clear_them() {
+        init_test_logging();
+        let keeper =
+            FirewallKeeperMock::new().clear_stale_result(Ok("Cleared rules left behind in ufw"));
+        let mut subject = make_bootstrapper_with_firewall_keeper(NeighborhoodMode::ZeroHop, keeper);
+
+        subject.clear_stale_firewall_rules();
+
+        TestLogHandler::new().exists_log_containing(
+            "INFO: Bootstrapper: Firewall: Cleared rules left behind in ufw",
+        );
+    }
+
+    #[test]
+    fn clear_stale_firewall_rules_warns_when_they_cant_be_cleared() {
+        init_test_logging();
+        let keeper = FirewallKeeperMock::new()
+            .clear_stale_result(Err("ufw status failed: ERROR: You need to be root"));
+        let mut subject = make_bootstrapper_with_firewall_keeper(NeighborhoodMode::ZeroHop, keeper);
+
+        subject.clear_stale_firewall_rules();
+
+        TestLogHandler::new().exists_log_containing(
+            "WARN: Bootstrapper: Couldn't clear firewall rules left behind by the Node that didn't shut down properly: ufw status failed: ERROR: You need to be root",
+        );
+    }
+
+    #[test]
+    fn keep_clandestine_port_open_leaves_the_firewall_alone_without_a_clandestine_port() {
+        let keep_open_params_arc = Arc::new(Mutex::new(vec![]));
+        let keeper = FirewallKeeperMock::new().keep_open_params(&keep_open_params_arc);
+        let mut subject = make_bootstrapper_with_firewall_keeper(NeighborhoodMode::ZeroHop, keeper);
+
+        subject.keep_clandestine_port_open();
+
+        assert!(keep_open_params_arc.lock().unwrap().is_empty());
+    }
+
     #[test]
     fn real_user_null() {
         let subject = RealUser::null();
@@ -1712,7 +2429,10 @@ mod tests {
                     Box<dyn ListenerHandler<Item = (), Error = ()>>,
                 >::new(),
                 logger_initializer: self.log_initializer_wrapper,
+                dns_modifier_factory: Box::new(DnsModifierFactoryMock::new().make_result(None)),
                 config: self.config,
+                instance_lock_opt: None,
+                firewall_keeper_opt: None,
             }
         }
     }
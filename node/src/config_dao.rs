@@ -1,8 +1,16 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 use crate::config_dao::ConfigDaoError::DatabaseError;
 use crate::database::db_initializer::ConnectionWrapper;
+#[cfg(test)]
+use crate::database::db_initializer::{DbInitializer, DbInitializerInMemory};
 use rusqlite::types::ToSql;
 use rusqlite::{OptionalExtension, Rows, NO_PARAMS};
+#[cfg(test)]
+use std::collections::HashMap;
+#[cfg(test)]
+use std::path::PathBuf;
+#[cfg(test)]
+use std::sync::Mutex;
 
 #[derive(Debug, PartialEq)]
 pub enum ConfigDaoError {
@@ -121,15 +129,104 @@ impl ConfigDaoReal {
     }
 
     fn try_update(&self, name: &str, value: &str) -> Result<(), ConfigDaoError> {
-        let mut stmt = match self
-            .conn
-            .prepare("update config set value = ? where name = ?")
-        {
-            Ok(stmt) => stmt,
-            Err(e) => return Err(ConfigDaoError::DatabaseError(format!("{}", e))),
-        };
         let params: &[&dyn ToSql] = &[&value, &name];
-        handle_update_execution(stmt.execute(params))
+        let mut result = Ok(());
+        let written = self.conn.write(&mut |tx| {
+            let mut stmt = tx
+                .prepare("update config set value = ? where name = ?")
+                .map_err(|e| format!("{}", e))?;
+            result = handle_update_execution(stmt.execute(params));
+            // Nothing to keep if the update didn't do what it should
+            result.as_ref().map_err(|e| format!("{:?}", e))?;
+            Ok(())
+        });
+        match written {
+            Err(e) if result.is_ok() => Err(ConfigDaoError::DatabaseError(e)),
+            _ => result,
+        }
+    }
+}
+
+// Keeps the configuration in a map rather than a database, for tests that need nothing else from
+// the schema. It starts with the same values a new database does.
+#[cfg(test)]
+pub struct ConfigDaoInMemory {
+    values: Mutex<HashMap<String, Option<String>>>,
+}
+
+#[cfg(test)]
+impl ConfigDao for ConfigDaoInMemory {
+    fn get_all(&self) -> Result<Vec<(String, Option<String>)>, ConfigDaoError> {
+        let mut all: Vec<(String, Option<String>)> = self
+            .values()
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        all.sort();
+        Ok(all)
+    }
+
+    fn get_string(&self, name: &str) -> Result<String, ConfigDaoError> {
+        match self.values().get(name) {
+            Some(Some(value)) => Ok(value.clone()),
+            Some(None) => Err(ConfigDaoError::NotPresent),
+            None => Err(ConfigDaoError::DatabaseError(format!(
+                "Bad schema: config row for '{}' not present",
+                name
+            ))),
+        }
+    }
+
+    fn set_string(&self, name: &str, value: &str) -> Result<(), ConfigDaoError> {
+        match self.values().get_mut(name) {
+            Some(slot) => {
+                *slot = Some(value.to_string());
+                Ok(())
+            }
+            None => Err(ConfigDaoError::NotPresent),
+        }
+    }
+
+    fn get_u64(&self, name: &str) -> Result<u64, ConfigDaoError> {
+        self.get_string(name)?
+            .parse::<u64>()
+            .map_err(|_| ConfigDaoError::TypeError)
+    }
+
+    fn set_u64(&self, name: &str, value: u64) -> Result<(), ConfigDaoError> {
+        self.set_string(name, &value.to_string())
+    }
+
+    // There's no joining a database transaction from here, so the value is set straight away
+    fn set_u64_transactional(
+        &self,
+        _transaction: &rusqlite::Transaction,
+        name: &str,
+        value: u64,
+    ) -> Result<(), ConfigDaoError> {
+        self.set_u64(name, value)
+    }
+}
+
+#[cfg(test)]
+impl ConfigDaoInMemory {
+    pub fn new(chain_id: u8) -> ConfigDaoInMemory {
+        // The schema says what a new database holds, so it's asked rather than repeated here
+        let conn = DbInitializerInMemory::new()
+            .initialize(&PathBuf::new(), chain_id)
+            .expect("Can't create in-memory database");
+        let values = ConfigDaoReal::new(conn)
+            .get_all()
+            .expect("Can't read in-memory database")
+            .into_iter()
+            .collect();
+        ConfigDaoInMemory {
+            values: Mutex::new(values),
+        }
+    }
+
+    fn values(&self) -> std::sync::MutexGuard<HashMap<String, Option<String>>> {
+        self.values.lock().expect("ConfigDaoInMemory is poisoned")
     }
 }
 
@@ -180,6 +277,58 @@ mod tests {
         assert_contains(&result, &("seed".to_string(), None));
     }
 
+    #[test]
+    fn in_memory_configuration_starts_out_like_a_new_database() {
+        let home_dir = ensure_node_home_directory_exists(
+            "node",
+            "in_memory_configuration_starts_out_like_a_new_database",
+        );
+        let real = ConfigDaoReal::new(
+            DbInitializerReal::new()
+                .initialize(&home_dir, DEFAULT_CHAIN_ID)
+                .unwrap(),
+        );
+        let subject = ConfigDaoInMemory::new(DEFAULT_CHAIN_ID);
+
+        let result = subject.get_all().unwrap();
+
+        let mut expected = real.get_all().unwrap();
+        expected.sort();
+        let without_port = |all: Vec<(String, Option<String>)>| -> Vec<(String, Option<String>)> {
+            all.into_iter()
+                .filter(|(name, _)| name != "clandestine_port")
+                .collect()
+        };
+        assert_eq!(without_port(result), without_port(expected));
+        assert!(subject.get_u64("clandestine_port").is_ok());
+    }
+
+    #[test]
+    fn in_memory_configuration_behaves_like_the_database() {
+        let subject = ConfigDaoInMemory::new(DEFAULT_CHAIN_ID);
+
+        subject.set_u64("gas_price", 42).unwrap();
+        subject.set_string("seed", "booga").unwrap();
+
+        assert_eq!(subject.get_u64("gas_price"), Ok(42));
+        assert_eq!(subject.get_string("seed"), Ok("booga".to_string()));
+        assert_eq!(subject.get_u64("seed"), Err(ConfigDaoError::TypeError));
+        assert_eq!(
+            subject.get_string("next_nonce"),
+            Err(ConfigDaoError::NotPresent)
+        );
+        assert_eq!(
+            subject.get_string("booga"),
+            Err(ConfigDaoError::DatabaseError(
+                "Bad schema: config row for 'booga' not present".to_string()
+            ))
+        );
+        assert_eq!(
+            subject.set_string("booga", "value"),
+            Err(ConfigDaoError::NotPresent)
+        );
+    }
+
     #[test]
     fn get_string_complains_about_nonexistent_row() {
         let home_dir =
@@ -355,15 +504,16 @@ mod tests {
                 .unwrap(),
         );
         {
-            let mut db = DbInitializerReal::new()
+            let db = DbInitializerReal::new()
                 .initialize(&home_dir, DEFAULT_CHAIN_ID)
                 .unwrap();
-            let transaction = db.transaction().unwrap();
 
-            subject
-                .set_u64_transactional(&transaction, &key, value)
-                .unwrap();
-            transaction.commit().unwrap();
+            db.write(&mut |transaction| {
+                subject
+                    .set_u64_transactional(transaction, &key, value)
+                    .map_err(|e| format!("{:?}", e))
+            })
+            .unwrap();
         }
 
         let result = subject.get_u64(key);
@@ -0,0 +1,597 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use crate::bootstrapper::RealUser;
+use crate::node_configurator::config_checker::clap_complaint;
+use crate::node_configurator::node_configurator_standard;
+use crate::restart_keeper::RESTART_KEEPER_VARIABLE;
+use actix::Message;
+use serde_derive::{Deserialize, Serialize};
+use std::io;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// How often the launcher looks to see whether the Node has exited
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+// Who the Node runs as, and where it keeps its data, are the launcher's business alone: the Node
+// hands its data directory over to the real user while it's still privileged
+const LAUNCHER_PARAMETERS: [&str; 5] = [
+    "chain",
+    "config-file",
+    "data-directory",
+    "real-user",
+    "run-as",
+];
+
+// What the daemon asks of the launcher: one JSON object per line on the launcher's end of the pipe
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LauncherCommand {
+    // The Node's command line, after the executable name
+    Start(Vec<String>),
+    Stop,
+}
+
+// What the launcher tells the daemon, the same way
+#[derive(Message, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LauncherReport {
+    Started(u32),
+    Exited(Option<i32>),
+    Refused(String),
+}
+
+pub trait NodeProcess: Send {
+    fn id(&self) -> u32;
+    // None while the Node is running; its exit code, if it had one, once it has stopped
+    fn exit_code(&mut self) -> Option<Option<i32>>;
+    fn kill(&mut self);
+}
+
+pub trait NodeSpawner: Send + Sync {
+    fn spawn(&self, args: &[String]) -> io::Result<Box<dyn NodeProcess>>;
+}
+
+struct NodeProcessReal {
+    child: Child,
+}
+
+impl NodeProcess for NodeProcessReal {
+    fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    fn exit_code(&mut self) -> Option<Option<i32>> {
+        match self.child.try_wait() {
+            Ok(Some(status)) => Some(status.code()),
+            Ok(None) => None,
+            Err(_) => Some(None),
+        }
+    }
+
+    // The Node keeps nothing that a sudden stop would leave inconsistent: the database has its
+    // write-ahead log
+    fn kill(&mut self) {
+        let _ = self.child.kill();
+    }
+}
+
+#[derive(Default)]
+pub struct NodeSpawnerReal {}
+
+impl NodeSpawner for NodeSpawnerReal {
+    fn spawn(&self, args: &[String]) -> io::Result<Box<dyn NodeProcess>> {
+        // The daemon starts the Node again when it asks to be restarted
+        let child = Command::new(std::env::current_exe()?)
+            .args(args)
+            .env(RESTART_KEEPER_VARIABLE, "daemon")
+            .stdin(Stdio::null())
+            .spawn()?;
+        Ok(Box::new(NodeProcessReal { child }))
+    }
+}
+
+impl NodeSpawnerReal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+// The privileged half of the daemon. It does nothing but start and stop the Node when the
+// unprivileged half asks, and always as the real user in the daemon's own data directory, whatever
+// else it's asked: the Node drops its privileges to that user as soon as it has opened its ports.
+pub struct Launcher {
+    spawner: Box<dyn NodeSpawner>,
+    real_user: RealUser,
+    chain_name: String,
+    data_directory: PathBuf,
+    reports: Arc<Mutex<Box<dyn Write + Send>>>,
+    node: Arc<Mutex<Option<Box<dyn NodeProcess>>>>,
+}
+
+impl Launcher {
+    pub fn new(
+        spawner: Box<dyn NodeSpawner>,
+        real_user: RealUser,
+        chain_name: &str,
+        data_directory: PathBuf,
+        reports: Box<dyn Write + Send>,
+    ) -> Launcher {
+        Launcher {
+            spawner,
+            real_user,
+            chain_name: chain_name.to_string(),
+            data_directory,
+            reports: Arc::new(Mutex::new(reports)),
+            node: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    // Until the daemon hangs up, which takes the Node down with it
+    pub fn run(&self, commands: &mut dyn BufRead) {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match commands.read_line(&mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => (),
+            }
+            match serde_json::from_str::<LauncherCommand>(line.trim()) {
+                Ok(LauncherCommand::Start(args)) => self.start(args),
+                Ok(LauncherCommand::Stop) => self.stop(),
+                Err(e) => report(
+                    &self.reports,
+                    LauncherReport::Refused(format!("Unintelligible command: {}", e)),
+                ),
+            }
+        }
+        self.stop();
+    }
+
+    fn start(&self, mut args: Vec<String>) {
+        if let Err(e) = check_node_args(&args) {
+            return report(&self.reports, LauncherReport::Refused(e));
+        }
+        let mut node = self.node.lock().expect("Launcher is poisoned");
+        if node.is_some() {
+            return report(
+                &self.reports,
+                LauncherReport::Refused("The Node is already running".to_string()),
+            );
+        }
+        args.extend(vec![
+            "--chain".to_string(),
+            self.chain_name.clone(),
+            "--data-directory".to_string(),
+            self.data_directory.to_string_lossy().to_string(),
+            "--real-user".to_string(),
+            real_user_triple(&self.real_user),
+        ]);
+        match self.spawner.spawn(&args) {
+            Ok(process) => {
+                let pid = process.id();
+                *node = Some(process);
+                report(&self.reports, LauncherReport::Started(pid));
+                self.watch();
+            }
+            Err(e) => report(
+                &self.reports,
+                LauncherReport::Refused(format!("Couldn't start the Node: {}", e)),
+            ),
+        }
+    }
+
+    // Whatever stopped the Node, the report comes from the watcher
+    fn stop(&self) {
+        if let Some(process) = self.node.lock().expect("Launcher is poisoned").as_mut() {
+            process.kill();
+        }
+    }
+
+    // No new Node can start until this has seen the old one exit and cleared it away
+    fn watch(&self) {
+        let node = self.node.clone();
+        let reports = self.reports.clone();
+        thread::spawn(move || loop {
+            thread::sleep(EXIT_POLL_INTERVAL);
+            let mut locked_node = node.lock().expect("Launcher is poisoned");
+            let exit_code = match locked_node.as_mut() {
+                Some(process) => match process.exit_code() {
+                    Some(exit_code) => exit_code,
+                    None => continue,
+                },
+                None => return,
+            };
+            *locked_node = None;
+            report(&reports, LauncherReport::Exited(exit_code));
+            return;
+        });
+    }
+}
+
+// The launcher is privileged, so it starts nothing but the Node, with nothing but the parameters
+// the Node knows
+fn check_node_args(args: &[String]) -> Result<(), String> {
+    match args.first() {
+        Some(subcommand) if subcommand == "run" => (),
+        _ => return Err("The launcher only runs the Node".to_string()),
+    }
+    let mut command_line = vec!["PrometheusNode".to_string()];
+    command_line.extend(args[1..].iter().cloned());
+    let matches = node_configurator_standard::app()
+        .get_matches_from_safe(command_line)
+        .map_err(|e| format!("Not a Node command line: {}", clap_complaint(&e)))?;
+    // Some of these have defaults, so they're always present
+    match LAUNCHER_PARAMETERS
+        .iter()
+        .find(|name| matches.occurrences_of(name) > 0)
+    {
+        Some(name) => Err(format!("Only the launcher chooses {}", name)),
+        None => Ok(()),
+    }
+}
+
+fn report(reports: &Arc<Mutex<Box<dyn Write + Send>>>, launcher_report: LauncherReport) {
+    let mut reports = reports.lock().expect("Launcher is poisoned");
+    let line = serde_json::to_string(&launcher_report).expect("Couldn't serialize report");
+    // If the daemon is gone, the next command read will find out
+    let _ = writeln!(reports, "{}", line).and_then(|_| reports.flush());
+}
+
+fn real_user_triple(real_user: &RealUser) -> String {
+    let part = |id: Option<i32>| id.map(|id| id.to_string()).unwrap_or_default();
+    format!(
+        "{}:{}:{}",
+        part(real_user.uid),
+        part(real_user.gid),
+        real_user
+            .home_dir
+            .as_ref()
+            .map(|home_dir| home_dir.to_string_lossy().to_string())
+            .unwrap_or_default()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+    use std::time::Instant;
+
+    // The launcher's reports go off to another thread's writer, so the test keeps a handle on it
+    #[derive(Clone, Default)]
+    struct SharedWriter {
+        bytes: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Write for SharedWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.bytes.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedWriter {
+        fn get_string(&self) -> String {
+            String::from_utf8(self.bytes.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    struct NodeProcessMock {
+        id: u32,
+        exit_codes: Arc<Mutex<Vec<Option<Option<i32>>>>>,
+        kills: Arc<Mutex<u32>>,
+    }
+
+    impl NodeProcess for NodeProcessMock {
+        fn id(&self) -> u32 {
+            self.id
+        }
+
+        fn exit_code(&mut self) -> Option<Option<i32>> {
+            let mut exit_codes = self.exit_codes.lock().unwrap();
+            if exit_codes.len() > 1 {
+                exit_codes.remove(0)
+            } else {
+                exit_codes[0]
+            }
+        }
+
+        fn kill(&mut self) {
+            *self.kills.lock().unwrap() += 1;
+            *self.exit_codes.lock().unwrap() = vec![Some(None)];
+        }
+    }
+
+    struct NodeSpawnerMock {
+        spawn_params: Arc<Mutex<Vec<Vec<String>>>>,
+        exit_codes: Vec<Option<Option<i32>>>,
+        kills: Arc<Mutex<u32>>,
+        fail: bool,
+    }
+
+    impl NodeSpawner for NodeSpawnerMock {
+        fn spawn(&self, args: &[String]) -> io::Result<Box<dyn NodeProcess>> {
+            self.spawn_params.lock().unwrap().push(args.to_vec());
+            if self.fail {
+                return Err(io::Error::from(io::ErrorKind::NotFound));
+            }
+            Ok(Box::new(NodeProcessMock {
+                id: 1234,
+                exit_codes: Arc::new(Mutex::new(self.exit_codes.clone())),
+                kills: self.kills.clone(),
+            }))
+        }
+    }
+
+    impl NodeSpawnerMock {
+        fn new(exit_codes: Vec<Option<Option<i32>>>) -> NodeSpawnerMock {
+            NodeSpawnerMock {
+                spawn_params: Arc::new(Mutex::new(vec![])),
+                exit_codes,
+                kills: Arc::new(Mutex::new(0)),
+                fail: false,
+            }
+        }
+    }
+
+    fn subject(spawner: Box<dyn NodeSpawner>, output: &SharedWriter) -> Launcher {
+        Launcher::new(
+            spawner,
+            RealUser::new(Some(123), Some(456), Some(PathBuf::from("/home/booga"))),
+            "ropsten",
+            PathBuf::from("/home/booga/data"),
+            Box::new(output.clone()),
+        )
+    }
+
+    fn commands(commands: Vec<LauncherCommand>) -> BufReader<io::Cursor<Vec<u8>>> {
+        let text: String = commands
+            .into_iter()
+            .map(|command| format!("{}\n", serde_json::to_string(&command).unwrap()))
+            .collect();
+        BufReader::new(io::Cursor::new(text.into_bytes()))
+    }
+
+    fn reports_from(output: &SharedWriter) -> Vec<LauncherReport> {
+        output
+            .get_string()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    fn wait_for_reports(output: &SharedWriter, count: usize) -> Vec<LauncherReport> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let reports = reports_from(output);
+            if reports.len() >= count || Instant::now() > deadline {
+                return reports;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    #[test]
+    fn the_node_is_started_as_the_real_user_and_its_exit_is_reported() {
+        let spawner = NodeSpawnerMock::new(vec![None, Some(Some(1))]);
+        let spawn_params = spawner.spawn_params.clone();
+        let output = SharedWriter::default();
+        let subject = subject(Box::new(spawner), &output);
+
+        subject.start(vec![
+            "run".to_string(),
+            "--ip".to_string(),
+            "1.2.3.4".to_string(),
+        ]);
+
+        assert_eq!(
+            wait_for_reports(&output, 2),
+            vec![
+                LauncherReport::Started(1234),
+                LauncherReport::Exited(Some(1))
+            ]
+        );
+        assert_eq!(
+            *spawn_params.lock().unwrap(),
+            vec![vec![
+                "run".to_string(),
+                "--ip".to_string(),
+                "1.2.3.4".to_string(),
+                "--chain".to_string(),
+                "ropsten".to_string(),
+                "--data-directory".to_string(),
+                "/home/booga/data".to_string(),
+                "--real-user".to_string(),
+                "123:456:/home/booga".to_string(),
+            ]]
+        );
+    }
+
+    #[test]
+    fn the_daemon_cannot_choose_who_the_node_runs_as() {
+        let spawner = NodeSpawnerMock::new(vec![None]);
+        let spawn_params = spawner.spawn_params.clone();
+        let output = SharedWriter::default();
+        let subject = subject(Box::new(spawner), &output);
+
+        subject.start(vec!["run".to_string(), "--real-user=0:0:/root".to_string()]);
+
+        assert_eq!(
+            reports_from(&output),
+            vec![LauncherReport::Refused(
+                "Only the launcher chooses real-user".to_string()
+            )]
+        );
+        assert!(spawn_params.lock().unwrap().is_empty());
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn the_daemon_cannot_have_the_node_run_as_someone_else_either() {
+        let spawner = NodeSpawnerMock::new(vec![None]);
+        let spawn_params = spawner.spawn_params.clone();
+        let output = SharedWriter::default();
+        let subject = subject(Box::new(spawner), &output);
+
+        subject.start(vec![
+            "run".to_string(),
+            "--run-as".to_string(),
+            "0".to_string(),
+        ]);
+
+        assert_eq!(
+            reports_from(&output),
+            vec![LauncherReport::Refused(
+                "Only the launcher chooses run-as".to_string()
+            )]
+        );
+        assert!(spawn_params.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn the_daemon_cannot_choose_where_the_node_keeps_its_data() {
+        let spawner = NodeSpawnerMock::new(vec![None]);
+        let spawn_params = spawner.spawn_params.clone();
+        let output = SharedWriter::default();
+        let subject = subject(Box::new(spawner), &output);
+
+        subject.start(vec![
+            "run".to_string(),
+            "--data-directory".to_string(),
+            "/etc".to_string(),
+        ]);
+        subject.start(vec![
+            "run".to_string(),
+            "--config-file".to_string(),
+            "/etc/shadow".to_string(),
+        ]);
+        subject.start(vec![
+            "run".to_string(),
+            "--chain".to_string(),
+            "ropsten".to_string(),
+        ]);
+
+        assert_eq!(
+            reports_from(&output),
+            vec![
+                LauncherReport::Refused("Only the launcher chooses data-directory".to_string()),
+                LauncherReport::Refused("Only the launcher chooses config-file".to_string()),
+                LauncherReport::Refused("Only the launcher chooses chain".to_string()),
+            ]
+        );
+        assert!(spawn_params.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn the_launcher_starts_nothing_but_the_node_with_the_nodes_own_parameters() {
+        let spawner = NodeSpawnerMock::new(vec![None]);
+        let spawn_params = spawner.spawn_params.clone();
+        let output = SharedWriter::default();
+        let subject = subject(Box::new(spawner), &output);
+
+        subject.start(vec!["restore-data".to_string(), "/etc".to_string()]);
+        subject.start(vec![]);
+        subject.start(vec!["run".to_string(), "--booga".to_string()]);
+
+        let reports = reports_from(&output);
+        assert_eq!(
+            reports[0..2].to_vec(),
+            vec![
+                LauncherReport::Refused("The launcher only runs the Node".to_string()),
+                LauncherReport::Refused("The launcher only runs the Node".to_string())
+            ]
+        );
+        match &reports[2] {
+            LauncherReport::Refused(message) => assert!(
+                message.starts_with("Not a Node command line: ") && message.contains("--booga"),
+                "{}",
+                message
+            ),
+            x => panic!("Expected refusal, got {:?}", x),
+        }
+        assert!(spawn_params.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn only_one_node_runs_at_a_time() {
+        let spawner = NodeSpawnerMock::new(vec![None]);
+        let output = SharedWriter::default();
+        let subject = subject(Box::new(spawner), &output);
+
+        subject.start(vec!["run".to_string()]);
+        subject.start(vec!["run".to_string()]);
+
+        assert_eq!(
+            reports_from(&output),
+            vec![
+                LauncherReport::Started(1234),
+                LauncherReport::Refused("The Node is already running".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn a_node_that_cannot_start_is_reported() {
+        let mut spawner = NodeSpawnerMock::new(vec![None]);
+        spawner.fail = true;
+        let output = SharedWriter::default();
+        let subject = subject(Box::new(spawner), &output);
+
+        subject.start(vec!["run".to_string()]);
+
+        match reports_from(&output).as_slice() {
+            [LauncherReport::Refused(ref message)] => {
+                assert!(
+                    message.starts_with("Couldn't start the Node: "),
+                    "{}",
+                    message
+                )
+            }
+            x => panic!("Expected one refusal, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn the_node_is_stopped_on_command_and_when_the_daemon_hangs_up() {
+        let spawner = NodeSpawnerMock::new(vec![None]);
+        let kills = spawner.kills.clone();
+        let output = SharedWriter::default();
+        let subject = subject(Box::new(spawner), &output);
+
+        subject.run(&mut commands(vec![
+            LauncherCommand::Start(vec!["run".to_string()]),
+            LauncherCommand::Stop,
+        ]));
+
+        assert_eq!(
+            wait_for_reports(&output, 2),
+            vec![LauncherReport::Started(1234), LauncherReport::Exited(None)]
+        );
+        assert!(*kills.lock().unwrap() >= 1);
+    }
+
+    #[test]
+    fn unintelligible_commands_are_refused() {
+        let output = SharedWriter::default();
+        let subject = subject(Box::new(NodeSpawnerMock::new(vec![None])), &output);
+
+        subject.run(&mut BufReader::new(io::Cursor::new(b"booga\n".to_vec())));
+
+        match reports_from(&output).as_slice() {
+            [LauncherReport::Refused(ref message)] => {
+                assert!(
+                    message.starts_with("Unintelligible command: "),
+                    "{}",
+                    message
+                )
+            }
+            x => panic!("Expected one refusal, got {:?}", x),
+        }
+    }
+}
@@ -0,0 +1,874 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+pub mod launcher;
+
+use crate::blockchain::blockchain_interface::chain_name_from_id;
+use crate::bootstrapper::RealUser;
+use crate::daemon::launcher::{Launcher, LauncherCommand, LauncherReport, NodeSpawnerReal};
+use crate::node_configurator::config_checker::clap_complaint;
+use crate::node_configurator::node_configurator_standard;
+use crate::node_configurator::node_configurator_standard::validators::validate_ui_port;
+use crate::node_configurator::{
+    app_head, chain_arg, data_directory_arg, make_multi_config, real_user_arg,
+    real_user_data_directory_and_chain_id,
+};
+use crate::privilege_drop::{PrivilegeDropper, PrivilegeDropperReal};
+use crate::restart_keeper::RESTART_EXIT_CODE;
+use crate::server_initializer::log_to_stderr;
+use crate::sub_lib::logger::Logger;
+use crate::sub_lib::main_tools::StdStreams;
+use crate::sub_lib::ui_gateway::{
+    DaemonNodeState, DaemonStatus, FromUiMessage, UiMessage, DEFAULT_DAEMON_PORT,
+};
+use crate::ui_gateway::auth_token::{
+    authenticate_client, generate_ui_auth_token, write_auth_token,
+};
+use crate::ui_gateway::ui_traffic_converter::{UiTrafficConverter, UiTrafficConverterReal};
+use crate::ui_gateway::websocket_supervisor::{WebSocketSupervisor, WebSocketSupervisorReal};
+use actix::{Actor, AsyncContext, Context, Handler, System};
+use clap::{value_t, App, Arg};
+use lazy_static::lazy_static;
+use log::LevelFilter;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const DAEMON_AUTH_TOKEN_FILE: &str = "daemon-auth-token";
+
+const DAEMON_HELP: &str =
+    "Stay in the background, waiting for UIs on the daemon port to set up, start and stop the Node. Started \
+     as root, by the system or with sudo, the daemon itself runs as the real user, but starts the Node with the \
+     privileges it needs, so the UI never has to ask for them. If the Node stops without being told to, the \
+     daemon starts it again.";
+const DAEMON_PORT_HELP: &str =
+    "The port on localhost where the daemon listens for UIs. A UI's first message must be the token in the \
+     file daemon-auth-token in the data directory.";
+
+// The launcher gives the Node these itself, so a UI can't send the Node to another user or directory
+const RESERVED_PARAMETERS: [&str; 5] = [
+    "chain",
+    "config-file",
+    "data-directory",
+    "real-user",
+    "run-as",
+];
+
+const MIN_RESTART_DELAY: Duration = Duration::from_secs(1);
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
+// A Node that ran this long before it stopped was doing fine, and is started again promptly
+const STABLE_RUN: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    static ref DEFAULT_DAEMON_PORT_VALUE: String = DEFAULT_DAEMON_PORT.to_string();
+}
+
+pub fn app() -> App<'static, 'static> {
+    app_head()
+        .about(DAEMON_HELP)
+        .arg(chain_arg())
+        .arg(data_directory_arg())
+        .arg(real_user_arg())
+        .arg(
+            Arg::with_name("daemon-port")
+                .long("daemon-port")
+                .value_name("DAEMON-PORT")
+                .takes_value(true)
+                .default_value(&DEFAULT_DAEMON_PORT_VALUE)
+                .validator(validate_ui_port)
+                .help(DAEMON_PORT_HELP),
+        )
+        // Marks the unprivileged half, which the privileged half starts
+        .arg(
+            Arg::with_name("supervise")
+                .long("supervise")
+                .takes_value(false)
+                .hidden(true),
+        )
+}
+
+// The daemon is two processes. This one keeps whatever privileges it was started with, and does
+// nothing but start and stop the Node; the other, started here as the real user, talks to UIs and
+// decides when the Node should run. They speak over the second one's standard input and output.
+pub fn daemon(args: &Vec<String>, streams: &mut StdStreams<'_>) -> i32 {
+    let multi_config = make_multi_config(&app(), args, None);
+    let (real_user, data_directory, chain_id) =
+        real_user_data_directory_and_chain_id(&multi_config);
+    if multi_config.arg_matches().is_present("supervise") {
+        let daemon_port = value_m!(multi_config, "daemon-port", u16)
+            .expect("daemon-port is not properly defaulted");
+        return supervise(daemon_port, data_directory, streams);
+    }
+    if let Err(e) = fs::create_dir_all(&data_directory) {
+        writeln!(
+            streams.stderr,
+            "Couldn't create data directory {}: {}",
+            data_directory.display(),
+            e
+        )
+        .expect("Couldn't write to stderr");
+        return 1;
+    }
+    PrivilegeDropperReal::new().chown(&data_directory, &real_user);
+    match launch(
+        args,
+        real_user,
+        chain_name_from_id(chain_id),
+        data_directory,
+    ) {
+        Ok(exit_code) => exit_code,
+        Err(e) => {
+            writeln!(streams.stderr, "Couldn't start the daemon: {}", e)
+                .expect("Couldn't write to stderr");
+            1
+        }
+    }
+}
+
+fn launch(
+    args: &Vec<String>,
+    real_user: RealUser,
+    chain_name: &str,
+    data_directory: PathBuf,
+) -> io::Result<i32> {
+    let mut command = Command::new(std::env::current_exe()?);
+    command
+        .arg("daemon")
+        .args(args.iter().skip(1))
+        .arg("--supervise")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped());
+    run_as(&mut command, &real_user);
+    let mut supervisor = command.spawn()?;
+    let reports = supervisor.stdin.take().expect("Supervisor has no input");
+    let mut commands = BufReader::new(supervisor.stdout.take().expect("Supervisor has no output"));
+    Launcher::new(
+        Box::new(NodeSpawnerReal::new()),
+        real_user,
+        chain_name,
+        data_directory,
+        Box::new(reports),
+    )
+    .run(&mut commands);
+    Ok(supervisor.wait()?.code().unwrap_or(1))
+}
+
+#[cfg(not(target_os = "windows"))]
+fn run_as(command: &mut Command, real_user: &RealUser) {
+    use std::os::unix::process::CommandExt;
+    command
+        .uid(real_user.uid.expect("User-ID logic not working") as u32)
+        .gid(real_user.gid.expect("Group-ID logic not working") as u32);
+}
+
+// Windows doesn't need it: everything runs as administrator the whole way
+#[cfg(target_os = "windows")]
+fn run_as(_command: &mut Command, _real_user: &RealUser) {}
+
+// Standard output belongs to the launcher here, so only errors go to the streams
+fn supervise(daemon_port: u16, data_directory: PathBuf, streams: &mut StdStreams<'_>) -> i32 {
+    log_to_stderr(LevelFilter::Info);
+    let auth_token = generate_ui_auth_token();
+    if let Err(e) = write_auth_token(&data_directory, DAEMON_AUTH_TOKEN_FILE, &auth_token) {
+        writeln!(streams.stderr, "{}", e).expect("Couldn't write to stderr");
+        return 1;
+    }
+    let system = System::new("daemon");
+    let daemon = Daemon::new(
+        daemon_port,
+        data_directory,
+        auth_token,
+        Box::new(LauncherClientReal {}),
+    )
+    .start();
+    let report_sub = daemon.recipient::<LauncherReport>();
+    let system_handle = System::current();
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        stdin
+            .lock()
+            .lines()
+            .filter_map(|line| line.ok())
+            .filter_map(|line| serde_json::from_str::<LauncherReport>(&line).ok())
+            .for_each(|report| report_sub.try_send(report).expect("Daemon is dead"));
+        // No launcher, no Node
+        system_handle.stop_with_code(1);
+    });
+    system.run()
+}
+
+// Everything after the executable name but the chain, data directory and real user, which are the
+// launcher's to add
+fn node_args(setup: &BTreeMap<String, String>) -> Vec<String> {
+    let mut args = vec![];
+    setup.iter().for_each(|(name, value)| {
+        args.push(format!("--{}", name));
+        if !value.is_empty() {
+            args.push(value.clone());
+        }
+    });
+    args
+}
+
+pub trait LauncherClient: Send {
+    fn send(&self, command: LauncherCommand);
+}
+
+struct LauncherClientReal {}
+
+impl LauncherClient for LauncherClientReal {
+    fn send(&self, command: LauncherCommand) {
+        let line = serde_json::to_string(&command).expect("Couldn't serialize command");
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        writeln!(stdout, "{}", line)
+            .and_then(|_| stdout.flush())
+            .expect("The launcher is gone");
+    }
+}
+
+pub struct Daemon {
+    port: u16,
+    data_directory: PathBuf,
+    setup: BTreeMap<String, String>,
+    node_state: DaemonNodeState,
+    node_pid_opt: Option<u32>,
+    node_started_at_opt: Option<Instant>,
+    // Whether the Node should be running: it was told to start and hasn't been told to stop
+    running_wanted: bool,
+    restarts: u32,
+    restart_delay: Duration,
+    // UIs waiting to hear whether the Node started
+    starting_clients: Vec<u64>,
+    launcher: Box<dyn LauncherClient>,
+    converter: Box<dyn UiTrafficConverter>,
+    websocket_supervisor: Option<Box<dyn WebSocketSupervisor>>,
+    auth_token: String,
+    authenticated_clients: HashSet<u64>,
+    logger: Logger,
+}
+
+impl Actor for Daemon {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        if self.websocket_supervisor.is_none() {
+            self.websocket_supervisor = Some(Box::new(WebSocketSupervisorReal::new(
+                self.port,
+                ctx.address().recipient::<FromUiMessage>(),
+            )));
+        }
+        info!(
+            self.logger,
+            "Waiting for UIs on port {} for the Node in {}",
+            self.port,
+            self.data_directory.display()
+        );
+    }
+}
+
+impl Handler<FromUiMessage> for Daemon {
+    type Result = ();
+
+    fn handle(&mut self, msg: FromUiMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let unmarshalled = self.converter.unmarshal(&msg.json);
+        if !self.authenticated_clients.contains(&msg.client_id) {
+            return self.authenticate(msg.client_id, unmarshalled);
+        }
+        match unmarshalled {
+            Err(e) => warning!(
+                self.logger,
+                "Error unmarshalling message from UI - ignoring: '{}'",
+                e
+            ),
+            Ok(ui_message) => self.handle_ui_message(msg.client_id, ui_message),
+        }
+    }
+}
+
+impl Handler<LauncherReport> for Daemon {
+    type Result = ();
+
+    fn handle(&mut self, msg: LauncherReport, ctx: &mut Self::Context) -> Self::Result {
+        if let Some(delay) = self.handle_report(msg) {
+            ctx.run_later(delay, |daemon, _| daemon.restart());
+        }
+    }
+}
+
+impl Daemon {
+    pub fn new(
+        port: u16,
+        data_directory: PathBuf,
+        auth_token: String,
+        launcher: Box<dyn LauncherClient>,
+    ) -> Daemon {
+        Daemon {
+            port,
+            data_directory,
+            setup: BTreeMap::new(),
+            node_state: DaemonNodeState::Stopped,
+            node_pid_opt: None,
+            node_started_at_opt: None,
+            running_wanted: false,
+            restarts: 0,
+            restart_delay: MIN_RESTART_DELAY,
+            starting_clients: vec![],
+            launcher,
+            converter: Box::new(UiTrafficConverterReal::new()),
+            websocket_supervisor: None,
+            auth_token,
+            authenticated_clients: HashSet::new(),
+            logger: Logger::new("Daemon"),
+        }
+    }
+
+    fn authenticate(&mut self, client_id: u64, unmarshalled: Result<UiMessage, String>) {
+        let websocket_supervisor = self
+            .websocket_supervisor
+            .as_ref()
+            .expect("WebSocketSupervisor is unbound");
+        authenticate_client(
+            &self.auth_token,
+            client_id,
+            unmarshalled,
+            &mut self.authenticated_clients,
+            websocket_supervisor.as_ref(),
+            &self.logger,
+        );
+    }
+
+    fn handle_ui_message(&mut self, client_id: u64, ui_message: UiMessage) {
+        match ui_message {
+            UiMessage::Authenticate(_) => {
+                debug!(self.logger, "UI {} authenticated again", client_id)
+            }
+            UiMessage::DaemonSetup(setup) => {
+                let response = self.set_up(setup);
+                self.send(client_id, UiMessage::DaemonSetupResponse(response));
+            }
+            UiMessage::DaemonStart => self.start(client_id),
+            UiMessage::DaemonStop => {
+                let response = self.stop();
+                self.send(client_id, UiMessage::DaemonStopResponse(response));
+            }
+            UiMessage::GetDaemonStatus => {
+                self.send(client_id, UiMessage::DaemonStatus(self.status()))
+            }
+            // Not shown: it could be the wallet password
+            _ => warning!(
+                self.logger,
+                "UI {} sent the daemon a message meant for the Node - ignoring",
+                client_id
+            ),
+        }
+    }
+
+    fn set_up(&mut self, setup: BTreeMap<String, String>) -> Result<(), String> {
+        if let Some(name) = setup
+            .keys()
+            .find(|name| RESERVED_PARAMETERS.contains(&name.as_str()))
+        {
+            return Err(format!(
+                "The daemon decides {} itself; give it to the daemon instead",
+                name
+            ));
+        }
+        // Checked as the Node will check it, short of starting the Node
+        let mut command_line = vec!["PrometheusNode".to_string()];
+        command_line.extend(node_args(&setup));
+        node_configurator_standard::app()
+            .get_matches_from_safe(command_line)
+            .map_err(|e| clap_complaint(&e))?;
+        info!(
+            self.logger,
+            "Set up the Node with {}",
+            setup.keys().cloned().collect::<Vec<String>>().join(", ")
+        );
+        self.setup = setup;
+        Ok(())
+    }
+
+    fn start(&mut self, client_id: u64) {
+        if self.running_wanted {
+            return self.send(
+                client_id,
+                UiMessage::DaemonStartResponse(
+                    Err("The Node has already been started".to_string()),
+                ),
+            );
+        }
+        self.running_wanted = true;
+        self.restarts = 0;
+        self.restart_delay = MIN_RESTART_DELAY;
+        self.starting_clients.push(client_id);
+        self.launch();
+    }
+
+    fn stop(&mut self) -> Result<(), String> {
+        if !self.running_wanted {
+            return Err("The Node hasn't been started".to_string());
+        }
+        self.running_wanted = false;
+        match self.node_state {
+            // The restart will find it isn't wanted
+            DaemonNodeState::Restarting => self.node_state = DaemonNodeState::Stopped,
+            _ => self.launcher.send(LauncherCommand::Stop),
+        }
+        info!(self.logger, "Stopping the Node");
+        Ok(())
+    }
+
+    fn restart(&mut self) {
+        if self.running_wanted && self.node_state == DaemonNodeState::Restarting {
+            self.launch();
+        }
+    }
+
+    fn launch(&mut self) {
+        self.node_state = DaemonNodeState::Starting;
+        let mut args = vec!["run".to_string()];
+        args.extend(node_args(&self.setup));
+        self.launcher.send(LauncherCommand::Start(args));
+    }
+
+    // Returns how long to wait before starting the Node again, if it should be
+    fn handle_report(&mut self, report: LauncherReport) -> Option<Duration> {
+        let restart_delay_opt = match report {
+            LauncherReport::Started(pid) => {
+                info!(self.logger, "The Node is running as process {}", pid);
+                self.node_state = DaemonNodeState::Running;
+                self.node_pid_opt = Some(pid);
+                self.node_started_at_opt = Some(Instant::now());
+                self.answer_starting_clients(Ok(()));
+                None
+            }
+            LauncherReport::Refused(reason) => {
+                error!(self.logger, "The Node couldn't be started: {}", reason);
+                if self.node_state == DaemonNodeState::Starting {
+                    self.node_state = DaemonNodeState::Stopped;
+                    self.running_wanted = false;
+                }
+                self.answer_starting_clients(Err(reason));
+                None
+            }
+            LauncherReport::Exited(exit_code) => self.exited(exit_code),
+        };
+        self.broadcast_status();
+        restart_delay_opt
+    }
+
+    fn exited(&mut self, exit_code: Option<i32>) -> Option<Duration> {
+        self.node_pid_opt = None;
+        let ran_for = self
+            .node_started_at_opt
+            .take()
+            .map(|started_at| started_at.elapsed())
+            .unwrap_or_default();
+        if !self.running_wanted {
+            info!(self.logger, "The Node has stopped");
+            self.node_state = DaemonNodeState::Stopped;
+            return None;
+        }
+        self.node_state = DaemonNodeState::Restarting;
+        // The Node was told to restart, and can't open its ports again itself
+        if exit_code == Some(RESTART_EXIT_CODE) {
+            info!(self.logger, "The Node is restarting");
+            return Some(Duration::from_secs(0));
+        }
+        if ran_for >= STABLE_RUN {
+            self.restart_delay = MIN_RESTART_DELAY;
+        }
+        let delay = self.restart_delay;
+        self.restart_delay = (delay * 2).min(MAX_RESTART_DELAY);
+        self.restarts += 1;
+        warning!(
+            self.logger,
+            "The Node stopped unexpectedly with exit code {:?}; starting it again in {:?}",
+            exit_code,
+            delay
+        );
+        Some(delay)
+    }
+
+    fn answer_starting_clients(&mut self, response: Result<(), String>) {
+        let clients = std::mem::replace(&mut self.starting_clients, vec![]);
+        clients.into_iter().for_each(|client_id| {
+            self.send(client_id, UiMessage::DaemonStartResponse(response.clone()))
+        });
+    }
+
+    // So UIs hear about it when the Node starts, stops, or restarts without being asked
+    fn broadcast_status(&mut self) {
+        let websocket_supervisor = self
+            .websocket_supervisor
+            .as_ref()
+            .expect("WebSocketSupervisor is unbound");
+        self.authenticated_clients
+            .retain(|client_id| websocket_supervisor.is_connected(*client_id));
+        let clients: Vec<u64> = self.authenticated_clients.iter().cloned().collect();
+        clients
+            .into_iter()
+            .for_each(|client_id| self.send(client_id, UiMessage::DaemonStatus(self.status())));
+    }
+
+    fn status(&self) -> DaemonStatus {
+        DaemonStatus {
+            node_state: self.node_state,
+            node_pid: self.node_pid_opt,
+            restarts: self.restarts,
+            setup: self.setup.keys().cloned().collect(),
+        }
+    }
+
+    // A UI that has gone away in the meantime isn't told
+    fn send(&self, client_id: u64, ui_message: UiMessage) {
+        let websocket_supervisor = self
+            .websocket_supervisor
+            .as_ref()
+            .expect("WebSocketSupervisor is unbound");
+        if websocket_supervisor.is_connected(client_id) {
+            let marshalled = self
+                .converter
+                .marshal(ui_message)
+                .expect("Internal error: failed to marshal UiMessage");
+            websocket_supervisor.send(client_id, &marshalled);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::logging::{init_test_logging, TestLogHandler};
+    use std::sync::{Arc, Mutex};
+
+    struct LauncherClientMock {
+        send_params: Arc<Mutex<Vec<LauncherCommand>>>,
+    }
+
+    impl LauncherClient for LauncherClientMock {
+        fn send(&self, command: LauncherCommand) {
+            self.send_params.lock().unwrap().push(command);
+        }
+    }
+
+    struct WebSocketSupervisorMock {
+        send_params: Arc<Mutex<Vec<(u64, String)>>>,
+        disconnect_params: Arc<Mutex<Vec<u64>>>,
+        disconnected_clients: HashSet<u64>,
+    }
+
+    impl WebSocketSupervisor for WebSocketSupervisorMock {
+        fn send(&self, client_id: u64, message_json: &str) {
+            self.send_params
+                .lock()
+                .unwrap()
+                .push((client_id, message_json.to_string()));
+        }
+
+        fn is_connected(&self, client_id: u64) -> bool {
+            !self.disconnected_clients.contains(&client_id)
+        }
+
+        fn disconnect(&self, client_id: u64) {
+            self.disconnect_params.lock().unwrap().push(client_id);
+        }
+    }
+
+    struct Fixture {
+        subject: Daemon,
+        launcher_commands: Arc<Mutex<Vec<LauncherCommand>>>,
+        sent: Arc<Mutex<Vec<(u64, String)>>>,
+        disconnected: Arc<Mutex<Vec<u64>>>,
+    }
+
+    impl Fixture {
+        fn new() -> Fixture {
+            let launcher_commands = Arc::new(Mutex::new(vec![]));
+            let sent = Arc::new(Mutex::new(vec![]));
+            let disconnected = Arc::new(Mutex::new(vec![]));
+            let mut subject = Daemon::new(
+                5332,
+                PathBuf::from("/home/booga/data"),
+                "secret".to_string(),
+                Box::new(LauncherClientMock {
+                    send_params: launcher_commands.clone(),
+                }),
+            );
+            subject.websocket_supervisor = Some(Box::new(WebSocketSupervisorMock {
+                send_params: sent.clone(),
+                disconnect_params: disconnected.clone(),
+                disconnected_clients: HashSet::new(),
+            }));
+            subject.authenticated_clients.insert(1);
+            Fixture {
+                subject,
+                launcher_commands,
+                sent,
+                disconnected,
+            }
+        }
+
+        fn take_sent(&self) -> Vec<(u64, UiMessage)> {
+            let converter = UiTrafficConverterReal::new();
+            self.sent
+                .lock()
+                .unwrap()
+                .drain(..)
+                .map(|(client_id, json)| (client_id, converter.unmarshal(&json).unwrap()))
+                .collect()
+        }
+
+        fn take_launcher_commands(&self) -> Vec<LauncherCommand> {
+            self.launcher_commands.lock().unwrap().drain(..).collect()
+        }
+    }
+
+    fn setup(pairs: Vec<(&str, &str)>) -> BTreeMap<String, String> {
+        pairs
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn status(f: &Fixture) -> DaemonStatus {
+        f.subject.status()
+    }
+
+    #[test]
+    fn uis_without_the_token_are_disconnected() {
+        let mut f = Fixture::new();
+
+        f.subject
+            .authenticate(2, Ok(UiMessage::Authenticate("wrong".to_string())));
+        f.subject
+            .authenticate(3, Ok(UiMessage::Authenticate("secret".to_string())));
+
+        assert_eq!(*f.disconnected.lock().unwrap(), vec![2]);
+        assert!(f.subject.authenticated_clients.contains(&3));
+        assert!(!f.subject.authenticated_clients.contains(&2));
+    }
+
+    #[test]
+    fn a_node_that_is_set_up_and_started_runs_with_the_setup() {
+        let mut f = Fixture::new();
+
+        f.subject.handle_ui_message(
+            1,
+            UiMessage::DaemonSetup(setup(vec![
+                ("dns-servers", "1.1.1.1"),
+                ("ip", "1.2.3.4"),
+                ("neighborhood-mode", "standard"),
+            ])),
+        );
+        f.subject.handle_ui_message(1, UiMessage::DaemonStart);
+
+        assert_eq!(
+            f.take_launcher_commands(),
+            vec![LauncherCommand::Start(
+                vec![
+                    "run",
+                    "--dns-servers",
+                    "1.1.1.1",
+                    "--ip",
+                    "1.2.3.4",
+                    "--neighborhood-mode",
+                    "standard",
+                ]
+                .into_iter()
+                .map(String::from)
+                .collect()
+            )]
+        );
+        assert_eq!(
+            f.take_sent(),
+            vec![(1, UiMessage::DaemonSetupResponse(Ok(())))]
+        );
+        assert_eq!(status(&f).node_state, DaemonNodeState::Starting);
+
+        f.subject.handle_report(LauncherReport::Started(4321));
+
+        assert_eq!(
+            f.take_sent(),
+            vec![
+                (1, UiMessage::DaemonStartResponse(Ok(()))),
+                (
+                    1,
+                    UiMessage::DaemonStatus(DaemonStatus {
+                        node_state: DaemonNodeState::Running,
+                        node_pid: Some(4321),
+                        restarts: 0,
+                        setup: vec![
+                            "dns-servers".to_string(),
+                            "ip".to_string(),
+                            "neighborhood-mode".to_string()
+                        ],
+                    })
+                )
+            ]
+        );
+    }
+
+    #[test]
+    fn setups_the_node_would_reject_are_rejected() {
+        let mut f = Fixture::new();
+
+        f.subject
+            .handle_ui_message(1, UiMessage::DaemonSetup(setup(vec![("ip", "booga")])));
+        f.subject.handle_ui_message(
+            1,
+            UiMessage::DaemonSetup(setup(vec![("real-user", "0:0:/root")])),
+        );
+        f.subject
+            .handle_ui_message(1, UiMessage::DaemonSetup(setup(vec![("run-as", "root")])));
+
+        let sent = f.take_sent();
+        match &sent[0] {
+            (1, UiMessage::DaemonSetupResponse(Err(message))) => {
+                assert!(message.contains("booga"), "{}", message)
+            }
+            x => panic!("Expected rejection, got {:?}", x),
+        }
+        assert_eq!(
+            sent[1],
+            (
+                1,
+                UiMessage::DaemonSetupResponse(Err(
+                    "The daemon decides real-user itself; give it to the daemon instead"
+                        .to_string()
+                ))
+            )
+        );
+        assert_eq!(
+            sent[2],
+            (
+                1,
+                UiMessage::DaemonSetupResponse(Err(
+                    "The daemon decides run-as itself; give it to the daemon instead".to_string()
+                ))
+            )
+        );
+        assert!(status(&f).setup.is_empty());
+    }
+
+    #[test]
+    fn a_node_that_stops_unexpectedly_is_started_again_ever_more_slowly() {
+        init_test_logging();
+        let mut f = Fixture::new();
+        f.subject.handle_ui_message(1, UiMessage::DaemonStart);
+        f.subject.handle_report(LauncherReport::Started(1));
+        f.take_launcher_commands();
+
+        let first = f.subject.handle_report(LauncherReport::Exited(Some(1)));
+        f.subject.restart();
+        f.subject.handle_report(LauncherReport::Started(2));
+        let second = f.subject.handle_report(LauncherReport::Exited(None));
+
+        assert_eq!(first, Some(MIN_RESTART_DELAY));
+        assert_eq!(second, Some(MIN_RESTART_DELAY * 2));
+        assert_eq!(f.take_launcher_commands().len(), 1);
+        assert_eq!(status(&f).restarts, 2);
+        assert_eq!(status(&f).node_state, DaemonNodeState::Restarting);
+        TestLogHandler::new().exists_log_containing(
+            "WARN: Daemon: The Node stopped unexpectedly with exit code Some(1); starting it again in 1s",
+        );
+    }
+
+    #[test]
+    fn a_node_that_asks_to_be_restarted_is_started_again_at_once() {
+        let mut f = Fixture::new();
+        f.subject.handle_ui_message(1, UiMessage::DaemonStart);
+        f.subject.handle_report(LauncherReport::Started(1));
+        f.take_launcher_commands();
+
+        let result = f
+            .subject
+            .handle_report(LauncherReport::Exited(Some(RESTART_EXIT_CODE)));
+        f.subject.restart();
+
+        assert_eq!(result, Some(Duration::from_secs(0)));
+        assert_eq!(f.take_launcher_commands().len(), 1);
+        assert_eq!(status(&f).restarts, 0);
+        assert_eq!(status(&f).node_state, DaemonNodeState::Starting);
+    }
+
+    #[test]
+    fn a_node_that_is_told_to_stop_stays_stopped() {
+        let mut f = Fixture::new();
+        f.subject.handle_ui_message(1, UiMessage::DaemonStart);
+        f.subject.handle_report(LauncherReport::Started(1));
+        f.take_launcher_commands();
+
+        f.subject.handle_ui_message(1, UiMessage::DaemonStop);
+        let result = f.subject.handle_report(LauncherReport::Exited(None));
+
+        assert_eq!(result, None);
+        assert_eq!(f.take_launcher_commands(), vec![LauncherCommand::Stop]);
+        assert_eq!(status(&f).node_state, DaemonNodeState::Stopped);
+        f.take_sent();
+        f.subject.handle_ui_message(1, UiMessage::DaemonStop);
+        assert_eq!(
+            f.take_sent(),
+            vec![(
+                1,
+                UiMessage::DaemonStopResponse(Err("The Node hasn't been started".to_string()))
+            )]
+        );
+    }
+
+    #[test]
+    fn a_stop_while_waiting_to_restart_cancels_the_restart() {
+        let mut f = Fixture::new();
+        f.subject.handle_ui_message(1, UiMessage::DaemonStart);
+        f.subject.handle_report(LauncherReport::Started(1));
+        f.subject.handle_report(LauncherReport::Exited(Some(1)));
+        f.take_launcher_commands();
+
+        f.subject.handle_ui_message(1, UiMessage::DaemonStop);
+        f.subject.restart();
+
+        assert!(f.take_launcher_commands().is_empty());
+        assert_eq!(status(&f).node_state, DaemonNodeState::Stopped);
+    }
+
+    #[test]
+    fn a_node_the_launcher_refuses_to_start_is_reported_and_not_retried() {
+        let mut f = Fixture::new();
+        f.subject.handle_ui_message(1, UiMessage::DaemonStart);
+
+        let result = f.subject.handle_report(LauncherReport::Refused(
+            "Couldn't start the Node: booga".to_string(),
+        ));
+
+        assert_eq!(result, None);
+        assert_eq!(
+            f.take_sent()[0],
+            (
+                1,
+                UiMessage::DaemonStartResponse(Err("Couldn't start the Node: booga".to_string()))
+            )
+        );
+        assert_eq!(status(&f).node_state, DaemonNodeState::Stopped);
+        assert!(!f.subject.running_wanted);
+    }
+
+    #[test]
+    fn messages_for_the_node_are_ignored() {
+        init_test_logging();
+        let mut f = Fixture::new();
+
+        f.subject
+            .handle_ui_message(1, UiMessage::SetWalletPassword("password".to_string()));
+
+        assert!(f.take_sent().is_empty());
+        assert!(f.take_launcher_commands().is_empty());
+        TestLogHandler::new().exists_log_containing(
+            "WARN: Daemon: UI 1 sent the daemon a message meant for the Node - ignoring",
+        );
+    }
+}
@@ -1,43 +1,87 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
+use crate::blockchain::blockchain_interface::chain_name;
 use crate::bootstrapper::RealUser;
 use crate::config_dao::{ConfigDao, ConfigDaoReal};
 use crate::database::db_initializer::{DbInitializer, DbInitializerReal, DATABASE_FILE};
-use crate::multi_config::{CommandLineVcl, EnvironmentVcl, MultiConfig, VirtualCommandLine};
-use crate::node_configurator::{app_head, chain_arg, data_directory_arg, real_user_arg};
+use crate::node_configurator::{
+    app_head, chain_arg, data_directory_arg, database_password_opt, encrypt_database_arg,
+    instance_name_arg, make_multi_config, profile_arg, real_user_arg, wallet_password_arg,
+    DATABASE_WALLET_PASSWORD_HELP, ENCRYPTED_DATABASE_HELP,
+};
 use crate::privilege_drop::{PrivilegeDropper, PrivilegeDropperReal};
 use crate::sub_lib::main_tools::StdStreams;
-use clap::Arg;
+use clap::{App, Arg};
 use heck::MixedCase;
 use serde_json::json;
 use serde_json::{Map, Value};
 use std::path::PathBuf;
 
 const DUMP_CONFIG_HELP: &str =
-    "Dump the configuration of PrometheusNode to stdout in JSON. Used chiefly by UIs.";
+    "Dump the configuration of PrometheusNode to stdout, as text or, with --json, as a document for UIs and \
+     other tools. Secrets are redacted unless you add --include-secrets.";
+const JSON_HELP: &str = "Dump the configuration as a JSON document instead of text.";
+const INCLUDE_SECRETS_HELP: &str =
+    "Include secrets, such as the encrypted mnemonic seed, instead of redacting them. Be careful where the \
+     output goes.";
+
+// Goes up whenever the JSON document changes shape, so tools can tell what they're reading
+pub const DUMP_CONFIG_FORMAT_VERSION: u32 = 1;
+pub const REDACTED: &str = "<redacted>";
+// Database configuration values nobody should see in a support ticket
+const SECRET_CONFIGURATION_NAMES: [&str; 1] = ["seed"];
 
 pub fn dump_config(args: &Vec<String>, streams: &mut StdStreams) -> i32 {
-    let (real_user, data_directory, chain_id) = distill_args(args);
+    let (real_user, data_directory, chain_id, json, include_secrets, password_opt) =
+        distill_args(args);
     PrivilegeDropperReal::new().drop_privileges(&real_user);
-    let config_dao = make_config_dao(&data_directory, chain_id);
+    let config_dao = make_config_dao(&data_directory, chain_id, password_opt);
     let configuration = config_dao.get_all().expect("Couldn't fetch configuration");
-    let json = configuration_to_json(configuration);
-    write_string(streams, json);
+    let configuration = if include_secrets {
+        configuration
+    } else {
+        redact_secrets(configuration)
+    };
+    let output = if json {
+        configuration_to_json(configuration, chain_id, &data_directory, include_secrets)
+    } else {
+        configuration_to_text(configuration)
+    };
+    write_string(streams, output);
     0
 }
 
-fn write_string(streams: &mut StdStreams, json: String) {
+fn write_string(streams: &mut StdStreams, output: String) {
     streams
         .stdout
-        .write_all(json.as_bytes())
-        .expect("Couldn't write JSON to stdout");
+        .write_all(output.as_bytes())
+        .expect("Couldn't write configuration to stdout");
     streams
         .stdout
         .flush()
-        .expect("Couldn't flush JSON to stdout");
+        .expect("Couldn't flush configuration to stdout");
 }
 
-fn configuration_to_json(configuration: Vec<(String, Option<String>)>) -> String {
+// A secret that isn't there stays None, so it's still plain that it's missing
+fn redact_secrets(configuration: Vec<(String, Option<String>)>) -> Vec<(String, Option<String>)> {
+    configuration
+        .into_iter()
+        .map(|(name, value)| {
+            if SECRET_CONFIGURATION_NAMES.contains(&name.as_str()) {
+                (name, value.map(|_| REDACTED.to_string()))
+            } else {
+                (name, value)
+            }
+        })
+        .collect()
+}
+
+fn configuration_to_json(
+    configuration: Vec<(String, Option<String>)>,
+    chain_id: u8,
+    data_directory: &PathBuf,
+    include_secrets: bool,
+) -> String {
     let mut map = Map::new();
     configuration.into_iter().for_each(|(name, value)| {
         let json_name = name.to_mixed_case();
@@ -46,12 +90,41 @@ fn configuration_to_json(configuration: Vec<(String, Option<String>)>) -> String
             Some(value) => map.insert(json_name, json!(value)),
         };
     });
-    let value: Value = Value::Object(map);
+    let value = json!({
+        "formatVersion": DUMP_CONFIG_FORMAT_VERSION,
+        "chain": chain_name(chain_id),
+        "dataDirectory": data_directory.to_string_lossy(),
+        "secretsIncluded": include_secrets,
+        "configuration": Value::Object(map),
+    });
     serde_json::to_string_pretty(&value).expect("Couldn't serialize configuration to JSON")
 }
 
-fn make_config_dao(data_directory: &PathBuf, chain_id: u8) -> ConfigDaoReal {
-    let conn = DbInitializerReal::new()
+fn configuration_to_text(configuration: Vec<(String, Option<String>)>) -> String {
+    let width = configuration
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    configuration
+        .into_iter()
+        .map(|(name, value)| {
+            format!(
+                "{:width$}  {}\n",
+                name,
+                value.unwrap_or_else(|| "(not set)".to_string()),
+                width = width
+            )
+        })
+        .collect()
+}
+
+fn make_config_dao(
+    data_directory: &PathBuf,
+    chain_id: u8,
+    password_opt: Option<String>,
+) -> ConfigDaoReal {
+    let conn = DbInitializerReal::with_password_opt(password_opt)
         .initialize(&data_directory, chain_id)
         .unwrap_or_else(|e| {
             panic!(
@@ -63,24 +136,54 @@ fn make_config_dao(data_directory: &PathBuf, chain_id: u8) -> ConfigDaoReal {
     ConfigDaoReal::new(conn)
 }
 
-fn distill_args(args: &Vec<String>) -> (RealUser, PathBuf, u8) {
-    let app = app_head()
+pub fn app() -> App<'static, 'static> {
+    app_head()
+        .about(DUMP_CONFIG_HELP)
+        .usage("PrometheusNode dump-config [FLAGS] [OPTIONS]")
         .arg(
             Arg::with_name("dump-config")
                 .long("dump-config")
                 .required(true)
                 .takes_value(false)
-                .help(DUMP_CONFIG_HELP),
+                .hidden(true),
         )
         .arg(chain_arg())
         .arg(data_directory_arg())
-        .arg(real_user_arg());
-    let vcls: Vec<Box<dyn VirtualCommandLine>> = vec![
-        Box::new(CommandLineVcl::new(args.clone())),
-        Box::new(EnvironmentVcl::new(&app)),
-    ];
-    let multi_config = MultiConfig::new(&app, vcls);
-    crate::node_configurator::real_user_data_directory_and_chain_id(&multi_config)
+        .arg(encrypt_database_arg(ENCRYPTED_DATABASE_HELP))
+        .arg(
+            Arg::with_name("include-secrets")
+                .long("include-secrets")
+                .takes_value(false)
+                .help(INCLUDE_SECRETS_HELP),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .takes_value(false)
+                .help(JSON_HELP),
+        )
+        .arg(profile_arg())
+        .arg(instance_name_arg())
+        .arg(real_user_arg())
+        .arg(wallet_password_arg(DATABASE_WALLET_PASSWORD_HELP))
+}
+
+fn distill_args(args: &Vec<String>) -> (RealUser, PathBuf, u8, bool, bool, Option<String>) {
+    let app = app();
+    let multi_config = make_multi_config(&app, args, None);
+    let json = multi_config.arg_matches().is_present("json");
+    let include_secrets = multi_config.arg_matches().is_present("include-secrets");
+    let (real_user, data_directory, chain_id) =
+        crate::node_configurator::real_user_data_directory_and_chain_id(&multi_config);
+    let password_opt = database_password_opt(&multi_config).unwrap_or_else(|e| panic!("{}", e));
+    (
+        real_user,
+        data_directory,
+        chain_id,
+        json,
+        include_secrets,
+        password_opt,
+    )
 }
 
 #[cfg(test)]
@@ -91,6 +194,7 @@ mod tests {
     };
     use crate::database::db_initializer::CURRENT_SCHEMA_VERSION;
     use crate::persistent_configuration::{PersistentConfiguration, PersistentConfigurationReal};
+    use crate::sub_lib::accountant::PaymentParameter;
     use crate::sub_lib::cryptde::PlainData;
     use crate::test_utils::{
         ensure_node_home_directory_exists, ArgsBuilder, FakeStreamHolder, DEFAULT_CHAIN_ID,
@@ -113,6 +217,7 @@ mod tests {
                 .param("--real-user", "123::")
                 .param("--chain", TEST_DEFAULT_CHAIN_NAME)
                 .opt("--dump-config")
+                .opt("--json")
                 .into(),
             &mut holder.streams(),
         );
@@ -120,21 +225,45 @@ mod tests {
         assert_eq!(result, 0);
         let output = holder.stdout.get_string();
         let actual_value: Value = serde_json::from_str(&output).unwrap();
-        let actual_map = match &actual_value {
+        assert_eq!(
+            actual_value["formatVersion"],
+            json!(DUMP_CONFIG_FORMAT_VERSION)
+        );
+        assert_eq!(actual_value["chain"], json!(TEST_DEFAULT_CHAIN_NAME));
+        assert_eq!(
+            actual_value["dataDirectory"],
+            json!(data_dir.to_str().unwrap())
+        );
+        assert_eq!(actual_value["secretsIncluded"], json!(false));
+        let actual_map = match &actual_value["configuration"] {
             Value::Object(map) => map,
             other => panic!("Was expecting Value::Object, got {:?} instead", other),
         };
         let expected_value = json!({
+           "balanceDecreasesFor": PaymentParameter::BalanceDecreasesFor.default_value().to_string(),
+           "balanceToDecreaseFrom": PaymentParameter::BalanceToDecreaseFrom.default_value().to_string(),
            "clandestinePort": actual_map.get ("clandestinePort"),
            "consumingWalletDerivationPath": null,
            "consumingWalletPublicKey": null,
+           "detectedDnsServers": null,
            "earningWalletAddress": null,
+           "exitEarningWalletAddress": null,
+           "feeBudgetPeriodStart": null,
+           "feeBudgetSpent": null,
            "gasPrice": "1",
+           "nextNonce": null,
+           "payableScanInterval": PaymentParameter::PayableScanInterval.default_value().to_string(),
+           "paymentGraceBeforeBan": PaymentParameter::PaymentGraceBeforeBan.default_value().to_string(),
+           "paymentReceivedScanInterval": PaymentParameter::PaymentReceivedScanInterval.default_value().to_string(),
+           "paymentSuggestedAfter": PaymentParameter::PaymentSuggestedAfter.default_value().to_string(),
+           "permanentDebtAllowed": PaymentParameter::PermanentDebtAllowed.default_value().to_string(),
            "schemaVersion": CURRENT_SCHEMA_VERSION,
            "seed": null,
+           "splitDnsDomains": null,
            "startBlock": &contract_creation_block_from_chain_id(chain_id_from_name(TEST_DEFAULT_CHAIN_NAME)).to_string(),
+           "unbanWhenBalanceBelow": PaymentParameter::UnbanWhenBalanceBelow.default_value().to_string(),
         });
-        assert_eq!(actual_value, expected_value);
+        assert_eq!(actual_value["configuration"], expected_value);
     }
 
     #[test]
@@ -154,6 +283,8 @@ mod tests {
             persistent_config.set_consuming_wallet_public_key(&PlainData::new(&[1, 2, 3, 4]));
             persistent_config
                 .set_earning_wallet_address("0x0123456789012345678901234567890123456789");
+            persistent_config
+                .set_exit_earning_wallet_address("0x9876543210987654321098765432109876543210");
             persistent_config.set_clandestine_port(3456);
         }
 
@@ -163,6 +294,7 @@ mod tests {
                 .param("--real-user", "123::")
                 .param("--chain", TEST_DEFAULT_CHAIN_NAME)
                 .opt("--dump-config")
+                .opt("--json")
                 .into(),
             &mut holder.streams(),
         );
@@ -171,15 +303,114 @@ mod tests {
         let output = holder.stdout.get_string();
         let actual_value: Value = serde_json::from_str(&output).unwrap();
         let expected_value = json!({
+           "balanceDecreasesFor": PaymentParameter::BalanceDecreasesFor.default_value().to_string(),
+           "balanceToDecreaseFrom": PaymentParameter::BalanceToDecreaseFrom.default_value().to_string(),
            "clandestinePort": "3456",
            "consumingWalletDerivationPath": null,
            "consumingWalletPublicKey": "01020304",
+           "detectedDnsServers": null,
            "earningWalletAddress": "0x0123456789012345678901234567890123456789",
+           "exitEarningWalletAddress": "0x9876543210987654321098765432109876543210",
+           "feeBudgetPeriodStart": null,
+           "feeBudgetSpent": null,
            "gasPrice": "1",
+           "nextNonce": null,
+           "payableScanInterval": PaymentParameter::PayableScanInterval.default_value().to_string(),
+           "paymentGraceBeforeBan": PaymentParameter::PaymentGraceBeforeBan.default_value().to_string(),
+           "paymentReceivedScanInterval": PaymentParameter::PaymentReceivedScanInterval.default_value().to_string(),
+           "paymentSuggestedAfter": PaymentParameter::PaymentSuggestedAfter.default_value().to_string(),
+           "permanentDebtAllowed": PaymentParameter::PermanentDebtAllowed.default_value().to_string(),
            "schemaVersion": CURRENT_SCHEMA_VERSION,
            "seed": null,
+           "splitDnsDomains": null,
            "startBlock": &contract_creation_block_from_chain_id(chain_id_from_name(TEST_DEFAULT_CHAIN_NAME)).to_string(),
+           "unbanWhenBalanceBelow": PaymentParameter::UnbanWhenBalanceBelow.default_value().to_string(),
         });
-        assert_eq!(actual_value, expected_value);
+        assert_eq!(actual_value["configuration"], expected_value);
+    }
+
+    #[test]
+    fn dump_config_redacts_secrets_unless_told_not_to() {
+        let data_dir = ensure_node_home_directory_exists(
+            "config_dumper",
+            "dump_config_redacts_secrets_unless_told_not_to",
+        )
+        .join("Substratum")
+        .join(TEST_DEFAULT_CHAIN_NAME);
+        let encrypted_seed = {
+            let conn = DbInitializerReal::new()
+                .initialize(&data_dir, DEFAULT_CHAIN_ID)
+                .unwrap();
+            let persistent_config = PersistentConfigurationReal::from(conn);
+            persistent_config.set_mnemonic_seed(&PlainData::new(&[1, 2, 3, 4]), "password");
+            persistent_config.encrypted_mnemonic_seed().unwrap()
+        };
+        let args = |include_secrets: bool| -> Vec<String> {
+            let args = ArgsBuilder::new()
+                .param("--data-directory", data_dir.to_str().unwrap())
+                .param("--real-user", "123::")
+                .param("--chain", TEST_DEFAULT_CHAIN_NAME)
+                .opt("--dump-config")
+                .opt("--json");
+            if include_secrets {
+                args.opt("--include-secrets").into()
+            } else {
+                args.into()
+            }
+        };
+        let mut redacted_holder = FakeStreamHolder::new();
+        let mut unredacted_holder = FakeStreamHolder::new();
+
+        dump_config(&args(false), &mut redacted_holder.streams());
+        dump_config(&args(true), &mut unredacted_holder.streams());
+
+        let redacted: Value = serde_json::from_str(&redacted_holder.stdout.get_string()).unwrap();
+        let unredacted: Value =
+            serde_json::from_str(&unredacted_holder.stdout.get_string()).unwrap();
+        assert_eq!(redacted["secretsIncluded"], json!(false));
+        assert_eq!(redacted["configuration"]["seed"], json!(REDACTED));
+        assert_eq!(unredacted["secretsIncluded"], json!(true));
+        assert_eq!(unredacted["configuration"]["seed"], json!(encrypted_seed));
+    }
+
+    #[test]
+    fn distill_args_takes_the_database_password_only_with_encrypt_database() {
+        let args = |encrypt_database: bool| {
+            let args = ArgsBuilder::new()
+                .opt("--dump-config")
+                .param("--chain", TEST_DEFAULT_CHAIN_NAME)
+                .param("--data-directory", "booga")
+                .param("--real-user", "123:456:/home/booga")
+                .param("--wallet-password", "password");
+            if encrypt_database {
+                args.opt("--encrypt-database").into()
+            } else {
+                args.into()
+            }
+        };
+
+        let (_, _, _, _, _, plain_password_opt) = distill_args(&args(false));
+        let (_, _, _, _, _, encrypted_password_opt) = distill_args(&args(true));
+
+        assert_eq!(plain_password_opt, None);
+        assert_eq!(encrypted_password_opt, Some("password".to_string()));
+    }
+
+    #[test]
+    fn configuration_to_text_lines_up_names_and_values() {
+        let configuration = vec![
+            ("gas_price".to_string(), Some("1".to_string())),
+            ("seed".to_string(), Some("secret".to_string())),
+            ("earning_wallet_address".to_string(), None),
+        ];
+
+        let result = configuration_to_text(redact_secrets(configuration));
+
+        assert_eq!(
+            result,
+            "gas_price               1\n\
+             seed                    <redacted>\n\
+             earning_wallet_address  (not set)\n"
+        );
     }
 }
@@ -0,0 +1,828 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use crate::blockchain::blockchain_interface::chain_name;
+use crate::bootstrapper::RealUser;
+use crate::database::db_encryption::{apply_password, is_readable};
+use crate::database::db_initializer::{DbInitializer, DbInitializerReal, DATABASE_FILE};
+use crate::neighborhood::NEIGHBORHOOD_SNAPSHOT_FILE;
+use crate::node_configurator::{
+    app_head, cannot_be_blank, chain_arg, data_directory_arg, database_password_opt,
+    encrypt_database_arg, flushed_write, instance_name_arg, make_multi_config, profile_arg,
+    real_user_arg, real_user_data_directory_and_chain_id, request_existing_password,
+    request_password_with_confirmation, request_password_with_retry, wallet_password_arg,
+    DATABASE_WALLET_PASSWORD_HELP, ENCRYPTED_DATABASE_HELP,
+};
+use crate::privilege_drop::{PrivilegeDropper, PrivilegeDropperReal};
+use crate::sub_lib::main_tools::StdStreams;
+use clap::{value_t, App, Arg};
+use rusqlite::{Connection, OpenFlags, NO_PARAMS};
+use serde_derive::{Deserialize, Serialize};
+use sodiumoxide::crypto::pwhash::argon2id13 as pwhash;
+use sodiumoxide::crypto::secretbox;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const BACKUP_DATA_HELP: &str =
+    "Write everything PrometheusNode keeps in its data directory (its wallets, settings, earnings and \
+     payment history, and the neighbors it knows) to an archive encrypted with a password, so that it can be restored with \
+     restore-data on another machine.";
+const RESTORE_DATA_HELP: &str =
+    "Put the contents of an archive written by backup-data into a data directory that has no database yet. \
+     An archive from an older version of PrometheusNode is upgraded; one from a newer version is refused.";
+const BACKUP_PATH_HELP: &str = "The file to write the archive to. It must not exist already.";
+const RESTORE_PATH_HELP: &str = "The archive to restore.";
+const ARCHIVE_PASSWORD_HELP: &str =
+    "The password the archive is encrypted with. If you leave it out, you'll be asked for it, which \
+     keeps it out of your shell history.";
+
+// Identifies the file and the layout that follows it: salt, nonce, then the sealed contents
+const ARCHIVE_MAGIC: &[u8] = b"PNARCHV1";
+const RESTORE_DIRECTORY: &str = "restore-in-progress";
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveContents {
+    pub chain_name: String,
+    pub files: Vec<ArchivedFile>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedFile {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+pub fn backup_app() -> App<'static, 'static> {
+    app_head()
+        .about(BACKUP_DATA_HELP)
+        .usage("PrometheusNode backup-data <PATH> [OPTIONS]")
+        .arg(
+            Arg::with_name("backup-data")
+                .long("backup-data")
+                .value_name("PATH")
+                .required(true)
+                .takes_value(true)
+                .empty_values(false)
+                .help(BACKUP_PATH_HELP),
+        )
+        .arg(archive_password_arg())
+        .arg(chain_arg())
+        .arg(data_directory_arg())
+        .arg(encrypt_database_arg(ENCRYPTED_DATABASE_HELP))
+        .arg(profile_arg())
+        .arg(instance_name_arg())
+        .arg(real_user_arg())
+        .arg(wallet_password_arg(DATABASE_WALLET_PASSWORD_HELP))
+}
+
+pub fn restore_app() -> App<'static, 'static> {
+    app_head()
+        .about(RESTORE_DATA_HELP)
+        .usage("PrometheusNode restore-data <PATH> [OPTIONS]")
+        .arg(
+            Arg::with_name("restore-data")
+                .long("restore-data")
+                .value_name("PATH")
+                .required(true)
+                .takes_value(true)
+                .empty_values(false)
+                .help(RESTORE_PATH_HELP),
+        )
+        .arg(archive_password_arg())
+        .arg(chain_arg())
+        .arg(data_directory_arg())
+        .arg(encrypt_database_arg(ENCRYPTED_DATABASE_HELP))
+        .arg(profile_arg())
+        .arg(instance_name_arg())
+        .arg(real_user_arg())
+        .arg(wallet_password_arg(DATABASE_WALLET_PASSWORD_HELP))
+}
+
+fn archive_password_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("archive-password")
+        .long("archive-password")
+        .value_name("ARCHIVE-PASSWORD")
+        .required(false)
+        .takes_value(true)
+        .help(ARCHIVE_PASSWORD_HELP)
+}
+
+pub fn backup_data(args: &Vec<String>, streams: &mut StdStreams<'_>) -> i32 {
+    backup_data_with(args, streams, &PrivilegeDropperReal::new())
+}
+
+pub fn restore_data(args: &Vec<String>, streams: &mut StdStreams<'_>) -> i32 {
+    restore_data_with(args, streams, &PrivilegeDropperReal::new())
+}
+
+fn backup_data_with(
+    args: &Vec<String>,
+    streams: &mut StdStreams<'_>,
+    privilege_dropper: &dyn PrivilegeDropper,
+) -> i32 {
+    let ArchiveArgs {
+        real_user,
+        data_directory,
+        chain_id,
+        archive_path,
+        password_opt,
+        database_password,
+    } = distill_args(&backup_app(), "backup-data", args);
+    privilege_dropper.drop_privileges(&real_user);
+    let result = if archive_path.exists() {
+        Err(format!("{} already exists", archive_path.display()))
+    } else {
+        database_password
+            .and_then(|database_password_opt| {
+                read_database(&data_directory, database_password_opt.as_ref())
+            })
+            .and_then(|database| {
+                let password = match password_opt {
+                    Some(password) => password,
+                    None => request_new_archive_password(streams)?,
+                };
+                let contents = ArchiveContents {
+                    chain_name: chain_name(chain_id).to_string(),
+                    files: vec![ArchivedFile {
+                        name: DATABASE_FILE.to_string(),
+                        data: database,
+                    }]
+                    .into_iter()
+                    .chain(read_snapshot(&data_directory)?)
+                    .collect(),
+                };
+                fs::write(&archive_path, seal_archive(&contents, &password)).map_err(|e| {
+                    format!("Couldn't write archive {}: {}", archive_path.display(), e)
+                })
+            })
+    };
+    finish(
+        streams,
+        result,
+        format!(
+            "Backed up {} to {}",
+            data_directory.display(),
+            archive_path.display()
+        ),
+    )
+}
+
+fn restore_data_with(
+    args: &Vec<String>,
+    streams: &mut StdStreams<'_>,
+    privilege_dropper: &dyn PrivilegeDropper,
+) -> i32 {
+    let ArchiveArgs {
+        real_user,
+        data_directory,
+        chain_id,
+        archive_path,
+        password_opt,
+        database_password,
+    } = distill_args(&restore_app(), "restore-data", args);
+    let result = database_password.and_then(|database_password_opt| {
+        fs::create_dir_all(&data_directory)
+            .map_err(|e| {
+                format!(
+                    "Couldn't create data directory {}: {}",
+                    data_directory.display(),
+                    e
+                )
+            })
+            .and_then(|_| {
+                privilege_dropper.chown(&data_directory, &real_user);
+                privilege_dropper.drop_privileges(&real_user);
+                let archive = fs::read(&archive_path).map_err(|e| {
+                    format!("Couldn't read archive {}: {}", archive_path.display(), e)
+                })?;
+                let password = match password_opt {
+                    Some(password) => password,
+                    None => request_existing_archive_password(streams)?,
+                };
+                let contents = open_archive(&archive, &password)?;
+                if contents.chain_name != chain_name(chain_id) {
+                    return Err(format!(
+                        "The archive is for {}, not {}; specify --chain {}",
+                        contents.chain_name,
+                        chain_name(chain_id),
+                        contents.chain_name
+                    ));
+                }
+                install_files(
+                    &data_directory,
+                    chain_id,
+                    database_password_opt,
+                    contents.files,
+                )
+            })
+    });
+    finish(
+        streams,
+        result,
+        format!(
+            "Restored {} to {}",
+            archive_path.display(),
+            data_directory.display()
+        ),
+    )
+}
+
+fn finish(streams: &mut StdStreams<'_>, result: Result<(), String>, success: String) -> i32 {
+    match result {
+        Ok(()) => {
+            writeln!(streams.stdout, "{}", success).expect("Couldn't write to stdout");
+            0
+        }
+        Err(e) => {
+            writeln!(streams.stderr, "{}", e).expect("Couldn't write to stderr");
+            1
+        }
+    }
+}
+
+struct ArchiveArgs {
+    real_user: RealUser,
+    data_directory: PathBuf,
+    chain_id: u8,
+    archive_path: PathBuf,
+    password_opt: Option<String>,
+    // Left for the caller to report, along with everything else that can go wrong
+    database_password: Result<Option<String>, String>,
+}
+
+fn distill_args(app: &App, mode: &str, args: &Vec<String>) -> ArchiveArgs {
+    let multi_config = make_multi_config(app, args, None);
+    let (real_user, data_directory, chain_id) =
+        real_user_data_directory_and_chain_id(&multi_config);
+    let archive_path =
+        value_m!(multi_config, mode, PathBuf).expect("archive path is not properly required");
+    let password_opt = value_m!(multi_config, "archive-password", String);
+    ArchiveArgs {
+        real_user,
+        data_directory,
+        chain_id,
+        archive_path,
+        password_opt,
+        database_password: database_password_opt(&multi_config),
+    }
+}
+
+fn read_database(data_directory: &Path, password_opt: Option<&String>) -> Result<Vec<u8>, String> {
+    let database_path = data_directory.join(DATABASE_FILE);
+    if !database_path.exists() {
+        return Err(format!(
+            "There is no database at {}",
+            database_path.display()
+        ));
+    }
+    let conn = open_read_only(&database_path, password_opt)
+        .map_err(|e| format!("Couldn't open {}: {}", database_path.display(), e))?;
+    // The file alone may be missing whatever is still in the write-ahead log, so SQLite writes a
+    // consistent copy of the whole database instead, even while a running Node goes on changing it
+    let copy_path = database_path.with_extension("db.backup");
+    let _ = fs::remove_file(&copy_path);
+    conn.execute("vacuum into ?", &[copy_path.to_string_lossy().to_string()])
+        .map_err(|e| format!("Couldn't copy {}: {}", database_path.display(), e))?;
+    let data = fs::read(&copy_path);
+    let _ = fs::remove_file(&copy_path);
+    data.map_err(|e| format!("Couldn't read {}: {}", copy_path.display(), e))
+}
+
+// A Node that hasn't met any neighbors yet has no snapshot
+fn read_snapshot(data_directory: &Path) -> Result<Option<ArchivedFile>, String> {
+    let snapshot_path = data_directory.join(NEIGHBORHOOD_SNAPSHOT_FILE);
+    if !snapshot_path.exists() {
+        return Ok(None);
+    }
+    fs::read(&snapshot_path)
+        .map(|data| {
+            Some(ArchivedFile {
+                name: NEIGHBORHOOD_SNAPSHOT_FILE.to_string(),
+                data,
+            })
+        })
+        .map_err(|e| format!("Couldn't read {}: {}", snapshot_path.display(), e))
+}
+
+// The files are unpacked off to one side and the database is opened there first, which upgrades it
+// or refuses it, so nothing reaches the data directory proper unless the Node will be able to use it
+fn install_files(
+    data_directory: &Path,
+    chain_id: u8,
+    password_opt: Option<String>,
+    files: Vec<ArchivedFile>,
+) -> Result<(), String> {
+    if data_directory.join(DATABASE_FILE).exists() {
+        return Err(format!(
+            "{} already has a database; move it out of the way before restoring over it",
+            data_directory.display()
+        ));
+    }
+    if !files.iter().any(|file| file.name == DATABASE_FILE) {
+        return Err("The archive contains no database".to_string());
+    }
+    let staging_directory = data_directory.join(RESTORE_DIRECTORY);
+    let _ = fs::remove_dir_all(&staging_directory);
+    fs::create_dir_all(&staging_directory)
+        .map_err(|e| format!("Couldn't create {}: {}", staging_directory.display(), e))?;
+    let result = stage_files(&staging_directory, chain_id, password_opt, &files).and_then(|_| {
+        files.iter().try_for_each(|file| {
+            fs::rename(
+                staging_directory.join(&file.name),
+                data_directory.join(&file.name),
+            )
+            .map_err(|e| format!("Couldn't move {} into place: {}", file.name, e))
+        })
+    });
+    let _ = fs::remove_dir_all(&staging_directory);
+    result
+}
+
+fn stage_files(
+    staging_directory: &PathBuf,
+    chain_id: u8,
+    password_opt: Option<String>,
+    files: &[ArchivedFile],
+) -> Result<(), String> {
+    files.iter().try_for_each(|file| {
+        if Path::new(&file.name).file_name() != Some(OsStr::new(&file.name)) {
+            return Err(format!(
+                "The archive contains a bad file name: {}",
+                file.name
+            ));
+        }
+        fs::write(staging_directory.join(&file.name), &file.data)
+            .map_err(|e| format!("Couldn't write {}: {}", file.name, e))
+    })?;
+    check_database(
+        &staging_directory.join(DATABASE_FILE),
+        password_opt.as_ref(),
+    )?;
+    DbInitializerReal::with_password_opt(password_opt)
+        .initialize(staging_directory, chain_id)
+        .map(|_| ())
+        .map_err(|e| format!("The archived database can't be used: {:?}", e))
+}
+
+// DbInitializer takes for granted that a database it finds is one of Node's
+fn check_database(database_path: &Path, password_opt: Option<&String>) -> Result<(), String> {
+    open_read_only(database_path, password_opt)
+        .and_then(|conn| {
+            conn.query_row(
+                "select value from config where name = 'schema_version'",
+                NO_PARAMS,
+                |row| row.get::<_, String>(0),
+            )
+            .map_err(|e| e.to_string())
+        })
+        .map(|_| ())
+        .map_err(|e| format!("The archived database can't be used: {}", e))
+}
+
+// A database that was never encrypted is read as it is, even with a password; DbInitializer will
+// encrypt it once it's in place
+fn open_read_only(
+    database_path: &Path,
+    password_opt: Option<&String>,
+) -> Result<Connection, String> {
+    let open = || {
+        let mut flags = OpenFlags::empty();
+        flags.insert(OpenFlags::SQLITE_OPEN_READ_ONLY);
+        Connection::open_with_flags(database_path, flags).map_err(|e| e.to_string())
+    };
+    let conn = open()?;
+    if is_readable(&conn).map_err(|e| format!("{:?}", e))? {
+        return Ok(conn);
+    }
+    let password = match password_opt {
+        Some(password) => password,
+        None => {
+            return Err(
+                "it isn't readable; if it's encrypted, specify --encrypt-database and --wallet-password"
+                    .to_string(),
+            )
+        }
+    };
+    let conn = open()?;
+    apply_password(&conn, password).map_err(|e| format!("{:?}", e))?;
+    if is_readable(&conn).map_err(|e| format!("{:?}", e))? {
+        Ok(conn)
+    } else {
+        Err("it can't be decrypted with that wallet password".to_string())
+    }
+}
+
+pub fn seal_archive(contents: &ArchiveContents, password: &str) -> Vec<u8> {
+    sodiumoxide::init().expect("Couldn't initialize sodiumoxide");
+    let salt = pwhash::gen_salt();
+    let nonce = secretbox::gen_nonce();
+    let key = derive_key(password, &salt);
+    let plaintext = serde_cbor::ser::to_vec(contents).expect("Couldn't serialize archive");
+    let mut archive = ARCHIVE_MAGIC.to_vec();
+    archive.extend_from_slice(&salt.0);
+    archive.extend_from_slice(&nonce.0);
+    archive.extend(secretbox::seal(&plaintext, &nonce, &key));
+    archive
+}
+
+pub fn open_archive(archive: &[u8], password: &str) -> Result<ArchiveContents, String> {
+    let salt_start = ARCHIVE_MAGIC.len();
+    let nonce_start = salt_start + pwhash::SALTBYTES;
+    let sealed_start = nonce_start + secretbox::NONCEBYTES;
+    if archive.len() < sealed_start || &archive[..salt_start] != ARCHIVE_MAGIC {
+        return Err("This is not an archive written by backup-data".to_string());
+    }
+    let salt =
+        pwhash::Salt::from_slice(&archive[salt_start..nonce_start]).expect("Salt length is wrong");
+    let nonce = secretbox::Nonce::from_slice(&archive[nonce_start..sealed_start])
+        .expect("Nonce length is wrong");
+    let key = derive_key(password, &salt);
+    let plaintext = secretbox::open(&archive[sealed_start..], &nonce, &key)
+        .map_err(|_| "Wrong password, or the archive is damaged".to_string())?;
+    serde_cbor::de::from_slice(&plaintext).map_err(|e| format!("The archive is damaged: {}", e))
+}
+
+fn derive_key(password: &str, salt: &pwhash::Salt) -> secretbox::Key {
+    let mut key = secretbox::Key([0; secretbox::KEYBYTES]);
+    pwhash::derive_key(
+        &mut key.0,
+        password.as_bytes(),
+        salt,
+        pwhash::OPSLIMIT_INTERACTIVE,
+        pwhash::MEMLIMIT_INTERACTIVE,
+    )
+    .expect("Couldn't derive archive key");
+    key
+}
+
+fn request_new_archive_password(streams: &mut StdStreams<'_>) -> Result<String, String> {
+    let result = request_password_with_retry("Archive password: ", streams, |streams| {
+        request_password_with_confirmation(
+            "Confirm archive password: ",
+            "Passwords do not match.",
+            streams,
+            cannot_be_blank,
+        )
+    });
+    result.map_err(|e| format!("No archive password: {:?}", e))
+}
+
+fn request_existing_archive_password(streams: &mut StdStreams<'_>) -> Result<String, String> {
+    let result = request_password_with_retry("Archive password: ", streams, |streams| {
+        request_existing_password(streams, cannot_be_blank)
+    });
+    flushed_write(streams.stdout, "\n");
+    result.map_err(|e| format!("No archive password: {:?}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistent_configuration::{PersistentConfiguration, PersistentConfigurationReal};
+    use crate::server_initializer::test_utils::PrivilegeDropperMock;
+    use crate::test_utils::{
+        ensure_node_home_directory_exists, ArgsBuilder, ByteArrayReader, FakeStreamHolder,
+        DEFAULT_CHAIN_ID, TEST_DEFAULT_CHAIN_NAME,
+    };
+
+    fn make_data_directory(test_name: &str, gas_price: u64) -> PathBuf {
+        let home_dir = ensure_node_home_directory_exists("data_archive", test_name);
+        let data_directory = home_dir.join("original");
+        fs::create_dir_all(&data_directory).unwrap();
+        let conn = DbInitializerReal::new()
+            .initialize(&data_directory, DEFAULT_CHAIN_ID)
+            .unwrap();
+        PersistentConfigurationReal::from(conn).set_gas_price(gas_price);
+        data_directory
+    }
+
+    fn archive_args(mode: &str, archive_path: &Path, data_directory: &Path) -> ArgsBuilder {
+        ArgsBuilder::new()
+            .param(&format!("--{}", mode), archive_path.to_str().unwrap())
+            .param("--chain", TEST_DEFAULT_CHAIN_NAME)
+            .param("--data-directory", data_directory.to_str().unwrap())
+            .param("--real-user", "123:456:/home/booga")
+    }
+
+    #[test]
+    fn an_archive_opens_with_the_password_it_was_sealed_with() {
+        let contents = ArchiveContents {
+            chain_name: "ropsten".to_string(),
+            files: vec![ArchivedFile {
+                name: "booga.db".to_string(),
+                data: vec![1, 2, 3, 4],
+            }],
+        };
+
+        let archive = seal_archive(&contents, "password");
+
+        assert_eq!(open_archive(&archive, "password"), Ok(contents));
+        assert_eq!(
+            open_archive(&archive, "drowssap"),
+            Err("Wrong password, or the archive is damaged".to_string())
+        );
+    }
+
+    #[test]
+    fn something_else_is_not_an_archive() {
+        let result = open_archive(b"PNARCHV0 and some more bytes", "password");
+
+        assert_eq!(
+            result,
+            Err("This is not an archive written by backup-data".to_string())
+        );
+    }
+
+    #[test]
+    fn backup_and_restore_carry_the_database_to_another_data_directory() {
+        let original = make_data_directory(
+            "backup_and_restore_carry_the_database_to_another_data_directory",
+            57,
+        );
+        let archive_path = original.parent().unwrap().join("node.archive");
+        let restored = original.parent().unwrap().join("restored");
+        let mut holder = FakeStreamHolder::new();
+
+        let backup_result = backup_data_with(
+            &archive_args("backup-data", &archive_path, &original)
+                .param("--archive-password", "password")
+                .into(),
+            &mut holder.streams(),
+            &PrivilegeDropperMock::new(),
+        );
+        let restore_result = restore_data_with(
+            &archive_args("restore-data", &archive_path, &restored)
+                .param("--archive-password", "password")
+                .into(),
+            &mut holder.streams(),
+            &PrivilegeDropperMock::new(),
+        );
+
+        assert_eq!(holder.stderr.get_string(), "");
+        assert_eq!(backup_result, 0);
+        assert_eq!(restore_result, 0);
+        assert_eq!(
+            holder.stdout.get_string(),
+            format!(
+                "Backed up {} to {}\nRestored {} to {}\n",
+                original.display(),
+                archive_path.display(),
+                archive_path.display(),
+                restored.display()
+            )
+        );
+        let conn = DbInitializerReal::new()
+            .initialize(&restored, DEFAULT_CHAIN_ID)
+            .unwrap();
+        assert_eq!(PersistentConfigurationReal::from(conn).gas_price(), 57);
+        assert!(!restored.join(RESTORE_DIRECTORY).exists());
+    }
+
+    #[test]
+    fn backup_and_restore_carry_the_neighborhood_snapshot_along() {
+        let original = make_data_directory(
+            "backup_and_restore_carry_the_neighborhood_snapshot_along",
+            57,
+        );
+        let snapshot = "AQIDBA@1.2.3.4:1234\nBQYHCA@2.3.4.5:2345\n";
+        fs::write(original.join(NEIGHBORHOOD_SNAPSHOT_FILE), snapshot).unwrap();
+        let archive_path = original.parent().unwrap().join("node.archive");
+        let restored = original.parent().unwrap().join("restored");
+        let mut holder = FakeStreamHolder::new();
+
+        let backup_result = backup_data_with(
+            &archive_args("backup-data", &archive_path, &original)
+                .param("--archive-password", "password")
+                .into(),
+            &mut holder.streams(),
+            &PrivilegeDropperMock::new(),
+        );
+        let restore_result = restore_data_with(
+            &archive_args("restore-data", &archive_path, &restored)
+                .param("--archive-password", "password")
+                .into(),
+            &mut holder.streams(),
+            &PrivilegeDropperMock::new(),
+        );
+
+        assert_eq!(holder.stderr.get_string(), "");
+        assert_eq!(backup_result, 0);
+        assert_eq!(restore_result, 0);
+        assert_eq!(
+            fs::read_to_string(restored.join(NEIGHBORHOOD_SNAPSHOT_FILE)).unwrap(),
+            snapshot
+        );
+        assert!(restored.join(DATABASE_FILE).exists());
+    }
+
+    #[test]
+    fn backup_asks_for_a_password_it_is_not_given() {
+        let original = make_data_directory("backup_asks_for_a_password_it_is_not_given", 57);
+        let archive_path = original.parent().unwrap().join("node.archive");
+        let mut holder = FakeStreamHolder::new();
+        holder.stdin = ByteArrayReader::new(b"password\npassword\n");
+
+        let result = backup_data_with(
+            &archive_args("backup-data", &archive_path, &original).into(),
+            &mut holder.streams(),
+            &PrivilegeDropperMock::new(),
+        );
+
+        assert_eq!(result, 0);
+        let contents = open_archive(&fs::read(&archive_path).unwrap(), "password").unwrap();
+        assert_eq!(contents.chain_name, TEST_DEFAULT_CHAIN_NAME);
+        assert_eq!(contents.files[0].name, DATABASE_FILE);
+        assert_eq!(contents.files.len(), 1);
+    }
+
+    #[test]
+    fn backup_with_encrypt_database_needs_the_wallet_password() {
+        let original =
+            make_data_directory("backup_with_encrypt_database_needs_the_wallet_password", 57);
+        let archive_path = original.parent().unwrap().join("node.archive");
+        let mut holder = FakeStreamHolder::new();
+
+        let result = backup_data_with(
+            &archive_args("backup-data", &archive_path, &original)
+                .param("--archive-password", "password")
+                .opt("--encrypt-database")
+                .into(),
+            &mut holder.streams(),
+            &PrivilegeDropperMock::new(),
+        );
+
+        assert_eq!(result, 1);
+        assert_eq!(
+            holder.stderr.get_string(),
+            "--encrypt-database needs the wallet password: specify --wallet-password\n"
+        );
+        assert!(!archive_path.exists());
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn backup_and_restore_carry_an_encrypted_database() {
+        let original = make_data_directory("backup_and_restore_carry_an_encrypted_database", 57);
+        DbInitializerReal::with_password_opt(Some("wallet password".to_string()))
+            .initialize(&original, DEFAULT_CHAIN_ID)
+            .unwrap();
+        let archive_path = original.parent().unwrap().join("node.archive");
+        let restored = original.parent().unwrap().join("restored");
+        let mut holder = FakeStreamHolder::new();
+        let args = |mode: &str, data_directory: &Path| -> Vec<String> {
+            archive_args(mode, &archive_path, data_directory)
+                .param("--archive-password", "password")
+                .opt("--encrypt-database")
+                .param("--wallet-password", "wallet password")
+                .into()
+        };
+
+        let backup_result = backup_data_with(
+            &args("backup-data", &original),
+            &mut holder.streams(),
+            &PrivilegeDropperMock::new(),
+        );
+        let restore_result = restore_data_with(
+            &args("restore-data", &restored),
+            &mut holder.streams(),
+            &PrivilegeDropperMock::new(),
+        );
+
+        assert_eq!(holder.stderr.get_string(), "");
+        assert_eq!(backup_result, 0);
+        assert_eq!(restore_result, 0);
+        assert!(DbInitializerReal::new()
+            .initialize(&restored, DEFAULT_CHAIN_ID)
+            .is_err());
+        let conn = DbInitializerReal::with_password_opt(Some("wallet password".to_string()))
+            .initialize(&restored, DEFAULT_CHAIN_ID)
+            .unwrap();
+        assert_eq!(PersistentConfigurationReal::from(conn).gas_price(), 57);
+    }
+
+    #[test]
+    fn backup_will_not_overwrite_an_existing_file() {
+        let original = make_data_directory("backup_will_not_overwrite_an_existing_file", 57);
+        let archive_path = original.parent().unwrap().join("node.archive");
+        fs::write(&archive_path, b"precious").unwrap();
+        let mut holder = FakeStreamHolder::new();
+
+        let result = backup_data_with(
+            &archive_args("backup-data", &archive_path, &original)
+                .param("--archive-password", "password")
+                .into(),
+            &mut holder.streams(),
+            &PrivilegeDropperMock::new(),
+        );
+
+        assert_eq!(result, 1);
+        assert_eq!(
+            holder.stderr.get_string(),
+            format!("{} already exists\n", archive_path.display())
+        );
+        assert_eq!(fs::read(&archive_path).unwrap(), b"precious".to_vec());
+    }
+
+    #[test]
+    fn restore_will_not_overwrite_an_existing_database() {
+        let original = make_data_directory("restore_will_not_overwrite_an_existing_database", 57);
+        let archive_path = original.parent().unwrap().join("node.archive");
+        let contents = ArchiveContents {
+            chain_name: TEST_DEFAULT_CHAIN_NAME.to_string(),
+            files: vec![ArchivedFile {
+                name: DATABASE_FILE.to_string(),
+                data: vec![1, 2, 3, 4],
+            }],
+        };
+        fs::write(&archive_path, seal_archive(&contents, "password")).unwrap();
+        let mut holder = FakeStreamHolder::new();
+
+        let result = restore_data_with(
+            &archive_args("restore-data", &archive_path, &original)
+                .param("--archive-password", "password")
+                .into(),
+            &mut holder.streams(),
+            &PrivilegeDropperMock::new(),
+        );
+
+        assert_eq!(result, 1);
+        assert_eq!(
+            holder.stderr.get_string(),
+            format!(
+                "{} already has a database; move it out of the way before restoring over it\n",
+                original.display()
+            )
+        );
+        let conn = DbInitializerReal::new()
+            .initialize(&original, DEFAULT_CHAIN_ID)
+            .unwrap();
+        assert_eq!(PersistentConfigurationReal::from(conn).gas_price(), 57);
+    }
+
+    #[test]
+    fn restore_refuses_an_archive_for_another_chain() {
+        let home_dir = ensure_node_home_directory_exists(
+            "data_archive",
+            "restore_refuses_an_archive_for_another_chain",
+        );
+        let archive_path = home_dir.join("node.archive");
+        let contents = ArchiveContents {
+            chain_name: "mainnet".to_string(),
+            files: vec![],
+        };
+        fs::write(&archive_path, seal_archive(&contents, "password")).unwrap();
+        let mut holder = FakeStreamHolder::new();
+
+        let result = restore_data_with(
+            &archive_args("restore-data", &archive_path, &home_dir.join("restored"))
+                .param("--archive-password", "password")
+                .into(),
+            &mut holder.streams(),
+            &PrivilegeDropperMock::new(),
+        );
+
+        assert_eq!(result, 1);
+        assert_eq!(
+            holder.stderr.get_string(),
+            format!(
+                "The archive is for mainnet, not {}; specify --chain mainnet\n",
+                TEST_DEFAULT_CHAIN_NAME
+            )
+        );
+    }
+
+    #[test]
+    fn restore_leaves_nothing_behind_when_the_database_is_unusable() {
+        let home_dir = ensure_node_home_directory_exists(
+            "data_archive",
+            "restore_leaves_nothing_behind_when_the_database_is_unusable",
+        );
+        let archive_path = home_dir.join("node.archive");
+        let restored = home_dir.join("restored");
+        let contents = ArchiveContents {
+            chain_name: TEST_DEFAULT_CHAIN_NAME.to_string(),
+            files: vec![ArchivedFile {
+                name: DATABASE_FILE.to_string(),
+                data: b"not a database".to_vec(),
+            }],
+        };
+        fs::write(&archive_path, seal_archive(&contents, "password")).unwrap();
+        let mut holder = FakeStreamHolder::new();
+
+        let result = restore_data_with(
+            &archive_args("restore-data", &archive_path, &restored)
+                .param("--archive-password", "password")
+                .into(),
+            &mut holder.streams(),
+            &PrivilegeDropperMock::new(),
+        );
+
+        assert_eq!(result, 1);
+        assert!(holder
+            .stderr
+            .get_string()
+            .starts_with("The archived database can't be used: "));
+        assert!(!restored.join(DATABASE_FILE).exists());
+        assert!(!restored.join(RESTORE_DIRECTORY).exists());
+    }
+}
@@ -0,0 +1,246 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::database::db_initializer::InitializationError;
+use rusqlite::{Connection, ErrorCode, NO_PARAMS};
+use std::path::Path;
+
+// With SQLCipher, a password given to a connection before it touches the file becomes the key the
+// whole file is encrypted with (by way of SQLCipher's own key derivation, salted per database).
+// Plain SQLite would ignore the pragma and leave the file readable, so without SQLCipher a password
+// is refused instead.
+#[cfg(feature = "sqlcipher")]
+pub fn apply_password(conn: &Connection, password: &str) -> Result<(), InitializationError> {
+    conn.execute_batch(&format!("pragma key = {}", quote(password)))
+        .map_err(InitializationError::SqliteError)
+}
+
+#[cfg(not(feature = "sqlcipher"))]
+pub fn apply_password(_conn: &Connection, _password: &str) -> Result<(), InitializationError> {
+    Err(InitializationError::EncryptionUnavailable)
+}
+
+// False if the file is encrypted and the connection doesn't have its password
+pub fn is_readable(conn: &Connection) -> Result<bool, InitializationError> {
+    match conn.query_row("select count(*) from sqlite_master", NO_PARAMS, |row| {
+        row.get::<_, i64>(0)
+    }) {
+        Ok(_) => Ok(true),
+        Err(rusqlite::Error::SqliteFailure(ref e, _)) if e.code == ErrorCode::NotADatabase => {
+            Ok(false)
+        }
+        Err(e) => Err(InitializationError::SqliteError(e)),
+    }
+}
+
+// Copies an unencrypted database into an encrypted one beside it, then puts the encrypted one in
+// its place. If anything goes wrong, the original is left where it was.
+#[cfg(feature = "sqlcipher")]
+pub fn encrypt_in_place(database_path: &Path, password: &str) -> Result<(), InitializationError> {
+    let encrypted_path = database_path.with_extension("db.encrypting");
+    let _ = std::fs::remove_file(&encrypted_path);
+    let conn = Connection::open(database_path).map_err(InitializationError::SqliteError)?;
+    empty_write_ahead_log(&conn)?;
+    conn.execute_batch(&format!(
+        "attach database {} as encrypted key {}; select sqlcipher_export('encrypted'); detach database encrypted;",
+        quote(&encrypted_path.to_string_lossy()),
+        quote(password)
+    ))
+    .map_err(InitializationError::SqliteError)?;
+    drop(conn);
+    // The log has been emptied, but the files are still there; left beside the encrypted database,
+    // they'd be read as part of it
+    remove_sidecars(database_path)
+        .and_then(|_| {
+            std::fs::rename(&encrypted_path, database_path).map_err(|e| {
+                format!(
+                    "Couldn't replace {:?} with its encrypted copy: {}",
+                    database_path, e
+                )
+            })
+        })
+        .map_err(|msg| {
+            let _ = std::fs::remove_file(&encrypted_path);
+            InitializationError::FileError(msg)
+        })
+}
+
+// In WAL mode, the latest changes may be in the write-ahead log rather than the database file. This
+// moves them into the file, so that the encrypted copy has them, and leaves the database out of WAL
+// mode, so that the log isn't written again before the copy replaces it.
+#[cfg(feature = "sqlcipher")]
+fn empty_write_ahead_log(conn: &Connection) -> Result<(), InitializationError> {
+    let busy = conn
+        .query_row("pragma wal_checkpoint(TRUNCATE)", NO_PARAMS, |row| {
+            row.get::<_, i64>(0)
+        })
+        .map_err(InitializationError::SqliteError)?;
+    if busy != 0 {
+        return Err(InitializationError::SqliteError(
+            rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+                Some("Another connection is using the database; it can't be encrypted".to_string()),
+            ),
+        ));
+    }
+    conn.query_row("pragma journal_mode = delete", NO_PARAMS, |row| {
+        row.get::<_, String>(0)
+    })
+    .map(|_| ())
+    .map_err(InitializationError::SqliteError)
+}
+
+#[cfg(feature = "sqlcipher")]
+fn remove_sidecars(database_path: &Path) -> Result<(), String> {
+    ["-wal", "-shm"].iter().try_for_each(|suffix| {
+        let mut sidecar = database_path.as_os_str().to_os_string();
+        sidecar.push(suffix);
+        match std::fs::remove_file(&sidecar) {
+            Ok(()) => Ok(()),
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!("Couldn't remove plaintext {:?}: {}", sidecar, e)),
+        }
+    })
+}
+
+#[cfg(not(feature = "sqlcipher"))]
+pub fn encrypt_in_place(_database_path: &Path, _password: &str) -> Result<(), InitializationError> {
+    Err(InitializationError::EncryptionUnavailable)
+}
+
+// Pragmas and attach statements don't take parameters, so the password has to be a literal
+#[cfg(feature = "sqlcipher")]
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::ensure_node_home_directory_exists;
+
+    #[test]
+    fn a_plain_database_is_readable() {
+        let home_dir =
+            ensure_node_home_directory_exists("db_encryption", "a_plain_database_is_readable");
+        let conn = Connection::open(home_dir.join("plain.db")).unwrap();
+        conn.execute("create table booga (value text)", NO_PARAMS)
+            .unwrap();
+
+        assert_eq!(is_readable(&conn), Ok(true));
+    }
+
+    #[test]
+    fn something_that_is_not_a_database_is_not_readable() {
+        let home_dir = ensure_node_home_directory_exists(
+            "db_encryption",
+            "something_that_is_not_a_database_is_not_readable",
+        );
+        let path = home_dir.join("garbage.db");
+        std::fs::write(&path, vec![0xA5u8; 4096]).unwrap();
+        let conn = Connection::open(&path).unwrap();
+
+        assert_eq!(is_readable(&conn), Ok(false));
+    }
+
+    #[cfg(not(feature = "sqlcipher"))]
+    #[test]
+    fn passwords_are_refused_without_sqlcipher() {
+        let conn = Connection::open_in_memory().unwrap();
+
+        assert_eq!(
+            apply_password(&conn, "password"),
+            Err(InitializationError::EncryptionUnavailable)
+        );
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn quote_doubles_single_quotes() {
+        assert_eq!(quote("it's"), "'it''s'".to_string());
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn encrypting_leaves_no_plaintext_write_ahead_log_behind() {
+        let home_dir = ensure_node_home_directory_exists(
+            "db_encryption",
+            "encrypting_leaves_no_plaintext_write_ahead_log_behind",
+        );
+        let live_path = home_dir.join("live.db");
+        let path = home_dir.join("copy.db");
+        let sidecar = |path: &Path, suffix: &str| {
+            let mut sidecar = path.as_os_str().to_os_string();
+            sidecar.push(suffix);
+            std::path::PathBuf::from(sidecar)
+        };
+        {
+            let conn = Connection::open(&live_path).unwrap();
+            conn.query_row("pragma journal_mode = wal", NO_PARAMS, |row| {
+                row.get::<_, String>(0)
+            })
+            .unwrap();
+            conn.execute_batch(
+                "pragma wal_autocheckpoint = 0; create table booga (value text); insert into booga values ('SECRET-SECRET')",
+            )
+            .unwrap();
+            // As a Node that was killed would leave it: the newest pages only in the log
+            ["", "-wal", "-shm"].iter().for_each(|suffix| {
+                std::fs::copy(sidecar(&live_path, suffix), sidecar(&path, suffix)).unwrap();
+            });
+        }
+        assert!(std::fs::read(sidecar(&path, "-wal"))
+            .unwrap()
+            .windows(13)
+            .any(|window| window == b"SECRET-SECRET"));
+
+        encrypt_in_place(&path, "password").unwrap();
+
+        assert!(!sidecar(&path, "-wal").exists());
+        assert!(!sidecar(&path, "-shm").exists());
+        assert!(!std::fs::read(&path)
+            .unwrap()
+            .windows(13)
+            .any(|window| window == b"SECRET-SECRET"));
+        let conn = Connection::open(&path).unwrap();
+        apply_password(&conn, "password").unwrap();
+        let value: String = conn
+            .query_row("select value from booga", NO_PARAMS, |row| row.get(0))
+            .unwrap();
+        assert_eq!(value, "SECRET-SECRET".to_string());
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn a_failure_to_move_files_leaves_the_original_database_in_place() {
+        let home_dir = ensure_node_home_directory_exists(
+            "db_encryption",
+            "a_failure_to_move_files_leaves_the_original_database_in_place",
+        );
+        let path = home_dir.join("plain.db");
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "create table booga (value text); insert into booga values ('PLAINTEXT')",
+            )
+            .unwrap();
+        }
+        // Not a file, so it can't be removed
+        let mut sidecar = path.as_os_str().to_os_string();
+        sidecar.push("-shm");
+        std::fs::create_dir(&sidecar).unwrap();
+
+        let result = encrypt_in_place(&path, "password");
+
+        match result {
+            Err(InitializationError::FileError(msg)) => {
+                assert!(msg.starts_with("Couldn't remove plaintext"), "{}", msg)
+            }
+            other => panic!("Expected FileError, got {:?}", other),
+        }
+        assert!(!path.with_extension("db.encrypting").exists());
+        let conn = Connection::open(&path).unwrap();
+        let value: String = conn
+            .query_row("select value from booga", NO_PARAMS, |row| row.get(0))
+            .unwrap();
+        assert_eq!(value, "PLAINTEXT".to_string());
+    }
+}
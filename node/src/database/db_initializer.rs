@@ -2,31 +2,71 @@
 use crate::blockchain::blockchain_interface::{
     chain_name_from_id, contract_creation_block_from_chain_id, DEFAULT_GAS_PRICE,
 };
+use crate::database::db_encryption::{apply_password, encrypt_in_place, is_readable};
+use crate::database::db_migrations::{upgrade, MIGRATIONS};
 use crate::persistent_configuration::{
     HIGHEST_RANDOM_CLANDESTINE_PORT, LOWEST_USABLE_INSECURE_PORT,
 };
-use rand::prelude::*;
+use crate::sub_lib::accountant::PAYMENT_PARAMETERS;
+use crate::sub_lib::logger::Logger;
+use crate::sub_lib::random::{Random, RandomReal};
+use lazy_static::lazy_static;
 use rusqlite::Error::InvalidColumnType;
-use rusqlite::{Connection, Error, OpenFlags, Statement, Transaction, NO_PARAMS};
+use rusqlite::{
+    Connection, Error, OpenFlags, Statement, Transaction, TransactionBehavior, NO_PARAMS,
+};
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::fs;
 use std::io::ErrorKind;
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
 use tokio::net::TcpListener;
 
 pub const DATABASE_FILE: &str = "node-data.db";
-pub const CURRENT_SCHEMA_VERSION: &str = "0.0.9";
+pub const CURRENT_SCHEMA_VERSION: &str = "0.0.16";
+// Shared with the migration that adds the table
+pub const PENDING_TRANSACTION_TABLE_SQL: &str = "create table if not exists pending_transaction (
+    consuming_wallet_address text not null,
+    nonce integer not null,
+    wallet_address text not null,
+    amount integer not null,
+    payment_timestamp integer not null,
+    transaction_hash text not null,
+    max_fee_per_gas text not null,
+    max_priority_fee_per_gas text null,
+    replacements text not null,
+    cancellation text null,
+    last_sent integer not null,
+    primary key (consuming_wallet_address, nonce)
+)";
+// How long a connection waits for another one to finish writing before giving up with "database is
+// locked"; Node's writes are small, so anything near this means something is stuck
+pub const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+
+lazy_static! {
+    // The writer for each database file that has a connection open, so that there's only ever one
+    static ref WRITERS: Mutex<HashMap<PathBuf, Weak<DatabaseWriter>>> = Mutex::new(HashMap::new());
+}
 
 pub trait ConnectionWrapper: Debug + Send {
+    // For reading; anything that changes the database goes through write()
     fn prepare(&self, query: &str) -> Result<Statement, rusqlite::Error>;
-    fn transaction(&mut self) -> Result<Transaction, rusqlite::Error>;
+    // Runs the action in a transaction on the database's writer, committing it if the action
+    // succeeds and rolling it back if it fails. Don't call write() from inside the action.
+    fn write(
+        &self,
+        action: &mut dyn FnMut(&Transaction) -> Result<(), String>,
+    ) -> Result<(), String>;
 }
 
 #[derive(Debug)]
 pub struct ConnectionWrapperReal {
     conn: Connection,
+    writer: Arc<DatabaseWriter>,
 }
 
 impl ConnectionWrapper for ConnectionWrapperReal {
@@ -34,14 +74,48 @@ impl ConnectionWrapper for ConnectionWrapperReal {
         self.conn.prepare(query)
     }
 
-    fn transaction(&mut self) -> Result<Transaction, Error> {
-        self.conn.transaction()
+    fn write(
+        &self,
+        action: &mut dyn FnMut(&Transaction) -> Result<(), String>,
+    ) -> Result<(), String> {
+        self.writer.write(action)
     }
 }
 
 impl ConnectionWrapperReal {
+    pub fn new(conn: Connection, writer: Arc<DatabaseWriter>) -> Self {
+        Self { conn, writer }
+    }
+}
+
+// Owns the one connection that writes to a database, and hands it to one writer at a time. Every
+// actor reads on its own connection, but their writes queue up here rather than contending for
+// SQLite's write lock, where a transaction that reads before it writes can fail with "database is
+// locked" however long the busy timeout.
+#[derive(Debug)]
+pub struct DatabaseWriter {
+    conn: Mutex<Connection>,
+}
+
+impl DatabaseWriter {
     pub fn new(conn: Connection) -> Self {
-        Self { conn }
+        Self {
+            conn: Mutex::new(conn),
+        }
+    }
+
+    pub fn write(
+        &self,
+        action: &mut dyn FnMut(&Transaction) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let mut conn = self.conn.lock().expect("DatabaseWriter is poisoned");
+        // Another process may be writing too, so the lock is taken at the start, where the busy
+        // timeout applies
+        let tx = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(|e| e.to_string())?;
+        action(&tx)?;
+        tx.commit().map_err(|e| e.to_string())
     }
 }
 
@@ -49,6 +123,13 @@ impl ConnectionWrapperReal {
 pub enum InitializationError {
     IncompatibleVersion(String),
     SqliteError(rusqlite::Error),
+    // The database is encrypted, and there's no password or the wrong one
+    Undecryptable,
+    // A password was given, but this Node was built without SQLCipher
+    EncryptionUnavailable,
+    // A file couldn't be moved or removed while encrypting the database; the original is left as
+    // it was
+    FileError(String),
 }
 
 pub trait DbInitializer {
@@ -59,8 +140,10 @@ pub trait DbInitializer {
     ) -> Result<Box<dyn ConnectionWrapper>, InitializationError>;
 }
 
-#[derive(Default)]
-pub struct DbInitializerReal {}
+pub struct DbInitializerReal {
+    password_opt: Option<String>,
+    random: Box<dyn Random>,
+}
 
 impl DbInitializer for DbInitializerReal {
     fn initialize(
@@ -74,21 +157,34 @@ impl DbInitializer for DbInitializerReal {
         let database_file_path = &path.join(DATABASE_FILE);
         match Connection::open_with_flags(database_file_path, flags) {
             Ok(conn) => {
+                let mut conn = self.unlock(conn, database_file_path)?;
+                Self::share(&conn)?;
                 let config = self.extract_configurations(&conn);
-                match self.check_version(config.get("schema_version")) {
-                    Ok(_) => Ok(Box::new(ConnectionWrapperReal::new(conn))),
-                    Err(e) => Err(e),
-                }
+                let found_version = self.found_version(config.get("schema_version"))?;
+                upgrade(
+                    &mut conn,
+                    &MIGRATIONS,
+                    &found_version,
+                    CURRENT_SCHEMA_VERSION,
+                    chain_id,
+                )?;
+                let writer = self.writer(database_file_path)?;
+                Ok(Box::new(ConnectionWrapperReal::new(conn, writer)))
             }
             Err(_) => {
                 let mut flags = OpenFlags::empty();
                 flags.insert(OpenFlags::SQLITE_OPEN_READ_WRITE);
                 flags.insert(OpenFlags::SQLITE_OPEN_CREATE);
                 match Connection::open_with_flags(database_file_path, flags) {
-                    Ok(conn) => match self.create_database_tables(&conn, chain_id) {
-                        Ok(()) => Ok(Box::new(ConnectionWrapperReal::new(conn))),
-                        Err(e) => Err(e),
-                    },
+                    Ok(conn) => {
+                        if let Some(password) = &self.password_opt {
+                            apply_password(&conn, password)?;
+                        }
+                        Self::share(&conn)?;
+                        self.create_database_tables(&conn, chain_id)?;
+                        let writer = self.writer(database_file_path)?;
+                        Ok(Box::new(ConnectionWrapperReal::new(conn, writer)))
+                    }
                     Err(e) => Err(InitializationError::SqliteError(e)),
                 }
             }
@@ -96,11 +192,168 @@ impl DbInitializer for DbInitializerReal {
     }
 }
 
+// Keeps the database in memory instead of in the data directory, for tests and for platforms with no
+// filesystem to speak of. Every connection it hands out shares one database, whatever path it's
+// given, and the database lasts exactly as long as the initializer. SQLite locks a shared in-memory
+// database by table and doesn't wait for the locks, so a read of a table while the writer is
+// writing it will see "database table is locked".
+pub struct DbInitializerInMemory {
+    uri: String,
+    // Open from the start, so the database outlives every other connection closing
+    writer: Arc<DatabaseWriter>,
+    created: Mutex<bool>,
+}
+
+impl DbInitializer for DbInitializerInMemory {
+    fn initialize(
+        &self,
+        _path: &PathBuf,
+        chain_id: u8,
+    ) -> Result<Box<dyn ConnectionWrapper>, InitializationError> {
+        let mut created = self
+            .created
+            .lock()
+            .expect("DbInitializerInMemory is poisoned");
+        let conn = Self::open(&self.uri)?;
+        if !*created {
+            DbInitializerReal::new().create_database_tables(&conn, chain_id)?;
+            *created = true;
+        }
+        Ok(Box::new(ConnectionWrapperReal::new(
+            conn,
+            self.writer.clone(),
+        )))
+    }
+}
+
+impl Default for DbInitializerInMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DbInitializerInMemory {
+    pub fn new() -> Self {
+        static NEXT_DATABASE: AtomicUsize = AtomicUsize::new(0);
+        let uri = format!(
+            "file:node-data-{}-{}?mode=memory&cache=shared",
+            std::process::id(),
+            NEXT_DATABASE.fetch_add(1, Ordering::Relaxed)
+        );
+        let anchor = Self::open(&uri).expect("Can't create in-memory database");
+        Self {
+            uri,
+            writer: Arc::new(DatabaseWriter::new(anchor)),
+            created: Mutex::new(false),
+        }
+    }
+
+    fn open(uri: &str) -> Result<Connection, InitializationError> {
+        let mut flags = OpenFlags::empty();
+        flags.insert(OpenFlags::SQLITE_OPEN_READ_WRITE);
+        flags.insert(OpenFlags::SQLITE_OPEN_CREATE);
+        flags.insert(OpenFlags::SQLITE_OPEN_URI);
+        Connection::open_with_flags(uri, flags).map_err(InitializationError::SqliteError)
+    }
+}
+
+impl Default for DbInitializerReal {
+    fn default() -> Self {
+        Self::with_password_opt(None)
+    }
+}
+
 impl DbInitializerReal {
     pub fn new() -> Self {
         Self::default()
     }
 
+    // With a password, the database is opened with SQLCipher, and created encrypted if it's new.
+    // One that was created unencrypted is encrypted the first time it's opened with a password.
+    pub fn with_password_opt(password_opt: Option<String>) -> Self {
+        Self {
+            password_opt,
+            random: Box::new(RandomReal {}),
+        }
+    }
+
+    // The clandestine port chosen for a new database comes from here
+    pub fn with_random(mut self, random: Box<dyn Random>) -> Self {
+        self.random = random;
+        self
+    }
+
+    // Node's actors each have their own connection. In WAL mode they go on reading while the writer
+    // writes, and anything else writing to the file waits for as long as the busy timeout instead of
+    // failing at once. WAL mode is recorded in the file, so the pragma only does anything the first
+    // time.
+    fn share(conn: &Connection) -> Result<(), InitializationError> {
+        conn.busy_timeout(BUSY_TIMEOUT)
+            .map_err(InitializationError::SqliteError)?;
+        conn.query_row("pragma journal_mode = wal", NO_PARAMS, |row| {
+            row.get::<_, String>(0)
+        })
+        .map(|_| ())
+        .map_err(InitializationError::SqliteError)
+    }
+
+    // The writer already open for the database if there is one, or a new one. Called once the
+    // database is known to be unlocked and up to date.
+    fn writer(
+        &self,
+        database_file_path: &PathBuf,
+    ) -> Result<Arc<DatabaseWriter>, InitializationError> {
+        let mut writers = WRITERS.lock().expect("Database writers are poisoned");
+        if let Some(writer) = writers.get(database_file_path).and_then(Weak::upgrade) {
+            return Ok(writer);
+        }
+        let conn = Self::reopen(database_file_path)?;
+        if let Some(password) = &self.password_opt {
+            apply_password(&conn, password)?;
+        }
+        Self::share(&conn)?;
+        let writer = Arc::new(DatabaseWriter::new(conn));
+        writers.retain(|_, writer| writer.upgrade().is_some());
+        writers.insert(database_file_path.clone(), Arc::downgrade(&writer));
+        Ok(writer)
+    }
+
+    fn unlock(
+        &self,
+        conn: Connection,
+        database_file_path: &PathBuf,
+    ) -> Result<Connection, InitializationError> {
+        let password = match &self.password_opt {
+            None if is_readable(&conn)? => return Ok(conn),
+            None => return Err(InitializationError::Undecryptable),
+            Some(password) => password,
+        };
+        apply_password(&conn, password)?;
+        if is_readable(&conn)? {
+            return Ok(conn);
+        }
+        // Either the password is wrong or the database has never been encrypted
+        drop(conn);
+        let conn = Self::reopen(database_file_path)?;
+        if !is_readable(&conn)? {
+            return Err(InitializationError::Undecryptable);
+        }
+        drop(conn);
+        encrypt_in_place(database_file_path, password)?;
+        let logger = Logger::new("DbInitializer");
+        info!(logger, "Encrypted the database at {:?}", database_file_path);
+        let conn = Self::reopen(database_file_path)?;
+        apply_password(&conn, password)?;
+        Ok(conn)
+    }
+
+    fn reopen(database_file_path: &PathBuf) -> Result<Connection, InitializationError> {
+        let mut flags = OpenFlags::empty();
+        flags.insert(OpenFlags::SQLITE_OPEN_READ_WRITE);
+        Connection::open_with_flags(database_file_path, flags)
+            .map_err(InitializationError::SqliteError)
+    }
+
     fn create_data_directory_if_necessary(data_directory: &PathBuf) {
         match fs::read_dir(data_directory) {
             Ok(_) => (),
@@ -127,7 +380,10 @@ impl DbInitializerReal {
         self.initialize_config(conn, chain_id)?;
         self.create_payable_table(conn)?;
         self.create_receivable_table(conn)?;
-        self.create_banned_table(conn)
+        self.create_banned_table(conn)?;
+        self.create_dispute_table(conn)?;
+        self.create_profile_consumption_table(conn)?;
+        self.create_pending_transaction_table(conn)
     }
 
     fn create_config_table(&self, conn: &Connection) -> Result<(), InitializationError> {
@@ -155,7 +411,7 @@ impl DbInitializerReal {
         Self::set_config_value(
             conn,
             "clandestine_port",
-            Some(&Self::choose_clandestine_port().to_string()),
+            Some(&self.choose_clandestine_port().to_string()),
             "clandestine port",
         );
         Self::set_config_value(
@@ -176,6 +432,12 @@ impl DbInitializerReal {
             None,
             "earning wallet address",
         );
+        Self::set_config_value(
+            conn,
+            "exit_earning_wallet_address",
+            None,
+            "exit earning wallet address",
+        );
         Self::set_config_value(
             conn,
             "schema_version",
@@ -190,22 +452,57 @@ impl DbInitializerReal {
             format!("{} start block", chain_name_from_id(chain_id)).as_str(),
         );
         Self::set_config_value(conn, "gas_price", Some(DEFAULT_GAS_PRICE), "gas price");
+        Self::set_config_value(conn, "next_nonce", None, "next consuming wallet nonce");
+        Self::set_config_value(
+            conn,
+            "fee_budget_period_start",
+            None,
+            "start of the current fee budget period",
+        );
+        Self::set_config_value(
+            conn,
+            "fee_budget_spent",
+            None,
+            "fees spent in the current fee budget period",
+        );
+        Self::set_config_value(
+            conn,
+            "detected_dns_servers",
+            None,
+            "DNS servers found on this system when none were specified",
+        );
+        Self::set_config_value(
+            conn,
+            "split_dns_domains",
+            None,
+            "domains looked up without the Node",
+        );
+        PAYMENT_PARAMETERS.iter().for_each(|parameter| {
+            Self::set_config_value(
+                conn,
+                &parameter.config_key(),
+                Some(&parameter.default_value().to_string()),
+                parameter.name(),
+            )
+        });
         Ok(())
     }
 
     fn create_payable_table(&self, conn: &Connection) -> Result<(), InitializationError> {
         conn.execute(
             "create table if not exists payable (
-                wallet_address text primary key,
+                wallet_address text not null,
+                consuming_wallet_address text not null default '',
                 balance integer not null,
                 last_paid_timestamp integer not null,
-                pending_payment_transaction text null
+                pending_payment_transaction text null,
+                primary key (wallet_address, consuming_wallet_address)
             )",
             NO_PARAMS,
         )
         .expect("Can't create payable table");
         conn.execute(
-            "create unique index if not exists idx_payable_wallet_address on payable (wallet_address)",
+            "create unique index if not exists idx_payable_wallet_address on payable (wallet_address, consuming_wallet_address)",
             NO_PARAMS,
         )
         .expect("Can't create payable wallet_address index");
@@ -244,6 +541,36 @@ impl DbInitializerReal {
         Ok(())
     }
 
+    fn create_dispute_table(&self, conn: &Connection) -> Result<(), InitializationError> {
+        conn.execute(
+            "create table dispute ( wallet_address text primary key, expiration integer not null )",
+            NO_PARAMS,
+        )
+        .expect("Can't create dispute table");
+        Ok(())
+    }
+
+    fn create_profile_consumption_table(
+        &self,
+        conn: &Connection,
+    ) -> Result<(), InitializationError> {
+        conn.execute(
+            "create table profile_consumption ( name text primary key, consumed integer not null )",
+            NO_PARAMS,
+        )
+        .expect("Can't create profile_consumption table");
+        Ok(())
+    }
+
+    fn create_pending_transaction_table(
+        &self,
+        conn: &Connection,
+    ) -> Result<(), InitializationError> {
+        conn.execute(PENDING_TRANSACTION_TABLE_SQL, NO_PARAMS)
+            .expect("Can't create pending_transaction table");
+        Ok(())
+    }
+
     fn extract_configurations(&self, conn: &Connection) -> HashMap<String, Option<String>> {
         let mut stmt = conn.prepare("select name, value from config").unwrap();
         let query_result = stmt.query_map(NO_PARAMS, |row| Ok((row.get(0), row.get(1))));
@@ -259,7 +586,10 @@ impl DbInitializerReal {
         .collect::<HashMap<String, Option<String>>>()
     }
 
-    fn check_version(&self, version: Option<&Option<String>>) -> Result<(), InitializationError> {
+    fn found_version(
+        &self,
+        version: Option<&Option<String>>,
+    ) -> Result<String, InitializationError> {
         match version {
             None => Err(InitializationError::IncompatibleVersion(format!(
                 "Need {}, found nothing",
@@ -269,24 +599,16 @@ impl DbInitializerReal {
                 "Need {}, found nothing",
                 CURRENT_SCHEMA_VERSION
             ))),
-            Some(Some(v_ref)) => {
-                if *v_ref == CURRENT_SCHEMA_VERSION {
-                    Ok(())
-                } else {
-                    Err(InitializationError::IncompatibleVersion(format!(
-                        "Need {}, found {}",
-                        CURRENT_SCHEMA_VERSION, v_ref
-                    )))
-                }
-            }
+            Some(Some(v_ref)) => Ok(v_ref.clone()),
         }
     }
 
-    fn choose_clandestine_port() -> u16 {
-        let mut rng = SmallRng::from_entropy();
+    fn choose_clandestine_port(&self) -> u16 {
         loop {
-            let candidate_port: u16 =
-                rng.gen_range(LOWEST_USABLE_INSECURE_PORT, HIGHEST_RANDOM_CLANDESTINE_PORT);
+            let candidate_port = self.random.range(
+                LOWEST_USABLE_INSECURE_PORT as u64,
+                HIGHEST_RANDOM_CLANDESTINE_PORT as u64,
+            ) as u16;
             match TcpListener::bind(&SocketAddr::V4(SocketAddrV4::new(
                 Ipv4Addr::from(0),
                 candidate_port,
@@ -326,7 +648,7 @@ pub mod test_utils {
     pub struct ConnectionWrapperMock<'a> {
         pub prepare_parameters: Arc<Mutex<Vec<String>>>,
         pub prepare_results: RefCell<Vec<Result<Statement<'a>, Error>>>,
-        pub transaction_results: RefCell<Vec<Result<Transaction<'a>, Error>>>,
+        pub write_results: RefCell<Vec<Result<(), String>>>,
     }
 
     unsafe impl<'a> Send for ConnectionWrapperMock<'a> {}
@@ -337,8 +659,8 @@ pub mod test_utils {
             self
         }
 
-        pub fn transaction_result(self, result: Result<Transaction<'a>, Error>) -> Self {
-            self.transaction_results.borrow_mut().push(result);
+        pub fn write_result(self, result: Result<(), String>) -> Self {
+            self.write_results.borrow_mut().push(result);
             self
         }
     }
@@ -352,8 +674,11 @@ pub mod test_utils {
             self.prepare_results.borrow_mut().remove(0)
         }
 
-        fn transaction(&mut self) -> Result<Transaction, Error> {
-            self.transaction_results.borrow_mut().remove(0)
+        fn write(
+            &self,
+            _action: &mut dyn FnMut(&Transaction) -> Result<(), String>,
+        ) -> Result<(), String> {
+            self.write_results.borrow_mut().remove(0)
         }
     }
 
@@ -404,9 +729,16 @@ pub mod test_utils {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::accountant::payable_dao::{PayableDao, PayableDaoReal};
+    use crate::accountant::receivable_dao::{ReceivableDao, ReceivableDaoReal};
     use crate::blockchain::blockchain_interface::chain_id_from_name;
+    use crate::config_dao::{ConfigDao, ConfigDaoReal};
+    use crate::sub_lib::accountant::PaymentParameter;
+    #[cfg(feature = "sqlcipher")]
+    use crate::test_utils::logging::{init_test_logging, TestLogHandler};
+    use crate::test_utils::random_seeded::RandomSeeded;
     use crate::test_utils::{
-        ensure_node_home_directory_does_not_exist, ensure_node_home_directory_exists,
+        ensure_node_home_directory_does_not_exist, ensure_node_home_directory_exists, make_wallet,
         DEFAULT_CHAIN_ID, TEST_DEFAULT_CHAIN_NAME,
     };
     use rusqlite::types::Type::Null;
@@ -414,6 +746,7 @@ mod tests {
     use std::fs::File;
     use std::io::{Read, Write};
     use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+    use std::thread;
     use tokio::net::TcpListener;
 
     #[test]
@@ -475,6 +808,27 @@ mod tests {
         assert!(banned_contents.next().is_none());
     }
 
+    #[test]
+    fn db_initialize_creates_dispute_table() {
+        let home_dir = ensure_node_home_directory_does_not_exist(
+            "accountant",
+            "db_initialize_creates_dispute_table",
+        );
+        let subject = DbInitializerReal::new();
+
+        subject.initialize(&home_dir, DEFAULT_CHAIN_ID).unwrap();
+
+        let mut flags = OpenFlags::empty();
+        flags.insert(OpenFlags::SQLITE_OPEN_READ_ONLY);
+        let conn = Connection::open_with_flags(&home_dir.join(DATABASE_FILE), flags).unwrap();
+
+        let mut stmt = conn
+            .prepare("select wallet_address, expiration from dispute")
+            .unwrap();
+        let mut dispute_contents = stmt.query_map(NO_PARAMS, |_| Ok(42)).unwrap();
+        assert!(dispute_contents.next().is_none());
+    }
+
     #[test]
     fn existing_database_with_correct_version_is_accepted_without_changes() {
         let home_dir = ensure_node_home_directory_exists(
@@ -516,14 +870,37 @@ mod tests {
             assert_eq!(actual_name, expected_name);
             value
         };
+        let verify_payment_parameter =
+            |cv: &mut Vec<(String, Option<String>)>, parameter: PaymentParameter| {
+                verify(
+                    cv,
+                    &parameter.config_key(),
+                    Some(&parameter.default_value().to_string()),
+                )
+            };
+        verify_payment_parameter(&mut config_vec, PaymentParameter::BalanceDecreasesFor);
+        verify_payment_parameter(&mut config_vec, PaymentParameter::BalanceToDecreaseFrom);
         let clandestine_port_str_opt = verify_name(&mut config_vec, "clandestine_port");
         let clandestine_port: u16 = clandestine_port_str_opt.unwrap().parse().unwrap();
         assert!(clandestine_port >= 1025);
         assert!(clandestine_port < 10000);
         verify(&mut config_vec, "consuming_wallet_derivation_path", None);
         verify(&mut config_vec, "consuming_wallet_public_key", None);
+        verify(&mut config_vec, "detected_dns_servers", None);
         verify(&mut config_vec, "earning_wallet_address", None);
+        verify(&mut config_vec, "exit_earning_wallet_address", None);
+        verify(&mut config_vec, "fee_budget_period_start", None);
+        verify(&mut config_vec, "fee_budget_spent", None);
         verify(&mut config_vec, "gas_price", Some(DEFAULT_GAS_PRICE));
+        verify(&mut config_vec, "next_nonce", None);
+        verify_payment_parameter(&mut config_vec, PaymentParameter::PayableScanInterval);
+        verify_payment_parameter(&mut config_vec, PaymentParameter::PaymentGraceBeforeBan);
+        verify_payment_parameter(
+            &mut config_vec,
+            PaymentParameter::PaymentReceivedScanInterval,
+        );
+        verify_payment_parameter(&mut config_vec, PaymentParameter::PaymentSuggestedAfter);
+        verify_payment_parameter(&mut config_vec, PaymentParameter::PermanentDebtAllowed);
         verify(&mut config_vec, "preexisting", Some("yes")); // makes sure we just created this database
         verify(
             &mut config_vec,
@@ -531,6 +908,7 @@ mod tests {
             Some(CURRENT_SCHEMA_VERSION),
         );
         verify(&mut config_vec, "seed", None);
+        verify(&mut config_vec, "split_dns_domains", None);
         verify(
             &mut config_vec,
             "start_block",
@@ -539,6 +917,7 @@ mod tests {
                 contract_creation_block_from_chain_id(chain_id_from_name(TEST_DEFAULT_CHAIN_NAME))
             )),
         );
+        verify_payment_parameter(&mut config_vec, PaymentParameter::UnbanWhenBalanceBelow);
         assert_eq!(config_vec, vec![]);
     }
 
@@ -606,11 +985,357 @@ mod tests {
         );
     }
 
+    #[cfg(not(feature = "sqlcipher"))]
+    #[test]
+    fn a_password_is_refused_without_sqlcipher() {
+        let home_dir = ensure_node_home_directory_exists(
+            "db_initializer",
+            "a_password_is_refused_without_sqlcipher",
+        );
+        let subject = DbInitializerReal::with_password_opt(Some("password".to_string()));
+
+        let result = subject.initialize(&home_dir, DEFAULT_CHAIN_ID);
+
+        assert_eq!(
+            result.err().unwrap(),
+            InitializationError::EncryptionUnavailable
+        );
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn an_encrypted_database_opens_only_with_its_password() {
+        let home_dir = ensure_node_home_directory_exists(
+            "db_initializer",
+            "an_encrypted_database_opens_only_with_its_password",
+        );
+        let password_opt = Some("password".to_string());
+        DbInitializerReal::with_password_opt(password_opt.clone())
+            .initialize(&home_dir, DEFAULT_CHAIN_ID)
+            .unwrap();
+
+        let right = DbInitializerReal::with_password_opt(password_opt)
+            .initialize(&home_dir, DEFAULT_CHAIN_ID);
+        let wrong = DbInitializerReal::with_password_opt(Some("drowssap".to_string()))
+            .initialize(&home_dir, DEFAULT_CHAIN_ID);
+        let none = DbInitializerReal::new().initialize(&home_dir, DEFAULT_CHAIN_ID);
+
+        assert!(right.is_ok());
+        assert_eq!(wrong.err().unwrap(), InitializationError::Undecryptable);
+        assert_eq!(none.err().unwrap(), InitializationError::Undecryptable);
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn an_unencrypted_database_is_encrypted_when_opened_with_a_password() {
+        init_test_logging();
+        let home_dir = ensure_node_home_directory_exists(
+            "db_initializer",
+            "an_unencrypted_database_is_encrypted_when_opened_with_a_password",
+        );
+        DbInitializerReal::new()
+            .initialize(&home_dir, DEFAULT_CHAIN_ID)
+            .unwrap();
+        let password_opt = Some("it's a password".to_string());
+
+        let result = DbInitializerReal::with_password_opt(password_opt.clone())
+            .initialize(&home_dir, DEFAULT_CHAIN_ID);
+
+        assert!(result.is_ok());
+        let config_dao = ConfigDaoReal::new(
+            DbInitializerReal::with_password_opt(password_opt)
+                .initialize(&home_dir, DEFAULT_CHAIN_ID)
+                .unwrap(),
+        );
+        assert_eq!(
+            config_dao.get_string("schema_version"),
+            Ok(CURRENT_SCHEMA_VERSION.to_string())
+        );
+        assert_eq!(
+            DbInitializerReal::new()
+                .initialize(&home_dir, DEFAULT_CHAIN_ID)
+                .err()
+                .unwrap(),
+            InitializationError::Undecryptable
+        );
+        TestLogHandler::new().exists_log_containing(&format!(
+            "INFO: DbInitializer: Encrypted the database at {:?}",
+            home_dir.join(DATABASE_FILE)
+        ));
+    }
+
+    #[test]
+    fn connections_are_in_wal_mode_and_wait_for_each_other() {
+        let home_dir = ensure_node_home_directory_exists(
+            "db_initializer",
+            "connections_are_in_wal_mode_and_wait_for_each_other",
+        );
+        let subject = DbInitializerReal::new();
+        let created = subject.initialize(&home_dir, DEFAULT_CHAIN_ID).unwrap();
+        let opened = subject.initialize(&home_dir, DEFAULT_CHAIN_ID).unwrap();
+
+        vec![created, opened].into_iter().for_each(|conn| {
+            let journal_mode: String = conn
+                .prepare("pragma journal_mode")
+                .unwrap()
+                .query_row(NO_PARAMS, |row| row.get(0))
+                .unwrap();
+            let busy_timeout: i64 = conn
+                .prepare("pragma busy_timeout")
+                .unwrap()
+                .query_row(NO_PARAMS, |row| row.get(0))
+                .unwrap();
+            assert_eq!(journal_mode, "wal");
+            assert_eq!(busy_timeout, BUSY_TIMEOUT.as_millis() as i64);
+        });
+    }
+
+    #[test]
+    fn connections_take_turns_writing_instead_of_finding_the_database_locked() {
+        let home_dir = ensure_node_home_directory_exists(
+            "db_initializer",
+            "connections_take_turns_writing_instead_of_finding_the_database_locked",
+        );
+        let wallet = make_wallet("booga");
+        let receivable_dao = ReceivableDaoReal::new(
+            DbInitializerReal::new()
+                .initialize(&home_dir, DEFAULT_CHAIN_ID)
+                .unwrap(),
+        );
+        receivable_dao.more_money_receivable(&wallet, 1);
+        let config_dao = ConfigDaoReal::new(
+            DbInitializerReal::new()
+                .initialize(&home_dir, DEFAULT_CHAIN_ID)
+                .unwrap(),
+        );
+        config_dao.set_u64("start_block", 0).unwrap();
+
+        let handles: Vec<thread::JoinHandle<()>> = (0..8)
+            .map(|_| {
+                let home_dir = home_dir.clone();
+                let wallet = wallet.clone();
+                thread::spawn(move || {
+                    let receivable_dao = ReceivableDaoReal::new(
+                        DbInitializerReal::new()
+                            .initialize(&home_dir, DEFAULT_CHAIN_ID)
+                            .unwrap(),
+                    );
+                    let conn = DbInitializerReal::new()
+                        .initialize(&home_dir, DEFAULT_CHAIN_ID)
+                        .unwrap();
+                    (0..25).for_each(|_| {
+                        receivable_dao.more_money_receivable(&wallet, 1);
+                        // Read, then write: the kind of transaction that fails in WAL mode when
+                        // another connection has written in between
+                        conn.write(&mut |tx| {
+                            let start_block: String = tx
+                                .query_row(
+                                    "select value from config where name = 'start_block'",
+                                    NO_PARAMS,
+                                    |row| row.get(0),
+                                )
+                                .map_err(|e| e.to_string())?;
+                            let next = start_block.parse::<u64>().unwrap() + 1;
+                            tx.execute(
+                                "update config set value = ? where name = 'start_block'",
+                                &[next.to_string()],
+                            )
+                            .map(|_| ())
+                            .map_err(|e| e.to_string())
+                        })
+                        .unwrap();
+                    });
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .for_each(|handle| handle.join().unwrap());
+
+        assert_eq!(
+            receivable_dao.account_status(&wallet).unwrap().balance,
+            1 + 8 * 25
+        );
+        assert_eq!(config_dao.get_u64("start_block"), Ok(8 * 25));
+    }
+
+    #[test]
+    fn connections_to_one_database_share_its_writer() {
+        let home_dir = ensure_node_home_directory_exists(
+            "db_initializer",
+            "connections_to_one_database_share_its_writer",
+        );
+        let other_home_dir = ensure_node_home_directory_exists(
+            "db_initializer",
+            "connections_to_one_database_share_its_writer_elsewhere",
+        );
+        let subject = DbInitializerReal::new();
+        let one = subject.initialize(&home_dir, DEFAULT_CHAIN_ID).unwrap();
+        let other = subject
+            .initialize(&other_home_dir, DEFAULT_CHAIN_ID)
+            .unwrap();
+        let database_file_path = home_dir.join(DATABASE_FILE);
+
+        let first = subject.writer(&database_file_path).unwrap();
+        let second = subject.writer(&database_file_path).unwrap();
+        let elsewhere = subject.writer(&other_home_dir.join(DATABASE_FILE)).unwrap();
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(!Arc::ptr_eq(&first, &elsewhere));
+        drop((one, other, first, second, elsewhere));
+        let replacement = subject.writer(&database_file_path).unwrap();
+        assert_eq!(Arc::strong_count(&replacement), 1);
+    }
+
+    #[test]
+    fn writer_rolls_back_a_write_that_fails() {
+        let home_dir = ensure_node_home_directory_exists(
+            "db_initializer",
+            "writer_rolls_back_a_write_that_fails",
+        );
+        let conn = DbInitializerReal::new()
+            .initialize(&home_dir, DEFAULT_CHAIN_ID)
+            .unwrap();
+        let config_dao = ConfigDaoReal::new(
+            DbInitializerReal::new()
+                .initialize(&home_dir, DEFAULT_CHAIN_ID)
+                .unwrap(),
+        );
+        let start_block = config_dao.get_u64("start_block").unwrap();
+
+        let result = conn.write(&mut |tx| {
+            tx.execute(
+                "update config set value = 'not rolled back' where name = 'start_block'",
+                NO_PARAMS,
+            )
+            .map_err(|e| e.to_string())?;
+            Err("booga".to_string())
+        });
+
+        assert_eq!(result, Err("booga".to_string()));
+        assert_eq!(config_dao.get_u64("start_block"), Ok(start_block));
+    }
+
+    #[test]
+    fn in_memory_connections_share_one_database_and_touch_no_files() {
+        let home_dir = ensure_node_home_directory_does_not_exist(
+            "db_initializer",
+            "in_memory_connections_share_one_database_and_touch_no_files",
+        );
+        let subject = DbInitializerInMemory::new();
+
+        let writer = ConfigDaoReal::new(subject.initialize(&home_dir, DEFAULT_CHAIN_ID).unwrap());
+        writer.set_u64("gas_price", 42).unwrap();
+        let reader = ConfigDaoReal::new(subject.initialize(&home_dir, DEFAULT_CHAIN_ID).unwrap());
+
+        assert_eq!(reader.get_u64("gas_price"), Ok(42));
+        assert_eq!(
+            reader.get_string("schema_version"),
+            Ok(CURRENT_SCHEMA_VERSION.to_string())
+        );
+        assert!(!home_dir.exists());
+    }
+
+    #[test]
+    fn in_memory_databases_are_kept_apart() {
+        let path = PathBuf::from("irrelevant");
+        let one = DbInitializerInMemory::new();
+        let another = DbInitializerInMemory::new();
+        ConfigDaoReal::new(one.initialize(&path, DEFAULT_CHAIN_ID).unwrap())
+            .set_u64("gas_price", 42)
+            .unwrap();
+
+        let result = ConfigDaoReal::new(another.initialize(&path, DEFAULT_CHAIN_ID).unwrap())
+            .get_u64("gas_price");
+
+        assert_eq!(result, Ok(DEFAULT_GAS_PRICE.parse::<u64>().unwrap()));
+    }
+
+    #[test]
+    fn in_memory_database_has_the_whole_schema() {
+        let subject = DbInitializerInMemory::new();
+        let wallet = make_wallet("booga");
+        let payable_dao = PayableDaoReal::new(
+            subject
+                .initialize(&PathBuf::from("irrelevant"), DEFAULT_CHAIN_ID)
+                .unwrap(),
+        );
+
+        payable_dao.more_money_payable(&wallet, 1234);
+
+        assert_eq!(payable_dao.account_status(&wallet).unwrap().balance, 1234);
+    }
+
+    #[test]
+    fn existing_database_with_an_older_version_is_upgraded() {
+        let home_dir = ensure_node_home_directory_exists(
+            "accountant",
+            "existing_database_with_an_older_version_is_upgraded",
+        );
+        {
+            DbInitializerReal::new()
+                .initialize(&home_dir, DEFAULT_CHAIN_ID)
+                .unwrap();
+            let mut flags = OpenFlags::empty();
+            flags.insert(OpenFlags::SQLITE_OPEN_READ_WRITE);
+            let conn = Connection::open_with_flags(&home_dir.join(DATABASE_FILE), flags).unwrap();
+            conn.execute(
+                "update config set value = ? where name = 'schema_version'",
+                &[MIGRATIONS[0].old_version],
+            )
+            .unwrap();
+        }
+        let subject = DbInitializerReal::new();
+
+        subject.initialize(&home_dir, DEFAULT_CHAIN_ID).unwrap();
+
+        let mut flags = OpenFlags::empty();
+        flags.insert(OpenFlags::SQLITE_OPEN_READ_ONLY);
+        let conn = Connection::open_with_flags(&home_dir.join(DATABASE_FILE), flags).unwrap();
+        let config = subject.extract_configurations(&conn);
+        assert_eq!(
+            config.get("schema_version"),
+            Some(&Some(CURRENT_SCHEMA_VERSION.to_string()))
+        );
+    }
+
+    #[test]
+    fn existing_database_from_a_later_node_is_rejected() {
+        let home_dir = ensure_node_home_directory_exists(
+            "accountant",
+            "existing_database_from_a_later_node_is_rejected",
+        );
+        {
+            DbInitializerReal::new()
+                .initialize(&home_dir, DEFAULT_CHAIN_ID)
+                .unwrap();
+            let mut flags = OpenFlags::empty();
+            flags.insert(OpenFlags::SQLITE_OPEN_READ_WRITE);
+            let conn = Connection::open_with_flags(&home_dir.join(DATABASE_FILE), flags).unwrap();
+            conn.execute(
+                "update config set value = '999.0.0' where name = 'schema_version'",
+                NO_PARAMS,
+            )
+            .unwrap();
+        }
+        let subject = DbInitializerReal::new();
+
+        let result = subject.initialize(&home_dir, DEFAULT_CHAIN_ID);
+
+        assert_eq!(
+            result.err().unwrap(),
+            InitializationError::IncompatibleVersion(format!(
+                "Need {}, found 999.0.0, which belongs to a later version of Node",
+                CURRENT_SCHEMA_VERSION
+            )),
+        );
+    }
+
     #[test]
     fn choose_clandestine_port_chooses_different_unused_ports_each_time() {
         let _listeners = (0..10)
             .map(|_| {
-                let port = DbInitializerReal::choose_clandestine_port();
+                let port = DbInitializerReal::new().choose_clandestine_port();
                 TcpListener::bind(&SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::from(0), port)))
                     .expect(&format!("Port {} was not free", port))
             })
@@ -619,7 +1344,7 @@ mod tests {
 
     #[test]
     fn choose_clandestine_port_chooses_ports_between_the_minimum_and_maximum() {
-        let clandestine_port_value = DbInitializerReal::choose_clandestine_port();
+        let clandestine_port_value = DbInitializerReal::new().choose_clandestine_port();
         assert!(
             clandestine_port_value >= LOWEST_USABLE_INSECURE_PORT,
             "clandestine_port_value should have been > 1024, but was {}",
@@ -632,6 +1357,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn choose_clandestine_port_chooses_the_same_port_with_the_same_seed() {
+        let first = DbInitializerReal::new().with_random(Box::new(RandomSeeded::new(1234)));
+        let second = DbInitializerReal::new().with_random(Box::new(RandomSeeded::new(1234)));
+
+        let result = (
+            first.choose_clandestine_port(),
+            first.choose_clandestine_port(),
+        );
+
+        assert_eq!(
+            result,
+            (
+                second.choose_clandestine_port(),
+                second.choose_clandestine_port()
+            )
+        );
+    }
+
     #[test]
     fn initialize_config_with_seed() {
         let home_dir =
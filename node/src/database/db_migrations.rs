@@ -0,0 +1,592 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::database::db_initializer::{InitializationError, PENDING_TRANSACTION_TABLE_SQL};
+use crate::sub_lib::accountant::PAYMENT_PARAMETERS;
+use crate::sub_lib::logger::Logger;
+use rusqlite::types::ToSql;
+use rusqlite::{Connection, Transaction, NO_PARAMS};
+use std::cmp::Ordering;
+
+// Takes a database from one schema version to the next. Whatever a migration does happens in the
+// same transaction as the change to schema_version, so a database is never left half-upgraded.
+pub struct Migration {
+    pub old_version: &'static str,
+    pub new_version: &'static str,
+    pub migrate: fn(&Transaction, u8) -> rusqlite::Result<()>,
+}
+
+// In order. When the schema changes, add a migration here and advance CURRENT_SCHEMA_VERSION
+// to its new_version; never change a migration that has been released.
+pub const MIGRATIONS: [Migration; 7] = [
+    Migration {
+        old_version: "0.0.9",
+        new_version: "0.0.10",
+        migrate: migrate_0_0_9_to_0_0_10,
+    },
+    Migration {
+        old_version: "0.0.10",
+        new_version: "0.0.11",
+        migrate: migrate_0_0_10_to_0_0_11,
+    },
+    Migration {
+        old_version: "0.0.11",
+        new_version: "0.0.12",
+        migrate: migrate_0_0_11_to_0_0_12,
+    },
+    Migration {
+        old_version: "0.0.12",
+        new_version: "0.0.13",
+        migrate: migrate_0_0_12_to_0_0_13,
+    },
+    Migration {
+        old_version: "0.0.13",
+        new_version: "0.0.14",
+        migrate: migrate_0_0_13_to_0_0_14,
+    },
+    Migration {
+        old_version: "0.0.14",
+        new_version: "0.0.15",
+        migrate: migrate_0_0_14_to_0_0_15,
+    },
+    Migration {
+        old_version: "0.0.15",
+        new_version: "0.0.16",
+        migrate: migrate_0_0_15_to_0_0_16,
+    },
+];
+
+// Brings a database that says it's at found_version up to wanted_version. A database from a later
+// Node is refused rather than downgraded: this Node can't know what it would be throwing away.
+pub fn upgrade(
+    conn: &mut Connection,
+    migrations: &[Migration],
+    found_version: &str,
+    wanted_version: &str,
+    chain_id: u8,
+) -> Result<(), InitializationError> {
+    if compare_versions(found_version, wanted_version) == Some(Ordering::Greater) {
+        return Err(InitializationError::IncompatibleVersion(format!(
+            "Need {}, found {}, which belongs to a later version of Node",
+            wanted_version, found_version
+        )));
+    }
+    let logger = Logger::new("DbInitializer");
+    let mut version = found_version.to_string();
+    while version != wanted_version {
+        let migration = match migrations
+            .iter()
+            .find(|migration| migration.old_version == version)
+        {
+            Some(migration) => migration,
+            None => {
+                return Err(InitializationError::IncompatibleVersion(format!(
+                    "Need {}, found {}",
+                    wanted_version, found_version
+                )))
+            }
+        };
+        apply(conn, migration, chain_id).map_err(InitializationError::SqliteError)?;
+        info!(
+            logger,
+            "Upgraded database schema from {} to {}", migration.old_version, migration.new_version
+        );
+        version = migration.new_version.to_string();
+    }
+    Ok(())
+}
+
+fn apply(conn: &mut Connection, migration: &Migration, chain_id: u8) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    (migration.migrate)(&tx, chain_id)?;
+    tx.execute(
+        "update config set value = ? where name = 'schema_version'",
+        &[migration.new_version],
+    )?;
+    tx.commit()
+}
+
+// None if either version isn't made of dot-separated numbers
+fn compare_versions(a: &str, b: &str) -> Option<Ordering> {
+    let parse = |version: &str| {
+        version
+            .split('.')
+            .map(|part| part.parse::<u64>().ok())
+            .collect::<Option<Vec<u64>>>()
+    };
+    Some(parse(a)?.cmp(&parse(b)?))
+}
+
+fn insert_config_value_if_missing(
+    tx: &Transaction,
+    name: &str,
+    value: Option<&str>,
+) -> rusqlite::Result<()> {
+    let params: &[&dyn ToSql] = &[&name, &value, &name];
+    tx.execute(
+        "insert into config (name, value) select ?, ? where not exists (select 1 from config where name = ?)",
+        params,
+    )
+    .map(|_| ())
+}
+
+// 0.0.15 databases from before pending payments survived restarts have nowhere to keep them
+fn migrate_0_0_15_to_0_0_16(tx: &Transaction, _chain_id: u8) -> rusqlite::Result<()> {
+    tx.execute(PENDING_TRANSACTION_TABLE_SQL, NO_PARAMS)
+        .map(|_| ())
+}
+
+// 0.0.14 databases from before consuming profiles kept every payable for the Node's own consuming
+// wallet, so they all move over to it; nor did they track what each profile consumed.
+fn migrate_0_0_14_to_0_0_15(tx: &Transaction, _chain_id: u8) -> rusqlite::Result<()> {
+    // A rename reports rows to a plain execute in this SQLite
+    tx.execute_batch("alter table payable rename to payable_0_0_14")?;
+    tx.execute(
+        "create table payable (
+            wallet_address text not null,
+            consuming_wallet_address text not null default '',
+            balance integer not null,
+            last_paid_timestamp integer not null,
+            pending_payment_transaction text null,
+            primary key (wallet_address, consuming_wallet_address)
+        )",
+        NO_PARAMS,
+    )?;
+    tx.execute(
+        "insert into payable (wallet_address, balance, last_paid_timestamp, pending_payment_transaction) \
+         select wallet_address, balance, last_paid_timestamp, pending_payment_transaction from payable_0_0_14",
+        NO_PARAMS,
+    )?;
+    tx.execute("drop table payable_0_0_14", NO_PARAMS)?;
+    tx.execute(
+        "create unique index idx_payable_wallet_address on payable (wallet_address, consuming_wallet_address)",
+        NO_PARAMS,
+    )?;
+    tx.execute(
+        "create table if not exists profile_consumption ( name text primary key, consumed integer not null )",
+        NO_PARAMS,
+    )
+    .map(|_| ())
+}
+
+// 0.0.9 databases from before configurable payment parameters lack their config values
+fn migrate_0_0_9_to_0_0_10(tx: &Transaction, _chain_id: u8) -> rusqlite::Result<()> {
+    PAYMENT_PARAMETERS.iter().try_for_each(|parameter| {
+        insert_config_value_if_missing(
+            tx,
+            &parameter.config_key(),
+            Some(&parameter.default_value().to_string()),
+        )
+    })
+}
+
+// 0.0.10 databases from before the exit earning wallet and persistent nonces lack their config
+// values
+fn migrate_0_0_10_to_0_0_11(tx: &Transaction, _chain_id: u8) -> rusqlite::Result<()> {
+    insert_config_value_if_missing(tx, "exit_earning_wallet_address", None)?;
+    insert_config_value_if_missing(tx, "next_nonce", None)
+}
+
+// 0.0.11 databases from before fee budgets and DNS server detection lack their config values
+fn migrate_0_0_11_to_0_0_12(tx: &Transaction, _chain_id: u8) -> rusqlite::Result<()> {
+    insert_config_value_if_missing(tx, "fee_budget_period_start", None)?;
+    insert_config_value_if_missing(tx, "fee_budget_spent", None)?;
+    insert_config_value_if_missing(tx, "detected_dns_servers", None)?;
+    Ok(())
+}
+
+// 0.0.12 databases from before split DNS lack its config value
+fn migrate_0_0_12_to_0_0_13(tx: &Transaction, _chain_id: u8) -> rusqlite::Result<()> {
+    insert_config_value_if_missing(tx, "split_dns_domains", None)
+}
+
+// 0.0.13 databases from before billing-claim disputes lack their table
+fn migrate_0_0_13_to_0_0_14(tx: &Transaction, _chain_id: u8) -> rusqlite::Result<()> {
+    tx.execute(
+        "create table if not exists dispute ( wallet_address text primary key, expiration integer not null )",
+        NO_PARAMS,
+    )
+    .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::db_initializer::{
+        DbInitializer, DbInitializerReal, CURRENT_SCHEMA_VERSION, DATABASE_FILE,
+    };
+    use crate::test_utils::logging::{init_test_logging, TestLogHandler};
+    use crate::test_utils::{ensure_node_home_directory_exists, DEFAULT_CHAIN_ID};
+    use rusqlite::{OpenFlags, NO_PARAMS};
+
+    fn make_database(test_name: &str, version: &str) -> Connection {
+        let home_dir = ensure_node_home_directory_exists("db_migrations", test_name);
+        DbInitializerReal::new()
+            .initialize(&home_dir, DEFAULT_CHAIN_ID)
+            .unwrap();
+        let mut flags = OpenFlags::empty();
+        flags.insert(OpenFlags::SQLITE_OPEN_READ_WRITE);
+        let conn = Connection::open_with_flags(&home_dir.join(DATABASE_FILE), flags).unwrap();
+        conn.execute(
+            "update config set value = ? where name = 'schema_version'",
+            &[version],
+        )
+        .unwrap();
+        conn
+    }
+
+    fn schema_version(conn: &Connection) -> String {
+        conn.query_row(
+            "select value from config where name = 'schema_version'",
+            NO_PARAMS,
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    fn table_exists(conn: &Connection, table: &str) -> bool {
+        conn.query_row(
+            "select count(*) from sqlite_master where type = 'table' and name = ?",
+            &[table],
+            |row| row.get::<_, i64>(0),
+        )
+        .unwrap()
+            > 0
+    }
+
+    fn create_table_one(tx: &Transaction, _chain_id: u8) -> rusqlite::Result<()> {
+        tx.execute("create table one (value text)", NO_PARAMS)
+            .map(|_| ())
+    }
+
+    fn create_table_two(tx: &Transaction, _chain_id: u8) -> rusqlite::Result<()> {
+        tx.execute("create table two (value text)", NO_PARAMS)
+            .map(|_| ())
+    }
+
+    fn create_table_three_badly(tx: &Transaction, _chain_id: u8) -> rusqlite::Result<()> {
+        tx.execute("create table three (value text)", NO_PARAMS)?;
+        tx.execute("create table three (value text)", NO_PARAMS)
+            .map(|_| ())
+    }
+
+    #[test]
+    fn migrations_are_applied_in_order_from_the_version_found() {
+        init_test_logging();
+        let mut conn = make_database(
+            "migrations_are_applied_in_order_from_the_version_found",
+            "1.0.0",
+        );
+        let migrations = [
+            Migration {
+                old_version: "1.0.1",
+                new_version: "1.1.0",
+                migrate: create_table_two,
+            },
+            Migration {
+                old_version: "1.0.0",
+                new_version: "1.0.1",
+                migrate: create_table_one,
+            },
+        ];
+
+        let result = upgrade(&mut conn, &migrations, "1.0.0", "1.1.0", DEFAULT_CHAIN_ID);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(schema_version(&conn), "1.1.0");
+        assert!(table_exists(&conn, "one"));
+        assert!(table_exists(&conn, "two"));
+        let tlh = TestLogHandler::new();
+        tlh.exists_log_containing(
+            "INFO: DbInitializer: Upgraded database schema from 1.0.0 to 1.0.1",
+        );
+        tlh.exists_log_containing(
+            "INFO: DbInitializer: Upgraded database schema from 1.0.1 to 1.1.0",
+        );
+    }
+
+    #[test]
+    fn a_failed_migration_leaves_the_database_as_the_last_good_one_did() {
+        let mut conn = make_database(
+            "a_failed_migration_leaves_the_database_as_the_last_good_one_did",
+            "1.0.0",
+        );
+        let migrations = [
+            Migration {
+                old_version: "1.0.0",
+                new_version: "1.0.1",
+                migrate: create_table_one,
+            },
+            Migration {
+                old_version: "1.0.1",
+                new_version: "1.0.2",
+                migrate: create_table_three_badly,
+            },
+        ];
+
+        let result = upgrade(&mut conn, &migrations, "1.0.0", "1.0.2", DEFAULT_CHAIN_ID);
+
+        match result {
+            Err(InitializationError::SqliteError(_)) => (),
+            x => panic!("Expected SqliteError, got {:?}", x),
+        }
+        assert_eq!(schema_version(&conn), "1.0.1");
+        assert!(table_exists(&conn, "one"));
+        assert!(!table_exists(&conn, "three"));
+    }
+
+    #[test]
+    fn a_database_from_a_later_node_is_refused() {
+        let mut conn = make_database("a_database_from_a_later_node_is_refused", "1.10.0");
+
+        let result = upgrade(&mut conn, &[], "1.10.0", "1.9.3", DEFAULT_CHAIN_ID);
+
+        assert_eq!(
+            result,
+            Err(InitializationError::IncompatibleVersion(
+                "Need 1.9.3, found 1.10.0, which belongs to a later version of Node".to_string()
+            ))
+        );
+        assert_eq!(schema_version(&conn), "1.10.0");
+    }
+
+    #[test]
+    fn a_database_with_no_way_forward_is_refused() {
+        let mut conn = make_database("a_database_with_no_way_forward_is_refused", "0.9.0");
+        let migrations = [Migration {
+            old_version: "0.9.0",
+            new_version: "0.9.1",
+            migrate: create_table_one,
+        }];
+
+        let result = upgrade(&mut conn, &migrations, "0.9.0", "1.0.0", DEFAULT_CHAIN_ID);
+
+        assert_eq!(
+            result,
+            Err(InitializationError::IncompatibleVersion(
+                "Need 1.0.0, found 0.9.0".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn versions_compare_numerically() {
+        assert_eq!(compare_versions("0.0.9", "0.0.11"), Some(Ordering::Less));
+        assert_eq!(compare_versions("0.1.0", "0.0.11"), Some(Ordering::Greater));
+        assert_eq!(compare_versions("0.0.11", "0.0.11"), Some(Ordering::Equal));
+        assert_eq!(compare_versions("0.0.11", "booga"), None);
+    }
+
+    #[test]
+    fn migrations_lead_to_the_current_schema_version() {
+        let last = MIGRATIONS.last().unwrap();
+
+        assert_eq!(last.new_version, CURRENT_SCHEMA_VERSION);
+        MIGRATIONS.windows(2).for_each(|pair| {
+            assert_eq!(pair[0].new_version, pair[1].old_version);
+        });
+    }
+
+    #[test]
+    fn migrate_0_0_11_to_0_0_12_adds_missing_config_values() {
+        let mut conn = make_database(
+            "migrate_0_0_11_to_0_0_12_adds_missing_config_values",
+            "0.0.11",
+        );
+        conn.execute(
+            "delete from config where name in ('fee_budget_period_start', 'fee_budget_spent', 'detected_dns_servers')",
+            NO_PARAMS,
+        )
+        .unwrap();
+        conn.execute(
+            "update config set value = '1' where name = 'gas_price'",
+            NO_PARAMS,
+        )
+        .unwrap();
+
+        let result = upgrade(&mut conn, &MIGRATIONS, "0.0.11", "0.0.12", DEFAULT_CHAIN_ID);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(schema_version(&conn), "0.0.12");
+        let count: i64 = conn
+            .query_row(
+                "select count(*) from config where name in ('fee_budget_period_start', 'fee_budget_spent', 'detected_dns_servers') and value is null",
+                NO_PARAMS,
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 3);
+        let gas_price: String = conn
+            .query_row(
+                "select value from config where name = 'gas_price'",
+                NO_PARAMS,
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(gas_price, "1");
+    }
+
+    #[test]
+    fn migrate_0_0_12_to_0_0_13_adds_split_dns_domains() {
+        let mut conn = make_database("migrate_0_0_12_to_0_0_13_adds_split_dns_domains", "0.0.12");
+        conn.execute(
+            "delete from config where name = 'split_dns_domains'",
+            NO_PARAMS,
+        )
+        .unwrap();
+
+        let result = upgrade(&mut conn, &MIGRATIONS, "0.0.12", "0.0.13", DEFAULT_CHAIN_ID);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(schema_version(&conn), "0.0.13");
+        let count: i64 = conn
+            .query_row(
+                "select count(*) from config where name = 'split_dns_domains' and value is null",
+                NO_PARAMS,
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn migrate_0_0_13_to_0_0_14_adds_dispute_table() {
+        let mut conn = make_database("migrate_0_0_13_to_0_0_14_adds_dispute_table", "0.0.13");
+        conn.execute("drop table dispute", NO_PARAMS).unwrap();
+
+        let result = upgrade(&mut conn, &MIGRATIONS, "0.0.13", "0.0.14", DEFAULT_CHAIN_ID);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(schema_version(&conn), "0.0.14");
+        assert!(table_exists(&conn, "dispute"));
+    }
+
+    #[test]
+    fn migrate_0_0_14_to_0_0_15_keys_payables_by_consuming_wallet_too() {
+        let mut conn = make_database(
+            "migrate_0_0_14_to_0_0_15_keys_payables_by_consuming_wallet_too",
+            "0.0.14",
+        );
+        conn.execute("drop table payable", NO_PARAMS).unwrap();
+        conn.execute("drop table profile_consumption", NO_PARAMS)
+            .unwrap();
+        conn.execute(
+            "create table payable (
+                wallet_address text primary key,
+                balance integer not null,
+                last_paid_timestamp integer not null,
+                pending_payment_transaction text null
+            )",
+            NO_PARAMS,
+        )
+        .unwrap();
+        conn.execute(
+            "create unique index idx_payable_wallet_address on payable (wallet_address)",
+            NO_PARAMS,
+        )
+        .unwrap();
+        conn.execute(
+            "insert into payable values ('0x1234', 100, 1000, '0xabcd')",
+            NO_PARAMS,
+        )
+        .unwrap();
+
+        let result = upgrade(&mut conn, &MIGRATIONS, "0.0.14", "0.0.15", DEFAULT_CHAIN_ID);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(schema_version(&conn), "0.0.15");
+        assert!(table_exists(&conn, "profile_consumption"));
+        assert!(!table_exists(&conn, "payable_0_0_14"));
+        let migrated: (String, String, i64, i64, Option<String>) = conn
+            .query_row(
+                "select wallet_address, consuming_wallet_address, balance, last_paid_timestamp, pending_payment_transaction from payable",
+                NO_PARAMS,
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            migrated,
+            (
+                "0x1234".to_string(),
+                "".to_string(),
+                100,
+                1000,
+                Some("0xabcd".to_string())
+            )
+        );
+        conn.execute(
+            "insert into payable values ('0x1234', '0x5678', 200, 2000, null)",
+            NO_PARAMS,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn migrate_0_0_15_to_0_0_16_adds_pending_transaction_table() {
+        let mut conn = make_database(
+            "migrate_0_0_15_to_0_0_16_adds_pending_transaction_table",
+            "0.0.15",
+        );
+        conn.execute("drop table pending_transaction", NO_PARAMS)
+            .unwrap();
+
+        let result = upgrade(&mut conn, &MIGRATIONS, "0.0.15", "0.0.16", DEFAULT_CHAIN_ID);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(schema_version(&conn), "0.0.16");
+        assert!(table_exists(&conn, "pending_transaction"));
+    }
+
+    #[test]
+    fn a_0_0_9_database_is_brought_up_to_the_current_schema_version() {
+        let mut conn = make_database(
+            "a_0_0_9_database_is_brought_up_to_the_current_schema_version",
+            "0.0.9",
+        );
+        PAYMENT_PARAMETERS
+            .iter()
+            .map(|parameter| parameter.config_key())
+            .chain(
+                [
+                    "exit_earning_wallet_address",
+                    "next_nonce",
+                    "fee_budget_period_start",
+                    "fee_budget_spent",
+                    "detected_dns_servers",
+                    "split_dns_domains",
+                ]
+                .iter()
+                .map(|name| name.to_string()),
+            )
+            .for_each(|name| {
+                conn.execute("delete from config where name = ?", &[&name])
+                    .unwrap();
+            });
+
+        let result = upgrade(
+            &mut conn,
+            &MIGRATIONS,
+            "0.0.9",
+            CURRENT_SCHEMA_VERSION,
+            DEFAULT_CHAIN_ID,
+        );
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(schema_version(&conn), CURRENT_SCHEMA_VERSION);
+        let config_value = |name: &str| -> Option<String> {
+            conn.query_row("select value from config where name = ?", &[name], |row| {
+                row.get(0)
+            })
+            .unwrap()
+        };
+        PAYMENT_PARAMETERS.iter().for_each(|parameter| {
+            assert_eq!(
+                config_value(&parameter.config_key()),
+                Some(parameter.default_value().to_string()),
+                "{:?}",
+                parameter
+            )
+        });
+        assert_eq!(config_value("exit_earning_wallet_address"), None);
+        assert_eq!(config_value("next_nonce"), None);
+        assert_eq!(config_value("split_dns_domains"), None);
+    }
+}
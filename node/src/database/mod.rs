@@ -1,4 +1,7 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 pub mod config_dumper;
 pub mod dao_utils;
+pub mod data_archive;
+pub mod db_encryption;
 pub mod db_initializer;
+pub mod db_migrations;
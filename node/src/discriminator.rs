@@ -1,6 +1,7 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 use crate::masquerader::{MasqueradeError, Masquerader};
 use crate::sub_lib::framer::Framer;
+use crate::sub_lib::framer::FramingViolation;
 use crate::sub_lib::logger::Logger;
 use serde::export::fmt::Debug;
 
@@ -70,14 +71,23 @@ impl Discriminator {
         }
         None
     }
+
+    // Once the data has broken one of its protocol's limits, nothing more can be taken from it
+    pub fn violation(&self) -> Option<FramingViolation> {
+        self.framer.violation()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::json_framer::JsonFramer;
+    use crate::json_masquerader::JsonMasquerader;
     use crate::masquerader::MasqueradeError;
     use crate::sub_lib::framer::FramedChunk;
+    use crate::test_utils::cores_package_strategies::{framing, garbage, json_shaped_garbage};
     use crate::test_utils::logging::{init_test_logging, TestLogHandler};
+    use proptest::prelude::*;
     use std::cell::RefCell;
     use std::ops::DerefMut;
     use std::sync::Arc;
@@ -85,6 +95,7 @@ mod tests {
 
     pub struct FramerMock {
         data: Vec<Vec<u8>>,
+        violation_opt: Option<FramingViolation>,
     }
 
     impl Framer for FramerMock {
@@ -102,11 +113,23 @@ mod tests {
                 })
             }
         }
+
+        fn violation(&self) -> Option<FramingViolation> {
+            self.violation_opt.clone()
+        }
     }
 
     impl FramerMock {
         pub fn new() -> FramerMock {
-            FramerMock { data: vec![] }
+            FramerMock {
+                data: vec![],
+                violation_opt: None,
+            }
+        }
+
+        pub fn violation_result(mut self, violation_opt: Option<FramingViolation>) -> FramerMock {
+            self.violation_opt = violation_opt;
+            self
         }
     }
 
@@ -288,4 +311,38 @@ mod tests {
         TestLogHandler::new()
             .exists_log_containing("WARN: Discriminator: High-level data error: that didn't work");
     }
+
+    #[test]
+    fn reports_the_violation_its_framer_saw() {
+        let framer =
+            FramerMock::new().violation_result(Some(FramingViolation::TooManyHeaders(100)));
+        let subject = Discriminator::new(Box::new(framer), vec![Box::new(MasqueraderMock::new())]);
+
+        let result = subject.violation();
+
+        assert_eq!(result, Some(FramingViolation::TooManyHeaders(100)));
+    }
+
+    proptest! {
+        #[test]
+        fn clandestine_discriminator_takes_nothing_from_garbage_and_does_not_panic(
+            data in prop_oneof![garbage(), json_shaped_garbage()],
+            framing in framing()
+        ) {
+            let mut subject = Discriminator::new(
+                Box::new(JsonFramer::new()),
+                vec![Box::new(JsonMasquerader::new())],
+            );
+            let mut chunks: Vec<UnmaskedChunk> = vec![];
+
+            framing.reads(&data).into_iter().for_each(|read| {
+                subject.add_data(&read);
+                while let Some(chunk) = subject.take_chunk() {
+                    chunks.push(chunk);
+                }
+            });
+
+            prop_assert_eq!(chunks, vec![]);
+        }
+    }
 }
@@ -1,16 +1,42 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
-use crate::stream_messages::{PoolBindMessage, RemovedStreamType};
-use crate::sub_lib::dispatcher::InboundClientData;
-use crate::sub_lib::dispatcher::{DispatcherSubs, StreamShutdownMsg};
+use crate::stream_messages::{PoolBindMessage, RemoveStreamMsg, RemovedStreamType};
+use crate::sub_lib::clock::{Clock, ClockReal};
+use crate::sub_lib::crash_context::handling;
+use crate::sub_lib::cryptde::PublicKey;
+use crate::sub_lib::dispatcher::{DispatcherSubs, NeighborKeyMessage, StreamShutdownMsg};
+use crate::sub_lib::dispatcher::{Endpoint, InboundClientData};
 use crate::sub_lib::logger::Logger;
+use crate::sub_lib::neighbor_rate_limiter::{NeighborRateLimiter, NeighborRateLimits, RateVerdict};
+use crate::sub_lib::neighbor_rate_limiter::{
+    CUT_OFF_TIME, MAX_THROTTLE_TIME, SUSTAINED_ABUSE_TIME,
+};
 use crate::sub_lib::peer_actors::BindMessage;
 use crate::sub_lib::stream_handler_pool::TransmitDataMsg;
-use crate::sub_lib::utils::NODE_MAILBOX_CAPACITY;
+use crate::sub_lib::utils::{localhost, NODE_MAILBOX_CAPACITY};
 use actix::Actor;
 use actix::Addr;
+use actix::AsyncContext;
 use actix::Context;
 use actix::Handler;
 use actix::Recipient;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+// How often neighbors that have stopped sending are forgotten by the rate limiters
+const RATE_LIMITER_CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+// The most throttled data held back at once, from and to all neighbors together; more is dropped
+const MAX_HELD_BYTES: usize = 32 * 1024 * 1024;
+
+// Data shows only the address of the neighbor it's from or for, unless it's sent to a key. Once the
+// Neighborhood has said which Node is on a stream, the neighbor is known by its key, so that it
+// can't get a fresh allowance by coming back from another address. Streams are told apart by port
+// as well as IP address, so that Nodes behind the same IP address don't share an allowance.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Neighbor {
+    Key(PublicKey),
+    Ip(IpAddr),
+}
 
 struct DispatcherOutSubs {
     to_proxy_server: Recipient<InboundClientData>,
@@ -22,6 +48,14 @@ struct DispatcherOutSubs {
 pub struct Dispatcher {
     subs: Option<DispatcherOutSubs>,
     to_stream: Option<Recipient<TransmitDataMsg>>,
+    remove_stream_sub: Option<Recipient<RemoveStreamMsg>>,
+    self_stream_shutdown_sub: Option<Recipient<StreamShutdownMsg>>,
+    rate_limits: NeighborRateLimits,
+    ingress_limiter_opt: Option<NeighborRateLimiter<Neighbor>>,
+    egress_limiter_opt: Option<NeighborRateLimiter<Neighbor>>,
+    neighbor_keys: HashMap<SocketAddr, PublicKey>,
+    held_bytes: usize,
+    clock: Box<dyn Clock>,
     logger: Logger,
 }
 
@@ -33,6 +67,7 @@ impl Handler<BindMessage> for Dispatcher {
     type Result = ();
 
     fn handle(&mut self, msg: BindMessage, ctx: &mut Self::Context) {
+        let _handling = handling("Dispatcher", "BindMessage");
         ctx.set_mailbox_capacity(NODE_MAILBOX_CAPACITY);
         let subs = DispatcherOutSubs {
             to_proxy_server: msg.peer_actors.proxy_server.from_dispatcher,
@@ -41,6 +76,11 @@ impl Handler<BindMessage> for Dispatcher {
             neighborhood_stream_shutdown_sub: msg.peer_actors.neighborhood.stream_shutdown_sub,
         };
         self.subs = Some(subs);
+        if self.ingress_limiter_opt.is_some() || self.egress_limiter_opt.is_some() {
+            ctx.run_interval(RATE_LIMITER_CLEANUP_INTERVAL, |act, _| {
+                act.forget_idle_neighbors()
+            });
+        }
     }
 }
 
@@ -48,21 +88,54 @@ impl Handler<PoolBindMessage> for Dispatcher {
     type Result = ();
 
     fn handle(&mut self, msg: PoolBindMessage, _ctx: &mut Self::Context) {
+        let _handling = handling("Dispatcher", "PoolBindMessage");
         self.to_stream = Some(msg.stream_handler_pool_subs.transmit_sub);
+        self.remove_stream_sub = Some(msg.stream_handler_pool_subs.remove_sub);
+        self.self_stream_shutdown_sub = Some(msg.dispatcher_subs.stream_shutdown_sub);
     }
 }
 
 impl Handler<InboundClientData> for Dispatcher {
     type Result = ();
 
-    fn handle(&mut self, msg: InboundClientData, _ctx: &mut Self::Context) {
+    fn handle(&mut self, msg: InboundClientData, ctx: &mut Self::Context) {
+        let _handling = handling("Dispatcher", "InboundClientData");
         if msg.is_clandestine {
-            self.subs
-                .as_ref()
-                .expect("Hopper unbound in Dispatcher")
-                .to_hopper
-                .try_send(msg)
-                .expect("Hopper is dead");
+            let neighbor = self.neighbor_at(msg.peer_addr);
+            let verdict = match self.ingress_limiter_opt.as_mut() {
+                Some(limiter) => limiter.admit(&neighbor, msg.data.len(), self.clock.instant()),
+                None => RateVerdict::Pass,
+            };
+            match verdict {
+                RateVerdict::Pass => self.send_to_hopper(msg),
+                RateVerdict::Throttle(_) if !self.hold(msg.data.len()) => warning!(
+                    self.logger,
+                    "Dropping {} bytes from {}: {} bytes of throttled data are held already",
+                    msg.data.len(),
+                    msg.peer_addr,
+                    self.held_bytes
+                ),
+                RateVerdict::Throttle(delay) => {
+                    debug!(
+                        self.logger,
+                        "Throttling {} bytes from {} for {}ms",
+                        msg.data.len(),
+                        msg.peer_addr,
+                        delay.as_millis()
+                    );
+                    ctx.run_later(delay, move |act, _| {
+                        act.release(msg.data.len());
+                        act.send_to_hopper(msg)
+                    });
+                }
+                RateVerdict::Disconnect => self.disconnect(msg.peer_addr),
+                RateVerdict::Drop => debug!(
+                    self.logger,
+                    "Dropping {} bytes from cut-off neighbor {}",
+                    msg.data.len(),
+                    msg.peer_addr
+                ),
+            }
         } else {
             self.subs
                 .as_ref()
@@ -77,18 +150,63 @@ impl Handler<InboundClientData> for Dispatcher {
 impl Handler<TransmitDataMsg> for Dispatcher {
     type Result = ();
 
-    fn handle(&mut self, msg: TransmitDataMsg, _ctx: &mut Self::Context) {
-        debug!(
-            self.logger,
-            "Relaying {} bytes to StreamHandlerPool for {:?}",
-            msg.data.len(),
-            msg.endpoint
-        );
-        self.to_stream
-            .as_ref()
-            .expect("StreamHandlerPool unbound in Dispatcher")
-            .try_send(msg)
-            .expect("StreamHandlerPool is dead");
+    fn handle(&mut self, msg: TransmitDataMsg, ctx: &mut Self::Context) {
+        let _handling = handling("Dispatcher", "TransmitDataMsg");
+        // Data with a sequence number is going to a browser or a server, not a neighbor
+        let neighbor = self.neighbor_for(&msg.endpoint);
+        let verdict = match self.egress_limiter_opt.as_mut() {
+            Some(limiter) if msg.sequence_number.is_none() => {
+                limiter.admit(&neighbor, msg.data.len(), self.clock.instant())
+            }
+            _ => RateVerdict::Pass,
+        };
+        match verdict {
+            RateVerdict::Pass => self.send_to_stream(msg),
+            RateVerdict::Throttle(_) if !self.hold(msg.data.len()) => warning!(
+                self.logger,
+                "Dropping {} bytes to {:?}: {} bytes of throttled data are held already",
+                msg.data.len(),
+                msg.endpoint,
+                self.held_bytes
+            ),
+            RateVerdict::Throttle(delay) => {
+                debug!(
+                    self.logger,
+                    "Throttling {} bytes to {:?} for {}ms",
+                    msg.data.len(),
+                    msg.endpoint,
+                    delay.as_millis()
+                );
+                ctx.run_later(delay, move |act, _| {
+                    act.release(msg.data.len());
+                    act.send_to_stream(msg)
+                });
+            }
+            RateVerdict::Disconnect => warning!(
+                self.logger,
+                "Data for {:?} has been over the limit of {} bytes/s for more than {}s, or by more than {}s' worth; dropping it for {}s",
+                msg.endpoint,
+                self.rate_limits.egress_bytes_per_sec_opt.unwrap_or(0),
+                SUSTAINED_ABUSE_TIME.as_secs(),
+                MAX_THROTTLE_TIME.as_secs(),
+                CUT_OFF_TIME.as_secs()
+            ),
+            RateVerdict::Drop => debug!(
+                self.logger,
+                "Dropping {} bytes to cut-off neighbor {:?}",
+                msg.data.len(),
+                msg.endpoint
+            ),
+        }
+    }
+}
+
+impl Handler<NeighborKeyMessage> for Dispatcher {
+    type Result = ();
+
+    fn handle(&mut self, msg: NeighborKeyMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Dispatcher", "NeighborKeyMessage");
+        self.neighbor_keys.insert(msg.peer_addr, msg.public_key);
     }
 }
 
@@ -96,29 +214,149 @@ impl Handler<StreamShutdownMsg> for Dispatcher {
     type Result = ();
 
     fn handle(&mut self, msg: StreamShutdownMsg, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Dispatcher", "StreamShutdownMsg");
         self.handle_stream_shutdown_msg(msg)
     }
 }
 
 impl Dispatcher {
-    pub fn new() -> Dispatcher {
+    pub fn new(rate_limits: NeighborRateLimits) -> Dispatcher {
         Dispatcher {
             subs: None,
             to_stream: None,
+            remove_stream_sub: None,
+            self_stream_shutdown_sub: None,
+            rate_limits,
+            ingress_limiter_opt: rate_limits
+                .ingress_bytes_per_sec_opt
+                .map(NeighborRateLimiter::new),
+            egress_limiter_opt: rate_limits
+                .egress_bytes_per_sec_opt
+                .map(NeighborRateLimiter::new),
+            neighbor_keys: HashMap::new(),
+            held_bytes: 0,
+            clock: Box::new(ClockReal {}),
             logger: Logger::new("Dispatcher"),
         }
     }
 
+    pub fn with_clock(mut self, clock: Box<dyn Clock>) -> Dispatcher {
+        self.clock = clock;
+        self
+    }
+
     pub fn make_subs_from(addr: &Addr<Dispatcher>) -> DispatcherSubs {
         DispatcherSubs {
             ibcd_sub: addr.clone().recipient::<InboundClientData>(),
             bind: addr.clone().recipient::<BindMessage>(),
             from_dispatcher_client: addr.clone().recipient::<TransmitDataMsg>(),
             stream_shutdown_sub: addr.clone().recipient::<StreamShutdownMsg>(),
+            neighbor_key_sub: addr.clone().recipient::<NeighborKeyMessage>(),
+        }
+    }
+
+    fn neighbor_at(&self, peer_addr: SocketAddr) -> Neighbor {
+        match self.neighbor_keys.get(&peer_addr) {
+            Some(public_key) => Neighbor::Key(public_key.clone()),
+            None => Neighbor::Ip(peer_addr.ip()),
+        }
+    }
+
+    fn neighbor_for(&self, endpoint: &Endpoint) -> Neighbor {
+        match endpoint {
+            Endpoint::Key(public_key) => Neighbor::Key(public_key.clone()),
+            Endpoint::Socket(socket_addr) => self.neighbor_at(*socket_addr),
+        }
+    }
+
+    // False if holding the data would put us over MAX_HELD_BYTES
+    fn hold(&mut self, bytes: usize) -> bool {
+        if self.held_bytes + bytes > MAX_HELD_BYTES {
+            return false;
+        }
+        self.held_bytes += bytes;
+        true
+    }
+
+    fn release(&mut self, bytes: usize) {
+        self.held_bytes -= bytes;
+    }
+
+    fn send_to_hopper(&self, msg: InboundClientData) {
+        self.subs
+            .as_ref()
+            .expect("Hopper unbound in Dispatcher")
+            .to_hopper
+            .try_send(msg)
+            .expect("Hopper is dead");
+    }
+
+    fn send_to_stream(&self, msg: TransmitDataMsg) {
+        debug!(
+            self.logger,
+            "Relaying {} bytes to StreamHandlerPool for {:?}",
+            msg.data.len(),
+            msg.endpoint
+        );
+        self.to_stream
+            .as_ref()
+            .expect("StreamHandlerPool unbound in Dispatcher")
+            .try_send(msg)
+            .expect("StreamHandlerPool is dead");
+    }
+
+    // Closing the stream tells the Neighborhood to drop the neighbor, just as if it had hung up
+    fn disconnect(&self, peer_addr: SocketAddr) {
+        warning!(
+            self.logger,
+            "Neighbor at {} has been over its limit of {} bytes/s for more than {}s, or by more than {}s' worth; disconnecting it for {}s",
+            peer_addr.ip(),
+            self.rate_limits.ingress_bytes_per_sec_opt.unwrap_or(0),
+            SUSTAINED_ABUSE_TIME.as_secs(),
+            MAX_THROTTLE_TIME.as_secs(),
+            CUT_OFF_TIME.as_secs()
+        );
+        self.remove_stream_sub
+            .as_ref()
+            .expect("StreamHandlerPool unbound in Dispatcher")
+            .try_send(RemoveStreamMsg {
+                local_addr: SocketAddr::new(localhost(), 0), // irrelevant; only logged
+                peer_addr,
+                stream_type: RemovedStreamType::Clandestine,
+                sub: self
+                    .self_stream_shutdown_sub
+                    .clone()
+                    .expect("StreamHandlerPool unbound in Dispatcher"),
+            })
+            .expect("StreamHandlerPool is dead");
+    }
+
+    fn forget_idle_neighbors(&mut self) {
+        let now = self.clock.instant();
+        if let Some(limiter) = self.ingress_limiter_opt.as_mut() {
+            limiter.forget_idle(now);
+        }
+        if let Some(limiter) = self.egress_limiter_opt.as_mut() {
+            limiter.forget_idle(now);
         }
+        let ingress_limiter_opt = &self.ingress_limiter_opt;
+        let egress_limiter_opt = &self.egress_limiter_opt;
+        self.neighbor_keys.retain(|_, public_key| {
+            let neighbor = Neighbor::Key(public_key.clone());
+            [ingress_limiter_opt, egress_limiter_opt]
+                .iter()
+                .any(|limiter_opt| match limiter_opt {
+                    Some(limiter) => limiter.remembers(&neighbor),
+                    None => false,
+                })
+        });
     }
 
     fn handle_stream_shutdown_msg(&mut self, msg: StreamShutdownMsg) {
+        // Another Node may come to use the same address and port
+        if let RemovedStreamType::Clandestine = msg.stream_type {
+            self.neighbor_keys.remove(&msg.peer_addr);
+        }
         let subs = self.subs.as_ref().expect("Dispatcher is unbound");
         match msg.stream_type {
             RemovedStreamType::Clandestine => subs
@@ -140,17 +378,22 @@ mod tests {
     use crate::persistent_configuration::HTTP_PORT;
     use crate::stream_messages::NonClandestineAttributes;
     use crate::sub_lib::dispatcher::Endpoint;
+    use crate::test_utils::clock_mock::ClockMock;
+    use crate::test_utils::logging::{init_test_logging, TestLogHandler};
     use crate::test_utils::recorder::Recorder;
     use crate::test_utils::recorder::{make_recorder, peer_actors_builder};
     use actix::Addr;
     use actix::System;
     use std::net::SocketAddr;
     use std::str::FromStr;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::{Instant, SystemTime};
 
     #[test]
     fn sends_inbound_data_for_proxy_server_to_proxy_server() {
         let system = System::new("test");
-        let subject = Dispatcher::new();
+        let subject = Dispatcher::new(NeighborRateLimits::default());
         let subject_addr: Addr<Dispatcher> = subject.start();
         let subject_ibcd = subject_addr.clone().recipient::<InboundClientData>();
         let proxy_server = Recorder::new();
@@ -191,7 +434,7 @@ mod tests {
     #[test]
     fn sends_inbound_data_for_hopper_to_hopper() {
         let system = System::new("test");
-        let subject = Dispatcher::new();
+        let subject = Dispatcher::new(NeighborRateLimits::default());
         let subject_addr: Addr<Dispatcher> = subject.start();
         let (hopper, hopper_awaiter, hopper_recording_arc) = make_recorder();
         let peer_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
@@ -230,7 +473,7 @@ mod tests {
     #[should_panic(expected = "ProxyServer unbound in Dispatcher")]
     fn inbound_client_data_handler_panics_when_proxy_server_is_unbound() {
         let system = System::new("test");
-        let subject = Dispatcher::new();
+        let subject = Dispatcher::new(NeighborRateLimits::default());
         let subject_addr: Addr<Dispatcher> = subject.start();
         let subject_ibcd = subject_addr.recipient::<InboundClientData>();
         let peer_addr = SocketAddr::from_str("1.2.3.4:8765").unwrap();
@@ -255,7 +498,7 @@ mod tests {
     #[should_panic(expected = "Hopper unbound in Dispatcher")]
     fn inbound_client_data_handler_panics_when_hopper_is_unbound() {
         let system = System::new("test");
-        let subject = Dispatcher::new();
+        let subject = Dispatcher::new(NeighborRateLimits::default());
         let subject_addr: Addr<Dispatcher> = subject.start();
         let subject_ibcd = subject_addr.recipient::<InboundClientData>();
         let peer_addr = SocketAddr::from_str("1.2.3.4:8765").unwrap();
@@ -280,7 +523,7 @@ mod tests {
     #[should_panic(expected = "StreamHandlerPool unbound in Dispatcher")]
     fn panics_when_stream_handler_pool_is_unbound() {
         let system = System::new("test");
-        let subject = Dispatcher::new();
+        let subject = Dispatcher::new(NeighborRateLimits::default());
         let subject_addr: Addr<Dispatcher> = subject.start();
         let subject_obcd = subject_addr.recipient::<TransmitDataMsg>();
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
@@ -301,7 +544,7 @@ mod tests {
     #[test]
     fn forwards_outbound_data_to_stream_handler_pool() {
         let system = System::new("test");
-        let subject = Dispatcher::new();
+        let subject = Dispatcher::new(NeighborRateLimits::default());
         let subject_addr: Addr<Dispatcher> = subject.start();
         let subject_obcd = subject_addr.clone().recipient::<TransmitDataMsg>();
         let stream_handler_pool = Recorder::new();
@@ -348,7 +591,7 @@ mod tests {
     #[test]
     fn handle_stream_shutdown_msg_routes_non_clandestine_to_proxy_server() {
         let system = System::new("test");
-        let subject = Dispatcher::new();
+        let subject = Dispatcher::new(NeighborRateLimits::default());
         let addr = subject.start();
         let (proxy_server, _, proxy_server_recording_arc) = make_recorder();
         let (neighborhood, _, neighborhood_recording_arc) = make_recorder();
@@ -382,7 +625,7 @@ mod tests {
     #[test]
     fn handle_stream_shutdown_msg_routes_clandestine_to_neighborhood() {
         let system = System::new("test");
-        let subject = Dispatcher::new();
+        let subject = Dispatcher::new(NeighborRateLimits::default());
         let addr = subject.start();
         let (proxy_server, _, proxy_server_recording_arc) = make_recorder();
         let (neighborhood, _, neighborhood_recording_arc) = make_recorder();
@@ -409,4 +652,365 @@ mod tests {
             &msg
         );
     }
+
+    #[test]
+    fn a_closed_clandestine_stream_takes_its_neighbors_key_with_it() {
+        let _system =
+            System::new("a_closed_clandestine_stream_takes_its_neighbors_key_with_it");
+        let mut subject = Dispatcher::new(NeighborRateLimits::default());
+        let peer_actors = peer_actors_builder().build();
+        subject.subs = Some(DispatcherOutSubs {
+            to_proxy_server: peer_actors.proxy_server.from_dispatcher,
+            to_hopper: peer_actors.hopper.from_dispatcher,
+            proxy_server_stream_shutdown_sub: peer_actors.proxy_server.stream_shutdown_sub,
+            neighborhood_stream_shutdown_sub: peer_actors.neighborhood.stream_shutdown_sub,
+        });
+        let peer_addr = SocketAddr::from_str("7.8.9.0:6543").unwrap();
+        subject
+            .neighbor_keys
+            .insert(peer_addr, PublicKey::new(b"neighbor"));
+
+        subject.handle_stream_shutdown_msg(StreamShutdownMsg {
+            peer_addr,
+            stream_type: RemovedStreamType::Clandestine,
+            report_to_counterpart: false,
+        });
+
+        assert_eq!(subject.neighbor_at(peer_addr), Neighbor::Ip(peer_addr.ip()));
+    }
+
+    fn make_clandestine_ibcd(peer_addr: SocketAddr, data: Vec<u8>) -> InboundClientData {
+        InboundClientData {
+            peer_addr,
+            reception_port: None,
+            last_data: false,
+            is_clandestine: true,
+            sequence_number: None,
+            data,
+        }
+    }
+
+    #[test]
+    fn inbound_data_over_a_neighbors_limit_is_held_back_then_delivered_in_order() {
+        let (hopper, hopper_awaiter, hopper_recording_arc) = make_recorder();
+        let peer_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let start = Instant::now();
+        thread::spawn(move || {
+            let system = System::new(
+                "inbound_data_over_a_neighbors_limit_is_held_back_then_delivered_in_order",
+            );
+            let subject = Dispatcher::new(NeighborRateLimits {
+                ingress_bytes_per_sec_opt: Some(1000),
+                egress_bytes_per_sec_opt: None,
+            });
+            let subject_addr: Addr<Dispatcher> = subject.start();
+            let mut peer_actors = peer_actors_builder().hopper(hopper).build();
+            peer_actors.dispatcher = Dispatcher::make_subs_from(&subject_addr);
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            subject_addr
+                .try_send(make_clandestine_ibcd(peer_addr, vec![1; 2000]))
+                .unwrap();
+            subject_addr
+                .try_send(make_clandestine_ibcd(peer_addr, vec![2; 200]))
+                .unwrap();
+
+            system.run();
+        });
+
+        hopper_awaiter.await_message_count(2);
+        assert!(
+            start.elapsed() >= Duration::from_millis(150),
+            "{:?}",
+            start.elapsed()
+        );
+        let hopper_recording = hopper_recording_arc.lock().unwrap();
+        assert_eq!(
+            hopper_recording.get_record::<InboundClientData>(0).data,
+            vec![1; 2000]
+        );
+        assert_eq!(
+            hopper_recording.get_record::<InboundClientData>(1).data,
+            vec![2; 200]
+        );
+    }
+
+    #[test]
+    fn sustained_abuse_is_timed_by_the_dispatchers_clock() {
+        init_test_logging();
+        let clock = ClockMock::new(SystemTime::now());
+        let subject_clock = clock.clone();
+        let (stream_handler_pool, stream_handler_pool_awaiter, recording_arc) = make_recorder();
+        let peer_addr = SocketAddr::from_str("2.3.4.5:6789").unwrap();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let system = System::new("sustained_abuse_is_timed_by_the_dispatchers_clock");
+            let subject = Dispatcher::new(NeighborRateLimits {
+                ingress_bytes_per_sec_opt: Some(1000),
+                egress_bytes_per_sec_opt: None,
+            })
+            .with_clock(Box::new(subject_clock));
+            let subject_addr: Addr<Dispatcher> = subject.start();
+            let mut peer_actors = peer_actors_builder().build();
+            peer_actors.dispatcher = Dispatcher::make_subs_from(&subject_addr);
+            subject_addr
+                .try_send(PoolBindMessage {
+                    dispatcher_subs: peer_actors.dispatcher.clone(),
+                    stream_handler_pool_subs: make_stream_handler_pool_subs_from(Some(
+                        stream_handler_pool,
+                    )),
+                    neighborhood_subs: peer_actors.neighborhood.clone(),
+                })
+                .unwrap();
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+            tx.send(subject_addr.recipient::<InboundClientData>())
+                .unwrap();
+
+            system.run();
+        });
+        let ibcd_sub = rx.recv().unwrap();
+        let log_handler = TestLogHandler::new();
+        // Two seconds of burst and nine seconds' worth of debt, kept up for a minute
+        ibcd_sub
+            .try_send(make_clandestine_ibcd(peer_addr, vec![1; 11000]))
+            .unwrap();
+        log_handler.await_log_containing(
+            "DEBUG: Dispatcher: Throttling 11000 bytes from 2.3.4.5:6789",
+            1000,
+        );
+        for n in 1..12 {
+            clock.advance(Duration::from_secs(5));
+            ibcd_sub
+                .try_send(make_clandestine_ibcd(peer_addr, vec![2; 5000 + n]))
+                .unwrap();
+            log_handler.await_log_containing(
+                &format!(
+                    "DEBUG: Dispatcher: Throttling {} bytes from 2.3.4.5:6789",
+                    5000 + n
+                ),
+                1000,
+            );
+        }
+
+        clock.advance(Duration::from_secs(5));
+        ibcd_sub
+            .try_send(make_clandestine_ibcd(peer_addr, vec![3; 5000]))
+            .unwrap();
+
+        stream_handler_pool_awaiter.await_message_count(1);
+        let recording = recording_arc.lock().unwrap();
+        assert_eq!(
+            recording.get_record::<RemoveStreamMsg>(0).peer_addr,
+            peer_addr
+        );
+    }
+
+    #[test]
+    fn a_neighbor_is_held_to_its_limit_by_its_key_once_the_neighborhood_names_it() {
+        let (stream_handler_pool, stream_handler_pool_awaiter, recording_arc) = make_recorder();
+        let old_addr = SocketAddr::from_str("3.4.5.6:7890").unwrap();
+        let new_addr = SocketAddr::from_str("4.5.6.7:8901").unwrap();
+        thread::spawn(move || {
+            let system = System::new(
+                "a_neighbor_is_held_to_its_limit_by_its_key_once_the_neighborhood_names_it",
+            );
+            let subject = Dispatcher::new(NeighborRateLimits {
+                ingress_bytes_per_sec_opt: Some(1000),
+                egress_bytes_per_sec_opt: None,
+            });
+            let subject_addr: Addr<Dispatcher> = subject.start();
+            let mut peer_actors = peer_actors_builder().build();
+            peer_actors.dispatcher = Dispatcher::make_subs_from(&subject_addr);
+            subject_addr
+                .try_send(PoolBindMessage {
+                    dispatcher_subs: peer_actors.dispatcher.clone(),
+                    stream_handler_pool_subs: make_stream_handler_pool_subs_from(Some(
+                        stream_handler_pool,
+                    )),
+                    neighborhood_subs: peer_actors.neighborhood.clone(),
+                })
+                .unwrap();
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+            [old_addr, new_addr].iter().for_each(|peer_addr| {
+                subject_addr
+                    .try_send(NeighborKeyMessage {
+                        peer_addr: *peer_addr,
+                        public_key: PublicKey::new(b"neighbor"),
+                    })
+                    .unwrap()
+            });
+
+            // Nine seconds' worth of debt from one address, then more than a second's worth
+            // from the other
+            subject_addr
+                .try_send(make_clandestine_ibcd(old_addr, vec![1; 11000]))
+                .unwrap();
+            subject_addr
+                .try_send(make_clandestine_ibcd(new_addr, vec![2; 1001]))
+                .unwrap();
+
+            system.run();
+        });
+
+        stream_handler_pool_awaiter.await_message_count(1);
+        let recording = recording_arc.lock().unwrap();
+        assert_eq!(
+            recording.get_record::<RemoveStreamMsg>(0).peer_addr,
+            new_addr
+        );
+    }
+
+    #[test]
+    fn throttled_data_is_held_only_up_to_a_limit() {
+        let mut subject = Dispatcher::new(NeighborRateLimits::default());
+
+        let first = subject.hold(MAX_HELD_BYTES - 1);
+        let too_much = subject.hold(2);
+        let just_enough = subject.hold(1);
+        subject.release(MAX_HELD_BYTES);
+        let after_release = subject.hold(2);
+
+        assert_eq!(first, true);
+        assert_eq!(too_much, false);
+        assert_eq!(just_enough, true);
+        assert_eq!(after_release, true);
+        assert_eq!(subject.held_bytes, 2);
+    }
+
+    #[test]
+    fn idle_neighbors_keys_are_forgotten_along_with_their_allowances() {
+        let clock = ClockMock::new(SystemTime::now());
+        let mut subject = Dispatcher::new(NeighborRateLimits {
+            ingress_bytes_per_sec_opt: Some(1000),
+            egress_bytes_per_sec_opt: None,
+        })
+        .with_clock(Box::new(clock.clone()));
+        let busy_addr = SocketAddr::from_str("5.6.7.8:1234").unwrap();
+        let idle_addr = SocketAddr::from_str("6.7.8.9:2345").unwrap();
+        subject
+            .neighbor_keys
+            .insert(busy_addr, PublicKey::new(b"busy"));
+        subject
+            .neighbor_keys
+            .insert(idle_addr, PublicKey::new(b"idle"));
+        let now = subject.clock.instant();
+        let limiter = subject.ingress_limiter_opt.as_mut().unwrap();
+        limiter.admit(&Neighbor::Key(PublicKey::new(b"busy")), 3000, now);
+        limiter.admit(&Neighbor::Key(PublicKey::new(b"idle")), 1000, now);
+        clock.advance(Duration::from_millis(1200));
+
+        subject.forget_idle_neighbors();
+
+        assert_eq!(
+            subject.neighbor_at(busy_addr),
+            Neighbor::Key(PublicKey::new(b"busy"))
+        );
+        assert_eq!(
+            subject.neighbor_at(idle_addr),
+            Neighbor::Ip(idle_addr.ip())
+        );
+    }
+
+    #[test]
+    fn nodes_behind_the_same_ip_address_are_known_by_their_own_keys() {
+        let mut subject = Dispatcher::new(NeighborRateLimits {
+            ingress_bytes_per_sec_opt: Some(1000),
+            egress_bytes_per_sec_opt: None,
+        });
+        let one_addr = SocketAddr::from_str("5.6.7.8:1234").unwrap();
+        let another_addr = SocketAddr::from_str("5.6.7.8:2345").unwrap();
+        let stranger_addr = SocketAddr::from_str("5.6.7.8:3456").unwrap();
+        subject
+            .neighbor_keys
+            .insert(one_addr, PublicKey::new(b"one"));
+        subject
+            .neighbor_keys
+            .insert(another_addr, PublicKey::new(b"another"));
+
+        assert_eq!(
+            subject.neighbor_at(one_addr),
+            Neighbor::Key(PublicKey::new(b"one"))
+        );
+        assert_eq!(
+            subject.neighbor_at(another_addr),
+            Neighbor::Key(PublicKey::new(b"another"))
+        );
+        assert_eq!(
+            subject.neighbor_at(stranger_addr),
+            Neighbor::Ip(stranger_addr.ip())
+        );
+    }
+
+    #[test]
+    fn outbound_data_to_browsers_is_not_limited() {
+        let system = System::new("outbound_data_to_browsers_is_not_limited");
+        let subject = Dispatcher::new(NeighborRateLimits {
+            ingress_bytes_per_sec_opt: None,
+            egress_bytes_per_sec_opt: Some(1),
+        });
+        let subject_addr: Addr<Dispatcher> = subject.start();
+        let (stream_handler_pool, _, recording_arc) = make_recorder();
+        let mut peer_actors = peer_actors_builder().build();
+        peer_actors.dispatcher = Dispatcher::make_subs_from(&subject_addr);
+        subject_addr
+            .try_send(PoolBindMessage {
+                dispatcher_subs: peer_actors.dispatcher.clone(),
+                stream_handler_pool_subs: make_stream_handler_pool_subs_from(Some(
+                    stream_handler_pool,
+                )),
+                neighborhood_subs: peer_actors.neighborhood.clone(),
+            })
+            .unwrap();
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        subject_addr
+            .try_send(TransmitDataMsg {
+                endpoint: Endpoint::Socket(SocketAddr::from_str("1.2.3.4:5678").unwrap()),
+                last_data: false,
+                sequence_number: Some(0),
+                data: vec![3; 1000],
+            })
+            .unwrap();
+
+        System::current().stop_with_code(0);
+        system.run();
+        let recording = recording_arc.lock().unwrap();
+        assert_eq!(
+            recording.get_record::<TransmitDataMsg>(0).data,
+            vec![3; 1000]
+        );
+    }
+
+    #[test]
+    fn disconnecting_a_neighbor_closes_its_stream() {
+        init_test_logging();
+        let system = System::new("disconnecting_a_neighbor_closes_its_stream");
+        let (stream_handler_pool, _, recording_arc) = make_recorder();
+        let stream_handler_pool_subs =
+            make_stream_handler_pool_subs_from(Some(stream_handler_pool));
+        let (dispatcher, _, _) = make_recorder();
+        let dispatcher_addr = dispatcher.start();
+        let mut subject = Dispatcher::new(NeighborRateLimits {
+            ingress_bytes_per_sec_opt: Some(1000),
+            egress_bytes_per_sec_opt: None,
+        });
+        subject.remove_stream_sub = Some(stream_handler_pool_subs.remove_sub);
+        subject.self_stream_shutdown_sub = Some(dispatcher_addr.recipient::<StreamShutdownMsg>());
+        let peer_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+
+        subject.disconnect(peer_addr);
+
+        System::current().stop_with_code(0);
+        system.run();
+        let recording = recording_arc.lock().unwrap();
+        let remove_stream_msg = recording.get_record::<RemoveStreamMsg>(0);
+        assert_eq!(remove_stream_msg.peer_addr, peer_addr);
+        assert_eq!(
+            remove_stream_msg.stream_type,
+            RemovedStreamType::Clandestine
+        );
+        TestLogHandler::new().exists_log_containing(
+            "WARN: Dispatcher: Neighbor at 1.2.3.4 has been over its limit of 1000 bytes/s for more than 60s, or by more than 10s' worth; disconnecting it for 600s",
+        );
+    }
 }
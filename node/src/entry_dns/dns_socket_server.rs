@@ -1,14 +1,21 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::entry_dns::relay::DnsRelay;
+use crate::node_configurator::node_configurator_standard;
+use crate::node_configurator::{determine_instance, early_value};
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::main_tools::StdStreams;
 use crate::sub_lib::socket_server::SocketServer;
+use clap::App;
+use dns_utility_lib::dns_modifier_factory::{DnsModifierFactory, DnsModifierFactoryReal};
 use std::net::IpAddr::V4;
-use std::net::Ipv4Addr;
 use std::net::SocketAddr;
+use std::net::{IpAddr, Ipv4Addr};
+use std::str::FromStr;
 use tokio::prelude::Async;
 use tokio::prelude::Future;
 
-const DNS_PORT: u16 = 53;
+pub const DNS_PORT: u16 = 53;
+pub const DNS_MODES: [&str; 2] = ["catch-all", "stub"];
 
 use crate::entry_dns::processing;
 use crate::sub_lib::udp_socket_wrapper::UdpSocketWrapperReal;
@@ -17,6 +24,18 @@ use crate::sub_lib::udp_socket_wrapper::UdpSocketWrapperTrait;
 pub struct DnsSocketServer {
     socket_wrapper: Box<dyn UdpSocketWrapperTrait>,
     buf: [u8; 65536],
+    loopback_ip: Ipv4Addr,
+    stub: bool,
+    // A stub resolver relays what it can't answer; a catch-all DNS server relays only queries in
+    // the split-DNS domains, if there are any, and answers everything else itself
+    relay_opt: Option<DnsRelay>,
+    split_domains: Vec<String>,
+}
+
+// The split-DNS domains are in the database, which only the Bootstrapper can open; so they arrive
+// after both servers have been initialized, along with the DNS servers to look them up with
+pub trait EntryDnsServer: SocketServer<()> {
+    fn split_dns(&mut self, split_domains: Vec<String>, upstream_servers: Vec<SocketAddr>);
 }
 
 impl Future for DnsSocketServer {
@@ -27,6 +46,18 @@ impl Future for DnsSocketServer {
         let logger = Logger::new("EntryDnsServer");
         loop {
             let mut buffer = self.buf;
+            let answer = match self.relay_opt.as_mut() {
+                Some(relay) => relay.poll_answer(&mut buffer),
+                None => Ok(Async::NotReady),
+            };
+            match answer {
+                Ok(Async::Ready((len, client_addr))) => {
+                    self.send(&buffer[0..len], client_addr, &logger)?;
+                    continue;
+                }
+                Ok(Async::NotReady) => (),
+                Err(e) => warning!(logger, "Couldn't receive an answer from upstream: {}", e),
+            }
             let (len, socket_addr) = match self.socket_wrapper.recv_from(&mut buffer) {
                 Ok(Async::Ready((len, socket_addr))) => (len, socket_addr),
                 Ok(Async::NotReady) => return Ok(Async::NotReady),
@@ -38,14 +69,30 @@ impl Future for DnsSocketServer {
                     return Err(());
                 }
             };
-            let response_length = processing::process(&mut buffer, len, &socket_addr, &logger);
-            if let Err(e) = self
-                .socket_wrapper
-                .send_to(&buffer[0..response_length], socket_addr)
-            {
-                error!(logger, "Unrecoverable error sending to UdpSocket: {}", e);
-                return Err(());
-            }
+            let response_length = match self.relay_opt.as_mut() {
+                Some(relay)
+                    if (self.stub && processing::needs_upstream(&mut buffer, len))
+                        || processing::is_in_split_domain(
+                            &mut buffer,
+                            len,
+                            &self.split_domains,
+                        ) =>
+                {
+                    match relay.relay(&buffer[0..len], socket_addr) {
+                        Ok(true) => {
+                            trace!(logger, "{} relayed upstream", socket_addr);
+                            continue;
+                        }
+                        Ok(false) => processing::make_server_failure(&mut buffer, len),
+                        Err(e) => {
+                            warning!(logger, "Couldn't relay a query upstream: {}", e);
+                            processing::make_server_failure(&mut buffer, len)
+                        }
+                    }
+                }
+                _ => processing::process(&mut buffer, len, &socket_addr, self.loopback_ip, &logger),
+            };
+            self.send(&buffer[0..response_length], socket_addr, &logger)?;
         }
     }
 }
@@ -55,16 +102,111 @@ impl SocketServer<()> for DnsSocketServer {
         &()
     }
 
-    fn initialize_as_privileged(&mut self, _args: &Vec<String>, _streams: &mut StdStreams<'_>) {
-        let socket_addr = SocketAddr::new(V4(Ipv4Addr::from(0)), DNS_PORT);
+    // An instance shares port 53 with the others by listening only on its own loopback address.
+    // So does a stub resolver, which is for this machine alone.
+    fn initialize_as_privileged(&mut self, args: &Vec<String>, _streams: &mut StdStreams<'_>) {
+        let app = node_configurator_standard::app();
+        let instance_opt = determine_instance(&app, args);
+        if let Some(ref instance) = instance_opt {
+            self.loopback_ip = instance.loopback_ip();
+        }
+        self.stub = early_value(&app, args, "--dns-mode") == Some("stub".to_string());
+        let listen_ip = if self.stub || instance_opt.is_some() {
+            self.loopback_ip
+        } else {
+            Ipv4Addr::from(0)
+        };
+        let socket_addr = SocketAddr::new(V4(listen_ip), DNS_PORT);
         // The following expect() will cause an appropriate panic if the port can't be opened
         self.socket_wrapper
             .bind(socket_addr)
             .unwrap_or_else(|_| panic!("Cannot bind socket to {:?}", socket_addr));
+        if self.stub {
+            let mut relay = DnsRelay::new(
+                Box::new(UdpSocketWrapperReal::new()),
+                upstream_servers(&app, args, &DnsModifierFactoryReal::new()),
+            );
+            relay
+                .bind()
+                .unwrap_or_else(|e| panic!("Cannot bind socket to relay DNS queries: {}", e));
+            self.relay_opt = Some(relay);
+        }
     }
 
     fn initialize_as_unprivileged(&mut self, _args: &Vec<String>, _streams: &mut StdStreams<'_>) {
         self.buf = [0; 65536];
+        if let Some(ref relay) = self.relay_opt {
+            let logger = Logger::new("EntryDnsServer");
+            if relay.upstream_servers().is_empty() {
+                warning!(
+                    logger,
+                    "Stub resolver has no DNS servers to relay to; give --dns-servers, or queries for \
+                     anything but addresses will fail"
+                );
+            } else {
+                info!(
+                    logger,
+                    "Stub resolver relaying queries for anything but addresses to {}",
+                    relay
+                        .upstream_servers()
+                        .iter()
+                        .map(|server| server.ip().to_string())
+                        .collect::<Vec<String>>()
+                        .join(",")
+                );
+            }
+        }
+    }
+}
+
+impl EntryDnsServer for DnsSocketServer {
+    // A stub resolver already has its relay; a catch-all DNS server needs one only now
+    fn split_dns(&mut self, split_domains: Vec<String>, upstream_servers: Vec<SocketAddr>) {
+        if split_domains.is_empty() {
+            return;
+        }
+        let logger = Logger::new("EntryDnsServer");
+        if self.relay_opt.is_none() {
+            let mut relay = DnsRelay::new(
+                Box::new(UdpSocketWrapperReal::new()),
+                upstream_servers
+                    .into_iter()
+                    .filter(|server| !server.ip().is_loopback())
+                    .collect(),
+            );
+            if let Err(e) = relay.bind() {
+                warning!(
+                    logger,
+                    "Couldn't bind a socket to relay split-DNS queries, so {} will be looked up \
+                     through the Node: {}",
+                    split_domains.join(","),
+                    e
+                );
+                return;
+            }
+            self.relay_opt = Some(relay);
+        }
+        let relay = self.relay_opt.as_ref().expect("Relay disappeared");
+        if relay.upstream_servers().is_empty() {
+            warning!(
+                logger,
+                "No DNS servers to look up {} with; give --dns-servers, or queries for them will fail",
+                split_domains.join(",")
+            );
+        } else {
+            info!(
+                logger,
+                "Looking up {} through {} instead of the Node",
+                split_domains.join(","),
+                relay
+                    .upstream_servers()
+                    .iter()
+                    .map(|server| server.ip().to_string())
+                    .collect::<Vec<String>>()
+                    .join(",")
+            );
+        }
+        self.split_domains = split_domains;
     }
 }
 
@@ -73,6 +215,20 @@ impl DnsSocketServer {
         DnsSocketServer {
             socket_wrapper: Box::new(UdpSocketWrapperReal::new()),
             buf: [0; 65536],
+            loopback_ip: Ipv4Addr::LOCALHOST,
+            stub: false,
+            relay_opt: None,
+            split_domains: vec![],
+        }
+    }
+
+    fn send(&mut self, buf: &[u8], addr: SocketAddr, logger: &Logger) -> Result<(), ()> {
+        match self.socket_wrapper.send_to(buf, addr) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!(logger, "Unrecoverable error sending to UdpSocket: {}", e);
+                Err(())
+            }
         }
     }
 }
@@ -83,14 +239,41 @@ impl Default for DnsSocketServer {
     }
 }
 
+// The DNS servers this machine would use if it weren't for the Node. Those on loopback are left
+// out: one of them is probably the Node itself.
+fn upstream_servers(
+    app: &App,
+    args: &Vec<String>,
+    dns_modifier_factory: &dyn DnsModifierFactory,
+) -> Vec<SocketAddr> {
+    let ips: Vec<IpAddr> = match early_value(app, args, "--dns-servers") {
+        Some(dns_servers) => dns_servers
+            .split(',')
+            .filter_map(|dns_server| IpAddr::from_str(dns_server.trim()).ok())
+            .collect(),
+        None => dns_modifier_factory
+            .make()
+            .and_then(|dns_modifier| dns_modifier.original_servers().ok())
+            .unwrap_or_else(|| vec![]),
+    };
+    ips.into_iter()
+        .filter(|ip| !ip.is_loopback())
+        .map(|ip| SocketAddr::new(ip, DNS_PORT))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::packet_facade::PacketFacade;
     use super::*;
+    use crate::node_configurator::instance::Instance;
     use crate::sub_lib::udp_socket_wrapper::UdpSocketWrapperTrait;
+    use crate::test_utils::dns_modifier_mock::{DnsModifierFactoryMock, DnsModifierMock};
+    use crate::test_utils::environment_guard::EnvironmentGuard;
     use crate::test_utils::logging::init_test_logging;
     use crate::test_utils::logging::TestLogHandler;
-    use crate::test_utils::FakeStreamHolder;
+    use crate::test_utils::scripted_udp_socket_wrapper::ScriptedUdpSocketWrapper;
+    use crate::test_utils::{ArgsBuilder, FakeStreamHolder};
     use std::borrow::Borrow;
     use std::borrow::BorrowMut;
     use std::clone::Clone;
@@ -103,7 +286,8 @@ mod tests {
     use std::sync::Arc;
     use std::sync::Mutex;
     use tokio;
-    use trust_dns::op::ResponseCode;
+    use trust_dns::op::{OpCode, ResponseCode};
+    use trust_dns::rr::{DNSClass, RecordType};
 
     struct UdpSocketWrapperMockGuts {
         log: Vec<String>,
@@ -191,7 +375,10 @@ mod tests {
         let socket_wrapper = make_socket_wrapper_mock();
         let mut subject = make_instrumented_subject(socket_wrapper.clone());
 
-        subject.initialize_as_privileged(&vec![], &mut FakeStreamHolder::new().streams());
+        subject.initialize_as_privileged(
+            &vec!["PrometheusNode".to_string()],
+            &mut FakeStreamHolder::new().streams(),
+        );
 
         let unwrapped_guts = socket_wrapper.guts.lock().unwrap();
         let borrowed_guts = unwrapped_guts.borrow();
@@ -199,6 +386,201 @@ mod tests {
         assert_eq!(log[0], "bind ('V4(0.0.0.0:53)')")
     }
 
+    #[test]
+    fn an_instance_listens_and_answers_on_its_own_loopback_address() {
+        let _guard = EnvironmentGuard::new();
+        let socket_wrapper = make_socket_wrapper_mock();
+        let mut subject = make_instrumented_subject(socket_wrapper.clone());
+        let loopback_ip = Instance::new("alice").loopback_ip();
+
+        subject.initialize_as_privileged(
+            &ArgsBuilder::new().param("--instance-name", "alice").into(),
+            &mut FakeStreamHolder::new().streams(),
+        );
+
+        let unwrapped_guts = socket_wrapper.guts.lock().unwrap();
+        let borrowed_guts = unwrapped_guts.borrow();
+        let log = &borrowed_guts.log;
+        assert_eq!(log[0], format!("bind ('V4({}:53)')", loopback_ip));
+        assert_eq!(subject.loopback_ip, loopback_ip);
+    }
+
+    #[test]
+    fn a_stub_resolver_listens_only_on_loopback_and_relays_to_the_dns_servers() {
+        let _guard = EnvironmentGuard::new();
+        let socket_wrapper = make_socket_wrapper_mock();
+        let mut subject = make_instrumented_subject(socket_wrapper.clone());
+
+        subject.initialize_as_privileged(
+            &ArgsBuilder::new()
+                .param("--dns-mode", "stub")
+                .param("--dns-servers", "1.1.1.1,127.0.0.53")
+                .into(),
+            &mut FakeStreamHolder::new().streams(),
+        );
+
+        let unwrapped_guts = socket_wrapper.guts.lock().unwrap();
+        let borrowed_guts = unwrapped_guts.borrow();
+        let log = &borrowed_guts.log;
+        assert_eq!(log[0], "bind ('V4(127.0.0.1:53)')");
+        assert_eq!(
+            subject.relay_opt.unwrap().upstream_servers(),
+            &vec![SocketAddr::from_str("1.1.1.1:53").unwrap()]
+        );
+    }
+
+    #[test]
+    fn without_dns_servers_a_stub_resolver_relays_where_this_machine_would_look() {
+        let _guard = EnvironmentGuard::new();
+        let dns_modifier = DnsModifierMock::new().original_servers_result(Ok(vec![
+            IpAddr::from_str("127.0.0.53").unwrap(),
+            IpAddr::from_str("8.8.8.8").unwrap(),
+        ]));
+        let dns_modifier_factory =
+            DnsModifierFactoryMock::new().make_result(Some(Box::new(dns_modifier)));
+
+        let result = upstream_servers(
+            &node_configurator_standard::app(),
+            &ArgsBuilder::new().param("--dns-mode", "stub").into(),
+            &dns_modifier_factory,
+        );
+
+        assert_eq!(result, vec![SocketAddr::from_str("8.8.8.8:53").unwrap()]);
+    }
+
+    #[test]
+    fn a_stub_resolver_answers_address_queries_and_relays_the_rest() {
+        let address_client = SocketAddr::from_str("127.0.0.1:1111").unwrap();
+        let mail_client = SocketAddr::from_str("127.0.0.1:2222").unwrap();
+        let upstream_server = SocketAddr::from_str("8.8.8.8:53").unwrap();
+        let socket_wrapper = ScriptedUdpSocketWrapper::new()
+            .recv_from_result(make_query(0x1111, RecordType::A), address_client)
+            .recv_from_result(make_query(0x2222, RecordType::MX), mail_client);
+        let relay_socket_wrapper = ScriptedUdpSocketWrapper::new();
+        let mut subject = DnsSocketServer {
+            socket_wrapper: Box::new(socket_wrapper.clone()),
+            buf: [0; 65536],
+            loopback_ip: Ipv4Addr::LOCALHOST,
+            stub: true,
+            relay_opt: Some(DnsRelay::new(
+                Box::new(relay_socket_wrapper.clone()),
+                vec![upstream_server],
+            )),
+            split_domains: vec![],
+        };
+
+        assert_eq!(subject.poll(), Ok(Async::NotReady));
+
+        let relayed = {
+            let sent = socket_wrapper.send_to_params.lock().unwrap();
+            assert_eq!(sent.len(), 1);
+            let (ref response, to) = sent[0];
+            assert_eq!(to, address_client);
+            let mut response = response.clone();
+            let length = response.len();
+            let facade = PacketFacade::new(&mut response, length);
+            assert_eq!(facade.get_transaction_id(), Some(0x1111));
+            assert_eq!(
+                facade.get_answers().unwrap()[0].get_rdata(),
+                vec![127, 0, 0, 1].as_slice()
+            );
+            let relayed = relay_socket_wrapper.send_to_params.lock().unwrap();
+            assert_eq!(relayed.len(), 1);
+            assert_eq!(relayed[0].1, upstream_server);
+            assert_eq!(relayed[0].0[2..], make_query(0x2222, RecordType::MX)[2..]);
+            relayed[0].0.clone()
+        };
+        let mut answer = relayed.clone();
+        answer[2] |= 0x80;
+        relay_socket_wrapper
+            .recv_from_results
+            .lock()
+            .unwrap()
+            .push((answer.clone(), upstream_server));
+
+        assert_eq!(subject.poll(), Ok(Async::NotReady));
+
+        let sent = socket_wrapper.send_to_params.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[1].1, mail_client);
+        assert_eq!(sent[1].0[0..2], [0x22, 0x22]);
+        assert_eq!(sent[1].0[2..], answer[2..]);
+    }
+
+    #[test]
+    fn a_catch_all_dns_server_relays_only_queries_in_split_domains() {
+        let corp_client = SocketAddr::from_str("192.168.0.5:1111").unwrap();
+        let other_client = SocketAddr::from_str("192.168.0.6:2222").unwrap();
+        let upstream_server = SocketAddr::from_str("10.0.0.53:53").unwrap();
+        let socket_wrapper = ScriptedUdpSocketWrapper::new()
+            .recv_from_result(
+                make_query_for("intranet.corp.example.com", 0x1111, RecordType::A),
+                corp_client,
+            )
+            .recv_from_result(make_query(0x2222, RecordType::A), other_client);
+        let relay_socket_wrapper = ScriptedUdpSocketWrapper::new();
+        let mut subject = DnsSocketServer {
+            socket_wrapper: Box::new(socket_wrapper.clone()),
+            buf: [0; 65536],
+            loopback_ip: Ipv4Addr::LOCALHOST,
+            stub: false,
+            relay_opt: Some(DnsRelay::new(
+                Box::new(relay_socket_wrapper.clone()),
+                vec![upstream_server],
+            )),
+            split_domains: vec!["corp.example.com".to_string()],
+        };
+
+        assert_eq!(subject.poll(), Ok(Async::NotReady));
+
+        let relayed = relay_socket_wrapper.send_to_params.lock().unwrap();
+        assert_eq!(relayed.len(), 1);
+        assert_eq!(relayed[0].1, upstream_server);
+        assert_eq!(
+            relayed[0].0[2..],
+            make_query_for("intranet.corp.example.com", 0x1111, RecordType::A)[2..]
+        );
+        let sent = socket_wrapper.send_to_params.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].1, other_client);
+    }
+
+    #[test]
+    fn split_dns_gives_a_catch_all_dns_server_a_relay_to_the_servers_off_loopback() {
+        init_test_logging();
+        let mut subject = make_instrumented_subject(make_socket_wrapper_mock());
+
+        subject.split_dns(
+            vec!["corp.example.com".to_string(), "lan".to_string()],
+            vec![
+                SocketAddr::from_str("127.0.0.53:53").unwrap(),
+                SocketAddr::from_str("10.0.0.53:53").unwrap(),
+            ],
+        );
+
+        assert_eq!(
+            subject.split_domains,
+            vec!["corp.example.com".to_string(), "lan".to_string()]
+        );
+        assert_eq!(
+            subject.relay_opt.unwrap().upstream_servers(),
+            &vec![SocketAddr::from_str("10.0.0.53:53").unwrap()]
+        );
+        TestLogHandler::new().exists_log_containing(
+            "INFO: EntryDnsServer: Looking up corp.example.com,lan through 10.0.0.53 instead of the Node",
+        );
+    }
+
+    #[test]
+    fn split_dns_without_domains_leaves_a_catch_all_dns_server_without_a_relay() {
+        let mut subject = make_instrumented_subject(make_socket_wrapper_mock());
+
+        subject.split_dns(vec![], vec![SocketAddr::from_str("10.0.0.53:53").unwrap()]);
+
+        assert!(subject.relay_opt.is_none());
+        assert!(subject.split_domains.is_empty());
+    }
+
     #[test]
     fn serves_multiple_requests_then_short_circuit_on_error() {
         init_test_logging();
@@ -352,6 +734,27 @@ mod tests {
         DnsSocketServer {
             socket_wrapper,
             buf: [0; 65536],
+            loopback_ip: Ipv4Addr::LOCALHOST,
+            stub: false,
+            relay_opt: None,
+            split_domains: vec![],
         }
     }
+
+    fn make_query(transaction_id: u16, record_type: RecordType) -> Vec<u8> {
+        make_query_for("booga.com", transaction_id, record_type)
+    }
+
+    fn make_query_for(name: &str, transaction_id: u16, record_type: RecordType) -> Vec<u8> {
+        let mut buf = [0u8; 100];
+        let length = {
+            let mut request = PacketFacade::new(&mut buf, 100);
+            request.set_transaction_id(transaction_id);
+            request.set_query(true);
+            request.set_opcode(OpCode::Query.into());
+            request.add_query(name, record_type.into(), DNSClass::IN.into());
+            request.get_length()
+        };
+        buf[0..length].to_vec()
+    }
 }
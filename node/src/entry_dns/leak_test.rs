@@ -0,0 +1,230 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use super::packet_facade::PacketFacade;
+use crate::sub_lib::ui_gateway::{DnsLeakReport, DnsLeakVerdict};
+use rand::Rng;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+use trust_dns::op::OpCode;
+use trust_dns::rr::{DNSClass, RecordType};
+
+const CANARY_DOMAIN: &str = "substratum.net";
+const NODE_DNS_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub trait DnsLeakTester: Send + Sync {
+    fn test(&self) -> DnsLeakReport;
+}
+
+// The way any program on this machine looks up a name, through whatever resolver it's set up with
+pub trait SystemResolver: Send + Sync {
+    fn resolve(&self, name: &str) -> Result<Vec<IpAddr>, String>;
+}
+
+pub struct SystemResolverReal {}
+
+impl SystemResolver for SystemResolverReal {
+    fn resolve(&self, name: &str) -> Result<Vec<IpAddr>, String> {
+        (name, 0)
+            .to_socket_addrs()
+            .map(|addrs| addrs.map(|addr| addr.ip()).collect())
+            .map_err(|e| e.to_string())
+    }
+}
+
+// The Node's DNS server answers a query for any address with its own, and the name is looked up
+// for real only at the far end of the clandestine network. So a made-up name that this machine's
+// resolver also turns into the Node's answer went through the Node; any other answer, or none,
+// came from a DNS server the Node knows nothing about.
+pub struct DnsLeakTesterReal {
+    node_dns_server: SocketAddr,
+    system_resolver: Box<dyn SystemResolver>,
+}
+
+impl DnsLeakTester for DnsLeakTesterReal {
+    fn test(&self) -> DnsLeakReport {
+        let canary = format!(
+            "dns-leak-test-{:016x}.{}",
+            rand::thread_rng().gen::<u64>(),
+            CANARY_DOMAIN
+        );
+        let node_answer = self.ask_node(&canary);
+        let (system_answers, system_error) = match self.system_resolver.resolve(&canary) {
+            Ok(ips) => (ips, None),
+            Err(e) => (vec![], Some(e)),
+        };
+        let verdict = match node_answer {
+            None => DnsLeakVerdict::NodeNotAnswering,
+            Some(ip) if system_answers.contains(&ip) => DnsLeakVerdict::NotLeaking,
+            Some(_) => DnsLeakVerdict::Leaking,
+        };
+        DnsLeakReport {
+            canary,
+            node_dns_server: self.node_dns_server,
+            node_answer,
+            system_answers,
+            system_error,
+            verdict,
+        }
+    }
+}
+
+impl DnsLeakTesterReal {
+    pub fn new(node_dns_server: SocketAddr) -> DnsLeakTesterReal {
+        DnsLeakTesterReal {
+            node_dns_server,
+            system_resolver: Box::new(SystemResolverReal {}),
+        }
+    }
+
+    fn ask_node(&self, name: &str) -> Option<IpAddr> {
+        let transaction_id = rand::thread_rng().gen::<u16>();
+        let mut buf = [0u8; 512];
+        let length = {
+            let mut query = PacketFacade::new(&mut buf, 512);
+            query.set_transaction_id(transaction_id);
+            query.set_query(true);
+            query.set_opcode(OpCode::Query.into());
+            query.set_recursion_desired(true);
+            query.add_query(name, RecordType::A.into(), DNSClass::IN.into());
+            query.get_length()
+        };
+        let socket = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)).ok()?;
+        socket.set_read_timeout(Some(NODE_DNS_TIMEOUT)).ok()?;
+        socket.send_to(&buf[0..length], self.node_dns_server).ok()?;
+        loop {
+            let (length, from) = socket.recv_from(&mut buf).ok()?;
+            let answer = PacketFacade::new(&mut buf, length);
+            if from != self.node_dns_server || answer.get_transaction_id() != Some(transaction_id) {
+                continue;
+            }
+            return answer.get_answers()?.iter().find_map(|record| {
+                match (
+                    RecordType::from(record.get_resource_type()),
+                    record.get_rdata(),
+                ) {
+                    (RecordType::A, &[a, b, c, d]) => Some(IpAddr::V4(Ipv4Addr::new(a, b, c, d))),
+                    _ => None,
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use std::thread;
+
+    struct SystemResolverMock {
+        result: Result<Vec<IpAddr>, String>,
+    }
+
+    impl SystemResolver for SystemResolverMock {
+        fn resolve(&self, _name: &str) -> Result<Vec<IpAddr>, String> {
+            self.result.clone()
+        }
+    }
+
+    // Answers one query the way the Node's DNS server would, with 127.0.0.1
+    fn start_node_dns_server() -> (SocketAddr, thread::JoinHandle<String>) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let node_dns_server = socket.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            let (length, from) = socket.recv_from(&mut buf).unwrap();
+            let (length, name) = {
+                let mut facade = PacketFacade::new(&mut buf, length);
+                let name = facade.get_queries().unwrap()[0]
+                    .get_query_name()
+                    .to_string();
+                facade.set_query(false);
+                facade.add_answer(
+                    &name,
+                    RecordType::A.into(),
+                    DNSClass::IN.into(),
+                    0,
+                    &[127, 0, 0, 1],
+                );
+                (facade.get_length(), name)
+            };
+            socket.send_to(&buf[0..length], from).unwrap();
+            name
+        });
+        (node_dns_server, handle)
+    }
+
+    fn make_subject(
+        node_dns_server: SocketAddr,
+        system_result: Result<Vec<IpAddr>, String>,
+    ) -> DnsLeakTesterReal {
+        DnsLeakTesterReal {
+            node_dns_server,
+            system_resolver: Box::new(SystemResolverMock {
+                result: system_result,
+            }),
+        }
+    }
+
+    #[test]
+    fn dns_that_goes_through_the_node_does_not_leak() {
+        let (node_dns_server, handle) = start_node_dns_server();
+        let subject = make_subject(
+            node_dns_server,
+            Ok(vec![IpAddr::from_str("127.0.0.1").unwrap()]),
+        );
+
+        let result = subject.test();
+
+        assert_eq!(result.verdict, DnsLeakVerdict::NotLeaking);
+        assert_eq!(result.node_answer, IpAddr::from_str("127.0.0.1").ok());
+        assert_eq!(handle.join().unwrap(), result.canary);
+        assert!(result.canary.starts_with("dns-leak-test-"));
+        assert!(result.canary.ends_with(".substratum.net"));
+    }
+
+    #[test]
+    fn dns_answered_by_anything_but_the_node_leaks() {
+        let (node_dns_server, handle) = start_node_dns_server();
+        let subject = make_subject(
+            node_dns_server,
+            Ok(vec![IpAddr::from_str("93.184.216.34").unwrap()]),
+        );
+
+        let result = subject.test();
+
+        assert_eq!(result.verdict, DnsLeakVerdict::Leaking);
+        assert_eq!(
+            result.system_answers,
+            vec![IpAddr::from_str("93.184.216.34").unwrap()]
+        );
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn dns_that_the_system_cannot_resolve_leaks() {
+        let (node_dns_server, handle) = start_node_dns_server();
+        let subject = make_subject(node_dns_server, Err("no such host".to_string()));
+
+        let result = subject.test();
+
+        assert_eq!(result.verdict, DnsLeakVerdict::Leaking);
+        assert_eq!(result.system_error, Some("no such host".to_string()));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn without_the_nodes_answer_there_is_no_verdict() {
+        let unused = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let node_dns_server = unused.local_addr().unwrap();
+        drop(unused);
+        let subject = make_subject(
+            node_dns_server,
+            Ok(vec![IpAddr::from_str("127.0.0.1").unwrap()]),
+        );
+
+        let result = subject.test();
+
+        assert_eq!(result.verdict, DnsLeakVerdict::NodeNotAnswering);
+        assert_eq!(result.node_answer, None);
+    }
+}
@@ -3,4 +3,6 @@
 #[macro_use]
 pub mod packet_facade; // public only so that it can be used by the integration test
 pub mod dns_socket_server;
+pub mod leak_test;
 mod processing;
+mod relay;
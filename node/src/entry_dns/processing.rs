@@ -16,7 +16,14 @@ use trust_dns::rr::RecordType;
 const HEADER_BYTES: usize = 12;
 const UNKNOWN: &str = "<unknown>";
 
-pub fn process(buf: &mut [u8], length: usize, addr: &SocketAddr, logger: &Logger) -> usize {
+// Every name is answered with the address where the Node's HTTP and TLS ports listen
+pub fn process(
+    buf: &mut [u8],
+    length: usize,
+    addr: &SocketAddr,
+    loopback_ip: Ipv4Addr,
+    logger: &Logger,
+) -> usize {
     let mut facade = PacketFacade::new(buf, length);
     let request_record = RequestRecord {
         timestamp: Instant::now(),
@@ -24,7 +31,7 @@ pub fn process(buf: &mut [u8], length: usize, addr: &SocketAddr, logger: &Logger
         queries: facade.get_queries().unwrap_or_else(|| vec![]),
     };
 
-    let response_size = make_response(&mut facade);
+    let response_size = make_response(&mut facade, loopback_ip);
 
     let latency = request_record.timestamp.elapsed();
     let response_record = ResponseRecord {
@@ -36,7 +43,7 @@ pub fn process(buf: &mut [u8], length: usize, addr: &SocketAddr, logger: &Logger
     response_size
 }
 
-fn make_response(mut facade: &mut PacketFacade) -> usize {
+fn make_response(mut facade: &mut PacketFacade, loopback_ip: Ipv4Addr) -> usize {
     match facade.get_opcode() {
         None => return make_format_error(facade),
         Some(opcode) if opcode == u8::from(OpCode::Query) => (),
@@ -67,7 +74,7 @@ fn make_response(mut facade: &mut PacketFacade) -> usize {
                 resource_type,
                 DNSClass::IN.into(),
                 3600,
-                &Ipv4Addr::LOCALHOST.octets(),
+                &loopback_ip.octets(),
             ),
             RecordType::AAAA => facade.add_answer(
                 &query.get_query_name(),
@@ -82,6 +89,51 @@ fn make_response(mut facade: &mut PacketFacade) -> usize {
     facade.get_length()
 }
 
+// What a stub resolver can't answer with the loopback address: queries for anything but Internet
+// addresses, which the Node has no way to carry. Anything malformed is left for process to refuse.
+pub fn needs_upstream(buf: &mut [u8], length: usize) -> bool {
+    let facade = PacketFacade::new(buf, length);
+    if facade.get_opcode() != Some(u8::from(OpCode::Query)) {
+        return false;
+    }
+    match facade.get_queries() {
+        Some(queries) => queries.iter().any(|query| {
+            query.get_query_class() != u16::from(DNSClass::IN)
+                || match RecordType::from(query.get_query_type()) {
+                    RecordType::A | RecordType::AAAA => false,
+                    _ => true,
+                }
+        }),
+        None => false,
+    }
+}
+
+// Queries for names in the split-DNS domains, or in their subdomains, go where this machine looked
+// before its DNS was subverted; the domains come lowercase and without dots at either end
+pub fn is_in_split_domain(buf: &mut [u8], length: usize, split_domains: &[String]) -> bool {
+    if split_domains.is_empty() {
+        return false;
+    }
+    let facade = PacketFacade::new(buf, length);
+    if facade.get_opcode() != Some(u8::from(OpCode::Query)) {
+        return false;
+    }
+    match facade.get_queries() {
+        Some(queries) => queries.iter().any(|query| {
+            let name = query.get_query_name().trim_end_matches('.').to_lowercase();
+            split_domains
+                .iter()
+                .any(|domain| name == *domain || name.ends_with(&format!(".{}", domain)))
+        }),
+        None => false,
+    }
+}
+
+pub fn make_server_failure(buf: &mut [u8], length: usize) -> usize {
+    let mut facade = PacketFacade::new(buf, length);
+    make_error(&mut facade, ResponseCode::ServFail.low())
+}
+
 fn display(opcode: u8) -> &'static str {
     match OpCode::from_u8(opcode) {
         Ok(OpCode::Notify) => "Notify",
@@ -206,7 +258,13 @@ mod tests {
         let truncated_buf = &mut correct_buf[0..truncated_length];
         let addr = SocketAddr::from(SocketAddrV4::new(Ipv4Addr::new(101, 102, 103, 104), 53));
 
-        let result = process(truncated_buf, truncated_length, &addr, &Logger::new(""));
+        let result = process(
+            truncated_buf,
+            truncated_length,
+            &addr,
+            Ipv4Addr::LOCALHOST,
+            &Logger::new(""),
+        );
 
         check_format_error_message(truncated_buf, 0x1234);
         assert_eq!(result, HEADER_BYTES);
@@ -228,7 +286,13 @@ mod tests {
         };
         let addr = SocketAddr::from(SocketAddrV4::new(Ipv4Addr::new(101, 102, 103, 104), 53));
 
-        let rsp_length = process(&mut buf, req_length, &addr, &Logger::new(""));
+        let rsp_length = process(
+            &mut buf,
+            req_length,
+            &addr,
+            Ipv4Addr::LOCALHOST,
+            &Logger::new(""),
+        );
 
         check_not_implemented_error_message(&mut buf, 0x1234, OpCode::Status.into());
         assert_eq!(rsp_length, HEADER_BYTES);
@@ -249,7 +313,13 @@ mod tests {
         };
         let addr = SocketAddr::from(SocketAddrV4::new(Ipv4Addr::new(101, 102, 103, 104), 53));
 
-        let rsp_length = process(&mut buf, req_length, &addr, &Logger::new(""));
+        let rsp_length = process(
+            &mut buf,
+            req_length,
+            &addr,
+            Ipv4Addr::LOCALHOST,
+            &Logger::new(""),
+        );
 
         check_not_implemented_error_message(&mut buf, 0x1234, OpCode::Query.into());
         assert_eq!(rsp_length, HEADER_BYTES);
@@ -270,7 +340,13 @@ mod tests {
         };
         let addr = SocketAddr::from(SocketAddrV4::new(Ipv4Addr::new(101, 102, 103, 104), 53));
 
-        let rsp_length = process(&mut buf, req_length, &addr, &Logger::new(""));
+        let rsp_length = process(
+            &mut buf,
+            req_length,
+            &addr,
+            Ipv4Addr::LOCALHOST,
+            &Logger::new(""),
+        );
 
         check_not_implemented_error_message(&mut buf, 0x1234, OpCode::Query.into());
         assert_eq!(rsp_length, HEADER_BYTES);
@@ -304,6 +380,7 @@ mod tests {
                 &mut buf,
                 req_length,
                 &addr,
+                Ipv4Addr::LOCALHOST,
                 &Logger::new("two_queries_are_answered"),
             )
         };
@@ -355,6 +432,98 @@ mod tests {
         );
     }
 
+    #[test]
+    fn a_queries_are_answered_with_the_loopback_address_given() {
+        let mut buf: [u8; 500] = [0; 500];
+        let req_length = {
+            let mut request = PacketFacade::new(&mut buf, 500);
+            request.set_transaction_id(0x4321);
+            request.set_query(true);
+            request.set_opcode(OpCode::Query.into());
+            request.add_query("ooga.com", RecordType::A.into(), DNSClass::IN.into());
+            request.get_length()
+        };
+        let addr = SocketAddr::from(SocketAddrV4::new(Ipv4Addr::new(101, 102, 103, 104), 53));
+
+        let rsp_length = process(
+            &mut buf,
+            req_length,
+            &addr,
+            Ipv4Addr::new(127, 1, 0, 42),
+            &Logger::new(""),
+        );
+
+        let response = PacketFacade::new(&mut buf, rsp_length);
+        let answers = response.get_answers().unwrap();
+        assert_eq!(answers[0].get_rdata(), vec![127, 1, 0, 42].as_slice());
+        assert_eq!(answers.len(), 1);
+    }
+
+    #[test]
+    fn only_queries_for_addresses_stay_out_of_upstream() {
+        let make_request = |buf: &mut [u8], record_type: RecordType| {
+            let mut request = PacketFacade::new(buf, 500);
+            request.set_transaction_id(0x4321);
+            request.set_query(true);
+            request.set_opcode(OpCode::Query.into());
+            request.add_query("ooga.com", RecordType::A.into(), DNSClass::IN.into());
+            request.add_query("booga.com", record_type.into(), DNSClass::IN.into());
+            request.get_length()
+        };
+        let mut buf: [u8; 500] = [0; 500];
+
+        let length = make_request(&mut buf, RecordType::AAAA);
+        assert_eq!(needs_upstream(&mut buf, length), false);
+        let length = make_request(&mut buf, RecordType::MX);
+        assert_eq!(needs_upstream(&mut buf, length), true);
+        assert_eq!(needs_upstream(&mut buf, 5), false);
+    }
+
+    #[test]
+    fn names_in_split_domains_and_their_subdomains_are_recognized() {
+        let check = |name: &str, split_domains: &[String]| {
+            let mut buf: [u8; 500] = [0; 500];
+            let length = {
+                let mut request = PacketFacade::new(&mut buf, 500);
+                request.set_transaction_id(0x4321);
+                request.set_query(true);
+                request.set_opcode(OpCode::Query.into());
+                request.add_query(name, RecordType::A.into(), DNSClass::IN.into());
+                request.get_length()
+            };
+            is_in_split_domain(&mut buf, length, split_domains)
+        };
+        let split_domains = vec!["corp.example.com".to_string(), "lan".to_string()];
+
+        assert_eq!(check("corp.example.com", &split_domains), true);
+        assert_eq!(check("Intranet.Corp.Example.com", &split_domains), true);
+        assert_eq!(check("printer.lan", &split_domains), true);
+        assert_eq!(check("notcorp.example.com", &split_domains), false);
+        assert_eq!(check("booga.com", &split_domains), false);
+        assert_eq!(check("printer.lan", &[]), false);
+    }
+
+    #[test]
+    fn make_server_failure_keeps_the_transaction_id() {
+        let mut buf: [u8; 500] = [0; 500];
+        let length = {
+            let mut request = PacketFacade::new(&mut buf, 500);
+            request.set_transaction_id(0x4321);
+            request.set_query(true);
+            request.set_opcode(OpCode::Query.into());
+            request.add_query("ooga.com", RecordType::MX.into(), DNSClass::IN.into());
+            request.get_length()
+        };
+
+        let rsp_length = make_server_failure(&mut buf, length);
+
+        let response = PacketFacade::new(&mut buf, rsp_length);
+        assert_eq!(response.get_transaction_id(), Some(0x4321));
+        assert_eq!(response.is_query(), Some(false));
+        assert_eq!(response.get_rcode(), Some(ResponseCode::ServFail.low()));
+        assert_eq!(rsp_length, HEADER_BYTES);
+    }
+
     #[test]
     fn write_log_produces_correct_text() {
         init_test_logging();
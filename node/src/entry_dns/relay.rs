@@ -0,0 +1,249 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::sub_lib::random::{Random, RandomReal};
+use crate::sub_lib::udp_socket_wrapper::UdpSocketWrapperTrait;
+use futures::try_ready;
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+use tokio::prelude::Async;
+
+// Clients give up and ask again long before this; an answer that comes later is thrown away
+const RELAY_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_PENDING_QUERIES: usize = 4096;
+
+struct PendingQuery {
+    client_addr: SocketAddr,
+    client_transaction_id: u16,
+    relayed_at: Instant,
+}
+
+// Passes the queries a stub resolver can't answer itself on to real DNS servers, and their answers
+// back. Each query goes out under a random transaction ID of our own, and only answers from the
+// servers it went to are believed, so that answers are hard to forge from elsewhere on the network.
+pub struct DnsRelay {
+    socket_wrapper: Box<dyn UdpSocketWrapperTrait>,
+    upstream_servers: Vec<SocketAddr>,
+    next_upstream: usize,
+    pending: HashMap<u16, PendingQuery>,
+    random: Box<dyn Random>,
+}
+
+impl DnsRelay {
+    pub fn new(
+        socket_wrapper: Box<dyn UdpSocketWrapperTrait>,
+        upstream_servers: Vec<SocketAddr>,
+    ) -> DnsRelay {
+        DnsRelay {
+            socket_wrapper,
+            upstream_servers,
+            next_upstream: 0,
+            pending: HashMap::new(),
+            random: Box::new(RandomReal {}),
+        }
+    }
+
+    // Our transaction IDs are drawn from here
+    #[cfg(test)]
+    pub fn with_random(mut self, random: Box<dyn Random>) -> DnsRelay {
+        self.random = random;
+        self
+    }
+
+    pub fn upstream_servers(&self) -> &Vec<SocketAddr> {
+        &self.upstream_servers
+    }
+
+    pub fn bind(&mut self) -> io::Result<bool> {
+        self.socket_wrapper
+            .bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0))
+    }
+
+    // Ok(false) means the query couldn't be relayed, and the client should be told so
+    pub fn relay(&mut self, query: &[u8], client_addr: SocketAddr) -> io::Result<bool> {
+        self.forget_stale_queries();
+        if self.upstream_servers.is_empty()
+            || query.len() < 2
+            || self.pending.len() >= MAX_PENDING_QUERIES
+        {
+            return Ok(false);
+        }
+        let client_transaction_id = u16::from_be_bytes([query[0], query[1]]);
+        let transaction_id = self.unused_transaction_id();
+        let mut relayed_query = query.to_vec();
+        relayed_query[0..2].copy_from_slice(&transaction_id.to_be_bytes());
+        let upstream_server = self.upstream_servers[self.next_upstream];
+        self.next_upstream = (self.next_upstream + 1) % self.upstream_servers.len();
+        match self
+            .socket_wrapper
+            .send_to(&relayed_query, upstream_server)?
+        {
+            Async::Ready(_) => {
+                self.pending.insert(
+                    transaction_id,
+                    PendingQuery {
+                        client_addr,
+                        client_transaction_id,
+                        relayed_at: Instant::now(),
+                    },
+                );
+                Ok(true)
+            }
+            Async::NotReady => Ok(false),
+        }
+    }
+
+    // An answer from upstream, restored to the transaction ID the client used, and where it goes
+    pub fn poll_answer(&mut self, buf: &mut [u8]) -> Result<Async<(usize, SocketAddr)>, io::Error> {
+        loop {
+            let (length, from) = try_ready!(self.socket_wrapper.recv_from(buf));
+            if length < 2 || !self.upstream_servers.contains(&from) {
+                continue;
+            }
+            let transaction_id = u16::from_be_bytes([buf[0], buf[1]]);
+            if let Some(pending) = self.pending.remove(&transaction_id) {
+                buf[0..2].copy_from_slice(&pending.client_transaction_id.to_be_bytes());
+                return Ok(Async::Ready((length, pending.client_addr)));
+            }
+        }
+    }
+
+    fn forget_stale_queries(&mut self) {
+        self.pending
+            .retain(|_, pending| pending.relayed_at.elapsed() < RELAY_TIMEOUT);
+    }
+
+    fn unused_transaction_id(&self) -> u16 {
+        loop {
+            let transaction_id = self.random.range(0, 0x1_0000) as u16;
+            if !self.pending.contains_key(&transaction_id) {
+                return transaction_id;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::random_seeded::RandomSeeded;
+    use crate::test_utils::scripted_udp_socket_wrapper::ScriptedUdpSocketWrapper;
+    use std::str::FromStr;
+
+    fn upstream(n: u8) -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 1, 1, n)), 53)
+    }
+
+    fn client() -> SocketAddr {
+        SocketAddr::from_str("127.0.0.1:5454").unwrap()
+    }
+
+    #[test]
+    fn queries_take_turns_among_the_upstream_servers_under_new_transaction_ids() {
+        let socket_wrapper = ScriptedUdpSocketWrapper::new();
+        let mut subject = DnsRelay::new(
+            Box::new(socket_wrapper.clone()),
+            vec![upstream(1), upstream(2)],
+        );
+
+        assert_eq!(subject.relay(&[0x12, 0x34, 0xAA], client()).unwrap(), true);
+        assert_eq!(subject.relay(&[0x56, 0x78, 0xBB], client()).unwrap(), true);
+        assert_eq!(subject.relay(&[0x9A, 0xBC, 0xCC], client()).unwrap(), true);
+
+        let send_to_params = socket_wrapper.send_to_params.lock().unwrap();
+        let destinations = send_to_params
+            .iter()
+            .map(|(_, addr)| *addr)
+            .collect::<Vec<SocketAddr>>();
+        assert_eq!(destinations, vec![upstream(1), upstream(2), upstream(1)]);
+        assert_eq!(send_to_params[1].0[2], 0xBB);
+        assert_eq!(subject.pending.len(), 3);
+        let pending =
+            &subject.pending[&u16::from_be_bytes([send_to_params[1].0[0], send_to_params[1].0[1]])];
+        assert_eq!(pending.client_transaction_id, 0x5678);
+        assert_eq!(pending.client_addr, client());
+    }
+
+    #[test]
+    fn transaction_ids_come_from_the_relays_random() {
+        let socket_wrapper = ScriptedUdpSocketWrapper::new();
+        let mut subject = DnsRelay::new(Box::new(socket_wrapper.clone()), vec![upstream(1)])
+            .with_random(Box::new(RandomSeeded::new(1234)));
+        let reference = RandomSeeded::new(1234);
+        let expected_ids = (0..2)
+            .map(|_| reference.range(0, 0x1_0000) as u16)
+            .collect::<Vec<u16>>();
+
+        subject.relay(&[0x12, 0x34, 0xAA], client()).unwrap();
+        subject.relay(&[0x56, 0x78, 0xBB], client()).unwrap();
+
+        let relayed_ids = socket_wrapper
+            .send_to_params
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(query, _)| u16::from_be_bytes([query[0], query[1]]))
+            .collect::<Vec<u16>>();
+        assert_eq!(relayed_ids, expected_ids);
+    }
+
+    #[test]
+    fn nothing_is_relayed_without_upstream_servers() {
+        let mut subject = DnsRelay::new(Box::new(ScriptedUdpSocketWrapper::new()), vec![]);
+
+        let result = subject.relay(&[0x12, 0x34, 0xAA], client());
+
+        assert_eq!(result.unwrap(), false);
+        assert!(subject.pending.is_empty());
+    }
+
+    #[test]
+    fn answers_go_back_to_the_client_under_its_own_transaction_id() {
+        let socket_wrapper = ScriptedUdpSocketWrapper::new();
+        let mut subject = DnsRelay::new(Box::new(socket_wrapper.clone()), vec![upstream(1)]);
+        subject.relay(&[0x12, 0x34, 0xAA], client()).unwrap();
+        let relayed = socket_wrapper.send_to_params.lock().unwrap()[0].0.clone();
+        let other_id = u16::from_be_bytes([relayed[0], relayed[1]])
+            .wrapping_add(1)
+            .to_be_bytes();
+        socket_wrapper
+            .recv_from_results
+            .lock()
+            .unwrap()
+            .extend(vec![
+                (vec![relayed[0], relayed[1], 0xDD], upstream(2)),
+                (vec![other_id[0], other_id[1], 0xEE], upstream(1)),
+                (vec![relayed[0], relayed[1], 0xFF], upstream(1)),
+            ]);
+        let mut buf = [0u8; 10];
+
+        let result = subject.poll_answer(&mut buf).unwrap();
+
+        assert_eq!(result, Async::Ready((3, client())));
+        assert_eq!(&buf[0..3], &[0x12, 0x34, 0xFF]);
+        assert!(subject.pending.is_empty());
+        assert_eq!(subject.poll_answer(&mut buf).unwrap(), Async::NotReady);
+    }
+
+    #[test]
+    fn queries_left_unanswered_too_long_are_forgotten() {
+        let mut subject =
+            DnsRelay::new(Box::new(ScriptedUdpSocketWrapper::new()), vec![upstream(1)]);
+        subject.pending.insert(
+            0x1234,
+            PendingQuery {
+                client_addr: client(),
+                client_transaction_id: 0x5678,
+                relayed_at: Instant::now() - RELAY_TIMEOUT - Duration::from_secs(1),
+            },
+        );
+
+        subject.relay(&[0x9A, 0xBC, 0xAA], client()).unwrap();
+
+        assert_eq!(subject.pending.len(), 1);
+        assert!(subject
+            .pending
+            .values()
+            .all(|pending| pending.client_transaction_id == 0x9ABC));
+    }
+}
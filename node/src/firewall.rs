@@ -0,0 +1,754 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::sub_lib::main_tools::StdStreams;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{ChildStdin, ChildStdout, Command, Stdio};
+
+// By the time the Node knows its clandestine port, and again when it stops, it no longer has the
+// privileges to change the firewall. So the rule is kept by a process of its own, started before
+// they're dropped: the Node tells the keeper its port over the keeper's standard input, and the
+// keeper answers on its standard output. When the Node exits, however it exits, the keeper's input
+// closes, and it takes the rule away again.
+
+// No spaces, so that netsh needn't be trusted with quoting
+const RULE_NAME: &str = "PrometheusNode-clandestine-port";
+// macOS's own pf.conf evaluates every anchor under com.apple
+const PF_ANCHOR: &str = "com.apple/250.PrometheusNode";
+
+pub trait CommandRunner {
+    // The command's standard output if it succeeds; otherwise what it said was wrong
+    fn run(&self, program: &str, args: &[&str], input_opt: Option<&str>) -> Result<String, String>;
+}
+
+#[derive(Default)]
+pub struct CommandRunnerReal {}
+
+impl CommandRunner for CommandRunnerReal {
+    fn run(&self, program: &str, args: &[&str], input_opt: Option<&str>) -> Result<String, String> {
+        let mut child = Command::new(program)
+            .args(args)
+            // Output to be parsed shouldn't depend on the user's language
+            .env("LC_ALL", "C")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Couldn't run {}: {}", program, e))?;
+        if let Some(input) = input_opt {
+            let mut stdin = child.stdin.take().expect("Command has no input");
+            stdin
+                .write_all(input.as_bytes())
+                .map_err(|e| format!("Couldn't write to {}: {}", program, e))?;
+        }
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("Couldn't run {}: {}", program, e))?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        } else {
+            Err(format!(
+                "{} {} failed: {}",
+                program,
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+}
+
+impl CommandRunnerReal {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+pub trait Firewall {
+    fn name(&self) -> &'static str;
+    // Whether a rule was added: a port that something else opened is left for it to close
+    fn open(&self, runner: &dyn CommandRunner, port: u16) -> Result<bool, String>;
+    fn close(&self, runner: &dyn CommandRunner, port: u16) -> Result<(), String>;
+    // Takes away whatever rule a keeper that was killed before it could close it left behind
+    fn clear_stale(&self, runner: &dyn CommandRunner) -> Result<(), String>;
+}
+
+pub struct Ufw {}
+
+impl Firewall for Ufw {
+    fn name(&self) -> &'static str {
+        "ufw"
+    }
+
+    fn open(&self, runner: &dyn CommandRunner, port: u16) -> Result<bool, String> {
+        let status = runner.run("ufw", &["status"], None)?;
+        let already_allowed = status.lines().any(|line| {
+            let words = line.split_whitespace().collect::<Vec<&str>>();
+            words.len() > 1
+                && (words[0] == port.to_string() || words[0] == format!("{}/tcp", port))
+                && words[1] == "ALLOW"
+        });
+        if already_allowed {
+            return Ok(false);
+        }
+        runner.run(
+            "ufw",
+            &["allow", &format!("{}/tcp", port), "comment", RULE_NAME],
+            None,
+        )?;
+        Ok(true)
+    }
+
+    fn close(&self, runner: &dyn CommandRunner, port: u16) -> Result<(), String> {
+        runner
+            .run("ufw", &["delete", "allow", &format!("{}/tcp", port)], None)
+            .map(|_| ())
+    }
+
+    // Only rules with our comment are ours; one deletion takes both the IPv4 and IPv6 rule
+    fn clear_stale(&self, runner: &dyn CommandRunner) -> Result<(), String> {
+        let status = runner.run("ufw", &["status"], None)?;
+        let mut stale: Vec<&str> = vec![];
+        status
+            .lines()
+            .filter(|line| line.contains(&format!("# {}", RULE_NAME)))
+            .filter_map(|line| line.split_whitespace().next())
+            .for_each(|to| {
+                if !stale.contains(&to) {
+                    stale.push(to)
+                }
+            });
+        stale.into_iter().try_for_each(|to| {
+            runner
+                .run("ufw", &["delete", "allow", to], None)
+                .map(|_| ())
+        })
+    }
+}
+
+// Only the runtime configuration is changed, so a keeper that's killed leaves nothing behind
+// that outlasts a reload
+pub struct Firewalld {}
+
+impl Firewall for Firewalld {
+    fn name(&self) -> &'static str {
+        "firewalld"
+    }
+
+    fn open(&self, runner: &dyn CommandRunner, port: u16) -> Result<bool, String> {
+        // Says "no", and fails, when the port isn't open
+        let query = runner.run(
+            "firewall-cmd",
+            &[&format!("--query-port={}/tcp", port)],
+            None,
+        );
+        if query.map(|answer| answer.trim() == "yes") == Ok(true) {
+            return Ok(false);
+        }
+        runner.run("firewall-cmd", &[&format!("--add-port={}/tcp", port)], None)?;
+        Ok(true)
+    }
+
+    fn close(&self, runner: &dyn CommandRunner, port: u16) -> Result<(), String> {
+        runner
+            .run(
+                "firewall-cmd",
+                &[&format!("--remove-port={}/tcp", port)],
+                None,
+            )
+            .map(|_| ())
+    }
+
+    // A runtime port can't be told from one somebody else opened, and none survives a reload
+    fn clear_stale(&self, _runner: &dyn CommandRunner) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+// The anchor is the Node's alone, so whatever was in it is replaced
+pub struct Pf {}
+
+impl Firewall for Pf {
+    fn name(&self) -> &'static str {
+        "pf"
+    }
+
+    fn open(&self, runner: &dyn CommandRunner, port: u16) -> Result<bool, String> {
+        let rule = format!("pass in proto tcp from any to any port {}\n", port);
+        runner.run("pfctl", &["-a", PF_ANCHOR, "-f", "-"], Some(&rule))?;
+        Ok(true)
+    }
+
+    fn close(&self, runner: &dyn CommandRunner, _port: u16) -> Result<(), String> {
+        runner
+            .run("pfctl", &["-a", PF_ANCHOR, "-F", "rules"], None)
+            .map(|_| ())
+    }
+
+    fn clear_stale(&self, runner: &dyn CommandRunner) -> Result<(), String> {
+        self.close(runner, 0)
+    }
+}
+
+pub struct WindowsFirewall {}
+
+impl Firewall for WindowsFirewall {
+    fn name(&self) -> &'static str {
+        "Windows Firewall"
+    }
+
+    // A rule of this name can only have been left by a keeper that was killed, so it goes first
+    fn open(&self, runner: &dyn CommandRunner, port: u16) -> Result<bool, String> {
+        let _ = self.close(runner, port);
+        runner.run(
+            "netsh",
+            &[
+                "advfirewall",
+                "firewall",
+                "add",
+                "rule",
+                &format!("name={}", RULE_NAME),
+                "dir=in",
+                "action=allow",
+                "protocol=TCP",
+                &format!("localport={}", port),
+            ],
+            None,
+        )?;
+        Ok(true)
+    }
+
+    fn close(&self, runner: &dyn CommandRunner, _port: u16) -> Result<(), String> {
+        runner
+            .run(
+                "netsh",
+                &[
+                    "advfirewall",
+                    "firewall",
+                    "delete",
+                    "rule",
+                    &format!("name={}", RULE_NAME),
+                ],
+                None,
+            )
+            .map(|_| ())
+    }
+
+    // netsh fails when there's no rule to delete, which is no failure here
+    fn clear_stale(&self, runner: &dyn CommandRunner) -> Result<(), String> {
+        let _ = self.close(runner, 0);
+        Ok(())
+    }
+}
+
+// On Linux, whichever of the usual front ends is active; if neither is, there's nothing to open
+pub fn find_firewall(runner: &dyn CommandRunner, os: &str) -> Option<Box<dyn Firewall>> {
+    match os {
+        "linux" => {
+            let ufw_active = runner
+                .run("ufw", &["status"], None)
+                .map(|status| status.starts_with("Status: active"))
+                .unwrap_or(false);
+            if ufw_active {
+                return Some(Box::new(Ufw {}));
+            }
+            let firewalld_running = runner
+                .run("firewall-cmd", &["--state"], None)
+                .map(|state| state.trim() == "running")
+                .unwrap_or(false);
+            if firewalld_running {
+                Some(Box::new(Firewalld {}))
+            } else {
+                None
+            }
+        }
+        "macos" => Some(Box::new(Pf {})),
+        "windows" => Some(Box::new(WindowsFirewall {})),
+        _ => None,
+    }
+}
+
+// Each port read is opened and answered for with a line starting "ok" or "err", as is "clear",
+// which takes away rules a previous keeper left behind; once there's nothing more to read, every
+// port that was opened is closed
+pub fn keep(
+    commands: &mut dyn BufRead,
+    replies: &mut dyn Write,
+    runner: &dyn CommandRunner,
+    firewall_opt: Option<&dyn Firewall>,
+) {
+    let mut opened: Vec<u16> = vec![];
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match commands.read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => (),
+        }
+        let reply = match (line.trim().parse::<u16>(), firewall_opt) {
+            (Err(_), Some(firewall)) if line.trim() == "clear" => {
+                match firewall.clear_stale(runner) {
+                    Ok(()) => format!("ok Cleared rules left behind in {}", firewall.name()),
+                    Err(e) => format!("err {}", e),
+                }
+            }
+            (Err(_), None) if line.trim() == "clear" => {
+                "ok No active firewall that the Node knows how to manage".to_string()
+            }
+            (Err(_), _) => format!("err {} is not a port", line.trim()),
+            (Ok(_), None) => "ok No active firewall that the Node knows how to manage".to_string(),
+            (Ok(port), Some(firewall)) => match firewall.open(runner, port) {
+                Ok(true) => {
+                    opened.push(port);
+                    format!("ok Opened port {} in {}", port, firewall.name())
+                }
+                Ok(false) => format!("ok Port {} was already open in {}", port, firewall.name()),
+                Err(e) => format!("err {}", e),
+            },
+        };
+        if writeln!(replies, "{}", reply)
+            .and_then(|_| replies.flush())
+            .is_err()
+        {
+            break;
+        }
+    }
+    if let Some(firewall) = firewall_opt {
+        opened.iter().for_each(|port| {
+            let _ = firewall.close(runner, *port);
+        });
+    }
+}
+
+// The hidden firewall-keeper subcommand, which the Node starts for itself
+pub fn firewall_keeper(_args: &Vec<String>, streams: &mut StdStreams<'_>) -> i32 {
+    ignore_termination_signals();
+    let runner = CommandRunnerReal::new();
+    let firewall_opt = find_firewall(&runner, std::env::consts::OS);
+    let mut commands = BufReader::new(&mut *streams.stdin);
+    keep(
+        &mut commands,
+        streams.stdout,
+        &runner,
+        firewall_opt.as_ref().map(|firewall| firewall.as_ref()),
+    );
+    0
+}
+
+// Ctrl-C, or systemd stopping the service, reaches the keeper along with the Node; it must outlive
+// the Node to clean up after it
+#[cfg(not(target_os = "windows"))]
+fn ignore_termination_signals() {
+    unsafe {
+        libc::signal(libc::SIGINT, libc::SIG_IGN);
+        libc::signal(libc::SIGTERM, libc::SIG_IGN);
+        libc::signal(libc::SIGHUP, libc::SIG_IGN);
+    }
+}
+
+// On Windows the keeper is started in a process group of its own instead
+#[cfg(target_os = "windows")]
+fn ignore_termination_signals() {}
+
+pub trait FirewallKeeper: Send {
+    // Ok says what was done, Err why it couldn't be
+    fn keep_open(&mut self, port: u16) -> Result<String, String>;
+    fn clear_stale(&mut self) -> Result<String, String>;
+}
+
+pub struct FirewallKeeperProcess {
+    commands: ChildStdin,
+    replies: BufReader<ChildStdout>,
+}
+
+impl FirewallKeeper for FirewallKeeperProcess {
+    fn keep_open(&mut self, port: u16) -> Result<String, String> {
+        self.ask(&port.to_string())
+    }
+
+    fn clear_stale(&mut self) -> Result<String, String> {
+        self.ask("clear")
+    }
+}
+
+impl FirewallKeeperProcess {
+    fn ask(&mut self, command: &str) -> Result<String, String> {
+        writeln!(self.commands, "{}", command)
+            .and_then(|_| self.commands.flush())
+            .map_err(|e| format!("The firewall keeper isn't listening: {}", e))?;
+        let mut reply = String::new();
+        match self.replies.read_line(&mut reply) {
+            Ok(0) | Err(_) => return Err("The firewall keeper didn't answer".to_string()),
+            Ok(_) => (),
+        }
+        parse_reply(reply.trim())
+    }
+
+    // Must be called with the privileges the keeper will need
+    pub fn launch() -> io::Result<FirewallKeeperProcess> {
+        let mut command = Command::new(std::env::current_exe()?);
+        command
+            .arg("firewall-keeper")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped());
+        detach_from_console(&mut command);
+        let mut keeper = command.spawn()?;
+        Ok(FirewallKeeperProcess {
+            commands: keeper.stdin.take().expect("Firewall keeper has no input"),
+            replies: BufReader::new(keeper.stdout.take().expect("Firewall keeper has no output")),
+        })
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn detach_from_console(_command: &mut Command) {}
+
+#[cfg(target_os = "windows")]
+fn detach_from_console(command: &mut Command) {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+    command.creation_flags(CREATE_NEW_PROCESS_GROUP);
+}
+
+fn parse_reply(reply: &str) -> Result<String, String> {
+    if reply.starts_with("ok ") {
+        Ok(reply.trim_start_matches("ok ").to_string())
+    } else if reply.starts_with("err ") {
+        Err(reply.trim_start_matches("err ").to_string())
+    } else {
+        Err(format!("The firewall keeper made no sense: {}", reply))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::ByteArrayWriter;
+    use std::cell::RefCell;
+    use std::io::Cursor;
+
+    #[derive(Default)]
+    struct CommandRunnerMock {
+        run_parameters: RefCell<Vec<String>>,
+        run_results: RefCell<Vec<Result<String, String>>>,
+    }
+
+    impl CommandRunner for CommandRunnerMock {
+        fn run(
+            &self,
+            program: &str,
+            args: &[&str],
+            input_opt: Option<&str>,
+        ) -> Result<String, String> {
+            let mut command = vec![program];
+            command.extend(args);
+            let mut parameter = command.join(" ");
+            if let Some(input) = input_opt {
+                parameter.push_str(&format!(" <<< {}", input.trim()));
+            }
+            self.run_parameters.borrow_mut().push(parameter);
+            let mut run_results = self.run_results.borrow_mut();
+            if run_results.is_empty() {
+                panic!("run called without a stub: {}", command.join(" "))
+            }
+            run_results.remove(0)
+        }
+    }
+
+    impl CommandRunnerMock {
+        fn new() -> Self {
+            Self::default()
+        }
+
+        fn run_result(self, result: Result<&str, &str>) -> Self {
+            self.run_results.borrow_mut().push(
+                result
+                    .map(|output| output.to_string())
+                    .map_err(|e| e.to_string()),
+            );
+            self
+        }
+
+        fn commands(&self) -> Vec<String> {
+            self.run_parameters.borrow().clone()
+        }
+    }
+
+    const UFW_STATUS: &str = "Status: active\n\nTo                         Action      From\n\
+                              --                         ------      ----\n\
+                              22/tcp                     ALLOW       Anywhere\n\
+                              8080                       ALLOW       Anywhere\n";
+
+    #[test]
+    fn ufw_opens_a_port_it_doesnt_allow_yet() {
+        let runner = CommandRunnerMock::new()
+            .run_result(Ok(UFW_STATUS))
+            .run_result(Ok("Rule added\n"));
+
+        let result = Ufw {}.open(&runner, 5542);
+
+        assert_eq!(result, Ok(true));
+        assert_eq!(
+            runner.commands(),
+            vec![
+                "ufw status",
+                "ufw allow 5542/tcp comment PrometheusNode-clandestine-port"
+            ]
+        );
+    }
+
+    #[test]
+    fn ufw_leaves_a_port_it_already_allows_alone() {
+        let runner = CommandRunnerMock::new().run_result(Ok(UFW_STATUS));
+
+        let result = Ufw {}.open(&runner, 8080);
+
+        assert_eq!(result, Ok(false));
+        assert_eq!(runner.commands(), vec!["ufw status"]);
+    }
+
+    #[test]
+    fn ufw_closes_a_port() {
+        let runner = CommandRunnerMock::new().run_result(Ok("Rule deleted\n"));
+
+        let result = Ufw {}.close(&runner, 5542);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(runner.commands(), vec!["ufw delete allow 5542/tcp"]);
+    }
+
+    #[test]
+    fn ufw_clears_only_the_rules_a_previous_node_left_behind() {
+        let status = "Status: active\n\nTo                         Action      From\n\
+                      --                         ------      ----\n\
+                      22/tcp                     ALLOW       Anywhere\n\
+                      5542/tcp                   ALLOW       Anywhere                   # PrometheusNode-clandestine-port\n\
+                      22/tcp (v6)                ALLOW       Anywhere (v6)\n\
+                      5542/tcp (v6)              ALLOW       Anywhere (v6)              # PrometheusNode-clandestine-port\n";
+        let runner = CommandRunnerMock::new()
+            .run_result(Ok(status))
+            .run_result(Ok("Rule deleted\nRule deleted (v6)\n"));
+
+        let result = Ufw {}.clear_stale(&runner);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            runner.commands(),
+            vec!["ufw status", "ufw delete allow 5542/tcp"]
+        );
+    }
+
+    #[test]
+    fn firewalld_opens_a_port_at_runtime_only() {
+        let runner = CommandRunnerMock::new()
+            .run_result(Err("no"))
+            .run_result(Ok("success\n"));
+
+        let result = Firewalld {}.open(&runner, 5542);
+
+        assert_eq!(result, Ok(true));
+        assert_eq!(
+            runner.commands(),
+            vec![
+                "firewall-cmd --query-port=5542/tcp",
+                "firewall-cmd --add-port=5542/tcp"
+            ]
+        );
+    }
+
+    #[test]
+    fn firewalld_leaves_a_port_already_open_alone() {
+        let runner = CommandRunnerMock::new().run_result(Ok("yes\n"));
+
+        let result = Firewalld {}.open(&runner, 5542);
+
+        assert_eq!(result, Ok(false));
+    }
+
+    #[test]
+    fn firewalld_closes_a_port() {
+        let runner = CommandRunnerMock::new().run_result(Ok("success\n"));
+
+        let result = Firewalld {}.close(&runner, 5542);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            runner.commands(),
+            vec!["firewall-cmd --remove-port=5542/tcp"]
+        );
+    }
+
+    #[test]
+    fn pf_loads_and_flushes_an_anchor_of_its_own() {
+        let runner = CommandRunnerMock::new()
+            .run_result(Ok(""))
+            .run_result(Ok(""));
+        let subject = Pf {};
+
+        let open_result = subject.open(&runner, 5542);
+        let close_result = subject.close(&runner, 5542);
+
+        assert_eq!(open_result, Ok(true));
+        assert_eq!(close_result, Ok(()));
+        assert_eq!(
+            runner.commands(),
+            vec![
+                "pfctl -a com.apple/250.PrometheusNode -f - <<< pass in proto tcp from any to any port 5542",
+                "pfctl -a com.apple/250.PrometheusNode -F rules"
+            ]
+        );
+    }
+
+    #[test]
+    fn windows_firewall_replaces_any_rule_left_behind() {
+        let runner = CommandRunnerMock::new()
+            .run_result(Err("No rules match the specified criteria."))
+            .run_result(Ok("Ok.\n"));
+
+        let result = WindowsFirewall {}.open(&runner, 5542);
+
+        assert_eq!(result, Ok(true));
+        assert_eq!(
+            runner.commands(),
+            vec![
+                "netsh advfirewall firewall delete rule name=PrometheusNode-clandestine-port",
+                "netsh advfirewall firewall add rule name=PrometheusNode-clandestine-port dir=in action=allow protocol=TCP localport=5542"
+            ]
+        );
+    }
+
+    #[test]
+    fn linux_firewalls_are_found_by_asking_them() {
+        let ufw_runner = CommandRunnerMock::new().run_result(Ok(UFW_STATUS));
+        let firewalld_runner = CommandRunnerMock::new()
+            .run_result(Ok("Status: inactive\n"))
+            .run_result(Ok("running\n"));
+        let no_runner = CommandRunnerMock::new()
+            .run_result(Err("Couldn't run ufw: No such file or directory"))
+            .run_result(Err("Couldn't run firewall-cmd: No such file or directory"));
+
+        let ufw = find_firewall(&ufw_runner, "linux");
+        let firewalld = find_firewall(&firewalld_runner, "linux");
+        let none = find_firewall(&no_runner, "linux");
+
+        assert_eq!(ufw.map(|firewall| firewall.name()), Some("ufw"));
+        assert_eq!(firewalld.map(|firewall| firewall.name()), Some("firewalld"));
+        assert_eq!(none.map(|firewall| firewall.name()), None);
+    }
+
+    #[test]
+    fn other_firewalls_are_found_by_operating_system() {
+        let runner = CommandRunnerMock::new();
+
+        let macos = find_firewall(&runner, "macos");
+        let windows = find_firewall(&runner, "windows");
+        let freebsd = find_firewall(&runner, "freebsd");
+
+        assert_eq!(macos.map(|firewall| firewall.name()), Some("pf"));
+        assert_eq!(
+            windows.map(|firewall| firewall.name()),
+            Some("Windows Firewall")
+        );
+        assert_eq!(freebsd.map(|firewall| firewall.name()), None);
+        assert!(runner.commands().is_empty());
+    }
+
+    #[test]
+    fn the_keeper_opens_ports_until_its_input_closes_then_closes_them() {
+        let runner = CommandRunnerMock::new()
+            .run_result(Ok("Status: active\n"))
+            .run_result(Ok("Rule added\n"))
+            .run_result(Ok(UFW_STATUS))
+            .run_result(Ok("Rule deleted\n"));
+        let mut commands = Cursor::new(b"5542\nbooga\n8080\n".to_vec());
+        let mut replies = ByteArrayWriter::new();
+
+        keep(&mut commands, &mut replies, &runner, Some(&Ufw {}));
+
+        assert_eq!(
+            replies.get_string(),
+            "ok Opened port 5542 in ufw\n\
+             err booga is not a port\n\
+             ok Port 8080 was already open in ufw\n"
+        );
+        assert_eq!(
+            runner.commands(),
+            vec![
+                "ufw status",
+                "ufw allow 5542/tcp comment PrometheusNode-clandestine-port",
+                "ufw status",
+                "ufw delete allow 5542/tcp"
+            ]
+        );
+    }
+
+    #[test]
+    fn the_keeper_reports_a_port_it_couldnt_open() {
+        let runner = CommandRunnerMock::new()
+            .run_result(Ok("Status: active\n"))
+            .run_result(Err("ufw allow 5542/tcp failed: ERROR: You need to be root"));
+        let mut commands = Cursor::new(b"5542\n".to_vec());
+        let mut replies = ByteArrayWriter::new();
+
+        keep(&mut commands, &mut replies, &runner, Some(&Ufw {}));
+
+        assert_eq!(
+            replies.get_string(),
+            "err ufw allow 5542/tcp failed: ERROR: You need to be root\n"
+        );
+        assert_eq!(runner.commands().len(), 2);
+    }
+
+    #[test]
+    fn the_keeper_clears_rules_left_behind_when_asked() {
+        let runner =
+            CommandRunnerMock::new().run_result(Err("No rules match the specified criteria."));
+        let mut commands = Cursor::new(b"clear\n".to_vec());
+        let mut replies = ByteArrayWriter::new();
+
+        keep(
+            &mut commands,
+            &mut replies,
+            &runner,
+            Some(&WindowsFirewall {}),
+        );
+
+        assert_eq!(
+            replies.get_string(),
+            "ok Cleared rules left behind in Windows Firewall\n"
+        );
+        assert_eq!(
+            runner.commands(),
+            vec!["netsh advfirewall firewall delete rule name=PrometheusNode-clandestine-port"]
+        );
+    }
+
+    #[test]
+    fn the_keeper_without_a_firewall_has_nothing_to_do() {
+        let runner = CommandRunnerMock::new();
+        let mut commands = Cursor::new(b"5542\n".to_vec());
+        let mut replies = ByteArrayWriter::new();
+
+        keep(&mut commands, &mut replies, &runner, None);
+
+        assert_eq!(
+            replies.get_string(),
+            "ok No active firewall that the Node knows how to manage\n"
+        );
+        assert!(runner.commands().is_empty());
+    }
+
+    #[test]
+    fn replies_are_parsed_into_results() {
+        assert_eq!(
+            parse_reply("ok Opened port 5542 in ufw"),
+            Ok("Opened port 5542 in ufw".to_string())
+        );
+        assert_eq!(
+            parse_reply("err booga is not a port"),
+            Err("booga is not a port".to_string())
+        );
+        assert_eq!(
+            parse_reply("booga"),
+            Err("The firewall keeper made no sense: booga".to_string())
+        );
+    }
+}
@@ -1,8 +1,8 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
 use super::live_cores_package::LiveCoresPackage;
+use crate::sub_lib::cryptde::CryptDE;
 use crate::sub_lib::cryptde::CryptData;
-use crate::sub_lib::cryptde::{encodex, CryptDE};
 use crate::sub_lib::dispatcher::{Endpoint, InboundClientData};
 use crate::sub_lib::hopper::{IncipientCoresPackage, NoLookupIncipientCoresPackage};
 use crate::sub_lib::logger::Logger;
@@ -42,7 +42,7 @@ impl ConsumingService {
         let target_node_addr = incipient_cores_package.node_addr.clone();
         match LiveCoresPackage::from_no_lookup_incipient(incipient_cores_package, self.cryptde) {
             Ok((live_package, _)) => {
-                let encrypted_package = match encodex(self.cryptde, &target_key, &live_package) {
+                let encrypted_package = match live_package.encode(self.cryptde, &target_key) {
                     Ok(p) => p,
                     Err(e) => {
                         error!(
@@ -74,7 +74,7 @@ impl ConsumingService {
         match LiveCoresPackage::from_incipient(incipient_cores_package, self.cryptde.borrow()) {
             Ok((live_package, next_hop)) => {
                 let encrypted_package =
-                    match encodex(self.cryptde, &next_hop.public_key, &live_package) {
+                    match live_package.encode(self.cryptde, &next_hop.public_key) {
                         Ok(p) => p,
                         Err(e) => {
                             error!(self.logger, "Couldn't encode package: {}", e);
@@ -180,7 +180,7 @@ mod tests {
                 endpoint: Endpoint::Socket(SocketAddr::from_str("1.2.1.2:1212").unwrap()),
                 last_data: false,
                 sequence_number: None,
-                data: encodex(cryptde(), &target_key, &lcp).unwrap().into(),
+                data: lcp.encode(cryptde(), &target_key).unwrap().into(),
             },
             transmit_data_msg
         );
@@ -247,7 +247,7 @@ mod tests {
         let record = dispatcher_recording.get_record::<TransmitDataMsg>(0);
         let (expected_lcp, _) =
             LiveCoresPackage::from_incipient(incipient_cores_package, cryptde).unwrap();
-        let expected_lcp_enc = encodex(cryptde, &destination_key, &expected_lcp).unwrap();
+        let expected_lcp_enc = expected_lcp.encode(cryptde, &destination_key).unwrap();
         assert_eq!(
             TransmitDataMsg {
                 endpoint: Endpoint::Key(destination_key.clone()),
@@ -294,7 +294,7 @@ mod tests {
         let record = hopper_recording.get_record::<InboundClientData>(0);
         let (expected_lcp, _) =
             LiveCoresPackage::from_incipient(incipient_cores_package, cryptde).unwrap();
-        let expected_lcp_enc = encodex(cryptde, &destination_key, &expected_lcp).unwrap();
+        let expected_lcp_enc = expected_lcp.encode(cryptde, &destination_key).unwrap();
         assert_eq!(
             InboundClientData {
                 peer_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 0),
@@ -1,8 +1,9 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
+use crate::hopper::wire_cores_package::WireCoresPackage;
 use crate::sub_lib::cryptde::CryptData;
 use crate::sub_lib::cryptde::PublicKey;
-use crate::sub_lib::cryptde::{decodex, CryptDE};
+use crate::sub_lib::cryptde::{decodex, CryptDE, PlainData};
 use crate::sub_lib::data_version::DataVersion;
 use crate::sub_lib::hop::LiveHop;
 use crate::sub_lib::hopper::IncipientCoresPackage;
@@ -12,6 +13,8 @@ use crate::sub_lib::route::RouteError;
 use serde_derive::{Deserialize, Serialize};
 use std::net::SocketAddr;
 
+// Goes between Nodes in the WireCoresPackage format of its version. Serde is only for version 0,
+// whose format is this struct in CBOR.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct LiveCoresPackage {
     pub version: DataVersion,
@@ -20,6 +23,8 @@ pub struct LiveCoresPackage {
 }
 
 impl LiveCoresPackage {
+    // What Nodes send. Version 1 is read too, but isn't sent until no Node that can't read it is
+    // left on the network.
     pub fn version() -> DataVersion {
         DataVersion::new(0, 0).expect("Internal Error")
     }
@@ -32,12 +37,48 @@ impl LiveCoresPackage {
         }
     }
 
+    pub fn to_wire(&self) -> PlainData {
+        if self.version.major() == 0 {
+            PlainData::from(
+                serde_cbor::ser::to_vec(self).expect("Serialization of LiveCoresPackage failed"),
+            )
+        } else {
+            WireCoresPackage::write(self.version, &self.hop_slices(), self.payload.as_slice())
+        }
+    }
+
+    fn hop_slices(&self) -> Vec<&[u8]> {
+        self.route.hops.iter().map(|hop| hop.as_slice()).collect()
+    }
+
+    pub fn from_wire(data: PlainData) -> Result<LiveCoresPackage, String> {
+        WireCoresPackage::parse(data).map(|wire| wire.to_live())
+    }
+
+    pub fn encode(
+        &self,
+        cryptde: &dyn CryptDE,
+        public_key: &PublicKey,
+    ) -> Result<CryptData, String> {
+        cryptde
+            .encode(public_key, &self.to_wire())
+            .map_err(|e| format!("Encryption error: {:?}", e))
+    }
+
+    pub fn decode(cryptde: &dyn CryptDE, data: &CryptData) -> Result<LiveCoresPackage, String> {
+        WireCoresPackage::decode(cryptde, data).map(|wire| wire.to_live())
+    }
+
     pub fn to_next_live(
         mut self,
         cryptde: &dyn CryptDE, // must be the CryptDE of the Node to which the top hop is encrypted
     ) -> Result<(LiveHop, LiveCoresPackage), RouteError> {
         let next_hop = self.route.shift(cryptde)?;
-        let next_live = LiveCoresPackage::new(self.route, self.payload);
+        let next_live = LiveCoresPackage {
+            version: self.version,
+            route: self.route,
+            payload: self.payload,
+        };
         Ok((next_hop, next_live))
     }
 
@@ -95,6 +136,10 @@ impl LiveCoresPackage {
 mod tests {
     use super::*;
     use crate::blockchain::blockchain_interface::contract_address;
+    use crate::discriminator::Discriminator;
+    use crate::json_framer::JsonFramer;
+    use crate::json_masquerader::JsonMasquerader;
+    use crate::masquerader::Masquerader;
     use crate::sub_lib::cryptde::encodex;
     use crate::sub_lib::cryptde::PlainData;
     use crate::sub_lib::cryptde_null::CryptDENull;
@@ -103,10 +148,13 @@ mod tests {
     use crate::sub_lib::node_addr::NodeAddr;
     use crate::sub_lib::route::Route;
     use crate::sub_lib::route::RouteSegment;
+    use crate::test_utils::cores_package_strategies::{framing, payload, route, Framing};
+    use crate::test_utils::golden_files::{assert_golden_bytes, golden_route};
     use crate::test_utils::{
         cryptde, make_meaningless_message_type, make_meaningless_route, make_paying_wallet,
         DEFAULT_CHAIN_ID,
     };
+    use proptest::prelude::*;
     use std::net::{IpAddr, SocketAddr};
     use std::str::FromStr;
 
@@ -392,10 +440,134 @@ mod tests {
         let original =
             LiveCoresPackage::new(make_meaningless_route(), CryptData::new(&[1, 2, 3, 4]));
 
-        let serialized = serde_cbor::ser::to_vec(&original).unwrap();
+        let serialized = original.to_wire();
 
-        let deserialized = serde_cbor::de::from_slice::<LiveCoresPackage>(&serialized[..]).unwrap();
+        let deserialized = LiveCoresPackage::from_wire(serialized).unwrap();
 
         assert_eq!(deserialized, original);
     }
+
+    #[test]
+    fn live_cores_package_encode_decode() {
+        let cryptde = cryptde();
+        let original =
+            LiveCoresPackage::new(make_meaningless_route(), CryptData::new(&[1, 2, 3, 4]));
+
+        let encoded = original.encode(cryptde, cryptde.public_key()).unwrap();
+
+        assert_eq!(LiveCoresPackage::decode(cryptde, &encoded), Ok(original));
+        assert_eq!(
+            LiveCoresPackage::decode(cryptde, &CryptData::new(&[])),
+            Err("Decryption error: EmptyData".to_string())
+        );
+    }
+
+    // What the Hopper, the Dispatcher and the StreamHandlerPool do between them to get a package
+    // from one Node to the next
+    fn send_over_the_wire(
+        live: &LiveCoresPackage,
+        sender: &dyn CryptDE,
+        receiver: &dyn CryptDE,
+        framing: &Framing,
+    ) -> Result<LiveCoresPackage, String> {
+        let encoded = live.encode(sender, receiver.public_key())?;
+        let masked = framing
+            .masquerader()
+            .mask(encoded.as_slice())
+            .map_err(|e| format!("{:?}", e))?;
+        let mut discriminator = Discriminator::new(
+            Box::new(JsonFramer::new()),
+            vec![Box::new(JsonMasquerader::new())],
+        );
+        let mut chunks = vec![];
+        framing.reads(&masked).into_iter().for_each(|read| {
+            discriminator.add_data(&read);
+            while let Some(chunk) = discriminator.take_chunk() {
+                chunks.push(chunk.chunk);
+            }
+        });
+        match chunks.as_slice() {
+            [chunk] => LiveCoresPackage::decode(receiver, &CryptData::new(chunk)),
+            other => Err(format!("Expected one chunk, got {}", other.len())),
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn live_cores_packages_survive_masking_and_framing_at_every_hop(
+            (keys, route) in route(),
+            payload in payload(),
+            framing in framing()
+        ) {
+            let cryptdes = keys
+                .iter()
+                .map(|key| CryptDENull::from(key, DEFAULT_CHAIN_ID))
+                .collect::<Vec<CryptDENull>>();
+            let destination = cryptdes.last().unwrap();
+            let incipient = IncipientCoresPackage::new(
+                &cryptdes[0],
+                route,
+                payload.clone(),
+                destination.public_key(),
+            )
+            .unwrap();
+            let (mut live, _) = LiveCoresPackage::from_incipient(incipient, &cryptdes[0]).unwrap();
+            for hop in 1..cryptdes.len() {
+                let arrived =
+                    send_over_the_wire(&live, &cryptdes[hop - 1], &cryptdes[hop], &framing);
+                prop_assert_eq!(arrived.as_ref(), Ok(&live));
+                live = arrived.unwrap();
+                if hop < cryptdes.len() - 1 {
+                    live = live.to_next_live(&cryptdes[hop]).unwrap().1;
+                }
+            }
+            let expired = live
+                .to_expired(SocketAddr::from_str("1.2.3.4:5678").unwrap(), destination)
+                .unwrap();
+
+            prop_assert_eq!(expired.payload, payload);
+        }
+
+        #[test]
+        fn a_truncated_live_cores_package_fails_to_decode(
+            (keys, route) in route(),
+            payload in payload(),
+            cut in any::<prop::sample::Index>()
+        ) {
+            let cryptde = CryptDENull::from(&keys[1], DEFAULT_CHAIN_ID);
+            let live = LiveCoresPackage::new(
+                route,
+                encodex(&cryptde, cryptde.public_key(), &payload).unwrap(),
+            );
+            let encoded = live.encode(&cryptde, cryptde.public_key()).unwrap();
+            let truncated = CryptData::new(&encoded.as_slice()[..cut.index(encoded.len())]);
+
+            let result = LiveCoresPackage::decode(&cryptde, &truncated);
+
+            prop_assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn golden_live_cores_package() {
+        let subject = LiveCoresPackage::new(golden_route(), CryptData::new(b"golden payload"));
+
+        let serialized = subject.to_wire();
+
+        assert_golden_bytes("golden_live_cores_package", serialized.as_slice());
+        assert_eq!(LiveCoresPackage::from_wire(serialized), Ok(subject));
+    }
+
+    #[test]
+    fn golden_wire_cores_package() {
+        let subject = LiveCoresPackage {
+            version: WireCoresPackage::version(),
+            ..LiveCoresPackage::new(golden_route(), CryptData::new(b"golden payload"))
+        };
+
+        let serialized = subject.to_wire();
+
+        assert_golden_bytes("golden_wire_cores_package", serialized.as_slice());
+        assert_eq!(LiveCoresPackage::from_wire(serialized), Ok(subject));
+    }
 }
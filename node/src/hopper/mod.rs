@@ -3,8 +3,11 @@
 mod consuming_service;
 pub mod live_cores_package;
 mod routing_service;
+mod routing_worker_pool;
+pub mod wire_cores_package;
 
 use crate::hopper::routing_service::RoutingServiceSubs;
+use crate::sub_lib::crash_context::handling;
 use crate::sub_lib::cryptde::CryptDE;
 use crate::sub_lib::dispatcher::InboundClientData;
 use crate::sub_lib::hopper::HopperSubs;
@@ -18,14 +21,17 @@ use actix::Context;
 use actix::Handler;
 use consuming_service::ConsumingService;
 use routing_service::RoutingService;
+use routing_worker_pool::RoutingWorkerPool;
 
 pub struct Hopper {
     cryptde: &'static dyn CryptDE,
     consuming_service: Option<ConsumingService>,
     routing_service: Option<RoutingService>,
+    routing_pool: Option<RoutingWorkerPool>,
     per_routing_service: u64,
     per_routing_byte: u64,
     is_decentralized: bool,
+    routing_workers: usize,
 }
 
 impl Actor for Hopper {
@@ -36,26 +42,44 @@ impl Handler<BindMessage> for Hopper {
     type Result = ();
 
     fn handle(&mut self, msg: BindMessage, ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Hopper", "BindMessage");
         ctx.set_mailbox_capacity(NODE_MAILBOX_CAPACITY);
         self.consuming_service = Some(ConsumingService::new(
             self.cryptde,
             msg.peer_actors.dispatcher.from_dispatcher_client.clone(),
             msg.peer_actors.hopper.from_dispatcher.clone(),
         ));
-        self.routing_service = Some(RoutingService::new(
+        let routing_service_subs = RoutingServiceSubs {
+            proxy_client_subs: msg.peer_actors.proxy_client,
+            proxy_server_subs: msg.peer_actors.proxy_server,
+            neighborhood_subs: msg.peer_actors.neighborhood,
+            hopper_subs: msg.peer_actors.hopper,
+            to_dispatcher: msg.peer_actors.dispatcher.from_dispatcher_client,
+            to_accountant_routing: msg.peer_actors.accountant.report_routing_service_provided,
+        };
+        let (cryptde, per_routing_service, per_routing_byte, is_decentralized) = (
             self.cryptde,
-            RoutingServiceSubs {
-                proxy_client_subs: msg.peer_actors.proxy_client,
-                proxy_server_subs: msg.peer_actors.proxy_server,
-                neighborhood_subs: msg.peer_actors.neighborhood,
-                hopper_subs: msg.peer_actors.hopper,
-                to_dispatcher: msg.peer_actors.dispatcher.from_dispatcher_client,
-                to_accountant_routing: msg.peer_actors.accountant.report_routing_service_provided,
-            },
             self.per_routing_service,
             self.per_routing_byte,
             self.is_decentralized,
-        ));
+        );
+        let make_routing_service = || {
+            RoutingService::new(
+                cryptde,
+                routing_service_subs.clone(),
+                per_routing_service,
+                per_routing_byte,
+                is_decentralized,
+            )
+        };
+        if self.routing_workers > 1 {
+            self.routing_pool = Some(RoutingWorkerPool::new(self.routing_workers, || {
+                let routing_service = make_routing_service();
+                move |ibcd: InboundClientData| routing_service.route(ibcd)
+            }));
+        } else {
+            self.routing_service = Some(make_routing_service());
+        }
     }
 }
 
@@ -69,6 +93,7 @@ impl Handler<NoLookupIncipientCoresPackage> for Hopper {
         msg: NoLookupIncipientCoresPackage,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
+        let _handling = handling("Hopper", "NoLookupIncipientCoresPackage");
         self.consuming_service
             .as_ref()
             .expect("Hopper unbound: no ConsumingService")
@@ -82,6 +107,7 @@ impl Handler<IncipientCoresPackage> for Hopper {
     type Result = ();
 
     fn handle(&mut self, msg: IncipientCoresPackage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Hopper", "IncipientCoresPackage");
         self.consuming_service
             .as_ref()
             .expect("Hopper unbound: no ConsumingService")
@@ -93,10 +119,15 @@ impl Handler<InboundClientData> for Hopper {
     type Result = ();
 
     fn handle(&mut self, msg: InboundClientData, _ctx: &mut Self::Context) -> Self::Result {
-        self.routing_service
-            .as_ref()
-            .expect("Hopper unbound: no RoutingService")
-            .route(msg);
+        let _handling = handling("Hopper", "InboundClientData");
+        match self.routing_pool.as_ref() {
+            Some(routing_pool) => routing_pool.route(msg),
+            None => self
+                .routing_service
+                .as_ref()
+                .expect("Hopper unbound: no RoutingService")
+                .route(msg),
+        }
     }
 }
 
@@ -106,9 +137,11 @@ impl Hopper {
             cryptde: config.cryptde,
             consuming_service: None,
             routing_service: None,
+            routing_pool: None,
             per_routing_service: config.per_routing_service,
             per_routing_byte: config.per_routing_byte,
             is_decentralized: config.is_decentralized,
+            routing_workers: config.routing_workers,
         }
     }
 
@@ -156,7 +189,7 @@ mod tests {
             )
             .unwrap();
         let live_package = LiveCoresPackage::new(route, data);
-        let live_data = PlainData::new(&serde_cbor::ser::to_vec(&live_package).unwrap()[..]);
+        let live_data = live_package.to_wire();
         let encrypted_package = cryptde
             .encode(&cryptde.public_key(), &live_data)
             .unwrap()
@@ -176,6 +209,7 @@ mod tests {
             per_routing_service: 100,
             per_routing_byte: 200,
             is_decentralized: false,
+            routing_workers: 1,
         });
         let subject_addr: Addr<Hopper> = subject.start();
 
@@ -214,6 +248,7 @@ mod tests {
             per_routing_service: 100,
             per_routing_byte: 200,
             is_decentralized: false,
+            routing_workers: 1,
         });
         let subject_addr: Addr<Hopper> = subject.start();
 
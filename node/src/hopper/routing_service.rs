@@ -1,8 +1,9 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 use super::live_cores_package::LiveCoresPackage;
+use super::wire_cores_package::WireCoresPackage;
 use crate::blockchain::payer::Payer;
 use crate::sub_lib::accountant::ReportRoutingServiceProvidedMessage;
-use crate::sub_lib::cryptde::{decodex, encodex, CryptDE, CryptData, CryptdecError};
+use crate::sub_lib::cryptde::{CryptDE, CryptData, CryptdecError};
 use crate::sub_lib::dispatcher::{Component, Endpoint, InboundClientData};
 use crate::sub_lib::hop::LiveHop;
 use crate::sub_lib::hopper::{ExpiredCoresPackage, HopperSubs, MessageType};
@@ -11,10 +12,11 @@ use crate::sub_lib::neighborhood::NeighborhoodSubs;
 use crate::sub_lib::proxy_client::ProxyClientSubs;
 use crate::sub_lib::proxy_server::ProxyServerSubs;
 use crate::sub_lib::stream_handler_pool::TransmitDataMsg;
+use crate::sub_lib::stream_tracing::{Span, Stage};
 use actix::Recipient;
-use std::borrow::Borrow;
 use std::net::SocketAddr;
 
+#[derive(Clone)]
 pub struct RoutingServiceSubs {
     pub proxy_client_subs: ProxyClientSubs,
     pub proxy_server_subs: ProxyServerSubs,
@@ -63,22 +65,22 @@ impl RoutingService {
         let last_data = ibcd.last_data;
         let ibcd_but_data = ibcd.clone_but_data();
 
-        let live_package =
-            match decodex::<LiveCoresPackage>(self.cryptde, &CryptData::new(&ibcd.data[..])) {
-                Ok(lcp) => lcp,
-                Err(e) => {
-                    error!(
-                        self.logger,
-                        "Couldn't decode CORES package in {}-byte buffer from {}: {}",
-                        ibcd.data.len(),
-                        ibcd.peer_addr,
-                        e
-                    );
-                    return;
-                }
-            };
+        let wire_package = match WireCoresPackage::decode(self.cryptde, &CryptData::from(ibcd.data))
+        {
+            Ok(wcp) => wcp,
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "Couldn't decode CORES package in {}-byte buffer from {}: {}",
+                    data_size,
+                    peer_addr,
+                    e
+                );
+                return;
+            }
+        };
 
-        let next_hop = match live_package.route.next_hop(self.cryptde.borrow()) {
+        let next_hop = match wire_package.next_hop(self.cryptde) {
             Ok(hop) => hop,
             Err(e) => {
                 error!(
@@ -89,14 +91,16 @@ impl RoutingService {
             }
         };
 
-        self.route_data(peer_addr, next_hop, live_package, last_data, &ibcd_but_data);
+        self.route_data(peer_addr, next_hop, wire_package, last_data, &ibcd_but_data);
     }
 
+    // A package just passing through stays in its wire form; only one that has arrived becomes a
+    // LiveCoresPackage
     fn route_data(
         &self,
         sender_addr: SocketAddr,
         next_hop: LiveHop,
-        live_package: LiveCoresPackage,
+        wire_package: WireCoresPackage,
         last_data: bool,
         ibcd_but_data: &InboundClientData,
     ) {
@@ -104,18 +108,23 @@ impl RoutingService {
             debug!(
                 self.logger,
                 "Routing LiveCoresPackage with {}-byte payload to {}",
-                live_package.payload.len(),
+                wire_package.payload().len(),
                 next_hop.public_key
             );
-            self.route_data_externally(live_package, next_hop.payer, last_data);
+            self.route_data_externally(wire_package, next_hop.payer, last_data);
         } else {
             debug!(
                 self.logger,
                 "Transferring LiveCoresPackage with {}-byte payload to {:?}",
-                live_package.payload.len(),
+                wire_package.payload().len(),
                 next_hop.component
             );
-            self.route_data_internally(&next_hop, sender_addr, live_package, ibcd_but_data)
+            self.route_data_internally(
+                &next_hop,
+                sender_addr,
+                wire_package.to_live(),
+                ibcd_but_data,
+            )
         }
     }
 
@@ -169,7 +178,8 @@ impl RoutingService {
                 return;
             }
         };
-        let payload = encodex(self.cryptde, &self.cryptde.public_key(), &next_lcp)
+        let payload = next_lcp
+            .encode(self.cryptde, &self.cryptde.public_key())
             .expect("Encryption of LiveCoresPackage failed");
         let inbound_client_data = InboundClientData {
             peer_addr: ibcd_but_data.peer_addr,
@@ -194,17 +204,16 @@ impl RoutingService {
         payer_owns_secret_key: bool,
     ) {
         let data_len = live_package.payload.len();
-        let expired_package =
-            match live_package.to_expired(immediate_neighbor_addr, self.cryptde.borrow()) {
-                Ok(pkg) => pkg,
-                Err(e) => {
-                    error!(
-                        self.logger,
-                        "Couldn't expire CORES package with {}-byte payload: {:?}", data_len, e
-                    );
-                    return;
-                }
-            };
+        let expired_package = match live_package.to_expired(immediate_neighbor_addr, self.cryptde) {
+            Ok(pkg) => pkg,
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "Couldn't expire CORES package with {}-byte payload: {:?}", data_len, e
+                );
+                return;
+            }
+        };
         trace!(
             self.logger,
             "Forwarding ExpiredCoresPackage to {:?}",
@@ -213,6 +222,11 @@ impl RoutingService {
         match (component, expired_package.payload) {
             (Component::ProxyClient, MessageType::ClientRequest(client_request)) => {
                 if !self.is_decentralized || payer_owns_secret_key {
+                    let span = Span::start(
+                        Stage::HopperRequest,
+                        &client_request.stream_key,
+                        client_request.sequenced_packet.sequence_number,
+                    );
                     self.routing_service_subs
                         .proxy_client_subs
                         .from_hopper
@@ -223,7 +237,8 @@ impl RoutingService {
                             client_request,
                             expired_package.payload_len,
                         ))
-                        .expect("ProxyClient is dead")
+                        .expect("ProxyClient is dead");
+                    span.end();
                 } else {
                     let payload_len = &expired_package.payload_len;
                     let address = match &expired_package.paying_wallet {
@@ -237,27 +252,46 @@ impl RoutingService {
                     );
                 }
             }
-            (Component::ProxyServer, MessageType::ClientResponse(client_reponse)) => self
+            (Component::ProxyServer, MessageType::ClientResponse(client_reponse)) => {
+                let span = Span::start(
+                    Stage::HopperResponse,
+                    &client_reponse.stream_key,
+                    client_reponse.sequenced_packet.sequence_number,
+                );
+                self.routing_service_subs
+                    .proxy_server_subs
+                    .from_hopper
+                    .try_send(ExpiredCoresPackage::new(
+                        expired_package.immediate_neighbor,
+                        expired_package.paying_wallet,
+                        expired_package.remaining_route,
+                        client_reponse,
+                        expired_package.payload_len,
+                    ))
+                    .expect("ProxyServer is dead");
+                span.end();
+            }
+            (Component::ProxyServer, MessageType::DnsResolveFailed(dns_resolve_failure)) => self
                 .routing_service_subs
                 .proxy_server_subs
-                .from_hopper
+                .dns_failure_from_hopper
                 .try_send(ExpiredCoresPackage::new(
                     expired_package.immediate_neighbor,
                     expired_package.paying_wallet,
                     expired_package.remaining_route,
-                    client_reponse,
+                    dns_resolve_failure,
                     expired_package.payload_len,
                 ))
                 .expect("ProxyServer is dead"),
-            (Component::ProxyServer, MessageType::DnsResolveFailed(dns_resolve_failure)) => self
+            (Component::ProxyServer, MessageType::BillingClaim(billing_claim)) => self
                 .routing_service_subs
                 .proxy_server_subs
-                .dns_failure_from_hopper
+                .billing_claim_from_hopper
                 .try_send(ExpiredCoresPackage::new(
                     expired_package.immediate_neighbor,
                     expired_package.paying_wallet,
                     expired_package.remaining_route,
-                    dns_resolve_failure,
+                    billing_claim,
                     expired_package.payload_len,
                 ))
                 .expect("ProxyServer is dead"),
@@ -282,11 +316,11 @@ impl RoutingService {
 
     fn route_data_externally(
         &self,
-        live_package: LiveCoresPackage,
+        wire_package: WireCoresPackage,
         payer: Option<Payer>,
         last_data: bool,
     ) {
-        let payload_size = live_package.payload.len();
+        let payload_size = wire_package.payload().len();
         match payer {
             Some(payer) => {
                 if !payer.owns_secret_key(&self.cryptde.digest()) {
@@ -328,7 +362,7 @@ impl RoutingService {
             }
         }
 
-        let transmit_msg = match self.to_transmit_data_msg(live_package, last_data) {
+        let transmit_msg = match self.to_transmit_data_msg(wire_package, last_data) {
             Ok(m) => m,
             Err(e) => {
                 error!(self.logger, "{:?}", e);
@@ -349,10 +383,10 @@ impl RoutingService {
 
     fn to_transmit_data_msg(
         &self,
-        live_package: LiveCoresPackage,
+        wire_package: WireCoresPackage,
         last_data: bool,
     ) -> Result<TransmitDataMsg, CryptdecError> {
-        let (next_hop, next_live_package) = match live_package.to_next_live(self.cryptde.borrow()) {
+        let (next_hop, next_wire_package) = match wire_package.to_next_wire(self.cryptde) {
             Err(e) => {
                 let msg = format!(
                     "Couldn't get next hop and outgoing LCP from incoming LCP: {:?}",
@@ -363,19 +397,21 @@ impl RoutingService {
             }
             Ok(p) => p,
         };
-        let next_live_package_enc =
-            match encodex(self.cryptde, &next_hop.public_key, &next_live_package) {
-                Ok(nlpe) => nlpe,
-                Err(e) => {
-                    let msg = format!("Couldn't serialize or encrypt outgoing LCP: {}", e);
-                    error!(self.logger, "{}", &msg);
-                    return Err(CryptdecError::OtherError(msg));
-                }
-            };
+        let next_wire_package_enc = match self
+            .cryptde
+            .encode(&next_hop.public_key, &next_wire_package)
+        {
+            Ok(nwpe) => nwpe,
+            Err(e) => {
+                let msg = format!("Couldn't encrypt outgoing LCP: {:?}", e);
+                error!(self.logger, "{}", &msg);
+                return Err(CryptdecError::OtherError(msg));
+            }
+        };
         Ok(TransmitDataMsg {
             endpoint: Endpoint::Key(next_hop.public_key),
             last_data,
-            data: next_live_package_enc.into(),
+            data: next_wire_package_enc.into(),
             sequence_number: None,
         })
     }
@@ -390,8 +426,11 @@ mod tests {
     use crate::sub_lib::accountant::ReportRoutingServiceProvidedMessage;
     use crate::sub_lib::cryptde::{encodex, PlainData, PublicKey};
     use crate::sub_lib::cryptde_null::CryptDENull;
+    use crate::sub_lib::data_version::DataVersion;
     use crate::sub_lib::hopper::{IncipientCoresPackage, MessageType, MessageType::ClientRequest};
-    use crate::sub_lib::proxy_client::{ClientResponsePayload, DnsResolveFailure};
+    use crate::sub_lib::proxy_client::{
+        BillingClaimPayload, ClientResponsePayload, DnsResolveFailure,
+    };
     use crate::sub_lib::proxy_server::ClientRequestPayload;
     use crate::sub_lib::route::{Route, RouteSegment};
     use crate::sub_lib::wallet::Wallet;
@@ -423,7 +462,7 @@ mod tests {
             )
             .unwrap(),
         );
-        let data_enc = encodex(cryptde, &cryptde.public_key(), &lcp).unwrap();
+        let data_enc = lcp.encode(cryptde, &cryptde.public_key()).unwrap();
         let inbound_client_data = InboundClientData {
             peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
             reception_port: None,
@@ -462,6 +501,59 @@ mod tests {
         assert_eq!(dns_resolve_failure, message.payload);
     }
 
+    #[test]
+    fn billing_claims_are_delivered_to_the_proxy_server() {
+        let cryptde = cryptde();
+        let route = route_to_proxy_server(&cryptde.public_key(), cryptde);
+        let billing_claim = BillingClaimPayload::new(1_234_567, true);
+        let lcp = LiveCoresPackage::new(
+            route,
+            encodex(
+                cryptde,
+                &cryptde.public_key(),
+                &MessageType::BillingClaim(billing_claim.clone()),
+            )
+            .unwrap(),
+        );
+        let data_enc = lcp.encode(cryptde, &cryptde.public_key()).unwrap();
+        let inbound_client_data = InboundClientData {
+            peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+            reception_port: None,
+            sequence_number: None,
+            last_data: false,
+            is_clandestine: false,
+            data: data_enc.into(),
+        };
+        let (proxy_server, proxy_server_awaiter, proxy_server_recording) = make_recorder();
+
+        let system = System::new("billing_claims_are_delivered_to_the_proxy_server");
+        let peer_actors = peer_actors_builder().proxy_server(proxy_server).build();
+        let subject = RoutingService::new(
+            cryptde,
+            RoutingServiceSubs {
+                proxy_client_subs: peer_actors.proxy_client,
+                proxy_server_subs: peer_actors.proxy_server,
+                neighborhood_subs: peer_actors.neighborhood,
+                hopper_subs: peer_actors.hopper,
+                to_dispatcher: peer_actors.dispatcher.from_dispatcher_client,
+                to_accountant_routing: peer_actors.accountant.report_routing_service_provided,
+            },
+            100,
+            200,
+            false,
+        );
+
+        subject.route(inbound_client_data);
+
+        System::current().stop();
+        system.run();
+
+        proxy_server_awaiter.await_message_count(1);
+        let recordings = proxy_server_recording.lock().unwrap();
+        let message = recordings.get_record::<ExpiredCoresPackage<BillingClaimPayload>>(0);
+        assert_eq!(billing_claim, message.payload);
+    }
+
     #[test]
     fn logs_and_ignores_message_that_cannot_be_decoded() {
         init_test_logging();
@@ -471,7 +563,7 @@ mod tests {
             route,
             encodex(cryptde, &cryptde.public_key(), &[42u8]).unwrap(),
         );
-        let data_enc = encodex(cryptde, &cryptde.public_key(), &lcp).unwrap();
+        let data_enc = lcp.encode(cryptde, &cryptde.public_key()).unwrap();
         let inbound_client_data = InboundClientData {
             peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
             reception_port: None,
@@ -517,7 +609,7 @@ mod tests {
             )
             .unwrap(),
         );
-        let data_enc = encodex(cryptde, &cryptde.public_key(), &lcp).unwrap();
+        let data_enc = lcp.encode(cryptde, &cryptde.public_key()).unwrap();
         let inbound_client_data = InboundClientData {
             peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
             reception_port: None,
@@ -560,7 +652,7 @@ mod tests {
                 .unwrap(),
         );
         let lcp_a = lcp.clone();
-        let data_ser = PlainData::new(&serde_cbor::ser::to_vec(&lcp).unwrap()[..]);
+        let data_ser = lcp.to_wire();
         let data_enc = cryptde.encode(&cryptde.public_key(), &data_ser).unwrap();
         let inbound_client_data = InboundClientData {
             peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
@@ -618,7 +710,7 @@ mod tests {
                 .unwrap(),
         );
         let lcp_a = lcp.clone();
-        let data_ser = PlainData::new(&serde_cbor::ser::to_vec(&lcp).unwrap()[..]);
+        let data_ser = lcp.to_wire();
         let data_enc = cryptde.encode(&cryptde.public_key(), &data_ser).unwrap();
         let inbound_client_data = InboundClientData {
             peer_addr: SocketAddr::from_str("1.3.2.4:5678").unwrap(),
@@ -687,7 +779,7 @@ mod tests {
                 .unwrap(),
         );
         let lcp_a = lcp.clone();
-        let data_enc = encodex(cryptde, &cryptde.public_key(), &lcp).unwrap();
+        let data_enc = lcp.encode(cryptde, &cryptde.public_key()).unwrap();
         let inbound_client_data = InboundClientData {
             peer_addr: SocketAddr::from_str("1.3.2.4:5678").unwrap(),
             reception_port: None,
@@ -754,7 +846,7 @@ mod tests {
         let payload = PlainData::new(&b"abcd"[..]);
         let lcp = LiveCoresPackage::new(route, cryptde.encode(&next_key, &payload).unwrap());
         let lcp_a = lcp.clone();
-        let data_ser = PlainData::new(&serde_cbor::ser::to_vec(&lcp).unwrap()[..]);
+        let data_ser = lcp.to_wire();
         let data_enc = cryptde.encode(&cryptde.public_key(), &data_ser).unwrap();
         let inbound_client_data = InboundClientData {
             peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
@@ -793,7 +885,7 @@ mod tests {
         let dispatcher_recording = dispatcher_recording_arc.lock().unwrap();
         let record = dispatcher_recording.get_record::<TransmitDataMsg>(0);
         let expected_lcp = lcp_a.to_next_live(cryptde).unwrap().1;
-        let expected_lcp_ser = PlainData::new(&serde_cbor::ser::to_vec(&expected_lcp).unwrap());
+        let expected_lcp_ser = expected_lcp.to_wire();
         let expected_lcp_enc = cryptde.encode(&next_key, &expected_lcp_ser).unwrap();
         assert_eq!(
             *record,
@@ -818,6 +910,98 @@ mod tests {
         )
     }
 
+    // What a Node relaying a package of the given version sends on, and what it should have sent
+    fn relay_package_of_version(version: DataVersion, test_name: &str) -> (PlainData, PlainData) {
+        BAN_CACHE.clear();
+        let cryptde = cryptde();
+        let (dispatcher, _, dispatcher_recording_arc) = make_recorder();
+        let next_key = PublicKey::new(&[65, 65, 65]);
+        let route = Route::one_way(
+            RouteSegment::new(
+                vec![&cryptde.public_key(), &next_key],
+                Component::Neighborhood,
+            ),
+            cryptde,
+            Some(make_paying_wallet(b"wallet")),
+            Some(contract_address(DEFAULT_CHAIN_ID)),
+        )
+        .unwrap();
+        let payload = cryptde
+            .encode(&next_key, &PlainData::new(&b"abcd"[..]))
+            .unwrap();
+        let lcp = LiveCoresPackage {
+            version,
+            ..LiveCoresPackage::new(route, payload)
+        };
+        let data_enc = cryptde
+            .encode(&cryptde.public_key(), &lcp.to_wire())
+            .unwrap();
+        let inbound_client_data = InboundClientData {
+            peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+            reception_port: None,
+            last_data: true,
+            is_clandestine: true,
+            sequence_number: None,
+            data: data_enc.into(),
+        };
+        let system = System::new(test_name);
+        let peer_actors = peer_actors_builder().dispatcher(dispatcher).build();
+        let subject = RoutingService::new(
+            cryptde,
+            RoutingServiceSubs {
+                proxy_client_subs: peer_actors.proxy_client,
+                proxy_server_subs: peer_actors.proxy_server,
+                neighborhood_subs: peer_actors.neighborhood,
+                hopper_subs: peer_actors.hopper,
+                to_dispatcher: peer_actors.dispatcher.from_dispatcher_client,
+                to_accountant_routing: peer_actors.accountant.report_routing_service_provided,
+            },
+            rate_pack_routing(103),
+            rate_pack_routing_byte(103),
+            false,
+        );
+
+        subject.route(inbound_client_data);
+
+        System::current().stop();
+        system.run();
+        let dispatcher_recording = dispatcher_recording_arc.lock().unwrap();
+        let record = dispatcher_recording.get_record::<TransmitDataMsg>(0);
+        let next_cryptde = CryptDENull::from(&next_key, DEFAULT_CHAIN_ID);
+        let outgoing = next_cryptde
+            .decode(&CryptData::new(&record.data[..]))
+            .unwrap();
+        let expected = lcp.to_next_live(cryptde).unwrap().1.to_wire();
+        (outgoing, expected)
+    }
+
+    #[test]
+    fn relays_version_0_packages_as_version_0() {
+        let _eg = EnvironmentGuard::new();
+
+        let (outgoing, expected) = relay_package_of_version(
+            LiveCoresPackage::version(),
+            "relays_version_0_packages_as_version_0",
+        );
+
+        assert_eq!(outgoing, expected);
+        let relayed = serde_cbor::de::from_slice::<LiveCoresPackage>(outgoing.as_slice()).unwrap();
+        assert_eq!(relayed.version, DataVersion::new(0, 0).unwrap());
+    }
+
+    #[test]
+    fn relays_version_1_packages_as_version_1() {
+        let _eg = EnvironmentGuard::new();
+
+        let (outgoing, expected) = relay_package_of_version(
+            WireCoresPackage::version(),
+            "relays_version_1_packages_as_version_1",
+        );
+
+        assert_eq!(outgoing, expected);
+        assert_eq!(&outgoing.as_slice()[..4], &[0, 1, 0, 0]);
+    }
+
     #[test]
     fn reprocesses_inbound_client_data_meant_for_this_node_and_destined_for_hopper() {
         let _eg = EnvironmentGuard::new();
@@ -841,7 +1025,7 @@ mod tests {
             cryptde.encode(&cryptde.public_key(), &payload).unwrap(),
         );
         let lcp_a = lcp.clone();
-        let data_enc = encodex(cryptde, &cryptde.public_key(), &lcp).unwrap();
+        let data_enc = lcp.encode(cryptde, &cryptde.public_key()).unwrap();
         let inbound_client_data = InboundClientData {
             peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
             reception_port: None,
@@ -878,7 +1062,7 @@ mod tests {
         let hopper_recording = hopper_recording_arc.lock().unwrap();
         let record = hopper_recording.get_record::<InboundClientData>(0);
         let expected_lcp = lcp_a.to_next_live(cryptde).unwrap().1;
-        let expected_lcp_enc = encodex(cryptde, &cryptde.public_key(), &expected_lcp).unwrap();
+        let expected_lcp_enc = expected_lcp.encode(cryptde, &cryptde.public_key()).unwrap();
         assert_eq!(
             *record,
             InboundClientData {
@@ -915,7 +1099,7 @@ mod tests {
         let icp =
             IncipientCoresPackage::new(&origin_cryptde, route, payload, &destination_key).unwrap();
         let (lcp, _) = LiveCoresPackage::from_incipient(icp, &origin_cryptde).unwrap();
-        let data_ser = PlainData::new(&serde_cbor::ser::to_vec(&lcp).unwrap()[..]);
+        let data_ser = lcp.to_wire();
         let data_enc = cryptde.encode(&cryptde.public_key(), &data_ser).unwrap();
         let inbound_client_data = InboundClientData {
             peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
@@ -1009,7 +1193,7 @@ mod tests {
 
         let icp = IncipientCoresPackage::new(cryptde, route, payload, &public_key).unwrap();
         let (lcp, _) = LiveCoresPackage::from_incipient(icp, cryptde).unwrap();
-        let data_ser = PlainData::new(&serde_cbor::ser::to_vec(&lcp).unwrap()[..]);
+        let data_ser = lcp.to_wire();
         let data_enc = cryptde.encode(&cryptde.public_key(), &data_ser).unwrap();
         let inbound_client_data = InboundClientData {
             peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
@@ -1139,7 +1323,7 @@ mod tests {
         );
 
         subject.route_data_externally(
-            lcp,
+            WireCoresPackage::parse(lcp.to_wire()).unwrap(),
             paying_wallet.map(|w| w.as_payer(&PublicKey::new(b"can't pay"), &contract_address)),
             true,
         );
@@ -1180,7 +1364,7 @@ mod tests {
         .unwrap();
         let payload = PlainData::new(&b"abcd"[..]);
         let lcp = LiveCoresPackage::new(route, cryptde.encode(&next_key, &payload).unwrap());
-        let data_enc = encodex(cryptde, &cryptde.public_key(), &lcp).unwrap();
+        let data_enc = lcp.encode(cryptde, &cryptde.public_key()).unwrap();
         let inbound_client_data = InboundClientData {
             peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
             reception_port: None,
@@ -1247,7 +1431,7 @@ mod tests {
             route,
             cryptde.encode(&cryptde.public_key(), &payload).unwrap(),
         );
-        let data_enc = encodex(cryptde, &cryptde.public_key(), &lcp).unwrap();
+        let data_enc = lcp.encode(cryptde, &cryptde.public_key()).unwrap();
         let inbound_client_data = InboundClientData {
             peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
             reception_port: None,
@@ -1343,7 +1527,7 @@ mod tests {
         init_test_logging();
         let cryptde = cryptde();
         let lcp = LiveCoresPackage::new(Route { hops: vec![] }, CryptData::new(&[]));
-        let data_ser = PlainData::new(&serde_cbor::ser::to_vec(&lcp).unwrap()[..]);
+        let data_ser = lcp.to_wire();
         let data_enc = cryptde.encode(&cryptde.public_key(), &data_ser).unwrap();
         let inbound_client_data = InboundClientData {
             peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
@@ -0,0 +1,158 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::sub_lib::crash_context::handling;
+use crate::sub_lib::dispatcher::InboundClientData;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::net::SocketAddr;
+use std::sync::mpsc;
+use std::sync::mpsc::Sender;
+use std::thread;
+
+// Routes InboundClientData on worker threads, so that decrypting and re-encrypting CORES packages
+// isn't limited to the Hopper's one core. Everything that arrives from a given peer goes to the
+// same worker, so the packages on a stream are dispatched in the order they arrived.
+pub struct RoutingWorkerPool {
+    workers: Vec<Sender<InboundClientData>>,
+}
+
+impl RoutingWorkerPool {
+    pub fn new<F, R>(size: usize, make_router: F) -> RoutingWorkerPool
+    where
+        F: Fn() -> R,
+        R: FnMut(InboundClientData) + Send + 'static,
+    {
+        let workers = (0..size.max(1))
+            .map(|index| {
+                let (tx, rx) = mpsc::channel::<InboundClientData>();
+                let mut router = make_router();
+                thread::Builder::new()
+                    .name(format!("Hopper worker {}", index))
+                    .spawn(move || {
+                        for ibcd in rx {
+                            let _handling = handling("Hopper", "InboundClientData");
+                            router(ibcd);
+                        }
+                    })
+                    .expect("Couldn't start Hopper worker");
+                tx
+            })
+            .collect();
+        RoutingWorkerPool { workers }
+    }
+
+    #[cfg(test)]
+    pub fn size(&self) -> usize {
+        self.workers.len()
+    }
+
+    pub fn route(&self, ibcd: InboundClientData) {
+        let index = self.worker_for(&ibcd.peer_addr);
+        self.workers[index]
+            .send(ibcd)
+            .expect("Hopper worker is dead");
+    }
+
+    fn worker_for(&self, peer_addr: &SocketAddr) -> usize {
+        let mut hasher = DefaultHasher::new();
+        peer_addr.hash(&mut hasher);
+        (hasher.finish() % self.workers.len() as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    fn make_ibcd(peer_addr: SocketAddr, data: Vec<u8>) -> InboundClientData {
+        InboundClientData {
+            peer_addr,
+            reception_port: None,
+            last_data: false,
+            is_clandestine: false,
+            sequence_number: None,
+            data,
+        }
+    }
+
+    fn wait_for_count(routed: &Arc<Mutex<Vec<(String, SocketAddr, u8)>>>, count: usize) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while routed.lock().unwrap().len() < count {
+            assert!(Instant::now() < deadline, "Workers didn't route everything");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn make_subject(size: usize) -> (RoutingWorkerPool, Arc<Mutex<Vec<(String, SocketAddr, u8)>>>) {
+        let routed = Arc::new(Mutex::new(vec![]));
+        let subject = RoutingWorkerPool::new(size, || {
+            let routed = routed.clone();
+            move |ibcd: InboundClientData| {
+                let worker = thread::current().name().unwrap().to_string();
+                routed
+                    .lock()
+                    .unwrap()
+                    .push((worker, ibcd.peer_addr, ibcd.data[0]))
+            }
+        });
+        (subject, routed)
+    }
+
+    #[test]
+    fn pool_has_at_least_one_worker() {
+        let (subject, _) = make_subject(0);
+
+        assert_eq!(subject.size(), 1);
+    }
+
+    #[test]
+    fn packages_from_one_peer_are_routed_by_one_worker_in_order() {
+        let (subject, routed) = make_subject(4);
+        let peers = (0..8)
+            .map(|port| SocketAddr::from_str(&format!("1.2.3.4:{}", 5000 + port)).unwrap())
+            .collect::<Vec<SocketAddr>>();
+
+        (0..50u8).for_each(|sequence| {
+            peers
+                .iter()
+                .for_each(|peer| subject.route(make_ibcd(*peer, vec![sequence])))
+        });
+
+        wait_for_count(&routed, 400);
+        let routed = routed.lock().unwrap();
+        peers.iter().for_each(|peer| {
+            let from_peer = routed
+                .iter()
+                .filter(|(_, addr, _)| addr == peer)
+                .collect::<Vec<_>>();
+            let sequences = from_peer.iter().map(|(_, _, s)| *s).collect::<Vec<u8>>();
+            assert_eq!(sequences, (0..50u8).collect::<Vec<u8>>());
+            assert!(from_peer
+                .iter()
+                .all(|(worker, _, _)| worker == &from_peer[0].0));
+        });
+    }
+
+    #[test]
+    fn packages_from_different_peers_are_spread_across_workers() {
+        let (subject, routed) = make_subject(4);
+
+        (0..64u16).for_each(|port| {
+            let peer = SocketAddr::from_str(&format!("1.2.3.4:{}", 6000 + port)).unwrap();
+            subject.route(make_ibcd(peer, vec![0]))
+        });
+
+        wait_for_count(&routed, 64);
+        let mut workers = routed
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(worker, _, _)| worker.clone())
+            .collect::<Vec<String>>();
+        workers.sort();
+        workers.dedup();
+        assert!(workers.len() > 1, "Only used {:?}", workers);
+    }
+}
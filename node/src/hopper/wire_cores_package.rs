@@ -0,0 +1,407 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use crate::hopper::live_cores_package::LiveCoresPackage;
+use crate::sub_lib::cryptde::{CryptDE, CryptData, PlainData};
+use crate::sub_lib::data_version::DataVersion;
+use crate::sub_lib::hop::LiveHop;
+use crate::sub_lib::route::{Route, RouteError};
+use bytes::Bytes;
+use std::iter;
+use std::ops::Range;
+
+const FIXED_HEADER_LEN: usize = 6;
+const LENGTH_LEN: usize = 4;
+
+// A decrypted LiveCoresPackage as it travels between Nodes. Version 1 is laid out as:
+//
+//   major version: u16 | minor version: u16 | hop count: u16
+//   one length per hop: u32 | payload length: u32
+//   the hops, back to back | the payload
+//
+// Numbers are big-endian. Parsing reads the header where it lies and keeps the hops and payload
+// as ranges of the buffer they arrived in, so a relaying Node copies the payload only once, into
+// the package it sends on.
+//
+// Version 0 packages are CBOR-encoded LiveCoresPackages. They're still read, and relayed as they
+// always were: decoded, shifted and encoded again, without going through the version 1 layout.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WireCoresPackage {
+    Cbor(LiveCoresPackage),
+    Layout {
+        buffer: Bytes,
+        version: DataVersion,
+        hops: Vec<Range<usize>>,
+        payload: Range<usize>,
+    },
+}
+
+impl WireCoresPackage {
+    pub fn version() -> DataVersion {
+        DataVersion::new(1, 0).expect("Internal Error")
+    }
+
+    pub fn parse(data: PlainData) -> Result<WireCoresPackage, String> {
+        match data.as_slice().first() {
+            // A version 1 header starts with the high byte of the major version; a version 0
+            // package starts with a CBOR map
+            Some(0) | None => Self::parse_layout(data),
+            Some(_) => Self::parse_cbor(data),
+        }
+    }
+
+    fn parse_cbor(data: PlainData) -> Result<WireCoresPackage, String> {
+        let live = serde_cbor::de::from_slice::<LiveCoresPackage>(data.as_slice())
+            .map_err(|e| format!("{}", e))?;
+        if live.version.major() != 0 {
+            return Err(format!(
+                "Unsupported CORES package version {}",
+                live.version
+            ));
+        }
+        Ok(WireCoresPackage::Cbor(live))
+    }
+
+    fn parse_layout(data: PlainData) -> Result<WireCoresPackage, String> {
+        let data: Vec<u8> = data.into();
+        let buffer = Bytes::from(data);
+        if buffer.len() < FIXED_HEADER_LEN {
+            return Err(format!(
+                "{}-byte CORES package is too short for a header",
+                buffer.len()
+            ));
+        }
+        let major = read_u16(&buffer, 0);
+        let version = DataVersion::new(major, read_u16(&buffer, 2))?;
+        if major != Self::version().major() {
+            return Err(format!("Unsupported CORES package version {}", version));
+        }
+        let hop_count = read_u16(&buffer, 4) as usize;
+        let header_len = FIXED_HEADER_LEN + (hop_count + 1) * LENGTH_LEN;
+        if buffer.len() < header_len {
+            return Err(format!(
+                "{}-byte CORES package is too short for the header of a {}-hop route",
+                buffer.len(),
+                hop_count
+            ));
+        }
+        let mut ranges = Vec::with_capacity(hop_count + 1);
+        let mut offset = header_len;
+        for index in 0..=hop_count {
+            let len = read_u32(&buffer, FIXED_HEADER_LEN + index * LENGTH_LEN) as usize;
+            let end = match offset.checked_add(len) {
+                Some(end) if end <= buffer.len() => end,
+                _ => return Err(format!("{}-byte CORES package is truncated", buffer.len())),
+            };
+            ranges.push(offset..end);
+            offset = end;
+        }
+        if offset != buffer.len() {
+            return Err(format!(
+                "CORES package has {} bytes of trailing data",
+                buffer.len() - offset
+            ));
+        }
+        let payload = ranges.pop().expect("Payload range disappeared");
+        Ok(WireCoresPackage::Layout {
+            buffer,
+            version,
+            hops: ranges,
+            payload,
+        })
+    }
+
+    pub fn decode(cryptde: &dyn CryptDE, data: &CryptData) -> Result<WireCoresPackage, String> {
+        let decrypted = match cryptde.decode(data) {
+            Ok(d) => d,
+            Err(e) => return Err(format!("Decryption error: {:?}", e)),
+        };
+        Self::parse(decrypted).map_err(|e| format!("Deserialization error: {}", e))
+    }
+
+    // In the version 1 layout; version 0 packages are written by LiveCoresPackage::to_wire()
+    pub fn write(version: DataVersion, hops: &[&[u8]], payload: &[u8]) -> PlainData {
+        let header_len = FIXED_HEADER_LEN + (hops.len() + 1) * LENGTH_LEN;
+        let body_len: usize = hops.iter().map(|hop| hop.len()).sum::<usize>() + payload.len();
+        let mut buffer = Vec::with_capacity(header_len + body_len);
+        buffer.extend_from_slice(&version.major().to_be_bytes());
+        buffer.extend_from_slice(&version.minor().to_be_bytes());
+        buffer.extend_from_slice(&(hops.len() as u16).to_be_bytes());
+        hops.iter()
+            .map(|hop| hop.len())
+            .chain(iter::once(payload.len()))
+            .for_each(|len| buffer.extend_from_slice(&(len as u32).to_be_bytes()));
+        hops.iter().for_each(|hop| buffer.extend_from_slice(hop));
+        buffer.extend_from_slice(payload);
+        PlainData::from(buffer)
+    }
+
+    pub fn data_version(&self) -> DataVersion {
+        match self {
+            WireCoresPackage::Cbor(live) => live.version,
+            WireCoresPackage::Layout { version, .. } => *version,
+        }
+    }
+
+    pub fn hop_count(&self) -> usize {
+        match self {
+            WireCoresPackage::Cbor(live) => live.route.hops.len(),
+            WireCoresPackage::Layout { hops, .. } => hops.len(),
+        }
+    }
+
+    pub fn hop(&self, index: usize) -> &[u8] {
+        match self {
+            WireCoresPackage::Cbor(live) => live.route.hops[index].as_slice(),
+            WireCoresPackage::Layout { buffer, hops, .. } => &buffer[hops[index].clone()],
+        }
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        match self {
+            WireCoresPackage::Cbor(live) => live.payload.as_slice(),
+            WireCoresPackage::Layout {
+                buffer, payload, ..
+            } => &buffer[payload.clone()],
+        }
+    }
+
+    pub fn next_hop(&self, cryptde: &dyn CryptDE) -> Result<LiveHop, RouteError> {
+        if self.hop_count() == 0 {
+            return Err(RouteError::EmptyRoute);
+        }
+        LiveHop::decode(cryptde, &CryptData::new(self.hop(0))).map_err(RouteError::HopDecodeProblem)
+    }
+
+    // The package to send on, in the version it arrived in, which the Node that sent it chose.
+    // A version 0 package is shifted and encoded as a LiveCoresPackage. A version 1 package comes
+    // out as LiveCoresPackage::to_next_live() followed by to_wire() would produce it, without
+    // building the LiveCoresPackage: the top hop is replaced by garbage at the bottom of the route.
+    pub fn to_next_wire(self, cryptde: &dyn CryptDE) -> Result<(LiveHop, PlainData), RouteError> {
+        if let WireCoresPackage::Cbor(live) = self {
+            let (next_hop, next_live) = live.to_next_live(cryptde)?;
+            return Ok((next_hop, next_live.to_wire()));
+        }
+        let next_hop = self.next_hop(cryptde)?;
+        let mut garbage: Vec<u8> = iter::repeat(0u8).take(self.hop(0).len()).collect();
+        cryptde.random(&mut garbage[..]);
+        let hops = (1..self.hop_count())
+            .map(|index| self.hop(index))
+            .chain(iter::once(&garbage[..]))
+            .collect::<Vec<&[u8]>>();
+        Ok((
+            next_hop,
+            Self::write(self.data_version(), &hops, self.payload()),
+        ))
+    }
+
+    pub fn to_live(self) -> LiveCoresPackage {
+        match self {
+            WireCoresPackage::Cbor(live) => live,
+            layout => LiveCoresPackage {
+                version: layout.data_version(),
+                route: Route {
+                    hops: (0..layout.hop_count())
+                        .map(|index| CryptData::new(layout.hop(index)))
+                        .collect(),
+                },
+                payload: CryptData::new(layout.payload()),
+            },
+        }
+    }
+}
+
+fn read_u16(buffer: &[u8], offset: usize) -> u16 {
+    (u16::from(buffer[offset]) << 8) | u16::from(buffer[offset + 1])
+}
+
+fn read_u32(buffer: &[u8], offset: usize) -> u32 {
+    (u32::from(read_u16(buffer, offset)) << 16) | u32::from(read_u16(buffer, offset + 2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::blockchain_interface::contract_address;
+    use crate::sub_lib::cryptde::PublicKey;
+    use crate::sub_lib::cryptde_null::CryptDENull;
+    use crate::sub_lib::dispatcher::Component;
+    use crate::sub_lib::route::RouteSegment;
+    use crate::test_utils::{cryptde, make_paying_wallet, DEFAULT_CHAIN_ID};
+
+    #[test]
+    fn write_lays_out_header_hops_and_payload() {
+        let result = WireCoresPackage::write(
+            DataVersion::new(1, 2).unwrap(),
+            &[&[10, 11], &[12]],
+            &[13, 14, 15],
+        );
+
+        assert_eq!(
+            result.as_slice(),
+            &[
+                0, 1, 0, 2, 0, 2, // version and hop count
+                0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 3, // lengths
+                10, 11, 12, 13, 14, 15, // hops and payload
+            ][..]
+        );
+    }
+
+    #[test]
+    fn parse_refers_to_hops_and_payload_in_place() {
+        let data =
+            WireCoresPackage::write(WireCoresPackage::version(), &[&[10, 11], &[12]], &[13, 14]);
+
+        let result = WireCoresPackage::parse(data).unwrap();
+
+        assert_eq!(result.data_version(), WireCoresPackage::version());
+        assert_eq!(result.hop_count(), 2);
+        assert_eq!(result.hop(0), &[10, 11]);
+        assert_eq!(result.hop(1), &[12]);
+        assert_eq!(result.payload(), &[13, 14]);
+        match result {
+            WireCoresPackage::Layout { ref buffer, .. } => {
+                assert_eq!(result.payload().as_ptr(), buffer[21..].as_ptr())
+            }
+            _ => panic!("Expected a version 1 layout, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn parse_rejects_malformed_packages() {
+        let good = WireCoresPackage::write(WireCoresPackage::version(), &[&[10, 11]], &[12]);
+        let check = |data: &[u8], expected: &str| {
+            assert_eq!(
+                WireCoresPackage::parse(PlainData::new(data)),
+                Err(expected.to_string())
+            )
+        };
+
+        check(&[0, 1, 0], "3-byte CORES package is too short for a header");
+        check(
+            &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            "Unsupported CORES package version 0.0",
+        );
+        check(
+            &good.as_slice()[..12],
+            "12-byte CORES package is too short for the header of a 1-hop route",
+        );
+        check(
+            &good.as_slice()[..good.len() - 1],
+            "16-byte CORES package is truncated",
+        );
+        check(
+            &[good.as_slice(), &[99]].concat(),
+            "CORES package has 1 bytes of trailing data",
+        );
+    }
+
+    #[test]
+    fn parse_reads_version_0_packages() {
+        let live = LiveCoresPackage::new(
+            Route {
+                hops: vec![CryptData::new(&[10, 11]), CryptData::new(&[12])],
+            },
+            CryptData::new(&[13, 14]),
+        );
+        let data = PlainData::from(serde_cbor::ser::to_vec(&live).unwrap());
+
+        let result = WireCoresPackage::parse(data.clone()).unwrap();
+
+        assert_eq!(result.data_version(), DataVersion::new(0, 0).unwrap());
+        assert_eq!(result.hop_count(), 2);
+        assert_eq!(result.hop(0), &[10, 11]);
+        assert_eq!(result.hop(1), &[12]);
+        assert_eq!(result.payload(), &[13, 14]);
+        assert_eq!(result.to_live(), live);
+        assert_eq!(live.to_wire(), data);
+    }
+
+    #[test]
+    fn parse_rejects_cbor_packages_of_later_versions() {
+        let live = LiveCoresPackage {
+            version: DataVersion::new(1, 0).unwrap(),
+            ..LiveCoresPackage::new(Route { hops: vec![] }, CryptData::new(&[13, 14]))
+        };
+        let data = PlainData::from(serde_cbor::ser::to_vec(&live).unwrap());
+
+        let result = WireCoresPackage::parse(data);
+
+        assert_eq!(
+            result,
+            Err("Unsupported CORES package version 1.0".to_string())
+        );
+    }
+
+    #[test]
+    fn to_next_wire_relays_a_package_in_the_version_it_arrived_in() {
+        let relay_key = PublicKey::new(&[1, 2]);
+        let relay_cryptde = CryptDENull::from(&relay_key, DEFAULT_CHAIN_ID);
+        let destination_key = PublicKey::new(&[3, 4]);
+        let route = Route::one_way(
+            RouteSegment::new(vec![&relay_key, &destination_key], Component::Neighborhood),
+            cryptde(),
+            Some(make_paying_wallet(b"wallet")),
+            Some(contract_address(DEFAULT_CHAIN_ID)),
+        )
+        .unwrap();
+        [LiveCoresPackage::version(), WireCoresPackage::version()]
+            .iter()
+            .for_each(|version| {
+                let live = LiveCoresPackage {
+                    version: *version,
+                    ..LiveCoresPackage::new(route.clone(), CryptData::new(&[5, 6, 7]))
+                };
+                let subject = WireCoresPackage::parse(live.to_wire()).unwrap();
+
+                let (_, next_wire) = subject.to_next_wire(&relay_cryptde).unwrap();
+
+                let (_, expected_live) = live.to_next_live(&relay_cryptde).unwrap();
+                assert_eq!(next_wire, expected_live.to_wire());
+                assert_eq!(
+                    WireCoresPackage::parse(next_wire).unwrap().data_version(),
+                    *version
+                );
+            });
+    }
+
+    #[test]
+    fn to_next_wire_matches_shifting_the_live_package() {
+        let relay_key = PublicKey::new(&[1, 2]);
+        let relay_cryptde = CryptDENull::from(&relay_key, DEFAULT_CHAIN_ID);
+        let destination_key = PublicKey::new(&[3, 4]);
+        let route = Route::one_way(
+            RouteSegment::new(vec![&relay_key, &destination_key], Component::Neighborhood),
+            cryptde(),
+            Some(make_paying_wallet(b"wallet")),
+            Some(contract_address(DEFAULT_CHAIN_ID)),
+        )
+        .unwrap();
+        let live = LiveCoresPackage::new(route, CryptData::new(&[5, 6, 7]));
+        let subject = WireCoresPackage::parse(live.to_wire()).unwrap();
+
+        let (next_hop, next_wire) = subject.to_next_wire(&relay_cryptde).unwrap();
+
+        let (expected_hop, expected_live) = live.to_next_live(&relay_cryptde).unwrap();
+        assert_eq!(next_hop, expected_hop);
+        assert_eq!(next_wire, expected_live.to_wire());
+    }
+
+    #[test]
+    fn next_hop_complains_about_an_empty_route() {
+        [LiveCoresPackage::version(), WireCoresPackage::version()]
+            .iter()
+            .for_each(|version| {
+                let live = LiveCoresPackage {
+                    version: *version,
+                    ..LiveCoresPackage::new(Route { hops: vec![] }, CryptData::new(&[1]))
+                };
+                let subject = WireCoresPackage::parse(live.to_wire()).unwrap();
+
+                assert_eq!(subject.next_hop(cryptde()), Err(RouteError::EmptyRoute));
+                assert_eq!(
+                    subject.to_next_wire(cryptde()).err(),
+                    Some(RouteError::EmptyRoute)
+                );
+            });
+    }
+}
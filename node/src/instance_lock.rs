@@ -0,0 +1,165 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+pub const LOCK_FILE: &str = "node.lock";
+
+// Keeps a second Node off a data directory that a running Node is using. The operating system
+// holds the lock for as long as the file is open, so it goes away with the Node however the Node
+// stops; the file stays behind, with the process ID of the last Node to hold it.
+#[derive(Debug)]
+pub struct InstanceLock {
+    // Never read; the lock lasts as long as the file stays open
+    _file: File,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum InstanceLockError {
+    // The process ID of the Node using the directory, if it could be read
+    AlreadyRunning(Option<u32>),
+    Unusable(PathBuf, String),
+}
+
+impl InstanceLock {
+    // On success, also returns the process ID of the last Node to use the directory, if any. It's
+    // not running any more, or the lock would have been refused.
+    pub fn acquire(
+        data_directory: &Path,
+    ) -> Result<(InstanceLock, Option<u32>), InstanceLockError> {
+        let path = data_directory.join(LOCK_FILE);
+        let unusable = |e: io::Error| InstanceLockError::Unusable(path.clone(), e.to_string());
+        fs::create_dir_all(data_directory).map_err(unusable)?;
+        let mut file = match open_exclusively(&path) {
+            Ok(file) => file,
+            Err(ref e) if is_held_elsewhere(e) => {
+                return Err(InstanceLockError::AlreadyRunning(read_pid(&path)))
+            }
+            Err(e) => return Err(unusable(e)),
+        };
+        let mut previous_contents = String::new();
+        let _ = file.read_to_string(&mut previous_contents);
+        let previous_pid = previous_contents.trim().parse::<u32>().ok();
+        file.set_len(0).map_err(unusable)?;
+        file.seek(SeekFrom::Start(0)).map_err(unusable)?;
+        write!(file, "{}", std::process::id()).map_err(unusable)?;
+        file.flush().map_err(unusable)?;
+        Ok((InstanceLock { _file: file }, previous_pid))
+    }
+}
+
+fn read_pid(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+#[cfg(not(target_os = "windows"))]
+fn open_exclusively(path: &Path) -> io::Result<File> {
+    use std::os::unix::io::AsRawFd;
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(path)?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(file)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn is_held_elsewhere(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::WouldBlock
+}
+
+// Others may read the process ID, but nobody else may open the file to write while we have it
+#[cfg(target_os = "windows")]
+fn open_exclusively(path: &Path) -> io::Result<File> {
+    use std::os::windows::fs::OpenOptionsExt;
+    const FILE_SHARE_READ: u32 = 0x0000_0001;
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .share_mode(FILE_SHARE_READ)
+        .open(path)
+}
+
+#[cfg(target_os = "windows")]
+fn is_held_elsewhere(e: &io::Error) -> bool {
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    e.raw_os_error() == Some(ERROR_SHARING_VIOLATION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::ensure_node_home_directory_exists;
+
+    #[test]
+    fn a_fresh_directory_is_locked_with_our_process_id() {
+        let home_dir = ensure_node_home_directory_exists(
+            "instance_lock",
+            "a_fresh_directory_is_locked_with_our_process_id",
+        );
+
+        let (_lock, previous_pid) = InstanceLock::acquire(&home_dir).unwrap();
+
+        assert_eq!(previous_pid, None);
+        assert_eq!(
+            fs::read_to_string(home_dir.join(LOCK_FILE)).unwrap(),
+            std::process::id().to_string()
+        );
+    }
+
+    #[test]
+    fn a_second_lock_is_refused_while_the_first_is_held() {
+        let home_dir = ensure_node_home_directory_exists(
+            "instance_lock",
+            "a_second_lock_is_refused_while_the_first_is_held",
+        );
+        let (_lock, _) = InstanceLock::acquire(&home_dir).unwrap();
+
+        let result = InstanceLock::acquire(&home_dir);
+
+        assert_eq!(
+            result.err(),
+            Some(InstanceLockError::AlreadyRunning(Some(std::process::id())))
+        );
+    }
+
+    #[test]
+    fn a_lock_left_behind_by_a_node_that_is_gone_is_taken_over() {
+        let home_dir = ensure_node_home_directory_exists(
+            "instance_lock",
+            "a_lock_left_behind_by_a_node_that_is_gone_is_taken_over",
+        );
+        fs::write(home_dir.join(LOCK_FILE), "123456789").unwrap();
+
+        let (_lock, previous_pid) = InstanceLock::acquire(&home_dir).unwrap();
+
+        assert_eq!(previous_pid, Some(123456789));
+        assert_eq!(
+            fs::read_to_string(home_dir.join(LOCK_FILE)).unwrap(),
+            std::process::id().to_string()
+        );
+    }
+
+    #[test]
+    fn the_lock_is_released_when_it_is_dropped() {
+        let home_dir = ensure_node_home_directory_exists(
+            "instance_lock",
+            "the_lock_is_released_when_it_is_dropped",
+        );
+        let (lock, _) = InstanceLock::acquire(&home_dir).unwrap();
+        drop(lock);
+
+        let result = InstanceLock::acquire(&home_dir);
+
+        assert_eq!(
+            result.map(|(_, pid)| pid).ok(),
+            Some(Some(std::process::id()))
+        );
+    }
+}
@@ -2,8 +2,15 @@
 use crate::sub_lib::framer::FramedChunk;
 use crate::sub_lib::framer::Framer;
 
+// No legitimate clandestine frame comes anywhere near this size. Anything bigger is garbage or
+// an attempt to make us buffer unbounded amounts of data from a peer, so we abandon it.
+pub const MAX_JSON_FRAME_SIZE: usize = 0x10_0000;
+
+// The scan state is kept between calls to take_frame, so that a frame arriving in many small
+// pieces is scanned once rather than from the beginning every time a piece arrives.
 #[derive(Default)]
 pub struct JsonFramer {
+    scan_offset: usize,
     possible_start: Option<usize>,
     possible_end: Option<usize>,
     data_so_far: Vec<u8>,
@@ -19,12 +26,18 @@ impl Framer for JsonFramer {
     }
 
     fn take_frame(&mut self) -> Option<FramedChunk> {
-        self.reset();
-        for i in 0..self.data_so_far.len() {
+        for i in self.scan_offset..self.data_so_far.len() {
             let byte = self.data_so_far[i];
             self.update_state(byte, i);
+            if let Some(start) = self.possible_start {
+                if (i + 1 - start) > MAX_JSON_FRAME_SIZE {
+                    self.reset();
+                    continue;
+                }
+            }
             if let Some(chunk) = self.check_data_chunk() {
                 self.data_so_far = Vec::from(&self.data_so_far[(i + 1)..]);
+                self.scan_offset = 0;
                 return Some(FramedChunk {
                     chunk,
                     last_chunk: true,
@@ -32,17 +45,18 @@ impl Framer for JsonFramer {
             }
         }
         match (self.possible_start, self.possible_end) {
-            (None, None) => {
-                self.data_so_far.clear();
-                None
-            }
             (Some(start), None) => {
                 self.data_so_far = Vec::from(&self.data_so_far[start..]);
                 self.possible_start = Some(0);
+                self.scan_offset = self.data_so_far.len();
+                None
+            }
+            _ => {
+                self.data_so_far.clear();
+                self.reset();
+                self.scan_offset = 0;
                 None
             }
-            // crashpoint - return none?
-            _ => panic!("Internal error framing JSON"),
         }
     }
 }
@@ -50,6 +64,7 @@ impl Framer for JsonFramer {
 impl JsonFramer {
     pub fn new() -> JsonFramer {
         JsonFramer {
+            scan_offset: 0,
             possible_start: None,
             possible_end: None,
             data_so_far: vec![],
@@ -233,4 +248,61 @@ mod tests {
 
         assert_eq!(result, None);
     }
+
+    #[test]
+    fn json_framer_abandons_oversized_frame_and_finds_the_next_one() {
+        let mut subject = JsonFramer::new();
+        let mut oversized = b"{\"bodyText\": \"".to_vec();
+        oversized.extend(vec![b'x'; MAX_JSON_FRAME_SIZE]);
+        oversized.extend(b"\"}");
+
+        subject.add_data(&oversized);
+        subject.add_data(b"{\"bodyText\": \"booga\"}");
+        let result = subject.take_frame();
+
+        assert_eq!(
+            result,
+            Some(FramedChunk {
+                chunk: b"{\"bodyText\": \"booga\"}".to_vec(),
+                last_chunk: true,
+            })
+        );
+        assert_eq!(subject.take_frame(), None);
+        assert_eq!(subject.data_so_far.len(), 0);
+    }
+
+    #[test]
+    fn json_framer_does_not_buffer_an_unterminated_frame_past_the_limit() {
+        let mut subject = JsonFramer::new();
+
+        subject.add_data(b"{\"bodyText\": \"");
+        (0..(MAX_JSON_FRAME_SIZE / 0x1_0000 + 1)).for_each(|_| {
+            subject.add_data(&[b'x'; 0x1_0000]);
+            assert_eq!(subject.take_frame(), None);
+            assert!(subject.data_so_far.len() <= MAX_JSON_FRAME_SIZE);
+        });
+    }
+
+    #[test]
+    fn json_framer_resumes_scanning_where_it_left_off() {
+        let mut subject = JsonFramer::new();
+
+        subject.add_data(b"garbage{\"bodyText\": \"}{");
+        assert_eq!(subject.take_frame(), None);
+        assert_eq!(subject.scan_offset, subject.data_so_far.len());
+        assert!(subject.in_double_quote_string);
+        subject.add_data(b"\\\"");
+        assert_eq!(subject.take_frame(), None);
+        assert_eq!(subject.scan_offset, subject.data_so_far.len());
+        subject.add_data(b"\"}trailing");
+        let result = subject.take_frame();
+
+        assert_eq!(
+            result.unwrap().chunk,
+            b"{\"bodyText\": \"}{\\\"\"}".to_vec()
+        );
+        assert_eq!(subject.take_frame(), None);
+        assert_eq!(subject.data_so_far.len(), 0);
+        assert_eq!(subject.scan_offset, 0);
+    }
 }
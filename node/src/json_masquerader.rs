@@ -1,14 +1,20 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 use crate::discriminator::UnmaskedChunk;
+use crate::json_framer::MAX_JSON_FRAME_SIZE;
 use crate::masquerader::MasqueradeError;
 use crate::masquerader::Masquerader;
 use crate::sub_lib::logger::Logger;
+use crate::sub_lib::obfuscation::{ObfuscationConfig, ObfuscationMetrics};
+use crate::sub_lib::random::{Random, RandomReal};
 use base64;
 use serde_derive::{Deserialize, Serialize};
 use serde_json;
+use std::sync::Arc;
 
 pub struct JsonMasquerader {
     logger: Logger,
+    padding_opt: Option<(ObfuscationConfig, Arc<ObfuscationMetrics>)>,
+    random: Box<dyn Random>,
 }
 
 impl Masquerader for JsonMasquerader {
@@ -23,13 +29,15 @@ impl Masquerader for JsonMasquerader {
     }
 
     fn mask(&self, data: &[u8]) -> Result<Vec<u8>, MasqueradeError> {
-        // crashpoint - return a MasqueradeError?
-        let json_string = match String::from_utf8(Vec::from(data)) {
-            Ok(string) => JsonMasquerader::make_text_structure(string),
-            Err(_) => JsonMasquerader::make_binary_structure(data),
+        let padding_opt = self.make_padding(data.len());
+        match String::from_utf8(Vec::from(data)) {
+            Ok(string) => JsonMasquerader::make_text_structure(string, padding_opt),
+            Err(_) => JsonMasquerader::make_binary_structure(data, padding_opt),
         }
-        .expect("Could not make json string");
-        Ok(json_string.into_bytes())
+        .map(|json_string| json_string.into_bytes())
+        .map_err(|e| {
+            MasqueradeError::HighLevelDataError(format!("Could not make JSON string: {}", e))
+        })
     }
 }
 
@@ -44,24 +52,73 @@ impl JsonMasquerader {
     pub fn new() -> JsonMasquerader {
         JsonMasquerader {
             logger: Logger::new("JsonMasquerader"),
+            padding_opt: None,
+            random: Box::new(RandomReal {}),
+        }
+    }
+
+    // Masked frames will carry a random amount of padding, which unmaskers ignore.
+    pub fn with_padding(
+        config: ObfuscationConfig,
+        metrics: Arc<ObfuscationMetrics>,
+    ) -> JsonMasquerader {
+        JsonMasquerader {
+            logger: Logger::new("JsonMasquerader"),
+            padding_opt: Some((config, metrics)),
+            random: Box::new(RandomReal {}),
+        }
+    }
+
+    pub fn with_random(mut self, random: Box<dyn Random>) -> JsonMasquerader {
+        self.random = random;
+        self
+    }
+
+    fn make_padding(&self, payload_len: usize) -> Option<String> {
+        match &self.padding_opt {
+            Some((config, metrics)) if config.max_padding > 0 => {
+                let padding = config.random_padding(self.random.as_ref());
+                metrics.record_padding(payload_len, padding.len());
+                Some(padding)
+            }
+            _ => None,
         }
     }
 
     fn unmask(&self, data: &[u8]) -> Result<UnmaskedChunk, MasqueradeError> {
+        if data.len() > MAX_JSON_FRAME_SIZE {
+            return Err(MasqueradeError::LowLevelDataError(format!(
+                "{}-byte frame exceeds the {}-byte limit",
+                data.len(),
+                MAX_JSON_FRAME_SIZE
+            )));
+        }
         let json_string = JsonMasquerader::string_from_data(data)?;
         let structure = JsonMasquerader::structure_from_string(json_string)?;
         let data_vector = JsonMasquerader::data_vector_from_structure(&structure)?;
         Ok(UnmaskedChunk::new(data_vector, true, false))
     }
 
-    fn make_text_structure(string: String) -> Result<String, serde_json::Error> {
-        let structure = JsonMasqueraderStringStructure { bodyText: string };
+    fn make_text_structure(
+        string: String,
+        padding: Option<String>,
+    ) -> Result<String, serde_json::Error> {
+        let structure = JsonMasqueraderStringStructure {
+            bodyText: string,
+            padding,
+        };
         serde_json::to_string(&structure)
     }
 
-    fn make_binary_structure(data: &[u8]) -> Result<String, serde_json::Error> {
+    fn make_binary_structure(
+        data: &[u8],
+        padding: Option<String>,
+    ) -> Result<String, serde_json::Error> {
         let base64 = base64::encode(data);
-        let structure = JsonMasqueraderDataStructure { bodyData: base64 };
+        let structure = JsonMasqueraderDataStructure {
+            bodyData: base64,
+            padding,
+        };
         serde_json::to_string(&structure)
     }
 
@@ -119,12 +176,16 @@ impl JsonMasquerader {
 #[allow(non_snake_case)]
 struct JsonMasqueraderStringStructure {
     bodyText: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    padding: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
 #[allow(non_snake_case)]
 struct JsonMasqueraderDataStructure {
     bodyData: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    padding: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -137,8 +198,12 @@ struct JsonMasqueraderUnmaskStructure {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::cores_package_strategies::{garbage, json_shaped_garbage};
     use crate::test_utils::logging::init_test_logging;
     use crate::test_utils::logging::TestLogHandler;
+    use crate::test_utils::random_seeded::RandomSeeded;
+    use proptest::prelude::*;
+    use std::time::Duration;
 
     #[test]
     fn json_masquerader_can_mask_and_unmask_bodytext() {
@@ -211,6 +276,97 @@ mod tests {
             serde_json::from_str(actual_json).unwrap();
         assert_eq!(actual_structure.bodyData, String::from("e8B9wQ=="));
     }
+    #[test]
+    fn json_masquerader_without_padding_adds_no_padding_field() {
+        let subject = JsonMasquerader::new();
+
+        let result = subject.mask(b"booga").unwrap();
+
+        assert_eq!(
+            String::from_utf8(result).unwrap(),
+            "{\"bodyText\":\"booga\"}".to_string()
+        );
+    }
+
+    #[test]
+    fn json_masquerader_with_padding_pads_and_unmasks_text_and_data() {
+        let metrics = Arc::new(ObfuscationMetrics::new());
+        let config = ObfuscationConfig {
+            max_jitter: Duration::from_millis(0),
+            max_padding: 50,
+        };
+        let subject = JsonMasquerader::with_padding(config, metrics.clone());
+        let unpadded_text_len = JsonMasquerader::new().mask(b"booga").unwrap().len();
+        let unpadded_data_len = JsonMasquerader::new()
+            .mask(&[0x7B, 0xC0, 0x7D, 0xC1])
+            .unwrap()
+            .len();
+
+        let padded_text = subject.mask(b"booga").unwrap();
+        let padded_data = subject.mask(&[0x7B, 0xC0, 0x7D, 0xC1]).unwrap();
+
+        assert_eq!(
+            subject.try_unmask(&padded_text).unwrap().chunk,
+            b"booga".to_vec()
+        );
+        assert_eq!(
+            subject.try_unmask(&padded_data).unwrap().chunk,
+            vec![0x7B, 0xC0, 0x7D, 0xC1]
+        );
+        assert_eq!(
+            JsonMasquerader::new()
+                .try_unmask(&padded_text)
+                .unwrap()
+                .chunk,
+            b"booga".to_vec()
+        );
+        let text_padding = padded_text.len() - unpadded_text_len;
+        let data_padding = padded_data.len() - unpadded_data_len;
+        let field_overhead = ",\"padding\":\"\"".len();
+        assert!(text_padding >= field_overhead && text_padding <= field_overhead + 50);
+        assert!(data_padding >= field_overhead && data_padding <= field_overhead + 50);
+        assert_eq!(metrics.frames(), 2);
+        assert_eq!(metrics.payload_bytes(), 9);
+        assert_eq!(
+            metrics.padding_bytes() as usize,
+            text_padding + data_padding - (2 * field_overhead)
+        );
+    }
+
+    #[test]
+    fn json_masquerader_pads_the_same_way_with_the_same_seed() {
+        let config = ObfuscationConfig {
+            max_jitter: Duration::from_millis(0),
+            max_padding: 50,
+        };
+        let make_subject = || {
+            JsonMasquerader::with_padding(config, Arc::new(ObfuscationMetrics::new()))
+                .with_random(Box::new(RandomSeeded::new(1234)))
+        };
+        let frames = |subject: JsonMasquerader| {
+            vec![
+                subject.mask(b"booga").unwrap(),
+                subject.mask(b"booga").unwrap(),
+            ]
+        };
+
+        let first_frames = frames(make_subject());
+        let second_frames = frames(make_subject());
+
+        assert_eq!(first_frames, second_frames);
+    }
+
+    proptest! {
+        #[test]
+        fn json_masquerader_refuses_garbage_without_panicking(
+            data in prop_oneof![garbage(), json_shaped_garbage()]
+        ) {
+            let result = JsonMasquerader::new().try_unmask(&data);
+
+            prop_assert!(result.is_err());
+        }
+    }
+
     #[test]
     fn json_masquerader_handles_json_that_terminates_prematurely() {
         let subject = JsonMasquerader::new();
@@ -276,4 +432,29 @@ mod tests {
         assert!(result.is_err(), "{:?}", result);
         TestLogHandler::new().exists_log_containing(msg_suffix);
     }
+
+    #[test]
+    fn json_masquerader_rejects_oversized_frames() {
+        init_test_logging();
+        let subject = JsonMasquerader::new();
+        let mut data = b"{\"bodyText\": \"".to_vec();
+        data.extend(vec![b'x'; MAX_JSON_FRAME_SIZE]);
+        data.extend(b"\"}");
+
+        let result = subject.try_unmask(&data);
+
+        let expected_message = format!(
+            "{}-byte frame exceeds the {}-byte limit",
+            data.len(),
+            MAX_JSON_FRAME_SIZE
+        );
+        assert_eq!(
+            result,
+            Err(MasqueradeError::LowLevelDataError(expected_message.clone()))
+        );
+        TestLogHandler::new().exists_log_containing(&format!(
+            "ERROR: JsonMasquerader: Low-level data error: {}",
+            expected_message
+        ));
+    }
 }
@@ -8,21 +8,26 @@ mod multi_config;
 
 pub mod accountant;
 mod actor_system_factory;
+pub mod audit_log;
 mod banned_dao;
 pub mod blockchain;
 mod bootstrapper;
 mod config_dao;
 mod crash_test_dummy;
+mod daemon;
 pub mod database;
 pub mod discriminator;
 mod dispatcher;
 pub mod entry_dns;
+mod firewall;
 pub mod hopper;
 pub mod http_request_start_finder;
+mod instance_lock;
 pub mod json_discriminator_factory;
 pub mod json_framer;
 pub mod json_masquerader;
 mod listener_handler;
+mod log_rotation;
 pub mod masquerader;
 pub mod neighborhood;
 pub mod node_configurator;
@@ -31,13 +36,17 @@ pub mod persistent_configuration;
 mod privilege_drop;
 mod proxy_client;
 pub mod proxy_server;
+pub mod raw_discriminator_factory;
+mod restart_keeper;
 pub mod run_modes;
 pub mod server_initializer;
+mod service;
 mod stream_handler_pool;
 mod stream_messages;
 mod stream_reader;
 mod stream_writer_sorted;
 mod stream_writer_unsorted;
+mod systemd;
 pub mod test_utils;
 pub mod tls_discriminator_factory;
 pub mod ui_gateway;
@@ -2,6 +2,7 @@
 use crate::bootstrapper::PortConfiguration;
 use crate::stream_messages::AddStreamMsg;
 use crate::sub_lib::logger::Logger;
+use crate::sub_lib::memory_guard::{MemoryGuard, MEMORY_GUARD};
 use crate::sub_lib::stream_connector::StreamConnector;
 use crate::sub_lib::stream_connector::StreamConnectorReal;
 use crate::sub_lib::tokio_wrappers::TokioListenerWrapper;
@@ -21,6 +22,11 @@ pub trait ListenerHandler: Send + Future {
         port: u16,
         port_configuration: PortConfiguration,
     ) -> io::Result<()>;
+    // Binds whatever free port the operating system offers, and returns it
+    fn bind_free_port_and_configuration(
+        &mut self,
+        port_configuration: PortConfiguration,
+    ) -> io::Result<u16>;
     fn bind_subs(&mut self, add_stream_sub: Recipient<AddStreamMsg>);
 }
 
@@ -34,6 +40,7 @@ pub struct ListenerHandlerReal {
     listener: Box<dyn TokioListenerWrapper>,
     add_stream_sub: Option<Recipient<AddStreamMsg>>,
     stream_connector: Box<dyn StreamConnector>,
+    memory_guard: &'static MemoryGuard,
     logger: Logger,
 }
 
@@ -44,17 +51,27 @@ impl ListenerHandler for ListenerHandlerReal {
         port_configuration: PortConfiguration,
     ) -> io::Result<()> {
         self.port = Some(port);
-        let is_clandestine = port_configuration.is_clandestine;
-        self.port_configuration = Some(port_configuration);
-        self.logger = Logger::new(&format!("ListenerHandler {}", port));
-        let ip_addr = IpAddr::V4(if is_clandestine {
+        let ip_addr = IpAddr::V4(if port_configuration.is_clandestine {
             Ipv4Addr::from(0)
         } else {
-            Ipv4Addr::LOCALHOST
+            port_configuration.loopback_ip
         });
+        self.port_configuration = Some(port_configuration);
+        self.logger = Logger::new(&format!("ListenerHandler {}", port));
         self.listener.bind(SocketAddr::new(ip_addr, port))
     }
 
+    fn bind_free_port_and_configuration(
+        &mut self,
+        port_configuration: PortConfiguration,
+    ) -> io::Result<u16> {
+        self.bind_port_and_configuration(0, port_configuration)?;
+        let port = self.listener.local_addr()?.port();
+        self.port = Some(port);
+        self.logger = Logger::new(&format!("ListenerHandler {}", port));
+        Ok(port)
+    }
+
     fn bind_subs(&mut self, add_stream_sub: Recipient<AddStreamMsg>) {
         self.add_stream_sub = Some(add_stream_sub);
     }
@@ -69,6 +86,14 @@ impl Future for ListenerHandlerReal {
             let result = self.listener.poll_accept();
             match result {
                 Ok(Async::Ready((stream, socket_addr))) => {
+                    if self.refuses_connections() {
+                        debug!(
+                            self.logger,
+                            "Refused clandestine connection from {}: this Node is short of memory",
+                            socket_addr
+                        );
+                        continue;
+                    }
                     let connection_info =
                         match self.stream_connector.split_stream(stream, &self.logger) {
                             Some(ci) => ci,
@@ -106,6 +131,16 @@ impl Future for ListenerHandlerReal {
 }
 
 impl ListenerHandlerReal {
+    fn refuses_connections(&self) -> bool {
+        let is_clandestine = self
+            .port_configuration
+            .as_ref()
+            .map_or(false, |port_configuration| {
+                port_configuration.is_clandestine
+            });
+        is_clandestine && self.memory_guard.refuses_clandestine_connections()
+    }
+
     fn new() -> ListenerHandlerReal {
         ListenerHandlerReal {
             port: None,
@@ -113,6 +148,7 @@ impl ListenerHandlerReal {
             listener: Box::new(TokioListenerWrapperReal::new()),
             add_stream_sub: None,
             stream_connector: Box::new(StreamConnectorReal {}),
+            memory_guard: &MEMORY_GUARD,
             logger: Logger::new("Uninitialized Listener"),
         }
     }
@@ -136,6 +172,8 @@ impl ListenerHandlerFactoryReal {
 mod tests {
     use super::*;
     use crate::node_test_utils::NullDiscriminatorFactory;
+    use crate::sub_lib::memory_guard::tests::make_memory_guard;
+    use crate::sub_lib::memory_guard::SheddingLevel;
     use crate::sub_lib::utils::localhost;
     use crate::test_utils::find_free_port;
     use crate::test_utils::little_tcp_server::LittleTcpServer;
@@ -151,6 +189,7 @@ mod tests {
     use std::cell::RefCell;
     use std::io::Error;
     use std::io::ErrorKind;
+    use std::io::Read;
     use std::net;
     use std::net::Shutdown;
     use std::net::TcpStream as StdTcpStream;
@@ -166,6 +205,7 @@ mod tests {
     struct TokioListenerWrapperMock {
         log: Arc<TestLog>,
         bind_results: Vec<io::Result<()>>,
+        local_addr_results: RefCell<Vec<io::Result<SocketAddr>>>,
         poll_accept_results: RefCell<Vec<io::Result<Async<(TcpStream, SocketAddr)>>>>,
     }
 
@@ -174,6 +214,7 @@ mod tests {
             TokioListenerWrapperMock {
                 log: Arc::new(TestLog::new()),
                 bind_results: vec![],
+                local_addr_results: RefCell::new(vec![]),
                 poll_accept_results: RefCell::new(vec![]),
             }
         }
@@ -185,6 +226,10 @@ mod tests {
             self.bind_results.remove(0)
         }
 
+        fn local_addr(&self) -> io::Result<SocketAddr> {
+            self.local_addr_results.borrow_mut().remove(0)
+        }
+
         fn poll_accept(&mut self) -> io::Result<Async<(TcpStream, SocketAddr)>> {
             self.poll_accept_results.borrow_mut().remove(0)
         }
@@ -196,6 +241,11 @@ mod tests {
             self
         }
 
+        pub fn local_addr_result(self, result: io::Result<SocketAddr>) -> TokioListenerWrapperMock {
+            self.local_addr_results.borrow_mut().push(result);
+            self
+        }
+
         pub fn poll_accept_results(
             self,
             result_vec: Vec<Result<Async<(TcpStream, SocketAddr)>, io::Error>>,
@@ -286,6 +336,45 @@ mod tests {
         assert!(!port_configuration.is_clandestine);
     }
 
+    #[test]
+    fn binds_non_clandestine_port_to_the_configured_loopback_address() {
+        let listener = TokioListenerWrapperMock::new().bind_result(Ok(()));
+        let listener_log = listener.log.clone();
+        let mut subject = ListenerHandlerReal::new();
+        subject.listener = Box::new(listener);
+
+        let result = subject.bind_port_and_configuration(
+            80,
+            PortConfiguration {
+                loopback_ip: Ipv4Addr::new(127, 1, 0, 42),
+                ..PortConfiguration::new(vec![], false)
+            },
+        );
+
+        assert_eq!(result.unwrap(), ());
+        assert_eq!(
+            listener_log.dump(),
+            vec!(format!("bind (V4(127.1.0.42:80))"))
+        );
+    }
+
+    #[test]
+    fn binds_a_free_port_and_reports_which_one_it_got() {
+        let listener = TokioListenerWrapperMock::new()
+            .bind_result(Ok(()))
+            .local_addr_result(Ok(SocketAddr::from_str("127.0.0.1:49321").unwrap()));
+        let listener_log = listener.log.clone();
+        let mut subject = ListenerHandlerReal::new();
+        subject.listener = Box::new(listener);
+
+        let result =
+            subject.bind_free_port_and_configuration(PortConfiguration::new(vec![], false));
+
+        assert_eq!(result.unwrap(), 49321);
+        assert_eq!(listener_log.dump(), vec!(format!("bind (V4(127.0.0.1:0))")));
+        assert_eq!(subject.port, Some(49321));
+    }
+
     #[test]
     fn handles_connection_errors() {
         init_test_logging();
@@ -374,6 +463,50 @@ mod tests {
         assert_eq!(recording.len(), 0);
     }
 
+    #[test]
+    fn refuses_clandestine_connections_under_memory_pressure() {
+        init_test_logging();
+        let (stream_handler_pool, _, recording_arc) = make_recorder();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let system = System::new("refuses_clandestine_connections_under_memory_pressure");
+            let add_stream_sub = start_recorder(stream_handler_pool);
+            tx.send(add_stream_sub).expect("Internal Error");
+            system.run();
+        });
+        let port = find_free_port();
+        thread::spawn(move || {
+            let add_stream_sub = rx.recv().unwrap();
+            let mut subject = ListenerHandlerReal::new();
+            subject.memory_guard = make_memory_guard(SheddingLevel::RefuseClandestineConnections);
+            subject.bind_subs(add_stream_sub);
+            subject
+                .bind_port_and_configuration(port, PortConfiguration::new(vec![], true))
+                .unwrap();
+            tokio::run(subject)
+        });
+        thread::sleep(Duration::from_millis(500));
+        let mut stream = net::TcpStream::connect(SocketAddr::new(localhost(), port)).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_millis(1000)))
+            .unwrap();
+        let mut buf = [0u8; 16];
+
+        let result = stream.read(&mut buf);
+
+        assert_eq!(result.unwrap(), 0);
+        TestLogHandler::new().await_log_containing(
+            &format!(
+                "DEBUG: ListenerHandler {}: Refused clandestine connection from {}: this Node is short of memory",
+                port,
+                stream.local_addr().unwrap()
+            ),
+            1000,
+        );
+        let recording = recording_arc.lock().unwrap();
+        assert_eq!(recording.len(), 0);
+    }
+
     #[test]
     fn converts_connections_into_connection_infos() {
         let (stream_handler_pool, awaiter, recording_arc) = make_recorder();
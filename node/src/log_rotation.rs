@@ -0,0 +1,430 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use flexi_logger::writers::LogWriter;
+use flexi_logger::{DeferredNow, FormatFunction, LevelFilter, Record};
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+pub const DEFAULT_ROTATE_SIZE_MB: u64 = 100;
+pub const DEFAULT_KEEP_FILES: usize = 50;
+
+const ROTATED_SUFFIXES: [&str; 3] = [".log", ".log.gz", ".zip"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogRotation {
+    pub max_size: u64,
+    pub max_age_opt: Option<Duration>,
+    pub keep_files: usize,
+    pub keep_age_opt: Option<Duration>,
+    pub gzip: bool,
+}
+
+impl Default for LogRotation {
+    fn default() -> Self {
+        LogRotation {
+            max_size: DEFAULT_ROTATE_SIZE_MB * 1_000_000,
+            max_age_opt: None,
+            keep_files: DEFAULT_KEEP_FILES,
+            keep_age_opt: None,
+            gzip: true,
+        }
+    }
+}
+
+// Writes the log to <stem>_rCURRENT.log, where flexi_logger would have, and moves it aside to
+// <stem>_rNNNNN.log (or .log.gz) when it gets too big or too old.
+pub struct RotatingFileWriter {
+    files: LogFiles,
+    format: FormatFunction,
+    current: Mutex<Option<CurrentFile>>,
+}
+
+impl LogWriter for RotatingFileWriter {
+    fn write(&self, now: &mut DeferredNow, record: &Record) -> io::Result<()> {
+        let mut line: Vec<u8> = vec![];
+        (self.format)(&mut line, now, record)?;
+        line.push(b'\n');
+        self.write_line(&line, SystemTime::now())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        match self.current.lock().expect("Log file is poisoned").as_mut() {
+            Some(current) => current.file.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn max_log_level(&self) -> LevelFilter {
+        // The log specification does the filtering
+        LevelFilter::Trace
+    }
+}
+
+impl RotatingFileWriter {
+    pub fn new(
+        directory: &Path,
+        instance_name_opt: Option<&str>,
+        rotation: LogRotation,
+        format: FormatFunction,
+    ) -> io::Result<RotatingFileWriter> {
+        let files = LogFiles {
+            directory: directory.to_path_buf(),
+            stem: match instance_name_opt {
+                Some(instance_name) => format!("PrometheusNode_{}", instance_name),
+                None => "PrometheusNode".to_string(),
+            },
+            rotation,
+        };
+        fs::create_dir_all(directory)?;
+        let now = SystemTime::now();
+        // Like flexi_logger, start every run with a fresh file
+        if fs::metadata(files.current_path())
+            .map(|metadata| metadata.len() > 0)
+            .unwrap_or(false)
+        {
+            files.rotate(now)?;
+        }
+        let current = files.open_current(now)?;
+        Ok(RotatingFileWriter {
+            files,
+            format,
+            current: Mutex::new(Some(current)),
+        })
+    }
+
+    pub fn current_path(&self) -> PathBuf {
+        self.files.current_path()
+    }
+
+    fn write_line(&self, line: &[u8], now: SystemTime) -> io::Result<()> {
+        let mut guard = self.current.lock().expect("Log file is poisoned");
+        let due = match guard.as_ref() {
+            Some(current) => self.files.is_due(current, line.len() as u64, now),
+            None => false,
+        };
+        if due {
+            // Closing the file first lets it be renamed on every platform
+            *guard = None;
+            self.files.rotate(now)?;
+        }
+        if guard.is_none() {
+            *guard = Some(self.files.open_current(now)?);
+        }
+        let current = guard.as_mut().expect("Log file disappeared");
+        current.file.write_all(line)?;
+        current.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+struct CurrentFile {
+    file: File,
+    size: u64,
+    opened_at: SystemTime,
+}
+
+struct LogFiles {
+    directory: PathBuf,
+    stem: String,
+    rotation: LogRotation,
+}
+
+impl LogFiles {
+    fn current_path(&self) -> PathBuf {
+        self.directory.join(format!("{}_rCURRENT.log", self.stem))
+    }
+
+    fn open_current(&self, now: SystemTime) -> io::Result<CurrentFile> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.current_path())?;
+        let size = file.metadata()?.len();
+        Ok(CurrentFile {
+            file,
+            size,
+            opened_at: now,
+        })
+    }
+
+    fn is_due(&self, current: &CurrentFile, line_size: u64, now: SystemTime) -> bool {
+        let too_big = current.size > 0 && current.size + line_size > self.rotation.max_size;
+        let too_old = match self.rotation.max_age_opt {
+            Some(max_age) => match now.duration_since(current.opened_at) {
+                Ok(age) => age >= max_age,
+                Err(_) => false,
+            },
+            None => false,
+        };
+        too_big || too_old
+    }
+
+    fn rotate(&self, now: SystemTime) -> io::Result<()> {
+        let index = match self.rotated().first() {
+            Some((index, _)) => index + 1,
+            None => 0,
+        };
+        let rotated_path = self
+            .directory
+            .join(format!("{}_r{:05}.log", self.stem, index));
+        fs::rename(self.current_path(), &rotated_path)?;
+        if self.rotation.gzip {
+            gzip(&rotated_path)?;
+        }
+        self.prune(now);
+        Ok(())
+    }
+
+    // Cleanup is best-effort: a file we can't delete now will get another chance next rotation
+    fn prune(&self, now: SystemTime) {
+        self.rotated()
+            .into_iter()
+            .enumerate()
+            .filter(|(position, (_, path))| {
+                let too_many = *position >= self.rotation.keep_files;
+                let too_old = match self.rotation.keep_age_opt {
+                    Some(keep_age) => fs::metadata(path)
+                        .and_then(|metadata| metadata.modified())
+                        .map(|modified| modified + keep_age < now)
+                        .unwrap_or(false),
+                    None => false,
+                };
+                too_many || too_old
+            })
+            .for_each(|(_, (_, path))| {
+                let _ = fs::remove_file(path);
+            });
+    }
+
+    // Newest (highest-numbered) first
+    fn rotated(&self) -> Vec<(u32, PathBuf)> {
+        let prefix = format!("{}_r", self.stem);
+        let mut rotated: Vec<(u32, PathBuf)> = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries
+                .flatten()
+                .filter_map(|entry| {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    if !name.starts_with(&prefix) {
+                        return None;
+                    }
+                    let rest = &name[prefix.len()..];
+                    let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+                    if digits == 0 || !ROTATED_SUFFIXES.contains(&&rest[digits..]) {
+                        return None;
+                    }
+                    match rest[..digits].parse::<u32>() {
+                        Ok(index) => Some((index, entry.path())),
+                        Err(_) => None,
+                    }
+                })
+                .collect(),
+            Err(_) => vec![],
+        };
+        rotated.sort_by(|a, b| b.cmp(a));
+        rotated
+    }
+}
+
+fn gzip(path: &Path) -> io::Result<()> {
+    let mut gz_name = path.as_os_str().to_os_string();
+    gz_name.push(".gz");
+    let mut encoder = GzEncoder::new(File::create(&gz_name)?, Compression::default());
+    io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?;
+    fs::remove_file(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::ensure_node_home_directory_exists;
+    use flate2::read::GzDecoder;
+    use flexi_logger::default_format;
+    use std::io::Read;
+
+    fn rotation(max_size: u64) -> LogRotation {
+        LogRotation {
+            max_size,
+            max_age_opt: None,
+            keep_files: 10,
+            keep_age_opt: None,
+            gzip: false,
+        }
+    }
+
+    fn file_names(directory: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn rotates_when_the_next_line_would_make_the_file_too_big() {
+        let home_dir = ensure_node_home_directory_exists(
+            "log_rotation",
+            "rotates_when_the_next_line_would_make_the_file_too_big",
+        );
+        let subject =
+            RotatingFileWriter::new(&home_dir, None, rotation(10), default_format).unwrap();
+        let now = SystemTime::now();
+
+        subject.write_line(b"first\n", now).unwrap();
+        subject.write_line(b"sec\n", now).unwrap();
+        subject.write_line(b"third\n", now).unwrap();
+        subject.flush().unwrap();
+
+        assert_eq!(
+            file_names(&home_dir),
+            vec![
+                "PrometheusNode_r00000.log".to_string(),
+                "PrometheusNode_rCURRENT.log".to_string()
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(home_dir.join("PrometheusNode_r00000.log")).unwrap(),
+            "first\nsec\n"
+        );
+        assert_eq!(
+            fs::read_to_string(subject.current_path()).unwrap(),
+            "third\n"
+        );
+    }
+
+    #[test]
+    fn rotates_when_the_file_gets_too_old() {
+        let home_dir =
+            ensure_node_home_directory_exists("log_rotation", "rotates_when_the_file_gets_too_old");
+        let mut config = rotation(1_000_000);
+        config.max_age_opt = Some(Duration::from_secs(3600));
+        let subject =
+            RotatingFileWriter::new(&home_dir, Some("alice"), config, default_format).unwrap();
+        let now = SystemTime::now();
+
+        subject.write_line(b"early\n", now).unwrap();
+        subject
+            .write_line(b"still early\n", now + Duration::from_secs(3599))
+            .unwrap();
+        subject
+            .write_line(b"late\n", now + Duration::from_secs(3600))
+            .unwrap();
+        subject.flush().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(home_dir.join("PrometheusNode_alice_r00000.log")).unwrap(),
+            "early\nstill early\n"
+        );
+        assert_eq!(
+            fs::read_to_string(home_dir.join("PrometheusNode_alice_rCURRENT.log")).unwrap(),
+            "late\n"
+        );
+    }
+
+    #[test]
+    fn gzips_rotated_files_when_asked() {
+        let home_dir =
+            ensure_node_home_directory_exists("log_rotation", "gzips_rotated_files_when_asked");
+        let mut config = rotation(10);
+        config.gzip = true;
+        let subject = RotatingFileWriter::new(&home_dir, None, config, default_format).unwrap();
+        let now = SystemTime::now();
+
+        subject.write_line(b"squeeze me\n", now).unwrap();
+        subject.write_line(b"not me\n", now).unwrap();
+
+        assert_eq!(
+            file_names(&home_dir),
+            vec![
+                "PrometheusNode_r00000.log.gz".to_string(),
+                "PrometheusNode_rCURRENT.log".to_string()
+            ]
+        );
+        let mut decoder =
+            GzDecoder::new(File::open(home_dir.join("PrometheusNode_r00000.log.gz")).unwrap());
+        let mut contents = String::new();
+        decoder.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "squeeze me\n");
+    }
+
+    #[test]
+    fn keeps_only_the_newest_rotated_files() {
+        let home_dir = ensure_node_home_directory_exists(
+            "log_rotation",
+            "keeps_only_the_newest_rotated_files",
+        );
+        fs::write(
+            home_dir.join("PrometheusNode_r00003.zip"),
+            b"left by flexi_logger",
+        )
+        .unwrap();
+        let mut config = rotation(1);
+        config.keep_files = 2;
+        let subject = RotatingFileWriter::new(&home_dir, None, config, default_format).unwrap();
+        let now = SystemTime::now();
+
+        vec!["a\n", "b\n", "c\n", "d\n"]
+            .into_iter()
+            .for_each(|line| subject.write_line(line.as_bytes(), now).unwrap());
+
+        assert_eq!(
+            file_names(&home_dir),
+            vec![
+                "PrometheusNode_r00005.log".to_string(),
+                "PrometheusNode_r00006.log".to_string(),
+                "PrometheusNode_rCURRENT.log".to_string()
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(home_dir.join("PrometheusNode_r00006.log")).unwrap(),
+            "c\n"
+        );
+    }
+
+    #[test]
+    fn deletes_rotated_files_that_are_too_old() {
+        let home_dir = ensure_node_home_directory_exists(
+            "log_rotation",
+            "deletes_rotated_files_that_are_too_old",
+        );
+        fs::write(home_dir.join("PrometheusNode_r00000.log"), b"ancient").unwrap();
+        let mut config = rotation(1);
+        config.keep_age_opt = Some(Duration::from_secs(86400));
+        let subject = RotatingFileWriter::new(&home_dir, None, config, default_format).unwrap();
+        let two_days_later = SystemTime::now() + Duration::from_secs(2 * 86400);
+
+        subject.write_line(b"a\n", two_days_later).unwrap();
+        subject.write_line(b"b\n", two_days_later).unwrap();
+
+        assert_eq!(
+            file_names(&home_dir),
+            vec!["PrometheusNode_rCURRENT.log".to_string()]
+        );
+    }
+
+    #[test]
+    fn starts_a_fresh_file_and_keeps_the_last_one() {
+        let home_dir = ensure_node_home_directory_exists(
+            "log_rotation",
+            "starts_a_fresh_file_and_keeps_the_last_one",
+        );
+        fs::write(home_dir.join("PrometheusNode_rCURRENT.log"), b"last run\n").unwrap();
+        fs::write(home_dir.join("PrometheusNode_r00001.log"), b"run before\n").unwrap();
+
+        let subject =
+            RotatingFileWriter::new(&home_dir, None, rotation(1_000_000), default_format).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(home_dir.join("PrometheusNode_r00002.log")).unwrap(),
+            "last run\n"
+        );
+        assert_eq!(fs::read_to_string(subject.current_path()).unwrap(), "");
+    }
+}
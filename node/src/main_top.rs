@@ -0,0 +1,17 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use node_lib::sub_lib::main_tools::StdStreams;
+use node_lib::ui_gateway::dashboard;
+use std::io;
+
+pub fn main() {
+    let mut streams: StdStreams<'_> = StdStreams {
+        stdin: &mut io::stdin(),
+        stdout: &mut io::stdout(),
+        stderr: &mut io::stderr(),
+    };
+
+    let args = &std::env::args().collect();
+
+    let exit_code = dashboard::go(args, &mut streams);
+    ::std::process::exit(exit_code);
+}
@@ -2,7 +2,7 @@
 
 use crate::sub_lib::logger::Logger;
 use clap::{App, ArgMatches};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::fmt::Debug;
 use std::fs::File;
 use std::io::{ErrorKind, Read};
@@ -51,23 +51,30 @@ impl<'a> MultiConfig<'a> {
     /// VirtualCommandLine objects placed later in the list will override values found in
     /// VirtualCommandLine objects placed earlier.
     pub fn new(schema: &App<'a, 'a>, vcls: Vec<Box<dyn VirtualCommandLine>>) -> MultiConfig<'a> {
+        Self::try_new(schema, vcls).unwrap_or_else(|e| {
+            if cfg!(test) {
+                panic!("{:?}. --panic to catch for testing--", e)
+            } else {
+                e.exit()
+            }
+        })
+    }
+
+    /// Like new(), but hands back clap's complaint instead of exiting, for callers that want to
+    /// report it along with other problems.
+    pub fn try_new(
+        schema: &App<'a, 'a>,
+        vcls: Vec<Box<dyn VirtualCommandLine>>,
+    ) -> Result<MultiConfig<'a>, clap::Error> {
         let initial: Box<dyn VirtualCommandLine> =
             Box::new(CommandLineVcl::new(vec![String::new()]));
         let merged = vcls
             .into_iter()
             .fold(initial, |so_far, vcl| merge(so_far, vcl));
-        MultiConfig {
-            arg_matches: schema
-                .clone()
-                .get_matches_from_safe(merged.args().into_iter())
-                .unwrap_or_else(|e| {
-                    if cfg!(test) {
-                        panic!("{:?}. --panic to catch for testing--", e)
-                    } else {
-                        e.exit()
-                    }
-                }),
-        }
+        let arg_matches = schema
+            .clone()
+            .get_matches_from_safe(merged.args().into_iter())?;
+        Ok(MultiConfig { arg_matches })
     }
 
     pub fn arg_matches(&'a self) -> &ArgMatches<'a> {
@@ -253,21 +260,39 @@ impl EnvironmentVcl {
             .iter()
             .map(|opt| opt.b.name.to_string())
             .collect();
+        let flag_names: HashSet<String> = schema
+            .p
+            .flags
+            .iter()
+            .map(|flag| flag.b.name.to_string())
+            .filter(|name| name != "help" && name != "version")
+            .collect();
         let mut vcl_args: Vec<Box<dyn VclArg>> = vec![];
         for (upper_name, value) in std::env::vars() {
             if (upper_name.len() < 4) || (&upper_name[0..4] != "SUB_") {
                 continue;
             }
             let lower_name = str::replace(&upper_name[4..].to_lowercase(), "_", "-");
+            let name = format!("--{}", lower_name);
             if opt_names.contains(&lower_name) {
-                let name = format!("--{}", lower_name);
                 vcl_args.push(Box::new(NameValueVclArg::new(&name, &value)));
+            } else if flag_names.contains(&lower_name) && flag_is_set(&value) {
+                vcl_args.push(Box::new(NameOnlyVclArg::new(&name)));
             }
         }
         EnvironmentVcl { vcl_args }
     }
 }
 
+// A flag has no value on the command line, so in the environment any value turns it on except one
+// that plainly means off
+fn flag_is_set(value: &str) -> bool {
+    match value.trim().to_lowercase().as_str() {
+        "" | "0" | "false" | "no" | "off" => false,
+        _ => true,
+    }
+}
+
 pub struct ConfigFileVcl {
     vcl_args: Vec<Box<dyn VclArg>>,
 }
@@ -284,60 +309,81 @@ impl VirtualCommandLine for ConfigFileVcl {
 
 impl ConfigFileVcl {
     pub fn new(file_path: &PathBuf, user_specified: bool) -> ConfigFileVcl {
+        match Self::try_new(file_path, user_specified) {
+            Ok(vcl) => vcl,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Like new(), but hands back the complaint instead of panicking, for a Node that's already
+    /// running and reads the file again.
+    pub fn try_new(file_path: &PathBuf, user_specified: bool) -> Result<ConfigFileVcl, String> {
         let logger = Logger::new("Bootstrapper");
         let mut file: File = match File::open(file_path) {
             Err(e) => {
                 if user_specified {
-                    panic!(
+                    return Err(format!(
                         "Configuration file at {:?} could not be read: {}",
                         file_path, e
-                    )
+                    ));
                 } else {
                     info!(
                         logger,
                         "No configuration file was found at {} - skipping",
                         file_path.display()
                     );
-                    return ConfigFileVcl { vcl_args: vec![] };
+                    return Ok(ConfigFileVcl { vcl_args: vec![] });
                 }
             }
             Ok(file) => file,
         };
         let mut contents = String::new();
         match file.read_to_string(&mut contents) {
-            Err(ref e) if e.kind() == ErrorKind::InvalidData => panic!("Configuration file at {:?} is corrupted: contains data that cannot be interpreted as UTF-8", file_path),
-            Err(e) => panic!("Configuration file at {:?}: {}", file_path, e),
+            Err(ref e) if e.kind() == ErrorKind::InvalidData => return Err(format!("Configuration file at {:?} is corrupted: contains data that cannot be interpreted as UTF-8", file_path)),
+            Err(e) => return Err(format!("Configuration file at {:?}: {}", file_path, e)),
             Ok(_) => (),
         };
         let table: Table = match toml::de::from_str(&contents) {
-            Err(e) => panic!(
-                "Configuration file at {:?} has bad TOML syntax: {}",
-                file_path, e
-            ),
+            Err(e) => {
+                return Err(format!(
+                    "Configuration file at {:?} has bad TOML syntax: {}",
+                    file_path, e
+                ))
+            }
             Ok(table) => table,
         };
-        let vcl_args: Vec<Box<dyn VclArg>> = table
-            .keys()
-            .map(|key| {
-                let name = format!("--{}", key);
-                let value = match table.get(key).expect("value disappeared") {
-                    Value::Table(_) => Self::complain_about_data_elements(file_path),
-                    Value::Array(_) => Self::complain_about_data_elements(file_path),
-                    Value::Datetime(_) => Self::complain_about_data_elements(file_path),
-                    Value::String(v) => v.as_str().to_string(),
-                    v => v.to_string(),
-                };
-                let result: Box<dyn VclArg> =
-                    Box::new(NameValueVclArg::new(&name, &value.to_string()));
-                result
-            })
-            .collect();
+        let mut vcl_args: Vec<Box<dyn VclArg>> = vec![];
+        for key in table.keys() {
+            let name = format!("--{}", key);
+            let value = match table.get(key).expect("value disappeared") {
+                Value::Table(_) | Value::Array(_) | Value::Datetime(_) => {
+                    return Err(Self::complain_about_data_elements(file_path))
+                }
+                Value::String(v) => v.as_str().to_string(),
+                v => v.to_string(),
+            };
+            vcl_args.push(Box::new(NameValueVclArg::new(&name, &value)));
+        }
 
-        ConfigFileVcl { vcl_args }
+        Ok(ConfigFileVcl { vcl_args })
     }
 
-    fn complain_about_data_elements(file_path: &PathBuf) -> ! {
-        panic!("Configuration file at {:?} contains unsupported Datetime or non-scalar configuration values", file_path)
+    // Parameter names without their leading dashes, each with the value the file gives it
+    pub fn values(&self) -> BTreeMap<String, String> {
+        self.vcl_args
+            .iter()
+            .map(|vcl_arg| {
+                let args = vcl_arg.to_args();
+                (
+                    args[0].trim_start_matches("--").to_string(),
+                    args.get(1).cloned().unwrap_or_default(),
+                )
+            })
+            .collect()
+    }
+
+    fn complain_about_data_elements(file_path: &PathBuf) -> String {
+        format!("Configuration file at {:?} contains unsupported Datetime or non-scalar configuration values", file_path)
     }
 }
 
@@ -758,6 +804,21 @@ pub(crate) mod tests {
         );
     }
 
+    #[test]
+    fn environment_vcl_sets_flags_unless_told_not_to() {
+        let _guard = EnvironmentGuard::new();
+        let schema = App::new("test")
+            .arg(Arg::with_name("json").long("json").takes_value(false))
+            .arg(Arg::with_name("quiet").long("quiet").takes_value(false));
+        std::env::set_var("SUB_JSON", "true");
+        std::env::set_var("SUB_QUIET", "Off");
+        std::env::set_var("SUB_HELP", "1");
+
+        let subject = EnvironmentVcl::new(&schema);
+
+        assert_eq!(vec!["".to_string(), "--json".to_string()], subject.args());
+    }
+
     #[test]
     fn config_file_vcl_works() {
         let home_dir = ensure_node_home_directory_exists("multi_config", "config_file_vcl_works");
@@ -794,6 +855,57 @@ pub(crate) mod tests {
         );
     }
 
+    #[test]
+    fn config_file_vcl_values_are_named_without_dashes() {
+        let home_dir = ensure_node_home_directory_exists(
+            "multi_config",
+            "config_file_vcl_values_are_named_without_dashes",
+        );
+        let mut file_path = home_dir.clone();
+        file_path.push("config.toml");
+        {
+            let mut toml_file = File::create(&file_path).unwrap();
+            toml_file
+                .write_all(b"numeric-arg = 47\nstring-arg = \"booga\"\n")
+                .unwrap();
+        }
+
+        let subject = ConfigFileVcl::new(&file_path, true);
+
+        assert_eq!(
+            subject
+                .values()
+                .into_iter()
+                .collect::<Vec<(String, String)>>(),
+            vec![
+                ("numeric-arg".to_string(), "47".to_string()),
+                ("string-arg".to_string(), "booga".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn config_file_vcl_try_new_complains_instead_of_panicking() {
+        let home_dir = ensure_node_home_directory_exists(
+            "multi_config",
+            "config_file_vcl_try_new_complains_instead_of_panicking",
+        );
+        let mut file_path = home_dir.clone();
+        file_path.push("config.toml");
+        {
+            let mut toml_file = File::create(&file_path).unwrap();
+            toml_file.write_all(b"array-arg = [1, 2]\n").unwrap();
+        }
+
+        let result = ConfigFileVcl::try_new(&file_path, true).err().unwrap();
+
+        assert!(
+            result.ends_with("contains unsupported Datetime or non-scalar configuration values"),
+            "{}",
+            result
+        );
+    }
+
     #[test]
     fn config_file_vcl_handles_missing_file_when_not_user_specified() {
         init_test_logging();
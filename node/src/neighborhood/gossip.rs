@@ -5,6 +5,7 @@ use crate::neighborhood::dot_graph::{
     render_dot_graph, DotRenderable, EdgeRenderable, NodeRenderable, NodeRenderableInner,
 };
 use crate::neighborhood::neighborhood_database::NeighborhoodDatabase;
+use crate::neighborhood::resumption::ResumptionTicket;
 use crate::neighborhood::AccessibleGossipRecord;
 use crate::sub_lib::cryptde::{CryptDE, CryptData, PlainData, PublicKey};
 use crate::sub_lib::data_version::DataVersion;
@@ -93,6 +94,10 @@ impl GossipNodeRecord {
                 human_readable.push_str(&format!("\n\t\tnode_addr_opt: {:?},", self.node_addr_opt));
                 human_readable
                     .push_str(&format!("\n\t\tearning_wallet: {:?},", nri.earning_wallet));
+                human_readable.push_str(&format!(
+                    "\n\t\texit_earning_wallet_opt: {:?},",
+                    nri.exit_earning_wallet_opt
+                ));
                 human_readable.push_str(&format!("\n\t\trate_pack: {:?},", nri.rate_pack));
                 human_readable.push_str(&format!(
                     "\n\t\tneighbors: {:?},",
@@ -121,6 +126,9 @@ impl GossipNodeRecord {
 pub struct Gossip {
     pub version: DataVersion,
     pub node_records: Vec<GossipNodeRecord>,
+    // Issued in update Gossip to a neighbor; presented back by that neighbor to resume after a disconnect
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resumption_ticket_opt: Option<ResumptionTicket>,
 }
 
 impl Into<MessageType> for Gossip {
@@ -297,6 +305,7 @@ impl Gossip {
         Self {
             version: Self::version(),
             node_records,
+            resumption_ticket_opt: None,
         }
     }
 
@@ -392,7 +401,8 @@ impl Gossip {
 
 pub struct GossipBuilder<'a> {
     db: &'a NeighborhoodDatabase,
-    gossip: Gossip,
+    // Visible to neighborhood_test_utils, which adds Nodes from outside the database
+    pub(super) gossip: Gossip,
     keys_so_far: HashSet<PublicKey>,
 }
 
@@ -440,9 +450,11 @@ mod tests {
     use super::super::gossip::GossipBuilder;
     use super::super::neighborhood_test_utils::make_node_record;
     use super::super::neighborhood_test_utils::make_node_record_f;
+    use super::super::neighborhood_test_utils::NodeRecordBuilder;
     use super::*;
+    use crate::golden_test;
     use crate::neighborhood::neighborhood_test_utils::db_from_node;
-    use crate::test_utils::{assert_string_contains, vec_to_btset};
+    use crate::test_utils::{assert_string_contains, rate_pack, vec_to_btset};
     use std::str::FromStr;
 
     #[test]
@@ -607,7 +619,7 @@ mod tests {
         let result = format!("{:?}", gossip);
         let expected = format!(
             "\nGossipNodeRecord {{{}{}{}{}\n}}",
-            "\n\tinner: NodeRecordInner {\n\t\tpublic_key: AQIDBA,\n\t\tnode_addr_opt: Some(1.2.3.4:[1234]),\n\t\tearning_wallet: Wallet { kind: Address(0x546900db8d6e0937497133d1ae6fdf5f4b75bcd0) },\n\t\trate_pack: RatePack { routing_byte_rate: 1235, routing_service_rate: 1236, exit_byte_rate: 1237, exit_service_rate: 1238 },\n\t\tneighbors: [],\n\t\tversion: 2,\n\t},",
+            "\n\tinner: NodeRecordInner {\n\t\tpublic_key: AQIDBA,\n\t\tnode_addr_opt: Some(1.2.3.4:[1234]),\n\t\tearning_wallet: Wallet { kind: Address(0x546900db8d6e0937497133d1ae6fdf5f4b75bcd0) },\n\t\texit_earning_wallet_opt: None,\n\t\trate_pack: RatePack { routing_byte_rate: 1235, routing_service_rate: 1236, exit_byte_rate: 1237, exit_service_rate: 1238 },\n\t\tneighbors: [],\n\t\tversion: 2,\n\t},",
             "\n\tnode_addr_opt: Some(1.2.3.4:[1234]),",
             "\n\tsigned_data:
 Length: 246 (0xf6) bytes
@@ -687,6 +699,7 @@ Length: 4 (0x4) bytes
                 GossipNodeRecord::from((&db, target_node.public_key(), true)),
                 neighbor_gnr,
             ],
+            resumption_ticket_opt: None,
         };
 
         let result = gossip.to_dot_graph(&source_node, &target_node);
@@ -789,4 +802,61 @@ Length: 4 (0x4) bytes
 
         assert_eq!(String::from("digraph db { \"src\" [label=\"Gossip From:\\n\\n1.2.3.4\"]; \"dest\" [label=\"Gossip To:\\n\\n2.3.4.5\"]; \"src\" -> \"dest\" [arrowhead=empty]; }"), result)
     }
+
+    #[test]
+    fn gossip_builder_can_describe_nodes_that_are_not_in_the_database() {
+        let db = db_from_node(&make_node_record(1234, true));
+        let lonely = NodeRecordBuilder::new(3456)
+            .neighbor(&PublicKey::new(&b"nobody"[..]))
+            .neighbor(&PublicKey::new(&b"else"[..]))
+            .version(7)
+            .build();
+        let quiet = NodeRecordBuilder::new(5678)
+            .public_key(&PublicKey::new(&b"quiet"[..]))
+            .node_addr(None)
+            .rate_pack(rate_pack(100))
+            .accepts_connections(false)
+            .routes_data(false)
+            .build();
+
+        let gossip = GossipBuilder::new(&db)
+            .node_record(&quiet, true)
+            .node_record(&lonely, false)
+            .node_record(&lonely, true)
+            .garbage_record(b"not a record")
+            .build();
+
+        let mut results = gossip
+            .node_records
+            .into_iter()
+            .map(AccessibleGossipRecord::try_from)
+            .collect::<Vec<Result<AccessibleGossipRecord, String>>>();
+        assert!(results.pop().unwrap().is_err());
+        let with_node_addr = results.pop().unwrap().unwrap();
+        let without_node_addr = results.pop().unwrap().unwrap();
+        let quiet_agr = results.pop().unwrap().unwrap();
+        assert_eq!(without_node_addr.node_addr_opt, None);
+        assert_eq!(with_node_addr.node_addr_opt, lonely.node_addr_opt());
+        assert_eq!(
+            with_node_addr.inner.neighbors,
+            vec_to_btset(vec![
+                PublicKey::new(&b"nobody"[..]),
+                PublicKey::new(&b"else"[..])
+            ])
+        );
+        assert_eq!(with_node_addr.inner.version, 7);
+        assert_eq!(quiet_agr.node_addr_opt, None);
+        assert_eq!(quiet_agr.inner.public_key, PublicKey::new(&b"quiet"[..]));
+        assert_eq!(quiet_agr.inner.rate_pack, rate_pack(100));
+        assert_eq!(quiet_agr.inner.accepts_connections, false);
+        assert_eq!(quiet_agr.inner.routes_data, false);
+    }
+
+    golden_test!(
+        golden_gossip,
+        Gossip::new(vec![
+            GossipNodeRecord::from(make_node_record(1234, true)),
+            GossipNodeRecord::from(make_node_record(2345, false)),
+        ])
+    );
 }
@@ -0,0 +1,394 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::entry_dns::packet_facade::PacketFacade;
+use crate::sub_lib::logger::Logger;
+use actix::Message;
+use actix::Recipient;
+use net2::UdpBuilder;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+use trust_dns::op::OpCode;
+use trust_dns::rr::{DNSClass, RecordType};
+
+pub const MDNS_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+pub const MDNS_PORT: u16 = 5353;
+// How often to ask who's out there, and to remind them we are
+pub const DISCOVERY_INTERVAL: Duration = Duration::from_secs(60);
+
+const SERVICE_TYPE: &str = "_substratum._tcp.local";
+const DESCRIPTOR_KEY: &str = "descriptor=";
+const RECORD_TTL: u32 = 120;
+const MAX_PACKET_SIZE: usize = 9000;
+const HEADER_BYTES: usize = 12;
+
+// A Node descriptor heard on the LAN that isn't ours
+#[derive(Clone, Debug, Message, PartialEq)]
+pub struct LanNeighborMessage {
+    pub descriptor: String,
+}
+
+pub trait LanDiscoverer: Send {
+    // Until the Node stops: advertises our descriptor, if we accept connections and so have one to
+    // give out, and passes every other one heard on the LAN to found_sub
+    fn start(&self, descriptor_opt: Option<String>, found_sub: Recipient<LanNeighborMessage>);
+}
+
+// DNS-SD over mDNS, as little of it as Nodes need to find one another: a PTR record from the
+// service type to each Node's instance name, and a TXT record on the instance name that carries
+// its descriptor. Anybody on the LAN can hear these, which is why it's off unless asked for.
+pub struct LanDiscovererReal {
+    logger: Logger,
+}
+
+impl LanDiscoverer for LanDiscovererReal {
+    fn start(&self, descriptor_opt: Option<String>, found_sub: Recipient<LanNeighborMessage>) {
+        let socket = match open_mdns_socket() {
+            Ok(socket) => socket,
+            Err(e) => {
+                warning!(
+                    self.logger,
+                    "Can't listen for other Nodes on the LAN at {}:{}: {}",
+                    MDNS_GROUP,
+                    MDNS_PORT,
+                    e
+                );
+                return;
+            }
+        };
+        let announcement_opt = match descriptor_opt.as_ref() {
+            None => None,
+            Some(descriptor) => match make_announcement(descriptor) {
+                Some(announcement) => Some(announcement),
+                None => {
+                    warning!(
+                        self.logger,
+                        "Node descriptor {} is too long to advertise on the LAN; only looking for other Nodes",
+                        descriptor
+                    );
+                    None
+                }
+            },
+        };
+        let logger = self.logger.clone();
+        thread::spawn(move || {
+            discover(socket, descriptor_opt, announcement_opt, found_sub, logger)
+        });
+    }
+}
+
+impl Default for LanDiscovererReal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LanDiscovererReal {
+    pub fn new() -> LanDiscovererReal {
+        LanDiscovererReal {
+            logger: Logger::new("LanDiscoverer"),
+        }
+    }
+}
+
+// Other mDNS responders, like Avahi or Bonjour, usually have the port already
+fn open_mdns_socket() -> io::Result<UdpSocket> {
+    let builder = UdpBuilder::new_v4()?;
+    builder.reuse_address(true)?;
+    #[cfg(unix)]
+    {
+        use net2::unix::UnixUdpBuilderExt;
+        builder.reuse_port(true)?;
+    }
+    let socket = builder.bind((Ipv4Addr::UNSPECIFIED, MDNS_PORT))?;
+    socket.join_multicast_v4(&MDNS_GROUP, &Ipv4Addr::UNSPECIFIED)?;
+    // So that several Nodes on one machine find one another too
+    socket.set_multicast_loop_v4(true)?;
+    Ok(socket)
+}
+
+fn discover(
+    socket: UdpSocket,
+    descriptor_opt: Option<String>,
+    announcement_opt: Option<Vec<u8>>,
+    found_sub: Recipient<LanNeighborMessage>,
+    logger: Logger,
+) {
+    let group = SocketAddr::from((MDNS_GROUP, MDNS_PORT));
+    let query = make_query();
+    let mut next_query_time = Instant::now();
+    let mut buf = [0u8; MAX_PACKET_SIZE];
+    loop {
+        let now = Instant::now();
+        if now >= next_query_time {
+            if let Some(announcement) = &announcement_opt {
+                send(&socket, announcement, group, &logger);
+            }
+            send(&socket, &query, group, &logger);
+            next_query_time = now + DISCOVERY_INTERVAL;
+        }
+        socket
+            .set_read_timeout(Some(next_query_time - now))
+            .expect("Read timeout is zero");
+        let length = match socket.recv_from(&mut buf) {
+            Ok((length, _)) => length,
+            Err(ref e)
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut =>
+            {
+                continue
+            }
+            Err(e) => {
+                error!(logger, "Stopped looking for other Nodes on the LAN: {}", e);
+                return;
+            }
+        };
+        if is_query_for_nodes(&mut buf, length) {
+            if let Some(announcement) = &announcement_opt {
+                send(&socket, announcement, group, &logger);
+            }
+            continue;
+        }
+        for descriptor in descriptors_in(&mut buf, length) {
+            if Some(&descriptor) == descriptor_opt.as_ref() {
+                continue;
+            }
+            if found_sub
+                .try_send(LanNeighborMessage { descriptor })
+                .is_err()
+            {
+                // The Neighborhood is gone, and the Node with it
+                return;
+            }
+        }
+    }
+}
+
+fn send(socket: &UdpSocket, packet: &[u8], group: SocketAddr, logger: &Logger) {
+    if let Err(e) = socket.send_to(packet, group) {
+        debug!(logger, "Couldn't send mDNS packet to {}: {}", group, e);
+    }
+}
+
+fn make_query() -> Vec<u8> {
+    let mut buf = [0u8; MAX_PACKET_SIZE];
+    let length = {
+        let mut query = PacketFacade::new(&mut buf, HEADER_BYTES);
+        query.set_transaction_id(0);
+        query.set_query(true);
+        query.set_opcode(OpCode::Query.into());
+        query.add_query(SERVICE_TYPE, RecordType::PTR.into(), DNSClass::IN.into());
+        query.get_length()
+    };
+    buf[0..length].to_vec()
+}
+
+// None if the descriptor won't fit in a TXT string
+fn make_announcement(descriptor: &str) -> Option<Vec<u8>> {
+    let txt = format!("{}{}", DESCRIPTOR_KEY, descriptor);
+    if txt.len() > 255 {
+        return None;
+    }
+    let instance_name = format!("{}.{}", instance_label(descriptor), SERVICE_TYPE);
+    let mut txt_rdata = vec![txt.len() as u8];
+    txt_rdata.extend(txt.as_bytes());
+    let mut buf = [0u8; MAX_PACKET_SIZE];
+    let length = {
+        let mut announcement = PacketFacade::new(&mut buf, HEADER_BYTES);
+        announcement.set_transaction_id(0);
+        announcement.set_query(false);
+        announcement.set_opcode(OpCode::Query.into());
+        announcement.set_authoritative_answer(true);
+        announcement.add_answer(
+            SERVICE_TYPE,
+            RecordType::PTR.into(),
+            DNSClass::IN.into(),
+            RECORD_TTL,
+            &encode_name(&instance_name),
+        );
+        announcement.add_answer(
+            &instance_name,
+            RecordType::TXT.into(),
+            DNSClass::IN.into(),
+            RECORD_TTL,
+            &txt_rdata,
+        );
+        announcement.get_length()
+    };
+    Some(buf[0..length].to_vec())
+}
+
+// The public key part of the descriptor: unique, and a legal DNS-SD instance name
+fn instance_label(descriptor: &str) -> String {
+    descriptor
+        .chars()
+        .take_while(|c| *c != ':' && *c != '@')
+        .take(63)
+        .collect()
+}
+
+fn encode_name(name: &str) -> Vec<u8> {
+    let mut result = vec![];
+    name.split('.').for_each(|label| {
+        result.push(label.len() as u8);
+        result.extend(label.as_bytes());
+    });
+    result.push(0);
+    result
+}
+
+fn is_query_for_nodes(buf: &mut [u8], length: usize) -> bool {
+    let packet = PacketFacade::new(buf, length);
+    if packet.is_query() != Some(true) {
+        return false;
+    }
+    packet
+        .get_queries()
+        .unwrap_or_default()
+        .iter()
+        .any(|query| {
+            let query_type = RecordType::from(query.get_query_type());
+            query.get_query_name().eq_ignore_ascii_case(SERVICE_TYPE)
+                && (query_type == RecordType::PTR || query_type == RecordType::ANY)
+        })
+}
+
+// Packets that aren't from Nodes, or that PacketFacade can't read (mDNS compresses names), have none
+fn descriptors_in(buf: &mut [u8], length: usize) -> Vec<String> {
+    let packet = PacketFacade::new(buf, length);
+    if packet.is_query() != Some(false) {
+        return vec![];
+    }
+    let suffix = format!(".{}", SERVICE_TYPE);
+    packet
+        .get_answers()
+        .unwrap_or_default()
+        .iter()
+        .filter(|record| {
+            RecordType::from(record.get_resource_type()) == RecordType::TXT
+                && record.get_name().to_lowercase().ends_with(&suffix)
+        })
+        .flat_map(|record| txt_strings(record.get_rdata()))
+        .filter(|txt| txt.starts_with(DESCRIPTOR_KEY))
+        .map(|txt| txt[DESCRIPTOR_KEY.len()..].to_string())
+        .collect()
+}
+
+fn txt_strings(rdata: &[u8]) -> Vec<String> {
+    let mut result = vec![];
+    let mut offset = 0;
+    while offset < rdata.len() {
+        let end = offset + 1 + rdata[offset] as usize;
+        if end > rdata.len() {
+            break;
+        }
+        result.push(String::from_utf8_lossy(&rdata[offset + 1..end]).to_string());
+        offset = end;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESCRIPTOR: &str = "AQIDBA:1.2.3.4:1234;2345";
+
+    #[test]
+    fn an_announcement_carries_the_descriptor_under_an_instance_of_the_service() {
+        let mut announcement = make_announcement(DESCRIPTOR).unwrap();
+        let length = announcement.len();
+
+        let packet = PacketFacade::new(&mut announcement, length);
+
+        assert_eq!(packet.is_query(), Some(false));
+        assert_eq!(packet.is_authoritative_answer(), Some(true));
+        let answers = packet.get_answers().unwrap();
+        assert_eq!(answers[0].get_name(), "_substratum._tcp.local");
+        assert_eq!(
+            RecordType::from(answers[0].get_resource_type()),
+            RecordType::PTR
+        );
+        assert_eq!(
+            answers[0].get_rdata(),
+            &encode_name("AQIDBA._substratum._tcp.local")[..]
+        );
+        assert_eq!(answers[1].get_name(), "AQIDBA._substratum._tcp.local");
+        assert_eq!(
+            RecordType::from(answers[1].get_resource_type()),
+            RecordType::TXT
+        );
+        assert_eq!(
+            descriptors_in(&mut announcement, length),
+            vec![DESCRIPTOR.to_string()]
+        );
+    }
+
+    #[test]
+    fn a_descriptor_too_long_for_a_txt_string_is_not_announced() {
+        let descriptor = format!("AQIDBA:1.2.3.4:{}", "1234;".repeat(50));
+
+        let result = make_announcement(&descriptor);
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn the_query_asks_for_nodes_and_nothing_else() {
+        let mut query = make_query();
+        let length = query.len();
+
+        assert!(is_query_for_nodes(&mut query, length));
+        assert!(descriptors_in(&mut query, length).is_empty());
+    }
+
+    #[test]
+    fn queries_for_other_services_are_not_for_nodes() {
+        let mut buf = [0u8; 512];
+        let length = {
+            let mut query = PacketFacade::new(&mut buf, HEADER_BYTES);
+            query.set_query(true);
+            query.add_query(
+                "_printer._tcp.local",
+                RecordType::PTR.into(),
+                DNSClass::IN.into(),
+            );
+            query.get_length()
+        };
+
+        assert!(!is_query_for_nodes(&mut buf, length));
+    }
+
+    #[test]
+    fn announcements_are_not_queries() {
+        let mut announcement = make_announcement(DESCRIPTOR).unwrap();
+        let length = announcement.len();
+
+        assert!(!is_query_for_nodes(&mut announcement, length));
+    }
+
+    #[test]
+    fn txt_records_of_other_services_have_no_descriptors() {
+        let mut buf = [0u8; 512];
+        let length = {
+            let mut response = PacketFacade::new(&mut buf, HEADER_BYTES);
+            response.set_query(false);
+            response.add_answer(
+                "printer._ipp._tcp.local",
+                RecordType::TXT.into(),
+                DNSClass::IN.into(),
+                RECORD_TTL,
+                b"\x15descriptor=AQIDBA:1:1",
+            );
+            response.get_length()
+        };
+
+        assert!(descriptors_in(&mut buf, length).is_empty());
+    }
+
+    #[test]
+    fn txt_strings_stops_at_a_string_that_runs_off_the_end() {
+        let result = txt_strings(b"\x03abc\x02de\x09fgh");
+
+        assert_eq!(result, vec!["abc".to_string(), "de".to_string()]);
+    }
+}
@@ -7,8 +7,11 @@ pub mod gossip_acceptor;
 mod gossip_producer;
 #[cfg(feature = "expose_test_privates")]
 pub mod gossip_producer;
+pub mod lan_discovery;
 pub mod neighborhood_database;
 pub mod node_record;
+pub mod resumption;
+pub mod route_cache;
 
 #[cfg(not(feature = "expose_test_privates"))]
 #[cfg(test)]
@@ -19,17 +22,25 @@ pub mod neighborhood_test_utils;
 
 use crate::blockchain::blockchain_interface::contract_address;
 use crate::bootstrapper::BootstrapperConfig;
-use crate::neighborhood::gossip::{DotGossipEndpoint, Gossip, GossipNodeRecord};
-use crate::neighborhood::gossip_acceptor::GossipAcceptanceResult;
+use crate::neighborhood::gossip::{DotGossipEndpoint, Gossip, GossipBuilder, GossipNodeRecord};
+use crate::neighborhood::gossip_acceptor::{GossipAcceptanceResult, MAX_DEGREE};
+use crate::neighborhood::lan_discovery::{LanDiscoverer, LanDiscovererReal, LanNeighborMessage};
 use crate::neighborhood::node_record::NodeRecordInner;
 use crate::stream_messages::RemovedStreamType;
+use crate::sub_lib::accountant::ReportBillingClaimMessage;
+use crate::sub_lib::clock::{Clock, ClockReal};
+use crate::sub_lib::crash_context::handling;
 use crate::sub_lib::cryptde::PublicKey;
 use crate::sub_lib::cryptde::{CryptDE, CryptData, PlainData};
-use crate::sub_lib::dispatcher::{Component, StreamShutdownMsg};
+use crate::sub_lib::dispatcher::{Component, NeighborKeyMessage, StreamShutdownMsg};
 use crate::sub_lib::hopper::{ExpiredCoresPackage, NoLookupIncipientCoresPackage};
 use crate::sub_lib::hopper::{IncipientCoresPackage, MessageType};
 use crate::sub_lib::logger::Logger;
+use crate::sub_lib::neighborhood::BillingClaimMessage;
+use crate::sub_lib::neighborhood::DebtorKeyMessage;
+use crate::sub_lib::neighborhood::DelinquencyMessage;
 use crate::sub_lib::neighborhood::DispatcherNodeQueryMessage;
+use crate::sub_lib::neighborhood::DisputeMessage;
 use crate::sub_lib::neighborhood::ExpectedService;
 use crate::sub_lib::neighborhood::ExpectedServices;
 use crate::sub_lib::neighborhood::NeighborhoodDotGraphRequest;
@@ -41,17 +52,24 @@ use crate::sub_lib::neighborhood::NodeRecordMetadataMessage;
 use crate::sub_lib::neighborhood::RemoveNeighborMessage;
 use crate::sub_lib::neighborhood::RouteQueryMessage;
 use crate::sub_lib::neighborhood::RouteQueryResponse;
+use crate::sub_lib::neighborhood::DEFAULT_MINIMUM_HOP_COUNT;
+use crate::sub_lib::neighborhood::{NeighborListRequest, NeighborReputation, NeighborSummary};
 use crate::sub_lib::node_addr::NodeAddr;
 use crate::sub_lib::peer_actors::{BindMessage, StartMessage};
 use crate::sub_lib::route::Route;
 use crate::sub_lib::route::RouteSegment;
 use crate::sub_lib::set_consuming_wallet_message::SetConsumingWalletMessage;
 use crate::sub_lib::stream_handler_pool::DispatcherNodeQueryResponse;
+use crate::sub_lib::ui_gateway::{ComponentHealth, GetHealthMessage, HealthState};
+use crate::sub_lib::ui_gateway::{GetNodeStatisticsMessage, NodeEvent, NodeStatistics};
 use crate::sub_lib::ui_gateway::{UiCarrierMessage, UiMessage};
 use crate::sub_lib::utils::{node_descriptor_delimiter, NODE_MAILBOX_CAPACITY};
 use crate::sub_lib::wallet::Wallet;
+use crate::systemd::{ServiceManager, ServiceManagerSystemd};
+use crate::ui_gateway::ui_traffic_converter::BROADCAST;
 use actix::Actor;
 use actix::Addr;
+use actix::AsyncContext;
 use actix::Context;
 use actix::Handler;
 use actix::MessageResult;
@@ -62,21 +80,59 @@ use gossip_producer::GossipProducer;
 use gossip_producer::GossipProducerReal;
 use neighborhood_database::NeighborhoodDatabase;
 use node_record::NodeRecord;
+use resumption::ResumptionTicket;
+use route_cache::{RouteCache, SegmentConstraints, ROUTE_CACHE_REPORT_INTERVAL};
 use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
+use std::fs;
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+// Long enough for the initial exchange of Gossip with our --neighbors to settle down
+pub const ROUTE_SELF_CHECK_DELAY: Duration = Duration::from_secs(30);
+
+// In the data directory: the descriptors of our neighbors, one per line, so that a restarted Node
+// can find its way back into the Network without --neighbors
+pub const NEIGHBORHOOD_SNAPSHOT_FILE: &str = "neighborhood-snapshot.txt";
 
 pub struct Neighborhood {
     cryptde: &'static dyn CryptDE,
     hopper: Option<Recipient<IncipientCoresPackage>>,
     hopper_no_lookup: Option<Recipient<NoLookupIncipientCoresPackage>>,
-    dot_graph_recipient: Option<Recipient<UiCarrierMessage>>,
+    ui_message_sub: Option<Recipient<UiCarrierMessage>>,
+    node_event_sub: Option<Recipient<NodeEvent>>,
+    report_billing_claim_sub: Option<Recipient<ReportBillingClaimMessage>>,
+    neighbor_key_sub: Option<Recipient<NeighborKeyMessage>>,
     gossip_acceptor: Box<dyn GossipAcceptor>,
     gossip_producer: Box<dyn GossipProducer>,
     neighborhood_database: NeighborhoodDatabase,
     consuming_wallet_opt: Option<Wallet>,
+    // Consuming wallets banned for delinquency
+    delinquent_wallets: HashSet<Wallet>,
+    // The Nodes we've seen paying with each consuming wallet, so that bans can be laid on them
+    debtor_keys: HashMap<Wallet, PublicKey>,
+    // Earning wallets of creditors whose billing claims our Accountant is disputing
+    disputed_wallets: HashSet<Wallet>,
+    neighbor_activity: HashMap<PublicKey, NeighborActivity>,
+    // Tickets our neighbors have issued us, by issuer, for getting back in after a disconnect
+    resumption_tickets: HashMap<PublicKey, ResumptionTicket>,
+    // What the UIs were last told about our neighbors and our descriptor
+    reported_neighbors: HashSet<PublicKey>,
+    reported_descriptor: String,
     next_return_route_id: u32,
+    route_cache: RouteCache,
     initial_neighbors: Vec<String>,
+    snapshot_path_opt: Option<PathBuf>,
+    is_decentralized: bool,
+    lan_discovery: bool,
+    lan_discoverer: Box<dyn LanDiscoverer>,
+    // Nodes found on the LAN that we've already debuted to, so that we do it only once per run
+    lan_neighbors_greeted: HashSet<PublicKey>,
+    route_self_check_delay: Duration,
+    service_manager: Box<dyn ServiceManager>,
+    clock: Box<dyn Clock>,
     logger: Logger,
     chain_id: u8,
 }
@@ -89,10 +145,14 @@ impl Handler<BindMessage> for Neighborhood {
     type Result = ();
 
     fn handle(&mut self, msg: BindMessage, ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Neighborhood", "BindMessage");
         ctx.set_mailbox_capacity(NODE_MAILBOX_CAPACITY);
         self.hopper = Some(msg.peer_actors.hopper.from_hopper_client);
         self.hopper_no_lookup = Some(msg.peer_actors.hopper.from_hopper_client_no_lookup);
-        self.dot_graph_recipient = Some(msg.peer_actors.ui_gateway.ui_message_sub)
+        self.ui_message_sub = Some(msg.peer_actors.ui_gateway.ui_message_sub);
+        self.node_event_sub = Some(msg.peer_actors.ui_gateway.node_event_sub);
+        self.report_billing_claim_sub = Some(msg.peer_actors.accountant.report_billing_claim_sub);
+        self.neighbor_key_sub = Some(msg.peer_actors.dispatcher.neighbor_key_sub);
     }
 }
 
@@ -100,6 +160,7 @@ impl Handler<SetConsumingWalletMessage> for Neighborhood {
     type Result = ();
 
     fn handle(&mut self, msg: SetConsumingWalletMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Neighborhood", "SetConsumingWalletMessage");
         self.consuming_wallet_opt = Some(msg.wallet);
     }
 }
@@ -107,53 +168,34 @@ impl Handler<SetConsumingWalletMessage> for Neighborhood {
 impl Handler<StartMessage> for Neighborhood {
     type Result = ();
 
-    fn handle(&mut self, _msg: StartMessage, _ctx: &mut Self::Context) -> Self::Result {
-        if self.initial_neighbors.is_empty() {
-            info!(self.logger, "Empty. No Nodes to report to; continuing");
-            return;
+    fn handle(&mut self, _msg: StartMessage, ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Neighborhood", "StartMessage");
+        if self.is_decentralized {
+            ctx.run_later(self.route_self_check_delay, |neighborhood, _ctx| {
+                neighborhood.check_route_capability()
+            });
         }
+        self.debut_to_initial_neighbors();
+        if self.lan_discovery {
+            self.start_lan_discovery(ctx);
+        }
+        // The listeners were bound before the actors started, so this is the last thing between
+        // us and traffic. If this arbiter stops turning over, the watchdog pings stop with it.
+        self.service_manager.notify_ready();
+        if let Some(interval) = self.service_manager.watchdog_interval() {
+            ctx.run_interval(interval, |neighborhood, _ctx| {
+                neighborhood.service_manager.notify_watchdog()
+            });
+        }
+    }
+}
 
-        let gossip = self
-            .gossip_producer
-            .produce_debut(&self.neighborhood_database);
-        self.initial_neighbors.iter().for_each(|neighbor| {
-            let node_descriptor = NodeDescriptor::from_str(self.cryptde, neighbor, self.chain_id)
-                .unwrap_or_else(|e| {
-                    panic!(
-                        "--neighbors must be <public key>{}<ip address>:<port>;<port>..., not '{}'",
-                        node_descriptor_delimiter(self.chain_id),
-                        e
-                    );
-                });
-            if let Some(node_addr) = &node_descriptor.node_addr_opt {
-                self.hopper_no_lookup
-                    .as_ref()
-                    .expect("unbound hopper")
-                    .try_send(
-                        NoLookupIncipientCoresPackage::new(
-                            self.cryptde,
-                            &node_descriptor.public_key,
-                            &node_addr,
-                            MessageType::Gossip(gossip.clone()),
-                        )
-                        .expect("Key magically disappeared"),
-                    )
-                    .expect("hopper is dead");
-                trace!(
-                    self.logger,
-                    "Sent Gossip: {}",
-                    gossip.to_dot_graph(
-                        self.neighborhood_database.root(),
-                        (&node_descriptor.public_key, &node_descriptor.node_addr_opt),
-                    )
-                );
-            } else {
-                panic!(
-                    "--neighbors node descriptors must have IP address and port list, not '{}'",
-                    neighbor
-                )
-            }
-        });
+impl Handler<LanNeighborMessage> for Neighborhood {
+    type Result = ();
+
+    fn handle(&mut self, msg: LanNeighborMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Neighborhood", "LanNeighborMessage");
+        self.handle_lan_neighbor(msg.descriptor)
     }
 }
 
@@ -165,6 +207,7 @@ impl Handler<NodeQueryMessage> for Neighborhood {
         msg: NodeQueryMessage,
         _ctx: &mut Self::Context,
     ) -> <Self as Handler<NodeQueryMessage>>::Result {
+        let _handling = handling("Neighborhood", "NodeQueryMessage");
         let node_record_ref_opt = match msg {
             NodeQueryMessage::IpAddress(ip_addr) => self.neighborhood_database.node_by_ip(&ip_addr),
             NodeQueryMessage::PublicKey(key) => self.neighborhood_database.node_by_key(&key),
@@ -192,6 +235,7 @@ impl Handler<DispatcherNodeQueryMessage> for Neighborhood {
         msg: DispatcherNodeQueryMessage,
         _ctx: &mut Self::Context,
     ) -> <Self as Handler<DispatcherNodeQueryMessage>>::Result {
+        let _handling = handling("Neighborhood", "DispatcherNodeQueryMessage");
         let node_record_ref_opt = match msg.query {
             NodeQueryMessage::IpAddress(ip_addr) => self.neighborhood_database.node_by_ip(&ip_addr),
             NodeQueryMessage::PublicKey(key) => self.neighborhood_database.node_by_key(&key),
@@ -228,6 +272,7 @@ impl Handler<RouteQueryMessage> for Neighborhood {
         msg: RouteQueryMessage,
         _ctx: &mut Self::Context,
     ) -> <Self as Handler<RouteQueryMessage>>::Result {
+        let _handling = handling("Neighborhood", "RouteQueryMessage");
         let msg_str = format!("{:?}", msg);
         let result = if msg.minimum_hop_count == 0 {
             Ok(self.zero_hop_route_response())
@@ -246,6 +291,7 @@ impl Handler<RouteQueryMessage> for Neighborhood {
             }
             Err(msg) => {
                 error!(self.logger, "Unsatisfied route query: {}", msg);
+                self.publish_event(NodeEvent::RouteFailure { reason: msg });
                 None
             }
         })
@@ -260,6 +306,7 @@ impl Handler<ExpiredCoresPackage<Gossip>> for Neighborhood {
         msg: ExpiredCoresPackage<Gossip>,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
+        let _handling = handling("Neighborhood", "ExpiredCoresPackage<Gossip>");
         let incoming_gossip = msg.payload;
         self.log_incoming_gossip(&incoming_gossip, msg.immediate_neighbor);
         self.handle_gossip(incoming_gossip, msg.immediate_neighbor);
@@ -270,11 +317,13 @@ impl Handler<RemoveNeighborMessage> for Neighborhood {
     type Result = ();
 
     fn handle(&mut self, msg: RemoveNeighborMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Neighborhood", "RemoveNeighborMessage");
         let public_key = &msg.public_key;
         match self.neighborhood_database.remove_neighbor(public_key) {
             Err(s) => error!(self.logger, "{}", s),
             Ok(db_changed) => {
                 if db_changed {
+                    self.route_cache.invalidate();
                     self.gossip_to_neighbors();
                     info!(
                         self.logger,
@@ -290,24 +339,153 @@ impl Handler<NodeRecordMetadataMessage> for Neighborhood {
     type Result = ();
 
     fn handle(&mut self, msg: NodeRecordMetadataMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Neighborhood", "NodeRecordMetadataMessage");
         match msg {
             NodeRecordMetadataMessage::Desirable(public_key, desirable) => {
                 if let Some(node_record) = self.neighborhood_database.node_by_key_mut(&public_key) {
                     node_record.set_desirable(desirable);
+                    self.route_cache.invalidate();
                 };
             }
         };
     }
 }
 
+impl Handler<DelinquencyMessage> for Neighborhood {
+    type Result = ();
+
+    fn handle(&mut self, msg: DelinquencyMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Neighborhood", "DelinquencyMessage");
+        match msg {
+            DelinquencyMessage::Ban(wallet) => {
+                if self.delinquent_wallets.insert(wallet.as_address_wallet()) {
+                    self.route_cache.invalidate();
+                    info!(
+                        self.logger,
+                        "Nodes paying with {} will no longer be used in routes until their debt is paid",
+                        wallet
+                    );
+                }
+            }
+            DelinquencyMessage::Unban(wallet) => {
+                if self.delinquent_wallets.remove(&wallet.as_address_wallet()) {
+                    self.route_cache.invalidate();
+                    info!(
+                        self.logger,
+                        "Nodes paying with {} may be used in routes again", wallet
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl Handler<DebtorKeyMessage> for Neighborhood {
+    type Result = ();
+
+    fn handle(&mut self, msg: DebtorKeyMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Neighborhood", "DebtorKeyMessage");
+        let wallet = msg.consuming_wallet.as_address_wallet();
+        let previous_key_opt = self
+            .debtor_keys
+            .insert(wallet.clone(), msg.public_key.clone());
+        if self.delinquent_wallets.contains(&wallet) && previous_key_opt != Some(msg.public_key) {
+            self.route_cache.invalidate();
+        }
+    }
+}
+
+impl Handler<BillingClaimMessage> for Neighborhood {
+    type Result = ();
+
+    fn handle(&mut self, msg: BillingClaimMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Neighborhood", "BillingClaimMessage");
+        self.handle_billing_claim(msg);
+    }
+}
+
+impl Handler<DisputeMessage> for Neighborhood {
+    type Result = ();
+
+    fn handle(&mut self, msg: DisputeMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Neighborhood", "DisputeMessage");
+        match msg {
+            DisputeMessage::Open(wallet) => {
+                if self.disputed_wallets.insert(wallet.as_address_wallet()) {
+                    self.route_cache.invalidate();
+                    info!(
+                        self.logger,
+                        "Nodes earning to {} will no longer be used in routes while their billing claim is disputed",
+                        wallet
+                    );
+                }
+            }
+            DisputeMessage::Close(wallet) => {
+                if self.disputed_wallets.remove(&wallet.as_address_wallet()) {
+                    self.route_cache.invalidate();
+                    info!(
+                        self.logger,
+                        "Nodes earning to {} may be used in routes again", wallet
+                    );
+                }
+            }
+        }
+    }
+}
+
 impl Handler<StreamShutdownMsg> for Neighborhood {
     type Result = ();
 
     fn handle(&mut self, msg: StreamShutdownMsg, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Neighborhood", "StreamShutdownMsg");
         self.handle_stream_shutdown_msg(msg);
     }
 }
 
+impl Handler<GetNodeStatisticsMessage> for Neighborhood {
+    type Result = MessageResult<GetNodeStatisticsMessage>;
+
+    fn handle(&mut self, _msg: GetNodeStatisticsMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Neighborhood", "GetNodeStatisticsMessage");
+        MessageResult(NodeStatistics {
+            neighbor_count: self
+                .neighborhood_database
+                .root()
+                .full_neighbor_keys(&self.neighborhood_database)
+                .len() as u64,
+            ..NodeStatistics::default()
+        })
+    }
+}
+
+impl Handler<GetHealthMessage> for Neighborhood {
+    type Result = MessageResult<GetHealthMessage>;
+
+    fn handle(&mut self, _msg: GetHealthMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Neighborhood", "GetHealthMessage");
+        let health = if !self.is_decentralized {
+            ComponentHealth::new(
+                "neighborhood",
+                HealthState::Ready,
+                "zero-hop; no neighbors needed",
+            )
+        } else {
+            let degree = self
+                .neighborhood_database
+                .root()
+                .full_neighbor_keys(&self.neighborhood_database)
+                .len();
+            let state = if degree == 0 {
+                HealthState::NotReady
+            } else {
+                HealthState::Ready
+            };
+            ComponentHealth::new("neighborhood", state, &format!("degree {}", degree))
+        };
+        MessageResult(vec![health])
+    }
+}
+
 impl Handler<NeighborhoodDotGraphRequest> for Neighborhood {
     type Result = ();
 
@@ -316,11 +494,12 @@ impl Handler<NeighborhoodDotGraphRequest> for Neighborhood {
         msg: NeighborhoodDotGraphRequest,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
+        let _handling = handling("Neighborhood", "NeighborhoodDotGraphRequest");
         info!(
             self.logger,
             "acknowledge request for neighborhood dot graph."
         );
-        self.dot_graph_recipient
+        self.ui_message_sub
             .as_ref()
             .expect("DOT graph recipient is unbound")
             .try_send(UiCarrierMessage {
@@ -333,6 +512,30 @@ impl Handler<NeighborhoodDotGraphRequest> for Neighborhood {
     }
 }
 
+impl Handler<NeighborListRequest> for Neighborhood {
+    type Result = ();
+
+    fn handle(&mut self, msg: NeighborListRequest, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("Neighborhood", "NeighborListRequest");
+        let neighbors = self.neighbor_summaries(self.clock.now());
+        self.ui_message_sub
+            .as_ref()
+            .expect("UiGateway is unbound")
+            .try_send(UiCarrierMessage {
+                client_id: msg.client_id,
+                data: UiMessage::NeighborList(neighbors),
+            })
+            .expect("UiGateway is dead")
+    }
+}
+
+// What this Node has seen of a full neighbor since it became one; none of it is Gossiped.
+#[derive(Clone, Debug, PartialEq)]
+struct NeighborActivity {
+    neighbor_since: SystemTime,
+    last_gossip_opt: Option<SystemTime>,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct AccessibleGossipRecord {
     pub signed_gossip: PlainData,
@@ -365,8 +568,8 @@ impl TryFrom<GossipNodeRecord> for AccessibleGossipRecord {
     }
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
-enum RouteDirection {
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum RouteDirection {
     Over,
     Back,
 }
@@ -379,31 +582,284 @@ impl Neighborhood {
         {
             panic!("A zero-hop PrometheusNode is not decentralized and cannot have a --neighbors setting")
         }
+        if neighborhood_config.mode.is_zero_hop() && config.lan_discovery {
+            panic!("A zero-hop PrometheusNode is not decentralized and cannot have a --lan-discovery setting")
+        }
         let gossip_acceptor: Box<dyn GossipAcceptor> = Box::new(GossipAcceptorReal::new(cryptde));
         let gossip_producer = Box::new(GossipProducerReal::new());
-        let neighborhood_database = NeighborhoodDatabase::new(
+        let mut neighborhood_database = NeighborhoodDatabase::new(
             &cryptde.public_key(),
             neighborhood_config.mode.clone(),
             config.earning_wallet.clone(),
             cryptde,
         );
+        if neighborhood_database
+            .root_mut()
+            .set_exit_earning_wallet_opt(config.exit_earning_wallet_opt.clone())
+        {
+            neighborhood_database
+                .root_mut()
+                .regenerate_signed_gossip(cryptde);
+        }
 
+        let reported_descriptor = neighborhood_database
+            .root()
+            .node_descriptor(cryptde, config.blockchain_bridge_config.chain_id);
         Neighborhood {
             cryptde,
             hopper: None,
             hopper_no_lookup: None,
-            dot_graph_recipient: None,
+            ui_message_sub: None,
+            node_event_sub: None,
+            report_billing_claim_sub: None,
+            neighbor_key_sub: None,
             gossip_acceptor,
             gossip_producer,
             neighborhood_database,
             consuming_wallet_opt: config.consuming_wallet.clone(),
+            delinquent_wallets: HashSet::new(),
+            debtor_keys: HashMap::new(),
+            disputed_wallets: HashSet::new(),
+            neighbor_activity: HashMap::new(),
+            resumption_tickets: HashMap::new(),
+            reported_neighbors: HashSet::new(),
+            reported_descriptor,
             next_return_route_id: 0,
+            route_cache: RouteCache::new(),
             initial_neighbors: neighborhood_config.mode.neighbor_configs().clone(),
+            snapshot_path_opt: None,
+            is_decentralized: neighborhood_config.mode.is_decentralized(),
+            lan_discovery: config.lan_discovery,
+            lan_discoverer: Box::new(LanDiscovererReal::new()),
+            lan_neighbors_greeted: HashSet::new(),
+            route_self_check_delay: ROUTE_SELF_CHECK_DELAY,
+            service_manager: Box::new(ServiceManagerSystemd::new()),
+            clock: Box::new(ClockReal {}),
             logger: Logger::new("Neighborhood"),
             chain_id: config.blockchain_bridge_config.chain_id,
         }
     }
 
+    pub fn with_clock(mut self, clock: Box<dyn Clock>) -> Neighborhood {
+        self.clock = clock;
+        self
+    }
+
+    // Where to remember our neighbors between runs; without one, they're forgotten
+    pub fn with_snapshot_file(mut self, snapshot_path: PathBuf) -> Neighborhood {
+        self.snapshot_path_opt = Some(snapshot_path);
+        self
+    }
+
+    // Starts out knowing other Nodes, neighbored as their records say, for harnesses that need a
+    // particular shape of Network without waiting for Gossip to build it
+    pub fn with_known_nodes(mut self, node_records: Vec<NodeRecord>) -> Neighborhood {
+        let root_key = self.cryptde.public_key().clone();
+        node_records.into_iter().for_each(|node_record| {
+            let neighbors_root = node_record.has_half_neighbor(&root_key);
+            let key = self
+                .neighborhood_database
+                .add_node(node_record)
+                .expect("Couldn't add known Node");
+            if neighbors_root {
+                self.neighborhood_database
+                    .add_half_neighbor(&key)
+                    .expect("Known Node disappeared");
+            }
+        });
+        self.neighborhood_database
+            .root_mut()
+            .regenerate_signed_gossip(self.cryptde);
+        self
+    }
+
+    fn debut_to_initial_neighbors(&self) {
+        let snapshot_neighbors = self.read_snapshot();
+        if self.initial_neighbors.is_empty() && snapshot_neighbors.is_empty() {
+            info!(self.logger, "Empty. No Nodes to report to; continuing");
+            return;
+        }
+
+        let gossip = self
+            .gossip_producer
+            .produce_debut(&self.neighborhood_database);
+        self.initial_neighbors.iter().for_each(|neighbor| {
+            let node_descriptor = NodeDescriptor::from_str(self.cryptde, neighbor, self.chain_id)
+                .unwrap_or_else(|e| {
+                    panic!(
+                        "--neighbors must be <public key>{}<ip address>:<port>;<port>..., not '{}'",
+                        node_descriptor_delimiter(self.chain_id),
+                        e
+                    );
+                });
+            if !self.debut_to(&node_descriptor, &gossip) {
+                panic!(
+                    "--neighbors node descriptors must have IP address and port list, not '{}'",
+                    neighbor
+                )
+            }
+        });
+        snapshot_neighbors.iter().for_each(|node_descriptor| {
+            self.debut_to(node_descriptor, &gossip);
+        });
+    }
+
+    // The neighbors we had when we last ran, except any that --neighbors names already
+    fn read_snapshot(&self) -> Vec<NodeDescriptor> {
+        let snapshot_path = match &self.snapshot_path_opt {
+            Some(snapshot_path) if self.is_decentralized => snapshot_path,
+            _ => return vec![],
+        };
+        let snapshot = match fs::read_to_string(snapshot_path) {
+            Ok(snapshot) => snapshot,
+            Err(_) => return vec![],
+        };
+        let initial_keys = self
+            .initial_neighbors
+            .iter()
+            .filter_map(|neighbor| {
+                NodeDescriptor::from_str(self.cryptde, neighbor, self.chain_id).ok()
+            })
+            .map(|node_descriptor| node_descriptor.public_key)
+            .collect::<HashSet<PublicKey>>();
+        snapshot
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| {
+                match NodeDescriptor::from_str(self.cryptde, line.trim(), self.chain_id) {
+                    Ok(node_descriptor) => Some(node_descriptor),
+                    Err(e) => {
+                        warning!(
+                            self.logger,
+                            "Ignoring unusable Node descriptor {} in {}: {}",
+                            line,
+                            snapshot_path.display(),
+                            e
+                        );
+                        None
+                    }
+                }
+            })
+            .filter(|node_descriptor| {
+                node_descriptor.node_addr_opt.is_some()
+                    && &node_descriptor.public_key != self.cryptde.public_key()
+                    && !initial_keys.contains(&node_descriptor.public_key)
+            })
+            .collect()
+    }
+
+    fn write_snapshot(&self) {
+        let snapshot_path = match &self.snapshot_path_opt {
+            Some(snapshot_path) => snapshot_path,
+            None => return,
+        };
+        let database = &self.neighborhood_database;
+        let mut descriptors = database
+            .root()
+            .half_neighbor_keys()
+            .into_iter()
+            .filter_map(|key| database.node_by_key(key))
+            .filter(|node| node.node_addr_opt().is_some())
+            .map(|node| node.node_descriptor(self.cryptde, self.chain_id))
+            .collect::<Vec<String>>();
+        descriptors.sort();
+        let snapshot = descriptors
+            .into_iter()
+            .map(|descriptor| descriptor + "\n")
+            .collect::<String>();
+        if let Err(e) = fs::write(snapshot_path, snapshot) {
+            warning!(
+                self.logger,
+                "Couldn't remember our neighbors in {}: {}",
+                snapshot_path.display(),
+                e
+            );
+        }
+    }
+
+    // False if there's no address to send the debut to
+    fn debut_to(&self, node_descriptor: &NodeDescriptor, gossip: &Gossip) -> bool {
+        let node_addr = match &node_descriptor.node_addr_opt {
+            Some(node_addr) => node_addr,
+            None => return false,
+        };
+        self.hopper_no_lookup
+            .as_ref()
+            .expect("unbound hopper")
+            .try_send(
+                NoLookupIncipientCoresPackage::new(
+                    self.cryptde,
+                    &node_descriptor.public_key,
+                    node_addr,
+                    MessageType::Gossip(gossip.clone()),
+                )
+                .expect("Key magically disappeared"),
+            )
+            .expect("hopper is dead");
+        trace!(
+            self.logger,
+            "Sent Gossip: {}",
+            gossip.to_dot_graph(
+                self.neighborhood_database.root(),
+                (&node_descriptor.public_key, &node_descriptor.node_addr_opt),
+            )
+        );
+        true
+    }
+
+    fn start_lan_discovery(&self, ctx: &mut Context<Self>) {
+        let root = self.neighborhood_database.root();
+        let descriptor_opt = if root.accepts_connections() {
+            Some(root.node_descriptor(self.cryptde, self.chain_id))
+        } else {
+            None
+        };
+        info!(
+            self.logger,
+            "Looking for other Nodes on the LAN{}",
+            if descriptor_opt.is_some() {
+                " and advertising this one to them"
+            } else {
+                ""
+            }
+        );
+        self.lan_discoverer.start(
+            descriptor_opt,
+            ctx.address().recipient::<LanNeighborMessage>(),
+        );
+    }
+
+    fn handle_lan_neighbor(&mut self, descriptor: String) {
+        let node_descriptor =
+            match NodeDescriptor::from_str(self.cryptde, &descriptor, self.chain_id) {
+                Ok(node_descriptor) => node_descriptor,
+                Err(e) => {
+                    debug!(
+                        self.logger,
+                        "Ignoring unusable Node descriptor {} from the LAN: {}", descriptor, e
+                    );
+                    return;
+                }
+            };
+        let public_key = &node_descriptor.public_key;
+        if public_key == self.neighborhood_database.root().public_key()
+            || self.neighborhood_database.node_by_key(public_key).is_some()
+            || self.lan_neighbors_greeted.contains(public_key)
+        {
+            return;
+        }
+        let gossip = self
+            .gossip_producer
+            .produce_debut(&self.neighborhood_database);
+        if self.debut_to(&node_descriptor, &gossip) {
+            info!(
+                self.logger,
+                "Found Node {} on the LAN; introducing ourselves", descriptor
+            );
+            self.lan_neighbors_greeted.insert(public_key.clone());
+        }
+    }
+
     pub fn make_subs_from(addr: &Addr<Neighborhood>) -> NeighborhoodSubs {
         NeighborhoodSubs {
             bind: addr.clone().recipient::<BindMessage>(),
@@ -417,6 +873,13 @@ impl Neighborhood {
             stream_shutdown_sub: addr.clone().recipient::<StreamShutdownMsg>(),
             set_consuming_wallet_sub: addr.clone().recipient::<SetConsumingWalletMessage>(),
             from_ui_gateway: addr.clone().recipient::<NeighborhoodDotGraphRequest>(),
+            neighbor_list_sub: addr.clone().recipient::<NeighborListRequest>(),
+            delinquency_sub: addr.clone().recipient::<DelinquencyMessage>(),
+            debtor_key_sub: addr.clone().recipient::<DebtorKeyMessage>(),
+            billing_claim_sub: addr.clone().recipient::<BillingClaimMessage>(),
+            dispute_sub: addr.clone().recipient::<DisputeMessage>(),
+            node_statistics_sub: addr.clone().recipient::<GetNodeStatisticsMessage>(),
+            health_sub: addr.clone().recipient::<GetHealthMessage>(),
         }
     }
 
@@ -440,6 +903,7 @@ impl Neighborhood {
         );
 
         let record_count = incoming_gossip.node_records.len();
+        let resumption_ticket_opt = incoming_gossip.resumption_ticket_opt;
         let agrs: Vec<AccessibleGossipRecord> = incoming_gossip
             .node_records
             .into_iter()
@@ -473,16 +937,144 @@ impl Neighborhood {
             return;
         }
 
+        if let Some(ticket) = &resumption_ticket_opt {
+            if &ticket.issuer == self.cryptde.public_key()
+                && self.resume_neighbor(ticket, &agrs, gossip_source)
+            {
+                self.report_neighbor_key(gossip_source);
+                self.announce_gossip_handling_completion(record_count);
+                return;
+            }
+        }
         self.handle_agrs(agrs, gossip_source);
+        if let Some(ticket) = resumption_ticket_opt {
+            self.keep_resumption_ticket(ticket);
+        }
         self.announce_gossip_handling_completion(record_count);
     }
 
+    // A neighbor we lost touch with is back with the ticket we gave it: take it back at once and
+    // send it update Gossip, rather than putting it through a Debut. False if the ticket won't do.
+    fn resume_neighbor(
+        &mut self,
+        ticket: &ResumptionTicket,
+        agrs: &[AccessibleGossipRecord],
+        gossip_source: SocketAddr,
+    ) -> bool {
+        let agr = match agrs {
+            [agr] => agr,
+            _ => return false,
+        };
+        let neighbor_key = agr.inner.public_key.clone();
+        if let Err(e) = ticket.redeem(self.cryptde, &neighbor_key, self.clock.now()) {
+            warning!(
+                self.logger,
+                "Not resuming {} at {}: {}",
+                neighbor_key,
+                gossip_source,
+                e
+            );
+            return false;
+        }
+        let db = &self.neighborhood_database;
+        if db.node_by_key(&neighbor_key).is_none()
+            || (!db.has_half_neighbor(db.root().public_key(), &neighbor_key)
+                && db.root().full_neighbor_keys(db).len() >= MAX_DEGREE)
+        {
+            return false;
+        }
+        let node_addr = match &agr.node_addr_opt {
+            Some(node_addr) => node_addr.clone(),
+            None => NodeAddr::from(&gossip_source),
+        };
+        let addr_restored = match self
+            .neighborhood_database
+            .restore_node_addr(&neighbor_key, &node_addr)
+        {
+            Ok(restored) => restored,
+            Err(e) => {
+                warning!(
+                    self.logger,
+                    "Not resuming {} at {}: {:?}",
+                    neighbor_key,
+                    gossip_source,
+                    e
+                );
+                return false;
+            }
+        };
+        let neighbor_added = self
+            .neighborhood_database
+            .add_half_neighbor(&neighbor_key)
+            .expect("Resuming Node disappeared");
+        if let Some(node) = self.neighborhood_database.node_by_key_mut(&neighbor_key) {
+            if node.version() < agr.inner.version {
+                node.inner = agr.inner.clone();
+                node.signed_gossip = agr.signed_gossip.clone();
+                node.signature = agr.signature.clone();
+            }
+        }
+        self.note_gossip_from(gossip_source, self.clock.now());
+        info!(
+            self.logger,
+            "Resumed neighborship with {} at {} on its resumption ticket", neighbor_key, node_addr
+        );
+        if addr_restored || neighbor_added {
+            self.neighborhood_database.root_mut().increment_version();
+            self.route_cache.invalidate();
+            self.gossip_to_neighbors();
+        } else {
+            self.gossip_to(&neighbor_key);
+        }
+        true
+    }
+
+    // Only tickets issued to us are worth keeping; the newest one from each issuer replaces the last
+    fn keep_resumption_ticket(&mut self, ticket: ResumptionTicket) {
+        if &ticket.holder == self.cryptde.public_key()
+            && &ticket.issuer != self.cryptde.public_key()
+        {
+            self.resumption_tickets
+                .insert(ticket.issuer.clone(), ticket);
+        }
+    }
+
+    // Instead of dropping a neighbor whose stream has closed, reconnect and hand it back the ticket it
+    // gave us. If the neighbor can't be reached, the StreamHandlerPool will have it removed after all.
+    fn present_resumption_ticket(&mut self, neighbor_key: &PublicKey, node_addr: NodeAddr) -> bool {
+        let ticket = match self.resumption_tickets.remove(neighbor_key) {
+            Some(ticket) => ticket,
+            None => return false,
+        };
+        let mut gossip = GossipBuilder::new(&self.neighborhood_database)
+            .node(self.neighborhood_database.root().public_key(), true)
+            .build();
+        gossip.resumption_ticket_opt = Some(ticket);
+        info!(
+            self.logger,
+            "Stream to {} at {} closed; presenting resumption ticket", neighbor_key, node_addr
+        );
+        self.send_gossip(gossip, neighbor_key.clone(), node_addr);
+        true
+    }
+
     fn handle_agrs(&mut self, agrs: Vec<AccessibleGossipRecord>, gossip_source: SocketAddr) {
         let ignored_node_name = self.gossip_source_name(&agrs, gossip_source);
         let gossip_record_count = agrs.len();
         let acceptance_result =
             self.gossip_acceptor
                 .handle(&mut self.neighborhood_database, agrs, gossip_source);
+        match &acceptance_result {
+            GossipAcceptanceResult::Ban(_) => (),
+            _ => {
+                self.note_gossip_from(gossip_source, self.clock.now());
+                self.report_neighbor_key(gossip_source);
+            }
+        }
+        match &acceptance_result {
+            GossipAcceptanceResult::Ignored | GossipAcceptanceResult::Ban(_) => (),
+            _ => self.route_cache.invalidate(),
+        }
         match acceptance_result {
             GossipAcceptanceResult::Accepted => self.gossip_to_neighbors(),
             GossipAcceptanceResult::Reply(next_debut, relay_target, relay_node_addr) => {
@@ -500,10 +1092,69 @@ impl Neighborhood {
         }
     }
 
-    fn announce_gossip_handling_completion(&self, record_count: usize) {
-        info!(
-            self.logger,
-            "Finished processing Gossip about {} Nodes", record_count
+    // So that the Dispatcher can hold the neighbor to its rate limits by key. Of several Nodes
+    // behind one IP address, the one that listens on the port the gossip came from is the one
+    // that sent it.
+    fn report_neighbor_key(&self, gossip_source: SocketAddr) {
+        let neighbor_key_sub = match &self.neighbor_key_sub {
+            Some(neighbor_key_sub) => neighbor_key_sub,
+            None => return,
+        };
+        let database = &self.neighborhood_database;
+        let neighbor_opt = database
+            .keys()
+            .into_iter()
+            .filter_map(|key| database.node_by_key(key))
+            .find(|node| match node.node_addr_opt() {
+                Some(node_addr) => {
+                    node_addr.ip_addr() == gossip_source.ip()
+                        && node_addr.ports().contains(&gossip_source.port())
+                }
+                None => false,
+            })
+            .or_else(|| database.node_by_ip(&gossip_source.ip()));
+        if let Some(neighbor) = neighbor_opt {
+            neighbor_key_sub
+                .try_send(NeighborKeyMessage {
+                    peer_addr: gossip_source,
+                    public_key: neighbor.public_key().clone(),
+                })
+                .expect("Dispatcher is dead");
+        }
+    }
+
+    fn handle_billing_claim(&self, msg: BillingClaimMessage) {
+        let creditor = match self.neighborhood_database.node_by_key(&msg.creditor_key) {
+            Some(node) => node,
+            None => {
+                warning!(
+                    self.logger,
+                    "Ignoring billing claim of {} Gwub from unknown Node {}",
+                    msg.claimed_gwub,
+                    msg.creditor_key
+                );
+                return;
+            }
+        };
+        let mut creditor_wallets = vec![creditor.earning_wallet()];
+        if creditor.exit_earning_wallet() != creditor.earning_wallet() {
+            creditor_wallets.push(creditor.exit_earning_wallet());
+        }
+        self.report_billing_claim_sub
+            .as_ref()
+            .expect("Accountant is unbound")
+            .try_send(ReportBillingClaimMessage {
+                creditor_wallets,
+                claimed_gwub: msg.claimed_gwub,
+                shared_wallet: msg.shared_wallet,
+            })
+            .expect("Accountant is dead");
+    }
+
+    fn announce_gossip_handling_completion(&self, record_count: usize) {
+        info!(
+            self.logger,
+            "Finished processing Gossip about {} Nodes", record_count
         );
     }
 
@@ -512,35 +1163,87 @@ impl Neighborhood {
             .root_mut()
             .regenerate_signed_gossip(self.cryptde);
         let neighbors = self.neighborhood_database.root().half_neighbor_keys();
-        neighbors.iter().for_each(|neighbor| {
-            let gossip = self
-                .gossip_producer
-                .produce(&self.neighborhood_database, neighbor);
-            let gossip_len = gossip.node_records.len();
-            let route = self.create_single_hop_route(neighbor);
-            let package =
-                IncipientCoresPackage::new(self.cryptde, route, gossip.clone().into(), neighbor)
-                    .expect("Key magically disappeared");
-            info!(
-                self.logger,
-                "Sending update Gossip about {} Nodes to Node {}", gossip_len, neighbor
-            );
-            self.hopper
-                .as_ref()
-                .expect("unbound hopper")
-                .try_send(package)
-                .expect("hopper is dead");
-            trace!(
-                self.logger,
-                "Sent Gossip: {}",
-                gossip.to_dot_graph(
-                    self.neighborhood_database.root(),
-                    self.neighborhood_database
-                        .node_by_key(*neighbor)
-                        .expect("Node magically disappeared"),
-                )
-            );
-        });
+        neighbors
+            .iter()
+            .for_each(|neighbor| self.gossip_to(neighbor));
+        self.publish_neighborhood_changes();
+    }
+
+    fn gossip_to(&self, neighbor: &PublicKey) {
+        let mut gossip = self
+            .gossip_producer
+            .produce(&self.neighborhood_database, neighbor);
+        gossip.resumption_ticket_opt = Some(ResumptionTicket::issue(
+            self.cryptde,
+            neighbor,
+            self.clock.now(),
+        ));
+        let gossip_len = gossip.node_records.len();
+        let route = self.create_single_hop_route(neighbor);
+        let package =
+            IncipientCoresPackage::new(self.cryptde, route, gossip.clone().into(), neighbor)
+                .expect("Key magically disappeared");
+        info!(
+            self.logger,
+            "Sending update Gossip about {} Nodes to Node {}", gossip_len, neighbor
+        );
+        self.hopper
+            .as_ref()
+            .expect("unbound hopper")
+            .try_send(package)
+            .expect("hopper is dead");
+        trace!(
+            self.logger,
+            "Sent Gossip: {}",
+            gossip.to_dot_graph(
+                self.neighborhood_database.root(),
+                self.neighborhood_database
+                    .node_by_key(neighbor)
+                    .expect("Node magically disappeared"),
+            )
+        );
+    }
+
+    // Every change to our neighbors or to our own record ends up being Gossiped about
+    fn publish_neighborhood_changes(&mut self) {
+        let root = self.neighborhood_database.root();
+        let neighbors = root
+            .half_neighbor_keys()
+            .into_iter()
+            .cloned()
+            .collect::<HashSet<PublicKey>>();
+        let descriptor = root.node_descriptor(self.cryptde, self.chain_id);
+        let mut events = neighbors
+            .difference(&self.reported_neighbors)
+            .map(|key| NodeEvent::NeighborConnected {
+                public_key: key.to_string(),
+            })
+            .chain(self.reported_neighbors.difference(&neighbors).map(|key| {
+                NodeEvent::NeighborDisconnected {
+                    public_key: key.to_string(),
+                }
+            }))
+            .collect::<Vec<NodeEvent>>();
+        if descriptor != self.reported_descriptor {
+            events.push(NodeEvent::DescriptorChanged {
+                node_descriptor: descriptor.clone(),
+            });
+        }
+        if neighbors != self.reported_neighbors {
+            self.reported_neighbors = neighbors;
+            self.write_snapshot();
+        }
+        self.reported_descriptor = descriptor;
+        events
+            .into_iter()
+            .for_each(|event| self.publish_event(event));
+    }
+
+    // Events are of no interest until there's a UiGateway to hear them
+    fn publish_event(&self, event: NodeEvent) {
+        if let Some(node_event_sub) = &self.node_event_sub {
+            node_event_sub.try_send(event).expect("UiGateway is dead");
+        }
     }
 
     fn create_single_hop_route(&self, destination: &PublicKey) -> Route {
@@ -587,8 +1290,9 @@ impl Neighborhood {
         &mut self,
         msg: RouteQueryMessage,
     ) -> Result<RouteQueryResponse, String> {
+        let root_key = self.cryptde.public_key().clone();
         let over = self.make_route_segment(
-            &self.cryptde.public_key(),
+            &root_key,
             msg.target_key_opt.as_ref(),
             msg.minimum_hop_count,
             msg.target_component,
@@ -597,19 +1301,24 @@ impl Neighborhood {
         debug!(self.logger, "Route over: {:?}", over);
         let back = self.make_route_segment(
             over.keys.last().expect("Empty segment"),
-            Some(&self.cryptde.public_key()),
+            Some(&root_key),
             msg.minimum_hop_count,
             msg.return_component_opt.expect("No return component"),
             RouteDirection::Back,
         )?;
         debug!(self.logger, "Route back: {:?}", back);
-        self.compose_route_query_response(over, back)
+        let consuming_wallet_opt = msg
+            .consuming_wallet_opt
+            .or_else(|| self.consuming_wallet_opt.clone());
+        self.compose_route_query_response(over, back, consuming_wallet_opt)
     }
 
+    // consuming_wallet_opt is the wallet that pays for the route
     fn compose_route_query_response(
         &mut self,
         over: RouteSegment,
         back: RouteSegment,
+        consuming_wallet_opt: Option<Wallet>,
     ) -> Result<RouteQueryResponse, String> {
         let segments = vec![&over, &back];
 
@@ -618,7 +1327,7 @@ impl Neighborhood {
         }
 
         let has_long_segment = segments.iter().any(|segment| segment.keys.len() > 2);
-        if self.consuming_wallet_opt.is_none() && has_long_segment {
+        if consuming_wallet_opt.is_none() && has_long_segment {
             return Err("Cannot make multi-hop route segment without consuming wallet".to_string());
         }
 
@@ -638,7 +1347,7 @@ impl Neighborhood {
                 over,
                 back,
                 self.cryptde,
-                self.consuming_wallet_opt.clone(),
+                consuming_wallet_opt,
                 return_route_id,
                 Some(contract_address(self.chain_id)),
             )
@@ -652,32 +1361,70 @@ impl Neighborhood {
     }
 
     fn make_route_segment(
-        &self,
+        &mut self,
         origin: &PublicKey,
         target: Option<&PublicKey>,
         minimum_hop_count: usize,
         target_component: Component,
         direction: RouteDirection,
     ) -> Result<RouteSegment, String> {
+        let constraints = SegmentConstraints {
+            origin: origin.clone(),
+            target_opt: target.cloned(),
+            minimum_hop_count,
+            direction,
+        };
+        let node_seq_opt = match self.route_cache.lookup(&constraints) {
+            Some(node_seq_opt) => node_seq_opt,
+            None => {
+                let node_seq_opt =
+                    self.choose_node_seq(origin, target, minimum_hop_count, direction);
+                self.route_cache.insert(constraints, node_seq_opt.clone());
+                node_seq_opt
+            }
+        };
+        self.report_route_cache();
+
+        match node_seq_opt {
+            Some(node_seq) => Ok(RouteSegment::new(
+                node_seq.iter().collect(),
+                target_component,
+            )),
+            None => {
+                let target_str = match target {
+                    Some(t) => format!(" {}", t),
+                    None => String::from("Unknown"),
+                };
+                Err(format!(
+                    "Couldn't find any routes: at least {}-hop from {} to {:?} at {}",
+                    minimum_hop_count, origin, target_component, target_str
+                ))
+            }
+        }
+    }
+
+    fn choose_node_seq(
+        &self,
+        origin: &PublicKey,
+        target: Option<&PublicKey>,
+        minimum_hop_count: usize,
+        direction: RouteDirection,
+    ) -> Option<Vec<PublicKey>> {
         let mut node_seqs =
             self.complete_routes(vec![origin], target, minimum_hop_count, direction);
-
         if node_seqs.is_empty() {
-            let target_str = match target {
-                Some(t) => format!(" {}", t),
-                None => String::from("Unknown"),
-            };
-            Err(format!(
-                "Couldn't find any routes: at least {}-hop from {} to {:?} at {}",
-                minimum_hop_count, origin, target_component, target_str
-            ))
-        } else {
-            // When the target is Some all exit nodes will be the target and it is not optimal to sort.
-            if target.is_none() {
-                self.sort_routes_by_desirable_exit_nodes(node_seqs.as_mut());
-            }
-            let chosen_node_seq = node_seqs.remove(0);
-            Ok(RouteSegment::new(chosen_node_seq, target_component))
+            return None;
+        }
+        // When the target is Some all exit nodes will be the target and it is not optimal to sort.
+        if target.is_none() {
+            self.sort_routes_by_desirable_exit_nodes(node_seqs.as_mut());
+        }
+        Some(node_seqs.remove(0).into_iter().cloned().collect())
+    }
+
+    fn report_route_cache(&self) {
+        if self.route_cache.lookups() % ROUTE_CACHE_REPORT_INTERVAL == 0 {
+            info!(self.logger, "Route cache: {}", self.route_cache);
         }
     }
 
@@ -741,7 +1488,7 @@ impl Neighborhood {
                         {
                             Ok(ExpectedService::Exit(
                                 route_segment_key.clone(),
-                                node.earning_wallet(),
+                                node.exit_earning_wallet(),
                                 node.rate_pack().clone(),
                             ))
                         }
@@ -805,6 +1552,90 @@ impl Neighborhood {
         }
     }
 
+    fn note_gossip_from(&mut self, gossip_source: SocketAddr, now: SystemTime) {
+        self.refresh_neighbor_activity(now);
+        if let Some(node) = self.neighborhood_database.node_by_ip(&gossip_source.ip()) {
+            if let Some(activity) = self.neighbor_activity.get_mut(node.public_key()) {
+                activity.last_gossip_opt = Some(now);
+            }
+        }
+    }
+
+    // Neighbors are timed from when this Node first noticed them, and forgotten when they go
+    fn refresh_neighbor_activity(&mut self, now: SystemTime) {
+        let full_neighbor_keys: HashSet<PublicKey> = self
+            .neighborhood_database
+            .root()
+            .full_neighbor_keys(&self.neighborhood_database)
+            .into_iter()
+            .cloned()
+            .collect();
+        self.neighbor_activity
+            .retain(|key, _| full_neighbor_keys.contains(key));
+        full_neighbor_keys.into_iter().for_each(|key| {
+            self.neighbor_activity
+                .entry(key)
+                .or_insert(NeighborActivity {
+                    neighbor_since: now,
+                    last_gossip_opt: None,
+                });
+        });
+    }
+
+    fn neighbor_summaries(&mut self, now: SystemTime) -> Vec<NeighborSummary> {
+        self.refresh_neighbor_activity(now);
+        let secs_before_now =
+            |time: SystemTime| now.duration_since(time).unwrap_or_default().as_secs();
+        let mut summaries: Vec<NeighborSummary> = self
+            .neighborhood_database
+            .root()
+            .full_neighbors(&self.neighborhood_database)
+            .into_iter()
+            .map(|node| {
+                let activity = &self.neighbor_activity[node.public_key()];
+                let public_key = node.public_key().to_string();
+                NeighborSummary {
+                    key_fingerprint: public_key.chars().take(8).collect(),
+                    public_key,
+                    node_addr: node.node_addr_opt().map(|node_addr| node_addr.to_string()),
+                    neighbor_for_secs: secs_before_now(activity.neighbor_since),
+                    version: node.version(),
+                    secs_since_last_gossip: activity.last_gossip_opt.map(secs_before_now),
+                    reputation: self.reputation_of(node),
+                }
+            })
+            .collect();
+        summaries.sort_by(|a, b| a.public_key.cmp(&b.public_key));
+        summaries
+    }
+
+    fn reputation_of(&self, node: &NodeRecord) -> NeighborReputation {
+        if self.is_delinquent(node) {
+            NeighborReputation::Delinquent
+        } else if !node.is_desirable() {
+            NeighborReputation::Undesirable
+        } else {
+            NeighborReputation::Good
+        }
+    }
+
+    // Bans are on consuming wallets, which Gossip doesn't carry: only the Nodes we've seen paying
+    // with a banned wallet can be found delinquent.
+    fn is_delinquent(&self, node: &NodeRecord) -> bool {
+        self.delinquent_wallets
+            .iter()
+            .filter_map(|wallet| self.debtor_keys.get(wallet))
+            .any(|public_key| public_key == node.public_key())
+    }
+
+    fn is_disputed(&self, node: &NodeRecord) -> bool {
+        self.disputed_wallets
+            .contains(&node.earning_wallet().as_address_wallet())
+            || self
+                .disputed_wallets
+                .contains(&node.exit_earning_wallet().as_address_wallet())
+    }
+
     fn advance_return_route_id(&mut self) -> u32 {
         let return_route_id = self.next_return_route_id;
         self.next_return_route_id = return_route_id.wrapping_add(1);
@@ -852,6 +1683,9 @@ impl Neighborhood {
                     node_record.routes_data()
                         || Self::is_orig_node_on_back_leg(**node_record, target_opt, direction)
                 })
+                .filter(|node_record| {
+                    !self.is_delinquent(node_record) && !self.is_disputed(node_record)
+                })
                 .flat_map(|node_record| {
                     let mut new_prefix = prefix.clone();
                     new_prefix.push(node_record.public_key());
@@ -873,6 +1707,93 @@ impl Neighborhood {
         }
     }
 
+    fn check_route_capability(&mut self) {
+        match self.diagnose_route_capability() {
+            None => info!(
+                self.logger,
+                "Route self-check passed: this Node can construct {}-hop routes",
+                DEFAULT_MINIMUM_HOP_COUNT
+            ),
+            Some(diagnosis) => {
+                warning!(self.logger, "Route self-check failed: {}", diagnosis);
+                self.ui_message_sub
+                    .as_ref()
+                    .expect("UiGateway is unbound")
+                    .try_send(UiCarrierMessage {
+                        client_id: BROADCAST,
+                        data: UiMessage::RouteCapabilityDiagnosis(diagnosis),
+                    })
+                    .expect("UiGateway is dead");
+            }
+        }
+    }
+
+    // Returns None if this Node can construct the routes that the ProxyServer will ask for, or
+    // a description of what's preventing it and what to do about it.
+    fn diagnose_route_capability(&mut self) -> Option<String> {
+        let minimum_hop_count = DEFAULT_MINIMUM_HOP_COUNT;
+        let root_key = self.cryptde.public_key();
+        let round_trip = self
+            .make_route_segment(
+                root_key,
+                None,
+                minimum_hop_count,
+                Component::ProxyClient,
+                RouteDirection::Over,
+            )
+            .and_then(|over| {
+                self.make_route_segment(
+                    over.keys.last().expect("Empty segment"),
+                    Some(root_key),
+                    minimum_hop_count,
+                    Component::ProxyServer,
+                    RouteDirection::Back,
+                )
+            });
+        if round_trip.is_ok() {
+            return match self.consuming_wallet_opt {
+                Some(_) => None,
+                None => Some(format!(
+                    "This Node can construct {}-hop routes, but it has no consuming wallet to pay for them. Supply --consuming-private-key, or set the consuming wallet password from the UI.",
+                    minimum_hop_count
+                )),
+            };
+        }
+        let neighbor_count = self
+            .neighborhood_database
+            .root()
+            .full_neighbors(&self.neighborhood_database)
+            .len();
+        let routing_node_count = self
+            .neighborhood_database
+            .keys()
+            .into_iter()
+            .filter(|key| *key != root_key)
+            .flat_map(|key| self.neighborhood_database.node_by_key(key))
+            .filter(|node| node.routes_data())
+            .count();
+        Some(if neighbor_count == 0 {
+            format!(
+                "This Node has no neighbors, but min-hops is {}. Make sure --neighbors is set and that those Nodes are running and reachable.",
+                minimum_hop_count
+            )
+        } else if routing_node_count < minimum_hop_count {
+            format!(
+                "You have {} neighbor{} and know of {} Node{} that route data, but min-hops is {}. Wait for more Gossip, or add more --neighbors.",
+                neighbor_count,
+                if neighbor_count == 1 { "" } else { "s" },
+                routing_node_count,
+                if routing_node_count == 1 { "" } else { "s" },
+                minimum_hop_count
+            )
+        } else {
+            format!(
+                "You know of {} Nodes that route data, but none of them can complete a {}-hop route out and back: the possible exit Nodes are all too close to this one. Add --neighbors from elsewhere in the Network.",
+                routing_node_count, minimum_hop_count
+            )
+        })
+    }
+
     fn handle_gossip_reply(
         &self,
         next_debut_gossip: Gossip,
@@ -935,13 +1856,21 @@ impl Neighborhood {
         if msg.stream_type != RemovedStreamType::Clandestine {
             panic!("Neighborhood should never get ShutdownStreamMsg about non-clandestine stream")
         }
-        let neighbor_key = match self.neighborhood_database.node_by_ip(&msg.peer_addr.ip()) {
+        let (neighbor_key, node_addr_opt) = match self
+            .neighborhood_database
+            .node_by_ip(&msg.peer_addr.ip())
+        {
             None => {
                 warning!(self.logger, "Received shutdown notification for stream to {}, but no Node with that IP is in the database - ignoring", msg.peer_addr.ip());
                 return;
             }
-            Some(n) => (n.public_key().clone()),
+            Some(n) => (n.public_key().clone(), n.node_addr_opt()),
         };
+        if let Some(node_addr) = node_addr_opt {
+            if self.present_resumption_ticket(&neighbor_key, node_addr) {
+                return;
+            }
+        }
         self.remove_neighbor(&neighbor_key, &msg.peer_addr);
     }
 
@@ -949,6 +1878,7 @@ impl Neighborhood {
         match self.neighborhood_database.remove_neighbor(neighbor_key) {
             Err(_) => panic!("Node suddenly disappeared"),
             Ok(true) => {
+                self.route_cache.invalidate();
                 debug!(
                     self.logger,
                     "Received shutdown notification for {} at {}: removing neighborship",
@@ -982,12 +1912,13 @@ mod tests {
     use super::*;
     use crate::blockchain::blockchain_interface::contract_address;
     use crate::neighborhood::gossip::Gossip;
-    use crate::neighborhood::gossip::GossipBuilder;
+    use crate::neighborhood::gossip::GossipNodeRecord;
     use crate::neighborhood::neighborhood_test_utils::*;
     use crate::neighborhood::node_record::NodeRecordInner;
+    use crate::neighborhood::resumption::RESUMPTION_TICKET_LIFETIME;
     use crate::persistent_configuration::TLS_PORT;
     use crate::stream_messages::{NonClandestineAttributes, RemovedStreamType};
-    use crate::sub_lib::cryptde::{decodex, encodex, CryptData};
+    use crate::sub_lib::cryptde::{decodex, encodex, CryptData, PlainData};
     use crate::sub_lib::cryptde_null::CryptDENull;
     use crate::sub_lib::dispatcher::Endpoint;
     use crate::sub_lib::hop::LiveHop;
@@ -995,6 +1926,8 @@ mod tests {
     use crate::sub_lib::neighborhood::{ExpectedServices, NeighborhoodMode};
     use crate::sub_lib::neighborhood::{NeighborhoodConfig, DEFAULT_RATE_PACK};
     use crate::sub_lib::stream_handler_pool::TransmitDataMsg;
+    use crate::test_utils::clock_mock::ClockMock;
+    use crate::test_utils::ensure_node_home_directory_exists;
     use crate::test_utils::logging::init_test_logging;
     use crate::test_utils::logging::TestLogHandler;
     use crate::test_utils::rate_pack;
@@ -1016,6 +1949,7 @@ mod tests {
     use std::convert::TryInto;
     use std::net::{IpAddr, SocketAddr};
     use std::str::FromStr;
+    use std::sync::mpsc;
     use std::sync::{Arc, Mutex};
     use std::thread;
     use tokio::prelude::Future;
@@ -1230,119 +2164,440 @@ mod tests {
     }
 
     #[test]
-    fn node_query_responds_with_none_when_initially_configured_with_no_data() {
-        let system = System::new("responds_with_none_when_initially_configured_with_no_data");
-        let subject = make_standard_subject();
+    fn node_statistics_count_full_neighbors() {
+        let system = System::new("node_statistics_count_full_neighbors");
+        let (_, _, _, subject) = make_o_r_e_subject();
         let addr: Addr<Neighborhood> = subject.start();
-        let sub: Recipient<NodeQueryMessage> = addr.recipient::<NodeQueryMessage>();
+        let sub: Recipient<GetNodeStatisticsMessage> = addr.recipient::<GetNodeStatisticsMessage>();
 
-        let future = sub.send(NodeQueryMessage::PublicKey(PublicKey::new(&b"booga"[..])));
+        let future = sub.send(GetNodeStatisticsMessage {});
 
         System::current().stop_with_code(0);
         system.run();
         let result = future.wait().unwrap();
-        assert_eq!(result.is_none(), true);
+        assert_eq!(
+            result,
+            NodeStatistics {
+                neighbor_count: 1,
+                ..NodeStatistics::default()
+            }
+        );
     }
 
     #[test]
-    fn node_query_responds_with_none_when_key_query_matches_no_configured_data() {
-        let cryptde = cryptde();
-        let earning_wallet = make_wallet("earning");
-        let consuming_wallet = Some(make_paying_wallet(b"consuming"));
-        let system =
-            System::new("node_query_responds_with_none_when_key_query_matches_no_configured_data");
-        let subject = Neighborhood::new(
-            cryptde,
-            &bc_from_nc_plus(
-                NeighborhoodConfig {
-                    mode: NeighborhoodMode::Standard(
-                        NodeAddr::new(&IpAddr::from_str("5.4.3.2").unwrap(), &vec![5678]),
-                        vec![NodeDescriptor::from((
-                            &PublicKey::new(&b"booga"[..]),
-                            &NodeAddr::new(
-                                &IpAddr::from_str("1.2.3.4").unwrap(),
-                                &vec![1234, 2345],
-                            ),
-                        ))
-                        .to_string(cryptde, DEFAULT_CHAIN_ID)],
-                        rate_pack(100),
-                    ),
-                },
-                earning_wallet.clone(),
-                consuming_wallet.clone(),
-            ),
-        );
+    fn health_reports_the_degree_of_a_decentralized_node() {
+        let system = System::new("health_reports_the_degree_of_a_decentralized_node");
+        let (_, _, _, subject) = make_o_r_e_subject();
         let addr: Addr<Neighborhood> = subject.start();
-        let sub: Recipient<NodeQueryMessage> = addr.recipient::<NodeQueryMessage>();
+        let sub: Recipient<GetHealthMessage> = addr.recipient::<GetHealthMessage>();
 
-        let future = sub.send(NodeQueryMessage::PublicKey(PublicKey::new(&b"blah"[..])));
+        let future = sub.send(GetHealthMessage {});
 
         System::current().stop_with_code(0);
         system.run();
         let result = future.wait().unwrap();
-        assert_eq!(result.is_none(), true);
+        assert_eq!(
+            result,
+            vec![ComponentHealth::new(
+                "neighborhood",
+                HealthState::Ready,
+                "degree 1"
+            )]
+        );
     }
 
     #[test]
-    fn node_query_responds_with_result_when_key_query_matches_configured_data() {
-        let cryptde = cryptde();
-        let earning_wallet = make_wallet("earning");
-        let consuming_wallet = Some(make_paying_wallet(b"consuming"));
-        let system =
-            System::new("node_query_responds_with_result_when_key_query_matches_configured_data");
-        let one_neighbor = make_node_record(2345, true);
-        let another_neighbor = make_node_record(3456, true);
-        let mut subject = Neighborhood::new(
-            cryptde,
-            &bc_from_nc_plus(
-                NeighborhoodConfig {
-                    mode: NeighborhoodMode::Standard(
-                        NodeAddr::new(&IpAddr::from_str("5.4.3.2").unwrap(), &vec![5678]),
-                        vec![node_record_to_neighbor_config(&one_neighbor, cryptde)],
-                        rate_pack(100),
-                    ),
-                },
-                earning_wallet.clone(),
-                consuming_wallet.clone(),
-            ),
+    fn health_says_a_decentralized_node_without_neighbors_is_not_ready() {
+        let system = System::new("health_says_a_decentralized_node_without_neighbors_is_not_ready");
+        let subject = make_standard_subject();
+        let addr: Addr<Neighborhood> = subject.start();
+        let sub: Recipient<GetHealthMessage> = addr.recipient::<GetHealthMessage>();
+
+        let future = sub.send(GetHealthMessage {});
+
+        System::current().stop_with_code(0);
+        system.run();
+        let result = future.wait().unwrap();
+        assert_eq!(
+            result,
+            vec![ComponentHealth::new(
+                "neighborhood",
+                HealthState::NotReady,
+                "degree 0"
+            )]
         );
-        subject
-            .neighborhood_database
-            .add_node(another_neighbor.clone())
-            .unwrap();
+    }
+
+    #[test]
+    fn health_says_a_zero_hop_node_needs_no_neighbors() {
+        let system = System::new("health_says_a_zero_hop_node_needs_no_neighbors");
+        let mut subject = make_standard_subject();
+        subject.is_decentralized = false;
         let addr: Addr<Neighborhood> = subject.start();
-        let sub: Recipient<NodeQueryMessage> = addr.recipient::<NodeQueryMessage>();
+        let sub: Recipient<GetHealthMessage> = addr.recipient::<GetHealthMessage>();
 
-        let future = sub.send(NodeQueryMessage::PublicKey(
-            another_neighbor.public_key().clone(),
-        ));
+        let future = sub.send(GetHealthMessage {});
 
         System::current().stop_with_code(0);
         system.run();
         let result = future.wait().unwrap();
         assert_eq!(
-            result.unwrap(),
-            NodeQueryResponseMetadata::new(
-                another_neighbor.public_key().clone(),
-                Some(another_neighbor.node_addr_opt().unwrap().clone()),
-                another_neighbor.rate_pack().clone(),
-            )
+            result,
+            vec![ComponentHealth::new(
+                "neighborhood",
+                HealthState::Ready,
+                "zero-hop; no neighbors needed"
+            )]
         );
     }
 
     #[test]
-    fn node_query_responds_with_none_when_ip_address_query_matches_no_configured_data() {
-        let cryptde = cryptde();
-        let earning_wallet = make_wallet("earning");
-        let consuming_wallet = Some(make_paying_wallet(b"consuming"));
-        let system = System::new(
-            "node_query_responds_with_none_when_ip_address_query_matches_no_configured_data",
+    fn neighbor_summaries_describe_full_neighbors_only() {
+        let (_, r, e, mut subject) = make_o_r_e_subject();
+        let now = SystemTime::now();
+        subject.neighbor_activity.insert(
+            r.public_key().clone(),
+            NeighborActivity {
+                neighbor_since: now - Duration::from_secs(100),
+                last_gossip_opt: Some(now - Duration::from_secs(5)),
+            },
         );
-        let subject = Neighborhood::new(
-            cryptde,
-            &bc_from_nc_plus(
-                NeighborhoodConfig {
-                    mode: NeighborhoodMode::Standard(
+        subject.neighbor_activity.insert(
+            e.public_key().clone(),
+            NeighborActivity {
+                neighbor_since: now - Duration::from_secs(1000),
+                last_gossip_opt: None,
+            },
+        );
+        let r_consuming_wallet = make_paying_wallet(b"r consuming");
+        subject.debtor_keys.insert(
+            r_consuming_wallet.as_address_wallet(),
+            r.public_key().clone(),
+        );
+        subject
+            .delinquent_wallets
+            .insert(r_consuming_wallet.as_address_wallet());
+
+        let result = subject.neighbor_summaries(now);
+
+        let public_key = r.public_key().to_string();
+        assert_eq!(
+            result,
+            vec![NeighborSummary {
+                key_fingerprint: public_key.chars().take(8).collect(),
+                public_key,
+                node_addr: r.node_addr_opt().map(|node_addr| node_addr.to_string()),
+                neighbor_for_secs: 100,
+                version: r.version(),
+                secs_since_last_gossip: Some(5),
+                reputation: NeighborReputation::Delinquent,
+            }]
+        );
+        assert_eq!(
+            subject
+                .neighbor_activity
+                .keys()
+                .collect::<Vec<&PublicKey>>(),
+            vec![r.public_key()]
+        );
+    }
+
+    #[test]
+    fn neighbors_are_timed_from_when_they_are_noticed_and_gossip_from_them_is_noted() {
+        let mut subject = make_standard_subject();
+        let root_key = subject.neighborhood_database.root().public_key().clone();
+        let mut r = make_node_record(3456, true);
+        r.set_desirable(false);
+        subject.neighborhood_database.add_node(r.clone()).unwrap();
+        subject
+            .neighborhood_database
+            .add_arbitrary_full_neighbor(&root_key, r.public_key());
+        let then = SystemTime::now() - Duration::from_secs(60);
+        let gossip_source: SocketAddr = SocketAddr::new(
+            r.node_addr_opt().unwrap().ip_addr(),
+            r.node_addr_opt().unwrap().ports()[0],
+        );
+
+        subject.note_gossip_from(gossip_source, then);
+        subject.note_gossip_from(SocketAddr::from_str("5.6.7.8:1234").unwrap(), then);
+
+        let result = subject.neighbor_summaries(then + Duration::from_secs(10));
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].neighbor_for_secs, 10);
+        assert_eq!(result[0].secs_since_last_gossip, Some(10));
+        assert_eq!(result[0].reputation, NeighborReputation::Undesirable);
+    }
+
+    #[test]
+    fn neighbor_list_request_is_answered_to_the_requesting_client() {
+        let (ui_gateway, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        thread::spawn(move || {
+            let system = System::new("neighbor_list_request_is_answered_to_the_requesting_client");
+            let (_, _, _, mut subject) = make_o_r_e_subject();
+            subject.ui_message_sub = Some(ui_gateway.start().recipient::<UiCarrierMessage>());
+            let addr: Addr<Neighborhood> = subject.start();
+
+            addr.try_send(NeighborListRequest { client_id: 1234 })
+                .unwrap();
+
+            system.run();
+        });
+
+        ui_gateway_awaiter.await_message_count(1);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        let response = ui_gateway_recording.get_record::<UiCarrierMessage>(0);
+        assert_eq!(response.client_id, 1234);
+        match &response.data {
+            UiMessage::NeighborList(neighbors) => {
+                assert_eq!(neighbors.len(), 1);
+                assert_eq!(neighbors[0].reputation, NeighborReputation::Good);
+                assert_eq!(neighbors[0].secs_since_last_gossip, None);
+            }
+            other => panic!("Expected NeighborList, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn known_nodes_are_neighbored_as_their_records_say() {
+        let mut relay = make_node_record(4567, true);
+        let mut exit = make_node_record(5678, true);
+        let root_key = cryptde().public_key().clone();
+        relay.add_half_neighbor_key(root_key.clone()).unwrap();
+        relay
+            .add_half_neighbor_key(exit.public_key().clone())
+            .unwrap();
+        exit.add_half_neighbor_key(relay.public_key().clone())
+            .unwrap();
+
+        let mut subject =
+            make_standard_subject().with_known_nodes(vec![relay.clone(), exit.clone()]);
+
+        let db = &subject.neighborhood_database;
+        assert_eq!(db.has_full_neighbor(&root_key, relay.public_key()), true);
+        assert_eq!(
+            db.has_full_neighbor(relay.public_key(), exit.public_key()),
+            true
+        );
+        assert_eq!(db.has_half_neighbor(&root_key, exit.public_key()), false);
+        let result =
+            subject.make_round_trip_route(RouteQueryMessage::data_indefinite_route_request(2));
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+
+    #[test]
+    fn repeated_route_queries_are_answered_from_the_route_cache() {
+        let (_, _, _, mut subject) = make_o_r_e_subject();
+
+        let first = subject
+            .make_round_trip_route(RouteQueryMessage::data_indefinite_route_request(2))
+            .unwrap();
+        let second = subject
+            .make_round_trip_route(RouteQueryMessage::data_indefinite_route_request(2))
+            .unwrap();
+
+        match (first.expected_services, second.expected_services) {
+            (
+                ExpectedServices::RoundTrip(first_over, first_back, _),
+                ExpectedServices::RoundTrip(second_over, second_back, _),
+            ) => {
+                assert_eq!(first_over, second_over);
+                assert_eq!(first_back, second_back);
+            }
+            other => panic!("Expected RoundTrips, got {:?}", other),
+        }
+        assert_eq!(subject.route_cache.misses(), 2);
+        assert_eq!(subject.route_cache.hits(), 2);
+        assert_eq!(subject.route_cache.len(), 2);
+    }
+
+    #[test]
+    fn route_cache_is_not_consulted_for_different_constraints() {
+        let (_, _, _, mut subject) = make_o_r_e_subject();
+
+        let two_hop =
+            subject.make_round_trip_route(RouteQueryMessage::data_indefinite_route_request(2));
+        let three_hop =
+            subject.make_round_trip_route(RouteQueryMessage::data_indefinite_route_request(3));
+        let three_hop_again =
+            subject.make_round_trip_route(RouteQueryMessage::data_indefinite_route_request(3));
+
+        assert!(two_hop.is_ok());
+        assert!(three_hop.is_err());
+        assert!(three_hop_again.is_err());
+        assert_eq!(subject.route_cache.misses(), 3);
+        assert_eq!(subject.route_cache.hits(), 1);
+    }
+
+    #[test]
+    fn route_cache_hit_rate_is_logged_periodically() {
+        init_test_logging();
+        let (_, _, _, mut subject) = make_o_r_e_subject();
+        subject.logger = Logger::new("route_cache_hit_rate_is_logged_periodically");
+
+        (0..(ROUTE_CACHE_REPORT_INTERVAL / 2)).for_each(|_| {
+            subject
+                .make_round_trip_route(RouteQueryMessage::data_indefinite_route_request(2))
+                .unwrap();
+        });
+
+        TestLogHandler::new().exists_log_containing(
+            "INFO: route_cache_hit_rate_is_logged_periodically: Route cache: 1000 route segments looked up: 998 served from cache (99.8% hit rate)",
+        );
+    }
+
+    #[test]
+    fn neighbor_list_request_tells_neighbor_ages_by_the_neighborhoods_clock() {
+        let clock = ClockMock::new(SystemTime::now());
+        let subject_clock = clock.clone();
+        let (ui_gateway, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let system =
+                System::new("neighbor_list_request_tells_neighbor_ages_by_the_neighborhoods_clock");
+            let (_, _, _, subject) = make_o_r_e_subject();
+            let mut subject = subject.with_clock(Box::new(subject_clock));
+            subject.ui_message_sub = Some(ui_gateway.start().recipient::<UiCarrierMessage>());
+            let addr: Addr<Neighborhood> = subject.start();
+            tx.send(addr.recipient::<NeighborListRequest>()).unwrap();
+
+            system.run();
+        });
+        let neighbor_list_sub = rx.recv().unwrap();
+        neighbor_list_sub
+            .try_send(NeighborListRequest { client_id: 1234 })
+            .unwrap();
+        ui_gateway_awaiter.await_message_count(1);
+
+        clock.advance(Duration::from_secs(100));
+        neighbor_list_sub
+            .try_send(NeighborListRequest { client_id: 1234 })
+            .unwrap();
+
+        ui_gateway_awaiter.await_message_count(2);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        match &ui_gateway_recording.get_record::<UiCarrierMessage>(1).data {
+            UiMessage::NeighborList(neighbors) => {
+                assert_eq!(neighbors.len(), 1);
+                assert_eq!(neighbors[0].neighbor_for_secs, 100);
+            }
+            other => panic!("Expected NeighborList, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn node_query_responds_with_none_when_initially_configured_with_no_data() {
+        let system = System::new("responds_with_none_when_initially_configured_with_no_data");
+        let subject = make_standard_subject();
+        let addr: Addr<Neighborhood> = subject.start();
+        let sub: Recipient<NodeQueryMessage> = addr.recipient::<NodeQueryMessage>();
+
+        let future = sub.send(NodeQueryMessage::PublicKey(PublicKey::new(&b"booga"[..])));
+
+        System::current().stop_with_code(0);
+        system.run();
+        let result = future.wait().unwrap();
+        assert_eq!(result.is_none(), true);
+    }
+
+    #[test]
+    fn node_query_responds_with_none_when_key_query_matches_no_configured_data() {
+        let cryptde = cryptde();
+        let earning_wallet = make_wallet("earning");
+        let consuming_wallet = Some(make_paying_wallet(b"consuming"));
+        let system =
+            System::new("node_query_responds_with_none_when_key_query_matches_no_configured_data");
+        let subject = Neighborhood::new(
+            cryptde,
+            &bc_from_nc_plus(
+                NeighborhoodConfig {
+                    mode: NeighborhoodMode::Standard(
+                        NodeAddr::new(&IpAddr::from_str("5.4.3.2").unwrap(), &vec![5678]),
+                        vec![NodeDescriptor::from((
+                            &PublicKey::new(&b"booga"[..]),
+                            &NodeAddr::new(
+                                &IpAddr::from_str("1.2.3.4").unwrap(),
+                                &vec![1234, 2345],
+                            ),
+                        ))
+                        .to_string(cryptde, DEFAULT_CHAIN_ID)],
+                        rate_pack(100),
+                    ),
+                },
+                earning_wallet.clone(),
+                consuming_wallet.clone(),
+            ),
+        );
+        let addr: Addr<Neighborhood> = subject.start();
+        let sub: Recipient<NodeQueryMessage> = addr.recipient::<NodeQueryMessage>();
+
+        let future = sub.send(NodeQueryMessage::PublicKey(PublicKey::new(&b"blah"[..])));
+
+        System::current().stop_with_code(0);
+        system.run();
+        let result = future.wait().unwrap();
+        assert_eq!(result.is_none(), true);
+    }
+
+    #[test]
+    fn node_query_responds_with_result_when_key_query_matches_configured_data() {
+        let cryptde = cryptde();
+        let earning_wallet = make_wallet("earning");
+        let consuming_wallet = Some(make_paying_wallet(b"consuming"));
+        let system =
+            System::new("node_query_responds_with_result_when_key_query_matches_configured_data");
+        let one_neighbor = make_node_record(2345, true);
+        let another_neighbor = make_node_record(3456, true);
+        let mut subject = Neighborhood::new(
+            cryptde,
+            &bc_from_nc_plus(
+                NeighborhoodConfig {
+                    mode: NeighborhoodMode::Standard(
+                        NodeAddr::new(&IpAddr::from_str("5.4.3.2").unwrap(), &vec![5678]),
+                        vec![node_record_to_neighbor_config(&one_neighbor, cryptde)],
+                        rate_pack(100),
+                    ),
+                },
+                earning_wallet.clone(),
+                consuming_wallet.clone(),
+            ),
+        );
+        subject
+            .neighborhood_database
+            .add_node(another_neighbor.clone())
+            .unwrap();
+        let addr: Addr<Neighborhood> = subject.start();
+        let sub: Recipient<NodeQueryMessage> = addr.recipient::<NodeQueryMessage>();
+
+        let future = sub.send(NodeQueryMessage::PublicKey(
+            another_neighbor.public_key().clone(),
+        ));
+
+        System::current().stop_with_code(0);
+        system.run();
+        let result = future.wait().unwrap();
+        assert_eq!(
+            result.unwrap(),
+            NodeQueryResponseMetadata::new(
+                another_neighbor.public_key().clone(),
+                Some(another_neighbor.node_addr_opt().unwrap().clone()),
+                another_neighbor.rate_pack().clone(),
+            )
+        );
+    }
+
+    #[test]
+    fn node_query_responds_with_none_when_ip_address_query_matches_no_configured_data() {
+        let cryptde = cryptde();
+        let earning_wallet = make_wallet("earning");
+        let consuming_wallet = Some(make_paying_wallet(b"consuming"));
+        let system = System::new(
+            "node_query_responds_with_none_when_ip_address_query_matches_no_configured_data",
+        );
+        let subject = Neighborhood::new(
+            cryptde,
+            &bc_from_nc_plus(
+                NeighborhoodConfig {
+                    mode: NeighborhoodMode::Standard(
                         NodeAddr::new(&IpAddr::from_str("5.4.3.2").unwrap(), &vec![5678]),
                         vec![NodeDescriptor::from((
                             &PublicKey::new(&b"booga"[..]),
@@ -1436,6 +2691,191 @@ mod tests {
         assert_eq!(result, None);
     }
 
+    #[test]
+    fn unsatisfied_route_query_is_published_as_a_route_failure() {
+        let (ui_gateway, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        thread::spawn(move || {
+            let system = System::new("unsatisfied_route_query_is_published_as_a_route_failure");
+            let mut subject = make_standard_subject();
+            subject.node_event_sub = Some(ui_gateway.start().recipient::<NodeEvent>());
+            let addr: Addr<Neighborhood> = subject.start();
+
+            addr.try_send(RouteQueryMessage::data_indefinite_route_request(5))
+                .unwrap();
+
+            system.run();
+        });
+
+        ui_gateway_awaiter.await_message_count(1);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        match ui_gateway_recording.get_record::<NodeEvent>(0) {
+            NodeEvent::RouteFailure { reason } => assert_eq!(reason.is_empty(), false),
+            x => panic!("Expected RouteFailure, got {:?}", x),
+        }
+        assert_eq!(ui_gateway_recording.len(), 1);
+    }
+
+    #[test]
+    fn gossiping_publishes_neighbor_and_descriptor_changes() {
+        let system = System::new("gossiping_publishes_neighbor_and_descriptor_changes");
+        let (hopper, _, _) = make_recorder();
+        let (ui_gateway, _, ui_gateway_recording_arc) = make_recorder();
+        let mut subject = make_standard_subject();
+        subject.hopper = Some(hopper.start().recipient());
+        subject.node_event_sub = Some(ui_gateway.start().recipient::<NodeEvent>());
+        let root_key = subject.neighborhood_database.root().public_key().clone();
+        let new_neighbor = make_node_record(3456, true);
+        let departed_key = PublicKey::new(&b"departed"[..]);
+        subject
+            .neighborhood_database
+            .add_node(new_neighbor.clone())
+            .unwrap();
+        subject
+            .neighborhood_database
+            .add_arbitrary_full_neighbor(&root_key, new_neighbor.public_key());
+        subject.reported_neighbors = vec![departed_key.clone()].into_iter().collect();
+        subject.neighborhood_database.root_mut().unset_node_addr();
+        subject
+            .neighborhood_database
+            .root_mut()
+            .set_node_addr(&NodeAddr::new(
+                &IpAddr::from_str("4.3.2.1").unwrap(),
+                &vec![4321],
+            ))
+            .unwrap();
+        let expected_descriptor = subject
+            .neighborhood_database
+            .root()
+            .node_descriptor(subject.cryptde, subject.chain_id);
+
+        subject.gossip_to_neighbors();
+        subject.gossip_to_neighbors();
+
+        System::current().stop_with_code(0);
+        system.run();
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        let events = (0..ui_gateway_recording.len())
+            .map(|index| ui_gateway_recording.get_record::<NodeEvent>(index).clone())
+            .collect::<Vec<NodeEvent>>();
+        assert_eq!(
+            events,
+            vec![
+                NodeEvent::NeighborConnected {
+                    public_key: new_neighbor.public_key().to_string()
+                },
+                NodeEvent::NeighborDisconnected {
+                    public_key: departed_key.to_string()
+                },
+                NodeEvent::DescriptorChanged {
+                    node_descriptor: expected_descriptor
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn neighbor_changes_are_remembered_in_the_snapshot() {
+        let home_dir = ensure_node_home_directory_exists(
+            "neighborhood",
+            "neighbor_changes_are_remembered_in_the_snapshot",
+        );
+        let snapshot_path = home_dir.join(NEIGHBORHOOD_SNAPSHOT_FILE);
+        let system = System::new("neighbor_changes_are_remembered_in_the_snapshot");
+        let (hopper, _, _) = make_recorder();
+        let mut subject = make_standard_subject().with_snapshot_file(snapshot_path.clone());
+        subject.hopper = Some(hopper.start().recipient());
+        let root_key = subject.neighborhood_database.root().public_key().clone();
+        let neighbor = make_node_record(3456, true);
+        let neighbor_without_address = make_node_record(4567, false);
+        subject
+            .neighborhood_database
+            .add_node(neighbor.clone())
+            .unwrap();
+        subject
+            .neighborhood_database
+            .add_node(neighbor_without_address.clone())
+            .unwrap();
+        subject
+            .neighborhood_database
+            .add_arbitrary_full_neighbor(&root_key, neighbor.public_key());
+        subject
+            .neighborhood_database
+            .add_arbitrary_full_neighbor(&root_key, neighbor_without_address.public_key());
+
+        subject.gossip_to_neighbors();
+
+        System::current().stop_with_code(0);
+        system.run();
+        assert_eq!(
+            fs::read_to_string(&snapshot_path).unwrap(),
+            format!(
+                "{}\n",
+                neighbor.node_descriptor(subject.cryptde, subject.chain_id)
+            )
+        );
+    }
+
+    #[test]
+    fn node_debuts_to_the_neighbors_in_its_snapshot_on_startup() {
+        init_test_logging();
+        let home_dir = ensure_node_home_directory_exists(
+            "neighborhood",
+            "node_debuts_to_the_neighbors_in_its_snapshot_on_startup",
+        );
+        let snapshot_path = home_dir.join(NEIGHBORHOOD_SNAPSHOT_FILE);
+        let cryptde = cryptde();
+        let neighbor = make_node_record(1234, true);
+        fs::write(
+            &snapshot_path,
+            format!(
+                "{}\nbooga\n",
+                neighbor.node_descriptor(cryptde, DEFAULT_CHAIN_ID)
+            ),
+        )
+        .unwrap();
+        let hopper = Recorder::new();
+        let hopper_awaiter = hopper.get_awaiter();
+        let hopper_recording = hopper.get_recording();
+        let subject = Neighborhood::new(
+            cryptde,
+            &bc_from_nc_plus(
+                NeighborhoodConfig {
+                    mode: NeighborhoodMode::Standard(
+                        NodeAddr::new(&IpAddr::from_str("5.4.3.2").unwrap(), &vec![1234]),
+                        vec![],
+                        rate_pack(100),
+                    ),
+                },
+                NodeRecord::earning_wallet_from_key(&cryptde.public_key()),
+                NodeRecord::consuming_wallet_from_key(&cryptde.public_key()),
+            ),
+        )
+        .with_snapshot_file(snapshot_path.clone());
+        thread::spawn(move || {
+            let system = System::new("node_debuts_to_the_neighbors_in_its_snapshot_on_startup");
+            let addr: Addr<Neighborhood> = subject.start();
+            let peer_actors = peer_actors_builder().hopper(hopper).build();
+            addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            addr.try_send(StartMessage {}).unwrap();
+
+            system.run();
+        });
+        hopper_awaiter.await_message_count(1);
+        let locked_recording = hopper_recording.lock().unwrap();
+        let package_ref: &NoLookupIncipientCoresPackage = locked_recording.get_record(0);
+        assert_eq!(&package_ref.public_key, neighbor.public_key());
+        assert_eq!(
+            Some(package_ref.node_addr.clone()),
+            neighbor.node_addr_opt()
+        );
+        assert_eq!(locked_recording.len(), 1);
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: Neighborhood: Ignoring unusable Node descriptor booga in {}",
+            snapshot_path.display()
+        ));
+    }
+
     #[test]
     fn route_query_responds_with_none_when_asked_for_two_hop_round_trip_route_without_consuming_wallet(
     ) {
@@ -1738,6 +3178,46 @@ mod tests {
         assert_eq!(expected_response, result);
     }
 
+    #[test]
+    fn route_query_pays_with_the_consuming_wallet_it_names() {
+        let cryptde = cryptde();
+        let system = System::new("route_query_pays_with_the_consuming_wallet_it_names");
+        let mut subject = make_standard_subject();
+        subject.consuming_wallet_opt = None;
+        let p = &subject.neighborhood_database.root().clone();
+        let q = &make_node_record(3456, true);
+        let r = &make_node_record(4567, false);
+        {
+            let db = &mut subject.neighborhood_database;
+            db.add_node(q.clone()).unwrap();
+            db.add_node(r.clone()).unwrap();
+            db.add_arbitrary_full_neighbor(p.public_key(), q.public_key());
+            db.add_arbitrary_full_neighbor(q.public_key(), r.public_key());
+        }
+        let addr: Addr<Neighborhood> = subject.start();
+        let sub: Recipient<RouteQueryMessage> = addr.recipient::<RouteQueryMessage>();
+        let mut msg = RouteQueryMessage::data_indefinite_route_request(2);
+        msg.consuming_wallet_opt = Some(make_paying_wallet(b"profile"));
+
+        let data_route = sub.send(msg);
+
+        System::current().stop_with_code(0);
+        system.run();
+        let result = data_route.wait().unwrap().unwrap();
+        assert_eq!(
+            result.route,
+            Route::round_trip(
+                segment(&[p, q, r], &Component::ProxyClient),
+                segment(&[r, q, p], &Component::ProxyServer),
+                cryptde,
+                Some(make_paying_wallet(b"profile")),
+                0,
+                Some(contract_address(DEFAULT_CHAIN_ID)),
+            )
+            .unwrap()
+        );
+    }
+
     #[test]
     fn sort_routes_by_desirable_exit_nodes() {
         let mut subject = make_standard_subject();
@@ -1841,6 +3321,7 @@ mod tests {
         let result: Result<RouteQueryResponse, String> = subject.compose_route_query_response(
             RouteSegment::new(vec![], Component::Neighborhood),
             RouteSegment::new(vec![], Component::Neighborhood),
+            None,
         );
         assert!(result.is_err());
         let error_expectation: String = result.expect_err("Expected an Err but got:");
@@ -1953,6 +3434,7 @@ mod tests {
         let result: Result<RouteQueryResponse, String> = subject.compose_route_query_response(
             RouteSegment::new(vec![], Component::ProxyClient),
             RouteSegment::new(vec![], Component::ProxyServer),
+            None,
         );
         assert!(result.is_err());
         let error_expectation: String = result.expect_err("Expected an Err but got:");
@@ -1969,6 +3451,7 @@ mod tests {
         let result: Result<RouteQueryResponse, String> = subject.compose_route_query_response(
             RouteSegment::new(vec![&PublicKey::new(&[3, 3, 8])], Component::ProxyClient),
             RouteSegment::new(vec![&PublicKey::new(&[8, 3, 3])], Component::ProxyServer),
+            None,
         );
         assert!(result.is_err());
         let error_expectation: String = result.expect_err("Expected an Err but got:");
@@ -1994,6 +3477,136 @@ mod tests {
         );
     }
 
+    #[test]
+    fn calculate_expected_service_pays_exit_services_to_exit_earning_wallet() {
+        let mut subject = make_standard_subject();
+        let originator = subject.neighborhood_database.root().public_key().clone();
+        let mut relay = make_node_record(3456, true);
+        relay.set_exit_earning_wallet_opt(Some(make_wallet("relay exit")));
+        let mut exit = make_node_record(4567, true);
+        exit.set_exit_earning_wallet_opt(Some(make_wallet("exit exit")));
+        let db = &mut subject.neighborhood_database;
+        db.add_node(relay.clone()).unwrap();
+        db.add_node(exit.clone()).unwrap();
+
+        let relay_result = subject.calculate_expected_service(
+            relay.public_key(),
+            Some(&originator),
+            Some(exit.public_key()),
+        );
+        let exit_result = subject.calculate_expected_service(
+            exit.public_key(),
+            Some(&originator),
+            Some(exit.public_key()),
+        );
+
+        assert_eq!(
+            relay_result,
+            Ok(ExpectedService::Routing(
+                relay.public_key().clone(),
+                relay.earning_wallet(),
+                rate_pack(3456),
+            ))
+        );
+        assert_eq!(
+            exit_result,
+            Ok(ExpectedService::Exit(
+                exit.public_key().clone(),
+                make_wallet("exit exit"),
+                rate_pack(4567),
+            ))
+        );
+    }
+
+    #[test]
+    fn neighborhood_reports_billing_claims_from_known_creditors_to_the_accountant() {
+        init_test_logging();
+        let subject_node = make_global_cryptde_node_record(5555, true);
+        let mut subject = neighborhood_from_nodes(&subject_node, None);
+        let mut exit = make_node_record(1234, true);
+        exit.set_exit_earning_wallet_opt(Some(make_wallet("exit exit")));
+        let plain = make_node_record(2345, true);
+        let stranger = make_node_record(3456, true);
+        subject
+            .neighborhood_database
+            .add_node(exit.clone())
+            .unwrap();
+        subject
+            .neighborhood_database
+            .add_node(plain.clone())
+            .unwrap();
+        let (accountant, _, accountant_recording_arc) = make_recorder();
+        let system = System::new(
+            "neighborhood_reports_billing_claims_from_known_creditors_to_the_accountant",
+        );
+        let addr: Addr<Neighborhood> = subject.start();
+        let peer_actors = peer_actors_builder().accountant(accountant).build();
+        addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        vec![
+            (&exit, 1234, false),
+            (&stranger, 3456, false),
+            (&plain, 2345, true),
+        ]
+        .into_iter()
+        .for_each(|(node, claimed_gwub, shared_wallet)| {
+            addr.try_send(BillingClaimMessage {
+                creditor_key: node.public_key().clone(),
+                claimed_gwub,
+                shared_wallet,
+            })
+            .unwrap()
+        });
+
+        System::current().stop();
+        system.run();
+        let accountant_recording = accountant_recording_arc.lock().unwrap();
+        assert_eq!(accountant_recording.len(), 2);
+        assert_eq!(
+            accountant_recording.get_record::<ReportBillingClaimMessage>(0),
+            &ReportBillingClaimMessage {
+                creditor_wallets: vec![exit.earning_wallet(), make_wallet("exit exit")],
+                claimed_gwub: 1234,
+                shared_wallet: false,
+            }
+        );
+        assert_eq!(
+            accountant_recording.get_record::<ReportBillingClaimMessage>(1),
+            &ReportBillingClaimMessage {
+                creditor_wallets: vec![plain.earning_wallet()],
+                claimed_gwub: 2345,
+                shared_wallet: true,
+            }
+        );
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: Neighborhood: Ignoring billing claim of 3456 Gwub from unknown Node {}",
+            stranger.public_key()
+        ));
+    }
+
+    #[test]
+    fn neighborhood_advertises_configured_exit_earning_wallet() {
+        let cryptde = cryptde();
+        let mut config = bc_from_nc_plus(
+            NeighborhoodConfig {
+                mode: NeighborhoodMode::ZeroHop,
+            },
+            make_wallet("earning"),
+            None,
+        );
+        config.exit_earning_wallet_opt = Some(make_wallet("exit earning"));
+
+        let subject = Neighborhood::new(cryptde, &config);
+
+        let root = subject.neighborhood_database.root();
+        assert_eq!(root.earning_wallet(), make_wallet("earning"));
+        assert_eq!(root.exit_earning_wallet(), make_wallet("exit earning"));
+        assert_eq!(
+            root.signed_gossip(),
+            &PlainData::from(serde_cbor::ser::to_vec(&root.inner).unwrap())
+        );
+    }
+
     /*
             Database:
 
@@ -2080,11 +3693,134 @@ mod tests {
         db.add_arbitrary_full_neighbor(p, q);
         db.add_arbitrary_full_neighbor(q, r);
 
-        // At least two hops from P to anywhere standard
-        let routes = subject.complete_routes(vec![p], None, 2, RouteDirection::Over);
+        // At least two hops from P to anywhere standard
+        let routes = subject.complete_routes(vec![p], None, 2, RouteDirection::Over);
+
+        let expected: Vec<Vec<&PublicKey>> = vec![];
+        assert_eq!(routes, expected);
+    }
+
+    #[test]
+    fn banned_debtors_are_left_out_of_routes_until_unbanned() {
+        init_test_logging();
+        let system = System::new("banned_debtors_are_left_out_of_routes_until_unbanned");
+        let mut subject = make_standard_subject();
+        let q = make_node_record(3456, true);
+        {
+            let db = &mut subject.neighborhood_database;
+            let p = db.root().public_key().clone();
+            db.add_node(q.clone()).unwrap();
+            db.add_arbitrary_full_neighbor(&p, q.public_key());
+        }
+        let q_consuming_wallet = make_paying_wallet(b"q consuming");
+        let addr: Addr<Neighborhood> = subject.start();
+        let route_sub = addr.clone().recipient::<RouteQueryMessage>();
+        let debtor_key_sub = addr.clone().recipient::<DebtorKeyMessage>();
+        let delinquency_sub = addr.recipient::<DelinquencyMessage>();
+
+        delinquency_sub
+            .try_send(DelinquencyMessage::Ban(q_consuming_wallet.clone()))
+            .unwrap();
+        let before_debtor_seen =
+            route_sub.send(RouteQueryMessage::data_indefinite_route_request(1));
+        debtor_key_sub
+            .try_send(DebtorKeyMessage {
+                consuming_wallet: q_consuming_wallet.clone(),
+                public_key: q.public_key().clone(),
+            })
+            .unwrap();
+        let during_ban = route_sub.send(RouteQueryMessage::data_indefinite_route_request(1));
+        delinquency_sub
+            .try_send(DelinquencyMessage::Unban(q_consuming_wallet.clone()))
+            .unwrap();
+        let after_ban = route_sub.send(RouteQueryMessage::data_indefinite_route_request(1));
+
+        System::current().stop_with_code(0);
+        system.run();
+        assert!(before_debtor_seen.wait().unwrap().is_some());
+        assert_eq!(during_ban.wait().unwrap(), None);
+        assert!(after_ban.wait().unwrap().is_some());
+        let tlh = TestLogHandler::new();
+        tlh.exists_log_containing(&format!(
+            "INFO: Neighborhood: Nodes paying with {} will no longer be used in routes until their debt is paid",
+            q_consuming_wallet
+        ));
+        tlh.exists_log_containing(&format!(
+            "INFO: Neighborhood: Nodes paying with {} may be used in routes again",
+            q_consuming_wallet
+        ));
+    }
+
+    #[test]
+    fn banning_a_nodes_earning_wallet_leaves_it_in_routes() {
+        let system = System::new("banning_a_nodes_earning_wallet_leaves_it_in_routes");
+        let mut subject = make_standard_subject();
+        let q = make_node_record(3456, true);
+        {
+            let db = &mut subject.neighborhood_database;
+            let p = db.root().public_key().clone();
+            db.add_node(q.clone()).unwrap();
+            db.add_arbitrary_full_neighbor(&p, q.public_key());
+        }
+        subject.debtor_keys.insert(
+            make_paying_wallet(b"q consuming").as_address_wallet(),
+            q.public_key().clone(),
+        );
+        let addr: Addr<Neighborhood> = subject.start();
+        let route_sub = addr.clone().recipient::<RouteQueryMessage>();
+        let delinquency_sub = addr.recipient::<DelinquencyMessage>();
+
+        delinquency_sub
+            .try_send(DelinquencyMessage::Ban(q.earning_wallet()))
+            .unwrap();
+        let during_ban = route_sub.send(RouteQueryMessage::data_indefinite_route_request(1));
+
+        System::current().stop_with_code(0);
+        system.run();
+        assert!(during_ban.wait().unwrap().is_some());
+    }
+
+    #[test]
+    fn disputed_nodes_are_left_out_of_routes_until_the_dispute_closes() {
+        init_test_logging();
+        let system = System::new("disputed_nodes_are_left_out_of_routes_until_the_dispute_closes");
+        let mut subject = make_standard_subject();
+        let mut q = make_node_record(4567, true);
+        q.set_exit_earning_wallet_opt(Some(make_wallet("q exit")));
+        {
+            let db = &mut subject.neighborhood_database;
+            let p = db.root().public_key().clone();
+            db.add_node(q.clone()).unwrap();
+            db.add_arbitrary_full_neighbor(&p, q.public_key());
+        }
+        let addr: Addr<Neighborhood> = subject.start();
+        let route_sub = addr.clone().recipient::<RouteQueryMessage>();
+        let dispute_sub = addr.recipient::<DisputeMessage>();
+
+        let before_dispute = route_sub.send(RouteQueryMessage::data_indefinite_route_request(1));
+        dispute_sub
+            .try_send(DisputeMessage::Open(make_wallet("q exit")))
+            .unwrap();
+        let during_dispute = route_sub.send(RouteQueryMessage::data_indefinite_route_request(1));
+        dispute_sub
+            .try_send(DisputeMessage::Close(make_wallet("q exit")))
+            .unwrap();
+        let after_dispute = route_sub.send(RouteQueryMessage::data_indefinite_route_request(1));
 
-        let expected: Vec<Vec<&PublicKey>> = vec![];
-        assert_eq!(routes, expected);
+        System::current().stop_with_code(0);
+        system.run();
+        assert!(before_dispute.wait().unwrap().is_some());
+        assert_eq!(during_dispute.wait().unwrap(), None);
+        assert!(after_dispute.wait().unwrap().is_some());
+        let tlh = TestLogHandler::new();
+        tlh.exists_log_containing(&format!(
+            "INFO: Neighborhood: Nodes earning to {} will no longer be used in routes while their billing claim is disputed",
+            make_wallet("q exit")
+        ));
+        tlh.exists_log_containing(&format!(
+            "INFO: Neighborhood: Nodes earning to {} may be used in routes again",
+            make_wallet("q exit")
+        ));
     }
 
     #[test]
@@ -2318,7 +4054,9 @@ mod tests {
         init_test_logging();
         let subject_node = make_global_cryptde_node_record(5555, true); // 9e7p7un06eHs6frl5A
         let neighbor = make_node_record(1000, true);
-        let mut subject = neighborhood_from_nodes(&subject_node, Some(&neighbor));
+        let now = SystemTime::now();
+        let mut subject = neighborhood_from_nodes(&subject_node, Some(&neighbor))
+            .with_clock(Box::new(ClockMock::new(now)));
         let full_neighbor = make_node_record(1234, true);
         let half_neighbor = make_node_record(2345, true);
         subject
@@ -2370,6 +4108,12 @@ mod tests {
             )
         }
         let digest_set = vec_to_set(vec![digest(package_1.clone()), digest(package_2.clone())]);
+        let ticketed_gossip = |key: &PublicKey| {
+            MessageType::Gossip(Gossip {
+                resumption_ticket_opt: Some(ResumptionTicket::issue(cryptde(), key, now)),
+                ..gossip.clone()
+            })
+        };
         assert_eq!(
             vec_to_set(vec![
                 (
@@ -2377,7 +4121,7 @@ mod tests {
                     encodex(
                         cryptde(),
                         full_neighbor.public_key(),
-                        &MessageType::Gossip(gossip.clone()),
+                        &ticketed_gossip(full_neighbor.public_key()),
                     )
                     .unwrap()
                 ),
@@ -2386,7 +4130,7 @@ mod tests {
                     encodex(
                         cryptde(),
                         half_neighbor.public_key(),
-                        &MessageType::Gossip(gossip.clone()),
+                        &ticketed_gossip(half_neighbor.public_key()),
                     )
                     .unwrap()
                 ),
@@ -2488,6 +4232,93 @@ mod tests {
         assert_eq!(0, hopper_recording.len());
     }
 
+    #[test]
+    fn neighborhood_tells_the_dispatcher_which_node_its_gossip_came_from() {
+        let subject_node = make_global_cryptde_node_record(5555, true); // 9e7p7un06eHs6frl5A
+        let neighbor = make_node_record(1000, true);
+        let mut subject = neighborhood_from_nodes(&subject_node, Some(&neighbor));
+        subject
+            .neighborhood_database
+            .add_node(neighbor.clone())
+            .unwrap();
+        let gossip_acceptor = GossipAcceptorMock::new()
+            .handle_result(GossipAcceptanceResult::Ignored)
+            .handle_result(GossipAcceptanceResult::Ignored);
+        subject.gossip_acceptor = Box::new(gossip_acceptor);
+        let (dispatcher, _, dispatcher_recording_arc) = make_recorder();
+        let system =
+            System::new("neighborhood_tells_the_dispatcher_which_node_its_gossip_came_from");
+        subject.neighbor_key_sub = Some(dispatcher.start().recipient::<NeighborKeyMessage>());
+        let neighbor_ip = neighbor.node_addr_opt().unwrap().ip_addr();
+
+        subject.handle_gossip(Gossip::new(vec![]), SocketAddr::new(neighbor_ip, 1234));
+        subject.handle_gossip(
+            Gossip::new(vec![]),
+            SocketAddr::from_str("9.9.9.9:1234").unwrap(),
+        );
+
+        System::current().stop();
+        system.run();
+        let dispatcher_recording = dispatcher_recording_arc.lock().unwrap();
+        assert_eq!(
+            dispatcher_recording.get_record::<NeighborKeyMessage>(0),
+            &NeighborKeyMessage {
+                peer_addr: SocketAddr::new(neighbor_ip, 1234),
+                public_key: neighbor.public_key().clone(),
+            }
+        );
+        assert_eq!(dispatcher_recording.len(), 1);
+    }
+
+    #[test]
+    fn neighborhood_tells_apart_nodes_behind_the_same_ip_address_by_port() {
+        let subject_node = make_global_cryptde_node_record(5555, true); // 9e7p7un06eHs6frl5A
+        let neighbor = make_node_record(1000, true);
+        let neighbor_ip = neighbor.node_addr_opt().unwrap().ip_addr();
+        let mut sibling = make_node_record(2000, false);
+        sibling
+            .set_node_addr(&NodeAddr::new(&neighbor_ip, &vec![4321]))
+            .unwrap();
+        let mut subject = neighborhood_from_nodes(&subject_node, Some(&neighbor));
+        subject
+            .neighborhood_database
+            .add_node(neighbor.clone())
+            .unwrap();
+        subject
+            .neighborhood_database
+            .add_node(sibling.clone())
+            .unwrap();
+        let gossip_acceptor = GossipAcceptorMock::new()
+            .handle_result(GossipAcceptanceResult::Ignored)
+            .handle_result(GossipAcceptanceResult::Ignored);
+        subject.gossip_acceptor = Box::new(gossip_acceptor);
+        let (dispatcher, _, dispatcher_recording_arc) = make_recorder();
+        let system =
+            System::new("neighborhood_tells_apart_nodes_behind_the_same_ip_address_by_port");
+        subject.neighbor_key_sub = Some(dispatcher.start().recipient::<NeighborKeyMessage>());
+
+        subject.handle_gossip(Gossip::new(vec![]), SocketAddr::new(neighbor_ip, 4321));
+        subject.handle_gossip(Gossip::new(vec![]), SocketAddr::new(neighbor_ip, 1000));
+
+        System::current().stop();
+        system.run();
+        let dispatcher_recording = dispatcher_recording_arc.lock().unwrap();
+        assert_eq!(
+            dispatcher_recording.get_record::<NeighborKeyMessage>(0),
+            &NeighborKeyMessage {
+                peer_addr: SocketAddr::new(neighbor_ip, 4321),
+                public_key: sibling.public_key().clone(),
+            }
+        );
+        assert_eq!(
+            dispatcher_recording.get_record::<NeighborKeyMessage>(1),
+            &NeighborKeyMessage {
+                peer_addr: SocketAddr::new(neighbor_ip, 1000),
+                public_key: neighbor.public_key().clone(),
+            }
+        );
+    }
+
     #[test]
     fn neighborhood_complains_about_inability_to_ban_when_gossip_acceptor_requests_it() {
         init_test_logging();
@@ -2566,6 +4397,30 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn neighborhood_does_not_accept_gossip_about_a_stranger_signed_by_someone_else() {
+        init_test_logging();
+        let mut subject = make_standard_subject();
+        let gossip_acceptor = GossipAcceptorMock::new();
+        subject.gossip_acceptor = Box::new(gossip_acceptor);
+        let impostor = NodeRecordBuilder::new(4444)
+            .neighbor(subject.neighborhood_database.root().public_key())
+            .signed_by(&PublicKey::new(&b"impostor"[..]))
+            .build();
+        let gossip = GossipBuilder::new(&subject.neighborhood_database)
+            .node_record(&impostor, true)
+            .build();
+        let gossip_source = SocketAddr::from_str("4.4.4.4:4444").unwrap();
+
+        subject.handle_gossip(gossip, gossip_source);
+
+        // No panic means that subject didn't try to invoke the GossipAcceptorMock: test passes!
+        TestLogHandler::new().exists_log_containing(&format!(
+            "ERROR: Neighborhood: Received Gossip with invalid signature from {}",
+            gossip_source
+        ));
+    }
+
     #[test]
     fn neighborhood_logs_received_gossip_in_dot_graph_format() {
         init_test_logging();
@@ -2764,6 +4619,7 @@ mod tests {
             target_component: Component::ProxyClient,
             minimum_hop_count: 3,
             return_component_opt: None,
+            consuming_wallet_opt: None,
         };
         let unsuccessful_three_hop_route = addr.send(three_hop_route_request);
         let public_key_query = addr.send(NodeQueryMessage::PublicKey(a.public_key().clone()));
@@ -3108,7 +4964,7 @@ mod tests {
                 None,
             );
             let mut subject = Neighborhood::new(cryptde, &config);
-            subject.dot_graph_recipient = Some(recipient);
+            subject.ui_message_sub = Some(recipient);
             subject
                 .neighborhood_database
                 .add_node(another_node_record_a)
@@ -3169,91 +5025,370 @@ mod tests {
             another_next_door_neighbor.public_key(),
         );
 
-        let minimum_hop_count = 2;
+        let minimum_hop_count = 2;
+
+        let result = subject.make_round_trip_route(RouteQueryMessage {
+            target_key_opt: None,
+            target_component: Component::ProxyClient,
+            minimum_hop_count,
+            return_component_opt: Some(Component::ProxyServer),
+            consuming_wallet_opt: None,
+        });
+
+        assert_eq!(
+            Err(format!(
+                "Couldn't find any routes: at least {}-hop from {} to ProxyClient at Unknown",
+                minimum_hop_count,
+                cryptde().public_key()
+            )),
+            result
+        );
+    }
+
+    #[test]
+    fn make_round_trip_succeeds_when_it_finds_non_next_door_neighbor_exit_node() {
+        let next_door_neighbor = make_node_record(3, true);
+        let exit_node = make_node_record(5, false);
+
+        let subject_node = make_global_cryptde_node_record(666, true); // 9e7p7un06eHs6frl5A
+        let mut subject = neighborhood_from_nodes(&subject_node, Some(&next_door_neighbor));
+
+        subject
+            .neighborhood_database
+            .add_node(next_door_neighbor.clone())
+            .unwrap();
+        subject
+            .neighborhood_database
+            .add_node(exit_node.clone())
+            .unwrap();
+
+        subject.neighborhood_database.add_arbitrary_full_neighbor(
+            subject_node.public_key(),
+            next_door_neighbor.public_key(),
+        );
+
+        subject
+            .neighborhood_database
+            .add_arbitrary_full_neighbor(next_door_neighbor.public_key(), exit_node.public_key());
+
+        let minimum_hop_count = 2;
+
+        let result = subject.make_round_trip_route(RouteQueryMessage {
+            target_key_opt: None,
+            target_component: Component::ProxyClient,
+            minimum_hop_count,
+            return_component_opt: Some(Component::ProxyServer),
+            consuming_wallet_opt: None,
+        });
+
+        let next_door_neighbor_cryptde =
+            CryptDENull::from(&next_door_neighbor.public_key(), DEFAULT_CHAIN_ID);
+        let exit_node_cryptde = CryptDENull::from(&exit_node.public_key(), DEFAULT_CHAIN_ID);
+
+        let hops = result.clone().unwrap().route.hops;
+        let actual_keys: Vec<PublicKey> = match hops.as_slice() {
+            [hop, exit, hop_back, origin, empty, _accounting] => vec![
+                decodex::<LiveHop>(cryptde(), hop).expect("hop").public_key,
+                decodex::<LiveHop>(&next_door_neighbor_cryptde, exit)
+                    .expect("exit")
+                    .public_key,
+                decodex::<LiveHop>(&exit_node_cryptde, hop_back)
+                    .expect("hop_back")
+                    .public_key,
+                decodex::<LiveHop>(&next_door_neighbor_cryptde, origin)
+                    .expect("origin")
+                    .public_key,
+                decodex::<LiveHop>(cryptde(), empty)
+                    .expect("empty")
+                    .public_key,
+            ],
+            l => panic!("our match is wrong, real size is {}, {:?}", l.len(), l),
+        };
+        let expected_public_keys = vec![
+            next_door_neighbor.public_key().clone(),
+            exit_node.public_key().clone(),
+            next_door_neighbor.public_key().clone(),
+            subject_node.public_key().clone(),
+            PublicKey::new(b""),
+        ];
+        assert_eq!(expected_public_keys, actual_keys);
+    }
+
+    #[test]
+    fn route_self_check_complains_about_having_no_neighbors() {
+        let mut subject = make_standard_subject();
+
+        let result = subject.diagnose_route_capability();
+
+        assert_eq!(
+            result,
+            Some("This Node has no neighbors, but min-hops is 3. Make sure --neighbors is set and that those Nodes are running and reachable.".to_string())
+        );
+    }
+
+    #[test]
+    fn route_self_check_complains_about_too_few_routing_nodes() {
+        let (_, _, _, mut subject) = make_o_r_e_subject();
+
+        let result = subject.diagnose_route_capability();
+
+        assert_eq!(
+            result,
+            Some("You have 1 neighbor and know of 2 Nodes that route data, but min-hops is 3. Wait for more Gossip, or add more --neighbors.".to_string())
+        );
+    }
+
+    #[test]
+    fn route_self_check_complains_when_every_exit_node_is_too_close() {
+        let mut subject = make_standard_subject();
+        let o = subject.neighborhood_database.root().clone();
+        let a = make_node_record(3456, false);
+        let b = make_node_record(4567, false);
+        let c = make_node_record(5678, false);
+        {
+            let db = &mut subject.neighborhood_database;
+            vec![&a, &b, &c]
+                .into_iter()
+                .for_each(|node| db.add_node(node.clone()).map(|_| ()).unwrap());
+            let nodes = vec![&o, &a, &b, &c];
+            nodes.iter().enumerate().for_each(|(index, from)| {
+                nodes[(index + 1)..].iter().for_each(|to| {
+                    db.add_arbitrary_full_neighbor(from.public_key(), to.public_key());
+                });
+            });
+        }
+
+        let result = subject.diagnose_route_capability();
+
+        assert_eq!(
+            result,
+            Some("You know of 3 Nodes that route data, but none of them can complete a 3-hop route out and back: the possible exit Nodes are all too close to this one. Add --neighbors from elsewhere in the Network.".to_string())
+        );
+    }
+
+    #[test]
+    fn route_self_check_passes_when_routes_can_be_made_and_paid_for() {
+        let mut subject = make_standard_subject();
+        make_route_self_check_chain(&mut subject);
+
+        let result = subject.diagnose_route_capability();
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn route_self_check_complains_about_missing_consuming_wallet() {
+        let mut subject = make_standard_subject();
+        make_route_self_check_chain(&mut subject);
+        subject.consuming_wallet_opt = None;
+
+        let result = subject.diagnose_route_capability();
+
+        assert_eq!(
+            result,
+            Some("This Node can construct 3-hop routes, but it has no consuming wallet to pay for them. Supply --consuming-private-key, or set the consuming wallet password from the UI.".to_string())
+        );
+    }
+
+    #[test]
+    fn route_self_check_runs_after_start_and_broadcasts_diagnosis_to_ui() {
+        init_test_logging();
+        let (ui_gateway, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        let mut subject = make_standard_subject();
+        subject.route_self_check_delay = Duration::from_millis(10);
+        subject.logger = Logger::new("route_self_check_runs_after_start");
+        thread::spawn(move || {
+            let system = System::new("route_self_check_runs_after_start");
+            let addr: Addr<Neighborhood> = subject.start();
+            let peer_actors = peer_actors_builder().ui_gateway(ui_gateway).build();
+            addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            addr.try_send(StartMessage {}).unwrap();
+
+            system.run();
+        });
+
+        ui_gateway_awaiter.await_message_count(1);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        let diagnosis = "This Node has no neighbors, but min-hops is 3. Make sure --neighbors is set and that those Nodes are running and reachable.";
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: BROADCAST,
+                data: UiMessage::RouteCapabilityDiagnosis(diagnosis.to_string()),
+            }
+        );
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: route_self_check_runs_after_start: Route self-check failed: {}",
+            diagnosis
+        ));
+    }
+
+    #[test]
+    fn start_tells_the_service_manager_the_node_is_ready_after_debuting() {
+        let (hopper, hopper_awaiter, hopper_recording_arc) = make_recorder();
+        let notifications_arc = Arc::new(Mutex::new(vec![]));
+        let mut subject = make_standard_subject();
+        subject.service_manager = Box::new(ServiceManagerMock {
+            notifications: notifications_arc.clone(),
+            watchdog_interval: None,
+        });
+        thread::spawn(move || {
+            let system = System::new("start_tells_the_service_manager_the_node_is_ready");
+            let addr: Addr<Neighborhood> = subject.start();
+            let peer_actors = peer_actors_builder().hopper(hopper).build();
+            addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            addr.try_send(StartMessage {}).unwrap();
+
+            system.run();
+        });
+
+        hopper_awaiter.await_message_count(1);
+        await_notification_count(&notifications_arc, 1);
+        assert_eq!(hopper_recording_arc.lock().unwrap().len(), 1);
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(*notifications_arc.lock().unwrap(), vec!["READY=1"]);
+    }
+
+    #[test]
+    fn start_has_the_service_manager_watchdog_pinged_periodically() {
+        let notifications_arc = Arc::new(Mutex::new(vec![]));
+        let mut subject = make_standard_subject();
+        subject.service_manager = Box::new(ServiceManagerMock {
+            notifications: notifications_arc.clone(),
+            watchdog_interval: Some(Duration::from_millis(10)),
+        });
+        thread::spawn(move || {
+            let system = System::new("start_has_the_service_manager_watchdog_pinged");
+            let addr: Addr<Neighborhood> = subject.start();
+            let peer_actors = peer_actors_builder().build();
+            addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            addr.try_send(StartMessage {}).unwrap();
 
-        let result = subject.make_round_trip_route(RouteQueryMessage {
-            target_key_opt: None,
-            target_component: Component::ProxyClient,
-            minimum_hop_count,
-            return_component_opt: Some(Component::ProxyServer),
+            system.run();
+        });
+
+        await_notification_count(&notifications_arc, 3);
+        let notifications = notifications_arc.lock().unwrap();
+        assert_eq!(notifications[0], "READY=1");
+        assert_eq!(notifications[1], "WATCHDOG=1");
+        assert_eq!(notifications[2], "WATCHDOG=1");
+    }
+
+    #[test]
+    fn start_with_lan_discovery_advertises_our_descriptor_on_the_lan() {
+        let started_arc = Arc::new(Mutex::new(vec![]));
+        let mut subject = make_standard_subject();
+        subject.lan_discovery = true;
+        subject.lan_discoverer = Box::new(LanDiscovererMock {
+            started: started_arc.clone(),
         });
+        let expected_descriptor = subject
+            .neighborhood_database
+            .root()
+            .node_descriptor(cryptde(), DEFAULT_CHAIN_ID);
+        let system = System::new("start_with_lan_discovery_advertises_our_descriptor_on_the_lan");
+        let addr: Addr<Neighborhood> = subject.start();
+        let peer_actors = peer_actors_builder().build();
+        addr.try_send(BindMessage { peer_actors }).unwrap();
 
+        addr.try_send(StartMessage {}).unwrap();
+
+        System::current().stop();
+        system.run();
         assert_eq!(
-            Err(format!(
-                "Couldn't find any routes: at least {}-hop from {} to ProxyClient at Unknown",
-                minimum_hop_count,
-                cryptde().public_key()
-            )),
-            result
+            *started_arc.lock().unwrap(),
+            vec![Some(expected_descriptor)]
         );
     }
 
     #[test]
-    fn make_round_trip_succeeds_when_it_finds_non_next_door_neighbor_exit_node() {
-        let next_door_neighbor = make_node_record(3, true);
-        let exit_node = make_node_record(5, false);
+    fn start_without_lan_discovery_keeps_quiet_on_the_lan() {
+        let started_arc = Arc::new(Mutex::new(vec![]));
+        let mut subject = make_standard_subject();
+        subject.lan_discoverer = Box::new(LanDiscovererMock {
+            started: started_arc.clone(),
+        });
+        let system = System::new("start_without_lan_discovery_keeps_quiet_on_the_lan");
+        let addr: Addr<Neighborhood> = subject.start();
+        let peer_actors = peer_actors_builder().build();
+        addr.try_send(BindMessage { peer_actors }).unwrap();
 
-        let subject_node = make_global_cryptde_node_record(666, true); // 9e7p7un06eHs6frl5A
-        let mut subject = neighborhood_from_nodes(&subject_node, Some(&next_door_neighbor));
+        addr.try_send(StartMessage {}).unwrap();
+
+        System::current().stop();
+        system.run();
+        assert!(started_arc.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "A zero-hop PrometheusNode is not decentralized and cannot have a --lan-discovery setting"
+    )]
+    fn zero_hop_neighborhood_with_lan_discovery_panics() {
+        let mut config = BootstrapperConfig::new();
+        config.lan_discovery = true;
+
+        Neighborhood::new(cryptde(), &config);
+    }
+
+    #[test]
+    fn a_node_found_on_the_lan_gets_our_debut_once() {
+        init_test_logging();
+        let mut subject = make_standard_subject();
+        let lan_node = make_node_record(5432, true);
+        let lan_descriptor = lan_node.node_descriptor(cryptde(), DEFAULT_CHAIN_ID);
+        let (hopper, _, hopper_recording_arc) = make_recorder();
+        let peer_actors = peer_actors_builder().hopper(hopper).build();
+        let system = System::new("a_node_found_on_the_lan_gets_our_debut_once");
+        subject.hopper_no_lookup = Some(peer_actors.hopper.from_hopper_client_no_lookup);
+
+        subject.handle_lan_neighbor(lan_descriptor.clone());
+        subject.handle_lan_neighbor(lan_descriptor.clone());
+
+        System::current().stop();
+        system.run();
+        let hopper_recording = hopper_recording_arc.lock().unwrap();
+        assert_eq!(hopper_recording.len(), 1);
+        let package = hopper_recording.get_record::<NoLookupIncipientCoresPackage>(0);
+        assert_eq!(&package.public_key, lan_node.public_key());
+        assert_eq!(package.node_addr, lan_node.node_addr_opt().unwrap());
+        TestLogHandler::new().exists_log_containing(&format!(
+            "INFO: Neighborhood: Found Node {} on the LAN; introducing ourselves",
+            lan_descriptor
+        ));
+    }
 
+    #[test]
+    fn lan_descriptors_that_are_ours_known_or_unusable_get_no_debut() {
+        let mut subject = make_standard_subject();
+        let known_node = make_node_record(5432, true);
         subject
             .neighborhood_database
-            .add_node(next_door_neighbor.clone())
+            .add_node(known_node.clone())
             .unwrap();
-        subject
+        let own_descriptor = subject
             .neighborhood_database
-            .add_node(exit_node.clone())
-            .unwrap();
+            .root()
+            .node_descriptor(cryptde(), DEFAULT_CHAIN_ID);
+        let (hopper, _, hopper_recording_arc) = make_recorder();
+        let peer_actors = peer_actors_builder().hopper(hopper).build();
+        let system = System::new("lan_descriptors_that_are_ours_known_or_unusable_get_no_debut");
+        subject.hopper_no_lookup = Some(peer_actors.hopper.from_hopper_client_no_lookup);
 
-        subject.neighborhood_database.add_arbitrary_full_neighbor(
-            subject_node.public_key(),
-            next_door_neighbor.public_key(),
+        subject.handle_lan_neighbor(own_descriptor);
+        subject.handle_lan_neighbor(known_node.node_descriptor(cryptde(), DEFAULT_CHAIN_ID));
+        subject.handle_lan_neighbor(String::from("booga"));
+        subject.handle_lan_neighbor(
+            NodeDescriptor::from(make_node_record(6543, true).public_key())
+                .to_string(cryptde(), DEFAULT_CHAIN_ID),
         );
 
-        subject
-            .neighborhood_database
-            .add_arbitrary_full_neighbor(next_door_neighbor.public_key(), exit_node.public_key());
-
-        let minimum_hop_count = 2;
-
-        let result = subject.make_round_trip_route(RouteQueryMessage {
-            target_key_opt: None,
-            target_component: Component::ProxyClient,
-            minimum_hop_count,
-            return_component_opt: Some(Component::ProxyServer),
-        });
-
-        let next_door_neighbor_cryptde =
-            CryptDENull::from(&next_door_neighbor.public_key(), DEFAULT_CHAIN_ID);
-        let exit_node_cryptde = CryptDENull::from(&exit_node.public_key(), DEFAULT_CHAIN_ID);
-
-        let hops = result.clone().unwrap().route.hops;
-        let actual_keys: Vec<PublicKey> = match hops.as_slice() {
-            [hop, exit, hop_back, origin, empty, _accounting] => vec![
-                decodex::<LiveHop>(cryptde(), hop).expect("hop").public_key,
-                decodex::<LiveHop>(&next_door_neighbor_cryptde, exit)
-                    .expect("exit")
-                    .public_key,
-                decodex::<LiveHop>(&exit_node_cryptde, hop_back)
-                    .expect("hop_back")
-                    .public_key,
-                decodex::<LiveHop>(&next_door_neighbor_cryptde, origin)
-                    .expect("origin")
-                    .public_key,
-                decodex::<LiveHop>(cryptde(), empty)
-                    .expect("empty")
-                    .public_key,
-            ],
-            l => panic!("our match is wrong, real size is {}, {:?}", l.len(), l),
-        };
-        let expected_public_keys = vec![
-            next_door_neighbor.public_key().clone(),
-            exit_node.public_key().clone(),
-            next_door_neighbor.public_key().clone(),
-            subject_node.public_key().clone(),
-            PublicKey::new(b""),
-        ];
-        assert_eq!(expected_public_keys, actual_keys);
+        System::current().stop();
+        system.run();
+        assert_eq!(hopper_recording_arc.lock().unwrap().len(), 0);
     }
 
     #[test]
@@ -3419,6 +5554,197 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn handle_stream_shutdown_presents_resumption_ticket_instead_of_removing_neighbor() {
+        let (hopper, _, hopper_recording_arc) = make_recorder();
+        let system = System::new(
+            "handle_stream_shutdown_presents_resumption_ticket_instead_of_removing_neighbor",
+        );
+        let shutdown_neighbor_node = make_node_record(3123, true);
+        let shutdown_neighbor_node_addr = shutdown_neighbor_node.node_addr_opt().unwrap();
+        let subject_node = make_global_cryptde_node_record(1345, true);
+        let mut subject = neighborhood_from_nodes(&subject_node, None);
+        subject
+            .neighborhood_database
+            .add_node(shutdown_neighbor_node.clone())
+            .unwrap();
+        subject.neighborhood_database.add_arbitrary_full_neighbor(
+            subject_node.public_key(),
+            shutdown_neighbor_node.public_key(),
+        );
+        let neighbor_cryptde =
+            CryptDENull::from(shutdown_neighbor_node.public_key(), DEFAULT_CHAIN_ID);
+        let ticket = ResumptionTicket::issue(
+            &neighbor_cryptde,
+            subject_node.public_key(),
+            SystemTime::now(),
+        );
+        subject.keep_resumption_ticket(ticket.clone());
+        let peer_actors = peer_actors_builder().hopper(hopper).build();
+        subject.hopper_no_lookup = Some(peer_actors.hopper.from_hopper_client_no_lookup);
+
+        subject.handle_stream_shutdown_msg(StreamShutdownMsg {
+            peer_addr: SocketAddr::new(
+                shutdown_neighbor_node_addr.ip_addr(),
+                shutdown_neighbor_node_addr.ports()[0],
+            ),
+            stream_type: RemovedStreamType::Clandestine,
+            report_to_counterpart: true,
+        });
+
+        System::current().stop_with_code(0);
+        system.run();
+        assert_eq!(
+            subject.neighborhood_database.has_half_neighbor(
+                subject_node.public_key(),
+                shutdown_neighbor_node.public_key(),
+            ),
+            true
+        );
+        assert_eq!(subject.resumption_tickets.is_empty(), true);
+        let hopper_recording = hopper_recording_arc.lock().unwrap();
+        assert_eq!(hopper_recording.len(), 1);
+        let package = hopper_recording.get_record::<NoLookupIncipientCoresPackage>(0);
+        assert_eq!(&package.public_key, shutdown_neighbor_node.public_key());
+        assert_eq!(package.node_addr, shutdown_neighbor_node_addr);
+        let gossip = match decodex::<MessageType>(&neighbor_cryptde, &package.payload).unwrap() {
+            MessageType::Gossip(g) => g,
+            x => panic!("Expected MessageType::Gossip, got {:?}", x),
+        };
+        assert_eq!(gossip.node_records.len(), 1);
+        assert_eq!(gossip.resumption_ticket_opt, Some(ticket));
+    }
+
+    #[test]
+    fn neighbor_presenting_its_resumption_ticket_is_taken_back_at_once() {
+        init_test_logging();
+        let (hopper, _, hopper_recording_arc) = make_recorder();
+        let system = System::new("neighbor_presenting_its_resumption_ticket_is_taken_back_at_once");
+        let returning_node = make_node_record(3123, true);
+        let returning_node_addr = returning_node.node_addr_opt().unwrap();
+        let subject_node = make_global_cryptde_node_record(1345, true);
+        let now = SystemTime::now();
+        let mut subject =
+            neighborhood_from_nodes(&subject_node, None).with_clock(Box::new(ClockMock::new(now)));
+        subject
+            .neighborhood_database
+            .add_node(returning_node.clone())
+            .unwrap();
+        subject
+            .neighborhood_database
+            .add_arbitrary_full_neighbor(subject_node.public_key(), returning_node.public_key());
+        subject
+            .neighborhood_database
+            .remove_neighbor(returning_node.public_key())
+            .unwrap();
+        let peer_actors = peer_actors_builder().hopper(hopper).build();
+        subject.hopper = Some(peer_actors.hopper.from_hopper_client);
+        let returning_db = db_from_node(&returning_node);
+        let mut gossip = GossipBuilder::new(&returning_db)
+            .node(returning_node.public_key(), true)
+            .build();
+        gossip.resumption_ticket_opt = Some(ResumptionTicket::issue(
+            cryptde(),
+            returning_node.public_key(),
+            now - Duration::from_secs(60),
+        ));
+
+        subject.handle_gossip(
+            gossip,
+            SocketAddr::new(
+                returning_node_addr.ip_addr(),
+                returning_node_addr.ports()[0],
+            ),
+        );
+
+        System::current().stop_with_code(0);
+        system.run();
+        let db = &subject.neighborhood_database;
+        assert_eq!(
+            db.has_half_neighbor(subject_node.public_key(), returning_node.public_key()),
+            true
+        );
+        assert_eq!(
+            db.node_by_ip(&returning_node_addr.ip_addr())
+                .map(|node| node.public_key()),
+            Some(returning_node.public_key())
+        );
+        let hopper_recording = hopper_recording_arc.lock().unwrap();
+        assert_eq!(hopper_recording.len(), 1);
+        let package = hopper_recording.get_record::<IncipientCoresPackage>(0);
+        let returning_cryptde = CryptDENull::from(returning_node.public_key(), DEFAULT_CHAIN_ID);
+        let reply = match decodex(&returning_cryptde, &package.payload).unwrap() {
+            MessageType::Gossip(g) => g,
+            x => panic!("Expected MessageType::Gossip, got {:?}", x),
+        };
+        assert_eq!(
+            reply.resumption_ticket_opt,
+            Some(ResumptionTicket::issue(
+                cryptde(),
+                returning_node.public_key(),
+                now
+            ))
+        );
+        TestLogHandler::new().exists_log_containing(&format!(
+            "INFO: Neighborhood: Resumed neighborship with {} at {} on its resumption ticket",
+            returning_node.public_key(),
+            returning_node_addr
+        ));
+    }
+
+    #[test]
+    fn expired_resumption_ticket_leaves_the_gossip_to_the_gossip_acceptor() {
+        init_test_logging();
+        let returning_node = make_node_record(3124, true);
+        let returning_node_addr = returning_node.node_addr_opt().unwrap();
+        let subject_node = make_global_cryptde_node_record(1346, true);
+        let now = SystemTime::now();
+        let mut subject =
+            neighborhood_from_nodes(&subject_node, None).with_clock(Box::new(ClockMock::new(now)));
+        subject
+            .neighborhood_database
+            .add_node(returning_node.clone())
+            .unwrap();
+        let handle_params_arc = Arc::new(Mutex::new(vec![]));
+        subject.gossip_acceptor = Box::new(
+            GossipAcceptorMock::new()
+                .handle_params(&handle_params_arc)
+                .handle_result(GossipAcceptanceResult::Ignored),
+        );
+        let returning_db = db_from_node(&returning_node);
+        let mut gossip = GossipBuilder::new(&returning_db)
+            .node(returning_node.public_key(), true)
+            .build();
+        gossip.resumption_ticket_opt = Some(ResumptionTicket::issue(
+            cryptde(),
+            returning_node.public_key(),
+            now - RESUMPTION_TICKET_LIFETIME - Duration::from_secs(1),
+        ));
+
+        subject.handle_gossip(
+            gossip,
+            SocketAddr::new(
+                returning_node_addr.ip_addr(),
+                returning_node_addr.ports()[0],
+            ),
+        );
+
+        assert_eq!(
+            subject
+                .neighborhood_database
+                .has_half_neighbor(subject_node.public_key(), returning_node.public_key()),
+            false
+        );
+        assert_eq!(handle_params_arc.lock().unwrap().len(), 1);
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: Neighborhood: Not resuming {} at {}:{}: Resumption ticket presented by {} expired 1s ago",
+            returning_node.public_key(),
+            returning_node_addr.ip_addr(),
+            returning_node_addr.ports()[0],
+            returning_node.public_key()
+        ));
+    }
+
     fn make_standard_subject() -> Neighborhood {
         let root_node = make_global_cryptde_node_record(9999, true);
         let neighbor_node = make_node_record(9998, true);
@@ -3443,6 +5769,21 @@ mod tests {
         (o.clone(), r.clone(), e.clone(), subject)
     }
 
+    // o <-> a <-> b <-> c: a three-hop route from o to c and back
+    fn make_route_self_check_chain(subject: &mut Neighborhood) {
+        let o = subject.neighborhood_database.root().clone();
+        let a = make_node_record(3456, false);
+        let b = make_node_record(4567, false);
+        let c = make_node_record(5678, false);
+        let db = &mut subject.neighborhood_database;
+        vec![&a, &b, &c]
+            .into_iter()
+            .for_each(|node| db.add_node(node.clone()).map(|_| ()).unwrap());
+        db.add_arbitrary_full_neighbor(o.public_key(), a.public_key());
+        db.add_arbitrary_full_neighbor(a.public_key(), b.public_key());
+        db.add_arbitrary_full_neighbor(b.public_key(), c.public_key());
+    }
+
     fn segment(nodes: &[&NodeRecord], component: &Component) -> RouteSegment {
         RouteSegment::new(
             nodes.into_iter().map(|n| n.public_key()).collect(),
@@ -3588,4 +5929,46 @@ mod tests {
             self.neighborhood_database.clone()
         }
     }
+
+    struct LanDiscovererMock {
+        started: Arc<Mutex<Vec<Option<String>>>>,
+    }
+
+    impl LanDiscoverer for LanDiscovererMock {
+        fn start(&self, descriptor_opt: Option<String>, _found_sub: Recipient<LanNeighborMessage>) {
+            self.started.lock().unwrap().push(descriptor_opt);
+        }
+    }
+
+    struct ServiceManagerMock {
+        notifications: Arc<Mutex<Vec<&'static str>>>,
+        watchdog_interval: Option<Duration>,
+    }
+
+    impl ServiceManager for ServiceManagerMock {
+        fn notify_ready(&self) {
+            self.notifications.lock().unwrap().push("READY=1");
+        }
+
+        fn notify_watchdog(&self) {
+            self.notifications.lock().unwrap().push("WATCHDOG=1");
+        }
+
+        fn watchdog_interval(&self) -> Option<Duration> {
+            self.watchdog_interval
+        }
+    }
+
+    fn await_notification_count(notifications_arc: &Arc<Mutex<Vec<&'static str>>>, count: usize) {
+        let deadline = SystemTime::now() + Duration::from_secs(5);
+        while notifications_arc.lock().unwrap().len() < count {
+            if SystemTime::now() > deadline {
+                panic!(
+                    "Service manager got only {:?}",
+                    notifications_arc.lock().unwrap()
+                );
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
 }
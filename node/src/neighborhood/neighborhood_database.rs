@@ -193,6 +193,21 @@ impl NeighborhoodDatabase {
         }
     }
 
+    // Gives back the NodeAddr that remove_neighbor took away, when that Node comes back
+    pub fn restore_node_addr(
+        &mut self,
+        node_key: &PublicKey,
+        node_addr: &NodeAddr,
+    ) -> Result<bool, NeighborhoodDatabaseError> {
+        let changed = match self.node_by_key_mut(node_key) {
+            Some(node_record) => node_record.set_node_addr(node_addr)?,
+            None => return Err(NodeKeyNotFound(node_key.clone())),
+        };
+        self.by_ip_addr
+            .insert(node_addr.ip_addr(), node_key.clone());
+        Ok(changed)
+    }
+
     pub fn to_dot_graph(&self) -> String {
         let renderables = self.to_dot_renderables();
         render_dot_graph(renderables)
@@ -779,4 +794,45 @@ mod tests {
         assert_eq!(0, subject.root().version());
         assert!(!result.ok().expect("should be ok"));
     }
+
+    #[test]
+    fn restore_node_addr_undoes_what_remove_neighbor_did_to_the_node_addr() {
+        let this_node = make_node_record(123, true);
+        let mut subject = db_from_node(&this_node);
+        let other_node = make_node_record(2345, true);
+        let other_node_addr = other_node.node_addr_opt().unwrap();
+        subject.add_node(other_node.clone()).unwrap();
+        subject.add_arbitrary_half_neighbor(&this_node.public_key(), &other_node.public_key());
+        subject.remove_neighbor(other_node.public_key()).unwrap();
+
+        let first = subject.restore_node_addr(other_node.public_key(), &other_node_addr);
+        let second = subject.restore_node_addr(other_node.public_key(), &other_node_addr);
+
+        assert_eq!(first, Ok(true));
+        assert_eq!(second, Ok(false));
+        assert_eq!(
+            subject
+                .node_by_ip(&other_node_addr.ip_addr())
+                .unwrap()
+                .public_key(),
+            other_node.public_key()
+        );
+    }
+
+    #[test]
+    fn restore_node_addr_complains_about_unknown_nodes() {
+        let this_node = make_node_record(123, true);
+        let mut subject = db_from_node(&this_node);
+        let unknown_node = make_node_record(2345, true);
+
+        let result = subject.restore_node_addr(
+            unknown_node.public_key(),
+            &unknown_node.node_addr_opt().unwrap(),
+        );
+
+        assert_eq!(
+            result,
+            Err(NodeKeyNotFound(unknown_node.public_key().clone()))
+        );
+    }
 }
@@ -2,16 +2,19 @@
 use super::neighborhood_database::NeighborhoodDatabase;
 use super::node_record::NodeRecord;
 use crate::bootstrapper::BootstrapperConfig;
-use crate::neighborhood::gossip::GossipNodeRecord;
+use crate::neighborhood::gossip::{GossipBuilder, GossipNodeRecord};
 use crate::neighborhood::node_record::NodeRecordInner;
 use crate::neighborhood::{AccessibleGossipRecord, Neighborhood};
 use crate::sub_lib::cryptde::PublicKey;
-use crate::sub_lib::cryptde::{CryptDE, PlainData};
+use crate::sub_lib::cryptde::{CryptDE, CryptData, PlainData};
 use crate::sub_lib::cryptde_null::CryptDENull;
-use crate::sub_lib::neighborhood::{NeighborhoodConfig, NeighborhoodMode, NodeDescriptor};
+use crate::sub_lib::neighborhood::{
+    NeighborhoodConfig, NeighborhoodMode, NodeDescriptor, RatePack,
+};
 use crate::sub_lib::node_addr::NodeAddr;
 use crate::sub_lib::wallet::Wallet;
 use crate::test_utils::*;
+use std::collections::BTreeSet;
 use std::convert::TryFrom;
 use std::net::IpAddr;
 use std::net::Ipv4Addr;
@@ -43,6 +46,114 @@ pub fn make_node_record(n: u16, has_ip: bool) -> NodeRecord {
     )
 }
 
+/// Builds a NodeRecord for a test, starting from the one `make_node_record(n, true)` makes.
+/// `neighbor()` lets a test describe any shape of Network without a database, and `signed_by()`
+/// makes a record whose signature doesn't match its key.
+pub struct NodeRecordBuilder {
+    public_key: PublicKey,
+    node_addr_opt: Option<NodeAddr>,
+    rate_pack: RatePack,
+    accepts_connections: bool,
+    routes_data: bool,
+    version: u32,
+    neighbors: BTreeSet<PublicKey>,
+    signer_opt: Option<PublicKey>,
+}
+
+impl NodeRecordBuilder {
+    pub fn new(n: u16) -> NodeRecordBuilder {
+        let node_record = make_node_record(n, true);
+        NodeRecordBuilder {
+            public_key: node_record.public_key().clone(),
+            node_addr_opt: node_record.node_addr_opt(),
+            rate_pack: node_record.rate_pack().clone(),
+            accepts_connections: true,
+            routes_data: true,
+            version: 0,
+            neighbors: BTreeSet::new(),
+            signer_opt: None,
+        }
+    }
+
+    pub fn public_key(mut self, public_key: &PublicKey) -> NodeRecordBuilder {
+        self.public_key = public_key.clone();
+        self
+    }
+
+    pub fn node_addr(mut self, node_addr_opt: Option<NodeAddr>) -> NodeRecordBuilder {
+        self.node_addr_opt = node_addr_opt;
+        self
+    }
+
+    pub fn rate_pack(mut self, rate_pack: RatePack) -> NodeRecordBuilder {
+        self.rate_pack = rate_pack;
+        self
+    }
+
+    pub fn accepts_connections(mut self, accepts_connections: bool) -> NodeRecordBuilder {
+        self.accepts_connections = accepts_connections;
+        self
+    }
+
+    pub fn routes_data(mut self, routes_data: bool) -> NodeRecordBuilder {
+        self.routes_data = routes_data;
+        self
+    }
+
+    pub fn version(mut self, version: u32) -> NodeRecordBuilder {
+        self.version = version;
+        self
+    }
+
+    pub fn neighbor(mut self, public_key: &PublicKey) -> NodeRecordBuilder {
+        self.neighbors.insert(public_key.clone());
+        self
+    }
+
+    pub fn signed_by(mut self, public_key: &PublicKey) -> NodeRecordBuilder {
+        self.signer_opt = Some(public_key.clone());
+        self
+    }
+
+    pub fn build(self) -> NodeRecord {
+        let mut node_record = NodeRecord::new_for_tests(
+            &self.public_key,
+            self.node_addr_opt.as_ref(),
+            0,
+            self.accepts_connections,
+            self.routes_data,
+        );
+        node_record.inner.rate_pack = self.rate_pack;
+        node_record.inner.version = self.version;
+        node_record.inner.neighbors = self.neighbors;
+        let signer = self.signer_opt.unwrap_or(self.public_key);
+        node_record.regenerate_signed_gossip(&CryptDENull::from(&signer, DEFAULT_CHAIN_ID));
+        node_record
+    }
+}
+
+// Gossip about Nodes that needn't be in the database, and Gossip no Node should have sent
+impl<'a> GossipBuilder<'a> {
+    pub fn node_record(mut self, node_record: &NodeRecord, reveal_node_addr: bool) -> Self {
+        let mut gnr = GossipNodeRecord::from(node_record.clone());
+        if !reveal_node_addr {
+            gnr.node_addr_opt = None
+        }
+        self.gossip.node_records.push(gnr);
+        self
+    }
+
+    // A record whose signed data isn't a NodeRecord at all
+    pub fn garbage_record(mut self, signed_data: &[u8]) -> Self {
+        self.gossip.node_records.push(GossipNodeRecord {
+            signed_data: PlainData::new(signed_data),
+            signature: CryptData::new(signed_data),
+            node_addr_opt: None,
+        });
+        self
+    }
+}
+
 pub fn make_node_record_f(
     n: u16,
     has_ip: bool,
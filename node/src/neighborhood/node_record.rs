@@ -20,6 +20,9 @@ pub struct NodeRecordInner {
     pub data_version: DataVersion,
     pub public_key: PublicKey,
     pub earning_wallet: Wallet,
+    // Where consumers should send payment for exit services, if not to earning_wallet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_earning_wallet_opt: Option<Wallet>,
     pub rate_pack: RatePack,
     pub neighbors: BTreeSet<PublicKey>,
     pub accepts_connections: bool,
@@ -81,6 +84,7 @@ impl NodeRecord {
                 data_version: NodeRecordInner::data_version(),
                 public_key: public_key.clone(),
                 earning_wallet,
+                exit_earning_wallet_opt: None,
                 rate_pack,
                 accepts_connections,
                 routes_data,
@@ -234,6 +238,22 @@ impl NodeRecord {
         }
     }
 
+    pub fn exit_earning_wallet(&self) -> Wallet {
+        match &self.inner.exit_earning_wallet_opt {
+            Some(exit_earning_wallet) => exit_earning_wallet.clone(),
+            None => self.earning_wallet(),
+        }
+    }
+
+    pub fn set_exit_earning_wallet_opt(&mut self, exit_earning_wallet_opt: Option<Wallet>) -> bool {
+        if self.inner.exit_earning_wallet_opt == exit_earning_wallet_opt {
+            false
+        } else {
+            self.inner.exit_earning_wallet_opt = exit_earning_wallet_opt;
+            true
+        }
+    }
+
     pub fn rate_pack(&self) -> &RatePack {
         &self.inner.rate_pack
     }
@@ -749,6 +769,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn exit_earning_wallet_defaults_to_earning_wallet() {
+        let this_node = make_node_record(1234, true);
+
+        assert_eq!(this_node.exit_earning_wallet(), this_node.earning_wallet());
+    }
+
+    #[test]
+    fn set_exit_earning_wallet_opt_overrides_earning_wallet_for_exit_services() {
+        let mut this_node = make_node_record(1234, true);
+        let exit_earning_wallet =
+            Wallet::from_str("0x2955a94429b1e8213f6df9c463cc2b9087b059ce").unwrap();
+
+        assert!(this_node.set_exit_earning_wallet_opt(Some(exit_earning_wallet.clone())));
+        assert!(!this_node.set_exit_earning_wallet_opt(Some(exit_earning_wallet.clone())));
+
+        assert_eq!(this_node.exit_earning_wallet(), exit_earning_wallet);
+        assert_eq!(
+            this_node.earning_wallet(),
+            Wallet::from_str("0x546900db8d6e0937497133d1ae6fdf5f4b75bcd0").unwrap()
+        );
+    }
+
+    #[test]
+    fn exit_earning_wallet_survives_gossip() {
+        let mut this_node = make_node_record(1234, true);
+        this_node.set_exit_earning_wallet_opt(Some(make_wallet("exit")));
+        this_node.regenerate_signed_gossip(cryptde());
+
+        let result = NodeRecordInner::try_from(GossipNodeRecord::from(this_node.clone())).unwrap();
+
+        assert_eq!(result.exit_earning_wallet_opt, Some(make_wallet("exit")));
+    }
+
     #[test]
     fn set_desirable_when_no_change_from_default() {
         let mut this_node = make_node_record(5432, true);
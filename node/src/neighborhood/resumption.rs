@@ -0,0 +1,179 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use crate::sub_lib::cryptde::{CryptDE, CryptData, PlainData, PublicKey};
+use serde_derive::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// How long after issuing a resumption ticket a Node will still honor it
+pub const RESUMPTION_TICKET_LIFETIME: Duration = Duration::from_secs(600);
+
+// Handed by a Node to each of its neighbors along with its update Gossip. If the connection between
+// them drops, the neighbor can present the ticket to be taken back as a neighbor straightaway,
+// without Debuting all over again.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ResumptionTicket {
+    pub issuer: PublicKey,
+    pub holder: PublicKey,
+    pub issued_at_secs: u64,
+    signature: CryptData,
+}
+
+impl ResumptionTicket {
+    pub fn issue(
+        cryptde: &dyn CryptDE, // must be the CryptDE of the issuing Node
+        holder: &PublicKey,
+        now: SystemTime,
+    ) -> ResumptionTicket {
+        let issuer = cryptde.public_key().clone();
+        let issued_at_secs = secs_since_epoch(now);
+        let signature = cryptde
+            .sign(&Self::signed_data(&issuer, holder, issued_at_secs))
+            .expect("Couldn't sign resumption ticket");
+        ResumptionTicket {
+            issuer,
+            holder: holder.clone(),
+            issued_at_secs,
+            signature,
+        }
+    }
+
+    pub fn redeem(
+        &self,
+        cryptde: &dyn CryptDE, // must be the CryptDE of the Node the ticket is presented to
+        presenter: &PublicKey,
+        now: SystemTime,
+    ) -> Result<(), String> {
+        if &self.issuer != cryptde.public_key() {
+            return Err(format!(
+                "Resumption ticket was issued by {}, not by this Node",
+                self.issuer
+            ));
+        }
+        if &self.holder != presenter {
+            return Err(format!(
+                "Resumption ticket issued to {} was presented by {}",
+                self.holder, presenter
+            ));
+        }
+        if !cryptde.verify_signature(
+            &Self::signed_data(&self.issuer, &self.holder, self.issued_at_secs),
+            &self.signature,
+            &self.issuer,
+        ) {
+            return Err(format!(
+                "Resumption ticket presented by {} has an invalid signature",
+                presenter
+            ));
+        }
+        let age_secs = secs_since_epoch(now).saturating_sub(self.issued_at_secs);
+        if age_secs > RESUMPTION_TICKET_LIFETIME.as_secs() {
+            return Err(format!(
+                "Resumption ticket presented by {} expired {}s ago",
+                presenter,
+                age_secs - RESUMPTION_TICKET_LIFETIME.as_secs()
+            ));
+        }
+        Ok(())
+    }
+
+    fn signed_data(issuer: &PublicKey, holder: &PublicKey, issued_at_secs: u64) -> PlainData {
+        PlainData::from(
+            serde_cbor::ser::to_vec(&(issuer, holder, issued_at_secs))
+                .expect("Serialization failed"),
+        )
+    }
+}
+
+fn secs_since_epoch(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sub_lib::cryptde_null::CryptDENull;
+    use crate::test_utils::{cryptde, DEFAULT_CHAIN_ID};
+
+    #[test]
+    fn ticket_is_honored_for_its_holder_until_it_expires() {
+        let holder = PublicKey::new(b"holder");
+        let issued = SystemTime::now();
+        let subject = ResumptionTicket::issue(cryptde(), &holder, issued);
+
+        assert_eq!(subject.issuer, cryptde().public_key().clone());
+        assert_eq!(subject.holder, holder);
+        assert_eq!(subject.redeem(cryptde(), &holder, issued), Ok(()));
+        assert_eq!(
+            subject.redeem(cryptde(), &holder, issued + RESUMPTION_TICKET_LIFETIME),
+            Ok(())
+        );
+        assert_eq!(
+            subject.redeem(
+                cryptde(),
+                &holder,
+                issued + RESUMPTION_TICKET_LIFETIME + Duration::from_secs(5)
+            ),
+            Err(format!(
+                "Resumption ticket presented by {} expired 5s ago",
+                holder
+            ))
+        );
+    }
+
+    #[test]
+    fn ticket_is_refused_when_presented_by_another_node() {
+        let holder = PublicKey::new(b"holder");
+        let impostor = PublicKey::new(b"impostor");
+        let now = SystemTime::now();
+        let subject = ResumptionTicket::issue(cryptde(), &holder, now);
+
+        let result = subject.redeem(cryptde(), &impostor, now);
+
+        assert_eq!(
+            result,
+            Err(format!(
+                "Resumption ticket issued to {} was presented by {}",
+                holder, impostor
+            ))
+        );
+    }
+
+    #[test]
+    fn ticket_is_refused_when_presented_to_another_node() {
+        let holder = PublicKey::new(b"holder");
+        let now = SystemTime::now();
+        let other_cryptde = CryptDENull::from(&PublicKey::new(b"other"), DEFAULT_CHAIN_ID);
+        let subject = ResumptionTicket::issue(cryptde(), &holder, now);
+
+        let result = subject.redeem(&other_cryptde, &holder, now);
+
+        assert_eq!(
+            result,
+            Err(format!(
+                "Resumption ticket was issued by {}, not by this Node",
+                cryptde().public_key()
+            ))
+        );
+    }
+
+    #[test]
+    fn ticket_is_refused_when_altered() {
+        let holder = PublicKey::new(b"holder");
+        let now = SystemTime::now();
+        let mut subject =
+            ResumptionTicket::issue(cryptde(), &holder, now - Duration::from_secs(60));
+        subject.issued_at_secs += 60;
+
+        let result = subject.redeem(cryptde(), &holder, now);
+
+        assert_eq!(
+            result,
+            Err(format!(
+                "Resumption ticket presented by {} has an invalid signature",
+                holder
+            ))
+        );
+    }
+}
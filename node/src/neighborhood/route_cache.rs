@@ -0,0 +1,169 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use super::RouteDirection;
+use crate::sub_lib::cryptde::PublicKey;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+// The cache's hit rate is logged once every this many segment lookups
+pub const ROUTE_CACHE_REPORT_INTERVAL: u64 = 1000;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SegmentConstraints {
+    pub origin: PublicKey,
+    pub target_opt: Option<PublicKey>,
+    pub minimum_hop_count: usize,
+    pub direction: RouteDirection,
+}
+
+// Remembers the node sequence chosen for each set of segment constraints, or that there wasn't one,
+// until the Network changes under it
+#[derive(Default)]
+pub struct RouteCache {
+    segments: HashMap<SegmentConstraints, Option<Vec<PublicKey>>>,
+    hits: u64,
+    misses: u64,
+    invalidations: u64,
+}
+
+impl RouteCache {
+    pub fn new() -> RouteCache {
+        RouteCache::default()
+    }
+
+    pub fn lookup(&mut self, constraints: &SegmentConstraints) -> Option<Option<Vec<PublicKey>>> {
+        match self.segments.get(constraints) {
+            Some(node_seq_opt) => {
+                self.hits += 1;
+                Some(node_seq_opt.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(
+        &mut self,
+        constraints: SegmentConstraints,
+        node_seq_opt: Option<Vec<PublicKey>>,
+    ) {
+        self.segments.insert(constraints, node_seq_opt);
+    }
+
+    pub fn invalidate(&mut self) {
+        if !self.segments.is_empty() {
+            self.segments.clear();
+            self.invalidations += 1;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    pub fn invalidations(&self) -> u64 {
+        self.invalidations
+    }
+
+    pub fn lookups(&self) -> u64 {
+        self.hits + self.misses
+    }
+}
+
+impl Display for RouteCache {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let hit_percent = match self.lookups() {
+            0 => 0.0,
+            lookups => (self.hits as f64) * 100.0 / (lookups as f64),
+        };
+        write!(
+            f,
+            "{} route segments looked up: {} served from cache ({:.1}% hit rate); cache cleared {} times by Network changes",
+            self.lookups(),
+            self.hits,
+            hit_percent,
+            self.invalidations
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_constraints(target_opt: Option<&str>, direction: RouteDirection) -> SegmentConstraints {
+        SegmentConstraints {
+            origin: PublicKey::new(b"origin"),
+            target_opt: target_opt.map(|t| PublicKey::new(t.as_bytes())),
+            minimum_hop_count: 3,
+            direction,
+        }
+    }
+
+    #[test]
+    fn lookups_are_counted_as_hits_and_misses() {
+        let mut subject = RouteCache::new();
+        let over = make_constraints(None, RouteDirection::Over);
+        let back = make_constraints(Some("origin"), RouteDirection::Back);
+        let node_seq = vec![PublicKey::new(b"origin"), PublicKey::new(b"exit")];
+
+        assert_eq!(subject.lookup(&over), None);
+        subject.insert(over.clone(), Some(node_seq.clone()));
+        subject.insert(back.clone(), None);
+
+        assert_eq!(subject.lookup(&over), Some(Some(node_seq)));
+        assert_eq!(subject.lookup(&back), Some(None));
+        assert_eq!(
+            subject.lookup(&make_constraints(None, RouteDirection::Back)),
+            None
+        );
+        assert_eq!(subject.hits(), 2);
+        assert_eq!(subject.misses(), 2);
+        assert_eq!(subject.len(), 2);
+    }
+
+    #[test]
+    fn invalidation_empties_the_cache_and_is_counted_only_when_there_was_something_to_clear() {
+        let mut subject = RouteCache::new();
+        let over = make_constraints(None, RouteDirection::Over);
+        subject.invalidate();
+        subject.insert(over.clone(), None);
+
+        subject.invalidate();
+
+        assert_eq!(subject.is_empty(), true);
+        assert_eq!(subject.lookup(&over), None);
+        assert_eq!(subject.invalidations(), 1);
+    }
+
+    #[test]
+    fn display_reports_hit_rate() {
+        let mut subject = RouteCache::new();
+        let over = make_constraints(None, RouteDirection::Over);
+        subject.lookup(&over);
+        subject.insert(over.clone(), None);
+        subject.lookup(&over);
+        subject.lookup(&over);
+        subject.invalidate();
+
+        assert_eq!(
+            subject.to_string(),
+            "3 route segments looked up: 2 served from cache (66.7% hit rate); cache cleared 1 times by Network changes"
+        );
+    }
+}
@@ -0,0 +1,155 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use crate::accountant::financials_dumper;
+use crate::daemon;
+use crate::database::config_dumper;
+use crate::database::data_archive;
+use crate::node_configurator::{
+    app_head, config_checker, node_configurator_generate_wallet, node_configurator_recover_wallet,
+    node_configurator_standard,
+};
+use crate::service;
+use crate::sub_lib::main_tools::StdStreams;
+use crate::ui_gateway::{descriptor_printer, dns_leak_reporter, neighbor_lister};
+use clap::{value_t, App, Arg, Shell};
+use std::io::Write;
+
+const BIN_NAME: &str = "PrometheusNode";
+const COMPLETIONS_HELP: &str =
+    "Print a script that completes PrometheusNode's subcommands and options as you type them in the given \
+     shell. For bash, for instance, save it with PrometheusNode completions bash > \
+     /etc/bash_completion.d/PrometheusNode";
+const SHELL_HELP: &str = "The shell the script is for.";
+const SHELLS: [&str; 4] = ["bash", "zsh", "fish", "powershell"];
+
+pub fn app() -> App<'static, 'static> {
+    app_head()
+        .about(COMPLETIONS_HELP)
+        .usage("PrometheusNode completions <SHELL>")
+        .arg(
+            Arg::with_name("shell")
+                .value_name("SHELL")
+                .index(1)
+                .required(true)
+                .possible_values(&SHELLS)
+                .case_insensitive(true)
+                .help(SHELL_HELP),
+        )
+}
+
+// Only the command line: a shell named in the environment or a config file would be a surprise
+pub fn completions(args: &Vec<String>, streams: &mut StdStreams<'_>) -> i32 {
+    let matches = match app().get_matches_from_safe(args) {
+        Ok(matches) => matches,
+        Err(e) => {
+            writeln!(streams.stderr, "{}", e.message).expect("Couldn't write to stderr");
+            return 1;
+        }
+    };
+    let shell = value_t!(matches, "shell", Shell).expect("shell is not properly required");
+    write_completions(shell, streams.stdout);
+    0
+}
+
+pub fn write_completions(shell: Shell, mut output: &mut dyn Write) {
+    command_line().gen_completions_to(BIN_NAME, shell, &mut output)
+}
+
+// Everything PrometheusNode accepts, straight from the definitions each subcommand parses with.
+// The run subcommand's options are at the top too, since run is what happens without a subcommand.
+fn command_line() -> App<'static, 'static> {
+    node_configurator_standard::app()
+        .subcommand(node_configurator_standard::app().name("run"))
+        .subcommand(node_configurator_standard::app().name("setup"))
+        .subcommand(daemon::app().name("daemon"))
+        .subcommand(service::app().name("service"))
+        .subcommand(node_configurator_generate_wallet::app().name("generate-wallet"))
+        .subcommand(node_configurator_recover_wallet::app().name("recover-wallet"))
+        .subcommand(config_checker::app().name("check-config"))
+        .subcommand(config_dumper::app().name("dump-config"))
+        .subcommand(financials_dumper::app().name("dump-financials"))
+        .subcommand(data_archive::backup_app().name("backup-data"))
+        .subcommand(data_archive::restore_app().name("restore-data"))
+        .subcommand(neighbor_lister::app().name("list-neighbors"))
+        .subcommand(descriptor_printer::app().name("print-descriptor"))
+        .subcommand(dns_leak_reporter::app().name("dns-leak-test"))
+        .subcommand(app().name("completions"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{ArgsBuilder, ByteArrayWriter, FakeStreamHolder};
+
+    #[test]
+    fn completions_are_printed_for_the_named_shell() {
+        let mut holder = FakeStreamHolder::new();
+
+        let result = completions(
+            &ArgsBuilder::new().opt("bash").into(),
+            &mut holder.streams(),
+        );
+
+        assert_eq!(result, 0);
+        let script = holder.stdout.get_string();
+        assert!(script.contains("_PrometheusNode()"), "{}", script);
+        assert!(script.contains("--blockchain-service-url"), "{}", script);
+        assert_eq!(holder.stderr.get_string(), "");
+    }
+
+    #[test]
+    fn an_unknown_shell_is_refused() {
+        let mut holder = FakeStreamHolder::new();
+
+        let result = completions(
+            &ArgsBuilder::new().opt("tcsh").into(),
+            &mut holder.streams(),
+        );
+
+        assert_eq!(result, 1);
+        assert_eq!(holder.stdout.get_string(), "");
+        assert!(
+            holder.stderr.get_string().contains("tcsh"),
+            "{}",
+            holder.stderr.get_string()
+        );
+    }
+
+    #[test]
+    fn every_shell_gets_every_subcommand_and_its_options() {
+        SHELLS.iter().for_each(|shell_name| {
+            let shell: Shell = shell_name.parse().unwrap();
+            let mut output = ByteArrayWriter::new();
+
+            write_completions(shell, &mut output);
+
+            let script = output.get_string();
+            [
+                "run",
+                "setup",
+                "daemon",
+                "daemon-port",
+                "service",
+                "uninstall",
+                "generate-wallet",
+                "recover-wallet",
+                "check-config",
+                "dump-config",
+                "dump-financials",
+                "backup-data",
+                "restore-data",
+                "list-neighbors",
+                "print-descriptor",
+                "dns-leak-test",
+                "completions",
+                "word-count",
+                "mnemonic",
+                "include-secrets",
+                "qr-png",
+                "archive-password",
+            ]
+            .iter()
+            .for_each(|word| assert!(script.contains(word), "{} lacks {}", shell_name, word));
+        });
+    }
+}
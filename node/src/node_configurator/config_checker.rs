@@ -0,0 +1,565 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use crate::blockchain::bip32::Bip32ECKeyPair;
+use crate::database::db_initializer::{DbInitializer, DbInitializerReal, DATABASE_FILE};
+use crate::multi_config::{ConfigFileVcl, MultiConfig};
+use crate::node_configurator::node_configurator_standard;
+use crate::node_configurator::node_configurator_standard::standard;
+use crate::node_configurator::{
+    database_password_opt, determine_config_file_path, make_virtual_command_lines,
+    real_user_data_directory_and_chain_id,
+};
+use crate::persistent_configuration::{PersistentConfiguration, PersistentConfigurationReal};
+use crate::sub_lib::accountant::{AccountantConfig, PAYMENT_PARAMETERS};
+use crate::sub_lib::main_tools::StdStreams;
+use crate::sub_lib::wallet::Wallet;
+use clap::{value_t, App, Arg};
+use rustc_hex::{FromHex, ToHex};
+use std::any::Any;
+use std::panic;
+
+const CHECK_CONFIG_HELP: &str =
+    "Check the configuration the Node would start with, from the command line, the environment, the config file \
+     and the database, and report every problem found without starting the Node. Exits with 1 if there are any.";
+
+#[derive(Debug, Default, PartialEq)]
+struct CheckReport {
+    problems: Vec<String>,
+    notes: Vec<String>,
+}
+
+pub fn check_config(args: &Vec<String>, streams: &mut StdStreams) -> i32 {
+    let report = find_problems(args);
+    report.notes.iter().for_each(|note| {
+        writeln!(streams.stdout, "Note: {}", note).expect("Couldn't write to stdout")
+    });
+    report.problems.iter().for_each(|problem| {
+        writeln!(streams.stderr, "Problem: {}", problem).expect("Couldn't write to stderr")
+    });
+    if report.problems.is_empty() {
+        writeln!(streams.stdout, "Configuration is good").expect("Couldn't write to stdout");
+        0
+    } else {
+        writeln!(
+            streams.stderr,
+            "Found {} problem{} with the configuration",
+            report.problems.len(),
+            if report.problems.len() == 1 { "" } else { "s" }
+        )
+        .expect("Couldn't write to stderr");
+        1
+    }
+}
+
+pub fn app() -> App<'static, 'static> {
+    node_configurator_standard::app()
+        .about(CHECK_CONFIG_HELP)
+        .usage("PrometheusNode check-config [FLAGS] [OPTIONS]")
+        .arg(
+            Arg::with_name("check-config")
+                .long("check-config")
+                .required(true)
+                .takes_value(false)
+                .hidden(true),
+        )
+}
+
+// Nothing here is written anywhere: the database is only opened if it already exists, and none of
+// the settings the Node would remember are stored.
+fn find_problems(args: &Vec<String>) -> CheckReport {
+    let app = app();
+    let mut report = CheckReport::default();
+    let config_file_vcl_opt = match quietly(|| {
+        let (config_file_path, user_specified) = determine_config_file_path(&app, args);
+        ConfigFileVcl::new(&config_file_path, user_specified)
+    }) {
+        Ok(config_file_vcl) => Some(config_file_vcl),
+        Err(problem) => {
+            report.problems.push(problem);
+            None
+        }
+    };
+    let vcls = make_virtual_command_lines(&app, args, config_file_vcl_opt);
+    let multi_config = match MultiConfig::try_new(&app, vcls) {
+        Ok(multi_config) => multi_config,
+        Err(e) => {
+            // Until the parameters make sense to clap, there's nothing more to check
+            report.problems.push(clap_complaint(&e));
+            return report;
+        }
+    };
+    report.problems.extend(check_parameters(&multi_config));
+    let (_, data_directory, chain_id) = real_user_data_directory_and_chain_id(&multi_config);
+    let database_file = data_directory.join(DATABASE_FILE);
+    let persistent_config_opt = if database_file.exists() {
+        match database_password_opt(&multi_config).and_then(|password_opt| {
+            DbInitializerReal::with_password_opt(password_opt)
+                .initialize(&data_directory, chain_id)
+                .map_err(|e| format!("Can't use the database at {:?}: {:?}", database_file, e))
+        }) {
+            Ok(conn) => Some(PersistentConfigurationReal::from(conn)),
+            Err(problem) => {
+                report.problems.push(problem);
+                None
+            }
+        }
+    } else {
+        report.notes.push(format!(
+            "There's no database at {:?} yet; the Node will create one when it starts",
+            database_file
+        ));
+        None
+    };
+    let persistent_config_ref_opt = persistent_config_opt
+        .as_ref()
+        .map(|persistent_config| persistent_config as &dyn PersistentConfiguration);
+    report.problems.extend(check_payment_parameters(
+        &multi_config,
+        persistent_config_ref_opt,
+    ));
+    let wallet_report = check_wallets(&multi_config, persistent_config_ref_opt);
+    report.problems.extend(wallet_report.problems);
+    report.notes.extend(wallet_report.notes);
+    report
+}
+
+fn check_parameters(multi_config: &MultiConfig) -> Vec<String> {
+    let mut problems = vec![];
+    if let Err(problem) = quietly(|| standard::make_neighborhood_config(multi_config)) {
+        problems.push(problem)
+    }
+    let ui_port = value_m!(multi_config, "ui-port", u16);
+    if ui_port.is_some() && value_m!(multi_config, "rest-port", u16) == ui_port {
+        problems.push("--rest-port and --ui-port must be different".to_string())
+    }
+    if let Some(fake_public_key) = value_m!(multi_config, "fake-public-key", String) {
+        if base64::decode(&fake_public_key).is_err() {
+            problems.push(format!("Invalid fake public key: {}", fake_public_key))
+        }
+    }
+    problems
+}
+
+fn check_payment_parameters(
+    multi_config: &MultiConfig,
+    persistent_config_opt: Option<&dyn PersistentConfiguration>,
+) -> Vec<String> {
+    let mut accountant_config = AccountantConfig::default();
+    PAYMENT_PARAMETERS.iter().for_each(|parameter| {
+        let value = value_m!(multi_config, parameter.name(), u64).unwrap_or_else(|| {
+            persistent_config_opt
+                .map(|persistent_config| persistent_config.payment_parameter(*parameter))
+                .unwrap_or_else(|| parameter.default_value())
+        });
+        accountant_config.set_payment_parameter(*parameter, value);
+    });
+    match accountant_config.payment_curves.validate() {
+        Ok(()) => vec![],
+        Err(e) => vec![e],
+    }
+}
+
+// The same rules the Node applies to its wallets at startup, gathered up instead of stopping at
+// the first one broken
+fn check_wallets(
+    multi_config: &MultiConfig,
+    persistent_config_opt: Option<&dyn PersistentConfiguration>,
+) -> CheckReport {
+    let mut report = CheckReport::default();
+    let persistent_config = match persistent_config_opt {
+        Some(persistent_config) => persistent_config,
+        None => return check_consuming_private_key(multi_config, None),
+    };
+    let earning_wallet_address_opt = value_m!(multi_config, "earning-wallet", String);
+    let established_earning_wallet_opt = persistent_config.earning_wallet_from_address();
+    if let (Some(address), Some(wallet)) =
+        (&earning_wallet_address_opt, &established_earning_wallet_opt)
+    {
+        if wallet.to_string().to_lowercase() != address.to_lowercase() {
+            report.problems.push(format!(
+                "--earning-wallet specifies an address ({}) different from the one previously set ({})",
+                address, wallet
+            ))
+        }
+    }
+    let private_key_report = check_consuming_private_key(multi_config, Some(persistent_config));
+    report.problems.extend(private_key_report.problems);
+    let private_key_given = value_m!(multi_config, "consuming-private-key", String).is_some();
+    let earning_address_known =
+        earning_wallet_address_opt.is_some() || established_earning_wallet_opt.is_some();
+    let encrypted_mnemonic_seed_opt = persistent_config.encrypted_mnemonic_seed();
+    let derivation_path_opt = persistent_config.consuming_wallet_derivation_path();
+    if private_key_given && encrypted_mnemonic_seed_opt.is_some() {
+        if earning_address_known {
+            report.problems.push("Cannot use --consuming-private-key and an earning wallet address when the database contains a mnemonic seed".to_string())
+        } else if derivation_path_opt.is_some() {
+            report.problems.push("Cannot use --consuming-private-key when the database contains a mnemonic seed and a consuming wallet derivation path".to_string())
+        }
+    }
+    if encrypted_mnemonic_seed_opt.is_none() {
+        return report;
+    }
+    match value_m!(multi_config, "wallet-password", String) {
+        None => report.notes.push(
+            "Supply --wallet-password (preferably as SUB_WALLET_PASSWORD) to check that the wallet in the database can be decrypted".to_string(),
+        ),
+        Some(wallet_password) => match persistent_config.mnemonic_seed(&wallet_password) {
+            Err(e) => report.problems.push(format!(
+                "Can't decrypt the mnemonic seed in the database with --wallet-password: {:?}",
+                e
+            )),
+            Ok(mnemonic_seed) => {
+                if let Some(derivation_path) = derivation_path_opt {
+                    if let Err(e) = Bip32ECKeyPair::from_raw(mnemonic_seed.as_ref(), &derivation_path) {
+                        report.problems.push(format!(
+                            "Can't derive the consuming wallet at {} from the mnemonic seed: {}",
+                            derivation_path, e
+                        ))
+                    }
+                }
+            }
+        },
+    }
+    report
+}
+
+fn check_consuming_private_key(
+    multi_config: &MultiConfig,
+    persistent_config_opt: Option<&dyn PersistentConfiguration>,
+) -> CheckReport {
+    let mut report = CheckReport::default();
+    let private_key = match value_m!(multi_config, "consuming-private-key", String) {
+        Some(private_key) => private_key,
+        None => return report,
+    };
+    let keypair = match private_key
+        .from_hex::<Vec<u8>>()
+        .map_err(|e| format!("{}", e))
+        .and_then(|raw_secret| Bip32ECKeyPair::from_raw_secret(&raw_secret[..]))
+    {
+        Ok(keypair) => keypair,
+        Err(e) => {
+            report.problems.push(format!(
+                "Cannot create a consuming wallet from --consuming-private-key: {}",
+                e
+            ));
+            return report;
+        }
+    };
+    let established_public_key_opt = persistent_config_opt
+        .and_then(|persistent_config| persistent_config.consuming_wallet_public_key());
+    if let Some(established_public_key_hex) = established_public_key_opt {
+        if keypair.secret().public().bytes().to_hex::<String>() != established_public_key_hex {
+            report.problems.push(format!(
+                "--consuming-private-key does not denote the consuming wallet ({}) you have used in the past",
+                Wallet::from(keypair)
+            ))
+        }
+    }
+    report
+}
+
+// Much of the configuration code panics on the first thing it doesn't like; here those panics
+// become problems to report, without the usual backtrace noise on stderr.
+fn quietly<T, F>(f: F) -> Result<T, String>
+where
+    F: FnOnce() -> T,
+{
+    let previous_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(f));
+    panic::set_hook(previous_hook);
+    result.map_err(|payload| panic_message(payload.as_ref()))
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else {
+        "unexplained failure".to_string()
+    }
+}
+
+// clap's message starts with "error: " and goes on to usage instructions; the first line is enough
+pub fn clap_complaint(e: &clap::Error) -> String {
+    let first_line = e.message.lines().next().unwrap_or("");
+    first_line
+        .trim_start_matches("error: ")
+        .trim_start_matches("\u{1b}[1;31merror:\u{1b}[0m ")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::bip39::Bip39Error;
+    use crate::multi_config::CommandLineVcl;
+    use crate::sub_lib::cryptde::PlainData;
+    use crate::test_utils::environment_guard::EnvironmentGuard;
+    use crate::test_utils::persistent_configuration_mock::PersistentConfigurationMock;
+    use crate::test_utils::{
+        ensure_node_home_directory_exists, ArgsBuilder, FakeStreamHolder, DEFAULT_CHAIN_ID,
+    };
+    use std::str::FromStr;
+
+    fn make_multi_config<'a>(args: ArgsBuilder) -> MultiConfig<'a> {
+        let app = app();
+        MultiConfig::new(
+            &app,
+            vec![Box::new(CommandLineVcl::new(
+                args.opt("--check-config").into(),
+            ))],
+        )
+    }
+
+    #[test]
+    fn check_config_passes_a_good_configuration_without_creating_a_database() {
+        let _guard = EnvironmentGuard::new();
+        let data_dir = ensure_node_home_directory_exists(
+            "config_checker",
+            "check_config_passes_a_good_configuration_without_creating_a_database",
+        );
+        let mut holder = FakeStreamHolder::new();
+
+        let result = check_config(
+            &ArgsBuilder::new()
+                .opt("--check-config")
+                .param("--data-directory", data_dir.to_str().unwrap())
+                .param("--neighborhood-mode", "zero-hop")
+                .into(),
+            &mut holder.streams(),
+        );
+
+        assert_eq!(result, 0);
+        let stdout = holder.stdout.get_string();
+        assert!(stdout.contains("There's no database at"), "{}", stdout);
+        assert!(stdout.ends_with("Configuration is good\n"), "{}", stdout);
+        assert_eq!(holder.stderr.get_string(), "");
+        assert!(!data_dir.join(DATABASE_FILE).exists());
+    }
+
+    #[test]
+    fn check_config_reports_every_problem_it_finds() {
+        let _guard = EnvironmentGuard::new();
+        let data_dir = ensure_node_home_directory_exists(
+            "config_checker",
+            "check_config_reports_every_problem_it_finds",
+        );
+        let mut holder = FakeStreamHolder::new();
+
+        let result = check_config(
+            &ArgsBuilder::new()
+                .opt("--check-config")
+                .param("--data-directory", data_dir.to_str().unwrap())
+                .param("--neighborhood-mode", "zero-hop")
+                .param("--ip", "1.2.3.4")
+                .param("--ui-port", "5333")
+                .param("--rest-port", "5333")
+                .param("--fake-public-key", "not*base64")
+                .into(),
+            &mut holder.streams(),
+        );
+
+        assert_eq!(result, 1);
+        assert_eq!(
+            holder.stderr.get_string(),
+            "Problem: Node cannot run as --neighborhood_mode zero-hop if --ip is specified\n\
+             Problem: --rest-port and --ui-port must be different\n\
+             Problem: Invalid fake public key: not*base64\n\
+             Found 3 problems with the configuration\n"
+        );
+    }
+
+    #[test]
+    fn check_config_reports_what_clap_objects_to() {
+        let _guard = EnvironmentGuard::new();
+        let mut holder = FakeStreamHolder::new();
+
+        let result = check_config(
+            &ArgsBuilder::new()
+                .opt("--check-config")
+                .param("--clandestine-port", "80")
+                .into(),
+            &mut holder.streams(),
+        );
+
+        assert_eq!(result, 1);
+        let stderr = holder.stderr.get_string();
+        assert!(stderr.starts_with("Problem: Invalid value"), "{}", stderr);
+        assert!(
+            stderr.ends_with("Found 1 problem with the configuration\n"),
+            "{}",
+            stderr
+        );
+    }
+
+    #[test]
+    fn check_config_reports_a_missing_config_file_and_keeps_going() {
+        let _guard = EnvironmentGuard::new();
+        let data_dir = ensure_node_home_directory_exists(
+            "config_checker",
+            "check_config_reports_a_missing_config_file_and_keeps_going",
+        );
+        let mut holder = FakeStreamHolder::new();
+
+        let result = check_config(
+            &ArgsBuilder::new()
+                .opt("--check-config")
+                .param("--data-directory", data_dir.to_str().unwrap())
+                .param("--config-file", "missing.toml")
+                .param("--neighborhood-mode", "zero-hop")
+                .param("--ui-port", "5333")
+                .param("--rest-port", "5333")
+                .into(),
+            &mut holder.streams(),
+        );
+
+        assert_eq!(result, 1);
+        let stderr = holder.stderr.get_string();
+        assert!(
+            stderr.starts_with("Problem: Configuration file at "),
+            "{}",
+            stderr
+        );
+        assert!(
+            stderr.contains("Problem: --rest-port and --ui-port must be different\n"),
+            "{}",
+            stderr
+        );
+    }
+
+    #[test]
+    fn check_config_reports_an_encrypted_database_it_has_no_password_for() {
+        let _guard = EnvironmentGuard::new();
+        let data_dir = ensure_node_home_directory_exists(
+            "config_checker",
+            "check_config_reports_an_encrypted_database_it_has_no_password_for",
+        );
+        DbInitializerReal::new()
+            .initialize(&data_dir, DEFAULT_CHAIN_ID)
+            .unwrap();
+        let mut holder = FakeStreamHolder::new();
+
+        let result = check_config(
+            &ArgsBuilder::new()
+                .opt("--check-config")
+                .param("--data-directory", data_dir.to_str().unwrap())
+                .param("--neighborhood-mode", "zero-hop")
+                .opt("--encrypt-database")
+                .into(),
+            &mut holder.streams(),
+        );
+
+        assert_eq!(result, 1);
+        assert_eq!(
+            holder.stderr.get_string(),
+            "Problem: --encrypt-database needs the wallet password: specify --wallet-password\n\
+             Found 1 problem with the configuration\n"
+        );
+    }
+
+    #[test]
+    fn check_payment_parameters_finds_curves_that_slope_the_wrong_way() {
+        let multi_config = make_multi_config(
+            ArgsBuilder::new()
+                .param("--balance-to-decrease-from", "100")
+                .param("--permanent-debt-allowed", "200"),
+        );
+
+        let result = check_payment_parameters(&multi_config, None);
+
+        assert_eq!(
+            result,
+            vec![
+                "balance-to-decrease-from (100) must be greater than permanent-debt-allowed (200)"
+                    .to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn check_wallets_asks_for_a_wallet_password_to_check_the_seed() {
+        let multi_config = make_multi_config(ArgsBuilder::new());
+        let persistent_config = PersistentConfigurationMock::new()
+            .earning_wallet_from_address_result(None)
+            .encrypted_mnemonic_seed_result(Some("encrypted".to_string()))
+            .consuming_wallet_derivation_path_result(Some("m/44'/60'/0'/0/0".to_string()));
+
+        let result = check_wallets(&multi_config, Some(&persistent_config));
+
+        assert_eq!(result.problems, Vec::<String>::new());
+        assert_eq!(
+            result.notes,
+            vec!["Supply --wallet-password (preferably as SUB_WALLET_PASSWORD) to check that the wallet in the database can be decrypted".to_string()]
+        );
+    }
+
+    #[test]
+    fn check_wallets_finds_a_wrong_password_and_a_changed_earning_wallet() {
+        let multi_config = make_multi_config(
+            ArgsBuilder::new()
+                .param("--wallet-password", "wrong")
+                .param(
+                    "--earning-wallet",
+                    "0x0123456789012345678901234567890123456789",
+                ),
+        );
+        let persistent_config = PersistentConfigurationMock::new()
+            .earning_wallet_from_address_result(Some(
+                Wallet::from_str("0x9876543210987654321098765432109876543210").unwrap(),
+            ))
+            .encrypted_mnemonic_seed_result(Some("encrypted".to_string()))
+            .consuming_wallet_derivation_path_result(None)
+            .mnemonic_seed_result(Err(Bip39Error::DecryptionFailure("bad".to_string())));
+
+        let result = check_wallets(&multi_config, Some(&persistent_config));
+
+        assert_eq!(
+            result.problems,
+            vec![
+                "--earning-wallet specifies an address (0x0123456789012345678901234567890123456789) different from the one previously set (0x9876543210987654321098765432109876543210)".to_string(),
+                "Can't decrypt the mnemonic seed in the database with --wallet-password: DecryptionFailure(\"bad\")".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_wallets_derives_the_consuming_wallet_when_it_can() {
+        let multi_config =
+            make_multi_config(ArgsBuilder::new().param("--wallet-password", "password"));
+        let persistent_config = PersistentConfigurationMock::new()
+            .earning_wallet_from_address_result(None)
+            .encrypted_mnemonic_seed_result(Some("encrypted".to_string()))
+            .consuming_wallet_derivation_path_result(Some("m/44'/60'/0'/0/0".to_string()))
+            .mnemonic_seed_result(Ok(PlainData::new(&[3; 64])));
+
+        let result = check_wallets(&multi_config, Some(&persistent_config));
+
+        assert_eq!(result, CheckReport::default());
+    }
+
+    #[test]
+    fn check_wallets_finds_a_consuming_private_key_that_has_changed() {
+        let multi_config = make_multi_config(ArgsBuilder::new().param(
+            "--consuming-private-key",
+            "ABCDEF01ABCDEF01ABCDEF01ABCDEF01ABCDEF01ABCDEF01ABCDEF01ABCDEF01",
+        ));
+        let persistent_config = PersistentConfigurationMock::new()
+            .earning_wallet_from_address_result(None)
+            .consuming_wallet_public_key_result(Some("0123456789abcdef".to_string()))
+            .encrypted_mnemonic_seed_result(None)
+            .consuming_wallet_derivation_path_result(None);
+
+        let result = check_wallets(&multi_config, Some(&persistent_config));
+
+        assert_eq!(result.problems.len(), 1, "{:?}", result);
+        assert!(
+            result.problems[0]
+                .starts_with("--consuming-private-key does not denote the consuming wallet (0x"),
+            "{:?}",
+            result
+        );
+    }
+}
@@ -0,0 +1,166 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use crate::multi_config::{CommandLineVcl, NameValueVclArg, VclArg};
+use clap::App;
+use std::collections::HashSet;
+use std::net::Ipv4Addr;
+
+// Names are spread over this many slots by a hash. Two names in the same slot want the same ports
+// and loopback address, so one of them needs a different name or ports of its own.
+pub const INSTANCE_SLOTS: u16 = 1000;
+const UI_PORT_BASE: u16 = 20000;
+const CLANDESTINE_PORT_BASE: u16 = 30000;
+const MAX_NAME_LENGTH: usize = 32;
+
+// One of several Nodes sharing a host. Everything that would otherwise collide comes from the name:
+// a data directory of its own, and with it the database; the log file; the UI and clandestine
+// ports; and the loopback address where the DNS, HTTP and TLS ports listen, since those can't move.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Instance {
+    pub name: String,
+    slot: u16,
+}
+
+impl Instance {
+    pub fn new(name: &str) -> Instance {
+        let name = name.to_lowercase();
+        let slot = (fnv1a(&name) % INSTANCE_SLOTS as u32) as u16;
+        Instance { name, slot }
+    }
+
+    pub fn ui_port(&self) -> u16 {
+        UI_PORT_BASE + self.slot
+    }
+
+    pub fn clandestine_port(&self) -> u16 {
+        CLANDESTINE_PORT_BASE + self.slot
+    }
+
+    // Somewhere in 127.1.0.1 - 127.1.3.250. All of 127.0.0.0/8 is loopback on Linux and Windows;
+    // macOS answers only on 127.0.0.1 unless the address is added to lo0.
+    pub fn loopback_ip(&self) -> Ipv4Addr {
+        Ipv4Addr::new(127, 1, (self.slot / 250) as u8, (self.slot % 250 + 1) as u8)
+    }
+}
+
+// DefaultHasher may change between Rust releases, and an instance's ports mustn't
+fn fnv1a(name: &str) -> u32 {
+    name.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+// The name becomes part of a directory and a file name, so it's kept to what's safe in both
+pub fn validate_instance_name(name: String) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+        return Err(format!(
+            "Instance name must be 1 to {} characters long: {}",
+            MAX_NAME_LENGTH, name
+        ));
+    }
+    if name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(())
+    } else {
+        Err(format!(
+            "Instance name may contain only letters, digits, '-' and '_': {}",
+            name
+        ))
+    }
+}
+
+// Like a profile, only what the schema knows about is supplied
+pub fn instance_vcl(instance: &Instance, schema: &App) -> CommandLineVcl {
+    let opt_names: HashSet<String> = schema
+        .p
+        .opts
+        .iter()
+        .map(|opt| opt.b.name.to_string())
+        .collect();
+    let vcl_args: Vec<Box<dyn VclArg>> = vec![
+        ("ui-port", instance.ui_port()),
+        ("clandestine-port", instance.clandestine_port()),
+    ]
+    .into_iter()
+    .filter(|(name, _)| opt_names.contains(*name))
+    .map(|(name, port)| {
+        Box::new(NameValueVclArg::new(
+            &format!("--{}", name),
+            &port.to_string(),
+        )) as Box<dyn VclArg>
+    })
+    .collect();
+    CommandLineVcl::from(vcl_args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::multi_config::VirtualCommandLine;
+    use clap::Arg;
+
+    #[test]
+    fn an_instance_is_the_same_whenever_and_however_its_name_is_given() {
+        let subject = Instance::new("Alice");
+
+        assert_eq!(subject, Instance::new("alice"));
+        assert_eq!(subject.name, "alice".to_string());
+        assert_eq!(fnv1a("alice"), 0x8722_13e7);
+        assert_eq!(subject.slot, 479);
+        assert_eq!(subject.ui_port(), 20479);
+    }
+
+    #[test]
+    fn ports_and_loopback_address_come_from_the_slot() {
+        let first = Instance {
+            name: "first".to_string(),
+            slot: 0,
+        };
+        let last = Instance {
+            name: "last".to_string(),
+            slot: INSTANCE_SLOTS - 1,
+        };
+
+        assert_eq!(first.ui_port(), 20000);
+        assert_eq!(first.clandestine_port(), 30000);
+        assert_eq!(first.loopback_ip(), Ipv4Addr::new(127, 1, 0, 1));
+        assert_eq!(last.ui_port(), 20999);
+        assert_eq!(last.clandestine_port(), 30999);
+        assert_eq!(last.loopback_ip(), Ipv4Addr::new(127, 1, 3, 250));
+    }
+
+    #[test]
+    fn instance_names_must_be_safe_in_file_names() {
+        assert_eq!(validate_instance_name("node-1_B".to_string()), Ok(()));
+        assert_eq!(
+            validate_instance_name("../booga".to_string()),
+            Err(
+                "Instance name may contain only letters, digits, '-' and '_': ../booga".to_string()
+            )
+        );
+        assert_eq!(
+            validate_instance_name(String::new()),
+            Err("Instance name must be 1 to 32 characters long: ".to_string())
+        );
+        assert!(validate_instance_name("x".repeat(33)).is_err());
+    }
+
+    #[test]
+    fn instance_vcl_supplies_only_what_the_schema_understands() {
+        let instance = Instance {
+            name: "test".to_string(),
+            slot: 42,
+        };
+        let schema =
+            App::new("test").arg(Arg::with_name("ui-port").long("ui-port").takes_value(true));
+
+        let result = instance_vcl(&instance, &schema);
+
+        assert_eq!(
+            result.args(),
+            vec!["".to_string(), "--ui-port".to_string(), "20042".to_string()]
+        );
+    }
+}
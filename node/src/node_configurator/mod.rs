@@ -1,16 +1,30 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
+pub mod completions;
+pub mod config_checker;
+pub mod instance;
 pub mod node_configurator_generate_wallet;
 pub mod node_configurator_recover_wallet;
 pub mod node_configurator_standard;
+pub mod node_setup;
+pub mod profile;
+pub mod secret_store;
 
+use crate::audit_log::{audit, init_audit_log, AuditSource};
 use crate::blockchain::bip32::Bip32ECKeyPair;
 use crate::blockchain::bip39::{Bip39, Bip39Error};
-use crate::blockchain::blockchain_interface::{chain_id_from_name, DEFAULT_CHAIN_NAME};
+use crate::blockchain::blockchain_interface::{
+    chain_id_from_name, CHAIN_NAMES, DEFAULT_CHAIN_NAME,
+};
 use crate::bootstrapper::RealUser;
 use crate::database::db_initializer::{DbInitializer, DbInitializerReal, DATABASE_FILE};
-use crate::multi_config::{merge, CommandLineVcl, EnvironmentVcl, MultiConfig, VclArg};
+use crate::multi_config::{
+    merge, CommandLineVcl, ConfigFileVcl, EnvironmentVcl, MultiConfig, VclArg, VirtualCommandLine,
+};
+use crate::node_configurator::instance::{instance_vcl, validate_instance_name, Instance};
+use crate::node_configurator::profile::{profile_from_name, profile_vcl, Profile, PROFILE_NAMES};
 use crate::persistent_configuration::{PersistentConfiguration, PersistentConfigurationReal};
+use crate::privilege_drop::resolve_run_as;
 use crate::sub_lib::cryptde::PlainData;
 use crate::sub_lib::main_tools::StdStreams;
 use crate::sub_lib::wallet::Wallet;
@@ -40,7 +54,9 @@ lazy_static! {
 
 const CHAIN_HELP: &str =
     "The blockchain network SubstratumNode will configure itself to use. You must ensure the \
-    Ethereum client specified by --blockchain-service-url communicates with the same blockchain network.";
+    Ethereum client specified by --blockchain-service-url communicates with the same blockchain network. \
+    xdai is a sidechain whose gas costs suit small payments; if you don't specify \
+    --blockchain-service-url for it, SubstratumNode uses the public xDai RPC endpoint.";
 pub const CONFIG_FILE_HELP: &str =
     "Optional TOML file containing configuration that doesn't often change. Should contain only \
      scalar items, string or numeric, whose names are exactly the same as the command-line parameters \
@@ -66,16 +82,48 @@ pub const EARNING_WALLET_HELP: &str =
      BIP32 derivation path (defaults to m/44'/60'/0'/0/1) or an Ethereum wallet address. (If the derivation path \
      includes single quotes, enclose it in double quotes.) Addresses must begin with 0x followed by 40 hexadecimal \
      digits (case-insensitive).";
+pub const ENCRYPTED_DATABASE_HELP: &str =
+    "Open a database the Node encrypts with --encrypt-database, using the key derived from --wallet-password. \
+     A database that isn't encrypted yet is encrypted by this, just as it would be by the Node.";
+const ENVIRONMENT_HELP: &str =
+    "Every option can also be set in the environment, which is the place for secrets: SUB_ followed by the \
+     option's name in capitals with underscores for dashes, such as SUB_DATA_DIRECTORY for --data-directory. \
+     A flag such as --json is set by any value except 0, false, no or off. The command line overrides the \
+     environment, and the environment overrides the config file.";
+pub const INSTANCE_NAME_HELP: &str =
+    "Run this Node as one of several on the same machine. The name picks a data directory of its own inside the \
+     --data-directory, and with it a database and config file of its own; names the log file after it; and sets \
+     --ui-port and --clandestine-port to ports of its own. The DNS, HTTP and TLS ports can't move, so they listen \
+     on a loopback address of the instance's own (in 127.1.0.0/16) instead of 127.0.0.1. Anything you specify \
+     yourself overrides the instance. The name decides where the config file is, so it must not be specified in \
+     a config file. Letters, digits, '-' and '_' only.";
+pub const KEYCHAIN_HELP: &str =
+    "Keep the wallet password and consuming private key in the operating system's keychain (Keychain on macOS, \
+     the Credential Manager on Windows, the Secret Service on Linux) instead of anywhere in plain text. A secret \
+     you supply some other way is stored there for next time; a secret you don't supply is taken from there.";
 pub const LANGUAGE_HELP: &str = "The language of the mnemonic phrase.";
 pub const MNEMONIC_PASSPHRASE_HELP: &str =
     "A passphrase for the mnemonic phrase. Cannot be changed later and still produce the same addresses. This is a \
      secret; providing it on the command line or in a config file is insecure and unwise. If you don't specify it anywhere, \
      you'll be prompted for it at the console.";
+pub const PROFILE_HELP: &str =
+    "A bundle of defaults for one network: its chain, bootstrap neighbors and UI port. Each profile also keeps its \
+     state in a data directory of its own, so switching between testnet and mainnet doesn't disturb either one. \
+     Anything else you specify overrides the profile. The profile decides where the config file is, so it must not \
+     be specified in a config file.";
 pub const REAL_USER_HELP: &str =
     "The user whose identity Node will assume when dropping privileges after bootstrapping. Since Node refuses to \
      run with root privilege after bootstrapping, you might want to use this if you start the Node as root, or if \
      you start the Node using pkexec or some other method that doesn't populate the SUDO_xxx variables. Use a value \
      like <uid>:<gid>:<home directory>.";
+pub const RUN_AS_HELP: &str =
+    "The account Node drops to once it has bound its privileged ports, given as <user> or <user>:<group>, by name \
+     or number, for running Node under a dedicated system account. Without a group, the user's own group is used; \
+     supplementary groups are dropped either way. The user's home directory is where the data directory goes by \
+     default. Use this or --real-user, not both.";
+pub const DATABASE_WALLET_PASSWORD_HELP: &str =
+    "The wallet password the database is encrypted with; only needed with --encrypt-database. This is a secret; \
+     providing it on the command line or in a config file is insecure and unwise.";
 pub const WALLET_PASSWORD_HELP: &str =
     "A password or phrase to encrypt your consuming wallet in the PrometheusNode database or decrypt a keystore file. Can be changed \
      later and still produce the same addresses. This is a secret; providing it on the command line or in a config file is \
@@ -92,6 +140,41 @@ pub fn app_head() -> App<'static, 'static> {
         // .author(crate_authors!("\n"))
         .author("Substratum, The-Dreadnought")
         .about(crate_description!())
+        .after_help(ENVIRONMENT_HELP)
+}
+
+// The one place that decides where configuration comes from. Each source overrides the ones before
+// it: the --profile defaults, the --instance-name ports, the config file (if there is one), then SUB_
+// environment variables, then the command line.
+pub fn make_multi_config<'a>(
+    app: &App<'a, 'a>,
+    args: &Vec<String>,
+    config_file_vcl_opt: Option<ConfigFileVcl>,
+) -> MultiConfig<'a> {
+    MultiConfig::new(
+        app,
+        make_virtual_command_lines(app, args, config_file_vcl_opt),
+    )
+}
+
+pub fn make_virtual_command_lines(
+    app: &App,
+    args: &Vec<String>,
+    config_file_vcl_opt: Option<ConfigFileVcl>,
+) -> Vec<Box<dyn VirtualCommandLine>> {
+    let mut vcls: Vec<Box<dyn VirtualCommandLine>> = vec![];
+    if let Some(profile) = determine_profile(app, args) {
+        vcls.push(Box::new(profile_vcl(profile, app)));
+    }
+    if let Some(instance) = determine_instance(app, args) {
+        vcls.push(Box::new(instance_vcl(&instance, app)));
+    }
+    if let Some(config_file_vcl) = config_file_vcl_opt {
+        vcls.push(Box::new(config_file_vcl));
+    }
+    vcls.push(Box::new(EnvironmentVcl::new(app)));
+    vcls.push(Box::new(CommandLineVcl::new(args.clone())));
+    vcls
 }
 
 // These Args are needed in more than one clap schema. To avoid code duplication, they're defined here and referred
@@ -133,7 +216,7 @@ pub fn chain_arg<'a>() -> Arg<'a, 'a> {
         .min_values(1)
         .max_values(1)
         .takes_value(true)
-        .possible_values(&["dev", "mainnet", "ropsten"])
+        .possible_values(&CHAIN_NAMES)
         .default_value(DEFAULT_CHAIN_NAME)
         .help(CHAIN_HELP)
 }
@@ -152,6 +235,29 @@ where
         .help(help)
 }
 
+pub fn encrypt_database_arg(help: &str) -> Arg {
+    Arg::with_name("encrypt-database")
+        .long("encrypt-database")
+        .takes_value(false)
+        .help(help)
+}
+
+pub fn instance_name_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("instance-name")
+        .long("instance-name")
+        .value_name("INSTANCE-NAME")
+        .takes_value(true)
+        .validator(validate_instance_name)
+        .help(INSTANCE_NAME_HELP)
+}
+
+pub fn keychain_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("keychain")
+        .long("keychain")
+        .takes_value(false)
+        .help(KEYCHAIN_HELP)
+}
+
 pub fn language_arg<'a>() -> Arg<'a, 'a> {
     Arg::with_name("language")
         .alias("language")
@@ -175,6 +281,16 @@ pub fn mnemonic_passphrase_arg<'a>() -> Arg<'a, 'a> {
         .help(MNEMONIC_PASSPHRASE_HELP)
 }
 
+pub fn profile_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("profile")
+        .long("profile")
+        .value_name("PROFILE")
+        .takes_value(true)
+        .possible_values(&PROFILE_NAMES)
+        .case_insensitive(true)
+        .help(PROFILE_HELP)
+}
+
 #[cfg(not(target_os = "windows"))]
 pub fn real_user_arg<'a>() -> Arg<'a, 'a> {
     Arg::with_name("real-user")
@@ -197,6 +313,30 @@ pub fn real_user_arg<'a>() -> Arg<'a, 'a> {
         .hidden(true)
 }
 
+#[cfg(not(target_os = "windows"))]
+pub fn run_as_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("run-as")
+        .long("run-as")
+        .value_name("USER[:GROUP]")
+        .required(false)
+        .takes_value(true)
+        .conflicts_with("real-user")
+        .validator(common_validators::validate_run_as)
+        .help(RUN_AS_HELP)
+}
+
+#[cfg(target_os = "windows")]
+pub fn run_as_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("run-as")
+        .long("run-as")
+        .value_name("USER[:GROUP]")
+        .required(false)
+        .takes_value(true)
+        .conflicts_with("real-user")
+        .validator(common_validators::validate_run_as)
+        .hidden(true)
+}
+
 pub fn wallet_password_arg(help: &str) -> Arg {
     Arg::with_name("wallet-password")
         .long("wallet-password")
@@ -227,11 +367,47 @@ pub fn determine_config_file_path(app: &App, args: &Vec<String>) -> (PathBuf, bo
     let config_file_path =
         value_m!(multi_config, "config-file", PathBuf).expect("config-file should be defaulted");
     let user_specified = multi_config.arg_matches().occurrences_of("config-file") > 0;
-    let data_directory: PathBuf = value_m!(multi_config, "data-directory", PathBuf)
-        .expect("data-directory should be defaulted");
+    let data_directory = match (
+        value_user_specified_m!(multi_config, "data-directory", PathBuf),
+        determine_profile(app, args),
+    ) {
+        ((Some(_), false), Some(profile)) => {
+            PathBuf::from(data_directory_default(&RealDirsWrapper {}, profile.name))
+        }
+        ((Some(data_directory), _), _) => data_directory,
+        _ => panic!("data-directory should be defaulted"),
+    };
+    let data_directory = match determine_instance(app, args) {
+        Some(instance) => data_directory.join(instance.name),
+        None => data_directory,
+    };
     (data_directory.join(config_file_path), user_specified)
 }
 
+// The profile has to be known before anything else is read, because it supplies the lowest-priority
+// defaults and decides where the config file is; so, like --config-file, it comes only from the
+// command line or the environment.
+pub fn determine_profile(app: &App, args: &Vec<String>) -> Option<&'static Profile> {
+    early_value(app, args, "--profile").and_then(|name| profile_from_name(&name))
+}
+
+// Like the profile, the instance decides where the config file is
+pub fn determine_instance(app: &App, args: &Vec<String>) -> Option<Instance> {
+    early_value(app, args, "--instance-name").map(|name| Instance::new(&name))
+}
+
+// For what's needed before the config file can be read: only the environment and the command line
+pub fn early_value(app: &App, args: &Vec<String>, name: &str) -> Option<String> {
+    merge(
+        Box::new(EnvironmentVcl::new(app)),
+        Box::new(CommandLineVcl::new(args.clone())),
+    )
+    .vcl_args()
+    .into_iter()
+    .find(|vcl_arg| vcl_arg.name() == name)
+    .and_then(|vcl_arg| vcl_arg.to_args().get(1).cloned())
+}
+
 pub fn create_wallet(
     config: &WalletCreationConfig,
     persistent_config: &dyn PersistentConfiguration,
@@ -254,11 +430,33 @@ pub fn create_wallet(
     }
 }
 
+// Names the wallets, never the seed or the password that protects it
+pub fn audit_wallet_creation(action: &str, config: &WalletCreationConfig) {
+    let earning = match &config.earning_wallet_address_opt {
+        Some(address) => format!("earning wallet {}", address),
+        None => "no earning wallet".to_string(),
+    };
+    let consuming = match config
+        .derivation_path_info_opt
+        .as_ref()
+        .and_then(|info| info.consuming_derivation_path_opt.as_ref())
+    {
+        Some(path) => format!("consuming wallet at {}", path),
+        None => "no consuming wallet".to_string(),
+    };
+    audit(
+        AuditSource::Cli,
+        action,
+        &format!("{}, {}", earning, consuming),
+    );
+}
+
 pub fn initialize_database(
     data_directory: &PathBuf,
     chain_id: u8,
+    password_opt: Option<String>,
 ) -> Box<dyn PersistentConfiguration> {
-    let conn = DbInitializerReal::new()
+    let conn = DbInitializerReal::with_password_opt(password_opt)
         .initialize(data_directory, chain_id)
         .unwrap_or_else(|e| {
             panic!(
@@ -270,16 +468,38 @@ pub fn initialize_database(
     Box::new(PersistentConfigurationReal::from(conn))
 }
 
+// The database has to be opened before anything else is read from it, so with --encrypt-database
+// the wallet password has to be there from the start
+pub fn database_password_opt(multi_config: &MultiConfig) -> Result<Option<String>, String> {
+    if !multi_config.arg_matches().is_present("encrypt-database") {
+        return Ok(None);
+    }
+    match value_m!(multi_config, "wallet-password", String) {
+        Some(password) => Ok(Some(password)),
+        None => Err(
+            "--encrypt-database needs the wallet password: specify --wallet-password".to_string(),
+        ),
+    }
+}
+
 pub fn real_user_data_directory_and_chain_id(
     multi_config: &MultiConfig,
 ) -> (RealUser, PathBuf, u8) {
-    let real_user = match value_m!(multi_config, "real-user", RealUser) {
-        None => RealUser::null().populate(),
-        Some(real_user) => real_user.populate(),
+    let real_user = match value_m!(multi_config, "run-as", String) {
+        Some(run_as) => resolve_run_as(&run_as).expect("--run-as is not properly validated"),
+        None => match value_m!(multi_config, "real-user", RealUser) {
+            None => RealUser::null().populate(),
+            Some(real_user) => real_user.populate(),
+        },
     };
 
     let chain_name =
         value_m!(multi_config, "chain", String).expect("--chain improperly defined in clap schema");
+    // A profile keeps its state apart from the plain chain's, and from other profiles'
+    let data_directory_leaf = match value_m!(multi_config, "profile", String) {
+        Some(profile_name) => profile_name.to_lowercase(),
+        None => chain_name.clone(),
+    };
 
     let data_directory = match value_user_specified_m!(multi_config, "data-directory", PathBuf) {
         (Some(data_directory), true) => data_directory,
@@ -305,10 +525,15 @@ pub fn real_user_data_directory_and_chain_id(
                 wrong_local_data_dir.replace(&wrong_home_dir, &right_home_dir);
             PathBuf::from(right_local_data_dir)
                 .join("Prometheus")
-                .join(chain_name.clone())
+                .join(data_directory_leaf)
         }
         _ => panic!("--data-directory improperly defined in clap schema"),
     };
+    // Each instance has a directory of its own inside the data directory, whoever chose it
+    let data_directory = match value_m!(multi_config, "instance-name", String) {
+        Some(instance_name) => data_directory.join(Instance::new(&instance_name).name),
+        None => data_directory,
+    };
 
     (
         real_user,
@@ -321,19 +546,14 @@ pub fn prepare_initialization_mode<'a>(
     app: &'a App,
     args: &Vec<String>,
 ) -> (MultiConfig<'a>, Box<dyn PersistentConfiguration>) {
-    let multi_config = MultiConfig::new(
-        &app,
-        vec![
-            Box::new(CommandLineVcl::new(args.clone())),
-            Box::new(EnvironmentVcl::new(&app)),
-        ],
-    );
+    let multi_config = make_multi_config(&app, args, None);
 
     let (_, data_directory, chain_id) = real_user_data_directory_and_chain_id(&multi_config);
-    let persistent_config_box = initialize_database(&data_directory, chain_id);
+    let persistent_config_box = initialize_database(&data_directory, chain_id, None);
     if persistent_config_box.encrypted_mnemonic_seed().is_some() {
         exit(1, "Cannot re-initialize Node: already initialized")
     }
+    init_audit_log(&data_directory);
     (multi_config, persistent_config_box)
 }
 
@@ -519,6 +739,7 @@ pub fn flushed_write(target: &mut dyn io::Write, string: &str) {
 }
 
 pub mod common_validators {
+    use crate::privilege_drop::resolve_run_as;
     use regex::Regex;
     use tiny_hderive::bip44::DerivationPath;
 
@@ -564,6 +785,10 @@ pub mod common_validators {
         }
     }
 
+    pub fn validate_run_as(run_as: String) -> Result<(), String> {
+        resolve_run_as(&run_as).map(|_| ())
+    }
+
     pub fn validate_real_user(triple: String) -> Result<(), String> {
         if Regex::new("^[0-9]*:[0-9]*:.*$")
             .expect("Failed to compile regular expression")
@@ -610,6 +835,8 @@ pub struct WalletCreationConfig {
     pub earning_wallet_address_opt: Option<String>,
     pub derivation_path_info_opt: Option<DerivationPathWalletInfo>,
     pub real_user: RealUser,
+    // The data directory to file the wallet password under in the keychain, if --keychain
+    pub keychain_data_directory_opt: Option<PathBuf>,
 }
 
 pub trait WalletCreationConfigMaker {
@@ -617,6 +844,7 @@ pub trait WalletCreationConfigMaker {
         &self,
         multi_config: &MultiConfig,
         streams: &mut StdStreams<'_>,
+        persistent_config: &dyn PersistentConfiguration,
     ) -> WalletCreationConfig {
         let mnemonic_passphrase = match value_m!(multi_config, "mnemonic-passphrase", String) {
             Some(mp) => mp,
@@ -641,7 +869,12 @@ pub trait WalletCreationConfigMaker {
                     Err(_) => panic!("--earning-wallet not properly validated by clap"),
                 },
             },
-            None => self.make_earning_wallet_info(streams),
+            // A watch-only earning wallet set up earlier keeps receiving payments; the new seed
+            // only supplies the consuming wallet.
+            None => match persistent_config.earning_wallet_address() {
+                Some(address) => Either::Left(address),
+                None => self.make_earning_wallet_info(streams),
+            },
         };
         let mnemonic_seed = self.make_mnemonic_seed(
             multi_config,
@@ -654,6 +887,11 @@ pub trait WalletCreationConfigMaker {
             Some(ru) => ru,
             None => RealUser::null(),
         };
+        let keychain_data_directory_opt = if multi_config.arg_matches().is_present("keychain") {
+            Some(real_user_data_directory_and_chain_id(multi_config).1)
+        } else {
+            None
+        };
         WalletCreationConfig {
             earning_wallet_address_opt: match &earning_wallet_info {
                 Either::Left(address) => Some(address.clone()),
@@ -670,6 +908,7 @@ pub trait WalletCreationConfigMaker {
                 consuming_derivation_path_opt: Some(consuming_derivation_path),
             }),
             real_user,
+            keychain_data_directory_opt,
         }
     }
 
@@ -727,10 +966,11 @@ mod tests {
     use crate::node_test_utils::MockDirsWrapper;
     use crate::sub_lib::wallet::{Wallet, DEFAULT_EARNING_DERIVATION_PATH};
     use crate::test_utils::environment_guard::EnvironmentGuard;
+    use crate::test_utils::logging::{init_test_logging, TestLogHandler};
     use crate::test_utils::persistent_configuration_mock::PersistentConfigurationMock;
     use crate::test_utils::{
-        ensure_node_home_directory_exists, ArgsBuilder, ByteArrayWriter, DEFAULT_CHAIN_ID,
-        TEST_DEFAULT_CHAIN_NAME,
+        ensure_node_home_directory_exists, ArgsBuilder, ByteArrayWriter, FakeStreamHolder,
+        DEFAULT_CHAIN_ID, TEST_DEFAULT_CHAIN_NAME,
     };
     use bip39::{Mnemonic, MnemonicType, Seed};
     use std::io::Cursor;
@@ -938,6 +1178,148 @@ mod tests {
         assert_eq!(result, expected.as_path().to_str().unwrap().to_string());
     }
 
+    #[test]
+    fn make_multi_config_prefers_command_line_then_environment_then_config_file() {
+        let _guard = EnvironmentGuard::new();
+        let home_dir = ensure_node_home_directory_exists(
+            "node_configurator",
+            "make_multi_config_prefers_command_line_then_environment_then_config_file",
+        );
+        let config_file_path = home_dir.join("config.toml");
+        std::fs::write(
+            &config_file_path,
+            "from-everywhere = \"file\"\nfrom-file-and-environment = \"file\"\nfrom-file = \"file\"\n",
+        )
+        .unwrap();
+        std::env::set_var("SUB_FROM_EVERYWHERE", "environment");
+        std::env::set_var("SUB_FROM_FILE_AND_ENVIRONMENT", "environment");
+        std::env::set_var("SUB_VERBOSE", "yes");
+        std::env::set_var("SUB_QUIET", "false");
+        let app = App::new("test")
+            .arg(
+                Arg::with_name("from-everywhere")
+                    .long("from-everywhere")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("from-file-and-environment")
+                    .long("from-file-and-environment")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("from-file")
+                    .long("from-file")
+                    .takes_value(true),
+            )
+            .arg(Arg::with_name("verbose").long("verbose").takes_value(false))
+            .arg(Arg::with_name("quiet").long("quiet").takes_value(false));
+        let args = ArgsBuilder::new()
+            .param("--from-everywhere", "command line")
+            .into();
+
+        let multi_config = make_multi_config(
+            &app,
+            &args,
+            Some(ConfigFileVcl::new(&config_file_path, true)),
+        );
+
+        assert_eq!(
+            value_m!(multi_config, "from-everywhere", String),
+            Some("command line".to_string())
+        );
+        assert_eq!(
+            value_m!(multi_config, "from-file-and-environment", String),
+            Some("environment".to_string())
+        );
+        assert_eq!(
+            value_m!(multi_config, "from-file", String),
+            Some("file".to_string())
+        );
+        assert!(multi_config.arg_matches().is_present("verbose"));
+        assert!(!multi_config.arg_matches().is_present("quiet"));
+    }
+
+    #[test]
+    fn make_multi_config_fills_in_from_the_profile_only_what_nobody_else_says() {
+        let _guard = EnvironmentGuard::new();
+        let app = App::new("test")
+            .arg(chain_arg())
+            .arg(profile_arg())
+            .arg(Arg::with_name("ui-port").long("ui-port").takes_value(true));
+        let args = ArgsBuilder::new()
+            .param("--profile", "testnet")
+            .param("--ui-port", "1234")
+            .into();
+
+        let multi_config = make_multi_config(&app, &args, None);
+
+        assert_eq!(
+            value_m!(multi_config, "chain", String),
+            Some("ropsten".to_string())
+        );
+        assert_eq!(value_m!(multi_config, "ui-port", u16), Some(1234));
+    }
+
+    #[test]
+    fn determine_profile_looks_in_the_environment_too() {
+        let _guard = EnvironmentGuard::new();
+        let app = App::new("test").arg(profile_arg());
+        std::env::set_var("SUB_PROFILE", "dev");
+
+        let result = determine_profile(&app, &ArgsBuilder::new().into());
+
+        assert_eq!(result.map(|profile| profile.name), Some("dev"));
+    }
+
+    #[test]
+    fn make_multi_config_takes_ports_from_the_instance_over_the_profile() {
+        let _guard = EnvironmentGuard::new();
+        let app = App::new("test")
+            .arg(chain_arg())
+            .arg(profile_arg())
+            .arg(instance_name_arg())
+            .arg(Arg::with_name("ui-port").long("ui-port").takes_value(true))
+            .arg(
+                Arg::with_name("clandestine-port")
+                    .long("clandestine-port")
+                    .takes_value(true),
+            );
+        let args = ArgsBuilder::new()
+            .param("--profile", "testnet")
+            .param("--instance-name", "alice")
+            .param("--clandestine-port", "1234")
+            .into();
+
+        let multi_config = make_multi_config(&app, &args, None);
+
+        assert_eq!(
+            value_m!(multi_config, "ui-port", u16),
+            Some(Instance::new("alice").ui_port())
+        );
+        assert_eq!(value_m!(multi_config, "clandestine-port", u16), Some(1234));
+    }
+
+    #[test]
+    fn real_user_data_directory_and_chain_id_puts_an_instance_inside_the_data_directory() {
+        let _guard = EnvironmentGuard::new();
+        let app = App::new("test")
+            .arg(chain_arg())
+            .arg(data_directory_arg())
+            .arg(instance_name_arg())
+            .arg(real_user_arg())
+            .arg(run_as_arg());
+        let args = ArgsBuilder::new()
+            .param("--data-directory", "data-dir")
+            .param("--instance-name", "Alice")
+            .param("--real-user", "123:456:/home/booga")
+            .into();
+        let multi_config = make_multi_config(&app, &args, None);
+
+        let (_, data_directory, _) = real_user_data_directory_and_chain_id(&multi_config);
+
+        assert_eq!(data_directory, PathBuf::from("data-dir").join("alice"));
+    }
+
     #[test]
     #[should_panic(expected = "1 Cannot re-initialize Node: already initialized")]
     fn prepare_initialization_mode_fails_if_mnemonic_seed_already_exists() {
@@ -990,6 +1372,53 @@ mod tests {
         assert_eq!(true, user_specified);
     }
 
+    #[test]
+    fn determine_config_file_path_looks_in_the_profile_data_directory() {
+        let _guard = EnvironmentGuard::new();
+        let app = determine_config_file_path_app().arg(profile_arg());
+        let args = ArgsBuilder::new().param("--profile", "testnet");
+
+        let (config_file_path, user_specified) = determine_config_file_path(&app, &args.into());
+
+        assert_eq!(
+            config_file_path,
+            PathBuf::from(data_directory_default(&RealDirsWrapper {}, "testnet"))
+                .join("config.toml")
+        );
+        assert_eq!(false, user_specified);
+    }
+
+    #[test]
+    fn determine_config_file_path_prefers_a_data_directory_to_the_profile() {
+        let _guard = EnvironmentGuard::new();
+        let app = determine_config_file_path_app().arg(profile_arg());
+        let args = ArgsBuilder::new()
+            .param("--profile", "testnet")
+            .param("--data-directory", "data-dir");
+
+        let (config_file_path, _) = determine_config_file_path(&app, &args.into());
+
+        assert_eq!(
+            config_file_path,
+            PathBuf::from("data-dir").join("config.toml")
+        );
+    }
+
+    #[test]
+    fn determine_config_file_path_looks_in_the_instance_data_directory() {
+        let _guard = EnvironmentGuard::new();
+        let app = determine_config_file_path_app().arg(instance_name_arg());
+        let args = ArgsBuilder::new().param("--data-directory", "data-dir");
+        std::env::set_var("SUB_INSTANCE_NAME", "bob");
+
+        let (config_file_path, _) = determine_config_file_path(&app, &args.into());
+
+        assert_eq!(
+            config_file_path,
+            PathBuf::from("data-dir").join("bob").join("config.toml")
+        );
+    }
+
     #[test]
     fn determine_config_file_path_finds_path_in_environment() {
         let _guard = EnvironmentGuard::new();
@@ -1339,8 +1768,11 @@ mod tests {
         pub fn new() -> TameWalletCreationConfigMaker {
             TameWalletCreationConfigMaker {
                 app: App::new("TameWalletCreationConfigMaker")
+                    .arg(chain_arg())
                     .arg(consuming_wallet_arg())
+                    .arg(data_directory_arg())
                     .arg(earning_wallet_arg("", |_| Ok(())))
+                    .arg(keychain_arg())
                     .arg(mnemonic_passphrase_arg())
                     .arg(real_user_arg())
                     .arg(wallet_password_arg(WALLET_PASSWORD_HELP)),
@@ -1362,7 +1794,11 @@ mod tests {
             stderr: &mut ByteArrayWriter::new(),
         };
 
-        let config = subject.make_wallet_creation_config(&multi_config, &mut streams);
+        let config = subject.make_wallet_creation_config(
+            &multi_config,
+            &mut streams,
+            &PersistentConfigurationMock::new().earning_wallet_address_result(None),
+        );
 
         let captured_output = stdout_writer.get_string();
         let expected_output = "Enter mnemonic passphrase: \
@@ -1388,6 +1824,7 @@ mod tests {
                     ),
                 }),
                 real_user: RealUser::null(),
+                keychain_data_directory_opt: None,
             },
         );
     }
@@ -1411,7 +1848,11 @@ mod tests {
             stderr: &mut ByteArrayWriter::new(),
         };
 
-        let config = subject.make_wallet_creation_config(&multi_config, &mut streams);
+        let config = subject.make_wallet_creation_config(
+            &multi_config,
+            &mut streams,
+            &PersistentConfigurationMock::new().earning_wallet_address_result(None),
+        );
 
         let captured_output = stdout_writer.get_string();
         let expected_output = "";
@@ -1433,10 +1874,41 @@ mod tests {
                     consuming_derivation_path_opt: Some("m/44'/60'/1'/2/3".to_string()),
                 }),
                 real_user: RealUser::new(Some(123), None, None),
+                keychain_data_directory_opt: None,
             },
         );
     }
 
+    #[test]
+    fn make_wallet_creation_config_with_keychain_remembers_where_to_file_the_password() {
+        let subject = TameWalletCreationConfigMaker::new();
+        let args = ArgsBuilder::new()
+            .param("--data-directory", "booga")
+            .param("--mnemonic-passphrase", "mnemonic passphrase")
+            .param("--wallet-password", "wallet password")
+            .param("--consuming-wallet", "m/44'/60'/1'/2/3")
+            .param("--earning-wallet", "m/44'/60'/3'/2/1")
+            .opt("--keychain");
+        let vcl = Box::new(CommandLineVcl::new(args.into()));
+        let multi_config = MultiConfig::new(&subject.app, vec![vcl]);
+        let mut streams = &mut StdStreams {
+            stdin: &mut Cursor::new(&[]),
+            stdout: &mut ByteArrayWriter::new(),
+            stderr: &mut ByteArrayWriter::new(),
+        };
+
+        let config = subject.make_wallet_creation_config(
+            &multi_config,
+            &mut streams,
+            &PersistentConfigurationMock::new().earning_wallet_address_result(None),
+        );
+
+        assert_eq!(
+            config.keychain_data_directory_opt,
+            Some(PathBuf::from("booga"))
+        );
+    }
+
     #[test]
     fn make_wallet_creation_config_non_defaults_with_earning_address() {
         let subject = TameWalletCreationConfigMaker::new();
@@ -1458,7 +1930,11 @@ mod tests {
             stderr: &mut ByteArrayWriter::new(),
         };
 
-        let config = subject.make_wallet_creation_config(&multi_config, &mut streams);
+        let config = subject.make_wallet_creation_config(
+            &multi_config,
+            &mut streams,
+            &PersistentConfigurationMock::new().earning_wallet_address_result(None),
+        );
 
         let captured_output = stdout_writer.get_string();
         let expected_output = "";
@@ -1475,6 +1951,44 @@ mod tests {
                     consuming_derivation_path_opt: Some("m/44'/60'/1'/2/3".to_string()),
                 }),
                 real_user: RealUser::new(Some(123), None, None),
+                keychain_data_directory_opt: None,
+            },
+        );
+    }
+
+    #[test]
+    fn make_wallet_creation_config_keeps_existing_watch_only_earning_address() {
+        let subject = TameWalletCreationConfigMaker::new();
+        let args = ArgsBuilder::new()
+            .param("--mnemonic-passphrase", "mnemonic passphrase")
+            .param("--wallet-password", "wallet password");
+        let vcl = Box::new(CommandLineVcl::new(args.into()));
+        let multi_config = MultiConfig::new(&subject.app, vec![vcl]);
+        let persistent_config = PersistentConfigurationMock::new().earning_wallet_address_result(
+            Some("0x0123456789ABCDEF0123456789ABCDEF01234567".to_string()),
+        );
+
+        let config = subject.make_wallet_creation_config(
+            &multi_config,
+            &mut FakeStreamHolder::new().streams(),
+            &persistent_config,
+        );
+
+        assert_eq!(
+            config,
+            WalletCreationConfig {
+                earning_wallet_address_opt: Some(
+                    "0x0123456789ABCDEF0123456789ABCDEF01234567".to_string()
+                ),
+                derivation_path_info_opt: Some(DerivationPathWalletInfo {
+                    mnemonic_seed: TameWalletCreationConfigMaker::hardcoded_mnemonic_seed(),
+                    wallet_password: "wallet password".to_string(),
+                    consuming_derivation_path_opt: Some(
+                        DEFAULT_CONSUMING_DERIVATION_PATH.to_string()
+                    ),
+                }),
+                real_user: RealUser::null(),
+                keychain_data_directory_opt: None,
             },
         );
     }
@@ -1491,7 +2005,11 @@ mod tests {
         let vcl = Box::new(CommandLineVcl::new(vec!["test".to_string()]));
         let multi_config = MultiConfig::new(&subject.app, vec![vcl]);
 
-        subject.make_wallet_creation_config(&multi_config, streams);
+        subject.make_wallet_creation_config(
+            &multi_config,
+            streams,
+            &PersistentConfigurationMock::new().earning_wallet_address_result(None),
+        );
     }
 
     #[test]
@@ -1509,6 +2027,7 @@ mod tests {
                 consuming_derivation_path_opt: Some("m/44'/60'/1'/2/3".to_string()),
             }),
             real_user: RealUser::null(),
+            keychain_data_directory_opt: None,
         };
         let set_mnemonic_seed_params_arc = Arc::new(Mutex::new(vec![]));
         let set_consuming_wallet_derivation_path_params_arc = Arc::new(Mutex::new(vec![]));
@@ -1555,6 +2074,7 @@ mod tests {
                 consuming_derivation_path_opt: Some("m/44'/60'/1'/2/3".to_string()),
             }),
             real_user: RealUser::null(),
+            keychain_data_directory_opt: None,
         };
         let set_mnemonic_seed_params_arc = Arc::new(Mutex::new(vec![]));
         let set_consuming_wallet_derivation_path_params_arc = Arc::new(Mutex::new(vec![]));
@@ -1591,4 +2111,27 @@ mod tests {
             vec!["0x9707f21F95B9839A54605100Ca69dCc2e7eaA26q".to_string()]
         );
     }
+
+    #[test]
+    fn audit_wallet_creation_names_the_wallets_but_not_the_secrets() {
+        init_test_logging();
+        let config = WalletCreationConfig {
+            earning_wallet_address_opt: Some(
+                "0x9707f21F95B9839A54605100Ca69dCc2e7eaA26q".to_string(),
+            ),
+            derivation_path_info_opt: Some(DerivationPathWalletInfo {
+                mnemonic_seed: PlainData::new(&[1, 2, 3, 4]),
+                wallet_password: "unaudited password".to_string(),
+                consuming_derivation_path_opt: Some("m/44'/60'/1'/2/3".to_string()),
+            }),
+            real_user: RealUser::null(),
+            keychain_data_directory_opt: None,
+        };
+
+        audit_wallet_creation("wallet-recovered", &config);
+
+        let tlh = TestLogHandler::new();
+        tlh.exists_log_containing("INFO: AuditLog: cli wallet-recovered: earning wallet 0x9707f21F95B9839A54605100Ca69dCc2e7eaA26q, consuming wallet at m/44'/60'/1'/2/3");
+        tlh.exists_no_log_containing("unaudited password");
+    }
 }
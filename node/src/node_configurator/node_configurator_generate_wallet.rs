@@ -4,11 +4,12 @@ use crate::blockchain::bip32::Bip32ECKeyPair;
 use crate::blockchain::bip39::Bip39;
 use crate::multi_config::MultiConfig;
 use crate::node_configurator::{
-    app_head, chain_arg, common_validators, consuming_wallet_arg, create_wallet,
-    data_directory_arg, earning_wallet_arg, flushed_write, language_arg, mnemonic_passphrase_arg,
-    prepare_initialization_mode, real_user_arg, request_password_with_confirmation,
-    request_password_with_retry, wallet_password_arg, Either, NodeConfigurator,
-    WalletCreationConfig, WalletCreationConfigMaker, EARNING_WALLET_HELP, WALLET_PASSWORD_HELP,
+    app_head, audit_wallet_creation, chain_arg, common_validators, consuming_wallet_arg,
+    create_wallet, data_directory_arg, earning_wallet_arg, flushed_write, instance_name_arg,
+    keychain_arg, language_arg, mnemonic_passphrase_arg, prepare_initialization_mode, profile_arg,
+    real_user_arg, request_password_with_confirmation, request_password_with_retry,
+    wallet_password_arg, Either, NodeConfigurator, WalletCreationConfig, WalletCreationConfigMaker,
+    EARNING_WALLET_HELP, WALLET_PASSWORD_HELP,
 };
 use crate::persistent_configuration::PersistentConfiguration;
 use crate::sub_lib::cryptde::PlainData;
@@ -33,6 +34,7 @@ impl NodeConfigurator<WalletCreationConfig> for NodeConfiguratorGenerateWallet {
         let config = self.parse_args(&multi_config, streams, persistent_config);
 
         create_wallet(&config, persistent_config);
+        audit_wallet_creation("wallet-generated", &config);
 
         config
     }
@@ -52,7 +54,7 @@ impl MnemonicFactory for MnemonicFactoryReal {
 
 const GENERATE_WALLET_HELP: &str =
     "Generate a new set of HD wallets with mnemonic recovery phrase from the standard \
-     BIP39 predefined list of words. Not valid as an environment variable.";
+     BIP39 predefined list of words.";
 const WORD_COUNT_HELP: &str =
     "The number of words in the mnemonic phrase. Ropsten defaults to 12 words. \
      Mainnet defaults to 24 words.";
@@ -61,12 +63,12 @@ const HELP_TEXT: &str = indoc!(
     r"ADDITIONAL HELP:
     If you already have a set of wallets you want PrometheusNode to use, try:
 
-        PrometheusNode --help --recover-wallet
+        PrometheusNode recover-wallet --help
 
     If the Node is already configured with your wallets, and you want to start the Node so that it
     stays running:
 
-        PrometheusNode --help"
+        PrometheusNode run --help"
 );
 
 impl WalletCreationConfigMaker for NodeConfiguratorGenerateWallet {
@@ -119,45 +121,54 @@ impl Default for NodeConfiguratorGenerateWallet {
     }
 }
 
+pub fn app() -> App<'static, 'static> {
+    app_head()
+        .about(GENERATE_WALLET_HELP)
+        .usage("PrometheusNode generate-wallet [FLAGS] [OPTIONS]")
+        .after_help(HELP_TEXT)
+        .arg(
+            Arg::with_name("generate-wallet")
+                .long("generate-wallet")
+                .required(true)
+                .takes_value(false)
+                .requires_all(&["language", "word-count"])
+                .hidden(true),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .takes_value(false)
+                .hidden(true),
+        )
+        .arg(chain_arg())
+        .arg(consuming_wallet_arg())
+        .arg(data_directory_arg())
+        .arg(earning_wallet_arg(
+            EARNING_WALLET_HELP,
+            common_validators::validate_earning_wallet,
+        ))
+        .arg(keychain_arg())
+        .arg(language_arg())
+        .arg(mnemonic_passphrase_arg())
+        .arg(profile_arg())
+        .arg(instance_name_arg())
+        .arg(real_user_arg())
+        .arg(wallet_password_arg(WALLET_PASSWORD_HELP))
+        .arg(
+            Arg::with_name("word-count")
+                .long("word-count")
+                .required(true)
+                .value_name("WORD-COUNT")
+                .possible_values(&["12", "15", "18", "21", "24"])
+                .default_value("12")
+                .help(WORD_COUNT_HELP),
+        )
+}
+
 impl NodeConfiguratorGenerateWallet {
     pub fn new() -> Self {
         Self {
-            app: app_head()
-                .after_help(HELP_TEXT)
-                .arg(
-                    Arg::with_name("generate-wallet")
-                        .long("generate-wallet")
-                        .required(true)
-                        .takes_value(false)
-                        .requires_all(&["language", "word-count"])
-                        .help(GENERATE_WALLET_HELP),
-                )
-                .arg(
-                    Arg::with_name("json")
-                        .long("json")
-                        .takes_value(false)
-                        .hidden(true),
-                )
-                .arg(chain_arg())
-                .arg(consuming_wallet_arg())
-                .arg(data_directory_arg())
-                .arg(earning_wallet_arg(
-                    EARNING_WALLET_HELP,
-                    common_validators::validate_earning_wallet,
-                ))
-                .arg(language_arg())
-                .arg(mnemonic_passphrase_arg())
-                .arg(real_user_arg())
-                .arg(wallet_password_arg(WALLET_PASSWORD_HELP))
-                .arg(
-                    Arg::with_name("word-count")
-                        .long("word-count")
-                        .required(true)
-                        .value_name("WORD-COUNT")
-                        .possible_values(&["12", "15", "18", "21", "24"])
-                        .default_value("12")
-                        .help(WORD_COUNT_HELP),
-                ),
+            app: app(),
             mnemonic_factory: Box::new(MnemonicFactoryReal {}),
         }
     }
@@ -171,7 +182,7 @@ impl NodeConfiguratorGenerateWallet {
         if persistent_config.encrypted_mnemonic_seed().is_some() {
             panic!("Can't generate wallets: mnemonic seed has already been created")
         }
-        self.make_wallet_creation_config(multi_config, streams)
+        self.make_wallet_creation_config(multi_config, streams, persistent_config)
     }
 
     fn request_mnemonic_passphrase(streams: &mut StdStreams) -> Option<String> {
@@ -477,7 +488,8 @@ mod tests {
                     wallet_password: password.to_string(),
                     consuming_derivation_path_opt: Some("m/44'/60'/0'/77/78".to_string()),
                 }),
-                real_user: RealUser::new(Some(123), Some(456), Some("/home/booga".into()))
+                real_user: RealUser::new(Some(123), Some(456), Some("/home/booga".into())),
+                keychain_data_directory_opt: None,
             },
         );
     }
@@ -529,6 +541,7 @@ mod tests {
                     ),
                 }),
                 real_user: RealUser::null(),
+                keychain_data_directory_opt: None,
             },
         );
     }
@@ -3,12 +3,12 @@
 use crate::blockchain::bip39::Bip39;
 use crate::multi_config::MultiConfig;
 use crate::node_configurator::{
-    app_head, chain_arg, common_validators, consuming_wallet_arg, create_wallet,
-    data_directory_arg, earning_wallet_arg, exit, flushed_write, language_arg,
-    mnemonic_passphrase_arg, prepare_initialization_mode, real_user_arg,
-    request_password_with_confirmation, request_password_with_retry, wallet_password_arg, Either,
-    NodeConfigurator, WalletCreationConfig, WalletCreationConfigMaker, EARNING_WALLET_HELP,
-    WALLET_PASSWORD_HELP,
+    app_head, audit_wallet_creation, chain_arg, common_validators, consuming_wallet_arg,
+    create_wallet, data_directory_arg, earning_wallet_arg, exit, flushed_write, instance_name_arg,
+    keychain_arg, language_arg, mnemonic_passphrase_arg, prepare_initialization_mode, profile_arg,
+    real_user_arg, request_password_with_confirmation, request_password_with_retry,
+    wallet_password_arg, Either, NodeConfigurator, WalletCreationConfig, WalletCreationConfigMaker,
+    EARNING_WALLET_HELP, WALLET_PASSWORD_HELP,
 };
 use crate::persistent_configuration::PersistentConfiguration;
 use crate::sub_lib::cryptde::PlainData;
@@ -32,6 +32,7 @@ impl NodeConfigurator<WalletCreationConfig> for NodeConfiguratorRecoverWallet {
         let config = self.parse_args(&multi_config, streams, persistent_config);
 
         create_wallet(&config, persistent_config);
+        audit_wallet_creation("wallet-recovered", &config);
 
         config
     }
@@ -39,7 +40,7 @@ impl NodeConfigurator<WalletCreationConfig> for NodeConfiguratorRecoverWallet {
 
 const RECOVER_WALLET_HELP: &str =
     "Import an existing set of HD wallets with mnemonic recovery phrase from the standard \
-     BIP39 predefined list of words. Not valid as an environment variable.";
+     BIP39 predefined list of words.";
 const MNEMONIC_HELP: &str =
     "An HD wallet mnemonic recovery phrase using predefined BIP39 word lists. This is a secret; providing it on the \
      command line or in a config file is insecure and unwise. If you don't specify it anywhere, you'll be prompted \
@@ -50,12 +51,12 @@ const HELP_TEXT: &str = indoc!(
     r"ADDITIONAL HELP:
     If you want to generate wallets to earn money into and spend money from, try:
 
-        PrometheusNode --help --generate-wallet
+        PrometheusNode generate-wallet --help
 
     If the Node is already configured with your wallets, and you want to start the Node so that it
     stays running:
 
-        PrometheusNode --help"
+        PrometheusNode run --help"
 );
 
 impl WalletCreationConfigMaker for NodeConfiguratorRecoverWallet {
@@ -95,43 +96,50 @@ impl Default for NodeConfiguratorRecoverWallet {
     }
 }
 
+pub fn app() -> App<'static, 'static> {
+    app_head()
+        .about(RECOVER_WALLET_HELP)
+        .usage("PrometheusNode recover-wallet [FLAGS] [OPTIONS]")
+        .after_help(HELP_TEXT)
+        .arg(
+            Arg::with_name("recover-wallet")
+                .long("recover-wallet")
+                .required(true)
+                .takes_value(false)
+                .requires_all(&["language"])
+                .hidden(true),
+        )
+        .arg(chain_arg())
+        .arg(consuming_wallet_arg())
+        .arg(data_directory_arg())
+        .arg(earning_wallet_arg(
+            EARNING_WALLET_HELP,
+            common_validators::validate_earning_wallet,
+        ))
+        .arg(keychain_arg())
+        .arg(language_arg())
+        .arg(
+            Arg::with_name("mnemonic")
+                .long("mnemonic")
+                .value_name("MNEMONIC-WORDS")
+                .required(false)
+                .empty_values(false)
+                .require_delimiter(true)
+                .value_delimiter(" ")
+                .min_values(12)
+                .max_values(24)
+                .help(MNEMONIC_HELP),
+        )
+        .arg(mnemonic_passphrase_arg())
+        .arg(profile_arg())
+        .arg(instance_name_arg())
+        .arg(real_user_arg())
+        .arg(wallet_password_arg(WALLET_PASSWORD_HELP))
+}
+
 impl NodeConfiguratorRecoverWallet {
     pub fn new() -> NodeConfiguratorRecoverWallet {
-        NodeConfiguratorRecoverWallet {
-            app: app_head()
-                .after_help(HELP_TEXT)
-                .arg(
-                    Arg::with_name("recover-wallet")
-                        .long("recover-wallet")
-                        .required(true)
-                        .takes_value(false)
-                        .requires_all(&["language"])
-                        .help(RECOVER_WALLET_HELP),
-                )
-                .arg(chain_arg())
-                .arg(consuming_wallet_arg())
-                .arg(data_directory_arg())
-                .arg(earning_wallet_arg(
-                    EARNING_WALLET_HELP,
-                    common_validators::validate_earning_wallet,
-                ))
-                .arg(language_arg())
-                .arg(
-                    Arg::with_name("mnemonic")
-                        .long("mnemonic")
-                        .value_name("MNEMONIC-WORDS")
-                        .required(false)
-                        .empty_values(false)
-                        .require_delimiter(true)
-                        .value_delimiter(" ")
-                        .min_values(12)
-                        .max_values(24)
-                        .help(MNEMONIC_HELP),
-                )
-                .arg(mnemonic_passphrase_arg())
-                .arg(real_user_arg())
-                .arg(wallet_password_arg(WALLET_PASSWORD_HELP)),
-        }
+        NodeConfiguratorRecoverWallet { app: app() }
     }
 
     fn parse_args(
@@ -146,7 +154,7 @@ impl NodeConfiguratorRecoverWallet {
                 "Can't recover wallets: mnemonic seed has already been created",
             );
         }
-        self.make_wallet_creation_config(multi_config, streams)
+        self.make_wallet_creation_config(multi_config, streams, persistent_config)
     }
 
     fn request_mnemonic_passphrase(streams: &mut StdStreams) -> Option<String> {
@@ -428,7 +436,8 @@ mod tests {
                     wallet_password: password.to_string(),
                     consuming_derivation_path_opt: Some(consuming_path.to_string()),
                 }),
-                real_user: RealUser::new(Some(123), Some(456), Some("/home/booga".into()))
+                real_user: RealUser::new(Some(123), Some(456), Some("/home/booga".into())),
+                keychain_data_directory_opt: None,
             },
         );
     }
@@ -471,6 +480,7 @@ mod tests {
                     ),
                 }),
                 real_user: RealUser::null(),
+                keychain_data_directory_opt: None,
             },
         );
     }
@@ -1,22 +1,34 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
 
-use crate::blockchain::blockchain_interface::DEFAULT_GAS_PRICE;
+use crate::blockchain::blockchain_interface::{chain_record, DEFAULT_GAS_PRICE};
 use crate::bootstrapper::BootstrapperConfig;
+use crate::entry_dns::dns_socket_server::{DNS_MODES, DNS_PORT};
+use crate::log_rotation::LogRotation;
 use crate::node_configurator;
+use crate::node_configurator::secret_store::SecretStoreReal;
 use crate::node_configurator::{
     app_head, chain_arg, common_validators, config_file_arg, data_directory_arg,
-    earning_wallet_arg, initialize_database, real_user_arg, wallet_password_arg, NodeConfigurator,
+    earning_wallet_arg, encrypt_database_arg, initialize_database, instance_name_arg, keychain_arg,
+    profile_arg, real_user_arg, run_as_arg, wallet_password_arg, NodeConfigurator,
 };
+use crate::sub_lib::accountant::{PaymentParameter, PAYMENT_PARAMETERS};
+use crate::sub_lib::blockchain_bridge::{FeeBudget, FeeBudgetPeriod, FeeStrategy};
 use crate::sub_lib::crash_point::CrashPoint;
 use crate::sub_lib::main_tools::StdStreams;
+use crate::sub_lib::memory_guard::MemoryThresholds;
+use crate::sub_lib::proxy_server::PortForward;
+use crate::sub_lib::spill_file::{SpillBudget, SpillConfig, SPILL_DIRECTORY};
 use crate::sub_lib::ui_gateway::DEFAULT_UI_PORT;
 use clap::{App, Arg};
+use dns_utility_lib::dns_modifier_factory::DnsModifierFactoryReal;
 use indoc::indoc;
 use lazy_static::lazy_static;
 
 pub const LOWEST_USABLE_INSECURE_PORT: u16 = 1025;
 pub const HIGHEST_USABLE_PORT: u16 = 65535;
+pub const MAX_OBFUSCATION_JITTER_MS: u64 = 1000;
+pub const MAX_OBFUSCATION_PADDING: usize = 4096;
 
 pub struct NodeConfiguratorStandardPrivileged {}
 
@@ -25,8 +37,10 @@ impl NodeConfigurator<BootstrapperConfig> for NodeConfiguratorStandardPrivileged
         let app = app();
         let multi_config = standard::make_service_mode_multi_config(&app, args);
         let mut bootstrapper_config = BootstrapperConfig::new();
-        standard::establish_port_configurations(&mut bootstrapper_config);
         standard::privileged_parse_args(&multi_config, &mut bootstrapper_config, streams);
+        standard::establish_port_configurations(&mut bootstrapper_config);
+        bootstrapper_config.ui_gateway_config.config_file_opt =
+            Some(standard::config_file_source(&app, args));
         bootstrapper_config
     }
 }
@@ -38,19 +52,28 @@ pub struct NodeConfiguratorStandardUnprivileged {
 impl NodeConfigurator<BootstrapperConfig> for NodeConfiguratorStandardUnprivileged {
     fn configure(&self, args: &Vec<String>, streams: &mut StdStreams<'_>) -> BootstrapperConfig {
         let app = app();
+        let secret_store = SecretStoreReal::new(&self.privileged_config.data_directory);
+        let mut unprivileged_config = BootstrapperConfig::new();
+        let multi_config = standard::make_unprivileged_multi_config(&app, args, &secret_store);
+        unprivileged_config.database_password_opt = standard::database_password_opt(&multi_config);
         let persistent_config = initialize_database(
             &self.privileged_config.data_directory,
             self.privileged_config.blockchain_bridge_config.chain_id,
+            unprivileged_config.database_password_opt.clone(),
         );
-        let mut unprivileged_config = BootstrapperConfig::new();
-        let multi_config = standard::make_service_mode_multi_config(&app, args);
         standard::unprivileged_parse_args(
             &multi_config,
             &mut unprivileged_config,
             streams,
             persistent_config.as_ref(),
         );
+        unprivileged_config.dns_servers = if self.privileged_config.dns_servers.is_empty() {
+            standard::detect_dns_servers(&DnsModifierFactoryReal::new(), persistent_config.as_ref())
+        } else {
+            self.privileged_config.dns_servers.clone()
+        };
         standard::configure_database(&unprivileged_config, persistent_config.as_ref());
+        standard::remember_secrets_if_asked(&multi_config, &secret_store);
         unprivileged_config
     }
 }
@@ -71,6 +94,15 @@ lazy_static! {
         you know what you're doing. Must be between {} and {}.",
         LOWEST_USABLE_INSECURE_PORT, HIGHEST_USABLE_PORT
     );
+    static ref REST_PORT_HELP: String = format!(
+        "The port at which scripts and monitoring systems can reach the Node's REST API on localhost: \
+        GET /status, /statistics, /descriptor, /neighbors, /neighbors/list, /financials, /financials/dump, /dns-leak-test and \
+        /settings/blockchain-service-url; PUT /settings/gas-price and \
+        /settings/payment-parameters/<parameter>. Every request must carry the token in ui-auth-token in the \
+        data directory as 'Authorization: Bearer <token>'. Leave it out to run without the REST API. Must be \
+        between {} and {}, and different from --ui-port.",
+        LOWEST_USABLE_INSECURE_PORT, HIGHEST_USABLE_PORT
+    );
     static ref CLANDESTINE_PORT_HELP: String = format!(
         "The port this Node will advertise to other Nodes at which clandestine traffic will be \
          received. If you don't specify a clandestine port, the Node will choose an unused \
@@ -80,19 +112,140 @@ lazy_static! {
          Must be between {} and {} [default: last used port]",
         LOWEST_USABLE_INSECURE_PORT, HIGHEST_USABLE_PORT
     );
+    static ref OBFUSCATION_JITTER_HELP: String = format!(
+        "The maximum random delay, in milliseconds, to insert before each frame of clandestine data \
+         this Node sends to another Node. Random delays make it harder for an observer to correlate \
+         flows or fingerprint websites by timing, at the cost of latency. Must be between 0 and {} [default: 0]",
+        MAX_OBFUSCATION_JITTER_MS
+    );
+    static ref OBFUSCATION_PADDING_HELP: String = format!(
+        "The maximum number of random padding bytes to add to each frame of clandestine data this \
+         Node sends to another Node. Random padding makes it harder for an observer to fingerprint \
+         websites by frame sizes, at the cost of bandwidth. Must be between 0 and {} [default: 0]",
+        MAX_OBFUSCATION_PADDING
+    );
     static ref GAS_PRICE_HELP: String = format!(
        "The Gas Price is the amount of Gwei you will pay per unit of gas used in a transaction. \
        If left unspecified PrometheusNode will use the previously stored value (Default {}). Valid range is 1-99 Gwei.",
        DEFAULT_GAS_PRICE);
+    static ref PAYMENT_PARAMETER_HELP: Vec<String> = PAYMENT_PARAMETERS
+        .iter()
+        .map(|parameter| format!(
+            "{} If left unspecified PrometheusNode will use the previously stored value (Default {}). \
+             Valid range is {}-{}.",
+            payment_parameter_description(*parameter),
+            parameter.default_value(),
+            parameter.range().start(),
+            parameter.range().end()
+        ))
+        .collect();
+}
+
+fn payment_parameter_description(parameter: PaymentParameter) -> &'static str {
+    match parameter {
+        PaymentParameter::PayableScanInterval => {
+            "How often, in seconds, PrometheusNode looks for debts it should pay."
+        }
+        PaymentParameter::PaymentReceivedScanInterval => {
+            "How often, in seconds, PrometheusNode checks the blockchain for payments to its earning \
+             wallet and looks for delinquent debtors."
+        }
+        PaymentParameter::PaymentSuggestedAfter => {
+            "How old, in seconds, a debt must be before it should be paid."
+        }
+        PaymentParameter::PaymentGraceBeforeBan => {
+            "How long, in seconds, a debtor has to pay after payment is suggested before it may be \
+             banned for delinquency."
+        }
+        PaymentParameter::PermanentDebtAllowed => {
+            "The debt, in gwub, that never needs to be paid and never gets a debtor banned."
+        }
+        PaymentParameter::BalanceToDecreaseFrom => {
+            "The debt, in gwub, that should be paid as soon as payment is suggested. The threshold \
+             falls from here to --permanent-debt-allowed as the debt ages."
+        }
+        PaymentParameter::BalanceDecreasesFor => {
+            "How old, in seconds, a debt must be before the payment threshold has fallen all the way \
+             to --permanent-debt-allowed."
+        }
+        PaymentParameter::UnbanWhenBalanceBelow => {
+            "The debt, in gwub, below which a debtor banned for delinquency is unbanned."
+        }
+    }
+}
+
+fn payment_parameter_args() -> Vec<Arg<'static, 'static>> {
+    PAYMENT_PARAMETERS
+        .iter()
+        .zip(PAYMENT_PARAMETER_HELP.iter())
+        .map(|(parameter, help)| {
+            let parameter = *parameter;
+            Arg::with_name(parameter.name())
+                .long(parameter.name())
+                .value_name(parameter.units())
+                .takes_value(true)
+                .validator(move |value| validators::validate_payment_parameter(parameter, value))
+                .help(help)
+        })
+        .collect()
 }
 
 const BLOCKCHAIN_SERVICE_HELP: &str =
     "The Ethereum client you wish to use to provide Blockchain \
      exit services from your PrometheusNode (e.g. http://localhost:8545, \
-     https://ropsten.infura.io/v3/YOUR-PROJECT-ID, https://mainnet.infura.io/v3/YOUR-PROJECT-ID).";
+     https://ropsten.infura.io/v3/YOUR-PROJECT-ID, https://mainnet.infura.io/v3/YOUR-PROJECT-ID). \
+     Defaults to the public RPC endpoint on chains that have one (--chain xdai). \
+     To guard against one of them going down, list several separated by commas, most preferred first; \
+     your PrometheusNode will fail over to the next one whenever the one it's using can't be reached.";
+const BLOCKCHAIN_SERVICE_WS_HELP: &str =
+    "A WebSocket endpoint (ws:// or wss://) on an Ethereum client that supports eth_subscribe, \
+     e.g. ws://localhost:8546. If you specify one, your PrometheusNode will learn of new blocks as soon \
+     as they're mined and look for payments to you right away; otherwise it polls for them.";
+const BUFFER_MEMORY_LIMIT_HELP: &str =
+    "How many kilobytes of data waiting to be written to any one stream your PrometheusNode will \
+     hold in memory. The rest goes to an encrypted file in the data directory until the stream \
+     catches up, so that one big download can't use up a small relay's memory. Default is 1024.";
+const BUFFER_SPILL_LIMIT_HELP: &str =
+    "How many megabytes of data waiting to be written to any one stream may go to disk once \
+     --buffer-memory-limit is reached. Past that, your PrometheusNode gives up on the stream. \
+     Specify 0 to keep everything in memory. Default is 256.";
+const BUFFER_SPILL_TOTAL_LIMIT_HELP: &str =
+    "How many megabytes of data waiting to be written may go to disk for all streams together. \
+     Past that, your PrometheusNode gives up on any stream that needs to spill more. Specify 0 to \
+     keep everything in memory. Default is 2048.";
+const CONSUMING_PROFILES_HELP: &str =
+    "Separate budgets for separate uses of your PrometheusNode. Each profile looks like \
+     NAME:LOW-HIGH or NAME:pac=PORT, optionally followed by :BUDGET and then :PRIVATE-KEY. LOW-HIGH \
+     is a range of local ports from which browser connections originate. With pac=PORT, the Node \
+     listens on PORT for http and PORT+1 for https and writes NAME.pac into the data directory for \
+     a browser's proxy auto-config. BUDGET is the most, in gwub, that profile may spend; once it's \
+     spent, the profile's traffic is refused. PRIVATE-KEY is the key of a consuming wallet that \
+     pays for that profile's traffic instead of your usual one. Consumption is tracked and \
+     reported separately for each profile. Separate profiles with commas \
+     (e.g. work:10000-19999:5000000,personal:pac=8090); if profiles overlap, the first match wins.";
+const DNS_MODE_HELP: &str =
+    "How the Node's DNS server works. catch-all answers every query from anywhere with the Node's own \
+     address, and suits a machine whose DNS has been subverted with dns_utility. stub listens only on \
+     127.0.0.1 and is for this machine alone: it answers queries for addresses with the Node's own \
+     address, so that the names are looked up at the far end of the clandestine network, and passes \
+     anything else on to the --dns-servers. Point just the programs you want to use the Node at \
+     127.0.0.1 for DNS, or point this machine's resolver at it; either is undone by pointing it back. \
+     Because the DNS server starts first, this must not be specified in a config file.";
 const DNS_SERVERS_HELP: &str =
     "IP addresses of DNS Servers for host name look-up while providing exit \
-     services for other PrometheusNodes (e.g. 1.0.0.1,1.1.1.1,8.8.8.8,9.9.9.9, etc.)";
+     services for other PrometheusNodes (e.g. 1.0.0.1,1.1.1.1,8.8.8.8,9.9.9.9, etc.); if you leave \
+     this out, the Node uses the DNS servers this machine used before its DNS was subverted, \
+     and records them in its database.";
+const SPLIT_DNS_DOMAINS_HELP: &str =
+    "Domains, such as a company's internal ones, to be looked up with the DNS servers this machine \
+     used before its DNS was subverted instead of through the Node, along with every name under them \
+     (e.g. corp.example.com,lan). They're remembered in the database for later runs; specify \
+     --split-dns-domains \"\" to forget them.";
+const TELEMETRY_URL_HELP: &str =
+    "An http:// URL to which the Node will POST a small anonymized report once a day: its version, \
+     its operating system, and ranges (not exact figures) for its neighbor count and the bytes it \
+     has relayed. Nothing in a report identifies your Node, your wallets or your neighbors. No \
+     telemetry is sent unless you specify this.";
 const DEV_FEE_HELP: &str = 
     "A Developer Fee based off user selected percentage rate of amount earned";	
 const EXIT_RATE_HELP: &str = 
@@ -103,18 +256,114 @@ const EARNING_WALLET_HELP: &str =
     "An Ethereum wallet address. Addresses must begin with 0x followed by 40 hexadecimal digits \
      (case-insensitive). If you already have a derivation-path earning wallet, don't supply this. \
      If you have supplied an earning wallet address before, either don't supply it again or be \
-     careful to supply exactly the same one you supplied before.";
+     careful to supply exactly the same one you supplied before. The Node never needs the private key \
+     for this wallet, so it may be a cold wallet; the address is remembered for later runs.";
+const ENCRYPT_DATABASE_HELP: &str =
+    "Encrypt the database with SQLCipher, using a key derived from --wallet-password, so that nothing \
+     that can read the file can read your wallets or financial records. A database that isn't encrypted \
+     yet is encrypted the first time you use this; after that, you must always give it, along with the \
+     wallet password. Only works if PrometheusNode was built with the sqlcipher feature.";
+const EXIT_BLOCK_MAIL_HELP: &str =
+    "Refuse to open exit streams to the ports mail clients use to send and fetch mail (143, 465, \
+     587 and 993), for operators who'd rather carry web traffic only. Port 25 is a separate matter: \
+     see --exit-block-smtp. If left unspecified, mail is carried like anything else.";
+const EXIT_BLOCK_SMTP_HELP: &str =
+    "Refuse to open exit streams to port 25, so that nobody can send spam through your Node. \
+     Mail clients use other ports, so only mail servers and spammers notice. If left unspecified, \
+     port 25 is open like any other.";
+const EXIT_EARNING_WALLET_HELP: &str =
+    "An Ethereum wallet address to which consuming Nodes should send payment for exit services, if you want \
+     to account for them separately from routing services. If you leave it out, exit services are paid to \
+     your earning wallet. Unlike the earning wallet, it may be changed from one run to the next; if left \
+     unspecified, PrometheusNode will use the previously stored value.";
+const EXIT_FAN_OUT_LIMIT_HELP: &str =
+    "The most different destinations any one originating Node may have your Node open exit streams \
+     to in a minute. Streams to further destinations are refused and logged. Default is 200.";
+const EXIT_NO_SCAN_DETECTION_HELP: &str =
+    "Don't watch for originating Nodes that use yours to scan for open ports. If left unspecified, \
+     an originating Node that tries many ports on one host, or one port on many hosts, within a \
+     minute is refused exit streams for ten minutes, and the refusal is logged.";
+const FEE_BUDGET_HELP: &str =
+    "The most Gwei you're willing to spend on transaction fees in each --fee-budget-period. When \
+     paying would risk going over, payments are deferred until the next period begins, and the UI \
+     says so. If left unspecified there is no budget.";
+const FEE_BUDGET_PERIOD_HELP: &str =
+    "How often your --fee-budget is renewed: weekly, or monthly (every 30 days).";
+const FEE_STRATEGY_HELP: &str =
+    "How much to bid for your payments to be mined quickly on chains that charge a base fee: slow \
+     adds a small tip for the miner and allows little room for the base fee to rise; fast adds a \
+     larger tip and allows the base fee to triple before the payment is mined.";
+const FORWARD_HELP: &str =
+    "Listen on a local port and carry whatever connects to it, byte for byte, through the Prometheus \
+     Network to a host and port beyond the exit Node, e.g. 5432:db.example.com:5432. This is for \
+     services that aren't web traffic: databases, game servers, remote desktops. Forward to port \
+     21 for FTP, and passive-mode data connections are carried too. Separate several with commas.";
+const GAS_PRICE_CAP_HELP: &str =
+    "The most Gwei you're willing to pay per unit of gas used in a transaction, base fee and tip \
+     together. Payments that would cost more are postponed until fees come down. If left \
+     unspecified there is no cap.";
 const IP_ADDRESS_HELP: &str = "The public IP address of your PrometheusNode: that is, the IPv4 \
      address at which other PrometheusNodes can contact yours. If you're running your Node behind \
      a router, this will be the IP address of the router. If this IP address starts with 192.168 or 10.0, \
      it's a local address rather than a public address, and other Nodes won't be able to see yours. \
      --ip is meaningless except in --neighborhood-mode standard.";
+const KILL_SWITCH_HELP: &str =
+    "Stop sending consuming traffic as soon as your Node can't build routes of the minimum hop \
+     count, even over routes built earlier, and show browsers an error page instead, until routes \
+     that long can be built again. Meaningless in --neighborhood-mode zero-hop. If left \
+     unspecified, established streams carry on and only new ones are refused.";
+const LAN_DISCOVERY_HELP: &str =
+    "Find other PrometheusNodes on your local network with mDNS, and make them neighbors without \
+     needing their descriptors in --neighbors; in --neighborhood-mode standard, advertise this Node \
+     to them as well. Handy for home labs and testing, but it tells everything on the LAN that a \
+     Node is running here, so it's off unless you specify it. Other Nodes can only reach yours \
+     if --ip is an address they can reach on the LAN.";
+const LOG_KEEP_DAYS_HELP: &str =
+    "Delete rotated log files once they're this many days old. If left unspecified, rotated logs \
+     are kept until --log-keep-files says they have to go.";
+const LOG_KEEP_FILES_HELP: &str =
+    "How many rotated log files to keep beside the current one. When there are more, the oldest \
+     are deleted. Default is 50.";
 const LOG_LEVEL_HELP: &str =
     "The minimum severity of the logs that should appear in the Node's logfile. You should probably not specify \
      a level lower than the default unless you're doing testing or forensics: a Node at the 'trace' log level \
      generates a lot of log traffic. This will both consume your disk space and degrade your Node's performance. \
      You should probably not specify a level higher than the default unless you have security concerns about \
      persistent logs being kept on your computer: if your Node crashes, it's good to know why.";
+const LOG_NO_GZIP_HELP: &str =
+    "Leave rotated log files as they are instead of compressing them with gzip.";
+const LOG_ROTATE_HOURS_HELP: &str =
+    "Start a new log file once the current one has been written to for this many hours, however \
+     small it is. If left unspecified, only --log-rotate-size starts new log files.";
+const LOG_ROTATE_SIZE_HELP: &str =
+    "Start a new log file once the current one would grow past this many megabytes. The old one \
+     is renamed with a number, and gzipped unless you specify --log-no-gzip. Default is 100.";
+const MAIL_CLIENTS_HELP: &str =
+    "Listen on ports 587 and 993 as well, so that desktop mail clients can send mail (SMTP with \
+     STARTTLS) and fetch it (IMAPS) through the Prometheus Network just as browsers browse through \
+     it. The Node answers the mail client's greeting itself and learns which server it wants from \
+     its TLS handshake; the exit never sees the mail. Exit Nodes may refuse mail with \
+     --exit-block-mail. If left unspecified, the Node listens for browsers only.";
+const MANAGE_FIREWALL_HELP: &str =
+    "Open the clandestine port in this machine's firewall (ufw or firewalld on Linux, pf on macOS, \
+     Windows Firewall) while the Node runs, and close it again when the Node stops, so that other \
+     Nodes can reach yours. Needs the privileges the Node starts with. If left unspecified, the \
+     firewall is left alone.";
+const MEMORY_THRESHOLDS_HELP: &str =
+    "Three increasing numbers of megabytes, separated by commas (e.g. 512,768,1024). Once the Node \
+     uses more memory than the first, it refuses new consuming streams; past the second, it also \
+     keeps only an eighth of --buffer-memory-limit of each stream in memory; past the third, it \
+     also refuses new clandestine connections. It takes each step back once it's using a tenth \
+     less than the threshold for that step. If left unspecified, the Node sheds no load, however \
+     much memory it uses.";
+const NEIGHBOR_EGRESS_LIMIT_HELP: &str =
+    "The most kilobytes per second the Node will send to any one neighbor. Data over the limit is \
+     held back until it fits; a neighbor that keeps the Node over it for a minute gets nothing \
+     more for ten minutes. If left unspecified, there is no limit.";
+const NEIGHBOR_INGRESS_LIMIT_HELP: &str =
+    "The most kilobytes per second the Node will accept from any one neighbor. Data over the limit \
+     is held back until it fits; a neighbor that stays over it for a minute, or gets ten seconds' \
+     worth ahead of it, is disconnected and ignored for ten minutes. If left unspecified, there is no limit.";
 const NEIGHBORS_HELP: &str = "One or more Node descriptors for running Nodes in the Prometheus \
      Network to which you'd like your Node to connect on startup. A Node descriptor looks like \
      this:\n\ngBviQbjOS3e5ReFQCvIhUM3i02d1zPleo1iXg/EN6zQ:86.75.30.9:5542 (initial ':' for testnet) and\n\
@@ -147,14 +396,31 @@ const WALLET_PASSWORD_HELP: &str =
      later and still produce the same addresses.";
 
 const HELP_TEXT: &str = indoc!(
-    r"ADDITIONAL HELP:
+    r"SUBCOMMANDS:
+    run                 Start PrometheusNode and keep it running; the default if you give none
+    setup               Store these parameters in the data directory's database, then stop
+    generate-wallet     Generate new wallets from a new mnemonic phrase
+    recover-wallet      Recover existing wallets from their mnemonic phrase
+    check-config        Check these parameters without starting PrometheusNode
+    dump-config         Print the configuration stored in the database
+    dump-financials     Print every payable and receivable
+    backup-data         Write the data directory to an encrypted archive
+    restore-data        Restore the data directory from an archive written by backup-data
+    list-neighbors      Print the neighbors of the PrometheusNode running on this machine
+    print-descriptor    Print the descriptor of the PrometheusNode running on this machine
+    completions         Print a script that completes all of this in bash, zsh, fish or PowerShell
+
+    Each subcommand has its own help, such as PrometheusNode dump-config --help. The flags that
+    used to select these modes, such as --generate-wallet, still work until the next release.
+
+ADDITIONAL HELP:
     If you want to generate wallets to earn money into and spend money from, try:
 
-        PrometheusNode --help --generate-wallet
+        PrometheusNode generate-wallet --help
 
     If you already have a set of wallets you want PrometheusNode to use, try:
 
-        PrometheusNode --help --recover-wallet
+        PrometheusNode recover-wallet --help
 
     PrometheusNode listens for connections from other PrometheusNodes using the computer's
     network interface. Configuring the internet router for port forwarding is a necessary
@@ -176,16 +442,47 @@ const HELP_TEXT: &str = indoc!(
         3. Create the port forwarding entries in the router."
 );
 
-fn app() -> App<'static, 'static> {
+pub fn app() -> App<'static, 'static> {
     app_head()
+        .usage("PrometheusNode [run | setup] [FLAGS] [OPTIONS]")
         .after_help(HELP_TEXT)
         .arg(
             Arg::with_name("blockchain-service-url")
                 .long("blockchain-service-url")
                 .empty_values(false)
-                .value_name("URL")
+                .value_name("URLS")
                 .takes_value(true)
+                .use_delimiter(true)
                 .help(BLOCKCHAIN_SERVICE_HELP),
+        ).arg(
+            Arg::with_name("blockchain-service-ws-url")
+                .long("blockchain-service-ws-url")
+                .empty_values(false)
+                .value_name("URL")
+                .takes_value(true)
+                .validator(validators::validate_ws_url)
+                .help(BLOCKCHAIN_SERVICE_WS_HELP),
+        ).arg(
+            Arg::with_name("buffer-memory-limit")
+                .long("buffer-memory-limit")
+                .value_name("KILOBYTES")
+                .takes_value(true)
+                .validator(validators::validate_buffer_limit)
+                .help(BUFFER_MEMORY_LIMIT_HELP),
+        ).arg(
+            Arg::with_name("buffer-spill-limit")
+                .long("buffer-spill-limit")
+                .value_name("MEGABYTES")
+                .takes_value(true)
+                .validator(validators::validate_buffer_limit)
+                .help(BUFFER_SPILL_LIMIT_HELP),
+        ).arg(
+            Arg::with_name("buffer-spill-total-limit")
+                .long("buffer-spill-total-limit")
+                .value_name("MEGABYTES")
+                .takes_value(true)
+                .validator(validators::validate_buffer_limit)
+                .help(BUFFER_SPILL_TOTAL_LIMIT_HELP),
         ).arg(
             Arg::with_name("Dev_Contribution")
                 .long("Dev_Contribution_Rate")
@@ -233,6 +530,15 @@ fn app() -> App<'static, 'static> {
                 .validator(validators::validate_private_key)
                 .help(node_configurator::CONSUMING_PRIVATE_KEY_HELP),
         )
+        .arg(
+            Arg::with_name("consuming-profiles")
+                .long("consuming-profiles")
+                .value_name("CONSUMING-PROFILES")
+                .takes_value(true)
+                .use_delimiter(true)
+                .validator(validators::validate_consuming_profile)
+                .help(CONSUMING_PROFILES_HELP),
+        )
         .arg(
             Arg::with_name("crash-point")
                 .long("crash-point")
@@ -244,6 +550,15 @@ fn app() -> App<'static, 'static> {
                 .hidden(true),
         )
         .arg(data_directory_arg())
+        .arg(
+            Arg::with_name("dns-mode")
+                .long("dns-mode")
+                .value_name("DNS-MODE")
+                .takes_value(true)
+                .possible_values(&DNS_MODES)
+                .default_value(DNS_MODES[0])
+                .help(DNS_MODE_HELP),
+        )
         .arg(
             Arg::with_name("dns-servers")
                 .long("dns-servers")
@@ -257,7 +572,43 @@ fn app() -> App<'static, 'static> {
             EARNING_WALLET_HELP,
             common_validators::validate_ethereum_address,
         ))
+        .arg(encrypt_database_arg(ENCRYPT_DATABASE_HELP))
         .arg(chain_arg())
+        .arg(
+            Arg::with_name("exit-block-mail")
+                .long("exit-block-mail")
+                .takes_value(false)
+                .help(EXIT_BLOCK_MAIL_HELP),
+        )
+        .arg(
+            Arg::with_name("exit-block-smtp")
+                .long("exit-block-smtp")
+                .takes_value(false)
+                .help(EXIT_BLOCK_SMTP_HELP),
+        )
+        .arg(
+            Arg::with_name("exit-earning-wallet")
+                .long("exit-earning-wallet")
+                .value_name("EXIT-EARNING-WALLET")
+                .required(false)
+                .takes_value(true)
+                .validator(common_validators::validate_ethereum_address)
+                .help(EXIT_EARNING_WALLET_HELP),
+        )
+        .arg(
+            Arg::with_name("exit-fan-out-limit")
+                .long("exit-fan-out-limit")
+                .value_name("COUNT")
+                .takes_value(true)
+                .validator(validators::validate_exit_fan_out_limit)
+                .help(EXIT_FAN_OUT_LIMIT_HELP),
+        )
+        .arg(
+            Arg::with_name("exit-no-scan-detection")
+                .long("exit-no-scan-detection")
+                .takes_value(false)
+                .help(EXIT_NO_SCAN_DETECTION_HELP),
+        )
         .arg(
             Arg::with_name("fake-public-key")
                 .long("fake-public-key")
@@ -265,6 +616,43 @@ fn app() -> App<'static, 'static> {
                 .takes_value(true)
                 .hidden(true),
         )
+        .arg(
+            Arg::with_name("fee-budget")
+                .long("fee-budget")
+                .value_name("GWEI")
+                .takes_value(true)
+                .validator(validators::validate_fee_budget)
+                .help(FEE_BUDGET_HELP),
+        )
+        .arg(
+            Arg::with_name("fee-budget-period")
+                .long("fee-budget-period")
+                .value_name("PERIOD")
+                .takes_value(true)
+                .possible_values(&["weekly", "monthly"])
+                .default_value("monthly")
+                .case_insensitive(true)
+                .help(FEE_BUDGET_PERIOD_HELP),
+        )
+        .arg(
+            Arg::with_name("fee-strategy")
+                .long("fee-strategy")
+                .value_name("FEE-STRATEGY")
+                .takes_value(true)
+                .possible_values(&["slow", "normal", "fast"])
+                .default_value("normal")
+                .case_insensitive(true)
+                .help(FEE_STRATEGY_HELP),
+        )
+        .arg(
+            Arg::with_name("forward")
+                .long("forward")
+                .value_name("LOCAL-PORT:HOST:PORT")
+                .takes_value(true)
+                .use_delimiter(true)
+                .validator(validators::validate_port_forward)
+                .help(FORWARD_HELP),
+        )
         .arg(
             Arg::with_name("gas-price")
                 .long("gas-price")
@@ -275,6 +663,15 @@ fn app() -> App<'static, 'static> {
                 .validator(validators::validate_gas_price)
                 .help(&GAS_PRICE_HELP),
         )
+        .arg(
+            Arg::with_name("gas-price-cap")
+                .long("gas-price-cap")
+                .value_name("GWEI")
+                .takes_value(true)
+                .validator(validators::validate_gas_price_cap)
+                .help(GAS_PRICE_CAP_HELP),
+        )
+        .arg(instance_name_arg())
         .arg(
             Arg::with_name("ip")
                 .long("ip")
@@ -283,6 +680,35 @@ fn app() -> App<'static, 'static> {
                 .validator(validators::validate_ip_address)
                 .help(IP_ADDRESS_HELP),
         )
+        .arg(keychain_arg())
+        .arg(
+            Arg::with_name("kill-switch")
+                .long("kill-switch")
+                .takes_value(false)
+                .help(KILL_SWITCH_HELP),
+        )
+        .arg(
+            Arg::with_name("lan-discovery")
+                .long("lan-discovery")
+                .takes_value(false)
+                .help(LAN_DISCOVERY_HELP),
+        )
+        .arg(
+            Arg::with_name("log-keep-days")
+                .long("log-keep-days")
+                .value_name("DAYS")
+                .takes_value(true)
+                .validator(validators::validate_log_rotation_limit)
+                .help(LOG_KEEP_DAYS_HELP),
+        )
+        .arg(
+            Arg::with_name("log-keep-files")
+                .long("log-keep-files")
+                .value_name("COUNT")
+                .takes_value(true)
+                .validator(validators::validate_log_rotation_limit)
+                .help(LOG_KEEP_FILES_HELP),
+        )
         .arg(
             Arg::with_name("log-level")
                 .long("log-level")
@@ -293,6 +719,64 @@ fn app() -> App<'static, 'static> {
                 .case_insensitive(true)
                 .help(LOG_LEVEL_HELP),
         )
+        .arg(
+            Arg::with_name("log-no-gzip")
+                .long("log-no-gzip")
+                .takes_value(false)
+                .help(LOG_NO_GZIP_HELP),
+        )
+        .arg(
+            Arg::with_name("log-rotate-hours")
+                .long("log-rotate-hours")
+                .value_name("HOURS")
+                .takes_value(true)
+                .validator(validators::validate_log_rotation_limit)
+                .help(LOG_ROTATE_HOURS_HELP),
+        )
+        .arg(
+            Arg::with_name("log-rotate-size")
+                .long("log-rotate-size")
+                .value_name("MEGABYTES")
+                .takes_value(true)
+                .validator(validators::validate_log_rotation_limit)
+                .help(LOG_ROTATE_SIZE_HELP),
+        )
+        .arg(
+            Arg::with_name("mail-clients")
+                .long("mail-clients")
+                .takes_value(false)
+                .help(MAIL_CLIENTS_HELP),
+        )
+        .arg(
+            Arg::with_name("manage-firewall")
+                .long("manage-firewall")
+                .takes_value(false)
+                .help(MANAGE_FIREWALL_HELP),
+        )
+        .arg(
+            Arg::with_name("memory-thresholds")
+                .long("memory-thresholds")
+                .value_name("MEGABYTES")
+                .takes_value(true)
+                .validator(validators::validate_memory_thresholds)
+                .help(MEMORY_THRESHOLDS_HELP),
+        )
+        .arg(
+            Arg::with_name("neighbor-egress-limit")
+                .long("neighbor-egress-limit")
+                .value_name("KBPS")
+                .takes_value(true)
+                .validator(validators::validate_neighbor_rate_limit)
+                .help(NEIGHBOR_EGRESS_LIMIT_HELP),
+        )
+        .arg(
+            Arg::with_name("neighbor-ingress-limit")
+                .long("neighbor-ingress-limit")
+                .value_name("KBPS")
+                .takes_value(true)
+                .validator(validators::validate_neighbor_rate_limit)
+                .help(NEIGHBOR_INGRESS_LIMIT_HELP),
+        )
         .arg(
             Arg::with_name("neighborhood-mode")
                 .long("neighborhood-mode")
@@ -311,7 +795,49 @@ fn app() -> App<'static, 'static> {
                 .use_delimiter(true)
                 .help(NEIGHBORS_HELP),
         )
+        .arg(profile_arg())
         .arg(real_user_arg())
+        .arg(run_as_arg())
+        .arg(
+            Arg::with_name("obfuscation-jitter")
+                .long("obfuscation-jitter")
+                .value_name("MILLISECONDS")
+                .takes_value(true)
+                .validator(validators::validate_obfuscation_jitter)
+                .help(&OBFUSCATION_JITTER_HELP),
+        )
+        .arg(
+            Arg::with_name("obfuscation-padding")
+                .long("obfuscation-padding")
+                .value_name("BYTES")
+                .takes_value(true)
+                .validator(validators::validate_obfuscation_padding)
+                .help(&OBFUSCATION_PADDING_HELP),
+        )
+        .arg(
+            Arg::with_name("rest-port")
+                .long("rest-port")
+                .value_name("REST-PORT")
+                .takes_value(true)
+                .validator(validators::validate_ui_port)
+                .help(&REST_PORT_HELP),
+        )
+        .arg(
+            Arg::with_name("split-dns-domains")
+                .long("split-dns-domains")
+                .value_name("SPLIT-DNS-DOMAINS")
+                .takes_value(true)
+                .validator(validators::validate_split_dns_domains)
+                .help(SPLIT_DNS_DOMAINS_HELP),
+        )
+        .arg(
+            Arg::with_name("telemetry-url")
+                .long("telemetry-url")
+                .value_name("TELEMETRY-URL")
+                .takes_value(true)
+                .validator(validators::validate_telemetry_url)
+                .help(TELEMETRY_URL_HELP),
+        )
         .arg(
             Arg::with_name("ui-port")
                 .long("ui-port")
@@ -322,11 +848,13 @@ fn app() -> App<'static, 'static> {
                 .help(&UI_PORT_HELP),
         )
         .arg(wallet_password_arg(WALLET_PASSWORD_HELP))
+        .args(&payment_parameter_args())
 }
 
-mod standard {
+pub mod standard {
     use super::*;
     use std::net::IpAddr;
+    use std::net::Ipv4Addr;
     use std::net::SocketAddr;
 
     use clap::{value_t, values_t};
@@ -337,53 +865,240 @@ mod standard {
     use crate::blockchain::blockchain_interface::chain_id_from_name;
     use crate::bootstrapper::PortConfiguration;
     use crate::http_request_start_finder::HttpRequestDiscriminatorFactory;
-    use crate::multi_config::{CommandLineVcl, ConfigFileVcl, EnvironmentVcl, MultiConfig};
+    use crate::multi_config::{
+        merge, CommandLineVcl, ConfigFileVcl, EnvironmentVcl, MultiConfig, VirtualCommandLine,
+    };
+    use crate::node_configurator::instance::Instance;
+    use crate::node_configurator::secret_store::{remember_secrets, secret_store_vcl, SecretStore};
     use crate::node_configurator::{
-        determine_config_file_path, real_user_data_directory_and_chain_id,
-        request_wallet_decryption_password,
+        determine_config_file_path, make_multi_config, make_virtual_command_lines,
+        real_user_data_directory_and_chain_id, request_wallet_decryption_password,
     };
-    use crate::persistent_configuration::{PersistentConfiguration, HTTP_PORT, TLS_PORT};
-    use crate::sub_lib::accountant::DEFAULT_EARNING_WALLET;
+    use crate::persistent_configuration::{
+        PersistentConfiguration, FTP_PORT, HTTP_PORT, IMAPS_PORT, SUBMISSION_PORT, TLS_PORT,
+    };
+    use crate::raw_discriminator_factory::RawDiscriminatorFactory;
+    use crate::sub_lib::accountant::{ConsumingProfile, ProfileSelector, DEFAULT_EARNING_WALLET};
     use crate::sub_lib::cryptde::{PlainData, PublicKey};
     use crate::sub_lib::cryptde_null::CryptDENull;
+    use crate::sub_lib::logger::Logger;
+    use crate::sub_lib::neighbor_rate_limiter::NeighborRateLimits;
     use crate::sub_lib::neighborhood::{NeighborhoodConfig, NeighborhoodMode, DEFAULT_RATE_PACK};
     use crate::sub_lib::node_addr::NodeAddr;
+    use crate::sub_lib::obfuscation::ObfuscationConfig;
+    use crate::sub_lib::proxy_client::{ExitProtections, DEFAULT_EXIT_FAN_OUT_LIMIT};
+    use crate::sub_lib::ui_gateway::ConfigFileSource;
     use crate::sub_lib::wallet::Wallet;
     use crate::tls_discriminator_factory::TlsDiscriminatorFactory;
+    use dns_utility_lib::dns_modifier_factory::DnsModifierFactory;
     use rustc_hex::{FromHex, ToHex};
     use std::convert::TryInto;
+    use std::path::Path;
     use std::str::FromStr;
+    use std::time::Duration;
 
     pub fn make_service_mode_multi_config<'a>(app: &'a App, args: &Vec<String>) -> MultiConfig<'a> {
         let (config_file_path, user_specified) = determine_config_file_path(app, args);
-        MultiConfig::new(
-            &app,
-            vec![
-                Box::new(CommandLineVcl::new(args.clone())),
-                Box::new(EnvironmentVcl::new(&app)),
-                Box::new(ConfigFileVcl::new(&config_file_path, user_specified)),
-            ],
+        make_multi_config(
+            app,
+            args,
+            Some(ConfigFileVcl::new(&config_file_path, user_specified)),
+        )
+    }
+
+    // With --keychain, whatever secrets the keychain holds come in below everything else, so any
+    // other source can still override them
+    pub fn make_unprivileged_multi_config<'a>(
+        app: &'a App,
+        args: &Vec<String>,
+        secret_store: &dyn SecretStore,
+    ) -> MultiConfig<'a> {
+        let multi_config = make_service_mode_multi_config(app, args);
+        if !multi_config.arg_matches().is_present("keychain") {
+            return multi_config;
+        }
+        let (config_file_path, user_specified) = determine_config_file_path(app, args);
+        let mut vcls: Vec<Box<dyn VirtualCommandLine>> =
+            vec![Box::new(secret_store_vcl(secret_store))];
+        vcls.extend(make_virtual_command_lines(
+            app,
+            args,
+            Some(ConfigFileVcl::new(&config_file_path, user_specified)),
+        ));
+        MultiConfig::new(app, vcls)
+    }
+
+    // The wallet password can't be asked for later, the way it otherwise can, but here it can come
+    // from the keychain
+    pub fn database_password_opt(multi_config: &MultiConfig) -> Option<String> {
+        node_configurator::database_password_opt(multi_config)
+            .unwrap_or_else(|e| panic!("{}, or use --keychain", e))
+    }
+
+    // A keychain that won't take a secret costs the user a prompt next time, not this run
+    pub fn remember_secrets_if_asked(multi_config: &MultiConfig, secret_store: &dyn SecretStore) {
+        if !multi_config.arg_matches().is_present("keychain") {
+            return;
+        }
+        if let Err(e) = remember_secrets(multi_config, secret_store) {
+            let logger = Logger::new("Bootstrapper");
+            warning!(logger, "{}", e);
+        }
+    }
+
+    // Without --dns-servers, an exit Node looks names up wherever this machine did before its DNS
+    // was subverted. What it finds goes into the database, so that there's a record of what it used.
+    pub fn detect_dns_servers(
+        dns_modifier_factory: &dyn DnsModifierFactory,
+        persistent_config: &dyn PersistentConfiguration,
+    ) -> Vec<SocketAddr> {
+        let logger = Logger::new("Bootstrapper");
+        let detected = match dns_modifier_factory.make() {
+            Some(dns_modifier) => dns_modifier.original_servers(),
+            None => {
+                Err("this system's DNS settings are of a kind Node doesn't recognize".to_string())
+            }
+        };
+        match detected {
+            Ok(ref dns_servers) if dns_servers.is_empty() => {
+                warning!(
+                    logger,
+                    "No --dns-servers given, and this system's DNS settings name no DNS servers"
+                );
+                vec![]
+            }
+            Ok(dns_servers) => {
+                info!(
+                    logger,
+                    "No --dns-servers given; using {} from this system's DNS settings",
+                    dns_servers
+                        .iter()
+                        .map(|dns_server| dns_server.to_string())
+                        .collect::<Vec<String>>()
+                        .join(",")
+                );
+                persistent_config.set_detected_dns_servers(&dns_servers);
+                dns_servers
+                    .into_iter()
+                    .map(|ip| SocketAddr::from((ip, 53)))
+                    .collect()
+            }
+            Err(e) => {
+                warning!(
+                    logger,
+                    "No --dns-servers given, and this system's DNS servers couldn't be found: {}",
+                    e
+                );
+                vec![]
+            }
+        }
+    }
+
+    // Remembers what the configuration file said at startup, so that the UiGateway can work out
+    // what has changed when it reads the file again
+    pub fn config_file_source(app: &App, args: &Vec<String>) -> ConfigFileSource {
+        let (path, user_specified) = determine_config_file_path(app, args);
+        let values = ConfigFileVcl::new(&path, user_specified).values();
+        let overridden = merge(
+            Box::new(EnvironmentVcl::new(app)),
+            Box::new(CommandLineVcl::new(args.clone())),
         )
+        .vcl_args()
+        .into_iter()
+        .map(|vcl_arg| vcl_arg.name().trim_start_matches("--").to_string())
+        .collect();
+        ConfigFileSource {
+            path,
+            user_specified,
+            values,
+            overridden,
+        }
     }
 
     pub fn establish_port_configurations(config: &mut BootstrapperConfig) {
+        // Every instance needs ports 80 and 443, so each listens on a loopback address of its own
+        let loopback_ip = match &config.instance_opt {
+            Some(instance) => instance.loopback_ip(),
+            None => Ipv4Addr::LOCALHOST,
+        };
+        config.ui_gateway_config.dns_server = SocketAddr::new(IpAddr::V4(loopback_ip), DNS_PORT);
         config.port_configurations.insert(
             HTTP_PORT,
-            PortConfiguration::new(
-                vec![Box::new(HttpRequestDiscriminatorFactory::new())],
-                false,
-            ),
+            PortConfiguration {
+                loopback_ip,
+                ..PortConfiguration::new(
+                    vec![Box::new(HttpRequestDiscriminatorFactory::new())],
+                    false,
+                )
+            },
         );
         config.port_configurations.insert(
             TLS_PORT,
-            PortConfiguration::new(
-                vec![
-                    Box::new(TlsDiscriminatorFactory::new()),
-                    Box::new(HttpRequestDiscriminatorFactory::new()),
-                ],
-                false,
-            ),
+            PortConfiguration {
+                loopback_ip,
+                ..PortConfiguration::new(
+                    vec![
+                        Box::new(TlsDiscriminatorFactory::new()),
+                        Box::new(HttpRequestDiscriminatorFactory::new()),
+                    ],
+                    false,
+                )
+            },
         );
+        if config.mail_clients {
+            // After a submission client's STARTTLS, both ports carry nothing but TLS
+            [SUBMISSION_PORT, IMAPS_PORT].iter().for_each(|port| {
+                config.port_configurations.insert(
+                    *port,
+                    PortConfiguration {
+                        loopback_ip,
+                        ..PortConfiguration::new(
+                            vec![Box::new(TlsDiscriminatorFactory::new())],
+                            false,
+                        )
+                    },
+                );
+            });
+        }
+        // A browser sends a PAC profile's http requests to PORT, and its https CONNECTs to PORT+1
+        for profile in &config.consuming_profiles {
+            if let ProfileSelector::Pac(port) = profile.selector {
+                config.port_configurations.insert(
+                    port,
+                    PortConfiguration {
+                        loopback_ip,
+                        ..PortConfiguration::new(
+                            vec![Box::new(HttpRequestDiscriminatorFactory::new())],
+                            false,
+                        )
+                    },
+                );
+                config.port_configurations.insert(
+                    port + 1,
+                    PortConfiguration {
+                        loopback_ip,
+                        ..PortConfiguration::new(
+                            vec![
+                                Box::new(TlsDiscriminatorFactory::new()),
+                                Box::new(HttpRequestDiscriminatorFactory::new()),
+                            ],
+                            false,
+                        )
+                    },
+                );
+            }
+        }
+        for port_forward in &config.port_forwards {
+            config.port_configurations.insert(
+                port_forward.local_port,
+                PortConfiguration {
+                    loopback_ip,
+                    // An FTP server speaks first, so the client would wait forever for the exit
+                    opens_on_connect: port_forward.remote_port == FTP_PORT,
+                    ..PortConfiguration::new(vec![Box::new(RawDiscriminatorFactory::new())], false)
+                },
+            );
+        }
     }
 
     pub fn privileged_parse_args(
@@ -395,14 +1110,26 @@ mod standard {
             config.blockchain_bridge_config.chain_id = chain_id_from_name(chain_name.as_str());
         }
 
-        config.blockchain_bridge_config.blockchain_service_url =
-            value_m!(multi_config, "blockchain-service-url", String);
-
         let (real_user, data_directory, chain_id) =
             real_user_data_directory_and_chain_id(multi_config);
         config.real_user = real_user;
         config.data_directory = data_directory;
         config.blockchain_bridge_config.chain_id = chain_id;
+        config.instance_opt =
+            value_m!(multi_config, "instance-name", String).map(|name| Instance::new(&name));
+
+        let blockchain_service_urls = values_m!(multi_config, "blockchain-service-url", String);
+        config.blockchain_bridge_config.blockchain_service_urls =
+            if blockchain_service_urls.is_empty() {
+                chain_record(chain_id)
+                    .default_blockchain_service_url
+                    .map(|url| vec![url.to_string()])
+                    .unwrap_or_default()
+            } else {
+                blockchain_service_urls
+            };
+        config.blockchain_bridge_config.blockchain_service_ws_url =
+            value_m!(multi_config, "blockchain-service-ws-url", String);
 
         config.dns_servers = values_m!(multi_config, "dns-servers", IpAddr)
             .into_iter()
@@ -411,15 +1138,62 @@ mod standard {
 
         config.log_level =
             value_m!(multi_config, "log-level", LevelFilter).expect("Internal Error");
+        config.log_rotation = make_log_rotation(multi_config);
 
         config.neighborhood_config = make_neighborhood_config(multi_config);
+        config.lan_discovery = multi_config.arg_matches().is_present("lan-discovery");
+        config.manage_firewall = multi_config.arg_matches().is_present("manage-firewall");
+        config.kill_switch = multi_config.arg_matches().is_present("kill-switch");
+        config.mail_clients = multi_config.arg_matches().is_present("mail-clients");
+        config.port_forwards = values_m!(multi_config, "forward", String)
+            .into_iter()
+            .map(|spec| {
+                PortForward::parse(&spec).expect("--forward not properly constrained by clap")
+            })
+            .collect();
+        config.neighbor_rate_limits = NeighborRateLimits {
+            ingress_bytes_per_sec_opt: value_m!(multi_config, "neighbor-ingress-limit", u64)
+                .map(|kilobytes| kilobytes * 1000),
+            egress_bytes_per_sec_opt: value_m!(multi_config, "neighbor-egress-limit", u64)
+                .map(|kilobytes| kilobytes * 1000),
+        };
+        config.exit_protections = ExitProtections {
+            block_smtp: multi_config.arg_matches().is_present("exit-block-smtp"),
+            block_mail: multi_config.arg_matches().is_present("exit-block-mail"),
+            fan_out_limit: value_m!(multi_config, "exit-fan-out-limit", usize)
+                .unwrap_or(DEFAULT_EXIT_FAN_OUT_LIMIT),
+            scan_detection: !multi_config
+                .arg_matches()
+                .is_present("exit-no-scan-detection"),
+        };
+        config.spill_config = make_spill_config(multi_config, &config.data_directory);
+        config.memory_thresholds_opt =
+            value_m!(multi_config, "memory-thresholds", String).map(|megabytes| {
+                MemoryThresholds::parse(&megabytes)
+                    .expect("--memory-thresholds not properly constrained by clap")
+            });
 
         config.ui_gateway_config.ui_port =
             value_m!(multi_config, "ui-port", u16).expect("Internal Error");
+        config.ui_gateway_config.rest_port_opt = value_m!(multi_config, "rest-port", u16);
+        if config.ui_gateway_config.rest_port_opt == Some(config.ui_gateway_config.ui_port) {
+            panic!("--rest-port and --ui-port must be different")
+        }
+        config.ui_gateway_config.telemetry_url_opt =
+            value_m!(multi_config, "telemetry-url", String);
 
         config.crash_point =
             value_m!(multi_config, "crash-point", CrashPoint).expect("Internal Error");
 
+        config.consuming_profiles = values_m!(multi_config, "consuming-profiles", ConsumingProfile);
+
+        config.obfuscation_config = ObfuscationConfig {
+            max_jitter: Duration::from_millis(
+                value_m!(multi_config, "obfuscation-jitter", u64).unwrap_or(0),
+            ),
+            max_padding: value_m!(multi_config, "obfuscation-padding", usize).unwrap_or(0),
+        };
+
         match value_m!(multi_config, "fake-public-key", String) {
             None => (),
             Some(public_key_str) => {
@@ -443,6 +1217,22 @@ mod standard {
         unprivileged_config.clandestine_port_opt = value_m!(multi_config, "clandestine-port", u16);
         unprivileged_config.blockchain_bridge_config.gas_price =
             value_m!(multi_config, "gas-price", u64);
+        unprivileged_config.blockchain_bridge_config.fee_strategy =
+            value_m!(multi_config, "fee-strategy", FeeStrategy).unwrap_or_default();
+        unprivileged_config.blockchain_bridge_config.gas_price_cap =
+            value_m!(multi_config, "gas-price-cap", u64);
+        unprivileged_config.blockchain_bridge_config.fee_budget =
+            value_m!(multi_config, "fee-budget", u64).map(|gwei| FeeBudget {
+                gwei,
+                period: value_m!(multi_config, "fee-budget-period", FeeBudgetPeriod)
+                    .unwrap_or_default(),
+            });
+        unprivileged_config.split_dns_domains =
+            match value_m!(multi_config, "split-dns-domains", String) {
+                Some(domains) => split_dns_domains(&domains),
+                None => persistent_config.split_dns_domains(),
+            };
+        get_payment_parameters(multi_config, persistent_config, unprivileged_config);
         get_wallets(
             streams,
             multi_config,
@@ -461,9 +1251,27 @@ mod standard {
         if persistent_config.earning_wallet_address().is_none() {
             persistent_config.set_earning_wallet_address(&config.earning_wallet.to_string());
         }
+        if let Some(exit_earning_wallet) = &config.exit_earning_wallet_opt {
+            if persistent_config
+                .exit_earning_wallet_from_address()
+                .as_ref()
+                != Some(exit_earning_wallet)
+            {
+                persistent_config.set_exit_earning_wallet_address(&exit_earning_wallet.to_string());
+            }
+        }
         if let Some(gas_price) = config.blockchain_bridge_config.gas_price {
             persistent_config.set_gas_price(gas_price)
         }
+        if persistent_config.split_dns_domains() != config.split_dns_domains {
+            persistent_config.set_split_dns_domains(&config.split_dns_domains)
+        }
+        PAYMENT_PARAMETERS.iter().for_each(|parameter| {
+            let value = config.accountant_config.payment_parameter(*parameter);
+            if persistent_config.payment_parameter(*parameter) != value {
+                persistent_config.set_payment_parameter(*parameter, value)
+            }
+        });
         match &config.consuming_wallet {
             Some(consuming_wallet)
                 if persistent_config
@@ -484,6 +1292,32 @@ mod standard {
         }
     }
 
+    // Lowercase and without dots at either end, so that they compare easily with query names
+    pub fn split_dns_domains(domains: &str) -> Vec<String> {
+        domains
+            .split(',')
+            .map(|domain| domain.trim().trim_matches('.').to_lowercase())
+            .filter(|domain| !domain.is_empty())
+            .collect()
+    }
+
+    pub fn get_payment_parameters(
+        multi_config: &MultiConfig,
+        persistent_config: &dyn PersistentConfiguration,
+        config: &mut BootstrapperConfig,
+    ) {
+        PAYMENT_PARAMETERS.iter().for_each(|parameter| {
+            let value = value_m!(multi_config, parameter.name(), u64)
+                .unwrap_or_else(|| persistent_config.payment_parameter(*parameter));
+            config
+                .accountant_config
+                .set_payment_parameter(*parameter, value);
+        });
+        if let Err(e) = config.accountant_config.payment_curves.validate() {
+            panic!("Can't continue; {}", e)
+        }
+    }
+
     pub fn get_wallets(
         streams: &mut StdStreams,
         multi_config: &MultiConfig,
@@ -524,6 +1358,50 @@ mod standard {
             Some(earning_wallet) => earning_wallet,
             None => DEFAULT_EARNING_WALLET.clone(),
         };
+        config.exit_earning_wallet_opt = match value_m!(multi_config, "exit-earning-wallet", String)
+        {
+            Some(address) => Some(
+                Wallet::from_str(&address)
+                    .expect("--exit-earning-wallet not properly constrained by clap"),
+            ),
+            None => persistent_config.exit_earning_wallet_from_address(),
+        };
+    }
+
+    fn make_log_rotation(multi_config: &MultiConfig) -> LogRotation {
+        let default = LogRotation::default();
+        LogRotation {
+            max_size: match value_m!(multi_config, "log-rotate-size", u64) {
+                Some(megabytes) => megabytes * 1_000_000,
+                None => default.max_size,
+            },
+            max_age_opt: value_m!(multi_config, "log-rotate-hours", u64)
+                .map(|hours| Duration::from_secs(hours * 60 * 60)),
+            keep_files: value_m!(multi_config, "log-keep-files", usize)
+                .unwrap_or(default.keep_files),
+            keep_age_opt: value_m!(multi_config, "log-keep-days", u64)
+                .map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+            gzip: !multi_config.arg_matches().is_present("log-no-gzip"),
+        }
+    }
+
+    fn make_spill_config(multi_config: &MultiConfig, data_directory: &Path) -> SpillConfig {
+        let default = SpillConfig::default();
+        SpillConfig {
+            memory_limit: match value_m!(multi_config, "buffer-memory-limit", usize) {
+                Some(kilobytes) => kilobytes * 1024,
+                None => default.memory_limit,
+            },
+            spill_limit: match value_m!(multi_config, "buffer-spill-limit", u64) {
+                Some(megabytes) => megabytes * 1024 * 1024,
+                None => default.spill_limit,
+            },
+            budget: match value_m!(multi_config, "buffer-spill-total-limit", u64) {
+                Some(megabytes) => SpillBudget::new(megabytes * 1024 * 1024),
+                None => default.budget,
+            },
+            directory_opt: Some(data_directory.join(SPILL_DIRECTORY)),
+        }
     }
 
     pub fn make_neighborhood_config(multi_config: &MultiConfig) -> NeighborhoodConfig {
@@ -580,10 +1458,14 @@ mod standard {
             earning_wallet_from_database_opt,
         ) {
             (None, None) => None,
-            (Some(address), None) => Some(
-                Wallet::from_str(&address)
-                    .expect("--earning-wallet not properly constrained by clap"),
-            ),
+            (Some(address), None) => {
+                let wallet = Wallet::from_str(&address)
+                    .expect("--earning-wallet not properly constrained by clap");
+                // Remember it, so a watch-only earning wallet doesn't silently fall back to the
+                // default the next time the Node starts without --earning-wallet
+                persistent_config.set_earning_wallet_address(&address);
+                Some(wallet)
+            }
             (None, Some(wallet)) => Some(wallet),
             (Some(address), Some(wallet)) => {
                 if wallet.to_string().to_lowercase() == address.to_lowercase() {
@@ -678,8 +1560,10 @@ mod standard {
     }
 }
 
-mod validators {
+pub mod validators {
     use super::*;
+    use crate::sub_lib::accountant::ConsumingProfile;
+    use crate::ui_gateway::telemetry::parse_telemetry_url;
     use regex::Regex;
     use std::net::IpAddr;
     use std::str::FromStr;
@@ -691,6 +1575,19 @@ mod validators {
         }
     }
 
+    pub fn validate_split_dns_domains(domains: String) -> Result<(), String> {
+        let domain_regex = Regex::new(r"^\.?[A-Za-z0-9_-]+(\.[A-Za-z0-9_-]+)*\.?$")
+            .expect("Failed to compile regular expression");
+        match domains
+            .split(',')
+            .map(|domain| domain.trim())
+            .find(|domain| !domain.is_empty() && !domain_regex.is_match(domain))
+        {
+            Some(domain) => Err(domain.to_string()),
+            None => Ok(()),
+        }
+    }
+
     pub fn validate_ui_port(port: String) -> Result<(), String> {
         match str::parse::<u16>(&port) {
             Ok(port_number) if port_number < LOWEST_USABLE_INSECURE_PORT => Err(port),
@@ -717,45 +1614,146 @@ mod validators {
         }
     }
 
+    pub fn validate_consuming_profile(profile: String) -> Result<(), String> {
+        match ConsumingProfile::from_str(&profile) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(profile),
+        }
+    }
+
+    pub fn validate_obfuscation_jitter(jitter: String) -> Result<(), String> {
+        match jitter.parse::<u64>() {
+            Ok(ms) if ms <= MAX_OBFUSCATION_JITTER_MS => Ok(()),
+            _ => Err(jitter),
+        }
+    }
+
+    pub fn validate_obfuscation_padding(padding: String) -> Result<(), String> {
+        match padding.parse::<usize>() {
+            Ok(bytes) if bytes <= MAX_OBFUSCATION_PADDING => Ok(()),
+            _ => Err(padding),
+        }
+    }
+
     pub fn validate_gas_price(gas_price: String) -> Result<(), String> {
         match gas_price.parse::<u8>() {
             Ok(gp) if gp > 0 && gp < 100 => Ok(()),
             _ => Err(gas_price),
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::blockchain::bip32::Bip32ECKeyPair;
-    use crate::blockchain::bip39::{Bip39, Bip39Error};
-    use crate::blockchain::blockchain_interface::{
-        chain_id_from_name, contract_address, DEFAULT_CHAIN_NAME,
-    };
-    use crate::bootstrapper::RealUser;
-    use crate::config_dao::{ConfigDao, ConfigDaoReal};
-    use crate::database::db_initializer;
-    use crate::database::db_initializer::{DbInitializer, DbInitializerReal};
-    use crate::multi_config::tests::FauxEnvironmentVcl;
-    use crate::multi_config::{
-        CommandLineVcl, ConfigFileVcl, MultiConfig, NameValueVclArg, VclArg, VirtualCommandLine,
-    };
-    use crate::persistent_configuration::PersistentConfigurationReal;
-    use crate::sub_lib::accountant::DEFAULT_EARNING_WALLET;
-    use crate::sub_lib::crash_point::CrashPoint;
-    use crate::sub_lib::cryptde::{CryptDE, PlainData, PublicKey};
-    use crate::sub_lib::cryptde_null::CryptDENull;
-    use crate::sub_lib::neighborhood::{NeighborhoodConfig, NeighborhoodMode, DEFAULT_RATE_PACK};
-    use crate::sub_lib::node_addr::NodeAddr;
-    use crate::sub_lib::wallet::Wallet;
-    use crate::test_utils::environment_guard::EnvironmentGuard;
-    use crate::test_utils::persistent_configuration_mock::PersistentConfigurationMock;
-    use crate::test_utils::{
-        ensure_node_home_directory_exists, ArgsBuilder, TEST_DEFAULT_CHAIN_NAME,
-    };
-    use crate::test_utils::{make_default_persistent_configuration, DEFAULT_CHAIN_ID};
-    use crate::test_utils::{ByteArrayWriter, FakeStreamHolder};
+    pub fn validate_ws_url(url: String) -> Result<(), String> {
+        if url.starts_with("ws://") || url.starts_with("wss://") {
+            Ok(())
+        } else {
+            Err(url)
+        }
+    }
+
+    pub fn validate_telemetry_url(url: String) -> Result<(), String> {
+        parse_telemetry_url(&url).map(|_| ())
+    }
+
+    pub fn validate_log_rotation_limit(limit: String) -> Result<(), String> {
+        match limit.parse::<u64>() {
+            Ok(value) if value > 0 => Ok(()),
+            _ => Err(limit),
+        }
+    }
+
+    pub fn validate_buffer_limit(limit: String) -> Result<(), String> {
+        match limit.parse::<u64>() {
+            Ok(_) => Ok(()),
+            Err(_) => Err(limit),
+        }
+    }
+
+    pub fn validate_port_forward(spec: String) -> Result<(), String> {
+        PortForward::parse(&spec).map(|_| ())
+    }
+
+    pub fn validate_memory_thresholds(megabytes: String) -> Result<(), String> {
+        MemoryThresholds::parse(&megabytes).map(|_| ())
+    }
+
+    pub fn validate_neighbor_rate_limit(kilobytes_per_sec: String) -> Result<(), String> {
+        match kilobytes_per_sec.parse::<u64>() {
+            Ok(value) if value > 0 => Ok(()),
+            _ => Err(kilobytes_per_sec),
+        }
+    }
+
+    pub fn validate_exit_fan_out_limit(count: String) -> Result<(), String> {
+        match count.parse::<usize>() {
+            Ok(value) if value > 0 => Ok(()),
+            _ => Err(count),
+        }
+    }
+
+    pub fn validate_fee_budget(fee_budget: String) -> Result<(), String> {
+        match fee_budget.parse::<u64>() {
+            Ok(gwei) if gwei > 0 => Ok(()),
+            _ => Err(fee_budget),
+        }
+    }
+
+    pub fn validate_gas_price_cap(gas_price_cap: String) -> Result<(), String> {
+        match gas_price_cap.parse::<u64>() {
+            Ok(cap) if cap > 0 => Ok(()),
+            _ => Err(gas_price_cap),
+        }
+    }
+
+    pub fn validate_payment_parameter(
+        parameter: PaymentParameter,
+        value: String,
+    ) -> Result<(), String> {
+        match value.parse::<u64>() {
+            Ok(number) if parameter.validate(number).is_ok() => Ok(()),
+            _ => Err(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::bip32::Bip32ECKeyPair;
+    use crate::blockchain::bip39::{Bip39, Bip39Error};
+    use crate::blockchain::blockchain_interface::{
+        chain_id_from_name, contract_address, DEFAULT_CHAIN_NAME, XDAI_BLOCKCHAIN_SERVICE_URL,
+    };
+    use crate::bootstrapper::RealUser;
+    use crate::config_dao::{ConfigDao, ConfigDaoReal};
+    use crate::database::db_initializer;
+    use crate::database::db_initializer::{DbInitializer, DbInitializerReal};
+    use crate::multi_config::tests::FauxEnvironmentVcl;
+    use crate::multi_config::{
+        CommandLineVcl, ConfigFileVcl, MultiConfig, NameValueVclArg, VclArg, VirtualCommandLine,
+    };
+    use crate::node_configurator::instance::Instance;
+    use crate::node_configurator::secret_store::Secret;
+    use crate::persistent_configuration::{PersistentConfigurationReal, HTTP_PORT, TLS_PORT};
+    use crate::sub_lib::accountant::{ConsumingProfile, ProfileSelector, DEFAULT_EARNING_WALLET};
+    use crate::sub_lib::crash_point::CrashPoint;
+    use crate::sub_lib::cryptde::{CryptDE, PlainData, PublicKey};
+    use crate::sub_lib::cryptde_null::CryptDENull;
+    use crate::sub_lib::neighbor_rate_limiter::NeighborRateLimits;
+    use crate::sub_lib::neighborhood::{NeighborhoodConfig, NeighborhoodMode, DEFAULT_RATE_PACK};
+    use crate::sub_lib::node_addr::NodeAddr;
+    use crate::sub_lib::obfuscation::ObfuscationConfig;
+    use crate::sub_lib::proxy_client::ExitProtections;
+    use crate::sub_lib::wallet::Wallet;
+    use crate::test_utils::dns_modifier_mock::{DnsModifierFactoryMock, DnsModifierMock};
+    use crate::test_utils::environment_guard::EnvironmentGuard;
+    use crate::test_utils::logging::{init_test_logging, TestLogHandler};
+    use crate::test_utils::persistent_configuration_mock::PersistentConfigurationMock;
+    use crate::test_utils::secret_store_mock::SecretStoreMock;
+    use crate::test_utils::{
+        ensure_node_home_directory_exists, make_wallet, ArgsBuilder, TEST_DEFAULT_CHAIN_NAME,
+    };
+    use crate::test_utils::{make_default_persistent_configuration, DEFAULT_CHAIN_ID};
+    use crate::test_utils::{ByteArrayWriter, FakeStreamHolder};
     use ethsign::keyfile::Crypto;
     use ethsign::Protected;
     use rustc_hex::{FromHex, ToHex};
@@ -768,6 +1766,7 @@ mod tests {
     use std::path::PathBuf;
     use std::str::FromStr;
     use std::sync::{Arc, Mutex};
+    use std::time::Duration;
 
     fn make_default_cli_params() -> ArgsBuilder {
         ArgsBuilder::new()
@@ -819,6 +1818,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn validate_split_dns_domains_accepts_domains_and_nothing() {
+        assert_eq!(
+            Ok(()),
+            validators::validate_split_dns_domains(String::from("corp.example.com, .lan."))
+        );
+        assert_eq!(
+            Ok(()),
+            validators::validate_split_dns_domains(String::new())
+        );
+    }
+
+    #[test]
+    fn validate_split_dns_domains_complains_about_what_is_not_a_domain() {
+        assert_eq!(
+            Err(String::from("corp..example.com")),
+            validators::validate_split_dns_domains(String::from("lan,corp..example.com"))
+        );
+        assert_eq!(
+            Err(String::from("10.0.0.0/8")),
+            validators::validate_split_dns_domains(String::from("10.0.0.0/8"))
+        );
+    }
+
     #[test]
     fn validate_ui_port_complains_about_non_numeric_ui_port() {
         let result = validators::validate_ui_port(String::from("booga"));
@@ -928,6 +1951,261 @@ mod tests {
         assert_eq!(Err(String::from("0x0")), result);
     }
 
+    #[test]
+    fn validate_ws_url_accepts_websocket_urls() {
+        assert_eq!(
+            validators::validate_ws_url("ws://localhost:8546".to_string()),
+            Ok(())
+        );
+        assert_eq!(
+            validators::validate_ws_url("wss://mainnet.infura.io/ws/v3/ID".to_string()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn validate_ws_url_rejects_other_urls() {
+        assert_eq!(
+            validators::validate_ws_url("http://localhost:8545".to_string()),
+            Err("http://localhost:8545".to_string())
+        );
+    }
+
+    #[test]
+    fn validate_telemetry_url_accepts_only_http_urls() {
+        assert_eq!(
+            validators::validate_telemetry_url("http://telemetry.example.com/report".to_string()),
+            Ok(())
+        );
+        assert_eq!(
+            validators::validate_telemetry_url("https://telemetry.example.com/report".to_string()),
+            Err("https://telemetry.example.com/report is not an http:// URL".to_string())
+        );
+    }
+
+    #[test]
+    fn validate_fee_budget_accepts_only_positive_numbers() {
+        assert_eq!(
+            validators::validate_fee_budget("50000000".to_string()),
+            Ok(())
+        );
+        assert_eq!(
+            validators::validate_fee_budget("0".to_string()),
+            Err("0".to_string())
+        );
+        assert_eq!(
+            validators::validate_fee_budget("lots".to_string()),
+            Err("lots".to_string())
+        );
+    }
+
+    #[test]
+    fn validate_neighbor_rate_limit_accepts_only_positive_numbers() {
+        assert_eq!(
+            validators::validate_neighbor_rate_limit("500".to_string()),
+            Ok(())
+        );
+        assert_eq!(
+            validators::validate_neighbor_rate_limit("0".to_string()),
+            Err("0".to_string())
+        );
+        assert_eq!(
+            validators::validate_neighbor_rate_limit("fast".to_string()),
+            Err("fast".to_string())
+        );
+    }
+
+    #[test]
+    fn validate_exit_fan_out_limit_accepts_only_positive_numbers() {
+        assert_eq!(
+            validators::validate_exit_fan_out_limit("50".to_string()),
+            Ok(())
+        );
+        assert_eq!(
+            validators::validate_exit_fan_out_limit("0".to_string()),
+            Err("0".to_string())
+        );
+        assert_eq!(
+            validators::validate_exit_fan_out_limit("many".to_string()),
+            Err("many".to_string())
+        );
+    }
+
+    #[test]
+    fn validate_log_rotation_limit_accepts_only_positive_numbers() {
+        assert_eq!(
+            validators::validate_log_rotation_limit("24".to_string()),
+            Ok(())
+        );
+        assert_eq!(
+            validators::validate_log_rotation_limit("0".to_string()),
+            Err("0".to_string())
+        );
+        assert_eq!(
+            validators::validate_log_rotation_limit("-1".to_string()),
+            Err("-1".to_string())
+        );
+    }
+
+    #[test]
+    fn validate_buffer_limit_accepts_zero_and_positive_numbers() {
+        assert_eq!(validators::validate_buffer_limit("0".to_string()), Ok(()));
+        assert_eq!(validators::validate_buffer_limit("512".to_string()), Ok(()));
+        assert_eq!(
+            validators::validate_buffer_limit("-1".to_string()),
+            Err("-1".to_string())
+        );
+        assert_eq!(
+            validators::validate_buffer_limit("lots".to_string()),
+            Err("lots".to_string())
+        );
+    }
+
+    #[test]
+    fn validate_port_forward_accepts_local_port_remote_host_and_remote_port() {
+        assert_eq!(
+            validators::validate_port_forward("5432:db.example.com:5432".to_string()),
+            Ok(())
+        );
+        assert_eq!(
+            validators::validate_port_forward("db.example.com:5432".to_string()),
+            Err(
+                "Port forward must look like LOCAL-PORT:HOST:PORT: 'db.example.com:5432'"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn validate_memory_thresholds_accepts_three_increasing_numbers() {
+        assert_eq!(
+            validators::validate_memory_thresholds("512,768,1024".to_string()),
+            Ok(())
+        );
+        assert_eq!(
+            validators::validate_memory_thresholds("1024,768,512".to_string()),
+            Err(
+                "Memory thresholds must be three increasing numbers of megabytes: '1024,768,512'"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn validate_gas_price_cap_accepts_positive_numbers() {
+        assert_eq!(validators::validate_gas_price_cap("1".to_string()), Ok(()));
+        assert_eq!(
+            validators::validate_gas_price_cap("500".to_string()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn validate_gas_price_cap_rejects_zero_and_non_numbers() {
+        assert_eq!(
+            validators::validate_gas_price_cap("0".to_string()),
+            Err("0".to_string())
+        );
+        assert_eq!(
+            validators::validate_gas_price_cap("lots".to_string()),
+            Err("lots".to_string())
+        );
+    }
+
+    #[test]
+    fn validate_payment_parameter_accepts_range_limits() {
+        let parameter = PaymentParameter::PayableScanInterval;
+
+        let low = validators::validate_payment_parameter(parameter, "60".to_string());
+        let high = validators::validate_payment_parameter(parameter, "604800".to_string());
+
+        assert_eq!(low, Ok(()));
+        assert_eq!(high, Ok(()));
+    }
+
+    #[test]
+    fn validate_payment_parameter_rejects_values_out_of_range() {
+        let parameter = PaymentParameter::PayableScanInterval;
+
+        let low = validators::validate_payment_parameter(parameter, "59".to_string());
+        let high = validators::validate_payment_parameter(parameter, "604801".to_string());
+
+        assert_eq!(low, Err(String::from("59")));
+        assert_eq!(high, Err(String::from("604801")));
+    }
+
+    #[test]
+    fn validate_payment_parameter_rejects_non_numbers() {
+        let result = validators::validate_payment_parameter(
+            PaymentParameter::PermanentDebtAllowed,
+            "lots".to_string(),
+        );
+
+        assert_eq!(result, Err(String::from("lots")));
+    }
+
+    #[test]
+    fn validate_consuming_profile_accepts_well_formed_profile() {
+        let result = validators::validate_consuming_profile("work:10000-19999:5000".to_string());
+
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn validate_consuming_profile_rejects_malformed_profile() {
+        let result = validators::validate_consuming_profile("work:19999-10000".to_string());
+
+        assert_eq!(result, Err("work:19999-10000".to_string()));
+    }
+
+    #[test]
+    fn validate_obfuscation_jitter_accepts_limits() {
+        assert_eq!(
+            validators::validate_obfuscation_jitter("0".to_string()),
+            Ok(())
+        );
+        assert_eq!(
+            validators::validate_obfuscation_jitter("1000".to_string()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn validate_obfuscation_jitter_rejects_bad_values() {
+        assert_eq!(
+            validators::validate_obfuscation_jitter("1001".to_string()),
+            Err("1001".to_string())
+        );
+        assert_eq!(
+            validators::validate_obfuscation_jitter("-1".to_string()),
+            Err("-1".to_string())
+        );
+    }
+
+    #[test]
+    fn validate_obfuscation_padding_accepts_limits() {
+        assert_eq!(
+            validators::validate_obfuscation_padding("0".to_string()),
+            Ok(())
+        );
+        assert_eq!(
+            validators::validate_obfuscation_padding("4096".to_string()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn validate_obfuscation_padding_rejects_bad_values() {
+        assert_eq!(
+            validators::validate_obfuscation_padding("4097".to_string()),
+            Err("4097".to_string())
+        );
+        assert_eq!(
+            validators::validate_obfuscation_padding("lots".to_string()),
+            Err("lots".to_string())
+        );
+    }
+
     #[test]
     fn make_neighborhood_config_standard_happy_path() {
         let multi_config = MultiConfig::new(
@@ -1166,31 +2444,398 @@ mod tests {
     }
 
     #[test]
-    fn can_read_dns_servers_and_consuming_private_key_from_config_file() {
+    fn privileged_configuration_remembers_the_config_file_for_reloading() {
+        let _guard = EnvironmentGuard::new();
         let home_dir = ensure_node_home_directory_exists(
             "node_configurator",
-            "can_read_wallet_parameters_from_config_file",
+            "privileged_configuration_remembers_the_config_file_for_reloading",
         );
-        let persistent_config = PersistentConfigurationReal::new(Box::new(ConfigDaoReal::new(
-            DbInitializerReal::new()
-                .initialize(&home_dir.clone(), DEFAULT_CHAIN_ID)
-                .unwrap(),
-        )));
-        let consuming_private_key =
-            "89ABCDEF89ABCDEF89ABCDEF89ABCDEF89ABCDEF89ABCDEF89ABCDEF89ABCDEF";
-        let config_file_path = home_dir.join("config.toml");
         {
-            let mut config_file = File::create(&config_file_path).unwrap();
-            writeln!(
-                config_file,
-                "dns-servers = \"1.2.3.4\"\nconsuming-private-key = \"{}\"",
-                consuming_private_key
-            )
-            .unwrap();
+            let mut config_file = File::create(home_dir.join("config.toml")).unwrap();
+            config_file
+                .write_all(b"dns-servers = \"1.2.3.4\"\nlog-level = \"info\"\n")
+                .unwrap();
         }
-        let args = ArgsBuilder::new()
-            .param("--data-directory", home_dir.to_str().unwrap())
-            .param("--ip", "1.2.3.4");
+        let subject = NodeConfiguratorStandardPrivileged {};
+
+        let configuration = subject.configure(
+            &ArgsBuilder::new()
+                .param("--data-directory", home_dir.to_str().unwrap())
+                .param("--ip", "1.2.3.4")
+                .param("--log-level", "warn")
+                .into(),
+            &mut FakeStreamHolder::new().streams(),
+        );
+
+        let config_file = configuration.ui_gateway_config.config_file_opt.unwrap();
+        assert_eq!(config_file.path, home_dir.join("config.toml"));
+        assert_eq!(config_file.user_specified, false);
+        assert_eq!(
+            config_file.values.into_iter().collect::<Vec<(String, String)>>(),
+            vec![
+                ("dns-servers".to_string(), "1.2.3.4".to_string()),
+                ("log-level".to_string(), "info".to_string()),
+            ]
+        );
+        assert_eq!(
+            config_file.overridden.into_iter().collect::<Vec<String>>(),
+            vec![
+                "data-directory".to_string(),
+                "ip".to_string(),
+                "log-level".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn privileged_configuration_listens_for_mail_clients_only_when_asked() {
+        let _guard = EnvironmentGuard::new();
+        let home_dir = ensure_node_home_directory_exists(
+            "node_configurator",
+            "privileged_configuration_listens_for_mail_clients_only_when_asked",
+        );
+        let subject = NodeConfiguratorStandardPrivileged {};
+        let args = || {
+            ArgsBuilder::new()
+                .param("--data-directory", home_dir.to_str().unwrap())
+                .param("--dns-servers", "1.2.3.4")
+                .param("--ip", "1.2.3.4")
+        };
+
+        let without = subject.configure(&args().into(), &mut FakeStreamHolder::new().streams());
+        let with = subject.configure(
+            &args().opt("--mail-clients").into(),
+            &mut FakeStreamHolder::new().streams(),
+        );
+
+        assert!(!without.port_configurations.contains_key(&587));
+        assert!(!without.port_configurations.contains_key(&993));
+        [587, 993].iter().for_each(|port| {
+            let port_configuration = &with.port_configurations[port];
+            assert!(!port_configuration.is_clandestine);
+            assert_eq!(port_configuration.loopback_ip, Ipv4Addr::LOCALHOST);
+        });
+    }
+
+    #[test]
+    fn privileged_configuration_listens_on_each_forwarded_port() {
+        let _guard = EnvironmentGuard::new();
+        let home_dir = ensure_node_home_directory_exists(
+            "node_configurator",
+            "privileged_configuration_listens_on_each_forwarded_port",
+        );
+        let subject = NodeConfiguratorStandardPrivileged {};
+
+        let configuration = subject.configure(
+            &ArgsBuilder::new()
+                .param("--data-directory", home_dir.to_str().unwrap())
+                .param("--dns-servers", "1.2.3.4")
+                .param("--ip", "1.2.3.4")
+                .param(
+                    "--forward",
+                    "5432:db.example.com:5433,3389:desktop.example.com:3389,2121:ftp.example.com:21",
+                )
+                .into(),
+            &mut FakeStreamHolder::new().streams(),
+        );
+
+        assert_eq!(
+            configuration.port_forwards,
+            vec![
+                PortForward {
+                    local_port: 5432,
+                    remote_host: "db.example.com".to_string(),
+                    remote_port: 5433,
+                },
+                PortForward {
+                    local_port: 3389,
+                    remote_host: "desktop.example.com".to_string(),
+                    remote_port: 3389,
+                },
+                PortForward {
+                    local_port: 2121,
+                    remote_host: "ftp.example.com".to_string(),
+                    remote_port: 21,
+                },
+            ]
+        );
+        [(5432, false), (3389, false), (2121, true)]
+            .iter()
+            .for_each(|(port, opens_on_connect)| {
+                let port_configuration = &configuration.port_configurations[port];
+                assert!(!port_configuration.is_clandestine);
+                assert_eq!(port_configuration.loopback_ip, Ipv4Addr::LOCALHOST);
+                assert_eq!(port_configuration.opens_on_connect, *opens_on_connect);
+            });
+    }
+
+    #[test]
+    fn privileged_configuration_gives_an_instance_its_own_directory_ports_and_loopback_address() {
+        let _guard = EnvironmentGuard::new();
+        let home_dir = ensure_node_home_directory_exists(
+            "node_configurator",
+            "privileged_configuration_gives_an_instance_its_own_directory_ports_and_loopback_address",
+        );
+        let instance = Instance::new("alice");
+        let subject = NodeConfiguratorStandardPrivileged {};
+
+        let configuration = subject.configure(
+            &ArgsBuilder::new()
+                .param("--data-directory", home_dir.to_str().unwrap())
+                .param("--dns-servers", "1.2.3.4")
+                .param("--ip", "1.2.3.4")
+                .param("--instance-name", "alice")
+                .into(),
+            &mut FakeStreamHolder::new().streams(),
+        );
+
+        assert_eq!(configuration.data_directory, home_dir.join("alice"));
+        assert_eq!(configuration.instance_opt, Some(instance.clone()));
+        assert_eq!(configuration.ui_gateway_config.ui_port, instance.ui_port());
+        assert_eq!(
+            configuration.port_configurations[&HTTP_PORT].loopback_ip,
+            instance.loopback_ip()
+        );
+        assert_eq!(
+            configuration.port_configurations[&TLS_PORT].loopback_ip,
+            instance.loopback_ip()
+        );
+        assert_eq!(
+            configuration.ui_gateway_config.dns_server,
+            SocketAddr::new(IpAddr::V4(instance.loopback_ip()), 53)
+        );
+    }
+
+    #[test]
+    fn unprivileged_parse_args_takes_the_clandestine_port_from_the_instance() {
+        let _guard = EnvironmentGuard::new();
+        let args: Vec<String> = ArgsBuilder::new().param("--instance-name", "alice").into();
+        let mut config = BootstrapperConfig::new();
+        let app = app();
+        let multi_config = node_configurator::make_multi_config(&app, &args, None);
+
+        standard::unprivileged_parse_args(
+            &multi_config,
+            &mut config,
+            &mut FakeStreamHolder::new().streams(),
+            &make_default_persistent_configuration(),
+        );
+
+        assert_eq!(
+            config.clandestine_port_opt,
+            Some(Instance::new("alice").clandestine_port())
+        );
+    }
+
+    #[test]
+    fn unprivileged_multi_config_takes_secrets_from_the_keychain_when_asked() {
+        let _guard = EnvironmentGuard::new();
+        let home_dir = ensure_node_home_directory_exists(
+            "node_configurator",
+            "unprivileged_multi_config_takes_secrets_from_the_keychain_when_asked",
+        );
+        let secret_store = SecretStoreMock::new()
+            .secret(Secret::WalletPassword, "keychain password")
+            .secret(
+                Secret::ConsumingPrivateKey,
+                "0123456789ABCDEF0123456789ABCDEF0123456789ABCDEF0123456789ABCDEF",
+            );
+        let app = app();
+        let args: Vec<String> = ArgsBuilder::new()
+            .param("--data-directory", home_dir.to_str().unwrap())
+            .param(
+                "--consuming-private-key",
+                "FEDCBA9876543210FEDCBA9876543210FEDCBA9876543210FEDCBA9876543210",
+            )
+            .opt("--keychain")
+            .into();
+
+        let multi_config = standard::make_unprivileged_multi_config(&app, &args, &secret_store);
+
+        assert_eq!(
+            value_m!(multi_config, "wallet-password", String),
+            Some("keychain password".to_string())
+        );
+        assert_eq!(
+            value_m!(multi_config, "consuming-private-key", String),
+            Some("FEDCBA9876543210FEDCBA9876543210FEDCBA9876543210FEDCBA9876543210".to_string())
+        );
+    }
+
+    #[test]
+    fn unprivileged_multi_config_leaves_the_keychain_alone_unless_asked() {
+        let _guard = EnvironmentGuard::new();
+        let home_dir = ensure_node_home_directory_exists(
+            "node_configurator",
+            "unprivileged_multi_config_leaves_the_keychain_alone_unless_asked",
+        );
+        let store_params_arc = Arc::new(Mutex::new(vec![]));
+        let secret_store = SecretStoreMock::new()
+            .secret(
+                Secret::ConsumingPrivateKey,
+                "0123456789ABCDEF0123456789ABCDEF0123456789ABCDEF0123456789ABCDEF",
+            )
+            .store_params(&store_params_arc);
+        let app = app();
+        let args: Vec<String> = ArgsBuilder::new()
+            .param("--data-directory", home_dir.to_str().unwrap())
+            .param("--wallet-password", "password")
+            .into();
+
+        let multi_config = standard::make_unprivileged_multi_config(&app, &args, &secret_store);
+        standard::remember_secrets_if_asked(&multi_config, &secret_store);
+
+        assert_eq!(
+            value_m!(multi_config, "consuming-private-key", String),
+            None
+        );
+        assert!(store_params_arc.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn database_password_opt_is_the_wallet_password_only_with_encrypt_database() {
+        let without_flag =
+            make_multi_config(ArgsBuilder::new().param("--wallet-password", "password"));
+        let with_flag = make_multi_config(
+            ArgsBuilder::new()
+                .opt("--encrypt-database")
+                .param("--wallet-password", "password"),
+        );
+
+        assert_eq!(standard::database_password_opt(&without_flag), None);
+        assert_eq!(
+            standard::database_password_opt(&with_flag),
+            Some("password".to_string())
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "--encrypt-database needs the wallet password: specify --wallet-password, or use --keychain"
+    )]
+    fn database_password_opt_complains_without_a_wallet_password() {
+        let multi_config = make_multi_config(ArgsBuilder::new().opt("--encrypt-database"));
+
+        standard::database_password_opt(&multi_config);
+    }
+
+    #[test]
+    fn remember_secrets_if_asked_stores_secrets_given_some_other_way() {
+        let store_params_arc = Arc::new(Mutex::new(vec![]));
+        let secret_store = SecretStoreMock::new()
+            .secret(Secret::WalletPassword, "old password")
+            .store_params(&store_params_arc);
+        let app = app();
+        let args = ArgsBuilder::new()
+            .param("--wallet-password", "new password")
+            .opt("--keychain");
+        let multi_config = MultiConfig::new(&app, vec![Box::new(CommandLineVcl::new(args.into()))]);
+
+        standard::remember_secrets_if_asked(&multi_config, &secret_store);
+
+        assert_eq!(
+            *store_params_arc.lock().unwrap(),
+            vec![(Secret::WalletPassword, "new password".to_string())]
+        );
+    }
+
+    #[test]
+    fn detect_dns_servers_uses_and_records_the_system_dns_servers() {
+        init_test_logging();
+        let dns_modifier = DnsModifierMock::new().original_servers_result(Ok(vec![
+            IpAddr::from_str("1.1.1.1").unwrap(),
+            IpAddr::from_str("1.0.0.1").unwrap(),
+        ]));
+        let dns_modifier_factory =
+            DnsModifierFactoryMock::new().make_result(Some(Box::new(dns_modifier)));
+        let set_detected_dns_servers_params_arc = Arc::new(Mutex::new(vec![]));
+        let persistent_config = PersistentConfigurationMock::new()
+            .set_detected_dns_servers_params(&set_detected_dns_servers_params_arc);
+
+        let result = standard::detect_dns_servers(&dns_modifier_factory, &persistent_config);
+
+        assert_eq!(
+            result,
+            vec![
+                SocketAddr::from_str("1.1.1.1:53").unwrap(),
+                SocketAddr::from_str("1.0.0.1:53").unwrap(),
+            ]
+        );
+        assert_eq!(
+            *set_detected_dns_servers_params_arc.lock().unwrap(),
+            vec![vec![
+                IpAddr::from_str("1.1.1.1").unwrap(),
+                IpAddr::from_str("1.0.0.1").unwrap(),
+            ]]
+        );
+        TestLogHandler::new().exists_log_containing(
+            "INFO: Bootstrapper: No --dns-servers given; using 1.1.1.1,1.0.0.1 from this system's DNS settings",
+        );
+    }
+
+    #[test]
+    fn detect_dns_servers_finds_nothing_if_the_system_dns_settings_are_unreadable() {
+        init_test_logging();
+        let dns_modifier = DnsModifierMock::new()
+            .original_servers_result(Err("/etc/resolv.conf was not found".to_string()));
+        let dns_modifier_factory =
+            DnsModifierFactoryMock::new().make_result(Some(Box::new(dns_modifier)));
+        let set_detected_dns_servers_params_arc = Arc::new(Mutex::new(vec![]));
+        let persistent_config = PersistentConfigurationMock::new()
+            .set_detected_dns_servers_params(&set_detected_dns_servers_params_arc);
+
+        let result = standard::detect_dns_servers(&dns_modifier_factory, &persistent_config);
+
+        assert_eq!(result, vec![]);
+        assert!(set_detected_dns_servers_params_arc
+            .lock()
+            .unwrap()
+            .is_empty());
+        TestLogHandler::new().exists_log_containing(
+            "WARN: Bootstrapper: No --dns-servers given, and this system's DNS servers couldn't be found: /etc/resolv.conf was not found",
+        );
+    }
+
+    #[test]
+    fn detect_dns_servers_finds_nothing_if_the_system_dns_settings_are_unrecognizable() {
+        init_test_logging();
+        let dns_modifier_factory = DnsModifierFactoryMock::new().make_result(None);
+        let persistent_config = PersistentConfigurationMock::new();
+
+        let result = standard::detect_dns_servers(&dns_modifier_factory, &persistent_config);
+
+        assert_eq!(result, vec![]);
+        TestLogHandler::new().exists_log_containing(
+            "WARN: Bootstrapper: No --dns-servers given, and this system's DNS servers couldn't be found: this system's DNS settings are of a kind Node doesn't recognize",
+        );
+    }
+
+    #[test]
+    fn can_read_dns_servers_and_consuming_private_key_from_config_file() {
+        let home_dir = ensure_node_home_directory_exists(
+            "node_configurator",
+            "can_read_wallet_parameters_from_config_file",
+        );
+        let persistent_config = PersistentConfigurationReal::new(Box::new(ConfigDaoReal::new(
+            DbInitializerReal::new()
+                .initialize(&home_dir.clone(), DEFAULT_CHAIN_ID)
+                .unwrap(),
+        )));
+        let consuming_private_key =
+            "89ABCDEF89ABCDEF89ABCDEF89ABCDEF89ABCDEF89ABCDEF89ABCDEF89ABCDEF";
+        let config_file_path = home_dir.join("config.toml");
+        {
+            let mut config_file = File::create(&config_file_path).unwrap();
+            writeln!(
+                config_file,
+                "dns-servers = \"1.2.3.4\"\nconsuming-private-key = \"{}\"",
+                consuming_private_key
+            )
+            .unwrap();
+        }
+        let args = ArgsBuilder::new()
+            .param("--data-directory", home_dir.to_str().unwrap())
+            .param("--ip", "1.2.3.4");
         let mut bootstrapper_config = BootstrapperConfig::new();
         let multi_config = MultiConfig::new(
             &app(),
@@ -1251,11 +2896,33 @@ mod tests {
                 "QmlsbA:1.2.3.4:1234;2345,VGVk:2.3.4.5:3456;4567",
             )
             .param("--ip", "34.56.78.90")
+            .opt("--lan-discovery")
+            .opt("--manage-firewall")
+            .opt("--kill-switch")
+            .opt("--mail-clients")
+            .param("--neighbor-ingress-limit", "500")
+            .param("--neighbor-egress-limit", "250")
+            .opt("--exit-block-smtp")
+            .opt("--exit-block-mail")
+            .param("--exit-fan-out-limit", "50")
+            .opt("--exit-no-scan-detection")
             .param("--clandestine-port", "1234")
             .param("--ui-port", "5335")
+            .param("--rest-port", "5336")
+            .param("--telemetry-url", "http://telemetry.example.com/report")
             .param("--data-directory", home_dir.to_str().unwrap())
             .param("--blockchain-service-url", "http://127.0.0.1:8545")
+            .param("--blockchain-service-ws-url", "ws://127.0.0.1:8546")
             .param("--log-level", "trace")
+            .param("--log-rotate-size", "20")
+            .param("--log-rotate-hours", "6")
+            .param("--log-keep-files", "7")
+            .param("--log-keep-days", "30")
+            .opt("--log-no-gzip")
+            .param("--buffer-memory-limit", "256")
+            .param("--buffer-spill-limit", "0")
+            .param("--buffer-spill-total-limit", "1024")
+            .param("--memory-thresholds", "512,768,1024")
             .param("--fake-public-key", "AQIDBA")
             .param("--wallet-password", "secret-wallet-password")
             .param(
@@ -1306,6 +2973,58 @@ mod tests {
             IpAddr::V4(Ipv4Addr::new(34, 56, 78, 90)),
         );
         assert_eq!(config.ui_gateway_config.ui_port, 5335);
+        assert_eq!(config.ui_gateway_config.rest_port_opt, Some(5336));
+        assert_eq!(
+            config.ui_gateway_config.telemetry_url_opt,
+            Some("http://telemetry.example.com/report".to_string())
+        );
+        assert!(config.lan_discovery);
+        assert!(config.manage_firewall);
+        assert!(config.kill_switch);
+        assert!(config.mail_clients);
+        assert_eq!(
+            config.neighbor_rate_limits,
+            NeighborRateLimits {
+                ingress_bytes_per_sec_opt: Some(500_000),
+                egress_bytes_per_sec_opt: Some(250_000),
+            }
+        );
+        assert_eq!(
+            config.exit_protections,
+            ExitProtections {
+                block_smtp: true,
+                block_mail: true,
+                fan_out_limit: 50,
+                scan_detection: false,
+            }
+        );
+        assert_eq!(
+            config.log_rotation,
+            LogRotation {
+                max_size: 20_000_000,
+                max_age_opt: Some(Duration::from_secs(6 * 60 * 60)),
+                keep_files: 7,
+                keep_age_opt: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+                gzip: false,
+            }
+        );
+        assert_eq!(
+            config.spill_config,
+            SpillConfig {
+                memory_limit: 256 * 1024,
+                spill_limit: 0,
+                budget: SpillBudget::new(1024 * 1024 * 1024),
+                directory_opt: Some(home_dir.join(SPILL_DIRECTORY)),
+            }
+        );
+        assert_eq!(
+            config.memory_thresholds_opt,
+            Some(MemoryThresholds {
+                refuse_consuming_streams: 512 * 1024 * 1024,
+                tighten_buffers: 768 * 1024 * 1024,
+                refuse_clandestine_connections: 1024 * 1024 * 1024,
+            })
+        );
         let expected_port_list: Vec<u16> = vec![];
         assert_eq!(
             config
@@ -1317,8 +3036,12 @@ mod tests {
             expected_port_list,
         );
         assert_eq!(
-            config.blockchain_bridge_config.blockchain_service_url,
-            Some("http://127.0.0.1:8545".to_string()),
+            config.blockchain_bridge_config.blockchain_service_urls,
+            vec!["http://127.0.0.1:8545".to_string()],
+        );
+        assert_eq!(
+            config.blockchain_bridge_config.blockchain_service_ws_url,
+            Some("ws://127.0.0.1:8546".to_string()),
         );
         assert_eq!(config.data_directory, home_dir);
         assert_eq!(
@@ -1368,6 +3091,10 @@ mod tests {
                 "0x0123456789012345678901234567890123456789",
             )
             .param("--consuming-private-key", consuming_private_key_text)
+            .param("--fee-strategy", "Fast")
+            .param("--gas-price-cap", "150")
+            .param("--fee-budget", "50000000")
+            .param("--fee-budget-period", "Weekly")
             .param("--real-user", "999:999:/home/booga");
         let mut config = BootstrapperConfig::new();
         let vcls: Vec<Box<dyn VirtualCommandLine>> =
@@ -1400,6 +3127,18 @@ mod tests {
                 Bip32ECKeyPair::from_raw_secret(consuming_private_key.as_slice()).unwrap()
             )),
         );
+        assert_eq!(
+            config.blockchain_bridge_config.fee_strategy,
+            FeeStrategy::Fast
+        );
+        assert_eq!(config.blockchain_bridge_config.gas_price_cap, Some(150));
+        assert_eq!(
+            config.blockchain_bridge_config.fee_budget,
+            Some(FeeBudget {
+                gwei: 50_000_000,
+                period: FeeBudgetPeriod::Weekly,
+            })
+        );
     }
 
     #[test]
@@ -1440,10 +3179,48 @@ mod tests {
             IpAddr::from_str("1.2.3.4").unwrap()
         );
         assert_eq!(config.ui_gateway_config.ui_port, 5333);
+        assert_eq!(config.ui_gateway_config.rest_port_opt, None);
+        assert_eq!(config.ui_gateway_config.telemetry_url_opt, None);
+        assert!(!config.lan_discovery);
+        assert!(!config.manage_firewall);
+        assert!(!config.kill_switch);
+        assert_eq!(config.port_forwards, vec![]);
+        assert!(!config.mail_clients);
+        assert_eq!(config.neighbor_rate_limits, NeighborRateLimits::default());
+        assert_eq!(config.exit_protections, ExitProtections::default());
+        assert_eq!(config.log_rotation, LogRotation::default());
+        assert_eq!(
+            config.spill_config,
+            SpillConfig {
+                directory_opt: Some(config.data_directory.join(SPILL_DIRECTORY)),
+                ..SpillConfig::default()
+            }
+        );
+        assert_eq!(config.memory_thresholds_opt, None);
         assert!(config.cryptde_null_opt.is_none());
         assert_eq!(config.real_user, RealUser::null().populate());
     }
 
+    #[test]
+    #[should_panic(expected = "--rest-port and --ui-port must be different")]
+    fn privileged_parse_args_rejects_rest_port_equal_to_ui_port() {
+        let args = ArgsBuilder::new()
+            .param("--dns-servers", "12.34.56.78")
+            .param("--ip", "1.2.3.4")
+            .param("--ui-port", "5335")
+            .param("--rest-port", "5335");
+        let mut config = BootstrapperConfig::new();
+        let vcls: Vec<Box<dyn VirtualCommandLine>> =
+            vec![Box::new(CommandLineVcl::new(args.into()))];
+        let multi_config = MultiConfig::new(&app(), vcls);
+
+        standard::privileged_parse_args(
+            &multi_config,
+            &mut config,
+            &mut FakeStreamHolder::new().streams(),
+        );
+    }
+
     #[test]
     #[cfg(not(target_os = "windows"))]
     fn privileged_parse_args_with_real_user_defaults_data_directory_properly() {
@@ -1476,6 +3253,42 @@ mod tests {
         );
     }
 
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn privileged_parse_args_with_run_as_drops_to_that_account() {
+        let args = ArgsBuilder::new()
+            .param("--dns-servers", "12.34.56.78,23.45.67.89")
+            .param("--ip", "1.2.3.4")
+            .param("--run-as", "0:1");
+        let mut config = BootstrapperConfig::new();
+        let vcls: Vec<Box<dyn VirtualCommandLine>> =
+            vec![Box::new(CommandLineVcl::new(args.into()))];
+        let multi_config = MultiConfig::new(&app(), vcls);
+
+        standard::privileged_parse_args(
+            &multi_config,
+            &mut config,
+            &mut FakeStreamHolder::new().streams(),
+        );
+
+        assert_eq!(config.real_user.uid, Some(0));
+        assert_eq!(config.real_user.gid, Some(1));
+        assert!(config.real_user.home_dir.is_some());
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn run_as_and_real_user_are_refused_together() {
+        let args: Vec<String> = ArgsBuilder::new()
+            .param("--run-as", "root")
+            .param("--real-user", "123:456:/home/booga")
+            .into();
+
+        let result = app().get_matches_from_safe(args);
+
+        assert!(result.is_err());
+    }
+
     #[test]
     fn unprivileged_parse_args_creates_configuration_with_defaults() {
         let args = ArgsBuilder::new().param("--dns-servers", "12.34.56.78,23.45.67.89");
@@ -1498,6 +3311,94 @@ mod tests {
         assert_eq!(None, config.clandestine_port_opt);
         assert_eq!(config.earning_wallet, DEFAULT_EARNING_WALLET.clone(),);
         assert_eq!(config.consuming_wallet, None,);
+        assert_eq!(
+            config.blockchain_bridge_config.fee_strategy,
+            FeeStrategy::Normal
+        );
+        assert_eq!(config.blockchain_bridge_config.gas_price_cap, None);
+        assert_eq!(config.blockchain_bridge_config.fee_budget, None);
+    }
+
+    #[test]
+    fn unprivileged_parse_args_renews_fee_budgets_monthly_by_default() {
+        let args = ArgsBuilder::new()
+            .param("--dns-servers", "12.34.56.78,23.45.67.89")
+            .param("--fee-budget", "1000000");
+        let mut config = BootstrapperConfig::new();
+        let vcls: Vec<Box<dyn VirtualCommandLine>> =
+            vec![Box::new(CommandLineVcl::new(args.into()))];
+        let multi_config = MultiConfig::new(&app(), vcls);
+
+        standard::unprivileged_parse_args(
+            &multi_config,
+            &mut config,
+            &mut FakeStreamHolder::new().streams(),
+            &make_default_persistent_configuration(),
+        );
+
+        assert_eq!(
+            config.blockchain_bridge_config.fee_budget,
+            Some(FeeBudget {
+                gwei: 1_000_000,
+                period: FeeBudgetPeriod::Monthly,
+            })
+        );
+    }
+
+    #[test]
+    fn unprivileged_parse_args_takes_split_dns_domains_from_the_command_line_before_the_database() {
+        let persistent_config = make_default_persistent_configuration()
+            .split_dns_domains_result(vec!["old.example.com".to_string()]);
+        let mut config = BootstrapperConfig::new();
+
+        standard::unprivileged_parse_args(
+            &make_multi_config(
+                ArgsBuilder::new().param("--split-dns-domains", "Corp.Example.com.,lan"),
+            ),
+            &mut config,
+            &mut FakeStreamHolder::new().streams(),
+            &persistent_config,
+        );
+
+        assert_eq!(
+            config.split_dns_domains,
+            vec!["corp.example.com".to_string(), "lan".to_string()]
+        );
+    }
+
+    #[test]
+    fn unprivileged_parse_args_takes_split_dns_domains_from_the_database_if_not_given() {
+        let persistent_config = make_default_persistent_configuration()
+            .split_dns_domains_result(vec!["corp.example.com".to_string()]);
+        let mut config = BootstrapperConfig::new();
+
+        standard::unprivileged_parse_args(
+            &make_multi_config(ArgsBuilder::new()),
+            &mut config,
+            &mut FakeStreamHolder::new().streams(),
+            &persistent_config,
+        );
+
+        assert_eq!(
+            config.split_dns_domains,
+            vec!["corp.example.com".to_string()]
+        );
+    }
+
+    #[test]
+    fn unprivileged_parse_args_forgets_split_dns_domains_when_given_none() {
+        let persistent_config = make_default_persistent_configuration()
+            .split_dns_domains_result(vec!["corp.example.com".to_string()]);
+        let mut config = BootstrapperConfig::new();
+
+        standard::unprivileged_parse_args(
+            &make_multi_config(ArgsBuilder::new().param("--split-dns-domains", "")),
+            &mut config,
+            &mut FakeStreamHolder::new().streams(),
+            &persistent_config,
+        );
+
+        assert!(config.split_dns_domains.is_empty());
     }
 
     fn make_multi_config<'a>(args: ArgsBuilder) -> MultiConfig<'a> {
@@ -1672,6 +3573,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn earning_wallet_address_without_keys_is_remembered_for_watch_only_operation() {
+        let multi_config = make_multi_config(ArgsBuilder::new().param(
+            "--earning-wallet",
+            "0xb00fa567890123456789012345678901234B00FA",
+        ));
+        let set_earning_wallet_address_params_arc = Arc::new(Mutex::new(vec![]));
+        let persistent_config = make_persistent_config(None, None, None, None, None)
+            .set_earning_wallet_address_params(&set_earning_wallet_address_params_arc);
+        let mut config = BootstrapperConfig::new();
+
+        standard::get_wallets(
+            &mut FakeStreamHolder::new().streams(),
+            &multi_config,
+            &persistent_config,
+            &mut config,
+        );
+
+        assert_eq!(config.consuming_wallet, None);
+        assert_eq!(
+            config.earning_wallet,
+            Wallet::new("0xb00fa567890123456789012345678901234b00fa")
+        );
+        let set_earning_wallet_address_params =
+            set_earning_wallet_address_params_arc.lock().unwrap();
+        assert_eq!(
+            *set_earning_wallet_address_params,
+            vec!["0xb00fa567890123456789012345678901234B00FA".to_string()]
+        );
+    }
+
     #[test]
     #[should_panic(
         expected = "Cannot use --consuming-private-key and earning wallet address when database contains mnemonic seed"
@@ -2103,6 +4035,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn privileged_configuration_defaults_xdai_to_its_public_endpoint() {
+        let subject = NodeConfiguratorStandardPrivileged {};
+        let args = ArgsBuilder::new()
+            .param("--dns-servers", "1.2.3.4")
+            .param("--ip", "1.2.3.4")
+            .param("--chain", "xdai");
+
+        let config = subject.configure(&args.into(), &mut FakeStreamHolder::new().streams());
+
+        assert_eq!(config.blockchain_bridge_config.chain_id, 100);
+        assert_eq!(
+            config.blockchain_bridge_config.blockchain_service_urls,
+            vec![XDAI_BLOCKCHAIN_SERVICE_URL.to_string()]
+        );
+    }
+
+    #[test]
+    fn privileged_configuration_prefers_specified_blockchain_service_urls_to_the_chain_default() {
+        let subject = NodeConfiguratorStandardPrivileged {};
+        let args = ArgsBuilder::new()
+            .param("--dns-servers", "1.2.3.4")
+            .param("--ip", "1.2.3.4")
+            .param("--chain", "xdai")
+            .param("--blockchain-service-url", "http://127.0.0.1:8545");
+
+        let config = subject.configure(&args.into(), &mut FakeStreamHolder::new().streams());
+
+        assert_eq!(
+            config.blockchain_bridge_config.blockchain_service_urls,
+            vec!["http://127.0.0.1:8545".to_string()]
+        );
+    }
+
     #[test]
     fn privileged_configuration_defaults_network_chain_selection_to_mainnet() {
         let subject = NodeConfiguratorStandardPrivileged {};
@@ -2172,6 +4138,126 @@ mod tests {
         assert_eq!(config.blockchain_bridge_config.gas_price, None);
     }
 
+    #[test]
+    fn unprivileged_parse_args_takes_payment_parameters_from_command_line() {
+        let multi_config = make_multi_config(
+            ArgsBuilder::new()
+                .param("--payable-scan-interval", "120")
+                .param("--permanent-debt-allowed", "2000000"),
+        );
+        let persistent_config = make_default_persistent_configuration()
+            .payment_parameter_result(PaymentParameter::PayableScanInterval, 3600)
+            .payment_parameter_result(PaymentParameter::PaymentSuggestedAfter, 7200);
+        let mut config = BootstrapperConfig::new();
+
+        standard::unprivileged_parse_args(
+            &multi_config,
+            &mut config,
+            &mut FakeStreamHolder::new().streams(),
+            &persistent_config,
+        );
+
+        assert_eq!(
+            config.accountant_config.payable_scan_interval,
+            Duration::from_secs(120)
+        );
+        assert_eq!(
+            config
+                .accountant_config
+                .payment_curves
+                .permanent_debt_allowed_gwub,
+            2_000_000
+        );
+        assert_eq!(
+            config
+                .accountant_config
+                .payment_parameter(PaymentParameter::PaymentSuggestedAfter),
+            7200
+        );
+        assert_eq!(
+            config
+                .accountant_config
+                .payment_parameter(PaymentParameter::PaymentReceivedScanInterval),
+            PaymentParameter::PaymentReceivedScanInterval.default_value()
+        );
+    }
+
+    #[test]
+    fn unprivileged_parse_args_takes_exit_earning_wallet_from_command_line() {
+        let multi_config = make_multi_config(ArgsBuilder::new().param(
+            "--exit-earning-wallet",
+            "0x0123456789012345678901234567890123456789",
+        ));
+        let persistent_config = make_default_persistent_configuration()
+            .exit_earning_wallet_from_address_result(Some(make_wallet("stored exit")));
+        let mut config = BootstrapperConfig::new();
+
+        standard::unprivileged_parse_args(
+            &multi_config,
+            &mut config,
+            &mut FakeStreamHolder::new().streams(),
+            &persistent_config,
+        );
+
+        assert_eq!(
+            config.exit_earning_wallet_opt,
+            Some(Wallet::from_str("0x0123456789012345678901234567890123456789").unwrap())
+        );
+    }
+
+    #[test]
+    fn unprivileged_parse_args_takes_exit_earning_wallet_from_database_if_not_on_command_line() {
+        let multi_config = make_multi_config(ArgsBuilder::new());
+        let persistent_config = make_default_persistent_configuration()
+            .exit_earning_wallet_from_address_result(Some(make_wallet("stored exit")));
+        let mut config = BootstrapperConfig::new();
+
+        standard::unprivileged_parse_args(
+            &multi_config,
+            &mut config,
+            &mut FakeStreamHolder::new().streams(),
+            &persistent_config,
+        );
+
+        assert_eq!(
+            config.exit_earning_wallet_opt,
+            Some(make_wallet("stored exit"))
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Can't continue; balance-to-decrease-from (1000) must be greater than permanent-debt-allowed (2000)"
+    )]
+    fn unprivileged_parse_args_rejects_inconsistent_payment_curves() {
+        let multi_config = make_multi_config(
+            ArgsBuilder::new()
+                .param("--balance-to-decrease-from", "1000")
+                .param("--permanent-debt-allowed", "2000"),
+        );
+        let mut config = BootstrapperConfig::new();
+
+        standard::unprivileged_parse_args(
+            &multi_config,
+            &mut config,
+            &mut FakeStreamHolder::new().streams(),
+            &make_default_persistent_configuration(),
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "error: Invalid value for \\'--payable-scan-interval <SECONDS>\\': 30"
+    )]
+    fn privileged_configuration_rejects_payment_parameter_out_of_range() {
+        let subject = NodeConfiguratorStandardPrivileged {};
+        let args = ArgsBuilder::new()
+            .param("--dns-servers", "1.2.3.4")
+            .param("--payable-scan-interval", "30");
+
+        subject.configure(&args.into(), &mut FakeStreamHolder::new().streams());
+    }
+
     #[test]
     #[should_panic(expected = "error: Invalid value for \\'--gas-price <GAS-PRICE>\\': unleaded")]
     fn privileged_configuration_rejects_invalid_gas_price() {
@@ -2183,6 +4269,141 @@ mod tests {
         subject.configure(&args.into(), &mut FakeStreamHolder::new().streams());
     }
 
+    #[test]
+    fn privileged_configuration_accepts_consuming_profiles() {
+        let subject = NodeConfiguratorStandardPrivileged {};
+        let args = ArgsBuilder::new()
+            .param("--dns-servers", "1.2.3.4")
+            .param("--neighborhood-mode", "zero-hop")
+            .param(
+                "--consuming-profiles",
+                "work:10000-19999:5000000,personal:20000-29999",
+            );
+
+        let config = subject.configure(&args.into(), &mut FakeStreamHolder::new().streams());
+
+        assert_eq!(
+            config.consuming_profiles,
+            vec![
+                ConsumingProfile {
+                    name: "work".to_string(),
+                    selector: ProfileSelector::OriginatingPorts(10000..=19999),
+                    budget_opt: Some(5_000_000),
+                    consuming_wallet_opt: None,
+                },
+                ConsumingProfile {
+                    name: "personal".to_string(),
+                    selector: ProfileSelector::OriginatingPorts(20000..=29999),
+                    budget_opt: None,
+                    consuming_wallet_opt: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn privileged_configuration_listens_on_the_ports_of_each_pac_profile() {
+        let _guard = EnvironmentGuard::new();
+        let home_dir = ensure_node_home_directory_exists(
+            "node_configurator",
+            "privileged_configuration_listens_on_the_ports_of_each_pac_profile",
+        );
+        let subject = NodeConfiguratorStandardPrivileged {};
+
+        let configuration = subject.configure(
+            &ArgsBuilder::new()
+                .param("--data-directory", home_dir.to_str().unwrap())
+                .param("--dns-servers", "1.2.3.4")
+                .param("--ip", "1.2.3.4")
+                .param("--consuming-profiles", "work:10000-19999,personal:pac=8090")
+                .into(),
+            &mut FakeStreamHolder::new().streams(),
+        );
+
+        assert_eq!(
+            configuration.consuming_profiles[1].selector,
+            ProfileSelector::Pac(8090)
+        );
+        assert!(!configuration.port_configurations.contains_key(&10000));
+        [(8090, 1), (8091, 2)]
+            .iter()
+            .for_each(|(port, discriminator_count)| {
+                let port_configuration = &configuration.port_configurations[port];
+                assert!(!port_configuration.is_clandestine);
+                assert_eq!(port_configuration.loopback_ip, Ipv4Addr::LOCALHOST);
+                assert_eq!(
+                    port_configuration.discriminator_factories.len(),
+                    *discriminator_count
+                );
+            });
+    }
+
+    #[test]
+    fn privileged_configuration_accepts_obfuscation_parameters() {
+        let subject = NodeConfiguratorStandardPrivileged {};
+        let args = ArgsBuilder::new()
+            .param("--dns-servers", "1.2.3.4")
+            .param("--neighborhood-mode", "zero-hop")
+            .param("--obfuscation-jitter", "25")
+            .param("--obfuscation-padding", "512");
+
+        let config = subject.configure(&args.into(), &mut FakeStreamHolder::new().streams());
+
+        assert_eq!(
+            config.obfuscation_config,
+            ObfuscationConfig {
+                max_jitter: Duration::from_millis(25),
+                max_padding: 512,
+            }
+        );
+    }
+
+    #[test]
+    fn privileged_configuration_defaults_to_no_obfuscation() {
+        let subject = NodeConfiguratorStandardPrivileged {};
+        let args = ArgsBuilder::new()
+            .param("--dns-servers", "1.2.3.4")
+            .param("--neighborhood-mode", "zero-hop");
+
+        let config = subject.configure(&args.into(), &mut FakeStreamHolder::new().streams());
+
+        assert_eq!(config.obfuscation_config, ObfuscationConfig::default());
+    }
+
+    #[test]
+    fn privileged_configuration_defaults_to_no_consuming_profiles() {
+        let subject = NodeConfiguratorStandardPrivileged {};
+        let args = ArgsBuilder::new()
+            .param("--dns-servers", "1.2.3.4")
+            .param("--neighborhood-mode", "zero-hop");
+
+        let config = subject.configure(&args.into(), &mut FakeStreamHolder::new().streams());
+
+        assert_eq!(config.consuming_profiles, vec![]);
+    }
+
+    #[test]
+    fn privileged_configuration_accepts_several_blockchain_service_urls_in_order() {
+        let subject = NodeConfiguratorStandardPrivileged {};
+        let args = ArgsBuilder::new()
+            .param("--dns-servers", "1.2.3.4")
+            .param("--neighborhood-mode", "zero-hop")
+            .param(
+                "--blockchain-service-url",
+                "http://127.0.0.1:8545,https://ropsten.infura.io/v3/booga",
+            );
+
+        let config = subject.configure(&args.into(), &mut FakeStreamHolder::new().streams());
+
+        assert_eq!(
+            config.blockchain_bridge_config.blockchain_service_urls,
+            vec![
+                "http://127.0.0.1:8545".to_string(),
+                "https://ropsten.infura.io/v3/booga".to_string()
+            ]
+        );
+    }
+
     #[test]
     fn configure_database_with_data_specified_on_command_line_but_not_in_database_without_seed() {
         let mut config = BootstrapperConfig::new();
@@ -2317,4 +4538,92 @@ mod tests {
             vec![DEFAULT_EARNING_WALLET.to_string()]
         )
     }
+
+    #[test]
+    fn configure_database_stores_only_changed_payment_parameters() {
+        let mut config = BootstrapperConfig::new();
+        config.clandestine_port_opt = None;
+        config.consuming_wallet = None;
+        config.earning_wallet = DEFAULT_EARNING_WALLET.clone();
+        config
+            .accountant_config
+            .set_payment_parameter(PaymentParameter::PaymentGraceBeforeBan, 1800);
+        config
+            .accountant_config
+            .set_payment_parameter(PaymentParameter::UnbanWhenBalanceBelow, 500);
+        let set_payment_parameter_params_arc = Arc::new(Mutex::new(vec![]));
+        let persistent_config = PersistentConfigurationMock::new()
+            .earning_wallet_address_result(None)
+            .consuming_wallet_public_key_result(None)
+            .consuming_wallet_derivation_path_result(None)
+            .payment_parameter_result(PaymentParameter::UnbanWhenBalanceBelow, 500)
+            .set_payment_parameter_params(&set_payment_parameter_params_arc);
+
+        standard::configure_database(&config, &persistent_config);
+
+        let set_payment_parameter_params = set_payment_parameter_params_arc.lock().unwrap();
+        assert_eq!(
+            *set_payment_parameter_params,
+            vec![(PaymentParameter::PaymentGraceBeforeBan, 1800)]
+        );
+    }
+
+    #[test]
+    fn configure_database_stores_split_dns_domains_only_if_changed() {
+        let mut config = BootstrapperConfig::new();
+        config.split_dns_domains = vec!["corp.example.com".to_string()];
+        let set_split_dns_domains_params_arc = Arc::new(Mutex::new(vec![]));
+        let persistent_config_with = |domains: Vec<String>| {
+            PersistentConfigurationMock::new()
+                .earning_wallet_address_result(None)
+                .consuming_wallet_public_key_result(None)
+                .consuming_wallet_derivation_path_result(None)
+                .split_dns_domains_result(domains)
+                .set_split_dns_domains_params(&set_split_dns_domains_params_arc)
+        };
+
+        standard::configure_database(&config, &persistent_config_with(vec![]));
+        standard::configure_database(
+            &config,
+            &persistent_config_with(vec!["corp.example.com".to_string()]),
+        );
+
+        let set_split_dns_domains_params = set_split_dns_domains_params_arc.lock().unwrap();
+        assert_eq!(
+            *set_split_dns_domains_params,
+            vec![vec!["corp.example.com".to_string()]]
+        );
+    }
+
+    #[test]
+    fn configure_database_stores_exit_earning_wallet_only_if_changed() {
+        let mut config = BootstrapperConfig::new();
+        config.clandestine_port_opt = None;
+        config.consuming_wallet = None;
+        config.earning_wallet = DEFAULT_EARNING_WALLET.clone();
+        config.exit_earning_wallet_opt = Some(make_wallet("new exit"));
+        let set_exit_earning_wallet_address_params_arc = Arc::new(Mutex::new(vec![]));
+        let persistent_config = PersistentConfigurationMock::new()
+            .earning_wallet_address_result(None)
+            .consuming_wallet_public_key_result(None)
+            .consuming_wallet_derivation_path_result(None)
+            .exit_earning_wallet_from_address_result(Some(make_wallet("old exit")))
+            .set_exit_earning_wallet_address_params(&set_exit_earning_wallet_address_params_arc);
+
+        standard::configure_database(&config, &persistent_config);
+        config.exit_earning_wallet_opt = Some(make_wallet("old exit"));
+        let persistent_config = persistent_config
+            .earning_wallet_address_result(None)
+            .consuming_wallet_public_key_result(None)
+            .consuming_wallet_derivation_path_result(None)
+            .exit_earning_wallet_from_address_result(Some(make_wallet("old exit")));
+        standard::configure_database(&config, &persistent_config);
+
+        let set_exit_earning_wallet_address_params =
+            set_exit_earning_wallet_address_params_arc.lock().unwrap();
+        assert_eq!(
+            *set_exit_earning_wallet_address_params,
+            vec![make_wallet("new exit").to_string()]
+        );
+    }
 }
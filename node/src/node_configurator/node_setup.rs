@@ -0,0 +1,99 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use crate::node_configurator::node_configurator_standard::{
+    NodeConfiguratorStandardPrivileged, NodeConfiguratorStandardUnprivileged,
+};
+use crate::node_configurator::NodeConfigurator;
+use crate::privilege_drop::{PrivilegeDropper, PrivilegeDropperReal};
+use crate::sub_lib::main_tools::StdStreams;
+use std::fs;
+
+// Does to the data directory everything the Node would do before it starts (creates the database,
+// stores the wallets and settings it's given) and then stops, without opening any ports.
+pub fn setup(args: &Vec<String>, streams: &mut StdStreams<'_>) -> i32 {
+    setup_with(args, streams, &PrivilegeDropperReal::new())
+}
+
+fn setup_with(
+    args: &Vec<String>,
+    streams: &mut StdStreams<'_>,
+    privilege_dropper: &dyn PrivilegeDropper,
+) -> i32 {
+    let privileged_config = NodeConfiguratorStandardPrivileged {}.configure(args, streams);
+    let data_directory = &privileged_config.data_directory;
+    if let Err(e) = fs::create_dir_all(data_directory) {
+        writeln!(
+            streams.stderr,
+            "Couldn't create data directory {}: {}",
+            data_directory.display(),
+            e
+        )
+        .expect("Couldn't write to stderr");
+        return 1;
+    }
+    let real_user = privileged_config.real_user.populate();
+    privilege_dropper.chown(data_directory, &real_user);
+    privilege_dropper.drop_privileges(&real_user);
+    NodeConfiguratorStandardUnprivileged::new(&privileged_config).configure(args, streams);
+    writeln!(
+        streams.stdout,
+        "Node is set up in {}",
+        data_directory.display()
+    )
+    .expect("Couldn't write to stdout");
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::db_initializer::{DbInitializer, DbInitializerReal};
+    use crate::persistent_configuration::{PersistentConfiguration, PersistentConfigurationReal};
+    use crate::server_initializer::test_utils::PrivilegeDropperMock;
+    use crate::test_utils::environment_guard::EnvironmentGuard;
+    use crate::test_utils::{
+        ensure_node_home_directory_exists, ArgsBuilder, FakeStreamHolder, DEFAULT_CHAIN_ID,
+        TEST_DEFAULT_CHAIN_NAME,
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn setup_stores_the_configuration_and_stops() {
+        let _guard = EnvironmentGuard::new();
+        let home_dir = ensure_node_home_directory_exists(
+            "node_setup",
+            "setup_stores_the_configuration_and_stops",
+        );
+        let data_directory = home_dir.join("data");
+        let chown_params_arc = Arc::new(Mutex::new(vec![]));
+        let drop_privileges_params_arc = Arc::new(Mutex::new(vec![]));
+        let privilege_dropper = PrivilegeDropperMock::new()
+            .chown_params(&chown_params_arc)
+            .drop_privileges_params(&drop_privileges_params_arc);
+        let args = ArgsBuilder::new()
+            .param("--chain", TEST_DEFAULT_CHAIN_NAME)
+            .param("--data-directory", data_directory.to_str().unwrap())
+            .param("--dns-servers", "1.2.3.4")
+            .param("--gas-price", "57")
+            .param("--ip", "1.2.3.4")
+            .param("--real-user", "123:456:/home/booga");
+        let mut holder = FakeStreamHolder::new();
+
+        let result = setup_with(&args.into(), &mut holder.streams(), &privilege_dropper);
+
+        assert_eq!(result, 0);
+        assert_eq!(
+            holder.stdout.get_string(),
+            format!("Node is set up in {}\n", data_directory.display())
+        );
+        let chown_params = chown_params_arc.lock().unwrap();
+        assert_eq!(chown_params.len(), 1);
+        assert_eq!(chown_params[0].0, data_directory);
+        assert_eq!(drop_privileges_params_arc.lock().unwrap().len(), 1);
+        let conn = DbInitializerReal::new()
+            .initialize(&data_directory, DEFAULT_CHAIN_ID)
+            .unwrap();
+        let persistent_config = PersistentConfigurationReal::from(conn);
+        assert_eq!(persistent_config.gas_price(), 57);
+    }
+}
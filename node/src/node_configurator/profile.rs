@@ -0,0 +1,131 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use crate::multi_config::{CommandLineVcl, NameValueVclArg, VclArg};
+use crate::sub_lib::ui_gateway::DEFAULT_UI_PORT;
+use clap::App;
+use lazy_static::lazy_static;
+use std::collections::HashSet;
+
+// A bundle of defaults for one network. Anything given on the command line, in the environment
+// or in the config file still overrides what the profile says.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Profile {
+    pub name: &'static str,
+    // The contract address and everything else that depends on the network come from the chain
+    pub chain: &'static str,
+    pub neighbors: &'static [&'static str],
+    // Each profile has its own UI port, so tools like --list-neighbors find the right Node
+    pub ui_port: u16,
+}
+
+pub const PROFILES: [Profile; 3] = [
+    Profile {
+        name: "mainnet",
+        chain: "mainnet",
+        neighbors: &[],
+        ui_port: DEFAULT_UI_PORT,
+    },
+    Profile {
+        name: "testnet",
+        chain: "ropsten",
+        neighbors: &[],
+        ui_port: DEFAULT_UI_PORT + 1,
+    },
+    Profile {
+        name: "dev",
+        chain: "dev",
+        neighbors: &[],
+        ui_port: DEFAULT_UI_PORT + 2,
+    },
+];
+
+lazy_static! {
+    pub static ref PROFILE_NAMES: Vec<&'static str> =
+        PROFILES.iter().map(|profile| profile.name).collect();
+}
+
+pub fn profile_from_name(name: &str) -> Option<&'static Profile> {
+    let name = name.to_lowercase();
+    PROFILES.iter().find(|profile| profile.name == name)
+}
+
+// Only the parameters the schema knows about are supplied; a tool that has no --neighbors, for
+// example, just doesn't get them.
+pub fn profile_vcl(profile: &Profile, schema: &App) -> CommandLineVcl {
+    let opt_names: HashSet<String> = schema
+        .p
+        .opts
+        .iter()
+        .map(|opt| opt.b.name.to_string())
+        .collect();
+    let mut candidates = vec![
+        ("chain", profile.chain.to_string()),
+        ("ui-port", profile.ui_port.to_string()),
+    ];
+    if !profile.neighbors.is_empty() {
+        candidates.push(("neighbors", profile.neighbors.join(",")));
+    }
+    let vcl_args: Vec<Box<dyn VclArg>> = candidates
+        .into_iter()
+        .filter(|(name, _)| opt_names.contains(*name))
+        .map(|(name, value)| {
+            Box::new(NameValueVclArg::new(&format!("--{}", name), &value)) as Box<dyn VclArg>
+        })
+        .collect();
+    CommandLineVcl::from(vcl_args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::blockchain_interface::CHAIN_NAMES;
+    use crate::multi_config::VirtualCommandLine;
+    use clap::Arg;
+
+    #[test]
+    fn every_profile_names_a_real_chain_and_its_own_ui_port() {
+        let ui_ports: HashSet<u16> = PROFILES.iter().map(|profile| profile.ui_port).collect();
+
+        PROFILES
+            .iter()
+            .for_each(|profile| assert!(CHAIN_NAMES.contains(&profile.chain), "{:?}", profile));
+        assert_eq!(ui_ports.len(), PROFILES.len());
+        assert_eq!(*PROFILE_NAMES, vec!["mainnet", "testnet", "dev"]);
+    }
+
+    #[test]
+    fn profiles_are_found_by_name_regardless_of_case() {
+        assert_eq!(profile_from_name("TestNet"), Some(&PROFILES[1]));
+        assert_eq!(profile_from_name("booga"), None);
+    }
+
+    #[test]
+    fn profile_vcl_supplies_only_what_the_schema_understands() {
+        let profile = Profile {
+            name: "test",
+            chain: "dev",
+            neighbors: &["AQIDBA:1.2.3.4:1234", "BQYHCA:2.3.4.5:2345"],
+            ui_port: 4321,
+        };
+        let schema = App::new("test")
+            .arg(Arg::with_name("chain").long("chain").takes_value(true))
+            .arg(
+                Arg::with_name("neighbors")
+                    .long("neighbors")
+                    .takes_value(true),
+            );
+
+        let result = profile_vcl(&profile, &schema);
+
+        assert_eq!(
+            result.args(),
+            vec![
+                "".to_string(),
+                "--chain".to_string(),
+                "dev".to_string(),
+                "--neighbors".to_string(),
+                "AQIDBA:1.2.3.4:1234,BQYHCA:2.3.4.5:2345".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,321 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use crate::multi_config::{CommandLineVcl, MultiConfig, NameValueVclArg, VclArg};
+use crate::node_configurator::WalletCreationConfig;
+use crate::sub_lib::logger::Logger;
+use clap::value_t;
+#[cfg(feature = "keychain")]
+use keyring::{Keyring, KeyringError};
+use std::path::Path;
+
+// Every secret is filed under this service, and under the Node's data directory as the account,
+// so two Nodes on one machine don't share their secrets
+#[cfg(feature = "keychain")]
+const KEYCHAIN_SERVICE: &str = "SubstratumNode";
+
+#[cfg(not(feature = "keychain"))]
+const KEYCHAIN_UNAVAILABLE: &str =
+    "This Node was built without keychain support (cargo build --features keychain)";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Secret {
+    WalletPassword,
+    ConsumingPrivateKey,
+}
+
+pub const SECRETS: [Secret; 2] = [Secret::WalletPassword, Secret::ConsumingPrivateKey];
+
+impl Secret {
+    // The parameter the secret would otherwise be given as
+    pub fn name(self) -> &'static str {
+        match self {
+            Secret::WalletPassword => "wallet-password",
+            Secret::ConsumingPrivateKey => "consuming-private-key",
+        }
+    }
+}
+
+pub trait SecretStore {
+    fn store(&self, secret: Secret, value: &str) -> Result<(), String>;
+    // Ok(None) if the secret has never been stored
+    fn retrieve(&self, secret: Secret) -> Result<Option<String>, String>;
+    fn forget(&self, secret: Secret) -> Result<(), String>;
+}
+
+// The platform keychain: Keychain on macOS, the Credential Manager (and so DPAPI) on Windows, and
+// whatever implements the Secret Service API (GNOME Keyring, KWallet) elsewhere
+pub struct SecretStoreReal {
+    #[cfg_attr(not(feature = "keychain"), allow(dead_code))]
+    account_prefix: String,
+}
+
+#[cfg(feature = "keychain")]
+impl SecretStore for SecretStoreReal {
+    fn store(&self, secret: Secret, value: &str) -> Result<(), String> {
+        let account = self.account(secret);
+        Keyring::new(KEYCHAIN_SERVICE, &account)
+            .set_password(value)
+            .map_err(|e| format!("{}", e))
+    }
+
+    fn retrieve(&self, secret: Secret) -> Result<Option<String>, String> {
+        let account = self.account(secret);
+        match Keyring::new(KEYCHAIN_SERVICE, &account).get_password() {
+            Ok(value) => Ok(Some(value)),
+            Err(KeyringError::NoPasswordFound) => Ok(None),
+            Err(e) => Err(format!("{}", e)),
+        }
+    }
+
+    fn forget(&self, secret: Secret) -> Result<(), String> {
+        let account = self.account(secret);
+        match Keyring::new(KEYCHAIN_SERVICE, &account).delete_password() {
+            Ok(()) | Err(KeyringError::NoPasswordFound) => Ok(()),
+            Err(e) => Err(format!("{}", e)),
+        }
+    }
+}
+
+// Without the keychain, every secret has to come some other way; --keychain is reported, as a
+// keychain that can't be read or written would be
+#[cfg(not(feature = "keychain"))]
+impl SecretStore for SecretStoreReal {
+    fn store(&self, _secret: Secret, _value: &str) -> Result<(), String> {
+        Err(KEYCHAIN_UNAVAILABLE.to_string())
+    }
+
+    fn retrieve(&self, _secret: Secret) -> Result<Option<String>, String> {
+        Err(KEYCHAIN_UNAVAILABLE.to_string())
+    }
+
+    fn forget(&self, _secret: Secret) -> Result<(), String> {
+        Err(KEYCHAIN_UNAVAILABLE.to_string())
+    }
+}
+
+impl SecretStoreReal {
+    pub fn new(data_directory: &Path) -> SecretStoreReal {
+        SecretStoreReal {
+            account_prefix: data_directory.display().to_string(),
+        }
+    }
+
+    #[cfg(any(feature = "keychain", test))]
+    fn account(&self, secret: Secret) -> String {
+        format!("{}:{}", self.account_prefix, secret.name())
+    }
+}
+
+// Supplies whatever secrets the keychain holds as though they'd been given on the command line.
+// A keychain that can't be read is reported and treated as empty, so that a secret given some
+// other way still works.
+pub fn secret_store_vcl(secret_store: &dyn SecretStore) -> CommandLineVcl {
+    let logger = Logger::new("Bootstrapper");
+    let vcl_args: Vec<Box<dyn VclArg>> = SECRETS
+        .iter()
+        .filter_map(|secret| match secret_store.retrieve(*secret) {
+            Ok(Some(value)) => Some(Box::new(NameValueVclArg::new(
+                &format!("--{}", secret.name()),
+                &value,
+            )) as Box<dyn VclArg>),
+            Ok(None) => None,
+            Err(e) => {
+                warning!(
+                    logger,
+                    "Couldn't read {} from the keychain: {}",
+                    secret.name(),
+                    e
+                );
+                None
+            }
+        })
+        .collect();
+    CommandLineVcl::from(vcl_args)
+}
+
+// Stores the secrets the Node was given some other way, so that next time it can find them in the
+// keychain instead
+pub fn remember_secrets(
+    multi_config: &MultiConfig,
+    secret_store: &dyn SecretStore,
+) -> Result<(), String> {
+    SECRETS.iter().try_for_each(
+        |secret| match value_m!(multi_config, secret.name(), String) {
+            Some(value) => remember_secret(secret_store, *secret, &value),
+            None => Ok(()),
+        },
+    )
+}
+
+// For --generate-wallet and --recover-wallet, which may have prompted for the password
+pub fn remember_wallet_password(
+    config: &WalletCreationConfig,
+    secret_store: &dyn SecretStore,
+) -> Result<(), String> {
+    match &config.derivation_path_info_opt {
+        Some(info) => remember_secret(secret_store, Secret::WalletPassword, &info.wallet_password),
+        None => Ok(()),
+    }
+}
+
+fn remember_secret(
+    secret_store: &dyn SecretStore,
+    secret: Secret,
+    value: &str,
+) -> Result<(), String> {
+    match secret_store.retrieve(secret) {
+        Ok(Some(ref stored)) if stored == value => Ok(()),
+        _ => secret_store
+            .store(secret, value)
+            .map_err(|e| format!("Couldn't store {} in the keychain: {}", secret.name(), e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bootstrapper::RealUser;
+    use crate::multi_config::VirtualCommandLine;
+    use crate::node_configurator::DerivationPathWalletInfo;
+    use crate::sub_lib::cryptde::PlainData;
+    use crate::test_utils::secret_store_mock::SecretStoreMock;
+    use clap::{App, Arg};
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+
+    fn make_multi_config(args: Vec<&str>) -> MultiConfig<'static> {
+        let schema = App::new("test")
+            .arg(
+                Arg::with_name("wallet-password")
+                    .long("wallet-password")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("consuming-private-key")
+                    .long("consuming-private-key")
+                    .takes_value(true),
+            );
+        MultiConfig::new(
+            &schema,
+            vec![Box::new(CommandLineVcl::new(
+                args.into_iter().map(|arg| arg.to_string()).collect(),
+            ))],
+        )
+    }
+
+    #[test]
+    fn accounts_are_kept_apart_by_data_directory_and_secret() {
+        let subject = SecretStoreReal::new(&PathBuf::from("/home/booga/.local/share/Substratum"));
+
+        assert_eq!(
+            subject.account(Secret::WalletPassword),
+            "/home/booga/.local/share/Substratum:wallet-password"
+        );
+        assert_eq!(
+            subject.account(Secret::ConsumingPrivateKey),
+            "/home/booga/.local/share/Substratum:consuming-private-key"
+        );
+    }
+
+    #[cfg(not(feature = "keychain"))]
+    #[test]
+    fn without_the_keychain_feature_the_keychain_is_unavailable() {
+        let subject = SecretStoreReal::new(&PathBuf::from("booga"));
+
+        let expected = KEYCHAIN_UNAVAILABLE.to_string();
+        assert_eq!(
+            subject.store(Secret::WalletPassword, "password"),
+            Err(expected.clone())
+        );
+        assert_eq!(subject.retrieve(Secret::WalletPassword), Err(expected.clone()));
+        assert_eq!(subject.forget(Secret::WalletPassword), Err(expected));
+    }
+
+    #[test]
+    fn secret_store_vcl_supplies_only_what_the_keychain_has() {
+        let secret_store = SecretStoreMock::new().secret(Secret::WalletPassword, "password");
+
+        let result = secret_store_vcl(&secret_store);
+
+        assert_eq!(
+            result.args(),
+            vec![
+                "".to_string(),
+                "--wallet-password".to_string(),
+                "password".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn secret_store_vcl_treats_an_unreadable_keychain_as_empty() {
+        let secret_store = SecretStoreMock::new().failure("The keychain is locked");
+
+        let result = secret_store_vcl(&secret_store);
+
+        assert_eq!(result.args(), vec!["".to_string()]);
+    }
+
+    #[test]
+    fn remember_secrets_stores_only_what_the_keychain_doesnt_already_have() {
+        let store_params_arc = Arc::new(Mutex::new(vec![]));
+        let secret_store = SecretStoreMock::new()
+            .secret(Secret::WalletPassword, "password")
+            .store_params(&store_params_arc);
+        let multi_config = make_multi_config(vec![
+            "",
+            "--wallet-password",
+            "password",
+            "--consuming-private-key",
+            "0123456789ABCDEF",
+        ]);
+
+        let result = remember_secrets(&multi_config, &secret_store);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            *store_params_arc.lock().unwrap(),
+            vec![(Secret::ConsumingPrivateKey, "0123456789ABCDEF".to_string())]
+        );
+    }
+
+    #[test]
+    fn remember_secrets_complains_when_the_keychain_refuses() {
+        let secret_store = SecretStoreMock::new().failure("The keychain is locked");
+        let multi_config = make_multi_config(vec!["", "--wallet-password", "password"]);
+
+        let result = remember_secrets(&multi_config, &secret_store);
+
+        assert_eq!(
+            result,
+            Err(
+                "Couldn't store wallet-password in the keychain: The keychain is locked"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn remember_wallet_password_stores_the_password_of_a_new_wallet() {
+        let store_params_arc = Arc::new(Mutex::new(vec![]));
+        let secret_store = SecretStoreMock::new().store_params(&store_params_arc);
+        let config = WalletCreationConfig {
+            earning_wallet_address_opt: None,
+            derivation_path_info_opt: Some(DerivationPathWalletInfo {
+                mnemonic_seed: PlainData::new(&[1, 2, 3, 4]),
+                wallet_password: "password".to_string(),
+                consuming_derivation_path_opt: None,
+            }),
+            real_user: RealUser::null(),
+            keychain_data_directory_opt: Some(PathBuf::from("booga")),
+        };
+
+        let result = remember_wallet_password(&config, &secret_store);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            *store_params_arc.lock().unwrap(),
+            vec![(Secret::WalletPassword, "password".to_string())]
+        );
+    }
+}
@@ -48,6 +48,8 @@ pub struct IdWrapperMock {
     getgid_results: RefCell<Vec<i32>>,
     setgid_params: Arc<Mutex<Vec<i32>>>,
     setgid_results: RefCell<Vec<i32>>,
+    setgroups_params: Arc<Mutex<Vec<Vec<i32>>>>,
+    setgroups_results: RefCell<Vec<i32>>,
 }
 
 impl IdWrapper for IdWrapperMock {
@@ -65,6 +67,10 @@ impl IdWrapper for IdWrapperMock {
         self.setgid_params.lock().unwrap().push(gid);
         self.setgid_results.borrow_mut().remove(0)
     }
+    fn setgroups(&self, gids: &[i32]) -> i32 {
+        self.setgroups_params.lock().unwrap().push(gids.to_vec());
+        self.setgroups_results.borrow_mut().remove(0)
+    }
 }
 
 #[allow(dead_code)]
@@ -102,6 +108,16 @@ impl IdWrapperMock {
         self.setgid_results.borrow_mut().push(gid_result);
         self
     }
+
+    pub fn setgroups_params(mut self, params: &Arc<Mutex<Vec<Vec<i32>>>>) -> Self {
+        self.setgroups_params = params.clone();
+        self
+    }
+
+    pub fn setgroups_result(self, groups_result: i32) -> Self {
+        self.setgroups_results.borrow_mut().push(groups_result);
+        self
+    }
 }
 
 pub struct MockDirsWrapper {
@@ -4,18 +4,23 @@ use crate::blockchain::bip39::{Bip39, Bip39Error};
 use crate::config_dao::ConfigDaoError;
 use crate::config_dao::{ConfigDao, ConfigDaoReal};
 use crate::database::db_initializer::ConnectionWrapper;
+use crate::sub_lib::accountant::PaymentParameter;
+use crate::sub_lib::blockchain_bridge::FeeSpending;
 use crate::sub_lib::cryptde::PlainData;
 use crate::sub_lib::wallet::Wallet;
 use rusqlite::Transaction;
 use rustc_hex::ToHex;
-use std::net::{Ipv4Addr, SocketAddrV4, TcpListener};
+use std::net::{IpAddr, Ipv4Addr, SocketAddrV4, TcpListener};
 use std::str::FromStr;
 
 pub const LOWEST_USABLE_INSECURE_PORT: u16 = 1025;
 pub const HIGHEST_RANDOM_CLANDESTINE_PORT: u16 = 9999;
 pub const HIGHEST_USABLE_PORT: u16 = 65535;
+pub const FTP_PORT: u16 = 21;
 pub const HTTP_PORT: u16 = 80;
 pub const TLS_PORT: u16 = 443;
+pub const SUBMISSION_PORT: u16 = 587;
+pub const IMAPS_PORT: u16 = 993;
 
 pub trait PersistentConfiguration: Send {
     fn current_schema_version(&self) -> String;
@@ -23,6 +28,8 @@ pub trait PersistentConfiguration: Send {
     fn set_clandestine_port(&self, port: u16);
     fn gas_price(&self) -> u64;
     fn set_gas_price(&self, gas_price: u64);
+    fn payment_parameter(&self, parameter: PaymentParameter) -> u64;
+    fn set_payment_parameter(&self, parameter: PaymentParameter, value: u64);
     fn encrypted_mnemonic_seed(&self) -> Option<String>;
     fn mnemonic_seed(&self, wallet_password: &str) -> Result<PlainData, Bip39Error>;
     fn set_mnemonic_seed(&self, seed: &dyn AsRef<[u8]>, wallet_password: &str);
@@ -33,8 +40,21 @@ pub trait PersistentConfiguration: Send {
     fn earning_wallet_from_address(&self) -> Option<Wallet>;
     fn earning_wallet_address(&self) -> Option<String>;
     fn set_earning_wallet_address(&self, address: &str);
+    fn exit_earning_wallet_from_address(&self) -> Option<Wallet>;
+    fn set_exit_earning_wallet_address(&self, address: &str);
     fn start_block(&self) -> u64;
+    fn set_start_block(&self, value: u64);
     fn set_start_block_transactionally(&self, tx: &Transaction, value: u64) -> Result<(), String>;
+    fn next_nonce(&self) -> Option<u64>;
+    fn set_next_nonce(&self, nonce: u64);
+    fn fee_spending(&self) -> Option<FeeSpending>;
+    fn set_fee_spending(&self, spending: FeeSpending);
+    fn detected_dns_servers(&self) -> Option<Vec<IpAddr>>;
+    fn set_detected_dns_servers(&self, dns_servers: &[IpAddr]);
+    fn split_dns_domains(&self) -> Vec<String>;
+    fn set_split_dns_domains(&self, domains: &[String]);
+    // Err, with the reason, if the database can't be written to any more
+    fn check_writable(&self) -> Result<(), String>;
 }
 
 pub struct PersistentConfigurationReal {
@@ -114,6 +134,30 @@ impl PersistentConfiguration for PersistentConfigurationReal {
             });
     }
 
+    fn payment_parameter(&self, parameter: PaymentParameter) -> u64 {
+        self.dao
+            .get_u64(&parameter.config_key())
+            .unwrap_or_else(|e| {
+                panic!(
+                    "Can't continue; {} configuration is inaccessible: {:?}",
+                    parameter.name(),
+                    e
+                )
+            })
+    }
+
+    fn set_payment_parameter(&self, parameter: PaymentParameter, value: u64) {
+        self.dao
+            .set_u64(&parameter.config_key(), value)
+            .unwrap_or_else(|e| {
+                panic!(
+                    "Can't continue; {} configuration is inaccessible: {:?}",
+                    parameter.name(),
+                    e
+                )
+            });
+    }
+
     fn encrypted_mnemonic_seed(&self) -> Option<String> {
         match self.dao.get_string("seed") {
             Ok(ems) => Some(ems),
@@ -278,6 +322,35 @@ impl PersistentConfiguration for PersistentConfigurationReal {
         }
     }
 
+    fn exit_earning_wallet_from_address(&self) -> Option<Wallet> {
+        match self.dao.get_string("exit_earning_wallet_address") {
+            Ok(address) => Some(Wallet::from_str(&address).unwrap_or_else(|_| {
+                panic!(
+                    "Database corrupt: invalid exit earning wallet address: '{}'",
+                    address
+                )
+            })),
+            Err(ConfigDaoError::NotPresent) => None,
+            Err(e) => panic!(
+                "Error trying to retrieve exit earning wallet address: {:?}",
+                e
+            ),
+        }
+    }
+
+    // Unlike the earning wallet, the exit earning wallet may be changed at any time: it only
+    // tells consumers where to send future payments for exit services.
+    fn set_exit_earning_wallet_address(&self, address: &str) {
+        match Wallet::from_str(address) {
+            Ok(_) => (),
+            Err(_) => panic!("Invalid exit earning wallet address '{}'", address),
+        }
+        match self.dao.set_string("exit_earning_wallet_address", address) {
+            Ok(_) => (),
+            Err(e) => panic!("Error setting exit earning wallet address: {:?}", e),
+        }
+    }
+
     fn start_block(&self) -> u64 {
         self.dao.get_u64("start_block").unwrap_or_else(|e| {
             panic!(
@@ -287,6 +360,15 @@ impl PersistentConfiguration for PersistentConfigurationReal {
         })
     }
 
+    fn set_start_block(&self, value: u64) {
+        self.dao.set_u64("start_block", value).unwrap_or_else(|e| {
+            panic!(
+                "Can't continue; start_block configuration is inaccessible: {:?}",
+                e
+            )
+        });
+    }
+
     fn set_start_block_transactionally(&self, tx: &Transaction, value: u64) -> Result<(), String> {
         self.dao
             .set_u64_transactional(tx, "start_block", value)
@@ -298,6 +380,132 @@ impl PersistentConfiguration for PersistentConfigurationReal {
                 ConfigDaoError::TypeError => panic!("Unknown error: TypeError"),
             })
     }
+
+    // None until the first payment has been sent
+    fn next_nonce(&self) -> Option<u64> {
+        match self.dao.get_u64("next_nonce") {
+            Ok(nonce) => Some(nonce),
+            Err(ConfigDaoError::NotPresent) => None,
+            Err(e) => panic!(
+                "Can't continue; next_nonce configuration is inaccessible: {:?}",
+                e
+            ),
+        }
+    }
+
+    fn set_next_nonce(&self, nonce: u64) {
+        self.dao.set_u64("next_nonce", nonce).unwrap_or_else(|e| {
+            panic!(
+                "Can't continue; next_nonce configuration is inaccessible: {:?}",
+                e
+            )
+        });
+    }
+
+    // None until fees have been spent under a fee budget
+    fn fee_spending(&self) -> Option<FeeSpending> {
+        let get = |name: &str| match self.dao.get_u64(name) {
+            Ok(value) => Some(value),
+            Err(ConfigDaoError::NotPresent) => None,
+            Err(e) => panic!(
+                "Can't continue; {} configuration is inaccessible: {:?}",
+                name, e
+            ),
+        };
+        match (get("fee_budget_period_start"), get("fee_budget_spent")) {
+            (Some(period_start), Some(gwei)) => Some(FeeSpending { period_start, gwei }),
+            _ => None,
+        }
+    }
+
+    fn set_fee_spending(&self, spending: FeeSpending) {
+        let set = |name: &str, value: u64| {
+            self.dao.set_u64(name, value).unwrap_or_else(|e| {
+                panic!(
+                    "Can't continue; {} configuration is inaccessible: {:?}",
+                    name, e
+                )
+            })
+        };
+        set("fee_budget_period_start", spending.period_start);
+        set("fee_budget_spent", spending.gwei);
+    }
+
+    // None unless the Node has had to find its own DNS servers because --dns-servers was left out
+    fn detected_dns_servers(&self) -> Option<Vec<IpAddr>> {
+        match self.dao.get_string("detected_dns_servers") {
+            Ok(dns_servers) => Some(
+                dns_servers
+                    .split(',')
+                    .map(|dns_server| {
+                        IpAddr::from_str(dns_server).unwrap_or_else(|_| {
+                            panic!(
+                                "Database corrupt: invalid detected DNS servers: '{}'",
+                                dns_servers
+                            )
+                        })
+                    })
+                    .collect(),
+            ),
+            Err(ConfigDaoError::NotPresent) => None,
+            Err(e) => panic!(
+                "Can't continue; detected_dns_servers configuration is inaccessible: {:?}",
+                e
+            ),
+        }
+    }
+
+    fn set_detected_dns_servers(&self, dns_servers: &[IpAddr]) {
+        let value = dns_servers
+            .iter()
+            .map(|dns_server| dns_server.to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+        self.dao
+            .set_string("detected_dns_servers", &value)
+            .unwrap_or_else(|e| {
+                panic!(
+                    "Can't continue; detected_dns_servers configuration is inaccessible: {:?}",
+                    e
+                )
+            });
+    }
+
+    // Domains to be looked up where this machine looked before its DNS was subverted; none unless set
+    fn split_dns_domains(&self) -> Vec<String> {
+        match self.dao.get_string("split_dns_domains") {
+            Ok(domains) => domains
+                .split(',')
+                .filter(|domain| !domain.is_empty())
+                .map(|domain| domain.to_string())
+                .collect(),
+            Err(ConfigDaoError::NotPresent) => vec![],
+            Err(e) => panic!(
+                "Can't continue; split_dns_domains configuration is inaccessible: {:?}",
+                e
+            ),
+        }
+    }
+
+    fn set_split_dns_domains(&self, domains: &[String]) {
+        self.dao
+            .set_string("split_dns_domains", &domains.join(","))
+            .unwrap_or_else(|e| {
+                panic!(
+                    "Can't continue; split_dns_domains configuration is inaccessible: {:?}",
+                    e
+                )
+            });
+    }
+
+    // Writes the schema version back as it is, which changes nothing but fails if the database
+    // has become read-only, locked or lost
+    fn check_writable(&self) -> Result<(), String> {
+        self.dao
+            .get_string("schema_version")
+            .and_then(|version| self.dao.set_string("schema_version", &version))
+            .map_err(|e| format!("{:?}", e))
+    }
 }
 
 impl From<Box<dyn ConnectionWrapper>> for PersistentConfigurationReal {
@@ -643,6 +851,273 @@ mod tests {
         subject.start_block();
     }
 
+    #[test]
+    fn set_start_block_success() {
+        let set_params_arc = Arc::new(Mutex::new(vec![]));
+        let config_dao = ConfigDaoMock::new()
+            .set_u64_params(&set_params_arc)
+            .set_u64_result(Ok(()));
+        let subject = PersistentConfigurationReal::new(Box::new(config_dao));
+
+        subject.set_start_block(1234);
+
+        let set_params = set_params_arc.lock().unwrap();
+        assert_eq!(*set_params, vec![("start_block".to_string(), 1234u64)]);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Can't continue; start_block configuration is inaccessible: NotPresent"
+    )]
+    fn set_start_block_panics_if_dao_error() {
+        let config_dao = ConfigDaoMock::new().set_u64_result(Err(ConfigDaoError::NotPresent));
+        let subject = PersistentConfigurationReal::new(Box::new(config_dao));
+
+        subject.set_start_block(1234);
+    }
+
+    #[test]
+    fn next_nonce_success() {
+        let config_dao = ConfigDaoMock::new().get_u64_result(Ok(17u64));
+        let subject = PersistentConfigurationReal::new(Box::new(config_dao));
+
+        let result = subject.next_nonce();
+
+        assert_eq!(result, Some(17));
+    }
+
+    #[test]
+    fn next_nonce_is_none_until_one_has_been_stored() {
+        let config_dao = ConfigDaoMock::new().get_u64_result(Err(ConfigDaoError::NotPresent));
+        let subject = PersistentConfigurationReal::new(Box::new(config_dao));
+
+        let result = subject.next_nonce();
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Can't continue; next_nonce configuration is inaccessible: TypeError"
+    )]
+    fn next_nonce_panics_if_dao_error() {
+        let config_dao = ConfigDaoMock::new().get_u64_result(Err(ConfigDaoError::TypeError));
+        let subject = PersistentConfigurationReal::new(Box::new(config_dao));
+
+        subject.next_nonce();
+    }
+
+    #[test]
+    fn set_next_nonce_success() {
+        let set_params_arc = Arc::new(Mutex::new(vec![]));
+        let config_dao = ConfigDaoMock::new()
+            .set_u64_params(&set_params_arc)
+            .set_u64_result(Ok(()));
+        let subject = PersistentConfigurationReal::new(Box::new(config_dao));
+
+        subject.set_next_nonce(18);
+
+        let set_params = set_params_arc.lock().unwrap();
+        assert_eq!(*set_params, vec![("next_nonce".to_string(), 18u64)]);
+    }
+
+    #[test]
+    fn fee_spending_success() {
+        let get_params_arc = Arc::new(Mutex::new(vec![]));
+        let config_dao = ConfigDaoMock::new()
+            .get_u64_params(&get_params_arc)
+            .get_u64_result(Ok(1_000_000))
+            .get_u64_result(Ok(42));
+        let subject = PersistentConfigurationReal::new(Box::new(config_dao));
+
+        let result = subject.fee_spending();
+
+        assert_eq!(
+            result,
+            Some(FeeSpending {
+                period_start: 1_000_000,
+                gwei: 42
+            })
+        );
+        let get_params = get_params_arc.lock().unwrap();
+        assert_eq!(
+            *get_params,
+            vec![
+                "fee_budget_period_start".to_string(),
+                "fee_budget_spent".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn fee_spending_is_none_until_some_has_been_stored() {
+        let config_dao = ConfigDaoMock::new()
+            .get_u64_result(Err(ConfigDaoError::NotPresent))
+            .get_u64_result(Err(ConfigDaoError::NotPresent));
+        let subject = PersistentConfigurationReal::new(Box::new(config_dao));
+
+        let result = subject.fee_spending();
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn set_fee_spending_success() {
+        let set_params_arc = Arc::new(Mutex::new(vec![]));
+        let config_dao = ConfigDaoMock::new()
+            .set_u64_params(&set_params_arc)
+            .set_u64_result(Ok(()))
+            .set_u64_result(Ok(()));
+        let subject = PersistentConfigurationReal::new(Box::new(config_dao));
+
+        subject.set_fee_spending(FeeSpending {
+            period_start: 1_000_000,
+            gwei: 42,
+        });
+
+        let set_params = set_params_arc.lock().unwrap();
+        assert_eq!(
+            *set_params,
+            vec![
+                ("fee_budget_period_start".to_string(), 1_000_000u64),
+                ("fee_budget_spent".to_string(), 42u64)
+            ]
+        );
+    }
+
+    #[test]
+    fn detected_dns_servers_success() {
+        let config_dao = ConfigDaoMock::new().get_string_result(Ok("1.1.1.1,1.0.0.1".to_string()));
+        let subject = PersistentConfigurationReal::new(Box::new(config_dao));
+
+        let result = subject.detected_dns_servers();
+
+        assert_eq!(
+            result,
+            Some(vec![IpAddr::from([1, 1, 1, 1]), IpAddr::from([1, 0, 0, 1])])
+        );
+    }
+
+    #[test]
+    fn detected_dns_servers_are_none_until_some_have_been_detected() {
+        let config_dao = ConfigDaoMock::new().get_string_result(Err(ConfigDaoError::NotPresent));
+        let subject = PersistentConfigurationReal::new(Box::new(config_dao));
+
+        let result = subject.detected_dns_servers();
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Database corrupt: invalid detected DNS servers: '1.1.1.1,booga'")]
+    fn detected_dns_servers_panics_if_database_is_corrupt() {
+        let config_dao = ConfigDaoMock::new().get_string_result(Ok("1.1.1.1,booga".to_string()));
+        let subject = PersistentConfigurationReal::new(Box::new(config_dao));
+
+        subject.detected_dns_servers();
+    }
+
+    #[test]
+    fn set_detected_dns_servers_success() {
+        let set_params_arc = Arc::new(Mutex::new(vec![]));
+        let config_dao = ConfigDaoMock::new()
+            .set_string_params(&set_params_arc)
+            .set_string_result(Ok(()));
+        let subject = PersistentConfigurationReal::new(Box::new(config_dao));
+
+        subject.set_detected_dns_servers(&[IpAddr::from([1, 1, 1, 1]), IpAddr::from([1, 0, 0, 1])]);
+
+        let set_params = set_params_arc.lock().unwrap();
+        assert_eq!(
+            *set_params,
+            vec![(
+                "detected_dns_servers".to_string(),
+                "1.1.1.1,1.0.0.1".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn split_dns_domains_success() {
+        let config_dao =
+            ConfigDaoMock::new().get_string_result(Ok("corp.example.com,lan".to_string()));
+        let subject = PersistentConfigurationReal::new(Box::new(config_dao));
+
+        let result = subject.split_dns_domains();
+
+        assert_eq!(
+            result,
+            vec!["corp.example.com".to_string(), "lan".to_string()]
+        );
+    }
+
+    #[test]
+    fn split_dns_domains_are_none_until_some_are_set_or_after_they_are_cleared() {
+        let config_dao = ConfigDaoMock::new()
+            .get_string_result(Err(ConfigDaoError::NotPresent))
+            .get_string_result(Ok(String::new()));
+        let subject = PersistentConfigurationReal::new(Box::new(config_dao));
+
+        assert_eq!(subject.split_dns_domains(), Vec::<String>::new());
+        assert_eq!(subject.split_dns_domains(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn set_split_dns_domains_success() {
+        let set_params_arc = Arc::new(Mutex::new(vec![]));
+        let config_dao = ConfigDaoMock::new()
+            .set_string_params(&set_params_arc)
+            .set_string_result(Ok(()));
+        let subject = PersistentConfigurationReal::new(Box::new(config_dao));
+
+        subject.set_split_dns_domains(&["corp.example.com".to_string(), "lan".to_string()]);
+
+        let set_params = set_params_arc.lock().unwrap();
+        assert_eq!(
+            *set_params,
+            vec![(
+                "split_dns_domains".to_string(),
+                "corp.example.com,lan".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn check_writable_writes_the_schema_version_back() {
+        let set_params_arc = Arc::new(Mutex::new(vec![]));
+        let config_dao = ConfigDaoMock::new()
+            .get_string_result(Ok("0.0.9".to_string()))
+            .set_string_params(&set_params_arc)
+            .set_string_result(Ok(()));
+        let subject = PersistentConfigurationReal::new(Box::new(config_dao));
+
+        let result = subject.check_writable();
+
+        assert_eq!(result, Ok(()));
+        let set_params = set_params_arc.lock().unwrap();
+        assert_eq!(
+            *set_params,
+            vec![("schema_version".to_string(), "0.0.9".to_string())]
+        );
+    }
+
+    #[test]
+    fn check_writable_reports_a_database_that_cant_be_written() {
+        let config_dao = ConfigDaoMock::new()
+            .get_string_result(Ok("0.0.9".to_string()))
+            .set_string_result(Err(ConfigDaoError::DatabaseError(
+                "attempt to write a readonly database".to_string(),
+            )));
+        let subject = PersistentConfigurationReal::new(Box::new(config_dao));
+
+        let result = subject.check_writable();
+
+        assert_eq!(
+            result,
+            Err("DatabaseError(\"attempt to write a readonly database\")".to_string())
+        );
+    }
+
     #[test]
     fn set_start_block_transactionally_success() {
         let config_dao = ConfigDaoMock::new().set_u64_transactional_result(Ok(()));
@@ -651,13 +1126,13 @@ mod tests {
             "persistent_configuration",
             "set_start_block_transactionally_success",
         );
-        let mut conn = DbInitializerReal::new()
+        let conn = DbInitializerReal::new()
             .initialize(&home_dir, DEFAULT_CHAIN_ID)
             .unwrap();
-        let transaction = conn.transaction().unwrap();
 
         let subject = PersistentConfigurationReal::new(Box::new(config_dao));
-        let result = subject.set_start_block_transactionally(&transaction, 1234);
+        let result = conn
+            .write(&mut |transaction| subject.set_start_block_transactionally(transaction, 1234));
 
         assert!(result.is_ok());
     }
@@ -709,6 +1184,60 @@ mod tests {
         subject.set_gas_price(3);
     }
 
+    #[test]
+    fn payment_parameter() {
+        let get_params_arc = Arc::new(Mutex::new(vec![]));
+        let config_dao = ConfigDaoMock::new()
+            .get_u64_params(&get_params_arc)
+            .get_u64_result(Ok(600u64));
+        let subject = PersistentConfigurationReal::new(Box::new(config_dao));
+
+        let result = subject.payment_parameter(PaymentParameter::PayableScanInterval);
+
+        assert_eq!(result, 600u64);
+        let get_params = get_params_arc.lock().unwrap();
+        assert_eq!(*get_params, vec!["payable_scan_interval".to_string()]);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Can't continue; permanent-debt-allowed configuration is inaccessible: NotPresent"
+    )]
+    fn payment_parameter_fails() {
+        let config_dao = ConfigDaoMock::new().get_u64_result(Err(ConfigDaoError::NotPresent));
+        let subject = PersistentConfigurationReal::new(Box::new(config_dao));
+
+        subject.payment_parameter(PaymentParameter::PermanentDebtAllowed);
+    }
+
+    #[test]
+    fn set_payment_parameter_succeeds() {
+        let set_params_arc = Arc::new(Mutex::new(vec![]));
+        let config_dao = ConfigDaoMock::new()
+            .set_u64_params(&set_params_arc)
+            .set_u64_result(Ok(()));
+        let subject = PersistentConfigurationReal::new(Box::new(config_dao));
+
+        subject.set_payment_parameter(PaymentParameter::BalanceDecreasesFor, 86_400);
+
+        let set_params = set_params_arc.lock().unwrap();
+        assert_eq!(
+            *set_params,
+            vec![("balance_decreases_for".to_string(), 86_400u64)]
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Can't continue; payment-grace-before-ban configuration is inaccessible: NotPresent"
+    )]
+    fn set_payment_parameter_fails() {
+        let config_dao = ConfigDaoMock::new().set_u64_result(Err(ConfigDaoError::NotPresent));
+        let subject = PersistentConfigurationReal::new(Box::new(config_dao));
+
+        subject.set_payment_parameter(PaymentParameter::PaymentGraceBeforeBan, 3600);
+    }
+
     #[test]
     fn set_start_block_transactionally_returns_err_when_transaction_fails() {
         let config_dao = ConfigDaoMock::new()
@@ -718,13 +1247,13 @@ mod tests {
             "persistent_configuration",
             "set_start_block_transactionally_returns_err_when_transaction_fails",
         );
-        let mut conn = DbInitializerReal::new()
+        let conn = DbInitializerReal::new()
             .initialize(&home_dir, DEFAULT_CHAIN_ID)
             .unwrap();
-        let transaction = conn.transaction().unwrap();
         let subject = PersistentConfigurationReal::new(Box::new(config_dao));
 
-        let result = subject.set_start_block_transactionally(&transaction, 1234);
+        let result = conn
+            .write(&mut |transaction| subject.set_start_block_transactionally(transaction, 1234));
 
         assert_eq!(Err(r#"DatabaseError("nah")"#.to_string()), result);
     }
@@ -1244,6 +1773,83 @@ mod tests {
         assert_eq!(set_string_params.len(), 0);
     }
 
+    #[test]
+    fn exit_earning_wallet_from_address_if_address_is_missing() {
+        let config_dao: Box<dyn ConfigDao> =
+            Box::new(ConfigDaoMock::new().get_string_result(Err(ConfigDaoError::NotPresent)));
+        let subject = PersistentConfigurationReal::new(config_dao);
+
+        assert_eq!(subject.exit_earning_wallet_from_address(), None);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Database corrupt: invalid exit earning wallet address: '123456invalid'"
+    )]
+    fn exit_earning_wallet_from_address_if_address_is_set_and_invalid() {
+        let config_dao: Box<dyn ConfigDao> =
+            Box::new(ConfigDaoMock::new().get_string_result(Ok("123456invalid".to_string())));
+        let subject = PersistentConfigurationReal::new(config_dao);
+
+        subject.exit_earning_wallet_from_address();
+    }
+
+    #[test]
+    fn exit_earning_wallet_from_address_if_address_is_set_and_valid() {
+        let get_string_params_arc = Arc::new(Mutex::new(vec![]));
+        let config_dao: Box<dyn ConfigDao> = Box::new(
+            ConfigDaoMock::new()
+                .get_string_params(&get_string_params_arc)
+                .get_string_result(Ok("0x0123456789ABCDEF0123456789ABCDEF01234567".to_string())),
+        );
+        let subject = PersistentConfigurationReal::new(config_dao);
+
+        let result = subject.exit_earning_wallet_from_address();
+
+        assert_eq!(
+            result,
+            Some(Wallet::from_str("0x0123456789ABCDEF0123456789ABCDEF01234567").unwrap())
+        );
+        let get_string_params = get_string_params_arc.lock().unwrap();
+        assert_eq!(
+            *get_string_params,
+            vec!["exit_earning_wallet_address".to_string()]
+        )
+    }
+
+    #[test]
+    fn set_exit_earning_wallet_address_overwrites_existing_address() {
+        let set_string_params_arc = Arc::new(Mutex::new(vec![]));
+        let config_dao: Box<dyn ConfigDao> = Box::new(
+            ConfigDaoMock::new()
+                .get_string_result(Ok("0xcafedeadbeefbabefacecafedeadbeefbabeface".to_string()))
+                .set_string_params(&set_string_params_arc)
+                .set_string_result(Ok(())),
+        );
+        let subject = PersistentConfigurationReal::new(config_dao);
+
+        subject.set_exit_earning_wallet_address("0x0123456789ABCDEF0123456789ABCDEF01234567");
+
+        let set_string_params = set_string_params_arc.lock().unwrap();
+        assert_eq!(
+            *set_string_params,
+            vec![(
+                "exit_earning_wallet_address".to_string(),
+                "0x0123456789ABCDEF0123456789ABCDEF01234567".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid exit earning wallet address 'booga'")]
+    fn set_exit_earning_wallet_address_bad_address() {
+        let config_dao: Box<dyn ConfigDao> =
+            Box::new(ConfigDaoMock::new().set_string_result(Ok(())));
+        let subject = PersistentConfigurationReal::new(config_dao);
+
+        subject.set_exit_earning_wallet_address("booga");
+    }
+
     #[test]
     #[should_panic(expected = "Database is corrupt: error retrieving one: TypeError")]
     fn handle_config_pair_result_handles_first_error() {
@@ -1289,15 +1895,13 @@ mod tests {
             "persistent_configuration",
             "set_start_block_transactionally_panics_for_not_present_error",
         );
-        let mut conn = DbInitializerReal::new()
+        let conn = DbInitializerReal::new()
             .initialize(&home_dir, DEFAULT_CHAIN_ID)
             .unwrap();
-        let transaction = conn.transaction().unwrap();
 
         let subject = PersistentConfigurationReal::new(Box::new(config_dao));
 
-        subject
-            .set_start_block_transactionally(&transaction, 1234)
+        conn.write(&mut |transaction| subject.set_start_block_transactionally(transaction, 1234))
             .unwrap();
     }
 
@@ -1311,15 +1915,13 @@ mod tests {
             "persistent_configuration",
             "set_start_block_transactionally_panics_for_type_error",
         );
-        let mut conn = DbInitializerReal::new()
+        let conn = DbInitializerReal::new()
             .initialize(&home_dir, DEFAULT_CHAIN_ID)
             .unwrap();
-        let transaction = conn.transaction().unwrap();
 
         let subject = PersistentConfigurationReal::new(Box::new(config_dao));
 
-        subject
-            .set_start_block_transactionally(&transaction, 1234)
+        conn.write(&mut |transaction| subject.set_start_block_transactionally(transaction, 1234))
             .unwrap();
     }
 }
@@ -19,6 +19,7 @@ pub trait IdWrapper: Send {
     fn getgid(&self) -> i32;
     fn setuid(&self, uid: i32) -> i32;
     fn setgid(&self, gid: i32) -> i32;
+    fn setgroups(&self, gids: &[i32]) -> i32;
 }
 
 pub struct IdWrapperReal;
@@ -37,6 +38,10 @@ impl IdWrapper for IdWrapperReal {
     fn setgid(&self, gid: i32) -> i32 {
         unsafe { setgid(gid) }
     }
+    fn setgroups(&self, gids: &[i32]) -> i32 {
+        let gids: Vec<libc::gid_t> = gids.iter().map(|gid| *gid as libc::gid_t).collect();
+        unsafe { libc::setgroups(gids.len() as _, gids.as_ptr()) }
+    }
 }
 
 #[cfg(target_os = "windows")]
@@ -53,6 +58,9 @@ impl IdWrapper for IdWrapperReal {
     fn setgid(&self, _gid: i32) -> i32 {
         -1
     }
+    fn setgroups(&self, _gids: &[i32]) -> i32 {
+        -1
+    }
 }
 
 pub trait PrivilegeDropper: Send {
@@ -67,10 +75,23 @@ pub struct PrivilegeDropperReal {
 impl PrivilegeDropper for PrivilegeDropperReal {
     #[cfg(not(target_os = "windows"))]
     fn drop_privileges(&self, real_user: &RealUser) {
+        let running_as_root = self.id_wrapper.getuid() == 0;
+        // Root's supplementary groups would otherwise outlive root itself, whatever its primary
+        // group; and only root may change them, so it has to happen before setuid
+        if running_as_root {
+            let gid = real_user.gid.expect("Group-ID logic not working");
+            let groups_result = self.id_wrapper.setgroups(&[gid]);
+            if groups_result != 0 {
+                panic!(
+                    "Error code {} resetting supplementary groups",
+                    groups_result
+                )
+            }
+        }
+
         if self.id_wrapper.getgid() == 0 {
-            let gid_result = self
-                .id_wrapper
-                .setgid(real_user.gid.expect("Group-ID logic not working"));
+            let gid = real_user.gid.expect("Group-ID logic not working");
+            let gid_result = self.id_wrapper.setgid(gid);
             if gid_result != 0 {
                 panic!("Error code {} resetting group id", gid_result)
             }
@@ -79,7 +100,7 @@ impl PrivilegeDropper for PrivilegeDropperReal {
             }
         }
 
-        if self.id_wrapper.getuid() == 0 {
+        if running_as_root {
             let uid_result = self
                 .id_wrapper
                 .setuid(real_user.uid.expect("User-ID logic not working"));
@@ -143,6 +164,63 @@ impl Default for PrivilegeDropperReal {
     }
 }
 
+// --run-as names the account to drop to, as user or user:group, by name or number. Without a
+// group, the user's own group is used; the home directory is always the user's.
+#[cfg(not(target_os = "windows"))]
+pub fn resolve_run_as(run_as: &str) -> Result<RealUser, String> {
+    use std::ffi::{CStr, CString, OsStr};
+    use std::os::unix::ffi::OsStrExt;
+    let mut parts = run_as.splitn(2, ':');
+    let user = parts.next().unwrap_or("");
+    let group_opt = parts.next();
+    let c_user = CString::new(user).map_err(|_| format!("No user named '{}'", user))?;
+    // Only ever called while starting up, before there are other threads to share the result with
+    let passwd = unsafe {
+        match user.parse::<libc::uid_t>() {
+            Ok(uid) => libc::getpwuid(uid),
+            Err(_) => libc::getpwnam(c_user.as_ptr()),
+        }
+    };
+    if passwd.is_null() {
+        return Err(format!("No user named '{}'", user));
+    }
+    let (uid, user_gid, home_dir) = unsafe {
+        (
+            (*passwd).pw_uid,
+            (*passwd).pw_gid,
+            PathBuf::from(OsStr::from_bytes(
+                CStr::from_ptr((*passwd).pw_dir).to_bytes(),
+            )),
+        )
+    };
+    let gid = match group_opt {
+        None => user_gid,
+        Some(group) => {
+            let c_group = CString::new(group).map_err(|_| format!("No group named '{}'", group))?;
+            let group_entry = unsafe {
+                match group.parse::<libc::gid_t>() {
+                    Ok(gid) => libc::getgrgid(gid),
+                    Err(_) => libc::getgrnam(c_group.as_ptr()),
+                }
+            };
+            if group_entry.is_null() {
+                return Err(format!("No group named '{}'", group));
+            }
+            unsafe { (*group_entry).gr_gid }
+        }
+    };
+    Ok(RealUser::new(
+        Some(uid as i32),
+        Some(gid as i32),
+        Some(home_dir),
+    ))
+}
+
+#[cfg(target_os = "windows")]
+pub fn resolve_run_as(_run_as: &str) -> Result<RealUser, String> {
+    Err("--run-as is for Linux and macOS; on Windows the Node runs as administrator".to_string())
+}
+
 #[cfg(not(target_os = "windows"))]
 #[cfg(test)]
 mod tests {
@@ -158,6 +236,7 @@ mod tests {
             .getgid_result(0)
             .getuid_result(0)
             .getgid_result(0)
+            .setgroups_result(0)
             .setgid_result(47);
         let mut subject = PrivilegeDropperReal::new();
         subject.id_wrapper = Box::new(id_wrapper);
@@ -165,6 +244,20 @@ mod tests {
         subject.drop_privileges(&RealUser::null().populate());
     }
 
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    #[should_panic(expected = "Error code 47 resetting supplementary groups")]
+    fn supplementary_groups_error_code_causes_panic() {
+        let id_wrapper = IdWrapperMock::new()
+            .getuid_result(0)
+            .getgid_result(0)
+            .setgroups_result(47);
+        let mut subject = PrivilegeDropperReal::new();
+        subject.id_wrapper = Box::new(id_wrapper);
+
+        subject.drop_privileges(&RealUser::new(Some(111), Some(222), None));
+    }
+
     #[cfg(not(target_os = "windows"))]
     #[test]
     #[should_panic(expected = "Error code 47 resetting user id")]
@@ -172,6 +265,7 @@ mod tests {
         let id_wrapper = IdWrapperMock::new()
             .getuid_result(0)
             .getgid_result(0)
+            .setgroups_result(0)
             .setgid_result(0)
             .getgid_result(202)
             .setuid_result(47);
@@ -188,6 +282,7 @@ mod tests {
         let id_wrapper = IdWrapperMock::new()
             .getuid_result(0)
             .getgid_result(0)
+            .setgroups_result(0)
             .setgid_result(0)
             .getgid_result(0);
         let mut subject = PrivilegeDropperReal::new();
@@ -203,6 +298,7 @@ mod tests {
         let id_wrapper = IdWrapperMock::new()
             .getuid_result(0)
             .getgid_result(0)
+            .setgroups_result(0)
             .setgid_result(0)
             .getgid_result(202)
             .setuid_result(0)
@@ -218,12 +314,15 @@ mod tests {
     fn works_okay_with_real_user() {
         let setuid_params_arc = Arc::new(Mutex::new(vec![]));
         let setgid_params_arc = Arc::new(Mutex::new(vec![]));
+        let setgroups_params_arc = Arc::new(Mutex::new(vec![]));
         let id_wrapper = IdWrapperMock::new()
             .getuid_result(0)
             .getgid_result(0)
             .setuid_params(&setuid_params_arc)
             .setgid_params(&setgid_params_arc)
+            .setgroups_params(&setgroups_params_arc)
             .setuid_result(0)
+            .setgroups_result(0)
             .setgid_result(0)
             .getuid_result(101)
             .getgid_result(202);
@@ -240,6 +339,39 @@ mod tests {
         assert_eq!(*setuid_params, vec![101]);
         let setgid_params = setgid_params_arc.lock().unwrap();
         assert_eq!(*setgid_params, vec![202]);
+        let setgroups_params = setgroups_params_arc.lock().unwrap();
+        assert_eq!(*setgroups_params, vec![vec![202]]);
+    }
+
+    #[test]
+    fn root_with_a_non_root_group_still_gives_up_its_supplementary_groups() {
+        let setuid_params_arc = Arc::new(Mutex::new(vec![]));
+        let setgid_params_arc = Arc::new(Mutex::new(vec![]));
+        let setgroups_params_arc = Arc::new(Mutex::new(vec![]));
+        let id_wrapper = IdWrapperMock::new()
+            .getuid_result(0)
+            .getgid_result(202)
+            .setuid_params(&setuid_params_arc)
+            .setgid_params(&setgid_params_arc)
+            .setgroups_params(&setgroups_params_arc)
+            .setgroups_result(0)
+            .setuid_result(0)
+            .getuid_result(101);
+        let mut subject = PrivilegeDropperReal::new();
+        subject.id_wrapper = Box::new(id_wrapper);
+
+        subject.drop_privileges(&RealUser::new(
+            Some(101),
+            Some(202),
+            Some("/home/user".into()),
+        ));
+
+        let setgroups_params = setgroups_params_arc.lock().unwrap();
+        assert_eq!(*setgroups_params, vec![vec![202]]);
+        let setgid_params = setgid_params_arc.lock().unwrap();
+        assert!(setgid_params.is_empty());
+        let setuid_params = setuid_params_arc.lock().unwrap();
+        assert_eq!(*setuid_params, vec![101]);
     }
 
     #[test]
@@ -261,4 +393,33 @@ mod tests {
         let setgid_params = setgid_params_arc.lock().unwrap();
         assert!(setgid_params.is_empty());
     }
+
+    #[test]
+    fn run_as_finds_a_user_and_its_group_by_name() {
+        let result = resolve_run_as("root").unwrap();
+
+        assert_eq!(result.uid, Some(0));
+        assert_eq!(result.gid, Some(0));
+        assert!(result.home_dir.is_some());
+    }
+
+    #[test]
+    fn run_as_takes_numbers_and_an_explicit_group() {
+        let result = resolve_run_as("0:1").unwrap();
+
+        assert_eq!(result.uid, Some(0));
+        assert_eq!(result.gid, Some(1));
+    }
+
+    #[test]
+    fn run_as_complains_about_unknown_users_and_groups() {
+        assert_eq!(
+            resolve_run_as("no-such-booga").err(),
+            Some("No user named 'no-such-booga'".to_string())
+        );
+        assert_eq!(
+            resolve_run_as("root:no-such-booga").err(),
+            Some("No group named 'no-such-booga'".to_string())
+        );
+    }
 }
@@ -0,0 +1,379 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::sub_lib::cryptde::PublicKey;
+use crate::sub_lib::proxy_client::ExitProtections;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+const SMTP_PORT: u16 = 25;
+// IMAP, SMTP submission over TLS, SMTP submission with STARTTLS, and IMAPS
+const MAIL_CLIENT_PORTS: [u16; 4] = [143, 465, 587, 993];
+// Fan-out and scans are judged on the streams an originator opened this recently
+const WINDOW: Duration = Duration::from_secs(60);
+// One host probed on this many ports in a window...
+const SCAN_PORTS_PER_HOST: usize = 20;
+// ...or one port probed on this many hosts, unless it's a port every browser uses
+const SCAN_HOSTS_PER_PORT: usize = 50;
+const WEB_PORTS: [u16; 2] = [80, 443];
+// An originator caught scanning gets no new streams for this long
+pub const SCANNER_BLOCK_TIME: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ExitVerdict {
+    Allow,
+    // Why the stream was refused
+    Refuse(String),
+    // The originator was caught scanning earlier; its refusal has been logged already
+    StillBlocked,
+}
+
+#[derive(Default)]
+struct OriginatorHistory {
+    streams: VecDeque<(Instant, String, u16)>,
+    blocked_until_opt: Option<Instant>,
+}
+
+impl OriginatorHistory {
+    fn forget_before_window(&mut self, now: Instant) {
+        while let Some((opened_at, _, _)) = self.streams.front() {
+            if now.duration_since(*opened_at) < WINDOW {
+                break;
+            }
+            self.streams.pop_front();
+        }
+    }
+
+    fn has_visited(&self, hostname: &str, port: u16) -> bool {
+        self.streams.iter().any(|(_, visited_host, visited_port)| {
+            visited_host == hostname && *visited_port == port
+        })
+    }
+
+    fn destination_count(&self) -> usize {
+        self.streams
+            .iter()
+            .map(|(_, hostname, port)| (hostname, port))
+            .collect::<HashSet<(&String, &u16)>>()
+            .len()
+    }
+
+    fn scan_pattern(&self, hostname: &str, port: u16) -> Option<String> {
+        let ports_on_host = self
+            .streams
+            .iter()
+            .filter(|(_, visited_host, _)| visited_host == hostname)
+            .map(|(_, _, visited_port)| visited_port)
+            .collect::<HashSet<&u16>>()
+            .len();
+        if ports_on_host >= SCAN_PORTS_PER_HOST {
+            return Some(format!(
+                "tried {} ports on {} within a minute",
+                ports_on_host, hostname
+            ));
+        }
+        if WEB_PORTS.contains(&port) {
+            return None;
+        }
+        let hosts_on_port = self
+            .streams
+            .iter()
+            .filter(|(_, _, visited_port)| *visited_port == port)
+            .map(|(_, visited_host, _)| visited_host)
+            .collect::<HashSet<&String>>()
+            .len();
+        if hosts_on_port >= SCAN_HOSTS_PER_PORT {
+            return Some(format!(
+                "tried port {} on {} hosts within a minute",
+                port, hosts_on_port
+            ));
+        }
+        None
+    }
+}
+
+// Decides whether the exit should open each new stream, judging each originator by the streams
+// it has had opened lately
+pub struct ExitGuard {
+    protections: ExitProtections,
+    histories: HashMap<PublicKey, OriginatorHistory>,
+}
+
+impl ExitGuard {
+    pub fn new(protections: ExitProtections) -> ExitGuard {
+        ExitGuard {
+            protections,
+            histories: HashMap::new(),
+        }
+    }
+
+    pub fn admit(
+        &mut self,
+        originator: &PublicKey,
+        hostname: &str,
+        port: u16,
+        now: Instant,
+    ) -> ExitVerdict {
+        if self.protections.block_smtp && port == SMTP_PORT {
+            return ExitVerdict::Refuse("outbound SMTP is blocked on this exit".to_string());
+        }
+        if self.protections.block_mail && MAIL_CLIENT_PORTS.contains(&port) {
+            return ExitVerdict::Refuse("mail is not carried by this exit".to_string());
+        }
+        let protections = self.protections;
+        let history = self.histories.entry(originator.clone()).or_default();
+        match history.blocked_until_opt {
+            Some(until) if now < until => return ExitVerdict::StillBlocked,
+            Some(_) => history.blocked_until_opt = None,
+            None => (),
+        }
+        history.forget_before_window(now);
+        let hostname = hostname.to_lowercase();
+        if !history.has_visited(&hostname, port)
+            && history.destination_count() >= protections.fan_out_limit
+        {
+            return ExitVerdict::Refuse(format!(
+                "originator has already opened streams to {} destinations within a minute",
+                protections.fan_out_limit
+            ));
+        }
+        history.streams.push_back((now, hostname.clone(), port));
+        if protections.scan_detection {
+            if let Some(pattern) = history.scan_pattern(&hostname, port) {
+                history.blocked_until_opt = Some(now + SCANNER_BLOCK_TIME);
+                history.streams.clear();
+                return ExitVerdict::Refuse(format!(
+                    "originator looks like a port scanner ({}); blocking it for {}s",
+                    pattern,
+                    SCANNER_BLOCK_TIME.as_secs()
+                ));
+            }
+        }
+        ExitVerdict::Allow
+    }
+
+    // Originators with nothing in the window, and no block running, needn't be remembered
+    pub fn forget_idle(&mut self, now: Instant) {
+        self.histories.retain(|_, history| {
+            history.forget_before_window(now);
+            let blocked = match history.blocked_until_opt {
+                Some(until) => now < until,
+                None => false,
+            };
+            blocked || !history.streams.is_empty()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn originator() -> PublicKey {
+        PublicKey::new(&b"originator"[..])
+    }
+
+    fn protections() -> ExitProtections {
+        ExitProtections {
+            block_smtp: false,
+            block_mail: false,
+            fan_out_limit: 1000,
+            scan_detection: true,
+        }
+    }
+
+    #[test]
+    fn smtp_is_allowed_unless_blocked() {
+        let now = Instant::now();
+        let mut permissive = ExitGuard::new(protections());
+        let mut strict = ExitGuard::new(ExitProtections {
+            block_smtp: true,
+            ..protections()
+        });
+
+        let permissive_result = permissive.admit(&originator(), "mail.example.com", 25, now);
+        let strict_result = strict.admit(&originator(), "mail.example.com", 25, now);
+        let strict_web_result = strict.admit(&originator(), "mail.example.com", 443, now);
+
+        assert_eq!(permissive_result, ExitVerdict::Allow);
+        assert_eq!(
+            strict_result,
+            ExitVerdict::Refuse("outbound SMTP is blocked on this exit".to_string())
+        );
+        assert_eq!(strict_web_result, ExitVerdict::Allow);
+    }
+
+    #[test]
+    fn mail_client_ports_are_allowed_unless_mail_is_blocked() {
+        let now = Instant::now();
+        let mut permissive = ExitGuard::new(protections());
+        let mut strict = ExitGuard::new(ExitProtections {
+            block_mail: true,
+            ..protections()
+        });
+
+        let permissive_result = permissive.admit(&originator(), "smtp.example.com", 587, now);
+        let strict_results = [143, 465, 587, 993]
+            .iter()
+            .map(|port| strict.admit(&originator(), "mail.example.com", *port, now))
+            .collect::<Vec<ExitVerdict>>();
+        let strict_smtp_result = strict.admit(&originator(), "mx.example.com", 25, now);
+        let strict_web_result = strict.admit(&originator(), "www.example.com", 443, now);
+
+        assert_eq!(permissive_result, ExitVerdict::Allow);
+        assert_eq!(
+            strict_results,
+            vec![ExitVerdict::Refuse("mail is not carried by this exit".to_string()); 4]
+        );
+        assert_eq!(strict_smtp_result, ExitVerdict::Allow);
+        assert_eq!(strict_web_result, ExitVerdict::Allow);
+    }
+
+    #[test]
+    fn an_originator_may_only_fan_out_so_far_in_a_minute() {
+        let now = Instant::now();
+        let mut subject = ExitGuard::new(ExitProtections {
+            fan_out_limit: 3,
+            ..protections()
+        });
+        ["one.com", "two.com", "three.com"].iter().for_each(|host| {
+            assert_eq!(
+                subject.admit(&originator(), host, 443, now),
+                ExitVerdict::Allow
+            )
+        });
+
+        let new_destination = subject.admit(&originator(), "four.com", 443, now);
+        let old_destination = subject.admit(&originator(), "TWO.com", 443, now);
+        let other_originator = subject.admit(&PublicKey::new(&b"other"[..]), "four.com", 443, now);
+        let a_minute_later = subject.admit(&originator(), "four.com", 443, now + WINDOW);
+
+        assert_eq!(
+            new_destination,
+            ExitVerdict::Refuse(
+                "originator has already opened streams to 3 destinations within a minute"
+                    .to_string()
+            )
+        );
+        assert_eq!(old_destination, ExitVerdict::Allow);
+        assert_eq!(other_originator, ExitVerdict::Allow);
+        assert_eq!(a_minute_later, ExitVerdict::Allow);
+    }
+
+    #[test]
+    fn probing_many_ports_on_one_host_is_a_scan() {
+        let now = Instant::now();
+        let mut subject = ExitGuard::new(protections());
+        (1..SCAN_PORTS_PER_HOST as u16).for_each(|port| {
+            assert_eq!(
+                subject.admit(&originator(), "1.2.3.4", port, now),
+                ExitVerdict::Allow
+            )
+        });
+
+        let result = subject.admit(&originator(), "1.2.3.4", 8080, now);
+
+        assert_eq!(
+            result,
+            ExitVerdict::Refuse(
+                "originator looks like a port scanner (tried 20 ports on 1.2.3.4 within a minute); blocking it for 600s"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn probing_one_port_on_many_hosts_is_a_scan() {
+        let now = Instant::now();
+        let mut subject = ExitGuard::new(protections());
+        (1..SCAN_HOSTS_PER_PORT).for_each(|host| {
+            assert_eq!(
+                subject.admit(&originator(), &format!("10.0.0.{}", host), 22, now),
+                ExitVerdict::Allow
+            )
+        });
+
+        let result = subject.admit(&originator(), "10.0.1.1", 22, now);
+
+        assert_eq!(
+            result,
+            ExitVerdict::Refuse(
+                "originator looks like a port scanner (tried port 22 on 50 hosts within a minute); blocking it for 600s"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn browsing_many_web_sites_is_not_a_scan() {
+        let now = Instant::now();
+        let mut subject = ExitGuard::new(protections());
+
+        let result = (0..SCAN_HOSTS_PER_PORT * 2)
+            .map(|host| subject.admit(&originator(), &format!("site{}.com", host), 443, now))
+            .collect::<Vec<ExitVerdict>>();
+
+        assert_eq!(result, vec![ExitVerdict::Allow; SCAN_HOSTS_PER_PORT * 2]);
+    }
+
+    #[test]
+    fn a_scanner_is_blocked_for_a_while() {
+        let now = Instant::now();
+        let mut subject = ExitGuard::new(protections());
+        (1..=SCAN_PORTS_PER_HOST as u16).for_each(|port| {
+            subject.admit(&originator(), "1.2.3.4", port, now);
+        });
+
+        let during_block = subject.admit(&originator(), "example.com", 443, now + WINDOW);
+        let after_block =
+            subject.admit(&originator(), "example.com", 443, now + SCANNER_BLOCK_TIME);
+
+        assert_eq!(during_block, ExitVerdict::StillBlocked);
+        assert_eq!(after_block, ExitVerdict::Allow);
+    }
+
+    #[test]
+    fn scan_detection_can_be_turned_off() {
+        let now = Instant::now();
+        let mut subject = ExitGuard::new(ExitProtections {
+            scan_detection: false,
+            ..protections()
+        });
+
+        let result = (1..=SCAN_PORTS_PER_HOST as u16 * 2)
+            .map(|port| subject.admit(&originator(), "1.2.3.4", port, now))
+            .collect::<Vec<ExitVerdict>>();
+
+        assert_eq!(result, vec![ExitVerdict::Allow; SCAN_PORTS_PER_HOST * 2]);
+    }
+
+    #[test]
+    fn idle_originators_are_forgotten_but_blocked_ones_are_not() {
+        let now = Instant::now();
+        let mut subject = ExitGuard::new(protections());
+        (1..=SCAN_PORTS_PER_HOST as u16).for_each(|port| {
+            subject.admit(&PublicKey::new(&b"scanner"[..]), "1.2.3.4", port, now);
+        });
+        subject.admit(&PublicKey::new(&b"idle"[..]), "example.com", 443, now);
+        subject.admit(
+            &PublicKey::new(&b"busy"[..]),
+            "example.com",
+            443,
+            now + WINDOW / 2,
+        );
+
+        subject.forget_idle(now + WINDOW);
+
+        let mut remembered = subject
+            .histories
+            .keys()
+            .cloned()
+            .collect::<Vec<PublicKey>>();
+        remembered.sort();
+        assert_eq!(
+            remembered,
+            vec![
+                PublicKey::new(&b"busy"[..]),
+                PublicKey::new(&b"scanner"[..])
+            ]
+        );
+    }
+}
@@ -1,48 +1,64 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
+mod exit_guard;
 #[cfg(test)]
 mod local_test_utils;
 mod resolver_wrapper;
+mod starttls;
 mod stream_establisher;
 mod stream_handler_pool;
 mod stream_reader;
 mod stream_writer;
 
+use crate::proxy_client::exit_guard::{ExitGuard, ExitVerdict};
 use crate::proxy_client::resolver_wrapper::ResolverWrapperFactory;
 use crate::proxy_client::resolver_wrapper::ResolverWrapperFactoryReal;
 use crate::proxy_client::stream_handler_pool::StreamHandlerPool;
 use crate::proxy_client::stream_handler_pool::StreamHandlerPoolFactory;
 use crate::proxy_client::stream_handler_pool::StreamHandlerPoolFactoryReal;
 use crate::sub_lib::accountant::ReportExitServiceProvidedMessage;
+use crate::sub_lib::clock::{Clock, ClockReal};
+use crate::sub_lib::crash_context::handling;
 use crate::sub_lib::cryptde::CryptDE;
 use crate::sub_lib::cryptde::PublicKey;
 use crate::sub_lib::hopper::MessageType;
 use crate::sub_lib::hopper::{ExpiredCoresPackage, IncipientCoresPackage};
 use crate::sub_lib::logger::Logger;
+use crate::sub_lib::neighborhood::DebtorKeyMessage;
 use crate::sub_lib::peer_actors::BindMessage;
+use crate::sub_lib::proxy_client::error_socket_addr;
 use crate::sub_lib::proxy_client::InboundServerData;
 use crate::sub_lib::proxy_client::ProxyClientConfig;
 use crate::sub_lib::proxy_client::ProxyClientSubs;
-use crate::sub_lib::proxy_client::{ClientResponsePayload, DnsResolveFailure};
+use crate::sub_lib::proxy_client::{
+    BillingClaimPayload, BillingClaimsMessage, ClientResponsePayload, DnsResolveFailure,
+};
 use crate::sub_lib::proxy_server::ClientRequestPayload;
 use crate::sub_lib::route::Route;
 use crate::sub_lib::sequence_buffer::SequencedPacket;
 use crate::sub_lib::stream_key::StreamKey;
+use crate::sub_lib::stream_tracing::{Span, Stage};
+use crate::sub_lib::ui_gateway::{GetNodeStatisticsMessage, NodeStatistics};
 use crate::sub_lib::utils::NODE_MAILBOX_CAPACITY;
 use crate::sub_lib::wallet::Wallet;
 use actix::Actor;
 use actix::Addr;
+use actix::AsyncContext;
 use actix::Context;
 use actix::Handler;
+use actix::MessageResult;
 use actix::Recipient;
 use pretty_hex::PrettyHex;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
+use std::time::Duration;
 use trust_dns_resolver::config::NameServerConfig;
 use trust_dns_resolver::config::Protocol;
 use trust_dns_resolver::config::ResolverConfig;
 use trust_dns_resolver::config::ResolverOpts;
 
+const EXIT_GUARD_CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
 pub struct ProxyClient {
     dns_servers: Vec<SocketAddr>,
     resolver_wrapper_factory: Box<dyn ResolverWrapperFactory>,
@@ -50,10 +66,18 @@ pub struct ProxyClient {
     cryptde: &'static dyn CryptDE,
     to_hopper: Option<Recipient<IncipientCoresPackage>>,
     to_accountant: Option<Recipient<ReportExitServiceProvidedMessage>>,
+    to_neighborhood: Option<Recipient<DebtorKeyMessage>>,
     pool: Option<Box<dyn StreamHandlerPool>>,
     stream_contexts: HashMap<StreamKey, StreamContext>,
+    // What each debtor owes us, until it's delivered over one of the debtor's own streams
+    billing_claims: HashMap<Wallet, u64>,
+    // The Nodes we've seen paying with each consuming wallet, since a claim on a wallet that
+    // several of them share covers what they all owe
+    debtor_keys: HashMap<Wallet, HashSet<PublicKey>>,
     exit_service_rate: u64,
     exit_byte_rate: u64,
+    exit_guard: ExitGuard,
+    clock: Box<dyn Clock>,
     logger: Logger,
 }
 
@@ -65,10 +89,12 @@ impl Handler<BindMessage> for ProxyClient {
     type Result = ();
 
     fn handle(&mut self, msg: BindMessage, ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("ProxyClient", "BindMessage");
         debug!(self.logger, "Handling BindMessage");
         ctx.set_mailbox_capacity(NODE_MAILBOX_CAPACITY);
         self.to_hopper = Some(msg.peer_actors.hopper.from_hopper_client);
         self.to_accountant = Some(msg.peer_actors.accountant.report_exit_service_provided);
+        self.to_neighborhood = Some(msg.peer_actors.neighborhood.debtor_key_sub);
         let mut config = ResolverConfig::new();
         for dns_server_ref in &self.dns_servers {
             info!(self.logger, "Adding DNS server: {}", dns_server_ref.ip());
@@ -88,6 +114,9 @@ impl Handler<BindMessage> for ProxyClient {
             self.exit_service_rate,
             self.exit_byte_rate,
         ));
+        ctx.run_interval(EXIT_GUARD_CLEANUP_INTERVAL, |act, _| {
+            act.exit_guard.forget_idle(act.clock.instant())
+        });
     }
 }
 
@@ -99,10 +128,15 @@ impl Handler<ExpiredCoresPackage<ClientRequestPayload>> for ProxyClient {
         msg: ExpiredCoresPackage<ClientRequestPayload>,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
+        let _handling = handling("ProxyClient", "ExpiredCoresPackage<ClientRequestPayload>");
         let payload = msg.payload;
         let paying_wallet = msg.paying_wallet;
         if paying_wallet.is_some() || &payload.originator_public_key == self.cryptde.public_key() {
-            let pool = self.pool.as_mut().expect("StreamHandlerPool unbound");
+            let span = Span::start(
+                Stage::ProxyClientRequest,
+                &payload.stream_key,
+                payload.sequenced_packet.sequence_number,
+            );
             let return_route = msg.remaining_route;
             let latest_stream_context = StreamContext {
                 return_route,
@@ -116,9 +150,30 @@ impl Handler<ExpiredCoresPackage<ClientRequestPayload>> for ProxyClient {
                 payload.sequenced_packet.sequence_number,
                 payload.sequenced_packet.data.len()
             );
-            self.stream_contexts
-                .insert(payload.stream_key, latest_stream_context);
+            if !self.admit_stream(&payload) {
+                self.refuse_stream(payload.stream_key, &latest_stream_context);
+                span.end();
+                return;
+            }
+            let pool = self.pool.as_mut().expect("StreamHandlerPool unbound");
+            let debtor_opt = match self
+                .stream_contexts
+                .insert(payload.stream_key, latest_stream_context)
+            {
+                None => paying_wallet
+                    .clone()
+                    .map(|wallet| (wallet, payload.originator_public_key.clone())),
+                Some(_) => None,
+            };
             pool.process_package(payload, paying_wallet);
+            if let Some((wallet, public_key)) = debtor_opt {
+                self.debtor_keys
+                    .entry(wallet.clone())
+                    .or_insert_with(HashSet::new)
+                    .insert(public_key.clone());
+                self.report_debtor_key(wallet, public_key);
+            }
+            span.end();
         } else {
             warning!(self.logger, "Refusing to provide exit services for CORES package with {}-byte payload without paying wallet", payload.sequenced_packet.data.len());
         }
@@ -129,6 +184,7 @@ impl Handler<InboundServerData> for ProxyClient {
     type Result = ();
 
     fn handle(&mut self, msg: InboundServerData, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("ProxyClient", "InboundServerData");
         let msg_data_len = msg.data.len();
         let msg_source = msg.source;
         let msg_sequence_number = msg.sequence_number;
@@ -154,6 +210,18 @@ impl Handler<InboundServerData> for ProxyClient {
             return;
         };
         self.report_response_exit_to_accountant(&stream_context, msg_data_len);
+        let billing_claims = &mut self.billing_claims;
+        let debtor_keys = &self.debtor_keys;
+        if let Some((gwub, shared_wallet)) =
+            stream_context.paying_wallet.as_ref().and_then(|wallet| {
+                billing_claims.remove(wallet).map(|gwub| {
+                    let debtor_count = debtor_keys.get(wallet).map(|keys| keys.len());
+                    (gwub, debtor_count.unwrap_or(0) > 1)
+                })
+            })
+        {
+            self.send_billing_claim(gwub, shared_wallet, &stream_context);
+        }
         if msg_last_data {
             debug!(
                 self.logger,
@@ -164,10 +232,36 @@ impl Handler<InboundServerData> for ProxyClient {
     }
 }
 
+impl Handler<BillingClaimsMessage> for ProxyClient {
+    type Result = ();
+
+    fn handle(&mut self, msg: BillingClaimsMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("ProxyClient", "BillingClaimsMessage");
+        self.billing_claims = msg
+            .claims
+            .into_iter()
+            .map(|claim| (claim.debtor, claim.gwub))
+            .collect();
+    }
+}
+
+impl Handler<GetNodeStatisticsMessage> for ProxyClient {
+    type Result = MessageResult<GetNodeStatisticsMessage>;
+
+    fn handle(&mut self, _msg: GetNodeStatisticsMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("ProxyClient", "GetNodeStatisticsMessage");
+        MessageResult(NodeStatistics {
+            active_exit_streams: self.stream_contexts.len() as u64,
+            ..NodeStatistics::default()
+        })
+    }
+}
+
 impl Handler<DnsResolveFailure> for ProxyClient {
     type Result = ();
 
     fn handle(&mut self, msg: DnsResolveFailure, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("ProxyClient", "DnsResolveFailure");
         let stream_key = msg.stream_key;
         let stream_context_opt = self.stream_contexts.get(&stream_key);
         match stream_context_opt {
@@ -206,18 +300,30 @@ impl ProxyClient {
         ProxyClient {
             dns_servers: config.dns_servers,
             resolver_wrapper_factory: Box::new(ResolverWrapperFactoryReal {}),
-            stream_handler_pool_factory: Box::new(StreamHandlerPoolFactoryReal {}),
+            stream_handler_pool_factory: Box::new(StreamHandlerPoolFactoryReal {
+                spill_config: config.spill_config,
+            }),
             cryptde: config.cryptde,
             to_hopper: None,
             to_accountant: None,
+            to_neighborhood: None,
             pool: None,
             stream_contexts: HashMap::new(),
+            billing_claims: HashMap::new(),
+            debtor_keys: HashMap::new(),
             exit_service_rate: config.exit_service_rate,
             exit_byte_rate: config.exit_byte_rate,
+            exit_guard: ExitGuard::new(config.exit_protections),
+            clock: Box::new(ClockReal {}),
             logger: Logger::new("ProxyClient"),
         }
     }
 
+    pub fn with_clock(mut self, clock: Box<dyn Clock>) -> ProxyClient {
+        self.clock = clock;
+        self
+    }
+
     pub fn make_subs_from(addr: &Addr<ProxyClient>) -> ProxyClientSubs {
         ProxyClientSubs {
             bind: addr.clone().recipient::<BindMessage>(),
@@ -226,9 +332,67 @@ impl ProxyClient {
                 .recipient::<ExpiredCoresPackage<ClientRequestPayload>>(),
             inbound_server_data: addr.clone().recipient::<InboundServerData>(),
             dns_resolve_failed: addr.clone().recipient::<DnsResolveFailure>(),
+            billing_claims: addr.clone().recipient::<BillingClaimsMessage>(),
+            node_statistics_sub: addr.clone().recipient::<GetNodeStatisticsMessage>(),
+        }
+    }
+
+    // Only the first package of a stream to somewhere else is judged; our own traffic never is
+    fn admit_stream(&mut self, payload: &ClientRequestPayload) -> bool {
+        if self.stream_contexts.contains_key(&payload.stream_key)
+            || &payload.originator_public_key == self.cryptde.public_key()
+        {
+            return true;
+        }
+        let hostname = match &payload.target_hostname {
+            Some(hostname) => hostname,
+            None => return true,
+        };
+        match self.exit_guard.admit(
+            &payload.originator_public_key,
+            hostname,
+            payload.target_port,
+            self.clock.instant(),
+        ) {
+            ExitVerdict::Allow => true,
+            ExitVerdict::Refuse(reason) => {
+                warning!(
+                    self.logger,
+                    "Refused stream {} to {}:{} from {}: {}",
+                    payload.stream_key,
+                    hostname,
+                    payload.target_port,
+                    payload.originator_public_key,
+                    reason
+                );
+                false
+            }
+            ExitVerdict::StillBlocked => {
+                debug!(
+                    self.logger,
+                    "Refused stream {} to {}:{} from {}: still blocked as a port scanner",
+                    payload.stream_key,
+                    hostname,
+                    payload.target_port,
+                    payload.originator_public_key
+                );
+                false
+            }
         }
     }
 
+    // Closes the stream at the originating end, rather than leaving its browser to time out
+    fn refuse_stream(&self, stream_key: StreamKey, stream_context: &StreamContext) {
+        let msg = InboundServerData {
+            stream_key,
+            last_data: true,
+            sequence_number: 0,
+            source: error_socket_addr(),
+            data: vec![],
+        };
+        let _ = self.send_response_to_hopper(msg, stream_context);
+    }
+
     fn send_response_to_hopper(
         &self,
         msg: InboundServerData,
@@ -238,6 +402,11 @@ impl ProxyClient {
         let msg_data_len = msg.data.len() as u32;
         let msg_source = msg.source;
         let msg_sequence_number = msg.sequence_number;
+        let span = Span::start(
+            Stage::ProxyClientResponse,
+            &msg_stream_key,
+            msg_sequence_number,
+        );
         let payload = MessageType::ClientResponse(ClientResponsePayload {
             version: ClientResponsePayload::version(),
             stream_key: msg.stream_key,
@@ -271,9 +440,49 @@ impl ProxyClient {
             .expect("Hopper unbound")
             .try_send(icp)
             .expect("Hopper is dead");
+        span.end();
         Ok(())
     }
 
+    // Goes back over the debtor's own return route, so no Node but the debtor learns what it owes
+    fn report_debtor_key(&self, consuming_wallet: Wallet, public_key: PublicKey) {
+        self.to_neighborhood
+            .as_ref()
+            .expect("Neighborhood unbound")
+            .try_send(DebtorKeyMessage {
+                consuming_wallet,
+                public_key,
+            })
+            .expect("Neighborhood is dead");
+    }
+
+    fn send_billing_claim(&self, gwub: u64, shared_wallet: bool, stream_context: &StreamContext) {
+        let icp = match IncipientCoresPackage::new(
+            self.cryptde,
+            stream_context.return_route.clone(),
+            BillingClaimPayload::new(gwub, shared_wallet).into(),
+            &stream_context.payload_destination_key,
+        ) {
+            Ok(icp) => icp,
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "Could not create CORES package for billing claim: {}", e
+                );
+                return;
+            }
+        };
+        debug!(
+            self.logger,
+            "Sending billing claim of {} gwub to {}", gwub, stream_context.payload_destination_key
+        );
+        self.to_hopper
+            .as_ref()
+            .expect("Hopper unbound")
+            .try_send(icp)
+            .expect("Hopper is dead");
+    }
+
     fn report_response_exit_to_accountant(
         &self,
         stream_context: &StreamContext,
@@ -318,12 +527,16 @@ mod tests {
     use crate::sub_lib::cryptde::CryptData;
     use crate::sub_lib::cryptde::PublicKey;
     use crate::sub_lib::hopper::MessageType;
+    use crate::sub_lib::proxy_client::BillingClaim;
     use crate::sub_lib::proxy_client::ClientResponsePayload;
+    use crate::sub_lib::proxy_client::ExitProtections;
     use crate::sub_lib::proxy_server::ClientRequestPayload;
     use crate::sub_lib::proxy_server::ProxyProtocol;
     use crate::sub_lib::route::Route;
     use crate::sub_lib::sequence_buffer::SequencedPacket;
+    use crate::sub_lib::spill_file::SpillConfig;
     use crate::sub_lib::wallet::Wallet;
+    use crate::test_utils::clock_mock::ClockMock;
     use crate::test_utils::logging::init_test_logging;
     use crate::test_utils::logging::TestLogHandler;
     use crate::test_utils::recorder::make_recorder;
@@ -338,6 +551,7 @@ mod tests {
     use std::sync::Arc;
     use std::sync::Mutex;
     use std::thread;
+    use std::time::SystemTime;
 
     fn dnss() -> Vec<SocketAddr> {
         vec![SocketAddr::from_str("8.8.8.8:53").unwrap()]
@@ -456,6 +670,8 @@ mod tests {
             dns_servers: vec![],
             exit_service_rate: 100,
             exit_byte_rate: 200,
+            exit_protections: ExitProtections::default(),
+            spill_config: SpillConfig::default(),
         });
     }
 
@@ -483,6 +699,8 @@ mod tests {
             ],
             exit_service_rate: 100,
             exit_byte_rate: 200,
+            exit_protections: ExitProtections::default(),
+            spill_config: SpillConfig::default(),
         });
         subject.resolver_wrapper_factory = Box::new(resolver_wrapper_factory);
         subject.stream_handler_pool_factory = Box::new(pool_factory);
@@ -547,6 +765,8 @@ mod tests {
             dns_servers: dnss(),
             exit_service_rate: 100,
             exit_byte_rate: 200,
+            exit_protections: ExitProtections::default(),
+            spill_config: SpillConfig::default(),
         });
         let subject_addr: Addr<ProxyClient> = subject.start();
 
@@ -569,6 +789,8 @@ mod tests {
                 dns_servers: vec![SocketAddr::from_str("1.1.1.1:53").unwrap()],
                 exit_service_rate: 0,
                 exit_byte_rate: 0,
+                exit_protections: ExitProtections::default(),
+                spill_config: SpillConfig::default(),
             });
             let subject_addr = subject.start();
             let subject_subs = ProxyClient::make_subs_from(&subject_addr);
@@ -608,6 +830,8 @@ mod tests {
                 dns_servers: vec![SocketAddr::from_str("1.1.1.1:53").unwrap()],
                 exit_service_rate: 0,
                 exit_byte_rate: 0,
+                exit_protections: ExitProtections::default(),
+                spill_config: SpillConfig::default(),
             });
             subject.stream_contexts.insert(
                 stream_key_inner,
@@ -696,6 +920,8 @@ mod tests {
             dns_servers: dnss(),
             exit_service_rate: 100,
             exit_byte_rate: 200,
+            exit_protections: ExitProtections::default(),
+            spill_config: SpillConfig::default(),
         });
         subject.resolver_wrapper_factory = Box::new(resolver_factory);
         subject.stream_handler_pool_factory = Box::new(pool_factory);
@@ -710,6 +936,65 @@ mod tests {
         assert_eq!(parameter, (request, Some(make_wallet("consuming")),));
     }
 
+    #[test]
+    fn new_paid_streams_tell_the_neighborhood_who_is_paying() {
+        let cryptde = cryptde();
+        let request = ClientRequestPayload {
+            version: ClientRequestPayload::version(),
+            stream_key: make_meaningless_stream_key(),
+            sequenced_packet: SequencedPacket {
+                data: b"inbound data".to_vec(),
+                sequence_number: 0,
+                last_data: false,
+            },
+            target_hostname: None,
+            target_port: 0,
+            protocol: ProxyProtocol::HTTP,
+            originator_public_key: PublicKey::new(&b"originator"[..]),
+        };
+        let package = ExpiredCoresPackage::new(
+            SocketAddr::from_str("1.2.3.4:1234").unwrap(),
+            Some(make_wallet("consuming")),
+            make_meaningless_route(),
+            request.clone().into(),
+            0,
+        );
+        let (neighborhood, _, neighborhood_recording_arc) = make_recorder();
+        let system = System::new("new_paid_streams_tell_the_neighborhood_who_is_paying");
+        let pool_factory =
+            StreamHandlerPoolFactoryMock::new().make_result(Box::new(StreamHandlerPoolMock::new()));
+        let resolver_factory =
+            ResolverWrapperFactoryMock::new().new_result(Box::new(ResolverWrapperMock::new()));
+        let mut subject = ProxyClient::new(ProxyClientConfig {
+            cryptde,
+            dns_servers: dnss(),
+            exit_service_rate: 100,
+            exit_byte_rate: 200,
+            exit_protections: ExitProtections::default(),
+            spill_config: SpillConfig::default(),
+        });
+        subject.resolver_wrapper_factory = Box::new(resolver_factory);
+        subject.stream_handler_pool_factory = Box::new(pool_factory);
+        let subject_addr: Addr<ProxyClient> = subject.start();
+        let peer_actors = peer_actors_builder().neighborhood(neighborhood).build();
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        subject_addr.try_send(package.clone()).unwrap();
+        subject_addr.try_send(package).unwrap();
+
+        System::current().stop_with_code(0);
+        system.run();
+        let neighborhood_recording = neighborhood_recording_arc.lock().unwrap();
+        assert_eq!(neighborhood_recording.len(), 1);
+        assert_eq!(
+            neighborhood_recording.get_record::<DebtorKeyMessage>(0),
+            &DebtorKeyMessage {
+                consuming_wallet: make_wallet("consuming"),
+                public_key: PublicKey::new(&b"originator"[..]),
+            }
+        );
+    }
+
     #[test]
     fn refuse_to_provide_exit_services_with_no_paying_wallet() {
         init_test_logging();
@@ -752,6 +1037,8 @@ mod tests {
             dns_servers: dnss(),
             exit_service_rate: rate_pack_exit(100),
             exit_byte_rate: rate_pack_exit_byte(100),
+            exit_protections: ExitProtections::default(),
+            spill_config: SpillConfig::default(),
         });
         subject.resolver_wrapper_factory = Box::new(resolver_factory);
         subject.stream_handler_pool_factory = Box::new(pool_factory);
@@ -807,6 +1094,8 @@ mod tests {
             dns_servers: dnss(),
             exit_service_rate: rate_pack_exit(100),
             exit_byte_rate: rate_pack_exit_byte(100),
+            exit_protections: ExitProtections::default(),
+            spill_config: SpillConfig::default(),
         });
         subject.resolver_wrapper_factory = Box::new(resolver_factory);
         subject.stream_handler_pool_factory = Box::new(pool_factory);
@@ -821,6 +1110,127 @@ mod tests {
         assert_eq!(parameter, (request, None,));
     }
 
+    #[test]
+    fn blocked_smtp_stream_is_refused_and_closed() {
+        init_test_logging();
+        let cryptde = cryptde();
+        let stream_key = make_meaningless_stream_key();
+        let originator_public_key = PublicKey::new(&b"originator"[..]);
+        let request = ClientRequestPayload {
+            version: ClientRequestPayload::version(),
+            stream_key: stream_key.clone(),
+            sequenced_packet: SequencedPacket {
+                data: b"HELO spammer.com\r\n".to_vec(),
+                sequence_number: 0,
+                last_data: false,
+            },
+            target_hostname: Some(String::from("mail.victim.com")),
+            target_port: 25,
+            protocol: ProxyProtocol::TLS,
+            originator_public_key: originator_public_key.clone(),
+        };
+        let package = ExpiredCoresPackage::new(
+            SocketAddr::from_str("1.2.3.4:1234").unwrap(),
+            Some(make_wallet("consuming")),
+            make_meaningless_route(),
+            request,
+            0,
+        );
+        let (hopper, _, hopper_recording_arc) = make_recorder();
+        let system = System::new("blocked_smtp_stream_is_refused_and_closed");
+        let mut process_package_parameters = Arc::new(Mutex::new(vec![]));
+        let pool = Box::new(
+            StreamHandlerPoolMock::new()
+                .process_package_parameters(&mut process_package_parameters),
+        );
+        let pool_factory = StreamHandlerPoolFactoryMock::new().make_result(pool);
+        let resolver_factory =
+            ResolverWrapperFactoryMock::new().new_result(Box::new(ResolverWrapperMock::new()));
+        let mut subject = ProxyClient::new(ProxyClientConfig {
+            cryptde,
+            dns_servers: dnss(),
+            exit_service_rate: 100,
+            exit_byte_rate: 200,
+            exit_protections: ExitProtections {
+                block_smtp: true,
+                ..ExitProtections::default()
+            },
+            spill_config: SpillConfig::default(),
+        });
+        subject.resolver_wrapper_factory = Box::new(resolver_factory);
+        subject.stream_handler_pool_factory = Box::new(pool_factory);
+        let subject_addr: Addr<ProxyClient> = subject.start();
+        let peer_actors = peer_actors_builder().hopper(hopper).build();
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        subject_addr.try_send(package).unwrap();
+
+        System::current().stop();
+        system.run();
+        assert!(process_package_parameters.lock().unwrap().is_empty());
+        let hopper_recording = hopper_recording_arc.lock().unwrap();
+        assert_eq!(
+            hopper_recording.get_record::<IncipientCoresPackage>(0),
+            &IncipientCoresPackage::new(
+                cryptde,
+                make_meaningless_route(),
+                MessageType::ClientResponse(ClientResponsePayload {
+                    version: ClientResponsePayload::version(),
+                    stream_key: stream_key.clone(),
+                    sequenced_packet: SequencedPacket {
+                        data: vec![],
+                        sequence_number: 0,
+                        last_data: true,
+                    },
+                }),
+                &originator_public_key,
+            )
+            .unwrap()
+        );
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: ProxyClient: Refused stream {} to mail.victim.com:25 from {}: outbound SMTP is blocked on this exit",
+            stream_key, originator_public_key
+        ));
+    }
+
+    #[test]
+    fn exit_fan_out_is_judged_by_the_proxy_clients_clock() {
+        let clock = ClockMock::new(SystemTime::now());
+        let mut subject = ProxyClient::new(ProxyClientConfig {
+            cryptde: cryptde(),
+            dns_servers: dnss(),
+            exit_service_rate: 100,
+            exit_byte_rate: 200,
+            exit_protections: ExitProtections {
+                fan_out_limit: 1,
+                ..ExitProtections::default()
+            },
+            spill_config: SpillConfig::default(),
+        })
+        .with_clock(Box::new(clock.clone()));
+        let payload_to = |hostname: &str| ClientRequestPayload {
+            version: ClientRequestPayload::version(),
+            stream_key: make_meaningless_stream_key(),
+            sequenced_packet: SequencedPacket {
+                data: vec![],
+                sequence_number: 0,
+                last_data: false,
+            },
+            target_hostname: Some(String::from(hostname)),
+            target_port: 443,
+            protocol: ProxyProtocol::TLS,
+            originator_public_key: PublicKey::new(&b"originator"[..]),
+        };
+        assert!(subject.admit_stream(&payload_to("one.com")));
+
+        let too_soon = subject.admit_stream(&payload_to("two.com"));
+        clock.advance(Duration::from_secs(60));
+        let a_minute_later = subject.admit_stream(&payload_to("two.com"));
+
+        assert!(!too_soon);
+        assert!(a_minute_later);
+    }
+
     #[test]
     fn inbound_server_data_is_translated_to_cores_packages() {
         init_test_logging();
@@ -834,6 +1244,8 @@ mod tests {
             dns_servers: vec![SocketAddr::from_str("8.7.6.5:4321").unwrap()],
             exit_service_rate: 100,
             exit_byte_rate: 200,
+            exit_protections: ExitProtections::default(),
+            spill_config: SpillConfig::default(),
         });
         subject.stream_contexts.insert(
             stream_key.clone(),
@@ -954,6 +1366,188 @@ mod tests {
         tlh.exists_log_containing(format!("ERROR: ProxyClient: Received InboundServerData (last_data) from 1.2.3.4:5678: stream +dKB2Lsh3ET2TS/J/cexaanFQz4, sequence 1237, length {}; but no such known stream - ignoring", data.len()).as_str());
     }
 
+    #[test]
+    fn billing_claims_go_only_to_their_debtors_and_only_once() {
+        let (hopper, hopper_awaiter, hopper_recording_arc) = make_recorder();
+        let debtor_stream_key = make_meaningless_stream_key();
+        let other_stream_key = StreamKey::new(
+            PublicKey::new(&b"other"[..]),
+            SocketAddr::from_str("5.6.7.8:9012").unwrap(),
+        );
+        let debtor_key = PublicKey::new(&b"debtor"[..]);
+        let other_key = PublicKey::new(&b"other"[..]);
+        let system = System::new("billing_claims_go_only_to_their_debtors_and_only_once");
+        let mut subject = ProxyClient::new(ProxyClientConfig {
+            cryptde: cryptde(),
+            dns_servers: vec![SocketAddr::from_str("8.7.6.5:4321").unwrap()],
+            exit_service_rate: 100,
+            exit_byte_rate: 200,
+            exit_protections: ExitProtections::default(),
+            spill_config: SpillConfig::default(),
+        });
+        subject.stream_contexts.insert(
+            debtor_stream_key.clone(),
+            StreamContext {
+                return_route: make_meaningless_route(),
+                payload_destination_key: debtor_key.clone(),
+                paying_wallet: Some(make_wallet("debtor")),
+            },
+        );
+        subject.stream_contexts.insert(
+            other_stream_key.clone(),
+            StreamContext {
+                return_route: make_meaningless_route(),
+                payload_destination_key: other_key.clone(),
+                paying_wallet: Some(make_wallet("other")),
+            },
+        );
+        let subject_addr: Addr<ProxyClient> = subject.start();
+        let peer_actors = peer_actors_builder().hopper(hopper).build();
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+        let inbound_server_data =
+            |stream_key: &StreamKey, sequence_number: u64| InboundServerData {
+                stream_key: stream_key.clone(),
+                last_data: false,
+                sequence_number,
+                source: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+                data: b"response".to_vec(),
+            };
+
+        subject_addr
+            .try_send(BillingClaimsMessage {
+                claims: vec![BillingClaim {
+                    debtor: make_wallet("debtor"),
+                    gwub: 1_234_567,
+                }],
+            })
+            .unwrap();
+        subject_addr
+            .try_send(inbound_server_data(&other_stream_key, 0))
+            .unwrap();
+        subject_addr
+            .try_send(inbound_server_data(&debtor_stream_key, 0))
+            .unwrap();
+        subject_addr
+            .try_send(inbound_server_data(&debtor_stream_key, 1))
+            .unwrap();
+
+        System::current().stop_with_code(0);
+        system.run();
+        hopper_awaiter.await_message_count(4);
+        let hopper_recording = hopper_recording_arc.lock().unwrap();
+        let billing_claim_package = IncipientCoresPackage::new(
+            cryptde(),
+            make_meaningless_route(),
+            MessageType::BillingClaim(BillingClaimPayload::new(1_234_567, false)),
+            &debtor_key,
+        )
+        .unwrap();
+        let billing_claim_packages = (0..hopper_recording.len())
+            .map(|index| hopper_recording.get_record::<IncipientCoresPackage>(index))
+            .filter(|package| *package == &billing_claim_package)
+            .count();
+        assert_eq!(billing_claim_packages, 1);
+        assert_eq!(
+            hopper_recording.get_record::<IncipientCoresPackage>(2),
+            &billing_claim_package
+        );
+        assert_eq!(hopper_recording.len(), 4);
+    }
+
+    #[test]
+    fn billing_claims_on_wallets_shared_by_several_debtors_say_so() {
+        let cryptde = cryptde();
+        let first_key = PublicKey::new(&b"first"[..]);
+        let second_key = PublicKey::new(&b"second"[..]);
+        let first_stream_key = StreamKey::new(
+            first_key.clone(),
+            SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+        );
+        let second_stream_key = StreamKey::new(
+            second_key.clone(),
+            SocketAddr::from_str("5.6.7.8:9012").unwrap(),
+        );
+        let package = |stream_key: &StreamKey, originator_public_key: &PublicKey| {
+            ExpiredCoresPackage::new(
+                SocketAddr::from_str("1.2.3.4:1234").unwrap(),
+                Some(make_wallet("shared")),
+                make_meaningless_route(),
+                ClientRequestPayload {
+                    version: ClientRequestPayload::version(),
+                    stream_key: stream_key.clone(),
+                    sequenced_packet: SequencedPacket {
+                        data: b"request".to_vec(),
+                        sequence_number: 0,
+                        last_data: false,
+                    },
+                    target_hostname: Some(String::from("target.com")),
+                    target_port: 80,
+                    protocol: ProxyProtocol::HTTP,
+                    originator_public_key: originator_public_key.clone(),
+                },
+                0,
+            )
+        };
+        let (hopper, hopper_awaiter, hopper_recording_arc) = make_recorder();
+        let system = System::new("billing_claims_on_wallets_shared_by_several_debtors_say_so");
+        let pool_factory =
+            StreamHandlerPoolFactoryMock::new().make_result(Box::new(StreamHandlerPoolMock::new()));
+        let resolver_factory =
+            ResolverWrapperFactoryMock::new().new_result(Box::new(ResolverWrapperMock::new()));
+        let mut subject = ProxyClient::new(ProxyClientConfig {
+            cryptde,
+            dns_servers: dnss(),
+            exit_service_rate: 100,
+            exit_byte_rate: 200,
+            exit_protections: ExitProtections::default(),
+            spill_config: SpillConfig::default(),
+        });
+        subject.resolver_wrapper_factory = Box::new(resolver_factory);
+        subject.stream_handler_pool_factory = Box::new(pool_factory);
+        let subject_addr: Addr<ProxyClient> = subject.start();
+        let peer_actors = peer_actors_builder().hopper(hopper).build();
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        subject_addr
+            .try_send(package(&first_stream_key, &first_key))
+            .unwrap();
+        subject_addr
+            .try_send(package(&second_stream_key, &second_key))
+            .unwrap();
+        subject_addr
+            .try_send(BillingClaimsMessage {
+                claims: vec![BillingClaim {
+                    debtor: make_wallet("shared"),
+                    gwub: 1_234_567,
+                }],
+            })
+            .unwrap();
+        subject_addr
+            .try_send(InboundServerData {
+                stream_key: first_stream_key,
+                last_data: false,
+                sequence_number: 0,
+                source: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+                data: b"response".to_vec(),
+            })
+            .unwrap();
+
+        System::current().stop_with_code(0);
+        system.run();
+        hopper_awaiter.await_message_count(2);
+        let hopper_recording = hopper_recording_arc.lock().unwrap();
+        assert_eq!(
+            hopper_recording.get_record::<IncipientCoresPackage>(1),
+            &IncipientCoresPackage::new(
+                cryptde,
+                make_meaningless_route(),
+                MessageType::BillingClaim(BillingClaimPayload::new(1_234_567, true)),
+                &first_key,
+            )
+            .unwrap()
+        );
+    }
+
     #[test]
     fn inbound_server_data_without_paying_wallet_does_not_report_exit_service() {
         init_test_logging();
@@ -966,6 +1560,8 @@ mod tests {
             dns_servers: vec![SocketAddr::from_str("8.7.6.5:4321").unwrap()],
             exit_service_rate: 100,
             exit_byte_rate: 200,
+            exit_protections: ExitProtections::default(),
+            spill_config: SpillConfig::default(),
         });
         subject.stream_contexts.insert(
             stream_key.clone(),
@@ -1016,6 +1612,8 @@ mod tests {
             dns_servers: vec![SocketAddr::from_str("8.7.6.5:4321").unwrap()],
             exit_service_rate: 100,
             exit_byte_rate: 200,
+            exit_protections: ExitProtections::default(),
+            spill_config: SpillConfig::default(),
         });
         subject.stream_contexts.insert(
             stream_key.clone(),
@@ -1064,6 +1662,8 @@ mod tests {
             dns_servers: vec![SocketAddr::from_str("8.7.6.5:4321").unwrap()],
             exit_service_rate: 100,
             exit_byte_rate: 200,
+            exit_protections: ExitProtections::default(),
+            spill_config: SpillConfig::default(),
         });
         let mut process_package_params_arc = Arc::new(Mutex::new(vec![]));
         let pool = StreamHandlerPoolMock::new()
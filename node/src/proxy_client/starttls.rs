@@ -0,0 +1,152 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::sub_lib::stream_connector::CONNECT_TIMEOUT_MS;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+// Mail servers' replies in the preamble are a few lines; anything longer isn't a mail server
+const MAX_REPLY_LENGTH: usize = 4096;
+
+// The originating Node has already played the mail server's part of the plaintext preamble for
+// the mail client, which has gone on to send its ClientHello. Here the exit plays the mail
+// client's part for the real server, so that the ClientHello arrives when the server expects it.
+pub fn negotiate_starttls(stream: &mut TcpStream) -> io::Result<()> {
+    let timeout = Some(Duration::from_millis(CONNECT_TIMEOUT_MS));
+    stream.set_read_timeout(timeout)?;
+    let result = converse(stream);
+    stream.set_read_timeout(None)?;
+    result
+}
+
+fn converse<S: Read + Write>(stream: &mut S) -> io::Result<()> {
+    expect_reply(stream, "220", "connection")?;
+    stream.write_all(b"EHLO localhost\r\n")?;
+    expect_reply(stream, "250", "EHLO")?;
+    stream.write_all(b"STARTTLS\r\n")?;
+    expect_reply(stream, "220", "STARTTLS")
+}
+
+// Reads a whole reply, one byte at a time so as not to read into the TLS that follows it
+fn expect_reply<S: Read>(stream: &mut S, code: &str, occasion: &str) -> io::Result<()> {
+    let mut reply = vec![];
+    let mut byte = [0u8];
+    loop {
+        if stream.read(&mut byte)? == 0 {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                format!("Mail server hung up instead of answering {}", occasion),
+            ));
+        }
+        reply.push(byte[0]);
+        if reply.len() > MAX_REPLY_LENGTH {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Mail server's answer to {} is too long", occasion),
+            ));
+        }
+        // A reply's last line has a space after its code; the others have a hyphen
+        if reply.ends_with(b"\r\n") {
+            let text = String::from_utf8_lossy(&reply).to_string();
+            let last_line = text.trim_end().lines().last().unwrap_or("");
+            if last_line.len() < 4 || last_line.as_bytes()[3] == b' ' {
+                return if last_line.starts_with(code) {
+                    Ok(())
+                } else {
+                    Err(io::Error::new(
+                        ErrorKind::Other,
+                        format!(
+                            "Mail server answered {} with '{}'",
+                            occasion,
+                            text.trim_end()
+                        ),
+                    ))
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    struct MailServer {
+        replies: Cursor<Vec<u8>>,
+        commands: Vec<u8>,
+    }
+
+    impl MailServer {
+        fn new(replies: &str) -> MailServer {
+            MailServer {
+                replies: Cursor::new(replies.as_bytes().to_vec()),
+                commands: vec![],
+            }
+        }
+    }
+
+    impl Read for MailServer {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.replies.read(buf)
+        }
+    }
+
+    impl Write for MailServer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.commands.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn says_ehlo_and_starttls_and_leaves_what_follows_unread() {
+        let mut server = MailServer::new(
+            "220 mail.example.com ESMTP\r\n250-mail.example.com\r\n250-PIPELINING\r\n250 STARTTLS\r\n220 Go ahead\r\n\x16\x03\x03",
+        );
+
+        let result = converse(&mut server);
+
+        assert!(result.is_ok());
+        assert_eq!(
+            String::from_utf8(server.commands).unwrap(),
+            "EHLO localhost\r\nSTARTTLS\r\n"
+        );
+        let mut rest = vec![];
+        server.replies.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"\x16\x03\x03".to_vec());
+    }
+
+    #[test]
+    fn fails_when_server_will_not_start_tls() {
+        let mut server = MailServer::new(
+            "220 mail.example.com ESMTP\r\n250 mail.example.com\r\n502 5.5.1 Unrecognized command\r\n",
+        );
+
+        let result = converse(&mut server);
+
+        let error = result.err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::Other);
+        assert_eq!(
+            error.to_string(),
+            "Mail server answered STARTTLS with '502 5.5.1 Unrecognized command'"
+        );
+    }
+
+    #[test]
+    fn fails_when_server_hangs_up() {
+        let mut server = MailServer::new("220 mail.example.com ESMTP\r\n250-mail.exa");
+
+        let result = converse(&mut server);
+
+        let error = result.err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(
+            error.to_string(),
+            "Mail server hung up instead of answering EHLO"
+        );
+    }
+}
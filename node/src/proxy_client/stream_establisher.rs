@@ -1,5 +1,7 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
+use crate::persistent_configuration::FTP_PORT;
+use crate::proxy_client::starttls::negotiate_starttls;
 use crate::proxy_client::stream_reader::StreamReader;
 use crate::proxy_client::stream_writer::StreamWriter;
 use crate::sub_lib::channel_wrappers::FuturesChannelFactory;
@@ -8,10 +10,11 @@ use crate::sub_lib::channel_wrappers::SenderWrapper;
 use crate::sub_lib::cryptde::CryptDE;
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::proxy_client::{InboundServerData, ProxyClientSubs};
-use crate::sub_lib::proxy_server::ClientRequestPayload;
+use crate::sub_lib::proxy_server::{ClientRequestPayload, ProxyProtocol};
 use crate::sub_lib::sequence_buffer::SequencedPacket;
-use crate::sub_lib::stream_connector::StreamConnector;
+use crate::sub_lib::spill_file::SpillConfig;
 use crate::sub_lib::stream_connector::StreamConnectorReal;
+use crate::sub_lib::stream_connector::{Preamble, StreamConnector};
 use crate::sub_lib::stream_key::StreamKey;
 use crate::sub_lib::tokio_wrappers::ReadHalfWrapper;
 use actix::Recipient;
@@ -29,6 +32,7 @@ pub struct StreamEstablisher {
     pub proxy_client_sub: Recipient<InboundServerData>,
     pub logger: Logger,
     pub channel_factory: Box<dyn FuturesChannelFactory<SequencedPacket>>,
+    pub spill_config: SpillConfig,
 }
 
 impl Clone for StreamEstablisher {
@@ -41,6 +45,7 @@ impl Clone for StreamEstablisher {
             proxy_client_sub: self.proxy_client_sub.clone(),
             logger: self.logger.clone(),
             channel_factory: Box::new(FuturesChannelFactoryReal {}),
+            spill_config: self.spill_config.clone(),
         }
     }
 }
@@ -56,6 +61,7 @@ impl StreamEstablisher {
             ip_addrs,
             &target_hostname,
             payload.target_port,
+            Self::preamble_for(payload.protocol),
             &self.logger,
         )?;
 
@@ -71,7 +77,8 @@ impl StreamEstablisher {
             connection_info.peer_addr,
             rx_to_write,
             payload.stream_key,
-        );
+        )
+        .with_spill(self.spill_config.clone());
         tokio::spawn(stream_writer);
 
         self.stream_adder_tx
@@ -80,6 +87,13 @@ impl StreamEstablisher {
         Ok(tx_to_write)
     }
 
+    fn preamble_for(protocol: ProxyProtocol) -> Option<Preamble> {
+        match protocol {
+            ProxyProtocol::SMTP => Some(negotiate_starttls),
+            _ => None,
+        }
+    }
+
     fn spawn_stream_reader(
         &self,
         payload: &ClientRequestPayload,
@@ -93,6 +107,13 @@ impl StreamEstablisher {
             self.stream_killer_tx.clone(),
             peer_addr,
         );
+        // Data connections are FTP too, but only the control connection has passive replies
+        let stream_reader = match payload.protocol {
+            ProxyProtocol::FTP if payload.target_port == FTP_PORT => {
+                stream_reader.with_ftp_control()
+            }
+            _ => stream_reader,
+        };
         debug!(self.logger, "Spawning StreamReader for {}", peer_addr);
         tokio::spawn(stream_reader);
         Ok(())
@@ -109,6 +130,7 @@ pub struct StreamEstablisherFactoryReal {
     pub stream_killer_tx: Sender<(StreamKey, u64)>,
     pub proxy_client_subs: ProxyClientSubs,
     pub logger: Logger,
+    pub spill_config: SpillConfig,
 }
 
 impl StreamEstablisherFactory for StreamEstablisherFactoryReal {
@@ -121,6 +143,7 @@ impl StreamEstablisherFactory for StreamEstablisherFactoryReal {
             proxy_client_sub: self.proxy_client_subs.inbound_server_data.clone(),
             logger: self.logger.clone(),
             channel_factory: Box::new(FuturesChannelFactoryReal {}),
+            spill_config: self.spill_config.clone(),
         }
     }
 }
@@ -128,7 +151,6 @@ impl StreamEstablisherFactory for StreamEstablisherFactoryReal {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::sub_lib::proxy_server::ProxyProtocol;
     use crate::test_utils::cryptde;
     use crate::test_utils::make_meaningless_stream_key;
     use crate::test_utils::recorder::make_recorder;
@@ -178,6 +200,7 @@ mod tests {
                 proxy_client_sub,
                 logger: Logger::new("ProxyClient"),
                 channel_factory: Box::new(FuturesChannelFactoryReal {}),
+                spill_config: SpillConfig::default(),
             };
             subject
                 .spawn_stream_reader(
@@ -225,4 +248,11 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn only_mail_submission_needs_a_preamble() {
+        assert!(StreamEstablisher::preamble_for(ProxyProtocol::SMTP).is_some());
+        assert!(StreamEstablisher::preamble_for(ProxyProtocol::TLS).is_none());
+        assert!(StreamEstablisher::preamble_for(ProxyProtocol::HTTP).is_none());
+    }
 }
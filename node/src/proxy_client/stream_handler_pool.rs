@@ -10,8 +10,9 @@ use crate::sub_lib::cryptde::CryptDE;
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::proxy_client::{error_socket_addr, ProxyClientSubs};
 use crate::sub_lib::proxy_client::{DnsResolveFailure, InboundServerData};
-use crate::sub_lib::proxy_server::ClientRequestPayload;
+use crate::sub_lib::proxy_server::{ClientRequestPayload, ProxyProtocol};
 use crate::sub_lib::sequence_buffer::SequencedPacket;
+use crate::sub_lib::spill_file::SpillConfig;
 use crate::sub_lib::stream_key::StreamKey;
 use crate::sub_lib::wallet::Wallet;
 use actix::Recipient;
@@ -69,6 +70,7 @@ impl StreamHandlerPoolReal {
         proxy_client_subs: ProxyClientSubs,
         exit_service_rate: u64,
         exit_byte_rate: u64,
+        spill_config: SpillConfig,
     ) -> StreamHandlerPoolReal {
         let (stream_killer_tx, stream_killer_rx) = mpsc::channel();
         let (stream_adder_tx, stream_adder_rx) = mpsc::channel();
@@ -80,6 +82,7 @@ impl StreamHandlerPoolReal {
                     stream_killer_tx,
                     proxy_client_subs: proxy_client_subs.clone(),
                     logger: Logger::new("ProxyClient"),
+                    spill_config,
                 }),
                 accountant_sub,
                 proxy_client_subs,
@@ -112,7 +115,10 @@ impl StreamHandlerPoolReal {
                 actix::spawn(future);
             }
             None => {
-                if payload.sequenced_packet.data.is_empty() {
+                // An FTP server speaks first, so an empty packet is how its client gets it talking
+                if payload.sequenced_packet.data.is_empty()
+                    && payload.protocol != ProxyProtocol::FTP
+                {
                     debug!(
                         Self::make_logger_copy(&inner_arc_1),
                         "Empty request payload received for nonexistent stream {:?} - ignoring",
@@ -462,7 +468,9 @@ pub trait StreamHandlerPoolFactory {
     ) -> Box<dyn StreamHandlerPool>;
 }
 
-pub struct StreamHandlerPoolFactoryReal {}
+pub struct StreamHandlerPoolFactoryReal {
+    pub spill_config: SpillConfig,
+}
 
 impl StreamHandlerPoolFactory for StreamHandlerPoolFactoryReal {
     fn make(
@@ -481,6 +489,7 @@ impl StreamHandlerPoolFactory for StreamHandlerPoolFactoryReal {
             proxy_client_subs,
             exit_service_rate,
             exit_byte_rate,
+            self.spill_config.clone(),
         ))
     }
 }
@@ -570,6 +579,7 @@ mod tests {
                 proxy_client_sub: peer_actors.proxy_client.inbound_server_data.clone(),
                 logger: logger.clone(),
                 channel_factory: Box::new(FuturesChannelFactoryMock::default()),
+                spill_config: SpillConfig::default(),
             };
             let inner = StreamHandlerPoolRealInner {
                 accountant_sub: peer_actors.accountant.report_exit_service_provided.clone(),
@@ -650,6 +660,7 @@ mod tests {
                 peer_actors.proxy_client.clone(),
                 100,
                 200,
+                SpillConfig::default(),
             );
             subject
                 .inner
@@ -712,6 +723,7 @@ mod tests {
                 peer_actors.proxy_client.clone(),
                 100,
                 200,
+                SpillConfig::default(),
             );
             subject
                 .inner
@@ -795,6 +807,7 @@ mod tests {
                 peer_actors.proxy_client.clone(),
                 100,
                 200,
+                SpillConfig::default(),
             );
             let (stream_killer_tx, stream_killer_rx) = mpsc::channel();
             subject.stream_killer_rx = stream_killer_rx;
@@ -814,6 +827,7 @@ mod tests {
                     proxy_client_sub: inner.proxy_client_subs.inbound_server_data.clone(),
                     logger: inner.logger.clone(),
                     channel_factory: Box::new(FuturesChannelFactoryReal {}),
+                    spill_config: SpillConfig::default(),
                 };
 
                 inner.establisher_factory = Box::new(StreamEstablisherFactoryMock {
@@ -905,6 +919,7 @@ mod tests {
                 peer_actors.proxy_client.clone(),
                 100,
                 200,
+                SpillConfig::default(),
             );
             let (stream_killer_tx, stream_killer_rx) = mpsc::channel();
             subject.stream_killer_rx = stream_killer_rx;
@@ -924,6 +939,7 @@ mod tests {
                     proxy_client_sub: inner.proxy_client_subs.inbound_server_data.clone(),
                     logger: inner.logger.clone(),
                     channel_factory: Box::new(FuturesChannelFactoryReal {}),
+                    spill_config: SpillConfig::default(),
                 };
 
                 inner.establisher_factory = Box::new(StreamEstablisherFactoryMock {
@@ -993,6 +1009,7 @@ mod tests {
                 peer_actors.proxy_client.clone(),
                 100,
                 200,
+                SpillConfig::default(),
             );
 
             run_process_package_in_actix(subject, package);
@@ -1078,6 +1095,7 @@ mod tests {
                 peer_actors.proxy_client.clone(),
                 100,
                 200,
+                SpillConfig::default(),
             );
             let (stream_killer_tx, stream_killer_rx) = mpsc::channel();
             subject.stream_killer_rx = stream_killer_rx;
@@ -1097,6 +1115,7 @@ mod tests {
                     proxy_client_sub: inner.proxy_client_subs.inbound_server_data.clone(),
                     logger: inner.logger.clone(),
                     channel_factory: Box::new(FuturesChannelFactoryReal {}),
+                    spill_config: SpillConfig::default(),
                 };
 
                 inner.establisher_factory = Box::new(StreamEstablisherFactoryMock {
@@ -1172,6 +1191,7 @@ mod tests {
                 peer_actors.proxy_client.clone(),
                 100,
                 200,
+                SpillConfig::default(),
             );
             let (stream_killer_tx, stream_killer_rx) = mpsc::channel();
             subject.stream_killer_rx = stream_killer_rx;
@@ -1187,6 +1207,7 @@ mod tests {
                 proxy_client_sub,
                 logger: subject.inner.lock().unwrap().logger.clone(),
                 channel_factory: Box::new(FuturesChannelFactoryReal {}),
+                spill_config: SpillConfig::default(),
             };
 
             subject.inner.lock().unwrap().establisher_factory =
@@ -1273,6 +1294,7 @@ mod tests {
                 peer_actors.proxy_client.clone(),
                 100,
                 200,
+                SpillConfig::default(),
             );
 
             let peer_addr = SocketAddr::from_str("3.4.5.6:80").unwrap();
@@ -1304,6 +1326,7 @@ mod tests {
                             }),
                         )],
                     }),
+                    spill_config: SpillConfig::default(),
                 };
 
                 inner.establisher_factory = Box::new(StreamEstablisherFactoryMock {
@@ -1367,6 +1390,7 @@ mod tests {
                 peer_actors.proxy_client.clone(),
                 100,
                 200,
+                SpillConfig::default(),
             );
             run_process_package_in_actix(subject, package);
         });
@@ -1436,6 +1460,7 @@ mod tests {
                 peer_actors.proxy_client.clone(),
                 100,
                 200,
+                SpillConfig::default(),
             );
             subject
                 .inner
@@ -1494,6 +1519,7 @@ mod tests {
                 peer_actors.proxy_client.clone(),
                 100,
                 200,
+                SpillConfig::default(),
             );
 
             subject.inner.lock().unwrap().establisher_factory =
@@ -1518,6 +1544,104 @@ mod tests {
         assert_eq!(hopper_recording.len(), 0);
     }
 
+    #[test]
+    fn process_package_creates_new_connection_for_zero_length_ftp_data_with_unfamiliar_stream_key()
+    {
+        let cryptde = cryptde();
+        let write_parameters = Arc::new(Mutex::new(vec![]));
+        let expected_write_parameters = write_parameters.clone();
+        let (proxy_client, proxy_client_awaiter, proxy_client_recording_arc) = make_recorder();
+        thread::spawn(move || {
+            let peer_actors = peer_actors_builder().proxy_client(proxy_client).build();
+            let client_request_payload = ClientRequestPayload {
+                version: ClientRequestPayload::version(),
+                stream_key: make_meaningless_stream_key(),
+                sequenced_packet: SequencedPacket {
+                    data: vec![],
+                    sequence_number: 0,
+                    last_data: false,
+                },
+                target_hostname: Some(String::from("3.4.5.6")),
+                target_port: 21,
+                protocol: ProxyProtocol::FTP,
+                originator_public_key: PublicKey::new(&b"booga"[..]),
+            };
+            let package = ExpiredCoresPackage::new(
+                SocketAddr::from_str("1.2.3.4:1234").unwrap(),
+                Some(make_wallet("consuming")),
+                make_meaningless_route(),
+                client_request_payload.into(),
+                0,
+            );
+            let peer_addr = SocketAddr::from_str("3.4.5.6:21").unwrap();
+            let greeting = b"220 Welcome\r\n";
+            let reader = ReadHalfWrapperMock {
+                poll_read_results: vec![
+                    (greeting.to_vec(), Ok(Async::Ready(greeting.len()))),
+                    (vec![], Err(Error::from(ErrorKind::ConnectionAborted))),
+                ],
+            };
+            let writer = WriteHalfWrapperMock {
+                poll_write_params: write_parameters,
+                poll_write_results: vec![Ok(Async::Ready(0))],
+                shutdown_results: Arc::new(Mutex::new(vec![])),
+            };
+            let mut subject = StreamHandlerPoolReal::new(
+                Box::new(ResolverWrapperMock::new()),
+                cryptde,
+                peer_actors.accountant.report_exit_service_provided.clone(),
+                peer_actors.proxy_client.clone(),
+                100,
+                200,
+                SpillConfig::default(),
+            );
+            let (stream_killer_tx, stream_killer_rx) = mpsc::channel();
+            subject.stream_killer_rx = stream_killer_rx;
+            let (stream_adder_tx, _stream_adder_rx) = mpsc::channel();
+            {
+                let mut inner = subject.inner.lock().unwrap();
+                let establisher = StreamEstablisher {
+                    cryptde,
+                    stream_adder_tx,
+                    stream_killer_tx,
+                    stream_connector: Box::new(StreamConnectorMock::new().with_connection(
+                        peer_addr.clone(),
+                        peer_addr.clone(),
+                        reader,
+                        writer,
+                    )),
+                    proxy_client_sub: inner.proxy_client_subs.inbound_server_data.clone(),
+                    logger: inner.logger.clone(),
+                    channel_factory: Box::new(FuturesChannelFactoryReal {}),
+                    spill_config: SpillConfig::default(),
+                };
+
+                inner.establisher_factory = Box::new(StreamEstablisherFactoryMock {
+                    make_results: RefCell::new(vec![establisher]),
+                });
+            }
+
+            run_process_package_in_actix(subject, package);
+        });
+
+        proxy_client_awaiter.await_message_count(1);
+        assert_eq!(
+            expected_write_parameters.lock().unwrap().remove(0),
+            Vec::<u8>::new()
+        );
+        let proxy_client_recording = proxy_client_recording_arc.lock().unwrap();
+        assert_eq!(
+            proxy_client_recording.get_record::<InboundServerData>(0),
+            &InboundServerData {
+                stream_key: make_meaningless_stream_key(),
+                last_data: false,
+                sequence_number: 0,
+                source: SocketAddr::from_str("3.4.5.6:21").unwrap(),
+                data: b"220 Welcome\r\n".to_vec(),
+            }
+        );
+    }
+
     #[test]
     fn clean_up_dead_streams_sends_server_drop_report_if_dead_stream_is_in_map() {
         let system = System::new("test");
@@ -1530,6 +1654,7 @@ mod tests {
             peer_actors.proxy_client,
             0,
             0,
+            SpillConfig::default(),
         );
         let (stream_killer_tx, stream_killer_rx) = mpsc::channel();
         subject.stream_killer_rx = stream_killer_rx;
@@ -1573,6 +1698,7 @@ mod tests {
             peer_actors.proxy_client,
             0,
             0,
+            SpillConfig::default(),
         );
         let (stream_killer_tx, stream_killer_rx) = mpsc::channel();
         subject.stream_killer_rx = stream_killer_rx;
@@ -1,4 +1,5 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::sub_lib::ftp_passive::{PassiveReply, PassiveReplyRewriter};
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::proxy_client::InboundServerData;
 use crate::sub_lib::sequencer::Sequencer;
@@ -7,6 +8,7 @@ use crate::sub_lib::tokio_wrappers::ReadHalfWrapper;
 use crate::sub_lib::utils;
 use crate::sub_lib::utils::indicates_dead_stream;
 use actix::Recipient;
+use std::net::IpAddr;
 use std::net::SocketAddr;
 use std::sync::mpsc::Sender;
 use tokio::prelude::Async;
@@ -20,6 +22,7 @@ pub struct StreamReader {
     peer_addr: SocketAddr,
     logger: Logger,
     sequencer: Sequencer,
+    passive_reply_rewriter_opt: Option<PassiveReplyRewriter>,
 }
 
 impl Future for StreamReader {
@@ -37,6 +40,7 @@ impl Future for StreamReader {
                         self.logger,
                         "Stream from {} was closed: (0-byte read)", self.peer_addr
                     );
+                    self.send_unfinished_line();
                     self.shutdown();
                     return Ok(Async::Ready(()));
                 }
@@ -51,7 +55,14 @@ impl Future for StreamReader {
                         );
                     }
                     let stream_key = self.stream_key;
-                    self.send_inbound_server_data(stream_key, Vec::from(&buf[0..len]), false);
+                    let data = if self.passive_reply_rewriter_opt.is_some() {
+                        self.pin_passive_replies(&buf[0..len])
+                    } else {
+                        Vec::from(&buf[0..len])
+                    };
+                    if !data.is_empty() {
+                        self.send_inbound_server_data(stream_key, data, false);
+                    }
                 }
                 Err(e) => {
                     if indicates_dead_stream(e.kind()) {
@@ -59,6 +70,7 @@ impl Future for StreamReader {
                             self.logger,
                             "Stream from {} was closed: {}", self.peer_addr, e
                         );
+                        self.send_unfinished_line();
                         self.shutdown();
                         return Err(());
                     } else {
@@ -92,6 +104,48 @@ impl StreamReader {
             peer_addr,
             logger: Logger::new(&format!("StreamReader for {:?}/{}", stream_key, peer_addr)[..]),
             sequencer: Sequencer::new(),
+            passive_reply_rewriter_opt: None,
+        }
+    }
+
+    pub fn with_ftp_control(mut self) -> StreamReader {
+        self.passive_reply_rewriter_opt = Some(PassiveReplyRewriter::new());
+        self
+    }
+
+    // A passive reply may name any host at all, including one the exit's operator would rather
+    // not connect to on a stranger's behalf, or a private address behind the server's NAT. The
+    // data connection goes to the server this control connection reached, whatever it says.
+    fn pin_passive_replies(&mut self, data: &[u8]) -> Vec<u8> {
+        let logger = &self.logger;
+        let peer_addr = self.peer_addr;
+        let rewriter = self
+            .passive_reply_rewriter_opt
+            .as_mut()
+            .expect("Not an FTP control stream");
+        rewriter.rewrite(data, |reply| match (reply, peer_addr.ip()) {
+            (PassiveReply::Pasv(ip, port), IpAddr::V4(peer_ip)) => {
+                if ip != peer_ip {
+                    debug!(
+                        logger,
+                        "Pinning passive reply for {}:{} to {}", ip, port, peer_ip
+                    );
+                }
+                Some(PassiveReply::Pasv(peer_ip, port))
+            }
+            (reply, _) => Some(PassiveReply::Epsv(reply.port())),
+        })
+    }
+
+    // The server's last line needn't end before the stream does
+    fn send_unfinished_line(&mut self) {
+        let line = match self.passive_reply_rewriter_opt.as_mut() {
+            Some(rewriter) => rewriter.flush(),
+            None => return,
+        };
+        if !line.is_empty() {
+            let stream_key = self.stream_key;
+            self.send_inbound_server_data(stream_key, line, false);
         }
     }
 
@@ -122,6 +176,7 @@ mod tests {
     use crate::test_utils::make_meaningless_stream_key;
     use crate::test_utils::recorder::make_recorder;
     use crate::test_utils::recorder::peer_actors_builder;
+    use crate::test_utils::tcp_stream_mock::TcpStreamMock;
     use crate::test_utils::tokio_wrapper_mocks::ReadHalfWrapperMock;
     use actix::System;
     use std::io::Error;
@@ -179,6 +234,7 @@ mod tests {
             peer_addr: SocketAddr::from_str("8.7.4.3:50").unwrap(),
             logger: Logger::new("test"),
             sequencer: Sequencer::new(),
+            passive_reply_rewriter_opt: None,
         };
 
         let _res = subject.poll();
@@ -259,6 +315,7 @@ mod tests {
             peer_addr: SocketAddr::from_str("5.7.9.0:95").unwrap(),
             logger: Logger::new("test"),
             sequencer: Sequencer::new(),
+            passive_reply_rewriter_opt: None,
         };
 
         let result = subject.poll();
@@ -304,6 +361,59 @@ mod tests {
         assert!(stream_killer_params.try_recv().is_err());
     }
 
+    #[test]
+    fn stream_reader_waits_out_a_would_block_and_stops_at_a_reset() {
+        let (proxy_client, proxy_client_awaiter, proxy_client_recording_arc) = make_recorder();
+        let (stream, _) = TcpStreamMock::new()
+            .read_chunk(b"HTTP/1.1 200 OK\r\n")
+            .read_would_block()
+            .read_chunk(b"\r\n")
+            .read_error(ErrorKind::ConnectionReset)
+            .split();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let system = System::new("test");
+            let peer_actors = peer_actors_builder().proxy_client(proxy_client).build();
+            tx.send(peer_actors.proxy_client.inbound_server_data)
+                .expect("Internal Error");
+
+            system.run();
+        });
+        let proxy_client_sub = rx.recv().unwrap();
+        let (stream_killer, stream_killer_params) = mpsc::channel();
+        let mut subject = StreamReader::new(
+            make_meaningless_stream_key(),
+            proxy_client_sub,
+            Box::new(stream),
+            stream_killer,
+            SocketAddr::from_str("5.7.9.1:95").unwrap(),
+        );
+
+        let first_result = subject.poll();
+        let second_result = subject.poll();
+
+        assert_eq!(first_result, Ok(Async::NotReady));
+        assert_eq!(second_result, Err(()));
+        proxy_client_awaiter.await_message_count(2);
+        let proxy_client_recording = proxy_client_recording_arc.lock().unwrap();
+        assert_eq!(
+            proxy_client_recording
+                .get_record::<InboundServerData>(0)
+                .data,
+            b"HTTP/1.1 200 OK\r\n".to_vec()
+        );
+        assert_eq!(
+            proxy_client_recording
+                .get_record::<InboundServerData>(1)
+                .data,
+            b"\r\n".to_vec()
+        );
+        assert_eq!(
+            stream_killer_params.try_recv().unwrap(),
+            (make_meaningless_stream_key(), 2)
+        );
+    }
+
     #[test]
     fn receiving_0_bytes_kills_stream() {
         init_test_logging();
@@ -326,6 +436,7 @@ mod tests {
             peer_addr: SocketAddr::from_str("5.3.4.3:654").unwrap(),
             logger: Logger::new("test"),
             sequencer,
+            passive_reply_rewriter_opt: None,
         };
         System::current().stop_with_code(0);
         system.run();
@@ -374,6 +485,7 @@ mod tests {
             peer_addr: SocketAddr::from_str("6.5.4.1:8325").unwrap(),
             logger: Logger::new("test"),
             sequencer: Sequencer::new(),
+            passive_reply_rewriter_opt: None,
         };
 
         let result = subject.poll();
@@ -395,4 +507,57 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn ftp_control_stream_pins_passive_replies_to_the_server() {
+        let (proxy_client, proxy_client_awaiter, proxy_client_recording_arc) = make_recorder();
+        let (stream, _) = TcpStreamMock::new()
+            .read_chunk(b"227 Entering Passive Mode (192,168,1,20,195,80)\r\n229 Entering Ext")
+            .read_chunk(b"ended Passive Mode (|||6446|)\r\n221 Good")
+            .read_error(ErrorKind::ConnectionReset)
+            .split();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let system = System::new("ftp_control_stream_pins_passive_replies_to_the_server");
+            let peer_actors = peer_actors_builder().proxy_client(proxy_client).build();
+            tx.send(peer_actors.proxy_client.inbound_server_data)
+                .expect("Internal Error");
+
+            system.run();
+        });
+        let proxy_client_sub = rx.recv().unwrap();
+        let (stream_killer, _) = mpsc::channel();
+        let mut subject = StreamReader::new(
+            make_meaningless_stream_key(),
+            proxy_client_sub,
+            Box::new(stream),
+            stream_killer,
+            SocketAddr::from_str("5.7.9.1:21").unwrap(),
+        )
+        .with_ftp_control();
+
+        let result = subject.poll();
+
+        assert_eq!(result, Err(()));
+        proxy_client_awaiter.await_message_count(3);
+        let proxy_client_recording = proxy_client_recording_arc.lock().unwrap();
+        assert_eq!(
+            proxy_client_recording
+                .get_record::<InboundServerData>(0)
+                .data,
+            b"227 Entering Passive Mode (5,7,9,1,195,80)\r\n".to_vec()
+        );
+        assert_eq!(
+            proxy_client_recording
+                .get_record::<InboundServerData>(1)
+                .data,
+            b"229 Entering Extended Passive Mode (|||6446|)\r\n".to_vec()
+        );
+        assert_eq!(
+            proxy_client_recording
+                .get_record::<InboundServerData>(2)
+                .data,
+            b"221 Good".to_vec()
+        );
+    }
 }
@@ -3,6 +3,7 @@ use crate::sub_lib::channel_wrappers::ReceiverWrapper;
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::sequence_buffer::SequenceBuffer;
 use crate::sub_lib::sequence_buffer::SequencedPacket;
+use crate::sub_lib::spill_file::SpillConfig;
 use crate::sub_lib::stream_key::StreamKey;
 use crate::sub_lib::tokio_wrappers::WriteHalfWrapper;
 use crate::sub_lib::utils::indicates_dead_stream;
@@ -32,7 +33,7 @@ impl Future for StreamWriter {
         let write_result = self.write_from_buffer_to_stream();
 
         match (read_result, write_result) {
-            (_, Err(_)) => Err(()),
+            (Err(_), _) | (_, Err(_)) => Err(()),
             (Ok(Async::NotReady), _) => Ok(Async::NotReady),
             _ => write_result,
         }
@@ -58,6 +59,11 @@ impl StreamWriter {
         }
     }
 
+    pub fn with_spill(mut self, spill_config: SpillConfig) -> StreamWriter {
+        self.sequence_buffer = SequenceBuffer::new().with_spill(spill_config);
+        self
+    }
+
     fn shutdown(&mut self) -> Result<Async<()>, ()> {
         match self.stream.shutdown() {
             Ok(Async::NotReady) => Ok(Async::NotReady),
@@ -70,7 +76,13 @@ impl StreamWriter {
         loop {
             match self.rx_to_write.poll() {
                 Ok(Async::Ready(Some(sequenced_packet))) => {
-                    self.sequence_buffer.push(sequenced_packet);
+                    if let Err(e) = self.sequence_buffer.push(sequenced_packet) {
+                        error!(
+                            self.logger,
+                            "Giving up on the stream to {}: {}", self.peer_addr, e
+                        );
+                        return Err(());
+                    }
                 }
                 Ok(Async::Ready(None)) => return Ok(Async::Ready(())),
                 Ok(Async::NotReady) => return Ok(Async::NotReady),
@@ -84,7 +96,16 @@ impl StreamWriter {
 
     fn write_from_buffer_to_stream(&mut self) -> Result<Async<()>, ()> {
         loop {
-            let packet_opt = self.sequence_buffer.poll();
+            let packet_opt = match self.sequence_buffer.poll() {
+                Ok(packet_opt) => packet_opt,
+                Err(e) => {
+                    error!(
+                        self.logger,
+                        "Giving up on the stream to {}: {}", self.peer_addr, e
+                    );
+                    return Err(());
+                }
+            };
 
             match packet_opt {
                 Some(packet) => {
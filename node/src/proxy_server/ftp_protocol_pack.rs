@@ -0,0 +1,49 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::persistent_configuration::FTP_PORT;
+use crate::proxy_server::protocol_pack::{Host, ProtocolPack, ServerImpersonator};
+use crate::proxy_server::server_impersonator_ftp::ServerImpersonatorFtp;
+use crate::sub_lib::cryptde::PlainData;
+use crate::sub_lib::proxy_server::ProxyProtocol;
+
+// FTP names no server in what the client sends, so it only travels over a --forward, which says
+// where the control connection goes. The exit pins the server's passive replies to that server,
+// and the ProxyServer turns each one into a short-lived forward for the data connection.
+pub struct FtpProtocolPack {}
+
+impl ProtocolPack for FtpProtocolPack {
+    fn proxy_protocol(&self) -> ProxyProtocol {
+        ProxyProtocol::FTP
+    }
+
+    fn standard_port(&self) -> u16 {
+        FTP_PORT
+    }
+
+    fn find_host(&self, _data: &PlainData) -> Option<Host> {
+        None
+    }
+
+    fn server_impersonator(&self) -> Box<dyn ServerImpersonator> {
+        Box::new(ServerImpersonatorFtp {})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn knows_its_protocol_and_standard_port() {
+        let subject = FtpProtocolPack {};
+
+        assert_eq!(subject.proxy_protocol(), ProxyProtocol::FTP);
+        assert_eq!(subject.standard_port(), 21);
+    }
+
+    #[test]
+    fn finds_no_host_in_ftp_commands() {
+        let result = FtpProtocolPack {}.find_host(&PlainData::new(b"USER anonymous\r\n"));
+
+        assert_eq!(result, None);
+    }
+}
@@ -12,6 +12,15 @@ lazy_static! {
     static ref HOST_PATTERN: Regex = Regex::new(r"^(?:https?://)?([^\s/]+)").expect("bad regex");
 }
 
+// The most of a request the ProxyServer will hold while framing it; past any of these, the request
+// is refused and the stream closed rather than buffered without end
+pub const MAX_REQUEST_LINE_LENGTH: usize = 8 * 1024;
+pub const MAX_HEADER_COUNT: usize = 100;
+pub const MAX_HEADERS_SIZE: usize = 64 * 1024;
+// A body with a Content-Length, or a chunk of a chunked body, is framed whole up to this size. A
+// larger one is passed along in pieces as it arrives, until what's left of it is no larger.
+pub const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
 pub struct HttpProtocolPack {}
 
 impl ProtocolPack for HttpProtocolPack {
@@ -0,0 +1,78 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::persistent_configuration::IMAPS_PORT;
+use crate::proxy_server::protocol_pack::{Host, ProtocolPack, ServerImpersonator};
+use crate::proxy_server::server_impersonator_tls::ServerImpersonatorTls;
+use crate::proxy_server::tls_protocol_pack::TlsProtocolPack;
+use crate::sub_lib::cryptde::PlainData;
+use crate::sub_lib::proxy_server::ProxyProtocol;
+
+// IMAPS is TLS from the first byte, so the mail server's name is in the ClientHello just as a
+// web server's is, and the exit has nothing to do but connect to port 993 instead of 443
+pub struct ImapsProtocolPack {}
+
+impl ProtocolPack for ImapsProtocolPack {
+    fn proxy_protocol(&self) -> ProxyProtocol {
+        ProxyProtocol::TLS
+    }
+
+    fn standard_port(&self) -> u16 {
+        IMAPS_PORT
+    }
+
+    fn find_host(&self, data: &PlainData) -> Option<Host> {
+        TlsProtocolPack {}.find_host(data)
+    }
+
+    fn server_impersonator(&self) -> Box<dyn ServerImpersonator> {
+        Box::new(ServerImpersonatorTls {})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn knows_its_protocol_and_standard_port() {
+        let subject = ImapsProtocolPack {};
+
+        assert_eq!(subject.proxy_protocol(), ProxyProtocol::TLS);
+        assert_eq!(subject.standard_port(), 993);
+    }
+
+    #[test]
+    fn finds_mail_server_in_client_hello() {
+        #[rustfmt::skip]
+        let data = PlainData::new(&[
+            0x16, // content_type: Handshake
+            0x00, 0x00, 0x00, 0x00, // version, length: don't care
+            0x01, // handshake_type: ClientHello
+            0x00, 0x00, 0x00, 0x00, 0x00, // length, version: don't care
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // random: don't care
+            0x00, // session_id_length
+            0x00, 0x00, // cipher_suites_length
+            0x00, // compression_methods_length
+            0x00, 0x13, // extensions_length
+            0x00, 0x00, // extension_type: server_name
+            0x00, 0x0F, // extension_length
+            0x00, 0x0D, // server_name_list_length
+            0x00, // server_name_type
+            0x00, 0x0A, // server_name_length
+            's' as u8, 'e' as u8, 'r' as u8, 'v' as u8, 'e' as u8, 'r' as u8, '.' as u8, 'c' as u8,
+            'o' as u8, 'm' as u8, // server_name
+        ]);
+
+        let result = ImapsProtocolPack {}.find_host(&data);
+
+        assert_eq!(
+            result,
+            Some(Host {
+                name: "server.com".to_string(),
+                port: None,
+            })
+        );
+    }
+}
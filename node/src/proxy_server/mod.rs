@@ -1,57 +1,85 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
 pub mod client_request_payload_factory;
+pub mod ftp_protocol_pack;
 pub mod http_protocol_pack;
+pub mod imaps_protocol_pack;
 pub mod protocol_pack;
+pub mod server_impersonator_ftp;
 pub mod server_impersonator_http;
 pub mod server_impersonator_tls;
+pub mod smtp_protocol_pack;
 pub mod tls_protocol_pack;
 
+use crate::bootstrapper::PortConfiguration;
+use crate::listener_handler::{ListenerHandlerFactory, ListenerHandlerFactoryReal};
+use crate::persistent_configuration::{FTP_PORT, HTTP_PORT, TLS_PORT};
 use crate::proxy_server::client_request_payload_factory::ClientRequestPayloadFactory;
 use crate::proxy_server::http_protocol_pack::HttpProtocolPack;
 use crate::proxy_server::protocol_pack::{from_ibcd, from_protocol, ProtocolPack};
+use crate::proxy_server::smtp_protocol_pack::SMTP_PREAMBLE_REPLIES;
+use crate::raw_discriminator_factory::RawDiscriminatorFactory;
 use crate::stream_messages::NonClandestineAttributes;
 use crate::stream_messages::RemovedStreamType;
+use crate::stream_messages::{AddStreamMsg, PoolBindMessage};
 use crate::sub_lib::accountant::ReportExitServiceConsumedMessage;
 use crate::sub_lib::accountant::ReportRoutingServiceConsumedMessage;
+use crate::sub_lib::accountant::{ConsumingProfile, ProfileSelector};
 use crate::sub_lib::bidi_hashmap::BidiHashMap;
+use crate::sub_lib::crash_context::handling;
 use crate::sub_lib::cryptde::CryptDE;
 use crate::sub_lib::cryptde::PublicKey;
 use crate::sub_lib::dispatcher::InboundClientData;
 use crate::sub_lib::dispatcher::{Endpoint, StreamShutdownMsg};
+use crate::sub_lib::ftp_passive::{PassiveReply, PassiveReplyRewriter};
 use crate::sub_lib::hopper::{ExpiredCoresPackage, IncipientCoresPackage};
 use crate::sub_lib::logger::Logger;
+use crate::sub_lib::memory_guard::{MemoryGuard, MEMORY_GUARD};
+use crate::sub_lib::neighborhood::BillingClaimMessage;
 use crate::sub_lib::neighborhood::RatePack;
 use crate::sub_lib::neighborhood::RouteQueryMessage;
 use crate::sub_lib::neighborhood::RouteQueryResponse;
 use crate::sub_lib::neighborhood::{ExpectedService, NodeRecordMetadataMessage};
-use crate::sub_lib::neighborhood::{ExpectedServices, DEFAULT_RATE_PACK};
+use crate::sub_lib::neighborhood::{
+    ExpectedServices, DEFAULT_MINIMUM_HOP_COUNT, DEFAULT_RATE_PACK,
+};
 use crate::sub_lib::peer_actors::BindMessage;
-use crate::sub_lib::proxy_client::{ClientResponsePayload, DnsResolveFailure};
+use crate::sub_lib::proxy_client::{BillingClaimPayload, ClientResponsePayload, DnsResolveFailure};
 use crate::sub_lib::proxy_server::ClientRequestPayload;
+use crate::sub_lib::proxy_server::ConsumingProfileExhaustedMessage;
+use crate::sub_lib::proxy_server::PassiveTunnelExpiredMessage;
+use crate::sub_lib::proxy_server::PortForward;
+use crate::sub_lib::proxy_server::ProxyProtocol;
 use crate::sub_lib::proxy_server::ProxyServerSubs;
-use crate::sub_lib::proxy_server::{AddReturnRouteMessage, AddRouteMessage};
+use crate::sub_lib::proxy_server::{AddReturnRouteMessage, AddRouteMessage, RouteFailureMessage};
 use crate::sub_lib::route::Route;
 use crate::sub_lib::set_consuming_wallet_message::SetConsumingWalletMessage;
 use crate::sub_lib::stream_handler_pool::TransmitDataMsg;
 use crate::sub_lib::stream_key::StreamKey;
+use crate::sub_lib::stream_tracing::{Span, Stage};
 use crate::sub_lib::ttl_hashmap::TtlHashMap;
+use crate::sub_lib::ui_gateway::{GetNodeStatisticsMessage, NodeStatistics};
 use crate::sub_lib::utils::NODE_MAILBOX_CAPACITY;
 use crate::sub_lib::wallet::Wallet;
 use actix::Actor;
 use actix::Addr;
+use actix::AsyncContext;
 use actix::Context;
 use actix::Handler;
+use actix::MessageResult;
 use actix::Recipient;
 use pretty_hex::PrettyHex;
-use std::collections::HashMap;
-use std::net::SocketAddr;
+use std::collections::{HashMap, HashSet};
+use std::net::{Ipv4Addr, SocketAddr};
 use std::rc::Rc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio;
 use tokio::prelude::Future;
+use tokio::timer::Delay;
 
 pub const RETURN_ROUTE_TTL: Duration = Duration::from_secs(120);
+// An FTP client connects for its data as soon as it reads the passive reply
+pub const PASSIVE_TUNNEL_LISTEN_TIME: Duration = Duration::from_secs(60);
 
 struct ProxyServerOutSubs {
     dispatcher: Recipient<TransmitDataMsg>,
@@ -62,7 +90,10 @@ struct ProxyServerOutSubs {
     update_node_record_metadata: Recipient<NodeRecordMetadataMessage>,
     add_return_route: Recipient<AddReturnRouteMessage>,
     add_route: Recipient<AddRouteMessage>,
+    route_failure: Recipient<RouteFailureMessage>,
+    passive_tunnel_expired: Recipient<PassiveTunnelExpiredMessage>,
     stream_shutdown_sub: Recipient<StreamShutdownMsg>,
+    billing_claim: Recipient<BillingClaimMessage>,
 }
 
 pub struct ProxyServer {
@@ -71,13 +102,35 @@ pub struct ProxyServer {
     stream_key_factory: Box<dyn StreamKeyFactory>,
     keys_and_addrs: BidiHashMap<StreamKey, SocketAddr>,
     tunneled_hosts: HashMap<StreamKey, String>,
+    // Keyed by the local port each forward listens on
+    port_forwards: HashMap<u16, PortForward>,
+    // Keyed by the address of each client connected to a forwarded port
+    forwarded_streams: HashMap<SocketAddr, PortForward>,
+    // Forwards opened for FTP data connections, keyed by local port like port_forwards. Each
+    // goes when its data connection closes, or when its listener stops if none was made.
+    passive_tunnels: HashMap<u16, PortForward>,
+    // Keyed by the address of each client on a forwarded FTP control connection
+    passive_reply_rewriters: HashMap<SocketAddr, PassiveReplyRewriter>,
+    listener_handler_factory: Box<dyn ListenerHandlerFactory>,
+    add_stream_sub_opt: Option<Recipient<AddStreamMsg>>,
     stream_key_routes: HashMap<StreamKey, RouteQueryResponse>,
     is_decentralized: bool,
+    minimum_hop_count: usize,
     consuming_wallet_balance: Option<i64>,
+    // With the kill switch, a route failure stops all consuming traffic until routes of the minimum
+    // hop count can be built again, rather than letting established streams carry on regardless
+    kill_switch: bool,
+    kill_switch_engaged: bool,
+    memory_guard: &'static MemoryGuard,
     cryptde: &'static dyn CryptDE,
     logger: Logger,
     route_ids_to_return_routes: TtlHashMap<u32, AddReturnRouteMessage>,
     browser_proxy_sequence_offset: bool,
+    consuming_profiles: Vec<ConsumingProfile>,
+    // Keyed by the address of each client whose connection belongs to a consuming profile
+    client_profiles: HashMap<SocketAddr, String>,
+    // Profiles whose budgets are used up; their traffic is refused
+    exhausted_profiles: HashSet<String>,
 }
 
 impl Actor for ProxyServer {
@@ -88,6 +141,7 @@ impl Handler<BindMessage> for ProxyServer {
     type Result = ();
 
     fn handle(&mut self, msg: BindMessage, ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("ProxyServer", "BindMessage");
         ctx.set_mailbox_capacity(NODE_MAILBOX_CAPACITY);
         let subs = ProxyServerOutSubs {
             dispatcher: msg.peer_actors.dispatcher.from_dispatcher_client,
@@ -98,12 +152,24 @@ impl Handler<BindMessage> for ProxyServer {
             update_node_record_metadata: msg.peer_actors.neighborhood.update_node_record_metadata,
             add_return_route: msg.peer_actors.proxy_server.add_return_route,
             add_route: msg.peer_actors.proxy_server.add_route,
+            route_failure: ctx.address().recipient::<RouteFailureMessage>(),
+            passive_tunnel_expired: ctx.address().recipient::<PassiveTunnelExpiredMessage>(),
             stream_shutdown_sub: msg.peer_actors.proxy_server.stream_shutdown_sub,
+            billing_claim: msg.peer_actors.neighborhood.billing_claim_sub,
         };
         self.subs = Some(subs);
     }
 }
 
+impl Handler<PoolBindMessage> for ProxyServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: PoolBindMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("ProxyServer", "PoolBindMessage");
+        self.add_stream_sub_opt = Some(msg.stream_handler_pool_subs.add_sub);
+    }
+}
+
 impl Handler<SetConsumingWalletMessage> for ProxyServer {
     type Result = ();
 
@@ -112,6 +178,7 @@ impl Handler<SetConsumingWalletMessage> for ProxyServer {
         _msg: SetConsumingWalletMessage,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
+        let _handling = handling("ProxyServer", "SetConsumingWalletMessage");
         self.consuming_wallet_balance = Some(0);
     }
 }
@@ -120,7 +187,33 @@ impl Handler<InboundClientData> for ProxyServer {
     type Result = ();
 
     fn handle(&mut self, msg: InboundClientData, _ctx: &mut Self::Context) -> Self::Result {
-        if msg.is_connect() {
+        let _handling = handling("ProxyServer", "InboundClientData");
+        let msg = match self.apply_consuming_profile(msg) {
+            Some(msg) => msg,
+            None => return,
+        };
+        let port_forward_opt = msg
+            .reception_port
+            .and_then(|port| {
+                self.port_forwards
+                    .get(&port)
+                    .or_else(|| self.passive_tunnels.get(&port))
+            })
+            .cloned();
+        if let Some(port_forward) = port_forward_opt {
+            // The Node can't read forwarded data; like a CONNECT tunnel's, it travels as TLS,
+            // unless it's FTP, whose control connection the exit has to read
+            let is_ftp = port_forward.remote_port == FTP_PORT
+                || self.passive_tunnels.contains_key(&port_forward.local_port);
+            self.forwarded_streams.insert(msg.peer_addr, port_forward);
+            self.handle_normal_client_data(
+                InboundClientData {
+                    reception_port: Some(if is_ftp { FTP_PORT } else { TLS_PORT }),
+                    ..msg
+                },
+                false,
+            );
+        } else if msg.is_connect() {
             self.tls_connect(&msg);
             self.browser_proxy_sequence_offset = true;
         } else {
@@ -129,10 +222,29 @@ impl Handler<InboundClientData> for ProxyServer {
     }
 }
 
+impl Handler<ConsumingProfileExhaustedMessage> for ProxyServer {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: ConsumingProfileExhaustedMessage,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let _handling = handling("ProxyServer", "ConsumingProfileExhaustedMessage");
+        warning!(
+            self.logger,
+            "Consuming profile '{}' has used up its budget; refusing its traffic",
+            msg.name
+        );
+        self.exhausted_profiles.insert(msg.name);
+    }
+}
+
 impl Handler<AddReturnRouteMessage> for ProxyServer {
     type Result = ();
 
     fn handle(&mut self, msg: AddReturnRouteMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("ProxyServer", "AddReturnRouteMessage");
         self.route_ids_to_return_routes
             .insert(msg.return_route_id, msg);
     }
@@ -157,11 +269,64 @@ impl Handler<AddRouteMessage> for ProxyServer {
     type Result = ();
 
     fn handle(&mut self, msg: AddRouteMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("ProxyServer", "AddRouteMessage");
         debug!(self.logger, "Establishing stream key {}", msg.stream_key);
+        if self.kill_switch_engaged {
+            info!(
+                self.logger,
+                "Kill switch released: {}-hop routes can be built again", DEFAULT_MINIMUM_HOP_COUNT
+            );
+            self.kill_switch_engaged = false;
+        }
         self.stream_key_routes.insert(msg.stream_key, msg.route);
     }
 }
 
+impl Handler<RouteFailureMessage> for ProxyServer {
+    type Result = ();
+
+    fn handle(&mut self, _msg: RouteFailureMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("ProxyServer", "RouteFailureMessage");
+        if !self.kill_switch || self.kill_switch_engaged {
+            return;
+        }
+        warning!(
+            self.logger,
+            "Kill switch engaged: {}-hop routes can't be built, so {} established stream{} will carry no more consuming traffic",
+            DEFAULT_MINIMUM_HOP_COUNT,
+            self.stream_key_routes.len(),
+            if self.stream_key_routes.len() == 1 { "" } else { "s" }
+        );
+        self.kill_switch_engaged = true;
+        self.stream_key_routes.clear();
+    }
+}
+
+impl Handler<PassiveTunnelExpiredMessage> for ProxyServer {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: PassiveTunnelExpiredMessage,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let _handling = handling("ProxyServer", "PassiveTunnelExpiredMessage");
+        self.close_unused_passive_tunnel(msg.local_port)
+    }
+}
+
+impl Handler<GetNodeStatisticsMessage> for ProxyServer {
+    type Result = MessageResult<GetNodeStatisticsMessage>;
+
+    fn handle(&mut self, _msg: GetNodeStatisticsMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("ProxyServer", "GetNodeStatisticsMessage");
+        MessageResult(NodeStatistics {
+            active_streams_originated: self.stream_key_routes.len() as u64,
+            ..NodeStatistics::default()
+        })
+    }
+}
+
 impl Handler<ExpiredCoresPackage<DnsResolveFailure>> for ProxyServer {
     type Result = ();
 
@@ -170,10 +335,24 @@ impl Handler<ExpiredCoresPackage<DnsResolveFailure>> for ProxyServer {
         msg: ExpiredCoresPackage<DnsResolveFailure>,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
+        let _handling = handling("ProxyServer", "ExpiredCoresPackage<DnsResolveFailure>");
         self.handle_dns_resolve_failure(&msg)
     }
 }
 
+impl Handler<ExpiredCoresPackage<BillingClaimPayload>> for ProxyServer {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: ExpiredCoresPackage<BillingClaimPayload>,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let _handling = handling("ProxyServer", "ExpiredCoresPackage<BillingClaimPayload>");
+        self.handle_billing_claim(&msg)
+    }
+}
+
 impl Handler<ExpiredCoresPackage<ClientResponsePayload>> for ProxyServer {
     type Result = ();
 
@@ -182,6 +361,7 @@ impl Handler<ExpiredCoresPackage<ClientResponsePayload>> for ProxyServer {
         msg: ExpiredCoresPackage<ClientResponsePayload>,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
+        let _handling = handling("ProxyServer", "ExpiredCoresPackage<ClientResponsePayload>");
         self.handle_client_response_payload(&msg)
     }
 }
@@ -190,6 +370,7 @@ impl Handler<StreamShutdownMsg> for ProxyServer {
     type Result = ();
 
     fn handle(&mut self, _msg: StreamShutdownMsg, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("ProxyServer", "StreamShutdownMsg");
         self.handle_stream_shutdown_msg(_msg)
     }
 }
@@ -199,23 +380,62 @@ impl ProxyServer {
         cryptde: &'static dyn CryptDE,
         is_decentralized: bool,
         consuming_wallet_balance: Option<i64>,
+        kill_switch: bool,
     ) -> ProxyServer {
+        if kill_switch && !is_decentralized {
+            panic!("A zero-hop PrometheusNode is not decentralized and cannot have a --kill-switch setting")
+        }
         ProxyServer {
             subs: None,
             client_request_payload_factory: ClientRequestPayloadFactory::new(),
             stream_key_factory: Box::new(StreamKeyFactoryReal {}),
             keys_and_addrs: BidiHashMap::new(),
             tunneled_hosts: HashMap::new(),
+            port_forwards: HashMap::new(),
+            forwarded_streams: HashMap::new(),
+            passive_tunnels: HashMap::new(),
+            passive_reply_rewriters: HashMap::new(),
+            listener_handler_factory: Box::new(ListenerHandlerFactoryReal::new()),
+            add_stream_sub_opt: None,
             stream_key_routes: HashMap::new(),
             is_decentralized,
+            minimum_hop_count: DEFAULT_MINIMUM_HOP_COUNT,
             consuming_wallet_balance,
+            kill_switch,
+            kill_switch_engaged: false,
+            memory_guard: &MEMORY_GUARD,
             cryptde,
             logger: Logger::new("ProxyServer"),
             route_ids_to_return_routes: TtlHashMap::new(RETURN_ROUTE_TTL),
             browser_proxy_sequence_offset: false,
+            consuming_profiles: vec![],
+            client_profiles: HashMap::new(),
+            exhausted_profiles: HashSet::new(),
         }
     }
 
+    // Only harnesses with fewer Nodes than a real Network should want anything but the default
+    pub fn with_minimum_hop_count(mut self, minimum_hop_count: usize) -> ProxyServer {
+        self.minimum_hop_count = minimum_hop_count;
+        self
+    }
+
+    pub fn with_port_forwards(mut self, port_forwards: Vec<PortForward>) -> ProxyServer {
+        self.port_forwards = port_forwards
+            .into_iter()
+            .map(|port_forward| (port_forward.local_port, port_forward))
+            .collect();
+        self
+    }
+
+    pub fn with_consuming_profiles(
+        mut self,
+        consuming_profiles: Vec<ConsumingProfile>,
+    ) -> ProxyServer {
+        self.consuming_profiles = consuming_profiles;
+        self
+    }
+
     pub fn make_subs_from(addr: &Addr<ProxyServer>) -> ProxyServerSubs {
         ProxyServerSubs {
             bind: addr.clone().recipient::<BindMessage>(),
@@ -226,13 +446,48 @@ impl ProxyServer {
             dns_failure_from_hopper: addr
                 .clone()
                 .recipient::<ExpiredCoresPackage<DnsResolveFailure>>(),
+            billing_claim_from_hopper: addr
+                .clone()
+                .recipient::<ExpiredCoresPackage<BillingClaimPayload>>(),
             add_return_route: addr.clone().recipient::<AddReturnRouteMessage>(),
             add_route: addr.clone().recipient::<AddRouteMessage>(),
             stream_shutdown_sub: addr.clone().recipient::<StreamShutdownMsg>(),
             set_consuming_wallet_sub: addr.clone().recipient::<SetConsumingWalletMessage>(),
+            node_statistics_sub: addr.clone().recipient::<GetNodeStatisticsMessage>(),
+            pool_bind: addr.clone().recipient::<PoolBindMessage>(),
+            profile_exhausted_sub: addr.clone().recipient::<ConsumingProfileExhaustedMessage>(),
         }
     }
 
+    // Only the exit Node of one of our routes can bill us this way, since nobody else holds a
+    // return route back to us; it gets to the Neighborhood keyed by that exit's public key.
+    fn handle_billing_claim(&mut self, msg: &ExpiredCoresPackage<BillingClaimPayload>) {
+        let return_route_info = match self.get_return_route_info(&msg.remaining_route) {
+            Some(rri) => rri,
+            None => return,
+        };
+        self.report_response_services_consumed(&return_route_info, 0, msg.payload_len, None);
+        let creditor_key = match return_route_info.find_exit_node_key() {
+            Some(key) => key.clone(),
+            None => {
+                debug!(
+                    self.logger,
+                    "Ignoring billing claim on zero-hop return route {}",
+                    return_route_info.return_route_id
+                );
+                return;
+            }
+        };
+        self.out_subs("Neighborhood")
+            .billing_claim
+            .try_send(BillingClaimMessage {
+                creditor_key,
+                claimed_gwub: msg.payload.gwub,
+                shared_wallet: msg.payload.shared_wallet,
+            })
+            .expect("Neighborhood is dead");
+    }
+
     fn handle_dns_resolve_failure(&mut self, msg: &ExpiredCoresPackage<DnsResolveFailure>) {
         let return_route_info = match self.get_return_route_info(&msg.remaining_route) {
             Some(rri) => rri,
@@ -268,7 +523,12 @@ impl ProxyServer {
                     ))
                     .expect("Neighborhood is dead");
 
-                self.report_response_services_consumed(&return_route_info, 0, msg.payload_len);
+                self.report_response_services_consumed(
+                    &return_route_info,
+                    0,
+                    msg.payload_len,
+                    self.client_profiles.get(&socket_addr).cloned(),
+                );
 
                 self.subs
                     .as_ref()
@@ -315,6 +575,11 @@ impl ProxyServer {
         );
         let payload_data_len = msg.payload_len;
         let response = &msg.payload;
+        let span = Span::start(
+            Stage::ProxyServerResponse,
+            &response.stream_key,
+            response.sequenced_packet.sequence_number,
+        );
         debug!(
             self.logger,
             "Relaying ClientResponsePayload (stream key {}, sequence {}, length {}) from Hopper to Dispatcher for client",
@@ -330,13 +595,28 @@ impl ProxyServer {
                     &return_route_info,
                     response.sequenced_packet.data.len(),
                     payload_data_len,
+                    self.client_profiles.get(&socket_addr).cloned(),
                 );
 
                 let last_data = response.sequenced_packet.last_data;
+                // A mail client on the submission port has had the Node's own replies first
+                let preamble_offset = match return_route_info.protocol {
+                    ProxyProtocol::SMTP => SMTP_PREAMBLE_REPLIES,
+                    _ => 0,
+                };
                 let sequence_number = Some(
                     response.sequenced_packet.sequence_number
-                        + self.browser_proxy_sequence_offset as u64,
+                        + self.browser_proxy_sequence_offset as u64
+                        + preamble_offset,
                 );
+                let data = match return_route_info.protocol {
+                    ProxyProtocol::FTP => self.open_passive_tunnels(
+                        socket_addr,
+                        &response.sequenced_packet.data,
+                        last_data,
+                    ),
+                    _ => response.sequenced_packet.data.clone(),
+                };
                 self
                     .subs
                     .as_ref()
@@ -346,9 +626,10 @@ impl ProxyServer {
                         endpoint: Endpoint::Socket(socket_addr),
                         last_data,
                         sequence_number,
-                        data: response.sequenced_packet.data.clone(),
+                        data,
                     })
                     .expect("Dispatcher is dead");
+                span.end();
                 if last_data {
                     debug!(self.logger, "Retiring stream key {}: no more data", &response.stream_key);
                     self.purge_stream_key(&response.stream_key);
@@ -364,6 +645,184 @@ impl ProxyServer {
         }
     }
 
+    // The exit has pinned each passive reply to the FTP server it came from. The client is told
+    // to connect to a fresh local port instead, which is forwarded to the server's data port
+    // just as the control connection's port is forwarded to its port 21.
+    fn open_passive_tunnels(
+        &mut self,
+        client_addr: SocketAddr,
+        data: &[u8],
+        last_data: bool,
+    ) -> Vec<u8> {
+        let control_host = match self.forwarded_streams.get(&client_addr) {
+            Some(port_forward) if port_forward.remote_port == FTP_PORT => {
+                port_forward.remote_host.clone()
+            }
+            _ => return data.to_vec(),
+        };
+        let mut rewriter = self
+            .passive_reply_rewriters
+            .remove(&client_addr)
+            .unwrap_or_default();
+        let mut result = rewriter.rewrite(data, |reply| {
+            let remote_host = match reply {
+                PassiveReply::Pasv(ip, _) => ip.to_string(),
+                PassiveReply::Epsv(_) => control_host.clone(),
+            };
+            match self.open_passive_tunnel(remote_host.clone(), reply.port()) {
+                Ok(local_port) => Some(match reply {
+                    PassiveReply::Pasv(_, _) => PassiveReply::Pasv(Ipv4Addr::LOCALHOST, local_port),
+                    PassiveReply::Epsv(_) => PassiveReply::Epsv(local_port),
+                }),
+                Err(e) => {
+                    error!(
+                        self.logger,
+                        "Couldn't open tunnel for FTP data to {}:{}: {}",
+                        remote_host,
+                        reply.port(),
+                        e
+                    );
+                    None
+                }
+            }
+        });
+        if last_data {
+            result.extend(rewriter.flush());
+        } else {
+            self.passive_reply_rewriters.insert(client_addr, rewriter);
+        }
+        result
+    }
+
+    // Returns the local port the FTP client should connect to
+    fn open_passive_tunnel(
+        &mut self,
+        remote_host: String,
+        remote_port: u16,
+    ) -> Result<u16, String> {
+        let add_stream_sub = self
+            .add_stream_sub_opt
+            .clone()
+            .ok_or_else(|| "StreamHandlerPool unbound in ProxyServer".to_string())?;
+        let passive_tunnel_expired_sub =
+            self.out_subs("ProxyServer").passive_tunnel_expired.clone();
+        let mut listener_handler = self.listener_handler_factory.make();
+        let local_port = listener_handler
+            .bind_free_port_and_configuration(PortConfiguration {
+                opens_on_connect: true,
+                ..PortConfiguration::new(vec![Box::new(RawDiscriminatorFactory::new())], false)
+            })
+            .map_err(|e| e.to_string())?;
+        listener_handler.bind_subs(add_stream_sub);
+        debug!(
+            self.logger,
+            "Tunnelling FTP data from local port {} to {}:{}", local_port, remote_host, remote_port
+        );
+        // The entry outlives the listener if a data connection was made, because that does
+        self.passive_tunnels.insert(
+            local_port,
+            PortForward {
+                local_port,
+                remote_host,
+                remote_port,
+            },
+        );
+        let deadline = Delay::new(Instant::now() + PASSIVE_TUNNEL_LISTEN_TIME).map_err(|_| ());
+        tokio::spawn(listener_handler.select(deadline).then(move |_| {
+            let _ = passive_tunnel_expired_sub.try_send(PassiveTunnelExpiredMessage { local_port });
+            Ok::<(), ()>(())
+        }));
+        Ok(local_port)
+    }
+
+    fn close_unused_passive_tunnel(&mut self, local_port: u16) {
+        let in_use = self
+            .forwarded_streams
+            .values()
+            .any(|port_forward| port_forward.local_port == local_port);
+        if !in_use && self.passive_tunnels.remove(&local_port).is_some() {
+            debug!(
+                self.logger,
+                "No FTP data connection came to local port {}; closing its tunnel", local_port
+            );
+        }
+    }
+
+    // Notes which consuming profile, if any, a browser connection belongs to, and refuses it if
+    // that profile's budget is used up. Data arriving on a profile's PAC ports carries on as if
+    // it had come in on the usual HTTP or TLS port.
+    fn apply_consuming_profile(&mut self, msg: InboundClientData) -> Option<InboundClientData> {
+        let reception_port = match msg.reception_port {
+            Some(port) => port,
+            None => return Some(msg),
+        };
+        let profile = match self
+            .consuming_profiles
+            .iter()
+            .find(|profile| profile.selects(reception_port, msg.peer_addr.port()))
+        {
+            Some(profile) => profile,
+            None => return Some(msg),
+        };
+        let new_reception_port = match profile.selector {
+            ProfileSelector::Pac(port) if reception_port == port => HTTP_PORT,
+            ProfileSelector::Pac(_) => TLS_PORT,
+            ProfileSelector::OriginatingPorts(_) => reception_port,
+        };
+        let name = profile.name.clone();
+        let msg = InboundClientData {
+            reception_port: Some(new_reception_port),
+            ..msg
+        };
+        if self.exhausted_profiles.contains(&name) {
+            self.refuse_exhausted_profile(msg, &name);
+            return None;
+        }
+        self.client_profiles.insert(msg.peer_addr, name);
+        Some(msg)
+    }
+
+    fn refuse_exhausted_profile(&mut self, msg: InboundClientData, profile_name: &str) {
+        let source_addr = msg.peer_addr;
+        let stream_key_opt = self.keys_and_addrs.b_to_a(&source_addr);
+        let tunneled = match stream_key_opt {
+            Some(ref stream_key) => self.tunneled_hosts.contains_key(stream_key),
+            None => false,
+        };
+        // A CONNECT request is plain HTTP, whichever port it came in on, and what follows it is TLS
+        let protocol_pack_opt = if msg.is_connect() {
+            Some(from_protocol(ProxyProtocol::HTTP))
+        } else if tunneled {
+            Some(from_protocol(ProxyProtocol::TLS))
+        } else {
+            from_ibcd(&msg, &self.logger)
+        };
+        if let Some(stream_key) = stream_key_opt {
+            self.purge_stream_key(&stream_key);
+        }
+        let _ = self.client_profiles.remove(&source_addr);
+        if let Some(protocol_pack) = protocol_pack_opt {
+            let data = protocol_pack
+                .server_impersonator()
+                .budget_exhausted(profile_name);
+            self.out_subs("Dispatcher")
+                .dispatcher
+                .try_send(TransmitDataMsg {
+                    endpoint: Endpoint::Socket(source_addr),
+                    last_data: true,
+                    sequence_number: Some(0),
+                    data,
+                })
+                .expect("Dispatcher is dead");
+        }
+        error!(
+            self.logger,
+            "Refused traffic from {}: consuming profile '{}' has used up its budget",
+            source_addr,
+            profile_name
+        );
+    }
+
     fn tls_connect(&mut self, msg: &InboundClientData) {
         let http_data = HttpProtocolPack {}.find_host(&msg.data.clone().into());
         match http_data {
@@ -413,6 +872,11 @@ impl ProxyServer {
         let add_return_route_sub = self.out_subs("ProxyServer").add_return_route.clone();
         let add_route_sub = self.out_subs("ProxyServer").add_route.clone();
         let stream_shutdown_sub = self.out_subs("ProxyServer").stream_shutdown_sub.clone();
+        let route_failure_sub_opt = if self.kill_switch {
+            Some(self.out_subs("ProxyServer").route_failure.clone())
+        } else {
+            None
+        };
         let source_addr = msg.peer_addr;
         if self.consuming_wallet_balance.is_none() && self.is_decentralized {
             let protocol_pack = match from_ibcd(&msg, &self.logger) {
@@ -435,6 +899,35 @@ impl ProxyServer {
             );
             return;
         }
+        // Streams already under way carry on; only new ones are refused
+        if self.memory_guard.refuses_consuming_streams()
+            && self.keys_and_addrs.b_to_a(&source_addr).is_none()
+        {
+            let protocol_pack = match from_ibcd(&msg, &self.logger) {
+                None => return,
+                Some(pp) => pp,
+            };
+            let data = protocol_pack.server_impersonator().memory_pressure();
+            let msg = TransmitDataMsg {
+                endpoint: Endpoint::Socket(source_addr),
+                last_data: true,
+                sequence_number: Some(0),
+                data,
+            };
+            dispatcher.try_send(msg).expect("Dispatcher is dead");
+            error!(
+                self.logger,
+                "Refused new stream from {}: this Node is short of memory", source_addr
+            );
+            return;
+        }
+        let profile_opt = self.client_profiles.get(&source_addr).cloned();
+        let profile_wallet_opt = profile_opt.as_ref().and_then(|name| {
+            self.consuming_profiles
+                .iter()
+                .find(|profile| &profile.name == name)
+                .and_then(|profile| profile.consuming_wallet_opt.clone())
+        });
         let stream_key = self.make_stream_key(&msg);
         let payload = match self.make_payload(msg, &stream_key) {
             Ok(payload) => payload,
@@ -442,8 +935,17 @@ impl ProxyServer {
                 return;
             }
         };
+        let span = Span::start(
+            Stage::ProxyServerRequest,
+            &stream_key,
+            payload.sequenced_packet.sequence_number,
+        );
         let logger = self.logger.clone();
-        let minimum_hop_count = if self.is_decentralized { 3 } else { 0 };
+        let minimum_hop_count = if self.is_decentralized {
+            self.minimum_hop_count
+        } else {
+            0
+        };
         let cryptde = self.cryptde.dup();
         match self.stream_key_routes.get(&stream_key) {
             Some(route_query_response) => {
@@ -461,6 +963,7 @@ impl ProxyServer {
                     payload,
                     logger,
                     source_addr,
+                    profile_opt,
                     &dispatcher,
                     &accountant_exit_sub,
                     &accountant_routing_sub,
@@ -472,6 +975,7 @@ impl ProxyServer {
                     },
                 )
                 .expect("Could not transmit to hopper");
+                span.end();
             }
             None => {
                 debug!(logger,
@@ -480,9 +984,10 @@ impl ProxyServer {
                 );
                 tokio::spawn(
                     route_source
-                        .send(RouteQueryMessage::data_indefinite_route_request(
-                            minimum_hop_count,
-                        ))
+                        .send(RouteQueryMessage {
+                            consuming_wallet_opt: profile_wallet_opt,
+                            ..RouteQueryMessage::data_indefinite_route_request(minimum_hop_count)
+                        })
                         .then(move |route_result| {
                             match route_result {
                                 Ok(Some(route_query_response)) => {
@@ -499,6 +1004,7 @@ impl ProxyServer {
                                         payload,
                                         logger,
                                         source_addr,
+                                        profile_opt,
                                         &dispatcher,
                                         &accountant_exit_sub,
                                         &accountant_routing_sub,
@@ -510,15 +1016,27 @@ impl ProxyServer {
                                         },
                                     )
                                     .expect("Could not transmit to hopper");
+                                    span.end();
                                 }
-                                Ok(None) => {
-                                    ProxyServer::handle_route_failure(
+                                Ok(None) => match route_failure_sub_opt {
+                                    Some(route_failure_sub) => {
+                                        route_failure_sub
+                                            .try_send(RouteFailureMessage {})
+                                            .expect("ProxyServer is dead");
+                                        ProxyServer::handle_kill_switch(
+                                            payload,
+                                            &logger,
+                                            source_addr,
+                                            &dispatcher,
+                                        );
+                                    }
+                                    None => ProxyServer::handle_route_failure(
                                         payload,
                                         &logger,
                                         source_addr,
                                         &dispatcher,
-                                    );
-                                }
+                                    ),
+                                },
                                 Err(e) => {
                                     error!(
                                         logger,
@@ -543,6 +1061,8 @@ impl ProxyServer {
         let msg_peer_addr = msg.peer_addr;
         let stream_key = match self.keys_and_addrs.b_to_a(&msg.peer_addr) {
             None => {
+                // A refused CONNECT never got a stream key
+                let _ = self.client_profiles.remove(&msg_peer_addr);
                 warning!(
                     self.logger,
                     "Received instruction to shut down nonexistent stream to peer {} - ignoring",
@@ -601,7 +1121,14 @@ impl ProxyServer {
     }
 
     fn purge_stream_key(&mut self, stream_key: &StreamKey) {
-        let _ = self.keys_and_addrs.remove_a(stream_key);
+        if let Some(socket_addr) = self.keys_and_addrs.remove_a(stream_key) {
+            let _ = self.passive_reply_rewriters.remove(&socket_addr);
+            let _ = self.client_profiles.remove(&socket_addr);
+            if let Some(port_forward) = self.forwarded_streams.remove(&socket_addr) {
+                // A passive tunnel carries a single data connection
+                let _ = self.passive_tunnels.remove(&port_forward.local_port);
+            }
+        }
         let _ = self.stream_key_routes.remove(stream_key);
         let _ = self.tunneled_hosts.remove(stream_key);
     }
@@ -612,6 +1139,7 @@ impl ProxyServer {
         stream_key: &StreamKey,
     ) -> Result<ClientRequestPayload, ()> {
         let tunnelled_host = self.tunneled_hosts.get(stream_key);
+        let port_forward_opt = self.forwarded_streams.get(&ibcd.peer_addr);
         let new_ibcd = match tunnelled_host {
             Some(_) => InboundClientData {
                 reception_port: Some(443),
@@ -629,13 +1157,19 @@ impl ProxyServer {
                 error!(self.logger, "Couldn't create ClientRequestPayload");
                 Err(())
             }
-            Some(payload) => match tunnelled_host {
-                Some(hostname) => Ok(ClientRequestPayload {
+            Some(payload) => match (tunnelled_host, port_forward_opt) {
+                (_, Some(port_forward)) => Ok(ClientRequestPayload {
+                    version: ClientRequestPayload::version(),
+                    target_hostname: Some(port_forward.remote_host.clone()),
+                    target_port: port_forward.remote_port,
+                    ..payload
+                }),
+                (Some(hostname), None) => Ok(ClientRequestPayload {
                     version: ClientRequestPayload::version(),
                     target_hostname: Some(hostname.clone()),
                     ..payload
                 }),
-                None => Ok(payload),
+                (None, None) => Ok(payload),
             },
         }
     }
@@ -647,6 +1181,7 @@ impl ProxyServer {
         payload: ClientRequestPayload,
         logger: Logger,
         source_addr: SocketAddr,
+        profile_opt: Option<String>,
         dispatcher: &Recipient<TransmitDataMsg>,
         accountant_exit_sub: &Recipient<ReportExitServiceConsumedMessage>,
         accountant_routing_sub: &Recipient<ReportRoutingServiceConsumedMessage>,
@@ -672,6 +1207,7 @@ impl ProxyServer {
                     accountant_exit_sub,
                     over.clone(),
                     &payload,
+                    profile_opt.clone(),
                     &logger,
                 );
                 ProxyServer::transmit_to_hopper(
@@ -682,6 +1218,7 @@ impl ProxyServer {
                     over.clone(),
                     &logger,
                     source_addr,
+                    profile_opt,
                     dispatcher,
                     accountant_routing_sub,
                     retire_stream_key_via,
@@ -696,6 +1233,7 @@ impl ProxyServer {
         accountant_routing_sub: &Recipient<ReportRoutingServiceConsumedMessage>,
         expected_services: Vec<ExpectedService>,
         payload_size: usize,
+        profile_opt: Option<String>,
         logger: &Logger,
     ) {
         let earning_wallets_and_rates: Vec<(&Wallet, &RatePack)> = expected_services
@@ -718,6 +1256,7 @@ impl ProxyServer {
                     payload_size,
                     service_rate: DEFAULT_RATE_PACK.routing_service_rate,
                     byte_rate: DEFAULT_RATE_PACK.routing_byte_rate,
+                    profile_opt: profile_opt.clone(),
                 };
                 accountant_routing_sub
                     .try_send(report_routing_service_consumed)
@@ -729,6 +1268,7 @@ impl ProxyServer {
         accountant_exit_sub: &Recipient<ReportExitServiceConsumedMessage>,
         expected_services: Vec<ExpectedService>,
         payload: &ClientRequestPayload,
+        profile_opt: Option<String>,
         logger: &Logger,
     ) {
         match expected_services
@@ -739,6 +1279,11 @@ impl ProxyServer {
                 }
                 _ => None,
             }) {
+            // The exit Node doesn't charge for writing an empty request, so neither do we.
+            Some(_) if payload.sequenced_packet.data.is_empty() => debug!(
+                logger,
+                "No exit service consumed by 0-byte request on stream {}", payload.stream_key
+            ),
             Some((earning_wallet, _rate_pack)) => {
                 let payload_size = payload.sequenced_packet.data.len();
                 let report_exit_service_consumed_message = ReportExitServiceConsumedMessage {
@@ -746,6 +1291,7 @@ impl ProxyServer {
                     payload_size,
                     service_rate: DEFAULT_RATE_PACK.exit_service_rate,
                     byte_rate: DEFAULT_RATE_PACK.exit_byte_rate,
+                    profile_opt: profile_opt.clone(),
                 };
                 accountant_exit_sub
                     .try_send(report_exit_service_consumed_message)
@@ -763,6 +1309,7 @@ impl ProxyServer {
         expected_services: Vec<ExpectedService>,
         logger: &Logger,
         source_addr: SocketAddr,
+        profile_opt: Option<String>,
         dispatcher: &Recipient<TransmitDataMsg>,
         accountant_routing_sub: &Recipient<ReportRoutingServiceConsumedMessage>,
         retire_stream_key_via: Option<&Recipient<StreamShutdownMsg>>,
@@ -801,6 +1348,7 @@ impl ProxyServer {
                     accountant_routing_sub,
                     expected_services,
                     pkg.payload.len(),
+                    profile_opt,
                     &logger,
                 );
                 hopper.try_send(pkg).expect("Hopper is dead");
@@ -838,6 +1386,31 @@ impl ProxyServer {
         error!(logger, "Failed to find route to {}", target_hostname);
     }
 
+    fn handle_kill_switch(
+        payload: ClientRequestPayload,
+        logger: &Logger,
+        source_addr: SocketAddr,
+        dispatcher: &Recipient<TransmitDataMsg>,
+    ) {
+        let target_hostname = ProxyServer::hostname(&payload);
+        let data = from_protocol(payload.protocol)
+            .server_impersonator()
+            .kill_switch_engaged(DEFAULT_MINIMUM_HOP_COUNT);
+        let msg = TransmitDataMsg {
+            endpoint: Endpoint::Socket(source_addr),
+            last_data: true,
+            sequence_number: Some(0),
+            data,
+        };
+        dispatcher.try_send(msg).expect("Dispatcher is dead");
+        error!(
+            logger,
+            "Kill switch refused request to {}: no {}-hop route",
+            target_hostname,
+            DEFAULT_MINIMUM_HOP_COUNT
+        );
+    }
+
     fn send_route_failure(
         payload: ClientRequestPayload,
         source_addr: SocketAddr,
@@ -888,6 +1461,7 @@ impl ProxyServer {
         return_route_info: &AddReturnRouteMessage,
         exit_size: usize,
         routing_size: usize,
+        profile_opt: Option<String>,
     ) {
         return_route_info
             .expected_services
@@ -904,6 +1478,7 @@ impl ProxyServer {
                         payload_size: exit_size,
                         service_rate: DEFAULT_RATE_PACK.exit_service_rate,
                         byte_rate: DEFAULT_RATE_PACK.exit_byte_rate,
+                        profile_opt: profile_opt.clone(),
                     })
                     .expect("Accountant is dead"),
                 ExpectedService::Routing(_, wallet, _rate_pack) => self
@@ -916,6 +1491,7 @@ impl ProxyServer {
                         payload_size: routing_size,
                         service_rate: DEFAULT_RATE_PACK.routing_service_rate,
                         byte_rate: DEFAULT_RATE_PACK.routing_byte_rate,
+                        profile_opt: profile_opt.clone(),
                     })
                     .expect("Accountant is dead"),
             });
@@ -939,6 +1515,7 @@ impl StreamKeyFactory for StreamKeyFactoryReal {
 mod tests {
     use super::*;
     use crate::blockchain::blockchain_interface::contract_address;
+    use crate::node_test_utils::make_stream_handler_pool_subs_from;
     use crate::persistent_configuration::{HTTP_PORT, TLS_PORT};
     use crate::proxy_server::protocol_pack::ServerImpersonator;
     use crate::proxy_server::server_impersonator_http::ServerImpersonatorHttp;
@@ -952,6 +1529,8 @@ mod tests {
     use crate::sub_lib::dispatcher::Component;
     use crate::sub_lib::hop::LiveHop;
     use crate::sub_lib::hopper::MessageType;
+    use crate::sub_lib::memory_guard::tests::make_memory_guard;
+    use crate::sub_lib::memory_guard::SheddingLevel;
     use crate::sub_lib::neighborhood::ExpectedServices;
     use crate::sub_lib::neighborhood::{ExpectedService, DEFAULT_RATE_PACK};
     use crate::sub_lib::proxy_client::{ClientResponsePayload, DnsResolveFailure};
@@ -969,6 +1548,8 @@ mod tests {
     use crate::test_utils::recorder::peer_actors_builder;
     use crate::test_utils::recorder::Recorder;
     use crate::test_utils::recorder::Recording;
+    use crate::test_utils::recorder::{make_dispatcher_subs_from, make_neighborhood_subs_from};
+    use crate::test_utils::tcp_stream_mock::{ListenerHandlerFactoryMock, ListenerHandlerMock};
     use crate::test_utils::zero_hop_route_response;
     use crate::test_utils::{cryptde, make_wallet};
     use crate::test_utils::{make_meaningless_route, make_paying_wallet};
@@ -1000,7 +1581,10 @@ mod tests {
                 update_node_record_metadata: addr.clone().recipient::<NodeRecordMetadataMessage>(),
                 add_return_route: addr.clone().recipient::<AddReturnRouteMessage>(),
                 add_route: addr.clone().recipient::<AddRouteMessage>(),
+                route_failure: addr.clone().recipient::<RouteFailureMessage>(),
+                passive_tunnel_expired: addr.clone().recipient::<PassiveTunnelExpiredMessage>(),
                 stream_shutdown_sub: addr.clone().recipient::<StreamShutdownMsg>(),
+                billing_claim: addr.clone().recipient::<BillingClaimMessage>(),
             }
         }
     }
@@ -1073,6 +1657,7 @@ mod tests {
         idx: usize,
         wallet: &Wallet,
         payload_size: usize,
+        profile_opt: Option<String>,
     ) {
         assert_eq!(
             accountant_recording.get_record::<ReportExitServiceConsumedMessage>(idx),
@@ -1081,6 +1666,7 @@ mod tests {
                 payload_size,
                 service_rate: DEFAULT_RATE_PACK.exit_service_rate,
                 byte_rate: DEFAULT_RATE_PACK.exit_byte_rate,
+                profile_opt,
             }
         );
     }
@@ -1090,6 +1676,7 @@ mod tests {
         idx: usize,
         wallet: &Wallet,
         payload_size: usize,
+        profile_opt: Option<String>,
     ) {
         assert_eq!(
             accountant_recording.get_record::<ReportRoutingServiceConsumedMessage>(idx),
@@ -1098,6 +1685,7 @@ mod tests {
                 payload_size,
                 service_rate: DEFAULT_RATE_PACK.routing_service_rate,
                 byte_rate: DEFAULT_RATE_PACK.routing_byte_rate,
+                profile_opt,
             }
         );
     }
@@ -1150,8 +1738,12 @@ mod tests {
                 .make_parameters(&make_parameters_arc)
                 .make_result(stream_key);
             let system = System::new("proxy_server_receives_http_request_from_dispatcher_then_sends_cores_package_to_hopper");
-            let mut subject =
-                ProxyServer::new(cryptde, false, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+            let mut subject = ProxyServer::new(
+                cryptde,
+                false,
+                Some(STANDARD_CONSUMING_WALLET_BALANCE),
+                false,
+            );
             subject.stream_key_factory = Box::new(stream_key_factory);
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
@@ -1251,8 +1843,12 @@ mod tests {
             let system = System::new(
                 "proxy_server_receives_connect_responds_with_ok_and_stores_stream_key_and_hostname",
             );
-            let mut subject =
-                ProxyServer::new(cryptde, false, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+            let mut subject = ProxyServer::new(
+                cryptde,
+                false,
+                Some(STANDARD_CONSUMING_WALLET_BALANCE),
+                false,
+            );
             subject.stream_key_factory = Box::new(stream_key_factory);
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
@@ -1297,7 +1893,12 @@ mod tests {
         let system = System::new("handle_client_response_payload_increments_sequence_number_when_browser_proxy_sequence_offset_is_true");
         let (dispatcher_mock, _, dispatcher_log_arc) = make_recorder();
         let cryptde = cryptde();
-        let mut subject = ProxyServer::new(cryptde, false, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+        let mut subject = ProxyServer::new(
+            cryptde,
+            false,
+            Some(STANDARD_CONSUMING_WALLET_BALANCE),
+            false,
+        );
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let stream_key = make_meaningless_stream_key();
         subject
@@ -1363,42 +1964,245 @@ mod tests {
     }
 
     #[test]
-    fn proxy_server_sends_route_failure_for_connect_requests_to_ports_other_than_443() {
+    fn handle_client_response_payload_numbers_mail_server_responses_after_the_nodes_own_smtp_replies(
+    ) {
+        let system = System::new(
+            "handle_client_response_payload_numbers_mail_server_responses_after_the_nodes_own_smtp_replies",
+        );
+        let (dispatcher_mock, _, dispatcher_log_arc) = make_recorder();
         let cryptde = cryptde();
-        let http_request = b"CONNECT https://realdomain.nu:8443 HTTP/1.1\r\nHost: https://bunkjunk.wrong:443\r\n\r\n";
-
-        let (hopper_mock, _hopper_awaiter, _hopper_recording_arc) = make_recorder();
-        let (neighborhood_mock, _, _neighborhood_recording_arc) = make_recorder();
-        let (dispatcher_mock, _dispatcher_awaiter, dispatcher_recording_arc) = make_recorder();
-
-        let neighborhood_mock = neighborhood_mock.route_query_response(Some(
-            zero_hop_route_response(&cryptde.public_key(), cryptde),
-        ));
-
+        let mut subject = ProxyServer::new(
+            cryptde,
+            false,
+            Some(STANDARD_CONSUMING_WALLET_BALANCE),
+            false,
+        );
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let stream_key = make_meaningless_stream_key();
-        let request_data = http_request.to_vec();
-        let msg_from_dispatcher = InboundClientData {
-            peer_addr: socket_addr.clone(),
-            reception_port: Some(8443),
-            sequence_number: Some(0),
-            last_data: false,
-            is_clandestine: false,
-            data: request_data.clone(),
-        };
+        subject
+            .keys_and_addrs
+            .insert(stream_key.clone(), socket_addr.clone());
+        subject.route_ids_to_return_routes.insert(
+            1234,
+            AddReturnRouteMessage {
+                return_route_id: 1234,
+                expected_services: vec![ExpectedService::Nothing],
+                protocol: ProxyProtocol::SMTP,
+                server_name: None,
+            },
+        );
+        let subject_addr: Addr<ProxyServer> = subject.start();
+        let expired_cores_package: ExpiredCoresPackage<ClientResponsePayload> =
+            ExpiredCoresPackage::new(
+                SocketAddr::from_str("1.2.3.4:1234").unwrap(),
+                Some(make_wallet("irrelevant")),
+                return_route_with_id(cryptde, 1234),
+                ClientResponsePayload {
+                    version: ClientResponsePayload::version(),
+                    stream_key,
+                    sequenced_packet: SequencedPacket {
+                        data: b"server hello".to_vec(),
+                        sequence_number: 0,
+                        last_data: false,
+                    },
+                }
+                .into(),
+                0,
+            );
+        let mut peer_actors = peer_actors_builder().dispatcher(dispatcher_mock).build();
+        peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
 
-        let stream_key_parameters_arc = Arc::new(Mutex::new(vec![]));
-        let stream_key_parameters_arc_thread = stream_key_parameters_arc.clone();
+        subject_addr.try_send(expired_cores_package).unwrap();
 
-        thread::spawn(move || {
-            let stream_key_factory = StreamKeyFactoryMock::new()
+        System::current().stop();
+        system.run();
+        let dispatcher_recording = dispatcher_log_arc.lock().unwrap();
+        let record = dispatcher_recording.get_record::<TransmitDataMsg>(0);
+        assert_eq!(record.sequence_number, Some(SMTP_PREAMBLE_REPLIES));
+        assert_eq!(record.data, b"server hello".to_vec());
+    }
+
+    #[test]
+    fn handle_client_response_payload_tunnels_ftp_passive_reply_through_a_local_port() {
+        let system = System::new(
+            "handle_client_response_payload_tunnels_ftp_passive_reply_through_a_local_port",
+        );
+        let (dispatcher_mock, _, dispatcher_log_arc) = make_recorder();
+        let (pool, _, _) = make_recorder();
+        let cryptde = cryptde();
+        let control_forward = PortForward {
+            local_port: 2121,
+            remote_host: "ftp.example.com".to_string(),
+            remote_port: 21,
+        };
+        let mut subject = ProxyServer::new(
+            cryptde,
+            false,
+            Some(STANDARD_CONSUMING_WALLET_BALANCE),
+            false,
+        )
+        .with_port_forwards(vec![control_forward.clone()]);
+        subject.listener_handler_factory = Box::new(
+            ListenerHandlerFactoryMock::new()
+                .make_result(ListenerHandlerMock::new().free_port(50123)),
+        );
+        let socket_addr = SocketAddr::from_str("127.0.0.1:5678").unwrap();
+        let stream_key = make_meaningless_stream_key();
+        subject
+            .keys_and_addrs
+            .insert(stream_key.clone(), socket_addr.clone());
+        subject
+            .forwarded_streams
+            .insert(socket_addr.clone(), control_forward);
+        subject.route_ids_to_return_routes.insert(
+            1234,
+            AddReturnRouteMessage {
+                return_route_id: 1234,
+                expected_services: vec![ExpectedService::Nothing],
+                protocol: ProxyProtocol::FTP,
+                server_name: None,
+            },
+        );
+        let subject_addr: Addr<ProxyServer> = subject.start();
+        let expired_cores_package = |data: &[u8], sequence_number: u64| {
+            ExpiredCoresPackage::<ClientResponsePayload>::new(
+                SocketAddr::from_str("1.2.3.4:1234").unwrap(),
+                Some(make_wallet("irrelevant")),
+                return_route_with_id(cryptde, 1234),
+                ClientResponsePayload {
+                    version: ClientResponsePayload::version(),
+                    stream_key,
+                    sequenced_packet: SequencedPacket {
+                        data: data.to_vec(),
+                        sequence_number,
+                        last_data: false,
+                    },
+                }
+                .into(),
+                0,
+            )
+        };
+        let mut peer_actors = peer_actors_builder().dispatcher(dispatcher_mock).build();
+        peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+        let pool_addr = pool.start();
+        subject_addr
+            .try_send(PoolBindMessage {
+                dispatcher_subs: make_dispatcher_subs_from(&pool_addr),
+                stream_handler_pool_subs: make_stream_handler_pool_subs_from(None),
+                neighborhood_subs: make_neighborhood_subs_from(&pool_addr),
+            })
+            .unwrap();
+
+        subject_addr
+            .try_send(expired_cores_package(b"227 Entering Passive Mode (5,7,", 3))
+            .unwrap();
+        subject_addr
+            .try_send(expired_cores_package(b"9,1,195,80)\r\n", 4))
+            .unwrap();
+
+        System::current().stop();
+        system.run();
+        let dispatcher_recording = dispatcher_log_arc.lock().unwrap();
+        let record = dispatcher_recording.get_record::<TransmitDataMsg>(0);
+        assert_eq!(record.sequence_number, Some(3));
+        assert_eq!(record.data, b"".to_vec());
+        let record = dispatcher_recording.get_record::<TransmitDataMsg>(1);
+        assert_eq!(record.sequence_number, Some(4));
+        assert_eq!(
+            record.data,
+            PassiveReply::Pasv(Ipv4Addr::LOCALHOST, 50123).to_line()
+        );
+    }
+
+    #[test]
+    fn passive_tunnel_that_no_data_connection_came_to_is_closed_when_its_listener_stops() {
+        let mut subject = ProxyServer::new(cryptde(), false, None, false);
+        let unused_tunnel = PortForward {
+            local_port: 50123,
+            remote_host: "5.7.9.1".to_string(),
+            remote_port: 50000,
+        };
+        let used_tunnel = PortForward {
+            local_port: 50124,
+            remote_host: "5.7.9.1".to_string(),
+            remote_port: 50001,
+        };
+        subject.passive_tunnels.insert(50123, unused_tunnel);
+        subject.passive_tunnels.insert(50124, used_tunnel.clone());
+        subject
+            .forwarded_streams
+            .insert(SocketAddr::from_str("127.0.0.1:5678").unwrap(), used_tunnel);
+
+        subject.close_unused_passive_tunnel(50123);
+        subject.close_unused_passive_tunnel(50124);
+
+        assert!(!subject.passive_tunnels.contains_key(&50123));
+        assert!(subject.passive_tunnels.contains_key(&50124));
+    }
+
+    #[test]
+    fn passive_tunnel_is_closed_with_its_data_connection() {
+        let mut subject = ProxyServer::new(cryptde(), false, None, false);
+        let tunnel = PortForward {
+            local_port: 50123,
+            remote_host: "5.7.9.1".to_string(),
+            remote_port: 50000,
+        };
+        let socket_addr = SocketAddr::from_str("127.0.0.1:5678").unwrap();
+        let stream_key = make_meaningless_stream_key();
+        subject.keys_and_addrs.insert(stream_key, socket_addr);
+        subject.passive_tunnels.insert(50123, tunnel.clone());
+        subject.forwarded_streams.insert(socket_addr, tunnel);
+
+        subject.purge_stream_key(&stream_key);
+
+        assert!(subject.forwarded_streams.is_empty());
+        assert!(subject.passive_tunnels.is_empty());
+    }
+
+    #[test]
+    fn proxy_server_sends_route_failure_for_connect_requests_to_ports_other_than_443() {
+        let cryptde = cryptde();
+        let http_request = b"CONNECT https://realdomain.nu:8443 HTTP/1.1\r\nHost: https://bunkjunk.wrong:443\r\n\r\n";
+
+        let (hopper_mock, _hopper_awaiter, _hopper_recording_arc) = make_recorder();
+        let (neighborhood_mock, _, _neighborhood_recording_arc) = make_recorder();
+        let (dispatcher_mock, _dispatcher_awaiter, dispatcher_recording_arc) = make_recorder();
+
+        let neighborhood_mock = neighborhood_mock.route_query_response(Some(
+            zero_hop_route_response(&cryptde.public_key(), cryptde),
+        ));
+
+        let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let stream_key = make_meaningless_stream_key();
+        let request_data = http_request.to_vec();
+        let msg_from_dispatcher = InboundClientData {
+            peer_addr: socket_addr.clone(),
+            reception_port: Some(8443),
+            sequence_number: Some(0),
+            last_data: false,
+            is_clandestine: false,
+            data: request_data.clone(),
+        };
+
+        let stream_key_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let stream_key_parameters_arc_thread = stream_key_parameters_arc.clone();
+
+        thread::spawn(move || {
+            let stream_key_factory = StreamKeyFactoryMock::new()
                 .make_parameters(&stream_key_parameters_arc_thread)
                 .make_result(stream_key);
             let system = System::new(
                 "proxy_server_receives_connect_responds_with_ok_and_stores_stream_key_and_hostname",
             );
-            let mut subject =
-                ProxyServer::new(cryptde, false, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+            let mut subject = ProxyServer::new(
+                cryptde,
+                false,
+                Some(STANDARD_CONSUMING_WALLET_BALANCE),
+                false,
+            );
             subject.stream_key_factory = Box::new(stream_key_factory);
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
@@ -1463,8 +2267,12 @@ mod tests {
             let system = System::new(
                 "proxy_server_receives_connect_responds_with_ok_and_stores_stream_key_and_hostname",
             );
-            let mut subject =
-                ProxyServer::new(cryptde, false, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+            let mut subject = ProxyServer::new(
+                cryptde,
+                false,
+                Some(STANDARD_CONSUMING_WALLET_BALANCE),
+                false,
+            );
             subject.stream_key_factory = Box::new(stream_key_factory);
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
@@ -1516,7 +2324,7 @@ mod tests {
         };
         let stream_key_factory = StreamKeyFactoryMock::new(); // can't make any stream keys; shouldn't have to
         let system = System::new("proxy_server_receives_http_request_with_no_consuming_wallet_and_sends_impersonated_response");
-        let mut subject = ProxyServer::new(cryptde, true, None);
+        let mut subject = ProxyServer::new(cryptde, true, None, false);
         subject.stream_key_factory = Box::new(stream_key_factory);
         subject.keys_and_addrs.insert(stream_key, socket_addr);
         let subject_addr: Addr<ProxyServer> = subject.start();
@@ -1553,6 +2361,63 @@ mod tests {
         );
     }
 
+    #[test]
+    fn proxy_server_refuses_new_streams_under_memory_pressure_and_sends_impersonated_response() {
+        init_test_logging();
+        let cryptde = cryptde();
+        let http_request = b"GET /index.html HTTP/1.1\r\nHost: nowhere.com\r\n\r\n";
+        let (hopper, _, hopper_log_arc) = make_recorder();
+        let (neighborhood, _, neighborhood_log_arc) = make_recorder();
+        let (dispatcher, _, dispatcher_log_arc) = make_recorder();
+        let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let msg_from_dispatcher = InboundClientData {
+            peer_addr: socket_addr.clone(),
+            reception_port: Some(HTTP_PORT),
+            sequence_number: Some(0),
+            last_data: true,
+            is_clandestine: false,
+            data: http_request.to_vec(),
+        };
+        let stream_key_factory = StreamKeyFactoryMock::new(); // can't make any stream keys; shouldn't have to
+        let system = System::new(
+            "proxy_server_refuses_new_streams_under_memory_pressure_and_sends_impersonated_response",
+        );
+        let mut subject = ProxyServer::new(cryptde, true, Some(0), false);
+        subject.stream_key_factory = Box::new(stream_key_factory);
+        subject.memory_guard = make_memory_guard(SheddingLevel::RefuseConsumingStreams);
+        let subject_addr: Addr<ProxyServer> = subject.start();
+        let mut peer_actors = peer_actors_builder()
+            .dispatcher(dispatcher)
+            .hopper(hopper)
+            .neighborhood(neighborhood)
+            .build();
+        peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        subject_addr.try_send(msg_from_dispatcher).unwrap();
+
+        System::current().stop();
+        system.run();
+        let neighborhood_recording = neighborhood_log_arc.lock().unwrap();
+        assert!(neighborhood_recording.is_empty());
+        let hopper_recording = hopper_log_arc.lock().unwrap();
+        assert!(hopper_recording.is_empty());
+        let dispatcher_recording = dispatcher_log_arc.lock().unwrap();
+        let record = dispatcher_recording.get_record::<TransmitDataMsg>(0);
+        assert_eq!(
+            record,
+            &TransmitDataMsg {
+                endpoint: Endpoint::Socket(socket_addr),
+                last_data: true,
+                sequence_number: Some(0),
+                data: ServerImpersonatorHttp {}.memory_pressure(),
+            }
+        );
+        TestLogHandler::new().exists_log_containing(
+            "ERROR: ProxyServer: Refused new stream from 1.2.3.4:5678: this Node is short of memory",
+        );
+    }
+
     #[test]
     fn proxy_server_receives_tls_request_with_no_consuming_wallet_and_sends_impersonated_response()
     {
@@ -1575,7 +2440,7 @@ mod tests {
         };
         let stream_key_factory = StreamKeyFactoryMock::new(); // can't make any stream keys; shouldn't have to
         let system = System::new("proxy_server_receives_tls_request_with_no_consuming_wallet_and_sends_impersonated_response");
-        let mut subject = ProxyServer::new(cryptde, true, None);
+        let mut subject = ProxyServer::new(cryptde, true, None, false);
         subject.stream_key_factory = Box::new(stream_key_factory);
         subject.keys_and_addrs.insert(stream_key, socket_addr);
         let subject_addr: Addr<ProxyServer> = subject.start();
@@ -1637,7 +2502,7 @@ mod tests {
             };
             let stream_key_factory = StreamKeyFactoryMock::new(); // can't make any stream keys; shouldn't have to
             let system = System::new("proxy_server_receives_http_request_with_no_consuming_wallet_in_zero_hop_mode_and_handles_normally");
-            let mut subject = ProxyServer::new(cryptde, false, None);
+            let mut subject = ProxyServer::new(cryptde, false, None, false);
             subject.stream_key_factory = Box::new(stream_key_factory);
             subject.keys_and_addrs.insert(stream_key, socket_addr);
             let subject_addr: Addr<ProxyServer> = subject.start();
@@ -1661,7 +2526,8 @@ mod tests {
                 target_key_opt: None,
                 target_component: Component::ProxyClient,
                 minimum_hop_count: 0,
-                return_component_opt: Some(Component::ProxyServer)
+                return_component_opt: Some(Component::ProxyServer),
+                consuming_wallet_opt: None,
             }
         );
         let dispatcher_recording = dispatcher_log_arc.lock().unwrap();
@@ -1712,7 +2578,7 @@ mod tests {
             };
             let stream_key_factory = StreamKeyFactoryMock::new(); // can't make any stream keys; shouldn't have to
             let system = System::new("proxy_server_receives_tls_request_with_no_consuming_wallet_in_zero_hop_mode_and_handles_normally");
-            let mut subject = ProxyServer::new(cryptde, false, None);
+            let mut subject = ProxyServer::new(cryptde, false, None, false);
             subject.stream_key_factory = Box::new(stream_key_factory);
             subject.keys_and_addrs.insert(stream_key, socket_addr);
             let subject_addr: Addr<ProxyServer> = subject.start();
@@ -1736,7 +2602,8 @@ mod tests {
                 target_key_opt: None,
                 target_component: Component::ProxyClient,
                 minimum_hop_count: 0,
-                return_component_opt: Some(Component::ProxyServer)
+                return_component_opt: Some(Component::ProxyServer),
+                consuming_wallet_opt: None,
             }
         );
         let dispatcher_recording = dispatcher_log_arc.lock().unwrap();
@@ -1806,8 +2673,12 @@ mod tests {
         thread::spawn(move || {
             let stream_key_factory = StreamKeyFactoryMock::new(); // can't make any stream keys; shouldn't have to
             let system = System::new("proxy_server_receives_http_request_from_dispatcher_then_sends_cores_package_to_hopper");
-            let mut subject =
-                ProxyServer::new(cryptde, false, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+            let mut subject = ProxyServer::new(
+                cryptde,
+                false,
+                Some(STANDARD_CONSUMING_WALLET_BALANCE),
+                false,
+            );
             subject.stream_key_factory = Box::new(stream_key_factory);
             subject.keys_and_addrs.insert(stream_key, socket_addr);
             let subject_addr: Addr<ProxyServer> = subject.start();
@@ -1872,7 +2743,7 @@ mod tests {
         thread::spawn(move || {
             let stream_key_factory = StreamKeyFactoryMock::new(); // can't make any stream keys; shouldn't have to
             let system = System::new("proxy_server_applies_late_wallet_information");
-            let mut subject = ProxyServer::new(cryptde, false, None);
+            let mut subject = ProxyServer::new(cryptde, false, None, false);
             subject.stream_key_factory = Box::new(stream_key_factory);
             subject.keys_and_addrs.insert(stream_key, socket_addr);
             let subject_addr: Addr<ProxyServer> = subject.start();
@@ -1990,8 +2861,12 @@ mod tests {
         thread::spawn(move || {
             let stream_key_factory = StreamKeyFactoryMock::new().make_result(stream_key);
             let system = System::new("proxy_server_receives_http_request_from_dispatcher_then_sends_cores_package_to_hopper");
-            let mut subject =
-                ProxyServer::new(cryptde, true, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+            let mut subject = ProxyServer::new(
+                cryptde,
+                true,
+                Some(STANDARD_CONSUMING_WALLET_BALANCE),
+                false,
+            );
             subject.stream_key_factory = Box::new(stream_key_factory);
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
@@ -2015,6 +2890,47 @@ mod tests {
         assert_eq!(record, &RouteQueryMessage::data_indefinite_route_request(3));
     }
 
+    #[test]
+    fn proxy_server_asks_for_routes_of_its_minimum_hop_count() {
+        let cryptde = cryptde();
+        let (neighborhood_mock, neighborhood_awaiter, neighborhood_recording_arc) = make_recorder();
+        let neighborhood_mock = neighborhood_mock.route_query_response(None);
+        let msg_from_dispatcher = InboundClientData {
+            peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+            reception_port: Some(HTTP_PORT),
+            sequence_number: Some(0),
+            last_data: true,
+            is_clandestine: false,
+            data: b"GET /index.html HTTP/1.1\r\nHost: nowhere.com\r\n\r\n".to_vec(),
+        };
+
+        thread::spawn(move || {
+            let system = System::new("proxy_server_asks_for_routes_of_its_minimum_hop_count");
+            let subject = ProxyServer::new(
+                cryptde,
+                true,
+                Some(STANDARD_CONSUMING_WALLET_BALANCE),
+                false,
+            )
+            .with_minimum_hop_count(2);
+            let subject_addr: Addr<ProxyServer> = subject.start();
+            let mut peer_actors = peer_actors_builder()
+                .neighborhood(neighborhood_mock)
+                .build();
+            peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            subject_addr.try_send(msg_from_dispatcher).unwrap();
+
+            system.run();
+        });
+
+        neighborhood_awaiter.await_message_count(1);
+        let recording = neighborhood_recording_arc.lock().unwrap();
+        let record = recording.get_record::<RouteQueryMessage>(0);
+        assert_eq!(record, &RouteQueryMessage::data_indefinite_route_request(2));
+    }
+
     #[test]
     fn proxy_server_adds_route_for_stream_key() {
         let cryptde = cryptde();
@@ -2042,8 +2958,12 @@ mod tests {
         thread::spawn(move || {
             let stream_key_factory = StreamKeyFactoryMock::new().make_result(stream_key);
             let system = System::new("proxy_server_adds_route_for_stream_key");
-            let mut subject =
-                ProxyServer::new(cryptde, true, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+            let mut subject = ProxyServer::new(
+                cryptde,
+                true,
+                Some(STANDARD_CONSUMING_WALLET_BALANCE),
+                false,
+            );
             subject.stream_key_factory = Box::new(stream_key_factory);
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
@@ -2120,8 +3040,12 @@ mod tests {
         thread::spawn(move || {
             let stream_key_factory = StreamKeyFactoryMock::new().make_result(stream_key);
             let system = System::new("proxy_server_uses_existing_route");
-            let mut subject =
-                ProxyServer::new(cryptde, true, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+            let mut subject = ProxyServer::new(
+                cryptde,
+                true,
+                Some(STANDARD_CONSUMING_WALLET_BALANCE),
+                false,
+            );
             subject.stream_key_factory = Box::new(stream_key_factory);
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder().hopper(hopper_mock).build();
@@ -2224,6 +3148,7 @@ mod tests {
             payload.clone(),
             logger,
             socket_addr,
+            None,
             &peer_actors.dispatcher.from_dispatcher_client,
             &peer_actors.accountant.report_exit_service_consumed,
             &peer_actors.accountant.report_routing_service_consumed,
@@ -2246,6 +3171,7 @@ mod tests {
                 payload_size: payload_enc.len(),
                 service_rate: DEFAULT_RATE_PACK.routing_service_rate,
                 byte_rate: DEFAULT_RATE_PACK.routing_byte_rate,
+                profile_opt: None,
             }
         );
         let record = recording.get_record::<ReportRoutingServiceConsumedMessage>(2);
@@ -2256,6 +3182,7 @@ mod tests {
                 payload_size: payload_enc.len(),
                 service_rate: DEFAULT_RATE_PACK.routing_service_rate,
                 byte_rate: DEFAULT_RATE_PACK.routing_byte_rate,
+                profile_opt: None,
             }
         );
         let recording = proxy_server_recording_arc.lock().unwrap();
@@ -2302,6 +3229,7 @@ mod tests {
             payload.clone(),
             logger,
             socket_addr,
+            None,
             &peer_actors.dispatcher.from_dispatcher_client,
             &peer_actors.accountant.report_exit_service_consumed,
             &peer_actors.accountant.report_routing_service_consumed,
@@ -2362,8 +3290,12 @@ mod tests {
             let stream_key_factory = StreamKeyFactoryMock::new().make_result(stream_key);
             let system =
                 System::new("proxy_server_logs_messages_when_routing_services_are_not_requested");
-            let mut subject =
-                ProxyServer::new(cryptde, true, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+            let mut subject = ProxyServer::new(
+                cryptde,
+                true,
+                Some(STANDARD_CONSUMING_WALLET_BALANCE),
+                false,
+            );
             subject.stream_key_factory = Box::new(stream_key_factory);
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
@@ -2426,8 +3358,12 @@ mod tests {
             let stream_key_factory = StreamKeyFactoryMock::new().make_result(stream_key);
             let system =
                 System::new("proxy_server_sends_message_to_accountant_for_exit_service_consumed");
-            let mut subject =
-                ProxyServer::new(cryptde, true, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+            let mut subject = ProxyServer::new(
+                cryptde,
+                true,
+                Some(STANDARD_CONSUMING_WALLET_BALANCE),
+                false,
+            );
             subject.stream_key_factory = Box::new(stream_key_factory);
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
@@ -2450,8 +3386,206 @@ mod tests {
                 payload_size: expected_data.len(),
                 service_rate: DEFAULT_RATE_PACK.exit_service_rate,
                 byte_rate: DEFAULT_RATE_PACK.exit_byte_rate,
+                profile_opt: None,
+            }
+        );
+    }
+
+    #[test]
+    fn proxy_server_charges_traffic_on_pac_ports_to_their_profile_and_routes_with_its_wallet() {
+        let cryptde = cryptde();
+        let http_request = b"GET /index.html HTTP/1.1\r\nHost: nowhere.com\r\n\r\n";
+        let (accountant_mock, accountant_awaiter, accountant_log_arc) = make_recorder();
+        let earning_wallet = make_wallet("earning wallet");
+        let profile_wallet = make_wallet("profile wallet");
+        let neighborhood_mock = Recorder::new().route_query_response(Some(RouteQueryResponse {
+            route: make_meaningless_route(),
+            expected_services: ExpectedServices::RoundTrip(
+                vec![
+                    ExpectedService::Nothing,
+                    ExpectedService::Exit(
+                        PublicKey::new(&[3]),
+                        earning_wallet.clone(),
+                        rate_pack(101),
+                    ),
+                ],
+                vec![],
+                0,
+            ),
+        }));
+        let neighborhood_log_arc = neighborhood_mock.get_recording();
+        let profile = ConsumingProfile {
+            name: "work".to_string(),
+            selector: ProfileSelector::Pac(8090),
+            budget_opt: Some(1_000_000),
+            consuming_wallet_opt: Some(profile_wallet.clone()),
+        };
+        let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let stream_key = make_meaningless_stream_key();
+        let expected_data = http_request.to_vec();
+        let msg_from_dispatcher = InboundClientData {
+            peer_addr: socket_addr.clone(),
+            reception_port: Some(8090),
+            sequence_number: Some(0),
+            last_data: true,
+            is_clandestine: false,
+            data: expected_data.clone(),
+        };
+        thread::spawn(move || {
+            let stream_key_factory = StreamKeyFactoryMock::new().make_result(stream_key);
+            let system = System::new("proxy_server_charges_traffic_on_pac_ports_to_their_profile_and_routes_with_its_wallet");
+            let mut subject = ProxyServer::new(
+                cryptde,
+                true,
+                Some(STANDARD_CONSUMING_WALLET_BALANCE),
+                false,
+            )
+            .with_consuming_profiles(vec![profile]);
+            subject.stream_key_factory = Box::new(stream_key_factory);
+            let subject_addr: Addr<ProxyServer> = subject.start();
+            let mut peer_actors = peer_actors_builder()
+                .accountant(accountant_mock)
+                .neighborhood(neighborhood_mock)
+                .build();
+            peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+            subject_addr.try_send(msg_from_dispatcher).unwrap();
+            system.run();
+        });
+
+        accountant_awaiter.await_message_count(1);
+        let neighborhood_recording = neighborhood_log_arc.lock().unwrap();
+        assert_eq!(
+            neighborhood_recording
+                .get_record::<RouteQueryMessage>(0)
+                .consuming_wallet_opt,
+            Some(profile_wallet)
+        );
+        let recording = accountant_log_arc.lock().unwrap();
+        assert_eq!(
+            recording.get_record::<ReportExitServiceConsumedMessage>(0),
+            &ReportExitServiceConsumedMessage {
+                earning_wallet,
+                payload_size: expected_data.len(),
+                service_rate: DEFAULT_RATE_PACK.exit_service_rate,
+                byte_rate: DEFAULT_RATE_PACK.exit_byte_rate,
+                profile_opt: Some("work".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn proxy_server_refuses_traffic_of_a_consuming_profile_whose_budget_is_used_up() {
+        init_test_logging();
+        let cryptde = cryptde();
+        let http_request = b"GET /index.html HTTP/1.1\r\nHost: nowhere.com\r\n\r\n";
+        let (hopper, _, hopper_log_arc) = make_recorder();
+        let (neighborhood, _, neighborhood_log_arc) = make_recorder();
+        let (dispatcher, _, dispatcher_log_arc) = make_recorder();
+        let socket_addr = SocketAddr::from_str("1.2.3.4:15678").unwrap();
+        let msg_from_dispatcher = InboundClientData {
+            peer_addr: socket_addr.clone(),
+            reception_port: Some(HTTP_PORT),
+            sequence_number: Some(0),
+            last_data: false,
+            is_clandestine: false,
+            data: http_request.to_vec(),
+        };
+        let stream_key_factory = StreamKeyFactoryMock::new(); // can't make any stream keys; shouldn't have to
+        let system = System::new(
+            "proxy_server_refuses_traffic_of_a_consuming_profile_whose_budget_is_used_up",
+        );
+        let mut subject =
+            ProxyServer::new(cryptde, true, Some(0), false).with_consuming_profiles(vec![
+                ConsumingProfile {
+                    name: "work".to_string(),
+                    selector: ProfileSelector::OriginatingPorts(10000..=19999),
+                    budget_opt: Some(1_000),
+                    consuming_wallet_opt: None,
+                },
+            ]);
+        subject.stream_key_factory = Box::new(stream_key_factory);
+        let subject_addr: Addr<ProxyServer> = subject.start();
+        let mut peer_actors = peer_actors_builder()
+            .dispatcher(dispatcher)
+            .hopper(hopper)
+            .neighborhood(neighborhood)
+            .build();
+        peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        subject_addr
+            .try_send(ConsumingProfileExhaustedMessage {
+                name: "work".to_string(),
+            })
+            .unwrap();
+        subject_addr.try_send(msg_from_dispatcher).unwrap();
+
+        System::current().stop();
+        system.run();
+        let neighborhood_recording = neighborhood_log_arc.lock().unwrap();
+        assert!(neighborhood_recording.is_empty());
+        let hopper_recording = hopper_log_arc.lock().unwrap();
+        assert!(hopper_recording.is_empty());
+        let dispatcher_recording = dispatcher_log_arc.lock().unwrap();
+        assert_eq!(
+            dispatcher_recording.get_record::<TransmitDataMsg>(0),
+            &TransmitDataMsg {
+                endpoint: Endpoint::Socket(socket_addr),
+                last_data: true,
+                sequence_number: Some(0),
+                data: ServerImpersonatorHttp {}.budget_exhausted("work"),
             }
         );
+        TestLogHandler::new().exists_log_containing(
+            "ERROR: ProxyServer: Refused traffic from 1.2.3.4:15678: consuming profile 'work' has used up its budget",
+        );
+    }
+
+    #[test]
+    fn proxy_server_does_not_report_exit_service_consumed_for_empty_request() {
+        init_test_logging();
+        let system =
+            System::new("proxy_server_does_not_report_exit_service_consumed_for_empty_request");
+        let (accountant_mock, _, accountant_recording_arc) = make_recorder();
+        let peer_actors = peer_actors_builder().accountant(accountant_mock).build();
+        let stream_key = make_meaningless_stream_key();
+        let payload = ClientRequestPayload {
+            version: ClientRequestPayload::version(),
+            stream_key: stream_key.clone(),
+            sequenced_packet: SequencedPacket {
+                data: vec![],
+                sequence_number: 3,
+                last_data: true,
+            },
+            target_hostname: None,
+            target_port: 0,
+            protocol: ProxyProtocol::TLS,
+            originator_public_key: cryptde().public_key().clone(),
+        };
+
+        ProxyServer::report_exit_service(
+            &peer_actors.accountant.report_exit_service_consumed,
+            vec![
+                ExpectedService::Nothing,
+                ExpectedService::Exit(
+                    PublicKey::new(&[3]),
+                    make_wallet("exit earning wallet"),
+                    rate_pack(103),
+                ),
+            ],
+            &payload,
+            None,
+            &Logger::new("ProxyServer"),
+        );
+
+        System::current().stop_with_code(0);
+        system.run();
+        assert_eq!(accountant_recording_arc.lock().unwrap().len(), 0);
+        TestLogHandler::new().exists_log_containing(&format!(
+            "DEBUG: ProxyServer: No exit service consumed by 0-byte request on stream {}",
+            stream_key
+        ));
     }
 
     #[test]
@@ -2479,8 +3613,12 @@ mod tests {
             let stream_key_factory = StreamKeyFactoryMock::new().make_result(stream_key);
             let system =
                 System::new("proxy_server_logs_message_when_exit_services_are_not_consumed");
-            let mut subject =
-                ProxyServer::new(cryptde, true, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+            let mut subject = ProxyServer::new(
+                cryptde,
+                true,
+                Some(STANDARD_CONSUMING_WALLET_BALANCE),
+                false,
+            );
             subject.stream_key_factory = Box::new(stream_key_factory);
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
@@ -2521,7 +3659,12 @@ mod tests {
         };
         thread::spawn(move || {
             let system = System::new("proxy_server_receives_http_request_from_dispatcher_but_neighborhood_cant_make_route");
-            let subject = ProxyServer::new(cryptde, true, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+            let subject = ProxyServer::new(
+                cryptde,
+                true,
+                Some(STANDARD_CONSUMING_WALLET_BALANCE),
+                false,
+            );
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
                 .dispatcher(dispatcher)
@@ -2593,23 +3736,138 @@ mod tests {
             protocol: ProxyProtocol::TLS,
             originator_public_key: cryptde.public_key().clone(),
         };
-        let logger = Logger::new("ProxyServer");
-        let source_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let logger = Logger::new("ProxyServer");
+        let source_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+
+        ProxyServer::try_transmit_to_hopper(
+            cryptde.dup(),
+            &peer_actors.hopper.from_hopper_client,
+            route_result,
+            payload,
+            logger,
+            source_addr,
+            None,
+            &peer_actors.dispatcher.from_dispatcher_client,
+            &peer_actors.accountant.report_exit_service_consumed,
+            &peer_actors.accountant.report_routing_service_consumed,
+            &peer_actors.proxy_server.add_return_route,
+            None,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn proxy_server_with_kill_switch_stops_using_established_routes_once_a_route_cant_be_made() {
+        init_test_logging();
+        let cryptde = cryptde();
+        let http_request = b"GET /index.html HTTP/1.1\r\nHost: nowhere.com\r\n\r\n";
+        let (neighborhood_mock, _, neighborhood_recording_arc) = make_recorder();
+        let neighborhood_mock = neighborhood_mock.route_query_response(None);
+        let (hopper_mock, _, hopper_recording_arc) = make_recorder();
+        let dispatcher = Recorder::new();
+        let dispatcher_awaiter = dispatcher.get_awaiter();
+        let dispatcher_recording_arc = dispatcher.get_recording();
+        let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let stream_key = make_meaningless_stream_key();
+        let msg_from_dispatcher = InboundClientData {
+            peer_addr: socket_addr.clone(),
+            reception_port: Some(HTTP_PORT),
+            sequence_number: Some(0),
+            last_data: true,
+            data: http_request.to_vec(),
+            is_clandestine: false,
+        };
+        thread::spawn(move || {
+            let stream_key_factory = StreamKeyFactoryMock::new().make_result(stream_key);
+            let system = System::new("proxy_server_with_kill_switch_stops_using_established_routes_once_a_route_cant_be_made");
+            let mut subject =
+                ProxyServer::new(cryptde, true, Some(STANDARD_CONSUMING_WALLET_BALANCE), true);
+            subject.stream_key_factory = Box::new(stream_key_factory);
+            let subject_addr: Addr<ProxyServer> = subject.start();
+            let mut peer_actors = peer_actors_builder()
+                .dispatcher(dispatcher)
+                .hopper(hopper_mock)
+                .neighborhood(neighborhood_mock)
+                .build();
+            peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+            subject_addr
+                .try_send(AddRouteMessage {
+                    stream_key,
+                    route: RouteQueryResponse {
+                        route: Route { hops: vec![] },
+                        expected_services: ExpectedServices::RoundTrip(
+                            vec![ExpectedService::Nothing],
+                            vec![],
+                            1234,
+                        ),
+                    },
+                })
+                .unwrap();
+            subject_addr.try_send(RouteFailureMessage {}).unwrap();
+
+            subject_addr.try_send(msg_from_dispatcher).unwrap();
+
+            system.run();
+        });
+
+        dispatcher_awaiter.await_message_count(1);
+        let recording = dispatcher_recording_arc.lock().unwrap();
+        let record = recording.get_record::<TransmitDataMsg>(0);
+        let expected_msg = TransmitDataMsg {
+            endpoint: Endpoint::Socket(SocketAddr::from_str("1.2.3.4:5678").unwrap()),
+            last_data: true,
+            sequence_number: Some(0),
+            data: ServerImpersonatorHttp {}.kill_switch_engaged(3),
+        };
+        assert_eq!(record, &expected_msg);
+        let recording = neighborhood_recording_arc.lock().unwrap();
+        let record = recording.get_record::<RouteQueryMessage>(0);
+        assert_eq!(record, &RouteQueryMessage::data_indefinite_route_request(3));
+        assert_eq!(hopper_recording_arc.lock().unwrap().len(), 0);
+        let tlh = TestLogHandler::new();
+        tlh.exists_log_containing("WARN: ProxyServer: Kill switch engaged: 3-hop routes can't be built, so 1 established stream will carry no more consuming traffic");
+        tlh.exists_log_containing(
+            "ERROR: ProxyServer: Kill switch refused request to nowhere.com: no 3-hop route",
+        );
+    }
 
-        ProxyServer::try_transmit_to_hopper(
-            cryptde.dup(),
-            &peer_actors.hopper.from_hopper_client,
-            route_result,
-            payload,
-            logger,
-            source_addr,
-            &peer_actors.dispatcher.from_dispatcher_client,
-            &peer_actors.accountant.report_exit_service_consumed,
-            &peer_actors.accountant.report_routing_service_consumed,
-            &peer_actors.proxy_server.add_return_route,
-            None,
-        )
-        .unwrap();
+    #[test]
+    fn proxy_server_with_kill_switch_releases_it_once_a_route_is_made() {
+        init_test_logging();
+        let system = System::new("proxy_server_with_kill_switch_releases_it_once_a_route_is_made");
+        let mut subject = ProxyServer::new(
+            cryptde(),
+            true,
+            Some(STANDARD_CONSUMING_WALLET_BALANCE),
+            true,
+        );
+        subject.kill_switch_engaged = true;
+        let subject_addr: Addr<ProxyServer> = subject.start();
+
+        subject_addr
+            .try_send(AddRouteMessage {
+                stream_key: make_meaningless_stream_key(),
+                route: RouteQueryResponse {
+                    route: Route { hops: vec![] },
+                    expected_services: ExpectedServices::RoundTrip(vec![], vec![], 1234),
+                },
+            })
+            .unwrap();
+
+        System::current().stop();
+        system.run();
+        TestLogHandler::new().exists_log_containing(
+            "INFO: ProxyServer: Kill switch released: 3-hop routes can be built again",
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "A zero-hop PrometheusNode is not decentralized and cannot have a --kill-switch setting"
+    )]
+    fn proxy_server_cant_have_kill_switch_in_zero_hop_mode() {
+        ProxyServer::new(cryptde(), false, None, true);
     }
 
     #[test]
@@ -2648,7 +3906,12 @@ mod tests {
         };
         thread::spawn(move || {
             let system = System::new("proxy_server_receives_http_request_from_dispatcher_but_neighborhood_cant_make_route");
-            let subject = ProxyServer::new(cryptde, true, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+            let subject = ProxyServer::new(
+                cryptde,
+                true,
+                Some(STANDARD_CONSUMING_WALLET_BALANCE),
+                false,
+            );
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
                 .dispatcher(dispatcher)
@@ -2740,8 +4003,12 @@ mod tests {
             IncipientCoresPackage::new(cryptde, route.clone(), expected_payload.into(), &key)
                 .unwrap();
         thread::spawn(move || {
-            let mut subject =
-                ProxyServer::new(cryptde, false, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+            let mut subject = ProxyServer::new(
+                cryptde,
+                false,
+                Some(STANDARD_CONSUMING_WALLET_BALANCE),
+                false,
+            );
             subject.stream_key_factory =
                 Box::new(StreamKeyFactoryMock::new().make_result(stream_key.clone()));
             let system = System::new("proxy_server_receives_tls_client_hello_from_dispatcher_then_sends_cores_package_to_hopper");
@@ -2811,8 +4078,12 @@ mod tests {
             IncipientCoresPackage::new(cryptde, route.clone(), expected_payload.into(), &key)
                 .unwrap();
         thread::spawn(move || {
-            let mut subject =
-                ProxyServer::new(cryptde, false, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+            let mut subject = ProxyServer::new(
+                cryptde,
+                false,
+                Some(STANDARD_CONSUMING_WALLET_BALANCE),
+                false,
+            );
             subject.stream_key_factory =
                 Box::new(StreamKeyFactoryMock::new().make_result(stream_key.clone()));
             let system = System::new("proxy_server_receives_tls_client_hello_from_dispatcher_then_sends_cores_package_to_hopper");
@@ -2880,8 +4151,12 @@ mod tests {
             IncipientCoresPackage::new(cryptde, route.clone(), expected_payload.into(), &key)
                 .unwrap();
         thread::spawn(move || {
-            let mut subject =
-                ProxyServer::new(cryptde, false, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+            let mut subject = ProxyServer::new(
+                cryptde,
+                false,
+                Some(STANDARD_CONSUMING_WALLET_BALANCE),
+                false,
+            );
             subject.stream_key_factory =
                 Box::new(StreamKeyFactoryMock::new().make_result(stream_key.clone()));
             let system = System::new("proxy_server_receives_tls_client_hello_from_dispatcher_then_sends_cores_package_to_hopper");
@@ -2904,6 +4179,151 @@ mod tests {
         assert_eq!(record, &expected_pkg);
     }
 
+    #[test]
+    fn proxy_server_receives_data_on_forwarded_port_then_sends_cores_package_for_remote_host_to_hopper(
+    ) {
+        let raw_request = b"\x00\x00\x00\x08\x04\xd2\x16\x2f";
+        let cryptde = cryptde();
+        let hopper_mock = Recorder::new();
+        let hopper_log_arc = hopper_mock.get_recording();
+        let hopper_awaiter = hopper_mock.get_awaiter();
+        let neighborhood_mock = Recorder::new().route_query_response(Some(
+            zero_hop_route_response(&cryptde.public_key(), cryptde),
+        ));
+        let stream_key = make_meaningless_stream_key();
+        let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let msg_from_dispatcher = InboundClientData {
+            peer_addr: socket_addr.clone(),
+            reception_port: Some(5432),
+            sequence_number: Some(0),
+            last_data: false,
+            is_clandestine: false,
+            data: raw_request.to_vec(),
+        };
+        let key = cryptde.public_key();
+        let route = zero_hop_route_response(&key, cryptde).route;
+        let expected_payload = ClientRequestPayload {
+            version: ClientRequestPayload::version(),
+            stream_key: stream_key.clone(),
+            sequenced_packet: SequencedPacket {
+                data: raw_request.to_vec(),
+                sequence_number: 0,
+                last_data: false,
+            },
+            target_hostname: Some("db.example.com".to_string()),
+            target_port: 5433,
+            protocol: ProxyProtocol::TLS,
+            originator_public_key: key.clone(),
+        };
+        let expected_pkg =
+            IncipientCoresPackage::new(cryptde, route.clone(), expected_payload.into(), &key)
+                .unwrap();
+        thread::spawn(move || {
+            let mut subject = ProxyServer::new(
+                cryptde,
+                false,
+                Some(STANDARD_CONSUMING_WALLET_BALANCE),
+                false,
+            )
+            .with_port_forwards(vec![PortForward {
+                local_port: 5432,
+                remote_host: "db.example.com".to_string(),
+                remote_port: 5433,
+            }]);
+            subject.stream_key_factory =
+                Box::new(StreamKeyFactoryMock::new().make_result(stream_key.clone()));
+            let system = System::new("proxy_server_receives_data_on_forwarded_port_then_sends_cores_package_for_remote_host_to_hopper");
+            let subject_addr: Addr<ProxyServer> = subject.start();
+            let mut peer_actors = peer_actors_builder()
+                .hopper(hopper_mock)
+                .neighborhood(neighborhood_mock)
+                .build();
+            peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            subject_addr.try_send(msg_from_dispatcher).unwrap();
+
+            system.run();
+        });
+
+        hopper_awaiter.await_message_count(1);
+        let recording = hopper_log_arc.lock().unwrap();
+        let record = recording.get_record::<IncipientCoresPackage>(0);
+        assert_eq!(record, &expected_pkg);
+    }
+
+    #[test]
+    fn proxy_server_opens_forwarded_ftp_port_as_ftp_so_the_exit_connects_at_once() {
+        let cryptde = cryptde();
+        let hopper_mock = Recorder::new();
+        let hopper_log_arc = hopper_mock.get_recording();
+        let hopper_awaiter = hopper_mock.get_awaiter();
+        let neighborhood_mock = Recorder::new().route_query_response(Some(
+            zero_hop_route_response(&cryptde.public_key(), cryptde),
+        ));
+        let stream_key = make_meaningless_stream_key();
+        let msg_from_dispatcher = InboundClientData {
+            peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+            reception_port: Some(2121),
+            sequence_number: Some(0),
+            last_data: false,
+            is_clandestine: false,
+            data: vec![],
+        };
+        let key = cryptde.public_key();
+        let route = zero_hop_route_response(&key, cryptde).route;
+        let expected_payload = ClientRequestPayload {
+            version: ClientRequestPayload::version(),
+            stream_key: stream_key.clone(),
+            sequenced_packet: SequencedPacket {
+                data: vec![],
+                sequence_number: 0,
+                last_data: false,
+            },
+            target_hostname: Some("ftp.example.com".to_string()),
+            target_port: FTP_PORT,
+            protocol: ProxyProtocol::FTP,
+            originator_public_key: key.clone(),
+        };
+        let expected_pkg =
+            IncipientCoresPackage::new(cryptde, route.clone(), expected_payload.into(), &key)
+                .unwrap();
+        thread::spawn(move || {
+            let mut subject = ProxyServer::new(
+                cryptde,
+                false,
+                Some(STANDARD_CONSUMING_WALLET_BALANCE),
+                false,
+            )
+            .with_port_forwards(vec![PortForward {
+                local_port: 2121,
+                remote_host: "ftp.example.com".to_string(),
+                remote_port: FTP_PORT,
+            }]);
+            subject.stream_key_factory =
+                Box::new(StreamKeyFactoryMock::new().make_result(stream_key.clone()));
+            let system = System::new(
+                "proxy_server_opens_forwarded_ftp_port_as_ftp_so_the_exit_connects_at_once",
+            );
+            let subject_addr: Addr<ProxyServer> = subject.start();
+            let mut peer_actors = peer_actors_builder()
+                .hopper(hopper_mock)
+                .neighborhood(neighborhood_mock)
+                .build();
+            peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            subject_addr.try_send(msg_from_dispatcher).unwrap();
+
+            system.run();
+        });
+
+        hopper_awaiter.await_message_count(1);
+        let recording = hopper_log_arc.lock().unwrap();
+        let record = recording.get_record::<IncipientCoresPackage>(0);
+        assert_eq!(record, &expected_pkg);
+    }
+
     #[test]
     fn proxy_server_receives_tls_client_hello_from_dispatcher_but_neighborhood_cant_make_route() {
         init_test_logging();
@@ -2945,7 +4365,12 @@ mod tests {
         };
         thread::spawn(move || {
             let system = System::new("proxy_server_receives_tls_client_hello_from_dispatcher_but_neighborhood_cant_make_route");
-            let subject = ProxyServer::new(cryptde, false, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+            let subject = ProxyServer::new(
+                cryptde,
+                false,
+                Some(STANDARD_CONSUMING_WALLET_BALANCE),
+                false,
+            );
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
                 .dispatcher(dispatcher)
@@ -2979,7 +4404,12 @@ mod tests {
         let system = System::new("proxy_server_receives_response_from_hopper");
         let (dispatcher_mock, _, dispatcher_log_arc) = make_recorder();
         let cryptde = cryptde();
-        let mut subject = ProxyServer::new(cryptde, false, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+        let mut subject = ProxyServer::new(
+            cryptde,
+            false,
+            Some(STANDARD_CONSUMING_WALLET_BALANCE),
+            false,
+        );
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let stream_key = make_meaningless_stream_key();
         subject
@@ -3034,7 +4464,12 @@ mod tests {
     #[test]
     fn handle_client_response_payload_purges_stream_keys_for_terminal_response() {
         let cryptde = cryptde();
-        let mut subject = ProxyServer::new(cryptde, false, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+        let mut subject = ProxyServer::new(
+            cryptde,
+            false,
+            Some(STANDARD_CONSUMING_WALLET_BALANCE),
+            false,
+        );
         subject.subs = Some(ProxyServerOutSubs::default());
 
         let stream_key = make_meaningless_stream_key();
@@ -3096,7 +4531,12 @@ mod tests {
         let (dispatcher_mock, _, dispatcher_log_arc) = make_recorder();
         let (accountant, _, accountant_recording_arc) = make_recorder();
         let cryptde = cryptde();
-        let mut subject = ProxyServer::new(cryptde, false, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+        let mut subject = ProxyServer::new(
+            cryptde,
+            false,
+            Some(STANDARD_CONSUMING_WALLET_BALANCE),
+            false,
+        );
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let stream_key = make_meaningless_stream_key();
         let irrelevant_public_key = PublicKey::from(&b"irrelevant"[..]);
@@ -3233,18 +4673,21 @@ mod tests {
             0,
             &incoming_route_d_wallet,
             first_exit_size,
+            None,
         );
         check_routing_report(
             &accountant_recording,
             1,
             &incoming_route_e_wallet,
             routing_size,
+            None,
         );
         check_routing_report(
             &accountant_recording,
             2,
             &incoming_route_f_wallet,
             routing_size,
+            None,
         );
         let routing_size = second_expired_cores_package.payload_len;
         check_exit_report(
@@ -3252,18 +4695,21 @@ mod tests {
             3,
             &incoming_route_g_wallet,
             second_exit_size,
+            None,
         );
         check_routing_report(
             &accountant_recording,
             4,
             &incoming_route_h_wallet,
             routing_size,
+            None,
         );
         check_routing_report(
             &accountant_recording,
             5,
             &incoming_route_i_wallet,
             routing_size,
+            None,
         );
         assert_eq!(accountant_recording.len(), 6);
     }
@@ -3275,7 +4721,12 @@ mod tests {
         let (dispatcher_mock, _, dispatcher_log_arc) = make_recorder();
 
         let cryptde = cryptde();
-        let mut subject = ProxyServer::new(cryptde, false, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+        let mut subject = ProxyServer::new(
+            cryptde,
+            false,
+            Some(STANDARD_CONSUMING_WALLET_BALANCE),
+            false,
+        );
 
         let stream_key = make_meaningless_stream_key();
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
@@ -3343,7 +4794,12 @@ mod tests {
         let system = System::new("proxy_server_records_accounting");
         let (accountant, _, accountant_recording_arc) = make_recorder();
         let cryptde = cryptde();
-        let mut subject = ProxyServer::new(cryptde, false, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+        let mut subject = ProxyServer::new(
+            cryptde,
+            false,
+            Some(STANDARD_CONSUMING_WALLET_BALANCE),
+            false,
+        );
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let stream_key = make_meaningless_stream_key();
         let irrelevant_public_key = PublicKey::from(&b"irrelevant"[..]);
@@ -3405,18 +4861,20 @@ mod tests {
         system.run();
 
         let accountant_recording = accountant_recording_arc.lock().unwrap();
-        check_exit_report(&accountant_recording, 0, &incoming_route_d_wallet, 0);
+        check_exit_report(&accountant_recording, 0, &incoming_route_d_wallet, 0, None);
         check_routing_report(
             &accountant_recording,
             1,
             &incoming_route_e_wallet,
             routing_size,
+            None,
         );
         check_routing_report(
             &accountant_recording,
             2,
             &incoming_route_f_wallet,
             routing_size,
+            None,
         );
         assert_eq!(accountant_recording.len(), 3);
     }
@@ -3428,7 +4886,12 @@ mod tests {
         let (neighborhood_mock, _, neighborhood_log_arc) = make_recorder();
 
         let cryptde = cryptde();
-        let mut subject = ProxyServer::new(cryptde, false, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+        let mut subject = ProxyServer::new(
+            cryptde,
+            false,
+            Some(STANDARD_CONSUMING_WALLET_BALANCE),
+            false,
+        );
 
         let stream_key = make_meaningless_stream_key();
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
@@ -3486,6 +4949,67 @@ mod tests {
         );
     }
 
+    #[test]
+    fn billing_claims_from_an_exit_go_to_the_neighborhood_under_its_public_key() {
+        let system = System::new("test");
+        let (neighborhood_mock, _, neighborhood_log_arc) = make_recorder();
+        let (accountant_mock, _, accountant_log_arc) = make_recorder();
+        let cryptde = cryptde();
+        let mut subject = ProxyServer::new(
+            cryptde,
+            false,
+            Some(STANDARD_CONSUMING_WALLET_BALANCE),
+            false,
+        );
+        let exit_public_key = PublicKey::from(&b"exit_key"[..]);
+        let exit_wallet = make_wallet("exit wallet");
+        subject.route_ids_to_return_routes.insert(
+            1234,
+            AddReturnRouteMessage {
+                return_route_id: 1234,
+                expected_services: vec![ExpectedService::Exit(
+                    exit_public_key.clone(),
+                    exit_wallet,
+                    rate_pack(10),
+                )],
+                protocol: ProxyProtocol::HTTP,
+                server_name: Some("server.com".to_string()),
+            },
+        );
+        let subject_addr: Addr<ProxyServer> = subject.start();
+        let expired_cores_package: ExpiredCoresPackage<BillingClaimPayload> =
+            ExpiredCoresPackage::new(
+                SocketAddr::from_str("1.2.3.4:1234").unwrap(),
+                Some(make_wallet("irrelevant")),
+                return_route_with_id(cryptde, 1234),
+                BillingClaimPayload::new(1_234_567, true),
+                0,
+            );
+        let mut peer_actors = peer_actors_builder()
+            .neighborhood(neighborhood_mock)
+            .accountant(accountant_mock)
+            .build();
+        peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        subject_addr.try_send(expired_cores_package).unwrap();
+
+        System::current().stop();
+        system.run();
+        let neighborhood_recording = neighborhood_log_arc.lock().unwrap();
+        assert_eq!(
+            neighborhood_recording.get_record::<BillingClaimMessage>(0),
+            &BillingClaimMessage {
+                creditor_key: exit_public_key,
+                claimed_gwub: 1_234_567,
+                shared_wallet: true,
+            }
+        );
+        assert_eq!(neighborhood_recording.len(), 1);
+        let accountant_recording = accountant_log_arc.lock().unwrap();
+        assert_eq!(accountant_recording.len(), 1);
+    }
+
     #[test]
     fn handle_dns_resolve_failure_logs_when_stream_key_be_gone_but_server_name_be_not() {
         init_test_logging();
@@ -3494,7 +5018,12 @@ mod tests {
         let (neighborhood_mock, _, _) = make_recorder();
 
         let cryptde = cryptde();
-        let mut subject = ProxyServer::new(cryptde, false, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+        let mut subject = ProxyServer::new(
+            cryptde,
+            false,
+            Some(STANDARD_CONSUMING_WALLET_BALANCE),
+            false,
+        );
 
         let stream_key = make_meaningless_stream_key();
         let return_route_id = 1234;
@@ -3563,7 +5092,12 @@ mod tests {
         let (neighborhood_mock, _, _) = make_recorder();
 
         let cryptde = cryptde();
-        let mut subject = ProxyServer::new(cryptde, false, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+        let mut subject = ProxyServer::new(
+            cryptde,
+            false,
+            Some(STANDARD_CONSUMING_WALLET_BALANCE),
+            false,
+        );
 
         let stream_key = make_meaningless_stream_key();
         let return_route_id = 1234;
@@ -3630,7 +5164,12 @@ mod tests {
         let (neighborhood_mock, _, _) = make_recorder();
         let (dispatcher_mock, _, _) = make_recorder();
 
-        let mut subject = ProxyServer::new(cryptde, false, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+        let mut subject = ProxyServer::new(
+            cryptde,
+            false,
+            Some(STANDARD_CONSUMING_WALLET_BALANCE),
+            false,
+        );
         subject.subs = Some(ProxyServerOutSubs::default());
 
         let peer_actors = peer_actors_builder()
@@ -3690,7 +5229,12 @@ mod tests {
         let cryptde = cryptde();
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let stream_key = make_meaningless_stream_key();
-        let mut subject = ProxyServer::new(cryptde, false, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+        let mut subject = ProxyServer::new(
+            cryptde,
+            false,
+            Some(STANDARD_CONSUMING_WALLET_BALANCE),
+            false,
+        );
         subject
             .keys_and_addrs
             .insert(stream_key.clone(), socket_addr.clone());
@@ -3734,7 +5278,7 @@ mod tests {
     fn panics_if_hopper_is_unbound() {
         let system = System::new("panics_if_hopper_is_unbound");
         let http_request = b"GET /index.html HTTP/1.1\r\nHost: nowhere.com\r\n\r\n";
-        let subject = ProxyServer::new(cryptde(), false, None);
+        let subject = ProxyServer::new(cryptde(), false, None, false);
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let expected_data = http_request.to_vec();
         let msg_from_dispatcher = InboundClientData {
@@ -3761,7 +5305,12 @@ mod tests {
         let (dispatcher, _, dispatcher_recording_arc) = make_recorder();
         let (accountant, _, accountant_recording_arc) = make_recorder();
         let system = System::new("report_response_services_consumed_complains_and_drops_package_if_return_route_id_is_unrecognized");
-        let mut subject = ProxyServer::new(cryptde, true, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+        let mut subject = ProxyServer::new(
+            cryptde,
+            true,
+            Some(STANDARD_CONSUMING_WALLET_BALANCE),
+            false,
+        );
         let stream_key = make_meaningless_stream_key();
         subject
             .keys_and_addrs
@@ -3807,7 +5356,12 @@ mod tests {
         let (dispatcher, _, dispatcher_recording_arc) = make_recorder();
         let (accountant, _, accountant_recording_arc) = make_recorder();
         let system = System::new("report_response_services_consumed_complains_and_drops_package_if_return_route_id_is_unreadable");
-        let mut subject = ProxyServer::new(cryptde, true, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+        let mut subject = ProxyServer::new(
+            cryptde,
+            true,
+            Some(STANDARD_CONSUMING_WALLET_BALANCE),
+            false,
+        );
         let stream_key = make_meaningless_stream_key();
         subject
             .keys_and_addrs
@@ -3858,8 +5412,12 @@ mod tests {
         let (tx, rx) = mpsc::channel();
         thread::spawn(move || {
             let system = System::new("report_response_services_consumed_complains_and_drops_package_if_return_route_id_does_not_exist");
-            let mut subject =
-                ProxyServer::new(cryptde, true, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+            let mut subject = ProxyServer::new(
+                cryptde,
+                true,
+                Some(STANDARD_CONSUMING_WALLET_BALANCE),
+                false,
+            );
             subject.route_ids_to_return_routes = TtlHashMap::new(Duration::from_millis(250));
             subject
                 .keys_and_addrs
@@ -3909,7 +5467,7 @@ mod tests {
 
     #[test]
     fn handle_stream_shutdown_msg_handles_unknown_peer_addr() {
-        let mut subject = ProxyServer::new(cryptde(), true, None);
+        let mut subject = ProxyServer::new(cryptde(), true, None, false);
         let unaffected_socket_addr = SocketAddr::from_str("2.3.4.5:6789").unwrap();
         let unaffected_stream_key =
             StreamKey::new(cryptde().public_key().clone(), unaffected_socket_addr);
@@ -3950,8 +5508,12 @@ mod tests {
     #[test]
     fn handle_stream_shutdown_msg_reports_to_counterpart_through_tunnel_when_necessary() {
         let system = System::new("test");
-        let mut subject =
-            ProxyServer::new(cryptde(), true, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+        let mut subject = ProxyServer::new(
+            cryptde(),
+            true,
+            Some(STANDARD_CONSUMING_WALLET_BALANCE),
+            false,
+        );
         let unaffected_socket_addr = SocketAddr::from_str("2.3.4.5:6789").unwrap();
         let unaffected_stream_key =
             StreamKey::new(cryptde().public_key().clone(), unaffected_socket_addr);
@@ -4068,8 +5630,12 @@ mod tests {
     #[test]
     fn handle_stream_shutdown_msg_reports_to_counterpart_without_tunnel_when_necessary() {
         let system = System::new("test");
-        let mut subject =
-            ProxyServer::new(cryptde(), true, Some(STANDARD_CONSUMING_WALLET_BALANCE));
+        let mut subject = ProxyServer::new(
+            cryptde(),
+            true,
+            Some(STANDARD_CONSUMING_WALLET_BALANCE),
+            false,
+        );
         let unaffected_socket_addr = SocketAddr::from_str("2.3.4.5:6789").unwrap();
         let unaffected_stream_key =
             StreamKey::new(cryptde().public_key().clone(), unaffected_socket_addr);
@@ -4179,7 +5745,7 @@ mod tests {
 
     #[test]
     fn handle_stream_shutdown_msg_does_not_report_to_counterpart_when_unnecessary() {
-        let mut subject = ProxyServer::new(cryptde(), true, None);
+        let mut subject = ProxyServer::new(cryptde(), true, None, false);
         let unaffected_socket_addr = SocketAddr::from_str("2.3.4.5:6789").unwrap();
         let unaffected_stream_key =
             StreamKey::new(cryptde().public_key().clone(), unaffected_socket_addr);
@@ -4245,7 +5811,7 @@ mod tests {
     )]
     fn handle_stream_shutdown_complains_about_clandestine_message() {
         let system = System::new("test");
-        let subject = ProxyServer::new(cryptde(), true, None);
+        let subject = ProxyServer::new(cryptde(), true, None, false);
         let subject_addr = subject.start();
 
         subject_addr
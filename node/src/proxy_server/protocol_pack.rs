@@ -1,9 +1,13 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
-use crate::persistent_configuration::{HTTP_PORT, TLS_PORT};
+use crate::persistent_configuration::{FTP_PORT, HTTP_PORT, IMAPS_PORT, SUBMISSION_PORT, TLS_PORT};
+use crate::proxy_server::ftp_protocol_pack::FtpProtocolPack;
 use crate::proxy_server::http_protocol_pack::HttpProtocolPack;
+use crate::proxy_server::imaps_protocol_pack::ImapsProtocolPack;
+use crate::proxy_server::smtp_protocol_pack::SmtpProtocolPack;
 use crate::proxy_server::tls_protocol_pack::TlsProtocolPack;
 use crate::sub_lib::cryptde::{PlainData, PublicKey};
 use crate::sub_lib::dispatcher::InboundClientData;
+use crate::sub_lib::framer::FramingViolation;
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::proxy_server::ProxyProtocol;
 
@@ -24,6 +28,8 @@ pub fn from_protocol(protocol: ProxyProtocol) -> Box<dyn ProtocolPack> {
     match protocol {
         ProxyProtocol::HTTP => Box::new(HttpProtocolPack {}),
         ProxyProtocol::TLS => Box::new(TlsProtocolPack {}),
+        ProxyProtocol::SMTP => Box::new(SmtpProtocolPack {}),
+        ProxyProtocol::FTP => Box::new(FtpProtocolPack {}),
     }
 }
 
@@ -31,6 +37,9 @@ pub fn from_standard_port(_standard_port: u16) -> Option<Box<dyn ProtocolPack>>
     match _standard_port {
         HTTP_PORT => Some(Box::new(HttpProtocolPack {})),
         TLS_PORT => Some(Box::new(TlsProtocolPack {})),
+        SUBMISSION_PORT => Some(Box::new(SmtpProtocolPack {})),
+        IMAPS_PORT => Some(Box::new(ImapsProtocolPack {})),
+        FTP_PORT => Some(Box::new(FtpProtocolPack {})),
         _ => None,
     }
 }
@@ -71,4 +80,8 @@ pub trait ServerImpersonator {
         server_name_opt: Option<String>,
     ) -> Vec<u8>;
     fn consuming_wallet_absent(&self) -> Vec<u8>;
+    fn framing_violation_response(&self, violation: &FramingViolation) -> Vec<u8>;
+    fn kill_switch_engaged(&self, minimum_hop_count: usize) -> Vec<u8>;
+    fn memory_pressure(&self) -> Vec<u8>;
+    fn budget_exhausted(&self, profile_name: &str) -> Vec<u8>;
 }
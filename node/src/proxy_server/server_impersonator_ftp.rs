@@ -0,0 +1,103 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::proxy_server::protocol_pack::ServerImpersonator;
+use crate::sub_lib::cryptde::PublicKey;
+use crate::sub_lib::framer::FramingViolation;
+
+// An FTP client shows the text of a 421 reply to its user and then gives up on the connection
+pub struct ServerImpersonatorFtp {}
+
+impl ServerImpersonator for ServerImpersonatorFtp {
+    fn route_query_failure_response(&self, server_name: &str) -> Vec<u8> {
+        Self::make_reply(&format!(
+            "Can't find a route through the Network to {}",
+            server_name
+        ))
+    }
+
+    fn dns_resolution_failure_response(
+        &self,
+        _exit_key: &PublicKey,
+        server_name_opt: Option<String>,
+    ) -> Vec<u8> {
+        Self::make_reply(&format!(
+            "Exit Node couldn't resolve {}",
+            server_name_opt.unwrap_or_else(|| "<unspecified>".to_string())
+        ))
+    }
+
+    fn consuming_wallet_absent(&self) -> Vec<u8> {
+        Self::make_reply("Can't consume without wallet to pay from")
+    }
+
+    fn framing_violation_response(&self, violation: &FramingViolation) -> Vec<u8> {
+        Self::make_reply(&format!("Request refused: {}", violation))
+    }
+
+    fn kill_switch_engaged(&self, minimum_hop_count: usize) -> Vec<u8> {
+        Self::make_reply(&format!(
+            "Kill switch engaged: can't build {}-hop routes",
+            minimum_hop_count
+        ))
+    }
+
+    fn memory_pressure(&self) -> Vec<u8> {
+        Self::make_reply("Node short of memory: can't take on new streams")
+    }
+
+    fn budget_exhausted(&self, profile_name: &str) -> Vec<u8> {
+        Self::make_reply(&format!(
+            "Consuming profile '{}' has used up its budget",
+            profile_name
+        ))
+    }
+}
+
+impl ServerImpersonatorFtp {
+    fn make_reply(text: &str) -> Vec<u8> {
+        format!("421 {}\r\n", text).into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn route_query_failure_response_is_a_421_reply() {
+        let result = ServerImpersonatorFtp {}.route_query_failure_response("ftp.example.com");
+
+        assert_eq!(
+            String::from_utf8(result).unwrap(),
+            "421 Can't find a route through the Network to ftp.example.com\r\n"
+        );
+    }
+
+    #[test]
+    fn dns_resolution_failure_response_is_a_421_reply() {
+        let exit_key = PublicKey::new(b"exit");
+        let subject = ServerImpersonatorFtp {};
+
+        let named =
+            subject.dns_resolution_failure_response(&exit_key, Some("ftp.nowhere".to_string()));
+        let unnamed = subject.dns_resolution_failure_response(&exit_key, None);
+
+        assert_eq!(
+            String::from_utf8(named).unwrap(),
+            "421 Exit Node couldn't resolve ftp.nowhere\r\n"
+        );
+        assert_eq!(
+            String::from_utf8(unnamed).unwrap(),
+            "421 Exit Node couldn't resolve <unspecified>\r\n"
+        );
+    }
+
+    #[test]
+    fn budget_exhausted_is_a_421_reply() {
+        let result = ServerImpersonatorFtp {}.budget_exhausted("work");
+
+        assert_eq!(
+            String::from_utf8(result).unwrap(),
+            "421 Consuming profile 'work' has used up its budget\r\n"
+        );
+    }
+}
@@ -1,6 +1,7 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 use crate::proxy_server::protocol_pack::ServerImpersonator;
 use crate::sub_lib::cryptde::PublicKey;
+use crate::sub_lib::framer::FramingViolation;
 
 pub struct ServerImpersonatorHttp {}
 
@@ -51,6 +52,63 @@ impl ServerImpersonator for ServerImpersonatorHttp {
             Set up a funded consuming wallet and try again.",
         )
     }
+
+    fn framing_violation_response(&self, violation: &FramingViolation) -> Vec<u8> {
+        let (status, title) = match violation {
+            FramingViolation::RequestLineTooLong(_) => (414, "URI Too Long"),
+            FramingViolation::TooManyHeaders(_) | FramingViolation::HeadersTooLarge(_) => {
+                (431, "Request Header Fields Too Large")
+            }
+            FramingViolation::RecordTooLarge(_) => (400, "Bad Request"),
+        };
+        ServerImpersonatorHttp::make_error_response(
+            status,
+            title,
+            &format!("Request refused: {}", violation),
+            REQUEST_TOO_LARGE_CONTENT,
+        )
+    }
+
+    fn kill_switch_engaged(&self, minimum_hop_count: usize) -> Vec<u8> {
+        ServerImpersonatorHttp::make_error_response(
+            503,
+            "Kill Switch Engaged",
+            &format!("Can't build {}-hop routes", minimum_hop_count),
+            &format!(
+                "Prometheus can't build {}-hop routes through the Network right now, and this Node \
+                 was started with --kill-switch, so it won't send your traffic over shorter routes \
+                 or routes it built earlier. It will start again as soon as enough is known about \
+                 the Network to build routes that long.",
+                minimum_hop_count
+            ),
+        )
+    }
+
+    fn memory_pressure(&self) -> Vec<u8> {
+        ServerImpersonatorHttp::make_error_response(
+            503,
+            "Node Short of Memory",
+            "Can't take on new streams",
+            "Your Node is using more memory than it was told it could, so it's turning away new \
+             connections until it has caught up with the ones it already has. Try again shortly.",
+        )
+    }
+
+    fn budget_exhausted(&self, profile_name: &str) -> Vec<u8> {
+        ServerImpersonatorHttp::make_error_response(
+            503,
+            "Budget Used Up",
+            &format!(
+                "Consuming profile '{}' has used up its budget",
+                profile_name
+            ),
+            &format!(
+                "Your Node was told to let the '{}' consuming profile spend only so much on \
+                 services from the Network, and it has spent it all, so its traffic is refused.",
+                profile_name
+            ),
+        )
+    }
 }
 
 impl ServerImpersonatorHttp {
@@ -82,6 +140,10 @@ const ERROR_TEMPLATE: &str = "<html>\n\
                               </body>\n\
                               </html>\n";
 
+const REQUEST_TOO_LARGE_CONTENT: &str =
+    "Your request is larger than Prometheus is willing to hold while passing it along, so it \
+     hasn't been sent. If it's a request you meant to make, try a smaller one.";
+
 const HTTP_RESPONSE_TEMPLATE: &str = "HTTP/1.1 {status} Routing Error\r\n\
                                       Content-Type: text/html\r\n\
                                       Content-Length: {length}\r\n\
@@ -231,4 +293,92 @@ mod tests {
         );
         assert_eq!(expected, result);
     }
+
+    #[test]
+    fn framing_violation_response_picks_the_status_for_the_limit_broken() {
+        let subject = ServerImpersonatorHttp {};
+
+        let result = vec![
+            FramingViolation::RequestLineTooLong(8192),
+            FramingViolation::TooManyHeaders(100),
+            FramingViolation::HeadersTooLarge(65536),
+        ]
+        .into_iter()
+        .map(|violation| subject.framing_violation_response(&violation))
+        .collect::<Vec<Vec<u8>>>();
+
+        assert_eq!(
+            result,
+            vec![
+                ServerImpersonatorHttp::make_error_response(
+                    414,
+                    "URI Too Long",
+                    "Request refused: request line longer than 8192 bytes",
+                    REQUEST_TOO_LARGE_CONTENT,
+                ),
+                ServerImpersonatorHttp::make_error_response(
+                    431,
+                    "Request Header Fields Too Large",
+                    "Request refused: more than 100 headers",
+                    REQUEST_TOO_LARGE_CONTENT,
+                ),
+                ServerImpersonatorHttp::make_error_response(
+                    431,
+                    "Request Header Fields Too Large",
+                    "Request refused: headers longer than 65536 bytes",
+                    REQUEST_TOO_LARGE_CONTENT,
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn memory_pressure_produces_expected_error_page() {
+        let subject = ServerImpersonatorHttp {};
+
+        let result = subject.memory_pressure();
+
+        let expected = ServerImpersonatorHttp::make_error_response(
+            503,
+            "Node Short of Memory",
+            "Can't take on new streams",
+            "Your Node is using more memory than it was told it could, so it's turning away new \
+             connections until it has caught up with the ones it already has. Try again shortly.",
+        );
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn budget_exhausted_produces_expected_error_page() {
+        let subject = ServerImpersonatorHttp {};
+
+        let result = subject.budget_exhausted("work");
+
+        let expected = ServerImpersonatorHttp::make_error_response(
+            503,
+            "Budget Used Up",
+            "Consuming profile 'work' has used up its budget",
+            "Your Node was told to let the 'work' consuming profile spend only so much on \
+             services from the Network, and it has spent it all, so its traffic is refused.",
+        );
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn kill_switch_engaged_produces_expected_error_page() {
+        let subject = ServerImpersonatorHttp {};
+
+        let result = subject.kill_switch_engaged(3);
+
+        let expected = ServerImpersonatorHttp::make_error_response(
+            503,
+            "Kill Switch Engaged",
+            "Can't build 3-hop routes",
+            "Prometheus can't build 3-hop routes through the Network right now, and this Node \
+             was started with --kill-switch, so it won't send your traffic over shorter routes \
+             or routes it built earlier. It will start again as soon as enough is known about \
+             the Network to build routes that long.",
+        );
+        assert_eq!(expected, result);
+    }
 }
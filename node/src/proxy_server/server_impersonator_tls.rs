@@ -1,6 +1,7 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 use crate::proxy_server::protocol_pack::ServerImpersonator;
 use crate::sub_lib::cryptde::PublicKey;
+use crate::sub_lib::framer::FramingViolation;
 
 pub struct ServerImpersonatorTls {}
 
@@ -20,6 +21,22 @@ impl ServerImpersonator for ServerImpersonatorTls {
     fn consuming_wallet_absent(&self) -> Vec<u8> {
         Vec::from(&TLS_INTERNAL_ERROR_ALERT[..])
     }
+
+    fn framing_violation_response(&self, _violation: &FramingViolation) -> Vec<u8> {
+        Vec::from(&TLS_RECORD_OVERFLOW_ALERT[..])
+    }
+
+    fn kill_switch_engaged(&self, _minimum_hop_count: usize) -> Vec<u8> {
+        Vec::from(&TLS_INTERNAL_ERROR_ALERT[..])
+    }
+
+    fn memory_pressure(&self) -> Vec<u8> {
+        Vec::from(&TLS_INTERNAL_ERROR_ALERT[..])
+    }
+
+    fn budget_exhausted(&self, _profile_name: &str) -> Vec<u8> {
+        Vec::from(&TLS_INTERNAL_ERROR_ALERT[..])
+    }
 }
 
 const TLS_INTERNAL_ERROR_ALERT: [u8; 7] = [
@@ -30,6 +47,14 @@ const TLS_INTERNAL_ERROR_ALERT: [u8; 7] = [
     0x50, // internal_error alert
 ];
 
+const TLS_RECORD_OVERFLOW_ALERT: [u8; 7] = [
+    0x15, // alert
+    0x03, 0x03, // TLS 1.2
+    0x00, 0x02, // packet length
+    0x02, // fatal alert
+    0x16, // record_overflow alert
+];
+
 const TLS_UNRECOGNIZED_NAME_ALERT: [u8; 7] = [
     0x15, // alert
     0x03, 0x03, // TLS 1.2
@@ -69,4 +94,40 @@ mod tests {
 
         assert_eq!(Vec::from(&TLS_INTERNAL_ERROR_ALERT[..]), result);
     }
+
+    #[test]
+    fn kill_switch_engaged_produces_internal_error_alert() {
+        let subject = ServerImpersonatorTls {};
+
+        let result = subject.kill_switch_engaged(3);
+
+        assert_eq!(Vec::from(&TLS_INTERNAL_ERROR_ALERT[..]), result);
+    }
+
+    #[test]
+    fn memory_pressure_produces_internal_error_alert() {
+        let subject = ServerImpersonatorTls {};
+
+        let result = subject.memory_pressure();
+
+        assert_eq!(Vec::from(&TLS_INTERNAL_ERROR_ALERT[..]), result);
+    }
+
+    #[test]
+    fn budget_exhausted_produces_internal_error_alert() {
+        let subject = ServerImpersonatorTls {};
+
+        let result = subject.budget_exhausted("work");
+
+        assert_eq!(Vec::from(&TLS_INTERNAL_ERROR_ALERT[..]), result);
+    }
+
+    #[test]
+    fn framing_violation_response_produces_record_overflow_alert() {
+        let subject = ServerImpersonatorTls {};
+
+        let result = subject.framing_violation_response(&FramingViolation::RecordTooLarge(18432));
+
+        assert_eq!(Vec::from(&TLS_RECORD_OVERFLOW_ALERT[..]), result);
+    }
 }
@@ -0,0 +1,310 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::persistent_configuration::SUBMISSION_PORT;
+use crate::proxy_server::protocol_pack::{Host, ProtocolPack, ServerImpersonator};
+use crate::proxy_server::server_impersonator_tls::ServerImpersonatorTls;
+use crate::proxy_server::tls_protocol_pack::TlsProtocolPack;
+use crate::sub_lib::cryptde::PlainData;
+use crate::sub_lib::proxy_server::ProxyProtocol;
+
+// How many replies the Node makes itself to a mail client before the client starts TLS: the
+// greeting, the answer to EHLO, and the go-ahead for STARTTLS. Responses from the mail server
+// are numbered after them.
+pub const SMTP_PREAMBLE_REPLIES: u64 = 3;
+// The longest command line RFC 5321 allows
+const MAX_COMMAND_LINE_LENGTH: usize = 512;
+
+// Mail submission with STARTTLS starts in the clear, and the mail client says nothing about which
+// server it wants until it sends its ClientHello. So the Node plays the server's part of the
+// plaintext preamble itself (see SmtpPreamble), and only the TLS that follows is framed and sent
+// through the Network, where the exit replays the preamble to the real server.
+pub struct SmtpProtocolPack {}
+
+impl ProtocolPack for SmtpProtocolPack {
+    fn proxy_protocol(&self) -> ProxyProtocol {
+        ProxyProtocol::SMTP
+    }
+
+    fn standard_port(&self) -> u16 {
+        SUBMISSION_PORT
+    }
+
+    fn find_host(&self, data: &PlainData) -> Option<Host> {
+        TlsProtocolPack {}.find_host(data)
+    }
+
+    // By the time anything has been framed, the mail client is speaking TLS
+    fn server_impersonator(&self) -> Box<dyn ServerImpersonator> {
+        Box::new(ServerImpersonatorTls {})
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SmtpReply {
+    pub data: Vec<u8>,
+    pub sequence_number: u64,
+    pub last_data: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SmtpPreambleStep {
+    Greeting,
+    Ehlo,
+    StartTls,
+    Tls,
+    Refused,
+}
+
+// The Node's half of the plaintext conversation with a mail client on the submission port
+pub struct SmtpPreamble {
+    step: SmtpPreambleStep,
+    pending: Vec<u8>,
+}
+
+impl Default for SmtpPreamble {
+    fn default() -> Self {
+        SmtpPreamble {
+            step: SmtpPreambleStep::Greeting,
+            pending: vec![],
+        }
+    }
+}
+
+impl SmtpPreamble {
+    pub fn new() -> SmtpPreamble {
+        SmtpPreamble::default()
+    }
+
+    pub fn take_greeting(&mut self) -> Option<SmtpReply> {
+        if self.step != SmtpPreambleStep::Greeting {
+            return None;
+        }
+        self.step = SmtpPreambleStep::Ehlo;
+        Some(Self::reply(
+            b"220 localhost ESMTP PrometheusNode\r\n",
+            0,
+            false,
+        ))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.step == SmtpPreambleStep::Tls
+    }
+
+    // Returns the replies to send the mail client and, once it has asked for STARTTLS, whatever
+    // it sent after that
+    pub fn add_data(&mut self, data: &[u8]) -> (Vec<SmtpReply>, Vec<u8>) {
+        if self.step == SmtpPreambleStep::Refused {
+            return (vec![], vec![]);
+        }
+        self.pending.extend_from_slice(data);
+        let mut replies = vec![];
+        while let Some(line) = self.take_line() {
+            let command = String::from_utf8_lossy(&line).to_uppercase();
+            let verb = command.split_whitespace().next().unwrap_or("");
+            let reply = match (self.step, verb) {
+                (SmtpPreambleStep::Ehlo, "EHLO") => {
+                    self.step = SmtpPreambleStep::StartTls;
+                    Self::reply(b"250-localhost\r\n250 STARTTLS\r\n", 1, false)
+                }
+                (SmtpPreambleStep::StartTls, "STARTTLS") => {
+                    self.step = SmtpPreambleStep::Tls;
+                    Self::reply(b"220 Ready to start TLS\r\n", 2, false)
+                }
+                (step, "QUIT") => self.refuse(step, b"221 Bye\r\n"),
+                (step, _) => self.refuse(step, b"530 Must issue a STARTTLS command first\r\n"),
+            };
+            replies.push(reply);
+        }
+        if self.awaits_command() && self.pending.len() > MAX_COMMAND_LINE_LENGTH {
+            let step = self.step;
+            replies.push(self.refuse(step, b"500 Line too long\r\n"));
+        }
+        let remainder = if self.is_finished() {
+            self.pending.drain(..).collect()
+        } else {
+            vec![]
+        };
+        (replies, remainder)
+    }
+
+    fn awaits_command(&self) -> bool {
+        self.step == SmtpPreambleStep::Ehlo || self.step == SmtpPreambleStep::StartTls
+    }
+
+    fn take_line(&mut self) -> Option<Vec<u8>> {
+        if !self.awaits_command() {
+            return None;
+        }
+        let end = self.pending.windows(2).position(|pair| pair == b"\r\n")?;
+        let line = self.pending[..end].to_vec();
+        self.pending.drain(..end + 2);
+        Some(line)
+    }
+
+    // The Node writes the refusal and closes the stream; the mail client gets no further
+    fn refuse(&mut self, step: SmtpPreambleStep, data: &[u8]) -> SmtpReply {
+        self.step = SmtpPreambleStep::Refused;
+        self.pending.clear();
+        let sequence_number = match step {
+            SmtpPreambleStep::Ehlo => 1,
+            _ => 2,
+        };
+        Self::reply(data, sequence_number, true)
+    }
+
+    fn reply(data: &[u8], sequence_number: u64, last_data: bool) -> SmtpReply {
+        SmtpReply {
+            data: data.to_vec(),
+            sequence_number,
+            last_data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn knows_its_protocol_and_standard_port() {
+        let subject = SmtpProtocolPack {};
+
+        assert_eq!(subject.proxy_protocol(), ProxyProtocol::SMTP);
+        assert_eq!(subject.standard_port(), 587);
+    }
+
+    #[test]
+    fn finds_mail_server_in_client_hello() {
+        #[rustfmt::skip]
+        let data = PlainData::new(&[
+            0x16, // content_type: Handshake
+            0x00, 0x00, 0x00, 0x00, // version, length: don't care
+            0x01, // handshake_type: ClientHello
+            0x00, 0x00, 0x00, 0x00, 0x00, // length, version: don't care
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // random: don't care
+            0x00, // session_id_length
+            0x00, 0x00, // cipher_suites_length
+            0x00, // compression_methods_length
+            0x00, 0x13, // extensions_length
+            0x00, 0x00, // extension_type: server_name
+            0x00, 0x0F, // extension_length
+            0x00, 0x0D, // server_name_list_length
+            0x00, // server_name_type
+            0x00, 0x0A, // server_name_length
+            's' as u8, 'e' as u8, 'r' as u8, 'v' as u8, 'e' as u8, 'r' as u8, '.' as u8, 'c' as u8,
+            'o' as u8, 'm' as u8, // server_name
+        ]);
+
+        let result = SmtpProtocolPack {}.find_host(&data);
+
+        assert_eq!(
+            result,
+            Some(Host {
+                name: "server.com".to_string(),
+                port: None,
+            })
+        );
+    }
+
+    #[test]
+    fn preamble_greets_once_answers_ehlo_and_starttls_then_hands_over_the_rest() {
+        let mut subject = SmtpPreamble::new();
+
+        let greeting = subject.take_greeting();
+        let second_greeting = subject.take_greeting();
+        let (ehlo_replies, ehlo_remainder) = subject.add_data(b"EHLO laptop.local\r\nSTART");
+        let (starttls_replies, starttls_remainder) = subject.add_data(b"TLS\r\n\x16\x03\x01");
+
+        assert_eq!(
+            greeting,
+            Some(SmtpReply {
+                data: b"220 localhost ESMTP PrometheusNode\r\n".to_vec(),
+                sequence_number: 0,
+                last_data: false,
+            })
+        );
+        assert_eq!(second_greeting, None);
+        assert_eq!(
+            ehlo_replies,
+            vec![SmtpReply {
+                data: b"250-localhost\r\n250 STARTTLS\r\n".to_vec(),
+                sequence_number: 1,
+                last_data: false,
+            }]
+        );
+        assert_eq!(ehlo_remainder, Vec::<u8>::new());
+        assert_eq!(
+            starttls_replies,
+            vec![SmtpReply {
+                data: b"220 Ready to start TLS\r\n".to_vec(),
+                sequence_number: 2,
+                last_data: false,
+            }]
+        );
+        assert_eq!(starttls_remainder, b"\x16\x03\x01".to_vec());
+        assert!(subject.is_finished());
+        assert_eq!(
+            subject.add_data(b"\x01\x00"),
+            (vec![], b"\x01\x00".to_vec())
+        );
+    }
+
+    #[test]
+    fn preamble_refuses_anything_but_starttls_and_then_ignores_the_client() {
+        let mut subject = SmtpPreamble::new();
+        subject.take_greeting();
+        subject.add_data(b"ehlo laptop.local\r\n");
+
+        let (replies, remainder) = subject.add_data(b"AUTH PLAIN c2VjcmV0\r\nSTARTTLS\r\n");
+        let after_refusal = subject.add_data(b"STARTTLS\r\n");
+
+        assert_eq!(
+            replies,
+            vec![SmtpReply {
+                data: b"530 Must issue a STARTTLS command first\r\n".to_vec(),
+                sequence_number: 2,
+                last_data: true,
+            }]
+        );
+        assert_eq!(remainder, Vec::<u8>::new());
+        assert_eq!(after_refusal, (vec![], vec![]));
+        assert!(!subject.is_finished());
+    }
+
+    #[test]
+    fn preamble_says_goodbye_to_quit() {
+        let mut subject = SmtpPreamble::new();
+        subject.take_greeting();
+
+        let (replies, _) = subject.add_data(b"QUIT\r\n");
+
+        assert_eq!(
+            replies,
+            vec![SmtpReply {
+                data: b"221 Bye\r\n".to_vec(),
+                sequence_number: 1,
+                last_data: true,
+            }]
+        );
+    }
+
+    #[test]
+    fn preamble_refuses_overlong_command_line() {
+        let mut subject = SmtpPreamble::new();
+        subject.take_greeting();
+
+        let (replies, _) = subject.add_data(&[b'X'; MAX_COMMAND_LINE_LENGTH + 1]);
+
+        assert_eq!(
+            replies,
+            vec![SmtpReply {
+                data: b"500 Line too long\r\n".to_vec(),
+                sequence_number: 1,
+                last_data: true,
+            }]
+        );
+    }
+}
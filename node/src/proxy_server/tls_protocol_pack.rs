@@ -5,6 +5,9 @@ use crate::sub_lib::binary_traverser::BinaryTraverser;
 use crate::sub_lib::cryptde::PlainData;
 use crate::sub_lib::proxy_server::ProxyProtocol;
 
+// RFC 5246 lets a record's ciphertext run 2048 bytes past its 2^14-byte plaintext, and no further
+pub const MAX_TLS_RECORD_LENGTH: usize = 16384 + 2048;
+
 pub struct TlsProtocolPack {}
 
 impl ProtocolPack for TlsProtocolPack {
@@ -45,8 +48,8 @@ impl TlsProtocolPack {
         let handshake_message_type_position = 5;
         let client_hello_message_type = 1u8;
         xvsr.advance(handshake_message_type_position - xvsr.offset())
-            .expect("Internal Error");
-        xvsr.get_u8() == Ok(client_hello_message_type)
+            .is_ok()
+            && xvsr.get_u8() == Ok(client_hello_message_type)
     }
 
     fn host_name_from_client_hello(xvsr: &mut BinaryTraverser) -> Result<String, ()> {
@@ -182,6 +185,18 @@ mod tests {
         assert_eq!(None, result);
     }
 
+    #[test]
+    fn does_not_panic_for_packet_truncated_before_length() {
+        let data = PlainData::new(&[
+            0x16, // content_type: Handshake
+            0x03, // version: don't care
+        ]);
+
+        let result = TlsProtocolPack {}.find_host(&data);
+
+        assert_eq!(None, result);
+    }
+
     #[test]
     fn does_not_panic_for_packet_truncated_before_handshake_type() {
         let data = PlainData::new(&[
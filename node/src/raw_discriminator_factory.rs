@@ -0,0 +1,48 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::discriminator::Discriminator;
+use crate::discriminator::DiscriminatorFactory;
+use crate::null_masquerader::NullMasquerader;
+use crate::sub_lib::raw_framer::RawFramer;
+
+#[derive(Debug, Default)]
+pub struct RawDiscriminatorFactory {}
+
+impl DiscriminatorFactory for RawDiscriminatorFactory {
+    fn make(&self) -> Discriminator {
+        Discriminator::new(
+            Box::new(RawFramer::new()),
+            vec![Box::new(NullMasquerader::new())],
+        )
+    }
+
+    fn duplicate(&self) -> Box<dyn DiscriminatorFactory> {
+        Box::new(RawDiscriminatorFactory {})
+    }
+}
+
+impl RawDiscriminatorFactory {
+    pub fn new() -> RawDiscriminatorFactory {
+        Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discriminator::UnmaskedChunk;
+
+    #[test]
+    fn factory_makes_discriminator_that_passes_anything_to_proxy_server() {
+        let data: &[u8] = b"\x00\x00\x00\x08\x04\xd2\x16\x2f";
+        let subject = RawDiscriminatorFactory::new();
+
+        let mut result = subject.make();
+
+        result.add_data(data);
+        assert_eq!(
+            result.take_chunk(),
+            Some(UnmaskedChunk::new(Vec::from(data), true, true))
+        );
+        assert_eq!(result.take_chunk(), None);
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::privilege_drop::{IdWrapper, IdWrapperReal};
+use crate::sub_lib::main_tools::StdStreams;
+use std::io;
+use std::process::Command;
+
+// The exit code by which a Node asks whatever started it to start it again
+pub const RESTART_EXIT_CODE: i32 = 75;
+// Set in a Node's environment by a parent that starts it again when it exits with
+// RESTART_EXIT_CODE: the restart keeper below, or the daemon
+pub const RESTART_KEEPER_VARIABLE: &str = "PROMETHEUS_NODE_RESTART_KEEPER";
+
+// A Node started as root drops its privileges once its ports are open, and a Node that has
+// dropped them can't open its ports again. So a root Node runs as the child of a small process
+// that stays root, does nothing else, and starts the Node again whenever it asks to be restarted.
+// Under systemd there's no need: systemd restarts the Node itself.
+pub fn is_wanted() -> bool {
+    keeper_wanted(
+        IdWrapperReal {}.getuid() == 0,
+        is_kept(),
+        cfg!(target_os = "windows"),
+    )
+}
+
+// Whether something privileged is waiting to restart this Node
+pub fn is_kept() -> bool {
+    std::env::var_os(RESTART_KEEPER_VARIABLE).is_some()
+        || std::env::var_os("NOTIFY_SOCKET").is_some()
+}
+
+fn keeper_wanted(privileged: bool, kept: bool, windows: bool) -> bool {
+    // Windows doesn't drop privileges, so it can restart the Node itself
+    privileged && !kept && !windows
+}
+
+pub trait NodeRunner {
+    // Runs the Node to completion and returns its exit code
+    fn run(&self, args: &[String]) -> io::Result<i32>;
+}
+
+pub struct NodeRunnerReal {}
+
+impl NodeRunner for NodeRunnerReal {
+    fn run(&self, args: &[String]) -> io::Result<i32> {
+        let mut command = Command::new(std::env::current_exe()?);
+        command.args(args).env(RESTART_KEEPER_VARIABLE, "keeper");
+        die_with_keeper(&mut command);
+        // Killed by a signal, it has no exit code, and isn't asking for anything
+        Ok(command.status()?.code().unwrap_or(1))
+    }
+}
+
+// So that a Node doesn't outlive a keeper that's been killed
+#[cfg(target_os = "linux")]
+fn die_with_keeper(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+    unsafe {
+        command.pre_exec(|| {
+            if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM) == 0 {
+                Ok(())
+            } else {
+                Err(io::Error::last_os_error())
+            }
+        });
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn die_with_keeper(_command: &mut Command) {}
+
+pub fn keep(runner: &dyn NodeRunner, args: &[String], streams: &mut StdStreams<'_>) -> i32 {
+    loop {
+        match runner.run(&args[1..]) {
+            Ok(RESTART_EXIT_CODE) => {
+                writeln!(streams.stderr, "Restarting the Node").expect("Couldn't write to stderr")
+            }
+            Ok(exit_code) => return exit_code,
+            Err(e) => {
+                writeln!(streams.stderr, "Couldn't start the Node: {}", e)
+                    .expect("Couldn't write to stderr");
+                return 1;
+            }
+        }
+    }
+}
+
+// Ctrl-C reaches the Node as well; the keeper waits to hear how it exits
+#[cfg(not(target_os = "windows"))]
+pub fn ignore_interrupts() {
+    unsafe {
+        libc::signal(libc::SIGINT, libc::SIG_IGN);
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub fn ignore_interrupts() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::FakeStreamHolder;
+    use std::cell::RefCell;
+
+    struct NodeRunnerMock {
+        run_params: RefCell<Vec<Vec<String>>>,
+        run_results: RefCell<Vec<io::Result<i32>>>,
+    }
+
+    impl NodeRunner for NodeRunnerMock {
+        fn run(&self, args: &[String]) -> io::Result<i32> {
+            self.run_params.borrow_mut().push(args.to_vec());
+            self.run_results.borrow_mut().remove(0)
+        }
+    }
+
+    impl NodeRunnerMock {
+        fn new(run_results: Vec<io::Result<i32>>) -> NodeRunnerMock {
+            NodeRunnerMock {
+                run_params: RefCell::new(vec![]),
+                run_results: RefCell::new(run_results),
+            }
+        }
+    }
+
+    #[test]
+    fn only_a_privileged_node_that_nothing_else_restarts_needs_a_keeper() {
+        assert!(keeper_wanted(true, false, false));
+        assert!(!keeper_wanted(false, false, false));
+        assert!(!keeper_wanted(true, true, false));
+        assert!(!keeper_wanted(true, false, true));
+    }
+
+    #[test]
+    fn keeper_starts_the_node_again_each_time_it_asks_and_then_passes_on_its_exit_code() {
+        let runner = NodeRunnerMock::new(vec![Ok(RESTART_EXIT_CODE), Ok(RESTART_EXIT_CODE), Ok(3)]);
+        let mut holder = FakeStreamHolder::new();
+        let args = vec![
+            "PrometheusNode".to_string(),
+            "--dns-servers".to_string(),
+            "1.1.1.1".to_string(),
+        ];
+
+        let result = keep(&runner, &args, &mut holder.streams());
+
+        assert_eq!(result, 3);
+        assert_eq!(*runner.run_params.borrow(), vec![args[1..].to_vec(); 3]);
+        assert_eq!(
+            holder.stderr.get_string(),
+            "Restarting the Node\nRestarting the Node\n"
+        );
+    }
+
+    #[test]
+    fn keeper_gives_up_if_the_node_cant_be_started() {
+        let runner = NodeRunnerMock::new(vec![Err(io::Error::from(io::ErrorKind::NotFound))]);
+        let mut holder = FakeStreamHolder::new();
+
+        let result = keep(
+            &runner,
+            &["PrometheusNode".to_string()],
+            &mut holder.streams(),
+        );
+
+        assert_eq!(result, 1);
+        assert_eq!(
+            holder.stderr.get_string(),
+            "Couldn't start the Node: entity not found\n"
+        );
+    }
+}
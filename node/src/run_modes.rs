@@ -1,35 +1,168 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
+use crate::accountant::financials_dumper;
+use crate::daemon;
 use crate::database::config_dumper;
+use crate::database::data_archive;
+use crate::firewall;
+use crate::node_configurator::completions;
+use crate::node_configurator::config_checker;
 use crate::node_configurator::node_configurator_generate_wallet::NodeConfiguratorGenerateWallet;
 use crate::node_configurator::node_configurator_recover_wallet::NodeConfiguratorRecoverWallet;
+use crate::node_configurator::node_setup;
+use crate::node_configurator::secret_store::{remember_wallet_password, SecretStoreReal};
 use crate::node_configurator::{NodeConfigurator, WalletCreationConfig};
 use crate::privilege_drop::{PrivilegeDropper, PrivilegeDropperReal};
+use crate::restart_keeper;
+use crate::restart_keeper::NodeRunnerReal;
 use crate::server_initializer::ServerInitializer;
+use crate::service;
 use crate::sub_lib::main_tools::{Command, StdStreams};
+use crate::ui_gateway::descriptor_printer;
+use crate::ui_gateway::dns_leak_reporter;
+use crate::ui_gateway::neighbor_lister;
 use actix::System;
 use futures::future::Future;
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum Mode {
     GenerateWallet,
     RecoverWallet,
+    CheckConfig,
     DumpConfig,
+    DumpFinancials,
+    BackupData,
+    RestoreData,
+    ListNeighbors,
+    PrintDescriptor,
+    DnsLeakTest,
     RunTheNode,
+    Setup,
+    Daemon,
+    Service,
+    Completions,
+    FirewallKeeper,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ModeFlag {
+    None,
+    Deprecated,
+    Supported,
+}
+
+// Every mode is a subcommand. Most can also be selected by a flag of the same name, which the
+// mode's own parser expects, so the subcommand is translated into its flag. Flags that predate
+// subcommands keep working, with a warning, until the next release.
+const SUBCOMMANDS: [(&str, Mode, ModeFlag); 16] = [
+    ("run", Mode::RunTheNode, ModeFlag::None),
+    ("setup", Mode::Setup, ModeFlag::None),
+    ("daemon", Mode::Daemon, ModeFlag::None),
+    ("service", Mode::Service, ModeFlag::None),
+    (
+        "generate-wallet",
+        Mode::GenerateWallet,
+        ModeFlag::Deprecated,
+    ),
+    ("recover-wallet", Mode::RecoverWallet, ModeFlag::Deprecated),
+    ("check-config", Mode::CheckConfig, ModeFlag::Deprecated),
+    ("dump-config", Mode::DumpConfig, ModeFlag::Deprecated),
+    (
+        "dump-financials",
+        Mode::DumpFinancials,
+        ModeFlag::Deprecated,
+    ),
+    ("backup-data", Mode::BackupData, ModeFlag::Supported),
+    ("restore-data", Mode::RestoreData, ModeFlag::Supported),
+    ("list-neighbors", Mode::ListNeighbors, ModeFlag::Deprecated),
+    (
+        "print-descriptor",
+        Mode::PrintDescriptor,
+        ModeFlag::Deprecated,
+    ),
+    ("dns-leak-test", Mode::DnsLeakTest, ModeFlag::Supported),
+    ("completions", Mode::Completions, ModeFlag::None),
+    // Started by the Node itself, never by hand
+    ("firewall-keeper", Mode::FirewallKeeper, ModeFlag::None),
+];
+
 pub fn go(args: &Vec<String>, streams: &mut StdStreams<'_>) -> i32 {
-    match determine_mode(args) {
+    let (mode, args) = match translate_subcommand(args) {
+        Some(translation) => translation,
+        None => {
+            warn_about_mode_flags(args, streams);
+            (determine_mode(args), args.clone())
+        }
+    };
+    let args = &args;
+    match mode {
         Mode::GenerateWallet => generate_wallet(args, streams),
         Mode::RecoverWallet => recover_wallet(args, streams),
+        Mode::CheckConfig => check_config(args, streams),
         Mode::DumpConfig => dump_config(args, streams),
+        Mode::DumpFinancials => dump_financials(args, streams),
+        Mode::BackupData => backup_data(args, streams),
+        Mode::RestoreData => restore_data(args, streams),
+        Mode::ListNeighbors => list_neighbors(args, streams),
+        Mode::PrintDescriptor => print_descriptor(args, streams),
+        Mode::DnsLeakTest => dns_leak_test(args, streams),
         Mode::RunTheNode => run_the_node(args, streams),
+        Mode::Setup => setup(args, streams),
+        Mode::Daemon => run_daemon(args, streams),
+        Mode::Service => run_service(args, streams),
+        Mode::Completions => completions(args, streams),
+        Mode::FirewallKeeper => firewall_keeper(args, streams),
+    }
+}
+
+fn translate_subcommand(args: &Vec<String>) -> Option<(Mode, Vec<String>)> {
+    let subcommand = args.get(1)?;
+    let (name, mode, flag) = SUBCOMMANDS.iter().find(|(name, _, _)| name == subcommand)?;
+    let mut translated = vec![args[0].clone()];
+    if *flag != ModeFlag::None {
+        translated.push(format!("--{}", name));
     }
+    translated.extend(args.iter().skip(2).cloned());
+    Some((*mode, translated))
+}
+
+fn warn_about_mode_flags(args: &Vec<String>, streams: &mut StdStreams<'_>) {
+    SUBCOMMANDS
+        .iter()
+        .filter(|(_, _, flag)| *flag == ModeFlag::Deprecated)
+        .map(|(name, _, _)| name)
+        .filter(|name| {
+            let flag = format!("--{}", name);
+            args.iter()
+                .any(|arg| *arg == flag || arg.starts_with(&format!("{}=", flag)))
+        })
+        .for_each(|name| {
+            writeln!(
+                streams.stderr,
+                "--{} is deprecated and will be removed in the next release; use the {} subcommand instead",
+                name, name
+            )
+            .expect("Couldn't write to stderr")
+        });
 }
 
 fn determine_mode(args: &Vec<String>) -> Mode {
-    if args.contains(&"--dump-config".to_string()) {
+    if args.contains(&"--check-config".to_string()) {
+        Mode::CheckConfig
+    } else if args.contains(&"--dump-config".to_string()) {
         Mode::DumpConfig
+    } else if has_flag(args, "--dump-financials") {
+        Mode::DumpFinancials
+    } else if has_flag(args, "--backup-data") {
+        Mode::BackupData
+    } else if has_flag(args, "--restore-data") {
+        Mode::RestoreData
+    } else if args.contains(&"--list-neighbors".to_string()) {
+        Mode::ListNeighbors
+    } else if args.contains(&"--print-descriptor".to_string()) {
+        Mode::PrintDescriptor
+    } else if has_flag(args, "--dns-leak-test") {
+        Mode::DnsLeakTest
     } else if args.contains(&"--recover-wallet".to_string()) {
         Mode::RecoverWallet
     } else if args.contains(&"--generate-wallet".to_string()) {
@@ -39,7 +172,16 @@ fn determine_mode(args: &Vec<String>) -> Mode {
     }
 }
 
+fn has_flag(args: &Vec<String>, flag: &str) -> bool {
+    args.iter()
+        .any(|arg| arg == flag || arg.starts_with(&format!("{}=", flag)))
+}
+
 fn run_the_node(args: &Vec<String>, streams: &mut StdStreams<'_>) -> i32 {
+    if restart_keeper::is_wanted() {
+        restart_keeper::ignore_interrupts();
+        return restart_keeper::keep(&NodeRunnerReal {}, args, streams);
+    }
     let system = System::new("main");
 
     let mut server_initializer = ServerInitializer::new();
@@ -62,10 +204,58 @@ fn recover_wallet(args: &Vec<String>, streams: &mut StdStreams<'_>) -> i32 {
     configuration_run(args, streams, &configurator)
 }
 
+fn setup(args: &Vec<String>, streams: &mut StdStreams<'_>) -> i32 {
+    node_setup::setup(args, streams)
+}
+
+fn run_daemon(args: &Vec<String>, streams: &mut StdStreams<'_>) -> i32 {
+    daemon::daemon(args, streams)
+}
+
+fn run_service(args: &Vec<String>, streams: &mut StdStreams<'_>) -> i32 {
+    service::service(args, streams)
+}
+
+fn check_config(args: &Vec<String>, streams: &mut StdStreams<'_>) -> i32 {
+    config_checker::check_config(args, streams)
+}
+
 fn dump_config(args: &Vec<String>, streams: &mut StdStreams<'_>) -> i32 {
     config_dumper::dump_config(args, streams)
 }
 
+fn dump_financials(args: &Vec<String>, streams: &mut StdStreams<'_>) -> i32 {
+    financials_dumper::dump_financials(args, streams)
+}
+
+fn backup_data(args: &Vec<String>, streams: &mut StdStreams<'_>) -> i32 {
+    data_archive::backup_data(args, streams)
+}
+
+fn restore_data(args: &Vec<String>, streams: &mut StdStreams<'_>) -> i32 {
+    data_archive::restore_data(args, streams)
+}
+
+fn list_neighbors(args: &Vec<String>, streams: &mut StdStreams<'_>) -> i32 {
+    neighbor_lister::list_neighbors(args, streams)
+}
+
+fn print_descriptor(args: &Vec<String>, streams: &mut StdStreams<'_>) -> i32 {
+    descriptor_printer::print_descriptor(args, streams)
+}
+
+fn dns_leak_test(args: &Vec<String>, streams: &mut StdStreams<'_>) -> i32 {
+    dns_leak_reporter::dns_leak_test(args, streams)
+}
+
+fn completions(args: &Vec<String>, streams: &mut StdStreams<'_>) -> i32 {
+    completions::completions(args, streams)
+}
+
+fn firewall_keeper(args: &Vec<String>, streams: &mut StdStreams<'_>) -> i32 {
+    firewall::firewall_keeper(args, streams)
+}
+
 fn configuration_run(
     args: &Vec<String>,
     streams: &mut StdStreams<'_>,
@@ -73,12 +263,26 @@ fn configuration_run(
 ) -> i32 {
     let config = configurator.configure(args, streams);
     PrivilegeDropperReal::new().drop_privileges(&config.real_user);
-    0
+    // The keychain belongs to the real user, so it has to wait until privileges are dropped
+    match &config.keychain_data_directory_opt {
+        Some(data_directory) => {
+            match remember_wallet_password(&config, &SecretStoreReal::new(data_directory)) {
+                Ok(()) => 0,
+                Err(e) => {
+                    writeln!(streams.stderr, "{}. The wallet was created anyway.", e)
+                        .expect("Couldn't write to stderr");
+                    1
+                }
+            }
+        }
+        None => 0,
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::FakeStreamHolder;
 
     #[test]
     fn generate_wallet() {
@@ -94,6 +298,17 @@ mod tests {
             .for_each(|args| check_mode(args, Mode::RecoverWallet));
     }
 
+    #[test]
+    fn check_config() {
+        [
+            vec!["--check-config"],
+            vec!["--check-config", "--dump-config"],
+            vec!["--generate-wallet", "--check-config"],
+        ]
+        .into_iter()
+        .for_each(|args| check_mode(args, Mode::CheckConfig));
+    }
+
     #[test]
     fn dump_config() {
         [["--dump-config"]]
@@ -101,6 +316,77 @@ mod tests {
             .for_each(|args| check_mode(args, Mode::DumpConfig));
     }
 
+    #[test]
+    fn dump_financials() {
+        [
+            vec!["--dump-financials"],
+            vec!["--dump-financials", "csv"],
+            vec!["--dump-financials=json"],
+        ]
+        .into_iter()
+        .for_each(|args| check_mode(args, Mode::DumpFinancials));
+    }
+
+    #[test]
+    fn dump_financials_outranks_wallet_modes() {
+        [
+            ["--generate-wallet", "--dump-financials"],
+            ["--dump-financials", "--recover-wallet"],
+        ]
+        .into_iter()
+        .for_each(|args| check_mode(args, Mode::DumpFinancials));
+    }
+
+    #[test]
+    fn backup_data() {
+        [
+            vec!["--backup-data", "node.archive"],
+            vec!["--backup-data=node.archive"],
+        ]
+        .into_iter()
+        .for_each(|args| check_mode(args, Mode::BackupData));
+    }
+
+    #[test]
+    fn restore_data() {
+        [
+            vec!["--restore-data", "node.archive"],
+            vec!["--restore-data=node.archive"],
+        ]
+        .into_iter()
+        .for_each(|args| check_mode(args, Mode::RestoreData));
+    }
+
+    #[test]
+    fn list_neighbors() {
+        [
+            vec!["--list-neighbors"],
+            vec!["--list-neighbors", "--ui-port", "5334"],
+        ]
+        .into_iter()
+        .for_each(|args| check_mode(args, Mode::ListNeighbors));
+    }
+
+    #[test]
+    fn print_descriptor() {
+        [
+            vec!["--print-descriptor"],
+            vec!["--print-descriptor", "--qr-png", "descriptor.png"],
+        ]
+        .into_iter()
+        .for_each(|args| check_mode(args, Mode::PrintDescriptor));
+    }
+
+    #[test]
+    fn dns_leak_test() {
+        [
+            vec!["--dns-leak-test"],
+            vec!["--dns-leak-test", "--ui-port", "5334"],
+        ]
+        .into_iter()
+        .for_each(|args| check_mode(args, Mode::DnsLeakTest));
+    }
+
     #[test]
     fn both_generate_and_recover() {
         [
@@ -117,6 +403,7 @@ mod tests {
             ["--booga", "--generate-wallet", "--dump-config"],
             ["--booga", "--recover-wallet", "--dump-config"],
             ["--generate-wallet", "--recover_wallet", "--dump-config"],
+            ["--dump-financials", "--booga", "--dump-config"],
         ]
         .into_iter()
         .for_each(|args| check_mode(args, Mode::DumpConfig));
@@ -127,6 +414,136 @@ mod tests {
         check_mode(&[], Mode::RunTheNode)
     }
 
+    #[test]
+    fn subcommands_are_translated_into_the_flags_their_modes_expect() {
+        vec![
+            ("run", Mode::RunTheNode, vec!["program", "--ip", "1.2.3.4"]),
+            ("setup", Mode::Setup, vec!["program", "--ip", "1.2.3.4"]),
+            ("daemon", Mode::Daemon, vec!["program", "--ip", "1.2.3.4"]),
+            ("service", Mode::Service, vec!["program", "--ip", "1.2.3.4"]),
+            (
+                "completions",
+                Mode::Completions,
+                vec!["program", "--ip", "1.2.3.4"],
+            ),
+            (
+                "firewall-keeper",
+                Mode::FirewallKeeper,
+                vec!["program", "--ip", "1.2.3.4"],
+            ),
+            (
+                "generate-wallet",
+                Mode::GenerateWallet,
+                vec!["program", "--generate-wallet", "--ip", "1.2.3.4"],
+            ),
+            (
+                "dump-financials",
+                Mode::DumpFinancials,
+                vec!["program", "--dump-financials", "--ip", "1.2.3.4"],
+            ),
+            (
+                "backup-data",
+                Mode::BackupData,
+                vec!["program", "--backup-data", "--ip", "1.2.3.4"],
+            ),
+            (
+                "dns-leak-test",
+                Mode::DnsLeakTest,
+                vec!["program", "--dns-leak-test", "--ip", "1.2.3.4"],
+            ),
+        ]
+        .into_iter()
+        .for_each(|(subcommand, expected_mode, expected_args)| {
+            let args = strs_to_strings(vec!["program", subcommand, "--ip", "1.2.3.4"]);
+
+            let result = translate_subcommand(&args);
+
+            assert_eq!(
+                result,
+                Some((expected_mode, strs_to_strings(expected_args))),
+                "{}",
+                subcommand
+            );
+        });
+    }
+
+    #[test]
+    fn a_subcommand_keeps_its_arguments() {
+        let args = strs_to_strings(vec!["program", "dump-financials", "csv"]);
+
+        let result = translate_subcommand(&args);
+
+        assert_eq!(
+            result,
+            Some((
+                Mode::DumpFinancials,
+                strs_to_strings(vec!["program", "--dump-financials", "csv"])
+            ))
+        );
+    }
+
+    #[test]
+    fn only_the_first_word_can_be_a_subcommand() {
+        [
+            vec!["program"],
+            vec!["program", "--ip", "1.2.3.4"],
+            vec!["program", "--data-directory", "setup"],
+            vec!["program", "booga"],
+        ]
+        .iter()
+        .for_each(|args| {
+            assert_eq!(
+                translate_subcommand(&strs_to_strings(args.clone())),
+                None,
+                "{:?}",
+                args
+            )
+        });
+    }
+
+    #[test]
+    fn mode_flags_are_deprecated() {
+        let args = strs_to_strings(vec![
+            "program",
+            "--dump-financials=csv",
+            "--generate-wallet",
+            "--ip",
+            "1.2.3.4",
+        ]);
+        let mut holder = FakeStreamHolder::new();
+
+        warn_about_mode_flags(&args, &mut holder.streams());
+
+        assert_eq!(
+            holder.stderr.get_string(),
+            "--generate-wallet is deprecated and will be removed in the next release; use the \
+             generate-wallet subcommand instead\n\
+             --dump-financials is deprecated and will be removed in the next release; use the \
+             dump-financials subcommand instead\n"
+        );
+        assert_eq!(holder.stdout.get_string(), "");
+    }
+
+    #[test]
+    fn mode_flags_newer_than_subcommands_are_not_deprecated() {
+        let args = strs_to_strings(vec!["program", "--backup-data", "node.archive"]);
+        let mut holder = FakeStreamHolder::new();
+
+        warn_about_mode_flags(&args, &mut holder.streams());
+
+        assert_eq!(holder.stderr.get_string(), "");
+    }
+
+    #[test]
+    fn running_without_a_mode_flag_is_not_deprecated() {
+        let args = strs_to_strings(vec!["program", "--ip", "1.2.3.4"]);
+        let mut holder = FakeStreamHolder::new();
+
+        warn_about_mode_flags(&args, &mut holder.streams());
+
+        assert_eq!(holder.stderr.get_string(), "");
+    }
+
     fn check_mode(args: &[&str], expected_mode: Mode) {
         let mut augmented_args: Vec<&str> = vec!["--unrelated"];
         augmented_args.extend(args);
@@ -3,10 +3,12 @@ use super::bootstrapper::Bootstrapper;
 use super::privilege_drop::PrivilegeDropper;
 use super::privilege_drop::PrivilegeDropperReal;
 use crate::bootstrapper::{BootstrapperConfig, RealUser};
-use crate::entry_dns::dns_socket_server::DnsSocketServer;
+use crate::entry_dns::dns_socket_server::{DnsSocketServer, EntryDnsServer};
+use crate::log_rotation::{LogRotation, RotatingFileWriter};
 use crate::node_configurator::node_configurator_standard::NodeConfiguratorStandardPrivileged;
 use crate::node_configurator::NodeConfigurator;
 use crate::sub_lib;
+use crate::sub_lib::crash_context::current_handling;
 use crate::sub_lib::main_tools::Command;
 use crate::sub_lib::main_tools::StdStreams;
 use crate::sub_lib::socket_server::SocketServer;
@@ -14,18 +16,37 @@ use backtrace::Backtrace;
 use chrono::{DateTime, Local};
 use flexi_logger::LogSpecBuilder;
 use flexi_logger::Logger;
-use flexi_logger::{Cleanup, Criterion, LevelFilter, Naming};
-use flexi_logger::{DeferredNow, Duplicate, Record};
+use flexi_logger::{DeferredNow, Duplicate, ReconfigurationHandle, Record};
+use flexi_logger::{LevelFilter, LogSpecification, LogTarget};
 use futures::try_ready;
+use lazy_static::lazy_static;
 use std::any::Any;
+use std::collections::BTreeMap;
+use std::fs;
 use std::panic::{Location, PanicInfo};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use std::{io, thread};
 use tokio::prelude::Async;
 use tokio::prelude::Future;
 
+// The name of the log file being written; rotated files get numbers instead
+pub const CURRENT_LOG_FILE: &str = "PrometheusNode_rCURRENT.log";
+
+// An instance's name goes between the program name and the rest
+pub fn current_log_file(instance_name_opt: Option<&str>) -> String {
+    match instance_name_opt {
+        Some(instance_name) => format!("PrometheusNode_{}_rCURRENT.log", instance_name),
+        None => CURRENT_LOG_FILE.to_string(),
+    }
+}
+
+lazy_static! {
+    static ref LOG_RECONFIGURATION: Mutex<Option<LogReconfiguration>> = Mutex::new(None);
+}
+
 pub struct ServerInitializer {
-    dns_socket_server: Box<dyn SocketServer<(), Item = (), Error = ()>>,
+    dns_socket_server: Box<dyn EntryDnsServer>,
     bootstrapper: Box<dyn SocketServer<BootstrapperConfig, Item = (), Error = ()>>,
     privilege_dropper: Box<dyn PrivilegeDropper>,
 }
@@ -57,6 +78,10 @@ impl Command for ServerInitializer {
             self.bootstrapper
                 .as_mut()
                 .initialize_as_unprivileged(args, streams);
+
+            let config = self.bootstrapper.get_configuration();
+            self.dns_socket_server
+                .split_dns(config.split_dns_domains.clone(), config.dns_servers.clone());
             1
         }
     }
@@ -92,39 +117,144 @@ impl Default for ServerInitializer {
     }
 }
 
+fn log_specification(
+    default_level: LevelFilter,
+    component_levels: &BTreeMap<String, LevelFilter>,
+) -> LogSpecification {
+    let mut builder = LogSpecBuilder::new();
+    builder
+        .default(default_level)
+        .module("tokio", LevelFilter::Off)
+        .module("mio", LevelFilter::Off);
+    component_levels.iter().for_each(|(component, level)| {
+        builder.module(component, *level);
+    });
+    builder.build()
+}
+
+// The levels in force, so changing one component's level doesn't lose the others
+struct LogReconfiguration {
+    handle: ReconfigurationHandle,
+    default_level: LevelFilter,
+    component_levels: BTreeMap<String, LevelFilter>,
+}
+
+// Changes the level for the rest of this run; --log-level still decides it at the next startup
+pub fn set_log_level(log_level: LevelFilter) -> Result<(), String> {
+    reconfigure_logging(|reconfiguration| reconfiguration.default_level = log_level)
+}
+
+// For one component's Logger (e.g. "Neighborhood"), leaving everything else alone. None puts the
+// component back on the level everything else logs at.
+pub fn set_component_log_level(
+    component: &str,
+    log_level_opt: Option<LevelFilter>,
+) -> Result<(), String> {
+    reconfigure_logging(|reconfiguration| match log_level_opt {
+        Some(log_level) => {
+            reconfiguration
+                .component_levels
+                .insert(component.to_string(), log_level);
+        }
+        None => {
+            reconfiguration.component_levels.remove(component);
+        }
+    })
+}
+
+// Not log::max_level(), which rises to the level of the chattiest component
+pub fn log_level() -> LevelFilter {
+    match LOG_RECONFIGURATION
+        .lock()
+        .expect("Log reconfiguration is poisoned")
+        .as_ref()
+    {
+        Some(reconfiguration) => reconfiguration.default_level,
+        None => log::max_level(),
+    }
+}
+
+fn reconfigure_logging<F>(change: F) -> Result<(), String>
+where
+    F: FnOnce(&mut LogReconfiguration),
+{
+    match LOG_RECONFIGURATION
+        .lock()
+        .expect("Log reconfiguration is poisoned")
+        .as_mut()
+    {
+        Some(reconfiguration) => {
+            change(reconfiguration);
+            let spec = log_specification(
+                reconfiguration.default_level,
+                &reconfiguration.component_levels,
+            );
+            reconfiguration.handle.set_new_spec(spec);
+            Ok(())
+        }
+        None => Err("Logging has not been started".to_string()),
+    }
+}
+
+// For the daemon, which leaves its log to whatever started it
+pub fn log_to_stderr(log_level: LevelFilter) {
+    Logger::with(log_specification(log_level, &BTreeMap::new()))
+        .format(format_function)
+        .start()
+        .expect("Logging subsystem failed to start");
+}
+
 pub trait LoggerInitializerWrapper: Send {
-    fn init(&mut self, file_path: PathBuf, real_user: &RealUser, log_level: LevelFilter);
+    fn init(
+        &mut self,
+        file_path: PathBuf,
+        real_user: &RealUser,
+        log_level: LevelFilter,
+        instance_name_opt: Option<&str>,
+        log_rotation: &LogRotation,
+    );
 }
 
 pub struct LoggerInitializerWrapperReal {}
 
 impl LoggerInitializerWrapper for LoggerInitializerWrapperReal {
-    fn init(&mut self, file_path: PathBuf, real_user: &RealUser, log_level: LevelFilter) {
-        Logger::with(
-            LogSpecBuilder::new()
-                .default(log_level)
-                .module("tokio", LevelFilter::Off)
-                .module("mio", LevelFilter::Off)
-                .build(),
+    fn init(
+        &mut self,
+        file_path: PathBuf,
+        real_user: &RealUser,
+        log_level: LevelFilter,
+        instance_name_opt: Option<&str>,
+        log_rotation: &LogRotation,
+    ) {
+        let writer = RotatingFileWriter::new(
+            &file_path,
+            instance_name_opt,
+            log_rotation.clone(),
+            format_function,
         )
-        .log_to_file()
-        .directory(file_path.clone())
-        .print_message()
-        .duplicate_to_stderr(Duplicate::Info)
-        .suppress_timestamp()
-        .format(format_function)
-        .rotate(
-            Criterion::Size(100_000_000),
-            Naming::Numbers,
-            Cleanup::KeepZipFiles(50),
-        )
-        .start()
-        .expect("Logging subsystem failed to start");
+        .expect("Log file could not be opened");
+        println!("Log is written to {}", writer.current_path().display());
+        let handle = Logger::with(log_specification(log_level, &BTreeMap::new()))
+            .log_target(LogTarget::Writer(Box::new(writer)))
+            .duplicate_to_stderr(Duplicate::Info)
+            .format(format_function)
+            .start()
+            .expect("Logging subsystem failed to start");
+        *LOG_RECONFIGURATION
+            .lock()
+            .expect("Log reconfiguration is poisoned") = Some(LogReconfiguration {
+            handle,
+            default_level: log_level,
+            component_levels: BTreeMap::new(),
+        });
         let privilege_dropper = PrivilegeDropperReal::new();
-        let logfile_name = file_path.join("PrometheusNode_rCURRENT.log");
+        let logfile_name = file_path.join(current_log_file(instance_name_opt));
         privilege_dropper.chown(&logfile_name, real_user);
-        std::panic::set_hook(Box::new(|panic_info| {
-            panic_hook(AltPanicInfo::from(panic_info))
+        // A panicking actor leaves the rest of the Node running without it, so don't carry on
+        std::panic::set_hook(Box::new(move |panic_info| {
+            panic_hook(AltPanicInfo::from(panic_info), Some(&file_path));
+            log::logger().flush();
+            std::process::abort();
         }));
     }
 }
@@ -164,7 +294,16 @@ impl<'a> From<&'a PanicInfo<'a>> for AltPanicInfo<'a> {
     }
 }
 
-fn panic_hook(panic_info: AltPanicInfo) {
+// Written into the data directory when the Node panics
+pub fn crash_dump_file(timestamp: &DateTime<Local>) -> String {
+    format!(
+        "PrometheusNode_crash_{}_{}.txt",
+        timestamp.format("%Y%m%dT%H%M%S"),
+        std::process::id()
+    )
+}
+
+fn panic_hook(panic_info: AltPanicInfo, crash_dump_dir_opt: Option<&Path>) {
     let location = match panic_info.location {
         None => "<unknown location>".to_string(),
         Some(location) => format!("{}:{}:{}", location.file, location.line, location.col),
@@ -176,10 +315,49 @@ fn panic_hook(panic_info: AltPanicInfo) {
     } else {
         "<message indecipherable>".to_string()
     };
+    let thread = thread::current();
+    let thread_name = thread.name().unwrap_or("<unnamed>");
+    let context = match current_handling() {
+        Some(handling) => format!(
+            "Thread '{}' panicked while {} was handling {}",
+            thread_name, handling.actor, handling.message
+        ),
+        None => format!(
+            "Thread '{}' panicked outside any actor's message handler",
+            thread_name
+        ),
+    };
     let logger = sub_lib::logger::Logger::new("PanicHandler");
+    error!(logger, "{}", context);
     error!(logger, "{} - {}", location, message);
     let backtrace = Backtrace::new();
     error!(logger, "{:?}", backtrace);
+    if let Some(crash_dump_dir) = crash_dump_dir_opt {
+        let now = Local::now();
+        let crash_dump_path = crash_dump_dir.join(crash_dump_file(&now));
+        let crash_dump = format!(
+            "SubstratumNode {}\n{}\n{}\n{} - {}\n{:?}\n",
+            env!("CARGO_PKG_VERSION"),
+            now.format("%Y-%m-%dT%H:%M:%S%.3f"),
+            context,
+            location,
+            message,
+            backtrace
+        );
+        match fs::write(&crash_dump_path, crash_dump) {
+            Ok(()) => error!(
+                logger,
+                "Crash dump written to {}",
+                crash_dump_path.display()
+            ),
+            Err(e) => error!(
+                logger,
+                "Couldn't write crash dump to {}: {}",
+                crash_dump_path.display(),
+                e
+            ),
+        }
+    }
 }
 
 // DeferredNow can't be constructed in a test; therefore this function is untestable...
@@ -212,6 +390,7 @@ pub fn real_format_function(
 #[cfg(test)]
 pub mod test_utils {
     use crate::bootstrapper::RealUser;
+    use crate::log_rotation::LogRotation;
     use crate::privilege_drop::PrivilegeDropper;
     use crate::server_initializer::LoggerInitializerWrapper;
     use crate::test_utils::logging::init_test_logging;
@@ -260,15 +439,26 @@ pub mod test_utils {
     }
 
     pub struct LoggerInitializerWrapperMock {
-        init_parameters: Arc<Mutex<Vec<(PathBuf, RealUser, LevelFilter)>>>,
+        init_parameters:
+            Arc<Mutex<Vec<(PathBuf, RealUser, LevelFilter, Option<String>, LogRotation)>>>,
     }
 
     impl LoggerInitializerWrapper for LoggerInitializerWrapperMock {
-        fn init(&mut self, file_path: PathBuf, real_user: &RealUser, log_level: LevelFilter) {
-            self.init_parameters
-                .lock()
-                .unwrap()
-                .push((file_path, real_user.clone(), log_level));
+        fn init(
+            &mut self,
+            file_path: PathBuf,
+            real_user: &RealUser,
+            log_level: LevelFilter,
+            instance_name_opt: Option<&str>,
+            log_rotation: &LogRotation,
+        ) {
+            self.init_parameters.lock().unwrap().push((
+                file_path,
+                real_user.clone(),
+                log_level,
+                instance_name_opt.map(|name| name.to_string()),
+                log_rotation.clone(),
+            ));
             assert!(init_test_logging());
         }
     }
@@ -282,7 +472,9 @@ pub mod test_utils {
 
         pub fn init_parameters(
             mut self,
-            parameters: &Arc<Mutex<Vec<(PathBuf, RealUser, LevelFilter)>>>,
+            parameters: &Arc<
+                Mutex<Vec<(PathBuf, RealUser, LevelFilter, Option<String>, LogRotation)>>,
+            >,
         ) -> Self {
             self.init_parameters = parameters.clone();
             self
@@ -295,10 +487,15 @@ pub mod tests {
     use super::*;
     use crate::crash_test_dummy::CrashTestDummy;
     use crate::server_initializer::test_utils::PrivilegeDropperMock;
+    use crate::sub_lib::crash_context::handling;
     use crate::sub_lib::crash_point::CrashPoint;
+    use crate::test_utils::ensure_node_home_directory_exists;
     use crate::test_utils::logging::{init_test_logging, TestLogHandler};
     use crate::test_utils::ByteArrayWriter;
     use crate::test_utils::{ByteArrayReader, FakeStreamHolder};
+    use chrono::TimeZone;
+    use std::net::SocketAddr;
+    use std::str::FromStr;
     use std::sync::Arc;
     use std::sync::Mutex;
 
@@ -321,10 +518,15 @@ pub mod tests {
         }
     }
 
+    impl EntryDnsServer for CrashTestDummy<()> {
+        fn split_dns(&mut self, _split_domains: Vec<String>, _upstream_servers: Vec<SocketAddr>) {}
+    }
+
     struct SocketServerMock<C> {
         get_configuration_result: C,
         initialize_as_privileged_params: Arc<Mutex<Vec<Vec<String>>>>,
         initialize_as_unprivileged_params: Arc<Mutex<Vec<Vec<String>>>>,
+        split_dns_params: Arc<Mutex<Vec<(Vec<String>, Vec<SocketAddr>)>>>,
     }
 
     impl<C> Future for SocketServerMock<C> {
@@ -359,12 +561,22 @@ pub mod tests {
         }
     }
 
+    impl EntryDnsServer for SocketServerMock<()> {
+        fn split_dns(&mut self, split_domains: Vec<String>, upstream_servers: Vec<SocketAddr>) {
+            self.split_dns_params
+                .lock()
+                .unwrap()
+                .push((split_domains, upstream_servers));
+        }
+    }
+
     impl<C> SocketServerMock<C> {
         pub fn new(get_configuration_result: C) -> SocketServerMock<C> {
             Self {
                 get_configuration_result,
                 initialize_as_privileged_params: Arc::new(Mutex::new(vec![])),
                 initialize_as_unprivileged_params: Arc::new(Mutex::new(vec![])),
+                split_dns_params: Arc::new(Mutex::new(vec![])),
             }
         }
 
@@ -385,6 +597,52 @@ pub mod tests {
             self.initialize_as_unprivileged_params = params.clone();
             self
         }
+
+        pub fn split_dns_params(
+            mut self,
+            params: &Arc<Mutex<Vec<(Vec<String>, Vec<SocketAddr>)>>>,
+        ) -> Self {
+            self.split_dns_params = params.clone();
+            self
+        }
+    }
+
+    #[test]
+    fn current_log_file_carries_the_instance_name() {
+        assert_eq!(current_log_file(None), CURRENT_LOG_FILE.to_string());
+        assert_eq!(
+            current_log_file(Some("alice")),
+            "PrometheusNode_alice_rCURRENT.log".to_string()
+        );
+    }
+
+    #[test]
+    fn log_specification_adds_component_levels_to_the_default() {
+        let mut component_levels = BTreeMap::new();
+        component_levels.insert("Neighborhood".to_string(), LevelFilter::Trace);
+
+        let result = log_specification(LevelFilter::Warn, &component_levels);
+
+        let filters = result
+            .module_filters()
+            .iter()
+            .map(|filter| (filter.module_name.clone(), filter.level_filter))
+            .collect::<Vec<(Option<String>, LevelFilter)>>();
+        assert!(
+            filters.contains(&(None, LevelFilter::Warn)),
+            "{:?}",
+            filters
+        );
+        assert!(
+            filters.contains(&(Some("Neighborhood".to_string()), LevelFilter::Trace)),
+            "{:?}",
+            filters
+        );
+        assert!(
+            filters.contains(&(Some("tokio".to_string()), LevelFilter::Off)),
+            "{:?}",
+            filters
+        );
     }
 
     #[test]
@@ -395,7 +653,7 @@ pub mod tests {
             location: None,
         };
 
-        panic_hook(panic_info);
+        panic_hook(panic_info, None);
 
         let tlh = TestLogHandler::new();
         tlh.exists_log_containing(
@@ -415,7 +673,7 @@ pub mod tests {
             }),
         };
 
-        panic_hook(panic_info);
+        panic_hook(panic_info, None);
 
         let tlh = TestLogHandler::new();
         tlh.exists_log_containing(
@@ -437,12 +695,97 @@ pub mod tests {
             }),
         };
 
-        panic_hook(panic_info);
+        panic_hook(panic_info, None);
 
         let tlh = TestLogHandler::new();
         tlh.exists_log_containing("ERROR: PanicHandler: file.txt:24:42 - I'm just a string slice");
     }
 
+    #[test]
+    fn panic_hook_logs_the_actor_and_message_being_handled() {
+        init_test_logging();
+        let thread = thread::Builder::new()
+            .name("panic_hook_logs_the_actor_and_message_being_handled".to_string())
+            .spawn(|| {
+                let _handling = handling("Neighborhood", "RouteQueryMessage");
+                let panic_info = AltPanicInfo {
+                    payload: &"Neighborhood blew up",
+                    location: None,
+                };
+                panic_hook(panic_info, None);
+            })
+            .unwrap();
+
+        thread.join().unwrap();
+
+        TestLogHandler::new().exists_log_containing(
+            "ERROR: PanicHandler: Thread 'panic_hook_logs_the_actor_and_message_being_handled' panicked while Neighborhood was handling RouteQueryMessage",
+        );
+    }
+
+    #[test]
+    fn panic_hook_writes_a_crash_dump_into_the_data_directory() {
+        init_test_logging();
+        let data_dir = ensure_node_home_directory_exists(
+            "server_initializer",
+            "panic_hook_writes_a_crash_dump_into_the_data_directory",
+        );
+        let panic_info = AltPanicInfo {
+            payload: &"Crash and burn",
+            location: Some(AltLocation {
+                file: "crash.rs".to_string(),
+                line: 12,
+                col: 34,
+            }),
+        };
+
+        panic_hook(panic_info, Some(&data_dir));
+
+        let crash_dumps = fs::read_dir(&data_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                path.file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .starts_with("PrometheusNode_crash_")
+            })
+            .collect::<Vec<PathBuf>>();
+        assert_eq!(crash_dumps.len(), 1, "{:?}", crash_dumps);
+        let crash_dump = fs::read_to_string(&crash_dumps[0]).unwrap();
+        assert!(
+            crash_dump.starts_with(&format!("SubstratumNode {}\n", env!("CARGO_PKG_VERSION"))),
+            "{}",
+            crash_dump
+        );
+        assert!(
+            crash_dump.contains(
+                "panicked outside any actor's message handler\ncrash.rs:12:34 - Crash and burn\n"
+            ),
+            "{}",
+            crash_dump
+        );
+        TestLogHandler::new().exists_log_containing(&format!(
+            "ERROR: PanicHandler: Crash dump written to {}",
+            crash_dumps[0].display()
+        ));
+    }
+
+    #[test]
+    fn crash_dump_file_is_named_for_the_time_and_process() {
+        let timestamp = Local.ymd(2019, 7, 4).and_hms(13, 5, 9);
+
+        let result = crash_dump_file(&timestamp);
+
+        assert_eq!(
+            result,
+            format!(
+                "PrometheusNode_crash_20190704T130509_{}.txt",
+                std::process::id()
+            )
+        );
+    }
+
     #[test]
     fn exits_after_all_socket_servers_exit() {
         let dns_socket_server = CrashTestDummy::new(CrashPoint::Error, ());
@@ -551,6 +894,32 @@ pub mod tests {
         assert_eq!(*drop_privileges_params, vec![real_user]);
     }
 
+    #[test]
+    fn go_hands_the_split_dns_domains_to_the_dns_server_once_the_bootstrapper_has_them() {
+        let mut bootstrapper_config = BootstrapperConfig::new();
+        bootstrapper_config.split_dns_domains = vec!["corp.example.com".to_string()];
+        bootstrapper_config.dns_servers = vec![SocketAddr::from_str("10.0.0.53:53").unwrap()];
+        let split_dns_params_arc = Arc::new(Mutex::new(vec![]));
+        let mut subject = ServerInitializer {
+            dns_socket_server: Box::new(
+                SocketServerMock::new(()).split_dns_params(&split_dns_params_arc),
+            ),
+            bootstrapper: Box::new(SocketServerMock::new(bootstrapper_config)),
+            privilege_dropper: Box::new(PrivilegeDropperMock::new()),
+        };
+
+        subject.go(&mut FakeStreamHolder::new().streams(), &vec![]);
+
+        let split_dns_params = split_dns_params_arc.lock().unwrap();
+        assert_eq!(
+            *split_dns_params,
+            vec![(
+                vec!["corp.example.com".to_string()],
+                vec![SocketAddr::from_str("10.0.0.53:53").unwrap()]
+            )]
+        );
+    }
+
     #[test]
     #[should_panic(expected = "kind: HelpDisplayed")]
     fn go_with_help_should_print_help_and_artificially_panic() {
@@ -0,0 +1,63 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use std::ffi::OsStr;
+use std::os::windows::ffi::OsStrExt;
+use std::ptr;
+use winapi::shared::minwindef::WORD;
+use winapi::um::winbase::{DeregisterEventSource, RegisterEventSourceW, ReportEventW};
+use winapi::um::winnt::{EVENTLOG_ERROR_TYPE, EVENTLOG_INFORMATION_TYPE, HANDLE, LPCWSTR};
+
+// The Application log in Event Viewer, where a service with no console says what's become of it.
+// The Node's own log stays in its data directory; this is only for starting and stopping.
+pub struct EventLog {
+    handle: HANDLE,
+}
+
+impl Drop for EventLog {
+    fn drop(&mut self) {
+        if !self.handle.is_null() {
+            unsafe { DeregisterEventSource(self.handle) };
+        }
+    }
+}
+
+impl EventLog {
+    pub fn new(source: &str) -> EventLog {
+        let source = wide(source);
+        let handle = unsafe { RegisterEventSourceW(ptr::null(), source.as_ptr()) };
+        EventLog { handle }
+    }
+
+    pub fn info(&self, message: &str) {
+        self.report(EVENTLOG_INFORMATION_TYPE, message)
+    }
+
+    pub fn error(&self, message: &str) {
+        self.report(EVENTLOG_ERROR_TYPE, message)
+    }
+
+    // There's nowhere left to complain if the Event Log itself is unavailable
+    fn report(&self, event_type: WORD, message: &str) {
+        if self.handle.is_null() {
+            return;
+        }
+        let message = wide(message);
+        let mut strings: [LPCWSTR; 1] = [message.as_ptr()];
+        unsafe {
+            ReportEventW(
+                self.handle,
+                event_type,
+                0,
+                0,
+                ptr::null_mut(),
+                1,
+                0,
+                strings.as_mut_ptr(),
+                ptr::null_mut(),
+            )
+        };
+    }
+}
+
+fn wide(s: &str) -> Vec<u16> {
+    OsStr::new(s).encode_wide().chain(Some(0)).collect()
+}
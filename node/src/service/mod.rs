@@ -0,0 +1,232 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+#[cfg(target_os = "windows")]
+mod event_log;
+#[cfg(target_os = "windows")]
+mod scm;
+
+use crate::node_configurator::config_checker::clap_complaint;
+use crate::node_configurator::{app_head, node_configurator_standard};
+use crate::sub_lib::main_tools::StdStreams;
+use clap::{App, AppSettings, Arg, SubCommand};
+
+const SERVICE_HELP: &str =
+    "Run the Node as a Windows service, which starts at boot and needs nobody logged in. Install it from an \
+     administrator's command prompt with the parameters it should always start with, and start and stop it \
+     like any other service. It runs as LocalSystem, so give it a --data-directory rather than relying on \
+     the default.";
+const INSTALL_HELP: &str =
+    "Register the Node with the Service Control Manager, to start at boot with the given parameters.";
+const UNINSTALL_HELP: &str = "Stop the Node if it's running as a service, and unregister it.";
+const RUN_HELP: &str = "What the Service Control Manager runs. Not for use from a command prompt.";
+const NODE_ARGS_HELP: &str = "The parameters the Node starts with, as you'd give them to run.";
+
+pub fn app() -> App<'static, 'static> {
+    app_head()
+        .about(SERVICE_HELP)
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("install")
+                .about(INSTALL_HELP)
+                .setting(AppSettings::TrailingVarArg)
+                .arg(node_args_arg()),
+        )
+        .subcommand(SubCommand::with_name("uninstall").about(UNINSTALL_HELP))
+        .subcommand(
+            SubCommand::with_name("run")
+                .about(RUN_HELP)
+                .setting(AppSettings::Hidden)
+                .setting(AppSettings::TrailingVarArg)
+                .arg(node_args_arg()),
+        )
+}
+
+fn node_args_arg() -> Arg<'static, 'static> {
+    Arg::with_name("node-args")
+        .value_name("NODE-ARGS")
+        .multiple(true)
+        .allow_hyphen_values(true)
+        .help(NODE_ARGS_HELP)
+}
+
+pub fn service(args: &Vec<String>, streams: &mut StdStreams<'_>) -> i32 {
+    let matches = match app().get_matches_from_safe(args) {
+        Ok(matches) => matches,
+        Err(e) => {
+            writeln!(streams.stderr, "{}", e.message).expect("Couldn't write to stderr");
+            return 1;
+        }
+    };
+    let (action, action_matches) = matches.subcommand();
+    let node_args = match action_matches.and_then(|matches| matches.values_of("node-args")) {
+        Some(values) => values.map(|value| value.to_string()).collect(),
+        None => vec![],
+    };
+    let result = match action {
+        "install" => launch_arguments(&args[0], &node_args).and_then(install),
+        "uninstall" => uninstall(),
+        "run" => run(node_args_with_program(&args[0], &node_args)),
+        _ => unreachable!("clap let an unknown service action through"),
+    };
+    match result {
+        Ok(message) => {
+            writeln!(streams.stdout, "{}", message).expect("Couldn't write to stdout");
+            0
+        }
+        Err(message) => {
+            writeln!(streams.stderr, "{}", message).expect("Couldn't write to stderr");
+            1
+        }
+    }
+}
+
+// A service that can't start says so only in the Event Log, so the parameters are checked now,
+// while there's still someone at the command prompt to read the complaint
+fn launch_arguments(program: &str, node_args: &[String]) -> Result<Vec<String>, String> {
+    node_configurator_standard::app()
+        .get_matches_from_safe(node_args_with_program(program, node_args))
+        .map_err(|e| format!("The service wouldn't start: {}", clap_complaint(&e)))?;
+    Ok(vec!["service".to_string(), "run".to_string()]
+        .into_iter()
+        .chain(node_args.iter().cloned())
+        .collect())
+}
+
+fn node_args_with_program(program: &str, node_args: &[String]) -> Vec<String> {
+    vec![program.to_string()]
+        .into_iter()
+        .chain(node_args.iter().cloned())
+        .collect()
+}
+
+#[cfg(target_os = "windows")]
+fn install(launch_arguments: Vec<String>) -> Result<String, String> {
+    scm::install(launch_arguments)
+}
+
+#[cfg(target_os = "windows")]
+fn uninstall() -> Result<String, String> {
+    scm::uninstall()
+}
+
+#[cfg(target_os = "windows")]
+fn run(node_args: Vec<String>) -> Result<String, String> {
+    scm::run(node_args)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn install(_launch_arguments: Vec<String>) -> Result<String, String> {
+    Err(not_windows())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn uninstall() -> Result<String, String> {
+    Err(not_windows())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn run(_node_args: Vec<String>) -> Result<String, String> {
+    Err(not_windows())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn not_windows() -> String {
+    "Services are for Windows. Elsewhere, have systemd or launchd start the Node, or use the daemon subcommand."
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::FakeStreamHolder;
+
+    #[test]
+    fn launch_arguments_put_the_node_parameters_after_service_run() {
+        let node_args = vec![
+            "--data-directory".to_string(),
+            "C:\\ProgramData\\PrometheusNode".to_string(),
+            "--ip".to_string(),
+            "1.2.3.4".to_string(),
+        ];
+
+        let result = launch_arguments("PrometheusNode.exe", &node_args);
+
+        assert_eq!(
+            result,
+            Ok(vec![
+                "service".to_string(),
+                "run".to_string(),
+                "--data-directory".to_string(),
+                "C:\\ProgramData\\PrometheusNode".to_string(),
+                "--ip".to_string(),
+                "1.2.3.4".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn launch_arguments_refuse_parameters_the_node_would_refuse() {
+        let node_args = vec!["--booga".to_string()];
+
+        let result = launch_arguments("PrometheusNode.exe", &node_args);
+
+        let message = result.err().unwrap();
+        assert!(
+            message.starts_with("The service wouldn't start: Found argument '--booga'"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn service_needs_an_action() {
+        let mut holder = FakeStreamHolder::new();
+
+        let result = service(&vec!["PrometheusNode".to_string()], &mut holder.streams());
+
+        assert_eq!(result, 1);
+        assert!(
+            holder.stderr.get_string().contains("install"),
+            "{}",
+            holder.stderr.get_string()
+        );
+    }
+
+    #[test]
+    fn install_complains_about_bad_node_parameters_before_anything_else() {
+        let mut holder = FakeStreamHolder::new();
+
+        let result = service(
+            &vec![
+                "PrometheusNode".to_string(),
+                "install".to_string(),
+                "--booga".to_string(),
+            ],
+            &mut holder.streams(),
+        );
+
+        assert_eq!(result, 1);
+        assert!(
+            holder
+                .stderr
+                .get_string()
+                .starts_with("The service wouldn't start: Found argument '--booga'"),
+            "{}",
+            holder.stderr.get_string()
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn services_are_refused_outside_windows() {
+        let mut holder = FakeStreamHolder::new();
+
+        let result = service(
+            &vec!["PrometheusNode".to_string(), "uninstall".to_string()],
+            &mut holder.streams(),
+        );
+
+        assert_eq!(result, 1);
+        assert_eq!(holder.stderr.get_string(), format!("{}\n", not_windows()));
+    }
+}
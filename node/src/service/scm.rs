@@ -0,0 +1,202 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use super::event_log::EventLog;
+use crate::server_initializer::ServerInitializer;
+use crate::sub_lib::main_tools::{Command, StdStreams};
+use actix::System;
+use futures::future::Future;
+use lazy_static::lazy_static;
+use std::ffi::OsString;
+use std::io;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use windows_service::service::{
+    ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode,
+    ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+};
+use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+use windows_service::{define_windows_service, service_dispatcher};
+
+const SERVICE_NAME: &str = "PrometheusNode";
+const SERVICE_DISPLAY_NAME: &str = "Prometheus Node";
+// How long the Service Control Manager should give the Node to start or stop before giving up
+const WAIT_HINT: Duration = Duration::from_secs(30);
+
+lazy_static! {
+    // The Service Control Manager calls service_main with its own arguments, not the command line
+    static ref NODE_ARGS: Mutex<Vec<String>> = Mutex::new(vec![]);
+}
+
+define_windows_service!(ffi_service_main, service_main);
+
+pub fn install(launch_arguments: Vec<String>) -> Result<String, String> {
+    let manager = ServiceManager::local_computer(
+        None::<&str>,
+        ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+    )
+    .map_err(|e| format!("Couldn't reach the Service Control Manager: {}", e))?;
+    let executable_path =
+        std::env::current_exe().map_err(|e| format!("Couldn't find PrometheusNode: {}", e))?;
+    let service_info = ServiceInfo {
+        name: OsString::from(SERVICE_NAME),
+        display_name: OsString::from(SERVICE_DISPLAY_NAME),
+        service_type: ServiceType::OWN_PROCESS,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path,
+        launch_arguments: launch_arguments.into_iter().map(OsString::from).collect(),
+        dependencies: vec![],
+        // LocalSystem, which has the privilege the Node needs to bind its ports
+        account_name: None,
+        account_password: None,
+    };
+    manager
+        .create_service(&service_info, ServiceAccess::QUERY_STATUS)
+        .map_err(|e| format!("Couldn't install the {} service: {}", SERVICE_NAME, e))?;
+    Ok(format!(
+        "Installed the {} service; it will start at boot, or now with: sc start {}",
+        SERVICE_NAME, SERVICE_NAME
+    ))
+}
+
+pub fn uninstall() -> Result<String, String> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)
+        .map_err(|e| format!("Couldn't reach the Service Control Manager: {}", e))?;
+    let service = manager
+        .open_service(
+            SERVICE_NAME,
+            ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE,
+        )
+        .map_err(|e| format!("Couldn't find the {} service: {}", SERVICE_NAME, e))?;
+    let status = service
+        .query_status()
+        .map_err(|e| format!("Couldn't tell whether the service is running: {}", e))?;
+    if status.current_state != ServiceState::Stopped {
+        service
+            .stop()
+            .map_err(|e| format!("Couldn't stop the {} service: {}", SERVICE_NAME, e))?;
+    }
+    // Windows finishes deleting the service once it has stopped
+    service
+        .delete()
+        .map_err(|e| format!("Couldn't uninstall the {} service: {}", SERVICE_NAME, e))?;
+    Ok(format!("Uninstalled the {} service", SERVICE_NAME))
+}
+
+pub fn run(node_args: Vec<String>) -> Result<String, String> {
+    *NODE_ARGS.lock().expect("Node arguments poisoned") = node_args;
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main).map_err(|e| {
+        format!(
+            "Couldn't run as a service; use run to start the Node from a command prompt: {}",
+            e
+        )
+    })?;
+    Ok(format!("The {} service has stopped", SERVICE_NAME))
+}
+
+fn service_main(_arguments: Vec<OsString>) {
+    let event_log = EventLog::new(SERVICE_NAME);
+    if let Err(e) = run_service(&event_log) {
+        event_log.error(&format!("The service failed: {}", e));
+    }
+}
+
+fn run_service(event_log: &EventLog) -> windows_service::Result<()> {
+    let system_arc: Arc<Mutex<Option<System>>> = Arc::new(Mutex::new(None));
+    let handler_system_arc = system_arc.clone();
+    let status_handle = service_control_handler::register(SERVICE_NAME, move |control| {
+        match control {
+            // Stopping the actor system lets the Node finish what it's doing and flush its log
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                if let Some(system) = handler_system_arc
+                    .lock()
+                    .expect("Actor system poisoned")
+                    .as_ref()
+                {
+                    system.stop_with_code(0);
+                }
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        }
+    })?;
+    status_handle.set_service_status(status(ServiceState::StartPending, 0))?;
+    let node_args = NODE_ARGS.lock().expect("Node arguments poisoned").clone();
+    event_log.info(&format!(
+        "Starting the Node with {}",
+        node_args[1..].join(" ")
+    ));
+    // Bad parameters and the like make the Node panic before it starts; say so where it'll be seen
+    let exit_code = panic::catch_unwind(AssertUnwindSafe(|| {
+        run_the_node(&node_args, &system_arc, || {
+            let _ = status_handle.set_service_status(status(ServiceState::Running, 0));
+        })
+    }))
+    .unwrap_or_else(|panic| {
+        let message = panic
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| panic.downcast_ref::<&str>().map(|s| s.to_string()))
+            .unwrap_or_else(|| "(no message)".to_string());
+        event_log.error(&format!("The Node panicked: {}", message));
+        1
+    });
+    status_handle.set_service_status(status(ServiceState::StopPending, 0))?;
+    if exit_code == 0 {
+        event_log.info("The Node has stopped");
+    } else {
+        event_log.error(&format!(
+            "The Node stopped with exit code {}; its log in the data directory says why",
+            exit_code
+        ));
+    }
+    status_handle.set_service_status(status(ServiceState::Stopped, exit_code))
+}
+
+// Like run, but with no console to talk to and a System the control handler can stop
+fn run_the_node<F>(args: &Vec<String>, system_arc: &Arc<Mutex<Option<System>>>, started: F) -> i32
+where
+    F: FnOnce(),
+{
+    let system = System::new("main");
+    *system_arc.lock().expect("Actor system poisoned") = Some(System::current());
+    let mut stdin = io::empty();
+    let mut stdout = io::sink();
+    let mut stderr = io::sink();
+    let mut streams = StdStreams {
+        stdin: &mut stdin,
+        stdout: &mut stdout,
+        stderr: &mut stderr,
+    };
+    let mut server_initializer = ServerInitializer::new();
+    server_initializer.go(&mut streams, args);
+    actix::spawn(server_initializer.map_err(|_| {
+        System::current().stop_with_code(1);
+    }));
+    started();
+    system.run()
+}
+
+fn status(current_state: ServiceState, exit_code: i32) -> ServiceStatus {
+    let controls_accepted = match current_state {
+        ServiceState::Running => ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+        _ => ServiceControlAccept::empty(),
+    };
+    ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state,
+        controls_accepted,
+        exit_code: match exit_code {
+            0 => ServiceExitCode::Win32(0),
+            code => ServiceExitCode::ServiceSpecific(code as u32),
+        },
+        checkpoint: 0,
+        wait_hint: match current_state {
+            ServiceState::StartPending | ServiceState::StopPending => WAIT_HINT,
+            _ => Duration::default(),
+        },
+    }
+}
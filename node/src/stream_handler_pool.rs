@@ -10,6 +10,7 @@ use crate::stream_writer_unsorted::StreamWriterUnsorted;
 use crate::sub_lib::channel_wrappers::FuturesChannelFactory;
 use crate::sub_lib::channel_wrappers::FuturesChannelFactoryReal;
 use crate::sub_lib::channel_wrappers::SenderWrapper;
+use crate::sub_lib::crash_context::handling;
 use crate::sub_lib::cryptde::PublicKey;
 use crate::sub_lib::dispatcher;
 use crate::sub_lib::dispatcher::Endpoint;
@@ -21,7 +22,9 @@ use crate::sub_lib::neighborhood::NodeQueryResponseMetadata;
 use crate::sub_lib::neighborhood::RemoveNeighborMessage;
 use crate::sub_lib::neighborhood::ZERO_RATE_PACK;
 use crate::sub_lib::node_addr::NodeAddr;
+use crate::sub_lib::obfuscation::{ObfuscationConfig, ObfuscationMetrics};
 use crate::sub_lib::sequence_buffer::SequencedPacket;
+use crate::sub_lib::spill_file::SpillConfig;
 use crate::sub_lib::stream_connector::StreamConnector;
 use crate::sub_lib::stream_connector::StreamConnectorReal;
 use crate::sub_lib::stream_handler_pool::DispatcherNodeQueryResponse;
@@ -38,11 +41,15 @@ use actix::Recipient;
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::net::SocketAddr;
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 use tokio;
 use tokio::prelude::Future;
 
+// Overhead added by clandestine obfuscation is logged after every this-many masked frames.
+pub const OBFUSCATION_REPORT_INTERVAL: u32 = 1000;
+
 // IMPORTANT: Nothing at or below the level of StreamHandlerPool should know about StreamKeys.
 // StreamKeys should exist solely between ProxyServer and ProxyClient. Many of the streams
 // overseen by StreamHandlerPool will not (and should not) have StreamKeys. Don't let the
@@ -106,6 +113,10 @@ pub struct StreamHandlerPool {
     channel_factory: Box<dyn FuturesChannelFactory<SequencedPacket>>,
     clandestine_discriminator_factories: Vec<Box<dyn DiscriminatorFactory>>,
     traffic_analyzer: Box<dyn TrafficAnalyzer>,
+    obfuscation_config: ObfuscationConfig,
+    obfuscation_metrics: Arc<ObfuscationMetrics>,
+    frames_since_obfuscation_report: u32,
+    spill_config: SpillConfig,
 }
 
 impl Actor for StreamHandlerPool {
@@ -116,6 +127,7 @@ impl Handler<AddStreamMsg> for StreamHandlerPool {
     type Result = ();
 
     fn handle(&mut self, msg: AddStreamMsg, _ctx: &mut Self::Context) {
+        let _handling = handling("StreamHandlerPool", "AddStreamMsg");
         self.handle_add_stream_msg(msg)
     }
 }
@@ -124,6 +136,7 @@ impl Handler<RemoveStreamMsg> for StreamHandlerPool {
     type Result = ();
 
     fn handle(&mut self, msg: RemoveStreamMsg, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("StreamHandlerPool", "RemoveStreamMsg");
         self.handle_remove_stream_msg(msg)
     }
 }
@@ -132,6 +145,7 @@ impl Handler<TransmitDataMsg> for StreamHandlerPool {
     type Result = ();
 
     fn handle(&mut self, msg: TransmitDataMsg, _ctx: &mut <Self as Actor>::Context) {
+        let _handling = handling("StreamHandlerPool", "TransmitDataMsg");
         self.handle_transmit_data_msg(msg)
     }
 }
@@ -139,6 +153,7 @@ impl Handler<TransmitDataMsg> for StreamHandlerPool {
 impl Handler<DispatcherNodeQueryResponse> for StreamHandlerPool {
     type Result = ();
     fn handle(&mut self, msg: DispatcherNodeQueryResponse, _ctx: &mut Self::Context) {
+        let _handling = handling("StreamHandlerPool", "DispatcherNodeQueryResponse");
         self.handle_dispatcher_node_query_response(msg);
     }
 }
@@ -147,6 +162,7 @@ impl Handler<PoolBindMessage> for StreamHandlerPool {
     type Result = ();
 
     fn handle(&mut self, msg: PoolBindMessage, ctx: &mut Self::Context) {
+        let _handling = handling("StreamHandlerPool", "PoolBindMessage");
         ctx.set_mailbox_capacity(NODE_MAILBOX_CAPACITY);
         self.dispatcher_subs = Some(msg.dispatcher_subs);
         self.self_subs = Some(msg.stream_handler_pool_subs);
@@ -158,7 +174,9 @@ impl Handler<PoolBindMessage> for StreamHandlerPool {
 impl StreamHandlerPool {
     pub fn new(
         clandestine_discriminator_factories: Vec<Box<dyn DiscriminatorFactory>>,
+        obfuscation_config: ObfuscationConfig,
     ) -> StreamHandlerPool {
+        let obfuscation_metrics = Arc::new(ObfuscationMetrics::new());
         StreamHandlerPool {
             stream_writers: HashMap::new(),
             dispatcher_subs: None,
@@ -169,10 +187,22 @@ impl StreamHandlerPool {
             stream_connector: Box::new(StreamConnectorReal {}),
             channel_factory: Box::new(FuturesChannelFactoryReal {}),
             clandestine_discriminator_factories,
-            traffic_analyzer: Box::new(TrafficAnalyzerReal {}),
+            traffic_analyzer: Box::new(TrafficAnalyzerReal {
+                obfuscation_config,
+                obfuscation_metrics: obfuscation_metrics.clone(),
+            }),
+            obfuscation_config,
+            obfuscation_metrics,
+            frames_since_obfuscation_report: 0,
+            spill_config: SpillConfig::default(),
         }
     }
 
+    pub fn with_spill_config(mut self, spill_config: SpillConfig) -> StreamHandlerPool {
+        self.spill_config = spill_config;
+        self
+    }
+
     pub fn make_subs_from(pool_addr: &Addr<StreamHandlerPool>) -> StreamHandlerPoolSubs {
         StreamHandlerPoolSubs {
             add_sub: recipient!(pool_addr, AddStreamMsg),
@@ -203,6 +233,12 @@ impl StreamHandlerPool {
             .expect("StreamHandlerPool is unbound")
             .remove_sub
             .clone();
+        let transmit_sub: Recipient<TransmitDataMsg> = self
+            .self_subs
+            .as_ref()
+            .expect("StreamHandlerPool is unbound")
+            .transmit_sub
+            .clone();
         let stream_shutdown_sub: Recipient<StreamShutdownMsg> = self
             .dispatcher_subs
             .as_ref()
@@ -214,12 +250,18 @@ impl StreamHandlerPool {
             origin_port,
             ibcd_sub,
             remove_sub,
+            transmit_sub,
             stream_shutdown_sub,
             port_configuration.discriminator_factories.clone(),
             port_configuration.is_clandestine,
             peer_addr,
             local_addr,
         );
+        let stream_reader = if port_configuration.opens_on_connect {
+            stream_reader.with_opening_packet()
+        } else {
+            stream_reader
+        };
         debug!(
             self.logger,
             "Setting up {}clandestine StreamReader with reception_port {:?} on {} to listen to {}",
@@ -246,12 +288,32 @@ impl StreamHandlerPool {
             .insert(StreamWriterKey::from(peer_addr), Some(tx));
 
         if is_clandestine {
-            tokio::spawn(StreamWriterUnsorted::new(write_stream, peer_addr, rx));
+            tokio::spawn(
+                StreamWriterUnsorted::new(write_stream, peer_addr, rx)
+                    .with_jitter(self.obfuscation_config, self.obfuscation_metrics.clone()),
+            );
         } else {
-            tokio::spawn(StreamWriterSorted::new(write_stream, peer_addr, rx));
+            tokio::spawn(
+                StreamWriterSorted::new(write_stream, peer_addr, rx)
+                    .with_spill(self.spill_config.clone()),
+            );
         };
     }
 
+    fn count_obfuscated_frame(&mut self) {
+        if !self.obfuscation_config.is_active() {
+            return;
+        }
+        self.frames_since_obfuscation_report += 1;
+        if self.frames_since_obfuscation_report >= OBFUSCATION_REPORT_INTERVAL {
+            info!(
+                self.logger,
+                "Clandestine obfuscation overhead: {}", self.obfuscation_metrics
+            );
+            self.frames_since_obfuscation_report = 0;
+        }
+    }
+
     fn handle_transmit_data_msg(&mut self, msg: TransmitDataMsg) {
         // TODO Can be recombined with DispatcherNodeQueryMessage after SC-358/GH-96
         debug!(
@@ -425,6 +487,9 @@ impl StreamHandlerPool {
                     }
                     Ok(_) => {
                         debug!(self.logger, "Queued {} bytes for transmission", packet_len);
+                        if msg.context.sequence_number.is_none() {
+                            self.count_obfuscated_frame();
+                        }
                     }
                 };
                 if msg.context.last_data {
@@ -534,11 +599,21 @@ trait TrafficAnalyzer {
     fn get_masquerader(&self) -> Box<dyn Masquerader>;
 }
 
-struct TrafficAnalyzerReal {}
+struct TrafficAnalyzerReal {
+    obfuscation_config: ObfuscationConfig,
+    obfuscation_metrics: Arc<ObfuscationMetrics>,
+}
 
 impl TrafficAnalyzer for TrafficAnalyzerReal {
     fn get_masquerader(&self) -> Box<dyn Masquerader> {
-        Box::new(JsonMasquerader::new())
+        if self.obfuscation_config.max_padding > 0 {
+            Box::new(JsonMasquerader::with_padding(
+                self.obfuscation_config,
+                self.obfuscation_metrics.clone(),
+            ))
+        } else {
+            Box::new(JsonMasquerader::new())
+        }
     }
 }
 
@@ -615,7 +690,7 @@ mod tests {
 
         thread::spawn(move || {
             let system = System::new("test");
-            let mut subject = StreamHandlerPool::new(vec![]);
+            let mut subject = StreamHandlerPool::new(vec![], ObfuscationConfig::default());
             subject.stream_connector = Box::new(StreamConnectorMock::new());
             let subject_addr: Addr<StreamHandlerPool> = subject.start();
             let subject_subs = StreamHandlerPool::make_subs_from(&subject_addr);
@@ -725,7 +800,7 @@ mod tests {
 
         thread::spawn(move || {
             let system = System::new("test");
-            let subject = StreamHandlerPool::new(vec![]);
+            let subject = StreamHandlerPool::new(vec![], ObfuscationConfig::default());
 
             let subject_addr: Addr<StreamHandlerPool> = subject.start();
             let subject_subs = StreamHandlerPool::make_subs_from(&subject_addr);
@@ -789,7 +864,7 @@ mod tests {
         thread::spawn(move || {
             let system = System::new("test");
 
-            let mut subject = StreamHandlerPool::new(vec![]);
+            let mut subject = StreamHandlerPool::new(vec![], ObfuscationConfig::default());
             subject.stream_connector = Box::new(
                 StreamConnectorMock::new()
                     .connect_pair_result(Err(Error::from(ErrorKind::ConnectionRefused))),
@@ -893,7 +968,10 @@ mod tests {
         thread::spawn(move || {
             let system = System::new("test");
 
-            let mut subject = StreamHandlerPool::new(vec![Box::new(JsonDiscriminatorFactory {})]);
+            let mut subject = StreamHandlerPool::new(
+                vec![Box::new(JsonDiscriminatorFactory {})],
+                ObfuscationConfig::default(),
+            );
             subject.stream_connector = Box::new(StreamConnectorMock::new().connection(
                 local_addr,
                 peer_addr,
@@ -965,7 +1043,7 @@ mod tests {
         let (recorder, _, recording_arc) = make_recorder();
         let system = System::new("test");
         let sub = recorder.start().recipient::<StreamShutdownMsg>();
-        let mut subject = StreamHandlerPool::new(vec![]);
+        let mut subject = StreamHandlerPool::new(vec![], ObfuscationConfig::default());
         let peer_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let local_addr = SocketAddr::from_str("127.0.0.1:0").unwrap();
         let sw_key = StreamWriterKey::from(peer_addr);
@@ -1001,7 +1079,7 @@ mod tests {
         let (recorder, _, recording_arc) = make_recorder();
         let system = System::new("test");
         let sub = recorder.start().recipient::<StreamShutdownMsg>();
-        let mut subject = StreamHandlerPool::new(vec![]);
+        let mut subject = StreamHandlerPool::new(vec![], ObfuscationConfig::default());
         let peer_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let local_addr = SocketAddr::from_str("127.0.0.1:0").unwrap();
         let sw_key = StreamWriterKey::from(peer_addr);
@@ -1039,7 +1117,7 @@ mod tests {
         let (recorder, _, recording_arc) = make_recorder();
         let system = System::new("test");
         let sub = recorder.start().recipient::<StreamShutdownMsg>();
-        let mut subject = StreamHandlerPool::new(vec![]);
+        let mut subject = StreamHandlerPool::new(vec![], ObfuscationConfig::default());
         let peer_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let local_addr = SocketAddr::from_str("127.0.0.1:0").unwrap();
         let sw_key = StreamWriterKey::from(peer_addr);
@@ -1078,7 +1156,7 @@ mod tests {
         let (neighborhood, neighborhood_awaiter, neighborhood_recording_arc) = make_recorder();
         thread::spawn(move || {
             let system = System::new("when_stream_handler_pool_fails_to_create_nonexistent_stream_for_write_then_it_logs_and_notifies_neighborhood");
-            let mut subject = StreamHandlerPool::new(vec![]);
+            let mut subject = StreamHandlerPool::new(vec![], ObfuscationConfig::default());
             subject.stream_connector = Box::new(
                 StreamConnectorMock::new()
                     .connect_pair_result(Err(Error::from(ErrorKind::Other)))
@@ -1152,7 +1230,10 @@ mod tests {
                 "stream_handler_pool_creates_nonexistent_stream_for_reading_and_writing",
             );
             let discriminator_factory = JsonDiscriminatorFactory::new();
-            let mut subject = StreamHandlerPool::new(vec![Box::new(discriminator_factory)]);
+            let mut subject = StreamHandlerPool::new(
+                vec![Box::new(discriminator_factory)],
+                ObfuscationConfig::default(),
+            );
             subject.stream_connector = Box::new(
                 StreamConnectorMock::new().connect_pair_result(Ok(ConnectionInfo {
                     reader: Box::new(
@@ -1261,7 +1342,7 @@ mod tests {
 
         thread::spawn(move || {
             let system = System::new("test");
-            let subject = StreamHandlerPool::new(vec![]);
+            let subject = StreamHandlerPool::new(vec![], ObfuscationConfig::default());
 
             let subject_addr: Addr<StreamHandlerPool> = subject.start();
             let subject_subs = StreamHandlerPool::make_subs_from(&subject_addr);
@@ -1339,7 +1420,7 @@ mod tests {
 
         thread::spawn(move || {
             let system = System::new("test");
-            let subject = StreamHandlerPool::new(vec![]);
+            let subject = StreamHandlerPool::new(vec![], ObfuscationConfig::default());
 
             let subject_addr: Addr<StreamHandlerPool> = subject.start();
             let subject_subs = StreamHandlerPool::make_subs_from(&subject_addr);
@@ -1387,7 +1468,7 @@ mod tests {
 
         thread::spawn(move || {
             let system = System::new("test");
-            let subject = StreamHandlerPool::new(vec![]);
+            let subject = StreamHandlerPool::new(vec![], ObfuscationConfig::default());
 
             let subject_addr: Addr<StreamHandlerPool> = subject.start();
             let subject_subs = StreamHandlerPool::make_subs_from(&subject_addr);
@@ -1451,7 +1532,7 @@ mod tests {
 
         thread::spawn(move || {
             let system = System::new("test");
-            let mut subject = StreamHandlerPool::new(vec![]);
+            let mut subject = StreamHandlerPool::new(vec![], ObfuscationConfig::default());
             subject
                 .stream_writers
                 .insert(StreamWriterKey::from(peer_addr), None);
@@ -1572,7 +1653,7 @@ mod tests {
 
         thread::spawn(move || {
             let system = System::new("test");
-            let mut subject = StreamHandlerPool::new(vec![]);
+            let mut subject = StreamHandlerPool::new(vec![], ObfuscationConfig::default());
             subject.stream_connector = Box::new(
                 StreamConnectorMock::new()
                     .connect_pair_result(Err(Error::from(ErrorKind::Other)))
@@ -1641,7 +1722,7 @@ mod tests {
         let sender_wrapper = SenderWrapperMock::new(peer_addr)
             .unbounded_send_params(&sender_wrapper_unbounded_send_params_arc)
             .unbounded_send_result(Ok(()));
-        let mut subject = StreamHandlerPool::new(vec![]);
+        let mut subject = StreamHandlerPool::new(vec![], ObfuscationConfig::default());
         subject.stream_writers.insert(
             StreamWriterKey::from(peer_addr),
             Some(Box::new(sender_wrapper)),
@@ -1701,7 +1782,7 @@ mod tests {
         };
 
         let system = System::new("test");
-        let subject = StreamHandlerPool::new(vec![]);
+        let subject = StreamHandlerPool::new(vec![], ObfuscationConfig::default());
         let subject_addr: Addr<StreamHandlerPool> = subject.start();
         let subject_subs = StreamHandlerPool::make_subs_from(&subject_addr);
         let peer_actors = peer_actors_builder().build();
@@ -1749,7 +1830,7 @@ mod tests {
 
         thread::spawn(move || {
             let system = System::new("test");
-            let subject = StreamHandlerPool::new(vec![]);
+            let subject = StreamHandlerPool::new(vec![], ObfuscationConfig::default());
 
             let subject_addr: Addr<StreamHandlerPool> = subject.start();
             let subject_subs = StreamHandlerPool::make_subs_from(&subject_addr);
@@ -1822,7 +1903,7 @@ mod tests {
 
         thread::spawn(move || {
             let system = System::new("test");
-            let mut subject = StreamHandlerPool::new(vec![]);
+            let mut subject = StreamHandlerPool::new(vec![], ObfuscationConfig::default());
             subject.traffic_analyzer = Box::new(TrafficAnalyzerMock {});
 
             let subject_addr: Addr<StreamHandlerPool> = subject.start();
@@ -1883,7 +1964,10 @@ mod tests {
                 "stream_handler_pool_creates_nonexistent_stream_for_reading_and_writing",
             );
             let discriminator_factory = JsonDiscriminatorFactory::new();
-            let mut subject = StreamHandlerPool::new(vec![Box::new(discriminator_factory)]);
+            let mut subject = StreamHandlerPool::new(
+                vec![Box::new(discriminator_factory)],
+                ObfuscationConfig::default(),
+            );
             subject.stream_connector = Box::new(StreamConnectorMock::new()); // this will panic if a connection is attempted
             let subject_addr: Addr<StreamHandlerPool> = subject.start();
             let subject_subs = StreamHandlerPool::make_subs_from(&subject_addr);
@@ -1924,4 +2008,71 @@ mod tests {
             1000,
         );
     }
+
+    #[test]
+    fn traffic_analyzer_pads_masked_data_only_when_padding_is_configured() {
+        let unpadded = TrafficAnalyzerReal {
+            obfuscation_config: ObfuscationConfig {
+                max_jitter: Duration::from_millis(10),
+                max_padding: 0,
+            },
+            obfuscation_metrics: Arc::new(ObfuscationMetrics::new()),
+        };
+        let padded = TrafficAnalyzerReal {
+            obfuscation_config: ObfuscationConfig {
+                max_jitter: Duration::from_millis(0),
+                max_padding: 10,
+            },
+            obfuscation_metrics: Arc::new(ObfuscationMetrics::new()),
+        };
+
+        let unpadded_result = unpadded.get_masquerader().mask(b"booga").unwrap();
+        let padded_result = padded.get_masquerader().mask(b"booga").unwrap();
+
+        assert_eq!(
+            unpadded_result,
+            JsonMasquerader::new().mask(b"booga").unwrap()
+        );
+        assert!(String::from_utf8(padded_result)
+            .unwrap()
+            .contains("\"padding\":"));
+        assert_eq!(padded.obfuscation_metrics.frames(), 1);
+    }
+
+    #[test]
+    fn obfuscation_overhead_is_logged_periodically() {
+        init_test_logging();
+        let mut subject = StreamHandlerPool::new(
+            vec![],
+            ObfuscationConfig {
+                max_jitter: Duration::from_millis(0),
+                max_padding: 10,
+            },
+        );
+        subject.logger = Logger::new("obfuscation_overhead_is_logged_periodically");
+        subject.obfuscation_metrics.record_padding(90, 10);
+
+        (0..(OBFUSCATION_REPORT_INTERVAL - 1)).for_each(|_| subject.count_obfuscated_frame());
+
+        let tlh = TestLogHandler::new();
+        tlh.exists_no_log_containing(
+            "obfuscation_overhead_is_logged_periodically: Clandestine obfuscation overhead",
+        );
+
+        subject.count_obfuscated_frame();
+
+        tlh.exists_log_containing(
+            "INFO: obfuscation_overhead_is_logged_periodically: Clandestine obfuscation overhead: 1 frames masked: 10 padding bytes added to 90 payload bytes (11.1% overhead)",
+        );
+        assert_eq!(subject.frames_since_obfuscation_report, 0);
+    }
+
+    #[test]
+    fn obfuscation_overhead_is_not_counted_when_obfuscation_is_off() {
+        let mut subject = StreamHandlerPool::new(vec![], ObfuscationConfig::default());
+
+        subject.count_obfuscated_frame();
+
+        assert_eq!(subject.frames_since_obfuscation_report, 0);
+    }
 }
@@ -1,12 +1,18 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 use crate::discriminator::Discriminator;
 use crate::discriminator::DiscriminatorFactory;
+use crate::persistent_configuration::SUBMISSION_PORT;
 use crate::proxy_server::http_protocol_pack::HttpProtocolPack;
+use crate::proxy_server::protocol_pack::from_standard_port;
+use crate::proxy_server::smtp_protocol_pack::{SmtpPreamble, SmtpReply};
 use crate::stream_messages::*;
 use crate::sub_lib::dispatcher;
+use crate::sub_lib::dispatcher::Endpoint;
 use crate::sub_lib::dispatcher::StreamShutdownMsg;
+use crate::sub_lib::framer::FramingViolation;
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::sequencer::Sequencer;
+use crate::sub_lib::stream_handler_pool::TransmitDataMsg;
 use crate::sub_lib::tokio_wrappers::ReadHalfWrapper;
 use crate::sub_lib::utils::indicates_dead_stream;
 use actix::Recipient;
@@ -21,11 +27,16 @@ pub struct StreamReaderReal {
     reception_port: Option<u16>,
     ibcd_sub: Recipient<dispatcher::InboundClientData>,
     remove_sub: Recipient<RemoveStreamMsg>,
+    transmit_sub: Recipient<TransmitDataMsg>,
     stream_shutdown_sub: Recipient<StreamShutdownMsg>,
     discriminators: Vec<Discriminator>,
     is_clandestine: bool,
     logger: Logger,
     sequencer: Sequencer,
+    forwarded_data: bool,
+    refused: bool,
+    smtp_preamble_opt: Option<SmtpPreamble>,
+    opening_packet_due: bool,
 }
 
 impl Future for StreamReaderReal {
@@ -33,6 +44,17 @@ impl Future for StreamReaderReal {
     type Error = ();
 
     fn poll(&mut self) -> Result<Async<()>, ()> {
+        if self.opening_packet_due {
+            self.opening_packet_due = false;
+            self.send_opening_packet();
+        }
+        if let Some(greeting) = self
+            .smtp_preamble_opt
+            .as_mut()
+            .and_then(|preamble| preamble.take_greeting())
+        {
+            self.send_smtp_reply(greeting);
+        }
         let mut buf = [0u8; 0x10_000];
         loop {
             match self.stream.poll_read(&mut buf) {
@@ -54,7 +76,10 @@ impl Future for StreamReaderReal {
                         length,
                         Self::stringify(self.local_addr, self.peer_addr)
                     );
-                    self.wrangle_discriminators(&buf, length)
+                    if !self.wrangle_data(&buf, length) {
+                        self.shutdown();
+                        return Ok(Async::Ready(()));
+                    }
                 }
                 Err(e) => {
                     if indicates_dead_stream(e.kind()) {
@@ -87,6 +112,7 @@ impl StreamReaderReal {
         reception_port: Option<u16>,
         ibcd_sub: Recipient<dispatcher::InboundClientData>,
         remove_sub: Recipient<RemoveStreamMsg>,
+        transmit_sub: Recipient<TransmitDataMsg>,
         stream_shutdown_sub: Recipient<StreamShutdownMsg>,
         discriminator_factories: Vec<Box<dyn DiscriminatorFactory>>,
         is_clandestine: bool,
@@ -108,15 +134,77 @@ impl StreamReaderReal {
             reception_port,
             ibcd_sub,
             remove_sub,
+            transmit_sub,
             stream_shutdown_sub,
             discriminators,
             is_clandestine,
             logger: Logger::new(&name),
             sequencer: Sequencer::new(),
+            forwarded_data: false,
+            refused: false,
+            smtp_preamble_opt: if !is_clandestine && reception_port == Some(SUBMISSION_PORT) {
+                Some(SmtpPreamble::new())
+            } else {
+                None
+            },
+            opening_packet_due: false,
+        }
+    }
+
+    pub fn with_opening_packet(mut self) -> StreamReaderReal {
+        self.opening_packet_due = true;
+        self
+    }
+
+    // An empty packet that gets the exit connecting to the server, which then has its say
+    fn send_opening_packet(&mut self) {
+        debug!(
+            self.logger,
+            "Opening stream for {} before it has sent anything", self.peer_addr
+        );
+        let msg = dispatcher::InboundClientData {
+            peer_addr: self.peer_addr,
+            reception_port: self.reception_port,
+            last_data: false,
+            is_clandestine: self.is_clandestine,
+            sequence_number: Some(self.sequencer.next_sequence_number()),
+            data: vec![],
+        };
+        self.ibcd_sub.try_send(msg).expect("Dispatcher is dead");
+    }
+
+    // A mail client on the submission port is answered by the Node itself until it asks for
+    // STARTTLS; only what it sends after that goes to the Discriminators. Returns false if the
+    // stream should be closed.
+    fn wrangle_data(&mut self, buf: &[u8], length: usize) -> bool {
+        let (replies, remainder) = match self.smtp_preamble_opt.as_mut() {
+            Some(preamble) if !preamble.is_finished() => preamble.add_data(&buf[..length]),
+            _ => return self.wrangle_discriminators(buf, length),
+        };
+        // After a refusal, the StreamHandlerPool closes the stream once it's written
+        replies
+            .into_iter()
+            .for_each(|reply| self.send_smtp_reply(reply));
+        if remainder.is_empty() {
+            true
+        } else {
+            self.wrangle_discriminators(&remainder, remainder.len())
         }
     }
 
-    fn wrangle_discriminators(&mut self, buf: &[u8], length: usize) {
+    fn send_smtp_reply(&self, reply: SmtpReply) {
+        self.transmit_sub
+            .try_send(TransmitDataMsg {
+                endpoint: Endpoint::Socket(self.peer_addr),
+                last_data: reply.last_data,
+                sequence_number: Some(reply.sequence_number),
+                data: reply.data,
+            })
+            .expect("StreamHandlerPool is dead");
+    }
+
+    // Returns false if the stream should be closed
+    fn wrangle_discriminators(&mut self, buf: &[u8], length: usize) -> bool {
         // Although discriminators is a vec, it was never really designed to have more than one.
         let is_connect = HttpProtocolPack::is_connect(buf);
         let chosen_discriminator = if self.discriminators.len() > 1 && is_connect {
@@ -168,6 +256,7 @@ impl StreamReaderReal {
                     debug!(self.logger, "Discriminator framed and unmasked {} bytes for {}; transmitting via Hopper",
                                               unmasked_chunk.chunk.len(), msg.peer_addr);
                     self.ibcd_sub.try_send(msg).expect("Dispatcher is dead");
+                    self.forwarded_data = true;
                 }
                 None => {
                     debug!(self.logger, "Discriminator has no more data framed");
@@ -175,6 +264,44 @@ impl StreamReaderReal {
                 }
             }
         }
+        match chosen_discriminator.violation() {
+            Some(ref violation) if !self.refused => self.refuse(violation),
+            _ => true,
+        }
+    }
+
+    // A browser whose first request breaks a limit is told why before its stream is closed; once
+    // responses have started coming back, there's no telling where a refusal would land, so the
+    // stream is simply closed. Returns false if the stream should be closed now.
+    fn refuse(&mut self, violation: &FramingViolation) -> bool {
+        self.refused = true;
+        warning!(
+            self.logger,
+            "Refusing data from {}: {}",
+            self.peer_addr,
+            violation
+        );
+        if self.is_clandestine || self.forwarded_data {
+            return false;
+        }
+        let protocol_pack = match self.reception_port.and_then(from_standard_port) {
+            Some(protocol_pack) => protocol_pack,
+            None => return false,
+        };
+        let data = protocol_pack
+            .server_impersonator()
+            .framing_violation_response(violation);
+        // The StreamHandlerPool closes the stream once the response is written, and whatever the
+        // browser sends meanwhile is thrown away by the Discriminator
+        self.transmit_sub
+            .try_send(TransmitDataMsg {
+                endpoint: Endpoint::Socket(self.peer_addr),
+                last_data: true,
+                sequence_number: Some(0),
+                data,
+            })
+            .expect("StreamHandlerPool is dead");
+        true
     }
 
     fn shutdown(&mut self) {
@@ -212,6 +339,9 @@ mod tests {
     use crate::masquerader::Masquerader;
     use crate::node_test_utils::make_stream_handler_pool_subs_from;
     use crate::persistent_configuration::HTTP_PORT;
+    use crate::proxy_server::http_protocol_pack::MAX_REQUEST_LINE_LENGTH;
+    use crate::proxy_server::protocol_pack::ProtocolPack;
+    use crate::raw_discriminator_factory::RawDiscriminatorFactory;
     use crate::stream_handler_pool::StreamHandlerPoolSubs;
     use crate::stream_messages::RemovedStreamType::NonClandestine;
     use crate::sub_lib::dispatcher::DispatcherSubs;
@@ -263,6 +393,7 @@ mod tests {
             None,
             dispatcher_subs.ibcd_sub,
             stream_handler_pool_subs.remove_sub,
+            stream_handler_pool_subs.transmit_sub,
             dispatcher_subs.stream_shutdown_sub.clone(),
             discriminator_factories,
             true,
@@ -312,6 +443,7 @@ mod tests {
             None,
             dispatcher_subs.ibcd_sub,
             stream_handler_pool_subs.remove_sub,
+            stream_handler_pool_subs.transmit_sub,
             dispatcher_subs.stream_shutdown_sub.clone(),
             discriminator_factories,
             true,
@@ -361,6 +493,7 @@ mod tests {
             Some(1234 as u16),
             dispatcher_subs.ibcd_sub,
             stream_handler_pool_subs.remove_sub,
+            stream_handler_pool_subs.transmit_sub,
             dispatcher_subs.stream_shutdown_sub,
             discriminator_factories,
             true,
@@ -404,6 +537,7 @@ mod tests {
             Some(1234 as u16),
             dispatcher_subs.ibcd_sub,
             stream_handler_pool_subs.remove_sub,
+            stream_handler_pool_subs.transmit_sub,
             dispatcher_subs.stream_shutdown_sub,
             discriminator_factories,
             true,
@@ -444,6 +578,7 @@ mod tests {
             Some(1234 as u16),
             dispatcher_subs.ibcd_sub,
             stream_handler_pool_subs.remove_sub,
+            stream_handler_pool_subs.transmit_sub,
             dispatcher_subs.stream_shutdown_sub,
             discriminator_factories,
             true,
@@ -483,6 +618,7 @@ mod tests {
             Some(1234 as u16),
             dispatcher_subs.ibcd_sub,
             stream_handler_pool_subs.remove_sub,
+            stream_handler_pool_subs.transmit_sub,
             dispatcher_subs.stream_shutdown_sub,
             discriminator_factories,
             true,
@@ -546,6 +682,7 @@ mod tests {
             Some(1234 as u16),
             dispatcher_subs.ibcd_sub,
             stream_handler_pool_subs.remove_sub,
+            stream_handler_pool_subs.transmit_sub,
             dispatcher_subs.stream_shutdown_sub,
             discriminator_factories,
             false,
@@ -598,6 +735,7 @@ mod tests {
             Some(1234 as u16),
             dispatcher_subs.ibcd_sub,
             stream_handler_pool_subs.remove_sub,
+            stream_handler_pool_subs.transmit_sub,
             dispatcher_subs.stream_shutdown_sub,
             discriminator_factories,
             false,
@@ -665,6 +803,7 @@ mod tests {
             Some(1234 as u16),
             dispatcher_subs.ibcd_sub,
             stream_handler_pool_subs.remove_sub,
+            stream_handler_pool_subs.transmit_sub,
             dispatcher_subs.stream_shutdown_sub,
             discriminator_factories,
             true,
@@ -706,6 +845,7 @@ mod tests {
             None,
             dispatcher_subs.ibcd_sub,
             stream_handler_pool_subs.remove_sub,
+            stream_handler_pool_subs.transmit_sub,
             dispatcher_subs.stream_shutdown_sub.clone(),
             discriminator_factories,
             true,
@@ -745,6 +885,7 @@ mod tests {
             Some(HTTP_PORT),
             dispatcher_subs.ibcd_sub,
             stream_handler_pool_subs.remove_sub,
+            stream_handler_pool_subs.transmit_sub,
             dispatcher_subs.stream_shutdown_sub.clone(),
             discriminator_factories,
             false,
@@ -772,4 +913,252 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn first_request_that_breaks_a_limit_is_answered_with_an_error_and_what_follows_is_ignored() {
+        init_test_logging();
+        let system = System::new("test");
+        let (shp_recording_arc, stream_handler_pool_subs) = stream_handler_pool_stuff();
+        let (d_recording_arc, dispatcher_subs) = dispatcher_stuff();
+        let peer_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let local_addr = SocketAddr::from_str("1.2.3.5:6789").unwrap();
+        let discriminator_factories: Vec<Box<dyn DiscriminatorFactory>> =
+            vec![Box::new(HttpRequestDiscriminatorFactory::new())];
+        let long_request_line = format!(
+            "GET http://here.com/{} HTTP/1.1",
+            "x".repeat(MAX_REQUEST_LINE_LENGTH)
+        )
+        .into_bytes();
+        let more_request = Vec::from("\r\n\r\nGET http://here.com HTTP/1.1\r\n\r\n".as_bytes());
+        let reader = ReadHalfWrapperMock::new()
+            .poll_read_result(
+                long_request_line.clone(),
+                Ok(Async::Ready(long_request_line.len())),
+            )
+            .poll_read_result(more_request.clone(), Ok(Async::Ready(more_request.len())))
+            .poll_read_result(vec![], Ok(Async::NotReady));
+        let mut subject = StreamReaderReal::new(
+            Box::new(reader),
+            Some(HTTP_PORT),
+            dispatcher_subs.ibcd_sub,
+            stream_handler_pool_subs.remove_sub,
+            stream_handler_pool_subs.transmit_sub,
+            dispatcher_subs.stream_shutdown_sub,
+            discriminator_factories,
+            false,
+            peer_addr,
+            local_addr,
+        );
+
+        let result = subject.poll();
+
+        System::current().stop_with_code(0);
+        system.run();
+        assert_eq!(result, Ok(Async::NotReady));
+        let shp_recording = shp_recording_arc.lock().unwrap();
+        assert_eq!(
+            shp_recording.get_record::<TransmitDataMsg>(0),
+            &TransmitDataMsg {
+                endpoint: Endpoint::Socket(peer_addr),
+                last_data: true,
+                sequence_number: Some(0),
+                data: HttpProtocolPack {}
+                    .server_impersonator()
+                    .framing_violation_response(&FramingViolation::RequestLineTooLong(
+                        MAX_REQUEST_LINE_LENGTH
+                    )),
+            }
+        );
+        assert_eq!(shp_recording.len(), 1);
+        assert_eq!(d_recording_arc.lock().unwrap().len(), 0);
+        TestLogHandler::new().exists_log_containing(
+            "WARN: StreamReader for 1.2.3.4:5678: Refusing data from 1.2.3.4:5678: request line longer than 8192 bytes",
+        );
+    }
+
+    #[test]
+    fn mail_client_on_submission_port_is_answered_locally_until_it_starts_tls() {
+        let system = System::new("test");
+        let (shp_recording_arc, stream_handler_pool_subs) = stream_handler_pool_stuff();
+        let (d_recording_arc, dispatcher_subs) = dispatcher_stuff();
+        let peer_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let local_addr = SocketAddr::from_str("1.2.3.5:6789").unwrap();
+        let discriminator_factories: Vec<Box<dyn DiscriminatorFactory>> =
+            vec![Box::new(TlsDiscriminatorFactory::new())];
+        let ehlo = Vec::from("EHLO laptop.local\r\n".as_bytes());
+        let client_hello = vec![0x16, 0x03, 0x01, 0x00, 0x03, 0x01, 0x02, 0x03];
+        let starttls_and_client_hello = [&b"STARTTLS\r\n"[..], &client_hello[..]].concat();
+        let reader = ReadHalfWrapperMock::new()
+            .poll_read_result(ehlo.clone(), Ok(Async::Ready(ehlo.len())))
+            .poll_read_result(
+                starttls_and_client_hello.clone(),
+                Ok(Async::Ready(starttls_and_client_hello.len())),
+            )
+            .poll_read_result(vec![], Ok(Async::NotReady));
+        let mut subject = StreamReaderReal::new(
+            Box::new(reader),
+            Some(SUBMISSION_PORT),
+            dispatcher_subs.ibcd_sub,
+            stream_handler_pool_subs.remove_sub,
+            stream_handler_pool_subs.transmit_sub,
+            dispatcher_subs.stream_shutdown_sub,
+            discriminator_factories,
+            false,
+            peer_addr,
+            local_addr,
+        );
+
+        let result = subject.poll();
+
+        System::current().stop_with_code(0);
+        system.run();
+        assert_eq!(result, Ok(Async::NotReady));
+        let shp_recording = shp_recording_arc.lock().unwrap();
+        let replies = (0..3)
+            .map(|index| {
+                let record = shp_recording.get_record::<TransmitDataMsg>(index);
+                (
+                    record.sequence_number,
+                    String::from_utf8(record.data.clone()).unwrap(),
+                )
+            })
+            .collect::<Vec<(Option<u64>, String)>>();
+        assert_eq!(
+            replies,
+            vec![
+                (
+                    Some(0),
+                    "220 localhost ESMTP PrometheusNode\r\n".to_string()
+                ),
+                (Some(1), "250-localhost\r\n250 STARTTLS\r\n".to_string()),
+                (Some(2), "220 Ready to start TLS\r\n".to_string()),
+            ]
+        );
+        assert_eq!(shp_recording.len(), 3);
+        let d_recording = d_recording_arc.lock().unwrap();
+        assert_eq!(
+            d_recording.get_record::<dispatcher::InboundClientData>(0),
+            &dispatcher::InboundClientData {
+                peer_addr,
+                reception_port: Some(SUBMISSION_PORT),
+                last_data: false,
+                is_clandestine: false,
+                sequence_number: Some(0),
+                data: client_hello,
+            }
+        );
+        assert_eq!(d_recording.len(), 1);
+    }
+
+    #[test]
+    fn later_request_that_breaks_a_limit_closes_the_stream() {
+        let system = System::new("test");
+        let (shp_recording_arc, stream_handler_pool_subs) = stream_handler_pool_stuff();
+        let (d_recording_arc, dispatcher_subs) = dispatcher_stuff();
+        let peer_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let local_addr = SocketAddr::from_str("1.2.3.5:6789").unwrap();
+        let discriminator_factories: Vec<Box<dyn DiscriminatorFactory>> =
+            vec![Box::new(HttpRequestDiscriminatorFactory::new())];
+        let good_request = Vec::from("GET http://here.com HTTP/1.1\r\n\r\n".as_bytes());
+        let huge_request = format!(
+            "GET http://here.com/{} HTTP/1.1\r\n\r\n",
+            "x".repeat(MAX_REQUEST_LINE_LENGTH)
+        )
+        .into_bytes();
+        let reader = ReadHalfWrapperMock::new()
+            .poll_read_result(good_request.clone(), Ok(Async::Ready(good_request.len())))
+            .poll_read_result(huge_request.clone(), Ok(Async::Ready(huge_request.len())))
+            .poll_read_result(vec![], Ok(Async::NotReady));
+        let mut subject = StreamReaderReal::new(
+            Box::new(reader),
+            Some(HTTP_PORT),
+            dispatcher_subs.ibcd_sub,
+            stream_handler_pool_subs.remove_sub,
+            stream_handler_pool_subs.transmit_sub,
+            dispatcher_subs.stream_shutdown_sub.clone(),
+            discriminator_factories,
+            false,
+            peer_addr,
+            local_addr,
+        );
+
+        let result = subject.poll();
+
+        System::current().stop_with_code(0);
+        system.run();
+        assert_eq!(result, Ok(Async::Ready(())));
+        assert_eq!(d_recording_arc.lock().unwrap().len(), 1);
+        let shp_recording = shp_recording_arc.lock().unwrap();
+        assert_eq!(
+            shp_recording.get_record::<RemoveStreamMsg>(0),
+            &RemoveStreamMsg {
+                peer_addr,
+                local_addr,
+                stream_type: NonClandestine(NonClandestineAttributes {
+                    reception_port: HTTP_PORT,
+                    sequence_number: 1,
+                }),
+                sub: dispatcher_subs.stream_shutdown_sub,
+            }
+        );
+        assert_eq!(shp_recording.len(), 1);
+    }
+
+    #[test]
+    fn stream_that_opens_on_connect_sends_an_empty_packet_first() {
+        let system = System::new("test");
+        let (_, stream_handler_pool_subs) = stream_handler_pool_stuff();
+        let (d_recording_arc, dispatcher_subs) = dispatcher_stuff();
+        let peer_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let local_addr = SocketAddr::from_str("127.0.0.1:2121").unwrap();
+        let discriminator_factories: Vec<Box<dyn DiscriminatorFactory>> =
+            vec![Box::new(RawDiscriminatorFactory::new())];
+        let user = Vec::from("USER anonymous\r\n".as_bytes());
+        let reader = ReadHalfWrapperMock::new()
+            .poll_read_result(user.clone(), Ok(Async::Ready(user.len())))
+            .poll_read_result(vec![], Ok(Async::NotReady));
+        let mut subject = StreamReaderReal::new(
+            Box::new(reader),
+            Some(2121),
+            dispatcher_subs.ibcd_sub,
+            stream_handler_pool_subs.remove_sub,
+            stream_handler_pool_subs.transmit_sub,
+            dispatcher_subs.stream_shutdown_sub,
+            discriminator_factories,
+            false,
+            peer_addr,
+            local_addr,
+        )
+        .with_opening_packet();
+
+        let result = subject.poll();
+
+        System::current().stop_with_code(0);
+        system.run();
+        assert_eq!(result, Ok(Async::NotReady));
+        let d_recording = d_recording_arc.lock().unwrap();
+        assert_eq!(
+            d_recording.get_record::<dispatcher::InboundClientData>(0),
+            &dispatcher::InboundClientData {
+                peer_addr,
+                reception_port: Some(2121),
+                last_data: false,
+                is_clandestine: false,
+                sequence_number: Some(0),
+                data: vec![],
+            }
+        );
+        assert_eq!(
+            d_recording.get_record::<dispatcher::InboundClientData>(1),
+            &dispatcher::InboundClientData {
+                peer_addr,
+                reception_port: Some(2121),
+                last_data: false,
+                is_clandestine: false,
+                sequence_number: Some(1),
+                data: user,
+            }
+        );
+        assert_eq!(d_recording.len(), 2);
+    }
 }
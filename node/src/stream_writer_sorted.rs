@@ -3,6 +3,7 @@ use crate::sub_lib::channel_wrappers::ReceiverWrapper;
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::sequence_buffer::SequenceBuffer;
 use crate::sub_lib::sequence_buffer::SequencedPacket;
+use crate::sub_lib::spill_file::SpillConfig;
 use crate::sub_lib::tokio_wrappers::WriteHalfWrapper;
 use crate::sub_lib::utils::indicates_dead_stream;
 use std::net::SocketAddr;
@@ -33,6 +34,7 @@ impl Future for StreamWriterSorted {
         match (read_result, write_result) {
             // read_result can only be NotReady or Ready; write_result can only be Err, NotReady, or Ready
             (_, WriteBufferStatus::StreamInError) => Err(()), // dead stream error, shut down (this must be first in the match)
+            (ReadChannelStatus::Overflowed, _) => Err(()), // more data than the stream may hold, shut down
             (ReadChannelStatus::StillOpen, _) => Ok(Async::NotReady), // may receive more data, don't shut down
             (ReadChannelStatus::Closed, WriteBufferStatus::BufferNotEmpty) => Ok(Async::NotReady), // still have packets to write, don't shut down yet
             (ReadChannelStatus::Closed, WriteBufferStatus::BufferEmpty) => Ok(Async::Ready(())), // all done, shut down
@@ -58,6 +60,11 @@ impl StreamWriterSorted {
         }
     }
 
+    pub fn with_spill(mut self, spill_config: SpillConfig) -> StreamWriterSorted {
+        self.sequence_buffer = SequenceBuffer::new().with_spill(spill_config);
+        self
+    }
+
     fn shutdown(&mut self) -> Result<Async<()>, ()> {
         match self.stream.shutdown() {
             Ok(Async::NotReady) => Ok(Async::NotReady),
@@ -70,7 +77,13 @@ impl StreamWriterSorted {
         loop {
             match self.rx_to_write.poll() {
                 Ok(Async::Ready(Some(sequenced_packet))) => {
-                    self.sequence_buffer.push(sequenced_packet)
+                    if let Err(e) = self.sequence_buffer.push(sequenced_packet) {
+                        error!(
+                            self.logger,
+                            "Giving up on the stream to {}: {}", self.peer_addr, e
+                        );
+                        return ReadChannelStatus::Overflowed;
+                    }
                 }
                 Ok(Async::Ready(None)) => return ReadChannelStatus::Closed,
                 Ok(Async::NotReady) => return ReadChannelStatus::StillOpen,
@@ -84,7 +97,16 @@ impl StreamWriterSorted {
 
     fn write_from_buffer_to_stream(&mut self) -> WriteBufferStatus {
         loop {
-            let packet_opt = self.sequence_buffer.poll();
+            let packet_opt = match self.sequence_buffer.poll() {
+                Ok(packet_opt) => packet_opt,
+                Err(e) => {
+                    error!(
+                        self.logger,
+                        "Giving up on the stream to {}: {}", self.peer_addr, e
+                    );
+                    return WriteBufferStatus::StreamInError;
+                }
+            };
 
             match packet_opt {
                 Some(packet) => {
@@ -149,6 +171,7 @@ impl StreamWriterSorted {
 enum ReadChannelStatus {
     StillOpen,
     Closed,
+    Overflowed,
 }
 
 enum WriteBufferStatus {
@@ -162,6 +185,7 @@ mod tests {
     use super::*;
     use crate::sub_lib::sequence_buffer::SequencedPacket;
     use crate::test_utils::channel_wrapper_mocks::ReceiverWrapperMock;
+    use crate::test_utils::ensure_node_home_directory_exists;
     use crate::test_utils::logging::init_test_logging;
     use crate::test_utils::logging::TestLogHandler;
     use crate::test_utils::tokio_wrapper_mocks::WriteHalfWrapperMock;
@@ -613,4 +637,45 @@ mod tests {
 
         assert_eq!(res, Err(()));
     }
+
+    #[test]
+    fn stream_writer_gives_up_on_the_stream_when_its_packets_dont_fit_in_the_spill_limit() {
+        init_test_logging();
+        let directory = ensure_node_home_directory_exists(
+            "stream_writer_sorted",
+            "stream_writer_gives_up_on_the_stream_when_its_packets_dont_fit_in_the_spill_limit",
+        );
+        let mut rx = Box::new(ReceiverWrapperMock::new());
+        rx.poll_results = vec![
+            Ok(Async::Ready(Some(SequencedPacket::new(
+                b"bcde".to_vec(),
+                1,
+                false,
+            )))),
+            Ok(Async::Ready(Some(SequencedPacket::new(
+                b"fghi".to_vec(),
+                2,
+                false,
+            )))),
+            Ok(Async::NotReady),
+        ];
+        let writer = WriteHalfWrapperMock::new();
+        let write_params = writer.poll_write_params.clone();
+        let peer_addr = SocketAddr::from_str("3.4.5.6:7890").unwrap();
+        let mut subject =
+            StreamWriterSorted::new(Box::new(writer), peer_addr, rx).with_spill(SpillConfig {
+                memory_limit: 4,
+                spill_limit: 3,
+                directory_opt: Some(directory),
+                ..SpillConfig::default()
+            });
+
+        let result = subject.poll();
+
+        assert_eq!(result, Err(()));
+        assert_eq!(write_params.lock().unwrap().len(), 0);
+        TestLogHandler::new().exists_log_containing(
+            "ERROR: StreamWriter for 3.4.5.6:7890: Giving up on the stream to 3.4.5.6:7890: 4 bytes in memory and 0 spilled; no room for packet #2",
+        );
+    }
 }
@@ -1,17 +1,25 @@
 use crate::sub_lib::channel_wrappers::ReceiverWrapper;
 use crate::sub_lib::logger::Logger;
+use crate::sub_lib::obfuscation::{ObfuscationConfig, ObfuscationMetrics};
+use crate::sub_lib::random::{Random, RandomReal};
 use crate::sub_lib::sequence_buffer::SequencedPacket;
 use crate::sub_lib::tokio_wrappers::WriteHalfWrapper;
 use crate::sub_lib::utils::indicates_dead_stream;
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::prelude::Async;
 use tokio::prelude::Future;
+use tokio::timer::Delay;
 
 pub struct StreamWriterUnsorted {
     stream: Box<dyn WriteHalfWrapper>,
     rx_to_write: Box<dyn ReceiverWrapper<SequencedPacket>>,
     logger: Logger,
     buf: Option<SequencedPacket>,
+    jitter_opt: Option<(ObfuscationConfig, Arc<ObfuscationMetrics>)>,
+    delay_opt: Option<Delay>,
+    random: Box<dyn Random>,
 }
 
 impl Future for StreamWriterUnsorted {
@@ -23,7 +31,10 @@ impl Future for StreamWriterUnsorted {
             match self.buf.take() {
                 None => {
                     self.buf = match self.rx_to_write.poll() {
-                        Ok(Async::Ready(Some(data))) => Some(data),
+                        Ok(Async::Ready(Some(data))) => {
+                            self.start_jitter_delay();
+                            Some(data)
+                        }
                         Ok(Async::Ready(None)) => return Ok(Async::Ready(())), // the channel has been closed on the tx side
                         Ok(Async::NotReady) => return Ok(Async::NotReady),
                         Err(e) => panic!(
@@ -32,6 +43,10 @@ impl Future for StreamWriterUnsorted {
                     }
                 }
                 Some(packet) => {
+                    if !self.jitter_delay_has_elapsed() {
+                        self.buf = Some(packet);
+                        return Ok(Async::NotReady);
+                    }
                     // TODO in SC-646 "Graceful Shutdown from GUI" (marked obsolete): handle packet.last_data = true here
                     debug!(
                         self.logger,
@@ -98,6 +113,54 @@ impl StreamWriterUnsorted {
             rx_to_write,
             logger,
             buf: None,
+            jitter_opt: None,
+            delay_opt: None,
+            random: Box::new(RandomReal {}),
+        }
+    }
+
+    // Each packet will wait a random interval up to config.max_jitter before it's written.
+    pub fn with_jitter(
+        mut self,
+        config: ObfuscationConfig,
+        metrics: Arc<ObfuscationMetrics>,
+    ) -> StreamWriterUnsorted {
+        if config.max_jitter > Duration::from_millis(0) {
+            self.jitter_opt = Some((config, metrics));
+        }
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_random(mut self, random: Box<dyn Random>) -> StreamWriterUnsorted {
+        self.random = random;
+        self
+    }
+
+    fn start_jitter_delay(&mut self) {
+        if let Some((config, metrics)) = &self.jitter_opt {
+            let jitter = config.random_jitter(self.random.as_ref());
+            metrics.record_jitter(jitter);
+            self.delay_opt = Some(Delay::new(Instant::now() + jitter));
+        }
+    }
+
+    fn jitter_delay_has_elapsed(&mut self) -> bool {
+        let result = match self.delay_opt.as_mut() {
+            None => return true,
+            Some(delay) => delay.poll(),
+        };
+        match result {
+            Ok(Async::NotReady) => false,
+            Ok(Async::Ready(())) => {
+                self.delay_opt = None;
+                true
+            }
+            Err(e) => {
+                warning!(self.logger, "Skipping jitter delay: {}", e);
+                self.delay_opt = None;
+                true
+            }
         }
     }
 }
@@ -108,6 +171,7 @@ mod tests {
     use crate::test_utils::channel_wrapper_mocks::ReceiverWrapperMock;
     use crate::test_utils::logging::init_test_logging;
     use crate::test_utils::logging::TestLogHandler;
+    use crate::test_utils::random_seeded::RandomSeeded;
     use crate::test_utils::tokio_wrapper_mocks::WriteHalfWrapperMock;
     use std::io;
     use std::io::ErrorKind;
@@ -402,4 +466,113 @@ mod tests {
         );
         assert_eq!(write_params.lock().unwrap().get(2).unwrap(), &b"s".to_vec());
     }
+
+    #[test]
+    fn stream_writer_with_jitter_delays_each_packet_and_records_it() {
+        let mut rx = Box::new(ReceiverWrapperMock::new());
+        rx.poll_results = vec![
+            Ok(Async::Ready(Some(SequencedPacket::new(
+                b"hello".to_vec(),
+                0,
+                false,
+            )))),
+            Ok(Async::Ready(Some(SequencedPacket::new(
+                b"world".to_vec(),
+                0,
+                false,
+            )))),
+            Ok(Async::Ready(None)),
+        ];
+        let writer = WriteHalfWrapperMock::new()
+            .poll_write_result(Ok(Async::Ready(5)))
+            .poll_write_result(Ok(Async::Ready(5)));
+        let write_params = writer.poll_write_params.clone();
+        let peer_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let metrics = Arc::new(ObfuscationMetrics::new());
+        let config = ObfuscationConfig {
+            max_jitter: Duration::from_millis(20),
+            max_padding: 0,
+        };
+        let subject = StreamWriterUnsorted::new(Box::new(writer), peer_addr, rx)
+            .with_jitter(config, metrics.clone());
+        let start = Instant::now();
+
+        let result = tokio::runtime::current_thread::Runtime::new()
+            .unwrap()
+            .block_on(subject);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            *write_params.lock().unwrap(),
+            vec![b"hello".to_vec(), b"world".to_vec()]
+        );
+        assert_eq!(metrics.delayed_frames(), 2);
+        assert!(metrics.total_jitter() <= Duration::from_millis(40));
+        assert!(start.elapsed() >= metrics.total_jitter());
+    }
+
+    #[test]
+    fn stream_writer_with_jitter_takes_its_delays_from_its_random() {
+        let mut rx = Box::new(ReceiverWrapperMock::new());
+        rx.poll_results = vec![
+            Ok(Async::Ready(Some(SequencedPacket::new(
+                b"hello".to_vec(),
+                0,
+                false,
+            )))),
+            Ok(Async::Ready(Some(SequencedPacket::new(
+                b"world".to_vec(),
+                0,
+                false,
+            )))),
+            Ok(Async::Ready(None)),
+        ];
+        let writer = WriteHalfWrapperMock::new()
+            .poll_write_result(Ok(Async::Ready(5)))
+            .poll_write_result(Ok(Async::Ready(5)));
+        let peer_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let metrics = Arc::new(ObfuscationMetrics::new());
+        let config = ObfuscationConfig {
+            max_jitter: Duration::from_millis(20),
+            max_padding: 0,
+        };
+        let reference = RandomSeeded::new(1234);
+        let expected_jitter = config.random_jitter(&reference) + config.random_jitter(&reference);
+        let subject = StreamWriterUnsorted::new(Box::new(writer), peer_addr, rx)
+            .with_jitter(config, metrics.clone())
+            .with_random(Box::new(RandomSeeded::new(1234)));
+
+        let result = tokio::runtime::current_thread::Runtime::new()
+            .unwrap()
+            .block_on(subject);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(metrics.delayed_frames(), 2);
+        assert_eq!(metrics.total_jitter(), expected_jitter);
+    }
+
+    #[test]
+    fn stream_writer_with_zero_jitter_does_not_delay() {
+        let mut rx = Box::new(ReceiverWrapperMock::new());
+        rx.poll_results = vec![
+            Ok(Async::Ready(Some(SequencedPacket::new(
+                b"hello".to_vec(),
+                0,
+                false,
+            )))),
+            Ok(Async::NotReady),
+        ];
+        let writer = WriteHalfWrapperMock::new().poll_write_result(Ok(Async::Ready(5)));
+        let write_params = writer.poll_write_params.clone();
+        let peer_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let metrics = Arc::new(ObfuscationMetrics::new());
+        let mut subject = StreamWriterUnsorted::new(Box::new(writer), peer_addr, rx)
+            .with_jitter(ObfuscationConfig::default(), metrics.clone());
+
+        let result = subject.poll();
+
+        assert_eq!(result, Ok(Async::NotReady));
+        assert_eq!(write_params.lock().unwrap().len(), 1);
+        assert_eq!(metrics.delayed_frames(), 0);
+    }
 }
@@ -1,12 +1,20 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
-use crate::accountant::{ReceivedPayments, SentPayments};
+use crate::accountant::{
+    PaymentCurves, ReceivedPayments, ResolvedPayment, SentPayments, DEFAULT_PAYABLE_SCAN_INTERVAL,
+    DEFAULT_PAYMENT_CURVES, DEFAULT_PAYMENT_RECEIVED_SCAN_INTERVAL,
+};
+use crate::blockchain::bip32::Bip32ECKeyPair;
 use crate::sub_lib::peer_actors::{BindMessage, StartMessage};
+use crate::sub_lib::ui_gateway::{ChangeConfigurationMessage, GetConfigurationMessage};
+use crate::sub_lib::ui_gateway::{GetHealthMessage, GetNodeStatisticsMessage};
 use crate::sub_lib::wallet::Wallet;
 use actix::Message;
 use actix::Recipient;
 use lazy_static::lazy_static;
+use rustc_hex::FromHex;
 use serde_derive::{Deserialize, Serialize};
 use std::fmt::{Debug, Formatter};
+use std::ops::RangeInclusive;
 use std::str::FromStr;
 use std::time::Duration;
 
@@ -21,6 +29,271 @@ lazy_static! {
 pub struct AccountantConfig {
     pub payable_scan_interval: Duration,
     pub payment_received_scan_interval: Duration,
+    pub payment_curves: PaymentCurves,
+}
+
+impl Default for AccountantConfig {
+    fn default() -> Self {
+        AccountantConfig {
+            payable_scan_interval: Duration::from_secs(DEFAULT_PAYABLE_SCAN_INTERVAL),
+            payment_received_scan_interval: Duration::from_secs(
+                DEFAULT_PAYMENT_RECEIVED_SCAN_INTERVAL,
+            ),
+            payment_curves: DEFAULT_PAYMENT_CURVES.clone(),
+        }
+    }
+}
+
+impl AccountantConfig {
+    pub fn payment_parameter(&self, parameter: PaymentParameter) -> u64 {
+        let curves = &self.payment_curves;
+        match parameter {
+            PaymentParameter::PayableScanInterval => self.payable_scan_interval.as_secs(),
+            PaymentParameter::PaymentReceivedScanInterval => {
+                self.payment_received_scan_interval.as_secs()
+            }
+            PaymentParameter::PaymentSuggestedAfter => curves.payment_suggested_after_sec as u64,
+            PaymentParameter::PaymentGraceBeforeBan => curves.payment_grace_before_ban_sec as u64,
+            PaymentParameter::PermanentDebtAllowed => curves.permanent_debt_allowed_gwub as u64,
+            PaymentParameter::BalanceToDecreaseFrom => curves.balance_to_decrease_from_gwub as u64,
+            PaymentParameter::BalanceDecreasesFor => curves.balance_decreases_for_sec as u64,
+            PaymentParameter::UnbanWhenBalanceBelow => curves.unban_when_balance_below_gwub as u64,
+        }
+    }
+
+    // Callers are expected to have checked the value with PaymentParameter::validate(), which
+    // guarantees that it fits in the i64 fields of PaymentCurves.
+    pub fn set_payment_parameter(&mut self, parameter: PaymentParameter, value: u64) {
+        let curves = &mut self.payment_curves;
+        match parameter {
+            PaymentParameter::PayableScanInterval => {
+                self.payable_scan_interval = Duration::from_secs(value)
+            }
+            PaymentParameter::PaymentReceivedScanInterval => {
+                self.payment_received_scan_interval = Duration::from_secs(value)
+            }
+            PaymentParameter::PaymentSuggestedAfter => {
+                curves.payment_suggested_after_sec = value as i64
+            }
+            PaymentParameter::PaymentGraceBeforeBan => {
+                curves.payment_grace_before_ban_sec = value as i64
+            }
+            PaymentParameter::PermanentDebtAllowed => {
+                curves.permanent_debt_allowed_gwub = value as i64
+            }
+            PaymentParameter::BalanceToDecreaseFrom => {
+                curves.balance_to_decrease_from_gwub = value as i64
+            }
+            PaymentParameter::BalanceDecreasesFor => {
+                curves.balance_decreases_for_sec = value as i64
+            }
+            PaymentParameter::UnbanWhenBalanceBelow => {
+                curves.unban_when_balance_below_gwub = value as i64
+            }
+        }
+    }
+}
+
+// The Accountant's payment thresholds, delinquency curve, and scan intervals. Each one is kept in
+// the config table under its config key, can be overridden at startup by the command-line flag of
+// the same name, and can be changed at runtime from the UI. Times are in seconds; amounts in gwub.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PaymentParameter {
+    PayableScanInterval,
+    PaymentReceivedScanInterval,
+    PaymentSuggestedAfter,
+    PaymentGraceBeforeBan,
+    PermanentDebtAllowed,
+    BalanceToDecreaseFrom,
+    BalanceDecreasesFor,
+    UnbanWhenBalanceBelow,
+}
+
+pub const PAYMENT_PARAMETERS: [PaymentParameter; 8] = [
+    PaymentParameter::PayableScanInterval,
+    PaymentParameter::PaymentReceivedScanInterval,
+    PaymentParameter::PaymentSuggestedAfter,
+    PaymentParameter::PaymentGraceBeforeBan,
+    PaymentParameter::PermanentDebtAllowed,
+    PaymentParameter::BalanceToDecreaseFrom,
+    PaymentParameter::BalanceDecreasesFor,
+    PaymentParameter::UnbanWhenBalanceBelow,
+];
+
+const SECONDS_PER_DAY: u64 = 86_400;
+const GWUB_PER_SUB: u64 = 1_000_000_000;
+
+impl PaymentParameter {
+    pub fn name(self) -> &'static str {
+        match self {
+            PaymentParameter::PayableScanInterval => "payable-scan-interval",
+            PaymentParameter::PaymentReceivedScanInterval => "payment-received-scan-interval",
+            PaymentParameter::PaymentSuggestedAfter => "payment-suggested-after",
+            PaymentParameter::PaymentGraceBeforeBan => "payment-grace-before-ban",
+            PaymentParameter::PermanentDebtAllowed => "permanent-debt-allowed",
+            PaymentParameter::BalanceToDecreaseFrom => "balance-to-decrease-from",
+            PaymentParameter::BalanceDecreasesFor => "balance-decreases-for",
+            PaymentParameter::UnbanWhenBalanceBelow => "unban-when-balance-below",
+        }
+    }
+
+    pub fn config_key(self) -> String {
+        self.name().replace('-', "_")
+    }
+
+    pub fn units(self) -> &'static str {
+        match self {
+            PaymentParameter::PermanentDebtAllowed
+            | PaymentParameter::BalanceToDecreaseFrom
+            | PaymentParameter::UnbanWhenBalanceBelow => "GWUB",
+            _ => "SECONDS",
+        }
+    }
+
+    pub fn range(self) -> RangeInclusive<u64> {
+        match self {
+            PaymentParameter::PayableScanInterval
+            | PaymentParameter::PaymentReceivedScanInterval => 60..=7 * SECONDS_PER_DAY,
+            PaymentParameter::PaymentSuggestedAfter | PaymentParameter::PaymentGraceBeforeBan => {
+                60..=30 * SECONDS_PER_DAY
+            }
+            PaymentParameter::BalanceDecreasesFor => 60..=365 * SECONDS_PER_DAY,
+            PaymentParameter::PermanentDebtAllowed | PaymentParameter::UnbanWhenBalanceBelow => {
+                0..=1_000 * GWUB_PER_SUB
+            }
+            PaymentParameter::BalanceToDecreaseFrom => 1..=1_000 * GWUB_PER_SUB,
+        }
+    }
+
+    pub fn default_value(self) -> u64 {
+        AccountantConfig::default().payment_parameter(self)
+    }
+
+    pub fn validate(self, value: u64) -> Result<(), String> {
+        let range = self.range();
+        if range.contains(&value) {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} must be between {} and {}, not {}",
+                self.name(),
+                range.start(),
+                range.end(),
+                value
+            ))
+        }
+    }
+}
+
+impl FromStr for PaymentParameter {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        PAYMENT_PARAMETERS
+            .iter()
+            .find(|parameter| parameter.name() == name)
+            .cloned()
+            .ok_or_else(|| format!("Unknown payment parameter: '{}'", name))
+    }
+}
+
+// A consuming profile attributes the services consumed on behalf of some of the browser
+// connections, so that (for example) a work browser and a personal browser can be budgeted
+// separately. A profile picks out its connections either by the range of local ports they
+// originate from, or by the pair of proxy ports that its PAC file points the browser at: PORT for
+// plain HTTP and PORT + 1 for CONNECT. Once a profile has consumed its BUDGET, its connections are
+// refused. A profile with a PRIVATE-KEY of its own has its routes paid for from that wallet rather
+// than the Node's consuming wallet. Specified as NAME:LOW-HIGH or NAME:pac=PORT, optionally
+// followed by :BUDGET and then :PRIVATE-KEY; BUDGET may be left empty to give a key without one.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConsumingProfile {
+    pub name: String,
+    pub selector: ProfileSelector,
+    pub budget_opt: Option<u64>,
+    pub consuming_wallet_opt: Option<Wallet>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ProfileSelector {
+    OriginatingPorts(RangeInclusive<u16>),
+    Pac(u16),
+}
+
+impl ConsumingProfile {
+    // Whether a browser connection from originating_port to our reception_port belongs to this profile
+    pub fn selects(&self, reception_port: u16, originating_port: u16) -> bool {
+        match &self.selector {
+            ProfileSelector::OriginatingPorts(ports) => ports.contains(&originating_port),
+            ProfileSelector::Pac(port) => reception_port == *port || reception_port == port + 1,
+        }
+    }
+
+    pub fn budget_exhausted(&self, consumed: u64) -> bool {
+        match self.budget_opt {
+            Some(budget) => consumed >= budget,
+            None => false,
+        }
+    }
+}
+
+impl FromStr for ConsumingProfile {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let bad = || {
+            format!(
+                "Consuming profile should look like NAME:LOW-HIGH[:BUDGET[:PRIVATE-KEY]] or NAME:pac=PORT[:BUDGET[:PRIVATE-KEY]], not '{}'",
+                spec
+            )
+        };
+        let parts = spec.split(':').collect::<Vec<&str>>();
+        if parts.len() < 2 || parts.len() > 4 || parts[0].is_empty() {
+            return Err(bad());
+        }
+        let selector = if parts[1].starts_with("pac=") {
+            match parts[1]["pac=".len()..].parse::<u16>() {
+                // The CONNECT port is the one after
+                Ok(port) if port < u16::max_value() => ProfileSelector::Pac(port),
+                _ => return Err(bad()),
+            }
+        } else {
+            match parts[1].split('-').collect::<Vec<&str>>().as_slice() {
+                [low, high] => match (low.parse::<u16>(), high.parse::<u16>()) {
+                    (Ok(low), Ok(high)) if low <= high => {
+                        ProfileSelector::OriginatingPorts(low..=high)
+                    }
+                    _ => return Err(bad()),
+                },
+                _ => return Err(bad()),
+            }
+        };
+        let budget_opt = match parts.get(2) {
+            Some(budget) if budget.is_empty() && parts.len() == 4 => None,
+            Some(budget) => Some(budget.parse::<u64>().map_err(|_| bad())?),
+            None => None,
+        };
+        let consuming_wallet_opt = match parts.get(3) {
+            Some(key) => Some(wallet_from_private_key(key).ok_or_else(bad)?),
+            None => None,
+        };
+        Ok(ConsumingProfile {
+            name: parts[0].to_string(),
+            selector,
+            budget_opt,
+            consuming_wallet_opt,
+        })
+    }
+}
+
+fn wallet_from_private_key(key: &str) -> Option<Wallet> {
+    if key.len() != 64 {
+        return None;
+    }
+    let raw_secret = key.from_hex::<Vec<u8>>().ok()?;
+    Bip32ECKeyPair::from_raw_secret(&raw_secret[..])
+        .ok()
+        .map(Wallet::from)
 }
 
 #[derive(Clone)]
@@ -33,7 +306,16 @@ pub struct AccountantSubs {
     pub report_exit_service_consumed: Recipient<ReportExitServiceConsumedMessage>,
     pub report_new_payments: Recipient<ReceivedPayments>,
     pub report_sent_payments: Recipient<SentPayments>,
+    pub report_resolved_payment: Recipient<ResolvedPayment>,
+    pub report_new_block: Recipient<NewBlockMessage>,
     pub get_financial_statistics_sub: Recipient<GetFinancialStatisticsMessage>,
+    pub dump_financials_sub: Recipient<DumpFinancialsMessage>,
+    pub set_payment_parameter_sub: Recipient<SetPaymentParameterMessage>,
+    pub report_billing_claim_sub: Recipient<ReportBillingClaimMessage>,
+    pub node_statistics_sub: Recipient<GetNodeStatisticsMessage>,
+    pub health_sub: Recipient<GetHealthMessage>,
+    pub configuration_sub: Recipient<GetConfigurationMessage>,
+    pub change_configuration_sub: Recipient<ChangeConfigurationMessage>,
 }
 
 impl Debug for AccountantSubs {
@@ -64,6 +346,25 @@ pub struct ReportRoutingServiceConsumedMessage {
     pub payload_size: usize,
     pub service_rate: u64,
     pub byte_rate: u64,
+    // The consuming profile of the browser connection the service was consumed for, if it has one
+    pub profile_opt: Option<String>,
+}
+
+// Sent by the Neighborhood when an exit Node, over the return route of one of our streams, claims
+// that our consuming wallet owes it claimed_gwub. The claim covers everything the Node earns, so it's checked against what we owe
+// all of creditor_wallets together. When shared_wallet is set, other Nodes paying with our consuming
+// wallet have run up part of the claim, so we can't hold the creditor to our own records.
+#[derive(Clone, PartialEq, Debug, Message)]
+pub struct ReportBillingClaimMessage {
+    pub creditor_wallets: Vec<Wallet>,
+    pub claimed_gwub: u64,
+    pub shared_wallet: bool,
+}
+
+// Sent as soon as a new block is mined, when the blockchain service can tell us
+#[derive(Clone, PartialEq, Debug, Message)]
+pub struct NewBlockMessage {
+    pub block_number: u64,
 }
 
 #[derive(Clone, PartialEq, Debug, Message)]
@@ -72,6 +373,7 @@ pub struct ReportExitServiceConsumedMessage {
     pub payload_size: usize,
     pub service_rate: u64,
     pub byte_rate: u64,
+    pub profile_opt: Option<String>,
 }
 
 #[derive(Clone, PartialEq, Debug, Message)]
@@ -84,6 +386,61 @@ pub struct GetFinancialStatisticsMessage {
 pub struct FinancialStatisticsMessage {
     pub pending_credit: i64,
     pub pending_debt: i64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<ProfileFinancialStatistics>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payments_deferred: Option<PaymentDeferral>,
+}
+
+// Why the last payable scan's payments were held back, to be tried again on the next one
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PaymentDeferral {
+    FeeCap,
+    FeeBudget,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileFinancialStatistics {
+    pub name: String,
+    pub consumed: u64,
+    pub budget: Option<u64>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FinancialsFormat {
+    Csv,
+    Json,
+}
+
+impl FromStr for FinancialsFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_lowercase().as_str() {
+            "csv" => Ok(FinancialsFormat::Csv),
+            "json" => Ok(FinancialsFormat::Json),
+            _ => Err(format!(
+                "Financials can be dumped as csv or json, not '{}'",
+                format
+            )),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Message)]
+pub struct SetPaymentParameterMessage {
+    pub client_id: u64,
+    pub parameter: PaymentParameter,
+    pub value: u64,
+}
+
+#[derive(Clone, PartialEq, Debug, Message)]
+pub struct DumpFinancialsMessage {
+    pub client_id: u64,
+    pub format: FinancialsFormat,
 }
 
 #[cfg(test)]
@@ -111,9 +468,267 @@ mod tests {
             report_exit_service_consumed: recipient!(recorder, ReportExitServiceConsumedMessage),
             report_new_payments: recipient!(recorder, ReceivedPayments),
             report_sent_payments: recipient!(recorder, SentPayments),
+            report_resolved_payment: recipient!(recorder, ResolvedPayment),
+            report_new_block: recipient!(recorder, NewBlockMessage),
             get_financial_statistics_sub: recipient!(recorder, GetFinancialStatisticsMessage),
+            dump_financials_sub: recipient!(recorder, DumpFinancialsMessage),
+            set_payment_parameter_sub: recipient!(recorder, SetPaymentParameterMessage),
+            report_billing_claim_sub: recipient!(recorder, ReportBillingClaimMessage),
+            node_statistics_sub: recipient!(recorder, GetNodeStatisticsMessage),
+            health_sub: recipient!(recorder, GetHealthMessage),
+            configuration_sub: recipient!(recorder, GetConfigurationMessage),
+            change_configuration_sub: recipient!(recorder, ChangeConfigurationMessage),
         };
 
         assert_eq!(format!("{:?}", subject), "AccountantSubs");
     }
+
+    #[test]
+    fn consuming_profile_from_str_without_budget() {
+        let result = ConsumingProfile::from_str("work:10000-19999").unwrap();
+
+        assert_eq!(
+            result,
+            ConsumingProfile {
+                name: "work".to_string(),
+                selector: ProfileSelector::OriginatingPorts(10000..=19999),
+                budget_opt: None,
+                consuming_wallet_opt: None,
+            }
+        );
+    }
+
+    #[test]
+    fn consuming_profile_from_str_with_budget() {
+        let result = ConsumingProfile::from_str("personal:20000-20000:5000000").unwrap();
+
+        assert_eq!(
+            result,
+            ConsumingProfile {
+                name: "personal".to_string(),
+                selector: ProfileSelector::OriginatingPorts(20000..=20000),
+                budget_opt: Some(5_000_000),
+                consuming_wallet_opt: None,
+            }
+        );
+    }
+
+    #[test]
+    fn consuming_profile_from_str_with_pac_port_and_wallet() {
+        let key = "cc46befe8d169b89db447bd725fc2368b12542113555302598430cb5d5c74ea9";
+
+        let result = ConsumingProfile::from_str(&format!("work:pac=8090::{}", key)).unwrap();
+
+        let expected_wallet = Wallet::from(
+            Bip32ECKeyPair::from_raw_secret(&key.from_hex::<Vec<u8>>().unwrap()).unwrap(),
+        );
+        assert_eq!(result.name, "work".to_string());
+        assert_eq!(result.selector, ProfileSelector::Pac(8090));
+        assert_eq!(result.budget_opt, None);
+        assert_eq!(
+            result.consuming_wallet_opt.map(|wallet| wallet.address()),
+            Some(expected_wallet.address())
+        );
+    }
+
+    #[test]
+    fn consuming_profile_from_str_complains_about_bad_specs() {
+        vec![
+            "",
+            "work",
+            ":1-2",
+            "work:1",
+            "work:2-1",
+            "work:1-2-3",
+            "work:1-70000",
+            "work:1-2:lots",
+            "work:1-2:",
+            "work:pac=",
+            "work:pac=65535",
+            "work:1-2:3:4",
+            "work:1-2:3:not hex at all",
+            "work:1-2:3:4:5",
+        ]
+        .into_iter()
+        .for_each(|spec| {
+            assert_eq!(
+                ConsumingProfile::from_str(spec),
+                Err(format!(
+                    "Consuming profile should look like NAME:LOW-HIGH[:BUDGET[:PRIVATE-KEY]] or NAME:pac=PORT[:BUDGET[:PRIVATE-KEY]], not '{}'",
+                    spec
+                )),
+                "{}",
+                spec
+            )
+        });
+    }
+
+    #[test]
+    fn consuming_profiles_select_connections_by_originating_port_or_pac_ports() {
+        let by_ports = ConsumingProfile::from_str("work:10000-19999").unwrap();
+        let by_pac = ConsumingProfile::from_str("personal:pac=8090").unwrap();
+
+        assert!(by_ports.selects(80, 10000));
+        assert!(by_ports.selects(443, 19999));
+        assert!(!by_ports.selects(80, 20000));
+        assert!(by_pac.selects(8090, 20000));
+        assert!(by_pac.selects(8091, 10000));
+        assert!(!by_pac.selects(80, 10000));
+        assert!(!by_pac.selects(8092, 10000));
+    }
+
+    #[test]
+    fn consuming_profile_budget_is_exhausted_once_it_is_all_consumed() {
+        let budgeted = ConsumingProfile::from_str("work:10000-19999:1000").unwrap();
+        let unbudgeted = ConsumingProfile::from_str("personal:20000-29999").unwrap();
+
+        assert!(!budgeted.budget_exhausted(999));
+        assert!(budgeted.budget_exhausted(1000));
+        assert!(!unbudgeted.budget_exhausted(u64::max_value()));
+    }
+
+    #[test]
+    fn financial_statistics_message_omits_profiles_when_there_are_none() {
+        let subject = FinancialStatisticsMessage {
+            pending_credit: 1,
+            pending_debt: 2,
+            profiles: vec![],
+            payments_deferred: None,
+        };
+
+        let result = serde_json::to_string(&subject).unwrap();
+
+        assert_eq!(result, r#"{"pendingCredit":1,"pendingDebt":2}"#);
+        assert_eq!(
+            serde_json::from_str::<FinancialStatisticsMessage>(&result).unwrap(),
+            subject
+        );
+    }
+
+    #[test]
+    fn financial_statistics_message_includes_profiles_when_there_are_some() {
+        let subject = FinancialStatisticsMessage {
+            pending_credit: 1,
+            pending_debt: 2,
+            profiles: vec![ProfileFinancialStatistics {
+                name: "work".to_string(),
+                consumed: 3,
+                budget: Some(4),
+            }],
+            payments_deferred: None,
+        };
+
+        let result = serde_json::to_string(&subject).unwrap();
+
+        assert_eq!(
+            result,
+            r#"{"pendingCredit":1,"pendingDebt":2,"profiles":[{"name":"work","consumed":3,"budget":4}]}"#
+        );
+    }
+
+    #[test]
+    fn financial_statistics_message_tells_why_payments_are_deferred() {
+        let subject = FinancialStatisticsMessage {
+            pending_credit: 1,
+            pending_debt: 2,
+            profiles: vec![],
+            payments_deferred: Some(PaymentDeferral::FeeBudget),
+        };
+
+        let result = serde_json::to_string(&subject).unwrap();
+
+        assert_eq!(
+            result,
+            r#"{"pendingCredit":1,"pendingDebt":2,"paymentsDeferred":"feeBudget"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<FinancialStatisticsMessage>(&result).unwrap(),
+            subject
+        );
+    }
+
+    #[test]
+    fn financials_format_from_str() {
+        assert_eq!(FinancialsFormat::from_str("csv"), Ok(FinancialsFormat::Csv));
+        assert_eq!(
+            FinancialsFormat::from_str("JSON"),
+            Ok(FinancialsFormat::Json)
+        );
+        assert_eq!(
+            FinancialsFormat::from_str("xml"),
+            Err("Financials can be dumped as csv or json, not 'xml'".to_string())
+        );
+    }
+
+    #[test]
+    fn payment_parameters_round_trip_through_accountant_config() {
+        let mut subject = AccountantConfig::default();
+
+        PAYMENT_PARAMETERS
+            .iter()
+            .enumerate()
+            .for_each(|(index, parameter)| {
+                let value = parameter.range().start() + 1 + index as u64;
+                subject.set_payment_parameter(*parameter, value);
+                assert_eq!(
+                    subject.payment_parameter(*parameter),
+                    value,
+                    "{:?}",
+                    parameter
+                );
+            });
+        assert_eq!(subject.payable_scan_interval, Duration::from_secs(61));
+        assert_eq!(subject.payment_curves.unban_when_balance_below_gwub, 8);
+    }
+
+    #[test]
+    fn payment_parameter_defaults_are_within_range() {
+        PAYMENT_PARAMETERS.iter().for_each(|parameter| {
+            assert_eq!(
+                parameter.validate(parameter.default_value()),
+                Ok(()),
+                "{:?}",
+                parameter
+            )
+        });
+        assert_eq!(
+            PaymentParameter::PayableScanInterval.default_value(),
+            DEFAULT_PAYABLE_SCAN_INTERVAL
+        );
+    }
+
+    #[test]
+    fn payment_parameter_validate_complains_about_values_out_of_range() {
+        assert_eq!(
+            PaymentParameter::PayableScanInterval.validate(59),
+            Err("payable-scan-interval must be between 60 and 604800, not 59".to_string())
+        );
+        assert_eq!(
+            PaymentParameter::BalanceToDecreaseFrom.validate(0),
+            Err("balance-to-decrease-from must be between 1 and 1000000000000, not 0".to_string())
+        );
+        assert_eq!(PaymentParameter::PermanentDebtAllowed.validate(0), Ok(()));
+    }
+
+    #[test]
+    fn payment_parameter_names_and_config_keys() {
+        PAYMENT_PARAMETERS.iter().for_each(|parameter| {
+            assert_eq!(PaymentParameter::from_str(parameter.name()), Ok(*parameter))
+        });
+        assert_eq!(
+            PaymentParameter::PaymentReceivedScanInterval.config_key(),
+            "payment_received_scan_interval".to_string()
+        );
+        assert_eq!(
+            PaymentParameter::from_str("booga"),
+            Err("Unknown payment parameter: 'booga'".to_string())
+        );
+    }
+
+    #[test]
+    fn payment_parameter_serializes_in_camel_case() {
+        let result = serde_json::to_string(&PaymentParameter::UnbanWhenBalanceBelow).unwrap();
+
+        assert_eq!(result, r#""unbanWhenBalanceBelow""#);
+    }
 }
@@ -2,18 +2,138 @@
 
 use crate::accountant::payable_dao::{PayableAccount, Payment};
 use crate::blockchain::blockchain_bridge::RetrieveTransactions;
-use crate::blockchain::blockchain_interface::BlockchainResult;
+use crate::blockchain::blockchain_interface::{
+    to_wei, BlockchainResult, ChainRecord, TransactionFee,
+};
 use crate::sub_lib::peer_actors::BindMessage;
+use crate::sub_lib::ui_gateway::GetHealthMessage;
+use crate::sub_lib::wallet::Wallet;
 use actix::Message;
 use actix::Recipient;
 use std::fmt;
 use std::fmt::{Debug, Formatter};
+use std::str::FromStr;
+use std::time::Duration;
+use web3::types::U256;
 
 #[derive(Clone, PartialEq, Debug, Default)]
 pub struct BlockchainBridgeConfig {
-    pub blockchain_service_url: Option<String>,
+    pub blockchain_service_urls: Vec<String>,
+    // For eth_subscribe; payments are polled for if there isn't one
+    pub blockchain_service_ws_url: Option<String>,
     pub chain_id: u8,
     pub gas_price: Option<u64>,
+    pub fee_strategy: FeeStrategy,
+    pub gas_price_cap: Option<u64>,
+    pub fee_budget: Option<FeeBudget>,
+}
+
+// How hard to bid for a place in the next blocks on chains with EIP-1559 fees
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum FeeStrategy {
+    Slow,
+    #[default]
+    Normal,
+    Fast,
+}
+
+impl FromStr for FeeStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "slow" => Ok(FeeStrategy::Slow),
+            "normal" => Ok(FeeStrategy::Normal),
+            "fast" => Ok(FeeStrategy::Fast),
+            _ => Err(format!("Invalid fee strategy: {}", s)),
+        }
+    }
+}
+
+impl FeeStrategy {
+    // The base fee can rise by an eighth every block, so the maximum fee leaves room for it to
+    // keep rising for a while before the transaction is mined.
+    pub fn fee_for(self, base_fee_per_gas: U256, chain: &ChainRecord) -> TransactionFee {
+        let (base_fee_eighths, priority_fee_gwei) = match self {
+            FeeStrategy::Slow => (9, chain.priority_fees_gwei[0]),
+            FeeStrategy::Normal => (16, chain.priority_fees_gwei[1]),
+            FeeStrategy::Fast => (24, chain.priority_fees_gwei[2]),
+        };
+        let max_priority_fee_per_gas = to_wei(priority_fee_gwei);
+        TransactionFee::Eip1559 {
+            max_fee_per_gas: base_fee_per_gas * base_fee_eighths / 8 + max_priority_fee_per_gas,
+            max_priority_fee_per_gas,
+        }
+    }
+}
+
+// The most to spend on transaction fees in each period, in Gwei
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FeeBudget {
+    pub gwei: u64,
+    pub period: FeeBudgetPeriod,
+}
+
+impl FeeBudget {
+    // Spending recorded in a period that's over doesn't count against the current one. Periods
+    // follow one another from the start of the first, so they don't drift with when we pay.
+    pub fn spending_at(self, spending: Option<FeeSpending>, now: u64) -> FeeSpending {
+        let length = self.period.length().as_secs();
+        match spending {
+            Some(spending) if now < spending.period_start + length => spending,
+            Some(spending) => FeeSpending {
+                period_start: now - (now - spending.period_start) % length,
+                gwei: 0,
+            },
+            None => FeeSpending {
+                period_start: now,
+                gwei: 0,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum FeeBudgetPeriod {
+    Weekly,
+    #[default]
+    Monthly,
+}
+
+impl FromStr for FeeBudgetPeriod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "weekly" => Ok(FeeBudgetPeriod::Weekly),
+            "monthly" => Ok(FeeBudgetPeriod::Monthly),
+            _ => Err(format!("Invalid fee budget period: {}", s)),
+        }
+    }
+}
+
+impl FeeBudgetPeriod {
+    // A month is taken to be 30 days
+    pub fn length(self) -> Duration {
+        match self {
+            FeeBudgetPeriod::Weekly => Duration::from_secs(7 * 24 * 60 * 60),
+            FeeBudgetPeriod::Monthly => Duration::from_secs(30 * 24 * 60 * 60),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FeeBudgetPeriod::Weekly => "week",
+            FeeBudgetPeriod::Monthly => "month",
+        }
+    }
+}
+
+// Fees spent since period_start, in seconds since the epoch
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FeeSpending {
+    pub period_start: u64,
+    pub gwei: u64,
 }
 
 #[derive(Clone)]
@@ -23,6 +143,9 @@ pub struct BlockchainBridgeSubs {
     pub retrieve_transactions: Recipient<RetrieveTransactions>,
     pub set_consuming_wallet_password_sub: Recipient<SetWalletPasswordMsg>,
     pub set_gas_price_sub: Recipient<SetGasPriceMsg>,
+    pub get_blockchain_service_url_sub: Recipient<GetBlockchainServiceUrlMsg>,
+    pub get_wallet_balances_sub: Recipient<GetWalletBalancesMsg>,
+    pub health_sub: Recipient<GetHealthMessage>,
 }
 
 impl Debug for BlockchainBridgeSubs {
@@ -32,8 +155,11 @@ impl Debug for BlockchainBridgeSubs {
 }
 
 #[derive(Clone, PartialEq, Debug)]
+// profile_wallet_opt is the consuming profile wallet to pay from, instead of the Node's own
+// consuming wallet
 pub struct ReportAccountsPayable {
     pub accounts: Vec<PayableAccount>,
+    pub profile_wallet_opt: Option<Wallet>,
 }
 
 #[derive(Clone, PartialEq, Debug, Message)]
@@ -48,6 +174,16 @@ pub struct SetGasPriceMsg {
     pub gas_price: String,
 }
 
+#[derive(Clone, PartialEq, Debug, Message)]
+pub struct GetBlockchainServiceUrlMsg {
+    pub client_id: u64,
+}
+
+#[derive(Clone, PartialEq, Debug, Message)]
+pub struct GetWalletBalancesMsg {
+    pub client_id: u64,
+}
+
 impl Message for ReportAccountsPayable {
     type Result = Result<Vec<BlockchainResult<Payment>>, String>;
 }
@@ -55,9 +191,133 @@ impl Message for ReportAccountsPayable {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::blockchain::blockchain_interface::{chain_id_from_name, chain_record};
     use crate::test_utils::recorder::Recorder;
     use actix::Actor;
 
+    #[test]
+    fn fee_strategy_parses_case_insensitively() {
+        assert_eq!(FeeStrategy::from_str("slow"), Ok(FeeStrategy::Slow));
+        assert_eq!(FeeStrategy::from_str("Normal"), Ok(FeeStrategy::Normal));
+        assert_eq!(FeeStrategy::from_str("FAST"), Ok(FeeStrategy::Fast));
+        assert_eq!(
+            FeeStrategy::from_str("ludicrous"),
+            Err("Invalid fee strategy: ludicrous".to_string())
+        );
+    }
+
+    #[test]
+    fn fee_strategies_bid_progressively_more() {
+        let base_fee = to_wei(40);
+        let mainnet = chain_record(chain_id_from_name("mainnet"));
+
+        assert_eq!(
+            FeeStrategy::Slow.fee_for(base_fee, mainnet),
+            TransactionFee::Eip1559 {
+                max_fee_per_gas: to_wei(46),
+                max_priority_fee_per_gas: to_wei(1),
+            }
+        );
+        assert_eq!(
+            FeeStrategy::Normal.fee_for(base_fee, mainnet),
+            TransactionFee::Eip1559 {
+                max_fee_per_gas: to_wei(82),
+                max_priority_fee_per_gas: to_wei(2),
+            }
+        );
+        assert_eq!(
+            FeeStrategy::Fast.fee_for(base_fee, mainnet),
+            TransactionFee::Eip1559 {
+                max_fee_per_gas: to_wei(123),
+                max_priority_fee_per_gas: to_wei(3),
+            }
+        );
+    }
+
+    #[test]
+    fn fee_strategies_tip_what_the_chain_expects() {
+        let xdai = chain_record(chain_id_from_name("xdai"));
+
+        assert_eq!(
+            FeeStrategy::Normal.fee_for(to_wei(8), xdai),
+            TransactionFee::Eip1559 {
+                max_fee_per_gas: to_wei(17),
+                max_priority_fee_per_gas: to_wei(1),
+            }
+        );
+        assert_eq!(
+            FeeStrategy::Fast.fee_for(to_wei(8), xdai),
+            TransactionFee::Eip1559 {
+                max_fee_per_gas: to_wei(26),
+                max_priority_fee_per_gas: to_wei(2),
+            }
+        );
+    }
+
+    #[test]
+    fn fee_budget_period_parses_case_insensitively() {
+        assert_eq!(
+            FeeBudgetPeriod::from_str("weekly"),
+            Ok(FeeBudgetPeriod::Weekly)
+        );
+        assert_eq!(
+            FeeBudgetPeriod::from_str("Monthly"),
+            Ok(FeeBudgetPeriod::Monthly)
+        );
+        assert_eq!(
+            FeeBudgetPeriod::from_str("daily"),
+            Err("Invalid fee budget period: daily".to_string())
+        );
+    }
+
+    #[test]
+    fn fee_budget_starts_a_period_with_the_first_spending() {
+        let subject = FeeBudget {
+            gwei: 1_000,
+            period: FeeBudgetPeriod::Weekly,
+        };
+
+        assert_eq!(
+            subject.spending_at(None, 1_000_000),
+            FeeSpending {
+                period_start: 1_000_000,
+                gwei: 0
+            }
+        );
+    }
+
+    #[test]
+    fn fee_budget_keeps_spending_until_its_period_is_over() {
+        let subject = FeeBudget {
+            gwei: 1_000,
+            period: FeeBudgetPeriod::Weekly,
+        };
+        let spending = FeeSpending {
+            period_start: 1_000_000,
+            gwei: 400,
+        };
+        let week = FeeBudgetPeriod::Weekly.length().as_secs();
+
+        assert_eq!(
+            subject.spending_at(Some(spending), 1_000_000 + week - 1),
+            spending
+        );
+        assert_eq!(
+            subject.spending_at(Some(spending), 1_000_000 + week),
+            FeeSpending {
+                period_start: 1_000_000 + week,
+                gwei: 0
+            }
+        );
+        assert_eq!(
+            subject.spending_at(Some(spending), 1_000_000 + 3 * week + 5),
+            FeeSpending {
+                period_start: 1_000_000 + 3 * week,
+                gwei: 0
+            }
+        );
+    }
+
     #[test]
     fn blockchain_bridge_subs_debug() {
         let recorder = Recorder::new().start();
@@ -68,6 +328,9 @@ mod tests {
             retrieve_transactions: recipient!(recorder, RetrieveTransactions),
             set_consuming_wallet_password_sub: recipient!(recorder, SetWalletPasswordMsg),
             set_gas_price_sub: recipient!(recorder, SetGasPriceMsg),
+            get_blockchain_service_url_sub: recipient!(recorder, GetBlockchainServiceUrlMsg),
+            get_wallet_balances_sub: recipient!(recorder, GetWalletBalancesMsg),
+            health_sub: recipient!(recorder, GetHealthMessage),
         };
 
         assert_eq!(format!("{:?}", subject), "BlockchainBridgeSubs");
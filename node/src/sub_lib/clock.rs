@@ -0,0 +1,51 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use std::time::{Instant, SystemTime};
+
+/// Where an actor gets the time, so that tests can decide what time it is. `now` is the time of
+/// day; `instant` is the monotonic time used for intervals and timeouts.
+pub trait Clock: Send {
+    fn now(&self) -> SystemTime;
+    fn instant(&self) -> Instant;
+}
+
+pub struct ClockReal {}
+
+impl Clock for ClockReal {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_real_tells_the_system_time() {
+        let subject = ClockReal {};
+        let before = SystemTime::now();
+
+        let result = subject.now();
+
+        let after = SystemTime::now();
+        assert!(before <= result, "{:?} is before {:?}", result, before);
+        assert!(result <= after, "{:?} is after {:?}", result, after);
+    }
+
+    #[test]
+    fn clock_real_tells_the_monotonic_time() {
+        let subject = ClockReal {};
+        let before = Instant::now();
+
+        let result = subject.instant();
+
+        let after = Instant::now();
+        assert!(before <= result, "{:?} is before {:?}", result, before);
+        assert!(result <= after, "{:?} is after {:?}", result, after);
+    }
+}
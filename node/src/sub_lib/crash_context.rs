@@ -0,0 +1,85 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use std::cell::Cell;
+
+// What this thread is doing, for the panic hook to report if it panics: each actor runs on a
+// thread of its own and handles one message at a time, so a thread is doing at most one thing.
+thread_local! {
+    static HANDLING: Cell<Option<Handling>> = Cell::new(None);
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Handling {
+    pub actor: &'static str,
+    pub message: &'static str,
+}
+
+// Until it's dropped, this thread is handling the message in the actor
+pub struct HandlingGuard {
+    previous: Option<Handling>,
+}
+
+impl Drop for HandlingGuard {
+    fn drop(&mut self) {
+        let previous = self.previous;
+        HANDLING.with(|handling| handling.set(previous));
+    }
+}
+
+// At the top of a Handler's handle(): let _handling = handling("Actor", "Message");
+pub fn handling(actor: &'static str, message: &'static str) -> HandlingGuard {
+    let previous = HANDLING.with(|handling| handling.replace(Some(Handling { actor, message })));
+    HandlingGuard { previous }
+}
+
+pub fn current_handling() -> Option<Handling> {
+    HANDLING.with(|handling| handling.get())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_thread_is_handling_a_message_until_the_guard_is_dropped() {
+        assert_eq!(current_handling(), None);
+        {
+            let _handling = handling("Neighborhood", "RouteQueryMessage");
+
+            assert_eq!(
+                current_handling(),
+                Some(Handling {
+                    actor: "Neighborhood",
+                    message: "RouteQueryMessage"
+                })
+            );
+        }
+        assert_eq!(current_handling(), None);
+    }
+
+    #[test]
+    fn a_nested_handling_restores_the_outer_one() {
+        let _outer = handling("Accountant", "ReportRoutingServiceProvidedMessage");
+        {
+            let _inner = handling("Accountant", "GetNodeStatisticsMessage");
+
+            assert_eq!(
+                current_handling().unwrap().message,
+                "GetNodeStatisticsMessage"
+            );
+        }
+        assert_eq!(
+            current_handling().unwrap().message,
+            "ReportRoutingServiceProvidedMessage"
+        );
+    }
+
+    #[test]
+    fn other_threads_arent_affected() {
+        let _handling = handling("Hopper", "InboundClientData");
+
+        let other = std::thread::spawn(current_handling).join().unwrap();
+
+        assert_eq!(other, None);
+    }
+}
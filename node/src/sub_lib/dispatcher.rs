@@ -76,7 +76,7 @@ impl<'a> Visitor<'a> for ComponentVisitor {
     }
 }
 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub enum Endpoint {
     Key(PublicKey),
     Socket(SocketAddr),
@@ -156,11 +156,20 @@ pub struct StreamShutdownMsg {
     pub report_to_counterpart: bool,
 }
 
+// Sent by the Neighborhood when it hears from a neighbor, so that the Dispatcher can hold the
+// neighbor to its rate limits by its key rather than by whatever address it's using
+#[derive(PartialEq, Clone, Message, Debug)]
+pub struct NeighborKeyMessage {
+    pub peer_addr: SocketAddr,
+    pub public_key: PublicKey,
+}
+
 pub struct DispatcherSubs {
     pub ibcd_sub: Recipient<InboundClientData>,
     pub bind: Recipient<BindMessage>,
     pub from_dispatcher_client: Recipient<TransmitDataMsg>,
     pub stream_shutdown_sub: Recipient<StreamShutdownMsg>,
+    pub neighbor_key_sub: Recipient<NeighborKeyMessage>,
 }
 
 impl Debug for DispatcherSubs {
@@ -176,6 +185,7 @@ impl Clone for DispatcherSubs {
             bind: self.bind.clone(),
             from_dispatcher_client: self.from_dispatcher_client.clone(),
             stream_shutdown_sub: self.stream_shutdown_sub.clone(),
+            neighbor_key_sub: self.neighbor_key_sub.clone(),
         }
     }
 }
@@ -197,6 +207,7 @@ mod tests {
             bind: recipient!(addr, BindMessage),
             from_dispatcher_client: recipient!(addr, TransmitDataMsg),
             stream_shutdown_sub: recipient!(addr, StreamShutdownMsg),
+            neighbor_key_sub: recipient!(addr, NeighborKeyMessage),
         };
 
         assert_eq!(format!("{:?}", subject), "DispatcherSubs");
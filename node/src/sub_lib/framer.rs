@@ -1,4 +1,5 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use std::fmt;
 
 #[derive(Debug, PartialEq)]
 pub struct FramedChunk {
@@ -6,7 +7,63 @@ pub struct FramedChunk {
     pub last_chunk: bool,
 }
 
+// Data that breaks one of its protocol's limits; each carries the limit that was broken
+#[derive(Clone, Debug, PartialEq)]
+pub enum FramingViolation {
+    RequestLineTooLong(usize),
+    TooManyHeaders(usize),
+    HeadersTooLarge(usize),
+    RecordTooLarge(usize),
+}
+
+impl fmt::Display for FramingViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            FramingViolation::RequestLineTooLong(limit) => {
+                write!(f, "request line longer than {} bytes", limit)
+            }
+            FramingViolation::TooManyHeaders(limit) => write!(f, "more than {} headers", limit),
+            FramingViolation::HeadersTooLarge(limit) => {
+                write!(f, "headers longer than {} bytes", limit)
+            }
+            FramingViolation::RecordTooLarge(limit) => {
+                write!(f, "TLS record longer than {} bytes", limit)
+            }
+        }
+    }
+}
+
 pub trait Framer: Send {
     fn add_data(&mut self, data: &[u8]);
     fn take_frame(&mut self) -> Option<FramedChunk>;
+    // Once a framer has seen a violation, it has thrown away what it was holding and frames
+    // nothing more from the stream
+    fn violation(&self) -> Option<FramingViolation> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn framing_violations_say_which_limit_was_broken() {
+        assert_eq!(
+            FramingViolation::RequestLineTooLong(8192).to_string(),
+            "request line longer than 8192 bytes"
+        );
+        assert_eq!(
+            FramingViolation::TooManyHeaders(100).to_string(),
+            "more than 100 headers"
+        );
+        assert_eq!(
+            FramingViolation::HeadersTooLarge(65536).to_string(),
+            "headers longer than 65536 bytes"
+        );
+        assert_eq!(
+            FramingViolation::RecordTooLarge(18432).to_string(),
+            "TLS record longer than 18432 bytes"
+        );
+    }
 }
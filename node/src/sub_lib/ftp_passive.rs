@@ -0,0 +1,275 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use std::net::Ipv4Addr;
+
+// Longer than any passive reply; a partial line past this is never going to be one
+const MAX_HELD_LINE_LENGTH: usize = 1024;
+
+// An FTP server's answer to PASV or EPSV: where the client should open the data connection
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PassiveReply {
+    // 227 Entering Passive Mode (h1,h2,h3,h4,p1,p2)
+    Pasv(Ipv4Addr, u16),
+    // 229 Entering Extended Passive Mode (|||port|); the host is the control connection's
+    Epsv(u16),
+}
+
+impl PassiveReply {
+    pub fn port(&self) -> u16 {
+        match self {
+            PassiveReply::Pasv(_, port) => *port,
+            PassiveReply::Epsv(port) => *port,
+        }
+    }
+
+    pub fn parse(line: &[u8]) -> Option<PassiveReply> {
+        let line = std::str::from_utf8(line).ok()?.trim_end();
+        match line.get(..4) {
+            Some("227 ") => Self::parse_pasv(&line[4..]),
+            Some("229 ") => Self::parse_epsv(&line[4..]),
+            _ => None,
+        }
+    }
+
+    pub fn to_line(&self) -> Vec<u8> {
+        match self {
+            PassiveReply::Pasv(ip, port) => {
+                let octets = ip.octets();
+                format!(
+                    "227 Entering Passive Mode ({},{},{},{},{},{})\r\n",
+                    octets[0],
+                    octets[1],
+                    octets[2],
+                    octets[3],
+                    port >> 8,
+                    port & 0xFF
+                )
+            }
+            PassiveReply::Epsv(port) => {
+                format!("229 Entering Extended Passive Mode (|||{}|)\r\n", port)
+            }
+        }
+        .into_bytes()
+    }
+
+    // Servers don't agree on the text around the numbers, or even on the parentheses, so this
+    // takes the first run of digits and commas
+    fn parse_pasv(text: &str) -> Option<PassiveReply> {
+        let start = text.find(|c: char| c.is_ascii_digit())?;
+        let numbers = text[start..]
+            .split(|c: char| !c.is_ascii_digit() && c != ',')
+            .next()?
+            .split(',')
+            .map(|number| number.parse::<u8>().ok())
+            .collect::<Option<Vec<u8>>>()?;
+        if numbers.len() != 6 {
+            return None;
+        }
+        Some(PassiveReply::Pasv(
+            Ipv4Addr::new(numbers[0], numbers[1], numbers[2], numbers[3]),
+            (u16::from(numbers[4]) << 8) | u16::from(numbers[5]),
+        ))
+    }
+
+    // RFC 2428 lets the server pick the delimiter, which is whatever follows the parenthesis
+    fn parse_epsv(text: &str) -> Option<PassiveReply> {
+        let open = text.find('(')?;
+        let close = open + text[open..].find(')')?;
+        let inner = &text[(open + 1)..close];
+        let delimiter = inner.chars().next()?;
+        let fields = inner.split(delimiter).collect::<Vec<&str>>();
+        if fields.len() != 5 {
+            return None;
+        }
+        match fields[3].parse::<u16>() {
+            Ok(port) if port > 0 => Some(PassiveReply::Epsv(port)),
+            _ => None,
+        }
+    }
+}
+
+// Rewrites the passive replies on one control connection. A chunk of data can end partway
+// through a line, so that part is held back until the rest of the line arrives.
+#[derive(Default)]
+pub struct PassiveReplyRewriter {
+    partial_line: Vec<u8>,
+}
+
+impl PassiveReplyRewriter {
+    pub fn new() -> PassiveReplyRewriter {
+        PassiveReplyRewriter::default()
+    }
+
+    // Returns every line the data finishes, with its passive replies rewritten
+    pub fn rewrite<F>(&mut self, data: &[u8], rewrite: F) -> Vec<u8>
+    where
+        F: FnMut(PassiveReply) -> Option<PassiveReply>,
+    {
+        let mut lines = std::mem::replace(&mut self.partial_line, vec![]);
+        lines.extend_from_slice(data);
+        let mut end = lines
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+            .map_or(0, |position| position + 1);
+        if lines.len() - end > MAX_HELD_LINE_LENGTH {
+            end = lines.len();
+        }
+        self.partial_line = lines.split_off(end);
+        rewrite_passive_replies(&lines, rewrite)
+    }
+
+    // Whatever is held back when the connection closes goes along as it is
+    pub fn flush(&mut self) -> Vec<u8> {
+        std::mem::replace(&mut self.partial_line, vec![])
+    }
+}
+
+// Replaces every passive reply in some control-connection data with whatever the rewrite makes
+// of it, or with a refusal if the rewrite makes nothing of it. An unfinished last line is passed
+// along untouched.
+fn rewrite_passive_replies<F>(data: &[u8], mut rewrite: F) -> Vec<u8>
+where
+    F: FnMut(PassiveReply) -> Option<PassiveReply>,
+{
+    let mut result = Vec::with_capacity(data.len());
+    let mut rest = data;
+    while !rest.is_empty() {
+        let end = rest
+            .iter()
+            .position(|byte| *byte == b'\n')
+            .map_or(rest.len(), |position| position + 1);
+        let line = &rest[..end];
+        match PassiveReply::parse(line) {
+            Some(reply) if line.ends_with(b"\n") => match rewrite(reply) {
+                Some(new_reply) => result.extend(new_reply.to_line()),
+                None => result.extend_from_slice(b"425 Can't open data connection\r\n"),
+            },
+            _ => result.extend_from_slice(line),
+        }
+        rest = &rest[end..];
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_pasv_replies_with_and_without_parentheses() {
+        assert_eq!(
+            PassiveReply::parse(b"227 Entering Passive Mode (192,168,1,20,195,80)\r\n"),
+            Some(PassiveReply::Pasv(Ipv4Addr::new(192, 168, 1, 20), 50000))
+        );
+        assert_eq!(
+            PassiveReply::parse(b"227 Entering passive mode 10,0,0,1,4,1."),
+            Some(PassiveReply::Pasv(Ipv4Addr::new(10, 0, 0, 1), 1025))
+        );
+    }
+
+    #[test]
+    fn parses_epsv_replies_whatever_the_delimiter() {
+        assert_eq!(
+            PassiveReply::parse(b"229 Entering Extended Passive Mode (|||6446|)\r\n"),
+            Some(PassiveReply::Epsv(6446))
+        );
+        assert_eq!(
+            PassiveReply::parse(b"229 EPSV ok (!!!50000!)\r\n"),
+            Some(PassiveReply::Epsv(50000))
+        );
+    }
+
+    #[test]
+    fn ignores_everything_else() {
+        [
+            &b"226 Transfer complete\r\n"[..],
+            b"227 Entering Passive Mode (192,168,1,20,195)\r\n",
+            b"227 Entering Passive Mode (192,168,1,300,195,80)\r\n",
+            b"229 Entering Extended Passive Mode (||6446|)\r\n",
+            b"229 Entering Extended Passive Mode (|||0|)\r\n",
+            b"150-227 Entering Passive Mode (1,2,3,4,5,6)\r\n",
+            b"\xFF\xFE",
+        ]
+        .iter()
+        .for_each(|line| assert_eq!(PassiveReply::parse(line), None, "{:?}", line));
+    }
+
+    #[test]
+    fn formats_replies_that_parse_back_the_same() {
+        let pasv = PassiveReply::Pasv(Ipv4Addr::new(127, 0, 0, 1), 50000);
+        let epsv = PassiveReply::Epsv(6446);
+
+        assert_eq!(
+            pasv.to_line(),
+            b"227 Entering Passive Mode (127,0,0,1,195,80)\r\n".to_vec()
+        );
+        assert_eq!(PassiveReply::parse(&pasv.to_line()), Some(pasv));
+        assert_eq!(
+            epsv.to_line(),
+            b"229 Entering Extended Passive Mode (|||6446|)\r\n".to_vec()
+        );
+        assert_eq!(PassiveReply::parse(&epsv.to_line()), Some(epsv));
+    }
+
+    #[test]
+    fn rewriter_holds_back_a_reply_split_across_chunks_until_its_line_ends() {
+        let mut subject = PassiveReplyRewriter::new();
+        let pin =
+            |reply: PassiveReply| Some(PassiveReply::Pasv(Ipv4Addr::new(1, 2, 3, 4), reply.port()));
+
+        let first = subject.rewrite(
+            b"200 Type set to I\r\n227 Entering Passive Mode (10,0,",
+            pin,
+        );
+        let second = subject.rewrite(b"0,1,4,", pin);
+        let third = subject.rewrite(b"1)\r\n226 Transfer", pin);
+        let last = subject.flush();
+
+        assert_eq!(first, b"200 Type set to I\r\n".to_vec());
+        assert_eq!(second, b"".to_vec());
+        assert_eq!(
+            third,
+            b"227 Entering Passive Mode (1,2,3,4,4,1)\r\n".to_vec()
+        );
+        assert_eq!(last, b"226 Transfer".to_vec());
+        assert_eq!(subject.flush(), b"".to_vec());
+    }
+
+    #[test]
+    fn rewriter_passes_along_a_partial_line_too_long_to_be_a_reply() {
+        let mut subject = PassiveReplyRewriter::new();
+        let data = vec![b'x'; MAX_HELD_LINE_LENGTH + 1];
+
+        let result = subject.rewrite(&data, |_| panic!("Not a passive reply"));
+
+        assert_eq!(result, data);
+        assert_eq!(subject.flush(), b"".to_vec());
+    }
+
+    #[test]
+    fn rewrites_only_whole_passive_replies() {
+        let data = b"200 Type set to I\r\n227 Entering Passive Mode (10,0,0,1,4,1)\r\n229 Entering Extended Passive Mode (|||6446|)\r\n227 Entering Passive Mode (10,0,0,1,4,";
+        let mut seen = vec![];
+
+        let result = rewrite_passive_replies(data, |reply| {
+            seen.push(reply);
+            match reply {
+                PassiveReply::Pasv(_, port) => {
+                    Some(PassiveReply::Pasv(Ipv4Addr::new(1, 2, 3, 4), port))
+                }
+                PassiveReply::Epsv(_) => None,
+            }
+        });
+
+        assert_eq!(
+            seen,
+            vec![
+                PassiveReply::Pasv(Ipv4Addr::new(10, 0, 0, 1), 1025),
+                PassiveReply::Epsv(6446)
+            ]
+        );
+        assert_eq!(
+            String::from_utf8(result).unwrap(),
+            "200 Type set to I\r\n227 Entering Passive Mode (1,2,3,4,4,1)\r\n425 Can't open data connection\r\n227 Entering Passive Mode (10,0,0,1,4,"
+        );
+    }
+}
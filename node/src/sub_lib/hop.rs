@@ -51,6 +51,7 @@ impl LiveHop {
 mod tests {
     use super::*;
     use crate::blockchain::blockchain_interface::contract_address;
+    use crate::golden_test;
     use crate::test_utils::{cryptde, make_paying_wallet, DEFAULT_CHAIN_ID};
 
     #[test]
@@ -148,4 +149,19 @@ mod tests {
             relay_hop
         );
     }
+
+    golden_test!(
+        golden_live_hop_without_payer,
+        LiveHop::new(&PublicKey::new(b"key"), None, Component::ProxyClient)
+    );
+
+    golden_test!(
+        golden_live_hop_with_payer,
+        LiveHop::new(
+            &PublicKey::new(b"key"),
+            Some(make_paying_wallet(b"golden"))
+                .map(|w| w.as_payer(&PublicKey::new(b"key"), &contract_address(DEFAULT_CHAIN_ID))),
+            Component::Hopper,
+        )
+    );
 }
@@ -7,7 +7,7 @@ use crate::sub_lib::cryptde::PublicKey;
 use crate::sub_lib::dispatcher::InboundClientData;
 use crate::sub_lib::node_addr::NodeAddr;
 use crate::sub_lib::peer_actors::BindMessage;
-use crate::sub_lib::proxy_client::{ClientResponsePayload, DnsResolveFailure};
+use crate::sub_lib::proxy_client::{BillingClaimPayload, ClientResponsePayload, DnsResolveFailure};
 use crate::sub_lib::proxy_server::ClientRequestPayload;
 use crate::sub_lib::route::Route;
 use crate::sub_lib::wallet::Wallet;
@@ -67,6 +67,7 @@ pub enum MessageType {
     ClientResponse(ClientResponsePayload),
     Gossip(Gossip),
     DnsResolveFailed(DnsResolveFailure),
+    BillingClaim(BillingClaimPayload),
 }
 
 impl IncipientCoresPackage {
@@ -116,12 +117,16 @@ impl<T> ExpiredCoresPackage<T> {
     }
 }
 
+// Threads decrypting and re-encrypting the CORES packages the Hopper routes
+pub const DEFAULT_ROUTING_WORKERS: usize = 4;
+
 #[derive(Clone)]
 pub struct HopperConfig {
     pub cryptde: &'static dyn CryptDE,
     pub per_routing_service: u64,
     pub per_routing_byte: u64,
     pub is_decentralized: bool,
+    pub routing_workers: usize,
 }
 
 #[derive(Clone)]
@@ -142,9 +147,13 @@ impl Debug for HopperSubs {
 mod tests {
     use super::*;
     use crate::blockchain::blockchain_interface::contract_address;
+    use crate::golden_test;
     use crate::sub_lib::cryptde::PlainData;
     use crate::sub_lib::dispatcher::Component;
+    use crate::sub_lib::proxy_server::ProxyProtocol;
     use crate::sub_lib::route::RouteSegment;
+    use crate::sub_lib::sequence_buffer::SequencedPacket;
+    use crate::test_utils::golden_files::golden_stream_key;
     use crate::test_utils::recorder::Recorder;
     use crate::test_utils::{
         cryptde, make_meaningless_message_type, make_paying_wallet, DEFAULT_CHAIN_ID,
@@ -287,4 +296,45 @@ mod tests {
         assert_eq!(subject.payload, payload);
         assert_eq!(subject.payload_len, 42);
     }
+
+    golden_test!(
+        golden_message_type_client_request,
+        MessageType::ClientRequest(ClientRequestPayload {
+            version: ClientRequestPayload::version(),
+            stream_key: golden_stream_key(),
+            sequenced_packet: SequencedPacket::new(
+                b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n".to_vec(),
+                0,
+                false
+            ),
+            target_hostname: Some("example.com".to_string()),
+            target_port: 80,
+            protocol: ProxyProtocol::HTTP,
+            originator_public_key: PublicKey::new(b"golden"),
+        })
+    );
+
+    golden_test!(
+        golden_message_type_client_response,
+        MessageType::ClientResponse(ClientResponsePayload {
+            version: ClientResponsePayload::version(),
+            stream_key: golden_stream_key(),
+            sequenced_packet: SequencedPacket::new(b"HTTP/1.1 200 OK\r\n\r\n".to_vec(), 1, true),
+        })
+    );
+
+    golden_test!(
+        golden_message_type_dns_resolve_failed,
+        MessageType::DnsResolveFailed(DnsResolveFailure::new(golden_stream_key()))
+    );
+
+    golden_test!(
+        golden_message_type_billing_claim,
+        MessageType::BillingClaim(BillingClaimPayload::new(1_234_567, false))
+    );
+
+    golden_test!(
+        golden_message_type_gossip,
+        MessageType::Gossip(Gossip::new(vec![]))
+    );
 }
@@ -1,6 +1,9 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::proxy_server::http_protocol_pack::{MAX_CHUNK_SIZE, MAX_HEADERS_SIZE};
+use crate::proxy_server::http_protocol_pack::{MAX_HEADER_COUNT, MAX_REQUEST_LINE_LENGTH};
 use crate::sub_lib::framer::FramedChunk;
 use crate::sub_lib::framer::Framer;
+use crate::sub_lib::framer::FramingViolation;
 use crate::sub_lib::framer_utils;
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::utils::index_of;
@@ -74,21 +77,30 @@ pub trait HttpPacketStartFinder: Send {
 pub struct HttpPacketFramer {
     framer_state: HttpFramerState,
     start_finder: Box<dyn HttpPacketStartFinder>,
+    violation_opt: Option<FramingViolation>,
     logger: Logger,
 }
 
 impl Framer for HttpPacketFramer {
     fn add_data(&mut self, data: &[u8]) {
-        self.framer_state.data_so_far.extend(data);
+        if self.violation_opt.is_none() {
+            self.framer_state.data_so_far.extend(data);
+        }
     }
 
     fn take_frame(&mut self) -> Option<FramedChunk> {
-        if self.framer_state.transfer_encoding_chunked == ChunkExistenceState::Chunk {
+        if self.violation_opt.is_some() {
+            None
+        } else if self.framer_state.transfer_encoding_chunked == ChunkExistenceState::Chunk {
             self.take_chunk_frame()
         } else {
             self.take_packet_frame()
         }
     }
+
+    fn violation(&self) -> Option<FramingViolation> {
+        self.violation_opt.clone()
+    }
 }
 
 impl HttpPacketFramer {
@@ -104,6 +116,7 @@ impl HttpPacketFramer {
                 lines: Vec::new(),
             },
             start_finder,
+            violation_opt: None,
             logger: Logger::new("HttpRequestFramer"),
         }
     }
@@ -136,8 +149,14 @@ impl HttpPacketFramer {
                     let remainder = self.framer_state.data_so_far.split_off(line_end + 2);
                     let line = self.framer_state.data_so_far.clone();
                     self.framer_state.data_so_far = remainder;
+                    if let Some(violation) =
+                        self.check_header_limits(line.len(), line.len() != CRLF.len())
+                    {
+                        self.violate(violation);
+                        return false;
+                    }
                     if self.framer_state.content_length == 0 {
-                        self.check_for_content_length(&line)
+                        self.check_for_content_length(&line);
                     }
                     if self.framer_state.transfer_encoding_chunked == ChunkExistenceState::Standard
                     {
@@ -149,16 +168,56 @@ impl HttpPacketFramer {
                         return true;
                     }
                 }
-                None => return false,
+                None => {
+                    let pending_len = self.framer_state.data_so_far.len();
+                    if let Some(violation) = self.check_header_limits(pending_len, false) {
+                        self.violate(violation);
+                    }
+                    return false;
+                }
             }
         }
         false
     }
 
+    // Checks a line before the body, whether it's complete or still arriving, against the limits.
+    // Only a complete line, other than the blank one that ends the headers, is counted as a header.
+    fn check_header_limits(&self, line_len: usize, is_header: bool) -> Option<FramingViolation> {
+        let lines = &self.framer_state.lines;
+        if lines.is_empty() {
+            return if line_len > MAX_REQUEST_LINE_LENGTH + CRLF.len() {
+                Some(FramingViolation::RequestLineTooLong(
+                    MAX_REQUEST_LINE_LENGTH,
+                ))
+            } else {
+                None
+            };
+        }
+        if is_header && lines.len() > MAX_HEADER_COUNT {
+            return Some(FramingViolation::TooManyHeaders(MAX_HEADER_COUNT));
+        }
+        let headers_size = lines[1..].iter().map(|line| line.len()).sum::<usize>() + line_len;
+        if headers_size > MAX_HEADERS_SIZE {
+            Some(FramingViolation::HeadersTooLarge(MAX_HEADERS_SIZE))
+        } else {
+            None
+        }
+    }
+
+    // Nothing the stream sends after a violation can be trusted to start a new packet
+    fn violate(&mut self, violation: FramingViolation) {
+        self.framer_state.data_so_far.clear();
+        self.framer_state.lines.clear();
+        self.violation_opt = Some(violation);
+    }
+
     fn seek_body_end(&mut self) -> Option<Vec<u8>> {
-        if (self.framer_state.packet_progress_state == PacketProgressState::SeekingBodyEnd)
-            && (self.framer_state.data_so_far.len() >= self.framer_state.content_length)
-        {
+        if self.framer_state.packet_progress_state != PacketProgressState::SeekingBodyEnd {
+            return None;
+        }
+        // Without headers, this is the rest of a body whose beginning has already been passed along
+        let has_headers = !self.framer_state.lines.is_empty();
+        if self.framer_state.data_so_far.len() >= self.framer_state.content_length {
             let remainder = self
                 .framer_state
                 .data_so_far
@@ -175,17 +234,36 @@ impl HttpPacketFramer {
                 self.framer_state.transfer_encoding_chunked = ChunkExistenceState::Standard;
             }
             self.framer_state.content_length = 0;
-            let mut request = vec![];
-            while !self.framer_state.lines.is_empty() {
-                request.extend(self.framer_state.lines.remove(0))
+            let request = self.drain_lines();
+            if has_headers {
+                info!(self.logger, "{}", summarize_http_packet(&request));
+            }
+            Some(request)
+        } else if self.framer_state.content_length > MAX_CHUNK_SIZE
+            && (has_headers || !self.framer_state.data_so_far.is_empty())
+        {
+            // Too large to hold until it's all here: pass along whatever has arrived
+            let piece = std::mem::replace(&mut self.framer_state.data_so_far, vec![]);
+            self.framer_state.content_length -= piece.len();
+            self.framer_state.lines.push(piece);
+            let request = self.drain_lines();
+            if has_headers {
+                info!(self.logger, "{}", summarize_http_packet(&request));
             }
-            info!(self.logger, "{}", summarize_http_packet(&request));
             Some(request)
         } else {
             None
         }
     }
 
+    fn drain_lines(&mut self) -> Vec<u8> {
+        let mut request = vec![];
+        while !self.framer_state.lines.is_empty() {
+            request.extend(self.framer_state.lines.remove(0))
+        }
+        request
+    }
+
     fn check_for_content_length(&mut self, line: &Vec<u8>) {
         if !line.starts_with(b"Content-Length:") {
             return;
@@ -312,7 +390,18 @@ impl HttpPacketFramer {
             .chunk_size
             .expect("If we are seeking the end of the chunk then we should have the chunk size");
         if self.framer_state.data_so_far.len() < (chunk_size + CRLF.len()) {
-            return None;
+            if chunk_size <= MAX_CHUNK_SIZE || self.framer_state.data_so_far.is_empty() {
+                return None;
+            }
+            // Too large to hold until it's all here: pass along whatever has arrived
+            let piece_len = self.framer_state.data_so_far.len().min(chunk_size);
+            let remainder = self.framer_state.data_so_far.split_off(piece_len);
+            let piece = std::mem::replace(&mut self.framer_state.data_so_far, remainder);
+            self.framer_state.chunk_size = Some(chunk_size - piece_len);
+            return Some(FramedChunk {
+                chunk: piece,
+                last_chunk: false,
+            });
         }
         let remaining_data = self
             .framer_state
@@ -357,7 +446,13 @@ impl HttpPacketFramer {
                     last_chunk: false,
                 })
             }
-            None => None,
+            None => {
+                // Trailers are headers too
+                if self.framer_state.data_so_far.len() > MAX_HEADERS_SIZE {
+                    self.violate(FramingViolation::HeadersTooLarge(MAX_HEADERS_SIZE));
+                }
+                None
+            }
         }
     }
 }
@@ -944,16 +1039,20 @@ mod framer_tests {
 
         let result = subject.take_frame();
 
-        assert_eq!(result, None);
+        // The chunk is too large to hold, so what has arrived of it is passed along
         assert_eq!(
-            subject.framer_state.data_so_far,
-            Vec::from(&b"98765432\r\nbeginning of content"[..])
+            result,
+            Some(FramedChunk {
+                chunk: Vec::from(&b"98765432\r\nbeginning of content"[..]),
+                last_chunk: false,
+            })
         );
+        assert_eq!(subject.framer_state.data_so_far, Vec::<u8>::new());
         assert_eq!(
             subject.framer_state.chunk_progress_state,
             ChunkProgressState::SeekingEndOfChunk
         );
-        assert_eq!(subject.framer_state.chunk_size, Some(0x98765432 + 10));
+        assert_eq!(subject.framer_state.chunk_size, Some(0x98765432 + 10 - 30));
     }
 
     #[test]
@@ -1095,4 +1194,157 @@ mod framer_tests {
         assert_eq!(to_string(&actual_chunk.chunk), to_string_s(&data[..]));
         assert_eq!(actual_chunk.last_chunk, false);
     }
+
+    #[test]
+    fn refuses_request_line_that_goes_on_too_long_and_ignores_what_follows() {
+        let mut data = Vec::from(&GOOD_FIRST_LINE[..]);
+        data.extend(vec![b'x'; MAX_REQUEST_LINE_LENGTH]);
+        let mut subject = HttpPacketFramer::new(Box::new(TameStartFinder {}));
+        subject.add_data(&data);
+
+        let result = subject.take_frame();
+
+        assert_eq!(result, None);
+        assert_eq!(
+            subject.violation(),
+            Some(FramingViolation::RequestLineTooLong(
+                MAX_REQUEST_LINE_LENGTH
+            ))
+        );
+        assert_eq!(subject.framer_state.data_so_far, Vec::<u8>::new());
+        subject.add_data(&b"GOOD_FIRST_LINE\r\n\r\n"[..]);
+        assert_eq!(subject.take_frame(), None);
+        assert_eq!(subject.framer_state.data_so_far, Vec::<u8>::new());
+    }
+
+    #[test]
+    fn accepts_as_many_headers_as_allowed_but_no_more() {
+        let headers = (0..MAX_HEADER_COUNT)
+            .map(|n| format!("Header-{}: value\r\n", n))
+            .collect::<String>();
+        let request = format!("GOOD_FIRST_LINE\r\n{}\r\n", headers);
+        let mut at_limit = HttpPacketFramer::new(Box::new(TameStartFinder {}));
+        let mut over_limit = HttpPacketFramer::new(Box::new(TameStartFinder {}));
+        at_limit.add_data(request.as_bytes());
+        over_limit
+            .add_data(format!("GOOD_FIRST_LINE\r\nOne-More: value\r\n{}\r\n", headers).as_bytes());
+
+        let at_limit_result = at_limit.take_frame();
+        let over_limit_result = over_limit.take_frame();
+
+        assert_eq!(
+            at_limit_result,
+            Some(FramedChunk {
+                chunk: request.into_bytes(),
+                last_chunk: false,
+            })
+        );
+        assert_eq!(at_limit.violation(), None);
+        assert_eq!(over_limit_result, None);
+        assert_eq!(
+            over_limit.violation(),
+            Some(FramingViolation::TooManyHeaders(MAX_HEADER_COUNT))
+        );
+    }
+
+    #[test]
+    fn refuses_headers_that_add_up_to_too_much() {
+        let header = format!("Big-Header: {}\r\n", "x".repeat(MAX_HEADERS_SIZE / 4));
+        let mut subject = HttpPacketFramer::new(Box::new(TameStartFinder {}));
+        subject.add_data(format!("GOOD_FIRST_LINE\r\n{}{}{}", header, header, header).as_bytes());
+        assert_eq!(subject.take_frame(), None);
+        assert_eq!(subject.violation(), None);
+        subject.add_data(header.as_bytes());
+
+        let result = subject.take_frame();
+
+        assert_eq!(result, None);
+        assert_eq!(
+            subject.violation(),
+            Some(FramingViolation::HeadersTooLarge(MAX_HEADERS_SIZE))
+        );
+    }
+
+    #[test]
+    fn passes_along_body_too_large_to_hold_as_it_arrives() {
+        let mut subject = HttpPacketFramer::new(Box::new(TameStartFinder {}));
+        let headers = format!(
+            "GOOD_FIRST_LINE\r\nContent-Length: {}\r\n\r\n",
+            MAX_CHUNK_SIZE + 10
+        );
+        subject.add_data(format!("{}start of body", headers).as_bytes());
+
+        let first = subject.take_frame();
+        let nothing_more = subject.take_frame();
+        subject.add_data(&vec![b'x'; MAX_CHUNK_SIZE - 14]);
+        subject.add_data(b"end of bodyGOOD_FIRST_LINE\r\n\r\n");
+        let second = subject.take_frame();
+        let next_request = subject.take_frame();
+
+        assert_eq!(
+            first,
+            Some(FramedChunk {
+                chunk: format!("{}start of body", headers).into_bytes(),
+                last_chunk: false,
+            })
+        );
+        assert_eq!(nothing_more, None);
+        let mut expected_rest = vec![b'x'; MAX_CHUNK_SIZE - 14];
+        expected_rest.extend_from_slice(b"end of body");
+        assert_eq!(
+            second,
+            Some(FramedChunk {
+                chunk: expected_rest,
+                last_chunk: false,
+            })
+        );
+        assert_eq!(
+            next_request,
+            Some(FramedChunk {
+                chunk: b"GOOD_FIRST_LINE\r\n\r\n".to_vec(),
+                last_chunk: false,
+            })
+        );
+        assert_eq!(subject.violation(), None);
+    }
+
+    #[test]
+    fn passes_along_chunk_too_large_to_hold_as_it_arrives() {
+        let mut subject = HttpPacketFramer::new(Box::new(TameStartFinder {}));
+        subject.framer_state.transfer_encoding_chunked = ChunkExistenceState::Chunk;
+        subject.framer_state.chunk_progress_state = ChunkProgressState::SeekingLengthHeader;
+        let size_line = format!("{:x}\r\n", MAX_CHUNK_SIZE + 1);
+        subject.add_data(format!("{}start of chunk", size_line).as_bytes());
+
+        let first = subject.take_frame();
+        subject.add_data(&vec![b'x'; MAX_CHUNK_SIZE + 1 - 14]);
+        subject.add_data(b"\r\n0\r\n\r\n");
+        let rest_of_chunk = subject.take_frame();
+        let final_chunk = subject.take_frame();
+
+        assert_eq!(
+            first,
+            Some(FramedChunk {
+                chunk: format!("{}start of chunk", size_line).into_bytes(),
+                last_chunk: false,
+            })
+        );
+        let mut expected_rest = vec![b'x'; MAX_CHUNK_SIZE + 1 - 14];
+        expected_rest.extend_from_slice(b"\r\n");
+        assert_eq!(
+            rest_of_chunk,
+            Some(FramedChunk {
+                chunk: expected_rest,
+                last_chunk: false,
+            })
+        );
+        assert_eq!(
+            final_chunk,
+            Some(FramedChunk {
+                chunk: b"0\r\n\r\n".to_vec(),
+                last_chunk: false,
+            })
+        );
+        assert_eq!(subject.violation(), None);
+    }
 }
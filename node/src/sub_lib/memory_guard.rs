@@ -0,0 +1,407 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::sub_lib::logger::Logger;
+use lazy_static::lazy_static;
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+// How often the watchdog looks at how much memory the Node is using
+pub const MEMORY_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+// Once buffers are tightened, each stream keeps only this fraction of --buffer-memory-limit in memory
+pub const TIGHT_BUFFER_DIVISOR: usize = 8;
+// Shedding is eased only once resident memory falls this far below the threshold that started it,
+// so that the Node doesn't flap back and forth across a threshold
+const EASING_PERCENT: u64 = 90;
+
+lazy_static! {
+    pub static ref MEMORY_GUARD: MemoryGuard = MemoryGuard::default();
+}
+
+// Each level sheds everything the levels below it shed, and more
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SheddingLevel {
+    Normal,
+    RefuseConsumingStreams,
+    TightenBuffers,
+    RefuseClandestineConnections,
+}
+
+const SHEDDING_LEVELS: [SheddingLevel; 4] = [
+    SheddingLevel::Normal,
+    SheddingLevel::RefuseConsumingStreams,
+    SheddingLevel::TightenBuffers,
+    SheddingLevel::RefuseClandestineConnections,
+];
+
+impl SheddingLevel {
+    fn shedding(self) -> &'static str {
+        match self {
+            SheddingLevel::Normal => "shedding nothing",
+            SheddingLevel::RefuseConsumingStreams => "refusing new consuming streams",
+            SheddingLevel::TightenBuffers => {
+                "tightening stream buffers to 1/8 of --buffer-memory-limit"
+            }
+            SheddingLevel::RefuseClandestineConnections => "refusing new clandestine connections",
+        }
+    }
+
+    fn easing(self) -> &'static str {
+        match self {
+            SheddingLevel::Normal => "shedding nothing",
+            SheddingLevel::RefuseConsumingStreams => "accepting new consuming streams again",
+            SheddingLevel::TightenBuffers => "restoring stream buffers to --buffer-memory-limit",
+            SheddingLevel::RefuseClandestineConnections => {
+                "accepting new clandestine connections again"
+            }
+        }
+    }
+}
+
+// How much load the Node is shedding right now. The watchdog sets it; everything that takes on new
+// work consults it.
+#[derive(Default)]
+pub struct MemoryGuard {
+    level: AtomicUsize,
+}
+
+impl MemoryGuard {
+    pub fn level(&self) -> SheddingLevel {
+        SHEDDING_LEVELS[self.level.load(Ordering::Relaxed)]
+    }
+
+    pub fn set_level(&self, level: SheddingLevel) {
+        self.level.store(level as usize, Ordering::Relaxed);
+    }
+
+    pub fn refuses_consuming_streams(&self) -> bool {
+        self.level() >= SheddingLevel::RefuseConsumingStreams
+    }
+
+    pub fn buffer_memory_limit(&self, configured_limit: usize) -> usize {
+        if self.level() >= SheddingLevel::TightenBuffers {
+            configured_limit / TIGHT_BUFFER_DIVISOR
+        } else {
+            configured_limit
+        }
+    }
+
+    pub fn refuses_clandestine_connections(&self) -> bool {
+        self.level() >= SheddingLevel::RefuseClandestineConnections
+    }
+}
+
+// Resident memory, in bytes, past which each level of shedding starts
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MemoryThresholds {
+    pub refuse_consuming_streams: u64,
+    pub tighten_buffers: u64,
+    pub refuse_clandestine_connections: u64,
+}
+
+impl MemoryThresholds {
+    // Takes three comma-separated numbers of megabytes, in increasing order
+    pub fn parse(megabytes: &str) -> Result<MemoryThresholds, String> {
+        let values = megabytes
+            .split(',')
+            .map(|value| value.trim().parse::<u64>())
+            .collect::<Result<Vec<u64>, _>>()
+            .map_err(|_| format!("Memory thresholds must be numbers: '{}'", megabytes))?;
+        match values.as_slice() {
+            [streams, buffers, connections]
+                if 0 < *streams && streams < buffers && buffers < connections =>
+            {
+                Ok(MemoryThresholds {
+                    refuse_consuming_streams: streams * 1024 * 1024,
+                    tighten_buffers: buffers * 1024 * 1024,
+                    refuse_clandestine_connections: connections * 1024 * 1024,
+                })
+            }
+            _ => Err(format!(
+                "Memory thresholds must be three increasing numbers of megabytes: '{}'",
+                megabytes
+            )),
+        }
+    }
+
+    pub fn threshold(&self, level: SheddingLevel) -> u64 {
+        match level {
+            SheddingLevel::Normal => 0,
+            SheddingLevel::RefuseConsumingStreams => self.refuse_consuming_streams,
+            SheddingLevel::TightenBuffers => self.tighten_buffers,
+            SheddingLevel::RefuseClandestineConnections => self.refuse_clandestine_connections,
+        }
+    }
+
+    pub fn level_for(&self, resident_bytes: u64, current: SheddingLevel) -> SheddingLevel {
+        let reached = SHEDDING_LEVELS
+            .iter()
+            .rev()
+            .find(|level| resident_bytes >= self.threshold(**level))
+            .cloned()
+            .unwrap_or(SheddingLevel::Normal);
+        let mut held = current;
+        while held > SheddingLevel::Normal && resident_bytes < self.easing_threshold(held) {
+            held = SHEDDING_LEVELS[held as usize - 1];
+        }
+        reached.max(held)
+    }
+
+    fn easing_threshold(&self, level: SheddingLevel) -> u64 {
+        self.threshold(level) / 100 * EASING_PERCENT
+    }
+}
+
+pub trait ResidentMemory: Send {
+    fn resident_bytes(&self) -> Option<u64>;
+}
+
+// Reads the Node's resident set size from /proc, so it knows nothing on platforms without one
+pub struct ResidentMemoryReal {}
+
+impl ResidentMemory for ResidentMemoryReal {
+    fn resident_bytes(&self) -> Option<u64> {
+        let status = fs::read_to_string("/proc/self/status").ok()?;
+        status
+            .lines()
+            .find(|line| line.starts_with("VmRSS:"))
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|kilobytes| kilobytes.parse::<u64>().ok())
+            .map(|kilobytes| kilobytes * 1024)
+    }
+}
+
+pub struct MemoryWatchdog {
+    thresholds: MemoryThresholds,
+    guard: &'static MemoryGuard,
+    resident_memory: Box<dyn ResidentMemory>,
+    logger: Logger,
+}
+
+impl MemoryWatchdog {
+    pub fn new(thresholds: MemoryThresholds) -> MemoryWatchdog {
+        MemoryWatchdog {
+            thresholds,
+            guard: &MEMORY_GUARD,
+            resident_memory: Box::new(ResidentMemoryReal {}),
+            logger: Logger::new("MemoryWatchdog"),
+        }
+    }
+
+    pub fn start(self) {
+        if self.resident_memory.resident_bytes().is_none() {
+            warning!(
+                self.logger,
+                "Can't tell how much memory this Node is using on this platform; ignoring memory thresholds"
+            );
+            return;
+        }
+        thread::Builder::new()
+            .name("MemoryWatchdog".to_string())
+            .spawn(move || loop {
+                self.check();
+                thread::sleep(MEMORY_CHECK_INTERVAL);
+            })
+            .expect("Couldn't start memory watchdog");
+    }
+
+    pub fn check(&self) {
+        let resident_bytes = match self.resident_memory.resident_bytes() {
+            Some(resident_bytes) => resident_bytes,
+            None => return,
+        };
+        let previous = self.guard.level();
+        let level = self.thresholds.level_for(resident_bytes, previous);
+        if level > previous {
+            SHEDDING_LEVELS[(previous as usize + 1)..=(level as usize)]
+                .iter()
+                .for_each(|step| {
+                    warning!(
+                        self.logger,
+                        "Resident memory is {} MB, past {} MB: {}",
+                        megabytes(resident_bytes),
+                        megabytes(self.thresholds.threshold(*step)),
+                        step.shedding()
+                    )
+                });
+        } else if level < previous {
+            SHEDDING_LEVELS[(level as usize + 1)..=(previous as usize)]
+                .iter()
+                .rev()
+                .for_each(|step| {
+                    info!(
+                        self.logger,
+                        "Resident memory is {} MB, back under {} MB: {}",
+                        megabytes(resident_bytes),
+                        megabytes(self.thresholds.easing_threshold(*step)),
+                        step.easing()
+                    )
+                });
+        }
+        self.guard.set_level(level);
+    }
+}
+
+fn megabytes(bytes: u64) -> u64 {
+    bytes / (1024 * 1024)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::test_utils::logging::init_test_logging;
+    use crate::test_utils::logging::TestLogHandler;
+    use std::cell::RefCell;
+
+    const MB: u64 = 1024 * 1024;
+
+    // Enforcement points are tested against guards of their own, so that they don't shed load for
+    // every other test running alongside them
+    pub fn make_memory_guard(level: SheddingLevel) -> &'static MemoryGuard {
+        let guard = Box::leak(Box::new(MemoryGuard::default()));
+        guard.set_level(level);
+        guard
+    }
+
+    struct ResidentMemoryMock {
+        results: RefCell<Vec<Option<u64>>>,
+    }
+
+    impl ResidentMemory for ResidentMemoryMock {
+        fn resident_bytes(&self) -> Option<u64> {
+            self.results.borrow_mut().remove(0)
+        }
+    }
+
+    fn make_watchdog(results: Vec<Option<u64>>) -> MemoryWatchdog {
+        let mut subject = MemoryWatchdog::new(thresholds());
+        subject.guard = make_memory_guard(SheddingLevel::Normal);
+        subject.resident_memory = Box::new(ResidentMemoryMock {
+            results: RefCell::new(results),
+        });
+        subject
+    }
+
+    fn thresholds() -> MemoryThresholds {
+        MemoryThresholds::parse("100,200,300").unwrap()
+    }
+
+    #[test]
+    fn thresholds_must_be_three_increasing_numbers() {
+        assert_eq!(
+            MemoryThresholds::parse("100, 200,300"),
+            Ok(MemoryThresholds {
+                refuse_consuming_streams: 100 * MB,
+                tighten_buffers: 200 * MB,
+                refuse_clandestine_connections: 300 * MB,
+            })
+        );
+        vec![
+            "100,200",
+            "100,200,300,400",
+            "200,100,300",
+            "0,100,200",
+            "100,200,200",
+        ]
+        .into_iter()
+        .for_each(|megabytes| {
+            assert_eq!(
+                MemoryThresholds::parse(megabytes),
+                Err(format!(
+                    "Memory thresholds must be three increasing numbers of megabytes: '{}'",
+                    megabytes
+                ))
+            )
+        });
+        assert_eq!(
+            MemoryThresholds::parse("100,lots,300"),
+            Err("Memory thresholds must be numbers: '100,lots,300'".to_string())
+        );
+    }
+
+    #[test]
+    fn level_climbs_at_each_threshold_but_eases_only_well_below_it() {
+        let subject = thresholds();
+
+        assert_eq!(
+            subject.level_for(99 * MB, SheddingLevel::Normal),
+            SheddingLevel::Normal
+        );
+        assert_eq!(
+            subject.level_for(100 * MB, SheddingLevel::Normal),
+            SheddingLevel::RefuseConsumingStreams
+        );
+        assert_eq!(
+            subject.level_for(300 * MB, SheddingLevel::Normal),
+            SheddingLevel::RefuseClandestineConnections
+        );
+        assert_eq!(
+            subject.level_for(271 * MB, SheddingLevel::RefuseClandestineConnections),
+            SheddingLevel::RefuseClandestineConnections
+        );
+        assert_eq!(
+            subject.level_for(269 * MB, SheddingLevel::RefuseClandestineConnections),
+            SheddingLevel::TightenBuffers
+        );
+        assert_eq!(
+            subject.level_for(50 * MB, SheddingLevel::RefuseClandestineConnections),
+            SheddingLevel::Normal
+        );
+    }
+
+    #[test]
+    fn guard_sheds_more_at_each_level() {
+        let subject = MemoryGuard::default();
+
+        let shedding = SHEDDING_LEVELS
+            .iter()
+            .map(|level| {
+                subject.set_level(*level);
+                (
+                    subject.refuses_consuming_streams(),
+                    subject.buffer_memory_limit(8192),
+                    subject.refuses_clandestine_connections(),
+                )
+            })
+            .collect::<Vec<(bool, usize, bool)>>();
+
+        assert_eq!(
+            shedding,
+            vec![
+                (false, 8192, false),
+                (true, 8192, false),
+                (true, 1024, false),
+                (true, 1024, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn watchdog_logs_each_step_as_it_sheds_and_eases_load() {
+        init_test_logging();
+        let subject = make_watchdog(vec![Some(250 * MB), None, Some(150 * MB), Some(10 * MB)]);
+
+        subject.check();
+        assert_eq!(subject.guard.level(), SheddingLevel::TightenBuffers);
+        subject.check();
+        assert_eq!(subject.guard.level(), SheddingLevel::TightenBuffers);
+        subject.check();
+        assert_eq!(subject.guard.level(), SheddingLevel::RefuseConsumingStreams);
+        subject.check();
+        assert_eq!(subject.guard.level(), SheddingLevel::Normal);
+
+        let tlh = TestLogHandler::new();
+        tlh.exists_log_containing(
+            "WARN: MemoryWatchdog: Resident memory is 250 MB, past 100 MB: refusing new consuming streams",
+        );
+        tlh.exists_log_containing(
+            "WARN: MemoryWatchdog: Resident memory is 250 MB, past 200 MB: tightening stream buffers to 1/8 of --buffer-memory-limit",
+        );
+        tlh.exists_log_containing(
+            "INFO: MemoryWatchdog: Resident memory is 150 MB, back under 180 MB: restoring stream buffers to --buffer-memory-limit",
+        );
+        tlh.exists_log_containing(
+            "INFO: MemoryWatchdog: Resident memory is 10 MB, back under 90 MB: accepting new consuming streams again",
+        );
+    }
+}
@@ -13,6 +13,8 @@ pub mod bidi_hashmap;
 pub mod binary_traverser;
 pub mod blockchain_bridge;
 pub mod channel_wrappers;
+pub mod clock;
+pub mod crash_context;
 pub mod crash_point;
 pub mod cryptde;
 pub mod cryptde_null;
@@ -21,25 +23,34 @@ pub mod data_version;
 pub mod dispatcher;
 pub mod framer;
 pub mod framer_utils;
+pub mod ftp_passive;
 pub mod hop;
 pub mod hopper;
 pub mod http_packet_framer;
 pub mod http_response_start_finder;
 pub mod limiter;
 pub mod main_tools;
+pub mod memory_guard;
+pub mod neighbor_rate_limiter;
 pub mod neighborhood;
 pub mod node_addr;
+pub mod node_descriptor_share;
+pub mod obfuscation;
 pub mod peer_actors;
 pub mod proxy_client;
 pub mod proxy_server;
+pub mod random;
+pub mod raw_framer;
 pub mod route;
 pub mod sequence_buffer;
 pub mod sequencer;
 pub mod set_consuming_wallet_message;
 pub mod socket_server;
+pub mod spill_file;
 pub mod stream_connector;
 pub mod stream_handler_pool;
 pub mod stream_key;
+pub mod stream_tracing;
 pub mod tcp_wrappers;
 pub mod tls_framer;
 pub mod tokio_wrappers;
@@ -0,0 +1,260 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+// A neighbor may send this many seconds' worth of its limit at once before it's throttled
+const BURST_SECS: f64 = 2.0;
+
+// A neighbor that stays over its limit this long without a break is cut off...
+pub const SUSTAINED_ABUSE_TIME: Duration = Duration::from_secs(60);
+// ...for this long
+pub const CUT_OFF_TIME: Duration = Duration::from_secs(10 * 60);
+// A neighbor so far over its limit that its data would have to be held back longer than this is
+// cut off at once, so that no neighbor can make us hold more than this much of its traffic
+pub const MAX_THROTTLE_TIME: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct NeighborRateLimits {
+    pub ingress_bytes_per_sec_opt: Option<u64>,
+    pub egress_bytes_per_sec_opt: Option<u64>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RateVerdict {
+    Pass,
+    // Hold the data this long, so that the neighbor's traffic evens out at its limit
+    Throttle(Duration),
+    // The neighbor has been over its limit too long or too far: drop the data and disconnect it
+    Disconnect,
+    // The neighbor is still cut off: drop the data
+    Drop,
+}
+
+struct Allowance {
+    // Bytes the neighbor may still send without waiting; negative when it's in debt
+    bytes: f64,
+    refilled_at: Instant,
+    over_limit_since_opt: Option<Instant>,
+    cut_off_until_opt: Option<Instant>,
+}
+
+impl Allowance {
+    fn full(bytes: f64, now: Instant) -> Allowance {
+        Allowance {
+            bytes,
+            refilled_at: now,
+            over_limit_since_opt: None,
+            cut_off_until_opt: None,
+        }
+    }
+
+    fn is_cut_off(&self, now: Instant) -> bool {
+        match self.cut_off_until_opt {
+            Some(until) => now < until,
+            None => false,
+        }
+    }
+}
+
+// A token bucket for each neighbor, told apart by whatever K the caller can see of them
+pub struct NeighborRateLimiter<K> {
+    bytes_per_sec: f64,
+    allowances: HashMap<K, Allowance>,
+}
+
+impl<K: Hash + Eq + Clone> NeighborRateLimiter<K> {
+    pub fn new(bytes_per_sec: u64) -> NeighborRateLimiter<K> {
+        NeighborRateLimiter {
+            bytes_per_sec: bytes_per_sec as f64,
+            allowances: HashMap::new(),
+        }
+    }
+
+    pub fn admit(&mut self, neighbor: &K, bytes: usize, now: Instant) -> RateVerdict {
+        let burst = self.burst();
+        let bytes_per_sec = self.bytes_per_sec;
+        let allowance = self
+            .allowances
+            .entry(neighbor.clone())
+            .or_insert_with(|| Allowance::full(burst, now));
+        if allowance.is_cut_off(now) {
+            return RateVerdict::Drop;
+        }
+        if allowance.cut_off_until_opt.is_some() {
+            *allowance = Allowance::full(burst, now);
+        }
+        if now > allowance.refilled_at {
+            let elapsed = now.duration_since(allowance.refilled_at);
+            allowance.bytes = burst.min(allowance.bytes + elapsed.as_secs_f64() * bytes_per_sec);
+            allowance.refilled_at = now;
+        }
+        if allowance.bytes >= 0.0 {
+            allowance.over_limit_since_opt = None;
+        }
+        allowance.bytes -= bytes as f64;
+        if allowance.bytes >= 0.0 {
+            return RateVerdict::Pass;
+        }
+        let over_limit_since = *allowance.over_limit_since_opt.get_or_insert(now);
+        let delay = Duration::from_secs_f64(-allowance.bytes / bytes_per_sec);
+        if delay > MAX_THROTTLE_TIME || now.duration_since(over_limit_since) >= SUSTAINED_ABUSE_TIME
+        {
+            allowance.cut_off_until_opt = Some(now + CUT_OFF_TIME);
+            RateVerdict::Disconnect
+        } else {
+            RateVerdict::Throttle(delay)
+        }
+    }
+
+    pub fn remembers(&self, neighbor: &K) -> bool {
+        self.allowances.contains_key(neighbor)
+    }
+
+    // Neighbors that have been quiet long enough to have their whole allowance back needn't be
+    // remembered; those still cut off must be, or they could come straight back
+    pub fn forget_idle(&mut self, now: Instant) {
+        let burst = self.burst();
+        let bytes_per_sec = self.bytes_per_sec;
+        self.allowances.retain(|_, allowance| {
+            if allowance.is_cut_off(now) {
+                return true;
+            }
+            let elapsed = if now > allowance.refilled_at {
+                now.duration_since(allowance.refilled_at)
+            } else {
+                Duration::from_secs(0)
+            };
+            allowance.bytes + elapsed.as_secs_f64() * bytes_per_sec < burst
+        });
+    }
+
+    fn burst(&self) -> f64 {
+        self.bytes_per_sec * BURST_SECS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_neighbor_within_its_limit_passes() {
+        let mut subject = NeighborRateLimiter::new(1000);
+        let now = Instant::now();
+
+        let result = (0..4)
+            .map(|n| subject.admit(&"neighbor", 1000, now + Duration::from_secs(n)))
+            .collect::<Vec<RateVerdict>>();
+
+        assert_eq!(result, vec![RateVerdict::Pass; 4]);
+    }
+
+    #[test]
+    fn a_neighbor_may_burst_before_it_is_throttled() {
+        let mut subject = NeighborRateLimiter::new(1000);
+        let now = Instant::now();
+
+        let first = subject.admit(&"neighbor", 2000, now);
+        let second = subject.admit(&"neighbor", 500, now);
+        let third = subject.admit(&"neighbor", 500, now);
+
+        assert_eq!(first, RateVerdict::Pass);
+        assert_eq!(second, RateVerdict::Throttle(Duration::from_millis(500)));
+        assert_eq!(third, RateVerdict::Throttle(Duration::from_millis(1000)));
+    }
+
+    #[test]
+    fn a_throttled_neighbor_that_slows_down_passes_again() {
+        let mut subject = NeighborRateLimiter::new(1000);
+        let now = Instant::now();
+        subject.admit(&"neighbor", 3000, now);
+
+        let result = subject.admit(&"neighbor", 500, now + Duration::from_millis(1500));
+
+        assert_eq!(result, RateVerdict::Pass);
+    }
+
+    #[test]
+    fn neighbors_have_allowances_of_their_own() {
+        let mut subject = NeighborRateLimiter::new(1000);
+        let now = Instant::now();
+        subject.admit(&"greedy", 5000, now);
+
+        let result = subject.admit(&"modest", 1000, now);
+
+        assert_eq!(result, RateVerdict::Pass);
+    }
+
+    // Keeps two seconds behind its limit until it's disconnected, and says when that was
+    fn abuse(subject: &mut NeighborRateLimiter<&'static str>, neighbor: &'static str) -> Instant {
+        let start = Instant::now();
+        let mut now = start;
+        let mut verdict = subject.admit(&neighbor, 4000, now);
+        while now < start + SUSTAINED_ABUSE_TIME {
+            assert_eq!(verdict, RateVerdict::Throttle(Duration::from_secs(2)));
+            now += Duration::from_secs(1);
+            verdict = subject.admit(&neighbor, 1000, now);
+        }
+        assert_eq!(verdict, RateVerdict::Disconnect);
+        now
+    }
+
+    #[test]
+    fn a_neighbor_over_its_limit_too_long_is_cut_off_for_a_while() {
+        let mut subject = NeighborRateLimiter::new(1000);
+        let now = abuse(&mut subject, "neighbor");
+
+        let during_cut_off = subject.admit(&"neighbor", 1, now + CUT_OFF_TIME / 2);
+        let after_cut_off = subject.admit(&"neighbor", 1, now + CUT_OFF_TIME);
+
+        assert_eq!(during_cut_off, RateVerdict::Drop);
+        assert_eq!(after_cut_off, RateVerdict::Pass);
+    }
+
+    #[test]
+    fn a_neighbor_too_far_over_its_limit_is_cut_off_at_once() {
+        let mut subject = NeighborRateLimiter::new(1000);
+        let now = Instant::now();
+        let within_reach = subject.admit(&"neighbor", 12000, now);
+
+        let out_of_reach = subject.admit(&"neighbor", 1, now);
+        let afterward = subject.admit(&"neighbor", 1, now + CUT_OFF_TIME / 2);
+
+        assert_eq!(within_reach, RateVerdict::Throttle(MAX_THROTTLE_TIME));
+        assert_eq!(out_of_reach, RateVerdict::Disconnect);
+        assert_eq!(afterward, RateVerdict::Drop);
+    }
+
+    #[test]
+    fn a_neighbor_that_pays_off_its_debt_starts_afresh() {
+        let mut subject = NeighborRateLimiter::new(1000);
+        let now = Instant::now();
+        subject.admit(&"neighbor", 4000, now);
+
+        let result = subject.admit(
+            &"neighbor",
+            4000,
+            now + SUSTAINED_ABUSE_TIME + Duration::from_secs(1),
+        );
+
+        assert_eq!(result, RateVerdict::Throttle(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn idle_neighbors_are_forgotten_but_cut_off_ones_are_not() {
+        let mut subject = NeighborRateLimiter::new(1000);
+        let now = abuse(&mut subject, "cut off");
+        subject.admit(&"idle", 1000, now);
+        subject.admit(&"busy", 1000, now + Duration::from_millis(500));
+
+        subject.forget_idle(now + Duration::from_millis(1200));
+
+        let mut remembered = subject.allowances.keys().cloned().collect::<Vec<&str>>();
+        remembered.sort();
+        assert_eq!(remembered, vec!["busy", "cut off"]);
+        assert!(subject.remembers(&"busy"));
+        assert!(!subject.remembers(&"idle"));
+    }
+}
@@ -5,21 +5,26 @@ use crate::sub_lib::cryptde::{CryptDE, PublicKey};
 use crate::sub_lib::dispatcher::{Component, StreamShutdownMsg};
 use crate::sub_lib::hopper::ExpiredCoresPackage;
 use crate::sub_lib::node_addr::NodeAddr;
+use crate::sub_lib::node_descriptor_share::node_descriptor_from_uri;
 use crate::sub_lib::peer_actors::{BindMessage, StartMessage};
 use crate::sub_lib::route::Route;
 use crate::sub_lib::set_consuming_wallet_message::SetConsumingWalletMessage;
 use crate::sub_lib::stream_handler_pool::DispatcherNodeQueryResponse;
 use crate::sub_lib::stream_handler_pool::TransmitDataMsg;
+use crate::sub_lib::ui_gateway::{GetHealthMessage, GetNodeStatisticsMessage};
 use crate::sub_lib::utils::node_descriptor_delimiter;
 use crate::sub_lib::wallet::Wallet;
 use actix::Message;
 use actix::Recipient;
 use lazy_static::lazy_static;
 use serde_derive::{Deserialize, Serialize};
+use std::fmt;
 use std::fmt::{Debug, Formatter};
 use std::net::IpAddr;
 use std::str::FromStr;
 
+pub const DEFAULT_MINIMUM_HOP_COUNT: usize = 3;
+
 pub const DEFAULT_RATE_PACK: RatePack = RatePack {
     routing_byte_rate: 100,
     routing_service_rate: 10000,
@@ -155,6 +160,9 @@ impl NodeDescriptor {
         s: &str,
         chain_id: u8,
     ) -> Result<NodeDescriptor, String> {
+        // So a neighbor can be given as the substratum:// URI another Node shared
+        let descriptor = node_descriptor_from_uri(s)?;
+        let s = descriptor.as_str();
         let delimiter = node_descriptor_delimiter(chain_id);
         let pieces: Vec<&str> = s.splitn(2, delimiter).collect();
 
@@ -220,6 +228,13 @@ pub struct NeighborhoodSubs {
     pub stream_shutdown_sub: Recipient<StreamShutdownMsg>,
     pub set_consuming_wallet_sub: Recipient<SetConsumingWalletMessage>,
     pub from_ui_gateway: Recipient<NeighborhoodDotGraphRequest>,
+    pub neighbor_list_sub: Recipient<NeighborListRequest>,
+    pub delinquency_sub: Recipient<DelinquencyMessage>,
+    pub debtor_key_sub: Recipient<DebtorKeyMessage>,
+    pub billing_claim_sub: Recipient<BillingClaimMessage>,
+    pub dispute_sub: Recipient<DisputeMessage>,
+    pub node_statistics_sub: Recipient<GetNodeStatisticsMessage>,
+    pub health_sub: Recipient<GetHealthMessage>,
 }
 
 impl Debug for NeighborhoodSubs {
@@ -257,6 +272,43 @@ pub struct NeighborhoodDotGraphRequest {
     pub client_id: u64,
 }
 
+#[derive(Clone, Debug, Message, PartialEq)]
+pub struct NeighborListRequest {
+    pub client_id: u64,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum NeighborReputation {
+    Good,
+    // Recently failed us as an exit, so kept out of routes for now
+    Undesirable,
+    // Earns to a wallet banned for not paying its debts
+    Delinquent,
+}
+
+impl fmt::Display for NeighborReputation {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            NeighborReputation::Good => write!(f, "good"),
+            NeighborReputation::Undesirable => write!(f, "undesirable"),
+            NeighborReputation::Delinquent => write!(f, "delinquent"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NeighborSummary {
+    pub public_key: String,
+    pub key_fingerprint: String,
+    pub node_addr: Option<String>,
+    pub neighbor_for_secs: u64,
+    pub version: u32,
+    pub secs_since_last_gossip: Option<u64>,
+    pub reputation: NeighborReputation,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum NodeQueryMessage {
     IpAddress(IpAddr),
@@ -280,6 +332,8 @@ pub struct RouteQueryMessage {
     pub target_component: Component,
     pub minimum_hop_count: usize,
     pub return_component_opt: Option<Component>,
+    // Who pays for the route, when it isn't the Node's own consuming wallet
+    pub consuming_wallet_opt: Option<Wallet>,
 }
 
 impl Message for RouteQueryMessage {
@@ -293,6 +347,7 @@ impl RouteQueryMessage {
             target_component: Component::ProxyClient,
             minimum_hop_count,
             return_component_opt: Some(Component::ProxyServer),
+            consuming_wallet_opt: None,
         }
     }
 }
@@ -326,6 +381,39 @@ pub enum NodeRecordMetadataMessage {
     Desirable(PublicKey, bool),
 }
 
+// Sent by the Accountant when a debtor's consuming wallet is banned for delinquency or, once it
+// has paid down its debt, unbanned.
+#[derive(Clone, Debug, Message, PartialEq)]
+pub enum DelinquencyMessage {
+    Ban(Wallet),
+    Unban(Wallet),
+}
+
+// Sent by the ProxyClient when a Node opens a stream through us and pays for it, so that a ban on
+// that consuming wallet can be laid on the Node behind it.
+#[derive(Clone, Debug, Message, PartialEq)]
+pub struct DebtorKeyMessage {
+    pub consuming_wallet: Wallet,
+    pub public_key: PublicKey,
+}
+
+// Sent by the ProxyServer when an exit Node tells us, over the return route of one of our streams,
+// what our consuming wallet owes it, and whether it has seen other Nodes paying with that wallet.
+#[derive(Clone, Debug, Message, PartialEq)]
+pub struct BillingClaimMessage {
+    pub creditor_key: PublicKey,
+    pub claimed_gwub: u64,
+    pub shared_wallet: bool,
+}
+
+// Sent by the Accountant when it disputes a creditor's billing claim and, once the dispute has
+// run its course, when it closes it. We don't route through a creditor while it's disputed.
+#[derive(Clone, Debug, Message, PartialEq)]
+pub enum DisputeMessage {
+    Open(Wallet),
+    Close(Wallet),
+}
+
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct RatePack {
     pub routing_byte_rate: u64,
@@ -369,6 +457,13 @@ mod tests {
             stream_shutdown_sub: recipient!(recorder, StreamShutdownMsg),
             set_consuming_wallet_sub: recipient!(recorder, SetConsumingWalletMessage),
             from_ui_gateway: recipient!(recorder, NeighborhoodDotGraphRequest),
+            neighbor_list_sub: recipient!(recorder, NeighborListRequest),
+            delinquency_sub: recipient!(recorder, DelinquencyMessage),
+            debtor_key_sub: recipient!(recorder, DebtorKeyMessage),
+            billing_claim_sub: recipient!(recorder, BillingClaimMessage),
+            dispute_sub: recipient!(recorder, DisputeMessage),
+            node_statistics_sub: recipient!(recorder, GetNodeStatisticsMessage),
+            health_sub: recipient!(recorder, GetHealthMessage),
         };
 
         assert_eq!(format!("{:?}", subject), "NeighborhoodSubs");
@@ -407,6 +502,26 @@ mod tests {
         assert_eq!(result, Err(String::from("R29vZEtleQ==:BadNodeAddr")));
     }
 
+    #[test]
+    fn node_descriptor_from_str_accepts_a_shared_uri() {
+        let result = NodeDescriptor::from_str(
+            cryptde(),
+            "substratum://R29vZEtleQ:1.2.3.4:1234;2345",
+            DEFAULT_CHAIN_ID,
+        );
+
+        assert_eq!(
+            result.unwrap(),
+            NodeDescriptor {
+                public_key: PublicKey::new(b"GoodKey"),
+                node_addr_opt: Some(NodeAddr::new(
+                    &IpAddr::from_str("1.2.3.4").unwrap(),
+                    &vec!(1234, 2345),
+                ))
+            },
+        )
+    }
+
     #[test]
     fn node_descriptor_from_str_handles_the_happy_path_with_node_addr() {
         let result = NodeDescriptor::from_str(
@@ -503,6 +618,7 @@ mod tests {
                 target_component: Component::ProxyClient,
                 minimum_hop_count: 2,
                 return_component_opt: Some(Component::ProxyServer),
+                consuming_wallet_opt: None,
             }
         );
     }
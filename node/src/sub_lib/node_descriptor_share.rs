@@ -0,0 +1,122 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use qrcode::QrCode;
+
+pub const NODE_DESCRIPTOR_URI_SCHEME: &str = "substratum://";
+
+// Pixels per QR module in a PNG; big enough for a phone camera to pick up from a screen
+const PNG_MODULE_PIXELS: u32 = 8;
+
+pub fn node_descriptor_uri(descriptor: &str) -> String {
+    let encoded: String = descriptor
+        .bytes()
+        .map(|byte| {
+            if is_uri_safe(byte) {
+                (byte as char).to_string()
+            } else {
+                format!("%{:02X}", byte)
+            }
+        })
+        .collect();
+    format!("{}{}", NODE_DESCRIPTOR_URI_SCHEME, encoded)
+}
+
+// Anything that isn't a substratum:// URI is taken to be a bare descriptor already
+pub fn node_descriptor_from_uri(uri_or_descriptor: &str) -> Result<String, String> {
+    if !uri_or_descriptor.starts_with(NODE_DESCRIPTOR_URI_SCHEME) {
+        return Ok(uri_or_descriptor.to_string());
+    }
+    let encoded = uri_or_descriptor[NODE_DESCRIPTOR_URI_SCHEME.len()..].trim_end_matches('/');
+    let mut bytes = vec![];
+    let mut remaining = encoded.bytes();
+    while let Some(byte) = remaining.next() {
+        if byte == b'%' {
+            let hex: String = remaining.by_ref().take(2).map(|b| b as char).collect();
+            match u8::from_str_radix(&hex, 16) {
+                Ok(decoded) if hex.len() == 2 => bytes.push(decoded),
+                _ => return Err(format!("Bad escape '%{}' in {}", hex, uri_or_descriptor)),
+            }
+        } else {
+            bytes.push(byte)
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| format!("{} isn't a node descriptor", uri_or_descriptor))
+}
+
+// Two characters to a module, so the code stays square in a terminal. The colors are swapped,
+// because a terminal draws light on dark.
+pub fn qr_code_text(data: &str) -> Result<String, String> {
+    let code = QrCode::new(data.as_bytes()).map_err(|e| format!("{}", e))?;
+    Ok(code
+        .render::<char>()
+        .dark_color(' ')
+        .light_color('\u{2588}')
+        .module_dimensions(2, 1)
+        .build())
+}
+
+pub fn qr_code_png(data: &str) -> Result<Vec<u8>, String> {
+    let code = QrCode::new(data.as_bytes()).map_err(|e| format!("{}", e))?;
+    let image = code
+        .render::<image::Luma<u8>>()
+        .module_dimensions(PNG_MODULE_PIXELS, PNG_MODULE_PIXELS)
+        .build();
+    let mut png = vec![];
+    image::DynamicImage::ImageLuma8(image)
+        .write_to(&mut png, image::ImageOutputFormat::PNG)
+        .map_err(|e| format!("{}", e))?;
+    Ok(png)
+}
+
+// Base64 keys can contain '/' and '+', which mean something else in a URI
+fn is_uri_safe(byte: u8) -> bool {
+    match byte {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => true,
+        b'-' | b'.' | b'_' | b'~' | b':' | b'@' | b';' => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn descriptors_survive_a_trip_through_a_uri() {
+        let descriptor = "AQ+D/A:1.2.3.4:1234;2345";
+
+        let uri = node_descriptor_uri(descriptor);
+
+        assert_eq!(uri, "substratum://AQ%2BD%2FA:1.2.3.4:1234;2345");
+        assert_eq!(node_descriptor_from_uri(&uri), Ok(descriptor.to_string()));
+    }
+
+    #[test]
+    fn bare_descriptors_pass_through_untouched() {
+        assert_eq!(
+            node_descriptor_from_uri("R29vZEtleQ@1.2.3.4:1234"),
+            Ok("R29vZEtleQ@1.2.3.4:1234".to_string())
+        );
+    }
+
+    #[test]
+    fn bad_escapes_are_rejected() {
+        assert_eq!(
+            node_descriptor_from_uri("substratum://AQ%2"),
+            Err("Bad escape '%2' in substratum://AQ%2".to_string())
+        );
+        assert_eq!(
+            node_descriptor_from_uri("substratum://AQ%G0"),
+            Err("Bad escape '%G0' in substratum://AQ%G0".to_string())
+        );
+    }
+
+    #[test]
+    fn qr_codes_can_be_drawn_as_text_and_as_png() {
+        let text = qr_code_text("substratum://AQIDBA:1.2.3.4:1234").unwrap();
+        let png = qr_code_png("substratum://AQIDBA:1.2.3.4:1234").unwrap();
+
+        assert!(text.lines().count() > 10, "{}", text);
+        assert!(text.contains('█'), "{}", text);
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    }
+}
@@ -0,0 +1,204 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::sub_lib::random::Random;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+const PADDING_CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+// Randomized delays and padding between clandestine frames make it harder for an observer to
+// fingerprint the websites being visited, or to correlate flows entering and leaving the Network,
+// by looking at packet sizes and timing. Both are off by default.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct ObfuscationConfig {
+    pub max_jitter: Duration,
+    pub max_padding: usize,
+}
+
+impl ObfuscationConfig {
+    pub fn is_active(&self) -> bool {
+        self.max_jitter > Duration::from_millis(0) || self.max_padding > 0
+    }
+
+    pub fn random_jitter(&self, random: &dyn Random) -> Duration {
+        let max_micros = self.max_jitter.as_micros() as u64;
+        if max_micros == 0 {
+            return Duration::from_millis(0);
+        }
+        Duration::from_micros(random.range(0, max_micros + 1))
+    }
+
+    pub fn random_padding(&self, random: &dyn Random) -> String {
+        if self.max_padding == 0 {
+            return String::new();
+        }
+        let len = random.range(0, self.max_padding as u64 + 1);
+        (0..len)
+            .map(|_| PADDING_CHARS[random.range(0, PADDING_CHARS.len() as u64) as usize] as char)
+            .collect()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ObfuscationMetrics {
+    frames: AtomicU64,
+    payload_bytes: AtomicU64,
+    padding_bytes: AtomicU64,
+    delayed_frames: AtomicU64,
+    jitter_micros: AtomicU64,
+}
+
+impl ObfuscationMetrics {
+    pub fn new() -> ObfuscationMetrics {
+        ObfuscationMetrics::default()
+    }
+
+    pub fn record_padding(&self, payload_len: usize, padding_len: usize) {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        self.payload_bytes
+            .fetch_add(payload_len as u64, Ordering::Relaxed);
+        self.padding_bytes
+            .fetch_add(padding_len as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_jitter(&self, jitter: Duration) {
+        self.delayed_frames.fetch_add(1, Ordering::Relaxed);
+        self.jitter_micros
+            .fetch_add(jitter.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    pub fn payload_bytes(&self) -> u64 {
+        self.payload_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn padding_bytes(&self) -> u64 {
+        self.padding_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn delayed_frames(&self) -> u64 {
+        self.delayed_frames.load(Ordering::Relaxed)
+    }
+
+    pub fn total_jitter(&self) -> Duration {
+        Duration::from_micros(self.jitter_micros.load(Ordering::Relaxed))
+    }
+}
+
+impl Display for ObfuscationMetrics {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let padding_percent = match self.payload_bytes() {
+            0 => 0.0,
+            payload_bytes => (self.padding_bytes() as f64) * 100.0 / (payload_bytes as f64),
+        };
+        let average_jitter_ms = match self.delayed_frames() {
+            0 => 0.0,
+            delayed_frames => {
+                (self.total_jitter().as_micros() as f64) / 1000.0 / (delayed_frames as f64)
+            }
+        };
+        write!(
+            f,
+            "{} frames masked: {} padding bytes added to {} payload bytes ({:.1}% overhead); {} frames delayed by {} ms total ({:.1} ms average)",
+            self.frames(),
+            self.padding_bytes(),
+            self.payload_bytes(),
+            padding_percent,
+            self.delayed_frames(),
+            self.total_jitter().as_millis(),
+            average_jitter_ms
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sub_lib::random::RandomReal;
+    use crate::test_utils::random_seeded::RandomSeeded;
+
+    #[test]
+    fn default_config_is_inactive() {
+        let subject = ObfuscationConfig::default();
+
+        assert_eq!(subject.is_active(), false);
+        assert_eq!(
+            subject.random_jitter(&RandomReal {}),
+            Duration::from_millis(0)
+        );
+        assert_eq!(subject.random_padding(&RandomReal {}), String::new());
+    }
+
+    #[test]
+    fn random_jitter_and_padding_stay_within_limits() {
+        let subject = ObfuscationConfig {
+            max_jitter: Duration::from_millis(5),
+            max_padding: 10,
+        };
+
+        assert_eq!(subject.is_active(), true);
+        (0..100).for_each(|_| {
+            assert!(subject.random_jitter(&RandomReal {}) <= Duration::from_millis(5));
+            let padding = subject.random_padding(&RandomReal {});
+            assert!(padding.len() <= 10);
+            assert!(padding.chars().all(|c| c.is_ascii_alphanumeric()));
+        });
+    }
+
+    #[test]
+    fn random_jitter_and_padding_repeat_with_the_same_seed() {
+        let subject = ObfuscationConfig {
+            max_jitter: Duration::from_millis(5),
+            max_padding: 10,
+        };
+        let choose = |random: &RandomSeeded| {
+            (0..10)
+                .map(|_| {
+                    (
+                        subject.random_jitter(random),
+                        subject.random_padding(random),
+                    )
+                })
+                .collect::<Vec<(Duration, String)>>()
+        };
+
+        let first = choose(&RandomSeeded::new(1234));
+        let second = choose(&RandomSeeded::new(1234));
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn metrics_accumulate_and_display() {
+        let subject = ObfuscationMetrics::new();
+
+        subject.record_padding(100, 10);
+        subject.record_padding(300, 30);
+        subject.record_jitter(Duration::from_millis(3));
+        subject.record_jitter(Duration::from_millis(5));
+
+        assert_eq!(subject.frames(), 2);
+        assert_eq!(subject.payload_bytes(), 400);
+        assert_eq!(subject.padding_bytes(), 40);
+        assert_eq!(subject.delayed_frames(), 2);
+        assert_eq!(subject.total_jitter(), Duration::from_millis(8));
+        assert_eq!(
+            subject.to_string(),
+            "2 frames masked: 40 padding bytes added to 400 payload bytes (10.0% overhead); 2 frames delayed by 8 ms total (4.0 ms average)"
+        );
+    }
+
+    #[test]
+    fn empty_metrics_display_without_dividing_by_zero() {
+        let subject = ObfuscationMetrics::new();
+
+        assert_eq!(
+            subject.to_string(),
+            "0 frames masked: 0 padding bytes added to 0 payload bytes (0.0% overhead); 0 frames delayed by 0 ms total (0.0 ms average)"
+        );
+    }
+}
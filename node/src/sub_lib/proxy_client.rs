@@ -5,7 +5,10 @@ use crate::sub_lib::hopper::{ExpiredCoresPackage, MessageType};
 use crate::sub_lib::peer_actors::BindMessage;
 use crate::sub_lib::proxy_server::ClientRequestPayload;
 use crate::sub_lib::sequence_buffer::SequencedPacket;
+use crate::sub_lib::spill_file::SpillConfig;
 use crate::sub_lib::stream_key::StreamKey;
+use crate::sub_lib::ui_gateway::GetNodeStatisticsMessage;
+use crate::sub_lib::wallet::Wallet;
 use actix::Message;
 use actix::Recipient;
 use serde_derive::{Deserialize, Serialize};
@@ -18,12 +21,38 @@ pub fn error_socket_addr() -> SocketAddr {
     SocketAddr::from(SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), 0))
 }
 
+pub const DEFAULT_EXIT_FAN_OUT_LIMIT: usize = 200;
+
 #[derive(Clone)]
 pub struct ProxyClientConfig {
     pub cryptde: &'static dyn CryptDE,
     pub dns_servers: Vec<SocketAddr>,
     pub exit_service_rate: u64,
     pub exit_byte_rate: u64,
+    pub exit_protections: ExitProtections,
+    pub spill_config: SpillConfig,
+}
+
+// What an exit Node won't open streams for, so that its operator hears less from abuse desks
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ExitProtections {
+    pub block_smtp: bool,
+    // Refuse the ports mail clients use, as opposed to port 25, which mail servers use
+    pub block_mail: bool,
+    // Different destinations any one originator may open streams to in a minute
+    pub fan_out_limit: usize,
+    pub scan_detection: bool,
+}
+
+impl Default for ExitProtections {
+    fn default() -> Self {
+        ExitProtections {
+            block_smtp: false,
+            block_mail: false,
+            fan_out_limit: DEFAULT_EXIT_FAN_OUT_LIMIT,
+            scan_detection: true,
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
@@ -64,12 +93,59 @@ impl Into<MessageType> for DnsResolveFailure {
     }
 }
 
+// What an exit Node tells the originator of a stream that the originator's consuming wallet owes it.
+// shared_wallet is set when the exit Node has seen more than one Node paying with that wallet, in
+// which case gwub includes the others' debt too. Nodes that predate it leave it out.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct BillingClaimPayload {
+    pub version: DataVersion,
+    pub gwub: u64,
+    #[serde(default)]
+    pub shared_wallet: bool,
+}
+
+impl BillingClaimPayload {
+    pub fn version() -> DataVersion {
+        DataVersion::new(0, 1).expect("Internal Error")
+    }
+
+    pub fn new(gwub: u64, shared_wallet: bool) -> Self {
+        Self {
+            version: Self::version(),
+            gwub,
+            shared_wallet,
+        }
+    }
+}
+
+impl Into<MessageType> for BillingClaimPayload {
+    fn into(self) -> MessageType {
+        MessageType::BillingClaim(self)
+    }
+}
+
+// What the Node with the debtor consuming wallet owes us
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct BillingClaim {
+    pub debtor: Wallet,
+    pub gwub: u64,
+}
+
+// Sent by the Accountant with the claims the ProxyClient should deliver. The only way we have back
+// to a debtor is the return route of a stream it has us exit, so each claim goes there, privately.
+#[derive(Clone, Debug, Message, PartialEq)]
+pub struct BillingClaimsMessage {
+    pub claims: Vec<BillingClaim>,
+}
+
 #[derive(Clone)]
 pub struct ProxyClientSubs {
     pub bind: Recipient<BindMessage>,
     pub from_hopper: Recipient<ExpiredCoresPackage<ClientRequestPayload>>,
     pub inbound_server_data: Recipient<InboundServerData>,
     pub dns_resolve_failed: Recipient<DnsResolveFailure>,
+    pub billing_claims: Recipient<BillingClaimsMessage>,
+    pub node_statistics_sub: Recipient<GetNodeStatisticsMessage>,
 }
 
 impl Debug for ProxyClientSubs {
@@ -108,7 +184,9 @@ pub struct InboundServerData {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::sub_lib::hopper::MessageType;
     use crate::sub_lib::peer_actors::BindMessage;
+    use crate::test_utils::golden_files::golden_hex_to_bytes;
     use crate::test_utils::make_meaningless_stream_key;
     use crate::test_utils::recorder::Recorder;
     use actix::Actor;
@@ -133,6 +211,25 @@ mod tests {
         )
     }
 
+    #[test]
+    fn billing_claim_from_node_without_shared_wallet_is_taken_as_unshared() {
+        let version_0_0 = golden_hex_to_bytes(
+            "a16c42696c6c696e67436c61696da26776657273696f6e830000006467777562\n1a0012d687\n",
+        )
+        .unwrap();
+
+        let result = serde_cbor::de::from_slice::<MessageType>(&version_0_0).unwrap();
+
+        assert_eq!(
+            result,
+            MessageType::BillingClaim(BillingClaimPayload {
+                version: DataVersion::new(0, 0).unwrap(),
+                gwub: 1_234_567,
+                shared_wallet: false,
+            })
+        );
+    }
+
     #[test]
     fn proxy_client_subs_debug() {
         let recorder = Recorder::new().start();
@@ -142,6 +239,8 @@ mod tests {
             from_hopper: recipient!(recorder, ExpiredCoresPackage<ClientRequestPayload>),
             inbound_server_data: recipient!(recorder, InboundServerData),
             dns_resolve_failed: recipient!(recorder, DnsResolveFailure),
+            billing_claims: recipient!(recorder, BillingClaimsMessage),
+            node_statistics_sub: recipient!(recorder, GetNodeStatisticsMessage),
         };
 
         assert_eq!(format!("{:?}", subject), "ProxyClientSubs");
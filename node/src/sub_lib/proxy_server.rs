@@ -1,4 +1,6 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::persistent_configuration::{HTTP_PORT, TLS_PORT};
+use crate::stream_messages::PoolBindMessage;
 use crate::sub_lib::cryptde::PublicKey;
 use crate::sub_lib::data_version::DataVersion;
 use crate::sub_lib::dispatcher::InboundClientData;
@@ -6,10 +8,11 @@ use crate::sub_lib::dispatcher::StreamShutdownMsg;
 use crate::sub_lib::hopper::{ExpiredCoresPackage, MessageType};
 use crate::sub_lib::neighborhood::{ExpectedService, RouteQueryResponse};
 use crate::sub_lib::peer_actors::BindMessage;
-use crate::sub_lib::proxy_client::{ClientResponsePayload, DnsResolveFailure};
+use crate::sub_lib::proxy_client::{BillingClaimPayload, ClientResponsePayload, DnsResolveFailure};
 use crate::sub_lib::sequence_buffer::SequencedPacket;
 use crate::sub_lib::set_consuming_wallet_message::SetConsumingWalletMessage;
 use crate::sub_lib::stream_key::StreamKey;
+use crate::sub_lib::ui_gateway::GetNodeStatisticsMessage;
 use actix::Message;
 use actix::Recipient;
 use serde_derive::{Deserialize, Serialize};
@@ -19,6 +22,10 @@ use std::fmt::Debug;
 pub enum ProxyProtocol {
     HTTP,
     TLS,
+    // Mail submission: TLS, but the exit has to say STARTTLS to the mail server first
+    SMTP,
+    // The control connection of a forwarded FTP port, whose passive replies open data tunnels
+    FTP,
 }
 
 // TODO: Based on the way it's used, this struct should comprise two elements: one, a nested
@@ -61,6 +68,23 @@ pub struct AddRouteMessage {
     pub route: RouteQueryResponse,
 }
 
+// The Neighborhood couldn't build a route of the minimum hop count for a new stream
+#[derive(Message, Debug, PartialEq)]
+pub struct RouteFailureMessage {}
+
+// The listener for an FTP data connection has stopped listening
+#[derive(Message, Debug, PartialEq)]
+pub struct PassiveTunnelExpiredMessage {
+    pub local_port: u16,
+}
+
+// Sent by the Accountant when a consuming profile has used up its budget, so that the profile's
+// connections are refused from then on
+#[derive(Message, Clone, Debug, PartialEq)]
+pub struct ConsumingProfileExhaustedMessage {
+    pub name: String,
+}
+
 #[derive(Clone)]
 pub struct ProxyServerSubs {
     // ProxyServer will handle these messages:
@@ -68,10 +92,14 @@ pub struct ProxyServerSubs {
     pub from_dispatcher: Recipient<InboundClientData>,
     pub from_hopper: Recipient<ExpiredCoresPackage<ClientResponsePayload>>,
     pub dns_failure_from_hopper: Recipient<ExpiredCoresPackage<DnsResolveFailure>>,
+    pub billing_claim_from_hopper: Recipient<ExpiredCoresPackage<BillingClaimPayload>>,
     pub add_return_route: Recipient<AddReturnRouteMessage>,
     pub add_route: Recipient<AddRouteMessage>,
     pub stream_shutdown_sub: Recipient<StreamShutdownMsg>,
     pub set_consuming_wallet_sub: Recipient<SetConsumingWalletMessage>,
+    pub node_statistics_sub: Recipient<GetNodeStatisticsMessage>,
+    pub pool_bind: Recipient<PoolBindMessage>,
+    pub profile_exhausted_sub: Recipient<ConsumingProfileExhaustedMessage>,
 }
 
 impl Debug for ProxyServerSubs {
@@ -80,6 +108,47 @@ impl Debug for ProxyServerSubs {
     }
 }
 
+// A local port whose connections are carried, byte for byte, to a fixed host and port beyond the
+// exit Node
+#[derive(Clone, Debug, PartialEq)]
+pub struct PortForward {
+    pub local_port: u16,
+    pub remote_host: String,
+    pub remote_port: u16,
+}
+
+impl PortForward {
+    // Takes LOCAL-PORT:HOST:PORT
+    pub fn parse(spec: &str) -> Result<PortForward, String> {
+        let parse_port = |port: &str| port.parse::<u16>().ok().filter(|port| *port > 0);
+        let (local_port, remote_host, remote_port) = match (spec.find(':'), spec.rfind(':')) {
+            (Some(first), Some(last)) if first < last => (
+                parse_port(&spec[..first]),
+                &spec[(first + 1)..last],
+                parse_port(&spec[(last + 1)..]),
+            ),
+            _ => (None, "", None),
+        };
+        match (local_port, remote_port) {
+            (Some(local_port), Some(_)) if local_port == HTTP_PORT || local_port == TLS_PORT => {
+                Err(format!(
+                    "Port {} is where the Node takes browser traffic; forward from another: '{}'",
+                    local_port, spec
+                ))
+            }
+            (Some(local_port), Some(remote_port)) if !remote_host.is_empty() => Ok(PortForward {
+                local_port,
+                remote_host: remote_host.to_string(),
+                remote_port,
+            }),
+            _ => Err(format!(
+                "Port forward must look like LOCAL-PORT:HOST:PORT: '{}'",
+                spec
+            )),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -96,12 +165,53 @@ mod tests {
             from_dispatcher: recipient!(recorder, InboundClientData),
             from_hopper: recipient!(recorder, ExpiredCoresPackage<ClientResponsePayload>),
             dns_failure_from_hopper: recipient!(recorder, ExpiredCoresPackage<DnsResolveFailure>),
+            billing_claim_from_hopper: recipient!(
+                recorder,
+                ExpiredCoresPackage<BillingClaimPayload>
+            ),
             add_return_route: recipient!(recorder, AddReturnRouteMessage),
             add_route: recipient!(recorder, AddRouteMessage),
             stream_shutdown_sub: recipient!(recorder, StreamShutdownMsg),
             set_consuming_wallet_sub: recipient!(recorder, SetConsumingWalletMessage),
+            node_statistics_sub: recipient!(recorder, GetNodeStatisticsMessage),
+            pool_bind: recipient!(recorder, PoolBindMessage),
+            profile_exhausted_sub: recipient!(recorder, ConsumingProfileExhaustedMessage),
         };
 
         assert_eq!(format!("{:?}", subject), "ProxyServerSubs");
     }
+
+    #[test]
+    fn port_forward_parses_local_port_remote_host_and_remote_port() {
+        assert_eq!(
+            PortForward::parse("5432:db.example.com:5433"),
+            Ok(PortForward {
+                local_port: 5432,
+                remote_host: "db.example.com".to_string(),
+                remote_port: 5433,
+            })
+        );
+        vec![
+            "5432",
+            "5432:db.example.com",
+            "5432::5433",
+            "0:db.example.com:5433",
+            "5432:db.example.com:70000",
+            "local:db.example.com:5433",
+        ]
+        .into_iter()
+        .for_each(|spec| {
+            assert_eq!(
+                PortForward::parse(spec),
+                Err(format!(
+                    "Port forward must look like LOCAL-PORT:HOST:PORT: '{}'",
+                    spec
+                ))
+            )
+        });
+        assert_eq!(
+            PortForward::parse("443:db.example.com:5433"),
+            Err("Port 443 is where the Node takes browser traffic; forward from another: '443:db.example.com:5433'".to_string())
+        );
+    }
 }
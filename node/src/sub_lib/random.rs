@@ -0,0 +1,49 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use rand::{Rng, RngCore};
+
+/// Where code that makes random choices gets its randomness, so that tests can have the same
+/// choices made every time. `range` picks a number at least `low` and less than `high`.
+pub trait Random: Send {
+    fn fill(&self, dest: &mut [u8]);
+    fn range(&self, low: u64, high: u64) -> u64;
+}
+
+pub struct RandomReal {}
+
+impl Random for RandomReal {
+    fn fill(&self, dest: &mut [u8]) {
+        rand::thread_rng().fill_bytes(dest)
+    }
+
+    fn range(&self, low: u64, high: u64) -> u64 {
+        rand::thread_rng().gen_range(low, high)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_real_fills_with_different_bytes_each_time() {
+        let subject = RandomReal {};
+        let mut first = [0u8; 32];
+        let mut second = [0u8; 32];
+
+        subject.fill(&mut first);
+        subject.fill(&mut second);
+
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn random_real_stays_in_range() {
+        let subject = RandomReal {};
+
+        (0..100).for_each(|_| {
+            let result = subject.range(10, 13);
+            assert!(result >= 10 && result < 13, "{} is out of range", result);
+        });
+    }
+}
@@ -0,0 +1,55 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::sub_lib::framer::FramedChunk;
+use crate::sub_lib::framer::Framer;
+
+// For streams with no protocol the Node understands: whatever has arrived is a frame
+#[derive(Default)]
+pub struct RawFramer {
+    data_so_far: Vec<u8>,
+}
+
+impl Framer for RawFramer {
+    fn add_data(&mut self, data: &[u8]) {
+        self.data_so_far.extend(data);
+    }
+
+    fn take_frame(&mut self) -> Option<FramedChunk> {
+        if self.data_so_far.is_empty() {
+            None
+        } else {
+            Some(FramedChunk {
+                chunk: self.data_so_far.split_off(0),
+                last_chunk: false,
+            })
+        }
+    }
+}
+
+impl RawFramer {
+    pub fn new() -> RawFramer {
+        RawFramer::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_everything_that_has_arrived_as_one_frame() {
+        let mut subject = RawFramer::new();
+        subject.add_data(b"SSH-2.0-");
+        subject.add_data(b"OpenSSH\r\n");
+
+        let result = subject.take_frame();
+
+        assert_eq!(
+            result,
+            Some(FramedChunk {
+                chunk: b"SSH-2.0-OpenSSH\r\n".to_vec(),
+                last_chunk: false,
+            })
+        );
+        assert_eq!(subject.take_frame(), None);
+    }
+}
@@ -351,8 +351,12 @@ pub enum RouteError {
 mod tests {
     use super::*;
     use crate::blockchain::blockchain_interface::contract_address;
+    use crate::golden_test;
     use crate::sub_lib::cryptde_null::CryptDENull;
+    use crate::test_utils::cores_package_strategies::{garbage, route};
+    use crate::test_utils::golden_files::{golden_cryptde, golden_route};
     use crate::test_utils::{cryptde, make_paying_wallet, make_wallet, DEFAULT_CHAIN_ID};
+    use proptest::prelude::*;
     use serde_cbor;
 
     #[test]
@@ -687,8 +691,7 @@ mod tests {
                 Component::Hopper
             )
         );
-        let mut garbage_can: Vec<u8> = iter::repeat(0u8).take(top_hop_len).collect();
-        cryptde.random(&mut garbage_can[..]);
+        let garbage_can = vec![b'4'; top_hop_len];
         let empty_public_key = PublicKey::new(b"");
         assert_eq!(
             subject.hops,
@@ -825,4 +828,29 @@ Encrypted with AQIDBA: Return Route ID: 1234
 
         assert_eq!(result, String::from("\n"));
     }
+
+    proptest! {
+        #[test]
+        fn shift_refuses_a_garbled_hop_without_panicking(
+            (keys, route) in route(),
+            garbage in garbage()
+        ) {
+            let cryptde = CryptDENull::from(&keys[0], DEFAULT_CHAIN_ID);
+            let mut subject = route;
+            // Still encrypted for the right Node, so only what's inside is wrong
+            subject.hops[0] =
+                CryptData::new(&[cryptde.private_key().as_slice(), &garbage[..]].concat());
+
+            let result = subject.shift(&cryptde);
+
+            prop_assert!(result.is_err());
+        }
+    }
+
+    golden_test!(golden_route_round_trip, golden_route());
+
+    golden_test!(
+        golden_route_single_hop,
+        Route::single_hop(&PublicKey::new(&[4, 3, 2, 1]), &golden_cryptde()).unwrap()
+    );
 }
@@ -1,6 +1,8 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
 use crate::sub_lib::logger::Logger;
+use crate::sub_lib::memory_guard::{MemoryGuard, MEMORY_GUARD};
+use crate::sub_lib::spill_file::{SpillConfig, SpillFile};
 use crate::sub_lib::stream_handler_pool::TransmitDataMsg;
 use crate::sub_lib::utils;
 use serde::de::Visitor;
@@ -145,11 +147,17 @@ impl SequencedPacketVisitor {
     }
 }
 
-#[derive(Clone)]
 pub struct SequenceBuffer {
     // BinaryHeap is a Priority Queue implemented with a heap. The priority queue allows
     // SequencedPackets to come in in any order and be retrieved in a sorted order.
     buffer: BinaryHeap<SequencedPacket>,
+    buffered_bytes: usize,
+    // Packets that arrive while buffered_bytes is over the memory limit wait here instead, until
+    // the stream's spill limit or the whole Node's is reached too
+    spill_config: SpillConfig,
+    spill_file_opt: Option<SpillFile>,
+    // Under memory pressure, less of the memory limit may be used
+    memory_guard: &'static MemoryGuard,
     next_expected_sequence_number: u64,
     seen_sequence_numbers: Vec<u64>,
     logger: Logger,
@@ -165,18 +173,39 @@ impl SequenceBuffer {
     pub fn new() -> SequenceBuffer {
         SequenceBuffer {
             buffer: BinaryHeap::new(),
+            buffered_bytes: 0,
+            spill_config: SpillConfig::default(),
+            spill_file_opt: None,
+            memory_guard: &MEMORY_GUARD,
             next_expected_sequence_number: 0,
             seen_sequence_numbers: vec![],
             logger: Logger::new("SequenceBuffer"),
         }
     }
 
-    pub fn push(&mut self, packet: SequencedPacket) {
+    pub fn with_spill(mut self, spill_config: SpillConfig) -> SequenceBuffer {
+        self.spill_config = spill_config;
+        self
+    }
+
+    pub fn with_memory_guard(mut self, memory_guard: &'static MemoryGuard) -> SequenceBuffer {
+        self.memory_guard = memory_guard;
+        self
+    }
+
+    // An error means the stream has more unwritten data than it's allowed to hold, or the data it
+    // couldn't hold in memory couldn't be spilled, and should be given up on: the packet is lost.
+    pub fn push(&mut self, packet: SequencedPacket) -> Result<(), String> {
         if packet.sequence_number >= self.next_expected_sequence_number
             && !self.seen_sequence_numbers.contains(&packet.sequence_number)
         {
+            let should_spill = self.should_spill(&packet)?;
             self.seen_sequence_numbers.push(packet.sequence_number);
-            self.buffer.push(packet);
+            if should_spill {
+                self.spill(packet)?;
+            } else {
+                self.keep(packet);
+            }
         } else {
             warning!(
                 self.logger,
@@ -184,10 +213,13 @@ impl SequenceBuffer {
                 packet.sequence_number
             );
         }
+        Ok(())
     }
 
-    pub fn poll(&mut self) -> Option<SequencedPacket> {
-        if self.buffer.is_empty() {
+    // An error means spilled packets couldn't be read back, and the stream should be given up on
+    pub fn poll(&mut self) -> Result<Option<SequencedPacket>, String> {
+        self.unspill_next_expected()?;
+        Ok(if self.buffer.is_empty() {
             None
         } else {
             let sequence_number_to_pop =
@@ -195,6 +227,9 @@ impl SequenceBuffer {
             if sequence_number_to_pop == self.next_expected_sequence_number {
                 self.next_expected_sequence_number += 1;
                 let packet = self.buffer.pop();
+                if let Some(packet) = packet.as_ref() {
+                    self.buffered_bytes -= packet.data.len();
+                }
 
                 if let Some(index) = utils::index_of(
                     self.seen_sequence_numbers.as_slice(),
@@ -211,7 +246,7 @@ impl SequenceBuffer {
                 );
                 None
             }
-        }
+        })
     }
 
     pub fn repush(&mut self, packet: SequencedPacket) {
@@ -220,7 +255,7 @@ impl SequenceBuffer {
         } else {
             self.next_expected_sequence_number = packet.sequence_number;
             self.seen_sequence_numbers.push(packet.sequence_number);
-            self.buffer.push(packet);
+            self.keep(packet);
         }
     }
 
@@ -229,22 +264,135 @@ impl SequenceBuffer {
     }
 
     pub fn len(&self) -> usize {
-        self.buffer.len()
+        self.buffer.len() + self.spilled_len()
     }
 
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
+
+    pub fn spilled_len(&self) -> usize {
+        self.spill_file_opt
+            .as_ref()
+            .map_or(0, |spill_file| spill_file.len())
+    }
+
+    fn keep(&mut self, packet: SequencedPacket) {
+        self.buffered_bytes += packet.data.len();
+        self.buffer.push(packet);
+    }
+
+    // The packet the stream is waiting for always stays in memory. Over the memory limit, the
+    // rest go to disk; over the stream's spill limit or the Node's as well, there's no room for
+    // them anywhere.
+    fn should_spill(&self, packet: &SequencedPacket) -> Result<bool, String> {
+        if !self.spill_config.is_active()
+            || packet.sequence_number == self.next_expected_sequence_number
+            || self.buffered_bytes + packet.data.len()
+                <= self
+                    .memory_guard
+                    .buffer_memory_limit(self.spill_config.memory_limit)
+        {
+            return Ok(false);
+        }
+        let spilled_bytes = self
+            .spill_file_opt
+            .as_ref()
+            .map_or(0, |spill_file| spill_file.size());
+        if spilled_bytes + packet.data.len() as u64 > self.spill_config.spill_limit {
+            return Err(format!(
+                "{} bytes in memory and {} spilled; no room for packet #{}",
+                self.buffered_bytes, spilled_bytes, packet.sequence_number
+            ));
+        }
+        let budget = &self.spill_config.budget;
+        if !budget.has_room_for(packet.data.len() as u64) {
+            return Err(format!(
+                "{} bytes in memory and the Node's {}-byte spill limit reached; no room for packet #{}",
+                self.buffered_bytes,
+                budget.limit(),
+                packet.sequence_number
+            ));
+        }
+        Ok(true)
+    }
+
+    // Keeping a packet in memory that's over the memory limit would make the limit meaningless
+    fn spill(&mut self, packet: SequencedPacket) -> Result<(), String> {
+        if self.spill_file_opt.is_none() {
+            let directory = self
+                .spill_config
+                .directory_opt
+                .clone()
+                .expect("Spilling without a directory");
+            match SpillFile::create(&directory, self.spill_config.budget.clone()) {
+                Ok(spill_file) => self.spill_file_opt = Some(spill_file),
+                Err(e) => {
+                    return Err(format!(
+                        "Couldn't create spill file in {:?} for packet #{}: {}",
+                        directory, packet.sequence_number, e
+                    ))
+                }
+            }
+        }
+        let spill_file = self
+            .spill_file_opt
+            .as_mut()
+            .expect("Spill file disappeared");
+        spill_file.push(&packet).map_err(|e| {
+            format!(
+                "Couldn't spill packet #{} to {:?}: {}",
+                packet.sequence_number,
+                spill_file.path(),
+                e
+            )
+        })
+    }
+
+    // Everything else stays where it is
+    fn unspill_next_expected(&mut self) -> Result<(), String> {
+        let next_expected = self.next_expected_sequence_number;
+        match self.buffer.peek() {
+            Some(packet) if packet.sequence_number == next_expected => return Ok(()),
+            _ => (),
+        }
+        let spill_file = match self.spill_file_opt.as_mut() {
+            Some(spill_file) => spill_file,
+            None => return Ok(()),
+        };
+        match spill_file.take(next_expected) {
+            Ok(Some(packet)) => self.keep(packet),
+            Ok(None) => (),
+            Err(e) => {
+                let message = format!(
+                    "Lost {} spilled packets: couldn't read #{} back from {:?}: {}",
+                    spill_file.len() + 1,
+                    next_expected,
+                    spill_file.path(),
+                    e
+                );
+                self.spill_file_opt = None;
+                return Err(message);
+            }
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::sub_lib::dispatcher::Endpoint;
+    use crate::sub_lib::memory_guard::tests::make_memory_guard;
+    use crate::sub_lib::memory_guard::SheddingLevel;
+    use crate::sub_lib::spill_file::SpillBudget;
     use crate::sub_lib::stream_handler_pool::TransmitDataMsg;
+    use crate::test_utils::ensure_node_home_directory_exists;
     use crate::test_utils::logging::init_test_logging;
     use crate::test_utils::logging::TestLogHandler;
+    use std::fs;
     use std::net::SocketAddr;
+    use std::path::PathBuf;
     use std::str::FromStr;
 
     #[test]
@@ -300,18 +448,18 @@ mod tests {
 
         let mut subject = SequenceBuffer::new();
 
-        subject.push(b.clone());
-        subject.push(d.clone());
-        subject.push(a.clone());
-        subject.push(e.clone());
-        subject.push(c.clone());
-
-        assert_eq!(subject.poll(), Some(a));
-        assert_eq!(subject.poll(), Some(b));
-        assert_eq!(subject.poll(), Some(c));
-        assert_eq!(subject.poll(), Some(d));
-        assert_eq!(subject.poll(), Some(e));
-        assert_eq!(subject.poll(), None);
+        subject.push(b.clone()).unwrap();
+        subject.push(d.clone()).unwrap();
+        subject.push(a.clone()).unwrap();
+        subject.push(e.clone()).unwrap();
+        subject.push(c.clone()).unwrap();
+
+        assert_eq!(subject.poll().unwrap(), Some(a));
+        assert_eq!(subject.poll().unwrap(), Some(b));
+        assert_eq!(subject.poll().unwrap(), Some(c));
+        assert_eq!(subject.poll().unwrap(), Some(d));
+        assert_eq!(subject.poll().unwrap(), Some(e));
+        assert_eq!(subject.poll().unwrap(), None);
     }
 
     #[test]
@@ -324,21 +472,21 @@ mod tests {
 
         let mut subject = SequenceBuffer::new();
 
-        subject.push(b.clone());
-        assert_eq!(subject.poll(), None);
-        subject.push(d.clone());
-        assert_eq!(subject.poll(), None);
-        subject.push(a.clone());
-        assert_eq!(subject.poll(), Some(a));
-        assert_eq!(subject.poll(), Some(b));
-        assert_eq!(subject.poll(), None);
-        subject.push(e.clone());
-        assert_eq!(subject.poll(), None);
-        subject.push(c.clone());
-        assert_eq!(subject.poll(), Some(c));
-        assert_eq!(subject.poll(), Some(d));
-        assert_eq!(subject.poll(), Some(e));
-        assert_eq!(subject.poll(), None);
+        subject.push(b.clone()).unwrap();
+        assert_eq!(subject.poll().unwrap(), None);
+        subject.push(d.clone()).unwrap();
+        assert_eq!(subject.poll().unwrap(), None);
+        subject.push(a.clone()).unwrap();
+        assert_eq!(subject.poll().unwrap(), Some(a));
+        assert_eq!(subject.poll().unwrap(), Some(b));
+        assert_eq!(subject.poll().unwrap(), None);
+        subject.push(e.clone()).unwrap();
+        assert_eq!(subject.poll().unwrap(), None);
+        subject.push(c.clone()).unwrap();
+        assert_eq!(subject.poll().unwrap(), Some(c));
+        assert_eq!(subject.poll().unwrap(), Some(d));
+        assert_eq!(subject.poll().unwrap(), Some(e));
+        assert_eq!(subject.poll().unwrap(), None);
     }
 
     #[test]
@@ -353,23 +501,23 @@ mod tests {
 
         let mut subject = SequenceBuffer::new();
 
-        subject.push(b.clone());
-        assert_eq!(subject.poll(), None);
-        subject.push(d.clone());
-        assert_eq!(subject.poll(), None);
-        subject.push(b_dup.clone());
-        assert_eq!(subject.poll(), None);
-        subject.push(a.clone());
-        assert_eq!(subject.poll(), Some(a));
-        assert_eq!(subject.poll(), Some(b));
-        assert_eq!(subject.poll(), None);
-        subject.push(e.clone());
-        assert_eq!(subject.poll(), None);
-        subject.push(c.clone());
-        assert_eq!(subject.poll(), Some(c));
-        assert_eq!(subject.poll(), Some(d));
-        assert_eq!(subject.poll(), Some(e));
-        assert_eq!(subject.poll(), None);
+        subject.push(b.clone()).unwrap();
+        assert_eq!(subject.poll().unwrap(), None);
+        subject.push(d.clone()).unwrap();
+        assert_eq!(subject.poll().unwrap(), None);
+        subject.push(b_dup.clone()).unwrap();
+        assert_eq!(subject.poll().unwrap(), None);
+        subject.push(a.clone()).unwrap();
+        assert_eq!(subject.poll().unwrap(), Some(a));
+        assert_eq!(subject.poll().unwrap(), Some(b));
+        assert_eq!(subject.poll().unwrap(), None);
+        subject.push(e.clone()).unwrap();
+        assert_eq!(subject.poll().unwrap(), None);
+        subject.push(c.clone()).unwrap();
+        assert_eq!(subject.poll().unwrap(), Some(c));
+        assert_eq!(subject.poll().unwrap(), Some(d));
+        assert_eq!(subject.poll().unwrap(), Some(e));
+        assert_eq!(subject.poll().unwrap(), None);
         TestLogHandler::new().exists_log_containing(
             "WARN: SequenceBuffer: Dropping packet with duplicate sequence number 1",
         );
@@ -386,32 +534,32 @@ mod tests {
 
         let mut subject = SequenceBuffer::new();
 
-        subject.push(b.clone());
-        assert_eq!(subject.poll(), None);
-        subject.push(d.clone());
-        assert_eq!(subject.poll(), None);
-        subject.push(a.clone());
-        assert_eq!(subject.poll(), Some(a));
-        assert_eq!(subject.poll(), Some(b));
-        assert_eq!(subject.poll(), None);
-        subject.push(e.clone());
-        assert_eq!(subject.poll(), None);
-        subject.push(c.clone());
-        assert_eq!(subject.poll(), Some(c));
-        assert_eq!(subject.poll(), Some(d));
-        subject.push(b_dup.clone());
-        assert_eq!(subject.poll(), Some(e));
-        assert_eq!(subject.poll(), None);
+        subject.push(b.clone()).unwrap();
+        assert_eq!(subject.poll().unwrap(), None);
+        subject.push(d.clone()).unwrap();
+        assert_eq!(subject.poll().unwrap(), None);
+        subject.push(a.clone()).unwrap();
+        assert_eq!(subject.poll().unwrap(), Some(a));
+        assert_eq!(subject.poll().unwrap(), Some(b));
+        assert_eq!(subject.poll().unwrap(), None);
+        subject.push(e.clone()).unwrap();
+        assert_eq!(subject.poll().unwrap(), None);
+        subject.push(c.clone()).unwrap();
+        assert_eq!(subject.poll().unwrap(), Some(c));
+        assert_eq!(subject.poll().unwrap(), Some(d));
+        subject.push(b_dup.clone()).unwrap();
+        assert_eq!(subject.poll().unwrap(), Some(e));
+        assert_eq!(subject.poll().unwrap(), None);
     }
 
     #[test]
     fn sequence_buffer_does_not_explode_when_popping_a_packet_that_seems_unseen() {
         let a = SequencedPacket::new(vec![1, 2, 3], 0, false);
         let mut subject = SequenceBuffer::new();
-        subject.push(a.clone());
+        subject.push(a.clone()).unwrap();
         subject.seen_sequence_numbers.clear();
 
-        let result = subject.poll();
+        let result = subject.poll().unwrap();
 
         assert_eq!(result, Some(a));
     }
@@ -423,14 +571,14 @@ mod tests {
         let b = SequencedPacket::new(vec![5, 9, 1, 2, 5], 2, false);
         let c = SequencedPacket::new(vec![5, 9, 1, 2, 5], 0, false);
 
-        subject.push(a);
-        subject.push(b);
-        subject.push(c.clone());
+        subject.push(a).unwrap();
+        subject.push(b).unwrap();
+        subject.push(c.clone()).unwrap();
 
-        let thing_we_pushed_back = subject.poll().unwrap();
+        let thing_we_pushed_back = subject.poll().unwrap().unwrap();
         assert_eq!(thing_we_pushed_back, c);
         subject.repush(thing_we_pushed_back.clone());
-        assert_eq!(subject.poll().unwrap(), thing_we_pushed_back);
+        assert_eq!(subject.poll().unwrap().unwrap(), thing_we_pushed_back);
     }
 
     #[test]
@@ -441,12 +589,12 @@ mod tests {
         let b = SequencedPacket::new(vec![5, 9, 1, 2, 5], 2, false);
         let c = SequencedPacket::new(vec![5, 9, 1, 2, 5], 0, false);
 
-        subject.push(a);
-        subject.push(b);
-        subject.push(c);
+        subject.push(a).unwrap();
+        subject.push(b).unwrap();
+        subject.push(c).unwrap();
 
-        let first_thing_we_pulled_out = subject.poll().unwrap();
-        let _second_thing_we_pulled_out = subject.poll().unwrap();
+        let first_thing_we_pulled_out = subject.poll().unwrap().unwrap();
+        let _second_thing_we_pulled_out = subject.poll().unwrap().unwrap();
         subject.repush(first_thing_we_pulled_out);
     }
 
@@ -459,21 +607,268 @@ mod tests {
         let b_imposter = SequencedPacket::new(vec![254, 5, 7], 1, false);
         let c = SequencedPacket::new(vec![89], 2, false);
 
-        subject.push(a.clone());
-        subject.push(b.clone());
+        subject.push(a.clone()).unwrap();
+        subject.push(b.clone()).unwrap();
 
-        assert_eq!(subject.poll(), Some(a));
-        assert_eq!(subject.poll(), Some(b.clone()));
+        assert_eq!(subject.poll().unwrap(), Some(a));
+        assert_eq!(subject.poll().unwrap(), Some(b.clone()));
 
         subject.repush(b.clone());
 
-        subject.push(b_imposter);
+        subject.push(b_imposter).unwrap();
+
+        assert_eq!(subject.poll().unwrap(), Some(b));
+        assert_eq!(subject.poll().unwrap(), None);
+
+        subject.push(c.clone()).unwrap();
+        assert_eq!(subject.poll().unwrap(), Some(c));
+    }
+
+    fn spill_config(memory_limit: usize, spill_limit: u64, directory: PathBuf) -> SpillConfig {
+        SpillConfig {
+            memory_limit,
+            spill_limit,
+            budget: SpillBudget::new(1_000_000),
+            directory_opt: Some(directory),
+        }
+    }
+
+    #[test]
+    fn sequence_buffer_spills_packets_over_the_memory_limit_and_returns_them_in_order() {
+        let directory = ensure_node_home_directory_exists(
+            "sequence_buffer",
+            "sequence_buffer_spills_packets_over_the_memory_limit_and_returns_them_in_order",
+        );
+        let packets = (0..5)
+            .map(|sequence_number| SequencedPacket::new(vec![0x5A; 4], sequence_number, false))
+            .collect::<Vec<SequencedPacket>>();
+        let mut subject =
+            SequenceBuffer::new().with_spill(spill_config(10, 1_000_000, directory.clone()));
+
+        packets
+            .iter()
+            .for_each(|packet| subject.push(packet.clone()).unwrap());
+
+        assert_eq!(subject.len(), 5);
+        assert_eq!(subject.spilled_len(), 3);
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 1);
+        packets
+            .into_iter()
+            .for_each(|packet| assert_eq!(subject.poll().unwrap(), Some(packet)));
+        assert_eq!(subject.poll().unwrap(), None);
+        assert_eq!(subject.spilled_len(), 0);
+        drop(subject);
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 0);
+    }
 
-        assert_eq!(subject.poll(), Some(b));
-        assert_eq!(subject.poll(), None);
+    #[test]
+    fn sequence_buffer_spills_sooner_once_buffers_are_tightened() {
+        let directory = ensure_node_home_directory_exists(
+            "sequence_buffer",
+            "sequence_buffer_spills_sooner_once_buffers_are_tightened",
+        );
+        let mut subject = SequenceBuffer::new()
+            .with_spill(spill_config(32, 1_000_000, directory))
+            .with_memory_guard(make_memory_guard(SheddingLevel::TightenBuffers));
+
+        (0..5).for_each(|sequence_number| {
+            subject
+                .push(SequencedPacket::new(vec![0; 4], sequence_number, false))
+                .unwrap()
+        });
+
+        assert_eq!(subject.len(), 5);
+        assert_eq!(subject.spilled_len(), 4);
+    }
+
+    #[test]
+    fn sequence_buffer_unspills_the_packet_it_is_waiting_for() {
+        let directory = ensure_node_home_directory_exists(
+            "sequence_buffer",
+            "sequence_buffer_unspills_the_packet_it_is_waiting_for",
+        );
+        let a = SequencedPacket::new(vec![1, 2, 3, 4], 0, false);
+        let b = SequencedPacket::new(vec![5, 6, 7, 8], 1, false);
+        let c = SequencedPacket::new(vec![9, 10, 11, 12], 2, false);
+        let d = SequencedPacket::new(vec![13, 14, 15, 16], 3, false);
+        let mut subject = SequenceBuffer::new().with_spill(spill_config(4, 1_000_000, directory));
+
+        subject.push(b.clone()).unwrap();
+        subject.push(c.clone()).unwrap();
+        subject.push(d.clone()).unwrap();
+        subject.push(a.clone()).unwrap();
+
+        assert_eq!(subject.spilled_len(), 2);
+        assert_eq!(subject.poll().unwrap(), Some(a));
+        assert_eq!(subject.poll().unwrap(), Some(b));
+        assert_eq!(subject.poll().unwrap(), Some(c));
+        assert_eq!(subject.poll().unwrap(), Some(d));
+        assert_eq!(subject.poll().unwrap(), None);
+    }
 
-        subject.push(c.clone());
-        assert_eq!(subject.poll(), Some(c));
+    #[test]
+    fn sequence_buffer_unspills_only_the_packet_it_is_waiting_for() {
+        let directory = ensure_node_home_directory_exists(
+            "sequence_buffer",
+            "sequence_buffer_unspills_only_the_packet_it_is_waiting_for",
+        );
+        let packets = (0..4)
+            .map(|sequence_number| SequencedPacket::new(vec![0x5A; 4], sequence_number, false))
+            .collect::<Vec<SequencedPacket>>();
+        let mut subject = SequenceBuffer::new().with_spill(spill_config(4, 1_000_000, directory));
+        subject.push(packets[0].clone()).unwrap();
+        packets[1..]
+            .iter()
+            .rev()
+            .for_each(|packet| subject.push(packet.clone()).unwrap());
+
+        assert_eq!(subject.spilled_len(), 3);
+        assert_eq!(subject.poll().unwrap(), Some(packets[0].clone()));
+        assert_eq!(subject.spilled_len(), 3);
+        assert_eq!(subject.poll().unwrap(), Some(packets[1].clone()));
+        assert_eq!(subject.spilled_len(), 2);
+        assert_eq!(subject.poll().unwrap(), Some(packets[2].clone()));
+        assert_eq!(subject.spilled_len(), 1);
+        assert_eq!(subject.poll().unwrap(), Some(packets[3].clone()));
+        assert_eq!(subject.spilled_len(), 0);
+    }
+
+    #[test]
+    fn sequence_buffer_refuses_a_packet_it_cannot_spill() {
+        let home_dir = ensure_node_home_directory_exists(
+            "sequence_buffer",
+            "sequence_buffer_refuses_a_packet_it_cannot_spill",
+        );
+        let not_a_directory = home_dir.join("not_a_directory");
+        fs::write(&not_a_directory, b"").unwrap();
+        let directory = not_a_directory.join("spill");
+        let mut subject =
+            SequenceBuffer::new().with_spill(spill_config(4, 1_000_000, directory.clone()));
+        subject
+            .push(SequencedPacket::new(vec![0; 4], 0, false))
+            .unwrap();
+
+        let result = subject.push(SequencedPacket::new(vec![0; 4], 1, false));
+
+        let message = result.err().unwrap();
+        assert!(
+            message.starts_with(&format!(
+                "Couldn't create spill file in {:?} for packet #1: ",
+                directory
+            )),
+            "{}",
+            message
+        );
+        assert_eq!(subject.len(), 1);
+    }
+
+    #[test]
+    fn sequence_buffer_fails_when_spilled_packets_cannot_be_read_back() {
+        let directory = ensure_node_home_directory_exists(
+            "sequence_buffer",
+            "sequence_buffer_fails_when_spilled_packets_cannot_be_read_back",
+        );
+        let a = SequencedPacket::new(vec![1, 2, 3, 4], 0, false);
+        let mut subject = SequenceBuffer::new().with_spill(spill_config(4, 1_000_000, directory));
+        subject.push(a.clone()).unwrap();
+        subject
+            .push(SequencedPacket::new(vec![5, 6, 7, 8], 1, false))
+            .unwrap();
+        let spill_file = subject.spill_file_opt.as_ref().unwrap();
+        let path = spill_file.path().to_path_buf();
+        fs::write(&path, vec![0; spill_file.size() as usize]).unwrap();
+
+        assert_eq!(subject.poll(), Ok(Some(a)));
+        let result = subject.poll();
+
+        assert_eq!(
+            result,
+            Err(format!(
+                "Lost 1 spilled packets: couldn't read #1 back from {:?}: Spilled packet was tampered with",
+                path
+            ))
+        );
+        assert_eq!(subject.spilled_len(), 0);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn sequence_buffer_refuses_packets_once_the_spill_limit_is_reached() {
+        let directory = ensure_node_home_directory_exists(
+            "sequence_buffer",
+            "sequence_buffer_refuses_packets_once_the_spill_limit_is_reached",
+        );
+        let mut subject = SequenceBuffer::new().with_spill(spill_config(4, 3, directory));
+        subject
+            .push(SequencedPacket::new(vec![0; 4], 0, false))
+            .unwrap();
+
+        let result = subject.push(SequencedPacket::new(vec![0; 4], 1, false));
+
+        assert_eq!(
+            result,
+            Err("4 bytes in memory and 0 spilled; no room for packet #1".to_string())
+        );
+        assert_eq!(subject.len(), 1);
+        assert_eq!(subject.spilled_len(), 0);
+    }
+
+    #[test]
+    fn sequence_buffers_refuse_packets_once_the_nodes_spill_limit_is_reached() {
+        let directory = ensure_node_home_directory_exists(
+            "sequence_buffer",
+            "sequence_buffers_refuse_packets_once_the_nodes_spill_limit_is_reached",
+        );
+        let config = SpillConfig {
+            budget: SpillBudget::new(500),
+            ..spill_config(4, 1_000_000, directory)
+        };
+        let mut first = SequenceBuffer::new().with_spill(config.clone());
+        let mut second = SequenceBuffer::new().with_spill(config.clone());
+        first
+            .push(SequencedPacket::new(vec![0; 4], 1, false))
+            .unwrap();
+        first
+            .push(SequencedPacket::new(vec![0; 40], 2, false))
+            .unwrap();
+        second
+            .push(SequencedPacket::new(vec![0; 4], 1, false))
+            .unwrap();
+
+        let result = second.push(SequencedPacket::new(vec![0; 450], 2, false));
+
+        assert_eq!(
+            result,
+            Err(
+                "4 bytes in memory and the Node's 500-byte spill limit reached; no room for packet #2"
+                    .to_string()
+            )
+        );
+        assert_eq!(first.spilled_len(), 1);
+        assert_eq!(second.spilled_len(), 0);
+        drop(first);
+        assert_eq!(config.budget.spilled(), 0);
+        second
+            .push(SequencedPacket::new(vec![0; 40], 2, false))
+            .unwrap();
+        assert_eq!(second.spilled_len(), 1);
+    }
+
+    #[test]
+    fn sequence_buffer_does_not_spill_without_a_directory() {
+        let mut subject = SequenceBuffer::new().with_spill(SpillConfig {
+            memory_limit: 0,
+            ..SpillConfig::default()
+        });
+
+        (0..3).for_each(|sequence_number| {
+            subject
+                .push(SequencedPacket::new(vec![0; 4], sequence_number, false))
+                .unwrap()
+        });
+
+        assert_eq!(subject.len(), 3);
+        assert_eq!(subject.spilled_len(), 0);
     }
 
     #[test]
@@ -0,0 +1,540 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::sub_lib::sequence_buffer::SequencedPacket;
+use sodiumoxide::crypto::secretbox;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+pub const DEFAULT_BUFFER_MEMORY_LIMIT_KB: usize = 1024;
+pub const DEFAULT_BUFFER_SPILL_LIMIT_MB: u64 = 256;
+pub const DEFAULT_BUFFER_SPILL_TOTAL_LIMIT_MB: u64 = 2048;
+pub const SPILL_DIRECTORY: &str = "spill";
+
+const SPILL_FILE_PREFIX: &str = "stream_";
+// Space left behind by packets taken out of order is reclaimed once there's at least this much of
+// it, and more of it than of packets still waiting
+const MIN_COMPACTION_BYTES: u64 = 64 * 1024;
+
+static NEXT_SPILL_FILE: AtomicUsize = AtomicUsize::new(0);
+
+// How much of a stream's unwritten data may sit in memory before the rest goes to disk
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpillConfig {
+    pub memory_limit: usize,
+    pub spill_limit: u64,
+    pub budget: SpillBudget,
+    pub directory_opt: Option<PathBuf>,
+}
+
+impl Default for SpillConfig {
+    fn default() -> Self {
+        SpillConfig {
+            memory_limit: DEFAULT_BUFFER_MEMORY_LIMIT_KB * 1024,
+            spill_limit: DEFAULT_BUFFER_SPILL_LIMIT_MB * 1024 * 1024,
+            budget: SpillBudget::new(DEFAULT_BUFFER_SPILL_TOTAL_LIMIT_MB * 1024 * 1024),
+            directory_opt: None,
+        }
+    }
+}
+
+// How many bytes all the Node's spill files together may hold. Clones share one count, so every
+// stream spilling under clones of one SpillConfig draws on the same budget.
+#[derive(Clone, Debug)]
+pub struct SpillBudget {
+    limit: u64,
+    spilled: Arc<AtomicU64>,
+}
+
+impl PartialEq for SpillBudget {
+    fn eq(&self, other: &Self) -> bool {
+        self.limit == other.limit
+    }
+}
+
+impl SpillBudget {
+    pub fn new(limit: u64) -> SpillBudget {
+        SpillBudget {
+            limit,
+            spilled: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    pub fn spilled(&self) -> u64 {
+        self.spilled.load(Ordering::SeqCst)
+    }
+
+    pub fn has_room_for(&self, bytes: u64) -> bool {
+        self.spilled() + bytes <= self.limit
+    }
+
+    // False, and nothing reserved, if the bytes would take the Node past its limit
+    pub fn reserve(&self, bytes: u64) -> bool {
+        let mut spilled = self.spilled();
+        loop {
+            if spilled + bytes > self.limit {
+                return false;
+            }
+            match self.spilled.compare_exchange(
+                spilled,
+                spilled + bytes,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => return true,
+                Err(current) => spilled = current,
+            }
+        }
+    }
+
+    pub fn release(&self, bytes: u64) {
+        self.spilled.fetch_sub(bytes, Ordering::SeqCst);
+    }
+}
+
+impl SpillConfig {
+    pub fn is_active(&self) -> bool {
+        self.directory_opt.is_some() && self.spill_limit > 0 && self.budget.limit() > 0
+    }
+
+    // Spill files outlive their streams only if the Node dies; nothing can read them afterward
+    pub fn remove_stale_files(&self) {
+        let directory = match &self.directory_opt {
+            Some(directory) => directory,
+            None => return,
+        };
+        if let Ok(entries) = fs::read_dir(directory) {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| {
+                    entry
+                        .file_name()
+                        .to_string_lossy()
+                        .starts_with(SPILL_FILE_PREFIX)
+                })
+                .for_each(|entry| {
+                    let _ = fs::remove_file(entry.path());
+                });
+        }
+    }
+}
+
+// SequencedPackets on disk, each sealed with a key that exists only in memory, so the file is
+// garbage to anyone who reads it, and is deleted when the SpillFile is dropped. Packets are taken
+// back one at a time by sequence number, in whatever order the stream needs them.
+pub struct SpillFile {
+    path: PathBuf,
+    file: File,
+    key: secretbox::Key,
+    // Where each packet's record starts, and how long it is
+    records: HashMap<u64, (u64, u64)>,
+    write_offset: u64,
+    // The length of all the records in records
+    live_bytes: u64,
+    // Holds live_bytes bytes of it
+    budget: SpillBudget,
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        self.budget.release(self.live_bytes);
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl SpillFile {
+    pub fn create(directory: &Path, budget: SpillBudget) -> io::Result<SpillFile> {
+        sodiumoxide::init()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "Couldn't initialize sodiumoxide"))?;
+        fs::create_dir_all(directory)?;
+        let (path, file) = Self::open_new(directory)?;
+        Ok(SpillFile {
+            path,
+            file,
+            key: secretbox::gen_key(),
+            records: HashMap::new(),
+            write_offset: 0,
+            live_bytes: 0,
+            budget,
+        })
+    }
+
+    fn open_new(directory: &Path) -> io::Result<(PathBuf, File)> {
+        let path = directory.join(format!(
+            "{}{}_{}.bin",
+            SPILL_FILE_PREFIX,
+            process::id(),
+            NEXT_SPILL_FILE.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok((path, file))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Bytes of the packets still to be taken back. The file itself may be bigger, until the space
+    // packets taken back out of order leave behind is reclaimed.
+    pub fn size(&self) -> u64 {
+        self.live_bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn push(&mut self, packet: &SequencedPacket) -> io::Result<()> {
+        let plaintext = serde_cbor::ser::to_vec(packet)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        let nonce = secretbox::gen_nonce();
+        let sealed = secretbox::seal(&plaintext, &nonce, &self.key);
+        let mut record = Vec::with_capacity(nonce.0.len() + sealed.len());
+        record.extend_from_slice(&nonce.0);
+        record.extend_from_slice(&sealed);
+        if !self.budget.reserve(record.len() as u64) {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "{} of the Node's {} spilled bytes are in use",
+                    self.budget.spilled(),
+                    self.budget.limit()
+                ),
+            ));
+        }
+        if let Err(e) = self.write_record(&record) {
+            self.budget.release(record.len() as u64);
+            return Err(e);
+        }
+        self.records.insert(
+            packet.sequence_number,
+            (self.write_offset, record.len() as u64),
+        );
+        self.write_offset += record.len() as u64;
+        self.live_bytes += record.len() as u64;
+        Ok(())
+    }
+
+    fn write_record(&mut self, record: &[u8]) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(self.write_offset))?;
+        self.file.write_all(record)
+    }
+
+    // Reads back only the packet asked for; None if it was never spilled
+    pub fn take(&mut self, sequence_number: u64) -> io::Result<Option<SequencedPacket>> {
+        let (offset, length) = match self.records.remove(&sequence_number) {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+        self.live_bytes -= length;
+        self.budget.release(length);
+        let packet = self.read_record(offset, length, sequence_number);
+        // Reclaiming space is worth a try, but the packet is already out whether it works or not
+        if self.is_empty() {
+            // Nothing left to read: start over at the front instead of growing without end
+            if self.file.set_len(0).is_ok() {
+                self.write_offset = 0;
+            }
+        } else {
+            let dead_bytes = self.write_offset - self.live_bytes;
+            if dead_bytes >= MIN_COMPACTION_BYTES && dead_bytes > self.live_bytes {
+                let _ = self.compact();
+            }
+        }
+        packet.map(Some)
+    }
+
+    // Moves the records still to be taken back into a new file, front to back, and deletes the
+    // old one. If anything goes wrong, the old file is kept as it was.
+    fn compact(&mut self) -> io::Result<()> {
+        let directory = self
+            .path
+            .parent()
+            .expect("Spill file has no directory")
+            .to_path_buf();
+        let (path, mut file) = Self::open_new(&directory)?;
+        let mut records: Vec<(u64, (u64, u64))> = self
+            .records
+            .iter()
+            .map(|(sequence_number, record)| (*sequence_number, *record))
+            .collect();
+        records.sort_by_key(|(_, (offset, _))| *offset);
+        let mut compacted = HashMap::new();
+        let mut write_offset = 0;
+        let copied = records
+            .into_iter()
+            .try_for_each(|(sequence_number, (offset, length))| {
+                let mut record = vec![0u8; length as usize];
+                self.file.seek(SeekFrom::Start(offset))?;
+                self.file.read_exact(&mut record)?;
+                file.write_all(&record)?;
+                compacted.insert(sequence_number, (write_offset, length));
+                write_offset += length;
+                Ok(())
+            });
+        if let Err(e) = copied {
+            let _ = fs::remove_file(&path);
+            return Err(e);
+        }
+        let old_path = std::mem::replace(&mut self.path, path);
+        self.file = file;
+        self.records = compacted;
+        self.write_offset = write_offset;
+        let _ = fs::remove_file(old_path);
+        Ok(())
+    }
+
+    fn read_record(
+        &mut self,
+        offset: u64,
+        length: u64,
+        sequence_number: u64,
+    ) -> io::Result<SequencedPacket> {
+        self.file.seek(SeekFrom::Start(offset))?;
+        let mut record = vec![0u8; length as usize];
+        self.file.read_exact(&mut record)?;
+        let (nonce, sealed) = record.split_at(secretbox::NONCEBYTES);
+        let nonce = secretbox::Nonce::from_slice(nonce).expect("Nonce is the wrong size");
+        let plaintext = secretbox::open(sealed, &nonce, &self.key).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Spilled packet was tampered with",
+            )
+        })?;
+        let packet = serde_cbor::de::from_slice::<SequencedPacket>(&plaintext)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        if packet.sequence_number != sequence_number {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Found packet #{} where #{} was spilled",
+                    packet.sequence_number, sequence_number
+                ),
+            ));
+        }
+        Ok(packet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::ensure_node_home_directory_exists;
+
+    #[test]
+    fn spill_config_is_inactive_without_a_directory_or_a_limit() {
+        let directory = PathBuf::from("/tmp");
+
+        assert_eq!(SpillConfig::default().is_active(), false);
+        assert_eq!(
+            SpillConfig {
+                directory_opt: Some(directory.clone()),
+                ..SpillConfig::default()
+            }
+            .is_active(),
+            true
+        );
+        assert_eq!(
+            SpillConfig {
+                spill_limit: 0,
+                directory_opt: Some(directory),
+                ..SpillConfig::default()
+            }
+            .is_active(),
+            false
+        );
+        assert_eq!(
+            SpillConfig {
+                budget: SpillBudget::new(0),
+                directory_opt: Some(PathBuf::from("/tmp")),
+                ..SpillConfig::default()
+            }
+            .is_active(),
+            false
+        );
+    }
+
+    #[test]
+    fn packets_are_taken_back_one_at_a_time_by_sequence_number() {
+        let directory = ensure_node_home_directory_exists(
+            "spill_file",
+            "packets_are_taken_back_one_at_a_time_by_sequence_number",
+        );
+        let mut subject = SpillFile::create(&directory, SpillBudget::new(1_000_000)).unwrap();
+        let first = SequencedPacket::new(b"first".to_vec(), 3, false);
+        let second = SequencedPacket::new(b"second".to_vec(), 1, true);
+        let third = SequencedPacket::new(b"third".to_vec(), 2, false);
+
+        subject.push(&first).unwrap();
+        subject.push(&second).unwrap();
+        subject.push(&third).unwrap();
+
+        assert_eq!(subject.len(), 3);
+        assert_eq!(subject.size(), fs::metadata(subject.path()).unwrap().len());
+        let size_of_three = subject.size();
+        assert_eq!(subject.take(1).unwrap(), Some(second));
+        assert_eq!(subject.len(), 2);
+        assert!(subject.size() < size_of_three);
+        assert_eq!(subject.budget.spilled(), subject.size());
+        assert_eq!(subject.take(1).unwrap(), None);
+        assert_eq!(subject.take(2).unwrap(), Some(third));
+        assert_eq!(subject.take(3).unwrap(), Some(first));
+        assert_eq!(subject.size(), 0);
+        assert_eq!(fs::metadata(subject.path()).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn space_left_by_packets_taken_out_of_order_is_reclaimed() {
+        let directory = ensure_node_home_directory_exists(
+            "spill_file",
+            "space_left_by_packets_taken_out_of_order_is_reclaimed",
+        );
+        let budget = SpillBudget::new(1_000_000);
+        let mut subject = SpillFile::create(&directory, budget.clone()).unwrap();
+        let packets: Vec<SequencedPacket> = (1..=4)
+            .map(|sequence_number| {
+                SequencedPacket::new(vec![sequence_number as u8; 50_000], sequence_number, false)
+            })
+            .collect();
+        packets
+            .iter()
+            .for_each(|packet| subject.push(packet).unwrap());
+        let old_path = subject.path().to_path_buf();
+
+        assert_eq!(subject.take(2).unwrap(), Some(packets[1].clone()));
+        assert_eq!(subject.take(3).unwrap(), Some(packets[2].clone()));
+        assert_eq!(subject.path(), old_path.as_path());
+        assert_eq!(subject.take(4).unwrap(), Some(packets[3].clone()));
+
+        assert_ne!(subject.path(), old_path.as_path());
+        assert!(!old_path.exists());
+        assert_eq!(subject.size(), fs::metadata(subject.path()).unwrap().len());
+        assert_eq!(budget.spilled(), subject.size());
+        assert_eq!(subject.take(1).unwrap(), Some(packets[0].clone()));
+        subject
+            .push(&SequencedPacket::new(b"more".to_vec(), 5, false))
+            .unwrap();
+        assert_eq!(
+            subject.take(5).unwrap(),
+            Some(SequencedPacket::new(b"more".to_vec(), 5, false))
+        );
+    }
+
+    #[test]
+    fn spilled_data_is_encrypted_and_removed_with_the_file() {
+        let directory = ensure_node_home_directory_exists(
+            "spill_file",
+            "spilled_data_is_encrypted_and_removed_with_the_file",
+        );
+        let mut subject = SpillFile::create(&directory, SpillBudget::new(1_000_000)).unwrap();
+        let secret = b"a secret that must not reach the disk".to_vec();
+        subject
+            .push(&SequencedPacket::new(secret.clone(), 0, false))
+            .unwrap();
+        let path = subject.path().to_path_buf();
+
+        let contents = fs::read(&path).unwrap();
+
+        assert!(!contents
+            .windows(secret.len())
+            .any(|window| window == &secret[..]));
+        drop(subject);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn spill_files_share_the_budget_of_the_config_they_were_cloned_from() {
+        let directory = ensure_node_home_directory_exists(
+            "spill_file",
+            "spill_files_share_the_budget_of_the_config_they_were_cloned_from",
+        );
+        let budget = SpillBudget::new(1_000_000);
+        let mut first = SpillFile::create(&directory, budget.clone()).unwrap();
+        let mut second = SpillFile::create(&directory, budget.clone()).unwrap();
+
+        first
+            .push(&SequencedPacket::new(vec![1; 100], 1, false))
+            .unwrap();
+        second
+            .push(&SequencedPacket::new(vec![2; 200], 1, false))
+            .unwrap();
+
+        assert_eq!(budget.spilled(), first.size() + second.size());
+        first.take(1).unwrap();
+        assert_eq!(budget.spilled(), second.size());
+        drop(second);
+        assert_eq!(budget.spilled(), 0);
+    }
+
+    #[test]
+    fn spill_file_refuses_a_packet_past_the_nodes_budget() {
+        let directory = ensure_node_home_directory_exists(
+            "spill_file",
+            "spill_file_refuses_a_packet_past_the_nodes_budget",
+        );
+        let mut subject = SpillFile::create(&directory, SpillBudget::new(300)).unwrap();
+        subject
+            .push(&SequencedPacket::new(vec![1; 100], 1, false))
+            .unwrap();
+        let size = subject.size();
+
+        let result = subject.push(&SequencedPacket::new(vec![2; 200], 2, false));
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            format!("{} of the Node's 300 spilled bytes are in use", size)
+        );
+        assert_eq!(subject.len(), 1);
+        assert_eq!(subject.size(), size);
+        assert_eq!(subject.budget.spilled(), size);
+    }
+
+    #[test]
+    fn budget_reserves_only_what_fits() {
+        let subject = SpillBudget::new(100);
+
+        assert_eq!(subject.reserve(60), true);
+        assert_eq!(subject.has_room_for(40), true);
+        assert_eq!(subject.has_room_for(41), false);
+        assert_eq!(subject.reserve(41), false);
+        assert_eq!(subject.spilled(), 60);
+        subject.release(60);
+        assert_eq!(subject.reserve(100), true);
+    }
+
+    #[test]
+    fn remove_stale_files_removes_only_spill_files() {
+        let directory = ensure_node_home_directory_exists(
+            "spill_file",
+            "remove_stale_files_removes_only_spill_files",
+        );
+        fs::write(directory.join("stream_123_0.bin"), b"stale").unwrap();
+        fs::write(directory.join("other.txt"), b"keep").unwrap();
+        let subject = SpillConfig {
+            directory_opt: Some(directory.clone()),
+            ..SpillConfig::default()
+        };
+
+        subject.remove_stale_files();
+
+        assert!(!directory.join("stream_123_0.bin").exists());
+        assert!(directory.join("other.txt").exists());
+    }
+}
@@ -18,6 +18,8 @@ use tokio::timer::Timeout;
 
 pub const CONNECT_TIMEOUT_MS: u64 = 5000;
 pub type ConnectionInfoFuture = Box<dyn Future<Item = ConnectionInfo, Error = io::Error> + Send>;
+// Whatever has to be said to a server over a new connection before it carries the client's data
+pub type Preamble = fn(&mut StdTcpStream) -> io::Result<()>;
 
 pub struct ConnectionInfo {
     pub reader: Box<dyn ReadHalfWrapper>,
@@ -33,6 +35,7 @@ pub trait StreamConnector: Send {
         ip_addrs: Vec<IpAddr>,
         target_hostname: &String,
         target_port: u16,
+        preamble_opt: Option<Preamble>,
         logger: &Logger,
     ) -> Result<ConnectionInfo, io::Error>;
     fn split_stream(&self, stream: TcpStream, logger: &Logger) -> Option<ConnectionInfo>;
@@ -95,6 +98,7 @@ impl StreamConnector for StreamConnectorReal {
         ip_addrs: Vec<IpAddr>,
         target_hostname: &String,
         target_port: u16,
+        preamble_opt: Option<Preamble>,
         logger: &Logger,
     ) -> Result<ConnectionInfo, io::Error> {
         let mut last_error = io::Error::from(ErrorKind::Other);
@@ -103,7 +107,10 @@ impl StreamConnector for StreamConnectorReal {
         for ip_addr in ip_addrs {
             let socket_addr = SocketAddr::new(ip_addr, target_port);
 
-            match StdTcpStream::connect(&socket_addr) {
+            match StdTcpStream::connect(&socket_addr).and_then(|mut stream| match preamble_opt {
+                Some(preamble) => preamble(&mut stream).map(|_| stream),
+                None => Ok(stream),
+            }) {
                 Ok(stream) => {
                     debug!(logger, "Connected new stream to {}", socket_addr);
                     let tokio_stream = TcpStream::from_std(stream, &Handle::default())
@@ -232,6 +239,7 @@ mod tests {
                 ip_addrs,
                 &"some hostname".to_string(),
                 socket_addr.port(),
+                None,
                 &logger,
             );
             tx.send(connection_result).unwrap();
@@ -268,6 +276,7 @@ mod tests {
                 ip_addrs,
                 &"some hostname".to_string(),
                 socket_addr.port(),
+                None,
                 &logger,
             );
             connection_info_tx.send(connection_result).unwrap();
@@ -301,7 +310,7 @@ mod tests {
         let (tx, rx) = mpsc::channel();
         let test_future = lazy(move || {
             let connection_result =
-                subject.connect_one(ip_addrs, &"some hostname".to_string(), 9876, &logger);
+                subject.connect_one(ip_addrs, &"some hostname".to_string(), 9876, None, &logger);
             tx.send(connection_result).unwrap();
             Ok(())
         });
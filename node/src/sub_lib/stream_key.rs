@@ -92,6 +92,10 @@ impl StreamKey {
             hash: hash.digest().bytes(),
         }
     }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.hash[..]
+    }
 }
 
 type HashType = [u8; sha1::DIGEST_LENGTH];
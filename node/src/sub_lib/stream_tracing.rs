@@ -0,0 +1,440 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::sub_lib::stream_key::StreamKey;
+use std::time::SystemTime;
+
+#[cfg(feature = "otel-tracing")]
+pub use self::exporter::start_exporter;
+
+// A client stream's trace and span IDs are derived from its StreamKey, which both ends of the
+// stream already know, so spans recorded on different Nodes line up without any change to the
+// CORES packages that travel between them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stage {
+    ProxyServerRequest,
+    HopperRequest,
+    ProxyClientRequest,
+    ProxyClientResponse,
+    HopperResponse,
+    ProxyServerResponse,
+}
+
+impl Stage {
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::ProxyServerRequest => "ProxyServer request",
+            Stage::HopperRequest => "Hopper request",
+            Stage::ProxyClientRequest => "ProxyClient request",
+            Stage::ProxyClientResponse => "ProxyClient response",
+            Stage::HopperResponse => "Hopper response",
+            Stage::ProxyServerResponse => "ProxyServer response",
+        }
+    }
+
+    pub fn parent(self) -> Option<Stage> {
+        match self {
+            Stage::ProxyServerRequest => None,
+            Stage::HopperRequest => Some(Stage::ProxyServerRequest),
+            Stage::ProxyClientRequest => Some(Stage::HopperRequest),
+            Stage::ProxyClientResponse => None,
+            Stage::HopperResponse => Some(Stage::ProxyClientResponse),
+            Stage::ProxyServerResponse => Some(Stage::HopperResponse),
+        }
+    }
+}
+
+pub type TraceId = [u8; 16];
+pub type SpanId = [u8; 8];
+
+pub fn trace_id(stream_key: &StreamKey) -> TraceId {
+    let mut trace_id = [0u8; 16];
+    trace_id.copy_from_slice(&stream_key.as_bytes()[..16]);
+    trace_id
+}
+
+pub fn span_id(trace_id: &TraceId, stage: Stage, sequence_number: u64) -> SpanId {
+    let mut hash = sha1::Sha1::new();
+    hash.update(trace_id);
+    hash.update(stage.name().as_bytes());
+    hash.update(&sequence_number.to_be_bytes());
+    let mut span_id = [0u8; 8];
+    span_id.copy_from_slice(&hash.digest().bytes()[..8]);
+    span_id
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpanRecord {
+    pub trace_id: TraceId,
+    pub span_id: SpanId,
+    pub parent_span_id_opt: Option<SpanId>,
+    pub stage: Stage,
+    pub sequence_number: u64,
+    pub start: SystemTime,
+    pub end: SystemTime,
+}
+
+impl SpanRecord {
+    pub fn new(
+        stage: Stage,
+        stream_key: &StreamKey,
+        sequence_number: u64,
+        start: SystemTime,
+    ) -> SpanRecord {
+        let trace_id = trace_id(stream_key);
+        SpanRecord {
+            trace_id,
+            span_id: span_id(&trace_id, stage, sequence_number),
+            parent_span_id_opt: stage
+                .parent()
+                .map(|parent| span_id(&trace_id, parent, sequence_number)),
+            stage,
+            sequence_number,
+            start,
+            end: start,
+        }
+    }
+}
+
+// Costs nothing unless the Node was built with the otel-tracing feature and the exporter is running
+pub struct Span {
+    record_opt: Option<SpanRecord>,
+}
+
+impl Span {
+    pub fn start(stage: Stage, stream_key: &StreamKey, sequence_number: u64) -> Span {
+        Span {
+            record_opt: if exporting() {
+                Some(SpanRecord::new(
+                    stage,
+                    stream_key,
+                    sequence_number,
+                    SystemTime::now(),
+                ))
+            } else {
+                None
+            },
+        }
+    }
+
+    pub fn end(self) {
+        if let Some(mut record) = self.record_opt {
+            record.end = SystemTime::now();
+            export(record);
+        }
+    }
+}
+
+#[cfg(feature = "otel-tracing")]
+fn exporting() -> bool {
+    exporter::is_running()
+}
+
+#[cfg(not(feature = "otel-tracing"))]
+fn exporting() -> bool {
+    false
+}
+
+#[cfg(feature = "otel-tracing")]
+fn export(record: SpanRecord) {
+    exporter::export(record)
+}
+
+#[cfg(not(feature = "otel-tracing"))]
+fn export(_record: SpanRecord) {}
+
+#[cfg(feature = "otel-tracing")]
+mod exporter {
+    use super::SpanRecord;
+    use crate::sub_lib::logger::Logger;
+    use lazy_static::lazy_static;
+    use serde_json::{json, Value};
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::sync::mpsc;
+    use std::sync::mpsc::{RecvTimeoutError, Sender};
+    use std::sync::Mutex;
+    use std::thread;
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+    pub const ENDPOINT_VARIABLE: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+    pub const DEFAULT_ENDPOINT: &str = "http://localhost:4318";
+    const EXPORT_INTERVAL: Duration = Duration::from_secs(5);
+    const MAX_BATCH_SIZE: usize = 512;
+
+    lazy_static! {
+        static ref SPAN_SENDER: Mutex<Option<Sender<SpanRecord>>> = Mutex::new(None);
+    }
+
+    // Sends spans in OTLP/HTTP JSON to the collector named by the standard OpenTelemetry
+    // environment variable. Only plain http is supported: run the collector next to the Node.
+    pub fn start_exporter() {
+        let logger = Logger::new("StreamTracing");
+        let endpoint =
+            std::env::var(ENDPOINT_VARIABLE).unwrap_or_else(|_| DEFAULT_ENDPOINT.to_string());
+        let (host_port, path) = match parse_endpoint(&endpoint) {
+            Ok(pair) => pair,
+            Err(msg) => {
+                error!(logger, "Not exporting spans: {}", msg);
+                return;
+            }
+        };
+        let (tx, rx) = mpsc::channel();
+        *SPAN_SENDER.lock().expect("Span sender is poisoned") = Some(tx);
+        info!(
+            logger,
+            "Exporting stream spans to http://{}{}", host_port, path
+        );
+        thread::spawn(move || {
+            let mut batch: Vec<SpanRecord> = vec![];
+            let mut last_export = Instant::now();
+            loop {
+                let disconnected = match rx.recv_timeout(EXPORT_INTERVAL) {
+                    Ok(record) => {
+                        batch.push(record);
+                        false
+                    }
+                    Err(RecvTimeoutError::Timeout) => false,
+                    Err(RecvTimeoutError::Disconnected) => true,
+                };
+                let due = disconnected
+                    || batch.len() >= MAX_BATCH_SIZE
+                    || last_export.elapsed() >= EXPORT_INTERVAL;
+                if !due {
+                    continue;
+                }
+                last_export = Instant::now();
+                if !batch.is_empty() {
+                    let body = encode(&batch).to_string();
+                    batch.clear();
+                    if let Err(msg) = post(&host_port, &path, &body) {
+                        warning!(logger, "Couldn't export spans to {}: {}", endpoint, msg);
+                    }
+                }
+                if disconnected {
+                    break;
+                }
+            }
+        });
+    }
+
+    pub fn is_running() -> bool {
+        SPAN_SENDER
+            .lock()
+            .expect("Span sender is poisoned")
+            .is_some()
+    }
+
+    pub fn export(record: SpanRecord) {
+        if let Some(tx) = SPAN_SENDER
+            .lock()
+            .expect("Span sender is poisoned")
+            .as_ref()
+        {
+            let _ = tx.send(record);
+        }
+    }
+
+    pub fn parse_endpoint(endpoint: &str) -> Result<(String, String), String> {
+        let rest = match endpoint.trim_end_matches('/') {
+            e if e.starts_with("http://") => e["http://".len()..].to_string(),
+            e => return Err(format!("{} {} is not an http:// URL", ENDPOINT_VARIABLE, e)),
+        };
+        let (host_port, base_path) = match rest.find('/') {
+            Some(index) => (rest[..index].to_string(), rest[index..].to_string()),
+            None => (rest.clone(), String::new()),
+        };
+        let host_port = if host_port.contains(':') {
+            host_port
+        } else {
+            format!("{}:80", host_port)
+        };
+        Ok((host_port, format!("{}/v1/traces", base_path)))
+    }
+
+    pub fn encode(batch: &[SpanRecord]) -> Value {
+        let spans: Vec<Value> = batch
+            .iter()
+            .map(|record| {
+                let mut span = json!({
+                    "traceId": hex(&record.trace_id),
+                    "spanId": hex(&record.span_id),
+                    "name": record.stage.name(),
+                    "kind": 1,
+                    "startTimeUnixNano": unix_nanos(record.start),
+                    "endTimeUnixNano": unix_nanos(record.end),
+                    "attributes": [
+                        {"key": "substratum.sequence_number", "value": {"intValue": record.sequence_number.to_string()}}
+                    ]
+                });
+                if let Some(parent_span_id) = record.parent_span_id_opt {
+                    span["parentSpanId"] = json!(hex(&parent_span_id));
+                }
+                span
+            })
+            .collect();
+        json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [
+                        {"key": "service.name", "value": {"stringValue": "PrometheusNode"}}
+                    ]
+                },
+                "scopeSpans": [{
+                    "scope": {"name": "SubstratumNode", "version": env!("CARGO_PKG_VERSION")},
+                    "spans": spans
+                }]
+            }]
+        })
+    }
+
+    fn post(host_port: &str, path: &str, body: &str) -> Result<(), String> {
+        let mut stream = TcpStream::connect(host_port).map_err(|e| format!("{:?}", e))?;
+        stream
+            .set_read_timeout(Some(EXPORT_INTERVAL))
+            .map_err(|e| format!("{:?}", e))?;
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            path,
+            host_port,
+            body.len(),
+            body
+        );
+        stream
+            .write_all(request.as_bytes())
+            .map_err(|e| format!("{:?}", e))?;
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response);
+        match response.lines().next() {
+            Some(status)
+                if status.split(' ').nth(1).map(|code| code.starts_with('2')) == Some(true) =>
+            {
+                Ok(())
+            }
+            Some(status) => Err(status.to_string()),
+            None => Err("no response".to_string()),
+        }
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn unix_nanos(time: SystemTime) -> String {
+        time.duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos())
+            .unwrap_or(0)
+            .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sub_lib::cryptde::PublicKey;
+    use std::net::SocketAddr;
+    use std::str::FromStr;
+
+    fn make_stream_key() -> StreamKey {
+        StreamKey::new(
+            PublicKey::new(&b"originator"[..]),
+            SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+        )
+    }
+
+    #[test]
+    fn both_ends_of_a_stream_agree_on_its_ids() {
+        let stream_key = make_stream_key();
+        let here = SpanRecord::new(Stage::ProxyClientRequest, &stream_key, 7, SystemTime::now());
+        let there = SpanRecord::new(
+            Stage::HopperRequest,
+            &make_stream_key(),
+            7,
+            SystemTime::now(),
+        );
+
+        assert_eq!(here.trace_id, there.trace_id);
+        assert_eq!(here.trace_id[..], stream_key.as_bytes()[..16]);
+        assert_eq!(here.parent_span_id_opt, Some(there.span_id));
+    }
+
+    #[test]
+    fn span_ids_differ_by_stage_and_sequence_number() {
+        let trace_id = trace_id(&make_stream_key());
+
+        let request = span_id(&trace_id, Stage::ProxyServerRequest, 0);
+        let response = span_id(&trace_id, Stage::ProxyServerResponse, 0);
+        let next_request = span_id(&trace_id, Stage::ProxyServerRequest, 1);
+
+        assert_ne!(request, response);
+        assert_ne!(request, next_request);
+        assert_eq!(request, span_id(&trace_id, Stage::ProxyServerRequest, 0));
+    }
+
+    #[test]
+    fn each_chain_of_spans_starts_where_its_data_enters_the_network() {
+        let record = SpanRecord::new(
+            Stage::ProxyServerRequest,
+            &make_stream_key(),
+            0,
+            SystemTime::now(),
+        );
+
+        assert_eq!(record.parent_span_id_opt, None);
+        assert_eq!(Stage::ProxyClientResponse.parent(), None);
+        assert_eq!(
+            Stage::ProxyServerResponse.parent(),
+            Some(Stage::HopperResponse)
+        );
+    }
+
+    #[cfg(not(feature = "otel-tracing"))]
+    #[test]
+    fn spans_record_nothing_without_the_feature() {
+        let span = Span::start(Stage::HopperRequest, &make_stream_key(), 0);
+
+        assert_eq!(span.record_opt, None);
+        span.end();
+    }
+
+    #[cfg(feature = "otel-tracing")]
+    #[test]
+    fn endpoints_are_parsed_into_address_and_traces_path() {
+        assert_eq!(
+            exporter::parse_endpoint("http://collector:4318"),
+            Ok(("collector:4318".to_string(), "/v1/traces".to_string()))
+        );
+        assert_eq!(
+            exporter::parse_endpoint("http://collector/otlp/"),
+            Ok(("collector:80".to_string(), "/otlp/v1/traces".to_string()))
+        );
+        assert_eq!(
+            exporter::parse_endpoint("https://collector:4318"),
+            Err(
+                "OTEL_EXPORTER_OTLP_ENDPOINT https://collector:4318 is not an http:// URL"
+                    .to_string()
+            )
+        );
+    }
+
+    #[cfg(feature = "otel-tracing")]
+    #[test]
+    fn spans_are_encoded_as_otlp_json() {
+        let start = std::time::UNIX_EPOCH + std::time::Duration::from_nanos(1_000_000_123);
+        let mut record = SpanRecord::new(Stage::HopperResponse, &make_stream_key(), 3, start);
+        record.end = start + std::time::Duration::from_nanos(500);
+
+        let result = exporter::encode(&[record.clone()]);
+
+        let span = &result["resourceSpans"][0]["scopeSpans"][0]["spans"][0];
+        assert_eq!(
+            result["resourceSpans"][0]["resource"]["attributes"][0]["value"]["stringValue"],
+            "PrometheusNode"
+        );
+        assert_eq!(span["name"], "Hopper response");
+        assert_eq!(span["traceId"].as_str().unwrap().len(), 32);
+        assert_eq!(span["spanId"].as_str().unwrap().len(), 16);
+        assert_eq!(span["parentSpanId"].as_str().unwrap().len(), 16);
+        assert_eq!(span["startTimeUnixNano"], "1000000123");
+        assert_eq!(span["endTimeUnixNano"], "1000000623");
+        assert_eq!(span["attributes"][0]["value"]["intValue"], "3");
+    }
+}
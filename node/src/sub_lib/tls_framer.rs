@@ -1,6 +1,8 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::proxy_server::tls_protocol_pack::MAX_TLS_RECORD_LENGTH;
 use crate::sub_lib::framer::FramedChunk;
 use crate::sub_lib::framer::Framer;
+use crate::sub_lib::framer::FramingViolation;
 use crate::sub_lib::utils::index_of;
 
 const PRESERVE_HEADER_LEN: usize = 4;
@@ -8,14 +10,20 @@ const PRESERVE_HEADER_LEN: usize = 4;
 #[derive(Default)]
 pub struct TlsFramer {
     data_so_far: Vec<u8>,
+    violation_opt: Option<FramingViolation>,
 }
 
 impl Framer for TlsFramer {
     fn add_data(&mut self, data: &[u8]) {
-        self.data_so_far.extend(data);
+        if self.violation_opt.is_none() {
+            self.data_so_far.extend(data);
+        }
     }
 
     fn take_frame(&mut self) -> Option<FramedChunk> {
+        if self.violation_opt.is_some() {
+            return None;
+        }
         let data_so_far_len = self.data_so_far.len();
         match TlsFramer::find_frame_offset(&self.data_so_far[..]) {
             None => {
@@ -30,7 +38,12 @@ impl Framer for TlsFramer {
             Some(offset) => {
                 let mut from_offset = self.data_so_far.split_off(offset);
                 let length = TlsFramer::to_usize(from_offset[3], from_offset[4]);
-                if 5 + length <= from_offset.len() {
+                if length > MAX_TLS_RECORD_LENGTH {
+                    self.data_so_far.clear();
+                    self.violation_opt =
+                        Some(FramingViolation::RecordTooLarge(MAX_TLS_RECORD_LENGTH));
+                    None
+                } else if 5 + length <= from_offset.len() {
                     let leftovers = from_offset.split_off(5 + length);
                     let chunk = from_offset;
                     self.data_so_far = leftovers;
@@ -45,6 +58,10 @@ impl Framer for TlsFramer {
             }
         }
     }
+
+    fn violation(&self) -> Option<FramingViolation> {
+        self.violation_opt.clone()
+    }
 }
 
 impl TlsFramer {
@@ -154,12 +171,30 @@ mod tests {
     #[test]
     fn tls_framer_does_not_reject_data_on_basis_of_illegal_length() {
         let mut subject = TlsFramer::new();
-        let data = vec![0x16, 0x03, 0x03, 0xFF, 0xFF, 0x05, 0x06, 0x07];
+        let data = vec![0x16, 0x03, 0x03, 0x48, 0x00, 0x05, 0x06, 0x07];
         subject.add_data(&data[..]);
         let result = subject.take_frame();
 
         assert_eq!(result, None);
         assert_eq!(subject.data_so_far, data);
+        assert_eq!(subject.violation(), None);
+    }
+
+    #[test]
+    fn tls_framer_refuses_record_too_long_to_hold_and_ignores_what_follows() {
+        let mut subject = TlsFramer::new();
+        subject.add_data(&[0x16, 0x03, 0x03, 0x48, 0x01, 0x05, 0x06, 0x07][..]);
+
+        let result = subject.take_frame();
+
+        assert_eq!(result, None);
+        assert_eq!(
+            subject.violation(),
+            Some(FramingViolation::RecordTooLarge(MAX_TLS_RECORD_LENGTH))
+        );
+        subject.add_data(&[0x16, 0x03, 0x03, 0x00, 0x01, 0x0A][..]);
+        assert_eq!(subject.take_frame(), None);
+        assert!(subject.data_so_far.is_empty());
     }
 
     #[test]
@@ -14,6 +14,7 @@ use tokio::prelude::AsyncWrite;
 
 pub trait TokioListenerWrapper: Send {
     fn bind(&mut self, addr: SocketAddr) -> io::Result<()>;
+    fn local_addr(&self) -> io::Result<SocketAddr>;
     fn poll_accept(&mut self) -> Result<Async<(TcpStream, SocketAddr)>, io::Error>;
 }
 
@@ -51,6 +52,13 @@ impl TokioListenerWrapper for TokioListenerWrapperReal {
         }
     }
 
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.delegate
+            .as_ref()
+            .expect("TcpListener not initialized - bind to a SocketAddr")
+            .local_addr()
+    }
+
     fn poll_accept(&mut self) -> Result<Async<(TcpStream, SocketAddr)>, io::Error> {
         self.delegate_mut().poll_accept()
     }
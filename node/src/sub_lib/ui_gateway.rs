@@ -1,17 +1,100 @@
 // Copyright (c) 2017-2018, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
-use crate::sub_lib::accountant::FinancialStatisticsMessage;
+use crate::audit_log::AuditEntry;
+use crate::sub_lib::accountant::{FinancialStatisticsMessage, FinancialsFormat, PaymentParameter};
+use crate::sub_lib::neighborhood::NeighborSummary;
+use crate::sub_lib::node_descriptor_share::{node_descriptor_uri, qr_code_png};
 use crate::sub_lib::peer_actors::BindMessage;
 use actix::Message;
 use actix::Recipient;
+use clap::crate_version;
 use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::{Debug, Formatter};
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
 
 pub const DEFAULT_UI_PORT: u16 = 5333;
+// Where the daemon listens for UIs, whether or not there's a Node running
+pub const DEFAULT_DAEMON_PORT: u16 = 5332;
 
-#[derive(Clone, Debug)]
+// Version 1 is the protocol of bare UiMessages, spoken by UIs that never say Hello. From version 2
+// on, a UI that says Hello gets every message to and from it wrapped in a UiEnvelope.
+pub const UI_PROTOCOL_VERSION: u32 = 2;
+
+// What a UI can ask this Node to do, so it doesn't have to guess from the Node's version
+pub const UI_CAPABILITIES: &[&str] = &[
+    "financials",
+    "gas-price",
+    "payment-parameters",
+    "wallet-password",
+    "node-descriptor",
+    "node-descriptor-share",
+    "node-status",
+    "node-statistics",
+    "neighbors",
+    "dns-leak-test",
+    "health",
+    "audit-log",
+    "configuration",
+    "log-level",
+    "reload",
+    "events",
+    "shutdown",
+    "restart",
+];
+
+#[derive(Clone)]
 pub struct UiGatewayConfig {
     pub ui_port: u16,
     pub node_descriptor: String,
+    pub rest_port_opt: Option<u16>,
+    // What a UI must present in its first message on the UI port
+    pub auth_token: String,
+    // Where to find the configuration file again on SIGHUP or a UI's order to reload it
+    pub config_file_opt: Option<ConfigFileSource>,
+    // Where the Node's own DNS server answers, for the DNS leak test
+    pub dns_server: SocketAddr,
+    // Where the Node's listeners accept connections from this machine, for the health report
+    pub listeners: Vec<SocketAddr>,
+    // Where to send anonymized telemetry; none is sent unless the user asks
+    pub telemetry_url_opt: Option<String>,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct ConfigFileSource {
+    pub path: PathBuf,
+    pub user_specified: bool,
+    // What the file said when the Node started, so a reload can tell what has changed
+    pub values: BTreeMap<String, String>,
+    // Parameters given on the command line or in the environment, which the file can't override
+    pub overridden: BTreeSet<String>,
+}
+
+// The file may hold secrets such as consuming-private-key, so only the names are shown
+impl Debug for ConfigFileSource {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        f.debug_struct("ConfigFileSource")
+            .field("path", &self.path)
+            .field("user_specified", &self.user_specified)
+            .field("values", &self.values.keys().collect::<Vec<&String>>())
+            .field("overridden", &self.overridden)
+            .finish()
+    }
+}
+
+impl Debug for UiGatewayConfig {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        f.debug_struct("UiGatewayConfig")
+            .field("ui_port", &self.ui_port)
+            .field("node_descriptor", &self.node_descriptor)
+            .field("rest_port_opt", &self.rest_port_opt)
+            .field("auth_token", &"********")
+            .field("config_file_opt", &self.config_file_opt)
+            .field("dns_server", &self.dns_server)
+            .field("listeners", &self.listeners)
+            .field("telemetry_url_opt", &self.telemetry_url_opt)
+            .finish()
+    }
 }
 
 #[derive(Clone)]
@@ -19,6 +102,7 @@ pub struct UiGatewaySubs {
     pub bind: Recipient<BindMessage>,
     pub ui_message_sub: Recipient<UiCarrierMessage>,
     pub from_ui_message_sub: Recipient<FromUiMessage>,
+    pub node_event_sub: Recipient<NodeEvent>,
 }
 
 impl Debug for UiGatewaySubs {
@@ -33,19 +117,424 @@ pub struct UiCarrierMessage {
     pub data: UiMessage,
 }
 
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct UiEnvelope {
+    pub version: u32,
+    pub message: UiMessage,
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub enum UiMessage {
+    Authenticate(String),
+    Hello {
+        protocol_version: u32,
+    },
+    Capabilities(UiCapabilities),
     GetFinancialStatisticsMessage,
     FinancialStatisticsResponse(FinancialStatisticsMessage),
+    DumpFinancialsRequest(FinancialsFormat),
+    DumpFinancialsResponse(String),
     SetGasPrice(String),
     SetGasPriceResponse(bool),
+    GetBlockchainServiceUrl,
+    BlockchainServiceUrl(Option<String>),
+    GetWalletBalances,
+    WalletBalances(WalletBalances),
+    SetPaymentParameter {
+        parameter: PaymentParameter,
+        value: u64,
+    },
+    SetPaymentParameterResponse(bool),
     SetWalletPassword(String),
     SetWalletPasswordResponse(bool),
     GetNodeDescriptor,
     NodeDescriptor(String),
+    GetNodeDescriptorShare,
+    NodeDescriptorShare(NodeDescriptorShare),
+    GetNodeStatus,
+    NodeStatus(NodeStatus),
+    GetNodeStatistics,
+    NodeStatistics(NodeStatistics),
     NeighborhoodDotGraphRequest,
     NeighborhoodDotGraphResponse(String),
+    GetNeighborList,
+    NeighborList(Vec<NeighborSummary>),
+    RouteCapabilityDiagnosis(String),
+    DnsLeakTest,
+    DnsLeakTestResponse(DnsLeakReport),
+    GetNodeHealth,
+    NodeHealth(NodeHealth),
+    // The most recent entries, oldest first; without a limit, the last 50
+    GetAuditLog {
+        #[serde(default)]
+        limit: Option<usize>,
+    },
+    AuditLog(Result<Vec<AuditEntry>, String>),
+    ConfigurationGet,
+    Configuration(Vec<ConfigurationEntry>),
+    ConfigurationChange {
+        name: String,
+        value: String,
+    },
+    ConfigurationChangeResponse(Result<(), String>),
+    // Without a component, the level for the whole Node; with one, only for that component's
+    // Logger (e.g. "Neighborhood"), where the level "default" puts it back with the rest
+    SetLogLevel {
+        level: String,
+        #[serde(default)]
+        component: Option<String>,
+    },
+    SetLogLevelResponse(Result<(), String>),
+    ReloadConfiguration,
+    ReloadConfigurationResponse(Result<ConfigurationReload, String>),
+    SubscribeToEvents(Vec<NodeEventKind>),
+    UnsubscribeFromEvents,
+    NodeEvent(NodeEvent),
     ShutdownMessage,
+    RestartMessage,
+    // The rest are for the daemon, not the Node. The setup is the Node's parameters, by name
+    // without the leading dashes, and replaces any setup given before.
+    DaemonSetup(BTreeMap<String, String>),
+    DaemonSetupResponse(Result<(), String>),
+    DaemonStart,
+    DaemonStartResponse(Result<(), String>),
+    DaemonStop,
+    DaemonStopResponse(Result<(), String>),
+    GetDaemonStatus,
+    DaemonStatus(DaemonStatus),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UiCapabilities {
+    // The version both sides speak: the lower of the UI's and the Node's
+    pub protocol_version: u32,
+    pub node_version: String,
+    pub capabilities: Vec<String>,
+}
+
+impl UiCapabilities {
+    pub fn negotiate(ui_protocol_version: u32) -> UiCapabilities {
+        UiCapabilities {
+            protocol_version: ui_protocol_version.min(UI_PROTOCOL_VERSION),
+            node_version: crate_version!().to_string(),
+            capabilities: UI_CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+}
+
+// What a UI needs to hand this Node's descriptor to a phone or another machine
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeDescriptorShare {
+    pub descriptor: String,
+    pub uri: String,
+    // A base64-encoded PNG of the URI, ready to go into a data: URL
+    pub qr_code_png: String,
+}
+
+impl NodeDescriptorShare {
+    pub fn new(descriptor: &str) -> Result<NodeDescriptorShare, String> {
+        let uri = node_descriptor_uri(descriptor);
+        let qr_code_png = base64::encode(&qr_code_png(&uri)?);
+        Ok(NodeDescriptorShare {
+            descriptor: descriptor.to_string(),
+            uri,
+            qr_code_png,
+        })
+    }
+}
+
+// Balances are in wei, as decimal strings: they can be far too big for a JSON number
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletBalance {
+    pub address: String,
+    pub gas_balance: Result<String, String>,
+    pub token_balance: Result<String, String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletBalances {
+    pub earning: WalletBalance,
+    // None until there's a consuming wallet, which may be waiting for its password
+    pub consuming: Option<WalletBalance>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeStatus {
+    pub version: String,
+    pub node_descriptor: String,
+    pub uptime_secs: u64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeStatistics {
+    pub uptime_secs: u64,
+    pub bytes_relayed: u64,
+    pub bytes_relayed_last_hour: u64,
+    pub exit_bytes_served: u64,
+    pub active_streams_originated: u64,
+    pub active_exit_streams: u64,
+    pub neighbor_count: u64,
+}
+
+impl NodeStatistics {
+    // Every actor asked for statistics fills in only the ones it keeps, so their answers add up
+    pub fn plus(self, other: NodeStatistics) -> NodeStatistics {
+        NodeStatistics {
+            uptime_secs: self.uptime_secs + other.uptime_secs,
+            bytes_relayed: self.bytes_relayed + other.bytes_relayed,
+            bytes_relayed_last_hour: self.bytes_relayed_last_hour + other.bytes_relayed_last_hour,
+            exit_bytes_served: self.exit_bytes_served + other.exit_bytes_served,
+            active_streams_originated: self.active_streams_originated
+                + other.active_streams_originated,
+            active_exit_streams: self.active_exit_streams + other.active_exit_streams,
+            neighbor_count: self.neighbor_count + other.neighbor_count,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum DnsLeakVerdict {
+    NotLeaking,
+    Leaking,
+    // Nothing to compare with: the Node's DNS server said nothing
+    NodeNotAnswering,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DnsLeakReport {
+    // A made-up name, so that no answer can have come from a cache
+    pub canary: String,
+    pub node_dns_server: SocketAddr,
+    pub node_answer: Option<IpAddr>,
+    // What any program on this machine would have been told
+    pub system_answers: Vec<IpAddr>,
+    pub system_error: Option<String>,
+    pub verdict: DnsLeakVerdict,
+}
+
+impl DnsLeakReport {
+    pub fn explanation(&self) -> String {
+        let system_said = match &self.system_error {
+            Some(e) => format!("couldn't look it up ({})", e),
+            None => format!("says {}", join_ips(&self.system_answers)),
+        };
+        match (self.verdict, self.node_answer) {
+            (DnsLeakVerdict::NotLeaking, Some(node_answer)) => format!(
+                "No DNS leak: this machine's resolver turned {} into {}, the Node's own answer, so \
+                 names are looked up at the far end of the clandestine network",
+                self.canary, node_answer
+            ),
+            (DnsLeakVerdict::Leaking, Some(node_answer)) => format!(
+                "DNS is leaking: the Node's DNS server at {} answered {} for {}, but this machine's \
+                 resolver {}, so lookups are going around the Node. Subvert this machine's DNS with \
+                 dns_utility, or point its resolver at {}",
+                self.node_dns_server,
+                node_answer,
+                self.canary,
+                system_said,
+                self.node_dns_server.ip()
+            ),
+            _ => format!(
+                "Can't tell whether DNS is leaking: the Node's DNS server at {} didn't answer for {}, \
+                 and this machine's resolver {}",
+                self.node_dns_server, self.canary, system_said
+            ),
+        }
+    }
+}
+
+fn join_ips(ips: &[IpAddr]) -> String {
+    ips.iter()
+        .map(|ip| ip.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum HealthState {
+    Ready,
+    NotReady,
+    // Switched off or not set up, so it neither helps nor hinders readiness
+    Disabled,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ComponentHealth {
+    pub component: String,
+    pub state: HealthState,
+    pub detail: String,
+}
+
+impl ComponentHealth {
+    pub fn new(component: &str, state: HealthState, detail: &str) -> ComponentHealth {
+        ComponentHealth {
+            component: component.to_string(),
+            state,
+            detail: detail.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeHealth {
+    // True unless some component is NotReady
+    pub ready: bool,
+    pub components: Vec<ComponentHealth>,
+}
+
+impl NodeHealth {
+    pub fn new(mut components: Vec<ComponentHealth>) -> NodeHealth {
+        components.sort_by(|a, b| a.component.cmp(&b.component));
+        NodeHealth {
+            ready: components
+                .iter()
+                .all(|component| component.state != HealthState::NotReady),
+            components,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum DaemonNodeState {
+    Stopped,
+    Starting,
+    Running,
+    // It stopped without being told to, and the daemon is about to start it again
+    Restarting,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DaemonStatus {
+    pub node_state: DaemonNodeState,
+    pub node_pid: Option<u32>,
+    // How many times the Node has had to be started again since it was last told to start
+    pub restarts: u32,
+    // Only the names: the values may include the wallet password
+    pub setup: Vec<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct GetNodeStatisticsMessage {}
+
+impl Message for GetNodeStatisticsMessage {
+    type Result = NodeStatistics;
+}
+
+// Each actor asked answers with the health of the components it owns
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct GetHealthMessage {}
+
+impl Message for GetHealthMessage {
+    type Result = Vec<ComponentHealth>;
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum NodeEventKind {
+    NeighborConnected,
+    NeighborDisconnected,
+    RouteFailure,
+    PaymentSent,
+    PaymentReceived,
+    DelinquencyBan,
+    DescriptorChanged,
+}
+
+// Published by whichever actor notices; the UiGateway passes each on to the UIs subscribed to it
+#[derive(Clone, Debug, Message, Serialize, Deserialize, PartialEq)]
+pub enum NodeEvent {
+    NeighborConnected {
+        public_key: String,
+    },
+    NeighborDisconnected {
+        public_key: String,
+    },
+    RouteFailure {
+        reason: String,
+    },
+    PaymentSent {
+        wallet: String,
+        amount: u64,
+        transaction: String,
+    },
+    PaymentReceived {
+        wallet: String,
+        amount: u64,
+        transaction: String,
+    },
+    DelinquencyBan {
+        wallet: String,
+    },
+    DescriptorChanged {
+        node_descriptor: String,
+    },
+}
+
+impl NodeEvent {
+    pub fn kind(&self) -> NodeEventKind {
+        match self {
+            NodeEvent::NeighborConnected { .. } => NodeEventKind::NeighborConnected,
+            NodeEvent::NeighborDisconnected { .. } => NodeEventKind::NeighborDisconnected,
+            NodeEvent::RouteFailure { .. } => NodeEventKind::RouteFailure,
+            NodeEvent::PaymentSent { .. } => NodeEventKind::PaymentSent,
+            NodeEvent::PaymentReceived { .. } => NodeEventKind::PaymentReceived,
+            NodeEvent::DelinquencyBan { .. } => NodeEventKind::DelinquencyBan,
+            NodeEvent::DescriptorChanged { .. } => NodeEventKind::DescriptorChanged,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ConfigurationEntry {
+    pub name: String,
+    pub value: String,
+}
+
+impl ConfigurationEntry {
+    pub fn new(name: &str, value: &str) -> ConfigurationEntry {
+        ConfigurationEntry {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+}
+
+// What came of reading the configuration file again. Parameters that need a restart keep their
+// old values until then.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigurationReload {
+    pub applied: Vec<ConfigurationEntry>,
+    pub rejected: Vec<String>,
+    pub needs_restart: Vec<String>,
+}
+
+// Each actor with parameters that may be changed at runtime answers with its own entries
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct GetConfigurationMessage {}
+
+impl Message for GetConfigurationMessage {
+    type Result = Vec<ConfigurationEntry>;
+}
+
+// Answered with None by actors that don't own the named parameter
+#[derive(Clone, PartialEq, Debug)]
+pub struct ChangeConfigurationMessage {
+    pub name: String,
+    pub value: String,
+}
+
+impl Message for ChangeConfigurationMessage {
+    type Result = Option<Result<(), String>>;
 }
 
 #[derive(Message, PartialEq, Debug)]
@@ -57,9 +546,19 @@ pub struct FromUiMessage {
 #[cfg(test)]
 mod tests {
     use crate::sub_lib::peer_actors::BindMessage;
-    use crate::sub_lib::ui_gateway::{FromUiMessage, UiCarrierMessage, UiGatewaySubs};
+    use crate::sub_lib::ui_gateway::DnsLeakVerdict;
+    use crate::sub_lib::ui_gateway::{
+        ComponentHealth, ConfigFileSource, HealthState, NodeDescriptorShare, NodeHealth,
+        UiCapabilities, UI_CAPABILITIES, UI_PROTOCOL_VERSION,
+    };
+    use crate::sub_lib::ui_gateway::{FromUiMessage, NodeEvent, UiCarrierMessage, UiGatewaySubs};
+    use crate::test_utils::make_dns_leak_report;
     use crate::test_utils::recorder::Recorder;
     use actix::Actor;
+    use std::collections::BTreeSet;
+    use std::net::IpAddr;
+    use std::path::PathBuf;
+    use std::str::FromStr;
 
     #[test]
     fn ui_gateway_subs_debug() {
@@ -69,8 +568,121 @@ mod tests {
             bind: recipient!(recorder, BindMessage),
             ui_message_sub: recipient!(recorder, UiCarrierMessage),
             from_ui_message_sub: recipient!(recorder, FromUiMessage),
+            node_event_sub: recipient!(recorder, NodeEvent),
         };
 
         assert_eq!(format!("{:?}", subject), "UiGatewaySubs");
     }
+
+    #[test]
+    fn config_file_source_debug_hides_the_values() {
+        let subject = ConfigFileSource {
+            path: PathBuf::from("config.toml"),
+            user_specified: false,
+            values: vec![(
+                "consuming-private-key".to_string(),
+                "89ABCDEF89ABCDEF".to_string(),
+            )]
+            .into_iter()
+            .collect(),
+            overridden: BTreeSet::new(),
+        };
+
+        assert_eq!(
+            format!("{:?}", subject),
+            "ConfigFileSource { path: \"config.toml\", user_specified: false, \
+             values: [\"consuming-private-key\"], overridden: {} }"
+        );
+    }
+
+    #[test]
+    fn capabilities_settle_on_the_lower_protocol_version() {
+        let older_ui = UiCapabilities::negotiate(1);
+        let newer_ui = UiCapabilities::negotiate(UI_PROTOCOL_VERSION + 1);
+
+        assert_eq!(older_ui.protocol_version, 1);
+        assert_eq!(newer_ui.protocol_version, UI_PROTOCOL_VERSION);
+        assert_eq!(
+            newer_ui.capabilities,
+            UI_CAPABILITIES
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<String>>()
+        );
+    }
+
+    #[test]
+    fn a_descriptor_share_carries_the_uri_and_a_png_of_it() {
+        let subject = NodeDescriptorShare::new("AQ/DBA:1.2.3.4:1234").unwrap();
+
+        assert_eq!(subject.descriptor, "AQ/DBA:1.2.3.4:1234".to_string());
+        assert_eq!(
+            subject.uri,
+            "substratum://AQ%2FDBA:1.2.3.4:1234".to_string()
+        );
+        let png = base64::decode(&subject.qr_code_png).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    }
+
+    #[test]
+    fn a_leak_report_says_where_lookups_went() {
+        let mut not_leaking = make_dns_leak_report(DnsLeakVerdict::NotLeaking);
+        not_leaking.system_answers = vec![IpAddr::from_str("127.0.0.1").unwrap()];
+        let leaking = make_dns_leak_report(DnsLeakVerdict::Leaking);
+        let mut unresolved = make_dns_leak_report(DnsLeakVerdict::Leaking);
+        unresolved.system_answers = vec![];
+        unresolved.system_error = Some("no such host".to_string());
+
+        assert_eq!(
+            not_leaking.explanation(),
+            "No DNS leak: this machine's resolver turned canary.example.com into 127.0.0.1, the \
+             Node's own answer, so names are looked up at the far end of the clandestine network"
+        );
+        assert_eq!(
+            leaking.explanation(),
+            "DNS is leaking: the Node's DNS server at 127.0.0.1:53 answered 127.0.0.1 for \
+             canary.example.com, but this machine's resolver says 93.184.216.34, so lookups are \
+             going around the Node. Subvert this machine's DNS with dns_utility, or point its \
+             resolver at 127.0.0.1"
+        );
+        assert!(unresolved
+            .explanation()
+            .contains("but this machine's resolver couldn't look it up (no such host)"));
+    }
+
+    #[test]
+    fn a_node_is_healthy_unless_some_component_is_not_ready() {
+        let healthy = NodeHealth::new(vec![
+            ComponentHealth::new("neighborhood", HealthState::Ready, "3 neighbors"),
+            ComponentHealth::new("blockchain", HealthState::Disabled, "no service URL"),
+        ]);
+        let unhealthy = NodeHealth::new(vec![
+            ComponentHealth::new("neighborhood", HealthState::NotReady, "no neighbors"),
+            ComponentHealth::new("database", HealthState::Ready, "writable"),
+        ]);
+
+        assert_eq!(
+            healthy,
+            NodeHealth {
+                ready: true,
+                components: vec![
+                    ComponentHealth::new("blockchain", HealthState::Disabled, "no service URL"),
+                    ComponentHealth::new("neighborhood", HealthState::Ready, "3 neighbors"),
+                ],
+            }
+        );
+        assert_eq!(unhealthy.ready, false);
+    }
+
+    #[test]
+    fn a_leak_report_without_the_nodes_answer_is_inconclusive() {
+        let mut subject = make_dns_leak_report(DnsLeakVerdict::NodeNotAnswering);
+        subject.node_answer = None;
+
+        assert_eq!(
+            subject.explanation(),
+            "Can't tell whether DNS is leaking: the Node's DNS server at 127.0.0.1:53 didn't answer \
+             for canary.example.com, and this machine's resolver says 93.184.216.34"
+        );
+    }
 }
@@ -378,6 +378,14 @@ impl<'de> serde::Deserialize<'de> for Wallet {
                                 false => {
                                     let bytes =
                                         serde::de::MapAccess::next_value::<Vec<u8>>(&mut map)?;
+                                    if bytes.len() != 20 {
+                                        return serde::export::Err(
+                                            <A::Error as serde::de::Error>::invalid_length(
+                                                bytes.len(),
+                                                &"a 20-byte address",
+                                            ),
+                                        );
+                                    }
                                     let mut address = [0u8; 20];
                                     address.copy_from_slice(bytes.as_slice());
                                     serde::export::Some(Address { 0: address })
@@ -441,6 +449,7 @@ mod tests {
     use rusqlite::Connection;
     use rustc_hex::FromHex;
     use serde_cbor;
+    use serde_derive::Serialize;
     use std::collections::hash_map::DefaultHasher;
     use std::convert::TryFrom;
     use std::str::FromStr;
@@ -568,6 +577,27 @@ mod tests {
         assert!(actual.congruent(&expected));
     }
 
+    #[test]
+    fn deserialization_with_cbor_rejects_address_of_wrong_length() {
+        #[derive(Serialize)]
+        struct ShortAddressWallet {
+            address: Vec<u8>,
+        }
+        let serialized = serde_cbor::to_vec(&ShortAddressWallet {
+            address: vec![1, 2, 3],
+        })
+        .unwrap();
+
+        let result = serde_cbor::from_slice::<Wallet>(&serialized[..]);
+
+        let message = format!("{:?}", result.err().unwrap());
+        assert!(
+            message.contains("invalid length 3, expected a 20-byte address"),
+            "{}",
+            message
+        );
+    }
+
     #[test]
     #[should_panic(expected = "No address for an uninitialized wallet!")]
     fn serialization_with_json_to_roundtrip_wallet_uninitialized() {
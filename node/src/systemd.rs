@@ -0,0 +1,231 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::sub_lib::logger::Logger;
+use std::env;
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::time::Duration;
+
+// Lets systemd supervise a Node run as a Type=notify service. systemd hands the Node a socket in
+// NOTIFY_SOCKET; the Node says READY=1 on it once it can carry traffic, and if the unit sets
+// WatchdogSec, WATCHDOG=1 often enough that systemd restarts it only when it stops saying so.
+// Without NOTIFY_SOCKET, none of this does anything.
+pub trait ServiceManager: Send {
+    fn notify_ready(&self);
+    fn notify_watchdog(&self);
+    fn watchdog_interval(&self) -> Option<Duration>;
+}
+
+// There's no systemd on Windows, so nothing there ever reads what was found in the environment
+#[cfg_attr(target_os = "windows", allow(dead_code))]
+pub struct ServiceManagerSystemd {
+    socket_path_opt: Option<PathBuf>,
+    watchdog_interval_opt: Option<Duration>,
+    logger: Logger,
+}
+
+impl ServiceManager for ServiceManagerSystemd {
+    fn notify_ready(&self) {
+        self.notify("READY=1")
+    }
+
+    fn notify_watchdog(&self) {
+        self.notify("WATCHDOG=1")
+    }
+
+    // Half the timeout, as systemd suggests, so that one late ping doesn't get the Node killed
+    fn watchdog_interval(&self) -> Option<Duration> {
+        self.watchdog_interval_opt
+    }
+}
+
+impl Default for ServiceManagerSystemd {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ServiceManagerSystemd {
+    pub fn new() -> ServiceManagerSystemd {
+        Self::from_environment(
+            env::var_os("NOTIFY_SOCKET"),
+            env::var("WATCHDOG_USEC").ok(),
+            env::var("WATCHDOG_PID").ok(),
+            std::process::id(),
+        )
+    }
+
+    fn from_environment(
+        notify_socket_opt: Option<OsString>,
+        watchdog_usec_opt: Option<String>,
+        watchdog_pid_opt: Option<String>,
+        pid: u32,
+    ) -> ServiceManagerSystemd {
+        let logger = Logger::new("Systemd");
+        let socket_path_opt = match notify_socket_opt {
+            None => None,
+            Some(ref path) if path.is_empty() => None,
+            // std can't address Linux's abstract sockets; systemd only uses them if asked to
+            Some(ref path) if path.to_string_lossy().starts_with('@') => {
+                warning!(
+                    logger,
+                    "NOTIFY_SOCKET {:?} is an abstract socket, which isn't supported; systemd won't hear from this Node",
+                    path
+                );
+                None
+            }
+            Some(path) => Some(PathBuf::from(path)),
+        };
+        // WATCHDOG_PID, if present, says which process the watchdog is meant for; a child that
+        // inherited the environment mustn't keep its parent alive
+        let watchdog_is_ours = match watchdog_pid_opt {
+            None => true,
+            Some(watchdog_pid) => watchdog_pid.parse::<u32>() == Ok(pid),
+        };
+        let watchdog_interval_opt = match (&socket_path_opt, watchdog_usec_opt) {
+            (Some(_), Some(ref usec)) if watchdog_is_ours => match usec.parse::<u64>() {
+                Ok(0) => None,
+                Ok(usec) => Some(Duration::from_micros(usec / 2)),
+                Err(_) => {
+                    warning!(
+                        logger,
+                        "WATCHDOG_USEC '{}' isn't a number of microseconds; not pinging the watchdog",
+                        usec
+                    );
+                    None
+                }
+            },
+            _ => None,
+        };
+        ServiceManagerSystemd {
+            socket_path_opt,
+            watchdog_interval_opt,
+            logger,
+        }
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    fn notify(&self, state: &str) {
+        use std::os::unix::net::UnixDatagram;
+        let socket_path = match &self.socket_path_opt {
+            Some(socket_path) => socket_path,
+            None => return,
+        };
+        match UnixDatagram::unbound()
+            .and_then(|socket| socket.send_to(state.as_bytes(), socket_path))
+        {
+            Ok(_) => trace!(self.logger, "Told systemd {}", state),
+            Err(e) => warning!(
+                self.logger,
+                "Couldn't tell systemd {} at {:?}: {}",
+                state,
+                socket_path,
+                e
+            ),
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    fn notify(&self, _state: &str) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::logging::{init_test_logging, TestLogHandler};
+
+    #[test]
+    fn without_a_notify_socket_there_is_nothing_to_notify_or_ping() {
+        let subject =
+            ServiceManagerSystemd::from_environment(None, Some("10000000".to_string()), None, 1234);
+
+        assert_eq!(subject.socket_path_opt, None);
+        assert_eq!(subject.watchdog_interval(), None);
+        subject.notify_ready();
+    }
+
+    #[test]
+    fn abstract_notify_sockets_are_refused_with_a_warning() {
+        init_test_logging();
+
+        let subject = ServiceManagerSystemd::from_environment(
+            Some(OsString::from("@/org/freedesktop/systemd1/notify")),
+            None,
+            None,
+            1234,
+        );
+
+        assert_eq!(subject.socket_path_opt, None);
+        TestLogHandler::new().exists_log_containing(
+            "WARN: Systemd: NOTIFY_SOCKET \"@/org/freedesktop/systemd1/notify\" is an abstract socket",
+        );
+    }
+
+    #[test]
+    fn watchdog_is_pinged_at_half_its_timeout() {
+        let subject = ServiceManagerSystemd::from_environment(
+            Some(OsString::from("/run/systemd/notify")),
+            Some("10000000".to_string()),
+            Some("1234".to_string()),
+            1234,
+        );
+
+        assert_eq!(subject.watchdog_interval(), Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn watchdog_meant_for_another_process_is_not_pinged() {
+        let subject = ServiceManagerSystemd::from_environment(
+            Some(OsString::from("/run/systemd/notify")),
+            Some("10000000".to_string()),
+            Some("4321".to_string()),
+            1234,
+        );
+
+        assert_eq!(subject.watchdog_interval(), None);
+    }
+
+    #[test]
+    fn unintelligible_watchdog_timeout_is_ignored_with_a_warning() {
+        init_test_logging();
+
+        let subject = ServiceManagerSystemd::from_environment(
+            Some(OsString::from("/run/systemd/notify")),
+            Some("booga".to_string()),
+            None,
+            1234,
+        );
+
+        assert_eq!(subject.watchdog_interval(), None);
+        TestLogHandler::new().exists_log_containing(
+            "WARN: Systemd: WATCHDOG_USEC 'booga' isn't a number of microseconds",
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn readiness_and_watchdog_pings_go_to_the_notify_socket() {
+        use crate::test_utils::ensure_node_home_directory_exists;
+        use std::os::unix::net::UnixDatagram;
+        let home_dir = ensure_node_home_directory_exists(
+            "systemd",
+            "readiness_and_watchdog_pings_go_to_the_notify_socket",
+        );
+        let socket_path = home_dir.join("notify");
+        let systemd = UnixDatagram::bind(&socket_path).unwrap();
+        let subject = ServiceManagerSystemd::from_environment(
+            Some(socket_path.into_os_string()),
+            None,
+            None,
+            1234,
+        );
+
+        subject.notify_ready();
+        subject.notify_watchdog();
+
+        let mut buf = [0u8; 64];
+        let len = systemd.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1");
+        let len = systemd.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"WATCHDOG=1");
+    }
+}
@@ -0,0 +1,359 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::blockchain::blockchain_interface::TRANSACTION_LITERAL;
+use crate::sub_lib::utils::localhost;
+use crate::sub_lib::wallet::Wallet;
+use crate::test_utils::find_free_port;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+type Responses = Arc<Mutex<HashMap<String, VecDeque<Value>>>>;
+type Requests = Arc<Mutex<Vec<(String, Value)>>>;
+
+/// A blockchain service for a Node to talk to instead of a real one: start it and give the Node
+/// `--blockchain-service-url` with its `url()`. It speaks just enough HTTP for web3's transport,
+/// answers each JSON-RPC method with the results it was given, and remembers every call, so that
+/// a test can follow a payment from the Node's side to the chain's and back. Methods it has no
+/// results for get a JSON-RPC error.
+pub struct BlockchainServerMock {
+    port_opt: Option<u16>,
+    responses: Responses,
+    requests: Requests,
+    running: Arc<AtomicBool>,
+}
+
+impl Default for BlockchainServerMock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for BlockchainServerMock {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        // The listener is waiting for a connection; give it one so it sees it's time to stop
+        if let Some(port) = self.port_opt {
+            let _ = TcpStream::connect(SocketAddr::new(localhost(), port));
+        }
+    }
+}
+
+impl BlockchainServerMock {
+    pub fn new() -> BlockchainServerMock {
+        BlockchainServerMock {
+            port_opt: None,
+            responses: Arc::new(Mutex::new(HashMap::new())),
+            requests: Arc::new(Mutex::new(vec![])),
+            running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // Given more than once for the same method, the results are used in order, and the last one
+    // is used from then on
+    pub fn response(self, method: &str, result: Value) -> BlockchainServerMock {
+        self.responses
+            .lock()
+            .unwrap()
+            .entry(method.to_string())
+            .or_default()
+            .push_back(result);
+        self
+    }
+
+    pub fn eth_balance(self, wei: u128) -> BlockchainServerMock {
+        self.response("eth_getBalance", json!(format!("{:#x}", wei)))
+    }
+
+    // The token contract's balanceOf is the only eth_call a Node makes
+    pub fn token_balance(self, wei: u128) -> BlockchainServerMock {
+        self.response("eth_call", json!(format!("0x{:064x}", wei)))
+    }
+
+    pub fn nonce(self, nonce: u64) -> BlockchainServerMock {
+        self.response("eth_getTransactionCount", json!(format!("{:#x}", nonce)))
+    }
+
+    pub fn block_number(self, block_number: u64) -> BlockchainServerMock {
+        self.response("eth_blockNumber", json!(format!("{:#x}", block_number)))
+    }
+
+    // What eth_sendRawTransaction says the transaction's hash is
+    pub fn transaction_hash(self, hash: &str) -> BlockchainServerMock {
+        self.response("eth_sendRawTransaction", json!(hash))
+    }
+
+    pub fn logs(self, logs: Vec<Value>) -> BlockchainServerMock {
+        self.response("eth_getLogs", Value::Array(logs))
+    }
+
+    pub fn start(mut self) -> BlockchainServerMock {
+        let port = find_free_port();
+        let listener = TcpListener::bind(SocketAddr::new(localhost(), port))
+            .expect("BlockchainServerMock couldn't bind");
+        self.port_opt = Some(port);
+        self.running.store(true, Ordering::SeqCst);
+        let responses = self.responses.clone();
+        let requests = self.requests.clone();
+        let running = self.running.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if !running.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    let responses = responses.clone();
+                    let requests = requests.clone();
+                    thread::spawn(move || serve(stream, &responses, &requests));
+                }
+            }
+        });
+        self
+    }
+
+    pub fn url(&self) -> String {
+        format!(
+            "http://{}:{}",
+            localhost(),
+            self.port_opt.expect("BlockchainServerMock isn't started")
+        )
+    }
+
+    // Each call's method and params, in the order they came in
+    pub fn requests(&self) -> Vec<(String, Value)> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    pub fn requests_for(&self, method: &str) -> Vec<Value> {
+        self.requests()
+            .into_iter()
+            .filter(|(request_method, _)| request_method == method)
+            .map(|(_, params)| params)
+            .collect()
+    }
+}
+
+// The log of a token transfer, as eth_getLogs returns it
+pub fn transfer_log(
+    from: &Wallet,
+    to: &Wallet,
+    wei: u128,
+    block_number: u64,
+    transaction_hash: &str,
+) -> Value {
+    let topic = |wallet: &Wallet| format!("0x{:0>64}", format!("{:x}", wallet.address()));
+    json!({
+        "address": "0x0000000000000000000000000000000000000000",
+        "blockHash": format!("0x{:064x}", block_number),
+        "blockNumber": format!("{:#x}", block_number),
+        "data": format!("0x{:064x}", wei),
+        "logIndex": "0x0",
+        "removed": false,
+        "topics": [format!("{:#x}", TRANSACTION_LITERAL), topic(from), topic(to)],
+        "transactionHash": transaction_hash,
+        "transactionIndex": "0x0"
+    })
+}
+
+// One connection's requests, one after another, until the client hangs up
+fn serve(stream: TcpStream, responses: &Responses, requests: &Requests) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(_) => return,
+    };
+    let mut reader = BufReader::new(stream);
+    loop {
+        let body = match read_request_body(&mut reader) {
+            Some(body) => body,
+            None => return,
+        };
+        let reply = match serde_json::from_slice::<Value>(&body) {
+            Ok(Value::Array(calls)) => Value::Array(
+                calls
+                    .iter()
+                    .map(|call| answer(call, responses, requests))
+                    .collect(),
+            ),
+            Ok(call) => answer(&call, responses, requests),
+            Err(e) => json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": {"code": -32700, "message": e.to_string()}
+            }),
+        }
+        .to_string();
+        let written = write!(
+            writer,
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            reply.len(),
+            reply
+        );
+        if written.is_err() {
+            return;
+        }
+    }
+}
+
+fn read_request_body(reader: &mut BufReader<TcpStream>) -> Option<Vec<u8>> {
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) | Err(_) => return None,
+            Ok(_) if line == "\r\n" => break,
+            Ok(_) => {
+                let mut parts = line.splitn(2, ':');
+                let name = parts.next().unwrap_or("").trim();
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = parts.next().unwrap_or("").trim().parse().ok()?;
+                }
+            }
+        }
+    }
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body).ok()?;
+    Some(body)
+}
+
+fn answer(call: &Value, responses: &Responses, requests: &Requests) -> Value {
+    let method = call["method"].as_str().unwrap_or("").to_string();
+    requests
+        .lock()
+        .unwrap()
+        .push((method.clone(), call["params"].clone()));
+    let mut responses = responses.lock().unwrap();
+    let result_opt = responses.get_mut(&method).and_then(|results| {
+        if results.len() > 1 {
+            results.pop_front()
+        } else {
+            results.front().cloned()
+        }
+    });
+    match result_opt {
+        Some(result) => json!({"jsonrpc": "2.0", "id": call["id"], "result": result}),
+        None => json!({
+            "jsonrpc": "2.0",
+            "id": call["id"],
+            "error": {
+                "code": -32601,
+                "message": format!("BlockchainServerMock has no results for {}", method)
+            }
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    fn post(url: &str, body: &str) -> Value {
+        let addr = SocketAddr::from_str(&url["http://".len()..]).unwrap();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        write!(
+            stream,
+            "POST / HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut reader = BufReader::new(stream);
+        let body = read_request_body(&mut reader).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn call(method: &str, id: u64) -> String {
+        json!({"jsonrpc": "2.0", "id": id, "method": method, "params": ["0x1234"]}).to_string()
+    }
+
+    #[test]
+    fn answers_with_the_results_it_was_given_and_repeats_the_last() {
+        let subject = BlockchainServerMock::new()
+            .nonce(1)
+            .nonce(2)
+            .eth_balance(1_000_000)
+            .start();
+
+        let results = vec![
+            post(&subject.url(), &call("eth_getTransactionCount", 1)),
+            post(&subject.url(), &call("eth_getTransactionCount", 2)),
+            post(&subject.url(), &call("eth_getTransactionCount", 3)),
+            post(&subject.url(), &call("eth_getBalance", 4)),
+        ];
+
+        assert_eq!(
+            results,
+            vec![
+                json!({"jsonrpc": "2.0", "id": 1, "result": "0x1"}),
+                json!({"jsonrpc": "2.0", "id": 2, "result": "0x2"}),
+                json!({"jsonrpc": "2.0", "id": 3, "result": "0x2"}),
+                json!({"jsonrpc": "2.0", "id": 4, "result": "0xf4240"}),
+            ]
+        );
+        assert_eq!(
+            subject.requests_for("eth_getTransactionCount"),
+            vec![json!(["0x1234"]), json!(["0x1234"]), json!(["0x1234"])]
+        );
+    }
+
+    #[test]
+    fn methods_without_results_get_an_error_and_batches_get_a_batch() {
+        let subject = BlockchainServerMock::new().block_number(42).start();
+        let batch = format!(
+            "[{},{}]",
+            call("eth_blockNumber", 1),
+            call("eth_sendRawTransaction", 2)
+        );
+
+        let result = post(&subject.url(), &batch);
+
+        assert_eq!(
+            result,
+            json!([
+                {"jsonrpc": "2.0", "id": 1, "result": "0x2a"},
+                {"jsonrpc": "2.0", "id": 2, "error": {
+                    "code": -32601,
+                    "message": "BlockchainServerMock has no results for eth_sendRawTransaction"
+                }}
+            ])
+        );
+        assert_eq!(
+            subject
+                .requests()
+                .into_iter()
+                .map(|(method, _)| method)
+                .collect::<Vec<String>>(),
+            vec!["eth_blockNumber", "eth_sendRawTransaction"]
+        );
+    }
+
+    #[test]
+    fn transfer_log_names_both_wallets_and_the_amount() {
+        let from = Wallet::from_str("0x3f69f9efd4f2592fd70be8c32ecd9dce71c472fc").unwrap();
+        let to = Wallet::from_str("0xadc1853c7859369639eb414b6342b36288fe6092").unwrap();
+
+        let result = transfer_log(&from, &to, 0x10_0000_0000_0000, 4_974_179, "0xabcd");
+
+        assert_eq!(
+            result["topics"],
+            json!([
+                "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef",
+                "0x0000000000000000000000003f69f9efd4f2592fd70be8c32ecd9dce71c472fc",
+                "0x000000000000000000000000adc1853c7859369639eb414b6342b36288fe6092"
+            ])
+        );
+        assert_eq!(
+            result["data"],
+            json!("0x0000000000000000000000000000000000000000000000000010000000000000")
+        );
+        assert_eq!(result["blockNumber"], json!("0x4be663"));
+        assert_eq!(result["transactionHash"], json!("0xabcd"));
+    }
+}
@@ -0,0 +1,67 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use crate::sub_lib::clock::Clock;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// A Clock that stands still until the test moves it. Clones share the same time, so a test can
+/// keep one and give another to the actor under test. The monotonic side starts at the moment
+/// the ClockMock is made and moves with the time of day.
+#[derive(Clone)]
+pub struct ClockMock {
+    times: Arc<Mutex<(SystemTime, Instant)>>,
+}
+
+impl Clock for ClockMock {
+    fn now(&self) -> SystemTime {
+        self.times.lock().unwrap().0
+    }
+
+    fn instant(&self) -> Instant {
+        self.times.lock().unwrap().1
+    }
+}
+
+impl ClockMock {
+    pub fn new(now: SystemTime) -> ClockMock {
+        ClockMock {
+            times: Arc::new(Mutex::new((now, Instant::now()))),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        let mut times = self.times.lock().unwrap();
+        times.0 += duration;
+        times.1 += duration;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clones_move_together() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let subject = ClockMock::new(start);
+        let clone = subject.clone();
+        let start_instant = subject.instant();
+
+        clone.advance(Duration::from_secs(60));
+
+        assert_eq!(subject.now(), start + Duration::from_secs(60));
+        assert_eq!(clone.now(), start + Duration::from_secs(60));
+        assert_eq!(subject.instant(), start_instant + Duration::from_secs(60));
+        assert_eq!(clone.instant(), start_instant + Duration::from_secs(60));
+    }
+
+    #[test]
+    fn stands_still_until_advanced() {
+        let subject = ClockMock::new(SystemTime::now());
+        let before = subject.instant();
+
+        std::thread::sleep(Duration::from_millis(10));
+
+        assert_eq!(subject.instant(), before);
+    }
+}
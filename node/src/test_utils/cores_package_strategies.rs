@@ -0,0 +1,204 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::blockchain::blockchain_interface::contract_address;
+use crate::json_masquerader::JsonMasquerader;
+use crate::sub_lib::cryptde::PublicKey;
+use crate::sub_lib::cryptde_null::CryptDENull;
+use crate::sub_lib::dispatcher::Component;
+use crate::sub_lib::hopper::MessageType;
+use crate::sub_lib::obfuscation::{ObfuscationConfig, ObfuscationMetrics};
+use crate::sub_lib::proxy_client::{ClientResponsePayload, DnsResolveFailure};
+use crate::sub_lib::proxy_server::{ClientRequestPayload, ProxyProtocol};
+use crate::sub_lib::route::{Route, RouteSegment};
+use crate::sub_lib::sequence_buffer::SequencedPacket;
+use crate::sub_lib::stream_key::StreamKey;
+use crate::test_utils::{make_paying_wallet, DEFAULT_CHAIN_ID};
+use proptest::collection::vec;
+use proptest::option;
+use proptest::prelude::*;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Proptest strategies for the pieces of a CORES package and the trip it takes between Nodes.
+// Every key can be turned into a working CryptDENull with `CryptDENull::from`, so a property can
+// play each Node on a generated Route.
+
+pub fn public_key() -> impl Strategy<Value = PublicKey> {
+    vec(any::<u8>(), 1..16).prop_map(PublicKey::from)
+}
+
+pub fn stream_key() -> impl Strategy<Value = StreamKey> {
+    (public_key(), any::<[u8; 4]>(), any::<u16>()).prop_map(|(key, ip, port)| {
+        StreamKey::new(key, SocketAddr::new(Ipv4Addr::from(ip).into(), port))
+    })
+}
+
+pub fn sequenced_packet() -> impl Strategy<Value = SequencedPacket> {
+    (vec(any::<u8>(), 0..2000), any::<u64>(), any::<bool>()).prop_map(
+        |(data, sequence_number, last_data)| SequencedPacket::new(data, sequence_number, last_data),
+    )
+}
+
+pub fn client_request() -> impl Strategy<Value = ClientRequestPayload> {
+    (
+        stream_key(),
+        sequenced_packet(),
+        option::of("[a-z]{1,16}\\.com"),
+        any::<u16>(),
+        prop_oneof![
+            Just(ProxyProtocol::HTTP),
+            Just(ProxyProtocol::TLS),
+            Just(ProxyProtocol::SMTP),
+            Just(ProxyProtocol::FTP)
+        ],
+        public_key(),
+    )
+        .prop_map(
+            |(stream_key, sequenced_packet, target_hostname, target_port, protocol, key)| {
+                ClientRequestPayload {
+                    version: ClientRequestPayload::version(),
+                    stream_key,
+                    sequenced_packet,
+                    target_hostname,
+                    target_port,
+                    protocol,
+                    originator_public_key: key,
+                }
+            },
+        )
+}
+
+pub fn client_response() -> impl Strategy<Value = ClientResponsePayload> {
+    (stream_key(), sequenced_packet()).prop_map(|(stream_key, sequenced_packet)| {
+        ClientResponsePayload {
+            version: ClientResponsePayload::version(),
+            stream_key,
+            sequenced_packet,
+        }
+    })
+}
+
+// Gossip is left out: it takes a database to make
+pub fn payload() -> impl Strategy<Value = MessageType> {
+    prop_oneof![
+        client_request().prop_map(MessageType::ClientRequest),
+        client_response().prop_map(MessageType::ClientResponse),
+        stream_key().prop_map(|stream_key| DnsResolveFailure::new(stream_key).into()),
+    ]
+}
+
+prop_compose! {
+    // A one-way Route built by the first of the Nodes it passes through, with their keys in order
+    pub fn route()(
+        keys in vec(public_key(), 2..6),
+        paid in any::<bool>(),
+        recipient in prop_oneof![
+            Just(Component::ProxyClient),
+            Just(Component::ProxyServer),
+            Just(Component::Neighborhood),
+        ]
+    ) -> (Vec<PublicKey>, Route) {
+        let route = Route::one_way(
+            RouteSegment::new(keys.iter().collect(), recipient),
+            &CryptDENull::from(&keys[0], DEFAULT_CHAIN_ID),
+            if paid { Some(make_paying_wallet(b"wallet")) } else { None },
+            Some(contract_address(DEFAULT_CHAIN_ID)),
+        )
+        .expect("Generated an unbuildable Route");
+        (keys, route)
+    }
+}
+
+/// How a package is masked and how the network breaks it up on its way to the next Node: the
+/// padding allowed, and the sizes of the reads the receiver gets it in.
+#[derive(Clone, Debug)]
+pub struct Framing {
+    pub max_padding: usize,
+    pub read_sizes: Vec<usize>,
+}
+
+impl Framing {
+    pub fn masquerader(&self) -> JsonMasquerader {
+        if self.max_padding == 0 {
+            JsonMasquerader::new()
+        } else {
+            JsonMasquerader::with_padding(
+                ObfuscationConfig {
+                    max_jitter: Duration::from_millis(0),
+                    max_padding: self.max_padding,
+                },
+                Arc::new(ObfuscationMetrics::new()),
+            )
+        }
+    }
+
+    // Once the read sizes run out, the last read takes whatever is left
+    pub fn reads(&self, data: &[u8]) -> Vec<Vec<u8>> {
+        let mut remaining = data;
+        let mut reads = vec![];
+        for size in &self.read_sizes {
+            if remaining.is_empty() {
+                break;
+            }
+            let (read, rest) = remaining.split_at((*size).min(remaining.len()));
+            reads.push(read.to_vec());
+            remaining = rest;
+        }
+        if !remaining.is_empty() {
+            reads.push(remaining.to_vec());
+        }
+        reads
+    }
+}
+
+pub fn framing() -> impl Strategy<Value = Framing> {
+    (
+        prop_oneof![Just(0usize), 1..100usize],
+        vec(1..500usize, 0..10),
+    )
+        .prop_map(|(max_padding, read_sizes)| Framing {
+            max_padding,
+            read_sizes,
+        })
+}
+
+pub fn garbage() -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), 0..1000)
+}
+
+// Gets past the JsonFramer, so the masquerader has to deal with it
+pub fn json_shaped_garbage() -> impl Strategy<Value = Vec<u8>> {
+    "\\{[ -~]{0,200}\\}".prop_map(|s| s.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_split_the_data_as_scripted_and_keep_the_rest_for_last() {
+        let subject = Framing {
+            max_padding: 0,
+            read_sizes: vec![2, 3],
+        };
+
+        let result = subject.reads(b"abcdefgh");
+
+        assert_eq!(
+            result,
+            vec![b"ab".to_vec(), b"cde".to_vec(), b"fgh".to_vec()]
+        );
+    }
+
+    #[test]
+    fn reads_stop_when_the_data_runs_out() {
+        let subject = Framing {
+            max_padding: 0,
+            read_sizes: vec![5, 5, 5],
+        };
+
+        let result = subject.reads(b"abcdefgh");
+
+        assert_eq!(result, vec![b"abcde".to_vec(), b"fgh".to_vec()]);
+    }
+}
@@ -0,0 +1,96 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use dns_utility_lib::dns_modifier::DnsModifier;
+use dns_utility_lib::dns_modifier_factory::DnsModifierFactory;
+use dns_utility_lib::repair::DnsProbe;
+use std::cell::RefCell;
+use std::io;
+use std::net::IpAddr;
+
+#[derive(Default)]
+pub struct DnsModifierMock {
+    revert_results: RefCell<Vec<Result<(), String>>>,
+    inspect_results: RefCell<Vec<(String, Result<(), String>)>>,
+    original_servers_results: RefCell<Vec<Result<Vec<IpAddr>, String>>>,
+}
+
+impl DnsModifier for DnsModifierMock {
+    fn type_name(&self) -> &'static str {
+        "DnsModifierMock"
+    }
+
+    fn subvert(&self) -> Result<(), String> {
+        unimplemented!()
+    }
+
+    fn revert(&self) -> Result<(), String> {
+        self.revert_results.borrow_mut().remove(0)
+    }
+
+    fn inspect(&self, stdout: &mut (dyn io::Write + Send)) -> Result<(), String> {
+        let (output, result) = self.inspect_results.borrow_mut().remove(0);
+        write!(stdout, "{}", output).unwrap();
+        result
+    }
+
+    fn original_servers(&self) -> Result<Vec<IpAddr>, String> {
+        self.original_servers_results.borrow_mut().remove(0)
+    }
+}
+
+impl DnsModifierMock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn revert_result(self, result: Result<(), String>) -> Self {
+        self.revert_results.borrow_mut().push(result);
+        self
+    }
+
+    pub fn inspect_result(self, output: &str, result: Result<(), String>) -> Self {
+        self.inspect_results
+            .borrow_mut()
+            .push((output.to_string(), result));
+        self
+    }
+
+    pub fn original_servers_result(self, result: Result<Vec<IpAddr>, String>) -> Self {
+        self.original_servers_results.borrow_mut().push(result);
+        self
+    }
+}
+
+#[derive(Default)]
+pub struct DnsModifierFactoryMock {
+    make_results: RefCell<Vec<Option<Box<dyn DnsModifier + Send>>>>,
+}
+
+impl DnsModifierFactory for DnsModifierFactoryMock {
+    fn make(&self) -> Option<Box<dyn DnsModifier>> {
+        self.make_results
+            .borrow_mut()
+            .remove(0)
+            .map(|modifier| modifier as Box<dyn DnsModifier>)
+    }
+}
+
+impl DnsModifierFactoryMock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn make_result(self, result: Option<Box<dyn DnsModifier + Send>>) -> Self {
+        self.make_results.borrow_mut().push(result);
+        self
+    }
+}
+
+pub struct DnsProbeMock {
+    pub answered: bool,
+}
+
+impl DnsProbe for DnsProbeMock {
+    fn is_answered(&self) -> bool {
+        self.answered
+    }
+}
@@ -0,0 +1,200 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+// Golden files pin down the wire format of everything we send from one Node to another. Each
+// golden file holds the serialized bytes of a canonical example of one message type, in hex.
+// If a change to a message type alters its serialization, the corresponding golden test will
+// fail; if the change is intentional, rerun the failing tests with REGENERATE_GOLDEN_FILES set
+// in the environment and commit the rewritten golden files along with the change.
+
+use crate::blockchain::blockchain_interface::contract_address;
+use crate::sub_lib::cryptde::PublicKey;
+use crate::sub_lib::cryptde_null::CryptDENull;
+use crate::sub_lib::dispatcher::Component;
+use crate::sub_lib::route::{Route, RouteSegment};
+use crate::sub_lib::stream_key::StreamKey;
+use crate::test_utils::{make_paying_wallet, DEFAULT_CHAIN_ID};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::env;
+use std::fmt::Debug;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+pub const GOLDEN_FILES_DIR: &str = "tests/golden";
+pub const REGENERATE_GOLDEN_FILES: &str = "REGENERATE_GOLDEN_FILES";
+const BYTES_PER_LINE: usize = 32;
+
+pub fn golden_file_path(name: &str) -> PathBuf {
+    PathBuf::from(GOLDEN_FILES_DIR).join(format!("{}.hex", name))
+}
+
+pub fn bytes_to_golden_hex(bytes: &[u8]) -> String {
+    bytes
+        .chunks(BYTES_PER_LINE)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>()
+                + "\n"
+        })
+        .collect()
+}
+
+pub fn golden_hex_to_bytes(hex: &str) -> Result<Vec<u8>, String> {
+    let digits = hex
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<Vec<char>>();
+    if digits.len() % 2 != 0 {
+        return Err(format!(
+            "Golden file contains an odd number ({}) of hex digits",
+            digits.len()
+        ));
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let pair = pair.iter().collect::<String>();
+            u8::from_str_radix(&pair, 16)
+                .map_err(|_| format!("Golden file contains non-hex data: '{}'", pair))
+        })
+        .collect()
+}
+
+pub fn assert_golden_bytes(name: &str, actual: &[u8]) {
+    let path = golden_file_path(name);
+    if env::var(REGENERATE_GOLDEN_FILES).is_ok() {
+        fs::create_dir_all(GOLDEN_FILES_DIR).expect("Couldn't create golden-file directory");
+        fs::write(&path, bytes_to_golden_hex(actual))
+            .unwrap_or_else(|e| panic!("Couldn't write golden file {:?}: {}", path, e));
+        return;
+    }
+    let expected_hex = fs::read_to_string(&path).unwrap_or_else(|e| {
+        panic!(
+            "Couldn't read golden file {:?} ({}); set {} to create it",
+            path, e, REGENERATE_GOLDEN_FILES
+        )
+    });
+    let expected = golden_hex_to_bytes(&expected_hex)
+        .unwrap_or_else(|e| panic!("Golden file {:?} is corrupt: {}", path, e));
+    assert_eq!(
+        bytes_to_golden_hex(actual),
+        bytes_to_golden_hex(&expected),
+        "Wire format of '{}' no longer matches {:?}. If this change is intentional, rerun with {} set and commit the new golden file.",
+        name,
+        path,
+        REGENERATE_GOLDEN_FILES
+    );
+}
+
+pub fn assert_golden_serialization<T>(name: &str, subject: &T)
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let serialized = serde_cbor::ser::to_vec(subject).expect("Serialization failed");
+    assert_golden_bytes(name, &serialized);
+    let deserialized =
+        serde_cbor::de::from_slice::<T>(&serialized).expect("Deserialization failed");
+    assert_eq!(&deserialized, subject);
+}
+
+// Golden data must not depend on anything random, so use this instead of test_utils::cryptde().
+pub fn golden_cryptde() -> CryptDENull {
+    CryptDENull::from(&PublicKey::new(b"golden"), DEFAULT_CHAIN_ID)
+}
+
+pub fn golden_route() -> Route {
+    let key1 = PublicKey::new(&[1, 2, 3, 4]);
+    let key2 = PublicKey::new(&[4, 3, 2, 1]);
+    Route::round_trip(
+        RouteSegment::new(vec![&key1, &key2], Component::ProxyClient),
+        RouteSegment::new(vec![&key2, &key1], Component::ProxyServer),
+        &golden_cryptde(),
+        Some(make_paying_wallet(b"golden")),
+        1234,
+        Some(contract_address(DEFAULT_CHAIN_ID)),
+    )
+    .expect("Couldn't construct golden Route")
+}
+
+pub fn golden_stream_key() -> StreamKey {
+    StreamKey::new(
+        PublicKey::new(b"golden"),
+        SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+    )
+}
+
+#[macro_export]
+macro_rules! golden_test {
+    ($test_name:ident, $subject:expr) => {
+        #[test]
+        fn $test_name() {
+            $crate::test_utils::golden_files::assert_golden_serialization(
+                stringify!($test_name),
+                &$subject,
+            );
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_to_golden_hex_wraps_lines() {
+        let bytes = (0..40u8).collect::<Vec<u8>>();
+
+        let result = bytes_to_golden_hex(&bytes);
+
+        assert_eq!(
+            result,
+            "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f\n\
+             2021222324252627\n"
+        );
+    }
+
+    #[test]
+    fn golden_hex_to_bytes_ignores_whitespace() {
+        let result = golden_hex_to_bytes("0a0B\n ff\t00\n").unwrap();
+
+        assert_eq!(result, vec![0x0A, 0x0B, 0xFF, 0x00]);
+    }
+
+    #[test]
+    fn golden_hex_to_bytes_complains_about_odd_digit_count() {
+        let result = golden_hex_to_bytes("0a0");
+
+        assert_eq!(
+            result,
+            Err("Golden file contains an odd number (3) of hex digits".to_string())
+        );
+    }
+
+    #[test]
+    fn golden_hex_to_bytes_complains_about_non_hex_data() {
+        let result = golden_hex_to_bytes("0aZZ");
+
+        assert_eq!(
+            result,
+            Err("Golden file contains non-hex data: 'ZZ'".to_string())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Wire format of 'golden_files_self_test' no longer matches")]
+    fn assert_golden_bytes_complains_about_mismatch() {
+        if env::var(REGENERATE_GOLDEN_FILES).is_ok() {
+            panic!("Wire format of 'golden_files_self_test' no longer matches");
+        }
+        assert_golden_bytes("golden_files_self_test", &[1, 2, 3, 5]);
+    }
+
+    #[test]
+    fn assert_golden_bytes_accepts_match() {
+        assert_golden_bytes("golden_files_self_test", &[1, 2, 3, 4]);
+    }
+}
@@ -1,18 +1,33 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
+pub mod blockchain_server_mock;
 #[macro_use]
 pub mod channel_wrapper_mocks;
+pub mod clock_mock;
 pub mod config_dao_mock;
+#[cfg(test)]
+pub mod cores_package_strategies;
 pub mod data_hunk;
 pub mod data_hunk_framer;
+pub mod dns_modifier_mock;
 pub mod environment_guard;
+#[macro_use]
+pub mod golden_files;
 pub mod little_tcp_server;
 pub mod logging;
+pub mod node_chain;
+pub mod node_harness;
 pub mod persistent_configuration_mock;
+pub mod random_seeded;
 pub mod recorder;
+pub mod route_builder;
+pub mod scripted_udp_socket_wrapper;
+pub mod secret_store_mock;
 pub mod stream_connector_mock;
+pub mod tcp_stream_mock;
 pub mod tcp_wrapper_mocks;
 pub mod tokio_wrapper_mocks;
+pub mod ui_client;
 
 use crate::blockchain::bip32::Bip32ECKeyPair;
 use crate::blockchain::blockchain_interface::contract_address;
@@ -36,9 +51,11 @@ use crate::sub_lib::route::Route;
 use crate::sub_lib::route::RouteSegment;
 use crate::sub_lib::sequence_buffer::SequencedPacket;
 use crate::sub_lib::stream_key::StreamKey;
+use crate::sub_lib::ui_gateway::{DnsLeakReport, DnsLeakVerdict};
 use crate::sub_lib::utils::localhost;
 use crate::sub_lib::wallet::Wallet;
 use crate::test_utils::persistent_configuration_mock::PersistentConfigurationMock;
+use crate::test_utils::route_builder::RouteBuilder;
 use ethsign_crypto::Keccak256;
 use lazy_static::lazy_static;
 use regex::Regex;
@@ -53,8 +70,8 @@ use std::io::Read;
 use std::io::Write;
 use std::io::{Error, ErrorKind};
 use std::iter::repeat;
+use std::net::{IpAddr, SocketAddr, TcpListener};
 use std::net::{Shutdown, TcpStream};
-use std::net::{SocketAddr, TcpListener};
 use std::path::PathBuf;
 use std::str::from_utf8;
 use std::str::FromStr;
@@ -123,6 +140,9 @@ impl Write for ByteArrayWriter {
 pub struct ByteArrayReader {
     byte_array: Vec<u8>,
     position: usize,
+    next_error: Option<Error>,
+    // Most bytes each of the next reads may return, however big its buffer
+    chunk_sizes: Vec<usize>,
 }
 
 impl ByteArrayReader {
@@ -130,13 +150,30 @@ impl ByteArrayReader {
         ByteArrayReader {
             byte_array: byte_array.to_vec(),
             position: 0,
+            next_error: None,
+            chunk_sizes: vec![],
         }
     }
+
+    pub fn reject_next_read(&mut self, error: Error) {
+        self.next_error = Some(error);
+    }
+
+    // A rejected read doesn't use up a chunk size; reads past the schedule return all they can
+    pub fn read_in_chunks(&mut self, chunk_sizes: Vec<usize>) {
+        self.chunk_sizes = chunk_sizes;
+    }
 }
 
 impl Read for ByteArrayReader {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let to_copy = min(buf.len(), self.byte_array.len() - self.position);
+        if let Some(error) = self.next_error.take() {
+            return Err(error);
+        }
+        let mut to_copy = min(buf.len(), self.byte_array.len() - self.position);
+        if !self.chunk_sizes.is_empty() {
+            to_copy = min(to_copy, self.chunk_sizes.remove(0));
+        }
         for idx in 0..to_copy {
             buf[idx] = self.byte_array[self.position + idx]
         }
@@ -270,19 +307,7 @@ pub fn make_meaningless_message_type() -> MessageType {
 }
 
 pub fn make_meaningless_route() -> Route {
-    Route::one_way(
-        RouteSegment::new(
-            vec![
-                &PublicKey::new(&b"ooga"[..]),
-                &PublicKey::new(&b"booga"[..]),
-            ],
-            Component::ProxyClient,
-        ),
-        cryptde(),
-        Some(make_paying_wallet(b"irrelevant")),
-        Some(contract_address(DEFAULT_CHAIN_ID)),
-    )
-    .unwrap()
+    RouteBuilder::new().build()
 }
 
 pub fn make_meaningless_public_key() -> PublicKey {
@@ -301,11 +326,23 @@ pub fn make_meaningless_wallet_private_key() -> PlainData {
 pub fn make_default_persistent_configuration() -> PersistentConfigurationMock {
     PersistentConfigurationMock::new()
         .earning_wallet_from_address_result(None)
+        .earning_wallet_address_result(None)
         .consuming_wallet_derivation_path_result(None)
         .consuming_wallet_public_key_result(None)
         .encrypted_mnemonic_seed_result(None)
 }
 
+pub fn make_dns_leak_report(verdict: DnsLeakVerdict) -> DnsLeakReport {
+    DnsLeakReport {
+        canary: "canary.example.com".to_string(),
+        node_dns_server: SocketAddr::from_str("127.0.0.1:53").unwrap(),
+        node_answer: Some(IpAddr::from_str("127.0.0.1").unwrap()),
+        system_answers: vec![IpAddr::from_str("93.184.216.34").unwrap()],
+        system_error: None,
+        verdict,
+    }
+}
+
 pub fn route_to_proxy_client(key: &PublicKey, cryptde: &dyn CryptDE) -> Route {
     shift_one_hop(zero_hop_route_response(key, cryptde).route, cryptde)
 }
@@ -620,13 +657,44 @@ mod tests {
     use crate::sub_lib::hop::LiveHop;
     use crate::sub_lib::neighborhood::ExpectedService;
     use std::borrow::BorrowMut;
-    use std::iter;
     use std::ops::Deref;
     use std::sync::Arc;
     use std::sync::Mutex;
     use std::thread;
     use std::time::Duration;
 
+    #[test]
+    fn byte_array_reader_reads_in_scheduled_chunks() {
+        let mut subject = ByteArrayReader::new(b"abcdefghij");
+        subject.read_in_chunks(vec![3, 0, 20]);
+        let mut buf = [0u8; 8];
+
+        let first = subject.read(&mut buf).unwrap();
+        assert_eq!(&buf[..first], b"abc");
+        let second = subject.read(&mut buf).unwrap();
+        let third = subject.read(&mut buf).unwrap();
+        assert_eq!(&buf[..third], b"defghij");
+        let fourth = subject.read(&mut buf).unwrap();
+
+        assert_eq!((first, second, third, fourth), (3, 0, 7, 0));
+    }
+
+    #[test]
+    fn byte_array_reader_rejects_only_the_next_read() {
+        let mut subject = ByteArrayReader::new(b"abc");
+        subject.read_in_chunks(vec![2]);
+        subject.reject_next_read(Error::from(ErrorKind::Interrupted));
+        let mut buf = [0u8; 8];
+
+        let first = subject.read(&mut buf);
+        let second = subject.read(&mut buf).unwrap();
+        assert_eq!(&buf[..second], b"ab");
+        let third = subject.read(&mut buf).unwrap();
+        assert_eq!(&buf[..third], b"c");
+
+        assert_eq!(first.err().unwrap().kind(), ErrorKind::Interrupted);
+    }
+
     #[test]
     fn characterize_zero_hop_route() {
         let cryptde = cryptde();
@@ -666,8 +734,6 @@ mod tests {
 
         let subject = route_to_proxy_client(&key, cryptde);
 
-        let mut garbage_can: Vec<u8> = iter::repeat(0u8).take(96).collect();
-        cryptde.random(&mut garbage_can[..]);
         assert_eq!(
             subject.hops,
             vec!(
@@ -678,7 +744,7 @@ mod tests {
                     .encode(&key, cryptde)
                     .unwrap(),
                 encrypt_return_route_id(0, cryptde),
-                CryptData::new(&garbage_can[..])
+                CryptData::new(&[b'4'; 96][..])
             )
         );
     }
@@ -690,8 +756,6 @@ mod tests {
 
         let subject = route_from_proxy_client(&key, cryptde);
 
-        let mut garbage_can: Vec<u8> = iter::repeat(0u8).take(96).collect();
-        cryptde.random(&mut garbage_can[..]);
         assert_eq!(
             subject.hops,
             vec!(
@@ -702,7 +766,7 @@ mod tests {
                     .encode(&key, cryptde)
                     .unwrap(),
                 encrypt_return_route_id(0, cryptde),
-                CryptData::new(&garbage_can[..])
+                CryptData::new(&[b'4'; 96][..])
             )
         );
     }
@@ -714,10 +778,6 @@ mod tests {
 
         let subject = route_to_proxy_server(&key, cryptde);
 
-        let mut first_garbage_can: Vec<u8> = iter::repeat(0u8).take(96).collect();
-        let mut second_garbage_can: Vec<u8> = iter::repeat(0u8).take(96).collect();
-        cryptde.random(&mut first_garbage_can[..]);
-        cryptde.random(&mut second_garbage_can[..]);
         assert_eq!(
             subject.hops,
             vec!(
@@ -725,8 +785,8 @@ mod tests {
                     .encode(&key, cryptde)
                     .unwrap(),
                 encrypt_return_route_id(0, cryptde),
-                CryptData::new(&first_garbage_can[..]),
-                CryptData::new(&second_garbage_can[..]),
+                CryptData::new(&[b'4'; 96][..]),
+                CryptData::new(&[b'4'; 96][..]),
             )
         );
     }
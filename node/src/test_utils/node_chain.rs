@@ -0,0 +1,407 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use crate::bootstrapper::BootstrapperConfig;
+use crate::neighborhood::node_record::NodeRecord;
+use crate::sub_lib::cryptde::{CryptDE, PublicKey};
+use crate::sub_lib::cryptde_real::CryptDEReal;
+use crate::sub_lib::dispatcher::{Endpoint, InboundClientData};
+use crate::sub_lib::neighborhood::NeighborhoodMode;
+use crate::sub_lib::node_addr::NodeAddr;
+use crate::sub_lib::proxy_client::ExitProtections;
+use crate::sub_lib::sequence_buffer::{SequenceBuffer, SequencedPacket};
+use crate::sub_lib::stream_handler_pool::TransmitDataMsg;
+use crate::test_utils::node_harness::{node_harness_builder, NodeHarness};
+use crate::test_utils::{find_free_port, make_paying_wallet, make_wallet, rate_pack};
+use actix::Recipient;
+use std::collections::HashMap;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const NODE_NAMES: [&str; 3] = ["originating", "relay", "exit"];
+const ORIGINATING: usize = 0;
+// Originating to relay to exit: the longest route three Nodes can make
+const CHAIN_HOP_COUNT: usize = 2;
+// Nothing listens here; it only tells one Node's streams from another's
+const CLANDESTINE_PORT: u16 = 5000;
+const BROWSER_PORT: u16 = 4000;
+
+type Browsers = Arc<Mutex<HashMap<SocketAddr, Sender<TransmitDataMsg>>>>;
+
+#[derive(Clone)]
+struct ChainLink {
+    public_key: PublicKey,
+    socket_addr: SocketAddr,
+    ibcd_sub: Recipient<InboundClientData>,
+}
+
+/// Three NodeHarnesses in a row—originating, relay and exit—each neighbored only to the next, so
+/// that a browser's requests of the originating Node take a two-hop route out through the exit
+/// Node and back. Whatever one Node would write to another's clandestine stream goes straight to
+/// the other's Dispatcher, with real CryptDEs on both ends; the exit Node opens real connections
+/// to servers, so a server on this machine can be reached at its IP address and port.
+pub struct NodeChain {
+    harnesses: Vec<NodeHarness>,
+    connector: BrowserConnector,
+}
+
+impl NodeChain {
+    pub fn start(data_directory: &PathBuf) -> NodeChain {
+        let chain_id = BootstrapperConfig::new().blockchain_bridge_config.chain_id;
+        let cryptdes = NODE_NAMES
+            .iter()
+            .map(|_| {
+                let cryptde: &'static dyn CryptDE = Box::leak(Box::new(CryptDEReal::new(chain_id)));
+                cryptde
+            })
+            .collect::<Vec<&'static dyn CryptDE>>();
+        let socket_addrs = (0..NODE_NAMES.len())
+            .map(|index| {
+                SocketAddr::new(
+                    IpAddr::V4(Ipv4Addr::new(1, 0, 0, index as u8 + 1)),
+                    CLANDESTINE_PORT,
+                )
+            })
+            .collect::<Vec<SocketAddr>>();
+        let node_records = (0..NODE_NAMES.len())
+            .map(|index| Self::make_node_record(&cryptdes, &socket_addrs, index))
+            .collect::<Vec<NodeRecord>>();
+        let (outbound_txs, outbound_rxs): (Vec<_>, Vec<_>) = NODE_NAMES
+            .iter()
+            .map(|_| mpsc::channel::<TransmitDataMsg>())
+            .unzip();
+        let harnesses = outbound_txs
+            .into_iter()
+            .enumerate()
+            .map(|(index, outbound_tx)| {
+                let node_data_directory = data_directory.join(NODE_NAMES[index]);
+                fs::create_dir_all(&node_data_directory)
+                    .expect("Couldn't create Node's data directory");
+                let known_nodes = node_records
+                    .iter()
+                    .enumerate()
+                    .filter(|(other, _)| *other != index)
+                    .map(|(_, node_record)| node_record.clone())
+                    .collect();
+                node_harness_builder(&node_data_directory)
+                    .config(Self::make_config(
+                        &node_data_directory,
+                        &socket_addrs[index],
+                        index,
+                    ))
+                    .cryptde(cryptdes[index])
+                    .known_nodes(known_nodes)
+                    .minimum_hop_count(CHAIN_HOP_COUNT)
+                    .outbound_sender(outbound_tx)
+                    .start()
+            })
+            .collect::<Vec<NodeHarness>>();
+        let links = harnesses
+            .iter()
+            .enumerate()
+            .map(|(index, harness)| ChainLink {
+                public_key: cryptdes[index].public_key().clone(),
+                socket_addr: socket_addrs[index],
+                ibcd_sub: harness.peer_actors().dispatcher.ibcd_sub.clone(),
+            })
+            .collect::<Vec<ChainLink>>();
+        let browsers: Browsers = Arc::new(Mutex::new(HashMap::new()));
+        outbound_rxs
+            .into_iter()
+            .enumerate()
+            .for_each(|(index, outbound_rx)| {
+                // Recipients can move between threads but not be shared, so each gets its own
+                let links = links.clone();
+                let browsers = browsers.clone();
+                thread::Builder::new()
+                    .name(format!("NodeChain {}", NODE_NAMES[index]))
+                    .spawn(move || {
+                        for msg in outbound_rx {
+                            Self::deliver(&links, index, &browsers, msg);
+                        }
+                    })
+                    .expect("Couldn't start NodeChain delivery");
+            });
+        let connector = BrowserConnector {
+            ibcd_sub: harnesses[ORIGINATING]
+                .peer_actors()
+                .dispatcher
+                .ibcd_sub
+                .clone(),
+            browsers,
+            next_browser: Arc::new(AtomicU32::new(1)),
+        };
+        NodeChain {
+            harnesses,
+            connector,
+        }
+    }
+
+    pub fn originating(&self) -> &NodeHarness {
+        &self.harnesses[ORIGINATING]
+    }
+
+    pub fn relay(&self) -> &NodeHarness {
+        &self.harnesses[1]
+    }
+
+    pub fn exit(&self) -> &NodeHarness {
+        &self.harnesses[2]
+    }
+
+    pub fn connect(&self, reception_port: u16) -> BrowserStream {
+        self.connector.connect(reception_port)
+    }
+
+    // For browsers on other threads
+    pub fn connector(&self) -> BrowserConnector {
+        self.connector.clone()
+    }
+
+    fn make_node_record(
+        cryptdes: &[&'static dyn CryptDE],
+        socket_addrs: &[SocketAddr],
+        index: usize,
+    ) -> NodeRecord {
+        let cryptde = cryptdes[index];
+        let mut node_record = NodeRecord::new(
+            cryptde.public_key(),
+            make_wallet(NODE_NAMES[index]),
+            rate_pack(100),
+            true,
+            true,
+            0,
+            cryptde,
+        );
+        node_record
+            .set_node_addr(&NodeAddr::from(&socket_addrs[index]))
+            .expect("Couldn't set NodeAddr");
+        (0..NODE_NAMES.len())
+            .filter(|other| *other + 1 == index || *other == index + 1)
+            .for_each(|other| {
+                node_record
+                    .add_half_neighbor_key(cryptdes[other].public_key().clone())
+                    .expect("Couldn't neighbor Nodes");
+            });
+        node_record.regenerate_signed_gossip(cryptde);
+        node_record
+    }
+
+    fn make_config(
+        data_directory: &PathBuf,
+        socket_addr: &SocketAddr,
+        index: usize,
+    ) -> BootstrapperConfig {
+        let mut config = BootstrapperConfig::new();
+        config.data_directory = data_directory.clone();
+        config.ui_gateway_config.ui_port = find_free_port();
+        config.neighborhood_config.mode =
+            NeighborhoodMode::Standard(NodeAddr::from(socket_addr), vec![], rate_pack(100));
+        config.earning_wallet = make_wallet(NODE_NAMES[index]);
+        if index == ORIGINATING {
+            config.consuming_wallet = Some(make_paying_wallet(NODE_NAMES[index].as_bytes()));
+        }
+        // Servers are addressed by IP, but the ProxyClient won't start without a DNS server
+        config.dns_servers = vec![SocketAddr::from_str("127.0.0.1:53").unwrap()];
+        // Every stream goes to the same few ports on one host, which looks like a scan
+        config.exit_protections = ExitProtections {
+            scan_detection: false,
+            ..ExitProtections::default()
+        };
+        config
+    }
+
+    fn deliver(links: &[ChainLink], from: usize, browsers: &Browsers, msg: TransmitDataMsg) {
+        let to_opt = links.iter().find(|link| match &msg.endpoint {
+            Endpoint::Key(key) => &link.public_key == key,
+            Endpoint::Socket(socket_addr) => link.socket_addr.ip() == socket_addr.ip(),
+        });
+        match to_opt {
+            Some(to) => {
+                // Fails only once the chain is shutting down
+                to.ibcd_sub
+                    .try_send(InboundClientData {
+                        peer_addr: links[from].socket_addr,
+                        reception_port: Some(to.socket_addr.port()),
+                        last_data: msg.last_data,
+                        is_clandestine: true,
+                        sequence_number: None,
+                        data: msg.data,
+                    })
+                    .ok();
+            }
+            None => {
+                let browser_addr = match msg.endpoint {
+                    Endpoint::Socket(socket_addr) => socket_addr,
+                    Endpoint::Key(_) => return,
+                };
+                if let Some(tx) = browsers.lock().unwrap().get(&browser_addr) {
+                    tx.send(msg).ok();
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct BrowserConnector {
+    ibcd_sub: Recipient<InboundClientData>,
+    browsers: Browsers,
+    next_browser: Arc<AtomicU32>,
+}
+
+impl BrowserConnector {
+    // A new browser connection to the originating Node's HTTP (80) or TLS (443) port
+    pub fn connect(&self, reception_port: u16) -> BrowserStream {
+        let browser_number = self.next_browser.fetch_add(1, Ordering::Relaxed);
+        let peer_addr = SocketAddr::new(
+            IpAddr::V4(Ipv4Addr::from(0x0A00_0000 | (browser_number & 0x00FF_FFFF))),
+            BROWSER_PORT,
+        );
+        let (tx, rx) = mpsc::channel();
+        self.browsers.lock().unwrap().insert(peer_addr, tx);
+        BrowserStream {
+            peer_addr,
+            reception_port,
+            next_sequence_number: 0,
+            ibcd_sub: self.ibcd_sub.clone(),
+            responses: rx,
+            sequence_buffer: SequenceBuffer::new(),
+            pending: vec![],
+            ended: false,
+            browsers: self.browsers.clone(),
+        }
+    }
+}
+
+/// One browser connection to the originating Node of a NodeChain. Dropping it closes the
+/// connection.
+pub struct BrowserStream {
+    peer_addr: SocketAddr,
+    reception_port: u16,
+    next_sequence_number: u64,
+    ibcd_sub: Recipient<InboundClientData>,
+    responses: Receiver<TransmitDataMsg>,
+    sequence_buffer: SequenceBuffer,
+    pending: Vec<u8>,
+    ended: bool,
+    browsers: Browsers,
+}
+
+impl Drop for BrowserStream {
+    fn drop(&mut self) {
+        self.send_client_data(vec![], true);
+        self.browsers.lock().unwrap().remove(&self.peer_addr);
+    }
+}
+
+impl BrowserStream {
+    pub fn send(&mut self, data: &[u8]) {
+        self.send_client_data(data.to_vec(), false);
+    }
+
+    // Exactly `count` bytes of what the Node has written to the browser, in order; anything
+    // past them is kept for the next call
+    pub fn receive(&mut self, count: usize, timeout: Duration) -> Result<Vec<u8>, String> {
+        let deadline = Instant::now() + timeout;
+        while self.pending.len() < count {
+            if let Some(packet) = self.sequence_buffer.poll()? {
+                self.pending.extend(packet.data);
+                self.ended = self.ended || packet.last_data;
+                continue;
+            }
+            if self.ended {
+                return Err(format!(
+                    "Node closed the stream after {} of {} bytes",
+                    self.pending.len(),
+                    count
+                ));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(format!(
+                    "Received only {} of {} bytes in {}ms",
+                    self.pending.len(),
+                    count,
+                    timeout.as_millis()
+                ));
+            }
+            if let Ok(msg) = self.responses.recv_timeout(deadline - now) {
+                self.sequence_buffer.push(SequencedPacket::new(
+                    msg.data,
+                    msg.sequence_number.unwrap_or(0),
+                    msg.last_data,
+                ))?;
+            }
+        }
+        Ok(self.pending.drain(..count).collect())
+    }
+
+    fn send_client_data(&mut self, data: Vec<u8>, last_data: bool) {
+        let sequence_number = self.next_sequence_number;
+        self.next_sequence_number += 1;
+        // Fails only once the chain is shutting down
+        self.ibcd_sub
+            .try_send(InboundClientData {
+                peer_addr: self.peer_addr,
+                reception_port: Some(self.reception_port),
+                last_data,
+                is_clandestine: false,
+                sequence_number: Some(sequence_number),
+                data,
+            })
+            .ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sub_lib::utils::localhost;
+    use crate::test_utils::ensure_node_home_directory_exists;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    #[test]
+    fn http_request_goes_out_through_the_exit_node_and_back() {
+        let data_directory = ensure_node_home_directory_exists(
+            "node_chain",
+            "http_request_goes_out_through_the_exit_node_and_back",
+        );
+        let listener = TcpListener::bind(SocketAddr::new(localhost(), 0)).unwrap();
+        let server_addr = listener.local_addr().unwrap();
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello".to_vec();
+        let server_response = response.clone();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![];
+            let mut buf = [0u8; 1024];
+            while !String::from_utf8_lossy(&request).contains("\r\n\r\n") {
+                let len = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..len]);
+            }
+            stream.write_all(&server_response).unwrap();
+        });
+        let subject = NodeChain::start(&data_directory);
+        let mut browser = subject.connect(80);
+
+        browser.send(
+            format!(
+                "GET / HTTP/1.1\r\nHost: {}:{}\r\n\r\n",
+                server_addr.ip(),
+                server_addr.port()
+            )
+            .as_bytes(),
+        );
+        let result = browser.receive(response.len(), Duration::from_secs(10));
+
+        assert_eq!(result, Ok(response));
+    }
+}
@@ -0,0 +1,453 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use crate::accountant::Accountant;
+use crate::actor_system_factory::{ActorFactory, ActorFactoryReal, ActorSystemFactoryReal};
+use crate::banned_dao::BannedCacheLoader;
+use crate::bootstrapper::BootstrapperConfig;
+use crate::database::db_initializer::DbInitializer;
+use crate::discriminator::DiscriminatorFactory;
+use crate::dispatcher::Dispatcher;
+use crate::neighborhood::node_record::NodeRecord;
+use crate::neighborhood::Neighborhood;
+use crate::proxy_client::ProxyClient;
+use crate::proxy_server::ProxyServer;
+use crate::stream_handler_pool::StreamHandlerPoolSubs;
+use crate::stream_messages::{AddStreamMsg, PoolBindMessage, RemoveStreamMsg};
+use crate::sub_lib::accountant::{AccountantSubs, ConsumingProfile};
+use crate::sub_lib::blockchain_bridge::BlockchainBridgeSubs;
+use crate::sub_lib::cryptde::CryptDE;
+use crate::sub_lib::dispatcher::{DispatcherSubs, InboundClientData};
+use crate::sub_lib::hopper::{HopperConfig, HopperSubs};
+use crate::sub_lib::neighbor_rate_limiter::NeighborRateLimits;
+use crate::sub_lib::neighborhood::{NeighborhoodSubs, DEFAULT_MINIMUM_HOP_COUNT};
+use crate::sub_lib::obfuscation::ObfuscationConfig;
+use crate::sub_lib::peer_actors::PeerActors;
+use crate::sub_lib::proxy_client::{ProxyClientConfig, ProxyClientSubs};
+use crate::sub_lib::proxy_server::{PortForward, ProxyServerSubs};
+use crate::sub_lib::spill_file::SpillConfig;
+use crate::sub_lib::stream_handler_pool::{DispatcherNodeQueryResponse, TransmitDataMsg};
+use crate::sub_lib::ui_gateway::{UiGatewayConfig, UiGatewaySubs};
+use crate::test_utils::clock_mock::ClockMock;
+use crate::test_utils::cryptde;
+use crate::test_utils::find_free_port;
+use crate::test_utils::recorder::{Recorder, Recording};
+use actix::{Actor, Addr, Arbiter, Context, Handler, Recipient, System};
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::SystemTime;
+
+/// Runs every actor of a Node in this process, in its own actix System, without binding any
+/// listeners or opening any streams. Traffic goes in through the Dispatcher as InboundClientData,
+/// and whatever the Node would have written to a stream is recorded as TransmitDataMsgs in place
+/// of the StreamHandlerPool. Every actor is reachable through `peer_actors()`, and the Accountant,
+/// Neighborhood, ProxyClient and Dispatcher tell time by a ClockMock that the test moves.
+///
+/// The actors that need a database use the one in the configured data directory, so give each
+/// test its own: `ensure_node_home_directory_exists` is a good source.
+///
+/// A harness built with `outbound_sender()` sends its outbound TransmitDataMsgs there instead of
+/// recording them, so that something else can carry them to other harnesses.
+pub struct NodeHarness {
+    system: System,
+    peer_actors: PeerActors,
+    outbound: Arc<Mutex<Recording>>,
+    clock: ClockMock,
+    thread_handle_opt: Option<JoinHandle<()>>,
+}
+
+impl Drop for NodeHarness {
+    fn drop(&mut self) {
+        self.system.stop();
+        if let Some(handle) = self.thread_handle_opt.take() {
+            handle.join().ok();
+        }
+    }
+}
+
+impl NodeHarness {
+    pub fn peer_actors(&self) -> &PeerActors {
+        &self.peer_actors
+    }
+
+    pub fn outbound(&self) -> &Arc<Mutex<Recording>> {
+        &self.outbound
+    }
+
+    pub fn clock(&self) -> &ClockMock {
+        &self.clock
+    }
+
+    pub fn send_client_data(&self, msg: InboundClientData) {
+        self.peer_actors
+            .dispatcher
+            .ibcd_sub
+            .try_send(msg)
+            .expect("Dispatcher is dead");
+    }
+}
+
+pub fn node_harness_builder(data_directory: &PathBuf) -> NodeHarnessBuilder {
+    NodeHarnessBuilder::new(data_directory)
+}
+
+pub struct NodeHarnessBuilder {
+    config: BootstrapperConfig,
+    cryptde: &'static dyn CryptDE,
+    clock: ClockMock,
+    known_nodes: Vec<NodeRecord>,
+    minimum_hop_count: usize,
+    outbound_tx_opt: Option<Sender<TransmitDataMsg>>,
+}
+
+impl NodeHarnessBuilder {
+    pub fn new(data_directory: &PathBuf) -> NodeHarnessBuilder {
+        let mut config = BootstrapperConfig::new();
+        config.data_directory = data_directory.clone();
+        config.ui_gateway_config.ui_port = find_free_port();
+        NodeHarnessBuilder {
+            config,
+            cryptde: cryptde(),
+            clock: ClockMock::new(SystemTime::now()),
+            known_nodes: vec![],
+            minimum_hop_count: DEFAULT_MINIMUM_HOP_COUNT,
+            outbound_tx_opt: None,
+        }
+    }
+
+    // Replaces the whole configuration, data directory and UI port included
+    pub fn config(mut self, config: BootstrapperConfig) -> NodeHarnessBuilder {
+        self.config = config;
+        self
+    }
+
+    pub fn cryptde(mut self, cryptde: &'static dyn CryptDE) -> NodeHarnessBuilder {
+        self.cryptde = cryptde;
+        self
+    }
+
+    pub fn clock(mut self, clock: ClockMock) -> NodeHarnessBuilder {
+        self.clock = clock;
+        self
+    }
+
+    pub fn ui_port(mut self, port: u16) -> NodeHarnessBuilder {
+        self.config.ui_gateway_config.ui_port = port;
+        self
+    }
+
+    pub fn clandestine_port(mut self, port: u16) -> NodeHarnessBuilder {
+        self.config.clandestine_port_opt = Some(port);
+        self
+    }
+
+    // Other Nodes the Neighborhood starts out knowing, neighbored as their records say
+    pub fn known_nodes(mut self, known_nodes: Vec<NodeRecord>) -> NodeHarnessBuilder {
+        self.known_nodes = known_nodes;
+        self
+    }
+
+    pub fn minimum_hop_count(mut self, minimum_hop_count: usize) -> NodeHarnessBuilder {
+        self.minimum_hop_count = minimum_hop_count;
+        self
+    }
+
+    pub fn outbound_sender(mut self, outbound_tx: Sender<TransmitDataMsg>) -> NodeHarnessBuilder {
+        self.outbound_tx_opt = Some(outbound_tx);
+        self
+    }
+
+    pub fn start(self) -> NodeHarness {
+        let stream_handler_pool = Recorder::new();
+        let outbound = stream_handler_pool.get_recording();
+        let actor_factory = NodeHarnessActorFactory {
+            clock: self.clock.clone(),
+            stream_handler_pool: RefCell::new(Some(stream_handler_pool)),
+            known_nodes: RefCell::new(self.known_nodes),
+            minimum_hop_count: self.minimum_hop_count,
+            outbound_tx_opt: self.outbound_tx_opt,
+        };
+        let cryptde = self.cryptde;
+        let config = self.config;
+        let (tx, rx) = mpsc::channel();
+        let thread_handle = thread::spawn(move || {
+            let system = System::new("node_harness");
+            let (peer_actors, _) = ActorSystemFactoryReal::make_and_bind_actors(
+                cryptde,
+                config,
+                Box::new(actor_factory),
+            );
+            tx.send((System::current(), peer_actors)).ok();
+            system.run();
+        });
+        let (system, peer_actors) = rx
+            .recv()
+            .expect("Node harness died before its actors started");
+        NodeHarness {
+            system,
+            peer_actors,
+            outbound,
+            clock: self.clock,
+            thread_handle_opt: Some(thread_handle),
+        }
+    }
+}
+
+struct NodeHarnessActorFactory {
+    clock: ClockMock,
+    stream_handler_pool: RefCell<Option<Recorder>>,
+    known_nodes: RefCell<Vec<NodeRecord>>,
+    minimum_hop_count: usize,
+    outbound_tx_opt: Option<Sender<TransmitDataMsg>>,
+}
+
+// Stands in for the StreamHandlerPool of a harness whose outbound traffic goes somewhere else
+struct OutboundForwarder {
+    outbound_tx: Sender<TransmitDataMsg>,
+}
+
+impl Actor for OutboundForwarder {
+    type Context = Context<Self>;
+}
+
+impl Handler<TransmitDataMsg> for OutboundForwarder {
+    type Result = ();
+
+    fn handle(&mut self, msg: TransmitDataMsg, _ctx: &mut Self::Context) {
+        self.outbound_tx.send(msg).ok();
+    }
+}
+
+macro_rules! outbound_forwarder_ignores {
+    ($message_type: ty) => {
+        impl Handler<$message_type> for OutboundForwarder {
+            type Result = ();
+
+            fn handle(&mut self, _msg: $message_type, _ctx: &mut Self::Context) {}
+        }
+    };
+}
+
+outbound_forwarder_ignores!(AddStreamMsg);
+outbound_forwarder_ignores!(RemoveStreamMsg);
+outbound_forwarder_ignores!(PoolBindMessage);
+outbound_forwarder_ignores!(DispatcherNodeQueryResponse);
+
+impl ActorFactory for NodeHarnessActorFactory {
+    fn make_and_start_dispatcher(
+        &self,
+        rate_limits: NeighborRateLimits,
+    ) -> (DispatcherSubs, Recipient<PoolBindMessage>) {
+        let dispatcher = Dispatcher::new(rate_limits).with_clock(Box::new(self.clock.clone()));
+        let addr: Addr<Dispatcher> = dispatcher.start();
+        (
+            Dispatcher::make_subs_from(&addr),
+            addr.recipient::<PoolBindMessage>(),
+        )
+    }
+
+    fn make_and_start_proxy_server(
+        &self,
+        cryptde: &'static dyn CryptDE,
+        is_decentralized: bool,
+        consuming_wallet_balance: Option<i64>,
+        kill_switch: bool,
+        port_forwards: Vec<PortForward>,
+        consuming_profiles: Vec<ConsumingProfile>,
+    ) -> ProxyServerSubs {
+        let minimum_hop_count = self.minimum_hop_count;
+        let addr: Addr<ProxyServer> = Arbiter::start(move |_| {
+            ProxyServer::new(
+                cryptde,
+                is_decentralized,
+                consuming_wallet_balance,
+                kill_switch,
+            )
+            .with_minimum_hop_count(minimum_hop_count)
+            .with_port_forwards(port_forwards)
+            .with_consuming_profiles(consuming_profiles)
+        });
+        ProxyServer::make_subs_from(&addr)
+    }
+
+    fn make_and_start_hopper(&self, config: HopperConfig) -> HopperSubs {
+        ActorFactoryReal {}.make_and_start_hopper(config)
+    }
+
+    fn make_and_start_neighborhood(
+        &self,
+        cryptde: &'static dyn CryptDE,
+        config: &BootstrapperConfig,
+    ) -> NeighborhoodSubs {
+        let neighborhood = Neighborhood::new(cryptde, config)
+            .with_clock(Box::new(self.clock.clone()))
+            .with_known_nodes(self.known_nodes.replace(vec![]));
+        let addr: Addr<Neighborhood> = neighborhood.start();
+        Neighborhood::make_subs_from(&addr)
+    }
+
+    fn make_and_start_accountant(
+        &self,
+        config: &BootstrapperConfig,
+        data_directory: &PathBuf,
+        db_initializer: &dyn DbInitializer,
+        banned_cache_loader: &dyn BannedCacheLoader,
+    ) -> AccountantSubs {
+        let accountant = ActorFactoryReal::make_accountant(
+            config,
+            data_directory,
+            db_initializer,
+            banned_cache_loader,
+        )
+        .with_clock(Box::new(self.clock.clone()));
+        let addr: Addr<Accountant> = accountant.start();
+        Accountant::make_subs_from(&addr)
+    }
+
+    fn make_and_start_ui_gateway(&self, config: UiGatewayConfig) -> UiGatewaySubs {
+        ActorFactoryReal {}.make_and_start_ui_gateway(config)
+    }
+
+    fn make_and_start_stream_handler_pool(
+        &self,
+        _clandestine_discriminator_factories: Vec<Box<dyn DiscriminatorFactory>>,
+        _obfuscation_config: ObfuscationConfig,
+        _spill_config: SpillConfig,
+    ) -> StreamHandlerPoolSubs {
+        let recorder = self
+            .stream_handler_pool
+            .borrow_mut()
+            .take()
+            .expect("StreamHandlerPool already started");
+        if let Some(outbound_tx) = self.outbound_tx_opt.clone() {
+            let addr: Addr<OutboundForwarder> = OutboundForwarder { outbound_tx }.start();
+            return StreamHandlerPoolSubs {
+                add_sub: recipient!(addr, AddStreamMsg),
+                transmit_sub: recipient!(addr, TransmitDataMsg),
+                remove_sub: recipient!(addr, RemoveStreamMsg),
+                bind: recipient!(addr, PoolBindMessage),
+                node_query_response: recipient!(addr, DispatcherNodeQueryResponse),
+            };
+        }
+        let addr: Addr<Recorder> = recorder.start();
+        StreamHandlerPoolSubs {
+            add_sub: recipient!(addr, AddStreamMsg),
+            transmit_sub: recipient!(addr, TransmitDataMsg),
+            remove_sub: recipient!(addr, RemoveStreamMsg),
+            bind: recipient!(addr, PoolBindMessage),
+            node_query_response: recipient!(addr, DispatcherNodeQueryResponse),
+        }
+    }
+
+    fn make_and_start_proxy_client(&self, config: ProxyClientConfig) -> ProxyClientSubs {
+        let proxy_client = ProxyClient::new(config).with_clock(Box::new(self.clock.clone()));
+        let addr: Addr<ProxyClient> = proxy_client.start();
+        ProxyClient::make_subs_from(&addr)
+    }
+
+    fn make_and_start_blockchain_bridge(
+        &self,
+        config: &BootstrapperConfig,
+        db_initializer: &dyn DbInitializer,
+    ) -> BlockchainBridgeSubs {
+        ActorFactoryReal {}.make_and_start_blockchain_bridge(config, db_initializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sub_lib::accountant::ReportRoutingServiceProvidedMessage;
+    use crate::sub_lib::dispatcher::Endpoint;
+    use crate::sub_lib::neighborhood::NeighborhoodMode;
+    use crate::sub_lib::node_addr::NodeAddr;
+    use crate::sub_lib::ui_gateway::GetNodeStatisticsMessage;
+    use crate::test_utils::recorder::RecordingAssertions;
+    use crate::test_utils::{ensure_node_home_directory_exists, make_wallet, rate_pack};
+    use futures::future::Future;
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    #[test]
+    fn browser_traffic_runs_through_the_node_and_out_to_the_stream_handler_pool() {
+        let data_directory = ensure_node_home_directory_exists(
+            "node_harness",
+            "browser_traffic_runs_through_the_node_and_out_to_the_stream_handler_pool",
+        );
+        let mut config = BootstrapperConfig::new();
+        config.data_directory = data_directory.clone();
+        config.ui_gateway_config.ui_port = find_free_port();
+        config.neighborhood_config.mode = NeighborhoodMode::Standard(
+            NodeAddr::new(&IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)), &vec![]),
+            vec![],
+            rate_pack(100),
+        );
+        let browser_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let harness = node_harness_builder(&data_directory).config(config).start();
+
+        harness.send_client_data(InboundClientData {
+            peer_addr: browser_addr,
+            reception_port: Some(80),
+            last_data: false,
+            is_clandestine: false,
+            sequence_number: Some(0),
+            data: b"GET / HTTP/1.1\r\nHost: nowhere.com\r\n\r\n".to_vec(),
+        });
+
+        let transmitted = harness
+            .outbound()
+            .await_message::<TransmitDataMsg>(Duration::from_secs(5));
+        assert_eq!(transmitted.endpoint, Endpoint::Socket(browser_addr));
+        assert_eq!(transmitted.last_data, true);
+        let response = String::from_utf8(transmitted.data).unwrap();
+        assert!(
+            response.starts_with("HTTP/1.1 402 "),
+            "Expected a 402, not {}",
+            response
+        );
+    }
+
+    #[test]
+    fn accountant_tells_time_by_the_harness_clock() {
+        let data_directory = ensure_node_home_directory_exists(
+            "node_harness",
+            "accountant_tells_time_by_the_harness_clock",
+        );
+        let harness = node_harness_builder(&data_directory)
+            .clock(ClockMock::new(
+                SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000 * 60),
+            ))
+            .start();
+        let accountant = &harness.peer_actors().accountant;
+        accountant
+            .report_routing_service_provided
+            .try_send(ReportRoutingServiceProvidedMessage {
+                paying_wallet: make_wallet("booga"),
+                payload_size: 1234,
+                service_rate: 42,
+                byte_rate: 24,
+            })
+            .unwrap();
+        let before = accountant
+            .node_statistics_sub
+            .send(GetNodeStatisticsMessage {})
+            .wait()
+            .unwrap();
+
+        harness.clock().advance(Duration::from_secs(60 * 60));
+        let after = accountant
+            .node_statistics_sub
+            .send(GetNodeStatisticsMessage {})
+            .wait()
+            .unwrap();
+
+        assert_eq!(before.bytes_relayed_last_hour, 1234);
+        assert_eq!(after.bytes_relayed_last_hour, 0);
+        assert_eq!(after.bytes_relayed, 1234);
+    }
+}
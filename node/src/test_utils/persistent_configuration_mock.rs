@@ -1,9 +1,13 @@
 use crate::blockchain::bip39::Bip39Error;
 use crate::persistent_configuration::PersistentConfiguration;
+use crate::sub_lib::accountant::PaymentParameter;
+use crate::sub_lib::blockchain_bridge::FeeSpending;
 use crate::sub_lib::cryptde::PlainData;
 use crate::sub_lib::wallet::Wallet;
 use rusqlite::Transaction;
 use std::cell::RefCell;
+use std::collections::HashMap;
+use std::net::IpAddr;
 use std::sync::{Arc, Mutex};
 
 type MnemonicSeedParam = (Vec<u8>, String);
@@ -25,10 +29,24 @@ pub struct PersistentConfigurationMock {
     earning_wallet_from_address_results: RefCell<Vec<Option<Wallet>>>,
     earning_wallet_address_results: RefCell<Vec<Option<String>>>,
     set_earning_wallet_address_params: Arc<Mutex<Vec<String>>>,
+    exit_earning_wallet_from_address_results: RefCell<Vec<Option<Wallet>>>,
+    set_exit_earning_wallet_address_params: Arc<Mutex<Vec<String>>>,
     start_block_results: RefCell<Vec<u64>>,
+    set_start_block_params: Arc<Mutex<Vec<u64>>>,
     set_start_block_transactionally_results: RefCell<Vec<Result<(), String>>>,
     set_gas_price_params: Arc<Mutex<Vec<u64>>>,
     gas_price_results: RefCell<Vec<u64>>,
+    next_nonce_results: RefCell<Vec<Option<u64>>>,
+    set_next_nonce_params: Arc<Mutex<Vec<u64>>>,
+    fee_spending_results: RefCell<Vec<Option<FeeSpending>>>,
+    set_fee_spending_params: Arc<Mutex<Vec<FeeSpending>>>,
+    detected_dns_servers_results: RefCell<Vec<Option<Vec<IpAddr>>>>,
+    set_detected_dns_servers_params: Arc<Mutex<Vec<Vec<IpAddr>>>>,
+    split_dns_domains_results: RefCell<Vec<Vec<String>>>,
+    set_split_dns_domains_params: Arc<Mutex<Vec<Vec<String>>>>,
+    check_writable_results: RefCell<Vec<Result<(), String>>>,
+    payment_parameter_results: RefCell<HashMap<PaymentParameter, u64>>,
+    set_payment_parameter_params: Arc<Mutex<Vec<(PaymentParameter, u64)>>>,
 }
 
 impl PersistentConfiguration for PersistentConfigurationMock {
@@ -52,6 +70,20 @@ impl PersistentConfiguration for PersistentConfigurationMock {
         self.set_gas_price_params.lock().unwrap().push(gas_price);
     }
 
+    fn payment_parameter(&self, parameter: PaymentParameter) -> u64 {
+        match self.payment_parameter_results.borrow().get(&parameter) {
+            Some(value) => *value,
+            None => parameter.default_value(),
+        }
+    }
+
+    fn set_payment_parameter(&self, parameter: PaymentParameter, value: u64) {
+        self.set_payment_parameter_params
+            .lock()
+            .unwrap()
+            .push((parameter, value));
+    }
+
     fn encrypted_mnemonic_seed(&self) -> Option<String> {
         Self::result_from(&self.encrypted_mnemonic_seed_results)
     }
@@ -108,6 +140,24 @@ impl PersistentConfiguration for PersistentConfigurationMock {
             .push(address.to_string());
     }
 
+    fn exit_earning_wallet_from_address(&self) -> Option<Wallet> {
+        if self
+            .exit_earning_wallet_from_address_results
+            .borrow()
+            .is_empty()
+        {
+            return None;
+        }
+        Self::result_from(&self.exit_earning_wallet_from_address_results)
+    }
+
+    fn set_exit_earning_wallet_address(&self, address: &str) {
+        self.set_exit_earning_wallet_address_params
+            .lock()
+            .unwrap()
+            .push(address.to_string());
+    }
+
     fn start_block(&self) -> u64 {
         if self.start_block_results.borrow().is_empty() {
             return 0;
@@ -115,6 +165,10 @@ impl PersistentConfiguration for PersistentConfigurationMock {
         Self::result_from(&self.start_block_results)
     }
 
+    fn set_start_block(&self, value: u64) {
+        self.set_start_block_params.lock().unwrap().push(value);
+    }
+
     fn set_start_block_transactionally(
         &self,
         _tx: &Transaction,
@@ -122,6 +176,63 @@ impl PersistentConfiguration for PersistentConfigurationMock {
     ) -> Result<(), String> {
         Self::result_from(&self.set_start_block_transactionally_results)
     }
+
+    fn next_nonce(&self) -> Option<u64> {
+        if self.next_nonce_results.borrow().is_empty() {
+            return None;
+        }
+        Self::result_from(&self.next_nonce_results)
+    }
+
+    fn set_next_nonce(&self, nonce: u64) {
+        self.set_next_nonce_params.lock().unwrap().push(nonce);
+    }
+
+    fn fee_spending(&self) -> Option<FeeSpending> {
+        if self.fee_spending_results.borrow().is_empty() {
+            return None;
+        }
+        Self::result_from(&self.fee_spending_results)
+    }
+
+    fn set_fee_spending(&self, spending: FeeSpending) {
+        self.set_fee_spending_params.lock().unwrap().push(spending);
+    }
+
+    fn detected_dns_servers(&self) -> Option<Vec<IpAddr>> {
+        if self.detected_dns_servers_results.borrow().is_empty() {
+            return None;
+        }
+        Self::result_from(&self.detected_dns_servers_results)
+    }
+
+    fn set_detected_dns_servers(&self, dns_servers: &[IpAddr]) {
+        self.set_detected_dns_servers_params
+            .lock()
+            .unwrap()
+            .push(dns_servers.to_vec());
+    }
+
+    fn split_dns_domains(&self) -> Vec<String> {
+        if self.split_dns_domains_results.borrow().is_empty() {
+            return vec![];
+        }
+        Self::result_from(&self.split_dns_domains_results)
+    }
+
+    fn set_split_dns_domains(&self, domains: &[String]) {
+        self.set_split_dns_domains_params
+            .lock()
+            .unwrap()
+            .push(domains.to_vec());
+    }
+
+    fn check_writable(&self) -> Result<(), String> {
+        if self.check_writable_results.borrow().is_empty() {
+            return Ok(());
+        }
+        Self::result_from(&self.check_writable_results)
+    }
 }
 
 impl PersistentConfigurationMock {
@@ -224,6 +335,21 @@ impl PersistentConfigurationMock {
         self
     }
 
+    pub fn payment_parameter_result(self, parameter: PaymentParameter, result: u64) -> Self {
+        self.payment_parameter_results
+            .borrow_mut()
+            .insert(parameter, result);
+        self
+    }
+
+    pub fn set_payment_parameter_params(
+        mut self,
+        params: &Arc<Mutex<Vec<(PaymentParameter, u64)>>>,
+    ) -> PersistentConfigurationMock {
+        self.set_payment_parameter_params = params.clone();
+        self
+    }
+
     pub fn set_consuming_wallet_derivation_path_params(
         mut self,
         params: &Arc<Mutex<Vec<(String, String)>>>,
@@ -268,11 +394,37 @@ impl PersistentConfigurationMock {
         self
     }
 
+    pub fn exit_earning_wallet_from_address_result(
+        self,
+        result: Option<Wallet>,
+    ) -> PersistentConfigurationMock {
+        self.exit_earning_wallet_from_address_results
+            .borrow_mut()
+            .push(result);
+        self
+    }
+
+    pub fn set_exit_earning_wallet_address_params(
+        mut self,
+        params: &Arc<Mutex<Vec<String>>>,
+    ) -> PersistentConfigurationMock {
+        self.set_exit_earning_wallet_address_params = params.clone();
+        self
+    }
+
     pub fn start_block_result(self, start_block: u64) -> Self {
         self.start_block_results.borrow_mut().push(start_block);
         self
     }
 
+    pub fn set_start_block_params(
+        mut self,
+        params: &Arc<Mutex<Vec<u64>>>,
+    ) -> PersistentConfigurationMock {
+        self.set_start_block_params = params.clone();
+        self
+    }
+
     pub fn set_start_block_transactionally_result(self, result: Result<(), String>) -> Self {
         self.set_start_block_transactionally_results
             .borrow_mut()
@@ -280,6 +432,63 @@ impl PersistentConfigurationMock {
         self
     }
 
+    pub fn next_nonce_result(self, result: Option<u64>) -> Self {
+        self.next_nonce_results.borrow_mut().push(result);
+        self
+    }
+
+    pub fn set_next_nonce_params(
+        mut self,
+        params: &Arc<Mutex<Vec<u64>>>,
+    ) -> PersistentConfigurationMock {
+        self.set_next_nonce_params = params.clone();
+        self
+    }
+
+    pub fn fee_spending_result(self, result: Option<FeeSpending>) -> Self {
+        self.fee_spending_results.borrow_mut().push(result);
+        self
+    }
+
+    pub fn set_fee_spending_params(
+        mut self,
+        params: &Arc<Mutex<Vec<FeeSpending>>>,
+    ) -> PersistentConfigurationMock {
+        self.set_fee_spending_params = params.clone();
+        self
+    }
+
+    pub fn detected_dns_servers_result(self, result: Option<Vec<IpAddr>>) -> Self {
+        self.detected_dns_servers_results.borrow_mut().push(result);
+        self
+    }
+
+    pub fn set_detected_dns_servers_params(
+        mut self,
+        params: &Arc<Mutex<Vec<Vec<IpAddr>>>>,
+    ) -> PersistentConfigurationMock {
+        self.set_detected_dns_servers_params = params.clone();
+        self
+    }
+
+    pub fn split_dns_domains_result(self, result: Vec<String>) -> Self {
+        self.split_dns_domains_results.borrow_mut().push(result);
+        self
+    }
+
+    pub fn set_split_dns_domains_params(
+        mut self,
+        params: &Arc<Mutex<Vec<Vec<String>>>>,
+    ) -> PersistentConfigurationMock {
+        self.set_split_dns_domains_params = params.clone();
+        self
+    }
+
+    pub fn check_writable_result(self, result: Result<(), String>) -> Self {
+        self.check_writable_results.borrow_mut().push(result);
+        self
+    }
+
     fn result_from<T: Clone>(results: &RefCell<Vec<T>>) -> T {
         let mut borrowed = results.borrow_mut();
         if borrowed.is_empty() {
@@ -0,0 +1,78 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use crate::sub_lib::random::Random;
+use rand::rngs::SmallRng;
+use rand::{Rng, RngCore, SeedableRng};
+use std::sync::{Arc, Mutex};
+
+/// A Random that makes the same choices every time it's made with the same seed. Clones draw
+/// from the same sequence, so a test can keep one and give another to the code under test.
+#[derive(Clone)]
+pub struct RandomSeeded {
+    rng: Arc<Mutex<SmallRng>>,
+}
+
+impl Random for RandomSeeded {
+    fn fill(&self, dest: &mut [u8]) {
+        self.rng.lock().unwrap().fill_bytes(dest)
+    }
+
+    fn range(&self, low: u64, high: u64) -> u64 {
+        self.rng.lock().unwrap().gen_range(low, high)
+    }
+}
+
+impl RandomSeeded {
+    pub fn new(seed: u64) -> RandomSeeded {
+        RandomSeeded {
+            rng: Arc::new(Mutex::new(SmallRng::seed_from_u64(seed))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw(subject: &RandomSeeded) -> (Vec<u8>, Vec<u64>) {
+        let mut bytes = vec![0u8; 16];
+        subject.fill(&mut bytes);
+        let numbers = (0..16).map(|_| subject.range(0, 1000)).collect();
+        (bytes, numbers)
+    }
+
+    #[test]
+    fn the_same_seed_makes_the_same_choices() {
+        let first = RandomSeeded::new(1234);
+        let second = RandomSeeded::new(1234);
+
+        assert_eq!(draw(&first), draw(&second));
+    }
+
+    #[test]
+    fn different_seeds_make_different_choices() {
+        let first = RandomSeeded::new(1234);
+        let second = RandomSeeded::new(4321);
+
+        assert_ne!(draw(&first), draw(&second));
+    }
+
+    #[test]
+    fn clones_draw_from_the_same_sequence() {
+        let subject = RandomSeeded::new(1234);
+        let clone = subject.clone();
+        let reference = RandomSeeded::new(1234);
+        let expected = (0..4)
+            .map(|_| reference.range(0, 1000))
+            .collect::<Vec<u64>>();
+
+        let result = vec![
+            subject.range(0, 1000),
+            clone.range(0, 1000),
+            subject.range(0, 1000),
+            clone.range(0, 1000),
+        ];
+
+        assert_eq!(result, expected);
+    }
+}
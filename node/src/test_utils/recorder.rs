@@ -1,23 +1,29 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 use crate::accountant::payable_dao::Payment;
-use crate::accountant::{ReceivedPayments, SentPayments};
-use crate::blockchain::blockchain_bridge::RetrieveTransactions;
-use crate::blockchain::blockchain_interface::{BlockchainError, BlockchainResult, Transaction};
+use crate::accountant::{ReceivedPayments, ResolvedPayment, SentPayments};
+use crate::blockchain::blockchain_bridge::{RetrieveTransactions, RetrievedTransactions};
+use crate::blockchain::blockchain_interface::{BlockchainError, BlockchainResult};
 use crate::neighborhood::gossip::Gossip;
 use crate::stream_messages::{AddStreamMsg, PoolBindMessage, RemoveStreamMsg};
 use crate::sub_lib::accountant::ReportExitServiceConsumedMessage;
 use crate::sub_lib::accountant::ReportExitServiceProvidedMessage;
 use crate::sub_lib::accountant::ReportRoutingServiceConsumedMessage;
 use crate::sub_lib::accountant::ReportRoutingServiceProvidedMessage;
-use crate::sub_lib::accountant::{AccountantSubs, GetFinancialStatisticsMessage};
+use crate::sub_lib::accountant::{
+    AccountantSubs, DumpFinancialsMessage, GetFinancialStatisticsMessage, NewBlockMessage,
+    ReportBillingClaimMessage, SetPaymentParameterMessage,
+};
 use crate::sub_lib::blockchain_bridge::{BlockchainBridgeSubs, SetWalletPasswordMsg};
-use crate::sub_lib::blockchain_bridge::{ReportAccountsPayable, SetGasPriceMsg};
+use crate::sub_lib::blockchain_bridge::{
+    GetBlockchainServiceUrlMsg, GetWalletBalancesMsg, ReportAccountsPayable, SetGasPriceMsg,
+};
 use crate::sub_lib::dispatcher::InboundClientData;
-use crate::sub_lib::dispatcher::{DispatcherSubs, StreamShutdownMsg};
+use crate::sub_lib::dispatcher::{DispatcherSubs, NeighborKeyMessage, StreamShutdownMsg};
 use crate::sub_lib::hopper::IncipientCoresPackage;
 use crate::sub_lib::hopper::{ExpiredCoresPackage, NoLookupIncipientCoresPackage};
 use crate::sub_lib::hopper::{HopperSubs, MessageType};
 use crate::sub_lib::neighborhood::DispatcherNodeQueryMessage;
+use crate::sub_lib::neighborhood::NeighborListRequest;
 use crate::sub_lib::neighborhood::NeighborhoodDotGraphRequest;
 use crate::sub_lib::neighborhood::NeighborhoodSubs;
 use crate::sub_lib::neighborhood::NodeQueryMessage;
@@ -26,24 +32,40 @@ use crate::sub_lib::neighborhood::NodeRecordMetadataMessage;
 use crate::sub_lib::neighborhood::RemoveNeighborMessage;
 use crate::sub_lib::neighborhood::RouteQueryMessage;
 use crate::sub_lib::neighborhood::RouteQueryResponse;
+use crate::sub_lib::neighborhood::{
+    BillingClaimMessage, DebtorKeyMessage, DelinquencyMessage, DisputeMessage,
+};
 use crate::sub_lib::peer_actors::PeerActors;
 use crate::sub_lib::peer_actors::{BindMessage, StartMessage};
-use crate::sub_lib::proxy_client::{ClientResponsePayload, InboundServerData};
+use crate::sub_lib::proxy_client::{
+    BillingClaimPayload, BillingClaimsMessage, ClientResponsePayload, InboundServerData,
+};
 use crate::sub_lib::proxy_client::{DnsResolveFailure, ProxyClientSubs};
+use crate::sub_lib::proxy_server::ConsumingProfileExhaustedMessage;
+use crate::sub_lib::proxy_server::PassiveTunnelExpiredMessage;
 use crate::sub_lib::proxy_server::ProxyServerSubs;
+use crate::sub_lib::proxy_server::RouteFailureMessage;
 use crate::sub_lib::proxy_server::{AddReturnRouteMessage, AddRouteMessage, ClientRequestPayload};
 use crate::sub_lib::set_consuming_wallet_message::SetConsumingWalletMessage;
 use crate::sub_lib::stream_handler_pool::DispatcherNodeQueryResponse;
 use crate::sub_lib::stream_handler_pool::TransmitDataMsg;
 use crate::sub_lib::ui_gateway::UiGatewaySubs;
-use crate::sub_lib::ui_gateway::{FromUiMessage, UiCarrierMessage};
+use crate::sub_lib::ui_gateway::{
+    ChangeConfigurationMessage, ComponentHealth, ConfigurationEntry, GetConfigurationMessage,
+};
+use crate::sub_lib::ui_gateway::{
+    FromUiMessage, GetHealthMessage, GetNodeStatisticsMessage, NodeEvent, NodeStatistics,
+    UiCarrierMessage,
+};
 use crate::test_utils::to_millis;
 use actix::Actor;
 use actix::Addr;
 use actix::Context;
 use actix::Handler;
 use actix::MessageResult;
+use std::any::type_name;
 use std::any::Any;
+use std::fmt::Debug;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::thread;
@@ -55,13 +77,18 @@ pub struct Recorder {
     recording: Arc<Mutex<Recording>>,
     node_query_responses: Vec<Option<NodeQueryResponseMetadata>>,
     route_query_responses: Vec<Option<RouteQueryResponse>>,
-    retrieve_transactions_responses: Vec<Result<Vec<Transaction>, BlockchainError>>,
+    retrieve_transactions_responses: Vec<Result<RetrievedTransactions, BlockchainError>>,
     report_accounts_payable_responses: Vec<Result<Vec<BlockchainResult<Payment>>, String>>,
+    node_statistics_responses: Vec<NodeStatistics>,
+    health_responses: Vec<Vec<ComponentHealth>>,
+    configuration_responses: Vec<Vec<ConfigurationEntry>>,
+    change_configuration_responses: Vec<Option<Result<(), String>>>,
 }
 
 #[derive(Default)]
 pub struct Recording {
     messages: Vec<Box<dyn Any + Send>>,
+    type_names: Vec<&'static str>,
 }
 
 pub struct RecordAwaiter {
@@ -88,9 +115,11 @@ recorder_message_handler!(ExpiredCoresPackage<MessageType>);
 recorder_message_handler!(ExpiredCoresPackage<ClientRequestPayload>);
 recorder_message_handler!(ExpiredCoresPackage<ClientResponsePayload>);
 recorder_message_handler!(ExpiredCoresPackage<DnsResolveFailure>);
+recorder_message_handler!(ExpiredCoresPackage<BillingClaimPayload>);
 recorder_message_handler!(ExpiredCoresPackage<Gossip>);
 recorder_message_handler!(AddReturnRouteMessage);
 recorder_message_handler!(TransmitDataMsg);
+recorder_message_handler!(NeighborKeyMessage);
 recorder_message_handler!(BindMessage);
 recorder_message_handler!(IncipientCoresPackage);
 recorder_message_handler!(NoLookupIncipientCoresPackage);
@@ -101,25 +130,42 @@ recorder_message_handler!(DispatcherNodeQueryResponse);
 recorder_message_handler!(DispatcherNodeQueryMessage);
 recorder_message_handler!(UiCarrierMessage);
 recorder_message_handler!(FromUiMessage);
+recorder_message_handler!(NodeEvent);
 recorder_message_handler!(GetFinancialStatisticsMessage);
+recorder_message_handler!(DumpFinancialsMessage);
+recorder_message_handler!(SetPaymentParameterMessage);
 recorder_message_handler!(ReportRoutingServiceProvidedMessage);
 recorder_message_handler!(ReportExitServiceProvidedMessage);
 recorder_message_handler!(ReportRoutingServiceConsumedMessage);
 recorder_message_handler!(ReportExitServiceConsumedMessage);
 recorder_message_handler!(SetWalletPasswordMsg);
 recorder_message_handler!(SetGasPriceMsg);
+recorder_message_handler!(GetBlockchainServiceUrlMsg);
+recorder_message_handler!(GetWalletBalancesMsg);
 recorder_message_handler!(SetConsumingWalletMessage);
 recorder_message_handler!(DnsResolveFailure);
 recorder_message_handler!(NodeRecordMetadataMessage);
+recorder_message_handler!(DelinquencyMessage);
+recorder_message_handler!(DebtorKeyMessage);
+recorder_message_handler!(BillingClaimsMessage);
+recorder_message_handler!(BillingClaimMessage);
+recorder_message_handler!(DisputeMessage);
+recorder_message_handler!(ReportBillingClaimMessage);
 recorder_message_handler!(ReceivedPayments);
 recorder_message_handler!(SentPayments);
+recorder_message_handler!(ResolvedPayment);
+recorder_message_handler!(NewBlockMessage);
 recorder_message_handler!(AddRouteMessage);
+recorder_message_handler!(RouteFailureMessage);
+recorder_message_handler!(PassiveTunnelExpiredMessage);
 recorder_message_handler!(AddStreamMsg);
 recorder_message_handler!(PoolBindMessage);
 recorder_message_handler!(RemoveStreamMsg);
 recorder_message_handler!(StreamShutdownMsg);
 recorder_message_handler!(NeighborhoodDotGraphRequest);
+recorder_message_handler!(NeighborListRequest);
 recorder_message_handler!(StartMessage);
+recorder_message_handler!(ConsumingProfileExhaustedMessage);
 
 impl Handler<NodeQueryMessage> for Recorder {
     type Result = MessageResult<NodeQueryMessage>;
@@ -185,6 +231,70 @@ impl Handler<ReportAccountsPayable> for Recorder {
     }
 }
 
+impl Handler<GetNodeStatisticsMessage> for Recorder {
+    type Result = MessageResult<GetNodeStatisticsMessage>;
+
+    fn handle(
+        &mut self,
+        msg: GetNodeStatisticsMessage,
+        _ctx: &mut Self::Context,
+    ) -> <Self as Handler<GetNodeStatisticsMessage>>::Result {
+        self.record(msg);
+        MessageResult(extract_response(
+            &mut self.node_statistics_responses,
+            "No NodeStatistics prepared for GetNodeStatisticsMessage",
+        ))
+    }
+}
+
+impl Handler<GetHealthMessage> for Recorder {
+    type Result = MessageResult<GetHealthMessage>;
+
+    fn handle(
+        &mut self,
+        msg: GetHealthMessage,
+        _ctx: &mut Self::Context,
+    ) -> <Self as Handler<GetHealthMessage>>::Result {
+        self.record(msg);
+        MessageResult(extract_response(
+            &mut self.health_responses,
+            "No ComponentHealths prepared for GetHealthMessage",
+        ))
+    }
+}
+
+impl Handler<GetConfigurationMessage> for Recorder {
+    type Result = MessageResult<GetConfigurationMessage>;
+
+    fn handle(
+        &mut self,
+        msg: GetConfigurationMessage,
+        _ctx: &mut Self::Context,
+    ) -> <Self as Handler<GetConfigurationMessage>>::Result {
+        self.record(msg);
+        MessageResult(extract_response(
+            &mut self.configuration_responses,
+            "No ConfigurationEntries prepared for GetConfigurationMessage",
+        ))
+    }
+}
+
+impl Handler<ChangeConfigurationMessage> for Recorder {
+    type Result = MessageResult<ChangeConfigurationMessage>;
+
+    fn handle(
+        &mut self,
+        msg: ChangeConfigurationMessage,
+        _ctx: &mut Self::Context,
+    ) -> <Self as Handler<ChangeConfigurationMessage>>::Result {
+        self.record(msg);
+        MessageResult(extract_response(
+            &mut self.change_configuration_responses,
+            "No results prepared for ChangeConfigurationMessage",
+        ))
+    }
+}
+
 fn extract_response<T>(responses: &mut Vec<T>, err_msg: &str) -> T
 where
     T: Clone,
@@ -209,6 +319,7 @@ impl Recorder {
         let messages: &mut Vec<Box<dyn Any + Send>> = &mut recording.messages;
         let item_box = Box::new(item);
         messages.push(item_box);
+        recording.type_names.push(type_name::<T>());
     }
 
     pub fn get_recording(&self) -> Arc<Mutex<Recording>> {
@@ -233,7 +344,7 @@ impl Recorder {
 
     pub fn retrieve_transactions_response(
         mut self,
-        response: Result<Vec<Transaction>, BlockchainError>,
+        response: Result<RetrievedTransactions, BlockchainError>,
     ) -> Recorder {
         self.retrieve_transactions_responses.push(response);
         self
@@ -246,6 +357,29 @@ impl Recorder {
         self.report_accounts_payable_responses.push(response);
         self
     }
+
+    pub fn node_statistics_response(mut self, response: NodeStatistics) -> Recorder {
+        self.node_statistics_responses.push(response);
+        self
+    }
+
+    pub fn health_response(mut self, response: Vec<ComponentHealth>) -> Recorder {
+        self.health_responses.push(response);
+        self
+    }
+
+    pub fn configuration_response(mut self, response: Vec<ConfigurationEntry>) -> Recorder {
+        self.configuration_responses.push(response);
+        self
+    }
+
+    pub fn change_configuration_response(
+        mut self,
+        response: Option<Result<(), String>>,
+    ) -> Recorder {
+        self.change_configuration_responses.push(response);
+        self
+    }
 }
 
 impl Recording {
@@ -285,6 +419,91 @@ impl Recording {
             ),
         }
     }
+
+    fn first_of_type<T>(&self) -> Option<&T>
+    where
+        T: Any + Send,
+    {
+        self.messages
+            .iter()
+            .find_map(|item_box| item_box.downcast_ref::<T>())
+    }
+
+    fn describe(&self) -> String {
+        if self.type_names.is_empty() {
+            return "nothing".to_string();
+        }
+        self.type_names
+            .iter()
+            .enumerate()
+            .map(|(index, type_name)| format!("#{} {}", index, type_name))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+// Assertions for tests whose recorders are running in another thread, so that they needn't sleep
+// and poll, or depend on the order in which several recorders hear things
+pub trait RecordingAssertions {
+    // Blocks until a message of type T has been recorded and returns the first one; panics, saying
+    // what was recorded instead, if none arrives in time
+    fn await_message<T>(&self, timeout: Duration) -> T
+    where
+        T: Any + Send + Clone;
+    // Blocks for the whole window, and panics as soon as a message of type T is recorded
+    fn expect_no_message<T>(&self, window: Duration)
+    where
+        T: Any + Send + Debug;
+}
+
+impl RecordingAssertions for Arc<Mutex<Recording>> {
+    fn await_message<T>(&self, timeout: Duration) -> T
+    where
+        T: Any + Send + Clone,
+    {
+        let begin = Instant::now();
+        loop {
+            {
+                let recording = self.lock().unwrap();
+                if let Some(message) = recording.first_of_type::<T>() {
+                    return message.clone();
+                }
+                if begin.elapsed() >= timeout {
+                    panic!(
+                        "After {}ms, recorder has no {}; it has recorded {}",
+                        to_millis(&timeout),
+                        type_name::<T>(),
+                        recording.describe()
+                    );
+                }
+            }
+            thread::sleep(Duration::from_millis(10))
+        }
+    }
+
+    fn expect_no_message<T>(&self, window: Duration)
+    where
+        T: Any + Send + Debug,
+    {
+        let begin = Instant::now();
+        loop {
+            {
+                let recording = self.lock().unwrap();
+                if let Some(message) = recording.first_of_type::<T>() {
+                    panic!(
+                        "Within {}ms, recorder received unexpected {}: {:?}",
+                        to_millis(&begin.elapsed()),
+                        type_name::<T>(),
+                        message
+                    );
+                }
+            }
+            if begin.elapsed() >= window {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10))
+        }
+    }
 }
 
 impl RecordAwaiter {
@@ -330,10 +549,16 @@ pub fn make_proxy_server_subs_from(addr: &Addr<Recorder>) -> ProxyServerSubs {
         dns_failure_from_hopper: addr
             .clone()
             .recipient::<ExpiredCoresPackage<DnsResolveFailure>>(),
+        billing_claim_from_hopper: addr
+            .clone()
+            .recipient::<ExpiredCoresPackage<BillingClaimPayload>>(),
         add_return_route: recipient!(addr, AddReturnRouteMessage),
         add_route: recipient!(addr, AddRouteMessage),
         stream_shutdown_sub: recipient!(addr, StreamShutdownMsg),
         set_consuming_wallet_sub: recipient!(addr, SetConsumingWalletMessage),
+        node_statistics_sub: recipient!(addr, GetNodeStatisticsMessage),
+        pool_bind: recipient!(addr, PoolBindMessage),
+        profile_exhausted_sub: recipient!(addr, ConsumingProfileExhaustedMessage),
     }
 }
 
@@ -343,6 +568,7 @@ pub fn make_dispatcher_subs_from(addr: &Addr<Recorder>) -> DispatcherSubs {
         bind: recipient!(addr, BindMessage),
         from_dispatcher_client: recipient!(addr, TransmitDataMsg),
         stream_shutdown_sub: recipient!(addr, StreamShutdownMsg),
+        neighbor_key_sub: recipient!(addr, NeighborKeyMessage),
     }
 }
 
@@ -363,6 +589,8 @@ pub fn make_proxy_client_subs_from(addr: &Addr<Recorder>) -> ProxyClientSubs {
             .recipient::<ExpiredCoresPackage<ClientRequestPayload>>(),
         inbound_server_data: recipient!(addr, InboundServerData),
         dns_resolve_failed: recipient!(addr, DnsResolveFailure),
+        billing_claims: recipient!(addr, BillingClaimsMessage),
+        node_statistics_sub: recipient!(addr, GetNodeStatisticsMessage),
     }
 }
 
@@ -379,6 +607,13 @@ pub fn make_neighborhood_subs_from(addr: &Addr<Recorder>) -> NeighborhoodSubs {
         stream_shutdown_sub: recipient!(addr, StreamShutdownMsg),
         set_consuming_wallet_sub: recipient!(addr, SetConsumingWalletMessage),
         from_ui_gateway: addr.clone().recipient::<NeighborhoodDotGraphRequest>(),
+        neighbor_list_sub: addr.clone().recipient::<NeighborListRequest>(),
+        delinquency_sub: recipient!(addr, DelinquencyMessage),
+        debtor_key_sub: recipient!(addr, DebtorKeyMessage),
+        billing_claim_sub: recipient!(addr, BillingClaimMessage),
+        dispute_sub: recipient!(addr, DisputeMessage),
+        node_statistics_sub: recipient!(addr, GetNodeStatisticsMessage),
+        health_sub: recipient!(addr, GetHealthMessage),
     }
 }
 
@@ -396,7 +631,16 @@ pub fn make_accountant_subs_from(addr: &Addr<Recorder>) -> AccountantSubs {
         report_exit_service_consumed: recipient!(addr, ReportExitServiceConsumedMessage),
         report_new_payments: recipient!(addr, ReceivedPayments),
         report_sent_payments: recipient!(addr, SentPayments),
+        report_resolved_payment: recipient!(addr, ResolvedPayment),
+        report_new_block: recipient!(addr, NewBlockMessage),
         get_financial_statistics_sub: recipient!(addr, GetFinancialStatisticsMessage),
+        dump_financials_sub: recipient!(addr, DumpFinancialsMessage),
+        set_payment_parameter_sub: recipient!(addr, SetPaymentParameterMessage),
+        report_billing_claim_sub: recipient!(addr, ReportBillingClaimMessage),
+        node_statistics_sub: recipient!(addr, GetNodeStatisticsMessage),
+        health_sub: recipient!(addr, GetHealthMessage),
+        configuration_sub: recipient!(addr, GetConfigurationMessage),
+        change_configuration_sub: recipient!(addr, ChangeConfigurationMessage),
     }
 }
 
@@ -405,6 +649,7 @@ pub fn make_ui_gateway_subs_from(addr: &Addr<Recorder>) -> UiGatewaySubs {
         bind: recipient!(addr, BindMessage),
         ui_message_sub: recipient!(addr, UiCarrierMessage),
         from_ui_message_sub: recipient!(addr, FromUiMessage),
+        node_event_sub: recipient!(addr, NodeEvent),
     }
 }
 
@@ -414,6 +659,9 @@ pub fn make_blockchain_bridge_subs_from(addr: &Addr<Recorder>) -> BlockchainBrid
         report_accounts_payable: recipient!(addr, ReportAccountsPayable),
         retrieve_transactions: recipient!(addr, RetrieveTransactions),
         set_gas_price_sub: recipient!(addr, SetGasPriceMsg),
+        get_blockchain_service_url_sub: recipient!(addr, GetBlockchainServiceUrlMsg),
+        get_wallet_balances_sub: recipient!(addr, GetWalletBalancesMsg),
+        health_sub: recipient!(addr, GetHealthMessage),
         set_consuming_wallet_password_sub: recipient!(addr, SetWalletPasswordMsg),
     }
 }
@@ -517,8 +765,9 @@ mod tests {
     use super::*;
     use actix::Message;
     use actix::System;
+    use std::sync::mpsc;
 
-    #[derive(Debug, PartialEq, Message)]
+    #[derive(Clone, Debug, PartialEq, Message)]
     struct FirstMessageType {
         string: String,
     }
@@ -572,4 +821,101 @@ mod tests {
         );
         assert_eq!(recording.len(), 2);
     }
+
+    fn start_recorder_in_thread(recorder: Recorder) -> Addr<Recorder> {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let system = System::new("start_recorder_in_thread");
+            tx.send(recorder.start()).unwrap();
+            system.run();
+        });
+        rx.recv().unwrap()
+    }
+
+    #[test]
+    fn await_message_returns_the_first_message_of_the_type_once_it_arrives() {
+        let recorder = Recorder::new();
+        let recording_arc = recorder.get_recording();
+        let rec_addr = start_recorder_in_thread(recorder);
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            rec_addr
+                .try_send(SecondMessageType {
+                    size: 42,
+                    flag: true,
+                })
+                .unwrap();
+            rec_addr
+                .try_send(FirstMessageType {
+                    string: "first".to_string(),
+                })
+                .unwrap();
+            rec_addr
+                .try_send(FirstMessageType {
+                    string: "second".to_string(),
+                })
+                .unwrap();
+        });
+
+        let result = recording_arc.await_message::<FirstMessageType>(Duration::from_millis(5000));
+
+        assert_eq!(
+            result,
+            FirstMessageType {
+                string: "first".to_string()
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "After 100ms, recorder has no node_lib::test_utils::recorder::tests::FirstMessageType; it has recorded #0 node_lib::test_utils::recorder::tests::SecondMessageType"
+    )]
+    fn await_message_says_what_arrived_instead_when_it_times_out() {
+        let recorder = Recorder::new();
+        let recording_arc = recorder.get_recording();
+        let rec_addr = start_recorder_in_thread(recorder);
+        rec_addr
+            .try_send(SecondMessageType {
+                size: 42,
+                flag: true,
+            })
+            .unwrap();
+
+        recording_arc.await_message::<FirstMessageType>(Duration::from_millis(100));
+    }
+
+    #[test]
+    fn expect_no_message_is_satisfied_by_messages_of_other_types() {
+        let recorder = Recorder::new();
+        let recording_arc = recorder.get_recording();
+        let rec_addr = start_recorder_in_thread(recorder);
+        rec_addr
+            .try_send(SecondMessageType {
+                size: 42,
+                flag: true,
+            })
+            .unwrap();
+
+        recording_arc.expect_no_message::<FirstMessageType>(Duration::from_millis(100));
+
+        assert_eq!(recording_arc.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "recorder received unexpected node_lib::test_utils::recorder::tests::FirstMessageType: FirstMessageType { string: \"unwelcome\" }"
+    )]
+    fn expect_no_message_fails_as_soon_as_one_arrives() {
+        let recorder = Recorder::new();
+        let recording_arc = recorder.get_recording();
+        let rec_addr = start_recorder_in_thread(recorder);
+        rec_addr
+            .try_send(FirstMessageType {
+                string: "unwelcome".to_string(),
+            })
+            .unwrap();
+
+        recording_arc.expect_no_message::<FirstMessageType>(Duration::from_millis(10_000));
+    }
 }
@@ -0,0 +1,201 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::blockchain::blockchain_interface::contract_address;
+use crate::sub_lib::cryptde::{CryptDE, CryptData, PublicKey};
+use crate::sub_lib::dispatcher::Component;
+use crate::sub_lib::route::{Route, RouteSegment};
+use crate::sub_lib::wallet::Wallet;
+use crate::test_utils::{cryptde, make_garbage_data, make_paying_wallet, DEFAULT_CHAIN_ID};
+use web3::types::Address;
+
+/// Builds a Route for a test. Unless told otherwise, it builds the same one-way Route as
+/// `make_meaningless_route`; `back()` makes it a round trip, and `garble_hop()` and `truncate()`
+/// spoil it in ways a real Route could arrive spoiled.
+pub struct RouteBuilder {
+    over: (Vec<PublicKey>, Component),
+    back_opt: Option<(Vec<PublicKey>, Component)>,
+    cryptde: &'static dyn CryptDE,
+    consuming_wallet_opt: Option<Wallet>,
+    return_route_id: u32,
+    contract_address_opt: Option<Address>,
+    garbled_hops: Vec<usize>,
+    hop_count_opt: Option<usize>,
+}
+
+impl Default for RouteBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RouteBuilder {
+    pub fn new() -> RouteBuilder {
+        RouteBuilder {
+            over: (
+                vec![PublicKey::new(&b"ooga"[..]), PublicKey::new(&b"booga"[..])],
+                Component::ProxyClient,
+            ),
+            back_opt: None,
+            cryptde: cryptde(),
+            consuming_wallet_opt: Some(make_paying_wallet(b"irrelevant")),
+            return_route_id: 0,
+            contract_address_opt: Some(contract_address(DEFAULT_CHAIN_ID)),
+            garbled_hops: vec![],
+            hop_count_opt: None,
+        }
+    }
+
+    pub fn over(mut self, keys: Vec<&PublicKey>, recipient: Component) -> RouteBuilder {
+        self.over = (keys.into_iter().cloned().collect(), recipient);
+        self
+    }
+
+    // The back segment must start where the over segment ends
+    pub fn back(mut self, keys: Vec<&PublicKey>, recipient: Component) -> RouteBuilder {
+        self.back_opt = Some((keys.into_iter().cloned().collect(), recipient));
+        self
+    }
+
+    // For a round trip, this must be the originating Node's CryptDE
+    pub fn cryptde(mut self, cryptde: &'static dyn CryptDE) -> RouteBuilder {
+        self.cryptde = cryptde;
+        self
+    }
+
+    pub fn consuming_wallet(mut self, consuming_wallet_opt: Option<Wallet>) -> RouteBuilder {
+        self.consuming_wallet_opt = consuming_wallet_opt;
+        self
+    }
+
+    pub fn return_route_id(mut self, return_route_id: u32) -> RouteBuilder {
+        self.return_route_id = return_route_id;
+        self
+    }
+
+    pub fn contract_address(mut self, contract_address_opt: Option<Address>) -> RouteBuilder {
+        self.contract_address_opt = contract_address_opt;
+        self
+    }
+
+    // The hop at this index will be bytes that no Node can decode
+    pub fn garble_hop(mut self, index: usize) -> RouteBuilder {
+        self.garbled_hops.push(index);
+        self
+    }
+
+    // Only this many hops will be kept, from the front
+    pub fn truncate(mut self, hop_count: usize) -> RouteBuilder {
+        self.hop_count_opt = Some(hop_count);
+        self
+    }
+
+    pub fn build(self) -> Route {
+        let (over_keys, over_recipient) = &self.over;
+        let over = RouteSegment::new(over_keys.iter().collect(), *over_recipient);
+        let mut route = match &self.back_opt {
+            None => Route::one_way(
+                over,
+                self.cryptde,
+                self.consuming_wallet_opt.clone(),
+                self.contract_address_opt,
+            ),
+            Some((back_keys, back_recipient)) => Route::round_trip(
+                over,
+                RouteSegment::new(back_keys.iter().collect(), *back_recipient),
+                self.cryptde,
+                self.consuming_wallet_opt.clone(),
+                self.return_route_id,
+                self.contract_address_opt,
+            ),
+        }
+        .expect("RouteBuilder couldn't build its Route");
+        self.garbled_hops.iter().for_each(|index| {
+            let hop_len = route.hops[*index].len();
+            route.hops[*index] = CryptData::new(&make_garbage_data(hop_len));
+        });
+        if let Some(hop_count) = self.hop_count_opt {
+            route.hops.truncate(hop_count);
+        }
+        route
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sub_lib::cryptde_null::CryptDENull;
+    use crate::sub_lib::route::RouteError;
+
+    fn cryptde_for(key: &PublicKey) -> CryptDENull {
+        CryptDENull::from(key, DEFAULT_CHAIN_ID)
+    }
+
+    #[test]
+    fn builds_the_meaningless_route_by_default() {
+        let result = RouteBuilder::new().build();
+
+        assert_eq!(result.hops, make_meaningless_route_hops());
+    }
+
+    fn make_meaningless_route_hops() -> Vec<CryptData> {
+        Route::one_way(
+            RouteSegment::new(
+                vec![
+                    &PublicKey::new(&b"ooga"[..]),
+                    &PublicKey::new(&b"booga"[..]),
+                ],
+                Component::ProxyClient,
+            ),
+            cryptde(),
+            Some(make_paying_wallet(b"irrelevant")),
+            Some(contract_address(DEFAULT_CHAIN_ID)),
+        )
+        .unwrap()
+        .hops
+    }
+
+    #[test]
+    fn builds_a_round_trip_that_each_node_can_follow() {
+        let a = cryptde().public_key().clone();
+        let b = PublicKey::new(&b"bbbb"[..]);
+        let c = PublicKey::new(&b"cccc"[..]);
+        let mut subject = RouteBuilder::new()
+            .over(vec![&a, &b, &c], Component::ProxyClient)
+            .back(vec![&c, &b, &a], Component::ProxyServer)
+            .consuming_wallet(None)
+            .return_route_id(1234)
+            .build();
+
+        let components = vec![&a, &b, &c, &b, &a]
+            .into_iter()
+            .map(|key| subject.shift(&cryptde_for(key)).unwrap().component)
+            .collect::<Vec<Component>>();
+
+        assert_eq!(
+            components,
+            vec![
+                Component::Hopper,
+                Component::Hopper,
+                Component::ProxyClient,
+                Component::Hopper,
+                Component::ProxyServer,
+            ]
+        );
+        assert_eq!(subject.id(cryptde()), Ok(1234));
+    }
+
+    #[test]
+    fn spoils_the_route_on_request() {
+        let ooga = PublicKey::new(&b"ooga"[..]);
+        let mut garbled = RouteBuilder::new().garble_hop(0).build();
+        let truncated = RouteBuilder::new().truncate(0).build();
+
+        let garbled_result = garbled.shift(&cryptde_for(&ooga));
+        let truncated_result = truncated.next_hop(&cryptde_for(&ooga));
+
+        match garbled_result {
+            Err(RouteError::HopDecodeProblem(_)) => (),
+            other => panic!("Expected HopDecodeProblem, got {:?}", other),
+        }
+        assert_eq!(truncated_result, Err(RouteError::EmptyRoute));
+    }
+}
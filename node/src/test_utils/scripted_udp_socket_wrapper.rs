@@ -0,0 +1,51 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::sub_lib::udp_socket_wrapper::UdpSocketWrapperTrait;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::prelude::Async;
+
+// Hands out the datagrams it's given, then NotReady forever; remembers everything sent. Clones
+// share their script and records, so a test can keep one while the subject owns another.
+#[derive(Clone, Default)]
+pub struct ScriptedUdpSocketWrapper {
+    pub bind_params: Arc<Mutex<Vec<SocketAddr>>>,
+    pub send_to_params: Arc<Mutex<Vec<(Vec<u8>, SocketAddr)>>>,
+    pub recv_from_results: Arc<Mutex<Vec<(Vec<u8>, SocketAddr)>>>,
+}
+
+impl UdpSocketWrapperTrait for ScriptedUdpSocketWrapper {
+    fn bind(&mut self, addr: SocketAddr) -> io::Result<bool> {
+        self.bind_params.lock().unwrap().push(addr);
+        Ok(true)
+    }
+
+    fn recv_from(&mut self, buf: &mut [u8]) -> Result<Async<(usize, SocketAddr)>, io::Error> {
+        let mut results = self.recv_from_results.lock().unwrap();
+        if results.is_empty() {
+            return Ok(Async::NotReady);
+        }
+        let (data, from) = results.remove(0);
+        buf[0..data.len()].copy_from_slice(&data);
+        Ok(Async::Ready((data.len(), from)))
+    }
+
+    fn send_to(&mut self, buf: &[u8], addr: SocketAddr) -> Result<Async<usize>, io::Error> {
+        self.send_to_params
+            .lock()
+            .unwrap()
+            .push((buf.to_vec(), addr));
+        Ok(Async::Ready(buf.len()))
+    }
+}
+
+impl ScriptedUdpSocketWrapper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn recv_from_result(self, data: Vec<u8>, from: SocketAddr) -> Self {
+        self.recv_from_results.lock().unwrap().push((data, from));
+        self
+    }
+}
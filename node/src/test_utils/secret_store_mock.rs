@@ -0,0 +1,64 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::node_configurator::secret_store::{Secret, SecretStore};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+// A keychain in memory; with a failure set, every operation fails with it
+#[derive(Default)]
+pub struct SecretStoreMock {
+    secrets: RefCell<HashMap<Secret, String>>,
+    failure_opt: Option<String>,
+    store_params: Arc<Mutex<Vec<(Secret, String)>>>,
+}
+
+impl SecretStore for SecretStoreMock {
+    fn store(&self, secret: Secret, value: &str) -> Result<(), String> {
+        self.store_params
+            .lock()
+            .unwrap()
+            .push((secret, value.to_string()));
+        self.fail_if_told_to()?;
+        self.secrets.borrow_mut().insert(secret, value.to_string());
+        Ok(())
+    }
+
+    fn retrieve(&self, secret: Secret) -> Result<Option<String>, String> {
+        self.fail_if_told_to()?;
+        Ok(self.secrets.borrow().get(&secret).cloned())
+    }
+
+    fn forget(&self, secret: Secret) -> Result<(), String> {
+        self.fail_if_told_to()?;
+        self.secrets.borrow_mut().remove(&secret);
+        Ok(())
+    }
+}
+
+impl SecretStoreMock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn secret(self, secret: Secret, value: &str) -> Self {
+        self.secrets.borrow_mut().insert(secret, value.to_string());
+        self
+    }
+
+    pub fn failure(mut self, failure: &str) -> Self {
+        self.failure_opt = Some(failure.to_string());
+        self
+    }
+
+    pub fn store_params(mut self, params: &Arc<Mutex<Vec<(Secret, String)>>>) -> Self {
+        self.store_params = params.clone();
+        self
+    }
+
+    fn fail_if_told_to(&self) -> Result<(), String> {
+        match &self.failure_opt {
+            Some(failure) => Err(failure.clone()),
+            None => Ok(()),
+        }
+    }
+}
@@ -2,6 +2,7 @@
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::stream_connector::ConnectionInfo;
 use crate::sub_lib::stream_connector::ConnectionInfoFuture;
+use crate::sub_lib::stream_connector::Preamble;
 use crate::sub_lib::stream_connector::StreamConnector;
 use crate::test_utils::tokio_wrapper_mocks::ReadHalfWrapperMock;
 use crate::test_utils::tokio_wrapper_mocks::WriteHalfWrapperMock;
@@ -34,6 +35,7 @@ impl StreamConnector for StreamConnectorMock {
         _ip_addrs: Vec<IpAddr>,
         _target_hostname: &String,
         _target_port: u16,
+        _preamble_opt: Option<Preamble>,
         _logger: &Logger,
     ) -> Result<ConnectionInfo, io::Error> {
         self.connect_pair_results.borrow_mut().remove(0)
@@ -0,0 +1,512 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::bootstrapper::PortConfiguration;
+use crate::listener_handler::{ListenerHandler, ListenerHandlerFactory};
+use crate::stream_messages::AddStreamMsg;
+use crate::sub_lib::stream_connector::ConnectionInfo;
+use crate::sub_lib::tokio_wrappers::{ReadHalfWrapper, WriteHalfWrapper};
+use actix::Recipient;
+use futures::task;
+use std::collections::VecDeque;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::prelude::{Async, AsyncRead, AsyncWrite, Future};
+
+enum Step<T> {
+    Go(T),
+    Fail(ErrorKind),
+    WouldBlock,
+    Delay(Duration),
+    DelayedUntil(Instant),
+}
+
+enum Turn {
+    Go,
+    Fail(io::Error),
+    // Not ready yet; the task should be polled again now, or at the Instant
+    Later(Option<Instant>),
+    // Out of script: nothing more will ever happen
+    Idle,
+}
+
+// Works through the errors, WouldBlocks and delays at the front of a script, and says whether
+// the step after them can go ahead
+fn take_turn<T>(steps: &mut VecDeque<Step<T>>) -> Turn {
+    loop {
+        let until = match steps.front_mut() {
+            None => return Turn::Idle,
+            Some(Step::Go(_)) => return Turn::Go,
+            Some(Step::Fail(kind)) => {
+                let error = io::Error::from(*kind);
+                steps.pop_front();
+                return Turn::Fail(error);
+            }
+            Some(Step::WouldBlock) => {
+                steps.pop_front();
+                return Turn::Later(None);
+            }
+            Some(Step::Delay(delay)) => {
+                let until = Instant::now() + *delay;
+                steps[0] = Step::DelayedUntil(until);
+                until
+            }
+            Some(Step::DelayedUntil(until)) => *until,
+        };
+        if Instant::now() < until {
+            return Turn::Later(Some(until));
+        }
+        steps.pop_front();
+    }
+}
+
+// A real socket would register with the reactor; the mock wakes its task itself
+fn wake_later(when_opt: Option<Instant>) {
+    if !task::is_in_task() {
+        return;
+    }
+    let task = task::current();
+    match when_opt {
+        None => task.notify(),
+        Some(when) => {
+            thread::spawn(move || {
+                let now = Instant::now();
+                if when > now {
+                    thread::sleep(when - now);
+                }
+                task.notify();
+            });
+        }
+    }
+}
+
+/// A TCP stream whose reads and writes follow a script, for testing code that owns either half
+/// of a connection without opening one. Reads hand out the scripted chunks, a bit at a time if
+/// the buffer is short; writes accept the scripted number of bytes and record them. Either script
+/// may also hold errors (`ConnectionReset` for a reset), WouldBlocks and delays. Past the end of
+/// its script, a read half waits forever and a write half accepts everything.
+#[derive(Default)]
+pub struct TcpStreamMock {
+    reads: VecDeque<Step<Vec<u8>>>,
+    writes: VecDeque<Step<usize>>,
+    written: Arc<Mutex<Vec<u8>>>,
+    shutdowns: Arc<Mutex<usize>>,
+}
+
+impl TcpStreamMock {
+    pub fn new() -> TcpStreamMock {
+        TcpStreamMock::default()
+    }
+
+    pub fn read_chunk(mut self, data: &[u8]) -> TcpStreamMock {
+        self.reads.push_back(Step::Go(data.to_vec()));
+        self
+    }
+
+    // The peer closed the stream: a 0-byte read
+    pub fn read_eof(mut self) -> TcpStreamMock {
+        self.reads.push_back(Step::Go(vec![]));
+        self
+    }
+
+    pub fn read_error(mut self, kind: ErrorKind) -> TcpStreamMock {
+        self.reads.push_back(Step::Fail(kind));
+        self
+    }
+
+    pub fn read_would_block(mut self) -> TcpStreamMock {
+        self.reads.push_back(Step::WouldBlock);
+        self
+    }
+
+    pub fn read_delay(mut self, delay: Duration) -> TcpStreamMock {
+        self.reads.push_back(Step::Delay(delay));
+        self
+    }
+
+    // The next write takes no more than this many bytes
+    pub fn write_accept(mut self, len: usize) -> TcpStreamMock {
+        self.writes.push_back(Step::Go(len));
+        self
+    }
+
+    pub fn write_error(mut self, kind: ErrorKind) -> TcpStreamMock {
+        self.writes.push_back(Step::Fail(kind));
+        self
+    }
+
+    pub fn write_would_block(mut self) -> TcpStreamMock {
+        self.writes.push_back(Step::WouldBlock);
+        self
+    }
+
+    pub fn write_delay(mut self, delay: Duration) -> TcpStreamMock {
+        self.writes.push_back(Step::Delay(delay));
+        self
+    }
+
+    // Everything the write half has accepted, in order
+    pub fn written(mut self, written_arc: &Arc<Mutex<Vec<u8>>>) -> TcpStreamMock {
+        self.written = written_arc.clone();
+        self
+    }
+
+    pub fn shutdowns(mut self, shutdowns_arc: &Arc<Mutex<usize>>) -> TcpStreamMock {
+        self.shutdowns = shutdowns_arc.clone();
+        self
+    }
+
+    pub fn split(self) -> (TcpStreamMockReadHalf, TcpStreamMockWriteHalf) {
+        (
+            TcpStreamMockReadHalf { steps: self.reads },
+            TcpStreamMockWriteHalf {
+                steps: self.writes,
+                written: self.written,
+                shutdowns: self.shutdowns,
+            },
+        )
+    }
+
+    pub fn connection_info(self, local_addr: SocketAddr, peer_addr: SocketAddr) -> ConnectionInfo {
+        let (reader, writer) = self.split();
+        ConnectionInfo {
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+            local_addr,
+            peer_addr,
+        }
+    }
+}
+
+pub struct TcpStreamMockReadHalf {
+    steps: VecDeque<Step<Vec<u8>>>,
+}
+
+impl ReadHalfWrapper for TcpStreamMockReadHalf {}
+
+impl Read for TcpStreamMockReadHalf {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        match self.try_read(buf) {
+            Ok(Async::Ready(len)) => Ok(len),
+            Ok(Async::NotReady) => Err(io::Error::from(ErrorKind::WouldBlock)),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl AsyncRead for TcpStreamMockReadHalf {
+    fn poll_read(&mut self, buf: &mut [u8]) -> Result<Async<usize>, io::Error> {
+        self.try_read(buf)
+    }
+}
+
+impl TcpStreamMockReadHalf {
+    fn try_read(&mut self, buf: &mut [u8]) -> Result<Async<usize>, io::Error> {
+        match take_turn(&mut self.steps) {
+            Turn::Go => (),
+            Turn::Fail(e) => return Err(e),
+            Turn::Later(when_opt) => {
+                wake_later(when_opt);
+                return Ok(Async::NotReady);
+            }
+            Turn::Idle => return Ok(Async::NotReady),
+        }
+        let len = match self.steps.front_mut() {
+            Some(Step::Go(chunk)) => {
+                let len = chunk.len().min(buf.len());
+                buf[..len].copy_from_slice(&chunk[..len]);
+                chunk.drain(..len);
+                if !chunk.is_empty() {
+                    return Ok(Async::Ready(len));
+                }
+                len
+            }
+            _ => unreachable!(),
+        };
+        self.steps.pop_front();
+        Ok(Async::Ready(len))
+    }
+}
+
+pub struct TcpStreamMockWriteHalf {
+    steps: VecDeque<Step<usize>>,
+    written: Arc<Mutex<Vec<u8>>>,
+    shutdowns: Arc<Mutex<usize>>,
+}
+
+impl WriteHalfWrapper for TcpStreamMockWriteHalf {}
+
+impl Write for TcpStreamMockWriteHalf {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, io::Error> {
+        match self.try_write(buf) {
+            Ok(Async::Ready(len)) => Ok(len),
+            Ok(Async::NotReady) => Err(io::Error::from(ErrorKind::WouldBlock)),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), io::Error> {
+        Ok(())
+    }
+}
+
+impl AsyncWrite for TcpStreamMockWriteHalf {
+    fn poll_write(&mut self, buf: &[u8]) -> Result<Async<usize>, io::Error> {
+        self.try_write(buf)
+    }
+
+    fn shutdown(&mut self) -> Result<Async<()>, io::Error> {
+        *self.shutdowns.lock().unwrap() += 1;
+        Ok(Async::Ready(()))
+    }
+}
+
+impl TcpStreamMockWriteHalf {
+    fn try_write(&mut self, buf: &[u8]) -> Result<Async<usize>, io::Error> {
+        let len = match take_turn(&mut self.steps) {
+            Turn::Go => match self.steps.pop_front() {
+                Some(Step::Go(limit)) => limit.min(buf.len()),
+                _ => unreachable!(),
+            },
+            Turn::Fail(e) => return Err(e),
+            Turn::Later(when_opt) => {
+                wake_later(when_opt);
+                return Ok(Async::NotReady);
+            }
+            Turn::Idle => buf.len(),
+        };
+        self.written.lock().unwrap().extend_from_slice(&buf[..len]);
+        Ok(Async::Ready(len))
+    }
+}
+
+/// Stands in for a listener: on its first poll, it hands each of its TcpStreamMocks to the
+/// StreamHandlerPool as though a client had just connected, then waits forever.
+pub struct ListenerHandlerMock {
+    bind_port_and_configuration_params: Arc<Mutex<Vec<u16>>>,
+    bind_port_and_configuration_results: Vec<io::Result<()>>,
+    free_port: u16,
+    port_configuration_opt: Option<PortConfiguration>,
+    add_stream_sub_opt: Option<Recipient<AddStreamMsg>>,
+    connections: Vec<(TcpStreamMock, SocketAddr, SocketAddr)>,
+    port_opt: Option<u16>,
+}
+
+impl ListenerHandler for ListenerHandlerMock {
+    fn bind_port_and_configuration(
+        &mut self,
+        port: u16,
+        port_configuration: PortConfiguration,
+    ) -> io::Result<()> {
+        self.bind_port_and_configuration_params
+            .lock()
+            .unwrap()
+            .push(port);
+        self.port_opt = Some(port);
+        self.port_configuration_opt = Some(port_configuration);
+        if self.bind_port_and_configuration_results.is_empty() {
+            Ok(())
+        } else {
+            self.bind_port_and_configuration_results.remove(0)
+        }
+    }
+
+    fn bind_free_port_and_configuration(
+        &mut self,
+        port_configuration: PortConfiguration,
+    ) -> io::Result<u16> {
+        self.port_opt = Some(self.free_port);
+        self.port_configuration_opt = Some(port_configuration);
+        Ok(self.free_port)
+    }
+
+    fn bind_subs(&mut self, add_stream_sub: Recipient<AddStreamMsg>) {
+        self.add_stream_sub_opt = Some(add_stream_sub);
+    }
+}
+
+impl Future for ListenerHandlerMock {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Result<Async<()>, ()> {
+        let add_stream_sub = self
+            .add_stream_sub_opt
+            .as_ref()
+            .expect("ListenerHandlerMock is unbound");
+        let port_configuration = self
+            .port_configuration_opt
+            .as_ref()
+            .expect("ListenerHandlerMock was never bound to a port");
+        let port_opt = self.port_opt;
+        self.connections
+            .drain(..)
+            .for_each(|(stream, local_addr, peer_addr)| {
+                add_stream_sub
+                    .try_send(AddStreamMsg::new(
+                        stream.connection_info(local_addr, peer_addr),
+                        port_opt,
+                        port_configuration.clone(),
+                    ))
+                    .expect("StreamHandlerPool is dead")
+            });
+        Ok(Async::NotReady)
+    }
+}
+
+impl ListenerHandlerMock {
+    pub fn new() -> ListenerHandlerMock {
+        ListenerHandlerMock {
+            bind_port_and_configuration_params: Arc::new(Mutex::new(vec![])),
+            bind_port_and_configuration_results: vec![],
+            free_port: 49152,
+            port_configuration_opt: None,
+            add_stream_sub_opt: None,
+            connections: vec![],
+            port_opt: None,
+        }
+    }
+
+    pub fn bind_port_and_configuration_params(
+        mut self,
+        params_arc: &Arc<Mutex<Vec<u16>>>,
+    ) -> ListenerHandlerMock {
+        self.bind_port_and_configuration_params = params_arc.clone();
+        self
+    }
+
+    pub fn bind_port_and_configuration_result(
+        mut self,
+        result: io::Result<()>,
+    ) -> ListenerHandlerMock {
+        self.bind_port_and_configuration_results.push(result);
+        self
+    }
+
+    pub fn free_port(mut self, free_port: u16) -> ListenerHandlerMock {
+        self.free_port = free_port;
+        self
+    }
+
+    pub fn connection(
+        mut self,
+        stream: TcpStreamMock,
+        local_addr: SocketAddr,
+        peer_addr: SocketAddr,
+    ) -> ListenerHandlerMock {
+        self.connections.push((stream, local_addr, peer_addr));
+        self
+    }
+}
+
+/// Hands out its ListenerHandlerMocks in order, one per make().
+#[derive(Default)]
+pub struct ListenerHandlerFactoryMock {
+    make_results: Mutex<Vec<ListenerHandlerMock>>,
+}
+
+impl ListenerHandlerFactory for ListenerHandlerFactoryMock {
+    fn make(&self) -> Box<dyn ListenerHandler<Item = (), Error = ()>> {
+        Box::new(self.make_results.lock().unwrap().remove(0))
+    }
+}
+
+impl ListenerHandlerFactoryMock {
+    pub fn new() -> ListenerHandlerFactoryMock {
+        ListenerHandlerFactoryMock::default()
+    }
+
+    pub fn make_result(self, result: ListenerHandlerMock) -> ListenerHandlerFactoryMock {
+        self.make_results.lock().unwrap().push(result);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::poll_fn;
+
+    #[test]
+    fn reads_follow_the_script_a_buffer_at_a_time() {
+        let (mut subject, _) = TcpStreamMock::new()
+            .read_chunk(b"abcdef")
+            .read_would_block()
+            .read_error(ErrorKind::Interrupted)
+            .read_chunk(b"gh")
+            .read_error(ErrorKind::ConnectionReset)
+            .split();
+        let mut buf = [0u8; 4];
+
+        let first = subject.read(&mut buf);
+        assert_eq!(&buf[..], b"abcd");
+        let second = subject.read(&mut buf);
+        assert_eq!(&buf[..2], b"ef");
+        let would_block = subject.read(&mut buf);
+        let interrupted = subject.read(&mut buf);
+        let third = subject.read(&mut buf);
+        assert_eq!(&buf[..2], b"gh");
+        let reset = subject.read(&mut buf);
+        let idle = subject.read(&mut buf);
+
+        assert_eq!(first.unwrap(), 4);
+        assert_eq!(second.unwrap(), 2);
+        assert_eq!(would_block.err().unwrap().kind(), ErrorKind::WouldBlock);
+        assert_eq!(interrupted.err().unwrap().kind(), ErrorKind::Interrupted);
+        assert_eq!(third.unwrap(), 2);
+        assert_eq!(reset.err().unwrap().kind(), ErrorKind::ConnectionReset);
+        assert_eq!(idle.err().unwrap().kind(), ErrorKind::WouldBlock);
+    }
+
+    #[test]
+    fn writes_take_what_the_script_allows_and_record_it() {
+        let written_arc = Arc::new(Mutex::new(vec![]));
+        let shutdowns_arc = Arc::new(Mutex::new(0));
+        let (_, mut subject) = TcpStreamMock::new()
+            .write_accept(3)
+            .write_would_block()
+            .write_error(ErrorKind::BrokenPipe)
+            .written(&written_arc)
+            .shutdowns(&shutdowns_arc)
+            .split();
+
+        let partial = subject.poll_write(b"abcdef");
+        let would_block = subject.write(b"def");
+        let broken = subject.poll_write(b"def");
+        let unscripted = subject.poll_write(b"def");
+        let shutdown = subject.shutdown();
+
+        assert_eq!(partial.unwrap(), Async::Ready(3));
+        assert_eq!(would_block.err().unwrap().kind(), ErrorKind::WouldBlock);
+        assert_eq!(broken.err().unwrap().kind(), ErrorKind::BrokenPipe);
+        assert_eq!(unscripted.unwrap(), Async::Ready(3));
+        assert_eq!(shutdown.unwrap(), Async::Ready(()));
+        assert_eq!(written_arc.lock().unwrap().as_slice(), b"abcdef");
+        assert_eq!(*shutdowns_arc.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn a_delayed_read_wakes_its_task_when_the_delay_is_over() {
+        let (mut subject, _) = TcpStreamMock::new()
+            .read_would_block()
+            .read_delay(Duration::from_millis(100))
+            .read_chunk(b"late")
+            .split();
+        let start = Instant::now();
+
+        let result = poll_fn(|| {
+            let mut buf = [0u8; 16];
+            subject.poll_read(&mut buf)
+        })
+        .wait();
+
+        assert_eq!(result.unwrap(), 4);
+        assert!(
+            start.elapsed() >= Duration::from_millis(100),
+            "{:?}",
+            start.elapsed()
+        );
+    }
+}
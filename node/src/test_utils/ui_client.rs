@@ -0,0 +1,295 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::sub_lib::ui_gateway::{
+    NodeEvent, NodeEventKind, UiCapabilities, UiMessage, UI_PROTOCOL_VERSION,
+};
+use crate::sub_lib::utils::localhost;
+use crate::ui_gateway::ui_traffic_converter::{UiTrafficConverter, UiTrafficConverterReal};
+use std::collections::VecDeque;
+use std::net::TcpStream;
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use websocket::sync::Writer;
+use websocket::{ClientBuilder, OwnedMessage};
+
+const UI_PROTOCOL: &str = "PrometheusNode-UI";
+
+/// Talks to a Node's UI port the way a UI does. A thread reads everything the Node says, so
+/// awaiting a response never loses an event that arrives first, and vice versa: each is kept until
+/// it's asked for. Every wait has a timeout, and runs out with an Err rather than a panic.
+pub struct UiClient {
+    writer: Writer<TcpStream>,
+    incoming: Receiver<Result<UiMessage, String>>,
+    converter: UiTrafficConverterReal,
+    responses: VecDeque<UiMessage>,
+    events: VecDeque<NodeEvent>,
+}
+
+impl UiClient {
+    // Keeps trying until the Node is listening or the timeout runs out
+    pub fn connect(port: u16, timeout: Duration) -> Result<UiClient, String> {
+        let url = format!("ws://{}:{}", localhost(), port);
+        let deadline = Instant::now() + timeout;
+        let client = loop {
+            let attempt = ClientBuilder::new(&url)
+                .map_err(|e| format!("Bad UI URL {}: {}", url, e))?
+                .add_protocol(UI_PROTOCOL)
+                .connect_insecure();
+            match attempt {
+                Ok(client) => break client,
+                Err(e) if Instant::now() >= deadline => {
+                    return Err(format!("Couldn't connect to {}: {}", url, e))
+                }
+                Err(_) => thread::sleep(Duration::from_millis(50)),
+            }
+        };
+        let (mut reader, writer) = client
+            .split()
+            .map_err(|e| format!("Couldn't split UI connection: {}", e))?;
+        let (tx, incoming) = mpsc::channel();
+        thread::spawn(move || {
+            let converter = UiTrafficConverterReal::new();
+            loop {
+                let item = match reader.recv_message() {
+                    Ok(OwnedMessage::Text(json)) => converter.unmarshal(&json),
+                    Ok(OwnedMessage::Close(_)) => Err("The Node closed the connection".to_string()),
+                    Ok(_) => continue,
+                    Err(e) => Err(format!("UI connection failed: {}", e)),
+                };
+                let finished = item.is_err();
+                if tx.send(item).is_err() || finished {
+                    break;
+                }
+            }
+        });
+        Ok(UiClient {
+            writer,
+            incoming,
+            converter: UiTrafficConverterReal::new(),
+            responses: VecDeque::new(),
+            events: VecDeque::new(),
+        })
+    }
+
+    // The Node disconnects a UI whose first message is anything else
+    pub fn authenticate(&mut self, token: &str) -> Result<(), String> {
+        self.send(UiMessage::Authenticate(token.to_string()))
+    }
+
+    pub fn hello(&mut self, timeout: Duration) -> Result<UiCapabilities, String> {
+        match self.transact(
+            UiMessage::Hello {
+                protocol_version: UI_PROTOCOL_VERSION,
+            },
+            timeout,
+        )? {
+            UiMessage::Capabilities(capabilities) => Ok(capabilities),
+            other => Err(format!("Expected Capabilities, got {:?}", other)),
+        }
+    }
+
+    pub fn send(&mut self, message: UiMessage) -> Result<(), String> {
+        let json = self.converter.marshal(message)?;
+        self.writer
+            .send_message(&OwnedMessage::Text(json))
+            .map_err(|e| format!("Couldn't send to the Node: {}", e))
+    }
+
+    // The next message from the Node that isn't an event
+    pub fn await_response(&mut self, timeout: Duration) -> Result<UiMessage, String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(response) = self.responses.pop_front() {
+                return Ok(response);
+            }
+            self.receive_until(deadline)?;
+        }
+    }
+
+    pub fn transact(&mut self, message: UiMessage, timeout: Duration) -> Result<UiMessage, String> {
+        self.send(message)?;
+        self.await_response(timeout)
+    }
+
+    // The Node doesn't answer a subscription, so there's nothing to wait for
+    pub fn subscribe(&mut self, kinds: Vec<NodeEventKind>) -> Result<(), String> {
+        self.send(UiMessage::SubscribeToEvents(kinds))
+    }
+
+    pub fn await_event(&mut self, timeout: Duration) -> Result<NodeEvent, String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(event);
+            }
+            self.receive_until(deadline)?;
+        }
+    }
+
+    pub fn close(mut self) {
+        let _ = self.writer.send_message(&OwnedMessage::Close(None));
+    }
+
+    fn receive_until(&mut self, deadline: Instant) -> Result<(), String> {
+        let now = Instant::now();
+        let timeout = if deadline > now {
+            deadline - now
+        } else {
+            Duration::from_millis(0)
+        };
+        match self.incoming.recv_timeout(timeout) {
+            Ok(Ok(UiMessage::NodeEvent(event))) => self.events.push_back(event),
+            Ok(Ok(message)) => self.responses.push_back(message),
+            Ok(Err(e)) => return Err(e),
+            Err(RecvTimeoutError::Timeout) => {
+                return Err("Timed out waiting for the Node".to_string())
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err("The UI connection is closed".to_string())
+            }
+        }
+        Ok(())
+    }
+}
+
+// Also stops the reading thread
+impl Drop for UiClient {
+    fn drop(&mut self) {
+        let _ = self.writer.shutdown_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::find_free_port;
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use websocket::sync::Server;
+
+    // A stand-in for the Node's UI port: accepts one UI, then runs the script, each entry saying
+    // whether to wait for a message from the UI before sending the rest. What it hears goes back
+    // over the channel.
+    fn start_fake_node(script: Vec<(bool, Vec<UiMessage>)>) -> (u16, Receiver<UiMessage>) {
+        let port = find_free_port();
+        let mut server =
+            Server::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port)).unwrap();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let converter = UiTrafficConverterReal::new();
+            let upgrade = server.accept().ok().unwrap();
+            assert_eq!(upgrade.protocols(), &[UI_PROTOCOL.to_string()]);
+            let mut client = upgrade.use_protocol(UI_PROTOCOL).accept().unwrap();
+            script.into_iter().for_each(|(listen_first, replies)| {
+                if listen_first {
+                    match client.recv_message().unwrap() {
+                        OwnedMessage::Text(json) => {
+                            tx.send(converter.unmarshal(&json).unwrap()).unwrap()
+                        }
+                        other => panic!("Expected text, got {:?}", other),
+                    }
+                }
+                replies.into_iter().for_each(|reply| {
+                    let json = converter.marshal_enveloped(reply).unwrap();
+                    client.send_message(&OwnedMessage::Text(json)).unwrap();
+                });
+            });
+            let _ = client.recv_message();
+        });
+        (port, rx)
+    }
+
+    fn route_failure(reason: &str) -> NodeEvent {
+        NodeEvent::RouteFailure {
+            reason: reason.to_string(),
+        }
+    }
+
+    #[test]
+    fn transact_sends_a_message_and_returns_the_response() {
+        let (port, heard) = start_fake_node(vec![
+            (true, vec![]),
+            (
+                true,
+                vec![UiMessage::NodeDescriptor("descriptor".to_string())],
+            ),
+        ]);
+        let mut subject = UiClient::connect(port, Duration::from_secs(1)).unwrap();
+        subject.authenticate("token").unwrap();
+
+        let result = subject.transact(UiMessage::GetNodeDescriptor, Duration::from_secs(1));
+
+        assert_eq!(
+            result,
+            Ok(UiMessage::NodeDescriptor("descriptor".to_string()))
+        );
+        assert_eq!(
+            heard.recv_timeout(Duration::from_secs(1)),
+            Ok(UiMessage::Authenticate("token".to_string()))
+        );
+        assert_eq!(
+            heard.recv_timeout(Duration::from_secs(1)),
+            Ok(UiMessage::GetNodeDescriptor)
+        );
+        subject.close();
+    }
+
+    #[test]
+    fn events_and_responses_are_each_kept_until_asked_for() {
+        let (port, heard) = start_fake_node(vec![(
+            true,
+            vec![
+                UiMessage::NodeEvent(route_failure("first")),
+                UiMessage::NodeDescriptor("descriptor".to_string()),
+                UiMessage::NodeEvent(route_failure("second")),
+            ],
+        )]);
+        let mut subject = UiClient::connect(port, Duration::from_secs(1)).unwrap();
+        subject
+            .subscribe(vec![NodeEventKind::RouteFailure])
+            .unwrap();
+
+        let first_event = subject.await_event(Duration::from_secs(1));
+        let second_event = subject.await_event(Duration::from_secs(1));
+        let response = subject.await_response(Duration::from_secs(1));
+
+        assert_eq!(first_event, Ok(route_failure("first")));
+        assert_eq!(second_event, Ok(route_failure("second")));
+        assert_eq!(
+            response,
+            Ok(UiMessage::NodeDescriptor("descriptor".to_string()))
+        );
+        assert_eq!(
+            heard.recv_timeout(Duration::from_secs(1)),
+            Ok(UiMessage::SubscribeToEvents(vec![
+                NodeEventKind::RouteFailure
+            ]))
+        );
+    }
+
+    #[test]
+    fn waiting_runs_out_with_an_error() {
+        let (port, _heard) = start_fake_node(vec![]);
+        let mut subject = UiClient::connect(port, Duration::from_secs(1)).unwrap();
+
+        let response = subject.await_response(Duration::from_millis(100));
+        let event = subject.await_event(Duration::from_millis(100));
+
+        assert_eq!(response, Err("Timed out waiting for the Node".to_string()));
+        assert_eq!(event, Err("Timed out waiting for the Node".to_string()));
+    }
+
+    #[test]
+    fn hello_returns_the_nodes_capabilities() {
+        let capabilities = UiCapabilities::negotiate(UI_PROTOCOL_VERSION);
+        let (port, _heard) = start_fake_node(vec![(
+            true,
+            vec![UiMessage::Capabilities(capabilities.clone())],
+        )]);
+        let mut subject = UiClient::connect(port, Duration::from_secs(1)).unwrap();
+
+        let result = subject.hello(Duration::from_secs(1));
+
+        assert_eq!(result, Ok(capabilities));
+    }
+}
@@ -0,0 +1,181 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use crate::sub_lib::logger::Logger;
+use crate::sub_lib::ui_gateway::UiMessage;
+use crate::ui_gateway::websocket_supervisor::WebSocketSupervisor;
+use rand::RngCore;
+use std::collections::HashSet;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+pub const UI_AUTH_TOKEN_FILE: &str = "ui-auth-token";
+
+const UI_AUTH_TOKEN_BYTES: usize = 32;
+
+// A fresh secret for every run of the Node, so a token read once is no good after a restart
+pub fn generate_ui_auth_token() -> String {
+    let mut bytes = [0u8; UI_AUTH_TOKEN_BYTES];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn write_ui_auth_token(data_directory: &Path, token: &str) -> Result<PathBuf, String> {
+    write_auth_token(data_directory, UI_AUTH_TOKEN_FILE, token)
+}
+
+// For anything besides the Node that lets UIs in on the same terms, such as the daemon
+pub fn write_auth_token(
+    data_directory: &Path,
+    file_name: &str,
+    token: &str,
+) -> Result<PathBuf, String> {
+    let path = data_directory.join(file_name);
+    fs::create_dir_all(data_directory).map_err(|e| {
+        format!(
+            "Couldn't create data directory {:?} for the UI token: {}",
+            data_directory, e
+        )
+    })?;
+    // An old token file may have been left behind with looser permissions than we'd create
+    let _ = fs::remove_file(&path);
+    let mut file = open_private(&path)
+        .map_err(|e| format!("Couldn't create UI token file {:?}: {}", path, e))?;
+    file.write_all(token.as_bytes())
+        .map_err(|e| format!("Couldn't write UI token file {:?}: {}", path, e))?;
+    Ok(path)
+}
+
+pub fn read_ui_auth_token(data_directory: &Path) -> Result<String, String> {
+    let path = data_directory.join(UI_AUTH_TOKEN_FILE);
+    let mut token = String::new();
+    fs::File::open(&path)
+        .and_then(|mut file| file.read_to_string(&mut token))
+        .map_err(|e| {
+            format!(
+                "couldn't read the UI token from {:?} (is the Node running?): {}",
+                path, e
+            )
+        })?;
+    Ok(token.trim().to_string())
+}
+
+// Compares every byte, so how long a wrong guess takes says nothing about how close it was
+pub fn ui_auth_token_matches(expected: &str, presented: &str) -> bool {
+    let expected = expected.as_bytes();
+    let presented = presented.as_bytes();
+    if expected.is_empty() || expected.len() != presented.len() {
+        return false;
+    }
+    expected
+        .iter()
+        .zip(presented.iter())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
+
+// A UI's first message must be the token; anything else gets it disconnected. The Node and the
+// daemon both let UIs in this way.
+pub fn authenticate_client(
+    expected_token: &str,
+    client_id: u64,
+    unmarshalled: Result<UiMessage, String>,
+    authenticated_clients: &mut HashSet<u64>,
+    websocket_supervisor: &dyn WebSocketSupervisor,
+    logger: &Logger,
+) {
+    match unmarshalled {
+        Ok(UiMessage::Authenticate(ref token)) if ui_auth_token_matches(expected_token, token) => {
+            authenticated_clients.retain(|client_id| websocket_supervisor.is_connected(*client_id));
+            authenticated_clients.insert(client_id);
+            info!(logger, "UI {} authenticated", client_id);
+        }
+        _ => {
+            warning!(
+                logger,
+                "UI {} didn't open with the right authentication token: disconnecting",
+                client_id
+            );
+            websocket_supervisor.disconnect(client_id);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn open_private(path: &Path) -> std::io::Result<fs::File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+}
+
+// On Windows, files in the user's local application data directory are private to the user already
+#[cfg(not(unix))]
+fn open_private(path: &Path) -> std::io::Result<fs::File> {
+    OpenOptions::new().write(true).create_new(true).open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::ensure_node_home_directory_exists;
+
+    #[test]
+    fn tokens_are_long_and_different_every_time() {
+        let first = generate_ui_auth_token();
+        let second = generate_ui_auth_token();
+
+        assert_eq!(first.len(), UI_AUTH_TOKEN_BYTES * 2);
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn a_written_token_can_be_read_back() {
+        let data_dir =
+            ensure_node_home_directory_exists("auth_token", "a_written_token_can_be_read_back");
+        write_ui_auth_token(&data_dir, "old token").unwrap();
+
+        let path = write_ui_auth_token(&data_dir, "new token").unwrap();
+
+        assert_eq!(path, data_dir.join(UI_AUTH_TOKEN_FILE));
+        assert_eq!(read_ui_auth_token(&data_dir), Ok("new token".to_string()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn the_token_file_is_readable_only_by_its_owner() {
+        use std::os::unix::fs::PermissionsExt;
+        let data_dir = ensure_node_home_directory_exists(
+            "auth_token",
+            "the_token_file_is_readable_only_by_its_owner",
+        );
+
+        let path = write_ui_auth_token(&data_dir, "token").unwrap();
+
+        let mode = fs::metadata(path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn reading_a_missing_token_explains_itself() {
+        let data_dir = ensure_node_home_directory_exists(
+            "auth_token",
+            "reading_a_missing_token_explains_itself",
+        );
+
+        let result = read_ui_auth_token(&data_dir).err().unwrap();
+
+        assert!(result.contains("is the Node running?"), "{}", result);
+    }
+
+    #[test]
+    fn only_the_exact_token_matches() {
+        assert!(ui_auth_token_matches("abcd", "abcd"));
+        assert!(!ui_auth_token_matches("abcd", "abce"));
+        assert!(!ui_auth_token_matches("abcd", "abc"));
+        assert!(!ui_auth_token_matches("", ""));
+    }
+}
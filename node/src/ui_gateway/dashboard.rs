@@ -0,0 +1,527 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use crate::node_configurator::instance::Instance;
+use crate::node_configurator::{
+    app_head, chain_arg, data_directory_arg, instance_name_arg, make_multi_config, profile_arg,
+    real_user_arg,
+};
+use crate::server_initializer::current_log_file;
+use crate::sub_lib::accountant::FinancialStatisticsMessage;
+use crate::sub_lib::main_tools::StdStreams;
+use crate::sub_lib::neighborhood::NeighborSummary;
+use crate::sub_lib::ui_gateway::{
+    NodeEvent, NodeEventKind, NodeStatistics, NodeStatus, UiMessage, DEFAULT_UI_PORT,
+};
+use crate::ui_gateway::neighbor_lister::{
+    connect_to_node, duration_string, render_neighbors, send_to_node,
+};
+use crate::ui_gateway::ui_traffic_converter::{UiTrafficConverter, UiTrafficConverterReal};
+use clap::{value_t, Arg};
+use lazy_static::lazy_static;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use websocket::result::WebSocketError;
+use websocket::OwnedMessage;
+
+const UI_PORT_HELP: &str = "The UI port of the running Node to watch.";
+
+const REFRESH_INTERVAL_HELP: &str = "How many seconds to wait between refreshes of the dashboard.";
+
+const DEFAULT_REFRESH_INTERVAL_SECS: u64 = 2;
+
+const EVENT_LINES: usize = 8;
+
+const LOG_LINES: usize = 10;
+
+// Enough for LOG_LINES lines of any sensible length, without reading a 100MB log every refresh
+const LOG_TAIL_BYTES: u64 = 16_384;
+
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+lazy_static! {
+    static ref DEFAULT_UI_PORT_VALUE: String = DEFAULT_UI_PORT.to_string();
+    static ref DEFAULT_REFRESH_INTERVAL_VALUE: String = DEFAULT_REFRESH_INTERVAL_SECS.to_string();
+}
+
+pub fn go(args: &Vec<String>, streams: &mut StdStreams) -> i32 {
+    let (ui_port, data_directory, log_file, refresh_interval) = distill_args(args);
+    match watch(
+        ui_port,
+        &data_directory,
+        &log_file,
+        refresh_interval,
+        streams,
+    ) {
+        Ok(()) => 0,
+        Err(e) => {
+            writeln!(streams.stderr, "substratum-top: {}", e).expect("Couldn't write to stderr");
+            1
+        }
+    }
+}
+
+// Runs until the Node goes away or the operator interrupts it
+fn watch(
+    ui_port: u16,
+    data_directory: &Path,
+    log_file: &Path,
+    refresh_interval: Duration,
+    streams: &mut StdStreams,
+) -> Result<(), String> {
+    let converter = UiTrafficConverterReal::new();
+    let mut client = connect_to_node(ui_port, data_directory, refresh_interval)?;
+    send_to_node(&mut client, UiMessage::SubscribeToEvents(all_event_kinds()))?;
+    let mut dashboard = Dashboard::default();
+    loop {
+        vec![
+            UiMessage::GetNodeStatus,
+            UiMessage::GetNodeStatistics,
+            UiMessage::GetNeighborList,
+            UiMessage::GetFinancialStatisticsMessage,
+        ]
+        .into_iter()
+        .map(|request| send_to_node(&mut client, request))
+        .collect::<Result<(), String>>()?;
+        let deadline = Instant::now() + refresh_interval;
+        let mut now = Instant::now();
+        while now < deadline {
+            client
+                .stream_ref()
+                .set_read_timeout(Some(deadline - now))
+                .map_err(|e| format!("{}", e))?;
+            match client.recv_message() {
+                Ok(OwnedMessage::Text(json)) => {
+                    if let Ok(message) = converter.unmarshal(&json) {
+                        dashboard.absorb(message)
+                    }
+                }
+                Ok(OwnedMessage::Close(_)) => return Err("the Node hung up".to_string()),
+                Ok(_) => (),
+                Err(WebSocketError::IoError(ref e))
+                    if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut =>
+                {
+                    break
+                }
+                Err(e) => return Err(format!("lost the Node: {}", e)),
+            }
+            now = Instant::now();
+        }
+        let log_lines = tail_lines(log_file, LOG_LINES);
+        write!(
+            streams.stdout,
+            "{}{}",
+            CLEAR_SCREEN,
+            dashboard.render(&log_lines)
+        )
+        .expect("Couldn't write dashboard to stdout");
+        streams
+            .stdout
+            .flush()
+            .expect("Couldn't flush dashboard to stdout");
+    }
+}
+
+fn all_event_kinds() -> Vec<NodeEventKind> {
+    vec![
+        NodeEventKind::NeighborConnected,
+        NodeEventKind::NeighborDisconnected,
+        NodeEventKind::RouteFailure,
+        NodeEventKind::PaymentSent,
+        NodeEventKind::PaymentReceived,
+        NodeEventKind::DelinquencyBan,
+        NodeEventKind::DescriptorChanged,
+    ]
+}
+
+#[derive(Default)]
+struct Dashboard {
+    status: Option<NodeStatus>,
+    statistics: Option<NodeStatistics>,
+    relay_rate: Option<u64>,
+    neighbors: Vec<NeighborSummary>,
+    financials: Option<FinancialStatisticsMessage>,
+    events: VecDeque<String>,
+}
+
+impl Dashboard {
+    fn absorb(&mut self, message: UiMessage) {
+        match message {
+            UiMessage::NodeStatus(status) => self.status = Some(status),
+            UiMessage::NodeStatistics(statistics) => {
+                self.relay_rate = self
+                    .statistics
+                    .as_ref()
+                    .and_then(|previous| relay_rate(previous, &statistics));
+                self.statistics = Some(statistics);
+            }
+            UiMessage::NeighborList(neighbors) => self.neighbors = neighbors,
+            UiMessage::FinancialStatisticsResponse(financials) => {
+                self.financials = Some(financials)
+            }
+            UiMessage::NodeEvent(event) => {
+                self.events.push_front(describe_event(&event));
+                self.events.truncate(EVENT_LINES);
+            }
+            _ => (),
+        }
+    }
+
+    fn render(&self, log_lines: &[String]) -> String {
+        let mut screen = String::new();
+        match &self.status {
+            Some(status) => {
+                screen.push_str(&format!(
+                    "Node {}, up {}\n{}\n",
+                    status.version,
+                    duration_string(status.uptime_secs),
+                    status.node_descriptor
+                ));
+            }
+            None => screen.push_str("Waiting for the Node...\n"),
+        }
+        screen.push_str("\nTHROUGHPUT\n");
+        match &self.statistics {
+            Some(statistics) => {
+                screen.push_str(&format!(
+                    "  Relayed {} bytes, {} in the last hour, {}\n  Served {} bytes as an exit\n",
+                    statistics.bytes_relayed,
+                    statistics.bytes_relayed_last_hour,
+                    match self.relay_rate {
+                        Some(rate) => format!("{} bytes/s now", rate),
+                        None => "rate not known yet".to_string(),
+                    },
+                    statistics.exit_bytes_served,
+                ));
+                screen.push_str(&format!(
+                    "\nSTREAMS\n  {} originated here, {} exiting here\n",
+                    statistics.active_streams_originated, statistics.active_exit_streams,
+                ));
+            }
+            None => screen.push_str("  -\n\nSTREAMS\n  -\n"),
+        }
+        screen.push_str("\nFINANCIALS\n");
+        match &self.financials {
+            Some(financials) => screen.push_str(&format!(
+                "  Pending credit {} gwub, pending debt {} gwub\n",
+                financials.pending_credit, financials.pending_debt
+            )),
+            None => screen.push_str("  -\n"),
+        }
+        screen.push_str(&format!("\nNEIGHBORS ({})\n", self.neighbors.len()));
+        screen.push_str(&indent(&render_neighbors(&self.neighbors)));
+        screen.push_str("\nEVENTS\n");
+        if self.events.is_empty() {
+            screen.push_str("  None yet\n");
+        }
+        self.events
+            .iter()
+            .for_each(|event| screen.push_str(&format!("  {}\n", event)));
+        screen.push_str("\nLOG\n");
+        log_lines
+            .iter()
+            .for_each(|line| screen.push_str(&format!("  {}\n", line)));
+        screen
+    }
+}
+
+// Uptime, not wall-clock time, so a slow answer from the Node doesn't skew the rate
+fn relay_rate(previous: &NodeStatistics, current: &NodeStatistics) -> Option<u64> {
+    if current.uptime_secs <= previous.uptime_secs || current.bytes_relayed < previous.bytes_relayed
+    {
+        return None;
+    }
+    Some(
+        (current.bytes_relayed - previous.bytes_relayed)
+            / (current.uptime_secs - previous.uptime_secs),
+    )
+}
+
+fn describe_event(event: &NodeEvent) -> String {
+    match event {
+        NodeEvent::NeighborConnected { public_key } => format!("Neighbor {} connected", public_key),
+        NodeEvent::NeighborDisconnected { public_key } => {
+            format!("Neighbor {} disconnected", public_key)
+        }
+        NodeEvent::RouteFailure { reason } => format!("Route failure: {}", reason),
+        NodeEvent::PaymentSent {
+            wallet,
+            amount,
+            transaction,
+        } => format!("Paid {} gwub to {} ({})", amount, wallet, transaction),
+        NodeEvent::PaymentReceived {
+            wallet,
+            amount,
+            transaction,
+        } => format!("Received {} gwub from {} ({})", amount, wallet, transaction),
+        NodeEvent::DelinquencyBan { wallet } => format!("Banned {} for delinquency", wallet),
+        NodeEvent::DescriptorChanged { node_descriptor } => {
+            format!("Descriptor is now {}", node_descriptor)
+        }
+    }
+}
+
+fn indent(text: &str) -> String {
+    text.lines().map(|line| format!("  {}\n", line)).collect()
+}
+
+fn tail_lines(path: &Path, count: usize) -> Vec<String> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return vec![],
+    };
+    let length = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+    let start = length.saturating_sub(LOG_TAIL_BYTES);
+    let mut bytes = vec![];
+    if file
+        .seek(SeekFrom::Start(start))
+        .and_then(|_| file.read_to_end(&mut bytes))
+        .is_err()
+    {
+        return vec![];
+    }
+    let text = String::from_utf8_lossy(&bytes);
+    // Starting partway into the file probably means starting partway into a line
+    let skip = if start > 0 { 1 } else { 0 };
+    let lines = text.lines().skip(skip).collect::<Vec<&str>>();
+    lines[lines.len().saturating_sub(count)..]
+        .iter()
+        .map(|line| line.to_string())
+        .collect()
+}
+
+fn distill_args(args: &Vec<String>) -> (u16, PathBuf, PathBuf, Duration) {
+    let app = app_head()
+        .arg(
+            Arg::with_name("ui-port")
+                .long("ui-port")
+                .value_name("UI-PORT")
+                .takes_value(true)
+                .default_value(&DEFAULT_UI_PORT_VALUE)
+                .help(UI_PORT_HELP),
+        )
+        .arg(
+            Arg::with_name("refresh-interval")
+                .long("refresh-interval")
+                .value_name("SECONDS")
+                .takes_value(true)
+                .default_value(&DEFAULT_REFRESH_INTERVAL_VALUE)
+                .help(REFRESH_INTERVAL_HELP),
+        )
+        .arg(chain_arg())
+        .arg(data_directory_arg())
+        .arg(profile_arg())
+        .arg(instance_name_arg())
+        .arg(real_user_arg());
+    let multi_config = make_multi_config(&app, args, None);
+    let ui_port =
+        value_m!(multi_config, "ui-port", u16).expect("ui-port is not properly defaulted");
+    let refresh_interval_secs = value_m!(multi_config, "refresh-interval", u64)
+        .expect("refresh-interval is not properly defaulted");
+    let (_, data_directory, _) =
+        crate::node_configurator::real_user_data_directory_and_chain_id(&multi_config);
+    // An instance's data directory is its own, but its log is named for it as well
+    let log_file = data_directory.join(current_log_file(
+        value_m!(multi_config, "instance-name", String)
+            .map(|name| Instance::new(&name).name)
+            .as_ref()
+            .map(|name| name.as_str()),
+    ));
+    (
+        ui_port,
+        data_directory,
+        log_file,
+        Duration::from_secs(refresh_interval_secs.max(1)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server_initializer::CURRENT_LOG_FILE;
+    use crate::test_utils::{
+        ensure_node_home_directory_exists, find_free_port, ArgsBuilder, FakeStreamHolder,
+    };
+    use std::fs;
+
+    fn make_statistics(uptime_secs: u64, bytes_relayed: u64) -> NodeStatistics {
+        NodeStatistics {
+            uptime_secs,
+            bytes_relayed,
+            bytes_relayed_last_hour: 100,
+            exit_bytes_served: 200,
+            active_streams_originated: 3,
+            active_exit_streams: 4,
+            neighbor_count: 0,
+        }
+    }
+
+    #[test]
+    fn an_empty_dashboard_says_what_its_waiting_for() {
+        let result = Dashboard::default().render(&[]);
+
+        assert_eq!(
+            result,
+            "Waiting for the Node...\n\
+             \n\
+             THROUGHPUT\n  -\n\
+             \n\
+             STREAMS\n  -\n\
+             \n\
+             FINANCIALS\n  -\n\
+             \n\
+             NEIGHBORS (0)\n  No neighbors\n\
+             \n\
+             EVENTS\n  None yet\n\
+             \n\
+             LOG\n"
+        );
+    }
+
+    #[test]
+    fn a_dashboard_shows_what_the_node_told_it() {
+        let mut subject = Dashboard::default();
+
+        subject.absorb(UiMessage::NodeStatus(NodeStatus {
+            version: "1.2.3".to_string(),
+            node_descriptor: "NODE-DESCRIPTOR".to_string(),
+            uptime_secs: 7384,
+        }));
+        subject.absorb(UiMessage::NodeStatistics(make_statistics(10, 1000)));
+        subject.absorb(UiMessage::NodeStatistics(make_statistics(12, 1500)));
+        subject.absorb(UiMessage::FinancialStatisticsResponse(
+            FinancialStatisticsMessage {
+                pending_credit: 12,
+                pending_debt: 34,
+                profiles: vec![],
+                payments_deferred: None,
+            },
+        ));
+        subject.absorb(UiMessage::NodeEvent(NodeEvent::NeighborConnected {
+            public_key: "AQIDBA".to_string(),
+        }));
+        subject.absorb(UiMessage::NodeEvent(NodeEvent::RouteFailure {
+            reason: "no exit".to_string(),
+        }));
+        subject.absorb(UiMessage::NodeDescriptor("ignored".to_string()));
+        let result = subject.render(&["first log line".to_string(), "second".to_string()]);
+
+        assert_eq!(
+            result,
+            "Node 1.2.3, up 2h 03m\nNODE-DESCRIPTOR\n\
+             \n\
+             THROUGHPUT\n  Relayed 1500 bytes, 100 in the last hour, 250 bytes/s now\n  Served 200 bytes as an exit\n\
+             \n\
+             STREAMS\n  3 originated here, 4 exiting here\n\
+             \n\
+             FINANCIALS\n  Pending credit 12 gwub, pending debt 34 gwub\n\
+             \n\
+             NEIGHBORS (0)\n  No neighbors\n\
+             \n\
+             EVENTS\n  Route failure: no exit\n  Neighbor AQIDBA connected\n\
+             \n\
+             LOG\n  first log line\n  second\n"
+        );
+    }
+
+    #[test]
+    fn only_the_latest_events_are_kept() {
+        let mut subject = Dashboard::default();
+
+        (0..(EVENT_LINES + 3)).for_each(|index| {
+            subject.absorb(UiMessage::NodeEvent(NodeEvent::DelinquencyBan {
+                wallet: format!("0x{}", index),
+            }))
+        });
+
+        assert_eq!(subject.events.len(), EVENT_LINES);
+        assert_eq!(
+            subject.events.front(),
+            Some(&format!("Banned 0x{} for delinquency", EVENT_LINES + 2))
+        );
+    }
+
+    #[test]
+    fn relay_rate_is_unknown_across_a_restart() {
+        assert_eq!(
+            relay_rate(&make_statistics(10, 1000), &make_statistics(20, 3000)),
+            Some(200)
+        );
+        assert_eq!(
+            relay_rate(&make_statistics(100, 1000), &make_statistics(5, 30)),
+            None
+        );
+        assert_eq!(
+            relay_rate(&make_statistics(10, 1000), &make_statistics(10, 1000)),
+            None
+        );
+    }
+
+    #[test]
+    fn tail_lines_shows_the_end_of_the_log() {
+        let data_dir =
+            ensure_node_home_directory_exists("dashboard", "tail_lines_shows_the_end_of_the_log");
+        let path = data_dir.join(CURRENT_LOG_FILE);
+        let long_line = "x".repeat(LOG_TAIL_BYTES as usize);
+        fs::write(&path, format!("{}\none\ntwo\nthree\n", long_line)).unwrap();
+
+        assert_eq!(
+            tail_lines(&path, 2),
+            vec!["two".to_string(), "three".to_string()]
+        );
+        assert_eq!(
+            tail_lines(&path, 10),
+            vec!["one".to_string(), "two".to_string(), "three".to_string()]
+        );
+        assert_eq!(
+            tail_lines(&data_dir.join("nonexistent"), 10),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn an_instance_is_watched_on_its_own_port_and_log() {
+        let data_dir = ensure_node_home_directory_exists(
+            "dashboard",
+            "an_instance_is_watched_on_its_own_port_and_log",
+        );
+        let instance = Instance::new("alice");
+
+        let (ui_port, data_directory, log_file, _) = distill_args(
+            &ArgsBuilder::new()
+                .param("--data-directory", data_dir.to_str().unwrap())
+                .param("--instance-name", "Alice")
+                .into(),
+        );
+
+        assert_eq!(ui_port, instance.ui_port());
+        assert_eq!(data_directory, data_dir.join("alice"));
+        assert_eq!(
+            log_file,
+            data_dir
+                .join("alice")
+                .join("PrometheusNode_alice_rCURRENT.log")
+        );
+    }
+
+    #[test]
+    fn go_complains_when_no_node_is_running() {
+        let port = find_free_port();
+        let mut holder = FakeStreamHolder::new();
+
+        let result = go(
+            &ArgsBuilder::new()
+                .param("--ui-port", &port.to_string())
+                .into(),
+            &mut holder.streams(),
+        );
+
+        assert_eq!(result, 1);
+        assert!(holder.stderr.get_string().starts_with(&format!(
+            "substratum-top: no Node is listening on UI port {}",
+            port
+        )));
+    }
+}
@@ -0,0 +1,220 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use crate::node_configurator::{
+    app_head, chain_arg, data_directory_arg, instance_name_arg, make_multi_config, profile_arg,
+    real_user_arg,
+};
+use crate::sub_lib::main_tools::StdStreams;
+use crate::sub_lib::node_descriptor_share::{node_descriptor_uri, qr_code_png, qr_code_text};
+use crate::sub_lib::ui_gateway::{UiMessage, DEFAULT_UI_PORT};
+use crate::ui_gateway::neighbor_lister::{connect_to_node, receive_from_node, send_to_node};
+use clap::{value_t, App, Arg};
+use lazy_static::lazy_static;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const PRINT_DESCRIPTOR_HELP: &str =
+    "Ask the Node running on this machine for its descriptor and print it as a substratum:// URI \
+     and a QR code, for another machine or a phone to use as a neighbor.";
+
+const UI_PORT_HELP: &str = "The UI port of the running Node to ask.";
+
+const QR_PNG_HELP: &str = "Also write the QR code as a PNG image to this file.";
+
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+lazy_static! {
+    static ref DEFAULT_UI_PORT_VALUE: String = DEFAULT_UI_PORT.to_string();
+}
+
+pub fn print_descriptor(args: &Vec<String>, streams: &mut StdStreams) -> i32 {
+    let (ui_port, data_directory, qr_png_opt) = distill_args(args);
+    let result = fetch_descriptor(ui_port, &data_directory, RESPONSE_TIMEOUT)
+        .and_then(|descriptor| render_descriptor(&descriptor, qr_png_opt.as_ref()));
+    match result {
+        Ok(output) => {
+            streams
+                .stdout
+                .write_all(output.as_bytes())
+                .expect("Couldn't write descriptor to stdout");
+            streams
+                .stdout
+                .flush()
+                .expect("Couldn't flush descriptor to stdout");
+            0
+        }
+        Err(e) => {
+            writeln!(streams.stderr, "Couldn't print descriptor: {}", e)
+                .expect("Couldn't write to stderr");
+            1
+        }
+    }
+}
+
+fn fetch_descriptor(
+    ui_port: u16,
+    data_directory: &Path,
+    timeout: Duration,
+) -> Result<String, String> {
+    let mut client = connect_to_node(ui_port, data_directory, timeout)?;
+    send_to_node(&mut client, UiMessage::GetNodeDescriptor)?;
+    let result = receive_from_node(&mut client, |message| match message {
+        UiMessage::NodeDescriptor(descriptor) => Some(descriptor),
+        _ => None,
+    });
+    let _ = client.shutdown();
+    result
+}
+
+fn render_descriptor(descriptor: &str, qr_png_opt: Option<&PathBuf>) -> Result<String, String> {
+    let uri = node_descriptor_uri(descriptor);
+    let mut output = format!("{}\n{}\n\n{}\n", descriptor, uri, qr_code_text(&uri)?);
+    if let Some(qr_png) = qr_png_opt {
+        fs::write(qr_png, qr_code_png(&uri)?)
+            .map_err(|e| format!("couldn't write {:?}: {}", qr_png, e))?;
+        output.push_str(&format!("QR code written to {:?}\n", qr_png));
+    }
+    Ok(output)
+}
+
+pub fn app() -> App<'static, 'static> {
+    app_head()
+        .about(PRINT_DESCRIPTOR_HELP)
+        .usage("PrometheusNode print-descriptor [OPTIONS]")
+        .arg(
+            Arg::with_name("print-descriptor")
+                .long("print-descriptor")
+                .required(true)
+                .takes_value(false)
+                .hidden(true),
+        )
+        .arg(
+            Arg::with_name("ui-port")
+                .long("ui-port")
+                .value_name("UI-PORT")
+                .takes_value(true)
+                .default_value(&DEFAULT_UI_PORT_VALUE)
+                .help(UI_PORT_HELP),
+        )
+        .arg(
+            Arg::with_name("qr-png")
+                .long("qr-png")
+                .value_name("FILE")
+                .takes_value(true)
+                .help(QR_PNG_HELP),
+        )
+        .arg(chain_arg())
+        .arg(data_directory_arg())
+        .arg(profile_arg())
+        .arg(instance_name_arg())
+        .arg(real_user_arg())
+}
+
+fn distill_args(args: &Vec<String>) -> (u16, PathBuf, Option<PathBuf>) {
+    let app = app();
+    let multi_config = make_multi_config(&app, args, None);
+    let ui_port =
+        value_m!(multi_config, "ui-port", u16).expect("ui-port is not properly defaulted");
+    let qr_png_opt = value_m!(multi_config, "qr-png", PathBuf);
+    let (_, data_directory, _) =
+        crate::node_configurator::real_user_data_directory_and_chain_id(&multi_config);
+    (ui_port, data_directory, qr_png_opt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{
+        ensure_node_home_directory_exists, find_free_port, ArgsBuilder, FakeStreamHolder,
+    };
+    use crate::ui_gateway::auth_token::write_ui_auth_token;
+    use crate::ui_gateway::neighbor_lister::UI_PROTOCOL;
+    use crate::ui_gateway::ui_traffic_converter::{UiTrafficConverter, UiTrafficConverterReal};
+    use std::net::Ipv4Addr;
+    use std::thread;
+    use websocket::sync::Server;
+    use websocket::OwnedMessage;
+
+    #[test]
+    fn print_descriptor_asks_the_node_and_shows_a_uri_and_qr_codes() {
+        let data_dir = ensure_node_home_directory_exists(
+            "descriptor_printer",
+            "print_descriptor_asks_the_node_and_shows_a_uri_and_qr_codes",
+        );
+        write_ui_auth_token(&data_dir, "token").unwrap();
+        let qr_png = data_dir.join("descriptor.png");
+        let port = find_free_port();
+        let mut server = Server::bind((Ipv4Addr::LOCALHOST, port)).unwrap();
+        let handle = thread::spawn(move || {
+            let upgrade = server.accept().ok().unwrap();
+            let mut client = upgrade.use_protocol(UI_PROTOCOL).accept().unwrap();
+            let _authentication = client.recv_message().unwrap();
+            let request = client.recv_message().unwrap();
+            client
+                .send_message(&OwnedMessage::Text(
+                    UiTrafficConverterReal::new()
+                        .marshal(UiMessage::NodeDescriptor("AQ/DBA:1.2.3.4:1234".to_string()))
+                        .unwrap(),
+                ))
+                .unwrap();
+            request
+        });
+        let mut holder = FakeStreamHolder::new();
+
+        let result = print_descriptor(
+            &ArgsBuilder::new()
+                .opt("--print-descriptor")
+                .param("--ui-port", &port.to_string())
+                .param("--data-directory", data_dir.to_str().unwrap())
+                .param("--qr-png", qr_png.to_str().unwrap())
+                .into(),
+            &mut holder.streams(),
+        );
+
+        assert_eq!(result, 0);
+        let stdout = holder.stdout.get_string();
+        assert!(
+            stdout.starts_with("AQ/DBA:1.2.3.4:1234\nsubstratum://AQ%2FDBA:1.2.3.4:1234\n\n"),
+            "{}",
+            stdout
+        );
+        assert!(
+            stdout.contains(&qr_code_text("substratum://AQ%2FDBA:1.2.3.4:1234").unwrap()),
+            "{}",
+            stdout
+        );
+        assert_eq!(
+            fs::read(&qr_png).unwrap(),
+            qr_code_png("substratum://AQ%2FDBA:1.2.3.4:1234").unwrap()
+        );
+        assert_eq!(
+            handle.join().unwrap(),
+            OwnedMessage::Text(
+                UiTrafficConverterReal::new()
+                    .marshal(UiMessage::GetNodeDescriptor)
+                    .unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn print_descriptor_complains_when_no_node_is_running() {
+        let port = find_free_port();
+        let mut holder = FakeStreamHolder::new();
+
+        let result = print_descriptor(
+            &ArgsBuilder::new()
+                .opt("--print-descriptor")
+                .param("--ui-port", &port.to_string())
+                .into(),
+            &mut holder.streams(),
+        );
+
+        assert_eq!(result, 1);
+        assert!(holder.stderr.get_string().starts_with(&format!(
+            "Couldn't print descriptor: no Node is listening on UI port {}",
+            port
+        )));
+    }
+}
@@ -0,0 +1,209 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use crate::node_configurator::{
+    app_head, chain_arg, data_directory_arg, instance_name_arg, make_multi_config, profile_arg,
+    real_user_arg,
+};
+use crate::sub_lib::main_tools::StdStreams;
+use crate::sub_lib::ui_gateway::{DnsLeakReport, DnsLeakVerdict, UiMessage, DEFAULT_UI_PORT};
+use crate::ui_gateway::neighbor_lister::{connect_to_node, receive_from_node, send_to_node};
+use clap::{value_t, App, Arg};
+use lazy_static::lazy_static;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const DNS_LEAK_TEST_HELP: &str =
+    "Ask the Node running on this machine to check that this machine's DNS lookups go through it, \
+     and say whether they're leaking out around the clandestine network. Exits with 0 only if \
+     they aren't.";
+
+const UI_PORT_HELP: &str = "The UI port of the running Node to ask.";
+
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+lazy_static! {
+    static ref DEFAULT_UI_PORT_VALUE: String = DEFAULT_UI_PORT.to_string();
+}
+
+pub fn dns_leak_test(args: &Vec<String>, streams: &mut StdStreams) -> i32 {
+    let (ui_port, data_directory) = distill_args(args);
+    match fetch_report(ui_port, &data_directory, RESPONSE_TIMEOUT) {
+        Ok(report) => {
+            writeln!(streams.stdout, "{}", report.explanation())
+                .expect("Couldn't write DNS leak report to stdout");
+            streams
+                .stdout
+                .flush()
+                .expect("Couldn't flush DNS leak report to stdout");
+            if report.verdict == DnsLeakVerdict::NotLeaking {
+                0
+            } else {
+                1
+            }
+        }
+        Err(e) => {
+            writeln!(streams.stderr, "Couldn't test for DNS leaks: {}", e)
+                .expect("Couldn't write to stderr");
+            1
+        }
+    }
+}
+
+fn fetch_report(
+    ui_port: u16,
+    data_directory: &Path,
+    timeout: Duration,
+) -> Result<DnsLeakReport, String> {
+    let mut client = connect_to_node(ui_port, data_directory, timeout)?;
+    send_to_node(&mut client, UiMessage::DnsLeakTest)?;
+    let result = receive_from_node(&mut client, |message| match message {
+        UiMessage::DnsLeakTestResponse(report) => Some(report),
+        _ => None,
+    });
+    let _ = client.shutdown();
+    result
+}
+
+pub fn app() -> App<'static, 'static> {
+    app_head()
+        .about(DNS_LEAK_TEST_HELP)
+        .usage("PrometheusNode dns-leak-test [OPTIONS]")
+        .arg(
+            Arg::with_name("dns-leak-test")
+                .long("dns-leak-test")
+                .required(true)
+                .takes_value(false)
+                .hidden(true),
+        )
+        .arg(
+            Arg::with_name("ui-port")
+                .long("ui-port")
+                .value_name("UI-PORT")
+                .takes_value(true)
+                .default_value(&DEFAULT_UI_PORT_VALUE)
+                .help(UI_PORT_HELP),
+        )
+        .arg(chain_arg())
+        .arg(data_directory_arg())
+        .arg(profile_arg())
+        .arg(instance_name_arg())
+        .arg(real_user_arg())
+}
+
+fn distill_args(args: &Vec<String>) -> (u16, PathBuf) {
+    let app = app();
+    let multi_config = make_multi_config(&app, args, None);
+    let ui_port =
+        value_m!(multi_config, "ui-port", u16).expect("ui-port is not properly defaulted");
+    let (_, data_directory, _) =
+        crate::node_configurator::real_user_data_directory_and_chain_id(&multi_config);
+    (ui_port, data_directory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{
+        ensure_node_home_directory_exists, find_free_port, make_dns_leak_report, ArgsBuilder,
+        FakeStreamHolder,
+    };
+    use crate::ui_gateway::auth_token::write_ui_auth_token;
+    use crate::ui_gateway::neighbor_lister::UI_PROTOCOL;
+    use crate::ui_gateway::ui_traffic_converter::{UiTrafficConverter, UiTrafficConverterReal};
+    use std::net::Ipv4Addr;
+    use std::thread;
+    use websocket::sync::Server;
+    use websocket::OwnedMessage;
+
+    fn run_against_node(test_name: &str, report: DnsLeakReport) -> (i32, FakeStreamHolder) {
+        let data_dir = ensure_node_home_directory_exists("dns_leak_reporter", test_name);
+        write_ui_auth_token(&data_dir, "token").unwrap();
+        let port = find_free_port();
+        let mut server = Server::bind((Ipv4Addr::LOCALHOST, port)).unwrap();
+        let handle = thread::spawn(move || {
+            let upgrade = server.accept().ok().unwrap();
+            let mut client = upgrade.use_protocol(UI_PROTOCOL).accept().unwrap();
+            let _authentication = client.recv_message().unwrap();
+            let request = client.recv_message().unwrap();
+            client
+                .send_message(&OwnedMessage::Text(
+                    UiTrafficConverterReal::new()
+                        .marshal(UiMessage::DnsLeakTestResponse(report))
+                        .unwrap(),
+                ))
+                .unwrap();
+            request
+        });
+        let mut holder = FakeStreamHolder::new();
+
+        let result = dns_leak_test(
+            &ArgsBuilder::new()
+                .opt("--dns-leak-test")
+                .param("--ui-port", &port.to_string())
+                .param("--data-directory", data_dir.to_str().unwrap())
+                .into(),
+            &mut holder.streams(),
+        );
+
+        assert_eq!(
+            handle.join().unwrap(),
+            OwnedMessage::Text(
+                UiTrafficConverterReal::new()
+                    .marshal(UiMessage::DnsLeakTest)
+                    .unwrap()
+            )
+        );
+        (result, holder)
+    }
+
+    #[test]
+    fn dns_leak_test_asks_the_node_and_succeeds_without_a_leak() {
+        let mut report = make_dns_leak_report(DnsLeakVerdict::NotLeaking);
+        report.system_answers = report.node_answer.into_iter().collect();
+
+        let (result, holder) = run_against_node(
+            "dns_leak_test_asks_the_node_and_succeeds_without_a_leak",
+            report.clone(),
+        );
+
+        assert_eq!(result, 0);
+        assert_eq!(
+            holder.stdout.get_string(),
+            format!("{}\n", report.explanation())
+        );
+    }
+
+    #[test]
+    fn dns_leak_test_fails_when_dns_leaks() {
+        let report = make_dns_leak_report(DnsLeakVerdict::Leaking);
+
+        let (result, holder) =
+            run_against_node("dns_leak_test_fails_when_dns_leaks", report.clone());
+
+        assert_eq!(result, 1);
+        assert_eq!(
+            holder.stdout.get_string(),
+            format!("{}\n", report.explanation())
+        );
+    }
+
+    #[test]
+    fn dns_leak_test_complains_when_no_node_is_running() {
+        let port = find_free_port();
+        let mut holder = FakeStreamHolder::new();
+
+        let result = dns_leak_test(
+            &ArgsBuilder::new()
+                .opt("--dns-leak-test")
+                .param("--ui-port", &port.to_string())
+                .into(),
+            &mut holder.streams(),
+        );
+
+        assert_eq!(result, 1);
+        assert!(holder.stderr.get_string().starts_with(&format!(
+            "Couldn't test for DNS leaks: no Node is listening on UI port {}",
+            port
+        )));
+    }
+}
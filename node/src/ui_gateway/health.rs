@@ -0,0 +1,199 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use crate::sub_lib::ui_gateway::{ComponentHealth, HealthState};
+use dns_utility_lib::dns_modifier_factory::{DnsModifierFactory, DnsModifierFactoryReal};
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::time::Duration;
+
+const LISTENER_PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+// The components whose health the UiGateway finds out for itself, rather than asking another actor
+pub trait HealthInspector: Send {
+    fn listeners(&self, listeners: &[SocketAddr]) -> ComponentHealth;
+    fn dns(&self) -> ComponentHealth;
+}
+
+#[derive(Default)]
+pub struct HealthInspectorReal {}
+
+impl HealthInspector for HealthInspectorReal {
+    fn listeners(&self, listeners: &[SocketAddr]) -> ComponentHealth {
+        listener_health(listeners, LISTENER_PROBE_TIMEOUT)
+    }
+
+    fn dns(&self) -> ComponentHealth {
+        dns_health(&DnsModifierFactoryReal::new())
+    }
+}
+
+impl HealthInspectorReal {
+    pub fn new() -> HealthInspectorReal {
+        Default::default()
+    }
+}
+
+// A listener is still bound if it still takes connections, the way an orchestrator would check
+fn listener_health(listeners: &[SocketAddr], timeout: Duration) -> ComponentHealth {
+    let unbound = listeners
+        .iter()
+        .filter(|addr| TcpStream::connect_timeout(addr, timeout).is_err())
+        .map(|addr| addr.to_string())
+        .collect::<Vec<String>>();
+    if unbound.is_empty() {
+        ComponentHealth::new(
+            "listeners",
+            HealthState::Ready,
+            &format!("{} bound", listeners.len()),
+        )
+    } else {
+        ComponentHealth::new(
+            "listeners",
+            HealthState::NotReady,
+            &format!("not accepting on {}", unbound.join(", ")),
+        )
+    }
+}
+
+// Subverted DNS sends every lookup to a loopback address, where the Node's DNS server is. DNS
+// that isn't subverted is no fault of the Node's, so it's reported as Disabled, not NotReady.
+fn dns_health(dns_modifier_factory: &dyn DnsModifierFactory) -> ComponentHealth {
+    let dns_modifier = match dns_modifier_factory.make() {
+        Some(dns_modifier) => dns_modifier,
+        None => {
+            return ComponentHealth::new(
+                "dns",
+                HealthState::Disabled,
+                "can't tell how this system's DNS is set up",
+            )
+        }
+    };
+    let mut output: Vec<u8> = vec![];
+    if let Err(e) = dns_modifier.inspect(&mut output) {
+        return ComponentHealth::new("dns", HealthState::NotReady, &e);
+    }
+    let nameservers = String::from_utf8_lossy(&output)
+        .split_whitespace()
+        .map(|nameserver| nameserver.to_string())
+        .collect::<Vec<String>>();
+    let subverted = !nameservers.is_empty()
+        && nameservers.iter().all(|nameserver| {
+            nameserver
+                .parse::<IpAddr>()
+                .map(|ip| ip.is_loopback())
+                .unwrap_or(false)
+        });
+    if subverted {
+        ComponentHealth::new("dns", HealthState::Ready, "subverted")
+    } else {
+        ComponentHealth::new(
+            "dns",
+            HealthState::Disabled,
+            &format!("not subverted: nameservers {}", nameservers.join(", ")),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::dns_modifier_mock::{DnsModifierFactoryMock, DnsModifierMock};
+    use std::net::{Ipv4Addr, TcpListener};
+
+    #[test]
+    fn listeners_are_ready_while_they_take_connections() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let result = listener_health(&[addr], Duration::from_millis(500));
+
+        assert_eq!(
+            result,
+            ComponentHealth::new("listeners", HealthState::Ready, "1 bound")
+        );
+    }
+
+    #[test]
+    fn listeners_that_refuse_connections_are_not_ready() {
+        let addr = {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+            listener.local_addr().unwrap()
+        };
+
+        let result = listener_health(&[addr], Duration::from_millis(500));
+
+        assert_eq!(
+            result,
+            ComponentHealth::new(
+                "listeners",
+                HealthState::NotReady,
+                &format!("not accepting on {}", addr)
+            )
+        );
+    }
+
+    #[test]
+    fn dns_is_ready_when_every_nameserver_is_loopback() {
+        let dns_modifier = DnsModifierMock::new().inspect_result("127.0.0.1\n::1\n", Ok(()));
+        let factory = DnsModifierFactoryMock::new().make_result(Some(Box::new(dns_modifier)));
+
+        let result = dns_health(&factory);
+
+        assert_eq!(
+            result,
+            ComponentHealth::new("dns", HealthState::Ready, "subverted")
+        );
+    }
+
+    #[test]
+    fn dns_that_isnt_subverted_is_disabled() {
+        let dns_modifier = DnsModifierMock::new().inspect_result("127.0.0.1\n8.8.8.8\n", Ok(()));
+        let factory = DnsModifierFactoryMock::new().make_result(Some(Box::new(dns_modifier)));
+
+        let result = dns_health(&factory);
+
+        assert_eq!(
+            result,
+            ComponentHealth::new(
+                "dns",
+                HealthState::Disabled,
+                "not subverted: nameservers 127.0.0.1, 8.8.8.8"
+            )
+        );
+    }
+
+    #[test]
+    fn dns_that_cant_be_inspected_is_not_ready() {
+        let dns_modifier = DnsModifierMock::new().inspect_result(
+            "",
+            Err("This system does not appear to be connected to a network".to_string()),
+        );
+        let factory = DnsModifierFactoryMock::new().make_result(Some(Box::new(dns_modifier)));
+
+        let result = dns_health(&factory);
+
+        assert_eq!(
+            result,
+            ComponentHealth::new(
+                "dns",
+                HealthState::NotReady,
+                "This system does not appear to be connected to a network"
+            )
+        );
+    }
+
+    #[test]
+    fn dns_on_an_unknown_system_is_disabled() {
+        let factory = DnsModifierFactoryMock::new().make_result(None);
+
+        let result = dns_health(&factory);
+
+        assert_eq!(
+            result,
+            ComponentHealth::new(
+                "dns",
+                HealthState::Disabled,
+                "can't tell how this system's DNS is set up"
+            )
+        );
+    }
+}
@@ -1,44 +1,128 @@
 // Copyright (c) 2017-2018, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
+pub mod auth_token;
+pub mod dashboard;
+pub mod descriptor_printer;
+pub mod dns_leak_reporter;
+mod health;
+pub mod neighbor_lister;
+mod rest_supervisor;
 mod shutdown_supervisor;
+pub mod telemetry;
 pub mod ui_traffic_converter;
-mod websocket_supervisor;
-
-use crate::sub_lib::accountant::GetFinancialStatisticsMessage;
-use crate::sub_lib::blockchain_bridge::{SetGasPriceMsg, SetWalletPasswordMsg};
+pub mod websocket_supervisor;
+
+use crate::audit_log::DEFAULT_RECENT_AUDIT_ENTRIES;
+use crate::audit_log::{audit, AuditLogReader, AuditLogReaderReal, AuditSource};
+use crate::entry_dns::leak_test::{DnsLeakTester, DnsLeakTesterReal};
+use crate::multi_config::{ConfigFileVcl, MultiConfig};
+use crate::node_configurator::config_checker::clap_complaint;
+use crate::node_configurator::node_configurator_standard;
+use crate::server_initializer;
+use crate::sub_lib::accountant::{
+    DumpFinancialsMessage, GetFinancialStatisticsMessage, SetPaymentParameterMessage,
+};
+use crate::sub_lib::blockchain_bridge::{
+    GetBlockchainServiceUrlMsg, GetWalletBalancesMsg, SetGasPriceMsg, SetWalletPasswordMsg,
+};
+use crate::sub_lib::crash_context::handling;
 use crate::sub_lib::logger::Logger;
+use crate::sub_lib::neighborhood::NeighborListRequest;
 use crate::sub_lib::neighborhood::NeighborhoodDotGraphRequest;
 use crate::sub_lib::peer_actors::BindMessage;
+use crate::sub_lib::ui_gateway::UiCapabilities;
 use crate::sub_lib::ui_gateway::UiGatewaySubs;
-use crate::sub_lib::ui_gateway::{FromUiMessage, UiCarrierMessage};
-use crate::sub_lib::ui_gateway::{UiGatewayConfig, UiMessage};
+use crate::sub_lib::ui_gateway::{ChangeConfigurationMessage, ConfigurationEntry};
+use crate::sub_lib::ui_gateway::{ConfigFileSource, ConfigurationReload};
+use crate::sub_lib::ui_gateway::{FromUiMessage, GetConfigurationMessage, UiCarrierMessage};
+use crate::sub_lib::ui_gateway::{GetHealthMessage, NodeHealth};
+use crate::sub_lib::ui_gateway::{GetNodeStatisticsMessage, NodeStatistics};
+use crate::sub_lib::ui_gateway::{NodeDescriptorShare, NodeEvent, NodeEventKind};
+use crate::sub_lib::ui_gateway::{NodeStatus, UiGatewayConfig, UiMessage};
+use crate::ui_gateway::auth_token::authenticate_client;
+use crate::ui_gateway::health::{HealthInspector, HealthInspectorReal};
+use crate::ui_gateway::rest_supervisor::is_rest_client;
+use crate::ui_gateway::rest_supervisor::RestSupervisor;
+use crate::ui_gateway::rest_supervisor::RestSupervisorReal;
 use crate::ui_gateway::shutdown_supervisor::ShutdownSupervisor;
 use crate::ui_gateway::shutdown_supervisor::ShutdownSupervisorReal;
+use crate::ui_gateway::telemetry::{TelemetryReport, TelemetrySender, TelemetrySenderReal};
+use crate::ui_gateway::telemetry::{TELEMETRY_FIRST_REPORT_DELAY, TELEMETRY_INTERVAL};
 use crate::ui_gateway::ui_traffic_converter::UiTrafficConverter;
 use crate::ui_gateway::ui_traffic_converter::UiTrafficConverterReal;
 use crate::ui_gateway::websocket_supervisor::WebSocketSupervisor;
 use crate::ui_gateway::websocket_supervisor::WebSocketSupervisorReal;
 use actix::Actor;
 use actix::Addr;
+use actix::AsyncContext;
 use actix::Context;
 use actix::Handler;
+use actix::MailboxError;
+use actix::Message;
 use actix::Recipient;
+use clap::crate_version;
+use futures::future;
+use futures::Future;
+use log::LevelFilter;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How long a shutdown or restart waits for active streams to finish, and how often it checks
+pub const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+const LOG_LEVEL_PARAMETER: &str = "log-level";
+
+// Puts a component back on the level the rest of the Node logs at
+const DEFAULT_COMPONENT_LOG_LEVEL: &str = "default";
 
 struct UiGatewayOutSubs {
     ui_message_sub: Recipient<UiCarrierMessage>,
     blockchain_bridge_set_consuming_wallet_password_sub: Recipient<SetWalletPasswordMsg>,
     blockchain_bridge_set_gas_price_sub: Recipient<SetGasPriceMsg>,
+    blockchain_bridge_get_blockchain_service_url_sub: Recipient<GetBlockchainServiceUrlMsg>,
+    blockchain_bridge_get_wallet_balances_sub: Recipient<GetWalletBalancesMsg>,
     accountant_get_financial_statistics_sub: Recipient<GetFinancialStatisticsMessage>,
+    accountant_dump_financials_sub: Recipient<DumpFinancialsMessage>,
+    accountant_set_payment_parameter_sub: Recipient<SetPaymentParameterMessage>,
     neighborhood: Recipient<NeighborhoodDotGraphRequest>,
+    neighborhood_neighbor_list_sub: Recipient<NeighborListRequest>,
+    node_statistics_subs: Vec<Recipient<GetNodeStatisticsMessage>>,
+    health_subs: Vec<Recipient<GetHealthMessage>>,
+    configuration_subs: Vec<Recipient<GetConfigurationMessage>>,
+    change_configuration_subs: Vec<Recipient<ChangeConfigurationMessage>>,
 }
 
 pub struct UiGateway {
     port: u16,
+    rest_port_opt: Option<u16>,
     node_descriptor: String,
+    started_at: Instant,
     converter: Box<dyn UiTrafficConverter>,
     subs: Option<UiGatewayOutSubs>,
     websocket_supervisor: Option<Box<dyn WebSocketSupervisor>>,
+    rest_supervisor: Option<Box<dyn RestSupervisor>>,
     shutdown_supervisor: Box<dyn ShutdownSupervisor>,
+    draining: bool,
+    auth_token: String,
+    // WebSocket clients that opened with the right token. REST requests each bear it, and the
+    // RestSupervisor refuses those that don't.
+    authenticated_clients: HashSet<u64>,
+    event_subscriptions: HashMap<u64, HashSet<NodeEventKind>>,
+    // UIs that have said Hello with a protocol version above 1, and so expect UiEnvelopes
+    protocol_versions: HashMap<u64, u32>,
+    config_file_opt: Option<ConfigFileSource>,
+    dns_leak_tester: Arc<dyn DnsLeakTester>,
+    listeners: Vec<SocketAddr>,
+    health_inspector: Box<dyn HealthInspector>,
+    audit_log_reader: Box<dyn AuditLogReader>,
+    telemetry_sender_opt: Option<Arc<dyn TelemetrySender>>,
+    telemetry_first_report_delay: Duration,
+    telemetry_interval: Duration,
     logger: Logger,
 }
 
@@ -46,11 +130,30 @@ impl UiGateway {
     pub fn new(config: &UiGatewayConfig) -> UiGateway {
         UiGateway {
             port: config.ui_port,
+            rest_port_opt: config.rest_port_opt,
             node_descriptor: config.node_descriptor.clone(),
+            started_at: Instant::now(),
             converter: Box::new(UiTrafficConverterReal::new()),
             subs: None,
             websocket_supervisor: None,
+            rest_supervisor: None,
             shutdown_supervisor: Box::new(ShutdownSupervisorReal::new()),
+            draining: false,
+            auth_token: config.auth_token.clone(),
+            authenticated_clients: HashSet::new(),
+            event_subscriptions: HashMap::new(),
+            protocol_versions: HashMap::new(),
+            config_file_opt: config.config_file_opt.clone(),
+            dns_leak_tester: Arc::new(DnsLeakTesterReal::new(config.dns_server)),
+            listeners: config.listeners.clone(),
+            health_inspector: Box::new(HealthInspectorReal::new()),
+            audit_log_reader: Box::new(AuditLogReaderReal {}),
+            telemetry_sender_opt: config
+                .telemetry_url_opt
+                .as_ref()
+                .map(|url| Arc::new(TelemetrySenderReal::new(url)) as Arc<dyn TelemetrySender>),
+            telemetry_first_report_delay: TELEMETRY_FIRST_REPORT_DELAY,
+            telemetry_interval: TELEMETRY_INTERVAL,
             logger: Logger::new("UiGateway"),
         }
     }
@@ -60,6 +163,7 @@ impl UiGateway {
             bind: recipient!(addr, BindMessage),
             ui_message_sub: recipient!(addr, UiCarrierMessage),
             from_ui_message_sub: recipient!(addr, FromUiMessage),
+            node_event_sub: recipient!(addr, NodeEvent),
         }
     }
 }
@@ -71,7 +175,8 @@ impl Actor for UiGateway {
 impl Handler<BindMessage> for UiGateway {
     type Result = ();
 
-    fn handle(&mut self, msg: BindMessage, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, msg: BindMessage, ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("UiGateway", "BindMessage");
         //        ctx.set_mailbox_capacity(?);
         let subs = UiGatewayOutSubs {
             ui_message_sub: msg.peer_actors.ui_gateway.ui_message_sub.clone(),
@@ -85,18 +190,62 @@ impl Handler<BindMessage> for UiGateway {
                 .blockchain_bridge
                 .set_gas_price_sub
                 .clone(),
+            blockchain_bridge_get_blockchain_service_url_sub: msg
+                .peer_actors
+                .blockchain_bridge
+                .get_blockchain_service_url_sub
+                .clone(),
+            blockchain_bridge_get_wallet_balances_sub: msg
+                .peer_actors
+                .blockchain_bridge
+                .get_wallet_balances_sub
+                .clone(),
             accountant_get_financial_statistics_sub: msg
                 .peer_actors
                 .accountant
                 .get_financial_statistics_sub
                 .clone(),
+            accountant_dump_financials_sub: msg.peer_actors.accountant.dump_financials_sub.clone(),
+            accountant_set_payment_parameter_sub: msg
+                .peer_actors
+                .accountant
+                .set_payment_parameter_sub
+                .clone(),
             neighborhood: msg.peer_actors.neighborhood.from_ui_gateway.clone(),
+            neighborhood_neighbor_list_sub: msg.peer_actors.neighborhood.neighbor_list_sub.clone(),
+            node_statistics_subs: vec![
+                msg.peer_actors.accountant.node_statistics_sub.clone(),
+                msg.peer_actors.neighborhood.node_statistics_sub.clone(),
+                msg.peer_actors.proxy_client.node_statistics_sub.clone(),
+                msg.peer_actors.proxy_server.node_statistics_sub.clone(),
+            ],
+            health_subs: vec![
+                msg.peer_actors.accountant.health_sub.clone(),
+                msg.peer_actors.blockchain_bridge.health_sub.clone(),
+                msg.peer_actors.neighborhood.health_sub.clone(),
+            ],
+            configuration_subs: vec![msg.peer_actors.accountant.configuration_sub.clone()],
+            change_configuration_subs: vec![msg
+                .peer_actors
+                .accountant
+                .change_configuration_sub
+                .clone()],
         };
         self.subs = Some(subs);
         self.websocket_supervisor = Some(Box::new(WebSocketSupervisorReal::new(
             self.port,
             msg.peer_actors.ui_gateway.from_ui_message_sub.clone(),
         )));
+        if let Some(rest_port) = self.rest_port_opt {
+            self.rest_supervisor = Some(Box::new(RestSupervisorReal::new(
+                rest_port,
+                msg.peer_actors.ui_gateway.ui_message_sub.clone(),
+                self.auth_token.clone(),
+            )));
+        }
+        listen_for_hangups(ctx, &self.logger);
+        listen_for_terminations(ctx, &self.logger);
+        self.schedule_telemetry(ctx);
         info!(self.logger, "UIGateway bound");
     }
 }
@@ -105,9 +254,23 @@ impl Handler<UiCarrierMessage> for UiGateway {
     type Result = ();
 
     // All UI messages, both inbound and outbound, come through here
-    fn handle(&mut self, msg: UiCarrierMessage, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, msg: UiCarrierMessage, ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("UiGateway", "UiCarrierMessage");
+        let client_id = msg.client_id;
         match msg.data {
+            UiMessage::Authenticate(_) => {
+                debug!(self.logger, "UI {} authenticated again", msg.client_id)
+            }
+            UiMessage::Hello { protocol_version } => self.greet(msg.client_id, protocol_version),
             UiMessage::SetWalletPassword(password) => {
+                audit(
+                    AuditSource::Ui,
+                    "wallet-password",
+                    &format!(
+                        "UI {} supplied the consuming wallet password",
+                        msg.client_id
+                    ),
+                );
                 self.subs
                     .as_ref()
                     .expect("UiGateway is unbound")
@@ -127,9 +290,50 @@ impl Handler<UiCarrierMessage> for UiGateway {
                     client_id: msg.client_id,
                 })
                 .expect("Accountant is dead"),
+            UiMessage::DumpFinancialsRequest(format) => self
+                .subs
+                .as_ref()
+                .expect("UiGateway is unbound")
+                .accountant_dump_financials_sub
+                .try_send(DumpFinancialsMessage {
+                    client_id: msg.client_id,
+                    format,
+                })
+                .expect("Accountant is dead"),
+            UiMessage::SetPaymentParameter { parameter, value } => {
+                audit(
+                    AuditSource::Ui,
+                    "payment-parameter",
+                    &format!("UI {} set {} to {}", msg.client_id, parameter.name(), value),
+                );
+                self.subs
+                    .as_ref()
+                    .expect("UiGateway is unbound")
+                    .accountant_set_payment_parameter_sub
+                    .try_send(SetPaymentParameterMessage {
+                        client_id: msg.client_id,
+                        parameter,
+                        value,
+                    })
+                    .expect("Accountant is dead")
+            }
             UiMessage::ShutdownMessage => {
                 info!(self.logger, "Received shutdown order");
-                self.shutdown_supervisor.shutdown();
+                audit(
+                    AuditSource::Ui,
+                    "shutdown",
+                    &format!("UI {} ordered a shutdown", msg.client_id),
+                );
+                self.drain(false, ctx);
+            }
+            UiMessage::RestartMessage => {
+                info!(self.logger, "Received restart order");
+                audit(
+                    AuditSource::Ui,
+                    "restart",
+                    &format!("UI {} ordered a restart", msg.client_id),
+                );
+                self.drain(true, ctx);
             }
             UiMessage::GetNodeDescriptor => self
                 .subs
@@ -141,21 +345,161 @@ impl Handler<UiCarrierMessage> for UiGateway {
                     data: UiMessage::NodeDescriptor(self.node_descriptor.clone()),
                 })
                 .expect("UiGateway is dead"),
-            UiMessage::SetGasPrice(gas_price) => set_gas_price(self, msg.client_id, &gas_price),
-            UiMessage::NodeDescriptor(_)
+            UiMessage::GetNodeDescriptorShare => {
+                match NodeDescriptorShare::new(&self.node_descriptor) {
+                    Ok(share) => self
+                        .subs
+                        .as_ref()
+                        .expect("UiGateway is unbound")
+                        .ui_message_sub
+                        .try_send(UiCarrierMessage {
+                            client_id: msg.client_id,
+                            data: UiMessage::NodeDescriptorShare(share),
+                        })
+                        .expect("UiGateway is dead"),
+                    Err(e) => error!(self.logger, "Couldn't share the node descriptor: {}", e),
+                }
+            }
+            UiMessage::GetNodeStatus => self
+                .subs
+                .as_ref()
+                .expect("UiGateway is unbound")
+                .ui_message_sub
+                .try_send(UiCarrierMessage {
+                    client_id: msg.client_id,
+                    data: UiMessage::NodeStatus(NodeStatus {
+                        version: crate_version!().to_string(),
+                        node_descriptor: self.node_descriptor.clone(),
+                        uptime_secs: self.started_at.elapsed().as_secs(),
+                    }),
+                })
+                .expect("UiGateway is dead"),
+            UiMessage::GetNodeStatistics => self.gather_node_statistics(msg.client_id),
+            UiMessage::GetNodeHealth => self.gather_node_health(msg.client_id),
+            UiMessage::GetAuditLog { limit } => self.report_audit_log(msg.client_id, limit),
+            UiMessage::ConfigurationGet => self.gather_configuration(msg.client_id),
+            UiMessage::ConfigurationChange { name, value } => {
+                audit(
+                    AuditSource::Ui,
+                    "configuration-change",
+                    &format!("UI {} set {} to {}", msg.client_id, name, value),
+                );
+                self.change_configuration(msg.client_id, name, value)
+            }
+            UiMessage::SetLogLevel { level, component } => {
+                audit(
+                    AuditSource::Ui,
+                    "log-level",
+                    &match &component {
+                        None => format!("UI {} set the log level to {}", msg.client_id, level),
+                        Some(component) => format!(
+                            "UI {} set the log level for {} to {}",
+                            msg.client_id, component, level
+                        ),
+                    },
+                );
+                self.set_log_level(msg.client_id, &level, component)
+            }
+            UiMessage::ReloadConfiguration => {
+                audit(
+                    AuditSource::Ui,
+                    "configuration-reload",
+                    &format!("UI {} ordered a reload", msg.client_id),
+                );
+                self.reload_configuration(Some(msg.client_id))
+            }
+            UiMessage::SubscribeToEvents(kinds) => {
+                debug!(self.logger, "UI {} subscribed to {:?}", client_id, kinds);
+                self.event_subscriptions
+                    .insert(client_id, kinds.into_iter().collect());
+            }
+            UiMessage::UnsubscribeFromEvents => {
+                self.event_subscriptions.remove(&client_id);
+            }
+            UiMessage::SetGasPrice(gas_price) => {
+                audit(
+                    AuditSource::Ui,
+                    "gas-price",
+                    &format!("UI {} set the gas price to {}", msg.client_id, gas_price),
+                );
+                set_gas_price(self, msg.client_id, &gas_price)
+            }
+            UiMessage::GetNeighborList => self
+                .subs
+                .as_ref()
+                .expect("UiGateway is unbound")
+                .neighborhood_neighbor_list_sub
+                .try_send(NeighborListRequest {
+                    client_id: msg.client_id,
+                })
+                .expect("Neighborhood is dead"),
+            UiMessage::GetBlockchainServiceUrl => self
+                .subs
+                .as_ref()
+                .expect("UiGateway is unbound")
+                .blockchain_bridge_get_blockchain_service_url_sub
+                .try_send(GetBlockchainServiceUrlMsg {
+                    client_id: msg.client_id,
+                })
+                .expect("Blockchain Bridge is dead"),
+            UiMessage::GetWalletBalances => self
+                .subs
+                .as_ref()
+                .expect("UiGateway is unbound")
+                .blockchain_bridge_get_wallet_balances_sub
+                .try_send(GetWalletBalancesMsg {
+                    client_id: msg.client_id,
+                })
+                .expect("Blockchain Bridge is dead"),
+            UiMessage::DnsLeakTest => self.test_for_dns_leaks(msg.client_id),
+            UiMessage::Capabilities(_)
+            | UiMessage::NodeDescriptor(_)
+            | UiMessage::NodeDescriptorShare(_)
+            | UiMessage::NodeStatus(_)
+            | UiMessage::NodeStatistics(_)
+            | UiMessage::NodeHealth(_)
+            | UiMessage::AuditLog(_)
             | UiMessage::SetWalletPasswordResponse(_)
             | UiMessage::FinancialStatisticsResponse(_)
+            | UiMessage::DumpFinancialsResponse(_)
             | UiMessage::SetGasPriceResponse(_)
-            | UiMessage::NeighborhoodDotGraphResponse(_) => {
-                let marshalled = self
-                    .converter
-                    .marshal(msg.data)
-                    .expect("Internal error: failed to marshal UiMessage");
-                self.websocket_supervisor
-                    .as_ref()
-                    .expect("WebsocketSupervisor is unbound")
-                    .send(msg.client_id, &marshalled);
+            | UiMessage::BlockchainServiceUrl(_)
+            | UiMessage::WalletBalances(_)
+            | UiMessage::SetPaymentParameterResponse(_)
+            | UiMessage::NeighborhoodDotGraphResponse(_)
+            | UiMessage::NeighborList(_)
+            | UiMessage::Configuration(_)
+            | UiMessage::ConfigurationChangeResponse(_)
+            | UiMessage::SetLogLevelResponse(_)
+            | UiMessage::ReloadConfigurationResponse(_)
+            | UiMessage::NodeEvent(_)
+            | UiMessage::RouteCapabilityDiagnosis(_)
+            | UiMessage::DnsLeakTestResponse(_) => {
+                if is_rest_client(msg.client_id) {
+                    self.rest_supervisor
+                        .as_ref()
+                        .expect("RestSupervisor is unbound")
+                        .send(msg.client_id, msg.data);
+                } else {
+                    let marshalled = self.marshal_for(msg.client_id, msg.data);
+                    self.websocket_supervisor
+                        .as_ref()
+                        .expect("WebsocketSupervisor is unbound")
+                        .send(msg.client_id, &marshalled);
+                }
             }
+            UiMessage::DaemonSetup(_)
+            | UiMessage::DaemonStart
+            | UiMessage::DaemonStop
+            | UiMessage::GetDaemonStatus
+            | UiMessage::DaemonSetupResponse(_)
+            | UiMessage::DaemonStartResponse(_)
+            | UiMessage::DaemonStopResponse(_)
+            | UiMessage::DaemonStatus(_) => warning!(
+                self.logger,
+                "UI {} sent the Node a message meant for the daemon - ignoring",
+                msg.client_id
+            ),
             UiMessage::NeighborhoodDotGraphRequest => {
                 debug!(self.logger, "in UiMessage::NeighborhoodDotGraphRequest");
                 self.subs
@@ -171,6 +515,715 @@ impl Handler<UiCarrierMessage> for UiGateway {
     }
 }
 
+impl Handler<NodeEvent> for UiGateway {
+    type Result = ();
+
+    fn handle(&mut self, msg: NodeEvent, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("UiGateway", "NodeEvent");
+        let websocket_supervisor = self
+            .websocket_supervisor
+            .as_ref()
+            .expect("WebsocketSupervisor is unbound");
+        // UIs that have gone away without unsubscribing are forgotten here
+        self.event_subscriptions
+            .retain(|client_id, _| websocket_supervisor.is_connected(*client_id));
+        let kind = msg.kind();
+        let (enveloped, bare): (Vec<u64>, Vec<u64>) = self
+            .event_subscriptions
+            .iter()
+            .filter(|(_, kinds)| kinds.contains(&kind))
+            .map(|(client_id, _)| *client_id)
+            .partition(|client_id| self.protocol_versions.contains_key(client_id));
+        if !enveloped.is_empty() {
+            let marshalled = self
+                .converter
+                .marshal_enveloped(UiMessage::NodeEvent(msg.clone()))
+                .expect("Internal error: failed to marshal UiMessage");
+            enveloped
+                .into_iter()
+                .for_each(|client_id| websocket_supervisor.send(client_id, &marshalled));
+        }
+        if !bare.is_empty() {
+            let marshalled = self
+                .converter
+                .marshal(UiMessage::NodeEvent(msg))
+                .expect("Internal error: failed to marshal UiMessage");
+            bare.into_iter()
+                .for_each(|client_id| websocket_supervisor.send(client_id, &marshalled));
+        }
+    }
+}
+
+// Sent to itself by the UiGateway while it waits for streams to finish before shutting down
+#[derive(Message)]
+struct DrainCheck {
+    restart: bool,
+    deadline: Instant,
+}
+
+#[derive(Message)]
+struct DrainReport {
+    restart: bool,
+    deadline: Instant,
+    active_streams: u64,
+}
+
+impl Handler<DrainCheck> for UiGateway {
+    type Result = ();
+
+    fn handle(&mut self, msg: DrainCheck, ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("UiGateway", "DrainCheck");
+        let addr = ctx.address();
+        let logger = self.logger.clone();
+        let future = self.collect_node_statistics().then(move |result| {
+            let active_streams = match result {
+                Ok(statistics) => {
+                    statistics.active_streams_originated + statistics.active_exit_streams
+                }
+                Err(e) => {
+                    error!(logger, "Couldn't count active streams: {:?}", e);
+                    0
+                }
+            };
+            addr.try_send(DrainReport {
+                restart: msg.restart,
+                deadline: msg.deadline,
+                active_streams,
+            })
+            .expect("UiGateway is dead");
+            Ok(())
+        });
+        actix::spawn(future);
+    }
+}
+
+impl Handler<DrainReport> for UiGateway {
+    type Result = ();
+
+    fn handle(&mut self, msg: DrainReport, ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("UiGateway", "DrainReport");
+        if msg.active_streams > 0 && Instant::now() < msg.deadline {
+            debug!(
+                self.logger,
+                "Waiting for {} active streams to finish", msg.active_streams
+            );
+            ctx.notify_later(
+                DrainCheck {
+                    restart: msg.restart,
+                    deadline: msg.deadline,
+                },
+                DRAIN_POLL_INTERVAL,
+            );
+            return;
+        }
+        if msg.active_streams > 0 {
+            warning!(
+                self.logger,
+                "Giving up on {} streams still active after {}s",
+                msg.active_streams,
+                DRAIN_TIMEOUT.as_secs()
+            );
+        }
+        if msg.restart {
+            info!(self.logger, "Restarting");
+            self.shutdown_supervisor.restart();
+        } else {
+            info!(self.logger, "Shutting down");
+            self.shutdown_supervisor.shutdown();
+        }
+    }
+}
+
+// Sent to itself by the UiGateway whenever the process gets SIGHUP
+#[cfg(not(target_os = "windows"))]
+#[derive(Message)]
+struct HangUp {}
+
+#[cfg(not(target_os = "windows"))]
+impl Handler<HangUp> for UiGateway {
+    type Result = ();
+
+    fn handle(&mut self, _msg: HangUp, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("UiGateway", "HangUp");
+        info!(self.logger, "Received SIGHUP; reloading configuration");
+        audit(AuditSource::Signal, "configuration-reload", "SIGHUP");
+        self.reload_configuration(None);
+    }
+}
+
+// Sent to itself by the UiGateway when the process gets SIGTERM, so that the shutdown is audited.
+// The Node still stops at once, as it would have without the handler.
+#[cfg(not(target_os = "windows"))]
+#[derive(Message)]
+struct Terminate {}
+
+#[cfg(not(target_os = "windows"))]
+impl Handler<Terminate> for UiGateway {
+    type Result = ();
+
+    fn handle(&mut self, _msg: Terminate, _ctx: &mut Self::Context) -> Self::Result {
+        let _handling = handling("UiGateway", "Terminate");
+        info!(self.logger, "Received SIGTERM; shutting down");
+        audit(AuditSource::Signal, "shutdown", "SIGTERM");
+        self.shutdown_supervisor.shutdown();
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn listen_for_hangups(ctx: &mut Context<UiGateway>, logger: &Logger) {
+    use futures::Stream;
+    use tokio_signal::unix::{Signal, SIGHUP};
+    let logger = logger.clone();
+    ctx.add_message_stream(
+        Signal::new(SIGHUP)
+            .flatten_stream()
+            .map(|_| HangUp {})
+            .map_err(move |e| error!(logger, "Can't listen for SIGHUP: {}", e)),
+    );
+}
+
+#[cfg(not(target_os = "windows"))]
+fn listen_for_terminations(ctx: &mut Context<UiGateway>, logger: &Logger) {
+    use futures::Stream;
+    use tokio_signal::unix::{Signal, SIGTERM};
+    let logger = logger.clone();
+    ctx.add_message_stream(
+        Signal::new(SIGTERM)
+            .flatten_stream()
+            .map(|_| Terminate {})
+            .map_err(move |e| error!(logger, "Can't listen for SIGTERM: {}", e)),
+    );
+}
+
+// Windows has no SIGHUP; a UI has to ask for a reload there
+#[cfg(target_os = "windows")]
+fn listen_for_hangups(_ctx: &mut Context<UiGateway>, _logger: &Logger) {}
+
+// Nor SIGTERM; a service stop there is a UI's shutdown order
+#[cfg(target_os = "windows")]
+fn listen_for_terminations(_ctx: &mut Context<UiGateway>, _logger: &Logger) {}
+
+impl UiGateway {
+    fn authenticate(&mut self, client_id: u64, unmarshalled: Result<UiMessage, String>) {
+        let websocket_supervisor = self
+            .websocket_supervisor
+            .as_ref()
+            .expect("WebsocketSupervisor is unbound");
+        authenticate_client(
+            &self.auth_token,
+            client_id,
+            unmarshalled,
+            &mut self.authenticated_clients,
+            websocket_supervisor.as_ref(),
+            &self.logger,
+        );
+    }
+
+    fn greet(&mut self, client_id: u64, protocol_version: u32) {
+        let capabilities = UiCapabilities::negotiate(protocol_version);
+        debug!(
+            self.logger,
+            "UI {} speaks protocol version {}; settling on {}",
+            client_id,
+            protocol_version,
+            capabilities.protocol_version
+        );
+        let websocket_supervisor = self
+            .websocket_supervisor
+            .as_ref()
+            .expect("WebsocketSupervisor is unbound");
+        self.protocol_versions
+            .retain(|client_id, _| websocket_supervisor.is_connected(*client_id));
+        if capabilities.protocol_version > 1 {
+            self.protocol_versions
+                .insert(client_id, capabilities.protocol_version);
+        } else {
+            self.protocol_versions.remove(&client_id);
+        }
+        self.subs
+            .as_ref()
+            .expect("UiGateway is unbound")
+            .ui_message_sub
+            .try_send(UiCarrierMessage {
+                client_id,
+                data: UiMessage::Capabilities(capabilities),
+            })
+            .expect("UiGateway is dead");
+    }
+
+    // UIs that never said Hello get the bare messages they've always had
+    fn marshal_for(&self, client_id: u64, ui_message: UiMessage) -> String {
+        let marshalled = if self.protocol_versions.contains_key(&client_id) {
+            self.converter.marshal_enveloped(ui_message)
+        } else {
+            self.converter.marshal(ui_message)
+        };
+        marshalled.expect("Internal error: failed to marshal UiMessage")
+    }
+
+    // Each actor knows some of the statistics; once they've all answered, the UI gets the lot
+    fn gather_node_statistics(&self, client_id: u64) {
+        let ui_message_sub = self
+            .subs
+            .as_ref()
+            .expect("UiGateway is unbound")
+            .ui_message_sub
+            .clone();
+        let logger = self.logger.clone();
+        let future = self.collect_node_statistics().then(move |result| {
+            match result {
+                Ok(statistics) => ui_message_sub
+                    .try_send(UiCarrierMessage {
+                        client_id,
+                        data: UiMessage::NodeStatistics(statistics),
+                    })
+                    .expect("UiGateway is dead"),
+                Err(e) => error!(logger, "Couldn't gather Node statistics: {:?}", e),
+            }
+            Ok(())
+        });
+        actix::spawn(future);
+    }
+
+    // The listeners and DNS are checked here; the actors that own the other components answer for
+    // them
+    fn gather_node_health(&self, client_id: u64) {
+        let subs = self.subs.as_ref().expect("UiGateway is unbound");
+        let ui_message_sub = subs.ui_message_sub.clone();
+        let own_health = vec![
+            self.health_inspector.listeners(&self.listeners),
+            self.health_inspector.dns(),
+        ];
+        let logger = self.logger.clone();
+        let future = future::join_all(
+            subs.health_subs
+                .iter()
+                .map(|sub| sub.send(GetHealthMessage {}))
+                .collect::<Vec<_>>(),
+        )
+        .then(move |result| {
+            match result {
+                Ok(health) => ui_message_sub
+                    .try_send(UiCarrierMessage {
+                        client_id,
+                        data: UiMessage::NodeHealth(NodeHealth::new(
+                            own_health
+                                .into_iter()
+                                .chain(health.into_iter().flatten())
+                                .collect(),
+                        )),
+                    })
+                    .expect("UiGateway is dead"),
+                Err(e) => error!(logger, "Couldn't gather Node health: {:?}", e),
+            }
+            Ok(())
+        });
+        actix::spawn(future);
+    }
+
+    // The test waits on the Node's own DNS server, which may be answering on this very thread, so
+    // it runs on a thread of its own and the report comes back like any other answer
+    fn test_for_dns_leaks(&self, client_id: u64) {
+        let ui_message_sub = self
+            .subs
+            .as_ref()
+            .expect("UiGateway is unbound")
+            .ui_message_sub
+            .clone();
+        let dns_leak_tester = self.dns_leak_tester.clone();
+        let logger = self.logger.clone();
+        thread::spawn(move || {
+            let report = dns_leak_tester.test();
+            info!(logger, "{}", report.explanation());
+            ui_message_sub
+                .try_send(UiCarrierMessage {
+                    client_id,
+                    data: UiMessage::DnsLeakTestResponse(report),
+                })
+                .expect("UiGateway is dead");
+        });
+    }
+
+    fn report_audit_log(&self, client_id: u64, limit_opt: Option<usize>) {
+        let result = self
+            .audit_log_reader
+            .recent(limit_opt.unwrap_or(DEFAULT_RECENT_AUDIT_ENTRIES));
+        self.subs
+            .as_ref()
+            .expect("UiGateway is unbound")
+            .ui_message_sub
+            .try_send(UiCarrierMessage {
+                client_id,
+                data: UiMessage::AuditLog(result),
+            })
+            .expect("UiGateway is dead");
+    }
+
+    // The log level belongs to no actor, so the UiGateway looks after it itself
+    fn gather_configuration(&self, client_id: u64) {
+        let subs = self.subs.as_ref().expect("UiGateway is unbound");
+        let ui_message_sub = subs.ui_message_sub.clone();
+        let own_entries = vec![ConfigurationEntry::new(
+            LOG_LEVEL_PARAMETER,
+            &server_initializer::log_level().to_string().to_lowercase(),
+        )];
+        let logger = self.logger.clone();
+        let future = future::join_all(
+            subs.configuration_subs
+                .iter()
+                .map(|sub| sub.send(GetConfigurationMessage {}))
+                .collect::<Vec<_>>(),
+        )
+        .then(move |result| {
+            match result {
+                Ok(entries) => ui_message_sub
+                    .try_send(UiCarrierMessage {
+                        client_id,
+                        data: UiMessage::Configuration(
+                            own_entries
+                                .into_iter()
+                                .chain(entries.into_iter().flatten())
+                                .collect(),
+                        ),
+                    })
+                    .expect("UiGateway is dead"),
+                Err(e) => error!(logger, "Couldn't gather configuration: {:?}", e),
+            }
+            Ok(())
+        });
+        actix::spawn(future);
+    }
+
+    // Whichever actor owns the named parameter validates and persists the change
+    fn change_configuration(&self, client_id: u64, name: String, value: String) {
+        let subs = self.subs.as_ref().expect("UiGateway is unbound");
+        let ui_message_sub = subs.ui_message_sub.clone();
+        if name == LOG_LEVEL_PARAMETER {
+            let result = change_log_level(&value);
+            if result.is_ok() {
+                info!(self.logger, "Log level set to {}", value);
+            }
+            ui_message_sub
+                .try_send(UiCarrierMessage {
+                    client_id,
+                    data: UiMessage::ConfigurationChangeResponse(result),
+                })
+                .expect("UiGateway is dead");
+            return;
+        }
+        let logger = self.logger.clone();
+        let future = future::join_all(
+            subs.change_configuration_subs
+                .iter()
+                .map(|sub| {
+                    sub.send(ChangeConfigurationMessage {
+                        name: name.clone(),
+                        value: value.clone(),
+                    })
+                })
+                .collect::<Vec<_>>(),
+        )
+        .then(move |result| {
+            match result {
+                Ok(results) => ui_message_sub
+                    .try_send(UiCarrierMessage {
+                        client_id,
+                        data: UiMessage::ConfigurationChangeResponse(
+                            results.into_iter().flatten().next().unwrap_or_else(|| {
+                                Err(format!(
+                                    "{} can't be changed while the Node is running",
+                                    name
+                                ))
+                            }),
+                        ),
+                    })
+                    .expect("UiGateway is dead"),
+                Err(e) => error!(logger, "Couldn't change configuration: {:?}", e),
+            }
+            Ok(())
+        });
+        actix::spawn(future);
+    }
+
+    // Reads the configuration file again and applies whatever has changed in it: the log level
+    // here, anything else by whichever actor owns it. The rest has to wait for a restart.
+    fn reload_configuration(&mut self, client_id_opt: Option<u64>) {
+        let ui_message_sub = self
+            .subs
+            .as_ref()
+            .expect("UiGateway is unbound")
+            .ui_message_sub
+            .clone();
+        let respond = move |result: Result<ConfigurationReload, String>| {
+            if let Some(client_id) = client_id_opt {
+                ui_message_sub
+                    .try_send(UiCarrierMessage {
+                        client_id,
+                        data: UiMessage::ReloadConfigurationResponse(result),
+                    })
+                    .expect("UiGateway is dead");
+            }
+        };
+        let (changed, needs_restart) = match self.read_config_file_changes() {
+            Ok(changes) => changes,
+            Err(e) => {
+                warning!(self.logger, "Configuration not reloaded: {}", e);
+                return respond(Err(e));
+            }
+        };
+        let (log_levels, relayed): (Vec<ConfigurationEntry>, Vec<ConfigurationEntry>) = changed
+            .into_iter()
+            .partition(|entry| entry.name == LOG_LEVEL_PARAMETER);
+        let own_outcomes = log_levels
+            .into_iter()
+            .map(|entry| {
+                let result = change_log_level(&entry.value);
+                (entry, Some(result))
+            })
+            .collect::<Vec<_>>();
+        let subs = self.subs.as_ref().expect("UiGateway is unbound");
+        let logger = self.logger.clone();
+        let future = future::join_all(
+            relayed
+                .into_iter()
+                .map(|entry| {
+                    future::join_all(
+                        subs.change_configuration_subs
+                            .iter()
+                            .map(|sub| {
+                                sub.send(ChangeConfigurationMessage {
+                                    name: entry.name.clone(),
+                                    value: entry.value.clone(),
+                                })
+                            })
+                            .collect::<Vec<_>>(),
+                    )
+                    .map(move |results| (entry, results.into_iter().flatten().next()))
+                })
+                .collect::<Vec<_>>(),
+        )
+        .then(move |result| {
+            match result {
+                Ok(relayed_outcomes) => respond(Ok(tally_reload(
+                    &logger,
+                    own_outcomes.into_iter().chain(relayed_outcomes),
+                    needs_restart,
+                ))),
+                Err(e) => error!(logger, "Couldn't reload configuration: {:?}", e),
+            }
+            Ok(())
+        });
+        actix::spawn(future);
+    }
+
+    // What the file says now that it didn't say last time, and which parameters it no longer
+    // mentions. Anything overridden on the command line or in the environment is left alone.
+    fn read_config_file_changes(
+        &mut self,
+    ) -> Result<(Vec<ConfigurationEntry>, Vec<String>), String> {
+        let source = match self.config_file_opt.as_mut() {
+            Some(source) => source,
+            None => return Err("There's no configuration file to reload".to_string()),
+        };
+        let vcl = ConfigFileVcl::try_new(&source.path, source.user_specified)?;
+        let values = vcl.values();
+        let schema = node_configurator_standard::app();
+        if let Err(e) = MultiConfig::try_new(&schema, vec![Box::new(vcl)]) {
+            return Err(format!(
+                "Configuration file at {:?} isn't valid: {}",
+                source.path,
+                clap_complaint(&e)
+            ));
+        }
+        let mut changed = vec![];
+        for (name, value) in &values {
+            if source.values.get(name) == Some(value) {
+                continue;
+            }
+            if source.overridden.contains(name) {
+                info!(
+                    self.logger,
+                    "{} changed in the configuration file, but the command line or environment overrides it",
+                    name
+                );
+                continue;
+            }
+            changed.push(ConfigurationEntry::new(name, value));
+        }
+        let removed = source
+            .values
+            .keys()
+            .filter(|name| !values.contains_key(*name) && !source.overridden.contains(*name))
+            .cloned()
+            .collect();
+        source.values = values;
+        Ok((changed, removed))
+    }
+
+    fn set_log_level(&self, client_id: u64, level: &str, component_opt: Option<String>) {
+        let result = match &component_opt {
+            None => change_log_level(level),
+            Some(component) => change_component_log_level(component, level),
+        };
+        if result.is_ok() {
+            match &component_opt {
+                None => info!(self.logger, "Log level set to {}", level),
+                Some(component) => {
+                    info!(self.logger, "Log level for {} set to {}", component, level)
+                }
+            }
+        }
+        self.subs
+            .as_ref()
+            .expect("UiGateway is unbound")
+            .ui_message_sub
+            .try_send(UiCarrierMessage {
+                client_id,
+                data: UiMessage::SetLogLevelResponse(result),
+            })
+            .expect("UiGateway is dead");
+    }
+
+    fn collect_node_statistics(&self) -> impl Future<Item = NodeStatistics, Error = MailboxError> {
+        let own_statistics = NodeStatistics {
+            uptime_secs: self.started_at.elapsed().as_secs(),
+            ..NodeStatistics::default()
+        };
+        future::join_all(
+            self.subs
+                .as_ref()
+                .expect("UiGateway is unbound")
+                .node_statistics_subs
+                .iter()
+                .map(|sub| sub.send(GetNodeStatisticsMessage {}))
+                .collect::<Vec<_>>(),
+        )
+        .map(|statistics| {
+            statistics
+                .into_iter()
+                .fold(own_statistics, NodeStatistics::plus)
+        })
+    }
+
+    fn schedule_telemetry(&self, ctx: &mut Context<Self>) {
+        if self.telemetry_sender_opt.is_none() {
+            return;
+        }
+        info!(
+            self.logger,
+            "Sending anonymized telemetry every {} hours: version, OS, and ranges for neighbor count and bytes relayed",
+            self.telemetry_interval.as_secs() / 3600
+        );
+        let interval = self.telemetry_interval;
+        ctx.run_later(self.telemetry_first_report_delay, move |act, ctx| {
+            act.report_telemetry();
+            ctx.run_interval(interval, |act, _| act.report_telemetry());
+        });
+    }
+
+    // The report goes from a thread of its own, so a slow endpoint can't hold up the UiGateway
+    fn report_telemetry(&self) {
+        let telemetry_sender = match &self.telemetry_sender_opt {
+            Some(telemetry_sender) => telemetry_sender.clone(),
+            None => return,
+        };
+        let logger = self.logger.clone();
+        let future = self.collect_node_statistics().then(move |result| {
+            match result {
+                Ok(statistics) => {
+                    let report = TelemetryReport::new(&statistics);
+                    debug!(logger, "Sending telemetry: {:?}", report);
+                    thread::spawn(move || {
+                        if let Err(e) = telemetry_sender.send(&report) {
+                            warning!(logger, "Couldn't send telemetry: {}", e)
+                        }
+                    });
+                }
+                Err(e) => error!(logger, "Couldn't gather telemetry: {:?}", e),
+            }
+            Ok(())
+        });
+        actix::spawn(future);
+    }
+
+    // Streams in progress get a chance to finish before the Node goes away
+    fn drain(&mut self, restart: bool, ctx: &mut Context<Self>) {
+        if self.draining {
+            info!(self.logger, "Already draining; ignoring further orders");
+            return;
+        }
+        self.draining = true;
+        ctx.notify(DrainCheck {
+            restart,
+            deadline: Instant::now() + DRAIN_TIMEOUT,
+        });
+    }
+}
+
+fn parse_log_level(value: &str) -> Result<LevelFilter, String> {
+    LevelFilter::from_str(value).map_err(|_| {
+        format!(
+            "{} must be one of off, error, warn, info, debug or trace, not '{}'",
+            LOG_LEVEL_PARAMETER, value
+        )
+    })
+}
+
+fn change_log_level(value: &str) -> Result<(), String> {
+    server_initializer::set_log_level(parse_log_level(value)?)
+}
+
+fn tally_reload<I>(logger: &Logger, outcomes: I, needs_restart: Vec<String>) -> ConfigurationReload
+where
+    I: IntoIterator<Item = (ConfigurationEntry, Option<Result<(), String>>)>,
+{
+    let mut reload = ConfigurationReload {
+        needs_restart,
+        ..ConfigurationReload::default()
+    };
+    outcomes
+        .into_iter()
+        .for_each(|(entry, result_opt)| match result_opt {
+            Some(Ok(())) => {
+                info!(logger, "Reloaded {} = {}", entry.name, entry.value);
+                reload.applied.push(entry)
+            }
+            Some(Err(e)) => {
+                warning!(logger, "Couldn't reload {}: {}", entry.name, e);
+                reload.rejected.push(e)
+            }
+            None => reload.needs_restart.push(entry.name),
+        });
+    reload.needs_restart.sort();
+    reload.needs_restart.iter().for_each(|name| {
+        warning!(
+            logger,
+            "{} changed in the configuration file, but the change needs a restart to take effect",
+            name
+        )
+    });
+    info!(
+        logger,
+        "Configuration reloaded: {} applied, {} rejected, {} waiting for a restart",
+        reload.applied.len(),
+        reload.rejected.len(),
+        reload.needs_restart.len()
+    );
+    reload
+}
+
+fn change_component_log_level(component: &str, value: &str) -> Result<(), String> {
+    if component.is_empty() || component.contains(char::is_whitespace) {
+        return Err(format!("'{}' isn't the name of a component", component));
+    }
+    let log_level_opt = if value == DEFAULT_COMPONENT_LOG_LEVEL {
+        None
+    } else {
+        Some(parse_log_level(value)?)
+    };
+    server_initializer::set_component_log_level(component, log_level_opt)
+}
+
 fn set_gas_price(ui_gateway: &UiGateway, client_id: u64, gas_price: &str) {
     ui_gateway
         .subs
@@ -189,7 +1242,12 @@ impl Handler<FromUiMessage> for UiGateway {
 
     // JSON messages from external UIs come in here, are translated to UiMessages, and sent to the handler above
     fn handle(&mut self, msg: FromUiMessage, _ctx: &mut Self::Context) -> Self::Result {
-        match self.converter.unmarshal(&msg.json) {
+        let _handling = handling("UiGateway", "FromUiMessage");
+        let unmarshalled = self.converter.unmarshal(&msg.json);
+        if !self.authenticated_clients.contains(&msg.client_id) {
+            return self.authenticate(msg.client_id, unmarshalled);
+        }
+        match unmarshalled {
             Err(e) => warning!(
                 self.logger,
                 "Error unmarshalling message from UI - ignoring: '{}'",
@@ -212,17 +1270,29 @@ impl Handler<FromUiMessage> for UiGateway {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::sub_lib::accountant::{FinancialStatisticsMessage, GetFinancialStatisticsMessage};
+    use crate::audit_log::AuditEntry;
+    use crate::sub_lib::accountant::{
+        FinancialStatisticsMessage, FinancialsFormat, GetFinancialStatisticsMessage,
+        PaymentParameter,
+    };
     use crate::sub_lib::blockchain_bridge::SetWalletPasswordMsg;
     use crate::sub_lib::ui_gateway::UiMessage;
-    use crate::test_utils::find_free_port;
+    use crate::sub_lib::ui_gateway::UI_PROTOCOL_VERSION;
+    use crate::sub_lib::ui_gateway::{ComponentHealth, HealthState};
+    use crate::sub_lib::ui_gateway::{DnsLeakReport, DnsLeakVerdict};
     use crate::test_utils::logging::init_test_logging;
     use crate::test_utils::logging::TestLogHandler;
+    use crate::test_utils::make_dns_leak_report;
     use crate::test_utils::recorder::peer_actors_builder;
     use crate::test_utils::recorder::{make_recorder, Recorder};
     use crate::test_utils::wait_for;
+    use crate::test_utils::{ensure_node_home_directory_exists, find_free_port};
+    use crate::ui_gateway::rest_supervisor::FIRST_REST_CLIENT_ID;
+    use crate::ui_gateway::ui_traffic_converter::BROADCAST;
     use actix::System;
     use std::cell::RefCell;
+    use std::fs;
+    use std::path::PathBuf;
     use std::sync::Arc;
     use std::sync::Mutex;
     use std::thread;
@@ -238,10 +1308,26 @@ mod tests {
                     .recipient::<SetWalletPasswordMsg>(
                 ),
                 blockchain_bridge_set_gas_price_sub: addr.clone().recipient::<SetGasPriceMsg>(),
+                blockchain_bridge_get_blockchain_service_url_sub: addr
+                    .clone()
+                    .recipient::<GetBlockchainServiceUrlMsg>(
+                ),
+                blockchain_bridge_get_wallet_balances_sub: addr
+                    .clone()
+                    .recipient::<GetWalletBalancesMsg>(),
                 accountant_get_financial_statistics_sub: addr
                     .clone()
                     .recipient::<GetFinancialStatisticsMessage>(),
+                accountant_dump_financials_sub: addr.clone().recipient::<DumpFinancialsMessage>(),
+                accountant_set_payment_parameter_sub: addr
+                    .clone()
+                    .recipient::<SetPaymentParameterMessage>(),
                 neighborhood: addr.clone().recipient::<NeighborhoodDotGraphRequest>(),
+                neighborhood_neighbor_list_sub: addr.clone().recipient::<NeighborListRequest>(),
+                node_statistics_subs: vec![],
+                health_subs: vec![],
+                configuration_subs: vec![],
+                change_configuration_subs: vec![],
             }
         }
     }
@@ -249,6 +1335,8 @@ mod tests {
     pub struct UiTrafficConverterMock {
         marshal_parameters: Arc<Mutex<Vec<UiMessage>>>,
         marshal_results: RefCell<Vec<Result<String, String>>>,
+        marshal_enveloped_parameters: Arc<Mutex<Vec<UiMessage>>>,
+        marshal_enveloped_results: RefCell<Vec<Result<String, String>>>,
         unmarshal_parameters: Arc<Mutex<Vec<String>>>,
         unmarshal_results: RefCell<Vec<Result<UiMessage, String>>>,
     }
@@ -259,6 +1347,14 @@ mod tests {
             self.marshal_results.borrow_mut().remove(0)
         }
 
+        fn marshal_enveloped(&self, ui_message: UiMessage) -> Result<String, String> {
+            self.marshal_enveloped_parameters
+                .lock()
+                .unwrap()
+                .push(ui_message);
+            self.marshal_enveloped_results.borrow_mut().remove(0)
+        }
+
         fn unmarshal(&self, json: &str) -> Result<UiMessage, String> {
             self.unmarshal_parameters
                 .lock()
@@ -273,6 +1369,8 @@ mod tests {
             UiTrafficConverterMock {
                 marshal_parameters: Arc::new(Mutex::new(vec![])),
                 marshal_results: RefCell::new(vec![]),
+                marshal_enveloped_parameters: Arc::new(Mutex::new(vec![])),
+                marshal_enveloped_results: RefCell::new(vec![]),
                 unmarshal_parameters: Arc::new(Mutex::new(vec![])),
                 unmarshal_results: RefCell::new(vec![]),
             }
@@ -293,6 +1391,24 @@ mod tests {
             self
         }
 
+        #[allow(dead_code)]
+        fn marshal_enveloped_parameters(
+            mut self,
+            parameters: &Arc<Mutex<Vec<UiMessage>>>,
+        ) -> UiTrafficConverterMock {
+            self.marshal_enveloped_parameters = parameters.clone();
+            self
+        }
+
+        #[allow(dead_code)]
+        fn marshal_enveloped_result(
+            self,
+            result: Result<String, String>,
+        ) -> UiTrafficConverterMock {
+            self.marshal_enveloped_results.borrow_mut().push(result);
+            self
+        }
+
         fn unmarshal_parameters(
             mut self,
             parameters: &Arc<Mutex<Vec<String>>>,
@@ -310,6 +1426,8 @@ mod tests {
     #[derive(Default)]
     struct WebSocketSupervisorMock {
         send_parameters: Arc<Mutex<Vec<(u64, String)>>>,
+        disconnected_clients: HashSet<u64>,
+        disconnect_parameters: Arc<Mutex<Vec<u64>>>,
     }
 
     impl WebSocketSupervisor for WebSocketSupervisorMock {
@@ -319,15 +1437,30 @@ mod tests {
                 .unwrap()
                 .push((client_id, String::from(message_json)));
         }
-    }
 
-    impl WebSocketSupervisorMock {
+        fn is_connected(&self, client_id: u64) -> bool {
+            !self.disconnected_clients.contains(&client_id)
+        }
+
+        fn disconnect(&self, client_id: u64) {
+            self.disconnect_parameters.lock().unwrap().push(client_id);
+        }
+    }
+
+    impl WebSocketSupervisorMock {
         fn new() -> WebSocketSupervisorMock {
             WebSocketSupervisorMock {
                 send_parameters: Arc::new(Mutex::new(vec![])),
+                disconnected_clients: HashSet::new(),
+                disconnect_parameters: Arc::new(Mutex::new(vec![])),
             }
         }
 
+        fn disconnected_client(mut self, client_id: u64) -> WebSocketSupervisorMock {
+            self.disconnected_clients.insert(client_id);
+            self
+        }
+
         fn send_parameters(
             mut self,
             parameters: &Arc<Mutex<Vec<(u64, String)>>>,
@@ -335,25 +1468,71 @@ mod tests {
             self.send_parameters = parameters.clone();
             self
         }
+
+        fn disconnect_parameters(
+            mut self,
+            parameters: &Arc<Mutex<Vec<u64>>>,
+        ) -> WebSocketSupervisorMock {
+            self.disconnect_parameters = parameters.clone();
+            self
+        }
+    }
+
+    #[derive(Default)]
+    struct RestSupervisorMock {
+        send_parameters: Arc<Mutex<Vec<(u64, UiMessage)>>>,
+    }
+
+    impl RestSupervisor for RestSupervisorMock {
+        fn send(&self, client_id: u64, message: UiMessage) {
+            self.send_parameters
+                .lock()
+                .unwrap()
+                .push((client_id, message));
+        }
+    }
+
+    impl RestSupervisorMock {
+        fn send_parameters(
+            mut self,
+            parameters: &Arc<Mutex<Vec<(u64, UiMessage)>>>,
+        ) -> RestSupervisorMock {
+            self.send_parameters = parameters.clone();
+            self
+        }
     }
 
     pub struct ShutdownSupervisorMock {
         shutdown_parameters: Arc<Mutex<Vec<()>>>,
+        restart_parameters: Arc<Mutex<Vec<()>>>,
     }
 
     impl ShutdownSupervisor for ShutdownSupervisorMock {
         fn shutdown(&self) {
             self.shutdown_parameters.lock().unwrap().push(());
         }
+
+        fn restart(&self) {
+            self.restart_parameters.lock().unwrap().push(());
+        }
     }
 
     impl ShutdownSupervisorMock {
         fn new() -> ShutdownSupervisorMock {
             ShutdownSupervisorMock {
                 shutdown_parameters: Arc::new(Mutex::new(vec![])),
+                restart_parameters: Arc::new(Mutex::new(vec![])),
             }
         }
 
+        fn restart_parameters(
+            mut self,
+            parameters: &Arc<Mutex<Vec<()>>>,
+        ) -> ShutdownSupervisorMock {
+            self.restart_parameters = parameters.clone();
+            self
+        }
+
         fn shutdown_parameters(
             mut self,
             parameters: &Arc<Mutex<Vec<()>>>,
@@ -369,6 +1548,12 @@ mod tests {
         let subject = UiGateway::new(&UiGatewayConfig {
             ui_port: find_free_port(),
             node_descriptor: String::from(""),
+            rest_port_opt: None,
+            auth_token: String::new(),
+            config_file_opt: None,
+            dns_server: "127.0.0.1:53".parse().unwrap(),
+            listeners: vec![],
+            telemetry_url_opt: None,
         });
         let system = System::new(
             "receiving_a_get_financial_statistics_message_sends_traffic_to_the_accountant",
@@ -394,12 +1579,101 @@ mod tests {
         )
     }
 
+    #[test]
+    fn receiving_a_dump_financials_request_sends_traffic_to_the_accountant() {
+        let (accountant, _, accountant_recording_arc) = make_recorder();
+        let subject = UiGateway::new(&UiGatewayConfig {
+            ui_port: find_free_port(),
+            node_descriptor: String::from(""),
+            rest_port_opt: None,
+            auth_token: String::new(),
+            config_file_opt: None,
+            dns_server: "127.0.0.1:53".parse().unwrap(),
+            listeners: vec![],
+            telemetry_url_opt: None,
+        });
+        let system =
+            System::new("receiving_a_dump_financials_request_sends_traffic_to_the_accountant");
+        let addr: Addr<UiGateway> = subject.start();
+        let mut peer_actors = peer_actors_builder().accountant(accountant).build();
+        peer_actors.ui_gateway = UiGateway::make_subs_from(&addr);
+        addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        addr.try_send(UiCarrierMessage {
+            client_id: 3,
+            data: UiMessage::DumpFinancialsRequest(FinancialsFormat::Csv),
+        })
+        .unwrap();
+
+        System::current().stop();
+        system.run();
+
+        let accountant_recorder = accountant_recording_arc.lock().unwrap();
+        assert_eq!(
+            accountant_recorder.get_record::<DumpFinancialsMessage>(0),
+            &DumpFinancialsMessage {
+                client_id: 3,
+                format: FinancialsFormat::Csv,
+            }
+        )
+    }
+
+    #[test]
+    fn receiving_a_set_payment_parameter_message_sends_traffic_to_the_accountant() {
+        let (accountant, _, accountant_recording_arc) = make_recorder();
+        let subject = UiGateway::new(&UiGatewayConfig {
+            ui_port: find_free_port(),
+            node_descriptor: String::from(""),
+            rest_port_opt: None,
+            auth_token: String::new(),
+            config_file_opt: None,
+            dns_server: "127.0.0.1:53".parse().unwrap(),
+            listeners: vec![],
+            telemetry_url_opt: None,
+        });
+        let system = System::new(
+            "receiving_a_set_payment_parameter_message_sends_traffic_to_the_accountant",
+        );
+        let addr: Addr<UiGateway> = subject.start();
+        let mut peer_actors = peer_actors_builder().accountant(accountant).build();
+        peer_actors.ui_gateway = UiGateway::make_subs_from(&addr);
+        addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        addr.try_send(UiCarrierMessage {
+            client_id: 5,
+            data: UiMessage::SetPaymentParameter {
+                parameter: PaymentParameter::PayableScanInterval,
+                value: 600,
+            },
+        })
+        .unwrap();
+
+        System::current().stop();
+        system.run();
+
+        let accountant_recorder = accountant_recording_arc.lock().unwrap();
+        assert_eq!(
+            accountant_recorder.get_record::<SetPaymentParameterMessage>(0),
+            &SetPaymentParameterMessage {
+                client_id: 5,
+                parameter: PaymentParameter::PayableScanInterval,
+                value: 600,
+            }
+        )
+    }
+
     #[test]
     fn receiving_a_set_consuming_wallet_password_message_sends_traffic_to_blockchain_bridge() {
         let (blockchain_bridge, _, blockchain_bridge_recorder_arc) = make_recorder();
         let subject = UiGateway::new(&UiGatewayConfig {
             ui_port: find_free_port(),
             node_descriptor: String::from(""),
+            rest_port_opt: None,
+            auth_token: String::new(),
+            config_file_opt: None,
+            dns_server: "127.0.0.1:53".parse().unwrap(),
+            listeners: vec![],
+            telemetry_url_opt: None,
         });
         let system = System::new(
             "receiving_a_set_consuming_wallet_password_message_sends_traffic_to_blockchain_bridge",
@@ -431,6 +1705,7 @@ mod tests {
 
     #[test]
     fn receiving_a_shutdown_message_triggers_the_shutdown_supervisor() {
+        init_test_logging();
         let shutdown_parameters = Arc::new(Mutex::new(vec![]));
         let shutdown_parameters_inside = shutdown_parameters.clone();
 
@@ -440,15 +1715,77 @@ mod tests {
             let mut subject = UiGateway::new(&UiGatewayConfig {
                 ui_port: find_free_port(),
                 node_descriptor: String::from(""),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: None,
+                dns_server: "127.0.0.1:53".parse().unwrap(),
+                listeners: vec![],
+                telemetry_url_opt: None,
             });
             subject.shutdown_supervisor = Box::new(supervisor);
             let system =
                 System::new("receiving_a_shutdown_message_triggers_the_shutdown_supervisor");
+            subject.subs = Some(UiGatewayOutSubs::default());
+            let addr: Addr<UiGateway> = subject.start();
+
+            addr.try_send(UiCarrierMessage {
+                client_id: 0,
+                data: UiMessage::ShutdownMessage,
+            })
+            .unwrap();
+
+            system.run();
+        });
+        wait_for(None, None, || shutdown_parameters.lock().unwrap().len() > 0);
+        TestLogHandler::new()
+            .exists_log_containing("INFO: AuditLog: ui shutdown: UI 0 ordered a shutdown");
+    }
+
+    #[test]
+    fn receiving_a_restart_message_waits_for_active_streams_before_restarting() {
+        let shutdown_parameters = Arc::new(Mutex::new(vec![]));
+        let restart_parameters = Arc::new(Mutex::new(vec![]));
+        let shutdown_parameters_inside = shutdown_parameters.clone();
+        let restart_parameters_inside = restart_parameters.clone();
+        let (proxy_server, _, proxy_server_recording_arc) = make_recorder();
+        let proxy_server = proxy_server
+            .node_statistics_response(NodeStatistics {
+                active_streams_originated: 2,
+                ..NodeStatistics::default()
+            })
+            .node_statistics_response(NodeStatistics::default());
+
+        thread::spawn(move || {
+            let system = System::new(
+                "receiving_a_restart_message_waits_for_active_streams_before_restarting",
+            );
+            let supervisor = ShutdownSupervisorMock::new()
+                .shutdown_parameters(&shutdown_parameters_inside)
+                .restart_parameters(&restart_parameters_inside);
+            let mut subject = UiGateway::new(&UiGatewayConfig {
+                ui_port: find_free_port(),
+                node_descriptor: String::from(""),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: None,
+                dns_server: "127.0.0.1:53".parse().unwrap(),
+                listeners: vec![],
+                telemetry_url_opt: None,
+            });
+            subject.shutdown_supervisor = Box::new(supervisor);
+            subject.subs = Some(UiGatewayOutSubs {
+                node_statistics_subs: vec![proxy_server
+                    .start()
+                    .recipient::<GetNodeStatisticsMessage>()],
+                ..Default::default()
+            });
             let addr: Addr<UiGateway> = subject.start();
-            let mut peer_actors = peer_actors_builder().build();
-            peer_actors.ui_gateway = UiGateway::make_subs_from(&addr);
-            addr.try_send(BindMessage { peer_actors }).unwrap();
 
+            addr.try_send(UiCarrierMessage {
+                client_id: 0,
+                data: UiMessage::RestartMessage,
+            })
+            .unwrap();
             addr.try_send(UiCarrierMessage {
                 client_id: 0,
                 data: UiMessage::ShutdownMessage,
@@ -457,60 +1794,1312 @@ mod tests {
 
             system.run();
         });
-        wait_for(None, None, || shutdown_parameters.lock().unwrap().len() > 0)
+
+        wait_for(None, None, || restart_parameters.lock().unwrap().len() > 0);
+        let proxy_server_recording = proxy_server_recording_arc.lock().unwrap();
+        assert_eq!(proxy_server_recording.len(), 2);
+        assert_eq!(shutdown_parameters.lock().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn receiving_a_get_node_descriptor_message_triggers_a_node_descriptor_response() {
+        let (ui_gateway_recorder, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+
+        thread::spawn(move || {
+            let system = System::new(
+                "receiving_a_get_node_descriptor_message_triggers_a_node_descriptor_response",
+            );
+            let mut subject = UiGateway::new(&UiGatewayConfig {
+                ui_port: find_free_port(),
+                node_descriptor: String::from("NODE-DESCRIPTOR"),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: None,
+                dns_server: "127.0.0.1:53".parse().unwrap(),
+                listeners: vec![],
+                telemetry_url_opt: None,
+            });
+            subject.authenticated_clients.insert(1234);
+            let ui_gateway_recorder_addr = ui_gateway_recorder.start();
+            subject.subs = Some(UiGatewayOutSubs {
+                ui_message_sub: ui_gateway_recorder_addr.recipient::<UiCarrierMessage>(),
+                ..Default::default()
+            });
+            let subject_addr = subject.start();
+            let subject_subs = UiGateway::make_subs_from(&subject_addr);
+
+            let request = serde_json::to_string(&UiMessage::GetNodeDescriptor).unwrap();
+            subject_subs
+                .from_ui_message_sub
+                .try_send(FromUiMessage {
+                    client_id: 1234,
+                    json: request,
+                })
+                .unwrap();
+
+            subject_subs
+                .ui_message_sub
+                .try_send(UiCarrierMessage {
+                    client_id: 1234,
+                    data: UiMessage::GetNodeDescriptor,
+                })
+                .unwrap();
+
+            system.run();
+        });
+
+        ui_gateway_awaiter.await_message_count(2);
+
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(ui_gateway_recording.len(), 2);
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(1),
+            &UiCarrierMessage {
+                client_id: 1234,
+                data: UiMessage::NodeDescriptor("NODE-DESCRIPTOR".to_string())
+            }
+        );
+    }
+
+    #[test]
+    fn receiving_a_get_node_descriptor_share_message_answers_with_a_uri_and_qr_code() {
+        let (ui_gateway_recorder, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        thread::spawn(move || {
+            let system = System::new(
+                "receiving_a_get_node_descriptor_share_message_answers_with_a_uri_and_qr_code",
+            );
+            let mut subject = UiGateway::new(&UiGatewayConfig {
+                ui_port: find_free_port(),
+                node_descriptor: String::from("AQIDBA:1.2.3.4:1234"),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: None,
+                dns_server: "127.0.0.1:53".parse().unwrap(),
+                listeners: vec![],
+                telemetry_url_opt: None,
+            });
+            subject.subs = Some(UiGatewayOutSubs {
+                ui_message_sub: ui_gateway_recorder.start().recipient::<UiCarrierMessage>(),
+                ..Default::default()
+            });
+            let subject_addr = subject.start();
+
+            subject_addr
+                .try_send(UiCarrierMessage {
+                    client_id: 1234,
+                    data: UiMessage::GetNodeDescriptorShare,
+                })
+                .unwrap();
+
+            system.run();
+        });
+
+        ui_gateway_awaiter.await_message_count(1);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 1234,
+                data: UiMessage::NodeDescriptorShare(
+                    NodeDescriptorShare::new("AQIDBA:1.2.3.4:1234").unwrap()
+                )
+            }
+        );
+    }
+
+    #[test]
+    fn receiving_a_get_node_status_message_triggers_a_node_status_response() {
+        let (ui_gateway_recorder, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        thread::spawn(move || {
+            let system =
+                System::new("receiving_a_get_node_status_message_triggers_a_node_status_response");
+            let mut subject = UiGateway::new(&UiGatewayConfig {
+                ui_port: find_free_port(),
+                node_descriptor: String::from("NODE-DESCRIPTOR"),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: None,
+                dns_server: "127.0.0.1:53".parse().unwrap(),
+                listeners: vec![],
+                telemetry_url_opt: None,
+            });
+            subject.subs = Some(UiGatewayOutSubs {
+                ui_message_sub: ui_gateway_recorder.start().recipient::<UiCarrierMessage>(),
+                ..Default::default()
+            });
+            let subject_addr = subject.start();
+
+            subject_addr
+                .try_send(UiCarrierMessage {
+                    client_id: 1234,
+                    data: UiMessage::GetNodeStatus,
+                })
+                .unwrap();
+
+            system.run();
+        });
+
+        ui_gateway_awaiter.await_message_count(1);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 1234,
+                data: UiMessage::NodeStatus(NodeStatus {
+                    version: crate_version!().to_string(),
+                    node_descriptor: "NODE-DESCRIPTOR".to_string(),
+                    uptime_secs: 0,
+                })
+            }
+        );
+    }
+
+    struct DnsLeakTesterMock {
+        report: DnsLeakReport,
+    }
+
+    impl DnsLeakTester for DnsLeakTesterMock {
+        fn test(&self) -> DnsLeakReport {
+            self.report.clone()
+        }
+    }
+
+    #[test]
+    fn receiving_a_dns_leak_test_message_reports_the_result() {
+        init_test_logging();
+        let (ui_gateway_recorder, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        thread::spawn(move || {
+            let system = System::new("receiving_a_dns_leak_test_message_reports_the_result");
+            let mut subject = UiGateway::new(&UiGatewayConfig {
+                ui_port: find_free_port(),
+                node_descriptor: String::from("NODE-DESCRIPTOR"),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: None,
+                dns_server: "127.0.0.1:53".parse().unwrap(),
+                listeners: vec![],
+                telemetry_url_opt: None,
+            });
+            subject.dns_leak_tester = Arc::new(DnsLeakTesterMock {
+                report: make_dns_leak_report(DnsLeakVerdict::Leaking),
+            });
+            subject.subs = Some(UiGatewayOutSubs {
+                ui_message_sub: ui_gateway_recorder.start().recipient::<UiCarrierMessage>(),
+                ..Default::default()
+            });
+            let subject_addr = subject.start();
+
+            subject_addr
+                .try_send(UiCarrierMessage {
+                    client_id: 1234,
+                    data: UiMessage::DnsLeakTest,
+                })
+                .unwrap();
+
+            system.run();
+        });
+
+        ui_gateway_awaiter.await_message_count(1);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 1234,
+                data: UiMessage::DnsLeakTestResponse(make_dns_leak_report(DnsLeakVerdict::Leaking)),
+            }
+        );
+        TestLogHandler::new().exists_log_containing("INFO: UiGateway: DNS is leaking: ");
+    }
+
+    #[test]
+    fn receiving_a_get_node_statistics_message_gathers_statistics_from_other_actors() {
+        let (ui_gateway_recorder, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        thread::spawn(move || {
+            let system = System::new(
+                "receiving_a_get_node_statistics_message_gathers_statistics_from_other_actors",
+            );
+            let accountant = Recorder::new().node_statistics_response(NodeStatistics {
+                bytes_relayed: 1000,
+                bytes_relayed_last_hour: 100,
+                exit_bytes_served: 200,
+                ..NodeStatistics::default()
+            });
+            let proxy_server = Recorder::new().node_statistics_response(NodeStatistics {
+                active_streams_originated: 3,
+                ..NodeStatistics::default()
+            });
+            let proxy_client = Recorder::new().node_statistics_response(NodeStatistics {
+                active_exit_streams: 4,
+                ..NodeStatistics::default()
+            });
+            let neighborhood = Recorder::new().node_statistics_response(NodeStatistics {
+                neighbor_count: 5,
+                ..NodeStatistics::default()
+            });
+            let mut subject = UiGateway::new(&UiGatewayConfig {
+                ui_port: find_free_port(),
+                node_descriptor: String::from(""),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: None,
+                dns_server: "127.0.0.1:53".parse().unwrap(),
+                listeners: vec![],
+                telemetry_url_opt: None,
+            });
+            subject.subs = Some(UiGatewayOutSubs {
+                ui_message_sub: ui_gateway_recorder.start().recipient::<UiCarrierMessage>(),
+                node_statistics_subs: vec![accountant, proxy_server, proxy_client, neighborhood]
+                    .into_iter()
+                    .map(|recorder| recorder.start().recipient::<GetNodeStatisticsMessage>())
+                    .collect(),
+                ..Default::default()
+            });
+            let subject_addr = subject.start();
+
+            subject_addr
+                .try_send(UiCarrierMessage {
+                    client_id: 1234,
+                    data: UiMessage::GetNodeStatistics,
+                })
+                .unwrap();
+
+            system.run();
+        });
+
+        ui_gateway_awaiter.await_message_count(1);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 1234,
+                data: UiMessage::NodeStatistics(NodeStatistics {
+                    uptime_secs: 0,
+                    bytes_relayed: 1000,
+                    bytes_relayed_last_hour: 100,
+                    exit_bytes_served: 200,
+                    active_streams_originated: 3,
+                    active_exit_streams: 4,
+                    neighbor_count: 5,
+                })
+            }
+        );
+    }
+
+    struct TelemetrySenderMock {
+        reports: Arc<Mutex<Vec<TelemetryReport>>>,
+    }
+
+    impl TelemetrySender for TelemetrySenderMock {
+        fn send(&self, report: &TelemetryReport) -> Result<(), String> {
+            self.reports.lock().unwrap().push(report.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn telemetry_is_reported_once_bound_when_asked_for() {
+        init_test_logging();
+        let reports_arc = Arc::new(Mutex::new(vec![]));
+        let reports_inside = reports_arc.clone();
+        thread::spawn(move || {
+            let system = System::new("telemetry_is_reported_once_bound_when_asked_for");
+            let accountant = Recorder::new().node_statistics_response(NodeStatistics {
+                bytes_relayed: 2_345_678,
+                ..NodeStatistics::default()
+            });
+            let neighborhood = Recorder::new().node_statistics_response(NodeStatistics {
+                neighbor_count: 4,
+                ..NodeStatistics::default()
+            });
+            let proxy_client = Recorder::new().node_statistics_response(NodeStatistics::default());
+            let proxy_server = Recorder::new().node_statistics_response(NodeStatistics::default());
+            let mut subject = UiGateway::new(&UiGatewayConfig {
+                ui_port: find_free_port(),
+                node_descriptor: String::from(""),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: None,
+                dns_server: "127.0.0.1:53".parse().unwrap(),
+                listeners: vec![],
+                telemetry_url_opt: None,
+            });
+            subject.telemetry_sender_opt = Some(Arc::new(TelemetrySenderMock {
+                reports: reports_inside,
+            }));
+            subject.telemetry_first_report_delay = Duration::from_millis(10);
+            let addr = subject.start();
+            let mut peer_actors = peer_actors_builder()
+                .accountant(accountant)
+                .neighborhood(neighborhood)
+                .proxy_client(proxy_client)
+                .proxy_server(proxy_server)
+                .build();
+            peer_actors.ui_gateway = UiGateway::make_subs_from(&addr);
+
+            addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            system.run();
+        });
+
+        wait_for(None, Some(5000), || reports_arc.lock().unwrap().len() > 0);
+        assert_eq!(
+            reports_arc.lock().unwrap()[0],
+            TelemetryReport {
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                os: std::env::consts::OS.to_string(),
+                neighbors: "3-5".to_string(),
+                bytes_relayed: "1MB-10MB".to_string(),
+            }
+        );
+        TestLogHandler::new().exists_log_containing(
+            "INFO: UiGateway: Sending anonymized telemetry every 24 hours: version, OS, and ranges for neighbor count and bytes relayed",
+        );
+    }
+
+    struct HealthInspectorMock {
+        listeners_parameters: Arc<Mutex<Vec<Vec<SocketAddr>>>>,
+    }
+
+    impl HealthInspector for HealthInspectorMock {
+        fn listeners(&self, listeners: &[SocketAddr]) -> ComponentHealth {
+            self.listeners_parameters
+                .lock()
+                .unwrap()
+                .push(listeners.to_vec());
+            ComponentHealth::new("listeners", HealthState::Ready, "2 bound")
+        }
+
+        fn dns(&self) -> ComponentHealth {
+            ComponentHealth::new("dns", HealthState::Disabled, "not subverted")
+        }
+    }
+
+    #[test]
+    fn receiving_a_get_node_health_message_gathers_health_from_other_actors() {
+        let (ui_gateway_recorder, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        let listeners_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let listeners_parameters_inside = listeners_parameters_arc.clone();
+        thread::spawn(move || {
+            let system =
+                System::new("receiving_a_get_node_health_message_gathers_health_from_other_actors");
+            let accountant = Recorder::new().health_response(vec![ComponentHealth::new(
+                "database",
+                HealthState::Ready,
+                "writable",
+            )]);
+            let neighborhood = Recorder::new().health_response(vec![ComponentHealth::new(
+                "neighborhood",
+                HealthState::NotReady,
+                "degree 0",
+            )]);
+            let mut subject = UiGateway::new(&UiGatewayConfig {
+                ui_port: find_free_port(),
+                node_descriptor: String::from(""),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: None,
+                dns_server: "127.0.0.1:53".parse().unwrap(),
+                listeners: vec![
+                    "127.0.0.1:80".parse().unwrap(),
+                    "127.0.0.1:1234".parse().unwrap(),
+                ],
+                telemetry_url_opt: None,
+            });
+            subject.health_inspector = Box::new(HealthInspectorMock {
+                listeners_parameters: listeners_parameters_inside,
+            });
+            subject.subs = Some(UiGatewayOutSubs {
+                ui_message_sub: ui_gateway_recorder.start().recipient::<UiCarrierMessage>(),
+                health_subs: vec![accountant, neighborhood]
+                    .into_iter()
+                    .map(|recorder| recorder.start().recipient::<GetHealthMessage>())
+                    .collect(),
+                ..Default::default()
+            });
+            let subject_addr = subject.start();
+
+            subject_addr
+                .try_send(UiCarrierMessage {
+                    client_id: 1234,
+                    data: UiMessage::GetNodeHealth,
+                })
+                .unwrap();
+
+            system.run();
+        });
+
+        ui_gateway_awaiter.await_message_count(1);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 1234,
+                data: UiMessage::NodeHealth(NodeHealth {
+                    ready: false,
+                    components: vec![
+                        ComponentHealth::new("database", HealthState::Ready, "writable"),
+                        ComponentHealth::new("dns", HealthState::Disabled, "not subverted"),
+                        ComponentHealth::new("listeners", HealthState::Ready, "2 bound"),
+                        ComponentHealth::new("neighborhood", HealthState::NotReady, "degree 0"),
+                    ],
+                })
+            }
+        );
+        assert_eq!(
+            *listeners_parameters_arc.lock().unwrap(),
+            vec![vec![
+                SocketAddr::from_str("127.0.0.1:80").unwrap(),
+                SocketAddr::from_str("127.0.0.1:1234").unwrap(),
+            ]]
+        );
+    }
+
+    struct AuditLogReaderMock {
+        recent_parameters: Arc<Mutex<Vec<usize>>>,
+        recent_results: RefCell<Vec<Result<Vec<AuditEntry>, String>>>,
+    }
+
+    impl AuditLogReader for AuditLogReaderMock {
+        fn recent(&self, limit: usize) -> Result<Vec<AuditEntry>, String> {
+            self.recent_parameters.lock().unwrap().push(limit);
+            self.recent_results.borrow_mut().remove(0)
+        }
+    }
+
+    #[test]
+    fn receiving_a_get_audit_log_message_reports_recent_entries() {
+        let (ui_gateway_recorder, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        let recent_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let recent_parameters_inside = recent_parameters_arc.clone();
+        let entry = AuditEntry {
+            timestamp: 1234,
+            source: AuditSource::Ui,
+            action: "shutdown".to_string(),
+            detail: "UI 1 ordered a shutdown".to_string(),
+        };
+        let entry_inside = entry.clone();
+        thread::spawn(move || {
+            let system = System::new("receiving_a_get_audit_log_message_reports_recent_entries");
+            let mut subject = UiGateway::new(&UiGatewayConfig {
+                ui_port: find_free_port(),
+                node_descriptor: String::from(""),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: None,
+                dns_server: "127.0.0.1:53".parse().unwrap(),
+                listeners: vec![],
+                telemetry_url_opt: None,
+            });
+            subject.audit_log_reader = Box::new(AuditLogReaderMock {
+                recent_parameters: recent_parameters_inside,
+                recent_results: RefCell::new(vec![
+                    Ok(vec![entry_inside]),
+                    Err("There's no audit log yet".to_string()),
+                ]),
+            });
+            subject.subs = Some(UiGatewayOutSubs {
+                ui_message_sub: ui_gateway_recorder.start().recipient::<UiCarrierMessage>(),
+                ..Default::default()
+            });
+            let subject_addr = subject.start();
+
+            subject_addr
+                .try_send(UiCarrierMessage {
+                    client_id: 1234,
+                    data: UiMessage::GetAuditLog { limit: None },
+                })
+                .unwrap();
+            subject_addr
+                .try_send(UiCarrierMessage {
+                    client_id: 4321,
+                    data: UiMessage::GetAuditLog { limit: Some(3) },
+                })
+                .unwrap();
+
+            system.run();
+        });
+
+        ui_gateway_awaiter.await_message_count(2);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 1234,
+                data: UiMessage::AuditLog(Ok(vec![entry])),
+            }
+        );
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(1),
+            &UiCarrierMessage {
+                client_id: 4321,
+                data: UiMessage::AuditLog(Err("There's no audit log yet".to_string())),
+            }
+        );
+        assert_eq!(
+            *recent_parameters_arc.lock().unwrap(),
+            vec![DEFAULT_RECENT_AUDIT_ENTRIES, 3]
+        );
+    }
+
+    #[test]
+    fn receiving_a_configuration_get_message_gathers_configuration_from_other_actors() {
+        let (ui_gateway_recorder, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        thread::spawn(move || {
+            let system = System::new(
+                "receiving_a_configuration_get_message_gathers_configuration_from_other_actors",
+            );
+            let accountant = Recorder::new().configuration_response(vec![ConfigurationEntry::new(
+                "payable-scan-interval",
+                "3600",
+            )]);
+            let mut subject = UiGateway::new(&UiGatewayConfig {
+                ui_port: find_free_port(),
+                node_descriptor: String::from(""),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: None,
+                dns_server: "127.0.0.1:53".parse().unwrap(),
+                listeners: vec![],
+                telemetry_url_opt: None,
+            });
+            subject.subs = Some(UiGatewayOutSubs {
+                ui_message_sub: ui_gateway_recorder.start().recipient::<UiCarrierMessage>(),
+                configuration_subs: vec![accountant.start().recipient::<GetConfigurationMessage>()],
+                ..Default::default()
+            });
+            let subject_addr = subject.start();
+
+            subject_addr
+                .try_send(UiCarrierMessage {
+                    client_id: 1234,
+                    data: UiMessage::ConfigurationGet,
+                })
+                .unwrap();
+
+            system.run();
+        });
+
+        ui_gateway_awaiter.await_message_count(1);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 1234,
+                data: UiMessage::Configuration(vec![
+                    ConfigurationEntry::new(
+                        "log-level",
+                        &server_initializer::log_level().to_string().to_lowercase()
+                    ),
+                    ConfigurationEntry::new("payable-scan-interval", "3600"),
+                ])
+            }
+        );
+    }
+
+    #[test]
+    fn receiving_a_configuration_change_message_relays_the_owners_verdict() {
+        let (ui_gateway_recorder, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        let (accountant, _, accountant_recording_arc) = make_recorder();
+        let (proxy_server, _, _) = make_recorder();
+        thread::spawn(move || {
+            let system =
+                System::new("receiving_a_configuration_change_message_relays_the_owners_verdict");
+            let accountant = accountant
+                .change_configuration_response(Some(Err(
+                    "payable-scan-interval must be between 60 and 604800, not 1".to_string(),
+                )))
+                .change_configuration_response(None);
+            let proxy_server = proxy_server.change_configuration_response(None);
+            let mut subject = UiGateway::new(&UiGatewayConfig {
+                ui_port: find_free_port(),
+                node_descriptor: String::from(""),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: None,
+                dns_server: "127.0.0.1:53".parse().unwrap(),
+                listeners: vec![],
+                telemetry_url_opt: None,
+            });
+            subject.subs = Some(UiGatewayOutSubs {
+                ui_message_sub: ui_gateway_recorder.start().recipient::<UiCarrierMessage>(),
+                change_configuration_subs: vec![accountant, proxy_server]
+                    .into_iter()
+                    .map(|recorder| recorder.start().recipient::<ChangeConfigurationMessage>())
+                    .collect(),
+                ..Default::default()
+            });
+            let subject_addr = subject.start();
+
+            vec![
+                ("payable-scan-interval", "1"),
+                ("booga", "1"),
+                ("log-level", "loud"),
+            ]
+            .into_iter()
+            .for_each(|(name, value)| {
+                subject_addr
+                    .try_send(UiCarrierMessage {
+                        client_id: 1234,
+                        data: UiMessage::ConfigurationChange {
+                            name: name.to_string(),
+                            value: value.to_string(),
+                        },
+                    })
+                    .unwrap()
+            });
+
+            system.run();
+        });
+
+        ui_gateway_awaiter.await_message_count(3);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        let mut responses = (0..3)
+            .map(|index| {
+                &ui_gateway_recording
+                    .get_record::<UiCarrierMessage>(index)
+                    .data
+            })
+            .collect::<Vec<&UiMessage>>();
+        // The log level is changed on the spot, so its response may overtake the others
+        responses.sort_by_key(|response| format!("{:?}", response));
+        assert_eq!(
+            responses,
+            vec![
+                &UiMessage::ConfigurationChangeResponse(Err(
+                    "booga can't be changed while the Node is running".to_string()
+                )),
+                &UiMessage::ConfigurationChangeResponse(Err(
+                    "log-level must be one of off, error, warn, info, debug or trace, not 'loud'"
+                        .to_string()
+                )),
+                &UiMessage::ConfigurationChangeResponse(Err(
+                    "payable-scan-interval must be between 60 and 604800, not 1".to_string()
+                )),
+            ]
+        );
+        let accountant_recording = accountant_recording_arc.lock().unwrap();
+        assert_eq!(
+            accountant_recording.get_record::<ChangeConfigurationMessage>(0),
+            &ChangeConfigurationMessage {
+                name: "payable-scan-interval".to_string(),
+                value: "1".to_string(),
+            }
+        );
+        assert_eq!(accountant_recording.len(), 2);
+    }
+
+    fn make_config_file_source(
+        path: PathBuf,
+        values: Vec<(&str, &str)>,
+        overridden: Vec<&str>,
+    ) -> ConfigFileSource {
+        ConfigFileSource {
+            path,
+            user_specified: true,
+            values: values
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            overridden: overridden
+                .into_iter()
+                .map(|name| name.to_string())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn reloading_configuration_applies_what_it_can_and_reports_the_rest() {
+        init_test_logging();
+        let home_dir = ensure_node_home_directory_exists(
+            "ui_gateway",
+            "reloading_configuration_applies_what_it_can_and_reports_the_rest",
+        );
+        let config_file_path = home_dir.join("config.toml");
+        fs::write(
+            &config_file_path,
+            "log-level = \"debug\"\n\
+             payable-scan-interval = 7200\n\
+             payment-suggested-after = 1200\n\
+             clandestine-port = 2345\n\
+             ip = \"2.3.4.5\"\n",
+        )
+        .unwrap();
+        let config_file = make_config_file_source(
+            config_file_path,
+            vec![
+                ("log-level", "warn"),
+                ("payable-scan-interval", "3600"),
+                ("payment-suggested-after", "1200"),
+                ("clandestine-port", "1234"),
+                ("dns-servers", "1.1.1.1"),
+                ("ip", "1.2.3.4"),
+            ],
+            vec!["ip"],
+        );
+        let (ui_gateway_recorder, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        let (accountant, _, accountant_recording_arc) = make_recorder();
+        thread::spawn(move || {
+            let system =
+                System::new("reloading_configuration_applies_what_it_can_and_reports_the_rest");
+            let accountant = accountant
+                .change_configuration_response(None)
+                .change_configuration_response(Some(Ok(())));
+            let mut subject = UiGateway::new(&UiGatewayConfig {
+                ui_port: find_free_port(),
+                node_descriptor: String::from(""),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: Some(config_file),
+                dns_server: "127.0.0.1:53".parse().unwrap(),
+                listeners: vec![],
+                telemetry_url_opt: None,
+            });
+            subject.subs = Some(UiGatewayOutSubs {
+                ui_message_sub: ui_gateway_recorder.start().recipient::<UiCarrierMessage>(),
+                change_configuration_subs: vec![accountant
+                    .start()
+                    .recipient::<ChangeConfigurationMessage>()],
+                ..Default::default()
+            });
+            let subject_addr = subject.start();
+            subject_addr
+                .try_send(UiCarrierMessage {
+                    client_id: 1234,
+                    data: UiMessage::ReloadConfiguration,
+                })
+                .unwrap();
+
+            system.run();
+        });
+
+        ui_gateway_awaiter.await_message_count(1);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 1234,
+                data: UiMessage::ReloadConfigurationResponse(Ok(ConfigurationReload {
+                    applied: vec![ConfigurationEntry::new("payable-scan-interval", "7200")],
+                    // Logging isn't started in tests, so the log level can't be changed
+                    rejected: vec!["Logging has not been started".to_string()],
+                    needs_restart: vec!["clandestine-port".to_string(), "dns-servers".to_string()],
+                })),
+            }
+        );
+        let accountant_recording = accountant_recording_arc.lock().unwrap();
+        assert_eq!(
+            accountant_recording.get_record::<ChangeConfigurationMessage>(0),
+            &ChangeConfigurationMessage {
+                name: "clandestine-port".to_string(),
+                value: "2345".to_string(),
+            }
+        );
+        assert_eq!(
+            accountant_recording.get_record::<ChangeConfigurationMessage>(1),
+            &ChangeConfigurationMessage {
+                name: "payable-scan-interval".to_string(),
+                value: "7200".to_string(),
+            }
+        );
+        assert_eq!(accountant_recording.len(), 2);
+        let tlh = TestLogHandler::new();
+        tlh.exists_log_containing("INFO: UiGateway: Reloaded payable-scan-interval = 7200");
+        tlh.exists_log_containing(
+            "WARN: UiGateway: Couldn't reload log-level: Logging has not been started",
+        );
+        tlh.exists_log_containing("WARN: UiGateway: dns-servers changed in the configuration file, but the change needs a restart to take effect");
+        tlh.exists_log_containing("INFO: UiGateway: ip changed in the configuration file, but the command line or environment overrides it");
+    }
+
+    #[test]
+    fn reloading_configuration_remembers_what_it_read_last_time() {
+        let home_dir = ensure_node_home_directory_exists(
+            "ui_gateway",
+            "reloading_configuration_remembers_what_it_read_last_time",
+        );
+        let config_file_path = home_dir.join("config.toml");
+        fs::write(&config_file_path, "payable-scan-interval = 7200\n").unwrap();
+        let config_file = make_config_file_source(
+            config_file_path,
+            vec![("payable-scan-interval", "3600")],
+            vec![],
+        );
+        let (ui_gateway_recorder, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        let (accountant, _, accountant_recording_arc) = make_recorder();
+        thread::spawn(move || {
+            let system = System::new("reloading_configuration_remembers_what_it_read_last_time");
+            let accountant = accountant.change_configuration_response(Some(Ok(())));
+            let mut subject = UiGateway::new(&UiGatewayConfig {
+                ui_port: find_free_port(),
+                node_descriptor: String::from(""),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: Some(config_file),
+                dns_server: "127.0.0.1:53".parse().unwrap(),
+                listeners: vec![],
+                telemetry_url_opt: None,
+            });
+            subject.subs = Some(UiGatewayOutSubs {
+                ui_message_sub: ui_gateway_recorder.start().recipient::<UiCarrierMessage>(),
+                change_configuration_subs: vec![accountant
+                    .start()
+                    .recipient::<ChangeConfigurationMessage>()],
+                ..Default::default()
+            });
+            let subject_addr = subject.start();
+            (0..2).for_each(|_| {
+                subject_addr
+                    .try_send(UiCarrierMessage {
+                        client_id: 1234,
+                        data: UiMessage::ReloadConfiguration,
+                    })
+                    .unwrap()
+            });
+
+            system.run();
+        });
+
+        ui_gateway_awaiter.await_message_count(2);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(1).data,
+            UiMessage::ReloadConfigurationResponse(Ok(ConfigurationReload::default()))
+        );
+        assert_eq!(accountant_recording_arc.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn reloading_configuration_refuses_a_file_the_node_would_not_start_with() {
+        init_test_logging();
+        let home_dir = ensure_node_home_directory_exists(
+            "ui_gateway",
+            "reloading_configuration_refuses_a_file_the_node_would_not_start_with",
+        );
+        let config_file_path = home_dir.join("config.toml");
+        fs::write(
+            &config_file_path,
+            "payable-scan-interval = 7200\nclandestine-port = \"booga\"\n",
+        )
+        .unwrap();
+        let config_file = make_config_file_source(
+            config_file_path.clone(),
+            vec![("payable-scan-interval", "3600")],
+            vec![],
+        );
+        let (ui_gateway_recorder, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        let (accountant, _, accountant_recording_arc) = make_recorder();
+        thread::spawn(move || {
+            let system =
+                System::new("reloading_configuration_refuses_a_file_the_node_would_not_start_with");
+            let mut subject = UiGateway::new(&UiGatewayConfig {
+                ui_port: find_free_port(),
+                node_descriptor: String::from(""),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: Some(config_file),
+                dns_server: "127.0.0.1:53".parse().unwrap(),
+                listeners: vec![],
+                telemetry_url_opt: None,
+            });
+            subject.subs = Some(UiGatewayOutSubs {
+                ui_message_sub: ui_gateway_recorder.start().recipient::<UiCarrierMessage>(),
+                change_configuration_subs: vec![accountant
+                    .start()
+                    .recipient::<ChangeConfigurationMessage>()],
+                ..Default::default()
+            });
+            let subject_addr = subject.start();
+            subject_addr
+                .try_send(UiCarrierMessage {
+                    client_id: 1234,
+                    data: UiMessage::ReloadConfiguration,
+                })
+                .unwrap();
+
+            system.run();
+        });
+
+        ui_gateway_awaiter.await_message_count(1);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        let complaint = match &ui_gateway_recording.get_record::<UiCarrierMessage>(0).data {
+            UiMessage::ReloadConfigurationResponse(Err(complaint)) => complaint.clone(),
+            other => panic!("Expected a refusal, not {:?}", other),
+        };
+        let prefix = format!("Configuration file at {:?} isn't valid: ", config_file_path);
+        assert!(complaint.starts_with(&prefix), "{}", complaint);
+        assert!(complaint.contains("booga"), "{}", complaint);
+        assert_eq!(accountant_recording_arc.lock().unwrap().len(), 0);
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: UiGateway: Configuration not reloaded: {}",
+            prefix
+        ));
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn a_hangup_reloads_configuration_without_answering_anyone() {
+        init_test_logging();
+        let home_dir = ensure_node_home_directory_exists(
+            "ui_gateway",
+            "a_hangup_reloads_configuration_without_answering_anyone",
+        );
+        let config_file_path = home_dir.join("config.toml");
+        fs::write(&config_file_path, "payable-scan-interval = 7200\n").unwrap();
+        let config_file = make_config_file_source(
+            config_file_path,
+            vec![("payable-scan-interval", "3600")],
+            vec![],
+        );
+        let (ui_gateway_recorder, _, ui_gateway_recording_arc) = make_recorder();
+        let (accountant, accountant_awaiter, accountant_recording_arc) = make_recorder();
+        thread::spawn(move || {
+            let system = System::new("a_hangup_reloads_configuration_without_answering_anyone");
+            let accountant = accountant.change_configuration_response(Some(Ok(())));
+            let mut subject = UiGateway::new(&UiGatewayConfig {
+                ui_port: find_free_port(),
+                node_descriptor: String::from(""),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: Some(config_file),
+                dns_server: "127.0.0.1:53".parse().unwrap(),
+                listeners: vec![],
+                telemetry_url_opt: None,
+            });
+            subject.subs = Some(UiGatewayOutSubs {
+                ui_message_sub: ui_gateway_recorder.start().recipient::<UiCarrierMessage>(),
+                change_configuration_subs: vec![accountant
+                    .start()
+                    .recipient::<ChangeConfigurationMessage>()],
+                ..Default::default()
+            });
+            let subject_addr = subject.start();
+            subject_addr.try_send(HangUp {}).unwrap();
+
+            system.run();
+        });
+
+        accountant_awaiter.await_message_count(1);
+        assert_eq!(
+            accountant_recording_arc
+                .lock()
+                .unwrap()
+                .get_record::<ChangeConfigurationMessage>(0),
+            &ChangeConfigurationMessage {
+                name: "payable-scan-interval".to_string(),
+                value: "7200".to_string(),
+            }
+        );
+        let tlh = TestLogHandler::new();
+        tlh.exists_log_containing("INFO: UiGateway: Received SIGHUP; reloading configuration");
+        tlh.await_log_containing(
+            "INFO: UiGateway: Reloaded payable-scan-interval = 7200",
+            1000,
+        );
+        assert_eq!(ui_gateway_recording_arc.lock().unwrap().len(), 0);
+        tlh.exists_log_containing("INFO: AuditLog: signal configuration-reload: SIGHUP");
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn a_termination_is_audited_before_the_node_shuts_down() {
+        init_test_logging();
+        let shutdown_parameters = Arc::new(Mutex::new(vec![]));
+        let shutdown_parameters_inside = shutdown_parameters.clone();
+        thread::spawn(move || {
+            let system = System::new("a_termination_is_audited_before_the_node_shuts_down");
+            let mut subject = UiGateway::new(&UiGatewayConfig {
+                ui_port: find_free_port(),
+                node_descriptor: String::from(""),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: None,
+                dns_server: "127.0.0.1:53".parse().unwrap(),
+                listeners: vec![],
+                telemetry_url_opt: None,
+            });
+            subject.shutdown_supervisor = Box::new(
+                ShutdownSupervisorMock::new().shutdown_parameters(&shutdown_parameters_inside),
+            );
+            subject.subs = Some(UiGatewayOutSubs::default());
+            let subject_addr = subject.start();
+            subject_addr.try_send(Terminate {}).unwrap();
+
+            system.run();
+        });
+
+        wait_for(None, None, || shutdown_parameters.lock().unwrap().len() > 0);
+        let tlh = TestLogHandler::new();
+        tlh.exists_log_containing("INFO: UiGateway: Received SIGTERM; shutting down");
+        tlh.exists_log_containing("INFO: AuditLog: signal shutdown: SIGTERM");
+    }
+
+    #[test]
+    fn set_log_level_rejects_nonsense_for_a_component() {
+        let (ui_gateway_recorder, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        thread::spawn(move || {
+            let system = System::new("set_log_level_rejects_nonsense_for_a_component");
+            let mut subject = UiGateway::new(&UiGatewayConfig {
+                ui_port: find_free_port(),
+                node_descriptor: String::from(""),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: None,
+                dns_server: "127.0.0.1:53".parse().unwrap(),
+                listeners: vec![],
+                telemetry_url_opt: None,
+            });
+            subject.subs = Some(UiGatewayOutSubs {
+                ui_message_sub: ui_gateway_recorder.start().recipient::<UiCarrierMessage>(),
+                ..Default::default()
+            });
+            let subject_addr = subject.start();
+
+            vec![("loud", "Neighborhood"), ("trace", "Proxy Server")]
+                .into_iter()
+                .for_each(|(level, component)| {
+                    subject_addr
+                        .try_send(UiCarrierMessage {
+                            client_id: 1234,
+                            data: UiMessage::SetLogLevel {
+                                level: level.to_string(),
+                                component: Some(component.to_string()),
+                            },
+                        })
+                        .unwrap()
+                });
+
+            system.run();
+        });
+
+        ui_gateway_awaiter.await_message_count(2);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 1234,
+                data: UiMessage::SetLogLevelResponse(Err(
+                    "log-level must be one of off, error, warn, info, debug or trace, not 'loud'"
+                        .to_string()
+                )),
+            }
+        );
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(1),
+            &UiCarrierMessage {
+                client_id: 1234,
+                data: UiMessage::SetLogLevelResponse(Err(
+                    "'Proxy Server' isn't the name of a component".to_string()
+                )),
+            }
+        );
     }
 
     #[test]
-    fn receiving_a_get_node_descriptor_message_triggers_a_node_descriptor_response() {
-        let (ui_gateway_recorder, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+    fn set_log_level_component_is_optional_on_the_wire() {
+        let result: UiMessage =
+            serde_json::from_str(r#"{"SetLogLevel":{"level":"debug"}}"#).unwrap();
 
-        thread::spawn(move || {
-            let system = System::new(
-                "receiving_a_get_node_descriptor_message_triggers_a_node_descriptor_response",
-            );
-            let mut subject = UiGateway::new(&UiGatewayConfig {
-                ui_port: find_free_port(),
-                node_descriptor: String::from("NODE-DESCRIPTOR"),
-            });
-            let ui_gateway_recorder_addr = ui_gateway_recorder.start();
-            subject.subs = Some(UiGatewayOutSubs {
-                ui_message_sub: ui_gateway_recorder_addr.recipient::<UiCarrierMessage>(),
-                ..Default::default()
-            });
-            let subject_addr = subject.start();
-            let subject_subs = UiGateway::make_subs_from(&subject_addr);
+        assert_eq!(
+            result,
+            UiMessage::SetLogLevel {
+                level: "debug".to_string(),
+                component: None,
+            }
+        );
+    }
 
-            let request = serde_json::to_string(&UiMessage::GetNodeDescriptor).unwrap();
-            subject_subs
-                .from_ui_message_sub
-                .try_send(FromUiMessage {
-                    client_id: 1234,
-                    json: request,
-                })
-                .unwrap();
+    #[test]
+    fn node_events_go_only_to_connected_uis_subscribed_to_them() {
+        let websocket_send_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let system = System::new("node_events_go_only_to_connected_uis_subscribed_to_them");
+        let mut subject = UiGateway::new(&UiGatewayConfig {
+            ui_port: find_free_port(),
+            node_descriptor: String::from(""),
+            rest_port_opt: None,
+            auth_token: String::new(),
+            config_file_opt: None,
+            dns_server: "127.0.0.1:53".parse().unwrap(),
+            listeners: vec![],
+            telemetry_url_opt: None,
+        });
+        subject.websocket_supervisor = Some(Box::new(
+            WebSocketSupervisorMock::new()
+                .send_parameters(&websocket_send_parameters_arc)
+                .disconnected_client(3),
+        ));
+        let subject_addr = subject.start();
+        vec![
+            (
+                1,
+                UiMessage::SubscribeToEvents(vec![NodeEventKind::PaymentSent]),
+            ),
+            (
+                2,
+                UiMessage::SubscribeToEvents(vec![
+                    NodeEventKind::PaymentSent,
+                    NodeEventKind::RouteFailure,
+                ]),
+            ),
+            (
+                3,
+                UiMessage::SubscribeToEvents(vec![NodeEventKind::RouteFailure]),
+            ),
+            (
+                4,
+                UiMessage::SubscribeToEvents(vec![NodeEventKind::RouteFailure]),
+            ),
+            (4, UiMessage::UnsubscribeFromEvents),
+        ]
+        .into_iter()
+        .for_each(|(client_id, data)| {
+            subject_addr
+                .try_send(UiCarrierMessage { client_id, data })
+                .unwrap()
+        });
+        let route_failure = NodeEvent::RouteFailure {
+            reason: "Not enough Nodes".to_string(),
+        };
+        let delinquency_ban = NodeEvent::DelinquencyBan {
+            wallet: "0x0123".to_string(),
+        };
 
-            subject_subs
-                .ui_message_sub
-                .try_send(UiCarrierMessage {
-                    client_id: 1234,
-                    data: UiMessage::GetNodeDescriptor,
-                })
-                .unwrap();
+        subject_addr.try_send(route_failure.clone()).unwrap();
+        subject_addr.try_send(delinquency_ban).unwrap();
 
-            system.run();
+        System::current().stop();
+        system.run();
+        assert_eq!(
+            *websocket_send_parameters_arc.lock().unwrap(),
+            vec![(
+                2,
+                UiTrafficConverterReal::new()
+                    .marshal(UiMessage::NodeEvent(route_failure))
+                    .unwrap()
+            )]
+        );
+    }
+
+    #[test]
+    fn a_ui_that_says_hello_gets_capabilities_and_enveloped_messages() {
+        let websocket_send_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let (ui_gateway, _, ui_gateway_recording_arc) = make_recorder();
+        let system = System::new("a_ui_that_says_hello_gets_capabilities_and_enveloped_messages");
+        let mut subject = UiGateway::new(&UiGatewayConfig {
+            ui_port: find_free_port(),
+            node_descriptor: String::from(""),
+            rest_port_opt: None,
+            auth_token: String::new(),
+            config_file_opt: None,
+            dns_server: "127.0.0.1:53".parse().unwrap(),
+            listeners: vec![],
+            telemetry_url_opt: None,
+        });
+        subject.websocket_supervisor = Some(Box::new(
+            WebSocketSupervisorMock::new().send_parameters(&websocket_send_parameters_arc),
+        ));
+        subject.subs = Some(UiGatewayOutSubs {
+            ui_message_sub: ui_gateway.start().recipient::<UiCarrierMessage>(),
+            ..UiGatewayOutSubs::default()
         });
+        let subject_addr = subject.start();
 
-        ui_gateway_awaiter.await_message_count(2);
+        vec![
+            (
+                1,
+                UiMessage::Hello {
+                    protocol_version: UI_PROTOCOL_VERSION + 1,
+                },
+            ),
+            (
+                2,
+                UiMessage::Hello {
+                    protocol_version: 1,
+                },
+            ),
+            (1, UiMessage::NodeDescriptor("descriptor".to_string())),
+            (2, UiMessage::NodeDescriptor("descriptor".to_string())),
+            (3, UiMessage::NodeDescriptor("descriptor".to_string())),
+        ]
+        .into_iter()
+        .for_each(|(client_id, data)| {
+            subject_addr
+                .try_send(UiCarrierMessage { client_id, data })
+                .unwrap()
+        });
 
+        System::current().stop();
+        system.run();
         let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
-        assert_eq!(ui_gateway_recording.len(), 2);
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 1,
+                data: UiMessage::Capabilities(UiCapabilities::negotiate(UI_PROTOCOL_VERSION)),
+            }
+        );
         assert_eq!(
             ui_gateway_recording.get_record::<UiCarrierMessage>(1),
             &UiCarrierMessage {
-                client_id: 1234,
-                data: UiMessage::NodeDescriptor("NODE-DESCRIPTOR".to_string())
+                client_id: 2,
+                data: UiMessage::Capabilities(UiCapabilities::negotiate(1)),
             }
         );
+        let converter = UiTrafficConverterReal::new();
+        let enveloped = converter
+            .marshal_enveloped(UiMessage::NodeDescriptor("descriptor".to_string()))
+            .unwrap();
+        let bare = converter
+            .marshal(UiMessage::NodeDescriptor("descriptor".to_string()))
+            .unwrap();
+        assert_eq!(
+            *websocket_send_parameters_arc.lock().unwrap(),
+            vec![(1, enveloped), (2, bare.clone()), (3, bare)]
+        );
+    }
+
+    #[test]
+    fn responses_to_rest_requests_are_directed_to_rest_supervisor() {
+        let websocket_send_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let rest_send_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let system = System::new("responses_to_rest_requests_are_directed_to_rest_supervisor");
+        let mut subject = UiGateway::new(&UiGatewayConfig {
+            ui_port: find_free_port(),
+            node_descriptor: String::from(""),
+            rest_port_opt: None,
+            auth_token: String::new(),
+            config_file_opt: None,
+            dns_server: "127.0.0.1:53".parse().unwrap(),
+            listeners: vec![],
+            telemetry_url_opt: None,
+        });
+        subject.websocket_supervisor = Some(Box::new(
+            WebSocketSupervisorMock::new().send_parameters(&websocket_send_parameters_arc),
+        ));
+        subject.rest_supervisor = Some(Box::new(
+            RestSupervisorMock::default().send_parameters(&rest_send_parameters_arc),
+        ));
+        let subject_addr = subject.start();
+
+        subject_addr
+            .try_send(UiCarrierMessage {
+                client_id: FIRST_REST_CLIENT_ID + 5,
+                data: UiMessage::SetGasPriceResponse(true),
+            })
+            .unwrap();
+
+        System::current().stop();
+        system.run();
+        assert_eq!(
+            *rest_send_parameters_arc.lock().unwrap(),
+            vec![(
+                FIRST_REST_CLIENT_ID + 5,
+                UiMessage::SetGasPriceResponse(true)
+            )]
+        );
+        assert_eq!(
+            websocket_send_parameters_arc.lock().unwrap().is_empty(),
+            true
+        );
     }
 
     #[test]
@@ -522,6 +3111,12 @@ mod tests {
         let mut subject = UiGateway::new(&UiGatewayConfig {
             ui_port: find_free_port(),
             node_descriptor: String::from(""),
+            rest_port_opt: None,
+            auth_token: String::new(),
+            config_file_opt: None,
+            dns_server: "127.0.0.1:53".parse().unwrap(),
+            listeners: vec![],
+            telemetry_url_opt: None,
         });
         subject.websocket_supervisor = Some(Box::new(
             WebSocketSupervisorMock::new().send_parameters(&receive_parameters_arc),
@@ -574,6 +3169,12 @@ mod tests {
         let mut subject = UiGateway::new(&UiGatewayConfig {
             ui_port: find_free_port(),
             node_descriptor: String::from(""),
+            rest_port_opt: None,
+            auth_token: String::new(),
+            config_file_opt: None,
+            dns_server: "127.0.0.1:53".parse().unwrap(),
+            listeners: vec![],
+            telemetry_url_opt: None,
         });
         subject.websocket_supervisor = Some(Box::new(
             WebSocketSupervisorMock::new().send_parameters(&receive_parameters_arc),
@@ -620,6 +3221,12 @@ mod tests {
         let subject = UiGateway::new(&UiGatewayConfig {
             ui_port: find_free_port(),
             node_descriptor: String::from(""),
+            rest_port_opt: None,
+            auth_token: String::new(),
+            config_file_opt: None,
+            dns_server: "127.0.0.1:53".parse().unwrap(),
+            listeners: vec![],
+            telemetry_url_opt: None,
         });
         let system =
             System::new("receiving_a_set_gas_price_message_sends_traffic_to_blockchain_bridge");
@@ -648,6 +3255,82 @@ mod tests {
         )
     }
 
+    #[test]
+    fn receiving_a_get_blockchain_service_url_message_sends_traffic_to_blockchain_bridge() {
+        let (blockchain_bridge, _, blockchain_bridge_recorder_arc) = make_recorder();
+        let subject = UiGateway::new(&UiGatewayConfig {
+            ui_port: find_free_port(),
+            node_descriptor: String::from(""),
+            rest_port_opt: None,
+            auth_token: String::new(),
+            config_file_opt: None,
+            dns_server: "127.0.0.1:53".parse().unwrap(),
+            listeners: vec![],
+            telemetry_url_opt: None,
+        });
+        let system = System::new(
+            "receiving_a_get_blockchain_service_url_message_sends_traffic_to_blockchain_bridge",
+        );
+        let addr: Addr<UiGateway> = subject.start();
+        let mut peer_actors = peer_actors_builder()
+            .blockchain_bridge(blockchain_bridge)
+            .build();
+        peer_actors.ui_gateway = UiGateway::make_subs_from(&addr);
+        addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        addr.try_send(UiCarrierMessage {
+            client_id: 5,
+            data: UiMessage::GetBlockchainServiceUrl,
+        })
+        .unwrap();
+
+        System::current().stop();
+        system.run();
+        let blockchain_bridge_recorder = blockchain_bridge_recorder_arc.lock().unwrap();
+        assert_eq!(
+            blockchain_bridge_recorder.get_record::<GetBlockchainServiceUrlMsg>(0),
+            &GetBlockchainServiceUrlMsg { client_id: 5 }
+        )
+    }
+
+    #[test]
+    fn receiving_a_get_wallet_balances_message_sends_traffic_to_blockchain_bridge() {
+        let (blockchain_bridge, _, blockchain_bridge_recorder_arc) = make_recorder();
+        let subject = UiGateway::new(&UiGatewayConfig {
+            ui_port: find_free_port(),
+            node_descriptor: String::from(""),
+            rest_port_opt: None,
+            auth_token: String::new(),
+            config_file_opt: None,
+            dns_server: "127.0.0.1:53".parse().unwrap(),
+            listeners: vec![],
+            telemetry_url_opt: None,
+        });
+        let system = System::new(
+            "receiving_a_get_wallet_balances_message_sends_traffic_to_blockchain_bridge",
+        );
+        let addr: Addr<UiGateway> = subject.start();
+        let mut peer_actors = peer_actors_builder()
+            .blockchain_bridge(blockchain_bridge)
+            .build();
+        peer_actors.ui_gateway = UiGateway::make_subs_from(&addr);
+        addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        addr.try_send(UiCarrierMessage {
+            client_id: 5,
+            data: UiMessage::GetWalletBalances,
+        })
+        .unwrap();
+
+        System::current().stop();
+        system.run();
+        let blockchain_bridge_recorder = blockchain_bridge_recorder_arc.lock().unwrap();
+        assert_eq!(
+            blockchain_bridge_recorder.get_record::<GetWalletBalancesMsg>(0),
+            &GetWalletBalancesMsg { client_id: 5 }
+        )
+    }
+
     #[test]
     fn set_gas_price_response_message_is_directed_to_websocket_supervisor() {
         let (ui_gateway_recorder, _, _) = make_recorder();
@@ -658,6 +3341,12 @@ mod tests {
         let mut subject = UiGateway::new(&UiGatewayConfig {
             ui_port: find_free_port(),
             node_descriptor: "".to_string(),
+            rest_port_opt: None,
+            auth_token: String::new(),
+            config_file_opt: None,
+            dns_server: "127.0.0.1:53".parse().unwrap(),
+            listeners: vec![],
+            telemetry_url_opt: None,
         });
         subject.websocket_supervisor = Some(Box::new(
             WebSocketSupervisorMock::new().send_parameters(&receive_parameters_arc),
@@ -698,6 +3387,60 @@ mod tests {
         )
     }
 
+    #[test]
+    fn route_capability_diagnosis_is_broadcast_by_websocket_supervisor() {
+        let (ui_gateway_recorder, _, _) = make_recorder();
+        let receive_parameters_arc = Arc::new(Mutex::new(vec![]));
+
+        let system = System::new("route_capability_diagnosis_is_broadcast_by_websocket_supervisor");
+        let mut subject = UiGateway::new(&UiGatewayConfig {
+            ui_port: find_free_port(),
+            node_descriptor: "".to_string(),
+            rest_port_opt: None,
+            auth_token: String::new(),
+            config_file_opt: None,
+            dns_server: "127.0.0.1:53".parse().unwrap(),
+            listeners: vec![],
+            telemetry_url_opt: None,
+        });
+        subject.websocket_supervisor = Some(Box::new(
+            WebSocketSupervisorMock::new().send_parameters(&receive_parameters_arc),
+        ));
+        let ui_gateway_recorder_addr = ui_gateway_recorder.start();
+        subject.subs = Some(UiGatewayOutSubs {
+            ui_message_sub: ui_gateway_recorder_addr.recipient::<UiCarrierMessage>(),
+            ..Default::default()
+        });
+        let subject_addr = subject.start();
+        let subject_subs = UiGateway::make_subs_from(&subject_addr);
+
+        subject_subs
+            .ui_message_sub
+            .try_send(UiCarrierMessage {
+                client_id: BROADCAST,
+                data: UiMessage::RouteCapabilityDiagnosis("diagnosis".to_string()),
+            })
+            .unwrap();
+
+        System::current().stop();
+        system.run();
+
+        wait_for(None, None, || {
+            receive_parameters_arc.lock().unwrap().len() > 0
+        });
+        let receive_parameters = receive_parameters_arc.lock().unwrap();
+        assert_eq!(
+            receive_parameters.get(0).unwrap(),
+            &(
+                BROADCAST,
+                serde_json::to_string(&UiMessage::RouteCapabilityDiagnosis(
+                    "diagnosis".to_string()
+                ))
+                .unwrap()
+            )
+        );
+    }
+
     #[test]
     fn financial_statistics_response_message_is_directed_to_websocket_supervisor() {
         let (ui_gateway_recorder, _, _) = make_recorder();
@@ -709,6 +3452,12 @@ mod tests {
         let mut subject = UiGateway::new(&UiGatewayConfig {
             ui_port: find_free_port(),
             node_descriptor: String::from(""),
+            rest_port_opt: None,
+            auth_token: String::new(),
+            config_file_opt: None,
+            dns_server: "127.0.0.1:53".parse().unwrap(),
+            listeners: vec![],
+            telemetry_url_opt: None,
         });
         subject.websocket_supervisor = Some(Box::new(
             WebSocketSupervisorMock::new().send_parameters(&receive_parameters_arc),
@@ -728,6 +3477,8 @@ mod tests {
                 data: UiMessage::FinancialStatisticsResponse(FinancialStatisticsMessage {
                     pending_credit: 1_000_000_001,
                     pending_debt: 2_000_000_001,
+                    profiles: vec![],
+                    payments_deferred: None,
                 }),
             })
             .unwrap();
@@ -750,7 +3501,9 @@ mod tests {
                 serde_json::to_string(&UiMessage::FinancialStatisticsResponse(
                     FinancialStatisticsMessage {
                         pending_credit: 1_000_000_001,
-                        pending_debt: 2_000_000_001
+                        pending_debt: 2_000_000_001,
+                        profiles: vec![],
+                        payments_deferred: None,
                     }
                 ))
                 .unwrap()
@@ -770,7 +3523,14 @@ mod tests {
             let mut subject = UiGateway::new(&UiGatewayConfig {
                 ui_port: find_free_port(),
                 node_descriptor: String::from(""),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: None,
+                dns_server: "127.0.0.1:53".parse().unwrap(),
+                listeners: vec![],
+                telemetry_url_opt: None,
             });
+            subject.authenticated_clients.insert(42);
             subject.converter = Box::new(handler);
             let system = System::new("good_from_ui_message_is_unmarshalled_and_resent");
             let addr: Addr<UiGateway> = subject.start();
@@ -813,7 +3573,14 @@ mod tests {
             let mut subject = UiGateway::new(&UiGatewayConfig {
                 ui_port: find_free_port(),
                 node_descriptor: String::from(""),
+                rest_port_opt: None,
+                auth_token: String::new(),
+                config_file_opt: None,
+                dns_server: "127.0.0.1:53".parse().unwrap(),
+                listeners: vec![],
+                telemetry_url_opt: None,
             });
+            subject.authenticated_clients.insert(0);
             subject.converter = Box::new(handler);
             let system = System::new("bad_from_ui_message_is_logged_and_ignored");
             let addr: Addr<UiGateway> = subject.start();
@@ -836,13 +3603,80 @@ mod tests {
         assert_eq!(ui_gateway_recording.len(), 0);
     }
 
+    #[test]
+    fn uis_that_dont_open_with_the_right_token_are_disconnected() {
+        init_test_logging();
+        let disconnect_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let (ui_gateway, _, ui_gateway_recording_arc) = make_recorder();
+        let system = System::new("uis_that_dont_open_with_the_right_token_are_disconnected");
+        let mut subject = UiGateway::new(&UiGatewayConfig {
+            ui_port: find_free_port(),
+            node_descriptor: String::from(""),
+            rest_port_opt: None,
+            auth_token: String::from("secret"),
+            config_file_opt: None,
+            dns_server: "127.0.0.1:53".parse().unwrap(),
+            listeners: vec![],
+            telemetry_url_opt: None,
+        });
+        subject.websocket_supervisor = Some(Box::new(
+            WebSocketSupervisorMock::new().disconnect_parameters(&disconnect_parameters_arc),
+        ));
+        subject.subs = Some(UiGatewayOutSubs {
+            ui_message_sub: ui_gateway.start().recipient::<UiCarrierMessage>(),
+            ..UiGatewayOutSubs::default()
+        });
+        let addr: Addr<UiGateway> = subject.start();
+        let converter = UiTrafficConverterReal::new();
+
+        vec![
+            (1, UiMessage::GetNodeDescriptor),
+            (2, UiMessage::Authenticate("wrong".to_string())),
+            (3, UiMessage::Authenticate("secret".to_string())),
+            (3, UiMessage::GetNodeStatus),
+            (2, UiMessage::GetNodeStatus),
+        ]
+        .into_iter()
+        .for_each(|(client_id, message)| {
+            addr.try_send(FromUiMessage {
+                client_id,
+                json: converter.marshal(message).unwrap(),
+            })
+            .unwrap()
+        });
+
+        System::current().stop();
+        system.run();
+        assert_eq!(*disconnect_parameters_arc.lock().unwrap(), vec![1, 2, 2]);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 3,
+                data: UiMessage::GetNodeStatus
+            }
+        );
+        assert_eq!(ui_gateway_recording.len(), 1);
+        TestLogHandler::new().exists_log_containing("INFO: UiGateway: UI 3 authenticated");
+        TestLogHandler::new().exists_log_containing(
+            "WARN: UiGateway: UI 1 didn't open with the right authentication token: disconnecting",
+        );
+    }
+
     #[test]
     fn request_for_dot_graph_forwards_request_to_neighbor() {
         let (neighborhood, _, neighborhood_recorder_arc) = make_recorder();
-        let subject = UiGateway::new(&UiGatewayConfig {
+        let mut subject = UiGateway::new(&UiGatewayConfig {
             ui_port: find_free_port(),
             node_descriptor: String::from(""),
+            rest_port_opt: None,
+            auth_token: String::new(),
+            config_file_opt: None,
+            dns_server: "127.0.0.1:53".parse().unwrap(),
+            listeners: vec![],
+            telemetry_url_opt: None,
         });
+        subject.authenticated_clients.insert(0);
         let system = System::new("request_for_dot_graph_forwards_request_to_neighbor");
         let addr: Addr<UiGateway> = subject.start();
         let mut peer_actors = peer_actors_builder().neighborhood(neighborhood).build();
@@ -863,4 +3697,42 @@ mod tests {
             &NeighborhoodDotGraphRequest { client_id: 0 }
         );
     }
+
+    #[test]
+    fn request_for_neighbor_list_forwards_request_to_neighborhood() {
+        let (neighborhood, _, neighborhood_recorder_arc) = make_recorder();
+        let mut subject = UiGateway::new(&UiGatewayConfig {
+            ui_port: find_free_port(),
+            node_descriptor: String::from(""),
+            rest_port_opt: None,
+            auth_token: String::new(),
+            config_file_opt: None,
+            dns_server: "127.0.0.1:53".parse().unwrap(),
+            listeners: vec![],
+            telemetry_url_opt: None,
+        });
+        subject.authenticated_clients.insert(1234);
+        let system = System::new("request_for_neighbor_list_forwards_request_to_neighborhood");
+        let addr: Addr<UiGateway> = subject.start();
+        let mut peer_actors = peer_actors_builder().neighborhood(neighborhood).build();
+        peer_actors.ui_gateway = UiGateway::make_subs_from(&addr);
+        addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        let json = UiTrafficConverterReal::new()
+            .marshal(UiMessage::GetNeighborList)
+            .unwrap();
+        addr.try_send(FromUiMessage {
+            client_id: 1234,
+            json,
+        })
+        .unwrap();
+
+        System::current().stop();
+        system.run();
+        let neighborhood_recorder = neighborhood_recorder_arc.lock().unwrap();
+        assert_eq!(
+            neighborhood_recorder.get_record::<NeighborListRequest>(0),
+            &NeighborListRequest { client_id: 1234 }
+        );
+    }
 }
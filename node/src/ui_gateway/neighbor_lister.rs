@@ -0,0 +1,367 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use crate::node_configurator::{
+    app_head, chain_arg, data_directory_arg, instance_name_arg, make_multi_config, profile_arg,
+    real_user_arg,
+};
+use crate::sub_lib::main_tools::StdStreams;
+use crate::sub_lib::neighborhood::NeighborSummary;
+use crate::sub_lib::ui_gateway::{UiMessage, DEFAULT_UI_PORT};
+use crate::ui_gateway::auth_token::read_ui_auth_token;
+use crate::ui_gateway::ui_traffic_converter::{UiTrafficConverter, UiTrafficConverterReal};
+use clap::{value_t, App, Arg};
+use lazy_static::lazy_static;
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use websocket::client::builder::ClientBuilder;
+use websocket::sync::Client;
+use websocket::OwnedMessage;
+
+const LIST_NEIGHBORS_HELP: &str =
+    "Ask the Node running on this machine for its current neighbors and print them as a table.";
+
+const UI_PORT_HELP: &str = "The UI port of the running Node to ask.";
+
+pub const UI_PROTOCOL: &str = "PrometheusNode-UI";
+
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+lazy_static! {
+    static ref DEFAULT_UI_PORT_VALUE: String = DEFAULT_UI_PORT.to_string();
+}
+
+pub fn list_neighbors(args: &Vec<String>, streams: &mut StdStreams) -> i32 {
+    let (ui_port, data_directory) = distill_args(args);
+    match fetch_neighbors(ui_port, &data_directory, RESPONSE_TIMEOUT) {
+        Ok(neighbors) => {
+            streams
+                .stdout
+                .write_all(render_neighbors(&neighbors).as_bytes())
+                .expect("Couldn't write neighbors to stdout");
+            streams
+                .stdout
+                .flush()
+                .expect("Couldn't flush neighbors to stdout");
+            0
+        }
+        Err(e) => {
+            writeln!(streams.stderr, "Couldn't list neighbors: {}", e)
+                .expect("Couldn't write to stderr");
+            1
+        }
+    }
+}
+
+fn fetch_neighbors(
+    ui_port: u16,
+    data_directory: &Path,
+    timeout: Duration,
+) -> Result<Vec<NeighborSummary>, String> {
+    let mut client = connect_to_node(ui_port, data_directory, timeout)?;
+    send_to_node(&mut client, UiMessage::GetNeighborList)?;
+    let result = receive_from_node(&mut client, |message| match message {
+        UiMessage::NeighborList(neighbors) => Some(neighbors),
+        _ => None,
+    });
+    let _ = client.shutdown();
+    result
+}
+
+// Connects to the Node's UI port and authenticates with the token the Node left in its data directory
+pub fn connect_to_node(
+    ui_port: u16,
+    data_directory: &Path,
+    timeout: Duration,
+) -> Result<Client<TcpStream>, String> {
+    let mut client = ClientBuilder::new(&format!("ws://127.0.0.1:{}", ui_port))
+        .map_err(|e| format!("{}", e))?
+        .add_protocol(UI_PROTOCOL)
+        .connect_insecure()
+        .map_err(|e| format!("no Node is listening on UI port {}: {}", ui_port, e))?;
+    client
+        .stream_ref()
+        .set_read_timeout(Some(timeout))
+        .map_err(|e| format!("{}", e))?;
+    let token = read_ui_auth_token(data_directory)?;
+    send_to_node(&mut client, UiMessage::Authenticate(token))?;
+    Ok(client)
+}
+
+pub fn send_to_node(client: &mut Client<TcpStream>, message: UiMessage) -> Result<(), String> {
+    let request = UiTrafficConverterReal::new().marshal(message)?;
+    client
+        .send_message(&OwnedMessage::Text(request))
+        .map_err(|e| format!("{}", e))
+}
+
+// Skips whatever else the Node says until it says what pick is looking for
+pub fn receive_from_node<T, F>(client: &mut Client<TcpStream>, pick: F) -> Result<T, String>
+where
+    F: Fn(UiMessage) -> Option<T>,
+{
+    let converter = UiTrafficConverterReal::new();
+    loop {
+        match client.recv_message() {
+            Ok(OwnedMessage::Text(json)) => {
+                if let Some(answer) = converter.unmarshal(&json).ok().and_then(&pick) {
+                    return Ok(answer);
+                }
+            }
+            Ok(OwnedMessage::Close(_)) => return Err("the Node hung up".to_string()),
+            Ok(_) => (),
+            Err(e) => return Err(format!("no answer from the Node: {}", e)),
+        }
+    }
+}
+
+pub fn render_neighbors(neighbors: &[NeighborSummary]) -> String {
+    if neighbors.is_empty() {
+        return "No neighbors\n".to_string();
+    }
+    let header = vec![
+        "KEY".to_string(),
+        "ADDRESS".to_string(),
+        "NEIGHBOR FOR".to_string(),
+        "VERSION".to_string(),
+        "LAST GOSSIP".to_string(),
+        "REPUTATION".to_string(),
+    ];
+    let rows = neighbors
+        .iter()
+        .map(|neighbor| {
+            vec![
+                neighbor.key_fingerprint.clone(),
+                neighbor
+                    .node_addr
+                    .clone()
+                    .unwrap_or_else(|| "-".to_string()),
+                duration_string(neighbor.neighbor_for_secs),
+                neighbor.version.to_string(),
+                match neighbor.secs_since_last_gossip {
+                    Some(secs) => format!("{} ago", duration_string(secs)),
+                    None => "never".to_string(),
+                },
+                neighbor.reputation.to_string(),
+            ]
+        })
+        .collect::<Vec<Vec<String>>>();
+    let widths = (0..header.len())
+        .map(|column| {
+            rows.iter()
+                .chain(vec![&header])
+                .map(|row| row[column].len())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect::<Vec<usize>>();
+    vec![&header]
+        .into_iter()
+        .chain(rows.iter())
+        .map(|row| {
+            let line = row
+                .iter()
+                .zip(widths.iter())
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect::<Vec<String>>()
+                .join("  ");
+            format!("{}\n", line.trim_end())
+        })
+        .collect()
+}
+
+pub fn duration_string(secs: u64) -> String {
+    match secs {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m {:02}s", s / 60, s % 60),
+        s if s < 86400 => format!("{}h {:02}m", s / 3600, (s % 3600) / 60),
+        s => format!("{}d {:02}h", s / 86400, (s % 86400) / 3600),
+    }
+}
+
+pub fn app() -> App<'static, 'static> {
+    app_head()
+        .about(LIST_NEIGHBORS_HELP)
+        .usage("PrometheusNode list-neighbors [OPTIONS]")
+        .arg(
+            Arg::with_name("list-neighbors")
+                .long("list-neighbors")
+                .required(true)
+                .takes_value(false)
+                .hidden(true),
+        )
+        .arg(
+            Arg::with_name("ui-port")
+                .long("ui-port")
+                .value_name("UI-PORT")
+                .takes_value(true)
+                .default_value(&DEFAULT_UI_PORT_VALUE)
+                .help(UI_PORT_HELP),
+        )
+        .arg(chain_arg())
+        .arg(data_directory_arg())
+        .arg(profile_arg())
+        .arg(instance_name_arg())
+        .arg(real_user_arg())
+}
+
+fn distill_args(args: &Vec<String>) -> (u16, PathBuf) {
+    let app = app();
+    let multi_config = make_multi_config(&app, args, None);
+    let ui_port =
+        value_m!(multi_config, "ui-port", u16).expect("ui-port is not properly defaulted");
+    let (_, data_directory, _) =
+        crate::node_configurator::real_user_data_directory_and_chain_id(&multi_config);
+    (ui_port, data_directory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sub_lib::neighborhood::NeighborReputation;
+    use crate::test_utils::{
+        ensure_node_home_directory_exists, find_free_port, ArgsBuilder, FakeStreamHolder,
+    };
+    use crate::ui_gateway::auth_token::write_ui_auth_token;
+    use std::net::Ipv4Addr;
+    use std::thread;
+    use websocket::sync::Server;
+
+    fn make_neighbors() -> Vec<NeighborSummary> {
+        vec![
+            NeighborSummary {
+                public_key: "AQIDBAUGBwgJCgsMDQ4PEA".to_string(),
+                key_fingerprint: "AQIDBAUG".to_string(),
+                node_addr: Some("1.2.3.4:[1234]".to_string()),
+                neighbor_for_secs: 7384,
+                version: 12,
+                secs_since_last_gossip: Some(45),
+                reputation: NeighborReputation::Good,
+            },
+            NeighborSummary {
+                public_key: "ZGNiYWZlZWQ".to_string(),
+                key_fingerprint: "ZGNiYWZl".to_string(),
+                node_addr: None,
+                neighbor_for_secs: 200_000,
+                version: 3,
+                secs_since_last_gossip: None,
+                reputation: NeighborReputation::Delinquent,
+            },
+        ]
+    }
+
+    #[test]
+    fn renders_neighbors_as_a_table() {
+        let result = render_neighbors(&make_neighbors());
+
+        assert_eq!(
+            result,
+            "KEY       ADDRESS         NEIGHBOR FOR  VERSION  LAST GOSSIP  REPUTATION\n\
+             AQIDBAUG  1.2.3.4:[1234]  2h 03m        12       45s ago      good\n\
+             ZGNiYWZl  -               2d 07h        3        never        delinquent\n"
+        );
+    }
+
+    #[test]
+    fn renders_an_empty_neighborhood() {
+        assert_eq!(render_neighbors(&[]), "No neighbors\n");
+    }
+
+    #[test]
+    fn duration_strings_are_readable() {
+        assert_eq!(duration_string(0), "0s");
+        assert_eq!(duration_string(59), "59s");
+        assert_eq!(duration_string(61), "1m 01s");
+        assert_eq!(duration_string(3599), "59m 59s");
+        assert_eq!(duration_string(3600), "1h 00m");
+        assert_eq!(duration_string(86400 + 3600 * 5), "1d 05h");
+    }
+
+    #[test]
+    fn list_neighbors_asks_the_node_over_its_ui_port() {
+        let data_dir = ensure_node_home_directory_exists(
+            "neighbor_lister",
+            "list_neighbors_asks_the_node_over_its_ui_port",
+        );
+        write_ui_auth_token(&data_dir, "token").unwrap();
+        let port = find_free_port();
+        let mut server = Server::bind((Ipv4Addr::LOCALHOST, port)).unwrap();
+        let handle = thread::spawn(move || {
+            let upgrade = server.accept().ok().unwrap();
+            let protocols = upgrade.protocols().to_vec();
+            let mut client = upgrade.use_protocol(UI_PROTOCOL).accept().unwrap();
+            let authentication = client.recv_message().unwrap();
+            let request = client.recv_message().unwrap();
+            let converter = UiTrafficConverterReal::new();
+            client
+                .send_message(&OwnedMessage::Text(
+                    converter
+                        .marshal(UiMessage::NodeDescriptor("irrelevant".to_string()))
+                        .unwrap(),
+                ))
+                .unwrap();
+            client
+                .send_message(&OwnedMessage::Text(
+                    converter
+                        .marshal(UiMessage::NeighborList(make_neighbors()))
+                        .unwrap(),
+                ))
+                .unwrap();
+            (protocols, authentication, request)
+        });
+        let mut holder = FakeStreamHolder::new();
+
+        let result = list_neighbors(
+            &ArgsBuilder::new()
+                .opt("--list-neighbors")
+                .param("--ui-port", &port.to_string())
+                .param("--data-directory", data_dir.to_str().unwrap())
+                .into(),
+            &mut holder.streams(),
+        );
+
+        assert_eq!(result, 0);
+        assert_eq!(
+            holder.stdout.get_string(),
+            render_neighbors(&make_neighbors())
+        );
+        let (protocols, authentication, request) = handle.join().unwrap();
+        assert_eq!(protocols, vec![UI_PROTOCOL.to_string()]);
+        assert_eq!(
+            authentication,
+            OwnedMessage::Text(
+                UiTrafficConverterReal::new()
+                    .marshal(UiMessage::Authenticate("token".to_string()))
+                    .unwrap()
+            )
+        );
+        assert_eq!(
+            request,
+            OwnedMessage::Text(
+                UiTrafficConverterReal::new()
+                    .marshal(UiMessage::GetNeighborList)
+                    .unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn list_neighbors_complains_when_no_node_is_running() {
+        let port = find_free_port();
+        let mut holder = FakeStreamHolder::new();
+
+        let result = list_neighbors(
+            &ArgsBuilder::new()
+                .opt("--list-neighbors")
+                .param("--ui-port", &port.to_string())
+                .into(),
+            &mut holder.streams(),
+        );
+
+        assert_eq!(result, 1);
+        assert!(holder.stderr.get_string().starts_with(&format!(
+            "Couldn't list neighbors: no Node is listening on UI port {}",
+            port
+        )));
+    }
+}
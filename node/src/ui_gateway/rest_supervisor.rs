@@ -0,0 +1,1123 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::sub_lib::accountant::{FinancialsFormat, PaymentParameter};
+use crate::sub_lib::logger::Logger;
+use crate::sub_lib::ui_gateway::{UiCarrierMessage, UiMessage};
+use crate::sub_lib::utils::localhost;
+use crate::ui_gateway::auth_token::ui_auth_token_matches;
+use crate::ui_gateway::ui_traffic_converter::BROADCAST;
+use actix::Recipient;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// REST requests are given client IDs from here up, so that the UiGateway can tell their responses
+// from those bound for WebSocket clients, whose IDs count up from zero.
+pub const FIRST_REST_CLIENT_ID: u64 = 0x8000_0000_0000_0000;
+pub const DEFAULT_REST_RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+const MAX_REQUEST_BODY_LENGTH: usize = 4096;
+
+pub fn is_rest_client(client_id: u64) -> bool {
+    client_id >= FIRST_REST_CLIENT_ID && client_id != BROADCAST
+}
+
+pub trait RestSupervisor: Send {
+    fn send(&self, client_id: u64, message: UiMessage);
+}
+
+pub struct RestSupervisorReal {
+    inner: Arc<Mutex<RestSupervisorInner>>,
+    logger: Logger,
+}
+
+struct RestSupervisorInner {
+    next_client_id: u64,
+    response_tx_by_client_id: HashMap<u64, Sender<UiMessage>>,
+}
+
+impl RestSupervisor for RestSupervisorReal {
+    fn send(&self, client_id: u64, message: UiMessage) {
+        let response_tx_opt = self
+            .inner
+            .lock()
+            .expect("RestSupervisor is poisoned")
+            .response_tx_by_client_id
+            .remove(&client_id);
+        match response_tx_opt {
+            Some(response_tx) => {
+                // The request may have timed out in the meantime; if so, nobody cares anymore
+                let _ = response_tx.send(message);
+            }
+            None => debug!(
+                self.logger,
+                "Dropping response to REST request {}, which has already timed out", client_id
+            ),
+        }
+    }
+}
+
+impl RestSupervisorReal {
+    pub fn new(
+        port: u16,
+        ui_message_sub: Recipient<UiCarrierMessage>,
+        auth_token: String,
+    ) -> RestSupervisorReal {
+        Self::with_response_timeout(
+            port,
+            ui_message_sub,
+            auth_token,
+            DEFAULT_REST_RESPONSE_TIMEOUT,
+        )
+    }
+
+    fn with_response_timeout(
+        port: u16,
+        ui_message_sub: Recipient<UiCarrierMessage>,
+        auth_token: String,
+        response_timeout: Duration,
+    ) -> RestSupervisorReal {
+        let inner = Arc::new(Mutex::new(RestSupervisorInner {
+            next_client_id: FIRST_REST_CLIENT_ID,
+            response_tx_by_client_id: HashMap::new(),
+        }));
+        let logger = Logger::new("RestSupervisor");
+        let server_address = SocketAddr::new(localhost(), port);
+        let listener = TcpListener::bind(server_address)
+            .unwrap_or_else(|e| panic!("Could not start REST server at {}: {}", server_address, e));
+        info!(logger, "REST API listening at {}", server_address);
+        let inner_clone = inner.clone();
+        let logger_clone = logger.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let inner = inner_clone.clone();
+                        let ui_message_sub = ui_message_sub.clone();
+                        let auth_token = auth_token.clone();
+                        let logger = logger_clone.clone();
+                        thread::spawn(move || {
+                            Self::handle_connection(
+                                stream,
+                                &inner,
+                                &ui_message_sub,
+                                &auth_token,
+                                response_timeout,
+                                &logger,
+                            )
+                        });
+                    }
+                    Err(e) => info!(logger_clone, "Unsuccessful connection to REST port: {}", e),
+                }
+            }
+        });
+        RestSupervisorReal { inner, logger }
+    }
+
+    fn handle_connection(
+        mut stream: TcpStream,
+        inner: &Arc<Mutex<RestSupervisorInner>>,
+        ui_message_sub: &Recipient<UiCarrierMessage>,
+        auth_token: &str,
+        response_timeout: Duration,
+        logger: &Logger,
+    ) {
+        let _ = stream.set_read_timeout(Some(response_timeout));
+        let response = match read_request(&mut BufReader::new(&stream)) {
+            Err(e) => RestResponse::error(400, &e),
+            Ok(request) => match authorize(&request, auth_token).and_then(|_| route(&request)) {
+                Err(response) => {
+                    if response.status == 401 {
+                        warning!(
+                            logger,
+                            "Refused REST request {} {} without the UI token",
+                            request.method,
+                            request.path
+                        );
+                    }
+                    response
+                }
+                Ok(ui_message) => {
+                    debug!(logger, "REST request {} {}", request.method, request.path);
+                    Self::relay(inner, ui_message_sub, ui_message, response_timeout)
+                }
+            },
+        };
+        if let Err(e) = stream.write_all(response.to_http().as_bytes()) {
+            info!(logger, "Couldn't answer REST client: {}", e);
+        }
+    }
+
+    fn relay(
+        inner: &Arc<Mutex<RestSupervisorInner>>,
+        ui_message_sub: &Recipient<UiCarrierMessage>,
+        ui_message: UiMessage,
+        response_timeout: Duration,
+    ) -> RestResponse {
+        let (response_tx, response_rx) = mpsc::channel();
+        let client_id = {
+            let mut locked_inner = inner.lock().expect("RestSupervisor is poisoned");
+            let client_id = locked_inner.next_client_id;
+            locked_inner.next_client_id = match client_id + 1 {
+                BROADCAST => FIRST_REST_CLIENT_ID,
+                next => next,
+            };
+            locked_inner
+                .response_tx_by_client_id
+                .insert(client_id, response_tx);
+            client_id
+        };
+        if ui_message_sub
+            .try_send(UiCarrierMessage {
+                client_id,
+                data: ui_message,
+            })
+            .is_err()
+        {
+            Self::forget(inner, client_id);
+            return RestResponse::error(503, "The Node is shutting down");
+        }
+        match response_rx.recv_timeout(response_timeout) {
+            Ok(message) => response_for(message),
+            Err(_) => {
+                Self::forget(inner, client_id);
+                RestResponse::error(504, "The Node didn't answer in time")
+            }
+        }
+    }
+
+    fn forget(inner: &Arc<Mutex<RestSupervisorInner>>, client_id: u64) {
+        inner
+            .lock()
+            .expect("RestSupervisor is poisoned")
+            .response_tx_by_client_id
+            .remove(&client_id);
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct RestRequest {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    authorization_opt: Option<String>,
+    body: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct RestResponse {
+    status: u16,
+    body: Value,
+}
+
+impl RestResponse {
+    fn ok(body: Value) -> RestResponse {
+        RestResponse { status: 200, body }
+    }
+
+    fn error(status: u16, message: &str) -> RestResponse {
+        RestResponse {
+            status,
+            body: json!({ "error": message }),
+        }
+    }
+
+    fn to_http(&self) -> String {
+        let reason = match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            404 => "Not Found",
+            500 => "Internal Server Error",
+            503 => "Service Unavailable",
+            504 => "Gateway Timeout",
+            _ => "",
+        };
+        let challenge = match self.status {
+            401 => "WWW-Authenticate: Bearer\r\n",
+            _ => "",
+        };
+        let body = self.body.to_string();
+        format!(
+            "HTTP/1.1 {} {}\r\n{}Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            reason,
+            challenge,
+            body.len(),
+            body
+        )
+    }
+}
+
+fn read_request(reader: &mut dyn BufRead) -> Result<RestRequest, String> {
+    let mut request_line = String::new();
+    reader
+        .read_line(&mut request_line)
+        .map_err(|e| format!("Couldn't read request: {}", e))?;
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+        _ => return Err(format!("Malformed request line: '{}'", request_line.trim())),
+    };
+    let mut content_length = 0;
+    let mut authorization_opt = None;
+    loop {
+        let mut header = String::new();
+        reader
+            .read_line(&mut header)
+            .map_err(|e| format!("Couldn't read request: {}", e))?;
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(colon) = header.find(':') {
+            let name = header[..colon].trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = header[colon + 1..]
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| format!("Bad Content-Length: '{}'", header))?;
+            } else if name.eq_ignore_ascii_case("authorization") {
+                authorization_opt = Some(header[colon + 1..].trim().to_string());
+            }
+        }
+    }
+    if content_length > MAX_REQUEST_BODY_LENGTH {
+        return Err(format!(
+            "Request body of {} bytes is longer than the {}-byte limit",
+            content_length, MAX_REQUEST_BODY_LENGTH
+        ));
+    }
+    let mut body = vec![0u8; content_length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|e| format!("Couldn't read request body: {}", e))?;
+    let (path, query) = match target.find('?') {
+        Some(question) => (
+            target[..question].to_string(),
+            parse_query(&target[question + 1..]),
+        ),
+        None => (target, HashMap::new()),
+    };
+    Ok(RestRequest {
+        method,
+        path,
+        query,
+        authorization_opt,
+        body: String::from_utf8_lossy(&body).to_string(),
+    })
+}
+
+// Every REST request carries the same token a WebSocket UI opens with, as a bearer token
+fn authorize(request: &RestRequest, auth_token: &str) -> Result<(), RestResponse> {
+    let presented =
+        request
+            .authorization_opt
+            .as_ref()
+            .and_then(|authorization| match authorization.find(' ') {
+                Some(space) if authorization[..space].eq_ignore_ascii_case("bearer") => {
+                    Some(authorization[space + 1..].trim())
+                }
+                _ => None,
+            });
+    match presented {
+        Some(token) if ui_auth_token_matches(auth_token, token) => Ok(()),
+        _ => Err(RestResponse::error(
+            401,
+            "Send the token in ui-auth-token in the data directory as 'Authorization: Bearer <token>'",
+        )),
+    }
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.find('=') {
+            Some(equals) => (pair[..equals].to_string(), pair[equals + 1..].to_string()),
+            None => (pair.to_string(), String::new()),
+        })
+        .collect()
+}
+
+fn route(request: &RestRequest) -> Result<UiMessage, RestResponse> {
+    let segments = request
+        .path
+        .trim_matches('/')
+        .split('/')
+        .collect::<Vec<&str>>();
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["status"]) => Ok(UiMessage::GetNodeStatus),
+        ("GET", ["statistics"]) => Ok(UiMessage::GetNodeStatistics),
+        ("GET", ["descriptor"]) => Ok(UiMessage::GetNodeDescriptor),
+        ("GET", ["descriptor", "share"]) => Ok(UiMessage::GetNodeDescriptorShare),
+        ("GET", ["neighbors"]) => Ok(UiMessage::NeighborhoodDotGraphRequest),
+        ("GET", ["neighbors", "list"]) => Ok(UiMessage::GetNeighborList),
+        ("GET", ["financials"]) => Ok(UiMessage::GetFinancialStatisticsMessage),
+        ("GET", ["financials", "dump"]) => {
+            match request.query.get("format").map(|format| format.as_str()) {
+                None => Ok(UiMessage::DumpFinancialsRequest(FinancialsFormat::Json)),
+                Some(format) => FinancialsFormat::from_str(format)
+                    .map(UiMessage::DumpFinancialsRequest)
+                    .map_err(|e| RestResponse::error(400, &e)),
+            }
+        }
+        ("GET", ["settings", "blockchain-service-url"]) => Ok(UiMessage::GetBlockchainServiceUrl),
+        ("GET", ["wallets", "balances"]) => Ok(UiMessage::GetWalletBalances),
+        ("GET", ["dns-leak-test"]) => Ok(UiMessage::DnsLeakTest),
+        ("GET", ["health"]) => Ok(UiMessage::GetNodeHealth),
+        ("GET", ["audit"]) => match request.query.get("limit") {
+            None => Ok(UiMessage::GetAuditLog { limit: None }),
+            Some(limit) => match limit.parse::<usize>() {
+                Ok(limit) => Ok(UiMessage::GetAuditLog { limit: Some(limit) }),
+                Err(_) => Err(RestResponse::error(
+                    400,
+                    "limit should be a non-negative integer",
+                )),
+            },
+        },
+        ("PUT", ["settings", "gas-price"]) => match body_value(&request.body) {
+            Some(gas_price) => Ok(UiMessage::SetGasPrice(gas_price)),
+            None => Err(RestResponse::error(
+                400,
+                "Gas price should be a number of Gwei",
+            )),
+        },
+        ("PUT", ["settings", "payment-parameters", name]) => {
+            let parameter =
+                PaymentParameter::from_str(name).map_err(|e| RestResponse::error(404, &e))?;
+            match body_value(&request.body).and_then(|value| value.parse::<u64>().ok()) {
+                Some(value) => Ok(UiMessage::SetPaymentParameter { parameter, value }),
+                None => Err(RestResponse::error(
+                    400,
+                    &format!("{} should be a non-negative integer", name),
+                )),
+            }
+        }
+        ("GET", ["configuration"]) => Ok(UiMessage::ConfigurationGet),
+        ("POST", ["configuration", "reload"]) => Ok(UiMessage::ReloadConfiguration),
+        ("PUT", ["configuration", name]) => match body_value(&request.body) {
+            Some(value) => Ok(UiMessage::ConfigurationChange {
+                name: name.to_string(),
+                value,
+            }),
+            None => Err(RestResponse::error(
+                400,
+                &format!("{} should be a number or a string", name),
+            )),
+        },
+        ("PUT", ["log-level"]) => log_level_request(&request.body, None),
+        ("PUT", ["log-level", component]) => {
+            log_level_request(&request.body, Some(component.to_string()))
+        }
+        (method, _) => Err(RestResponse::error(
+            404,
+            &format!("No such resource: {} {}", method, request.path),
+        )),
+    }
+}
+
+fn log_level_request(body: &str, component: Option<String>) -> Result<UiMessage, RestResponse> {
+    match body_value(body) {
+        Some(level) => Ok(UiMessage::SetLogLevel { level, component }),
+        None => Err(RestResponse::error(400, "The log level should be a string")),
+    }
+}
+
+// Setting bodies may be bare JSON numbers or strings
+fn body_value(body: &str) -> Option<String> {
+    match serde_json::from_str::<Value>(body.trim()) {
+        Ok(Value::Number(number)) => Some(number.to_string()),
+        Ok(Value::String(string)) => Some(string),
+        _ => None,
+    }
+}
+
+fn response_for(message: UiMessage) -> RestResponse {
+    match message {
+        UiMessage::NodeStatus(status) => RestResponse::ok(json!(status)),
+        UiMessage::NodeStatistics(statistics) => RestResponse::ok(json!(statistics)),
+        UiMessage::NodeDescriptor(descriptor) => {
+            RestResponse::ok(json!({ "nodeDescriptor": descriptor }))
+        }
+        UiMessage::NodeDescriptorShare(share) => RestResponse::ok(json!(share)),
+        UiMessage::NeighborhoodDotGraphResponse(graph) => {
+            RestResponse::ok(json!({ "dotGraph": graph }))
+        }
+        UiMessage::NeighborList(neighbors) => RestResponse::ok(json!({ "neighbors": neighbors })),
+        UiMessage::FinancialStatisticsResponse(statistics) => RestResponse::ok(json!(statistics)),
+        UiMessage::DumpFinancialsResponse(financials) => {
+            RestResponse::ok(json!({ "financials": financials }))
+        }
+        UiMessage::BlockchainServiceUrl(url_opt) => {
+            RestResponse::ok(json!({ "blockchainServiceUrl": url_opt }))
+        }
+        UiMessage::WalletBalances(balances) => RestResponse::ok(json!(balances)),
+        UiMessage::DnsLeakTestResponse(report) => RestResponse::ok(json!(report)),
+        // Orchestrators look only at the status, so a Node that isn't ready says so there
+        UiMessage::NodeHealth(health) => RestResponse {
+            status: if health.ready { 200 } else { 503 },
+            body: json!(health),
+        },
+        UiMessage::AuditLog(Ok(entries)) => RestResponse::ok(json!({ "auditLog": entries })),
+        UiMessage::AuditLog(Err(e)) => RestResponse::error(500, &e),
+        UiMessage::Configuration(entries) => RestResponse::ok(json!({ "configuration": entries })),
+        UiMessage::ReloadConfigurationResponse(Ok(reload)) => RestResponse::ok(json!(reload)),
+        UiMessage::SetGasPriceResponse(true)
+        | UiMessage::SetPaymentParameterResponse(true)
+        | UiMessage::ConfigurationChangeResponse(Ok(()))
+        | UiMessage::SetLogLevelResponse(Ok(())) => RestResponse::ok(json!({ "success": true })),
+        UiMessage::SetGasPriceResponse(false) => {
+            RestResponse::error(400, "The Node didn't accept that gas price")
+        }
+        UiMessage::SetPaymentParameterResponse(false) => {
+            RestResponse::error(400, "The Node didn't accept that payment parameter value")
+        }
+        UiMessage::ConfigurationChangeResponse(Err(e))
+        | UiMessage::SetLogLevelResponse(Err(e))
+        | UiMessage::ReloadConfigurationResponse(Err(e)) => RestResponse::error(400, &e),
+        other => RestResponse::error(500, &format!("Unexpected response: {:?}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit_log::{AuditEntry, AuditSource};
+    use crate::sub_lib::accountant::FinancialStatisticsMessage;
+    use crate::sub_lib::neighborhood::{NeighborReputation, NeighborSummary};
+    use crate::sub_lib::ui_gateway::{
+        ComponentHealth, ConfigurationEntry, ConfigurationReload, DnsLeakVerdict, HealthState,
+        NodeDescriptorShare, NodeHealth, NodeStatistics, NodeStatus, WalletBalance, WalletBalances,
+    };
+    use crate::test_utils::logging::{init_test_logging, TestLogHandler};
+    use crate::test_utils::recorder::make_recorder;
+    use crate::test_utils::{find_free_port, make_dns_leak_report};
+    use actix::{Actor, System};
+    use std::io::{Cursor, Read};
+
+    fn make_request(method: &str, path: &str, body: &str) -> RestRequest {
+        read_request(&mut Cursor::new(format!(
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        )))
+        .unwrap()
+    }
+
+    fn http_exchange(port: u16, request: &str) -> String {
+        let mut stream = TcpStream::connect(SocketAddr::new(localhost(), port)).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn rest_client_ids_are_distinct_from_websocket_client_ids_and_broadcasts() {
+        assert_eq!(is_rest_client(0), false);
+        assert_eq!(is_rest_client(FIRST_REST_CLIENT_ID - 1), false);
+        assert_eq!(is_rest_client(FIRST_REST_CLIENT_ID), true);
+        assert_eq!(is_rest_client(BROADCAST - 1), true);
+        assert_eq!(is_rest_client(BROADCAST), false);
+    }
+
+    #[test]
+    fn reads_requests_with_queries_and_bodies() {
+        let result = make_request("PUT", "/settings/gas-price?a=b&c", "42");
+
+        assert_eq!(
+            result,
+            RestRequest {
+                method: "PUT".to_string(),
+                path: "/settings/gas-price".to_string(),
+                query: vec![
+                    ("a".to_string(), "b".to_string()),
+                    ("c".to_string(), String::new())
+                ]
+                .into_iter()
+                .collect(),
+                authorization_opt: None,
+                body: "42".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn reads_the_authorization_header() {
+        let result = read_request(&mut Cursor::new(
+            "GET /status HTTP/1.1\r\nauthorization:  Bearer abc123 \r\n\r\n",
+        ))
+        .unwrap();
+
+        assert_eq!(result.authorization_opt, Some("Bearer abc123".to_string()));
+    }
+
+    #[test]
+    fn authorizes_only_requests_that_bear_the_ui_token() {
+        let with_authorization = |authorization: Option<&str>| {
+            let mut request = make_request("PUT", "/log-level", "\"debug\"");
+            request.authorization_opt = authorization.map(|a| a.to_string());
+            authorize(&request, "abc123")
+        };
+        let refusal = RestResponse::error(
+            401,
+            "Send the token in ui-auth-token in the data directory as 'Authorization: Bearer <token>'",
+        );
+
+        assert_eq!(with_authorization(Some("Bearer abc123")), Ok(()));
+        assert_eq!(with_authorization(Some("bearer abc123")), Ok(()));
+        assert_eq!(with_authorization(None), Err(refusal.clone()));
+        assert_eq!(
+            with_authorization(Some("Bearer abc124")),
+            Err(refusal.clone())
+        );
+        assert_eq!(
+            with_authorization(Some("Basic abc123")),
+            Err(refusal.clone())
+        );
+        assert_eq!(with_authorization(Some("abc123")), Err(refusal.clone()));
+        assert_eq!(with_authorization(Some("Bearer ")), Err(refusal));
+    }
+
+    #[test]
+    fn refuses_to_read_overlong_bodies() {
+        let result = read_request(&mut Cursor::new(
+            "PUT /settings/gas-price HTTP/1.1\r\nContent-Length: 4097\r\n\r\n",
+        ));
+
+        assert_eq!(
+            result,
+            Err("Request body of 4097 bytes is longer than the 4096-byte limit".to_string())
+        );
+    }
+
+    #[test]
+    fn routes_requests_to_ui_messages() {
+        vec![
+            ("GET", "/status", "", UiMessage::GetNodeStatus),
+            ("GET", "/statistics", "", UiMessage::GetNodeStatistics),
+            ("GET", "/neighbors/list", "", UiMessage::GetNeighborList),
+            ("GET", "/descriptor", "", UiMessage::GetNodeDescriptor),
+            (
+                "GET",
+                "/descriptor/share",
+                "",
+                UiMessage::GetNodeDescriptorShare,
+            ),
+            (
+                "GET",
+                "/neighbors/",
+                "",
+                UiMessage::NeighborhoodDotGraphRequest,
+            ),
+            (
+                "GET",
+                "/financials",
+                "",
+                UiMessage::GetFinancialStatisticsMessage,
+            ),
+            (
+                "GET",
+                "/financials/dump",
+                "",
+                UiMessage::DumpFinancialsRequest(FinancialsFormat::Json),
+            ),
+            (
+                "GET",
+                "/financials/dump?format=csv",
+                "",
+                UiMessage::DumpFinancialsRequest(FinancialsFormat::Csv),
+            ),
+            (
+                "GET",
+                "/settings/blockchain-service-url",
+                "",
+                UiMessage::GetBlockchainServiceUrl,
+            ),
+            ("GET", "/wallets/balances", "", UiMessage::GetWalletBalances),
+            ("GET", "/dns-leak-test", "", UiMessage::DnsLeakTest),
+            ("GET", "/health", "", UiMessage::GetNodeHealth),
+            ("GET", "/audit", "", UiMessage::GetAuditLog { limit: None }),
+            (
+                "GET",
+                "/audit?limit=10",
+                "",
+                UiMessage::GetAuditLog { limit: Some(10) },
+            ),
+            (
+                "PUT",
+                "/settings/gas-price",
+                "23",
+                UiMessage::SetGasPrice("23".to_string()),
+            ),
+            (
+                "PUT",
+                "/settings/gas-price",
+                "\"24\"",
+                UiMessage::SetGasPrice("24".to_string()),
+            ),
+            (
+                "PUT",
+                "/settings/payment-parameters/payment-suggested-after",
+                "1200",
+                UiMessage::SetPaymentParameter {
+                    parameter: PaymentParameter::PaymentSuggestedAfter,
+                    value: 1200,
+                },
+            ),
+            ("GET", "/configuration", "", UiMessage::ConfigurationGet),
+            (
+                "POST",
+                "/configuration/reload",
+                "",
+                UiMessage::ReloadConfiguration,
+            ),
+            (
+                "PUT",
+                "/configuration/log-level",
+                "\"debug\"",
+                UiMessage::ConfigurationChange {
+                    name: "log-level".to_string(),
+                    value: "debug".to_string(),
+                },
+            ),
+            (
+                "PUT",
+                "/log-level",
+                "\"warn\"",
+                UiMessage::SetLogLevel {
+                    level: "warn".to_string(),
+                    component: None,
+                },
+            ),
+            (
+                "PUT",
+                "/log-level/Neighborhood",
+                "\"trace\"",
+                UiMessage::SetLogLevel {
+                    level: "trace".to_string(),
+                    component: Some("Neighborhood".to_string()),
+                },
+            ),
+        ]
+        .into_iter()
+        .for_each(|(method, path, body, expected)| {
+            assert_eq!(
+                route(&make_request(method, path, body)),
+                Ok(expected),
+                "{} {}",
+                method,
+                path
+            )
+        });
+    }
+
+    #[test]
+    fn rejects_requests_that_dont_make_sense() {
+        assert_eq!(
+            route(&make_request("DELETE", "/status", "")),
+            Err(RestResponse::error(404, "No such resource: DELETE /status"))
+        );
+        assert_eq!(
+            route(&make_request("GET", "/financials/dump?format=xml", "")),
+            Err(RestResponse::error(
+                400,
+                "Financials can be dumped as csv or json, not 'xml'"
+            ))
+        );
+        assert_eq!(
+            route(&make_request("PUT", "/settings/gas-price", "{}")),
+            Err(RestResponse::error(
+                400,
+                "Gas price should be a number of Gwei"
+            ))
+        );
+        assert_eq!(
+            route(&make_request(
+                "PUT",
+                "/settings/payment-parameters/booga",
+                "1"
+            )),
+            Err(RestResponse::error(
+                404,
+                "Unknown payment parameter: 'booga'"
+            ))
+        );
+        assert_eq!(
+            route(&make_request(
+                "PUT",
+                "/settings/payment-parameters/payment-suggested-after",
+                "-1"
+            )),
+            Err(RestResponse::error(
+                400,
+                "payment-suggested-after should be a non-negative integer"
+            ))
+        );
+        assert_eq!(
+            route(&make_request("GET", "/audit?limit=lots", "")),
+            Err(RestResponse::error(
+                400,
+                "limit should be a non-negative integer"
+            ))
+        );
+    }
+
+    #[test]
+    fn turns_ui_responses_into_rest_responses() {
+        assert_eq!(
+            response_for(UiMessage::NodeStatus(NodeStatus {
+                version: "1.2.3".to_string(),
+                node_descriptor: "NODE-DESCRIPTOR".to_string(),
+                uptime_secs: 45,
+            })),
+            RestResponse::ok(
+                json!({"version": "1.2.3", "nodeDescriptor": "NODE-DESCRIPTOR", "uptimeSecs": 45})
+            )
+        );
+        assert_eq!(
+            response_for(UiMessage::NodeStatistics(NodeStatistics {
+                uptime_secs: 45,
+                bytes_relayed: 1000,
+                bytes_relayed_last_hour: 100,
+                exit_bytes_served: 200,
+                active_streams_originated: 3,
+                active_exit_streams: 4,
+                neighbor_count: 5,
+            })),
+            RestResponse::ok(json!({
+                "uptimeSecs": 45,
+                "bytesRelayed": 1000,
+                "bytesRelayedLastHour": 100,
+                "exitBytesServed": 200,
+                "activeStreamsOriginated": 3,
+                "activeExitStreams": 4,
+                "neighborCount": 5
+            }))
+        );
+        assert_eq!(
+            response_for(UiMessage::FinancialStatisticsResponse(
+                FinancialStatisticsMessage {
+                    pending_credit: 12,
+                    pending_debt: 34,
+                    profiles: vec![],
+                    payments_deferred: None,
+                }
+            )),
+            RestResponse::ok(json!({"pendingCredit": 12, "pendingDebt": 34}))
+        );
+        assert_eq!(
+            response_for(UiMessage::NeighborList(vec![NeighborSummary {
+                public_key: "AQIDBAUGBwgJCgsMDQ4PEA".to_string(),
+                key_fingerprint: "AQIDBAUG".to_string(),
+                node_addr: None,
+                neighbor_for_secs: 100,
+                version: 2,
+                secs_since_last_gossip: Some(5),
+                reputation: NeighborReputation::Undesirable,
+            }])),
+            RestResponse::ok(json!({"neighbors": [{
+                "publicKey": "AQIDBAUGBwgJCgsMDQ4PEA",
+                "keyFingerprint": "AQIDBAUG",
+                "nodeAddr": null,
+                "neighborForSecs": 100,
+                "version": 2,
+                "secsSinceLastGossip": 5,
+                "reputation": "undesirable"
+            }]}))
+        );
+        assert_eq!(
+            response_for(UiMessage::NodeDescriptorShare(NodeDescriptorShare {
+                descriptor: "AQIDBA:1.2.3.4:1234".to_string(),
+                uri: "substratum://AQIDBA:1.2.3.4:1234".to_string(),
+                qr_code_png: "iVBORw0KGgo".to_string(),
+            })),
+            RestResponse::ok(json!({
+                "descriptor": "AQIDBA:1.2.3.4:1234",
+                "uri": "substratum://AQIDBA:1.2.3.4:1234",
+                "qrCodePng": "iVBORw0KGgo"
+            }))
+        );
+        assert_eq!(
+            response_for(UiMessage::BlockchainServiceUrl(None)),
+            RestResponse::ok(json!({ "blockchainServiceUrl": null }))
+        );
+        assert_eq!(
+            response_for(UiMessage::WalletBalances(WalletBalances {
+                earning: WalletBalance {
+                    address: "0x0123".to_string(),
+                    gas_balance: Ok("1000000000000000000".to_string()),
+                    token_balance: Err("QueryFailed".to_string()),
+                },
+                consuming: None,
+            })),
+            RestResponse::ok(json!({
+                "earning": {
+                    "address": "0x0123",
+                    "gasBalance": {"Ok": "1000000000000000000"},
+                    "tokenBalance": {"Err": "QueryFailed"}
+                },
+                "consuming": null
+            }))
+        );
+        assert_eq!(
+            response_for(UiMessage::DnsLeakTestResponse(make_dns_leak_report(
+                DnsLeakVerdict::NotLeaking
+            ))),
+            RestResponse::ok(json!({
+                "canary": "canary.example.com",
+                "nodeDnsServer": "127.0.0.1:53",
+                "nodeAnswer": "127.0.0.1",
+                "systemAnswers": ["93.184.216.34"],
+                "systemError": null,
+                "verdict": "NotLeaking"
+            }))
+        );
+        assert_eq!(
+            response_for(UiMessage::NodeHealth(NodeHealth::new(vec![
+                ComponentHealth::new("database", HealthState::Ready, "writable"),
+                ComponentHealth::new("dns", HealthState::Disabled, "not subverted"),
+            ]))),
+            RestResponse::ok(json!({
+                "ready": true,
+                "components": [
+                    {"component": "database", "state": "Ready", "detail": "writable"},
+                    {"component": "dns", "state": "Disabled", "detail": "not subverted"}
+                ]
+            }))
+        );
+        assert_eq!(
+            response_for(UiMessage::NodeHealth(NodeHealth::new(vec![
+                ComponentHealth::new("neighborhood", HealthState::NotReady, "degree 0"),
+            ]))),
+            RestResponse {
+                status: 503,
+                body: json!({
+                    "ready": false,
+                    "components": [
+                        {"component": "neighborhood", "state": "NotReady", "detail": "degree 0"}
+                    ]
+                })
+            }
+        );
+        assert_eq!(
+            response_for(UiMessage::AuditLog(Ok(vec![AuditEntry {
+                timestamp: 1234,
+                source: AuditSource::Signal,
+                action: "configuration-reload".to_string(),
+                detail: "SIGHUP".to_string(),
+            }]))),
+            RestResponse::ok(json!({
+                "auditLog": [{
+                    "timestamp": 1234,
+                    "source": "signal",
+                    "action": "configuration-reload",
+                    "detail": "SIGHUP"
+                }]
+            }))
+        );
+        assert_eq!(
+            response_for(UiMessage::AuditLog(Err(
+                "There's no audit log yet".to_string()
+            ))),
+            RestResponse::error(500, "There's no audit log yet")
+        );
+        assert_eq!(
+            response_for(UiMessage::SetPaymentParameterResponse(true)),
+            RestResponse::ok(json!({"success": true}))
+        );
+        assert_eq!(
+            response_for(UiMessage::SetGasPriceResponse(false)),
+            RestResponse::error(400, "The Node didn't accept that gas price")
+        );
+        assert_eq!(
+            response_for(UiMessage::Configuration(vec![ConfigurationEntry::new(
+                "log-level",
+                "warn"
+            )])),
+            RestResponse::ok(json!({"configuration": [{"name": "log-level", "value": "warn"}]}))
+        );
+        assert_eq!(
+            response_for(UiMessage::ConfigurationChangeResponse(Err(
+                "booga can't be changed while the Node is running".to_string()
+            ))),
+            RestResponse::error(400, "booga can't be changed while the Node is running")
+        );
+        assert_eq!(
+            response_for(UiMessage::ReloadConfigurationResponse(Ok(
+                ConfigurationReload {
+                    applied: vec![ConfigurationEntry::new("log-level", "debug")],
+                    rejected: vec![],
+                    needs_restart: vec!["neighbors".to_string()],
+                }
+            ))),
+            RestResponse::ok(json!({
+                "applied": [{"name": "log-level", "value": "debug"}],
+                "rejected": [],
+                "needsRestart": ["neighbors"],
+            }))
+        );
+        assert_eq!(
+            response_for(UiMessage::ReloadConfigurationResponse(Err(
+                "There's no configuration file to reload".to_string()
+            ))),
+            RestResponse::error(400, "There's no configuration file to reload")
+        );
+        assert_eq!(
+            response_for(UiMessage::SetLogLevelResponse(Ok(()))),
+            RestResponse::ok(json!({"success": true}))
+        );
+        assert_eq!(
+            response_for(UiMessage::SetLogLevelResponse(Err(
+                "'' isn't the name of a component".to_string()
+            ))),
+            RestResponse::error(400, "'' isn't the name of a component")
+        );
+    }
+
+    #[test]
+    fn relays_requests_to_the_ui_gateway_and_answers_with_its_response() {
+        let port = find_free_port();
+        let (ui_gateway, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        let (recipient_tx, recipient_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let system =
+                System::new("relays_requests_to_the_ui_gateway_and_answers_with_its_response");
+            recipient_tx
+                .send(ui_gateway.start().recipient::<UiCarrierMessage>())
+                .unwrap();
+            system.run();
+        });
+        let subject =
+            RestSupervisorReal::new(port, recipient_rx.recv().unwrap(), "abc123".to_string());
+        let client = thread::spawn(move || {
+            http_exchange(
+                port,
+                "GET /descriptor HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer abc123\r\n\r\n",
+            )
+        });
+
+        ui_gateway_awaiter.await_message_count(1);
+        let request = ui_gateway_recording_arc
+            .lock()
+            .unwrap()
+            .get_record::<UiCarrierMessage>(0)
+            .client_id;
+        subject.send(
+            request,
+            UiMessage::NodeDescriptor("NODE-DESCRIPTOR".to_string()),
+        );
+
+        let response = client.join().unwrap();
+        assert_eq!(
+            ui_gateway_recording_arc
+                .lock()
+                .unwrap()
+                .get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: FIRST_REST_CLIENT_ID,
+                data: UiMessage::GetNodeDescriptor,
+            }
+        );
+        let body = json!({"nodeDescriptor": "NODE-DESCRIPTOR"}).to_string();
+        assert_eq!(
+            response,
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+        );
+    }
+
+    #[test]
+    fn gives_up_on_responses_that_take_too_long() {
+        let port = find_free_port();
+        let (ui_gateway, _, _) = make_recorder();
+        let (recipient_tx, recipient_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let system = System::new("gives_up_on_responses_that_take_too_long");
+            recipient_tx
+                .send(ui_gateway.start().recipient::<UiCarrierMessage>())
+                .unwrap();
+            system.run();
+        });
+        let subject = RestSupervisorReal::with_response_timeout(
+            port,
+            recipient_rx.recv().unwrap(),
+            "abc123".to_string(),
+            Duration::from_millis(100),
+        );
+
+        let response = http_exchange(
+            port,
+            "GET /status HTTP/1.1\r\nAuthorization: Bearer abc123\r\n\r\n",
+        );
+
+        assert_eq!(
+            response.starts_with("HTTP/1.1 504 Gateway Timeout\r\n"),
+            true
+        );
+        assert_eq!(
+            subject
+                .inner
+                .lock()
+                .unwrap()
+                .response_tx_by_client_id
+                .is_empty(),
+            true
+        );
+        subject.send(FIRST_REST_CLIENT_ID, UiMessage::GetNodeStatus);
+    }
+
+    #[test]
+    fn refuses_unauthenticated_requests_without_telling_the_ui_gateway() {
+        init_test_logging();
+        let port = find_free_port();
+        let (ui_gateway, _, ui_gateway_recording_arc) = make_recorder();
+        let (recipient_tx, recipient_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let system =
+                System::new("refuses_unauthenticated_requests_without_telling_the_ui_gateway");
+            recipient_tx
+                .send(ui_gateway.start().recipient::<UiCarrierMessage>())
+                .unwrap();
+            system.run();
+        });
+        let _subject =
+            RestSupervisorReal::new(port, recipient_rx.recv().unwrap(), "abc123".to_string());
+
+        let responses = vec![
+            "PUT /log-level HTTP/1.1\r\nContent-Length: 7\r\n\r\n\"debug\"",
+            "POST /configuration/reload HTTP/1.1\r\nAuthorization: Bearer wrong\r\n\r\n",
+            "GET /financials/dump HTTP/1.1\r\nAuthorization: abc123\r\n\r\n",
+        ]
+        .into_iter()
+        .map(|request| http_exchange(port, request))
+        .collect::<Vec<String>>();
+
+        responses.iter().for_each(|response| {
+            assert!(
+                response.starts_with(
+                    "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Bearer\r\nContent-Type: application/json\r\n"
+                ),
+                "{}",
+                response
+            )
+        });
+        thread::sleep(Duration::from_millis(100));
+        assert!(ui_gateway_recording_arc.lock().unwrap().is_empty());
+        TestLogHandler::new().exists_log_containing(
+            "WARN: RestSupervisor: Refused REST request PUT /log-level without the UI token",
+        );
+    }
+}
@@ -1,7 +1,13 @@
 // Copyright (c) 2017-2018, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
+use crate::restart_keeper;
+use crate::restart_keeper::RESTART_EXIT_CODE;
+use std::process::Command;
+
 trait Signaler: Send {
     fn sigterm(&self);
+    fn reexec(&self);
+    fn exit_for_restart(&self);
 }
 
 struct SignalerReal {}
@@ -10,6 +16,32 @@ impl Signaler for SignalerReal {
     fn sigterm(&self) {
         std::process::exit(0);
     }
+
+    // Same executable, same arguments; environment and config file are read afresh
+    fn reexec(&self) {
+        let mut command =
+            Command::new(std::env::current_exe().expect("Can't find the Node executable"));
+        command.args(std::env::args_os().skip(1));
+        replace_process(command);
+    }
+
+    fn exit_for_restart(&self) {
+        std::process::exit(RESTART_EXIT_CODE);
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn replace_process(mut command: Command) {
+    use std::os::unix::process::CommandExt;
+    // Only returns if it fails; our sockets are close-on-exec, so the new Node can bind them
+    let error = command.exec();
+    panic!("Couldn't restart the Node: {}", error);
+}
+
+#[cfg(target_os = "windows")]
+fn replace_process(mut command: Command) {
+    command.spawn().expect("Couldn't restart the Node");
+    std::process::exit(0);
 }
 
 impl SignalerReal {
@@ -20,10 +52,12 @@ impl SignalerReal {
 
 pub trait ShutdownSupervisor: Send {
     fn shutdown(&self);
+    fn restart(&self);
 }
 
 pub struct ShutdownSupervisorReal {
     signaler: Box<dyn Signaler>,
+    kept: bool,
 }
 
 impl ShutdownSupervisor for ShutdownSupervisorReal {
@@ -31,12 +65,23 @@ impl ShutdownSupervisor for ShutdownSupervisorReal {
         // TODO: Additional graceful-shutdown code should go here
         self.signaler.sigterm();
     }
+
+    // A Node started as root has dropped its privileges by now, and couldn't open its ports
+    // again if it started itself; whatever started it still has them
+    fn restart(&self) {
+        if self.kept {
+            self.signaler.exit_for_restart();
+        } else {
+            self.signaler.reexec();
+        }
+    }
 }
 
 impl ShutdownSupervisorReal {
     pub fn new() -> ShutdownSupervisorReal {
         ShutdownSupervisorReal {
             signaler: Box::new(SignalerReal::new()),
+            kept: restart_keeper::is_kept(),
         }
     }
 }
@@ -49,25 +94,47 @@ mod tests {
 
     struct SignalerMock {
         sigterm_parameters: Arc<Mutex<Vec<()>>>,
+        reexec_parameters: Arc<Mutex<Vec<()>>>,
+        exit_for_restart_parameters: Arc<Mutex<Vec<()>>>,
     }
 
     impl Signaler for SignalerMock {
         fn sigterm(&self) {
             self.sigterm_parameters.lock().unwrap().push(());
         }
+
+        fn reexec(&self) {
+            self.reexec_parameters.lock().unwrap().push(());
+        }
+
+        fn exit_for_restart(&self) {
+            self.exit_for_restart_parameters.lock().unwrap().push(());
+        }
     }
 
     impl SignalerMock {
         fn new() -> SignalerMock {
             SignalerMock {
                 sigterm_parameters: Arc::new(Mutex::new(vec![])),
+                reexec_parameters: Arc::new(Mutex::new(vec![])),
+                exit_for_restart_parameters: Arc::new(Mutex::new(vec![])),
             }
         }
 
+        fn reexec_parameters(self, parameters: &mut Arc<Mutex<Vec<()>>>) -> SignalerMock {
+            *parameters = self.reexec_parameters.clone();
+            self
+        }
+
         fn sigterm_parameters(self, parameters: &mut Arc<Mutex<Vec<()>>>) -> SignalerMock {
             *parameters = self.sigterm_parameters.clone();
             self
         }
+
+        fn exit_for_restart_parameters(self, parameters: &mut Arc<Mutex<Vec<()>>>) -> SignalerMock {
+            *parameters = self.exit_for_restart_parameters.clone();
+            self
+        }
     }
 
     #[test]
@@ -81,4 +148,38 @@ mod tests {
 
         assert_eq!(sigterm_parameters.lock().unwrap().len(), 1);
     }
+
+    #[test]
+    fn restart_demand_triggers_reexec() {
+        let mut subject = ShutdownSupervisorReal::new();
+        let mut sigterm_parameters = Arc::new(Mutex::new(vec![]));
+        let mut reexec_parameters = Arc::new(Mutex::new(vec![]));
+        let signaler = SignalerMock::new()
+            .sigterm_parameters(&mut sigterm_parameters)
+            .reexec_parameters(&mut reexec_parameters);
+        subject.signaler = Box::new(signaler);
+        subject.kept = false;
+
+        subject.restart();
+
+        assert_eq!(reexec_parameters.lock().unwrap().len(), 1);
+        assert_eq!(sigterm_parameters.lock().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn restart_demand_after_privileges_are_dropped_leaves_the_restart_to_the_keeper() {
+        let mut subject = ShutdownSupervisorReal::new();
+        let mut reexec_parameters = Arc::new(Mutex::new(vec![]));
+        let mut exit_for_restart_parameters = Arc::new(Mutex::new(vec![]));
+        let signaler = SignalerMock::new()
+            .reexec_parameters(&mut reexec_parameters)
+            .exit_for_restart_parameters(&mut exit_for_restart_parameters);
+        subject.signaler = Box::new(signaler);
+        subject.kept = true;
+
+        subject.restart();
+
+        assert_eq!(exit_for_restart_parameters.lock().unwrap().len(), 1);
+        assert_eq!(reexec_parameters.lock().unwrap().len(), 0);
+    }
 }
@@ -0,0 +1,300 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+use crate::sub_lib::ui_gateway::NodeStatistics;
+use serde_derive::Serialize;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+// The first report waits until the Node has had time to find its neighbors
+pub const TELEMETRY_FIRST_REPORT_DELAY: Duration = Duration::from_secs(10 * 60);
+pub const TELEMETRY_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+const TELEMETRY_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Upper limits of the ranges reported instead of the exact number of bytes relayed
+const BYTES_RELAYED_BUCKETS: &[(u64, &str)] = &[
+    (1, "0"),
+    (1_000_000, "under 1MB"),
+    (10_000_000, "1MB-10MB"),
+    (100_000_000, "10MB-100MB"),
+    (1_000_000_000, "100MB-1GB"),
+    (10_000_000_000, "1GB-10GB"),
+];
+
+// All a report says. Nothing in it identifies the Node, its wallets or its neighbors, and the
+// numbers are only ranges, so reports from the same Node can't be told from those of any other.
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct TelemetryReport {
+    pub version: String,
+    pub os: String,
+    pub neighbors: String,
+    pub bytes_relayed: String,
+}
+
+impl TelemetryReport {
+    pub fn new(statistics: &NodeStatistics) -> TelemetryReport {
+        TelemetryReport {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            neighbors: neighbors_bucket(statistics.neighbor_count).to_string(),
+            bytes_relayed: bytes_relayed_bucket(statistics.bytes_relayed).to_string(),
+        }
+    }
+}
+
+pub trait TelemetrySender: Send + Sync {
+    fn send(&self, report: &TelemetryReport) -> Result<(), String>;
+}
+
+pub struct TelemetrySenderReal {
+    host_port: String,
+    path: String,
+}
+
+impl TelemetrySender for TelemetrySenderReal {
+    fn send(&self, report: &TelemetryReport) -> Result<(), String> {
+        let body = serde_json::to_string(report).expect("Internal error: telemetry report");
+        post(&self.host_port, &self.path, &body)
+    }
+}
+
+impl TelemetrySenderReal {
+    // The URL has been through parse_telemetry_url already, on the command line
+    pub fn new(url: &str) -> TelemetrySenderReal {
+        let (host_port, path) = parse_telemetry_url(url).expect("Bad telemetry URL");
+        TelemetrySenderReal { host_port, path }
+    }
+}
+
+// Only plain http is supported, like the stream tracing exporter; there's nothing secret to hide
+pub fn parse_telemetry_url(url: &str) -> Result<(String, String), String> {
+    if !url.starts_with("http://") {
+        return Err(format!("{} is not an http:// URL", url));
+    }
+    let rest = url.trim_start_matches("http://");
+    let (host_port, path) = match rest.find('/') {
+        Some(index) => (&rest[..index], &rest[index..]),
+        None => (rest, "/"),
+    };
+    if host_port.is_empty() {
+        return Err(format!("{} has no host", url));
+    }
+    let host_port = if host_port.contains(':') {
+        host_port.to_string()
+    } else {
+        format!("{}:80", host_port)
+    };
+    Ok((host_port, path.to_string()))
+}
+
+fn neighbors_bucket(neighbor_count: u64) -> &'static str {
+    match neighbor_count {
+        0 => "0",
+        1..=2 => "1-2",
+        3..=5 => "3-5",
+        6..=10 => "6-10",
+        _ => "over 10",
+    }
+}
+
+fn bytes_relayed_bucket(bytes_relayed: u64) -> &'static str {
+    BYTES_RELAYED_BUCKETS
+        .iter()
+        .find(|(limit, _)| bytes_relayed < *limit)
+        .map(|(_, bucket)| *bucket)
+        .unwrap_or("over 10GB")
+}
+
+fn post(host_port: &str, path: &str, body: &str) -> Result<(), String> {
+    let addr = host_port
+        .to_socket_addrs()
+        .map_err(|e| format!("{:?}", e))?
+        .next()
+        .ok_or_else(|| format!("{} has no address", host_port))?;
+    let mut stream =
+        TcpStream::connect_timeout(&addr, TELEMETRY_TIMEOUT).map_err(|e| format!("{:?}", e))?;
+    stream
+        .set_read_timeout(Some(TELEMETRY_TIMEOUT))
+        .map_err(|e| format!("{:?}", e))?;
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        host_port,
+        body.len(),
+        body
+    );
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|e| format!("{:?}", e))?;
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response);
+    match response.lines().next() {
+        Some(status)
+            if status.split(' ').nth(1).map(|code| code.starts_with('2')) == Some(true) =>
+        {
+            Ok(())
+        }
+        Some(status) => Err(status.to_string()),
+        None => Err("no response".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, TcpListener};
+    use std::thread;
+
+    #[test]
+    fn neighbor_counts_are_reported_as_ranges() {
+        let result = [0, 1, 2, 3, 5, 6, 10, 11, 500]
+            .iter()
+            .map(|count| neighbors_bucket(*count))
+            .collect::<Vec<&str>>();
+
+        assert_eq!(
+            result,
+            vec!["0", "1-2", "1-2", "3-5", "3-5", "6-10", "6-10", "over 10", "over 10"]
+        );
+    }
+
+    #[test]
+    fn bytes_relayed_are_reported_as_ranges() {
+        let result = [
+            0,
+            1,
+            999_999,
+            1_000_000,
+            50_000_000,
+            100_000_000,
+            9_999_999_999,
+            10_000_000_000,
+        ]
+        .iter()
+        .map(|bytes| bytes_relayed_bucket(*bytes))
+        .collect::<Vec<&str>>();
+
+        assert_eq!(
+            result,
+            vec![
+                "0",
+                "under 1MB",
+                "under 1MB",
+                "1MB-10MB",
+                "10MB-100MB",
+                "100MB-1GB",
+                "1GB-10GB",
+                "over 10GB"
+            ]
+        );
+    }
+
+    #[test]
+    fn a_report_holds_nothing_but_coarse_figures() {
+        let statistics = NodeStatistics {
+            uptime_secs: 12345,
+            bytes_relayed: 2_345_678,
+            bytes_relayed_last_hour: 34567,
+            exit_bytes_served: 45678,
+            active_streams_originated: 5,
+            active_exit_streams: 6,
+            neighbor_count: 4,
+        };
+
+        let result = serde_json::to_value(TelemetryReport::new(&statistics)).unwrap();
+
+        assert_eq!(
+            result,
+            serde_json::json!({
+                "version": env!("CARGO_PKG_VERSION"),
+                "os": std::env::consts::OS,
+                "neighbors": "3-5",
+                "bytes_relayed": "1MB-10MB",
+            })
+        );
+    }
+
+    #[test]
+    fn telemetry_urls_are_parsed_into_address_and_path() {
+        assert_eq!(
+            parse_telemetry_url("http://telemetry.example.com/report"),
+            Ok((
+                "telemetry.example.com:80".to_string(),
+                "/report".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_telemetry_url("http://1.2.3.4:8080"),
+            Ok(("1.2.3.4:8080".to_string(), "/".to_string()))
+        );
+        assert_eq!(
+            parse_telemetry_url("https://telemetry.example.com/report"),
+            Err("https://telemetry.example.com/report is not an http:// URL".to_string())
+        );
+        assert_eq!(
+            parse_telemetry_url("http:///report"),
+            Err("http:///report has no host".to_string())
+        );
+    }
+
+    #[test]
+    fn reports_are_posted_as_json() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buffer = [0u8; 4096];
+            let length = stream.read(&mut buffer).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+                .unwrap();
+            String::from_utf8(buffer[..length].to_vec()).unwrap()
+        });
+        let subject = TelemetrySenderReal::new(&format!("http://127.0.0.1:{}/report", port));
+        let report = TelemetryReport {
+            version: "1.2.3".to_string(),
+            os: "linux".to_string(),
+            neighbors: "1-2".to_string(),
+            bytes_relayed: "0".to_string(),
+        };
+
+        let result = subject.send(&report);
+
+        assert_eq!(result, Ok(()));
+        let request = server.join().unwrap();
+        assert!(
+            request.starts_with("POST /report HTTP/1.1\r\n"),
+            "{}",
+            request
+        );
+        assert!(
+            request.ends_with(
+                "\r\n\r\n{\"version\":\"1.2.3\",\"os\":\"linux\",\"neighbors\":\"1-2\",\"bytes_relayed\":\"0\"}"
+            ),
+            "{}",
+            request
+        );
+    }
+
+    #[test]
+    fn a_refused_report_is_an_error() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buffer = [0u8; 4096];
+            let length = stream.read(&mut buffer).unwrap();
+            assert!(length > 0);
+            stream
+                .write_all(b"HTTP/1.1 503 Service Unavailable\r\n\r\n")
+                .unwrap();
+        });
+        let subject = TelemetrySenderReal::new(&format!("http://127.0.0.1:{}", port));
+        let report = TelemetryReport::new(&NodeStatistics::default());
+
+        let result = subject.send(&report);
+
+        server.join().unwrap();
+        assert_eq!(result, Err("HTTP/1.1 503 Service Unavailable".to_string()));
+    }
+}
@@ -1,12 +1,14 @@
 // Copyright (c) 2017-2018, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
-use crate::sub_lib::ui_gateway::UiMessage;
+use crate::sub_lib::ui_gateway::{UiEnvelope, UiMessage, UI_PROTOCOL_VERSION};
+use serde_json::Value;
 
-#[allow(dead_code)]
 pub const BROADCAST: u64 = 0xFFFF_FFFF_FFFF_FFFF;
 
 pub trait UiTrafficConverter: Send {
     fn marshal(&self, ui_message: UiMessage) -> Result<String, String>;
+    fn marshal_enveloped(&self, ui_message: UiMessage) -> Result<String, String>;
+    // Accepts both bare UiMessages and UiEnvelopes
     fn unmarshal(&self, json: &str) -> Result<UiMessage, String>;
 }
 
@@ -18,8 +20,28 @@ impl UiTrafficConverter for UiTrafficConverterReal {
         serde_json::to_string(&ui_message).map_err(|e| e.to_string())
     }
 
-    fn unmarshal(&self, _json: &str) -> Result<UiMessage, String> {
-        serde_json::from_str(_json).map_err(|e| e.to_string())
+    fn marshal_enveloped(&self, ui_message: UiMessage) -> Result<String, String> {
+        serde_json::to_string(&UiEnvelope {
+            version: UI_PROTOCOL_VERSION,
+            message: ui_message,
+        })
+        .map_err(|e| e.to_string())
+    }
+
+    fn unmarshal(&self, json: &str) -> Result<UiMessage, String> {
+        let value: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        if value.get("version").is_none() {
+            return serde_json::from_value(value).map_err(|e| e.to_string());
+        }
+        let envelope: UiEnvelope = serde_json::from_value(value).map_err(|e| e.to_string())?;
+        if envelope.version == 0 || envelope.version > UI_PROTOCOL_VERSION {
+            Err(format!(
+                "UI protocol version {} is not supported; this Node speaks versions 1 through {}",
+                envelope.version, UI_PROTOCOL_VERSION
+            ))
+        } else {
+            Ok(envelope.message)
+        }
     }
 }
 
@@ -32,6 +54,7 @@ impl UiTrafficConverterReal {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::sub_lib::ui_gateway::NodeEvent;
 
     #[test]
     fn a_shutdown_message_is_properly_marshalled_and_unmarshalled() {
@@ -43,6 +66,89 @@ mod tests {
         assert_eq!(unmarshalled, Ok(UiMessage::ShutdownMessage));
     }
 
+    #[test]
+    fn a_restart_message_is_properly_marshalled_and_unmarshalled() {
+        let subject = UiTrafficConverterReal::new();
+
+        let marshalled = serde_json::to_string(&UiMessage::RestartMessage).unwrap();
+        let unmarshalled = subject.unmarshal(&marshalled);
+
+        assert_eq!(unmarshalled, Ok(UiMessage::RestartMessage));
+    }
+
+    #[test]
+    fn an_enveloped_message_is_properly_marshalled_and_unmarshalled() {
+        let subject = UiTrafficConverterReal::new();
+
+        let marshalled = subject
+            .marshal_enveloped(UiMessage::NodeDescriptor("descriptor".to_string()))
+            .unwrap();
+        let unmarshalled = subject.unmarshal(&marshalled);
+
+        assert_eq!(
+            marshalled,
+            format!(
+                r#"{{"version":{},"message":{{"NodeDescriptor":"descriptor"}}}}"#,
+                UI_PROTOCOL_VERSION
+            )
+        );
+        assert_eq!(
+            unmarshalled,
+            Ok(UiMessage::NodeDescriptor("descriptor".to_string()))
+        );
+    }
+
+    #[test]
+    fn a_hello_is_properly_unmarshalled() {
+        let subject = UiTrafficConverterReal::new();
+
+        let unmarshalled = subject.unmarshal(r#"{"Hello":{"protocol_version":2}}"#);
+
+        assert_eq!(
+            unmarshalled,
+            Ok(UiMessage::Hello {
+                protocol_version: 2
+            })
+        );
+    }
+
+    #[test]
+    fn an_envelope_from_the_future_is_rejected() {
+        let subject = UiTrafficConverterReal::new();
+
+        let unmarshalled = subject.unmarshal(&format!(
+            r#"{{"version":{},"message":"GetNodeStatus"}}"#,
+            UI_PROTOCOL_VERSION + 1
+        ));
+
+        assert_eq!(
+            unmarshalled,
+            Err(format!(
+                "UI protocol version {} is not supported; this Node speaks versions 1 through {}",
+                UI_PROTOCOL_VERSION + 1,
+                UI_PROTOCOL_VERSION
+            ))
+        );
+    }
+
+    #[test]
+    fn a_node_event_is_properly_marshalled() {
+        let subject = UiTrafficConverterReal::new();
+
+        let result = subject
+            .marshal(UiMessage::NodeEvent(NodeEvent::PaymentReceived {
+                wallet: "0x0123".to_string(),
+                amount: 1234,
+                transaction: "0x4567".to_string(),
+            }))
+            .unwrap();
+
+        assert_eq!(
+            result,
+            r#"{"NodeEvent":{"PaymentReceived":{"wallet":"0x0123","amount":1234,"transaction":"0x4567"}}}"#
+        );
+    }
+
     #[test]
     fn a_neighborhood_dot_graph_request_is_properly_marshaled_and_unmarshaled() {
         let subject = UiTrafficConverterReal::new();
@@ -2,6 +2,7 @@
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::ui_gateway::FromUiMessage;
 use crate::sub_lib::utils::localhost;
+use crate::ui_gateway::ui_traffic_converter::BROADCAST;
 use actix::Recipient;
 use bytes::BytesMut;
 use futures::future::FutureResult;
@@ -51,6 +52,8 @@ impl ClientWrapper for ClientWrapperReal {
 
 pub trait WebSocketSupervisor: Send {
     fn send(&self, client_id: u64, message_json: &str);
+    fn is_connected(&self, client_id: u64) -> bool;
+    fn disconnect(&self, client_id: u64);
 }
 
 pub struct WebSocketSupervisorReal {
@@ -68,14 +71,38 @@ struct WebSocketSupervisorInner {
 impl WebSocketSupervisor for WebSocketSupervisorReal {
     fn send(&self, client_id: u64, message_json: &str) {
         let mut locked_inner = self.inner.lock().expect("WebSocketSupervisor is poisoned");
+        if client_id == BROADCAST {
+            locked_inner
+                .client_by_id
+                .values_mut()
+                .for_each(|client| WebSocketSupervisorReal::send_to_client(client, message_json));
+            return;
+        }
         match locked_inner.client_by_id.get_mut(&client_id) {
-            Some(client) => match client.send(OwnedMessage::Text(message_json.to_string())) {
-                Ok(_) => client.flush().expect("Flush error"),
-                Err(e) => panic!("Send error: {:?}", e),
-            },
+            Some(client) => WebSocketSupervisorReal::send_to_client(client, message_json),
             None => panic!("Tried to send to a nonexistent client"),
         };
     }
+
+    // Clients are forgotten here as soon as they say goodbye
+    fn is_connected(&self, client_id: u64) -> bool {
+        self.inner
+            .lock()
+            .expect("WebSocketSupervisor is poisoned")
+            .client_id_by_socket_addr
+            .values()
+            .any(|id| *id == client_id)
+    }
+
+    // The client is forgotten as usual once it acknowledges
+    fn disconnect(&self, client_id: u64) {
+        let mut locked_inner = self.inner.lock().expect("WebSocketSupervisor is poisoned");
+        if let Some(client) = locked_inner.client_by_id.get_mut(&client_id) {
+            if client.send(OwnedMessage::Close(None)).is_ok() {
+                let _ = client.flush();
+            }
+        }
+    }
 }
 
 impl WebSocketSupervisorReal {
@@ -110,6 +137,13 @@ impl WebSocketSupervisorReal {
         WebSocketSupervisorReal { inner }
     }
 
+    fn send_to_client(client: &mut Box<dyn ClientWrapper>, message_json: &str) {
+        match client.send(OwnedMessage::Text(message_json.to_string())) {
+            Ok(_) => client.flush().expect("Flush error"),
+            Err(e) => panic!("Send error: {:?}", e),
+        }
+    }
+
     fn remove_failures<I, E>(
         stream: impl Stream<Item = I, Error = E>,
         logger: &Logger,
@@ -336,6 +370,7 @@ mod tests {
     use futures::future::lazy;
     use std::collections::HashSet;
     use std::net::Shutdown;
+    use std::sync::mpsc;
     use std::thread;
     use std::time::Duration;
     use websocket::client::sync::Client;
@@ -626,6 +661,36 @@ mod tests {
         assert_eq!(another_close_msg, OwnedMessage::Close(None));
     }
 
+    #[test]
+    fn a_client_is_connected_until_it_closes_the_connection() {
+        let port = find_free_port();
+        let (ui_gateway, _, _) = make_recorder();
+        let (inner_tx, inner_rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            let system = System::new("a_client_is_connected_until_it_closes_the_connection");
+            let from_ui_message = ui_gateway.start().recipient::<FromUiMessage>();
+            let subject = lazy(move || {
+                let subject = WebSocketSupervisorReal::new(port, from_ui_message);
+                inner_tx.send(subject.inner.clone()).unwrap();
+                Ok(())
+            });
+            actix::spawn(subject);
+            system.run();
+        });
+        let subject = WebSocketSupervisorReal {
+            inner: inner_rx.recv().unwrap(),
+        };
+
+        let mut client = wait_for_client(port, "PrometheusNode-UI");
+        wait_for(None, None, || subject.is_connected(0));
+        client.send_message(&OwnedMessage::Close(None)).unwrap();
+        client.recv_message().unwrap();
+
+        wait_for(None, None, || !subject.is_connected(0));
+        assert_eq!(subject.is_connected(1), false);
+    }
+
     #[test]
     fn client_dot_graph_request_is_forwarded_to_ui_gateway() {
         let port = find_free_port();
@@ -739,6 +804,35 @@ mod tests {
         system.run();
     }
 
+    #[test]
+    fn disconnect_sends_a_close_to_the_client() {
+        let port = find_free_port();
+        let (ui_gateway, _, _) = make_recorder();
+        let ui_gateway_recipient = ui_gateway.start().recipient::<FromUiMessage>();
+        let system = System::new("disconnect_sends_a_close_to_the_client");
+        let lazy_future = lazy(move || {
+            let subject = WebSocketSupervisorReal::new(port, ui_gateway_recipient);
+            let mut mock_client = ClientWrapperMock::new();
+            mock_client.send_results.push(Ok(()));
+            mock_client.flush_results.push(Ok(()));
+            let client_id = subject.inject_mock_client(mock_client);
+
+            subject.disconnect(client_id);
+            subject.disconnect(client_id + 1);
+
+            let mock_client_ref = subject.get_mock_client(client_id);
+            assert_eq!(
+                *mock_client_ref.send_params.lock().unwrap(),
+                vec![OwnedMessage::Close(None)]
+            );
+            Ok(())
+        });
+
+        actix::spawn(lazy_future);
+        System::current().stop();
+        system.run();
+    }
+
     #[test]
     fn once_a_client_sends_a_close_no_more_data_is_accepted() {
         let port = find_free_port();
@@ -853,6 +947,41 @@ mod tests {
         system.run();
     }
 
+    #[test]
+    fn send_to_broadcast_sends_the_message_to_every_client() {
+        let port = find_free_port();
+        let (ui_gateway, _, _) = make_recorder();
+        let ui_gateway_recipient = ui_gateway.start().recipient::<FromUiMessage>();
+        let system = System::new("send_to_broadcast_sends_the_message_to_every_client");
+        let lazy_future = lazy(move || {
+            let subject = WebSocketSupervisorReal::new(port, ui_gateway_recipient);
+            let client_ids = (0..2)
+                .map(|_| {
+                    let mut mock_client = ClientWrapperMock::new();
+                    mock_client.send_results.push(Ok(()));
+                    mock_client.flush_results.push(Ok(()));
+                    subject.inject_mock_client(mock_client)
+                })
+                .collect::<Vec<u64>>();
+
+            let json_string = "{totally: 'valid'}";
+
+            subject.send(BROADCAST, json_string);
+
+            client_ids.into_iter().for_each(|client_id| {
+                let mock_client_ref = subject.get_mock_client(client_id);
+                assert_eq!(
+                    *mock_client_ref.send_params.lock().unwrap(),
+                    vec![OwnedMessage::Text(String::from(json_string))]
+                );
+            });
+            Ok(())
+        });
+        actix::spawn(lazy_future);
+        System::current().stop();
+        system.run();
+    }
+
     #[test]
     #[should_panic(expected = "Flush error: NoDataAvailable")]
     fn send_tries_to_send_message_and_panics_on_flush() {
@@ -0,0 +1,48 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+pub mod utils;
+
+use node_lib::sub_lib::ui_gateway::{UiMessage, DEFAULT_UI_PORT};
+use node_lib::test_utils::blockchain_server_mock::BlockchainServerMock;
+use node_lib::test_utils::ui_client::UiClient;
+use node_lib::ui_gateway::auth_token::read_ui_auth_token;
+use std::time::Duration;
+use utils::CommandConfig;
+
+#[test]
+fn wallet_balances_come_from_the_blockchain_service_integration() {
+    fdlimit::raise_fd_limit();
+    let server = BlockchainServerMock::new()
+        .eth_balance(1_000_000_000_000_000_000)
+        .token_balance(2_000_000_000_000_000_000)
+        .start();
+    let mut node = utils::SubstratumNode::start_standard(Some(
+        CommandConfig::new().pair("--blockchain-service-url", &server.url()),
+    ));
+    node.wait_for_log("UIGateway bound", Some(5000));
+    let mut client = UiClient::connect(DEFAULT_UI_PORT, Duration::from_millis(5000)).unwrap();
+    let token = read_ui_auth_token(&utils::SubstratumNode::data_dir()).unwrap();
+    client.authenticate(&token).unwrap();
+
+    let response = client
+        .transact(UiMessage::GetWalletBalances, Duration::from_millis(5000))
+        .unwrap();
+
+    let balances = match response {
+        UiMessage::WalletBalances(balances) => balances,
+        other => panic!("Expected WalletBalances, got {:?}", other),
+    };
+    let consuming = balances.consuming.expect("No consuming wallet balance");
+    assert_eq!(consuming.gas_balance, Ok("1000000000000000000".to_string()));
+    assert_eq!(
+        consuming.token_balance,
+        Ok("2000000000000000000".to_string())
+    );
+    assert_eq!(
+        balances.earning.token_balance,
+        Ok("2000000000000000000".to_string())
+    );
+    assert!(!server.requests_for("eth_getBalance").is_empty());
+    client.send(UiMessage::ShutdownMessage).unwrap();
+    node.wait_for_exit();
+}
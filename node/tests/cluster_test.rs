@@ -0,0 +1,31 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+pub mod utils;
+
+use std::collections::HashSet;
+use std::net::TcpStream;
+use utils::SubstratumNodeCluster;
+
+// macOS answers only on 127.0.0.1 unless the instances' loopback addresses are added to lo0
+#[cfg(not(target_os = "macos"))]
+#[test]
+fn three_nodes_start_on_ports_of_their_own_and_gossip_integration() {
+    fdlimit::raise_fd_limit();
+    let mut cluster = SubstratumNodeCluster::start("cluster", 3);
+
+    cluster.wait_for_log_everywhere("UIGateway bound", Some(5000));
+    cluster.members[0]
+        .node
+        .wait_for_log("Received Gossip: digraph db", Some(10000));
+
+    let ports = cluster
+        .members
+        .iter()
+        .flat_map(|member| vec![member.ui_port, member.clandestine_port])
+        .collect::<HashSet<u16>>();
+    assert_eq!(ports.len(), 6);
+    cluster.members.iter().for_each(|member| {
+        TcpStream::connect((member.node.loopback_ip().unwrap(), 80)).unwrap();
+        TcpStream::connect(("127.0.0.1", member.ui_port)).unwrap();
+    });
+}
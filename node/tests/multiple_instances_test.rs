@@ -0,0 +1,24 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+pub mod utils;
+
+use std::net::TcpStream;
+use utils::SubstratumNode;
+
+// macOS answers only on 127.0.0.1 unless the instances' loopback addresses are added to lo0
+#[cfg(not(target_os = "macos"))]
+#[test]
+fn two_instances_run_side_by_side_integration() {
+    fdlimit::raise_fd_limit();
+    let mut alice = SubstratumNode::start_instance("alice", None);
+    let mut bob = SubstratumNode::start_instance("bob", None);
+
+    alice.wait_for_log("UIGateway bound", Some(5000));
+    bob.wait_for_log("UIGateway bound", Some(5000));
+
+    assert_ne!(alice.ui_port(), bob.ui_port());
+    [&alice, &bob].iter().for_each(|node| {
+        TcpStream::connect((node.loopback_ip().unwrap(), 80)).unwrap();
+        TcpStream::connect(("127.0.0.1", node.ui_port().unwrap())).unwrap();
+    });
+}
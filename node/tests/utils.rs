@@ -1,19 +1,53 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
+#[cfg(target_os = "windows")]
+use lazy_static::lazy_static;
+use node_lib::blockchain::blockchain_interface::{chain_id_from_name, DEFAULT_CHAIN_NAME};
+use node_lib::node_configurator::instance::Instance;
+use node_lib::sub_lib::utils::node_descriptor_delimiter;
+use node_lib::test_utils::find_free_port;
+use std::collections::HashSet;
 use std::env;
+use std::fs::File;
 use std::io;
+use std::io::Read;
+use std::io::{Seek, SeekFrom};
+#[cfg(target_os = "windows")]
+use std::mem;
+use std::net::Ipv4Addr;
 use std::ops::Drop;
+#[cfg(target_os = "windows")]
+use std::os::windows::io::AsRawHandle;
 use std::path::Path;
 use std::process;
 use std::process::Output;
+use std::process::Stdio;
+#[cfg(target_os = "windows")]
+use std::ptr;
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::thread::JoinHandle;
 use std::time::Duration;
 use std::time::Instant;
+#[cfg(target_os = "windows")]
+use winapi::shared::minwindef::{DWORD, LPVOID};
+#[cfg(target_os = "windows")]
+use winapi::um::jobapi2::{AssignProcessToJobObject, CreateJobObjectW, SetInformationJobObject};
+#[cfg(target_os = "windows")]
+use winapi::um::winnt::{
+    JobObjectExtendedLimitInformation, HANDLE, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+    JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+};
 
 pub struct SubstratumNode {
     pub logfile_contents: String,
+    logfile_offset: u64,
+    instance_opt: Option<Instance>,
     child: Option<process::Child>,
     output: Option<Output>,
+    stdout: Arc<Mutex<Vec<u8>>>,
+    stderr: Arc<Mutex<Vec<u8>>>,
+    capture_handles: Vec<JoinHandle<()>>,
 }
 
 pub struct CommandConfig {
@@ -61,6 +95,23 @@ impl SubstratumNode {
         Self::data_dir().join("node-data.db").into_boxed_path()
     }
 
+    // An instance keeps its data and log in a directory of its own, under the usual one
+    pub fn instance_data_dir(instance: &Instance) -> Box<Path> {
+        Self::data_dir().join(&instance.name).into_boxed_path()
+    }
+
+    pub fn instance_path_to_logfile(instance: &Instance) -> Box<Path> {
+        Self::instance_data_dir(instance)
+            .join(format!("SubstratumNode_{}_rCURRENT.log", instance.name))
+            .into_boxed_path()
+    }
+
+    pub fn instance_path_to_database(instance: &Instance) -> Box<Path> {
+        Self::instance_data_dir(instance)
+            .join("node-data.db")
+            .into_boxed_path()
+    }
+
     #[allow(dead_code)]
     pub fn output(&mut self) -> Option<Output> {
         self.output.take()
@@ -68,15 +119,57 @@ impl SubstratumNode {
 
     #[allow(dead_code)]
     pub fn start_standard(config: Option<CommandConfig>) -> SubstratumNode {
-        let mut command = SubstratumNode::make_node_command(config);
-        eprintln!("{:?}", command);
-        let child = command.spawn().unwrap();
-        thread::sleep(Duration::from_millis(500)); // needs time to open logfile and sockets
-        SubstratumNode {
-            logfile_contents: String::new(),
-            child: Some(child),
-            output: None,
-        }
+        let command = SubstratumNode::make_node_command(config);
+        SubstratumNode::spawn(command, None)
+    }
+
+    #[allow(dead_code)]
+    pub fn start_instance(name: &str, config: Option<CommandConfig>) -> SubstratumNode {
+        let instance = Instance::new(name);
+        Self::remove_database_at(&Self::instance_path_to_database(&instance));
+        let config = config
+            .unwrap_or(CommandConfig::new())
+            .pair("--instance-name", name);
+        let command = SubstratumNode::make_node_command(Some(config));
+        SubstratumNode::spawn(command, Some(instance))
+    }
+
+    #[allow(dead_code)]
+    pub fn start_cluster_member(name: &str, config: CommandConfig) -> SubstratumNode {
+        let instance = Instance::new(name);
+        Self::remove_database_at(&Self::instance_path_to_database(&instance));
+        let mut command = command_to_start();
+        let mut args = Self::cluster_member_args();
+        args.extend(Self::get_extra_args(Some(
+            config.pair("--instance-name", name),
+        )));
+        command.args(&args);
+        SubstratumNode::spawn(command, Some(instance))
+    }
+
+    // Everything the Node has written to stdout so far, whether or not it's still running
+    #[allow(dead_code)]
+    pub fn stdout_so_far(&self) -> String {
+        String::from_utf8_lossy(&self.stdout.lock().unwrap()).to_string()
+    }
+
+    #[allow(dead_code)]
+    pub fn stderr_so_far(&self) -> String {
+        String::from_utf8_lossy(&self.stderr.lock().unwrap()).to_string()
+    }
+
+    #[allow(dead_code)]
+    pub fn ui_port(&self) -> Option<u16> {
+        self.instance_opt
+            .as_ref()
+            .map(|instance| instance.ui_port())
+    }
+
+    #[allow(dead_code)]
+    pub fn loopback_ip(&self) -> Option<Ipv4Addr> {
+        self.instance_opt
+            .as_ref()
+            .map(|instance| instance.loopback_ip())
     }
 
     #[allow(dead_code)]
@@ -108,22 +201,14 @@ impl SubstratumNode {
 
     #[allow(dead_code)]
     pub fn wait_for_log(&mut self, pattern: &str, limit_ms: Option<u64>) {
-        let regex = regex::Regex::new(pattern).unwrap();
-        let real_limit_ms = limit_ms.unwrap_or(0xFFFFFFFF);
-        let started_at = Instant::now();
-        loop {
-            self.logfile_contents = std::fs::read_to_string(Self::path_to_logfile()).unwrap();
-            if regex.is_match(&self.logfile_contents[..]) {
-                break;
-            }
-            assert_eq!(
-                SubstratumNode::millis_since(started_at) < real_limit_ms,
-                true,
-                "Timeout: waited for more than {}ms",
-                real_limit_ms
-            );
-            thread::sleep(Duration::from_millis(200));
-        }
+        self.wait_for_match(pattern, 0, limit_ms);
+    }
+
+    // For ordering assertions: the pattern counts only if it's logged after the marker is
+    #[allow(dead_code)]
+    pub fn wait_for_log_after(&mut self, marker: &str, pattern: &str, limit_ms: Option<u64>) {
+        let marker_end = self.wait_for_match(marker, 0, limit_ms);
+        self.wait_for_match(pattern, marker_end, limit_ms);
     }
 
     #[allow(dead_code)]
@@ -135,8 +220,14 @@ impl SubstratumNode {
                 self.output = Some(output);
                 self.output.clone()
             }
-            (Some(child), None) => match child.wait_with_output() {
-                Ok(output) => Some(output),
+            (Some(mut child), None) => match child.wait() {
+                Ok(status) => {
+                    // The pipes close when the Node exits, so the capture threads finish too
+                    self.capture_handles
+                        .drain(..)
+                        .for_each(|handle| handle.join().unwrap());
+                    Some(self.make_output(status))
+                }
                 Err(e) => panic!("{:?}", e),
             },
             (Some(_), Some(_)) => panic!("Internal error: Inconsistent SubstratumNode state"),
@@ -152,11 +243,7 @@ impl SubstratumNode {
             (Some(mut child), None) => {
                 child.kill()?;
                 let result = child.wait()?;
-                self.output = Some(Output {
-                    status: result,
-                    stdout: vec![],
-                    stderr: vec![],
-                });
+                self.output = Some(self.make_output(result));
                 result
             }
             (None, Some(output)) => {
@@ -169,18 +256,24 @@ impl SubstratumNode {
         })
     }
 
+    // The child is cmd, not the Node, so the whole tree under it goes; other tests' Nodes don't.
     #[cfg(target_os = "windows")]
     pub fn kill(&mut self) {
-        let mut command = process::Command::new("taskkill");
-        command.args(&vec!["/IM", "SubstratumNode.exe", "/F"]);
-        let _ = command.output().expect("Couldn't kill SubstratumNode.exe");
-        self.child.take();
+        if let Some(child) = self.child.take() {
+            let _ = process::Command::new("taskkill")
+                .args(&vec!["/PID", &child.id().to_string(), "/T", "/F"])
+                .output()
+                .expect("Couldn't kill SubstratumNode.exe");
+        }
         // Be nice if we could figure out how to populate self.output here
     }
 
     pub fn remove_database() {
-        let database = Self::path_to_database();
-        match std::fs::remove_file(database.clone()) {
+        Self::remove_database_at(&Self::path_to_database());
+    }
+
+    fn remove_database_at(database: &Path) {
+        match std::fs::remove_file(database) {
             Ok(_) => (),
             Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
             Err(e) => panic!(
@@ -190,6 +283,118 @@ impl SubstratumNode {
         }
     }
 
+    // Returns where in logfile_contents the match ends. Only complete lines are searched, each of
+    // them once, so a pattern spanning lines that arrive in different reads won't be found.
+    fn wait_for_match(
+        &mut self,
+        pattern: &str,
+        search_from: usize,
+        limit_ms: Option<u64>,
+    ) -> usize {
+        let regex = regex::Regex::new(pattern).unwrap();
+        let real_limit_ms = limit_ms.unwrap_or(0xFFFFFFFF);
+        let started_at = Instant::now();
+        let mut search_from = search_from;
+        loop {
+            self.read_appended_log();
+            if let Some(found) = regex.find_at(&self.logfile_contents, search_from) {
+                return found.end();
+            }
+            search_from = self.logfile_contents.len();
+            assert_eq!(
+                SubstratumNode::millis_since(started_at) < real_limit_ms,
+                true,
+                "Timeout: waited for more than {}ms\nstdout:\n{}\nstderr:\n{}",
+                real_limit_ms,
+                self.stdout_so_far(),
+                self.stderr_so_far()
+            );
+            thread::sleep(Duration::from_millis(200));
+        }
+    }
+
+    // Appends the complete lines logged since the last read to logfile_contents. When the file is
+    // shorter than what's been read, it has been truncated or rotated, and reading starts over at
+    // the beginning of the new one; what was read before is kept.
+    fn read_appended_log(&mut self) {
+        let logfile = match self.instance_opt {
+            Some(ref instance) => Self::instance_path_to_logfile(instance),
+            None => Self::path_to_logfile(),
+        };
+        // A Node that fails early may never create its logfile
+        let mut file = match File::open(&logfile) {
+            Ok(file) => file,
+            Err(_) => return,
+        };
+        let length = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+        if length < self.logfile_offset {
+            self.logfile_offset = 0;
+        }
+        let mut appended = vec![];
+        if file.seek(SeekFrom::Start(self.logfile_offset)).is_err()
+            || file.read_to_end(&mut appended).is_err()
+        {
+            return;
+        }
+        let complete_length = match appended.iter().rposition(|byte| *byte == b'\n') {
+            Some(last_newline) => last_newline + 1,
+            None => return,
+        };
+        self.logfile_contents
+            .push_str(&String::from_utf8_lossy(&appended[..complete_length]));
+        self.logfile_offset += complete_length as u64;
+    }
+
+    fn spawn(mut command: process::Command, instance_opt: Option<Instance>) -> SubstratumNode {
+        eprintln!("{:?}", command);
+        let mut child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        die_with_test(&child);
+        let (stdout, stdout_handle) = Self::capture(child.stdout.take().unwrap());
+        let (stderr, stderr_handle) = Self::capture(child.stderr.take().unwrap());
+        thread::sleep(Duration::from_millis(500)); // needs time to open logfile and sockets
+        SubstratumNode {
+            logfile_contents: String::new(),
+            logfile_offset: 0,
+            instance_opt,
+            child: Some(child),
+            output: None,
+            stdout,
+            stderr,
+            capture_handles: vec![stdout_handle, stderr_handle],
+        }
+    }
+
+    // Reads until the Node closes the stream; otherwise a chatty Node would fill the pipe and block
+    fn capture<R: Read + Send + 'static>(mut stream: R) -> (Arc<Mutex<Vec<u8>>>, JoinHandle<()>) {
+        let captured = Arc::new(Mutex::new(vec![]));
+        let captured_inner = captured.clone();
+        let handle = thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            loop {
+                match stream.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(len) => captured_inner
+                        .lock()
+                        .unwrap()
+                        .extend_from_slice(&buf[..len]),
+                }
+            }
+        });
+        (captured, handle)
+    }
+
+    fn make_output(&self, status: process::ExitStatus) -> Output {
+        Output {
+            status,
+            stdout: self.stdout.lock().unwrap().clone(),
+            stderr: self.stderr.lock().unwrap().clone(),
+        }
+    }
+
     fn millis_since(started_at: Instant) -> u64 {
         let interval = Instant::now().duration_since(started_at);
         let second_milliseconds = interval.as_secs() * 1000;
@@ -245,6 +450,19 @@ impl SubstratumNode {
             .args
     }
 
+    #[allow(dead_code)]
+    fn cluster_member_args() -> Vec<String> {
+        apply_prefix_parameters(CommandConfig::new())
+            .pair("--dns-servers", "8.8.8.8")
+            .pair("--neighborhood-mode", "standard")
+            .pair(
+                "--consuming-private-key",
+                "CCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCC",
+            )
+            .pair("--log-level", "trace")
+            .args
+    }
+
     #[allow(dead_code)]
     fn dump_config_args() -> Vec<String> {
         apply_prefix_parameters(CommandConfig::new())
@@ -274,6 +492,139 @@ impl SubstratumNode {
     }
 }
 
+// One of the Nodes in a SubstratumNodeCluster, with what the others need to know to reach it
+#[allow(dead_code)]
+pub struct ClusterMember {
+    pub node: SubstratumNode,
+    pub name: String,
+    pub ui_port: u16,
+    pub clandestine_port: u16,
+    pub descriptor: String,
+}
+
+// Several standard-mode Nodes on this host, each an instance on a loopback address of its own
+// (the HTTP, TLS and DNS ports can't move, so that's how they keep from colliding) with UI and
+// clandestine ports from find_free_port. Each Node after the first is started with the one
+// before it as its neighbor. The Nodes are killed when the cluster is dropped, which happens
+// on the way out of a panicking test too; if one of them fails to start, the ones already
+// started are killed the same way.
+#[allow(dead_code)]
+pub struct SubstratumNodeCluster {
+    pub members: Vec<ClusterMember>,
+}
+
+impl SubstratumNodeCluster {
+    #[allow(dead_code)]
+    pub fn start(name_prefix: &str, count: usize) -> SubstratumNodeCluster {
+        let delimiter = node_descriptor_delimiter(chain_id_from_name(DEFAULT_CHAIN_NAME));
+        let mut cluster = SubstratumNodeCluster { members: vec![] };
+        Self::names_with_loopback_ips_of_their_own(name_prefix, count)
+            .into_iter()
+            .enumerate()
+            .for_each(|(index, name)| {
+                let loopback_ip = Instance::new(&name).loopback_ip();
+                let ui_port = find_free_port();
+                let clandestine_port = find_free_port();
+                // Six bytes, so the base64 needs no padding
+                let public_key = base64::encode(&[0xC1, 0x05, 0x7E, 0x12, 0x00, index as u8]);
+                let descriptor = format!(
+                    "{}{}{}:{}",
+                    public_key, delimiter, loopback_ip, clandestine_port
+                );
+                let mut config = CommandConfig::new()
+                    .pair("--ip", &loopback_ip.to_string())
+                    .pair("--ui-port", &ui_port.to_string())
+                    .pair("--clandestine-port", &clandestine_port.to_string())
+                    .pair("--fake-public-key", &public_key);
+                if let Some(previous) = cluster.members.last() {
+                    config = config.pair("--neighbors", &previous.descriptor);
+                }
+                let node = SubstratumNode::start_cluster_member(&name, config);
+                cluster.members.push(ClusterMember {
+                    node,
+                    name,
+                    ui_port,
+                    clandestine_port,
+                    descriptor,
+                });
+            });
+        cluster
+    }
+
+    #[allow(dead_code)]
+    pub fn wait_for_log_everywhere(&mut self, pattern: &str, limit_ms: Option<u64>) {
+        self.members
+            .iter_mut()
+            .for_each(|member| member.node.wait_for_log(pattern, limit_ms));
+    }
+
+    // Names whose instances share a loopback address would fight over its HTTP port
+    #[allow(dead_code)]
+    fn names_with_loopback_ips_of_their_own(name_prefix: &str, count: usize) -> Vec<String> {
+        let mut loopback_ips = HashSet::new();
+        (0..)
+            .map(|suffix| format!("{}-{}", name_prefix, suffix))
+            .filter(|name| loopback_ips.insert(Instance::new(name).loopback_ip()))
+            .take(count)
+            .collect()
+    }
+}
+
+impl Drop for SubstratumNodeCluster {
+    fn drop(&mut self) {
+        self.members.iter_mut().rev().for_each(|member| {
+            let _ = member.node.kill();
+        });
+    }
+}
+
+// Every child goes into a job object that kills what's in it when its last handle is closed, and
+// nothing closes it but Windows, when the test process ends: so however a test ends, even killed
+// in the debugger, its Nodes end with it. The Node is cmd's child and joins the job with cmd; if
+// cmd starts it before cmd itself is assigned, which is unlikely, it's left to kill().
+#[cfg(target_os = "windows")]
+struct KillOnCloseJob(HANDLE);
+
+#[cfg(target_os = "windows")]
+unsafe impl Send for KillOnCloseJob {}
+
+#[cfg(target_os = "windows")]
+unsafe impl Sync for KillOnCloseJob {}
+
+#[cfg(target_os = "windows")]
+lazy_static! {
+    static ref KILL_ON_CLOSE_JOB: KillOnCloseJob = unsafe {
+        let job = CreateJobObjectW(ptr::null_mut(), ptr::null());
+        assert_eq!(job.is_null(), false, "Couldn't create job object");
+        let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = mem::zeroed();
+        info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+        let result = SetInformationJobObject(
+            job,
+            JobObjectExtendedLimitInformation,
+            &mut info as *mut JOBOBJECT_EXTENDED_LIMIT_INFORMATION as LPVOID,
+            mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as DWORD,
+        );
+        assert_ne!(result, 0, "Couldn't make job object kill on close");
+        KillOnCloseJob(job)
+    };
+}
+
+#[cfg(target_os = "windows")]
+fn die_with_test(child: &process::Child) {
+    let result =
+        unsafe { AssignProcessToJobObject(KILL_ON_CLOSE_JOB.0, child.as_raw_handle() as HANDLE) };
+    assert_ne!(
+        result,
+        0,
+        "Couldn't put process {} in job object",
+        child.id()
+    );
+}
+
+// Elsewhere a Node whose test dies without dropping it is orphaned, as it always has been
+#[cfg(not(target_os = "windows"))]
+fn die_with_test(_child: &process::Child) {}
+
 #[cfg(target_os = "windows")]
 fn command_to_start() -> process::Command {
     process::Command::new("cmd")